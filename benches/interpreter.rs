@@ -0,0 +1,172 @@
+//! Interpreter throughput on whole workloads, measured with criterion:
+//!
+//!     cargo bench --bench interpreter
+//!
+//! Unlike `benches/opcodes.rs`, which times single opcodes, these run
+//! loops shaped like real contracts, so that changes to dispatch, gas
+//! accounting, the stack and memory show up together.
+
+use std::collections::BTreeSet;
+
+use criterion::{black_box, criterion_group, criterion_main, Criterion};
+use ejit_evm::ethereum::{
+    cancun::{
+        fork_types::{Account, Address},
+        state::{set_account, State},
+        utils::prepare_message,
+        vm::{instructions::Ops, interpreter::process_message_call, Environment},
+    },
+    ethereum_types::{
+        bytes::Bytes,
+        numeric::{Uint, U256},
+    },
+    forks::ExecutionRules,
+};
+
+const GAS: Uint = 1 << 40;
+
+const CALLER: [u8; 20] = [0xca; 20];
+const CONTRACT: [u8; 20] = [0xc0; 20];
+
+/// Bytecode with forward references to jump destinations.
+#[derive(Default)]
+struct Asm {
+    code: Vec<u8>,
+}
+
+impl Asm {
+    fn op(&mut self, op: Ops) -> &mut Self {
+        self.code.push(op as u8);
+        self
+    }
+
+    fn push(&mut self, value: &[u8]) -> &mut Self {
+        self.code.push(Ops::PUSH1 as u8 + value.len() as u8 - 1);
+        self.code.extend_from_slice(value);
+        self
+    }
+
+    fn push_u64(&mut self, value: u64) -> &mut Self {
+        let bytes = value.to_be_bytes();
+        let skip = bytes.iter().take_while(|b| **b == 0).count().min(7);
+        self.push(&bytes[skip..])
+    }
+
+    /// A JUMPDEST, returning its pc.
+    fn label(&mut self) -> u64 {
+        self.code.push(Ops::JUMPDEST as u8);
+        self.code.len() as u64 - 1
+    }
+}
+
+/// `prelude`, then a loop running `iterations` times around `body`, which
+/// must leave the stack as it found it.
+fn repeat(prelude: impl Fn(&mut Asm), iterations: u64, body: impl Fn(&mut Asm)) -> Vec<u8> {
+    let mut asm = Asm::default();
+    prelude(&mut asm);
+    asm.push_u64(iterations);
+    let top = asm.label();
+    body(&mut asm);
+    asm.push(&[1]).op(Ops::SWAP1).op(Ops::SUB).op(Ops::DUP1).push_u64(top).op(Ops::JUMPI);
+    asm.op(Ops::POP).op(Ops::STOP);
+    asm.code
+}
+
+/// The shape of the snailtracer benchmark: fixed point arithmetic on
+/// vectors kept in memory, with signed division and a pseudo-random
+/// sequence.
+fn snailtracer() -> Vec<u8> {
+    const ONE: u64 = 1_000_000;
+    repeat(|_| (), 2000, |asm| {
+        // seed = seed * 6364136223846793005 + 1442695040888963407 at 0x00.
+        asm.push(&[0]).op(Ops::MLOAD)
+            .push_u64(6364136223846793005).op(Ops::MUL)
+            .push_u64(1442695040888963407).op(Ops::ADD)
+            .op(Ops::DUP1).push(&[0]).op(Ops::MSTORE);
+        // x = x * x / ONE + (seed % ONE) - ONE / 2 at 0x20, and y = y * x / ONE
+        // - x at 0x40.
+        asm.push_u64(ONE).op(Ops::SWAP1).op(Ops::MOD)
+            .push_u64(ONE / 2).op(Ops::SWAP1).op(Ops::SUB)
+            .push(&[0x20]).op(Ops::MLOAD).op(Ops::DUP1).op(Ops::MUL)
+            .push_u64(ONE).op(Ops::SWAP1).op(Ops::SDIV).op(Ops::ADD)
+            .op(Ops::DUP1).push(&[0x20]).op(Ops::MSTORE)
+            .op(Ops::DUP1).push(&[0x40]).op(Ops::MLOAD).op(Ops::MUL)
+            .push_u64(ONE).op(Ops::SWAP1).op(Ops::SDIV).op(Ops::SUB)
+            .push(&[0x40]).op(Ops::MSTORE);
+    })
+}
+
+/// Hashing a growing buffer, as contracts computing storage keys and
+/// signatures do.
+fn keccak_loop() -> Vec<u8> {
+    repeat(|_| (), 2000, |asm| {
+        asm.push(&[0x40]).push(&[0]).op(Ops::KECCAK)
+            .push(&[0x20]).op(Ops::MSTORE);
+    })
+}
+
+/// ERC-20 style transfers: two balances read, checked, updated and
+/// written back.
+fn erc20_transfers() -> Vec<u8> {
+    // The sender starts with enough for every transfer.
+    let prelude = |asm: &mut Asm| {
+        asm.push_u64(u64::MAX).push(&[1]).op(Ops::SSTORE);
+    };
+    repeat(prelude, 500, |asm| {
+        // balance[1] -= 3, jumping to 0, which is not a JUMPDEST, if it
+        // would underflow.
+        asm.push(&[1]).op(Ops::SLOAD)
+            .push(&[3]).op(Ops::DUP2).op(Ops::LT)
+            .push(&[0]).op(Ops::JUMPI)
+            .push(&[3]).op(Ops::SWAP1).op(Ops::SUB).push(&[1]).op(Ops::SSTORE)
+            // balance[2] += 3.
+            .push(&[2]).op(Ops::SLOAD).push(&[3]).op(Ops::ADD).push(&[2]).op(Ops::SSTORE);
+    })
+}
+
+fn run(code: &[u8]) -> Uint {
+    let caller = Address::from_be_bytes(CALLER);
+    let contract = Address::from_be_bytes(CONTRACT);
+    let mut state = State::default();
+    set_account(&mut state, &caller, Some(Account::default()));
+    set_account(
+        &mut state,
+        &contract,
+        Some(Account { nonce: 1, balance: U256::ZERO, code: Bytes(code.to_vec()) }),
+    );
+    let mut env = Environment {
+        caller: caller.clone(),
+        origin: caller.clone(),
+        gas_limit: GAS,
+        ..Environment::new(&mut state, ExecutionRules::CANCUN)
+    };
+    let message = prepare_message(
+        caller,
+        Some(contract),
+        U256::ZERO,
+        Bytes::default(),
+        GAS,
+        &env,
+        None,
+        true,
+        false,
+        BTreeSet::new(),
+        BTreeSet::new(),
+    )
+    .unwrap();
+    let output = process_message_call(message, &mut env).unwrap();
+    assert!(output.error.is_none(), "{:?}", output.error);
+    GAS - output.gas_left
+}
+
+fn workloads(c: &mut Criterion) {
+    let workloads = [("snailtracer", snailtracer()), ("keccak", keccak_loop()), ("erc20", erc20_transfers())];
+    let mut group = c.benchmark_group("interpreter");
+    for (name, code) in &workloads {
+        group.bench_function(*name, |b| b.iter(|| run(black_box(code))));
+    }
+    group.finish();
+}
+
+criterion_group!(benches, workloads);
+criterion_main!(benches);
@@ -0,0 +1,177 @@
+//! Per-opcode micro-benchmarks.
+//!
+//! Each case is a short pattern around one opcode, unrolled `ITERATIONS`
+//! times after an optional prelude which sets up memory or storage. The
+//! time and gas of the prelude on its own are subtracted, so the table shows
+//! the cost of one iteration of the pattern:
+//!
+//!     cargo bench --bench opcodes
+//!     cargo bench --bench opcodes -- sstore keccak
+//!
+//! Arguments select the cases whose names contain any of them.
+
+use std::{
+    collections::BTreeSet,
+    time::{Duration, Instant},
+};
+
+use ejit_evm::ethereum::{
+    cancun::{
+        fork_types::{Account, Address},
+        state::{set_account, State},
+        utils::prepare_message,
+        vm::{instructions::Ops, interpreter::process_message_call, Environment},
+    },
+    ethereum_types::{
+        bytes::Bytes,
+        numeric::{Uint, U256},
+    },
+    forks::ExecutionRules,
+};
+
+/// Number of times each pattern is repeated.
+const ITERATIONS: usize = 1000;
+
+/// Number of timed runs of each case, of which the fastest is reported.
+const RUNS: usize = 20;
+
+/// Enough gas for `ITERATIONS` cold SSTOREs.
+const GAS: Uint = 1 << 40;
+
+const CALLER: [u8; 20] = [0xca; 20];
+const CONTRACT: [u8; 20] = [0xc0; 20];
+
+struct Case {
+    name: String,
+    prelude: Vec<u8>,
+    /// The pattern for iteration `i`.
+    pattern: Box<dyn Fn(usize) -> Vec<u8>>,
+}
+
+fn push(value: &[u8]) -> Vec<u8> {
+    let mut code = vec![Ops::PUSH1 as u8 + value.len() as u8 - 1];
+    code.extend_from_slice(value);
+    code
+}
+
+fn cases() -> Vec<Case> {
+    let a = [0xfe; 32];
+    let b = [0x7f; 32];
+    let mut cases = vec![
+        Case {
+            name: "PUSH1/POP (overhead)".into(),
+            prelude: Vec::new(),
+            pattern: Box::new(|_| [push(&[1]), vec![Ops::POP as u8]].concat()),
+        },
+        Case {
+            name: "ADD".into(),
+            prelude: Vec::new(),
+            pattern: Box::new(move |_| [push(&a), push(&b), vec![Ops::ADD as u8, Ops::POP as u8]].concat()),
+        },
+        Case {
+            name: "MUL".into(),
+            prelude: Vec::new(),
+            pattern: Box::new(move |_| [push(&a), push(&b), vec![Ops::MUL as u8, Ops::POP as u8]].concat()),
+        },
+        Case {
+            name: "SSTORE cold".into(),
+            prelude: Vec::new(),
+            // A new slot each time.
+            pattern: Box::new(|i| {
+                [push(&[1]), push(&(i as u16).to_be_bytes()), vec![Ops::SSTORE as u8]].concat()
+            }),
+        },
+        Case {
+            name: "SSTORE warm".into(),
+            // The prelude warms the slot, after which every store is a
+            // no-op of the same value.
+            prelude: [push(&[1]), push(&[0]), vec![Ops::SSTORE as u8]].concat(),
+            pattern: Box::new(|_| [push(&[1]), push(&[0]), vec![Ops::SSTORE as u8]].concat()),
+        },
+    ];
+    for size in [32_u16, 256, 1024, 4096] {
+        cases.push(Case {
+            name: format!("KECCAK256 {size} bytes"),
+            // Expand the memory up front so that only hashing is measured.
+            prelude: [push(&[0]), push(&(size - 1).to_be_bytes()), vec![Ops::MSTORE8 as u8]].concat(),
+            pattern: Box::new(move |_| {
+                [push(&size.to_be_bytes()), push(&[0]), vec![Ops::KECCAK as u8, Ops::POP as u8]].concat()
+            }),
+        });
+    }
+    cases
+}
+
+/// Runs `code` as the body of a contract and returns the time taken and
+/// the gas used.
+fn run(code: &[u8]) -> (Duration, Uint) {
+    let caller = Address::from_be_bytes(CALLER);
+    let contract = Address::from_be_bytes(CONTRACT);
+    let mut state = State::default();
+    set_account(&mut state, &caller, Some(Account::default()));
+    set_account(
+        &mut state,
+        &contract,
+        Some(Account { nonce: 1, balance: U256::ZERO, code: Bytes(code.to_vec()) }),
+    );
+    let mut env = Environment {
+        caller: caller.clone(),
+        origin: caller.clone(),
+        gas_limit: GAS,
+        ..Environment::new(&mut state, ExecutionRules::CANCUN)
+    };
+    let message = prepare_message(
+        caller,
+        Some(contract),
+        U256::ZERO,
+        Bytes::default(),
+        GAS,
+        &env,
+        None,
+        true,
+        false,
+        BTreeSet::new(),
+        BTreeSet::new(),
+    )
+    .unwrap();
+    let start = Instant::now();
+    let output = process_message_call(message, &mut env).unwrap();
+    let elapsed = start.elapsed();
+    assert!(output.error.is_none(), "{:?}", output.error);
+    (elapsed, GAS - output.gas_left)
+}
+
+/// The fastest of `RUNS` runs of `code`.
+fn measure(code: &[u8]) -> (Duration, Uint) {
+    (0..RUNS).map(|_| run(code)).min_by_key(|(elapsed, _)| *elapsed).unwrap()
+}
+
+/// Time and gas of one iteration of `case`.
+fn per_iteration(case: &Case) -> (f64, Uint) {
+    let mut code = case.prelude.clone();
+    let (base_time, base_gas) = measure(&[code.as_slice(), &[Ops::STOP as u8]].concat());
+    for i in 0..ITERATIONS {
+        code.extend((case.pattern)(i));
+    }
+    code.push(Ops::STOP as u8);
+    let (time, gas) = measure(&code);
+    let ns = time.saturating_sub(base_time).as_nanos() as f64 / ITERATIONS as f64;
+    (ns, (gas - base_gas) / ITERATIONS as Uint)
+}
+
+fn main() {
+    let filters: Vec<String> = std::env::args()
+        .skip(1)
+        .filter(|arg| !arg.starts_with("--"))
+        .map(|arg| arg.to_lowercase())
+        .collect();
+    println!("{:<24} {:>8} {:>14}", "case", "gas", "ns");
+    for case in cases() {
+        let name = case.name.to_lowercase();
+        if !filters.is_empty() && !filters.iter().any(|f| name.contains(f)) {
+            continue;
+        }
+        let (ns, gas) = per_iteration(&case);
+        println!("{:<24} {gas:>8} {ns:>14.1}", case.name);
+    }
+}
@@ -0,0 +1,116 @@
+//! The building blocks under the interpreter and block import, measured
+//! with criterion:
+//!
+//!     cargo bench --bench primitives
+//!     cargo bench --bench primitives -- trie
+//!
+//! U256 arithmetic is what every arithmetic opcode costs, trie roots are
+//! computed for the state, transactions and receipts of every block, and
+//! RLP is how blocks and transactions arrive and are hashed. Keccak is run
+//! over the code and trie keys of every account a state root covers, which
+//! `keccak256_cached` saves and `keccak256_batch` runs four at a time.
+
+use criterion::{black_box, criterion_group, criterion_main, Criterion};
+use ejit_evm::ethereum::{
+    cancun::{blocks::Header, fork_types::Address, transactions::LegacyTransaction, trie::Trie},
+    crypto::hash::{keccak256, keccak256_batch, keccak256_cached},
+    ethereum_rlp::rlp,
+    ethereum_types::{bytes::Bytes, numeric::U256},
+};
+
+/// A full width number, so that no limb is skipped.
+fn wide(seed: u8) -> U256 {
+    let mut bytes = [0; 32];
+    for (i, b) in bytes.iter_mut().enumerate() {
+        *b = seed.wrapping_mul(31).wrapping_add(i as u8 * 7) | 1;
+    }
+    U256::from_be_bytes(bytes)
+}
+
+fn u256(c: &mut Criterion) {
+    let (a, b, m) = (wide(1), wide(2), wide(3).shr(8));
+    let mut group = c.benchmark_group("u256");
+    group.bench_function("add", |bench| bench.iter(|| black_box(a) + black_box(b)));
+    group.bench_function("mul", |bench| bench.iter(|| black_box(a) * black_box(b)));
+    group.bench_function("div_rem", |bench| bench.iter(|| black_box(a).div_rem(black_box(m))));
+    group.bench_function("signed_div", |bench| bench.iter(|| black_box(a).signed_div(black_box(m))));
+    group.bench_function("add_mod", |bench| bench.iter(|| black_box(a).add_mod(black_box(b), black_box(m))));
+    group.bench_function("mul_mod", |bench| bench.iter(|| black_box(a).mul_mod(black_box(b), black_box(m))));
+    group.bench_function("pow", |bench| bench.iter(|| black_box(a).pow(black_box(b))));
+    group.finish();
+}
+
+fn keccak(c: &mut Criterion) {
+    let code = vec![0x5b; 0x6000];
+    let keys: Vec<[u8; 32]> = (0..1000u32).map(|i| wide(i as u8).to_be_bytes()).collect();
+    let mut group = c.benchmark_group("keccak");
+    group.bench_function("code", |b| b.iter(|| keccak256(black_box(&code))));
+    group.bench_function("code_cached", |b| b.iter(|| keccak256_cached(black_box(&code))));
+    group.bench_function("keys", |b| b.iter(|| keys.iter().map(|key| keccak256(black_box(key))).collect::<Vec<_>>()));
+    group.bench_function("keys_batch", |b| b.iter(|| keccak256_batch(keys.iter().map(|key| black_box(&key[..])))));
+    group.bench_function("keys_cached", |b| b.iter(|| keys.iter().map(|key| keccak256_cached(black_box(key))).collect::<Vec<_>>()));
+    // Branch nodes of a proof, four blocks each.
+    let nodes: Vec<Vec<u8>> = (0..256u32).map(|i| vec![i as u8; 532]).collect();
+    group.bench_function("nodes", |b| b.iter(|| nodes.iter().map(|node| keccak256(black_box(node))).collect::<Vec<_>>()));
+    group.bench_function("nodes_batch", |b| b.iter(|| keccak256_batch(nodes.iter().map(|node| black_box(&node[..])))));
+    group.finish();
+}
+
+fn trie(c: &mut Criterion) {
+    let mut group = c.benchmark_group("trie");
+    for (name, secured) in [("root", false), ("secure_root", true)] {
+        let mut trie = Trie::new(secured, Bytes::default());
+        for i in 0..1000u32 {
+            let key = Bytes(i.to_be_bytes().to_vec());
+            trie.set(key, Bytes(wide(i as u8).to_be_bytes().to_vec()));
+        }
+        group.bench_function(name, |b| b.iter(|| black_box(&trie).root().unwrap()));
+    }
+    group.finish();
+}
+
+/// The transactions of a full-ish block of transfers.
+fn transactions() -> Vec<LegacyTransaction> {
+    (0..200u32)
+        .map(|i| LegacyTransaction {
+            nonce: U256::from(i),
+            gas_price: 30_000_000_000,
+            gas: 21000,
+            to: Some(Address::from_be_bytes([i as u8; 20])).into(),
+            value: wide(i as u8).shr(160),
+            data: Bytes(vec![i as u8; 68]),
+            v: U256::from(37),
+            r: wide(i as u8 ^ 0x55),
+            s: wide(i as u8 ^ 0xaa).shr(1),
+        })
+        .collect()
+}
+
+fn rlp(c: &mut Criterion) {
+    let header = Header {
+        number: 20_000_000,
+        gas_limit: 30_000_000,
+        gas_used: 12_345_678,
+        timestamp: U256::from(1_700_000_000u64),
+        extra_data: Bytes(b"ejit-evm".to_vec()),
+        base_fee_per_gas: Some(7),
+        ..Default::default()
+    };
+    let transactions = transactions();
+    let encoded_header = rlp::encode(&header).unwrap();
+    let encoded_transactions = rlp::encode(&transactions).unwrap();
+
+    let mut group = c.benchmark_group("rlp");
+    group.bench_function("encode_header", |b| b.iter(|| rlp::encode(black_box(&header)).unwrap()));
+    group.bench_function("decode_header", |b| {
+        b.iter(|| rlp::decode_to::<Header>(black_box(&encoded_header)).unwrap())
+    });
+    group.bench_function("encode_transactions", |b| b.iter(|| rlp::encode(black_box(&transactions)).unwrap()));
+    group.bench_function("decode_transactions", |b| {
+        b.iter(|| rlp::decode_to::<Vec<LegacyTransaction>>(black_box(&encoded_transactions)).unwrap())
+    });
+    group.finish();
+}
+
+criterion_group!(benches, u256, keccak, trie, rlp);
+criterion_main!(benches);
@@ -0,0 +1,15 @@
+//! Arbitrary bytes as JSON documents, which must fail to decode cleanly.
+
+#![no_main]
+
+use ejit_evm::{
+    ethereum::{cancun::blocks::Header, genesis::Genesis},
+    json::{from_json, Value},
+};
+use libfuzzer_sys::fuzz_target;
+
+fuzz_target!(|data: &[u8]| {
+    let _ = from_json::<Value>(data);
+    let _ = from_json::<Header>(data);
+    let _ = from_json::<Genesis>(data);
+});
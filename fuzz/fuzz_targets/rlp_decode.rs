@@ -0,0 +1,23 @@
+//! Arbitrary bytes as blocks, headers and transactions. Decoding must fail
+//! cleanly, and what strict mode accepts must encode back to the input.
+
+#![no_main]
+
+use ejit_evm::ethereum::{
+    cancun::{
+        blocks::{Block, Header},
+        transactions::{decode_raw_transaction, Transaction},
+    },
+    ethereum_rlp::rlp::{decode_to, decode_to_strict, encode},
+};
+use libfuzzer_sys::fuzz_target;
+
+fuzz_target!(|data: &[u8]| {
+    if let Ok(block) = decode_to_strict::<Block>(data) {
+        assert_eq!(encode(&block).unwrap().0, data);
+    }
+    let _ = decode_to::<Block>(data);
+    let _ = decode_to::<Header>(data);
+    let _ = decode_to::<Transaction>(data);
+    let _ = decode_raw_transaction(data);
+});
@@ -0,0 +1,201 @@
+//! Block assembly in the form of the `b11r` tools of the execution clients.
+//!
+//! A block is built in two steps. `transition` executes the transactions
+//! and reports the roots of the resulting state, receipts and logs; the
+//! header of the block then takes those and the roots of its transactions,
+//! ommers and withdrawals. `seal_block` does the second step from the
+//! `ApplyBodyOutput` of the first, and `build_block` fills in what follows
+//! from the contents of a block for a header which already has the rest,
+//! as `evm b11r` does with the `header.json` written from a `t8n` result.
+//!
+//! Blocks are not mined: the `nonce` and `mixHash` of a proof of work
+//! block are taken from the header as given.
+//!
+//! [`transition`]: crate::t8n::transition
+
+use crate::{
+    ethereum::{
+        cancun::{
+            blocks::{Block, Header, Withdrawal},
+            fork::{compute_header_hash, encode_trie_value, ApplyBodyOutput},
+            fork_types::Root,
+            transactions::{encode_transaction, Transaction},
+            trie::Trie,
+        },
+        crypto::hash::{keccak256, Hash32},
+        ethereum_rlp::rlp,
+        ethereum_types::{bytes::Bytes, numeric::Uint},
+        exceptions::Exception,
+    },
+    impl_json,
+};
+
+/// A block as `evm b11r` writes it.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct B11rOutput {
+    pub rlp: Bytes,
+    pub hash: Hash32,
+}
+
+impl_json!(B11rOutput : rlp "rlp", hash "hash");
+
+impl B11rOutput {
+    pub fn new(block: &Block) -> Result<Self, Exception> {
+        Ok(Self { rlp: Bytes(rlp::encode(block)?.0), hash: compute_header_hash(&block.header)? })
+    }
+}
+
+/// Root of the trie of `transactions` by index.
+pub fn transactions_root(transactions: &[Transaction]) -> Result<Root, Exception> {
+    let mut trie: Trie<Bytes, Bytes> = Trie::new(false, Bytes::default());
+    for (i, tx) in transactions.iter().enumerate() {
+        trie.set(rlp::encode(&(i as Uint))?, encode_trie_value(encode_transaction(tx)?)?);
+    }
+    Ok(trie.root()?)
+}
+
+/// Root of the trie of `withdrawals` by index.
+pub fn withdrawals_root(withdrawals: &[Withdrawal]) -> Result<Root, Exception> {
+    let mut trie: Trie<Bytes, Bytes> = Trie::new(false, Bytes::default());
+    for (i, wd) in withdrawals.iter().enumerate() {
+        trie.set(rlp::encode(&(i as Uint))?, rlp::encode(wd)?);
+    }
+    Ok(trie.root()?)
+}
+
+/// Hash of the RLP list of `ommers`.
+pub fn ommers_hash(ommers: &[Header]) -> Result<Hash32, Exception> {
+    Ok(keccak256(&rlp::encode(&ommers.to_vec())?))
+}
+
+/// The block of `header` with these contents, filling in the transactions
+/// root, ommers hash and withdrawals root where `header` leaves them zero
+/// or absent. The fields which depend on execution are taken as given.
+pub fn build_block(
+    mut header: Header,
+    transactions: Vec<Transaction>,
+    ommers: Vec<Header>,
+    withdrawals: Option<Vec<Withdrawal>>,
+) -> Result<Block, Exception> {
+    if header.transactions_root == Root::default() {
+        header.transactions_root = transactions_root(&transactions)?;
+    }
+    if header.ommers_hash == Hash32::default() {
+        header.ommers_hash = ommers_hash(&ommers)?;
+    }
+    if let Some(withdrawals) = &withdrawals {
+        if header.withdrawals_root.as_ref().is_none_or(|root| *root == Root::default()) {
+            header.withdrawals_root = Some(withdrawals_root(withdrawals)?);
+        }
+    }
+    Ok(Block { header, transactions, ommers, withdrawals })
+}
+
+/// The block of `template` with these contents, whose execution `body`
+/// describes: the roots, bloom and gas used of `template` are replaced by
+/// those of `body` and of the contents.
+pub fn seal_block(
+    template: &Header,
+    transactions: Vec<Transaction>,
+    ommers: Vec<Header>,
+    withdrawals: Option<Vec<Withdrawal>>,
+    body: &ApplyBodyOutput,
+) -> Result<Block, Exception> {
+    let header = Header {
+        ommers_hash: ommers_hash(&ommers)?,
+        state_root: body.state_root.clone(),
+        transactions_root: body.transactions_root.clone(),
+        receipt_root: body.receipt_root.clone(),
+        bloom: body.block_logs_bloom.clone(),
+        gas_used: body.block_gas_used,
+        withdrawals_root: body.withdrawals_root.clone(),
+        blob_gas_used: body.blob_gas_used,
+        requests_hash: body.requests_hash.clone(),
+        ..template.clone()
+    };
+    Ok(Block { header, transactions, ommers, withdrawals })
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{
+        ethereum::{cancun::transactions::sign_transaction, crypto::eliptic_curve::secp256k1_public_key},
+        prelude::*,
+        t8n::{state_to_alloc, transition, T8nEnv},
+    };
+
+    use super::{build_block, seal_block, transactions_root, B11rOutput};
+
+    #[test]
+    fn seal_and_import() {
+        let secret_key = U256::from(0x5eed_u32);
+        let sender = Address::from_be_bytes(keccak256(&secp256k1_public_key(secret_key))[12..].try_into().unwrap());
+        let genesis = Genesis::post_merge(&["shanghai", "cancun"])
+            .chain_id(1)
+            .account(sender.clone(), Account { balance: U256::from(1_000_000_000_000_000_000_u64), ..Default::default() });
+        let mut chain = BlockChain::from_genesis(genesis).unwrap();
+        let parent = chain.blocks[0].header.clone();
+
+        let mut tx = Transaction::FeeMarketTransaction(FeeMarketTransaction {
+            chain_id: 1,
+            max_priority_fee_per_gas: 1,
+            max_fee_per_gas: 2_000_000_000,
+            gas: 21000,
+            to: Some(Address::from_be_bytes([0xaa; 20])).into(),
+            value: U256::from(1_u32),
+            ..Default::default()
+        });
+        sign_transaction(&mut tx, secret_key, None).unwrap();
+
+        let env = T8nEnv {
+            coinbase: Address::from_be_bytes([0xc0; 20]),
+            gas_limit: parent.gas_limit,
+            number: 1,
+            timestamp: U256::from(12_u32),
+            random: Some(Bytes32::default()),
+            parent_base_fee: parent.base_fee_per_gas,
+            parent_gas_used: Some(parent.gas_used),
+            parent_gas_limit: Some(parent.gas_limit),
+            parent_excess_blob_gas: parent.excess_blob_gas,
+            parent_blob_gas_used: parent.blob_gas_used,
+            parent_beacon_block_root: Some(Root::default()),
+            withdrawals: Some(Vec::new()),
+            ..Default::default()
+        };
+        let alloc = state_to_alloc(&chain.state);
+        let fork = fork_rules("cancun").unwrap();
+        let (_, result) = transition(&alloc, &env, &[Ok(tx.clone())], fork, &ChainSpec::MAINNET, None).unwrap();
+        assert!(result.rejected.is_empty());
+
+        let template = Header {
+            parent_hash: chain.head_hash().clone(),
+            coinbase: env.coinbase.clone(),
+            number: env.number,
+            gas_limit: env.gas_limit,
+            timestamp: env.timestamp,
+            base_fee_per_gas: result.base_fee,
+            excess_blob_gas: result.excess_blob_gas,
+            parent_beacon_block_root: env.parent_beacon_block_root.clone(),
+            ..Default::default()
+        };
+        let block = seal_block(&template, vec![tx.clone()], Vec::new(), Some(Vec::new()), &result.body).unwrap();
+        assert_eq!(block.header.transactions_root, transactions_root(&block.transactions).unwrap());
+
+        // The same block from a header with the execution fields only.
+        let header = Header {
+            state_root: block.header.state_root.clone(),
+            receipt_root: block.header.receipt_root.clone(),
+            bloom: block.header.bloom.clone(),
+            gas_used: block.header.gas_used,
+            blob_gas_used: block.header.blob_gas_used,
+            ..template
+        };
+        let built = build_block(header, vec![tx], Vec::new(), Some(Vec::new())).unwrap();
+        let output = B11rOutput::new(&block).unwrap();
+        assert_eq!(B11rOutput::new(&built).unwrap(), output);
+
+        let block: Block = rlp::decode_to(&output.rlp).unwrap();
+        assert_eq!(chain.import_block(block).unwrap(), output.hash);
+        assert_eq!(chain.head_hash(), &output.hash);
+    }
+}
@@ -0,0 +1,483 @@
+//! Command line interface.
+//!
+//! `ejit-evm replay <file>` imports the block of a `.replay` file again and
+//! reports whether the result is the one the file was captured with.
+//!
+//! `ejit-evm t8n` is a transition tool with the options of `evm t8n`: it
+//! reads `alloc.json`, `env.json` and `txs.json` (or `txs.rlp`) and writes
+//! `alloc.json` and `result.json` to `--output.basedir`. A file named
+//! `stdin` is read from standard input, and the outputs named `stdout` are
+//! printed as one object with `alloc` and `result` members. The chain id and
+//! parameters are mainnet's, or those of the `config` section of the genesis
+//! file given with `--input.genesis`, and `--state.chainid` sets the chain
+//! id.
+//!
+//! `ejit-evm b11r` assembles a block as `evm b11r` does, from `header.json`
+//! with the members of a JSON-RPC block, `txs.rlp`, `ommers.json`, a list
+//! of RLP encoded headers, and optionally `withdrawals.json`. It writes the
+//! RLP and hash of the block to `block.json`.
+//!
+//! `ejit-evm run` executes bytecode as the code of a contract in an empty
+//! state, with `--input` as calldata, and prints the output and gas used.
+//! `--trace` writes an EIP-3155 trace of the steps to standard error.
+//!
+//! `ejit-evm import` imports RLP dumps or era1 files into a data directory,
+//! which holds the genesis of the chain in `genesis.json` and its blocks as
+//! an RLP dump in `blocks.rlp`. The state is not stored: opening the
+//! directory executes the blocks again. A new directory starts from
+//! `--genesis`, mainnet's unless given. `ejit-evm export` prints the state
+//! at the head of a data directory in the format of `geth dump`.
+//!
+//! `ejit-evm test` runs the `GeneralStateTests` fixtures in the files and
+//! directories it is given, and fails if any entry does.
+//!
+//! Setting `EJIT_LOG` to `error`, `warn`, `info`, `debug` or `trace` logs
+//! to standard error at that level.
+
+use std::{
+    collections::{BTreeMap, BTreeSet},
+    fs::File,
+    io::{BufWriter, Read},
+    path::{Path, PathBuf},
+    process::ExitCode,
+};
+
+use ejit_evm::{
+    ethereum::{
+        cancun::{
+            blocks::Header,
+            fork::{replay::Replay, BlockChain},
+            fork_types::{Account, Address, Root},
+            state::{set_account, state_root, State},
+            transactions::Transaction,
+            utils::prepare_message,
+            vm::{interpreter::process_message_call, tracing::Eip3155Tracer, Environment},
+        },
+        chain_spec::ChainSpec,
+        ethereum_rlp::rlp::{self, Extended},
+        ethereum_types::{
+            bytes::Bytes,
+            numeric::{Uint, U256},
+        },
+        forks::fixture_fork,
+        genesis::Genesis,
+        utils::hexadecimal::hex_to_bytes,
+    },
+    json::{from_json, to_json, to_json_pretty, Encoder, JsonDecode, JsonEncode},
+    b11r::{build_block, B11rOutput},
+    spec_tests::{run_state_test_file, StateTestFilter},
+    t8n::{transition, Alloc, T8nEnv, T8nTransaction},
+};
+
+const USAGE: &str = "usage: ejit-evm replay <file.replay>
+       ejit-evm t8n [--input.alloc=<file>] [--input.env=<file>] [--input.txs=<file>] [--input.genesis=<file>]
+                    [--output.basedir=<dir>] [--output.alloc=<file>] [--output.result=<file>]
+                    [--state.fork=<fork>] [--state.chainid=<id>] [--state.reward=<wei>]
+       ejit-evm b11r [--input.header=<file>] [--input.txs=<file>] [--input.ommers=<file>]
+                     [--input.withdrawals=<file>] [--output.basedir=<dir>] [--output.block=<file>]
+       ejit-evm run (--code=<hex> | --codefile=<file>) [--input=<hex>] [--gas=<gas>] [--fork=<fork>] [--trace]
+       ejit-evm import --datadir=<dir> [--genesis=<file>] <file.rlp | file.era1>...
+       ejit-evm export --datadir=<dir> [--output=<file>]
+       ejit-evm test [--fork=<fork>] [--name=<substring>] <file or dir>...";
+
+fn main() -> ExitCode {
+    ejit_evm::log::init_from_env();
+    let args: Vec<String> = std::env::args().skip(1).collect();
+    match args.iter().map(String::as_str).collect::<Vec<_>>()[..] {
+        ["replay", path] => replay(path),
+        ["t8n", ref options @ ..] => tool("t8n", t8n(options)),
+        ["b11r", ref options @ ..] => tool("b11r", b11r(options)),
+        ["run", ref options @ ..] => tool("run", run(options)),
+        ["import", ref options @ ..] => tool("import", import(options)),
+        ["export", ref options @ ..] => tool("export", export(options)),
+        ["test", ref options @ ..] => match test(options) {
+            Ok(true) => ExitCode::SUCCESS,
+            Ok(false) => ExitCode::FAILURE,
+            Err(error) => tool("test", Err(error)),
+        },
+        _ => {
+            eprintln!("{USAGE}");
+            ExitCode::from(2)
+        }
+    }
+}
+
+fn describe(result: &Result<Root, String>) -> String {
+    match result {
+        Ok(state_root) => format!("imported, state root {}", to_json(state_root)),
+        Err(error) => format!("rejected, {error}"),
+    }
+}
+
+fn replay(path: &str) -> ExitCode {
+    let replay = match Replay::from_file(path) {
+        Ok(replay) => replay,
+        Err(error) => {
+            eprintln!("{path}: {error:?}");
+            return ExitCode::FAILURE;
+        }
+    };
+    let block = replay.block.header.number;
+    let result = replay.run();
+    println!("block {block}: {}", describe(&result));
+    if result == replay.result {
+        ExitCode::SUCCESS
+    } else {
+        println!("captured: {}", describe(&replay.result));
+        ExitCode::FAILURE
+    }
+}
+
+fn tool(name: &str, result: Result<(), String>) -> ExitCode {
+    match result {
+        Ok(()) => ExitCode::SUCCESS,
+        Err(error) => {
+            eprintln!("{name}: {error}");
+            ExitCode::FAILURE
+        }
+    }
+}
+
+/// The options of a tool, as `--name=value` or `--name value`.
+fn parse_options<'a>(args: &[&'a str], known: &[&str]) -> Result<BTreeMap<&'a str, &'a str>, String> {
+    let (options, arguments) = parse_args(args, known, &[])?;
+    match arguments.first() {
+        Some(arg) => Err(format!("unexpected argument {arg}")),
+        None => Ok(options),
+    }
+}
+
+/// `parse_options`, also taking `flags`, which have no value and read as
+/// `true`, and arguments which are not options.
+fn parse_args<'a>(
+    args: &[&'a str],
+    known: &[&str],
+    flags: &[&str],
+) -> Result<(BTreeMap<&'a str, &'a str>, Vec<&'a str>), String> {
+    let mut options = BTreeMap::new();
+    let mut arguments = Vec::new();
+    let mut args = args.iter();
+    while let Some(arg) = args.next() {
+        let Some(arg) = arg.strip_prefix("--") else {
+            arguments.push(*arg);
+            continue;
+        };
+        let (name, value) = match arg.split_once('=') {
+            Some((name, value)) => (name, value),
+            None if flags.contains(&arg) => (arg, "true"),
+            None => (arg, *args.next().ok_or_else(|| format!("--{arg} needs a value"))?),
+        };
+        if !known.contains(&name) && !flags.contains(&name) {
+            return Err(format!("unknown option --{name}\n{USAGE}"));
+        }
+        options.insert(name, value);
+    }
+    Ok((options, arguments))
+}
+
+fn read_input(path: &str) -> Result<String, String> {
+    let mut input = String::new();
+    let result = if path == "stdin" {
+        std::io::stdin().read_to_string(&mut input).map(|_| ())
+    } else {
+        std::fs::read_to_string(path).map(|s| input = s)
+    };
+    result.map_err(|error| format!("{path}: {error}"))?;
+    Ok(input)
+}
+
+fn read_json<T: for<'de> JsonDecode<'de> + Default>(path: &str) -> Result<T, String> {
+    from_json(read_input(path)?.as_bytes()).map_err(|error| format!("{path}: {error:?}"))
+}
+
+/// A value from the RLP in the hex string of the JSON file at `path`.
+fn read_rlp<T: Extended + Default>(path: &str) -> Result<T, String> {
+    let encoded: Bytes = read_json(path)?;
+    rlp::decode_to_strict(&encoded).map_err(|error| format!("{path}: {error:?}"))
+}
+
+fn t8n(args: &[&str]) -> Result<(), String> {
+    let known = [
+        "input.alloc",
+        "input.env",
+        "input.txs",
+        "input.genesis",
+        "output.basedir",
+        "output.alloc",
+        "output.result",
+        "state.fork",
+        "state.chainid",
+        "state.reward",
+    ];
+    let options = parse_options(args, &known)?;
+    let option = |name: &str, default: &'static str| options.get(name).copied().unwrap_or(default);
+
+    let fork_name = option("state.fork", "Cancun");
+    let fork = fixture_fork(fork_name).ok_or_else(|| format!("unknown fork {fork_name}"))?;
+    let mut chain_spec = match options.get("input.genesis") {
+        Some(path) => Genesis::from_file(path).map_err(|error| format!("{path}: {error:?}"))?.chain_spec,
+        None => ChainSpec::MAINNET,
+    };
+    if let Some(chain_id) = options.get("state.chainid") {
+        chain_spec.chain_id = chain_id.parse().map_err(|_| "bad --state.chainid".to_string())?;
+    }
+    // A negative reward, as `evm t8n` takes it, pays nothing.
+    let reward = match options.get("state.reward") {
+        None => fork.proof_of_work().map(|proof_of_work| proof_of_work.block_reward),
+        Some(reward) if reward.starts_with('-') => None,
+        Some(reward) => Some(reward.parse().map_err(|_| "bad --state.reward".to_string())?),
+    };
+
+    let alloc: Alloc = read_json(option("input.alloc", "alloc.json"))?;
+    let env: T8nEnv = read_json(option("input.env", "env.json"))?;
+    let txs_path = option("input.txs", "txs.json");
+    let txs = if txs_path.ends_with(".rlp") {
+        // The RLP list of the transactions as in a block, as a hex string.
+        read_rlp::<Vec<Transaction>>(txs_path)?.into_iter().map(Ok).collect::<Vec<_>>()
+    } else {
+        let txs: Vec<T8nTransaction> = read_json(txs_path)?;
+        txs.iter().map(|tx| tx.to_transaction(chain_spec.chain_id)).collect()
+    };
+
+    let (alloc, result) =
+        transition(&alloc, &env, &txs, fork, &chain_spec, reward).map_err(|error| format!("{error:?}"))?;
+
+    let basedir = Path::new(option("output.basedir", "."));
+    let mut stdout = Vec::new();
+    let outputs: [(&str, &str, &dyn JsonEncode); 2] = [
+        ("alloc", option("output.alloc", "alloc.json"), &alloc),
+        ("result", option("output.result", "result.json"), &result),
+    ];
+    for (name, path, value) in outputs {
+        if path == "stdout" {
+            stdout.push((name, value));
+        } else {
+            let path = basedir.join(path);
+            std::fs::write(&path, to_json_pretty(value)).map_err(|error| format!("{}: {error}", path.display()))?;
+        }
+    }
+    if !stdout.is_empty() {
+        let mut encoder = Encoder::pretty(2);
+        let mut o = encoder.object();
+        for (name, value) in stdout {
+            o.field(name, value);
+        }
+        o.end();
+        println!("{}", encoder.finish());
+    }
+    Ok(())
+}
+
+fn b11r(args: &[&str]) -> Result<(), String> {
+    let known = ["input.header", "input.txs", "input.ommers", "input.withdrawals", "output.basedir", "output.block"];
+    let options = parse_options(args, &known)?;
+    let option = |name: &str, default: &'static str| options.get(name).copied().unwrap_or(default);
+
+    let header: Header = read_json(option("input.header", "header.json"))?;
+    let transactions: Vec<Transaction> = read_rlp(option("input.txs", "txs.rlp"))?;
+    let ommers_path = option("input.ommers", "ommers.json");
+    let ommers = read_json::<Vec<Bytes>>(ommers_path)?
+        .iter()
+        .map(|encoded| rlp::decode_to_strict(encoded).map_err(|error| format!("{ommers_path}: {error:?}")))
+        .collect::<Result<Vec<Header>, _>>()?;
+    let withdrawals = options.get("input.withdrawals").map(|path| read_json(path)).transpose()?;
+
+    let block = build_block(header, transactions, ommers, withdrawals).map_err(|error| format!("{error:?}"))?;
+    let output = B11rOutput::new(&block).map_err(|error| format!("{error:?}"))?;
+    match option("output.block", "block.json") {
+        "stdout" => println!("{}", to_json_pretty(&output)),
+        path => {
+            let path = Path::new(option("output.basedir", ".")).join(path);
+            std::fs::write(&path, to_json_pretty(&output)).map_err(|error| format!("{}: {error}", path.display()))?;
+        }
+    }
+    Ok(())
+}
+
+/// Sender of the message of `run`.
+const CALLER: [u8; 20] = [0xca; 20];
+
+/// Account holding the code of `run`.
+const CONTRACT: [u8; 20] = [0xc0; 20];
+
+fn run(args: &[&str]) -> Result<(), String> {
+    let (options, arguments) = parse_args(args, &["code", "codefile", "input", "gas", "fork"], &["trace"])?;
+    if let Some(arg) = arguments.first() {
+        return Err(format!("unexpected argument {arg}"));
+    }
+    let code = match (options.get("code"), options.get("codefile")) {
+        (Some(code), None) => code.to_string(),
+        (None, Some(path)) => read_input(path)?,
+        _ => return Err(format!("give one of --code and --codefile\n{USAGE}")),
+    };
+    let code = hex_to_bytes(code.trim()).map_err(|_| "code is not hex".to_string())?;
+    let input = hex_to_bytes(options.get("input").copied().unwrap_or("0x")).map_err(|_| "--input is not hex")?;
+    let gas: Uint = options.get("gas").map_or(Ok(10_000_000), |gas| gas.parse()).map_err(|_| "bad --gas")?;
+    let fork_name = options.get("fork").copied().unwrap_or("Cancun");
+    let fork = fixture_fork(fork_name).ok_or_else(|| format!("unknown fork {fork_name}"))?;
+
+    let caller = Address::from_be_bytes(CALLER);
+    let contract = Address::from_be_bytes(CONTRACT);
+    let mut state = State::default();
+    set_account(&mut state, &caller, Some(Account::default()));
+    set_account(&mut state, &contract, Some(Account { nonce: 1, balance: U256::ZERO, code }));
+    let mut tracer = options.contains_key("trace").then(|| Eip3155Tracer::new(std::io::stderr()));
+    let mut env = Environment {
+        caller: caller.clone(),
+        origin: caller.clone(),
+        gas_limit: gas,
+        tracer: tracer.as_mut().map(|tracer| tracer as _),
+        ..Environment::new(&mut state, fork.execution_rules())
+    };
+    let message = prepare_message(
+        caller,
+        Some(contract),
+        U256::ZERO,
+        input,
+        gas,
+        &env,
+        None,
+        true,
+        false,
+        BTreeSet::new(),
+        BTreeSet::new(),
+    )
+    .map_err(|error| format!("{error:?}"))?;
+    let output = process_message_call(message, &mut env).map_err(|error| format!("{error:?}"))?;
+    let gas_used = gas - output.gas_left;
+    if let Some(tracer) = &mut tracer {
+        tracer.summary(&output.return_data, gas_used, output.error.as_ref());
+    }
+
+    let mut encoder = Encoder::pretty(2);
+    let mut o = encoder.object();
+    o.field("output", &output.return_data);
+    o.key("gasUsed");
+    o.encoder.quantity(&gas_used.to_be_bytes());
+    o.field("error", &output.error.map(|error| format!("{error:?}")));
+    o.end();
+    println!("{}", encoder.finish());
+    Ok(())
+}
+
+/// A chain kept in a data directory: its genesis and its blocks.
+struct DataDir {
+    genesis: PathBuf,
+    blocks: PathBuf,
+}
+
+impl DataDir {
+    fn new(path: &str) -> Self {
+        let path = Path::new(path);
+        Self { genesis: path.join("genesis.json"), blocks: path.join("blocks.rlp") }
+    }
+
+    /// The chain, executing the blocks stored.
+    fn open(&self) -> Result<BlockChain, String> {
+        let genesis = Genesis::from_file(&self.genesis).map_err(|error| format!("{}: {error}", self.genesis.display()))?;
+        let mut chain = BlockChain::from_genesis(genesis).map_err(|error| error.to_string())?;
+        if self.blocks.exists() {
+            let file = File::open(&self.blocks).map_err(|error| format!("{}: {error}", self.blocks.display()))?;
+            chain.import_blocks(file).map_err(|error| format!("{}: {error}", self.blocks.display()))?;
+        }
+        Ok(chain)
+    }
+}
+
+fn import(args: &[&str]) -> Result<(), String> {
+    let (options, files) = parse_args(args, &["datadir", "genesis"], &[])?;
+    let datadir = options.get("datadir").ok_or_else(|| format!("--datadir is needed\n{USAGE}"))?;
+    let dir = DataDir::new(datadir);
+    if !dir.genesis.exists() {
+        let genesis = match options.get("genesis") {
+            Some(path) => Genesis::from_file(path).map_err(|error| format!("{path}: {error}"))?,
+            None => Genesis::mainnet().map_err(|error| error.to_string())?,
+        };
+        std::fs::create_dir_all(datadir).map_err(|error| format!("{datadir}: {error}"))?;
+        std::fs::write(&dir.genesis, to_json_pretty(&genesis)).map_err(|error| format!("{datadir}: {error}"))?;
+    } else if options.contains_key("genesis") {
+        return Err(format!("{datadir} already has a genesis"));
+    }
+
+    let mut chain = dir.open()?;
+    let stored = chain.blocks.len();
+    let mut result = Ok(());
+    for path in files {
+        let file = File::open(path).map_err(|error| format!("{path}: {error}"))?;
+        // Keep the blocks before the one which failed.
+        if let Err(error) = chain.import_blocks(file) {
+            result = Err(format!("{path}: {error}"));
+            break;
+        }
+    }
+    let writer = BufWriter::new(File::create(&dir.blocks).map_err(|error| format!("{datadir}: {error}"))?);
+    chain.export_blocks(1.., writer).map_err(|error| format!("{datadir}: {error}"))?;
+    let head = &chain.blocks.last().unwrap().header;
+    let imported = chain.blocks.len().saturating_sub(stored);
+    eprintln!("imported {imported} blocks, head {} {}", head.number, chain.head_hash());
+    result
+}
+
+fn export(args: &[&str]) -> Result<(), String> {
+    let options = parse_options(args, &["datadir", "output"])?;
+    let datadir = options.get("datadir").ok_or_else(|| format!("--datadir is needed\n{USAGE}"))?;
+    let chain = DataDir::new(datadir).open()?;
+    let root = state_root(&chain.state).map_err(|error| format!("{error:?}"))?;
+
+    let mut encoder = Encoder::pretty(2);
+    let mut o = encoder.object();
+    o.field("root", &root);
+    o.field("accounts", &chain.state.dump(..));
+    o.end();
+    let dump = encoder.finish();
+    match options.get("output").copied().unwrap_or("stdout") {
+        "stdout" => println!("{dump}"),
+        path => std::fs::write(path, dump).map_err(|error| format!("{path}: {error}"))?,
+    }
+    Ok(())
+}
+
+/// Whether every entry of the fixtures passed.
+fn test(args: &[&str]) -> Result<bool, String> {
+    let (options, paths) = parse_args(args, &["fork", "name"], &[])?;
+    let filter = StateTestFilter {
+        fork: options.get("fork").map(|fork| fork.to_string()),
+        name: options.get("name").map(|name| name.to_string()),
+    };
+    let mut files = Vec::new();
+    for path in paths {
+        fixture_files(Path::new(path), &mut files).map_err(|error| format!("{path}: {error}"))?;
+    }
+
+    let (mut passed, mut failed) = (0, 0);
+    for file in &files {
+        let results = run_state_test_file(file, &filter).map_err(|error| format!("{}: {error}", file.display()))?;
+        for result in results {
+            match &result.failure {
+                None => passed += 1,
+                Some(failure) => {
+                    failed += 1;
+                    println!("FAIL {} {} {}: {failure}", result.name, result.fork, result.index);
+                }
+            }
+        }
+    }
+    println!("{passed} passed, {failed} failed");
+    Ok(failed == 0)
+}
+
+/// The `.json` files at `path`, searching directories recursively.
+fn fixture_files(path: &Path, files: &mut Vec<PathBuf>) -> std::io::Result<()> {
+    if !path.is_dir() {
+        files.push(path.to_path_buf());
+        return Ok(());
+    }
+    let mut entries = std::fs::read_dir(path)?.map(|entry| entry.map(|entry| entry.path())).collect::<Result<Vec<_>, _>>()?;
+    entries.sort();
+    for entry in entries {
+        if entry.is_dir() || entry.extension().is_some_and(|extension| extension == "json") {
+            fixture_files(&entry, files)?;
+        }
+    }
+    Ok(())
+}
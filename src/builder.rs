@@ -0,0 +1,187 @@
+//! Building blocks on the head of a chain.
+//!
+//! `build_payload` produces the next block of a proof of stake chain from
+//! the payload attributes of the consensus layer and a list of candidate
+//! transactions. The candidates are tried in the order given and those
+//! which cannot be included, because they are invalid, do not fit in the
+//! gas or blob gas left or are out of order, are left out and reported.
+//! The header is then derived from the parent and the execution, so the
+//! block passes `state_transition` on the same chain.
+//!
+//! The block keeps the gas limit of its parent.
+
+use crate::{
+    b11r::seal_block,
+    engine::PayloadAttributes,
+    ethereum::{
+        cancun::{
+            blocks::{Block, Header},
+            fork::{
+                calculate_base_fee_per_gas, get_last_256_block_hashes, prevalidate::prevalidate_transaction,
+                ApplyBodyOutput, BlockChain, BlockEnvironment, BlockExecution, EMPTY_OMMER_HASH, INITIAL_BASE_FEE,
+            },
+            transactions::Transaction,
+            vm::gas::calculate_excess_blob_gas,
+        },
+        ethereum_types::numeric::{Uint, U256},
+        exceptions::Exception,
+    },
+    t8n::Rejected,
+};
+
+/// A block built by `build_payload`.
+#[derive(Debug, Clone)]
+pub struct BuiltPayload {
+    pub block: Block,
+    /// The execution of the block.
+    pub body: ApplyBodyOutput,
+    /// The candidates left out of the block.
+    pub rejected: Vec<Rejected>,
+    /// Priority fees paid to the fee recipient, in wei.
+    pub block_value: U256,
+}
+
+/// Build the block following the head of `chain` with as many of
+/// `transactions` as can be included, in their order.
+pub fn build_payload(
+    chain: &BlockChain,
+    transactions: &[Transaction],
+    attributes: &PayloadAttributes,
+) -> Result<BuiltPayload, Exception> {
+    let parent = &chain.blocks.last().unwrap().header;
+    if attributes.timestamp <= parent.timestamp {
+        return Err(Exception::InvalidBlock("attributes.timestamp <= parent.timestamp"));
+    }
+    let mut header = Header {
+        parent_hash: chain.head_hash().clone(),
+        ommers_hash: EMPTY_OMMER_HASH,
+        coinbase: attributes.suggested_fee_recipient.clone(),
+        number: parent.number + 1,
+        gas_limit: parent.gas_limit,
+        timestamp: attributes.timestamp,
+        prev_randao: attributes.prev_randao.clone(),
+        ..Default::default()
+    };
+    let fork = chain.fork_for(&header)?;
+    if fork.proof_of_work().is_some() {
+        return Err(Exception::EthereumException("cannot build a proof of work block"));
+    }
+    let constraints = fork.header_constraints();
+    let rules = fork.execution_rules();
+
+    if constraints.base_fee {
+        // The parent of the first London block has no base fee.
+        header.base_fee_per_gas = Some(match parent.base_fee_per_gas {
+            Some(parent_base_fee) => {
+                calculate_base_fee_per_gas(
+                    header.gas_limit,
+                    parent.gas_limit,
+                    parent.gas_used,
+                    parent_base_fee,
+                    &chain.chain_spec,
+                )?
+            }
+            None => INITIAL_BASE_FEE,
+        });
+    }
+    if constraints.blob_gas {
+        header.excess_blob_gas = Some(calculate_excess_blob_gas(parent, &chain.chain_spec).unwrap_or(0));
+    }
+    if constraints.parent_beacon_block_root {
+        header.parent_beacon_block_root = Some(attributes.parent_beacon_block_root.clone());
+    }
+    let withdrawals = constraints.withdrawals.then(|| attributes.withdrawals.clone());
+
+    let env = BlockEnvironment {
+        block_hashes: get_last_256_block_hashes(chain),
+        coinbase: header.coinbase.clone(),
+        number: header.number,
+        base_fee_per_gas: header.base_fee_per_gas,
+        gas_limit: header.gas_limit,
+        time: header.timestamp,
+        prev_randao: header.prev_randao.clone(),
+        chain_id: chain.chain_spec.chain_id,
+        parent_beacon_block_root: header.parent_beacon_block_root.clone(),
+        excess_blob_gas: header.excess_blob_gas,
+        rules,
+        chain_spec: chain.chain_spec.clone(),
+        code_cache: Some(chain.code_cache.clone()),
+    };
+    let mut state = chain.state.clone();
+    let mut execution = BlockExecution::begin(&mut state, &env)?;
+    let mut included = Vec::new();
+    let mut rejected = Vec::new();
+    for (index, tx) in transactions.iter().enumerate() {
+        let result = prevalidate_transaction(tx, chain.chain_spec.chain_id, rules)
+            .and_then(|prevalidated| execution.apply_transaction(&mut state, &env, tx, prevalidated.sender));
+        match result {
+            Ok(()) => included.push(tx.clone()),
+            Err(error) => rejected.push(Rejected { index, error: format!("{error:?}") }),
+        }
+    }
+    let body = execution.finish(&mut state, &env, &[], None, withdrawals.as_deref())?;
+
+    let base_fee = header.base_fee_per_gas.unwrap_or_default();
+    let block_value = body
+        .receipts
+        .iter()
+        .map(|receipt| U256::from(receipt.gas_used as u64) * U256::from((receipt.effective_gas_price - base_fee) as u64))
+        .fold(U256::ZERO, |total, fee| total + fee);
+
+    let block = seal_block(&header, included, Vec::new(), withdrawals, &body)?;
+    Ok(BuiltPayload { block, body, rejected, block_value })
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{
+        engine::PayloadAttributes,
+        ethereum::{cancun::transactions::sign_transaction, crypto::eliptic_curve::secp256k1_public_key},
+        prelude::*,
+    };
+
+    use super::build_payload;
+
+    #[test]
+    fn build_and_import() {
+        let secret_key = U256::from(0x5eed_u32);
+        let sender = Address::from_be_bytes(keccak256(&secp256k1_public_key(secret_key))[12..].try_into().unwrap());
+        let genesis = Genesis::post_merge(&["shanghai", "cancun"])
+            .chain_id(1)
+            .account(sender.clone(), Account { balance: U256::from(1_000_000_000_000_000_000_u64), ..Default::default() });
+        let mut chain = BlockChain::from_genesis(genesis).unwrap();
+
+        let transfer = |nonce: U256, gas: Uint| {
+            let mut tx = Transaction::FeeMarketTransaction(FeeMarketTransaction {
+                chain_id: 1,
+                nonce,
+                max_priority_fee_per_gas: 2,
+                max_fee_per_gas: 2_000_000_000,
+                gas,
+                to: Some(Address::from_be_bytes([0xaa; 20])).into(),
+                value: U256::from(1_u32),
+                ..Default::default()
+            });
+            sign_transaction(&mut tx, secret_key, None).unwrap();
+            tx
+        };
+        // The second does not fit in the block and the third then has a
+        // nonce too high.
+        let transactions =
+            [transfer(U256::ZERO, 21000), transfer(U256::ONE, 40_000_000), transfer(U256::from(2_u32), 21000)];
+        let attributes = PayloadAttributes {
+            timestamp: U256::from(12_u32),
+            suggested_fee_recipient: Address::from_be_bytes([0xc0; 20]),
+            ..Default::default()
+        };
+        let payload = build_payload(&chain, &transactions, &attributes).unwrap();
+        assert_eq!(payload.block.transactions.len(), 1);
+        assert_eq!(payload.rejected.iter().map(|rejected| rejected.index).collect::<Vec<_>>(), [1, 2]);
+        assert_eq!(payload.block_value, U256::from(2 * 21000_u32));
+        assert_eq!(payload.block.header.gas_used, 21000);
+
+        let hash = chain.import_block(payload.block).unwrap();
+        assert_eq!(chain.head_hash(), &hash);
+        assert_eq!(get_account(&chain.state, &Address::from_be_bytes([0xaa; 20])).balance, U256::ONE);
+    }
+}
@@ -0,0 +1,223 @@
+//! Call graphs built from call frame traces.
+//!
+//! Enable call frame tracing by setting `Environment::call_frames` to
+//! `Some(Vec::new())` before executing a message, then build a graph with
+//! `CallGraph::from_frames`. Several traces can be added to the same graph
+//! to profile a whole block or range of blocks.
+//!
+//! Nodes are the accounts whose code was executed, so a `DELEGATECALL` into
+//! a library is attributed to the library. Edges count the calls from the
+//! code running in the parent frame to the code of the child frame. The
+//! sender of a transaction is a node with no gas of its own.
+//!
+//! `CallGraph::hottest` orders the contracts by the gas spent in their own
+//! code, which is a reasonable first guess at what the JIT should compile
+//! ahead of time. `CallGraph::to_dot` renders the graph for Graphviz:
+//!
+//! ```sh
+//! dot -Tsvg calls.dot > calls.svg
+//! ```
+
+use std::{collections::BTreeMap, fmt::Write};
+
+use crate::ethereum::{
+    cancun::{fork_types::Address, vm::CallFrame},
+    ethereum_types::numeric::Uint,
+};
+
+/// Totals for the code of one account.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct CallNode {
+    /// Number of frames which executed this code.
+    pub calls: u64,
+    /// Number of those frames which halted exceptionally or reverted.
+    pub errors: u64,
+    /// Gas used by those frames, including their children.
+    pub gas_used: Uint,
+    /// Gas used by those frames, excluding their children.
+    pub self_gas_used: Uint,
+}
+
+/// Totals for the calls from one account's code to another.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct CallEdge {
+    pub calls: u64,
+    /// Calls which created the callee.
+    pub creates: u64,
+    /// Gas used by the callee frames, including their children.
+    pub gas_used: Uint,
+}
+
+/// Contracts and the calls between them.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct CallGraph {
+    pub nodes: BTreeMap<Address, CallNode>,
+    pub edges: BTreeMap<(Address, Address), CallEdge>,
+}
+
+impl CallGraph {
+    /// Build a graph from the frames of one trace.
+    pub fn from_frames(frames: &[CallFrame]) -> Self {
+        let mut graph = Self::default();
+        graph.add_frames(frames);
+        graph
+    }
+
+    /// Add the frames of one trace, in the order they were recorded.
+    pub fn add_frames(&mut self, frames: &[CallFrame]) {
+        // Indices of the frames enclosing the current one.
+        let mut parents: Vec<usize> = Vec::new();
+        for (index, frame) in frames.iter().enumerate() {
+            while let Some(&parent) = parents.last() {
+                if frames[parent].depth < frame.depth {
+                    break;
+                }
+                parents.pop();
+            }
+            let from = match parents.last() {
+                Some(&parent) => frames[parent].code_address.clone(),
+                None => frame.caller.clone(),
+            };
+            let to = frame.code_address.clone();
+
+            let node = self.nodes.entry(to.clone()).or_default();
+            node.calls += 1;
+            node.errors += frame.error.is_some() as u64;
+            node.gas_used += frame.gas_used;
+            node.self_gas_used += frame.gas_used;
+            if let Some(&parent) = parents.last() {
+                let parent = self.nodes.get_mut(&frames[parent].code_address).unwrap();
+                parent.self_gas_used = parent.self_gas_used.saturating_sub(frame.gas_used);
+            } else {
+                self.nodes.entry(from.clone()).or_default();
+            }
+
+            let edge = self.edges.entry((from, to)).or_default();
+            edge.calls += 1;
+            edge.creates += frame.is_create as u64;
+            edge.gas_used += frame.gas_used;
+
+            parents.push(index);
+        }
+    }
+
+    /// Merge the totals of another graph into this one.
+    pub fn merge(&mut self, other: &CallGraph) {
+        for (address, node) in &other.nodes {
+            let total = self.nodes.entry(address.clone()).or_default();
+            total.calls += node.calls;
+            total.errors += node.errors;
+            total.gas_used += node.gas_used;
+            total.self_gas_used += node.self_gas_used;
+        }
+        for (key, edge) in &other.edges {
+            let total = self.edges.entry(key.clone()).or_default();
+            total.calls += edge.calls;
+            total.creates += edge.creates;
+            total.gas_used += edge.gas_used;
+        }
+    }
+
+    /// Accounts whose code was executed, most expensive first.
+    pub fn hottest(&self) -> Vec<(Address, Uint)> {
+        let mut hottest = self
+            .nodes
+            .iter()
+            .filter(|(_, node)| node.calls != 0)
+            .map(|(address, node)| (address.clone(), node.self_gas_used))
+            .collect::<Vec<_>>();
+        hottest.sort_by(|a, b| b.1.cmp(&a.1).then_with(|| a.0.cmp(&b.0)));
+        hottest
+    }
+
+    /// Render the graph in the Graphviz DOT language.
+    pub fn to_dot(&self) -> String {
+        let mut dot = String::from("digraph calls {\n    node [shape=box, fontname=monospace];\n");
+        for (address, node) in &self.nodes {
+            writeln!(
+                dot,
+                "    \"{}\" [label=\"{}\\ncalls: {}\\nerrors: {}\\ngas: {}\\nself gas: {}\"];",
+                address.to_hex(),
+                address.to_hex(),
+                node.calls,
+                node.errors,
+                node.gas_used,
+                node.self_gas_used,
+            )
+            .unwrap();
+        }
+        for ((from, to), edge) in &self.edges {
+            let style = if edge.creates != 0 { ", style=dashed" } else { "" };
+            writeln!(
+                dot,
+                "    \"{}\" -> \"{}\" [label=\"{} x {} gas\"{style}];",
+                from.to_hex(),
+                to.to_hex(),
+                edge.calls,
+                edge.gas_used,
+            )
+            .unwrap();
+        }
+        dot.push_str("}\n");
+        dot
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::ethereum::{
+        cancun::{fork_types::Address, vm::CallFrame},
+        ethereum_types::numeric::U256,
+    };
+
+    use super::CallGraph;
+
+    fn frame(depth: u128, caller: u8, target: u8, code: u8, gas_used: u128) -> CallFrame {
+        CallFrame {
+            depth,
+            caller: Address::from_be_bytes([caller; 20]),
+            current_target: Address::from_be_bytes([target; 20]),
+            code_address: Address::from_be_bytes([code; 20]),
+            is_create: false,
+            value: U256::ZERO,
+            gas: 100_000,
+            gas_used,
+            error: None,
+        }
+    }
+
+    #[test]
+    fn nested_calls() {
+        // 1 calls 2, which delegates to 3 twice and then calls 4.
+        let frames = [
+            frame(0, 1, 2, 2, 1000),
+            frame(1, 2, 2, 3, 100),
+            frame(1, 2, 2, 3, 200),
+            frame(1, 2, 4, 4, 300),
+            frame(2, 4, 2, 2, 50),
+        ];
+        let graph = CallGraph::from_frames(&frames);
+        let node = |n: u8| graph.nodes[&Address::from_be_bytes([n; 20])].clone();
+        let edge = |a: u8, b: u8| {
+            graph.edges[&(Address::from_be_bytes([a; 20]), Address::from_be_bytes([b; 20]))].clone()
+        };
+        assert_eq!(node(1).calls, 0);
+        assert_eq!(node(2).calls, 2);
+        assert_eq!(node(2).gas_used, 1050);
+        assert_eq!(node(2).self_gas_used, 1000 - 600 + 50);
+        assert_eq!(node(3).calls, 2);
+        assert_eq!(node(4).self_gas_used, 250);
+        assert_eq!(edge(2, 3).calls, 2);
+        assert_eq!(edge(2, 3).gas_used, 300);
+        assert_eq!(edge(4, 2).calls, 1);
+        assert_eq!(graph.edges.len(), 4);
+
+        let hottest = graph.hottest();
+        assert_eq!(hottest[0], (Address::from_be_bytes([2; 20]), 450));
+        assert_eq!(hottest.len(), 3);
+
+        let dot = graph.to_dot();
+        assert!(dot.starts_with("digraph calls {"));
+        assert!(dot.contains(&format!("\"0x{}\" -> \"0x{}\" [label=\"2 x 300 gas\"];", "02".repeat(20), "03".repeat(20))));
+    }
+}
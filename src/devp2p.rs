@@ -0,0 +1,608 @@
+//! Peer-to-peer networking: node discovery, the RLPx transport and the
+//! `eth/68` and `snap/1` protocols.
+//!
+//! `Peer::connect` opens a TCP connection to a node given by its enode URL,
+//! runs the RLPx handshake of `rlpx`, exchanges `Hello` messages of the
+//! base protocol and then `eth` `Status` messages, checking that both ends
+//! follow the same chain. After that the peer's headers and bodies can be
+//! requested, and `sync` uses those requests to import the blocks which a
+//! peer has past the head of a `BlockChain`. `snap` fetches the state of a
+//! block instead, for which peers must support `snap/1` too. `Peer::serve`
+//! answers the requests of both protocols from a chain, so two instances
+//! can sync from each other.
+//!
+//! Peers are dialled by enode, which `discv4` finds from bootnodes, and
+//! described by the node records of `enr`. Transaction pool gossip is not
+//! implemented: announcements are read and dropped.
+
+/// Declare the messages of a subprotocol as an enum of their types, with
+/// their ids relative to the protocol's offset.
+macro_rules! protocol_messages {
+    ($(#[$attr : meta])* $name : ident, $unknown : literal, $($id : literal => $variant : ident($t : ty),)*) => {
+        $(#[$attr])*
+        #[derive(Debug, Clone)]
+        pub enum $name {
+            $($variant($t),)*
+        }
+
+        impl $name {
+            pub fn id(&self) -> u64 {
+                match self {
+                    $($name::$variant(_) => $id,)*
+                }
+            }
+
+            pub fn encode(&self) -> Result<Bytes, RLPException> {
+                match self {
+                    $($name::$variant(message) => rlp::encode(message),)*
+                }
+            }
+
+            pub fn decode(id: u64, payload: &[u8]) -> Result<Self, P2pError> {
+                match id {
+                    $($id => Ok($name::$variant(rlp::decode_to_strict(payload)?)),)*
+                    _ => Err(P2pError::Protocol($unknown)),
+                }
+            }
+        }
+    };
+}
+
+pub mod discv4;
+pub mod ecies;
+pub mod enr;
+pub mod eth;
+pub mod rlpx;
+pub mod snap;
+pub mod snappy;
+
+use std::{
+    fmt,
+    io::{self, Read, Write},
+    net::{SocketAddr, TcpStream, ToSocketAddrs},
+    str::FromStr,
+    time::Duration,
+};
+
+use crate::ethereum::{
+    cancun::{
+        blocks::Header,
+        fork::{compute_header_hash, BlockChain},
+    },
+    crypto::{
+        eliptic_curve::{secp256k1_public_key, SECP256K1N},
+        hash::Hash32,
+    },
+    ethereum_rlp::{
+        exceptions::RLPException,
+        rlp,
+        stream::{RlpItem, RlpStream},
+    },
+    ethereum_types::{bytes::Bytes, numeric::U256},
+    exceptions::Exception,
+    utils::hexadecimal::hex_to_bytes,
+};
+
+use eth::{
+    BlockBodies, BlockBody, BlockHeaders, BlockId, EthMessage, GetBlockHeaders, GetByHashes, HeadersRequest,
+    PooledTransactions, Receipts, Status, ETH_VERSION,
+};
+use rlpx::Rlpx;
+use snap::{SnapMessage, SnapState, SNAP_VERSION};
+
+/// Why a connection failed or was closed.
+#[derive(Debug)]
+pub enum P2pError {
+    Io(io::Error),
+    /// A message is not valid RLP or not of the expected shape.
+    Rlp(RLPException),
+    /// A handshake or frame failed to decrypt or authenticate.
+    Crypto(&'static str),
+    /// The peer broke the rules of a protocol.
+    Protocol(&'static str),
+    /// The peer disconnected, with this reason.
+    Disconnected(u64),
+    /// A block received from the peer could not be imported.
+    Block(Exception),
+    /// State received from the peer failed verification.
+    State(Exception),
+    /// An enode URL is malformed.
+    InvalidEnode,
+}
+
+impl From<io::Error> for P2pError {
+    fn from(value: io::Error) -> Self {
+        P2pError::Io(value)
+    }
+}
+
+impl From<RLPException> for P2pError {
+    fn from(value: RLPException) -> Self {
+        P2pError::Rlp(value)
+    }
+}
+
+/// `N` random bytes from the operating system.
+fn random_bytes<const N: usize>() -> [u8; N] {
+    let mut bytes = [0; N];
+    std::fs::File::open("/dev/urandom")
+        .and_then(|mut urandom| urandom.read_exact(&mut bytes))
+        .expect("cannot read /dev/urandom");
+    bytes
+}
+
+/// A random secp256k1 secret key.
+pub fn random_secret_key() -> U256 {
+    loop {
+        let key = U256::from_be_bytes(random_bytes());
+        if !key.is_zero() && key < SECP256K1N {
+            return key;
+        }
+    }
+}
+
+/// The address of a node: `enode://<public key>@<host>:<port>`.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Enode {
+    /// The public key, without the `0x04` prefix.
+    pub id: Bytes,
+    pub address: SocketAddr,
+}
+
+impl FromStr for Enode {
+    type Err = P2pError;
+
+    /// Any query, such as the `discport` of some nodes, is ignored.
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let rest = s.strip_prefix("enode://").ok_or(P2pError::InvalidEnode)?;
+        let (id, host) = rest.split_once('@').ok_or(P2pError::InvalidEnode)?;
+        let host = host.split('?').next().unwrap();
+        let id = hex_to_bytes(id).map_err(|_| P2pError::InvalidEnode)?;
+        if id.len() != 64 {
+            return Err(P2pError::InvalidEnode);
+        }
+        let address = host.to_socket_addrs().ok().and_then(|mut a| a.next()).ok_or(P2pError::InvalidEnode)?;
+        Ok(Self { id, address })
+    }
+}
+
+impl fmt::Display for Enode {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "enode://")?;
+        for b in self.id.iter() {
+            write!(f, "{b:02x}")?;
+        }
+        write!(f, "@{}", self.address)
+    }
+}
+
+/// Message ids of the base protocol. Those of the capabilities follow in
+/// the order of their names, `eth` from `ETH_OFFSET` and its 17 messages
+/// then `snap` from `SNAP_OFFSET`.
+pub const HELLO: u64 = 0x00;
+pub const DISCONNECT: u64 = 0x01;
+pub const PING: u64 = 0x02;
+pub const PONG: u64 = 0x03;
+pub const ETH_OFFSET: u64 = 0x10;
+pub const SNAP_OFFSET: u64 = ETH_OFFSET + 0x11;
+
+/// Disconnect reasons.
+pub const DISCONNECT_REQUESTED: u64 = 0x00;
+pub const DISCONNECT_USELESS_PEER: u64 = 0x03;
+pub const DISCONNECT_SUBPROTOCOL_ERROR: u64 = 0x10;
+
+/// Most headers or bodies sent in one response, as in geth.
+const MAX_RESPONSE_ITEMS: usize = 1024;
+
+/// Headers requested at once by `sync`.
+const HEADERS_BATCH: u64 = 192;
+
+/// The first message of the base protocol.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Hello {
+    pub protocol_version: u64,
+    pub client_id: String,
+    /// Names and versions of the supported subprotocols.
+    pub capabilities: Vec<(String, u64)>,
+    pub listen_port: u64,
+    /// The sender's public key.
+    pub id: Bytes,
+}
+
+impl Hello {
+    /// The `Hello` of this client with `secret_key`.
+    pub fn new(secret_key: U256) -> Self {
+        Self {
+            protocol_version: 5,
+            client_id: format!("ejit-evm/v{}", env!("CARGO_PKG_VERSION")),
+            capabilities: vec![("eth".to_string(), ETH_VERSION), ("snap".to_string(), SNAP_VERSION)],
+            listen_port: 0,
+            id: secp256k1_public_key(secret_key),
+        }
+    }
+
+    fn encode(&self) -> Result<Bytes, RLPException> {
+        let mut out = Bytes::default();
+        rlp::encode_sequence(
+            &mut out,
+            &[&self.protocol_version, &self.client_id, &self.capabilities, &self.listen_port, &self.id],
+        )?;
+        Ok(out)
+    }
+
+    /// Items added by later versions of the protocol are ignored.
+    fn decode(payload: &[u8]) -> Result<Self, RLPException> {
+        let mut fields = RlpStream::new(payload).next_list()?;
+        let protocol_version = fields.next_u64()?;
+        let client_id = String::from_utf8_lossy(fields.next_bytes()?).into_owned();
+        let mut capabilities = Vec::new();
+        let mut list = fields.next_list()?;
+        while !list.is_empty() {
+            let mut capability = list.next_list()?;
+            let name = String::from_utf8_lossy(capability.next_bytes()?).into_owned();
+            capabilities.push((name, capability.next_u64()?));
+        }
+        let listen_port = fields.next_u64()?;
+        let id = Bytes(fields.next_bytes()?.to_vec());
+        Ok(Self { protocol_version, client_id, capabilities, listen_port, id })
+    }
+}
+
+/// A message of one of the subprotocols.
+#[derive(Debug, Clone)]
+pub enum Message {
+    Eth(EthMessage),
+    Snap(SnapMessage),
+}
+
+impl From<EthMessage> for Message {
+    fn from(value: EthMessage) -> Self {
+        Message::Eth(value)
+    }
+}
+
+impl From<SnapMessage> for Message {
+    fn from(value: SnapMessage) -> Self {
+        Message::Snap(value)
+    }
+}
+
+/// A connected peer which speaks `eth/68`.
+pub struct Peer<S> {
+    rlpx: Rlpx<S>,
+    /// The peer's `Hello`.
+    pub hello: Hello,
+    /// The peer's `Status`.
+    pub status: Status,
+    next_request_id: u64,
+}
+
+impl Peer<TcpStream> {
+    /// Dial `enode` and complete the handshakes, announcing `status`.
+    pub fn connect(enode: &Enode, secret_key: U256, status: &Status) -> Result<Self, P2pError> {
+        let stream = TcpStream::connect_timeout(&enode.address, Duration::from_secs(10))?;
+        stream.set_read_timeout(Some(Duration::from_secs(30)))?;
+        stream.set_nodelay(true)?;
+        let rlpx = Rlpx::connect(stream, secret_key, &enode.id)?;
+        Self::handshake(rlpx, secret_key, status)
+    }
+}
+
+impl<S: Read + Write> Peer<S> {
+    /// Exchange `Hello` and `Status` messages over an RLPx connection.
+    ///
+    /// The peer is disconnected if it does not support `eth/68` or follows
+    /// another network or genesis.
+    pub fn handshake(mut rlpx: Rlpx<S>, secret_key: U256, status: &Status) -> Result<Self, P2pError> {
+        rlpx.send(HELLO, &Hello::new(secret_key).encode()?)?;
+        let hello = match rlpx.recv()? {
+            (HELLO, payload) => Hello::decode(&payload)?,
+            (DISCONNECT, payload) => return Err(P2pError::Disconnected(disconnect_reason(&payload))),
+            _ => return Err(P2pError::Protocol("expected Hello")),
+        };
+        rlpx.snappy = hello.protocol_version >= 5;
+        let mut peer = Self { rlpx, hello, status: Status::default(), next_request_id: 0 };
+        if !peer.hello.capabilities.iter().any(|(name, version)| name == "eth" && *version == ETH_VERSION) {
+            peer.disconnect(DISCONNECT_USELESS_PEER)?;
+            return Err(P2pError::Protocol("peer does not support eth/68"));
+        }
+
+        peer.send(EthMessage::Status(status.clone()))?;
+        let Message::Eth(EthMessage::Status(remote)) = peer.recv()? else {
+            return Err(P2pError::Protocol("expected Status"));
+        };
+        if remote.network_id != status.network_id || remote.genesis_hash != status.genesis_hash {
+            peer.disconnect(DISCONNECT_SUBPROTOCOL_ERROR)?;
+            return Err(P2pError::Protocol("peer is on another network"));
+        }
+        peer.status = remote;
+        Ok(peer)
+    }
+
+    pub fn send(&mut self, message: impl Into<Message>) -> Result<(), P2pError> {
+        match message.into() {
+            Message::Eth(message) => self.rlpx.send(ETH_OFFSET + message.id(), &message.encode()?),
+            Message::Snap(message) => self.rlpx.send(SNAP_OFFSET + message.id(), &message.encode()?),
+        }
+    }
+
+    /// Receive the next subprotocol message, answering pings on the way.
+    pub fn recv(&mut self) -> Result<Message, P2pError> {
+        loop {
+            let (id, payload) = self.rlpx.recv()?;
+            match id {
+                PING => self.rlpx.send(PONG, &[0xc0])?,
+                DISCONNECT => return Err(P2pError::Disconnected(disconnect_reason(&payload))),
+                id if id >= SNAP_OFFSET => return Ok(Message::Snap(SnapMessage::decode(id - SNAP_OFFSET, &payload)?)),
+                id if id >= ETH_OFFSET => return Ok(Message::Eth(EthMessage::decode(id - ETH_OFFSET, &payload)?)),
+                _ => {}
+            }
+        }
+    }
+
+    /// Tell the peer why the connection is being closed.
+    pub fn disconnect(&mut self, reason: u64) -> Result<(), P2pError> {
+        self.rlpx.send(DISCONNECT, &rlp::encode(&vec![reason])?)
+    }
+
+    /// Answer a request of the peer from `chain`. Requests for pooled
+    /// transactions and receipts, and for states other than the head's, get
+    /// empty responses and other messages are ignored.
+    pub fn serve(&mut self, chain: &BlockChain, message: &Message) -> Result<(), P2pError> {
+        let message = match message {
+            Message::Eth(message) => message,
+            Message::Snap(message) => {
+                let state = SnapState::from_state(&chain.state)?;
+                return match state.respond(message) {
+                    Some(response) => self.send(response),
+                    None => Ok(()),
+                };
+            }
+        };
+        let response = match message {
+            EthMessage::GetBlockHeaders(GetBlockHeaders { request_id, request }) => {
+                let headers = headers_from(chain, request).map_err(P2pError::Block)?;
+                EthMessage::BlockHeaders(BlockHeaders { request_id: *request_id, headers })
+            }
+            EthMessage::GetBlockBodies(GetByHashes { request_id, hashes }) => {
+                let bodies = hashes
+                    .iter()
+                    .take(MAX_RESPONSE_ITEMS)
+                    .map_while(|hash| chain.get_block(hash))
+                    .map(|block| BlockBody {
+                        transactions: block.transactions.clone(),
+                        ommers: block.ommers.clone(),
+                        withdrawals: block.withdrawals.clone(),
+                    })
+                    .collect();
+                EthMessage::BlockBodies(BlockBodies { request_id: *request_id, bodies })
+            }
+            _ => return self.serve_empty(&Message::Eth(message.clone())),
+        };
+        self.send(response)
+    }
+
+    /// Answer a request of the peer with an empty response.
+    fn serve_empty(&mut self, message: &Message) -> Result<(), P2pError> {
+        let message = match message {
+            Message::Eth(message) => message,
+            Message::Snap(message) => {
+                return match message.empty_response() {
+                    Some(response) => self.send(response),
+                    None => Ok(()),
+                };
+            }
+        };
+        let response = match message {
+            EthMessage::GetBlockHeaders(GetBlockHeaders { request_id, .. }) => {
+                EthMessage::BlockHeaders(BlockHeaders { request_id: *request_id, headers: Vec::new() })
+            }
+            EthMessage::GetBlockBodies(GetByHashes { request_id, .. }) => {
+                EthMessage::BlockBodies(BlockBodies { request_id: *request_id, bodies: Vec::new() })
+            }
+            EthMessage::GetPooledTransactions(GetByHashes { request_id, .. }) => {
+                EthMessage::PooledTransactions(PooledTransactions { request_id: *request_id, transactions: Vec::new() })
+            }
+            EthMessage::GetReceipts(GetByHashes { request_id, .. }) => {
+                EthMessage::Receipts(Receipts { request_id: *request_id, receipts: Vec::new() })
+            }
+            _ => return Ok(()),
+        };
+        self.send(response)
+    }
+
+    /// Send `request` with a new request id and wait for the response with
+    /// the same id, which `response` picks out.
+    fn request<T>(
+        &mut self,
+        request: impl FnOnce(u64) -> Message,
+        mut response: impl FnMut(Message, u64) -> Result<T, Message>,
+    ) -> Result<T, P2pError> {
+        let request_id = self.next_request_id;
+        self.next_request_id += 1;
+        self.send(request(request_id))?;
+        loop {
+            match response(self.recv()?, request_id) {
+                Ok(result) => return Ok(result),
+                Err(other) => self.serve_empty(&other)?,
+            }
+        }
+    }
+
+    /// Request headers: `limit` of them from `start`, every `skip + 1`th,
+    /// in decreasing order of number if `reverse`.
+    pub fn get_block_headers(
+        &mut self,
+        start: BlockId,
+        limit: u64,
+        skip: u64,
+        reverse: bool,
+    ) -> Result<Vec<Header>, P2pError> {
+        let request = HeadersRequest { start, limit, skip, reverse };
+        self.request(
+            |request_id| EthMessage::GetBlockHeaders(GetBlockHeaders { request_id, request }).into(),
+            |message, id| match message {
+                Message::Eth(EthMessage::BlockHeaders(response)) if response.request_id == id => Ok(response.headers),
+                other => Err(other),
+            },
+        )
+    }
+
+    /// Request the bodies of the blocks with `hashes`. The peer may return
+    /// fewer than requested, but in order.
+    pub fn get_block_bodies(&mut self, hashes: &[Hash32]) -> Result<Vec<BlockBody>, P2pError> {
+        let hashes = hashes.to_vec();
+        self.request(
+            |request_id| EthMessage::GetBlockBodies(GetByHashes { request_id, hashes }).into(),
+            |message, id| match message {
+                Message::Eth(EthMessage::BlockBodies(response)) if response.request_id == id => Ok(response.bodies),
+                other => Err(other),
+            },
+        )
+    }
+}
+
+/// The reason of a `Disconnect`, which some clients send outside a list.
+fn disconnect_reason(payload: &[u8]) -> u64 {
+    let mut stream = RlpStream::new(payload);
+    let reason = match stream.next_item() {
+        Ok(Some(RlpItem::List(mut list))) => list.next_u64(),
+        Ok(Some(RlpItem::Bytes(_))) => RlpStream::new(payload).next_u64(),
+        _ => return DISCONNECT_REQUESTED,
+    };
+    reason.unwrap_or(DISCONNECT_REQUESTED)
+}
+
+/// The canonical headers which `request` asks for.
+fn headers_from(chain: &BlockChain, request: &HeadersRequest) -> Result<Vec<Header>, Exception> {
+    let start = match &request.start {
+        BlockId::Hash(hash) => match chain.canonical_index(hash) {
+            Some(index) => index as u64,
+            None => return Ok(Vec::new()),
+        },
+        BlockId::Number(number) => *number,
+    };
+    let first = chain.blocks[0].header.number as u64;
+    let step = request.skip.saturating_add(1);
+    let mut headers = Vec::new();
+    let mut number = Some(start);
+    while let Some(n) = number {
+        let Some(block) = n.checked_sub(first).and_then(|index| chain.blocks.get(index as usize)) else {
+            break;
+        };
+        if headers.len() as u64 >= request.limit || headers.len() >= MAX_RESPONSE_ITEMS {
+            break;
+        }
+        headers.push(block.header.clone());
+        number = if request.reverse { n.checked_sub(step) } else { n.checked_add(step) };
+    }
+    Ok(headers)
+}
+
+/// Import the blocks which `peer` has past the head of `chain`, returning
+/// how many were imported.
+///
+/// Headers are fetched in batches from the block after the head, so the
+/// peer must be on the same canonical chain up to it.
+pub fn sync<S: Read + Write>(chain: &mut BlockChain, peer: &mut Peer<S>) -> Result<usize, P2pError> {
+    let mut imported = 0;
+    loop {
+        let head = chain.blocks.last().unwrap().header.number as u64;
+        let headers = peer.get_block_headers(BlockId::Number(head + 1), HEADERS_BATCH, 0, false)?;
+        if headers.is_empty() {
+            return Ok(imported);
+        }
+        let hashes = headers.iter().map(compute_header_hash).collect::<Result<Vec<_>, _>>().map_err(P2pError::Block)?;
+        let mut bodies = Vec::new();
+        while bodies.len() < hashes.len() {
+            let batch = peer.get_block_bodies(&hashes[bodies.len()..])?;
+            if batch.is_empty() {
+                return Err(P2pError::Protocol("peer returned no block bodies"));
+            }
+            bodies.extend(batch);
+        }
+        for (header, body) in headers.into_iter().zip(bodies) {
+            chain.import_block(body.into_block(header)).map_err(P2pError::Block)?;
+            imported += 1;
+        }
+        // Headers which do not extend the head end up in `side_blocks`.
+        if chain.blocks.last().unwrap().header.number as u64 == head {
+            return Err(P2pError::Protocol("peer headers do not extend the head"));
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{
+        net::TcpListener,
+        thread::{self, JoinHandle},
+    };
+
+    use crate::{
+        builder::build_payload,
+        engine::PayloadAttributes,
+        ethereum::{crypto::eliptic_curve::secp256k1_public_key, genesis::Genesis},
+        prelude::*,
+    };
+
+    use super::{eth::Status, rlpx::Rlpx, sync, Enode, Hello, P2pError, Peer, random_secret_key};
+
+    pub(super) fn chain() -> BlockChain {
+        BlockChain::from_genesis(Genesis::post_merge(&["shanghai", "cancun"])).unwrap()
+    }
+
+    /// Serve `chain` to the first peer which connects, until it disconnects.
+    pub(super) fn serve(chain: BlockChain) -> (Enode, JoinHandle<()>) {
+        let server_key = random_secret_key();
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let enode = Enode { id: secp256k1_public_key(server_key), address: listener.local_addr().unwrap() };
+        let server = thread::spawn(move || {
+            let (stream, _) = listener.accept().unwrap();
+            let rlpx = Rlpx::accept(stream, server_key).unwrap();
+            let status = Status::new(&chain).unwrap();
+            let mut peer = Peer::handshake(rlpx, server_key, &status).unwrap();
+            loop {
+                match peer.recv() {
+                    Ok(message) => peer.serve(&chain, &message).unwrap(),
+                    Err(P2pError::Disconnected(_)) => return,
+                    Err(error) => panic!("{error:?}"),
+                }
+            }
+        });
+        (enode, server)
+    }
+
+    #[test]
+    fn enode_and_hello() {
+        let id = "a979fb575495b8d6db44f750317d0f4622bf4c2aa3365d6af7c284339968eef29b69ad0dce72a4d8db5ebb4968de0e3bec910127f134779fbcb0cb6d3331163c";
+        let enode: Enode = format!("enode://{id}@127.0.0.1:30303?discport=0").parse().unwrap();
+        assert_eq!(enode.to_string(), format!("enode://{id}@127.0.0.1:30303"));
+        assert!("enode://abcd@127.0.0.1:30303".parse::<Enode>().is_err());
+
+        let hello = Hello::new(U256::from(7_u32));
+        assert_eq!(Hello::decode(&hello.encode().unwrap()).unwrap(), hello);
+    }
+
+    #[test]
+    fn sync_from_peer() {
+        let mut server_chain = chain();
+        for timestamp in [12_u32, 24] {
+            let attributes = PayloadAttributes { timestamp: U256::from(timestamp), ..Default::default() };
+            let payload = build_payload(&server_chain, &[], &attributes).unwrap();
+            server_chain.import_block(payload.block).unwrap();
+        }
+        let server_head = server_chain.head_hash().clone();
+        let (enode, server) = serve(server_chain);
+
+        let mut client_chain = chain();
+        let status = Status::new(&client_chain).unwrap();
+        let mut peer = Peer::connect(&enode, random_secret_key(), &status).unwrap();
+        assert_eq!(peer.status.best_hash, server_head);
+        assert!(peer.hello.client_id.starts_with("ejit-evm/"));
+        assert_eq!(sync(&mut client_chain, &mut peer).unwrap(), 2);
+        assert_eq!(*client_chain.head_hash(), server_head);
+        peer.disconnect(0).unwrap();
+        server.join().unwrap();
+    }
+}
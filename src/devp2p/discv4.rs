@@ -0,0 +1,714 @@
+//! Node discovery, version 4.
+//!
+//! Nodes find each other over UDP with signed packets: a `Ping` which a
+//! `Pong` answers proves that a node is reachable at its address, and
+//! `FindNode` asks a node for the nodes it knows closest to a target, by
+//! the XOR distance between the hashes of their public keys. `Discovery`
+//! keeps the nodes which answered in a Kademlia table and `lookup` walks
+//! towards a target through them, starting from the bootnodes. The
+//! `EnrRequest` of EIP-868 fetches a node's record.
+//!
+//! A node only answers `FindNode` and `EnrRequest` from nodes it has
+//! exchanged pings with, so that it cannot be used to flood a spoofed
+//! address. Discovery v5 is not implemented.
+
+use std::{
+    collections::{BTreeMap, BTreeSet},
+    io,
+    net::{IpAddr, Ipv4Addr, Ipv6Addr, SocketAddr, UdpSocket},
+    time::{Duration, Instant, SystemTime, UNIX_EPOCH},
+};
+
+use crate::{
+    ethereum::{
+        crypto::{
+            eliptic_curve::{secp256k1_public_key, secp256k1_recover, secp256k1_sign},
+            hash::{keccak256, Hash32},
+        },
+        ethereum_rlp::{exceptions::RLPException, rlp, stream::RlpStream},
+        ethereum_types::{
+            bytes::Bytes,
+            numeric::{U256, U64},
+        },
+    },
+    impl_extended,
+};
+
+use super::{enr::Enr, Enode, P2pError};
+
+/// The version of the protocol implemented.
+pub const DISCOVERY_VERSION: u64 = 4;
+
+/// The largest packet allowed.
+pub const MAX_PACKET_SIZE: usize = 1280;
+
+/// Seconds for which a packet is valid after it is sent.
+const EXPIRATION: u64 = 20;
+
+/// How long to wait for a response.
+const TIMEOUT: Duration = Duration::from_millis(500);
+
+/// Nodes in each bucket of the table, and the number returned by a lookup.
+pub const BUCKET_SIZE: usize = 16;
+
+/// Nodes queried in each round of a lookup.
+const ALPHA: usize = 3;
+
+/// Nodes in a `Neighbors` packet, which keeps it within `MAX_PACKET_SIZE`.
+const MAX_NEIGHBORS: usize = 12;
+
+/// Where a node receives discovery packets and RLPx connections.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Endpoint {
+    /// The IPv4 or IPv6 address.
+    pub ip: Bytes,
+    pub udp: U64,
+    pub tcp: U64,
+}
+
+impl_extended!(Endpoint: ip, udp, tcp);
+
+impl Endpoint {
+    pub fn new(ip: IpAddr, udp: u16, tcp: u16) -> Self {
+        Self { ip: ip_bytes(ip), udp: udp as u64, tcp: tcp as u64 }
+    }
+}
+
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Ping {
+    pub version: U64,
+    pub from: Endpoint,
+    pub to: Endpoint,
+    pub expiration: U64,
+    /// The sequence number of the sender's record.
+    pub enr_seq: Option<U64>,
+}
+
+impl_extended!(Ping: version, from, to, expiration, enr_seq);
+
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Pong {
+    /// The address the ping came from, as the recipient saw it.
+    pub to: Endpoint,
+    /// The hash of the packet of the ping.
+    pub ping_hash: Hash32,
+    pub expiration: U64,
+    pub enr_seq: Option<U64>,
+}
+
+impl_extended!(Pong: to, ping_hash, expiration, enr_seq);
+
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct FindNode {
+    /// A public key, whose hash the nodes returned are closest to.
+    pub target: Bytes,
+    pub expiration: U64,
+}
+
+impl_extended!(FindNode: target, expiration);
+
+/// A node in a `Neighbors` packet.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct NodeRecord {
+    pub ip: Bytes,
+    pub udp: U64,
+    pub tcp: U64,
+    pub id: Bytes,
+}
+
+impl_extended!(NodeRecord: ip, udp, tcp, id);
+
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Neighbors {
+    pub nodes: Vec<NodeRecord>,
+    pub expiration: U64,
+}
+
+impl_extended!(Neighbors: nodes, expiration);
+
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct EnrRequest {
+    pub expiration: U64,
+}
+
+impl_extended!(EnrRequest: expiration);
+
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct EnrResponse {
+    /// The hash of the packet of the request.
+    pub request_hash: Hash32,
+    pub enr: Enr,
+}
+
+impl_extended!(EnrResponse: request_hash, enr);
+
+protocol_messages! {
+    /// A discovery packet, by its type.
+    Packet, "unknown discovery packet",
+    0x01 => Ping(Ping),
+    0x02 => Pong(Pong),
+    0x03 => FindNode(FindNode),
+    0x04 => Neighbors(Neighbors),
+    0x05 => EnrRequest(EnrRequest),
+    0x06 => EnrResponse(EnrResponse),
+}
+
+impl Packet {
+    /// The time after which the packet is to be ignored.
+    fn expiration(&self) -> Option<u64> {
+        match self {
+            Packet::Ping(ping) => Some(ping.expiration),
+            Packet::Pong(pong) => Some(pong.expiration),
+            Packet::FindNode(find_node) => Some(find_node.expiration),
+            Packet::Neighbors(neighbors) => Some(neighbors.expiration),
+            Packet::EnrRequest(request) => Some(request.expiration),
+            Packet::EnrResponse(_) => None,
+        }
+    }
+}
+
+/// Sign `packet` with `secret_key`, returning its encoding and hash.
+///
+/// A packet is `hash || signature || type || data`, the signature being
+/// over the hash of `type || data` and the hash over everything after it.
+pub fn encode_packet(secret_key: U256, packet: &Packet) -> Result<(Vec<u8>, Hash32), RLPException> {
+    let mut data = vec![packet.id() as u8];
+    data.extend_from_slice(&packet.encode()?);
+    let (r, s, v) = secp256k1_sign(keccak256(&data), secret_key)
+        .map_err(|_| RLPException::EncodingError("secret key out of range"))?;
+    let mut signed = r.to_be_bytes().to_vec();
+    signed.extend_from_slice(&s.to_be_bytes());
+    signed.push(v.bit(0) as u8);
+    signed.extend_from_slice(&data);
+    let hash = keccak256(&signed);
+    let mut out = hash.0.to_vec();
+    out.extend_from_slice(&signed);
+    Ok((out, hash))
+}
+
+/// Check the hash and signature of a packet, returning it with the public
+/// key of its sender and its hash.
+///
+/// Items after those of the known fields, which later versions may add,
+/// are ignored.
+pub fn decode_packet(data: &[u8]) -> Result<(Packet, Bytes, Hash32), P2pError> {
+    if data.len() < 32 + 65 + 1 || data.len() > MAX_PACKET_SIZE {
+        return Err(P2pError::Protocol("bad discovery packet size"));
+    }
+    let hash = Hash32(data[..32].try_into().unwrap());
+    if keccak256(&data[32..]) != hash {
+        return Err(P2pError::Crypto("bad discovery packet hash"));
+    }
+    let r = U256::from_be_bytes(data[32..64].try_into().unwrap());
+    let s = U256::from_be_bytes(data[64..96].try_into().unwrap());
+    let v = U256::from(data[96] as u32);
+    let public_key = secp256k1_recover(r, s, v, keccak256(&data[97..]))
+        .map_err(|_| P2pError::Crypto("bad discovery packet signature"))?;
+    let id = data[97] as u64;
+    let fields = match id {
+        0x01 => 5,
+        0x02 => 4,
+        0x05 => 1,
+        _ => 2,
+    };
+    let packet = Packet::decode(id, &known_fields(&data[98..], fields)?)?;
+    Ok((packet, public_key, hash))
+}
+
+/// The list in `payload` cut to its first `fields` items.
+fn known_fields(payload: &[u8], fields: usize) -> Result<Bytes, RLPException> {
+    let mut items = RlpStream::new(payload).next_list()?;
+    let mut kept = Bytes::default();
+    for _ in 0..fields {
+        if items.is_empty() {
+            break;
+        }
+        kept.extend(items.next_raw()?.iter().copied());
+    }
+    let mut out = Bytes::default();
+    rlp::encode_joined_encodings(&mut out, kept);
+    Ok(out)
+}
+
+fn ip_bytes(ip: IpAddr) -> Bytes {
+    match ip {
+        IpAddr::V4(ip) => Bytes(ip.octets().to_vec()),
+        IpAddr::V6(ip) => Bytes(ip.octets().to_vec()),
+    }
+}
+
+fn parse_ip(ip: &[u8]) -> Option<IpAddr> {
+    match ip.len() {
+        4 => Some(IpAddr::V4(Ipv4Addr::from(<[u8; 4]>::try_from(ip).unwrap()))),
+        16 => Some(IpAddr::V6(Ipv6Addr::from(<[u8; 16]>::try_from(ip).unwrap()))),
+        _ => None,
+    }
+}
+
+fn expiration() -> u64 {
+    now() + EXPIRATION
+}
+
+fn now() -> u64 {
+    SystemTime::now().duration_since(UNIX_EPOCH).unwrap().as_secs()
+}
+
+/// A node found by discovery.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Node {
+    /// The public key, without the `0x04` prefix.
+    pub id: Bytes,
+    pub ip: IpAddr,
+    pub udp: u16,
+    pub tcp: u16,
+}
+
+impl Node {
+    /// The address to dial the node at with `Peer::connect`.
+    pub fn enode(&self) -> Enode {
+        Enode { id: self.id.clone(), address: SocketAddr::new(self.ip, self.tcp) }
+    }
+
+    fn udp_address(&self) -> SocketAddr {
+        SocketAddr::new(self.ip, self.udp)
+    }
+
+    fn endpoint(&self) -> Endpoint {
+        Endpoint::new(self.ip, self.udp, self.tcp)
+    }
+}
+
+/// A node given by enode, which takes discovery packets on its RLPx port.
+impl From<&Enode> for Node {
+    fn from(enode: &Enode) -> Self {
+        Node { id: enode.id.clone(), ip: enode.address.ip(), udp: enode.address.port(), tcp: enode.address.port() }
+    }
+}
+
+impl TryFrom<&Enr> for Node {
+    type Error = P2pError;
+
+    fn try_from(enr: &Enr) -> Result<Self, P2pError> {
+        const INCOMPLETE: P2pError = P2pError::Protocol("node record has no address");
+        let id = enr.public_key().map_err(|_| P2pError::Crypto("node record has an invalid key"))?;
+        let udp = enr.udp().ok_or(INCOMPLETE)?;
+        Ok(Node { id, ip: enr.ip().ok_or(INCOMPLETE)?, udp, tcp: enr.tcp().unwrap_or(udp) })
+    }
+}
+
+impl TryFrom<&NodeRecord> for Node {
+    type Error = P2pError;
+
+    fn try_from(record: &NodeRecord) -> Result<Self, P2pError> {
+        const INVALID: P2pError = P2pError::Protocol("invalid node in Neighbors");
+        if record.id.len() != 64 {
+            return Err(INVALID);
+        }
+        Ok(Node {
+            id: record.id.clone(),
+            ip: parse_ip(&record.ip).ok_or(INVALID)?,
+            udp: u16::try_from(record.udp).map_err(|_| INVALID)?,
+            tcp: u16::try_from(record.tcp).map_err(|_| INVALID)?,
+        })
+    }
+}
+
+/// The XOR of the hashes of two public keys.
+fn distance(a: &Hash32, b: &Hash32) -> [u8; 32] {
+    std::array::from_fn(|i| a.0[i] ^ b.0[i])
+}
+
+/// Nodes by the logarithm of their distance from the local node, the most
+/// recently seen last in each bucket.
+struct Table {
+    local: Hash32,
+    buckets: Vec<Vec<Node>>,
+}
+
+impl Table {
+    fn new(local: Hash32) -> Self {
+        Self { local, buckets: vec![Vec::new(); 256] }
+    }
+
+    fn bucket(&self, id: &Bytes) -> Option<usize> {
+        let distance = distance(&self.local, &keccak256(id));
+        let zeros = distance.iter().position(|b| *b != 0)?;
+        Some(255 - (zeros * 8 + distance[zeros].leading_zeros() as usize))
+    }
+
+    fn contains(&self, id: &Bytes) -> bool {
+        self.bucket(id).is_some_and(|bucket| self.buckets[bucket].iter().any(|node| node.id == *id))
+    }
+
+    /// Add `node`, or move it to the end of its bucket. Nodes already in a
+    /// full bucket are kept, as long-lived nodes are the likeliest to stay.
+    fn insert(&mut self, node: Node) {
+        let Some(bucket) = self.bucket(&node.id) else { return };
+        let bucket = &mut self.buckets[bucket];
+        bucket.retain(|other| other.id != node.id);
+        if bucket.len() < BUCKET_SIZE {
+            bucket.push(node);
+        }
+    }
+
+    fn closest(&self, target: &Hash32, n: usize) -> Vec<Node> {
+        let mut nodes: Vec<&Node> = self.buckets.iter().flatten().collect();
+        nodes.sort_by_key(|node| distance(target, &keccak256(&node.id)));
+        nodes.into_iter().take(n).cloned().collect()
+    }
+}
+
+/// A discovery endpoint and the nodes it has found.
+pub struct Discovery {
+    socket: UdpSocket,
+    secret_key: U256,
+    /// The record of the local node.
+    pub enr: Enr,
+    tcp_port: u16,
+    table: Table,
+    /// Pings sent from `handle`, by packet hash, whose pongs are not
+    /// waited for.
+    pending: BTreeMap<Hash32, Node>,
+}
+
+impl Discovery {
+    /// Listen on `address`, announcing `tcp_port` for RLPx. The record
+    /// carries the address bound, so it should be a public one for other
+    /// nodes to reach.
+    pub fn bind(address: SocketAddr, secret_key: U256, tcp_port: u16) -> Result<Self, P2pError> {
+        let socket = UdpSocket::bind(address)?;
+        let address = socket.local_addr()?;
+        let enr = Enr::new(secret_key, 1, address.ip(), address.port(), tcp_port);
+        let table = Table::new(keccak256(&secp256k1_public_key(secret_key)));
+        Ok(Self { socket, secret_key, enr, tcp_port, table, pending: BTreeMap::new() })
+    }
+
+    /// The local node.
+    pub fn local_node(&self) -> Result<Node, P2pError> {
+        let address = self.socket.local_addr()?;
+        Ok(Node { id: secp256k1_public_key(self.secret_key), ip: address.ip(), udp: address.port(), tcp: self.tcp_port })
+    }
+
+    /// The nodes in the table, all of which have answered a ping.
+    pub fn nodes(&self) -> Vec<Node> {
+        self.table.buckets.iter().flatten().cloned().collect()
+    }
+
+    /// Ping each of `bootnodes` and look up the local node, which fills the
+    /// table with the nodes around it. Returns the size of the table.
+    pub fn bootstrap(&mut self, bootnodes: &[Node]) -> usize {
+        for node in bootnodes {
+            let _ = self.ping(node);
+        }
+        let local = secp256k1_public_key(self.secret_key);
+        self.lookup(&local);
+        self.nodes().len()
+    }
+
+    /// Ping `node`, adding it to the table if it answers. Returns the
+    /// sequence number of its record.
+    pub fn ping(&mut self, node: &Node) -> Result<Option<u64>, P2pError> {
+        let hash = self.send_ping(node)?;
+        let enr_seq = self.await_response(node, None, |packet| match packet {
+            Packet::Pong(pong) if pong.ping_hash == hash => Some(pong.enr_seq),
+            _ => None,
+        })?;
+        self.table.insert(node.clone());
+        Ok(enr_seq)
+    }
+
+    /// Ask `node` for the nodes it knows closest to the public key `target`.
+    pub fn find_node(&mut self, node: &Node, target: &Bytes) -> Result<Vec<Node>, P2pError> {
+        let request = Packet::FindNode(FindNode { target: target.clone(), expiration: expiration() });
+        self.send(node, &request)?;
+        let mut found = Vec::new();
+        self.await_response(node, Some(&request), |packet| match packet {
+            Packet::Neighbors(neighbors) => {
+                let count = neighbors.nodes.len();
+                found.extend(neighbors.nodes.iter().filter_map(|record| Node::try_from(record).ok()));
+                // Responses come in packets of `MAX_NEIGHBORS` nodes.
+                (count < MAX_NEIGHBORS || found.len() >= BUCKET_SIZE).then_some(())
+            }
+            _ => None,
+        })?;
+        Ok(found)
+    }
+
+    /// Fetch the record of `node`.
+    pub fn request_enr(&mut self, node: &Node) -> Result<Enr, P2pError> {
+        let request = Packet::EnrRequest(EnrRequest { expiration: expiration() });
+        let hash = self.send(node, &request)?;
+        let enr = self.await_response(node, Some(&request), |packet| match packet {
+            Packet::EnrResponse(response) if response.request_hash == hash => Some(response.enr),
+            _ => None,
+        })?;
+        if enr.public_key().ok().as_ref() != Some(&node.id) {
+            return Err(P2pError::Protocol("node record of another node"));
+        }
+        Ok(enr)
+    }
+
+    /// Find the `BUCKET_SIZE` nodes closest to the public key `target`,
+    /// asking `ALPHA` nodes at a time for ones closer than those found so
+    /// far until the closest have all been asked.
+    pub fn lookup(&mut self, target: &Bytes) -> Vec<Node> {
+        let target_hash = keccak256(target);
+        let local = secp256k1_public_key(self.secret_key);
+        let mut found: BTreeMap<[u8; 32], Node> = BTreeMap::new();
+        for node in self.table.closest(&target_hash, BUCKET_SIZE) {
+            found.insert(distance(&target_hash, &keccak256(&node.id)), node);
+        }
+        let mut asked = BTreeSet::new();
+        loop {
+            let round: Vec<Node> = found
+                .values()
+                .take(BUCKET_SIZE)
+                .filter(|node| !asked.contains(&node.id))
+                .take(ALPHA)
+                .cloned()
+                .collect();
+            if round.is_empty() {
+                break;
+            }
+            for node in round {
+                asked.insert(node.id.clone());
+                if !self.table.contains(&node.id) && self.ping(&node).is_err() {
+                    found.remove(&distance(&target_hash, &keccak256(&node.id)));
+                    continue;
+                }
+                for neighbor in self.find_node(&node, target).unwrap_or_default() {
+                    if neighbor.id != local {
+                        found.entry(distance(&target_hash, &keccak256(&neighbor.id))).or_insert(neighbor);
+                    }
+                }
+            }
+        }
+        found.into_values().filter(|node| asked.contains(&node.id)).take(BUCKET_SIZE).collect()
+    }
+
+    /// Handle the packets which arrive within `timeout`, answering
+    /// requests. This keeps a node reachable while it is not looking up
+    /// others.
+    pub fn poll(&mut self, timeout: Duration) -> Result<(), P2pError> {
+        let deadline = Instant::now() + timeout;
+        while let Some((packet, id, hash, from)) = self.recv(deadline)? {
+            self.handle(packet, id, hash, from)?;
+        }
+        Ok(())
+    }
+
+    fn send(&self, node: &Node, packet: &Packet) -> Result<Hash32, P2pError> {
+        self.send_to(node.udp_address(), packet)
+    }
+
+    fn send_to(&self, address: SocketAddr, packet: &Packet) -> Result<Hash32, P2pError> {
+        let (data, hash) = encode_packet(self.secret_key, packet)?;
+        self.socket.send_to(&data, address)?;
+        Ok(hash)
+    }
+
+    fn send_ping(&self, node: &Node) -> Result<Hash32, P2pError> {
+        let local = self.local_node()?;
+        self.send(
+            node,
+            &Packet::Ping(Ping {
+                version: DISCOVERY_VERSION,
+                from: local.endpoint(),
+                to: node.endpoint(),
+                expiration: expiration(),
+                enr_seq: Some(self.enr.seq),
+            }),
+        )
+    }
+
+    /// The next valid packet to arrive before `deadline`, with its sender's
+    /// public key, its hash and where it came from.
+    fn recv(&mut self, deadline: Instant) -> Result<Option<(Packet, Bytes, Hash32, SocketAddr)>, P2pError> {
+        let mut buffer = [0; MAX_PACKET_SIZE];
+        loop {
+            let Some(timeout) = deadline.checked_duration_since(Instant::now()).filter(|t| !t.is_zero()) else {
+                return Ok(None);
+            };
+            self.socket.set_read_timeout(Some(timeout))?;
+            let (len, from) = match self.socket.recv_from(&mut buffer) {
+                Ok(received) => received,
+                Err(error) if matches!(error.kind(), io::ErrorKind::WouldBlock | io::ErrorKind::TimedOut) => {
+                    return Ok(None);
+                }
+                Err(error) => return Err(error.into()),
+            };
+            // Invalid and expired packets are dropped.
+            let Ok((packet, id, hash)) = decode_packet(&buffer[..len]) else { continue };
+            if packet.expiration().is_some_and(|expiration| expiration < now()) {
+                continue;
+            }
+            return Ok(Some((packet, id, hash, from)));
+        }
+    }
+
+    /// Wait for the packet from `node` which `response` picks out,
+    /// handling the others. `request` is sent again if the node pings, as
+    /// it will have dropped it if it did not know the local node yet.
+    fn await_response<T>(
+        &mut self,
+        node: &Node,
+        request: Option<&Packet>,
+        mut response: impl FnMut(Packet) -> Option<T>,
+    ) -> Result<T, P2pError> {
+        let deadline = Instant::now() + TIMEOUT;
+        while let Some((packet, id, hash, from)) = self.recv(deadline)? {
+            if id != node.id {
+                self.handle(packet, id, hash, from)?;
+                continue;
+            }
+            let is_ping = matches!(packet, Packet::Ping(_));
+            if is_ping {
+                self.handle(packet, id, hash, from)?;
+                if let Some(request) = request {
+                    self.send(node, request)?;
+                }
+            } else if let Some(result) = response(packet.clone()) {
+                return Ok(result);
+            } else {
+                self.handle(packet, id, hash, from)?;
+            }
+        }
+        Err(io::Error::from(io::ErrorKind::TimedOut).into())
+    }
+
+    /// Answer a packet which is not a response being waited for.
+    fn handle(&mut self, packet: Packet, id: Bytes, hash: Hash32, from: SocketAddr) -> Result<(), P2pError> {
+        match packet {
+            Packet::Ping(ping) => {
+                let pong = Pong {
+                    to: Endpoint::new(from.ip(), from.port(), ping.from.tcp as u16),
+                    ping_hash: hash,
+                    expiration: expiration(),
+                    enr_seq: Some(self.enr.seq),
+                };
+                self.send_to(from, &Packet::Pong(pong))?;
+                let node = Node { id, ip: from.ip(), udp: from.port(), tcp: ping.from.tcp as u16 };
+                if self.table.contains(&node.id) {
+                    self.table.insert(node);
+                } else {
+                    // Ping back, so that the node joins the table and may
+                    // make requests once it answers.
+                    let hash = self.send_ping(&node)?;
+                    self.pending.insert(hash, node);
+                }
+            }
+            Packet::Pong(pong) => {
+                if let Some(node) = self.pending.remove(&pong.ping_hash).filter(|node| node.id == id) {
+                    self.table.insert(node);
+                }
+            }
+            Packet::FindNode(find_node) if self.table.contains(&id) => {
+                let target = keccak256(&find_node.target);
+                let nodes: Vec<NodeRecord> = self
+                    .table
+                    .closest(&target, BUCKET_SIZE + 1)
+                    .into_iter()
+                    .filter(|node| node.id != id)
+                    .take(BUCKET_SIZE)
+                    .map(|node| NodeRecord { ip: ip_bytes(node.ip), udp: node.udp as u64, tcp: node.tcp as u64, id: node.id })
+                    .collect();
+                let mut chunks: Vec<&[NodeRecord]> = nodes.chunks(MAX_NEIGHBORS).collect();
+                if chunks.is_empty() {
+                    chunks.push(&[]);
+                }
+                for chunk in chunks {
+                    let neighbors = Neighbors { nodes: chunk.to_vec(), expiration: expiration() };
+                    self.send_to(from, &Packet::Neighbors(neighbors))?;
+                }
+            }
+            Packet::EnrRequest(_) if self.table.contains(&id) => {
+                let response = EnrResponse { request_hash: hash, enr: self.enr.clone() };
+                self.send_to(from, &Packet::EnrResponse(response))?;
+            }
+            _ => {}
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{
+        sync::{
+            atomic::{AtomicBool, Ordering},
+            Arc,
+        },
+        thread,
+        time::Duration,
+    };
+
+    use crate::{
+        devp2p::random_secret_key,
+        ethereum::{
+            crypto::eliptic_curve::{secp256k1_public_key, secp256k1_sign},
+            ethereum_rlp::rlp::Extended,
+        },
+        prelude::*,
+    };
+
+    use super::{decode_packet, encode_packet, Discovery, Endpoint, FindNode, Packet, Ping};
+
+    #[test]
+    fn packets() {
+        let secret_key = U256::from(0x5ec12e7_u32);
+        let packet = Packet::FindNode(FindNode { target: Bytes(vec![7; 64]), expiration: 1 });
+        let (data, hash) = encode_packet(secret_key, &packet).unwrap();
+        let (decoded, id, decoded_hash) = decode_packet(&data).unwrap();
+        let Packet::FindNode(find_node) = decoded else { panic!() };
+        assert_eq!((find_node.target, id, decoded_hash), (Bytes(vec![7; 64]), secp256k1_public_key(secret_key), hash));
+
+        let mut tampered = data.clone();
+        *tampered.last_mut().unwrap() ^= 1;
+        assert!(decode_packet(&tampered).is_err());
+
+        // A ping with an item from a later version of the protocol.
+        let mut payload = Bytes::default();
+        let fields: [&dyn Extended; 6] = [&4_u64, &Endpoint::default(), &Endpoint::default(), &1_u64, &2_u64, &3_u64];
+        rlp::encode_sequence(&mut payload, &fields).unwrap();
+        let data = [&[0x01], &payload[..]].concat();
+        let (r, s, v) = secp256k1_sign(keccak256(&data), secret_key).unwrap();
+        let signed = [&r.to_be_bytes()[..], &s.to_be_bytes(), &[v.bit(0) as u8], &data].concat();
+        let packet = [&keccak256(&signed).0[..], &signed].concat();
+        let (Packet::Ping(Ping { enr_seq: Some(2), .. }), _, _) = decode_packet(&packet).unwrap() else { panic!() };
+    }
+
+    #[test]
+    fn lookup() {
+        let stop = Arc::new(AtomicBool::new(false));
+        let serve = |mut discovery: Discovery| {
+            let stop = stop.clone();
+            thread::spawn(move || {
+                while !stop.load(Ordering::Relaxed) {
+                    discovery.poll(Duration::from_millis(50)).unwrap();
+                }
+            })
+        };
+        let bind = || Discovery::bind("127.0.0.1:0".parse().unwrap(), random_secret_key(), 30303).unwrap();
+
+        // `b` knows `c`, and `a` only knows `b`.
+        let c = bind();
+        let c_node = c.local_node().unwrap();
+        let c_enr = c.enr.clone();
+        let c_thread = serve(c);
+        let mut b = bind();
+        let b_node = b.local_node().unwrap();
+        b.ping(&c_node).unwrap();
+        let b_thread = serve(b);
+
+        let mut a = bind();
+        assert_eq!(a.bootstrap(&[b_node.clone()]), 2);
+        let nodes = a.nodes();
+        assert!(nodes.contains(&b_node) && nodes.contains(&c_node));
+        assert_eq!(a.request_enr(&c_node).unwrap(), c_enr);
+        assert_eq!(c_node.enode().address.port(), 30303);
+
+        stop.store(true, Ordering::Relaxed);
+        b_thread.join().unwrap();
+        c_thread.join().unwrap();
+    }
+}
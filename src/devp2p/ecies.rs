@@ -0,0 +1,94 @@
+//! The ECIES scheme of RLPx, which encrypts the handshake messages to the
+//! static public key of their recipient.
+//!
+//! A message is encrypted with AES-128 in counter mode under a key derived
+//! from a fresh ephemeral key and the recipient's key, and authenticated
+//! with HMAC-SHA256 over the IV, the ciphertext and `shared_mac_data`,
+//! which for EIP-8 handshake messages is their size prefix. The output is
+//! the ephemeral public key, the IV, the ciphertext and the MAC.
+
+use crate::ethereum::{
+    crypto::{
+        aes::AesCtr,
+        eliptic_curve::{secp256k1_ecdh, secp256k1_public_key},
+        hash::{hmac_sha256, sha256},
+    },
+    ethereum_types::numeric::U256,
+};
+
+use super::P2pError;
+
+/// Bytes which encryption adds to a message: the uncompressed ephemeral
+/// public key, the IV and the MAC.
+pub const OVERHEAD: usize = 65 + 16 + 32;
+
+/// The encryption key and MAC key of a shared secret, from the NIST SP
+/// 800-56 concatenation KDF with sha256.
+fn derive_keys(shared_secret: &[u8; 32]) -> ([u8; 16], [u8; 32]) {
+    let key_material = sha256(&[&1_u32.to_be_bytes()[..], shared_secret].concat());
+    let encryption_key = key_material[..16].try_into().unwrap();
+    let mac_key = sha256(&key_material[16..]).0;
+    (encryption_key, mac_key)
+}
+
+/// Encrypt `message` to `remote_public_key` with the ephemeral secret key
+/// `ephemeral_secret` and initialization vector `iv`, which must both be
+/// random.
+pub fn encrypt(
+    remote_public_key: &[u8],
+    message: &[u8],
+    shared_mac_data: &[u8],
+    ephemeral_secret: U256,
+    iv: [u8; 16],
+) -> Result<Vec<u8>, P2pError> {
+    let shared_secret =
+        secp256k1_ecdh(ephemeral_secret, remote_public_key).map_err(|_| P2pError::Crypto("invalid public key"))?;
+    let (encryption_key, mac_key) = derive_keys(&shared_secret);
+
+    let mut out = Vec::with_capacity(message.len() + OVERHEAD);
+    out.push(0x04);
+    out.extend_from_slice(&secp256k1_public_key(ephemeral_secret));
+    out.extend_from_slice(&iv);
+    let ciphertext_start = out.len();
+    out.extend_from_slice(message);
+    AesCtr::new(&encryption_key, iv).apply(&mut out[ciphertext_start..]);
+    let mac = hmac_sha256(&mac_key, &[&out[65..], shared_mac_data].concat());
+    out.extend_from_slice(&mac.0);
+    Ok(out)
+}
+
+/// Decrypt `data` sent to the owner of `secret_key`.
+pub fn decrypt(secret_key: U256, data: &[u8], shared_mac_data: &[u8]) -> Result<Vec<u8>, P2pError> {
+    if data.len() < OVERHEAD || data[0] != 0x04 {
+        return Err(P2pError::Crypto("malformed ECIES message"));
+    }
+    let (body, mac) = data.split_at(data.len() - 32);
+    let shared_secret =
+        secp256k1_ecdh(secret_key, &body[1..65]).map_err(|_| P2pError::Crypto("invalid ephemeral key"))?;
+    let (encryption_key, mac_key) = derive_keys(&shared_secret);
+    if hmac_sha256(&mac_key, &[&body[65..], shared_mac_data].concat()).0 != mac {
+        return Err(P2pError::Crypto("ECIES MAC mismatch"));
+    }
+    let iv = body[65..81].try_into().unwrap();
+    let mut message = body[81..].to_vec();
+    AesCtr::new(&encryption_key, iv).apply(&mut message);
+    Ok(message)
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::ethereum::{crypto::eliptic_curve::secp256k1_public_key, ethereum_types::numeric::U256};
+
+    use super::{decrypt, encrypt, OVERHEAD};
+
+    #[test]
+    fn round_trip() {
+        let secret_key = U256::from(0x5ec12e7_u32);
+        let public_key = secp256k1_public_key(secret_key);
+        let sealed = encrypt(&public_key, b"hello", b"\x00\x76", U256::from(0xe9_u32), [7; 16]).unwrap();
+        assert_eq!(sealed.len(), 5 + OVERHEAD);
+        assert_eq!(decrypt(secret_key, &sealed, b"\x00\x76").unwrap(), b"hello");
+        assert!(decrypt(secret_key, &sealed, b"\x00\x77").is_err());
+        assert!(decrypt(U256::from(2_u32), &sealed, b"\x00\x76").is_err());
+    }
+}
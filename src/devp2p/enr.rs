@@ -0,0 +1,304 @@
+//! Ethereum Node Records (EIP-778).
+//!
+//! A record is a signed, versioned list of key/value pairs describing a
+//! node: its public key and the addresses it can be reached at. Only the
+//! `v4` identity scheme, with secp256k1 signatures, is implemented. Records
+//! are written out as `enr:` followed by their RLP encoding in URL-safe
+//! base64.
+
+use std::{
+    collections::BTreeMap,
+    fmt,
+    net::{IpAddr, Ipv4Addr, Ipv6Addr},
+    str::FromStr,
+};
+
+use crate::ethereum::{
+    crypto::{
+        eliptic_curve::{secp256k1_compress, secp256k1_decompress, secp256k1_public_key, secp256k1_recover, secp256k1_sign},
+        hash::{keccak256, Hash32},
+    },
+    ethereum_rlp::{
+        exceptions::RLPException,
+        rlp::{self, Extended},
+        stream::RlpStream,
+    },
+    ethereum_types::{bytes::Bytes, numeric::U256},
+    exceptions::Exception,
+};
+
+/// The largest encoded record allowed.
+pub const MAX_ENR_SIZE: usize = 300;
+
+/// A signed node record.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Enr {
+    /// Incremented whenever the record changes.
+    pub seq: u64,
+    /// Keys and the RLP encodings of their values, in key order.
+    pairs: BTreeMap<Vec<u8>, Vec<u8>>,
+    /// `r` and `s` of the signature over the other items.
+    signature: Bytes,
+}
+
+impl Enr {
+    /// The record of the node with `secret_key`, reachable at `ip` on
+    /// `udp` for discovery and `tcp` for RLPx.
+    ///
+    /// Panics if `secret_key` is not a valid secp256k1 secret key.
+    pub fn new(secret_key: U256, seq: u64, ip: IpAddr, udp: u16, tcp: u16) -> Self {
+        let mut enr = Self { seq, ..Default::default() };
+        let public_key = secp256k1_compress(&secp256k1_public_key(secret_key)).unwrap();
+        enr.set("id", &Bytes(b"v4".to_vec())).unwrap();
+        enr.set("secp256k1", &Bytes(public_key.to_vec())).unwrap();
+        let (ip_key, udp_key, tcp_key, ip) = match ip {
+            IpAddr::V4(ip) => ("ip", "udp", "tcp", ip.octets().to_vec()),
+            IpAddr::V6(ip) => ("ip6", "udp6", "tcp6", ip.octets().to_vec()),
+        };
+        enr.set(ip_key, &Bytes(ip)).unwrap();
+        enr.set(udp_key, &(udp as u64)).unwrap();
+        enr.set(tcp_key, &(tcp as u64)).unwrap();
+        enr.sign(secret_key).expect("secret key out of range");
+        enr
+    }
+
+    /// Set `key` to `value`, bump the sequence number and sign the record
+    /// again with `secret_key`.
+    pub fn insert(&mut self, key: &str, value: &dyn Extended, secret_key: U256) -> Result<(), RLPException> {
+        self.set(key, value)?;
+        self.seq += 1;
+        self.sign(secret_key)
+    }
+
+    fn set(&mut self, key: &str, value: &dyn Extended) -> Result<(), RLPException> {
+        let mut encoded = Bytes::default();
+        value.encode(&mut encoded)?;
+        self.pairs.insert(key.as_bytes().to_vec(), encoded.0);
+        Ok(())
+    }
+
+    /// The RLP encoding of the value of `key`.
+    pub fn get(&self, key: &str) -> Option<&[u8]> {
+        self.pairs.get(key.as_bytes()).map(|value| &value[..])
+    }
+
+    fn get_bytes(&self, key: &str) -> Option<&[u8]> {
+        RlpStream::new(self.get(key)?).next_bytes().ok()
+    }
+
+    fn get_u64(&self, key: &str) -> Option<u64> {
+        RlpStream::new(self.get(key)?).next_u64().ok()
+    }
+
+    /// The uncompressed public key, without the `0x04` prefix.
+    pub fn public_key(&self) -> Result<Bytes, Exception> {
+        let compressed = self.get_bytes("secp256k1").ok_or(Exception::InvalidSignatureError("record has no public key"))?;
+        secp256k1_decompress(compressed)
+    }
+
+    /// The node id: the hash of the public key.
+    pub fn node_id(&self) -> Result<Hash32, Exception> {
+        Ok(keccak256(&self.public_key()?))
+    }
+
+    /// The IPv4 address, or else the IPv6 one.
+    pub fn ip(&self) -> Option<IpAddr> {
+        if let Some(ip) = self.get_bytes("ip").and_then(|ip| <[u8; 4]>::try_from(ip).ok()) {
+            return Some(IpAddr::V4(Ipv4Addr::from(ip)));
+        }
+        let ip = <[u8; 16]>::try_from(self.get_bytes("ip6")?).ok()?;
+        Some(IpAddr::V6(Ipv6Addr::from(ip)))
+    }
+
+    /// The discovery port for `ip()`.
+    pub fn udp(&self) -> Option<u16> {
+        let key = if self.get("ip").is_some() { "udp" } else { "udp6" };
+        self.get_u64(key).and_then(|port| u16::try_from(port).ok())
+    }
+
+    /// The RLPx port for `ip()`.
+    pub fn tcp(&self) -> Option<u16> {
+        let key = if self.get("ip").is_some() { "tcp" } else { "tcp6" };
+        self.get_u64(key).and_then(|port| u16::try_from(port).ok())
+    }
+
+    /// The encoding of `[seq, k, v, ...]`, which the signature covers.
+    fn content(&self) -> Bytes {
+        let mut items = Bytes::default();
+        self.items(&mut items);
+        let mut content = Bytes::default();
+        rlp::encode_joined_encodings(&mut content, items);
+        content
+    }
+
+    fn items(&self, out: &mut Bytes) {
+        self.seq.encode(out).unwrap();
+        for (key, value) in &self.pairs {
+            rlp::encode_bytes(out, key);
+            out.extend(value.iter().copied());
+        }
+    }
+
+    fn sign(&mut self, secret_key: U256) -> Result<(), RLPException> {
+        let (r, s, _) = secp256k1_sign(keccak256(&self.content()), secret_key)
+            .map_err(|_| RLPException::EncodingError("secret key out of range"))?;
+        let mut signature = r.to_be_bytes().to_vec();
+        signature.extend_from_slice(&s.to_be_bytes());
+        self.signature = Bytes(signature);
+        Ok(())
+    }
+
+    /// Check the signature under the `v4` scheme.
+    pub fn verify(&self) -> Result<(), Exception> {
+        if self.get_bytes("id") != Some(b"v4") {
+            return Err(Exception::InvalidSignatureError("unknown identity scheme"));
+        }
+        let public_key = self.public_key()?;
+        let Ok(signature) = <[u8; 64]>::try_from(&self.signature[..]) else {
+            return Err(Exception::InvalidSignatureError("signature is not 64 bytes"));
+        };
+        let r = U256::from_be_bytes(signature[..32].try_into().unwrap());
+        let s = U256::from_be_bytes(signature[32..].try_into().unwrap());
+        let hash = keccak256(&self.content());
+        // The recovery id is not part of the signature, so try both.
+        for v in [U256::ZERO, U256::ONE] {
+            if secp256k1_recover(r, s, v, hash.clone()).is_ok_and(|key| key == public_key) {
+                return Ok(());
+            }
+        }
+        Err(Exception::InvalidSignatureError("record signature does not match its key"))
+    }
+}
+
+impl Extended for Enr {
+    fn encode<'a, 'b>(&self, buffer: &'a mut Bytes) -> Result<(), RLPException> {
+        let mut items = Bytes::default();
+        self.signature.encode(&mut items)?;
+        self.items(&mut items);
+        rlp::encode_joined_encodings(buffer, items);
+        Ok(())
+    }
+
+    /// Records which are too large, unsorted or wrongly signed are
+    /// rejected.
+    fn decode<'a, 'b>(&mut self, buffer: &'a mut &'b [u8]) -> Result<(), RLPException> {
+        let mut stream = RlpStream::new(buffer);
+        let raw = stream.next_raw()?;
+        *buffer = stream.remaining();
+        if raw.len() > MAX_ENR_SIZE {
+            return Err(RLPException::DecodingError("node record too large"));
+        }
+        let mut items = RlpStream::list(raw)?;
+        let mut enr = Enr { signature: Bytes(items.next_bytes()?.to_vec()), seq: items.next_u64()?, ..Default::default() };
+        while !items.is_empty() {
+            let key = items.next_bytes()?.to_vec();
+            if enr.pairs.last_key_value().is_some_and(|(last, _)| *last >= key) {
+                return Err(RLPException::DecodingError("node record keys not sorted"));
+            }
+            enr.pairs.insert(key, items.next_raw()?.to_vec());
+        }
+        enr.verify().map_err(|_| RLPException::DecodingError("invalid node record signature"))?;
+        *self = enr;
+        Ok(())
+    }
+}
+
+impl FromStr for Enr {
+    type Err = RLPException;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        const INVALID: RLPException = RLPException::DecodingError("invalid enr: text");
+        let encoded = s.strip_prefix("enr:").and_then(base64_decode).ok_or(INVALID)?;
+        rlp::decode_to(&encoded)
+    }
+}
+
+impl fmt::Display for Enr {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "enr:{}", base64_encode(&rlp::encode(self).map_err(|_| fmt::Error)?))
+    }
+}
+
+const BASE64_ALPHABET: &[u8; 64] = b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789-_";
+
+/// URL-safe base64 without padding.
+fn base64_encode(data: &[u8]) -> String {
+    let mut out = String::with_capacity(data.len().div_ceil(3) * 4);
+    for chunk in data.chunks(3) {
+        let mut group = [0; 3];
+        group[..chunk.len()].copy_from_slice(chunk);
+        let bits = u32::from_be_bytes([0, group[0], group[1], group[2]]);
+        for i in 0..chunk.len() + 1 {
+            out.push(BASE64_ALPHABET[(bits >> (18 - 6 * i) & 0x3f) as usize] as char);
+        }
+    }
+    out
+}
+
+fn base64_decode(text: &str) -> Option<Vec<u8>> {
+    let mut out = Vec::with_capacity(text.len() * 3 / 4);
+    let mut bits = 0_u32;
+    let mut count = 0;
+    for c in text.bytes() {
+        let value = BASE64_ALPHABET.iter().position(|a| *a == c)? as u32;
+        bits = bits << 6 | value;
+        count += 6;
+        if count >= 8 {
+            count -= 8;
+            out.push((bits >> count) as u8);
+        }
+    }
+    Some(out)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::net::{IpAddr, Ipv4Addr, Ipv6Addr};
+
+    use crate::{
+        ethereum::{
+            crypto::eliptic_curve::secp256k1_public_key, ethereum_types::bytes::Bytes4,
+            utils::hexadecimal::hex_to_bytes,
+        },
+        prelude::*,
+    };
+
+    use super::{base64_decode, base64_encode, Enr};
+
+    #[test]
+    fn eip778_example() {
+        let text = "enr:-IS4QHCYrYZbAKWCBRlAy5zzaDZXJBGkcnh4MHcBFZntXNFrdvJjX04jRzjzCBOonrkTfj499SZuOh8R33Ls8RRcy5wBgmlkgnY0gmlwhH8AAAGJc2VjcDI1NmsxoQPKY0yuDUmstAHYpMa2_oxVtw0RW_QAdpzBQA8yWM0xOIN1ZHCCdl8";
+        let enr: Enr = text.parse().unwrap();
+        assert_eq!(enr.to_string(), text);
+        assert_eq!(enr.seq, 1);
+        assert_eq!(enr.ip(), Some(IpAddr::V4(Ipv4Addr::LOCALHOST)));
+        assert_eq!(enr.udp(), Some(30303));
+        assert_eq!(enr.tcp(), None);
+        assert_eq!(
+            enr.node_id().unwrap().0.to_vec(),
+            hex_to_bytes("a448f24c6d18e575453db13171562b71999873db5b286df957af199ec94617f7").unwrap().0,
+        );
+
+        // A record whose signature does not match.
+        let mut forged = enr.clone();
+        forged.signature.0[10] ^= 1;
+        assert!(rlp::decode_to::<Enr>(&rlp::encode(&forged).unwrap()).is_err());
+    }
+
+    #[test]
+    fn new_records() {
+        let secret_key = U256::from(0x5ec12e7_u32);
+        let mut enr = Enr::new(secret_key, 1, IpAddr::V6(Ipv6Addr::LOCALHOST), 30301, 30302);
+        enr.insert("eth", &vec![(Bytes4([1, 2, 3, 4]), 0_u64)], secret_key).unwrap();
+        let decoded: Enr = enr.to_string().parse().unwrap();
+        assert_eq!(decoded, enr);
+        assert_eq!(decoded.seq, 2);
+        assert_eq!((decoded.ip(), decoded.udp(), decoded.tcp()), (Some(IpAddr::V6(Ipv6Addr::LOCALHOST)), Some(30301), Some(30302)));
+        assert_eq!(decoded.public_key().unwrap(), secp256k1_public_key(secret_key));
+
+        for data in [&b""[..], b"f", b"fo", b"foo", b"foob"] {
+            assert_eq!(base64_decode(&base64_encode(data)).unwrap(), data);
+        }
+        assert_eq!(base64_encode(b"\xfb\xff"), "-_8");
+    }
+}
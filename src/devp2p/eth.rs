@@ -0,0 +1,346 @@
+//! Messages of the `eth` wire protocol, version 68.
+//!
+//! After the `Status` exchange, peers announce new transactions and blocks
+//! and ask each other for headers, bodies, transactions and receipts. Each
+//! request carries an id which its response repeats. Transactions and
+//! headers are encoded as they are in blocks, so the messages reuse the
+//! crate's types.
+
+use crate::{
+    ethereum::{
+        cancun::{
+            blocks::{Block, Header, Withdrawal},
+            fork::{compute_header_hash, BlockChain},
+            transactions::Transaction,
+        },
+        crypto::hash::Hash32,
+        ethereum_rlp::{
+            exceptions::RLPException,
+            rlp::{self, Extended},
+        },
+        ethereum_types::{
+            bytes::{Bytes, Bytes4, Verbatim},
+            numeric::{U256, U64},
+        },
+        exceptions::Exception,
+        fork_criteria::{ForkCriteria, ForkSchedule},
+    },
+    impl_extended,
+};
+
+use super::P2pError;
+
+/// The version of the protocol implemented.
+pub const ETH_VERSION: u64 = 68;
+
+/// An EIP-2124 fork identifier, by which peers tell whether they follow
+/// the same forks.
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct ForkId {
+    /// CRC32 checksum of the genesis hash and the forks passed.
+    pub hash: Bytes4,
+    /// The block number or timestamp of the next fork, zero if none.
+    pub next: U64,
+}
+
+impl_extended!(ForkId: hash, next);
+
+impl ForkId {
+    /// The fork id of a chain with `schedule` and genesis `genesis_hash`,
+    /// whose head has `head_number` and `head_timestamp`.
+    ///
+    /// Forks at genesis, including timestamp forks at or before its
+    /// `genesis_timestamp`, are left out.
+    pub fn new(
+        schedule: &ForkSchedule,
+        genesis_hash: &Hash32,
+        genesis_timestamp: U256,
+        head_number: u64,
+        head_timestamp: U256,
+    ) -> Self {
+        let mut numbers = Vec::new();
+        let mut timestamps = Vec::new();
+        for (criteria, _) in schedule.forks() {
+            match *criteria {
+                ForkCriteria::ByBlockNumber(number) if number > 0 => numbers.push(number as u64),
+                ForkCriteria::ByTimestamp(timestamp) if timestamp > genesis_timestamp => timestamps.push(timestamp),
+                _ => {}
+            }
+        }
+        numbers.sort();
+        numbers.dedup();
+        timestamps.sort();
+        timestamps.dedup();
+
+        let mut hash = crc32(0, &genesis_hash.0);
+        for number in numbers {
+            if head_number < number {
+                return Self { hash: Bytes4(hash.to_be_bytes()), next: number };
+            }
+            hash = crc32(hash, &number.to_be_bytes());
+        }
+        for timestamp in timestamps {
+            let timestamp_u64 = u64::from_be_bytes(timestamp.to_be_bytes()[24..].try_into().unwrap());
+            if head_timestamp < timestamp {
+                return Self { hash: Bytes4(hash.to_be_bytes()), next: timestamp_u64 };
+            }
+            hash = crc32(hash, &timestamp_u64.to_be_bytes());
+        }
+        Self { hash: Bytes4(hash.to_be_bytes()), next: 0 }
+    }
+}
+
+/// Continue the IEEE CRC32 checksum `crc` over `data`.
+fn crc32(crc: u32, data: &[u8]) -> u32 {
+    let mut crc = !crc;
+    for b in data {
+        crc ^= *b as u32;
+        for _ in 0..8 {
+            crc = if crc & 1 == 1 { (crc >> 1) ^ 0xedb88320 } else { crc >> 1 };
+        }
+    }
+    !crc
+}
+
+/// The first message of the protocol, describing the chain of the sender.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Status {
+    pub version: U64,
+    pub network_id: U64,
+    pub total_difficulty: U256,
+    pub best_hash: Hash32,
+    pub genesis_hash: Hash32,
+    pub fork_id: ForkId,
+}
+
+impl_extended!(Status: version, network_id, total_difficulty, best_hash, genesis_hash, fork_id);
+
+impl Status {
+    /// The status of `chain`, whose network id is taken to be its chain
+    /// id, as it is for mainnet and the public testnets.
+    pub fn new(chain: &BlockChain) -> Result<Self, Exception> {
+        let genesis = &chain.blocks[0].header;
+        let head = &chain.blocks.last().unwrap().header;
+        let genesis_hash = compute_header_hash(genesis)?;
+        let total_difficulty: u128 = chain.blocks.iter().map(|block| block.header.difficulty).sum();
+        let mut total_difficulty_bytes = [0; 32];
+        total_difficulty_bytes[16..].copy_from_slice(&total_difficulty.to_be_bytes());
+        Ok(Self {
+            version: ETH_VERSION,
+            network_id: chain.chain_spec.chain_id,
+            total_difficulty: U256::from_be_bytes(total_difficulty_bytes),
+            best_hash: chain.head_hash().clone(),
+            fork_id: ForkId::new(&chain.fork_schedule, &genesis_hash, genesis.timestamp, head.number as u64, head.timestamp),
+            genesis_hash,
+        })
+    }
+}
+
+/// The first block of a header request, by hash or number.
+#[derive(Debug, Clone, PartialEq)]
+pub enum BlockId {
+    Hash(Hash32),
+    Number(U64),
+}
+
+impl Default for BlockId {
+    fn default() -> Self {
+        BlockId::Number(0)
+    }
+}
+
+impl Extended for BlockId {
+    fn encode<'a, 'b>(&self, buffer: &'a mut Bytes) -> Result<(), RLPException> {
+        match self {
+            BlockId::Hash(hash) => hash.encode(buffer),
+            BlockId::Number(number) => number.encode(buffer),
+        }
+    }
+
+    /// A hash is the only 32 byte form.
+    fn decode<'a, 'b>(&mut self, buffer: &'a mut &'b [u8]) -> Result<(), RLPException> {
+        let mut bytes = Bytes::default();
+        bytes.decode(buffer)?;
+        *self = match bytes.len() {
+            32 => BlockId::Hash(Hash32(bytes.0.try_into().unwrap())),
+            0..=8 if bytes.first() != Some(&0) => BlockId::Number(bytes.iter().fold(0, |n, b| n << 8 | *b as u64)),
+            _ => return Err(RLPException::DecodingError("invalid block number or hash")),
+        };
+        Ok(())
+    }
+}
+
+/// `limit` headers from `start`, every `skip + 1`th, in decreasing order of
+/// number if `reverse`.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct HeadersRequest {
+    pub start: BlockId,
+    pub limit: U64,
+    pub skip: U64,
+    pub reverse: bool,
+}
+
+impl_extended!(HeadersRequest: start, limit, skip, reverse);
+
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct GetBlockHeaders {
+    pub request_id: U64,
+    pub request: HeadersRequest,
+}
+
+impl_extended!(GetBlockHeaders: request_id, request);
+
+#[derive(Debug, Clone, Default)]
+pub struct BlockHeaders {
+    pub request_id: U64,
+    pub headers: Vec<Header>,
+}
+
+impl_extended!(BlockHeaders: request_id, headers);
+
+/// A request by hash for blocks' bodies, pooled transactions or receipts.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct GetByHashes {
+    pub request_id: U64,
+    pub hashes: Vec<Hash32>,
+}
+
+impl_extended!(GetByHashes: request_id, hashes);
+
+/// The contents of a block apart from its header.
+#[derive(Debug, Clone, Default)]
+pub struct BlockBody {
+    pub transactions: Vec<Transaction>,
+    pub ommers: Vec<Header>,
+    pub withdrawals: Option<Vec<Withdrawal>>,
+}
+
+impl_extended!(BlockBody: transactions, ommers, withdrawals);
+
+impl BlockBody {
+    /// The block of `header` with this body.
+    pub fn into_block(self, header: Header) -> Block {
+        Block { header, transactions: self.transactions, ommers: self.ommers, withdrawals: self.withdrawals }
+    }
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct BlockBodies {
+    pub request_id: U64,
+    pub bodies: Vec<BlockBody>,
+}
+
+impl_extended!(BlockBodies: request_id, bodies);
+
+#[derive(Debug, Clone, Default)]
+pub struct NewBlock {
+    pub block: Block,
+    pub total_difficulty: U256,
+}
+
+impl_extended!(NewBlock: block, total_difficulty);
+
+/// Transactions a peer has, by type, encoded size and hash.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct NewPooledTransactionHashes {
+    pub types: Bytes,
+    pub sizes: Vec<U64>,
+    pub hashes: Vec<Hash32>,
+}
+
+impl_extended!(NewPooledTransactionHashes: types, sizes, hashes);
+
+#[derive(Debug, Clone, Default)]
+pub struct PooledTransactions {
+    pub request_id: U64,
+    pub transactions: Vec<Transaction>,
+}
+
+impl_extended!(PooledTransactions: request_id, transactions);
+
+/// The receipts of blocks, each list kept in its encoded form.
+#[derive(Debug, Clone, Default)]
+pub struct Receipts {
+    pub request_id: U64,
+    pub receipts: Vec<Vec<Verbatim>>,
+}
+
+impl_extended!(Receipts: request_id, receipts);
+
+protocol_messages! {
+    /// A message of the protocol.
+    EthMessage, "unknown eth message",
+    0x00 => Status(Status),
+    0x01 => NewBlockHashes(Vec<(Hash32, U64)>),
+    0x02 => Transactions(Vec<Transaction>),
+    0x03 => GetBlockHeaders(GetBlockHeaders),
+    0x04 => BlockHeaders(BlockHeaders),
+    0x05 => GetBlockBodies(GetByHashes),
+    0x06 => BlockBodies(BlockBodies),
+    0x07 => NewBlock(NewBlock),
+    0x08 => NewPooledTransactionHashes(NewPooledTransactionHashes),
+    0x09 => GetPooledTransactions(GetByHashes),
+    0x0a => PooledTransactions(PooledTransactions),
+    0x0f => GetReceipts(GetByHashes),
+    0x10 => Receipts(Receipts),
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{
+        ethereum::{
+            cancun::fork::BlockChain,
+            ethereum_rlp::rlp,
+            ethereum_types::bytes::Bytes4,
+            fork_criteria::ForkSchedule,
+            genesis::{Genesis, MAINNET_GENESIS_HASH},
+        },
+        prelude::*,
+    };
+
+    use super::{BlockId, EthMessage, ForkId, GetBlockHeaders, HeadersRequest};
+
+    #[test]
+    fn mainnet_fork_ids() {
+        let genesis = Genesis::mainnet().unwrap();
+        let schedule = ForkSchedule::new(&genesis.forks);
+        let fork_id = |number: u64, timestamp: u64| {
+            let id = ForkId::new(&schedule, &MAINNET_GENESIS_HASH, U256::ZERO, number, U256::from(timestamp));
+            (id.hash.0, id.next)
+        };
+        // From EIP-2124.
+        assert_eq!(fork_id(0, 0), ([0xfc, 0x64, 0xec, 0x04], 1150000));
+        assert_eq!(fork_id(1150000, 0), ([0x97, 0xc2, 0xc3, 0x4c], 1920000));
+        assert_eq!(fork_id(7280000, 0), ([0x66, 0x8d, 0xb0, 0xaf], 9069000));
+        // Shanghai, and Cancun by timestamp.
+        assert_eq!(fork_id(17034870, 1681338455), ([0xdc, 0xe9, 0x6c, 0x2d], 1710338135));
+        assert_eq!(fork_id(19426587, 1710338135).0, [0x9f, 0x3d, 0x22, 0x54]);
+    }
+
+    #[test]
+    fn messages() {
+        let chain = BlockChain::mainnet().unwrap();
+        let status = super::Status::new(&chain).unwrap();
+        assert_eq!(status.fork_id.hash, Bytes4([0xfc, 0x64, 0xec, 0x04]));
+        let encoded = EthMessage::Status(status.clone()).encode().unwrap();
+        let EthMessage::Status(decoded) = EthMessage::decode(0x00, &encoded).unwrap() else { panic!() };
+        assert_eq!(decoded, status);
+
+        // Requests by hash and by number.
+        for start in [BlockId::Hash(Hash32([7; 32])), BlockId::Number(0), BlockId::Number(0x1234)] {
+            let request = GetBlockHeaders {
+                request_id: 9,
+                request: HeadersRequest { start, limit: 192, skip: 0, reverse: false },
+            };
+            let encoded = rlp::encode(&request).unwrap();
+            assert_eq!(rlp::decode_to::<GetBlockHeaders>(&encoded).unwrap(), request);
+        }
+        assert!(EthMessage::decode(0x0b, &[0xc0]).is_err());
+
+        // Messages are decoded strictly, so a request id with a leading
+        // zero byte is rejected.
+        let canonical = [0xc7, 0x09, 0xc5, 0x80, 0x81, 0xc0, 0x80, 0x80];
+        assert!(EthMessage::decode(0x03, &canonical).is_ok());
+        assert!(EthMessage::decode(0x03, &[0xc9, 0x82, 0x00, 0x09, 0xc5, 0x80, 0x81, 0xc0, 0x80, 0x80]).is_err());
+    }
+}
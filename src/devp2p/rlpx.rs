@@ -0,0 +1,274 @@
+//! The RLPx transport: an authenticated handshake between two node keys,
+//! then encrypted and MACed frames each carrying one message.
+//!
+//! Only the EIP-8 form of the handshake messages is sent or accepted; every
+//! client in use today supports it. The secrets which both sides derive
+//! from the handshake key AES-256 in counter mode for the frames of each
+//! direction and seed a running Keccak MAC of everything sent, which is
+//! mixed with the AES of the MAC secret as the specification describes.
+
+use std::io::{Read, Write};
+
+use tiny_keccak::{Hasher, Keccak};
+
+use crate::ethereum::{
+    crypto::{
+        aes::{Aes, AesCtr},
+        eliptic_curve::{secp256k1_ecdh, secp256k1_public_key, secp256k1_recover, secp256k1_sign},
+        hash::{keccak256, Hash32},
+    },
+    ethereum_rlp::{
+        rlp,
+        stream::RlpStream,
+    },
+    ethereum_types::{
+        bytes::Bytes,
+        numeric::{U256, U64},
+    },
+};
+
+use super::{ecies, random_bytes, random_secret_key, snappy, P2pError};
+
+/// The version of the handshake messages.
+const AUTH_VERSION: U64 = 4;
+
+/// Largest frame which the three byte size of its header can describe.
+const MAX_FRAME_SIZE: usize = (1 << 24) - 1;
+
+/// An RLPx connection over `stream`.
+pub struct Rlpx<S> {
+    stream: S,
+    /// The public key of the peer, without the `0x04` prefix.
+    pub remote_id: Bytes,
+    egress_aes: AesCtr,
+    ingress_aes: AesCtr,
+    mac_aes: Aes,
+    egress_mac: Keccak,
+    ingress_mac: Keccak,
+    /// Whether message payloads are compressed, which the base protocol
+    /// turns on after `Hello`.
+    pub snappy: bool,
+}
+
+/// Handshake values from which the session secrets are derived.
+struct Handshake<'a> {
+    ephemeral_shared: [u8; 32],
+    initiator_nonce: [u8; 32],
+    recipient_nonce: [u8; 32],
+    auth: &'a [u8],
+    ack: &'a [u8],
+}
+
+impl<S: Read + Write> Rlpx<S> {
+    /// Open a connection to the node `remote_id` as the initiator.
+    pub fn connect(mut stream: S, secret_key: U256, remote_id: &[u8]) -> Result<Self, P2pError> {
+        let ephemeral_secret = random_secret_key();
+        let nonce = random_bytes::<32>();
+
+        let static_shared = secp256k1_ecdh(secret_key, remote_id).map_err(|_| P2pError::Crypto("invalid node id"))?;
+        let (r, s, v) = secp256k1_sign(Hash32(xor(&static_shared, &nonce)), ephemeral_secret)
+            .map_err(|_| P2pError::Crypto("invalid ephemeral key"))?;
+        let mut signature = [&r.to_be_bytes()[..], &s.to_be_bytes()].concat();
+        signature.push(v.to_be_bytes()[31]);
+        let mut body = Bytes::default();
+        rlp::encode_sequence(
+            &mut body,
+            &[&Bytes(signature), &secp256k1_public_key(secret_key), &Hash32(nonce), &AUTH_VERSION],
+        )?;
+        let auth = seal(remote_id, body)?;
+        stream.write_all(&auth)?;
+
+        let ack = read_packet(&mut stream)?;
+        let body = ecies::decrypt(secret_key, &ack[2..], &ack[..2])?;
+        let mut fields = RlpStream::new(&body).next_list()?;
+        let remote_ephemeral = fields.next_bytes()?;
+        let recipient_nonce = fixed::<32>(fields.next_bytes()?)?;
+
+        let ephemeral_shared = secp256k1_ecdh(ephemeral_secret, remote_ephemeral)
+            .map_err(|_| P2pError::Crypto("invalid ephemeral key"))?;
+        let handshake = Handshake { ephemeral_shared, initiator_nonce: nonce, recipient_nonce, auth: &auth, ack: &ack };
+        Ok(Self::new(stream, Bytes(remote_id.to_vec()), &handshake, true))
+    }
+
+    /// Accept a connection on `stream` as the recipient.
+    pub fn accept(mut stream: S, secret_key: U256) -> Result<Self, P2pError> {
+        let auth = read_packet(&mut stream)?;
+        let body = ecies::decrypt(secret_key, &auth[2..], &auth[..2])?;
+        let mut fields = RlpStream::new(&body).next_list()?;
+        let signature = fixed::<65>(fields.next_bytes()?)?;
+        let remote_id = fields.next_bytes()?.to_vec();
+        let initiator_nonce = fixed::<32>(fields.next_bytes()?)?;
+
+        // The signature recovers the initiator's ephemeral key.
+        let static_shared = secp256k1_ecdh(secret_key, &remote_id).map_err(|_| P2pError::Crypto("invalid node id"))?;
+        let r = U256::from_be_bytes(signature[..32].try_into().unwrap());
+        let s = U256::from_be_bytes(signature[32..64].try_into().unwrap());
+        let v = U256::from(signature[64] as u32);
+        let remote_ephemeral = secp256k1_recover(r, s, v, Hash32(xor(&static_shared, &initiator_nonce)))
+            .map_err(|_| P2pError::Crypto("invalid auth signature"))?;
+
+        let ephemeral_secret = random_secret_key();
+        let nonce = random_bytes::<32>();
+        let mut body = Bytes::default();
+        rlp::encode_sequence(&mut body, &[&secp256k1_public_key(ephemeral_secret), &Hash32(nonce), &AUTH_VERSION])?;
+        let ack = seal(&remote_id, body)?;
+        stream.write_all(&ack)?;
+
+        let ephemeral_shared = secp256k1_ecdh(ephemeral_secret, &remote_ephemeral)
+            .map_err(|_| P2pError::Crypto("invalid ephemeral key"))?;
+        let handshake = Handshake { ephemeral_shared, initiator_nonce, recipient_nonce: nonce, auth: &auth, ack: &ack };
+        Ok(Self::new(stream, Bytes(remote_id), &handshake, false))
+    }
+
+    /// Derive the session secrets.
+    fn new(stream: S, remote_id: Bytes, handshake: &Handshake, initiator: bool) -> Self {
+        let nonce_hash = keccak256(&[handshake.recipient_nonce, handshake.initiator_nonce].concat());
+        let shared_secret = keccak256(&[&handshake.ephemeral_shared[..], &nonce_hash.0].concat());
+        let aes_secret = keccak256(&[&handshake.ephemeral_shared[..], &shared_secret.0].concat());
+        let mac_secret = keccak256(&[&handshake.ephemeral_shared[..], &aes_secret.0].concat());
+
+        let mac = |nonce: &[u8; 32], packet: &[u8]| {
+            let mut mac = Keccak::v256();
+            mac.update(&xor(&mac_secret.0, nonce));
+            mac.update(packet);
+            mac
+        };
+        let (sent, received) = if initiator { (handshake.auth, handshake.ack) } else { (handshake.ack, handshake.auth) };
+        let (own_nonce, remote_nonce) = if initiator {
+            (&handshake.initiator_nonce, &handshake.recipient_nonce)
+        } else {
+            (&handshake.recipient_nonce, &handshake.initiator_nonce)
+        };
+        Self {
+            stream,
+            remote_id,
+            egress_aes: AesCtr::new(&aes_secret.0, [0; 16]),
+            ingress_aes: AesCtr::new(&aes_secret.0, [0; 16]),
+            mac_aes: Aes::new(&mac_secret.0),
+            egress_mac: mac(remote_nonce, sent),
+            ingress_mac: mac(own_nonce, received),
+            snappy: false,
+        }
+    }
+
+    /// Send the message `id` with the RLP encoded `payload`.
+    pub fn send(&mut self, id: u64, payload: &[u8]) -> Result<(), P2pError> {
+        let mut data = rlp::encode(&id)?.0;
+        if self.snappy {
+            data.extend_from_slice(&snappy::compress(payload));
+        } else {
+            data.extend_from_slice(payload);
+        }
+        self.write_frame(&data)
+    }
+
+    /// Receive the next message, as its id and RLP encoded payload.
+    pub fn recv(&mut self) -> Result<(u64, Vec<u8>), P2pError> {
+        let data = self.read_frame()?;
+        let mut stream = RlpStream::new(&data);
+        let id = stream.next_u64()?;
+        let payload = if self.snappy { snappy::decompress(stream.remaining())? } else { stream.remaining().to_vec() };
+        Ok((id, payload))
+    }
+
+    fn write_frame(&mut self, data: &[u8]) -> Result<(), P2pError> {
+        if data.len() > MAX_FRAME_SIZE {
+            return Err(P2pError::Protocol("message too large"));
+        }
+        // The size, then the empty header data `[0, 0]`.
+        let mut header = [0; 16];
+        header[..3].copy_from_slice(&(data.len() as u32).to_be_bytes()[1..]);
+        header[3..6].copy_from_slice(&[0xc2, 0x80, 0x80]);
+        self.egress_aes.apply(&mut header);
+        let header_mac = update_mac(&mut self.egress_mac, &self.mac_aes, &header);
+
+        let mut frame = data.to_vec();
+        frame.resize(data.len().next_multiple_of(16), 0);
+        self.egress_aes.apply(&mut frame);
+        self.egress_mac.update(&frame);
+        let seed = digest(&self.egress_mac);
+        let frame_mac = update_mac(&mut self.egress_mac, &self.mac_aes, &seed);
+
+        let mut out = Vec::with_capacity(frame.len() + 64);
+        out.extend_from_slice(&header);
+        out.extend_from_slice(&header_mac);
+        out.extend_from_slice(&frame);
+        out.extend_from_slice(&frame_mac);
+        self.stream.write_all(&out)?;
+        Ok(self.stream.flush()?)
+    }
+
+    fn read_frame(&mut self) -> Result<Vec<u8>, P2pError> {
+        let mut header = [0; 32];
+        self.stream.read_exact(&mut header)?;
+        let (header, mac) = header.split_at_mut(16);
+        if update_mac(&mut self.ingress_mac, &self.mac_aes, header) != *mac {
+            return Err(P2pError::Crypto("frame header MAC mismatch"));
+        }
+        self.ingress_aes.apply(header);
+        let size = u32::from_be_bytes([0, header[0], header[1], header[2]]) as usize;
+
+        let mut frame = vec![0; size.next_multiple_of(16) + 16];
+        self.stream.read_exact(&mut frame)?;
+        let mac_start = frame.len() - 16;
+        let (frame_data, mac) = frame.split_at_mut(mac_start);
+        self.ingress_mac.update(frame_data);
+        let seed = digest(&self.ingress_mac);
+        if update_mac(&mut self.ingress_mac, &self.mac_aes, &seed) != *mac {
+            return Err(P2pError::Crypto("frame MAC mismatch"));
+        }
+        self.ingress_aes.apply(frame_data);
+        frame.truncate(size);
+        Ok(frame)
+    }
+}
+
+/// Pad `body` and encrypt it to `remote_id` as an EIP-8 handshake packet,
+/// with its size prefix.
+fn seal(remote_id: &[u8], mut body: Bytes) -> Result<Vec<u8>, P2pError> {
+    // Random padding tells EIP-8 messages apart from the old fixed size ones.
+    let padding = 100 + random_bytes::<1>()[0] as usize;
+    body.0.resize(body.len() + padding, 0);
+    let size = ((body.len() + ecies::OVERHEAD) as u16).to_be_bytes();
+    let sealed = ecies::encrypt(remote_id, &body, &size, random_secret_key(), random_bytes())?;
+    Ok([&size[..], &sealed].concat())
+}
+
+/// Read a handshake packet with its size prefix.
+fn read_packet(stream: &mut impl Read) -> Result<Vec<u8>, P2pError> {
+    let mut packet = vec![0; 2];
+    stream.read_exact(&mut packet)?;
+    let size = u16::from_be_bytes([packet[0], packet[1]]) as usize;
+    if size < ecies::OVERHEAD {
+        return Err(P2pError::Protocol("handshake packet too short"));
+    }
+    packet.resize(2 + size, 0);
+    stream.read_exact(&mut packet[2..])?;
+    Ok(packet)
+}
+
+fn fixed<const N: usize>(bytes: &[u8]) -> Result<[u8; N], P2pError> {
+    bytes.try_into().map_err(|_| P2pError::Protocol("malformed handshake"))
+}
+
+fn xor(a: &[u8; 32], b: &[u8; 32]) -> [u8; 32] {
+    std::array::from_fn(|i| a[i] ^ b[i])
+}
+
+/// The first 16 bytes of the current MAC digest.
+fn digest(mac: &Keccak) -> [u8; 16] {
+    let mut out = [0; 32];
+    mac.clone().finalize(&mut out);
+    out[..16].try_into().unwrap()
+}
+
+/// Mix `seed` into `mac` with the MAC secret, returning the new digest.
+fn update_mac(mac: &mut Keccak, mac_aes: &Aes, seed: &[u8]) -> [u8; 16] {
+    let mut block = digest(mac);
+    mac_aes.encrypt_block(&mut block);
+    for (b, s) in block.iter_mut().zip(seed) {
+        *b ^= s;
+    }
+    mac.update(&block);
+    digest(mac)
+}
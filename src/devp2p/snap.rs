@@ -0,0 +1,650 @@
+//! The `snap/1` protocol, through which a node downloads the state of a
+//! recent block directly instead of executing every block before it.
+//!
+//! Accounts and storage slots are fetched in ranges of hashed keys, each
+//! with a Merkle proof of its first and last key which shows that the
+//! range is complete under the state root. Contract code is fetched by
+//! hash. When the peer has moved on to a newer state, `heal` fetches the
+//! trie nodes which differ from the downloaded state, top down, and with
+//! them the changed entries.
+//!
+//! The result is a `SnapState`, keyed by the hashes of addresses and slots
+//! as the tries are. The protocol does not carry the preimages, so it can be
+//! checked against a state root and served to other peers but not turned
+//! back into a `State`.
+
+use std::{
+    collections::{BTreeMap, BTreeSet},
+    io::{Read, Write},
+};
+
+use crate::{
+    ethereum::{
+        cancun::{
+            fork_types::Root,
+            state::State,
+            trie::{
+                proof::{self, compact_path, decode_compact, node_at, prove, verify_range, Healer, Leaves},
+                EMPTY_TRIE_ROOT,
+            },
+        },
+        crypto::hash::{keccak256, Hash32},
+        ethereum_rlp::{exceptions::RLPException, rlp},
+        ethereum_types::{
+            bytes::Bytes,
+            numeric::{Uint, U256, U64},
+        },
+    },
+    impl_extended,
+};
+
+use super::{Message, P2pError, Peer};
+
+/// The version of the protocol implemented.
+pub const SNAP_VERSION: u64 = 1;
+
+/// Soft limit on the size of the responses requested, as in geth.
+const RESPONSE_BYTES: U64 = 512 * 1024;
+
+/// Bytecodes or trie nodes requested at once.
+const BATCH: usize = 128;
+
+/// An account as the state trie stores it.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct AccountState {
+    pub nonce: Uint,
+    pub balance: U256,
+    pub storage_root: Root,
+    pub code_hash: Hash32,
+}
+
+impl_extended!(AccountState: nonce, balance, storage_root, code_hash);
+
+/// An account in the form the protocol sends, with an empty storage root
+/// and code hash left out.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct SlimAccount {
+    pub nonce: Uint,
+    pub balance: U256,
+    pub storage_root: Bytes,
+    pub code_hash: Bytes,
+}
+
+impl_extended!(SlimAccount: nonce, balance, storage_root, code_hash);
+
+impl From<&AccountState> for SlimAccount {
+    fn from(account: &AccountState) -> Self {
+        let storage_root = if account.storage_root == EMPTY_TRIE_ROOT { &[][..] } else { &account.storage_root.0 };
+        let code_hash = if account.code_hash == keccak256(&[]) { &[][..] } else { &account.code_hash.0 };
+        Self {
+            nonce: account.nonce,
+            balance: account.balance,
+            storage_root: Bytes(storage_root.to_vec()),
+            code_hash: Bytes(code_hash.to_vec()),
+        }
+    }
+}
+
+impl TryFrom<SlimAccount> for AccountState {
+    type Error = P2pError;
+
+    fn try_from(account: SlimAccount) -> Result<Self, P2pError> {
+        const INVALID: P2pError = P2pError::Protocol("invalid slim account");
+        let storage_root = match account.storage_root.len() {
+            0 => EMPTY_TRIE_ROOT,
+            _ => Root(account.storage_root.0.try_into().map_err(|_| INVALID)?),
+        };
+        let code_hash = match account.code_hash.len() {
+            0 => keccak256(&[]),
+            _ => Hash32(account.code_hash.0.try_into().map_err(|_| INVALID)?),
+        };
+        Ok(Self { nonce: account.nonce, balance: account.balance, storage_root, code_hash })
+    }
+}
+
+/// Accounts of the state `root` with hashes from `origin` to `limit`.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct GetAccountRange {
+    pub request_id: U64,
+    pub root: Root,
+    pub origin: Hash32,
+    pub limit: Hash32,
+    /// Soft limit on the size of the response.
+    pub bytes: U64,
+}
+
+impl_extended!(GetAccountRange: request_id, root, origin, limit, bytes);
+
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct AccountRange {
+    pub request_id: U64,
+    pub accounts: Vec<(Hash32, SlimAccount)>,
+    /// Nodes proving the origin and the last account.
+    pub proof: Vec<Bytes>,
+}
+
+impl_extended!(AccountRange: request_id, accounts, proof);
+
+/// Storage slots of `accounts` in the state `root`. `origin` and `limit`,
+/// which may be empty, apply to the first account.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct GetStorageRanges {
+    pub request_id: U64,
+    pub root: Root,
+    pub accounts: Vec<Hash32>,
+    pub origin: Bytes,
+    pub limit: Bytes,
+    pub bytes: U64,
+}
+
+impl_extended!(GetStorageRanges: request_id, root, accounts, origin, limit, bytes);
+
+/// The slots of some of the accounts asked for, as the storage tries store
+/// them, with a proof for the last range if it is not a whole trie.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct StorageRanges {
+    pub request_id: U64,
+    pub slots: Vec<Vec<(Hash32, Bytes)>>,
+    pub proof: Vec<Bytes>,
+}
+
+impl_extended!(StorageRanges: request_id, slots, proof);
+
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct GetByteCodes {
+    pub request_id: U64,
+    pub hashes: Vec<Hash32>,
+    pub bytes: U64,
+}
+
+impl_extended!(GetByteCodes: request_id, hashes, bytes);
+
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct ByteCodes {
+    pub request_id: U64,
+    pub codes: Vec<Bytes>,
+}
+
+impl_extended!(ByteCodes: request_id, codes);
+
+/// Trie nodes of the state `root` by path. Each path set is the compact
+/// path of a node in the account trie, or the hash of an account followed
+/// by compact paths in its storage trie.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct GetTrieNodes {
+    pub request_id: U64,
+    pub root: Root,
+    pub paths: Vec<Vec<Bytes>>,
+    pub bytes: U64,
+}
+
+impl_extended!(GetTrieNodes: request_id, root, paths, bytes);
+
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct TrieNodes {
+    pub request_id: U64,
+    pub nodes: Vec<Bytes>,
+}
+
+impl_extended!(TrieNodes: request_id, nodes);
+
+protocol_messages! {
+    /// A message of the protocol.
+    SnapMessage, "unknown snap message",
+    0x00 => GetAccountRange(GetAccountRange),
+    0x01 => AccountRange(AccountRange),
+    0x02 => GetStorageRanges(GetStorageRanges),
+    0x03 => StorageRanges(StorageRanges),
+    0x04 => GetByteCodes(GetByteCodes),
+    0x05 => ByteCodes(ByteCodes),
+    0x06 => GetTrieNodes(GetTrieNodes),
+    0x07 => TrieNodes(TrieNodes),
+}
+
+impl SnapMessage {
+    /// The response to a request for a state which is not available.
+    pub fn empty_response(&self) -> Option<SnapMessage> {
+        Some(match self {
+            SnapMessage::GetAccountRange(request) => {
+                SnapMessage::AccountRange(AccountRange { request_id: request.request_id, ..Default::default() })
+            }
+            SnapMessage::GetStorageRanges(request) => {
+                SnapMessage::StorageRanges(StorageRanges { request_id: request.request_id, ..Default::default() })
+            }
+            SnapMessage::GetByteCodes(request) => {
+                SnapMessage::ByteCodes(ByteCodes { request_id: request.request_id, ..Default::default() })
+            }
+            SnapMessage::GetTrieNodes(request) => {
+                SnapMessage::TrieNodes(TrieNodes { request_id: request.request_id, ..Default::default() })
+            }
+            _ => return None,
+        })
+    }
+}
+
+/// A state keyed by the hashes of addresses and storage keys.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct SnapState {
+    pub accounts: BTreeMap<Hash32, AccountState>,
+    /// The storage of accounts which have any, with values as the storage
+    /// tries store them.
+    pub storage: BTreeMap<Hash32, Leaves>,
+    /// Contract code by hash.
+    pub codes: BTreeMap<Hash32, Bytes>,
+}
+
+impl SnapState {
+    pub fn from_state(state: &State) -> Result<Self, RLPException> {
+        let mut snap = SnapState::default();
+        for (address, account) in state.accounts() {
+            let hash = keccak256(&address[..]);
+            let mut storage = Leaves::new();
+            for (key, value) in state.storage(address) {
+                storage.insert(keccak256(&key.0), rlp::encode(value)?);
+            }
+            let code_hash = keccak256(&account.code);
+            if !account.code.is_empty() {
+                snap.codes.insert(code_hash.clone(), account.code.clone());
+            }
+            let storage_root = proof::root(&storage);
+            snap.accounts.insert(hash.clone(), AccountState { nonce: account.nonce, balance: account.balance, storage_root, code_hash });
+            if !storage.is_empty() {
+                snap.storage.insert(hash, storage);
+            }
+        }
+        Ok(snap)
+    }
+
+    /// The entries of the account trie.
+    pub fn account_leaves(&self) -> Leaves {
+        self.accounts.iter().map(|(hash, account)| (hash.clone(), rlp::encode(account).unwrap())).collect()
+    }
+
+    /// The state root.
+    pub fn root(&self) -> Root {
+        proof::root(&self.account_leaves())
+    }
+
+    /// Answer a request for this state, or for another with an empty
+    /// response.
+    pub fn respond(&self, request: &SnapMessage) -> Option<SnapMessage> {
+        let account_leaves = self.account_leaves();
+        let root = proof::root(&account_leaves);
+        Some(match request {
+            SnapMessage::GetAccountRange(request) if request.root == root => {
+                let mut accounts = Vec::new();
+                let mut size = 0;
+                for (hash, account) in self.accounts.range(request.origin.clone()..) {
+                    if *hash > request.limit || (!accounts.is_empty() && size >= request.bytes) {
+                        break;
+                    }
+                    let account = SlimAccount::from(account);
+                    size += 32 + rlp::encode(&account).unwrap().len() as u64;
+                    accounts.push((hash.clone(), account));
+                }
+                let last = accounts.last().map(|(hash, _)| hash);
+                let proof = edge_proof(&account_leaves, &request.origin, last);
+                SnapMessage::AccountRange(AccountRange { request_id: request.request_id, accounts, proof })
+            }
+            SnapMessage::GetStorageRanges(request) if request.root == root => {
+                let mut slots = Vec::new();
+                let mut proof = Vec::new();
+                let mut size = 0;
+                let empty = Leaves::new();
+                for (i, account) in request.accounts.iter().enumerate() {
+                    let bound = |bytes: &Bytes, default| match (i, <[u8; 32]>::try_from(&bytes[..])) {
+                        (0, Ok(bound)) => Hash32(bound),
+                        _ => Hash32(default),
+                    };
+                    let origin = bound(&request.origin, [0; 32]);
+                    let limit = bound(&request.limit, [0xff; 32]);
+                    let leaves = self.storage.get(account).unwrap_or(&empty);
+                    let mut range = Vec::new();
+                    let mut complete = true;
+                    for (key, value) in leaves.range(origin.clone()..) {
+                        if *key > limit || (size >= request.bytes && !(slots.is_empty() && range.is_empty())) {
+                            complete = false;
+                            break;
+                        }
+                        size += 32 + value.len() as u64;
+                        range.push((key.clone(), value.clone()));
+                    }
+                    if !complete || origin != Hash32::default() {
+                        proof = edge_proof(leaves, &origin, range.last().map(|(key, _)| key));
+                        slots.push(range);
+                        break;
+                    }
+                    slots.push(range);
+                    if size >= request.bytes {
+                        break;
+                    }
+                }
+                SnapMessage::StorageRanges(StorageRanges { request_id: request.request_id, slots, proof })
+            }
+            SnapMessage::GetByteCodes(request) => {
+                let mut codes = Vec::new();
+                let mut size = 0;
+                for code in request.hashes.iter().filter_map(|hash| self.codes.get(hash)) {
+                    if !codes.is_empty() && size >= request.bytes {
+                        break;
+                    }
+                    size += code.len() as u64;
+                    codes.push(code.clone());
+                }
+                SnapMessage::ByteCodes(ByteCodes { request_id: request.request_id, codes })
+            }
+            SnapMessage::GetTrieNodes(request) if request.root == root => {
+                let mut nodes = Vec::new();
+                let mut size = 0;
+                'paths: for path_set in &request.paths {
+                    let Some((first, rest)) = path_set.split_first() else { break };
+                    let found: Vec<Option<Bytes>> = if rest.is_empty() {
+                        vec![decode_compact(first).ok().and_then(|(path, _)| node_at(&account_leaves, &path))]
+                    } else {
+                        let account = <[u8; 32]>::try_from(&first[..]).ok().map(Hash32);
+                        let leaves = account.and_then(|account| self.storage.get(&account));
+                        let node = |path: &Bytes| Some(node_at(leaves?, &decode_compact(path).ok()?.0)?);
+                        rest.iter().map(node).collect()
+                    };
+                    // The nodes must be in the order of the paths, so the
+                    // response ends at the first one which is missing.
+                    for node in found {
+                        let Some(node) = node else { break 'paths };
+                        size += node.len() as u64;
+                        nodes.push(node);
+                    }
+                    if size >= request.bytes {
+                        break;
+                    }
+                }
+                SnapMessage::TrieNodes(TrieNodes { request_id: request.request_id, nodes })
+            }
+            request => return request.empty_response(),
+        })
+    }
+}
+
+/// The nodes proving `origin` and `last`.
+fn edge_proof(leaves: &Leaves, origin: &Hash32, last: Option<&Hash32>) -> Vec<Bytes> {
+    let mut proof = prove(leaves, origin);
+    for node in last.map(|last| prove(leaves, last)).unwrap_or_default() {
+        if !proof.contains(&node) {
+            proof.push(node);
+        }
+    }
+    proof
+}
+
+/// The key after `key`, if there is one.
+fn next_key(key: &Hash32) -> Option<Hash32> {
+    let mut next = key.clone();
+    for b in next.0.iter_mut().rev() {
+        let (sum, carry) = b.overflowing_add(1);
+        *b = sum;
+        if !carry {
+            return Some(next);
+        }
+    }
+    None
+}
+
+impl<S: Read + Write> Peer<S> {
+    /// Whether the peer speaks `snap/1`.
+    pub fn supports_snap(&self) -> bool {
+        self.hello.capabilities.iter().any(|(name, version)| name == "snap" && *version == SNAP_VERSION)
+    }
+
+    fn snap_request<T>(
+        &mut self,
+        request: impl FnOnce(u64) -> SnapMessage,
+        mut response: impl FnMut(SnapMessage, u64) -> Option<T>,
+    ) -> Result<T, P2pError> {
+        self.request(
+            |request_id| Message::Snap(request(request_id)),
+            |message, id| match message {
+                Message::Snap(message) => response(message.clone(), id).ok_or(Message::Snap(message)),
+                other => Err(other),
+            },
+        )
+    }
+
+    /// Request the accounts of the state `root` from `origin`, checked
+    /// against the proof which comes with them.
+    pub fn get_account_range(&mut self, root: &Root, origin: &Hash32) -> Result<BTreeMap<Hash32, AccountState>, P2pError> {
+        let response = self.snap_request(
+            |request_id| {
+                SnapMessage::GetAccountRange(GetAccountRange {
+                    request_id,
+                    root: root.clone(),
+                    origin: origin.clone(),
+                    limit: Hash32([0xff; 32]),
+                    bytes: RESPONSE_BYTES,
+                })
+            },
+            |message, id| match message {
+                SnapMessage::AccountRange(response) if response.request_id == id => Some(response),
+                _ => None,
+            },
+        )?;
+        if !response.accounts.windows(2).all(|pair| pair[0].0 < pair[1].0) {
+            return Err(P2pError::Protocol("accounts out of order"));
+        }
+        let mut accounts = BTreeMap::new();
+        for (hash, account) in response.accounts {
+            accounts.insert(hash, AccountState::try_from(account)?);
+        }
+        let leaves = accounts.iter().map(|(hash, account)| Ok((hash.clone(), rlp::encode(account)?))).collect::<Result<_, P2pError>>()?;
+        verify_range(root, origin, &leaves, &response.proof).map_err(P2pError::State)?;
+        Ok(accounts)
+    }
+
+    /// Request the storage slots of `account`, whose storage trie has
+    /// `storage_root`, from `origin`.
+    pub fn get_storage_range(
+        &mut self,
+        root: &Root,
+        account: &Hash32,
+        storage_root: &Root,
+        origin: &Hash32,
+    ) -> Result<Leaves, P2pError> {
+        let response = self.snap_request(
+            |request_id| {
+                SnapMessage::GetStorageRanges(GetStorageRanges {
+                    request_id,
+                    root: root.clone(),
+                    accounts: vec![account.clone()],
+                    origin: Bytes(origin.0.to_vec()),
+                    limit: Bytes::default(),
+                    bytes: RESPONSE_BYTES,
+                })
+            },
+            |message, id| match message {
+                SnapMessage::StorageRanges(response) if response.request_id == id => Some(response),
+                _ => None,
+            },
+        )?;
+        let slots = response.slots.into_iter().next().unwrap_or_default();
+        if !slots.windows(2).all(|pair| pair[0].0 < pair[1].0) {
+            return Err(P2pError::Protocol("storage slots out of order"));
+        }
+        let slots = slots.into_iter().collect();
+        verify_range(storage_root, origin, &slots, &response.proof).map_err(P2pError::State)?;
+        Ok(slots)
+    }
+
+    /// Request contract code by hash. The peer may return only some of it.
+    pub fn get_byte_codes(&mut self, hashes: &[Hash32]) -> Result<Vec<Bytes>, P2pError> {
+        let hashes = hashes.to_vec();
+        self.snap_request(
+            |request_id| SnapMessage::GetByteCodes(GetByteCodes { request_id, hashes, bytes: RESPONSE_BYTES }),
+            |message, id| match message {
+                SnapMessage::ByteCodes(response) if response.request_id == id => Some(response.codes),
+                _ => None,
+            },
+        )
+    }
+
+    /// Request trie nodes of the state `root` by path. The peer may return
+    /// only the first ones.
+    pub fn get_trie_nodes(&mut self, root: &Root, paths: Vec<Vec<Bytes>>) -> Result<Vec<Bytes>, P2pError> {
+        self.snap_request(
+            |request_id| {
+                SnapMessage::GetTrieNodes(GetTrieNodes { request_id, root: root.clone(), paths, bytes: RESPONSE_BYTES })
+            },
+            |message, id| match message {
+                SnapMessage::TrieNodes(response) if response.request_id == id => Some(response.nodes),
+                _ => None,
+            },
+        )
+    }
+}
+
+/// Download the state `root` from `peer`.
+pub fn sync_state<S: Read + Write>(peer: &mut Peer<S>, root: &Root) -> Result<SnapState, P2pError> {
+    let mut state = SnapState::default();
+    let mut origin = Some(Hash32::default());
+    while let Some(from) = origin {
+        let accounts = peer.get_account_range(root, &from)?;
+        origin = accounts.keys().next_back().and_then(next_key);
+        state.accounts.extend(accounts);
+    }
+    for (hash, account) in &state.accounts {
+        if account.storage_root == EMPTY_TRIE_ROOT {
+            continue;
+        }
+        let mut storage = Leaves::new();
+        let mut origin = Some(Hash32::default());
+        while let Some(from) = origin {
+            let slots = peer.get_storage_range(root, hash, &account.storage_root, &from)?;
+            origin = slots.keys().next_back().and_then(next_key);
+            storage.extend(slots);
+        }
+        state.storage.insert(hash.clone(), storage);
+    }
+    fetch_codes(peer, &mut state)?;
+    Ok(state)
+}
+
+/// Bring `state` up to date with the state `root` of `peer`.
+pub fn heal<S: Read + Write>(peer: &mut Peer<S>, state: &mut SnapState, root: &Root) -> Result<(), P2pError> {
+    let accounts = heal_trie(peer, root, None, root, state.account_leaves())?;
+    state.accounts.clear();
+    for (hash, encoded) in accounts {
+        state.accounts.insert(hash, rlp::decode_to_strict(&encoded)?);
+    }
+    state.storage.retain(|hash, _| state.accounts.contains_key(hash));
+    for (hash, account) in &state.accounts {
+        let storage = state.storage.remove(hash).unwrap_or_default();
+        let storage = if proof::root(&storage) == account.storage_root {
+            storage
+        } else {
+            heal_trie(peer, root, Some(hash), &account.storage_root, storage)?
+        };
+        if !storage.is_empty() {
+            state.storage.insert(hash.clone(), storage);
+        }
+    }
+    fetch_codes(peer, state)
+}
+
+/// Heal the account trie, or the storage trie of `account`, of the state
+/// `root`.
+fn heal_trie<S: Read + Write>(
+    peer: &mut Peer<S>,
+    root: &Root,
+    account: Option<&Hash32>,
+    trie_root: &Root,
+    leaves: Leaves,
+) -> Result<Leaves, P2pError> {
+    let mut healer = Healer::new(trie_root, leaves);
+    while !healer.is_done() {
+        let missing = healer.missing(BATCH);
+        let paths = missing
+            .iter()
+            .map(|(path, _)| match account {
+                Some(account) => vec![Bytes(account.0.to_vec()), compact_path(path)],
+                None => vec![compact_path(path)],
+            })
+            .collect();
+        let nodes = peer.get_trie_nodes(root, paths)?;
+        if nodes.is_empty() {
+            return Err(P2pError::Protocol("peer returned no trie nodes"));
+        }
+        for ((path, _), node) in missing.iter().zip(&nodes) {
+            healer.insert(path, node).map_err(P2pError::State)?;
+        }
+    }
+    Ok(healer.into_leaves())
+}
+
+/// Fetch the code of the accounts of `state` which is not in it yet.
+fn fetch_codes<S: Read + Write>(peer: &mut Peer<S>, state: &mut SnapState) -> Result<(), P2pError> {
+    let empty = keccak256(&[]);
+    let mut missing: BTreeSet<Hash32> = state
+        .accounts
+        .values()
+        .map(|account| account.code_hash.clone())
+        .filter(|hash| *hash != empty && !state.codes.contains_key(hash))
+        .collect();
+    while !missing.is_empty() {
+        let batch: Vec<Hash32> = missing.iter().take(BATCH).cloned().collect();
+        let codes = peer.get_byte_codes(&batch)?;
+        if codes.is_empty() {
+            return Err(P2pError::Protocol("peer returned no bytecodes"));
+        }
+        for code in codes {
+            let hash = keccak256(&code);
+            if !missing.remove(&hash) {
+                return Err(P2pError::Protocol("peer returned unrequested bytecode"));
+            }
+            state.codes.insert(hash, code);
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{
+        devp2p::{eth::Status, random_secret_key, tests, Peer},
+        prelude::*,
+    };
+
+    use super::{heal, sync_state, SnapState};
+
+    #[test]
+    fn sync_and_heal() {
+        let mut server_chain = tests::chain();
+        let state = &mut server_chain.state;
+        for i in 1..=40_u32 {
+            let address = Address::from_be_bytes(keccak256(&i.to_be_bytes())[12..].try_into().unwrap());
+            let code = Bytes(vec![0x60, (i % 3) as u8]);
+            set_account(state, &address, Some(Account { nonce: i as Uint, balance: U256::from(i), code }));
+            for j in 0..i % 5 {
+                set_storage(state, &address, &Bytes32([j as u8; 32]), U256::from(i * j + 1));
+            }
+        }
+        let expected = SnapState::from_state(&server_chain.state).unwrap();
+        let root = expected.root();
+        let (enode, server) = tests::serve(server_chain);
+
+        let status = Status::new(&tests::chain()).unwrap();
+        let mut peer = Peer::connect(&enode, random_secret_key(), &status).unwrap();
+        assert!(peer.supports_snap());
+        assert_eq!(sync_state(&mut peer, &root).unwrap(), expected);
+
+        // A stale copy gets back what was removed or changed.
+        let mut stale = expected.clone();
+        stale.accounts.pop_first();
+        stale.accounts.values_mut().nth(3).unwrap().balance = U256::from(7_u32);
+        stale.storage.values_mut().next().unwrap().pop_last();
+        stale.codes.clear();
+        heal(&mut peer, &mut stale, &root).unwrap();
+        assert_eq!(stale, expected);
+
+        // An unknown root gets empty responses.
+        assert!(sync_state(&mut peer, &Root([1; 32])).is_err());
+        peer.disconnect(0).unwrap();
+        server.join().unwrap();
+    }
+}
@@ -0,0 +1,289 @@
+//! Snappy block compression, which RLPx applies to the payload of every
+//! message after `Hello` when both peers speak version 5 of the base
+//! protocol.
+//!
+//! A block is the varint length of the uncompressed data followed by
+//! literals and back references. `compress` finds references with a hash
+//! table of four byte sequences, as the reference implementation does,
+//! without trying as hard to find long ones.
+//!
+//! The framing format, used by era1 archives, splits a stream into chunks
+//! of at most 64 KiB, each compressed as a block and checksummed with a
+//! masked CRC-32C of its uncompressed data.
+
+use super::P2pError;
+
+/// Largest message the decoder accepts, the limit of the other clients.
+pub const MAX_UNCOMPRESSED_LEN: usize = 16 * 1024 * 1024;
+
+const TABLE_BITS: u32 = 14;
+
+/// The chunk which starts a framed stream.
+const STREAM_IDENTIFIER: [u8; 10] = [0xff, 0x06, 0x00, 0x00, b's', b'N', b'a', b'P', b'p', b'Y'];
+
+/// Most uncompressed data in one chunk of a framed stream.
+const MAX_CHUNK_LEN: usize = 1 << 16;
+
+const CHUNK_COMPRESSED: u8 = 0x00;
+const CHUNK_UNCOMPRESSED: u8 = 0x01;
+
+/// Compress `data` into a snappy block.
+pub fn compress(data: &[u8]) -> Vec<u8> {
+    let mut out = Vec::with_capacity(data.len() / 2 + 16);
+    write_varint(&mut out, data.len() as u64);
+
+    let mut table = vec![0_usize; 1 << TABLE_BITS];
+    let hash = |i: usize| {
+        let word = u32::from_le_bytes(data[i..i + 4].try_into().unwrap());
+        (word.wrapping_mul(0x1e35a7bd) >> (32 - TABLE_BITS)) as usize
+    };
+    let mut literal_start = 0;
+    let mut i = 0;
+    while i + 4 <= data.len() {
+        let h = hash(i);
+        // Positions are stored plus one so that zero is empty.
+        let candidate = table[h].checked_sub(1);
+        table[h] = i + 1;
+        match candidate {
+            Some(candidate) if i - candidate <= 0xffff && data[candidate..candidate + 4] == data[i..i + 4] => {
+                let mut length = 4;
+                while i + length < data.len() && data[candidate + length] == data[i + length] {
+                    length += 1;
+                }
+                write_literal(&mut out, &data[literal_start..i]);
+                write_copy(&mut out, i - candidate, length);
+                i += length;
+                literal_start = i;
+            }
+            _ => i += 1,
+        }
+    }
+    write_literal(&mut out, &data[literal_start..]);
+    out
+}
+
+/// Decompress a snappy block.
+pub fn decompress(block: &[u8]) -> Result<Vec<u8>, P2pError> {
+    const CORRUPT: P2pError = P2pError::Protocol("corrupt snappy block");
+    let mut input = block;
+    let length = read_varint(&mut input).ok_or(CORRUPT)?;
+    if length > MAX_UNCOMPRESSED_LEN as u64 {
+        return Err(P2pError::Protocol("message too large"));
+    }
+    let length = length as usize;
+    let mut out = Vec::with_capacity(length);
+    while let Some((&tag, rest)) = input.split_first() {
+        input = rest;
+        let (offset, copy_length) = match tag & 3 {
+            0 => {
+                let mut literal_length = (tag >> 2) as usize + 1;
+                if literal_length > 60 {
+                    let n = literal_length - 60;
+                    let bytes = input.get(..n).ok_or(CORRUPT)?;
+                    literal_length = bytes.iter().rev().fold(0, |acc, b| acc << 8 | *b as usize) + 1;
+                    input = &input[n..];
+                }
+                let literal = input.get(..literal_length).ok_or(CORRUPT)?;
+                out.extend_from_slice(literal);
+                input = &input[literal_length..];
+                if out.len() > length {
+                    return Err(CORRUPT);
+                }
+                continue;
+            }
+            1 => {
+                let low = *input.first().ok_or(CORRUPT)? as usize;
+                input = &input[1..];
+                (((tag as usize) >> 5) << 8 | low, ((tag >> 2) & 7) as usize + 4)
+            }
+            2 => {
+                let bytes = input.get(..2).ok_or(CORRUPT)?;
+                input = &input[2..];
+                (u16::from_le_bytes(bytes.try_into().unwrap()) as usize, (tag >> 2) as usize + 1)
+            }
+            _ => {
+                let bytes = input.get(..4).ok_or(CORRUPT)?;
+                input = &input[4..];
+                (u32::from_le_bytes(bytes.try_into().unwrap()) as usize, (tag >> 2) as usize + 1)
+            }
+        };
+        if offset == 0 || offset > out.len() || out.len() + copy_length > length {
+            return Err(CORRUPT);
+        }
+        // The copy may overlap what it appends, so it goes byte by byte.
+        let start = out.len() - offset;
+        for j in 0..copy_length {
+            out.push(out[start + j]);
+        }
+    }
+    if out.len() != length {
+        return Err(CORRUPT);
+    }
+    Ok(out)
+}
+
+/// Compress `data` into a framed stream.
+pub fn compress_framed(data: &[u8]) -> Vec<u8> {
+    let mut out = STREAM_IDENTIFIER.to_vec();
+    for chunk in data.chunks(MAX_CHUNK_LEN) {
+        let compressed = compress(chunk);
+        // Data which does not compress is stored as it is.
+        let (kind, body) = if compressed.len() < chunk.len() {
+            (CHUNK_COMPRESSED, &compressed[..])
+        } else {
+            (CHUNK_UNCOMPRESSED, chunk)
+        };
+        out.push(kind);
+        out.extend(&((body.len() + 4) as u32).to_le_bytes()[..3]);
+        out.extend(masked_crc32c(chunk).to_le_bytes());
+        out.extend(body);
+    }
+    out
+}
+
+/// Decompress a framed stream.
+pub fn decompress_framed(stream: &[u8]) -> Result<Vec<u8>, P2pError> {
+    const CORRUPT: P2pError = P2pError::Protocol("corrupt snappy stream");
+    if !stream.starts_with(&STREAM_IDENTIFIER) {
+        return Err(CORRUPT);
+    }
+    let mut input = &stream[STREAM_IDENTIFIER.len()..];
+    let mut out = Vec::new();
+    while !input.is_empty() {
+        let header = input.get(..4).ok_or(CORRUPT)?;
+        let length = u32::from_le_bytes([header[1], header[2], header[3], 0]) as usize;
+        let body = input.get(4..4 + length).ok_or(CORRUPT)?;
+        match header[0] {
+            CHUNK_COMPRESSED | CHUNK_UNCOMPRESSED => {
+                let checksum = u32::from_le_bytes(body.get(..4).ok_or(CORRUPT)?.try_into().unwrap());
+                let data = match header[0] {
+                    CHUNK_COMPRESSED => decompress(&body[4..])?,
+                    _ => body[4..].to_vec(),
+                };
+                if data.len() > MAX_CHUNK_LEN || masked_crc32c(&data) != checksum {
+                    return Err(CORRUPT);
+                }
+                if out.len() + data.len() > MAX_UNCOMPRESSED_LEN {
+                    return Err(P2pError::Protocol("message too large"));
+                }
+                out.extend(data);
+            }
+            // Repeated stream identifiers, padding and other skippable
+            // chunks.
+            0x80..=0xff => {}
+            _ => return Err(CORRUPT),
+        }
+        input = &input[4 + length..];
+    }
+    Ok(out)
+}
+
+/// The CRC-32C of `data`, rotated and offset as the framing format masks
+/// it.
+fn masked_crc32c(data: &[u8]) -> u32 {
+    const TABLE: [u32; 256] = {
+        let mut table = [0; 256];
+        let mut i = 0;
+        while i < 256 {
+            let mut crc = i as u32;
+            let mut bit = 0;
+            while bit < 8 {
+                crc = if crc & 1 == 1 { (crc >> 1) ^ 0x82f6_3b78 } else { crc >> 1 };
+                bit += 1;
+            }
+            table[i] = crc;
+            i += 1;
+        }
+        table
+    };
+    let mut crc = !0_u32;
+    for byte in data {
+        crc = TABLE[((crc ^ *byte as u32) & 0xff) as usize] ^ (crc >> 8);
+    }
+    let crc = !crc;
+    crc.rotate_right(15).wrapping_add(0xa282_ead8)
+}
+
+fn write_varint(out: &mut Vec<u8>, mut n: u64) {
+    while n >= 0x80 {
+        out.push(n as u8 | 0x80);
+        n >>= 7;
+    }
+    out.push(n as u8);
+}
+
+fn read_varint(input: &mut &[u8]) -> Option<u64> {
+    let mut n = 0;
+    for shift in (0..64).step_by(7) {
+        let (&b, rest) = input.split_first()?;
+        *input = rest;
+        n |= ((b & 0x7f) as u64) << shift;
+        if b < 0x80 {
+            return Some(n);
+        }
+    }
+    None
+}
+
+fn write_literal(out: &mut Vec<u8>, literal: &[u8]) {
+    if literal.is_empty() {
+        return;
+    }
+    let n = literal.len() - 1;
+    if n < 60 {
+        out.push((n as u8) << 2);
+    } else {
+        // The length follows the tag in one to four little endian bytes.
+        let len = (1..4).find(|len| n >> (8 * len) == 0).unwrap_or(4);
+        out.push(((59 + len) as u8) << 2);
+        out.extend_from_slice(&(n as u32).to_le_bytes()[..len]);
+    }
+    out.extend_from_slice(literal);
+}
+
+/// Write a back reference in pieces of at most 64 bytes, using the two
+/// byte offset form.
+fn write_copy(out: &mut Vec<u8>, offset: usize, mut length: usize) {
+    while length > 0 {
+        let piece = length.min(64);
+        out.push((((piece - 1) as u8) << 2) | 2);
+        out.extend_from_slice(&(offset as u16).to_le_bytes());
+        length -= piece;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{compress, compress_framed, decompress, decompress_framed, masked_crc32c};
+
+    #[test]
+    fn round_trip() {
+        let repetitive = b"abcdefgh".repeat(100);
+        let mixed: Vec<u8> = (0..5000_u32).map(|i| (i * i % 251) as u8).collect();
+        for data in [&b""[..], b"a", b"abcd", &repetitive, &mixed, &[0; 70000]] {
+            let compressed = compress(data);
+            assert_eq!(decompress(&compressed).unwrap(), data);
+        }
+        assert!(compress(&repetitive).len() < 64);
+
+        // A literal followed by a copy which overlaps itself.
+        assert_eq!(decompress(&[0x0a, 0x00, b'a', 0x15, 0x01]).unwrap(), b"aaaaaaaaaa");
+        assert!(decompress(&[0x0a, 0x00, b'a']).is_err());
+        assert!(decompress(&[0x02, 0x05, 0x01]).is_err());
+    }
+
+    #[test]
+    fn framed() {
+        // The CRC-32C check value, before masking.
+        assert_eq!(masked_crc32c(b"123456789"), 0xe306_9283_u32.rotate_right(15).wrapping_add(0xa282_ead8));
+
+        let mixed: Vec<u8> = (0..200_000_u64).map(|i| (i * i % 251) as u8).collect();
+        for data in [&b""[..], b"abc", &[7; 100_000], &mixed] {
+            assert_eq!(decompress_framed(&compress_framed(data)).unwrap(), data);
+        }
+        let mut stream = compress_framed(b"abcdefgh");
+        assert!(decompress_framed(&stream[1..]).is_err());
+        *stream.last_mut().unwrap() ^= 1;
+        assert!(decompress_framed(&stream).is_err());
+    }
+}
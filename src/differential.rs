@@ -0,0 +1,547 @@
+//! Differential testing of the interpreter against revm.
+//!
+//! `compare` runs a `Case`, a transaction calling code with some storage,
+//! through the crate's own interpreter and through revm in the same
+//! Cancun environment, and compares the outcome, the gas used, the output,
+//! the logs and the accounts and storage slots revm reports as loaded. When
+//! they differ, the `Divergence` also points at the first instruction at
+//! which the traces of the two parted ways:
+//!
+//! ```
+//! use ejit_evm::differential::{compare, random_code, Case};
+//!
+//! for seed in 0..20 {
+//!     if let Err(divergence) = compare(&Case::new(random_code(seed, 64))) {
+//!         panic!("seed {seed}: {divergence}");
+//!     }
+//! }
+//! ```
+//!
+//! Slots written by one side are compared only if revm loaded them, which
+//! it does for every slot it reads or writes, so a write the other side
+//! does not make shows up in the storage unless the traces differ first.
+//!
+//! The ejit backend is meant to be a third leg, compared in the same way,
+//! once `ejit_evm` builds. This module needs the `revm` feature.
+
+use std::{collections::BTreeMap, fmt};
+
+use revm::{
+    db::{CacheDB, EmptyDB},
+    inspector_handle_register,
+    interpreter::Interpreter,
+    primitives::{AccountInfo, BlobExcessGasAndPrice, Bytecode, ExecutionResult, SpecId, TxKind, B256},
+    Database, Evm, EvmContext, Inspector,
+};
+
+use crate::ethereum::{
+    cancun::{
+        blocks::Log,
+        fork::process_transaction,
+        fork_types::{Account, Address},
+        state::{get_account, get_storage, set_account, set_storage, State, TransientStorage},
+        transactions::{LegacyTransaction, Transaction},
+        vm::{
+            exceptions::VmError,
+            instructions::Ops,
+            tracing::{Step, Tracer},
+            Environment,
+        },
+    },
+    crypto::hash::{keccak256, Hash32},
+    ethereum_types::{
+        bytes::{Bytes, Bytes32},
+        numeric::{Uint, U256},
+    },
+    forks::ExecutionRules,
+};
+
+/// The account which sends the transaction of a case.
+pub const CALLER: Address = Address::from_be_bytes([0xca; 20]);
+/// The account whose code a case calls.
+pub const CONTRACT: Address = Address::from_be_bytes([0xc0; 20]);
+/// The miner of the block of a case.
+pub const COINBASE: Address = Address::from_be_bytes([0xcb; 20]);
+
+const NUMBER: Uint = 1;
+const TIMESTAMP: u64 = 1000;
+const GAS_LIMIT: Uint = 30_000_000;
+const BASE_FEE: Uint = 7;
+const GAS_PRICE: Uint = 10;
+const PREV_RANDAO: [u8; 32] = [0x11; 32];
+const CALLER_BALANCE: u64 = 10_u64.pow(19);
+
+/// A transaction from `CALLER` to `CONTRACT`, which has `code` and
+/// `storage`.
+#[derive(Debug, Clone)]
+pub struct Case {
+    pub code: Bytes,
+    pub storage: Vec<(Bytes32, U256)>,
+    pub data: Bytes,
+    pub value: U256,
+    pub gas: Uint,
+}
+
+impl Case {
+    /// A call to `code` with no data, no value and 100000 gas.
+    pub fn new(code: Bytes) -> Self {
+        Self { code, storage: Vec::new(), data: Bytes::default(), value: U256::ZERO, gas: 100_000 }
+    }
+}
+
+/// How a transaction ended.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Status {
+    Success,
+    Revert,
+    /// An exceptional halt, which uses all the gas.
+    Halt,
+    /// The transaction was rejected.
+    Invalid,
+}
+
+/// An account after a transaction.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct AccountOutcome {
+    pub nonce: Uint,
+    pub balance: U256,
+    pub code: Bytes,
+    pub storage: BTreeMap<Bytes32, U256>,
+}
+
+/// What a transaction did, as seen by one of the two.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Outcome {
+    pub status: Status,
+    pub gas_used: Uint,
+    pub output: Bytes,
+    pub logs: Vec<Log>,
+    pub accounts: BTreeMap<Address, AccountOutcome>,
+}
+
+/// An instruction as it was run, reduced to what both traces have.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct TraceStep {
+    /// Depth of the frame, one for the message of the transaction.
+    pub depth: Uint,
+    pub pc: Uint,
+    pub op: u8,
+    /// Gas left before the instruction.
+    pub gas: Uint,
+}
+
+/// A case on which the interpreter and revm disagree.
+#[derive(Debug, Clone)]
+pub struct Divergence {
+    pub interpreter: Outcome,
+    pub revm: Outcome,
+    /// The index of the first step which differs, if the traces do.
+    pub step: Option<usize>,
+    pub interpreter_trace: Vec<TraceStep>,
+    pub revm_trace: Vec<TraceStep>,
+}
+
+impl fmt::Display for Divergence {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self.step {
+            Some(i) => {
+                let describe = |step: Option<&TraceStep>| match step {
+                    Some(step) => {
+                        let name = Ops::from_u8(step.op).map_or("INVALID", Ops::name);
+                        format!("{name} at pc {} depth {} with {} gas", step.pc, step.depth, step.gas)
+                    }
+                    None => "the end of the trace".to_string(),
+                };
+                writeln!(f, "the traces diverge at step {i}:")?;
+                writeln!(f, "  interpreter: {}", describe(self.interpreter_trace.get(i)))?;
+                writeln!(f, "  revm:        {}", describe(self.revm_trace.get(i)))?;
+            }
+            None => writeln!(f, "the traces agree, but the outcomes do not:")?,
+        }
+        writeln!(f, "  interpreter: {:?}", self.interpreter)?;
+        write!(f, "  revm:        {:?}", self.revm)
+    }
+}
+
+/// Run `case` through the interpreter and revm and compare what they did.
+pub fn compare(case: &Case) -> Result<Outcome, Box<Divergence>> {
+    let (revm, revm_trace) = run_revm(case);
+    let (interpreter, interpreter_trace) = run_interpreter(case, &revm.accounts);
+    if interpreter == revm {
+        return Ok(interpreter);
+    }
+    let step = first_divergence(&interpreter_trace, &revm_trace);
+    Err(Box::new(Divergence { interpreter, revm, step, interpreter_trace, revm_trace }))
+}
+
+/// The index of the first step at which `a` and `b` differ, including one
+/// ending before the other.
+pub fn first_divergence(a: &[TraceStep], b: &[TraceStep]) -> Option<usize> {
+    (0..a.len().max(b.len())).find(|&i| a.get(i) != b.get(i))
+}
+
+/// Code of about `len` instructions for fuzzing, generated from `seed`.
+///
+/// The instructions are those which depend only on the environment of a
+/// case. Small values are pushed for the operands an instruction needs, so
+/// that most code runs to its end and memory offsets, jumps and slots
+/// often hit something.
+pub fn random_code(seed: u64, len: usize) -> Bytes {
+    const OPS: &[u8] = &[
+        0x01, 0x02, 0x03, 0x04, 0x05, 0x06, 0x07, 0x08, 0x09, 0x0a, 0x0b, 0x10, 0x11, 0x12, 0x13, 0x14, 0x15,
+        0x16, 0x17, 0x18, 0x19, 0x1a, 0x1b, 0x1c, 0x1d, 0x20, 0x30, 0x31, 0x32, 0x33, 0x34, 0x35, 0x36, 0x37,
+        0x38, 0x39, 0x3b, 0x3d, 0x3f, 0x40, 0x41, 0x42, 0x43, 0x44, 0x45, 0x46, 0x47, 0x48, 0x49, 0x4a, 0x50,
+        0x51, 0x52, 0x53, 0x54, 0x55, 0x56, 0x57, 0x58, 0x59, 0x5a, 0x5b, 0x5c, 0x5d, 0x5e, 0x5f, 0x80, 0x81,
+        0x82, 0x83, 0x84, 0x85, 0x86, 0x87, 0x88, 0x89, 0x8a, 0x8b, 0x8c, 0x8d, 0x8e, 0x8f, 0x90, 0x91, 0x92,
+        0x93, 0x94, 0x95, 0x96, 0x97, 0x98, 0x99, 0x9a, 0x9b, 0x9c, 0x9d, 0x9e, 0x9f, 0xa0, 0xa1, 0xa2, 0xf3,
+        0xfd,
+    ];
+    // xorshift64*, which is plenty for picking instructions.
+    let mut state = seed.wrapping_mul(0x9e37_79b9_7f4a_7c15) | 1;
+    let mut next = move || {
+        state ^= state >> 12;
+        state ^= state << 25;
+        state ^= state >> 27;
+        state.wrapping_mul(0x2545_f491_4f6c_dd1d)
+    };
+    let mut code = Vec::new();
+    let mut depth = 0;
+    for _ in 0..len {
+        let r = next();
+        if r % 16 == 0 {
+            code.push(0x7f);
+            code.extend((0..4).flat_map(|_| next().to_be_bytes()));
+            depth += 1;
+            continue;
+        }
+        let op = OPS[(r >> 8) as usize % OPS.len()];
+        let (inputs, outputs) = stack_effect(op);
+        for _ in depth..inputs {
+            code.extend([0x60, (next() >> 8) as u8 % 64]);
+            depth += 1;
+        }
+        code.push(op);
+        depth = depth - inputs + outputs;
+    }
+    Bytes(code)
+}
+
+/// The items the instructions of `random_code` take from the stack and
+/// put on it.
+fn stack_effect(op: u8) -> (usize, usize) {
+    match op {
+        0x08 | 0x09 => (3, 1),
+        0x15 | 0x19 | 0x31 | 0x35 | 0x3b | 0x3f | 0x40 | 0x49 | 0x51 | 0x54 | 0x5c => (1, 1),
+        0x01..=0x20 => (2, 1),
+        0x37 | 0x39 | 0x5e | 0xa1 => (3, 0),
+        0x50 | 0x56 => (1, 0),
+        0x52 | 0x53 | 0x55 | 0x57 | 0x5d | 0xa0 | 0xf3 | 0xfd => (2, 0),
+        0x5b => (0, 0),
+        0x80..=0x8f => (op as usize - 0x7f, op as usize - 0x7e),
+        0x90..=0x9f => (op as usize - 0x8e, op as usize - 0x8e),
+        0xa2 => (4, 0),
+        _ => (0, 1),
+    }
+}
+
+fn revm_address(address: &Address) -> revm::primitives::Address {
+    revm::primitives::Address::from_slice(&address[..])
+}
+
+fn revm_u256(value: U256) -> revm::primitives::U256 {
+    revm::primitives::U256::from_be_bytes(value.to_be_bytes())
+}
+
+fn from_revm_u256(value: revm::primitives::U256) -> U256 {
+    U256::from_be_bytes(value.to_be_bytes())
+}
+
+/// Collects the steps of revm.
+#[derive(Default)]
+struct RevmTrace(Vec<TraceStep>);
+
+impl<DB: Database> Inspector<DB> for RevmTrace {
+    fn step(&mut self, interp: &mut Interpreter, context: &mut EvmContext<DB>) {
+        self.0.push(TraceStep {
+            depth: context.journaled_state.depth() as Uint,
+            pc: interp.program_counter() as Uint,
+            op: interp.current_opcode(),
+            gas: interp.gas.remaining() as Uint,
+        });
+    }
+}
+
+fn run_revm(case: &Case) -> (Outcome, Vec<TraceStep>) {
+    let mut db = CacheDB::new(EmptyDB::default());
+    let caller = AccountInfo { balance: revm::primitives::U256::from(CALLER_BALANCE), ..Default::default() };
+    db.insert_account_info(revm_address(&CALLER), caller);
+    let code = Bytecode::new_raw(case.code.0.clone().into());
+    let contract = AccountInfo { code_hash: code.hash_slow(), code: Some(code), ..Default::default() };
+    db.insert_account_info(revm_address(&CONTRACT), contract);
+    for (key, value) in &case.storage {
+        let key = revm::primitives::U256::from_be_bytes(key.0);
+        db.insert_account_storage(revm_address(&CONTRACT), key, revm_u256(*value)).unwrap();
+    }
+
+    let mut evm = Evm::builder()
+        .with_db(db)
+        .with_external_context(RevmTrace::default())
+        .modify_cfg_env(|cfg| cfg.chain_id = 1)
+        .modify_block_env(|block| {
+            block.number = revm::primitives::U256::from(NUMBER);
+            block.coinbase = revm_address(&COINBASE);
+            block.timestamp = revm::primitives::U256::from(TIMESTAMP);
+            block.gas_limit = revm::primitives::U256::from(GAS_LIMIT);
+            block.basefee = revm::primitives::U256::from(BASE_FEE);
+            block.prevrandao = Some(B256::from(PREV_RANDAO));
+            block.blob_excess_gas_and_price = Some(BlobExcessGasAndPrice::new(0));
+        })
+        .modify_tx_env(|tx| {
+            tx.caller = revm_address(&CALLER);
+            tx.gas_limit = case.gas as u64;
+            tx.gas_price = revm::primitives::U256::from(GAS_PRICE);
+            tx.transact_to = TxKind::Call(revm_address(&CONTRACT));
+            tx.value = revm_u256(case.value);
+            tx.data = case.data.0.clone().into();
+            tx.nonce = Some(0);
+        })
+        .append_handler_register(inspector_handle_register)
+        .with_spec_id(SpecId::CANCUN)
+        .build();
+    let result = evm.transact();
+    let trace = std::mem::take(&mut evm.context.external.0);
+
+    let Ok(result) = result else {
+        let outcome = Outcome {
+            status: Status::Invalid,
+            gas_used: 0,
+            output: Bytes::default(),
+            logs: Vec::new(),
+            accounts: BTreeMap::new(),
+        };
+        return (outcome, trace);
+    };
+    let gas_used = result.result.gas_used() as Uint;
+    let (status, output, logs) = match result.result {
+        ExecutionResult::Success { output, logs, .. } => (Status::Success, output.into_data(), logs),
+        ExecutionResult::Revert { output, .. } => (Status::Revert, output, Vec::new()),
+        ExecutionResult::Halt { .. } => (Status::Halt, Default::default(), Vec::new()),
+    };
+    let output = Bytes(output.to_vec());
+    let logs = logs
+        .into_iter()
+        .map(|log| Log {
+            address: Address::from_be_bytes(log.address.0 .0),
+            topics: log.topics().iter().map(|topic| Hash32(topic.0)).collect(),
+            data: Bytes(log.data.data.to_vec()),
+        })
+        .collect();
+    let accounts = result
+        .state
+        .into_iter()
+        .map(|(address, account)| {
+            let address = Address::from_be_bytes(address.0 .0);
+            if account.is_selfdestructed() {
+                return (address, AccountOutcome::default());
+            }
+            let code = account.info.code.map(|code| Bytes(code.original_bytes().to_vec())).unwrap_or_default();
+            let storage = account
+                .storage
+                .into_iter()
+                .map(|(key, slot)| (Bytes32(key.to_be_bytes()), from_revm_u256(slot.present_value)))
+                .collect();
+            let balance = from_revm_u256(account.info.balance);
+            (address, AccountOutcome { nonce: account.info.nonce as Uint, balance, code, storage })
+        })
+        .collect();
+    (Outcome { status, gas_used, output, logs, accounts }, trace)
+}
+
+/// Collects the steps of the interpreter and the output of the
+/// transaction.
+#[derive(Default)]
+struct InterpreterTrace {
+    steps: Vec<TraceStep>,
+    output: Bytes,
+}
+
+impl Tracer for InterpreterTrace {
+    fn step(&mut self, step: &Step) {
+        self.steps.push(TraceStep { depth: step.depth, pc: step.pc, op: step.op as u8, gas: step.gas });
+    }
+
+    fn exit(&mut self, output: &Bytes, _: Uint, _: Option<&VmError>) {
+        // The last call to end is the message of the transaction.
+        self.output = output.clone();
+    }
+}
+
+/// Run `case` in the interpreter, reporting the accounts and slots which
+/// revm loaded, those of `loaded`.
+fn run_interpreter(case: &Case, loaded: &BTreeMap<Address, AccountOutcome>) -> (Outcome, Vec<TraceStep>) {
+    let mut state = State::default();
+    set_account(&mut state, &CALLER, Some(Account { balance: U256::from(CALLER_BALANCE), ..Default::default() }));
+    set_account(&mut state, &CONTRACT, Some(Account { code: case.code.clone(), ..Default::default() }));
+    for (key, value) in &case.storage {
+        set_storage(&mut state, &CONTRACT, key, *value);
+    }
+
+    let mut trace = InterpreterTrace::default();
+    let tx = Transaction::LegacyTransaction(LegacyTransaction {
+        gas_price: GAS_PRICE,
+        gas: case.gas,
+        to: Some(CONTRACT).into(),
+        value: case.value,
+        data: case.data.clone(),
+        ..Default::default()
+    });
+    let mut env = Environment {
+        caller: CALLER,
+        // EmptyDB of revm hashes the decimal block number.
+        block_hashes: vec![keccak256(b"0")],
+        origin: CALLER,
+        coinbase: COINBASE,
+        number: NUMBER,
+        base_fee_per_gas: BASE_FEE,
+        gas_limit: GAS_LIMIT,
+        gas_price: GAS_PRICE,
+        time: U256::from(TIMESTAMP),
+        prev_randao: Bytes32(PREV_RANDAO),
+        tracer: Some(&mut trace),
+        ..Environment::new(&mut state, ExecutionRules::CANCUN)
+    };
+    let result = process_transaction(&mut env, &tx);
+    drop(env);
+
+    let (status, gas_used, logs) = match result {
+        Ok((gas_used, logs, None)) => (Status::Success, gas_used, logs),
+        Ok((gas_used, _, Some(VmError::Revert))) => (Status::Revert, gas_used, Vec::new()),
+        Ok((gas_used, _, Some(_))) => (Status::Halt, gas_used, Vec::new()),
+        Err(_) => {
+            let outcome = Outcome {
+                status: Status::Invalid,
+                gas_used: 0,
+                output: Bytes::default(),
+                logs: Vec::new(),
+                accounts: BTreeMap::new(),
+            };
+            return (outcome, trace.steps);
+        }
+    };
+    let output = if status == Status::Halt { Bytes::default() } else { trace.output };
+    let accounts = loaded
+        .iter()
+        .map(|(address, loaded)| {
+            let Account { nonce, balance, code } = get_account(&state, address).into_owned();
+            let storage = loaded.storage.keys().map(|key| (*key, get_storage(&state, address, key))).collect();
+            (address.clone(), AccountOutcome { nonce, balance, code, storage })
+        })
+        .collect();
+    (Outcome { status, gas_used, output, logs, accounts }, trace.steps)
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::ethereum::ethereum_types::{
+        bytes::{Bytes, Bytes32},
+        numeric::U256,
+    };
+
+    use super::{compare, first_divergence, random_code, Case, Divergence, Status, TraceStep};
+
+    fn slot(n: u8) -> Bytes32 {
+        Bytes32(U256::from(n as u32).to_be_bytes())
+    }
+
+    /// Cases which each exercise one part of a transaction.
+    fn fixtures() -> Vec<(&'static str, Case)> {
+        let case = |code: &[u8]| Case::new(Bytes(code.to_vec()));
+        // Each of the block's fields stored at the next word of memory,
+        // then returned.
+        let mut environment = Vec::new();
+        for (i, op) in [0x41, 0x42, 0x43, 0x44, 0x45, 0x46, 0x47, 0x48, 0x4a].into_iter().enumerate() {
+            let offset = 32 * i as u16;
+            environment.extend([op, 0x61, (offset >> 8) as u8, offset as u8, 0x52]);
+        }
+        environment.extend([0x60, 0x00, 0x40, 0x61, 0x01, 0x20, 0x52, 0x61, 0x01, 0x40, 0x60, 0x00, 0xf3]);
+        vec![
+            // PUSH1 0 SLOAD PUSH1 1 ADD PUSH1 0 SSTORE STOP
+            ("counter", Case { storage: vec![(slot(0), U256::from(41_u32))], ..case(&[0x60, 0x00, 0x54, 0x60, 0x01, 0x01, 0x60, 0x00, 0x55, 0x00]) }),
+            // PUSH1 0 PUSH1 0 SSTORE, which is refunded.
+            ("refund", Case { storage: vec![(slot(0), U256::from(5_u32))], ..case(&[0x60, 0x00, 0x60, 0x00, 0x55]) }),
+            // PUSH1 42 PUSH1 0 MSTORE PUSH1 32 PUSH1 0 REVERT
+            ("revert", case(&[0x60, 0x2a, 0x60, 0x00, 0x52, 0x60, 0x20, 0x60, 0x00, 0xfd])),
+            // PUSH1 0xaa PUSH1 0 MSTORE PUSH1 1 PUSH1 32 PUSH1 0 LOG1
+            ("log", case(&[0x60, 0xaa, 0x60, 0x00, 0x52, 0x60, 0x01, 0x60, 0x20, 0x60, 0x00, 0xa1])),
+            // PUSH1 32 PUSH1 0 KECCAK256 PUSH1 0 MSTORE PUSH1 32 PUSH1 0 RETURN
+            ("keccak", case(&[0x60, 0x20, 0x60, 0x00, 0x20, 0x60, 0x00, 0x52, 0x60, 0x20, 0x60, 0x00, 0xf3])),
+            ("invalid", case(&[0xfe])),
+            // JUMPDEST PUSH1 0 JUMP
+            ("out of gas", case(&[0x5b, 0x60, 0x00, 0x56])),
+            // A call to the identity precompile with 42 in memory, returning
+            // what it returned.
+            (
+                "precompile",
+                case(&[
+                    0x60, 0x2a, 0x60, 0x00, 0x52, 0x60, 0x20, 0x60, 0x00, 0x60, 0x20, 0x60, 0x00, 0x60, 0x00, 0x60,
+                    0x04, 0x5a, 0xf1, 0x50, 0x60, 0x20, 0x60, 0x00, 0xf3,
+                ]),
+            ),
+            // CALLVALUE PUSH1 0 CALLDATALOAD ADD PUSH1 0 MSTORE PUSH1 32 PUSH1 0 RETURN
+            (
+                "value and data",
+                Case {
+                    value: U256::from(1000_u32),
+                    data: Bytes(vec![0x12; 32]),
+                    ..case(&[0x34, 0x60, 0x00, 0x35, 0x01, 0x60, 0x00, 0x52, 0x60, 0x20, 0x60, 0x00, 0xf3])
+                },
+            ),
+            ("environment", case(&environment)),
+            ("intrinsic gas", Case { gas: 20_000, ..case(&[0x00]) }),
+        ]
+    }
+
+    #[test]
+    fn fixtures_agree() {
+        let mut statuses = Vec::new();
+        for (name, case) in fixtures() {
+            match compare(&case) {
+                Ok(outcome) => statuses.push(outcome.status),
+                Err(divergence) => panic!("{name}: {divergence}"),
+            }
+        }
+        for status in [Status::Success, Status::Revert, Status::Halt, Status::Invalid] {
+            assert!(statuses.contains(&status));
+        }
+    }
+
+    #[test]
+    fn random_code_agrees() {
+        for seed in 0..300 {
+            if let Err(divergence) = compare(&Case::new(random_code(seed, 48))) {
+                panic!("seed {seed}: {divergence}");
+            }
+        }
+    }
+
+    #[test]
+    fn divergent_step() {
+        let step = |pc, op, gas| TraceStep { depth: 1, pc, op, gas };
+        let a = [step(0, 0x60, 100), step(2, 0x01, 97)];
+        let b = [step(0, 0x60, 100), step(2, 0x01, 95)];
+        assert_eq!(first_divergence(&a, &b), Some(1));
+        assert_eq!(first_divergence(&a, &a[..1]), Some(1));
+        assert_eq!(first_divergence(&a, &a), None);
+
+        let outcome = compare(&Case::new(Bytes(vec![0x00]))).unwrap();
+        let divergence = Divergence {
+            interpreter: outcome.clone(),
+            revm: outcome,
+            step: Some(1),
+            interpreter_trace: a.to_vec(),
+            revm_trace: b.to_vec(),
+        };
+        let report = divergence.to_string();
+        assert!(report.contains("step 1:\n  interpreter: ADD at pc 2 depth 1 with 97 gas\n  revm:        ADD at pc 2 depth 1 with 95"));
+    }
+}
@@ -1,501 +1,1244 @@
-use std::collections::VecDeque;
+//! A compiler from EVM code to register code, for the hybrid executor.
+//!
+//! `Compiler` turns the code of hot contracts into a `Program` of
+//! straight-line segments, see `ir`. Within a segment, stack items are
+//! tracked at compile time by a `VStack`: pushes, `DUPn`, `SWAPn` and `POP`
+//! cost nothing at run time, arithmetic reads its operands from constants,
+//! registers or the stack and writes a register, and the stack is written
+//! once at the end of the segment. Gas and stack depth are checked once
+//! per segment. There are `NUM_REGS` registers: when they all hold items,
+//! the vstack is spilled to the stack, and its items read back from there.
+//!
+//! Arithmetic on constants is done at compile time, as is arithmetic one
+//! of whose operands decides the result, such as `x + 0`. Instructions
+//! whose results are not read, because they are popped, are left out.
+//! The compiler knows how many bits each register can have, and runs
+//! instructions whose operands and result fit in 64 bits on 64 bits.
+//!
+//! Registers hold whole 256-bit items, worked on as four 64-bit limbs.
+//! Moving them to AVX2 registers for the bitwise instructions and the byte
+//! swaps of memory words measured twice as slow as the limbs, since each
+//! instruction pays a load and a store.
+//!
+//! Memory is read and written in place. The gas of growing it is charged
+//! by the instruction, and if there is not enough, the instruction is left
+//! to the interpreter with the gas the segment charged for it and the
+//! instructions after it.
+//!
+//! Hashes, and copies from call data, code and return data, charge the
+//! gas of the words they read and grow memory as memory instructions do.
+//! Storage is read and written in place as well, with the gas of `SLOAD`
+//! and `SSTORE` worked out by `sload_gas` and `sstore_gas` of the
+//! interpreter, and left to the interpreter in the same way if there is
+//! not enough of it or the frame may not write.
+//!
+//! Values of the environment, such as `CALLER` or `NUMBER`, are read from
+//! the frame once, and kept in the `InterpreterState` for later reads.
+//!
+//! Message calls, and `GAS` which reads the exact gas left, end the
+//! segment. The stack is written, the instruction is run by its
+//! implementation in `instructions`, which calls `process_message` and so
+//! runs the callee compiled if it is hot, and the status it pushes is read
+//! back from the stack by the next segment.
+//!
+//! `STOP`, `RETURN`, `REVERT` and undefined instructions end the frame in
+//! compiled code, with a `Status` which gives what the frame returns, and
+//! the output read from memory. `SELFDESTRUCT` is run as message calls
+//! are, and ends the frame.
+//!
+//! Segments end at jumps and start at each `JUMPDEST`, so that the stack
+//! is written at every jump. Jumps to a constant go straight to the segment
+//! of their `JUMPDEST`, and other jumps look it up in a jump table.
+//!
+//! Whatever compiled code cannot do exactly as the interpreter does, for
+//! instance an instruction without a translation, it leaves to the
+//! interpreter: the stack is written and `interpret` runs the rest of the
+//! frame from that instruction.
+//!
+//! With the `revm` feature, `EjitEvm` runs whole transactions against a
+//! revm database with this compiler, see `transact`.
+//!
+//! The ejit code generator is not available to this crate, so the register
+//! code runs in `runtime::execute` rather than as machine code.
 
-use ejit::{cpu_info, Cond, CpuInfo, EntryInfo, Executable, Ins, Src, Type, Vsize, R, V};
+use std::{collections::BTreeSet, fmt};
 
-use revm::{interpreter::Interpreter, primitives::{EVMResult, TxKind}, Database, Evm};
+use crate::ethereum::{
+    cancun::vm::{
+        exceptions::VmError,
+        gas::{GAS_BASE, GAS_FAST_STEP, GAS_HIGH, GAS_JUMPDEST, GAS_LOW, GAS_KECCAK256, GAS_MID, GAS_VERY_LOW, GAS_WARM_ACCESS},
+        hybrid::{self, Compiled},
+        instructions::Ops,
+        interpreter::interpret,
+        runtime::get_valid_jump_destinations,
+        Evm,
+    },
+    ethereum_types::numeric::{Uint, U256},
+    forks::ExecutionRules,
+};
 
-pub struct EjitEvm<'a, EXT, DB: Database> {
-    pub(crate) evm: Evm<'a, EXT, DB>,
-}
+use ir::{BinOp, Buffer, Env, Exit, Ins, Status, TernOp, UnOp, VElem, NUM_REGS, R};
+use vstack::VStack;
 
-/// Virtual stack, keeps track of items pushed on the stack.
-/// 
-#[derive(Debug, Clone, Copy)]
-pub enum VElem {
-    /// A big-endian constant, ie. PUSH4 etc.
-    Constant([u8; 32]),
+pub mod ir;
+pub mod runtime;
+#[cfg(feature = "revm")]
+pub mod transact;
+pub mod vstack;
 
-    /// An unknown item passed to this trace on the stack. ie. [bp, #n*32]
-    Bp(i32),
+#[cfg(feature = "revm")]
+pub use transact::EjitEvm;
 
-    /// In registers
-    Reg(u8),
-}
+/// Compiles code for `HybridExecutor`, declining code which would leave to
+/// the interpreter at its first instruction.
+#[derive(Debug, Default)]
+pub struct Compiler;
 
-impl VElem {
-    pub(crate) fn as_c4(&self) -> Option<[u64; 4]> {
-        match self {
-            VElem::Constant(c) => {
-                Some(from_u8(*c))
-            }
-            _ => None,
-        }
-    }
-
-    pub(crate) fn as_bp(&self) -> Option<i32> {
-        match self {
-            VElem::Bp(off) => {
-                Some(*off)
-            }
-            _ => None,
-        }
+impl hybrid::Compiler for Compiler {
+    fn compile(&self, code: &[u8], rules: ExecutionRules) -> Option<Box<dyn Compiled>> {
+        let program = Program::compile(code, rules);
+        let declined = match program.ins[1] {
+            Ins::Interpret(exit) => program.exits[exit as usize].pc == 0,
+            _ => false,
+        };
+        (!declined).then(|| Box::new(program) as Box<dyn Compiled>)
     }
 }
 
-#[derive(Debug, Clone, Default)]
-pub struct VStack {
-    pub(crate) stack: VecDeque<VElem>,
-    pub(crate) old_values: usize,
-    pub(crate) new_values: usize,
-}
-
-#[repr(C)]
+/// Compiled code.
 #[derive(Debug)]
-pub struct InterpreterState {
-    pub(crate) mem: * mut u8,
-    pub(crate) mem_size: u64,
-    pub(crate) gas_remaining: u64,
-    pub(crate) contract: [u8; 32],
+pub struct Program {
+    pub(crate) ins: Vec<Ins>,
+    pub(crate) exits: Vec<Exit>,
+    /// The jump table: the index of the `Begin` of the segment at each
+    /// `JUMPDEST`, or `NO_LABEL`.
+    pub(crate) labels: Vec<u32>,
 }
 
-impl InterpreterState {
-    pub(crate) fn mem() -> i32 {
-        0x00
-    }
+/// In `Program::labels`, no `JUMPDEST` at this pc.
+pub(crate) const NO_LABEL: u32 = u32::MAX;
+
+/// The most bits of the length of memory or a buffer, which is at most
+/// `isize::MAX`.
+const LEN_BITS: u32 = 63;
 
-    pub(crate) fn mem_size() -> i32 {
-        0x08
+impl Program {
+    /// Compile `code` for `rules`.
+    pub fn compile(code: &[u8], rules: ExecutionRules) -> Self {
+        let mut codegen = Codegen {
+            code,
+            rules,
+            ins: Vec::new(),
+            exits: Vec::new(),
+            vstack: VStack::new(),
+            segment: None,
+            taken: 0,
+            bits: [256; NUM_REGS],
+            labels: Vec::new(),
+        };
+        codegen.compile();
+        Program { ins: codegen.ins, exits: codegen.exits, labels: codegen.labels }
     }
+}
 
-    pub(crate) fn gas() -> i32 {
-        0x10
+impl fmt::Display for Program {
+    /// The instructions, numbered, then the exits.
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for (i, ins) in self.ins.iter().enumerate() {
+            writeln!(f, "{i:4}: {ins}")?;
+        }
+        for (i, exit) in self.exits.iter().enumerate() {
+            writeln!(f, "exit {i}: {exit}")?;
+        }
+        Ok(())
     }
 }
 
-impl InterpreterState {
-    pub fn new(mem: &mut [u8]) -> Self {
-        Self {
-            mem: mem.as_mut_ptr(),
-            mem_size: mem.len() as u64,
-            gas: 0,
+/// The register code of `code`, compiled for Cancun.
+pub fn compile_to_ir(code: &[u8]) -> Vec<Ins> {
+    Program::compile(code, ExecutionRules::CANCUN).ins
+}
+
+impl Compiled for Program {
+    fn run(&self, evm: &mut Evm) -> Result<(), VmError> {
+        // Steps are only traced by the interpreter.
+        if evm.env.tracer.as_ref().is_some_and(|tracer| tracer.traces_steps()) {
+            return interpret(evm);
         }
+        runtime::execute(self, evm)
     }
 }
 
-#[derive(Debug)]
-pub struct Compiler {
-    pub(crate) ins : Vec<Ins>,
-    pub(crate) vstack: VStack,
-    pub(crate) constants : Vec<[u64; 4]>,
-    pub(crate) cpu_info: CpuInfo,
-
-    // Registers
-    pub(crate) t : Box<[R]>,
-    pub(crate) bp : R,
-    pub(crate) i: R,
-
-    // labels.
-    pub(crate) mem_expand: u32,
-    pub(crate) revert_overflow: u32,
-    pub(crate) revert_gas: u32,
-    pub(crate) skip_label: u32,
-    pub(crate) mem_expand_overflow: u32,
+/// The segment being compiled.
+struct Segment {
+    /// The index of its `Begin`.
+    begin: usize,
+    gas: u64,
+    /// The stack height relative to the start of the segment, and the
+    /// lowest and highest it gets.
+    height: i32,
+    min: i32,
+    max: i32,
+    /// Its exits, with the gas of the segment before each.
+    exits: Vec<(usize, u64)>,
 }
 
-impl Compiler {
-    pub(crate) fn new() -> Self {
-        let mut cpu_info = ejit::cpu_info();
-        let t = (0..8).map(|i| cpu_info.alloc_any().unwrap()).collect();
-        let bp = cpu_info.alloc_any().unwrap();
-        let i = cpu_info.alloc_any().unwrap();
-
-        let mut c = Self {
-            ins: Default::default(),
-            vstack: Default::default(),
-            constants: Default::default(),
-            cpu_info,
-            t,
-            bp,
-            mem_expand: 1000,
-            revert_gas: 1001,
-            revert_overflow: 1002,
-            mem_expand_overflow: 1003,
-            skip_label: 10000,
-            i,
-        };
-        // println!("{c:?}");
-        c
-    }
-
-    pub(crate) fn compile(&mut self, data: &[u8]) {
-        use Ins::*;
-        let sp = self.cpu_info.sp();
-        let bp = self.bp;
-        let save_all : Box<[Src]> = self.cpu_info.save().iter().map(Into::into).collect();
-        let entry_info = EntryInfo::new()
-            .with_saves(self.cpu_info.save())
-            .with_args(&[self.i][..])
-            .with_res(&[self.t[4], self.t[5]])
-            .boxed();
-        self.ins.extend([Enter(entry_info.clone()), Mov(bp, sp.into())]);
+struct Codegen<'a> {
+    code: &'a [u8],
+    rules: ExecutionRules,
+    ins: Vec<Ins>,
+    exits: Vec<Exit>,
+    vstack: VStack,
+    segment: Option<Segment>,
+    /// The registers taken by the instruction being compiled.
+    taken: u32,
+    /// The most bits the value in each register can have.
+    bits: [u32; NUM_REGS],
+    labels: Vec<u32>,
+}
+
+impl Codegen<'_> {
+    fn compile(&mut self) {
+        let code = self.code;
+        let jumpdests = get_valid_jump_destinations(code);
+        self.labels = vec![NO_LABEL; code.len()];
         let mut pc = 0;
-        use revm::interpreter::opcode::*;
-        use Ins::*;
-        use ejit::Type::*;
-        while let Some(&op) = data.get(pc) {
-            pc += 1;
-            match op {
-                PUSH1..=PUSH31  => self.gen_push(data, &mut pc, op),
-                ADD => self.gen_add(),
-                MSTORE => self.gen_mstore(),
-                RETURN => self.gen_return(sp, bp, &entry_info),
-                _ => todo!(),
+        // Whether the instruction at `pc` is reached other than by a jump.
+        let mut reachable = true;
+        while let Some(&byte) = code.get(pc) {
+            let len = if (0x5f..=0x7f).contains(&byte) { (byte - 0x5f) as usize } else { 0 };
+            if jumpdests.contains(&(pc as Uint)) {
+                self.end_segment();
+                self.labels[pc] = self.ins.len() as u32;
+                reachable = true;
             }
-        }
-        self.gen_mem_expand_function();
-        // for (i, c) in self.constants.iter().enumerate() {
-        //     use ejit::Type::*;
-        //     self.ins.extend([Label(i as u32), D(U64, c[0]), D(U64, c[1]), D(U64, c[2]), D(U64, c[3])]);
-        // }
-        for i in &self.ins {
-            let indent = match i {
-                Label(_) => "",
-                _ => "  ",
+            if !reachable {
+                pc += 1 + len;
+                continue;
+            }
+            self.open(pc);
+            let Some(op) = Ops::from_u8(byte).filter(|op| self.rules.allows(*op)) else {
+                self.end(Status::Invalid, pc);
+                reachable = false;
+                pc += 1;
+                continue;
             };
-            println!("{indent}{i:?}")
+            match byte {
+                // PUSH0 to PUSH32
+                0x5f..=0x7f => self.push(&code[(pc + 1).min(code.len())..(pc + 1 + len).min(code.len())], len),
+                // DUP1 to DUP16
+                0x80..=0x8f => {
+                    let n = (byte - 0x80) as usize;
+                    self.op(GAS_VERY_LOW, n + 1, n + 2);
+                    self.vstack.dup(n);
+                }
+                // SWAP1 to SWAP16
+                0x90..=0x9f => {
+                    let n = (byte - 0x8f) as usize;
+                    self.op(GAS_VERY_LOW, n + 1, n + 1);
+                    self.vstack.swap(n);
+                }
+                _ => match op {
+                    Ops::ADD => self.bin(BinOp::Add, GAS_VERY_LOW),
+                    Ops::MUL => self.bin(BinOp::Mul, GAS_LOW),
+                    Ops::SUB => self.bin(BinOp::Sub, GAS_VERY_LOW),
+                    Ops::DIV => self.bin(BinOp::Div, GAS_LOW),
+                    Ops::SDIV => self.bin(BinOp::SDiv, GAS_LOW),
+                    Ops::MOD => self.bin(BinOp::Mod, GAS_LOW),
+                    Ops::SMOD => self.bin(BinOp::SMod, GAS_LOW),
+                    Ops::ADDMOD => self.tern(TernOp::AddMod, GAS_MID),
+                    Ops::MULMOD => self.tern(TernOp::MulMod, GAS_MID),
+                    Ops::SIGNEXTEND => self.bin(BinOp::SignExtend, GAS_LOW),
+                    Ops::LT => self.bin(BinOp::Lt, GAS_VERY_LOW),
+                    Ops::GT => self.bin(BinOp::Gt, GAS_VERY_LOW),
+                    Ops::SLT => self.bin(BinOp::SLt, GAS_VERY_LOW),
+                    Ops::SGT => self.bin(BinOp::SGt, GAS_VERY_LOW),
+                    Ops::EQ => self.bin(BinOp::Eq, GAS_VERY_LOW),
+                    Ops::ISZERO => self.un(UnOp::IsZero, GAS_VERY_LOW),
+                    Ops::AND => self.bin(BinOp::And, GAS_VERY_LOW),
+                    Ops::OR => self.bin(BinOp::Or, GAS_VERY_LOW),
+                    Ops::XOR => self.bin(BinOp::Xor, GAS_VERY_LOW),
+                    Ops::NOT => self.un(UnOp::Not, GAS_VERY_LOW),
+                    Ops::BYTE => self.bin(BinOp::Byte, GAS_VERY_LOW),
+                    Ops::SHL => self.bin(BinOp::Shl, GAS_VERY_LOW),
+                    Ops::SHR => self.bin(BinOp::Shr, GAS_VERY_LOW),
+                    Ops::SAR => self.bin(BinOp::Sar, GAS_VERY_LOW),
+                    Ops::POP => {
+                        self.op(GAS_BASE, 1, 0);
+                        self.vstack.top1();
+                    }
+                    Ops::MLOAD => {
+                        let exit = self.exit_before(pc, 1);
+                        self.op(GAS_VERY_LOW, 1, 1);
+                        let offset = self.vstack.top1();
+                        let dest = self.reg();
+                        self.ins.push(Ins::MLoad { dest, offset, exit });
+                        self.vstack.push(VElem::Reg(dest));
+                    }
+                    Ops::MSTORE | Ops::MSTORE8 => {
+                        let exit = self.exit_before(pc, 0);
+                        self.op(GAS_VERY_LOW, 2, 0);
+                        let (offset, value) = self.vstack.top2();
+                        self.ins.push(match op {
+                            Ops::MSTORE => Ins::MStore { offset, value, exit },
+                            _ => Ins::MStore8 { offset, value, exit },
+                        });
+                    }
+                    Ops::MSIZE => self.load(GAS_BASE, LEN_BITS, Ins::MSize),
+                    Ops::MCOPY => {
+                        let exit = self.exit_before(pc, 0);
+                        self.op(GAS_VERY_LOW, 3, 0);
+                        let (dest, src, len) = self.vstack.top3();
+                        self.ins.push(Ins::MCopy { dest, src, len, exit });
+                    }
+                    Ops::KECCAK => {
+                        let exit = self.exit_before(pc, 1);
+                        self.op(GAS_KECCAK256, 2, 1);
+                        let (offset, len) = self.vstack.top2();
+                        let dest = self.reg();
+                        self.ins.push(Ins::Keccak { dest, offset, len, exit });
+                        self.vstack.push(VElem::Reg(dest));
+                    }
+                    Ops::CALLDATALOAD => {
+                        self.op(GAS_VERY_LOW, 1, 1);
+                        self.reserve(1);
+                        let offset = self.vstack.top1();
+                        let dest = self.reg();
+                        self.ins.push(Ins::CallDataLoad { dest, offset });
+                        self.vstack.push(VElem::Reg(dest));
+                    }
+                    Ops::CALLDATASIZE => self.load(GAS_BASE, LEN_BITS, |dest| Ins::Size(dest, Buffer::CallData)),
+                    Ops::CODESIZE => self.load(GAS_BASE, LEN_BITS, |dest| Ins::Size(dest, Buffer::Code)),
+                    Ops::RETURNDATASIZE => self.load(GAS_BASE, LEN_BITS, |dest| Ins::Size(dest, Buffer::ReturnData)),
+                    Ops::ADDRESS => self.env(Env::Address),
+                    Ops::ORIGIN => self.env(Env::Origin),
+                    Ops::CALLER => self.env(Env::Caller),
+                    Ops::CALLVALUE => self.env(Env::CallValue),
+                    Ops::GASPRICE => self.env(Env::GasPrice),
+                    Ops::COINBASE => self.env(Env::Coinbase),
+                    Ops::TIMESTAMP => self.env(Env::Timestamp),
+                    Ops::NUMBER => self.env(Env::Number),
+                    Ops::PREVRANDAO => self.env(Env::PrevRandao),
+                    Ops::GASLIMIT => self.env(Env::GasLimit),
+                    Ops::CHAINID => self.env(Env::ChainId),
+                    Ops::BASEFEE => self.env(Env::BaseFee),
+                    Ops::BLOBBASEFEE => self.env(Env::BlobBaseFee),
+                    Ops::SELFBALANCE => self.load(GAS_FAST_STEP, 256, Ins::SelfBalance),
+                    Ops::CALLDATACOPY | Ops::CODECOPY | Ops::RETURNDATACOPY => {
+                        let exit = self.exit_before(pc, 0);
+                        self.op(GAS_VERY_LOW, 3, 0);
+                        let (dest, src, len) = self.vstack.top3();
+                        let buffer = match op {
+                            Ops::CALLDATACOPY => Buffer::CallData,
+                            Ops::CODECOPY => Buffer::Code,
+                            _ => Buffer::ReturnData,
+                        };
+                        self.ins.push(Ins::Copy { buffer, dest, src, len, exit });
+                    }
+                    Ops::SLOAD => {
+                        let exit = self.exit_before(pc, 1);
+                        self.op(0, 1, 1);
+                        let key = self.vstack.top1();
+                        let dest = self.reg();
+                        self.ins.push(Ins::SLoad { dest, key, exit });
+                        self.vstack.push(VElem::Reg(dest));
+                    }
+                    Ops::SSTORE => {
+                        let exit = self.exit_before(pc, 0);
+                        self.op(0, 2, 0);
+                        let (key, value) = self.vstack.top2();
+                        self.ins.push(Ins::SStore { key, value, exit });
+                    }
+                    Ops::TLOAD => {
+                        self.op(GAS_WARM_ACCESS, 1, 1);
+                        self.reserve(1);
+                        let key = self.vstack.top1();
+                        let dest = self.reg();
+                        self.ins.push(Ins::TLoad { dest, key });
+                        self.vstack.push(VElem::Reg(dest));
+                    }
+                    Ops::TSTORE => {
+                        let exit = self.exit_before(pc, 0);
+                        self.op(GAS_WARM_ACCESS, 2, 0);
+                        let (key, value) = self.vstack.top2();
+                        self.ins.push(Ins::TStore { key, value, exit });
+                    }
+                    Ops::CALL | Ops::CALLCODE | Ops::DELEGATECALL | Ops::STATICCALL | Ops::GAS => self.host(pc, op),
+                    Ops::STOP => {
+                        self.end(Status::Stop, pc + 1);
+                        reachable = false;
+                    }
+                    Ops::RETURN | Ops::REVERT => {
+                        let exit = self.exit_before(pc, 0);
+                        self.op(0, 2, 0);
+                        let (offset, len) = self.vstack.top2();
+                        // REVERT fails at its pc.
+                        let (status, next) = match op {
+                            Ops::RETURN => (Status::Return, pc + 1),
+                            _ => (Status::Revert, pc),
+                        };
+                        let done = self.exit(next);
+                        self.ins.push(Ins::Output { status, offset, len, exit, done });
+                        self.vstack = VStack::new();
+                        self.close();
+                        reachable = false;
+                    }
+                    Ops::SELFDESTRUCT => {
+                        self.host(pc, op);
+                        reachable = false;
+                    }
+                    Ops::JUMPDEST => self.op(GAS_JUMPDEST, 0, 0),
+                    Ops::PC => {
+                        self.op(GAS_BASE, 0, 1);
+                        self.vstack.push(VElem::Constant(U256::from(pc as u64)));
+                    }
+                    Ops::JUMP => {
+                        self.op(GAS_MID, 1, 0);
+                        self.reserve(1);
+                        let dest = self.vstack.top1();
+                        self.jump(&jumpdests, pc, dest, None);
+                        reachable = false;
+                    }
+                    Ops::JUMPI => {
+                        self.op(GAS_HIGH, 2, 0);
+                        self.reserve(2);
+                        let (dest, cond) = self.vstack.top2();
+                        match cond.as_constant() {
+                            Some(cond) if cond.is_zero() => self.end_segment(),
+                            Some(_) => {
+                                self.jump(&jumpdests, pc, dest, None);
+                                reachable = false;
+                            }
+                            None => self.jump(&jumpdests, pc, dest, Some(cond)),
+                        }
+                    }
+                    _ => {
+                        self.interpret(pc);
+                        reachable = false;
+                    }
+                },
+            }
+            pc += 1 + len;
+        }
+        // Running off the end of the code stops.
+        if reachable {
+            self.open(pc);
+            self.end(Status::Stop, pc);
+        }
+        self.eliminate_dead_code();
+        for ins in &mut self.ins {
+            if let Ins::Goto(target) | Ins::GotoIf(target, _) = ins {
+                *target = self.labels[*target as usize];
+            }
         }
-        let prog = Executable::from_ir(&self.ins).unwrap();
-        println!("{}", prog.fmt_url());
-        todo!();
     }
 
-    pub(crate) fn gen_return(&mut self, sp: R, bp: R, entry_info: &Box<EntryInfo>) {
-        use Ins::*;
-        use Type::*;
-        use Cond::*;
-        let (a, b) = self.vstack.top2();
-        let t4 = self.t[4];
-        let t5 = self.t[5];
-        let t6 = self.t[6];
-        self.gen_u64(t4, a);
-        self.gen_u64(t5, b);
-        self.ins.extend([
-            Add(t5, t4, t5.into()),
-            Br(Ugt, self.mem_expand_overflow),
-        ]);
-        self.gen_mem_expand(t5, t6);
-        self.ins.extend([
-            Ld(U64, t6, self.i, InterpreterState::mem()),
-            Add(t5, t5, t6.into()),
-            Mov(self.cpu_info.sp(), self.bp.into()),
-            Leave(entry_info.clone()),
-            Ret,
-        ]);
-    }
-
-    // https://github.com/ethereum/execution-specs/blob/master/src/ethereum/frontier/vm/instructions/memory.py
-    pub(crate) fn gen_mstore(&mut self) {
-        use Ins::*;
-        use Type::*;
-        let t0 = self.t[0];
-        let t1 = self.t[1];
-        let t2 = self.t[2];
-        let t3 = self.t[3];
-        let t4 = self.t[4];
-        let t5 = self.t[5];
-        let (start_position, value) = self.vstack.top2();
-        self.gen_t0(start_position);
-        self.gen_u64(t4, value);
-        self.ins.extend([
-            Add(t5, t4, 32.into()),
-            Br(Cond::Ugt, self.mem_expand_overflow),
-        ]);
-        self.gen_mem_expand(t5, self.t[6]);
-        self.ins.extend([
-            Ld(U64, t5, self.i, InterpreterState::mem()),
-            Add(t5, t5, t4.into()),
-            St(U64, t3, t4, 0x00),
-            St(U64, t2, t4, 0x08),
-            St(U64, t1, t4, 0x10),
-            St(U64, t0, t4, 0x18),
-        ]);
-    }
-
-    pub(crate) fn gen_add(&mut self) {
-        use Ins::*;
-        use Type::*;
-        let t0 = self.t[0];
-        let t1 = self.t[1];
-        let t2 = self.t[2];
-        let t3 = self.t[3];
-        let t4 = self.t[4];
-        let (a, b) = self.vstack.top2();
-        if let Some(ca) = a.as_c4() {
-            if let Some(cb) = b.as_c4() {
-                let sum = add256(ca, cb);
-                self.vstack.push(VElem::Constant(to_u8(sum)));
-            } else {
-                self.gen_t0(b);
-                self.ins.extend([
-                    Add(t0, t0, ca[0].into()),
-                    Adc(t1, t1, ca[1].into()),
-                    Adc(t2, t2, ca[2].into()),
-                    Adc(t3, t3, ca[3].into()),
-                ]);
-                self.vstack.push(VElem::Reg(0));
+    /// End the segment, if one is open, writing the vstack to the stack.
+    fn end_segment(&mut self) {
+        if self.segment.is_some() {
+            let (pop, push) = self.vstack.take();
+            if pop > 0 || !push.is_empty() {
+                self.ins.push(Ins::Flush { pop, push });
             }
-        } else {
-            if let Some(cb) = b.as_c4() {
-                self.gen_t0(a);
-                self.ins.extend([
-                    Add(t0, t0, cb[0].into()),
-                    Adc(t1, t1, cb[1].into()),
-                    Adc(t2, t2, cb[2].into()),
-                    Adc(t3, t3, cb[3].into()),
-                ]);
-                self.vstack.push(VElem::Reg(0));
-            } else if let Some(off) = b.as_bp() {
-                self.gen_t0(a);
-                self.ins.extend([
-                    Ld(U64, t4, self.bp, off+0x18),
-                    Add(t0, t0, t4.into()),
-                    Ld(U64, t4, self.bp, off+0x10),
-                    Adc(t1, t1, t4.into()),
-                    Ld(U64, t4, self.bp, off+0x08),
-                    Adc(t2, t2, t4.into()),
-                    Ld(U64, t4, self.bp, off+0x00),
-                    Adc(t3, t3, t4.into()),
-                ]);
-                self.vstack.push(VElem::Reg(0));
+            self.close();
+        }
+    }
+
+    /// The item `e` in a form which survives a `Flush`.
+    fn keep(&mut self, e: VElem) -> VElem {
+        match e {
+            VElem::Bp(_) => {
+                let dest = self.reg();
+                self.ins.push(Ins::Mov(dest, e));
+                VElem::Reg(dest)
             }
+            _ => e,
         }
     }
 
-    pub(crate) fn gen_push(&mut self, data: &[u8], pc: &mut usize, op: u8) {
-        let len = ((op - revm::interpreter::opcode::PUSH1) as usize) + 1;
-        if *pc + len > data.len() {
-            todo!();
-            // generate failure code - must fail at runtime?
-        } else {
-            let mut c = [0; 32];
-            c[32-len..32].copy_from_slice(&data[*pc..*pc+len]);
-            self.vstack.push(VElem::Constant(c));
-            *pc += len;
+    /// End the segment with the jump at `pc` to `dest`, if `cond` is not
+    /// zero when given. Jumps to a constant `JUMPDEST` go straight to its
+    /// segment.
+    fn jump(&mut self, jumpdests: &BTreeSet<Uint>, pc: usize, dest: VElem, cond: Option<VElem>) {
+        // The operands are off the vstack, but their registers are not free.
+        for e in [Some(dest), cond].into_iter().flatten() {
+            if let VElem::Reg(r) = e {
+                self.taken |= 1 << r;
+            }
         }
+        let dest = self.keep(dest);
+        let cond = cond.map(|cond| self.keep(cond));
+        self.end_segment();
+        let label = dest
+            .as_constant()
+            .and_then(|dest| dest.to_uint().ok())
+            .filter(|dest| jumpdests.contains(dest))
+            .map(|dest| dest as u32);
+        let pc = pc as u32;
+        self.ins.push(match (label, cond) {
+            // Resolved to instructions once all segments are compiled.
+            (Some(label), None) => Ins::Goto(label),
+            (Some(label), Some(cond)) => Ins::GotoIf(label, cond),
+            (None, None) => Ins::Jump { dest, pc },
+            (None, Some(cond)) => Ins::JumpI { dest, cond, pc },
+        });
     }
 
-    pub(crate) fn gen_u64(&mut self, dest: ejit::R, e: VElem) {
-        use Ins::*;
-        use Type::*;
+    /// Start a segment at `pc` unless one is open.
+    fn open(&mut self, pc: usize) {
+        if self.segment.is_none() {
+            self.segment = Some(Segment { begin: self.ins.len(), gas: 0, height: 0, min: 0, max: 0, exits: Vec::new() });
+            self.ins.push(Ins::Begin { pc: pc as u32, gas: 0, need: 0, grow: 0 });
+        }
+    }
+
+    /// End the segment, writing its gas and stack bounds to its `Begin`.
+    fn close(&mut self) {
+        let segment = self.segment.take().unwrap();
+        if let Ins::Begin { gas, need, grow, .. } = &mut self.ins[segment.begin] {
+            *gas = segment.gas;
+            *need = -segment.min as u16;
+            *grow = segment.max as u16;
+        }
+        for (exit, gas) in segment.exits {
+            self.exits[exit].refund = segment.gas - gas;
+        }
+    }
+
+    /// Account for an instruction which costs `gas` and replaces `inputs`
+    /// stack items with `outputs`.
+    fn op(&mut self, gas: u128, inputs: usize, outputs: usize) {
+        let segment = self.segment.as_mut().unwrap();
+        segment.gas += gas as u64;
+        segment.height -= inputs as i32;
+        segment.min = segment.min.min(segment.height);
+        segment.height += outputs as i32;
+        segment.max = segment.max.max(segment.height);
+    }
+
+    /// A way out to the interpreter at `pc`, with the stack as it is now.
+    fn exit(&mut self, pc: usize) -> u32 {
+        let (pop, push) = self.vstack.snapshot();
+        let segment = self.segment.as_mut().unwrap();
+        segment.exits.push((self.exits.len(), segment.gas));
+        self.exits.push(Exit { pc: pc as u32, refund: 0, pop, push });
+        self.exits.len() as u32 - 1
+    }
+
+    /// An exit to the instruction at `pc` for when it cannot be completed,
+    /// after reserving `n` registers for it.
+    fn exit_before(&mut self, pc: usize, n: u32) -> u32 {
+        // Spilling after the exit would move the slots it reads.
+        self.reserve(n);
+        self.exit(pc)
+    }
+
+    /// Leave to the interpreter at `pc` and end the segment.
+    fn interpret(&mut self, pc: usize) {
+        let exit = self.exit(pc);
+        self.ins.push(Ins::Interpret(exit));
+        self.vstack = VStack::new();
+        self.close();
+    }
+
+    /// Run the instruction at `pc` as the interpreter does, with the stack
+    /// as it is now, and end the segment.
+    fn host(&mut self, pc: usize, op: Ops) {
+        let exit = self.exit(pc);
+        self.ins.push(Ins::Host(op, exit));
+        self.vstack = VStack::new();
+        self.close();
+    }
+
+    /// End the frame with `status` and the pc at `pc`, with the stack as it
+    /// is now, and end the segment.
+    fn end(&mut self, status: Status, pc: usize) {
+        let exit = self.exit(pc);
+        self.ins.push(Ins::End(status, exit));
+        self.vstack = VStack::new();
+        self.close();
+    }
+
+    /// The registers holding items of the vstack.
+    fn live(&self) -> u32 {
+        self.vstack.stack.iter().fold(0, |live, e| match e {
+            VElem::Reg(r) => live | 1 << r,
+            _ => live,
+        })
+    }
+
+    /// Make sure that `n` registers are free for the next instruction,
+    /// before it takes its operands, spilling the vstack to the stack if
+    /// not.
+    fn reserve(&mut self, n: u32) {
+        self.taken = 0;
+        if NUM_REGS as u32 - self.live().count_ones() < n {
+            let (pop, push) = self.vstack.take();
+            self.ins.push(Ins::Flush { pop, push });
+        }
+    }
+
+    /// A free register, of those made sure of by `reserve`, for a value
+    /// of up to 256 bits until its `bits` are set.
+    fn reg(&mut self) -> R {
+        let r = (!(self.live() | self.taken)).trailing_zeros();
+        assert!(r < NUM_REGS as u32, "registers not reserved");
+        self.taken |= 1 << r;
+        self.bits[r as usize] = 256;
+        r as R
+    }
+
+    /// The most bits the item `e` can have.
+    fn bits_of(&self, e: VElem) -> u32 {
         match e {
-            VElem::Constant(c) => {
-                let c = from_u8(c);
-                if c[1] != 0 || c[2] != 0 || c[3] != 0 {
-                    self.ins.push(Jmp(self.revert_overflow));
-                }
-                self.ins.push(Mov(dest, c[0].into()))
-            },
-            VElem::Bp(imm) => {
-                self.ins.extend([
-                    Ld(U64, dest, self.bp, imm+0x00),
-                    Cmp(dest, 0.into()),
-                    Br(ejit::Cond::Ne, self.revert_overflow),
-                    Ld(U64, dest, self.bp, imm+0x18),
-                ]);
-            }
-            VElem::Reg(_) => {
-                todo!()
-            }
+            VElem::Constant(c) => c.bits(),
+            VElem::Bp(_) => 256,
+            VElem::Reg(r) => self.bits[r as usize],
         }
     }
 
-    pub(crate) fn gen_mem_expand(&mut self, ptr: ejit::R, mem_size: ejit::R) {
-        use ejit::Type::*;
-        use ejit::Cond::*;
-        use ejit::Ins::*;
-        self.ins.extend([
-            Ld(U64, mem_size, self.i, InterpreterState::mem_size()),
-            Cmp(ptr, mem_size.into()),
-            Br(Ule, self.skip_label),
-            CallLocal(self.mem_expand),
-            Label(self.skip_label),
-        ]);
-        self.skip_label += 1;
-    }
-
-    pub(crate) fn gen_mem_expand_function(&mut self) {
-        use ejit::Type::*;
-        use ejit::Cond::*;
-        use ejit::Ins::*;
-        self.ins.extend([
-            Label(self.mem_expand),
-            Ret,
-            Label(self.mem_expand_overflow),
-            Ret,
-        ]);
-        self.skip_label += 1;
-    }
-
-    pub(crate) fn gen_t0(&mut self, b: VElem) {
-        use ejit::Type::*;
-        use ejit::Cond::*;
-        use ejit::Ins::*;
-        match b {
-            VElem::Constant(c) => {
-                let c = b.as_c4().unwrap();
-                self.ins.extend([
-                    Mov(self.t[0], c[0].into()),
-                    Mov(self.t[1], c[1].into()),
-                    Mov(self.t[2], c[2].into()),
-                    Mov(self.t[3], c[3].into()),
-                ]);
+    /// Leave out pure instructions whose register is not read before the
+    /// end of their segment or the next write to it, as that of an item
+    /// which is popped. Registers are not read across segments.
+    fn eliminate_dead_code(&mut self) {
+        let mut live = 0_u32;
+        let mut keep = vec![true; self.ins.len()];
+        for (i, ins) in self.ins.iter().enumerate().rev() {
+            if let Ins::Begin { .. } = ins {
+                live = 0;
+                continue;
             }
-            VElem::Bp(x) => {
-                self.ins.extend([
-                    Ld(U64, self.t[0], self.bp, x.into()),
-                    Ld(U64, self.t[1], self.bp, x.into()),
-                    Ld(U64, self.t[2], self.bp, x.into()),
-                    Ld(U64, self.t[3], self.bp, x.into()),
-                ]);
+            let dest = ins.dest().map_or(0, |dest| 1 << dest);
+            if ins.is_pure() && live & dest == 0 {
+                keep[i] = false;
+                continue;
             }
-            VElem::Reg(0) => {
+            live &= !dest;
+            let exits = ins.exits().into_iter().flat_map(|exit| &self.exits[exit as usize].push[..]);
+            for e in ins.operands().iter().chain(exits) {
+                if let VElem::Reg(r) = e {
+                    live |= 1 << r;
+                }
             }
-            _ => todo!(),
         }
+        // The new index of each instruction, for the jump table.
+        let mut index = Vec::with_capacity(keep.len());
+        let mut n = 0;
+        for &keep in &keep {
+            index.push(n);
+            n += keep as u32;
+        }
+        for label in &mut self.labels {
+            if *label != NO_LABEL {
+                *label = index[*label as usize];
+            }
+        }
+        let mut keep = keep.into_iter();
+        self.ins.retain(|_| keep.next().unwrap());
     }
-}
 
-pub(crate) fn add256(ca: [u64; 4], cb: [u64; 4]) -> [u64; 4] {
-    let (sum0, cy0) = ca[0].overflowing_add(cb[0]);
+    /// Push the big-endian `data`, zero padded to `len` bytes as past the
+    /// end of the code.
+    fn push(&mut self, data: &[u8], len: usize) {
+        self.op(if len == 0 { GAS_BASE } else { GAS_VERY_LOW }, 0, 1);
+        let mut bytes = [0; 32];
+        bytes[32 - len..][..data.len()].copy_from_slice(data);
+        self.vstack.push(VElem::Constant(U256::from_be_bytes(bytes)));
+    }
 
-    let (sum1, cy1a) = ca[1].overflowing_add(cb[1]);
-    let (sum1, cy1b) = sum1.overflowing_add(if cy0 { 1 } else {0} );
+    /// An instruction which costs `gas` and pushes the value of up to
+    /// `bits` bits `ins` writes to its register.
+    fn load(&mut self, gas: u128, bits: u32, ins: impl FnOnce(R) -> Ins) {
+        self.op(gas, 0, 1);
+        self.reserve(1);
+        let dest = self.reg();
+        self.ins.push(ins(dest));
+        self.bits[dest as usize] = bits;
+        self.vstack.push(VElem::Reg(dest));
+    }
 
-    let (sum2, cy2a) = ca[2].overflowing_add(cb[2]);
-    let (sum2, cy2b) = sum2.overflowing_add(if cy1a || cy1b { 1 } else {0} );
+    fn env(&mut self, env: Env) {
+        self.load(GAS_BASE, env.bits(), |dest| Ins::Env(dest, env));
+    }
+
+    /// A binary instruction, folded if its operands decide the result, and
+    /// on 64 bits if they and the result fit in them.
+    fn bin(&mut self, op: BinOp, gas: u128) {
+        self.op(gas, 2, 1);
+        self.reserve(1);
+        let (a, b) = self.vstack.top2();
+        if let Some(e) = op.fold(a, b) {
+            return self.vstack.push(e);
+        }
+        let (bits_a, bits_b) = (self.bits_of(a), self.bits_of(b));
+        let bits = op.bits(bits_a, bits_b, a.as_constant());
+        let dest = self.reg();
+        let narrow = bits_a <= 64 && bits_b <= 64 && bits <= 64 && !matches!(op, BinOp::Sub | BinOp::SignExtend);
+        self.ins.push(if narrow { Ins::Narrow(op, dest, a, b) } else { Ins::Bin(op, dest, a, b) });
+        self.bits[dest as usize] = bits;
+        self.vstack.push(VElem::Reg(dest));
+    }
 
-    let (sum3, _cy3a) = ca[3].overflowing_add(cb[3]);
-    let (sum3, _cy3b) = sum3.overflowing_add(if cy2a || cy2b { 1 } else {0} );
+    fn un(&mut self, op: UnOp, gas: u128) {
+        self.op(gas, 1, 1);
+        self.reserve(1);
+        let a = self.vstack.top1();
+        if let Some(a) = a.as_constant() {
+            return self.vstack.push(VElem::Constant(op.apply(a)));
+        }
+        let dest = self.reg();
+        self.ins.push(Ins::Un(op, dest, a));
+        self.bits[dest as usize] = op.bits();
+        self.vstack.push(VElem::Reg(dest));
+    }
 
-    [sum0, sum1, sum2, sum3]
+    fn tern(&mut self, op: TernOp, gas: u128) {
+        self.op(gas, 3, 1);
+        self.reserve(1);
+        let (a, b, c) = self.vstack.top3();
+        if let (Some(a), Some(b), Some(c)) = (a.as_constant(), b.as_constant(), c.as_constant()) {
+            return self.vstack.push(VElem::Constant(op.apply(a, b, c)));
+        }
+        let dest = self.reg();
+        self.ins.push(Ins::Tern(op, dest, a, b, c));
+        self.vstack.push(VElem::Reg(dest));
+    }
 }
 
-pub(crate) fn sub256(ca: [u64; 4], cb: [u64; 4]) -> [u64; 4] {
-    let (sum0, cy0) = ca[0].overflowing_sub(cb[0]);
+#[cfg(test)]
+mod tests {
+    use std::collections::BTreeSet;
 
-    let (sum1, cy1a) = ca[1].overflowing_sub(cb[1]);
-    let (sum1, cy1b) = sum1.overflowing_sub(if cy0 { 1 } else {0} );
+    use proptest::{collection::vec, prelude::*};
 
-    let (sum2, cy2a) = ca[2].overflowing_sub(cb[2]);
-    let (sum2, cy2b) = sum2.overflowing_sub(if cy1a || cy1b { 1 } else {0} );
+    use crate::ethereum::{
+        cancun::{
+            fork_types::{Account, Address},
+            state::{begin_transaction, set_account, State, TransientStorage},
+            utils::prepare_message,
+            vm::{
+                exceptions::VmError,
+                hybrid::{HybridExecutor, Promotion},
+                interpreter::execute_code,
+                Environment,
+            },
+        },
+        crypto::hash::keccak256,
+        ethereum_types::{bytes::Bytes, numeric::{Uint, U256}},
+        forks::ExecutionRules,
+    };
 
-    let (sum3, _cy3a) = ca[3].overflowing_sub(cb[3]);
-    let (sum3, _cy3b) = sum3.overflowing_sub(if cy2a || cy2b { 1 } else {0} );
+    use super::{
+        ir::{Ins, NUM_REGS},
+        compile_to_ir, Compiler, Program,
+    };
 
-    [sum0, sum1, sum2, sum3]
-}
+    /// What a frame left: its stack, memory, gas, refund counter, pc,
+    /// output and error.
+    type Frame = (Vec<U256>, Vec<u8>, Uint, i64, Uint, Bytes, Option<VmError>);
 
-pub(crate) fn to_u8(ca: [u64; 4]) -> [u8; 32] {
-    let mut res = [0; 32];
-    res[0x18..0x20].copy_from_slice(&ca[0].to_be_bytes());
-    res[0x10..0x18].copy_from_slice(&ca[1].to_be_bytes());
-    res[0x08..0x10].copy_from_slice(&ca[2].to_be_bytes());
-    res[0x00..0x08].copy_from_slice(&ca[3].to_be_bytes());
-    res
-}
+    /// A contract which returns 42 as a word: PUSH1 42 PUSH0 MSTORE PUSH1 32
+    /// PUSH0 RETURN.
+    const CALLEE: [u8; 20] = [0xce; 20];
+    /// Contracts which write 1 to slot 0 of their storage and transient
+    /// storage: PUSH1 1 PUSH0 SSTORE, and TSTORE.
+    const STORES: [u8; 20] = [0x55; 20];
+    const TSTORES: [u8; 20] = [0x5d; 20];
+    /// A contract which returns its call data and the word of it at 4:
+    /// CALLDATASIZE PUSH0 PUSH0 CALLDATACOPY PUSH1 4 CALLDATALOAD PUSH1 32
+    /// MSTORE PUSH1 64 PUSH0 RETURN, for call data of a word.
+    const ECHO: [u8; 20] = [0xec; 20];
 
-pub(crate) fn from_u8(c: [u8; 32]) -> [u64; 4] {
-    [
-        u64::from_be_bytes(c[0x18..0x20].try_into().unwrap()),
-        u64::from_be_bytes(c[0x10..0x18].try_into().unwrap()),
-        u64::from_be_bytes(c[0x08..0x10].try_into().unwrap()),
-        u64::from_be_bytes(c[0x00..0x08].try_into().unwrap()),
-    ]
-}
+    /// Run `code` as init code with `gas` in a state with only `CALLEE`,
+    /// `STORES`, `TSTORES` and `ECHO`.
+    fn run(executor: &HybridExecutor, code: &[u8], gas: Uint) -> Frame {
+        let mut state = State::default();
+        let contracts = [
+            (CALLEE, vec![0x60, 42, 0x5f, 0x52, 0x60, 32, 0x5f, 0xf3]),
+            (STORES, vec![0x60, 1, 0x5f, 0x55]),
+            (TSTORES, vec![0x60, 1, 0x5f, 0x5d]),
+            (ECHO, vec![0x36, 0x5f, 0x5f, 0x37, 0x60, 4, 0x35, 0x60, 32, 0x52, 0x60, 64, 0x5f, 0xf3]),
+        ];
+        for (address, code) in contracts {
+            let account = Account { code: Bytes(code), ..Default::default() };
+            set_account(&mut state, &Address::from_be_bytes(address), Some(account));
+        }
+        // As a transaction does, for the original values of storage.
+        begin_transaction(&mut state, &mut TransientStorage::default());
+        let mut env = Environment {
+            executor: Some(executor),
+            number: 7,
+            time: U256::from(1_700_000_000_u32),
+            base_fee_per_gas: 10,
+            excess_blob_gas: 1 << 20,
+            ..Environment::new(&mut state, ExecutionRules::CANCUN)
+        };
+        let caller = Address::from_be_bytes([0xca; 20]);
+        let message = prepare_message(
+            caller,
+            None,
+            U256::ZERO,
+            Bytes(code.to_vec()),
+            gas,
+            &env,
+            None,
+            true,
+            false,
+            BTreeSet::new(),
+            BTreeSet::new(),
+        )
+        .unwrap();
+        // As process_create_message does, for the storage of the code.
+        set_account(env.state, &message.current_target, Some(Account::default()));
+        let evm = execute_code(message, &mut env);
+        (evm.stack, evm.memory, evm.gas_left, evm.refund_counter, evm.pc, evm.output, evm.error)
+    }
 
-impl<'a, EXT, DB: Database> EjitEvm<'a, EXT, DB> {
-    pub(crate) fn new(evm: Evm<'a, EXT, DB>) -> Self {
-        Self { evm }
+    /// Run `code` interpreted and then compiled, checking that both leave
+    /// the same frame, and return it with whether the code was compiled.
+    fn check(code: &[u8], gas: Uint) -> (Frame, bool) {
+        let executor = HybridExecutor::new(Box::new(Compiler), Promotion { calls: 1, gas: Uint::MAX });
+        let interpreted = run(&executor, code, gas);
+        let compiled = run(&executor, code, gas);
+        assert_eq!(compiled, interpreted, "{code:02x?}");
+        (compiled, executor.is_compiled(&keccak256(code)))
     }
 
-    pub(crate) fn transact(&mut self) -> EVMResult<DB::Error> {
-        let tx = self.evm.tx();
-        let TxKind::Create = tx.transact_to else { todo!() };
+    #[test]
+    fn arithmetic() {
+        // PUSH1 7 PUSH1 5 SUB PUSH1 3 MUL PUSH1 2 SWAP1 DIV DUP1 PUSH1 4 MOD
+        // PUSH0 NOT PUSH1 1 ADD ISZERO POP
+        let code = [
+            0x60, 7, 0x60, 5, 0x03, 0x60, 3, 0x02, 0x60, 2, 0x90, 0x04, 0x80, 0x60, 4, 0x06, 0x5f, 0x19, 0x60, 1, 0x01,
+            0x15, 0x50,
+        ];
+        let ((stack, _, gas_left, ..), compiled) = check(&code, 1000);
+        assert!(compiled);
+        // (5 - 7) * 3 wraps, halved unsigned.
+        assert_eq!(stack, [U256::MAX.shr(1) - U256::from(2_u32), U256::from(4_u32)]);
+        assert_eq!(gas_left, 1000 - 12 * 3 - 3 * 5 - 2 * 2);
+
+        // ADDMOD, comparisons, and shifts of constants and registers.
+        // PUSH1 5 PUSH1 7 PUSH1 9 ADDMOD PUSH1 1 SHL DUP1 PUSH1 3 LT PUSH1 3
+        // SAR SWAP2 SLT
+        let code = [0x60, 5, 0x60, 7, 0x60, 9, 0x08, 0x60, 1, 0x1b, 0x80, 0x60, 3, 0x10, 0x60, 3, 0x1d, 0x91, 0x12];
+        assert!(check(&code, 1000).1);
+    }
+
+    #[test]
+    fn failures_match_the_interpreter() {
+        // ADD on an empty stack.
+        let ((.., error), compiled) = check(&[0x01], 1000);
+        assert_eq!((error, compiled), (Some(VmError::StackUnderflowError), true));
+        // Out of gas half way through a segment.
+        let ((.., error), _) = check(&[0x60, 1, 0x60, 2, 0x01, 0x60, 3, 0x02], 10);
+        assert_eq!(error, Some(VmError::OutOfGasError));
+        // An instruction without a translation, and an undefined one.
+        let ((stack, memory, ..), compiled) = check(&[0x60, 1, 0x60, 2, 0x01, 0x5f, 0x52, 0x60, 1], 1000);
+        assert_eq!((stack, memory[31], compiled), (vec![U256::ONE], 3, true));
+        let ((.., error), compiled) = check(&[0x60, 1, 0x0c], 1000);
+        assert_eq!((error, compiled), (Some(VmError::InvalidOpcode), true));
+        // A PUSH running past the end of the code.
+        let ((stack, ..), _) = check(&[0x61, 1], 1000);
+        assert_eq!(stack, [U256::from(256_u32)]);
+    }
+
+    #[test]
+    fn jumps() {
+        // PUSH1 5 JUMPDEST PUSH1 1 SWAP1 SUB DUP1 PUSH1 2 JUMPI PUSH1 42, a
+        // loop counting down from 5.
+        let code = [0x60, 5, 0x5b, 0x60, 1, 0x90, 0x03, 0x80, 0x60, 2, 0x57, 0x60, 42];
+        let ((stack, _, gas_left, ..), compiled) = check(&code, 1000);
+        assert!(compiled);
+        assert_eq!(stack, [U256::ZERO, U256::from(42_u32)]);
+        assert_eq!(gas_left, 1000 - 3 - 5 * (1 + 3 + 3 + 3 + 3 + 3 + 10) - 3);
 
-        let mut compiler = Compiler::new();
-        compiler.compile(&tx.data);
+        // PUSH1 2 PUSH1 4 MUL JUMP STOP STOP JUMPDEST PUSH1 1, to a computed
+        // destination.
+        let code = [0x60, 2, 0x60, 4, 0x02, 0x56, 0x00, 0x00, 0x5b, 0x60, 1];
+        assert_eq!(check(&code, 1000).0 .0, [U256::ONE]);
+        // PUSH1 6 JUMPDEST JUMP STOP STOP JUMPDEST PUSH1 42, to a destination
+        // pushed by another segment.
+        let code = [0x60, 6, 0x5b, 0x56, 0x00, 0x00, 0x5b, 0x60, 42];
+        assert_eq!(check(&code, 1000).0 .0, [U256::from(42_u32)]);
+        // PUSH1 0 PUSH1 9 JUMPI PUSH1 1, not taken.
+        assert_eq!(check(&[0x60, 0, 0x60, 9, 0x57, 0x60, 1], 1000).0 .0, [U256::ONE]);
 
-        // let interpreter = InterpreterState::new(contract, gas_limit, is_static);
+        // PUSH1 10 JUMPDEST PUSH0 ISZERO ISZERO SWAP1 JUMPI PUSH1 1 JUMPDEST
+        // PUSH1 2, with the condition in the register the destination is
+        // read into.
+        let code = [0x60, 10, 0x5b, 0x5f, 0x15, 0x15, 0x90, 0x57, 0x60, 1, 0x5b, 0x60, 2];
+        assert_eq!(check(&code, 1000).0 .0, [U256::ONE, U256::from(2_u32)]);
 
-        todo!()
+        // PUSH1 4 JUMP PUSH1 0x5b, into push data.
+        let ((.., pc, _, error), _) = check(&[0x60, 4, 0x56, 0x60, 0x5b], 1000);
+        assert_eq!((pc, error), (2, Some(VmError::InvalidJumpDestError)));
+        // PUSH1 1 PUSH1 2 MUL JUMP, to a computed destination which is not a
+        // JUMPDEST.
+        let ((.., error), _) = check(&[0x60, 1, 0x60, 2, 0x02, 0x56], 1000);
+        assert_eq!(error, Some(VmError::InvalidJumpDestError));
     }
 
-}
+    #[test]
+    fn memory() {
+        // PUSH1 0xff PUSH1 33 MSTORE8 PUSH1 2 PUSH1 1 MSTORE MSIZE PUSH1 0
+        // MLOAD PUSH1 16 PUSH1 0 PUSH1 50 MCOPY MSIZE
+        let code = [
+            0x60, 0xff, 0x60, 33, 0x53, 0x60, 2, 0x60, 1, 0x52, 0x59, 0x60, 0, 0x51, 0x60, 16, 0x60, 0, 0x60, 50, 0x5e,
+            0x59,
+        ];
+        let ((stack, memory, gas_left, ..), compiled) = check(&code, 1000);
+        assert!(compiled);
+        assert_eq!(stack, [U256::from(64_u32), U256::ZERO, U256::from(96_u32)]);
+        assert_eq!((memory[32], memory[33], memory[50 + 15]), (2, 0xff, 0));
+        // Three words of memory, and a word copied.
+        assert_eq!(gas_left, 1000 - 12 * 3 - 2 * 2 - 3 * 3 - 3);
 
-impl VStack {
-    pub fn new() -> Self {
-        Self { stack: Default::default(), old_values: 0, new_values: 0 }
+        // Out of gas growing memory.
+        let code = [0x60, 1, 0x61, 0x10, 0x00, 0x52, 0x60, 1];
+        let ((.., error), _) = check(&code, 400);
+        assert_eq!(error, Some(VmError::OutOfGasError));
+        // Out of gas at the PUSH1 after the MSTORE, whose memory the segment
+        // cannot pay for once it charged the PUSH1, and with exactly enough.
+        let ((.., error), _) = check(&[0x60, 1, 0x5f, 0x52, 0x60, 1], 3 + 2 + 3 + 3 + 2);
+        assert_eq!(error, Some(VmError::OutOfGasError));
+        let ((.., gas_left, _, _, _, error), _) = check(&[0x60, 1, 0x5f, 0x52, 0x00, 0x60, 1], 3 + 2 + 3 + 3);
+        assert_eq!((gas_left, error), (0, None));
+        // PUSH32 MAX MLOAD, past any memory that can be paid for.
+        let ((.., error), _) = check(&[&[0x7f][..], &[0xff; 32], &[0x51]].concat(), 1000);
+        assert_eq!(error, Some(VmError::OutOfGasError));
     }
 
-    /// Ensure at least n items on the vstack.
-    pub(crate) fn prep(&mut self, n: usize) {
-        while self.stack.len() < n {
-            self.stack.push_back(VElem::Bp(self.old_values as i32));
-            self.old_values += 32;
+    #[test]
+    fn calls() {
+        // PUSH1 32 PUSH0 PUSH0 PUSH0 (PUSH0) PUSH20 CALLEE GAS CALL, and
+        // CALLCODE, DELEGATECALL and STATICCALL, then PUSH0 MLOAD.
+        for (op, value) in [(0xf1, true), (0xf2, true), (0xf4, false), (0xfa, false)] {
+            let mut code = vec![0x60, 32, 0x5f, 0x5f, 0x5f];
+            if value {
+                code.push(0x5f);
+            }
+            code.extend([&[0x73][..], &CALLEE, &[0x5a, op, 0x5f, 0x51]].concat());
+            let ((stack, ..), compiled) = check(&code, 100_000);
+            assert_eq!((stack, compiled), (vec![U256::ONE, U256::from(42_u32)], true), "{op:02x}");
         }
+
+        // GAS PUSH1 2 GAS, the exact gas left at each.
+        let ((stack, ..), _) = check(&[0x5a, 0x60, 2, 0x5a], 1000);
+        assert_eq!(stack, [U256::from(998_u32), U256::from(2_u32), U256::from(993_u32)]);
+        // A CALL with too few operands, and without the gas for it.
+        let ((.., error), _) = check(&[0x5f, 0x5a, 0xf1], 1000);
+        assert_eq!(error, Some(VmError::StackUnderflowError));
+        let code = [&[0x5f, 0x5f, 0x5f, 0x5f, 0x5f, 0x73][..], &CALLEE, &[0x5a, 0xf1]].concat();
+        let ((.., error), _) = check(&code, 2000);
+        assert_eq!(error, Some(VmError::OutOfGasError));
     }
 
-    pub(crate) fn top1(&mut self) -> VElem {
-        self.prep(1);
-        self.stack.pop_front().unwrap()
+    #[test]
+    fn data() {
+        // PUSH1 0xff PUSH0 MSTORE8 PUSH1 1 PUSH0 KECCAK256 PUSH0 PUSH0 KECCAK256
+        let code = [0x60, 0xff, 0x5f, 0x53, 0x60, 1, 0x5f, 0x20, 0x5f, 0x5f, 0x20];
+        let ((stack, _, gas_left, ..), compiled) = check(&code, 1000);
+        assert!(compiled);
+        let hashes = [keccak256(&[0xff]), keccak256(&[])].map(|hash| U256::from_be_bytes(hash.0));
+        assert_eq!(stack, hashes);
+        assert_eq!(gas_left, 1000 - 2 * 3 - 4 * 2 - 3 - 3 - (30 + 6) - 30);
+        // CODESIZE PUSH0 PUSH0 CODECOPY PUSH0 MLOAD
+        let code = [0x38, 0x5f, 0x5f, 0x39, 0x5f, 0x51];
+        let ((stack, memory, ..), _) = check(&code, 1000);
+        assert_eq!((stack, &memory[..6]), (vec![U256::from_be_slice(&[&code[..], &[0; 26]].concat())], &code[..]));
+
+        // PUSH4 0xdeadbeef PUSH0 MSTORE PUSH0 PUSH0 PUSH1 32 PUSH0 PUSH0 PUSH20
+        // ECHO GAS CALL RETURNDATASIZE PUSH0 PUSH1 64 RETURNDATACOPY PUSH1 64
+        // MLOAD PUSH1 96 MLOAD
+        let code = [
+            &[0x63, 0xde, 0xad, 0xbe, 0xef, 0x5f, 0x52, 0x5f, 0x5f, 0x60, 32, 0x5f, 0x5f, 0x73][..],
+            &ECHO,
+            &[0x5a, 0xf1, 0x3d, 0x5f, 0x60, 64, 0x3e, 0x60, 64, 0x51, 0x60, 96, 0x51],
+        ]
+        .concat();
+        let ((stack, ..), compiled) = check(&code, 100_000);
+        assert!(compiled);
+        let word = U256::from(0xdeadbeef_u32);
+        assert_eq!(stack, [U256::ONE, word, word.shl(32)]);
+        // PUSH1 1 PUSH0 PUSH0 RETURNDATACOPY, past the end of the return data.
+        let ((.., error), _) = check(&[0x60, 1, 0x5f, 0x5f, 0x3e], 1000);
+        assert_eq!(error, Some(VmError::OutOfBoundsRead));
     }
 
-    pub(crate) fn top2(&mut self) -> (VElem, VElem) {
-        self.prep(2);
-        (self.stack.pop_front().unwrap(), self.stack.pop_front().unwrap())
+    #[test]
+    fn environment() {
+        // ADDRESS ORIGIN CALLER CALLVALUE GASPRICE COINBASE TIMESTAMP NUMBER
+        // PREVRANDAO GASLIMIT CHAINID SELFBALANCE BASEFEE BLOBBASEFEE CALLER
+        let code = [0x30, 0x32, 0x33, 0x34, 0x3a, 0x41, 0x42, 0x43, 0x44, 0x45, 0x46, 0x47, 0x48, 0x4a, 0x33];
+        let ((stack, _, gas_left, ..), compiled) = check(&code, 1000);
+        assert!(compiled);
+        assert_eq!(gas_left, 1000 - 14 * 2 - 5);
+        let caller = U256::from_be_slice(&[0xca; 20]);
+        assert_eq!((stack[2], stack[14]), (caller, caller));
+        assert_eq!((stack[6], stack[7]), (U256::from(1_700_000_000_u32), U256::from(7_u32)));
+        assert_eq!((stack[10], stack[12]), (U256::ONE, U256::from(10_u32)));
     }
 
-    pub(crate) fn top3(&mut self) -> (VElem, VElem, VElem) {
-        self.prep(3);
-        (self.stack.pop_front().unwrap(), self.stack.pop_front().unwrap(), self.stack.pop_front().unwrap())
+    #[test]
+    fn storage() {
+        // PUSH1 7 PUSH1 1 SSTORE PUSH1 1 SLOAD PUSH0 SLOAD PUSH1 1 SLOAD PUSH0
+        // PUSH1 1 SSTORE, which sets slot 1 and clears it again.
+        let code = [0x60, 7, 0x60, 1, 0x55, 0x60, 1, 0x54, 0x5f, 0x54, 0x60, 1, 0x54, 0x5f, 0x60, 1, 0x55];
+        let ((stack, _, gas_left, refund_counter, ..), compiled) = check(&code, 100_000);
+        assert!(compiled);
+        assert_eq!(stack, [U256::from(7_u32), U256::ZERO, U256::from(7_u32)]);
+        // A cold SSTORE setting the slot, warm and cold SLOADs, and a warm
+        // SSTORE restoring it.
+        assert_eq!(gas_left, 100_000 - 19 - (2100 + 20_000) - 100 - 2100 - 100 - 100);
+        assert_eq!(refund_counter, 20_000 - 100);
+        // PUSH1 5 PUSH1 1 TSTORE PUSH1 1 TLOAD
+        let ((stack, ..), _) = check(&[0x60, 5, 0x60, 1, 0x5d, 0x60, 1, 0x5c], 1000);
+        assert_eq!(stack, [U256::from(5_u32)]);
+
+        // PUSH1 1 PUSH0 SSTORE, with no more than the call stipend left, and
+        // without the gas for the write.
+        let ((.., error), _) = check(&[0x60, 1, 0x5f, 0x55], 5 + 2300);
+        assert_eq!(error, Some(VmError::OutOfGasError));
+        let ((.., error), _) = check(&[0x60, 1, 0x5f, 0x55], 5 + 20_000);
+        assert_eq!(error, Some(VmError::OutOfGasError));
+        // Writes in a static frame, and not.
+        for (address, op, status) in [(STORES, 0xfa, 0), (TSTORES, 0xfa, 0), (STORES, 0xf4, 1), (TSTORES, 0xf4, 1)] {
+            let code = [&[0x5f, 0x5f, 0x5f, 0x5f, 0x73][..], &address, &[0x5a, op]].concat();
+            assert_eq!(check(&code, 100_000).0 .0, [U256::from(status as u32)]);
+        }
     }
 
-    pub(crate) fn push(&mut self, e: VElem) {
-        self.stack.push_back(e);
+    #[test]
+    fn terminators() {
+        // PUSH1 42 PUSH0 MSTORE PUSH1 7 PUSH1 32 PUSH0 RETURN PUSH1 1, and
+        // the same with REVERT.
+        let mut code = [0x60, 42, 0x5f, 0x52, 0x60, 7, 0x60, 32, 0x5f, 0xf3, 0x60, 1];
+        let ((stack, _, gas_left, _, pc, output, error), compiled) = check(&code, 1000);
+        assert!(compiled);
+        assert_eq!((stack, gas_left, pc, error), (vec![U256::from(7_u32)], 1000 - 19, 10, None));
+        assert_eq!(U256::from_be_slice(&output), U256::from(42_u32));
+        code[9] = 0xfd;
+        let ((stack, _, gas_left, _, pc, output, error), _) = check(&code, 1000);
+        assert_eq!((stack, gas_left, pc, error), (vec![U256::from(7_u32)], 1000 - 19, 9, Some(VmError::Revert)));
+        assert_eq!(U256::from_be_slice(&output), U256::from(42_u32));
+        // RETURN of more memory than there is gas for.
+        let ((.., error), _) = check(&[0x61, 0x10, 0x00, 0x5f, 0xf3], 100);
+        assert_eq!(error, Some(VmError::OutOfGasError));
+
+        // PUSH1 1 STOP PUSH1 2, and PUSH1 1 INVALID.
+        let ((stack, .., pc, _, error), _) = check(&[0x60, 1, 0x00, 0x60, 2], 1000);
+        assert_eq!((stack, pc, error), (vec![U256::ONE], 3, None));
+        let ((stack, .., pc, _, error), _) = check(&[0x60, 1, 0xfe], 1000);
+        assert_eq!((stack, pc, error), (vec![U256::ONE], 2, Some(VmError::InvalidOpcode)));
+
+        // PUSH1 1 PUSH20 CALLEE SELFDESTRUCT PUSH1 2
+        let code = [&[0x60, 1, 0x73][..], &CALLEE, &[0xff, 0x60, 2]].concat();
+        let ((stack, .., error), compiled) = check(&code, 100_000);
+        assert_eq!((stack, error, compiled), (vec![U256::ONE], None, true));
     }
-}
 
-pub(crate) mod tests {
-    use revm::{db::{CacheDB, EmptyDB}, interpreter, primitives::{Bytecode, ExecutionResult, Output, ResultAndState, TxEnv, TxKind}, Context, Evm, EvmBuilder};
+    /// Check that `code` compiles to the lines of `expected`, leading
+    /// spaces left out.
+    fn snapshot(code: &[u8], expected: &[&str]) {
+        let program = Program::compile(code, ExecutionRules::CANCUN);
+        assert_eq!(compile_to_ir(code), program.ins);
+        let lines: Vec<_> = program.to_string().lines().map(|line| line.trim_start().to_string()).collect();
+        assert_eq!(lines, expected);
+    }
+
+    #[test]
+    fn ir() {
+        // PUSH1 0x80 PUSH1 0x40 MSTORE CALLVALUE DUP1 ISZERO PUSH1 14 JUMPI
+        // PUSH0 DUP1 REVERT JUMPDEST POP CALLER PUSH0 SSTORE STOP, a prologue
+        // rejecting value.
+        let code = [
+            0x60, 0x80, 0x60, 0x40, 0x52, 0x34, 0x80, 0x15, 0x60, 14, 0x57, 0x5f, 0x80, 0xfd, 0x5b, 0x50, 0x33, 0x5f, 0x55,
+            0x00,
+        ];
+        snapshot(
+            &code,
+            &[
+                "0: begin pc 0 gas 30 need 0 grow 3",
+                "1: mstore 64, 128 else exit 0",
+                "2: r0 = callvalue",
+                "3: r1 = iszero r0",
+                "4: flush pop 0 push [r0]",
+                "5: goto 8 if r1",
+                "6: begin pc 11 gas 5 need 0 grow 2",
+                "7: revert 0, 0 exit 2 else exit 1",
+                "8: begin pc 14 gas 7 need 1 grow 1",
+                "9: r0 = caller",
+                "10: sstore 0, r0 else exit 3",
+                "11: stop exit 4",
+                "exit 0: pc 4 refund 24 pop 0 push [128, 64]",
+                "exit 1: pc 13 refund 0 pop 0 push [0, 0]",
+                "exit 2: pc 13 refund 0 pop 0 push []",
+                "exit 3: pc 18 refund 0 pop 1 push [r0, 0]",
+                "exit 4: pc 20 refund 0 pop 1 push []",
+            ],
+        );
+
+        // PUSH1 5 JUMPDEST PUSH1 1 SWAP1 SUB DUP1 PUSH1 2 JUMPI PUSH1 42, a
+        // loop.
+        let code = [0x60, 5, 0x5b, 0x60, 1, 0x90, 0x03, 0x80, 0x60, 2, 0x57, 0x60, 42];
+        snapshot(
+            &code,
+            &[
+                "0: begin pc 0 gas 3 need 0 grow 1",
+                "1: flush pop 0 push [5]",
+                "2: begin pc 2 gas 26 need 1 grow 2",
+                "3: r0 = sub bp[-1], 1",
+                "4: flush pop 1 push [r0]",
+                "5: goto 2 if r0",
+                "6: begin pc 11 gas 3 need 0 grow 1",
+                "7: stop exit 0",
+                "exit 0: pc 13 refund 0 pop 0 push [42]",
+            ],
+        );
 
-    use crate::EjitEvm;
+        // PUSH1 32 PUSH0 PUSH0 CALLDATACOPY PUSH0 MLOAD PUSH1 32 PUSH0
+        // KECCAK256 GAS JUMP, with exits before each instruction which can
+        // run out of gas, and a jump to a computed destination.
+        let code = [0x60, 32, 0x5f, 0x5f, 0x37, 0x5f, 0x51, 0x60, 32, 0x5f, 0x20, 0x5a, 0x56];
+        snapshot(
+            &code,
+            &[
+                "0: begin pc 0 gas 50 need 0 grow 3",
+                "1: copy calldata 0, 0, 32 else exit 0",
+                "2: r0 = mload 0 else exit 1",
+                "3: r1 = keccak256 0, 32 else exit 2",
+                "4: host gas exit 3",
+                "5: begin pc 12 gas 8 need 1 grow 0",
+                "6: r0 = bp[-1]",
+                "7: flush pop 1 push []",
+                "8: jump r0 at pc 12",
+                "exit 0: pc 4 refund 43 pop 0 push [32, 0, 0]",
+                "exit 1: pc 6 refund 38 pop 0 push [0]",
+                "exit 2: pc 10 refund 30 pop 0 push [r0, 32, 0]",
+                "exit 3: pc 11 refund 0 pop 0 push [r0, r1]",
+            ],
+        );
+    }
 
     #[test]
-    fn default() {
-        let context = Context::default();
-        let db = CacheDB::new(EmptyDB::new());
-        let mut tx_env = TxEnv::default();
-        tx_env.transact_to = TxKind::Create;
-        use interpreter::opcode::*;
-        tx_env.data = vec![PUSH1, 2, PUSH1, 0, MSTORE, PUSH1, 0x20, PUSH1, 0, RETURN].into();
-        let mut evm = Evm::builder()
-            .with_tx_env(tx_env)
-            .build();
+    fn folding() {
+        // PUSH1 2 PUSH1 3 MUL CALLER PUSH0 ADD POP MSIZE PUSH1 1 AND CODESIZE
+        // ADD: a product of constants, an addition of zero whose result is
+        // popped, and sizes added on 64 bits.
+        let code = [0x60, 2, 0x60, 3, 0x02, 0x33, 0x5f, 0x01, 0x50, 0x59, 0x60, 1, 0x16, 0x38, 0x01];
+        snapshot(
+            &code,
+            &[
+                "0: begin pc 0 gas 33 need 0 grow 3",
+                "1: r0 = msize",
+                "2: r0 = and64 1, r0",
+                "3: r1 = size code",
+                "4: r0 = add64 r1, r0",
+                "5: stop exit 0",
+                "exit 0: pc 15 refund 0 pop 0 push [6, r0]",
+            ],
+        );
+        let ((stack, ..), _) = check(&code, 1000);
+        assert_eq!(stack, [U256::from(6_u32), U256::from(15_u32)]);
 
-        let res1 = evm.transact();
+        // CALLER PUSH1 1 ADD, too wide for 64 bits.
+        snapshot(
+            &[0x33, 0x60, 1, 0x01],
+            &[
+                "0: begin pc 0 gas 8 need 0 grow 2",
+                "1: r0 = caller",
+                "2: r0 = add 1, r0",
+                "3: stop exit 0",
+                "exit 0: pc 4 refund 0 pop 0 push [r0]",
+            ],
+        );
+    }
 
-        let mut ejit_evm = EjitEvm::new(evm);
+    #[test]
+    fn spills() {
+        // 20 x (PUSH1 n NUMBER MUL), more results than registers, then 19 x
+        // ADD, with n from 2 so that none of them folds.
+        let mut code: Vec<u8> = (2..22).flat_map(|n| [0x60, n, 0x43, 0x02]).collect();
+        code.extend([0x01; 19]);
+        let program = Program::compile(&code, ExecutionRules::CANCUN);
+        assert!(matches!(program.ins[2 * NUM_REGS], Ins::Flush { pop: 0, .. }));
+        let ((stack, ..), compiled) = check(&code, 1000);
+        assert_eq!((stack, compiled), (vec![U256::from(7 * 230_u32)], true));
 
-        let res2 = ejit_evm.transact();
+        // A spill of items below the segment: SWAP1 to an item on the stack,
+        // with all registers taken.
+        let mut code = vec![0x60, 1, 0x5b];
+        code.extend((2..18).flat_map(|n| [0x60, n, 0x43, 0x02]));
+        code.extend([0x90, 0x60, 1, 0x02]);
+        check(&code, 1000);
+    }
 
-        let Ok(ResultAndState { result: ExecutionResult::Success { output: Output::Create(out1, addr), .. }, ..}) = res1 else {
-            unreachable!()
-        };
+    #[test]
+    fn deep_stacks() {
+        // 1023 pushes, then DUP16 overflows.
+        let mut code = [0x5f].repeat(1023);
+        code.extend([0x60, 1, 0x01, 0x8f, 0x8f]);
+        let ((stack, .., error), _) = check(&code, 100_000);
+        assert_eq!((stack.len(), error), (1024, Some(VmError::StackOverflowError)));
+    }
 
-        let Ok(ResultAndState { result: ExecutionResult::Success { output: Output::Create(out2, addr), .. }, ..}) = res2 else {
-            unreachable!()
-        };
+    /// Instructions on the stack, with immediates for the pushes.
+    fn ops() -> impl Strategy<Value = Vec<u8>> {
+        let op = prop_oneof![
+            (0x01_u8..=0x0b).prop_filter("EXP", |op| *op != 0x0a).prop_map(|op| vec![op]),
+            (0x10_u8..=0x1d).prop_map(|op| vec![op]),
+            (0x80_u8..=0x9f).prop_map(|op| vec![op]),
+            Just(vec![0x50]),
+            Just(vec![0x5f]),
+            // MLOAD, MSTORE, MSTORE8, MSIZE and MCOPY.
+            (0x51_u8..=0x53).prop_map(|op| vec![op]),
+            Just(vec![0x59]),
+            Just(vec![0x5e]),
+            // KECCAK256, and loads and copies of call data, code and return
+            // data.
+            Just(vec![0x20]),
+            prop::sample::select(vec![0x35_u8, 0x36, 0x37, 0x38, 0x39, 0x3d, 0x3e]).prop_map(|op| vec![op]),
+            // Values of the environment, and SELFBALANCE.
+            prop::sample::select(vec![0x30_u8, 0x32, 0x33, 0x34, 0x3a, 0x41, 0x42, 0x43, 0x44, 0x45, 0x46, 0x47, 0x48, 0x4a])
+                .prop_map(|op| vec![op]),
+            // SLOAD, SSTORE, TLOAD and TSTORE.
+            prop::sample::select(vec![0x54_u8, 0x55, 0x5c, 0x5d]).prop_map(|op| vec![op]),
+            // GAS and calls, mostly to empty accounts and precompiles.
+            Just(vec![0x5a]),
+            prop::sample::select(vec![0xf1_u8, 0xf2, 0xf4, 0xfa]).prop_map(|op| vec![op]),
+            // STOP, RETURN, REVERT, INVALID and SELFDESTRUCT.
+            prop::sample::select(vec![0x00_u8, 0xf3, 0xfd, 0xfe, 0xff]).prop_map(|op| vec![op]),
+            // PC, JUMP, JUMPI, JUMPDEST and pushes of destinations.
+            (0x56_u8..=0x58).prop_map(|op| vec![op]),
+            Just(vec![0x5b]),
+            (0..64_u8).prop_map(|n| vec![0x60, n]),
+            any::<u8>().prop_map(|n| vec![0x60, n]),
+            any::<[u8; 32]>().prop_map(|n| [&[0x7f][..], &n].concat()),
+        ];
+        vec(op, 0..64).prop_map(|ops| ops.concat())
+    }
 
-        todo!();
+    proptest! {
+        #[test]
+        fn narrowed_instructions_match(a in any::<u64>(), b in any::<u64>(), shift_a in 0..64_u32, shift_b in 0..64_u32, op in 0..20_usize) {
+            use super::ir::BinOp::*;
+            let op = [Add, Mul, Sub, Div, SDiv, Mod, SMod, SignExtend, Lt, Gt, SLt, SGt, Eq, And, Or, Xor, Byte, Shl, Shr, Sar][op];
+            let (a, b) = (a >> shift_a, b >> shift_b);
+            let (bits_a, bits_b) = (64 - a.leading_zeros(), 64 - b.leading_zeros());
+            let bits = op.bits(bits_a, bits_b, Some(U256::from(a)));
+            let result = op.apply(U256::from(a), U256::from(b));
+            prop_assert!(result.bits() <= bits, "{op:?} {a} {b}");
+            if bits <= 64 && !matches!(op, Sub | SignExtend) {
+                prop_assert_eq!(U256::from(op.apply_u64(a, b)), result, "{:?} {} {}", op, a, b);
+            }
+        }
+
+        #[test]
+        fn compiled_code_runs_as_interpreted(prefix in vec(any::<u8>(), 0..8), code in ops(), gas in 0..2000_u128) {
+            // A few items on the stack to start with, most of the time.
+            let code = [prefix.iter().flat_map(|n| [0x60, *n]).collect(), code].concat();
+            check(&code, gas);
+        }
     }
 }
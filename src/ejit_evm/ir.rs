@@ -0,0 +1,586 @@
+//! The register code EVM code is compiled to.
+//!
+//! Code is split into segments of straight-line instructions. A segment
+//! starts with `Ins::Begin`, which charges the static gas of all of its
+//! instructions and checks the stack depth once, so that the instructions
+//! in it do neither. Operands are the stack items as the compiler knows
+//! them, `VElem`s, and the stack itself is only written by `Ins::Flush`
+//! and on leaving compiled code.
+//!
+//! Instructions, and `Program`s, are written out by their `Display`
+//! implementations one per line, as in `r2 = add 0x1, bp[-1]`.
+
+use std::{cmp::Ordering, fmt};
+
+use crate::ethereum::{
+    cancun::vm::{exceptions::VmError, instructions::Ops},
+    ethereum_types::numeric::U256,
+};
+
+/// A register, holding one stack item.
+pub type R = u8;
+
+/// The number of registers. When an instruction needs a register and all
+/// of them hold stack items, the items are written to the stack and read
+/// from there.
+pub const NUM_REGS: usize = 16;
+
+/// A stack item as the compiler knows it.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum VElem {
+    /// A constant, ie. pushed by PUSH4 etc.
+    Constant(U256),
+
+    /// The stack item at `bp + n`, where `bp` is the stack length at the
+    /// last `Begin` or `Flush`. Items below it are negative, the top one
+    /// being `Bp(-1)`.
+    Bp(i32),
+
+    /// In a register.
+    Reg(R),
+}
+
+impl VElem {
+    pub(crate) fn as_constant(&self) -> Option<U256> {
+        match self {
+            VElem::Constant(c) => Some(*c),
+            _ => None,
+        }
+    }
+}
+
+impl fmt::Display for VElem {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            // Decimal, unless wider than 64 bits.
+            VElem::Constant(c) if *c <= U256::from(u64::MAX) => write!(f, "{c}"),
+            VElem::Constant(c) => write!(f, "{c:?}"),
+            VElem::Bp(n) => write!(f, "bp[{n}]"),
+            VElem::Reg(r) => write!(f, "r{r}"),
+        }
+    }
+}
+
+/// An instruction on two stack items, the first of which is the top.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum BinOp {
+    Add,
+    Mul,
+    Sub,
+    Div,
+    SDiv,
+    Mod,
+    SMod,
+    SignExtend,
+    Lt,
+    Gt,
+    SLt,
+    SGt,
+    Eq,
+    And,
+    Or,
+    Xor,
+    Byte,
+    Shl,
+    Shr,
+    Sar,
+}
+
+/// A shift amount, saturating at 256.
+fn shift_amount(shift: U256) -> u32 {
+    if shift < U256::from(256_u32) { shift.low_u64() as u32 } else { 256 }
+}
+
+impl BinOp {
+    /// The result of the instruction with `a` on top of `b`.
+    pub fn apply(self, a: U256, b: U256) -> U256 {
+        match self {
+            BinOp::Add => a + b,
+            BinOp::Mul => a * b,
+            BinOp::Sub => a - b,
+            BinOp::Div => a.div_rem(b).0,
+            BinOp::SDiv => a.signed_div(b),
+            BinOp::Mod => a.div_rem(b).1,
+            BinOp::SMod => a.signed_rem(b),
+            BinOp::SignExtend => b.sign_extend(a),
+            BinOp::Lt => U256::from((a < b) as u32),
+            BinOp::Gt => U256::from((a > b) as u32),
+            BinOp::SLt => U256::from((a.signed_cmp(&b) == Ordering::Less) as u32),
+            BinOp::SGt => U256::from((a.signed_cmp(&b) == Ordering::Greater) as u32),
+            BinOp::Eq => U256::from((a == b) as u32),
+            BinOp::And => a & b,
+            BinOp::Or => a | b,
+            BinOp::Xor => a ^ b,
+            BinOp::Byte => b.byte(a),
+            BinOp::Shl => b.shl(shift_amount(a)),
+            BinOp::Shr => b.shr(shift_amount(a)),
+            BinOp::Sar => b.sar(shift_amount(a)),
+        }
+    }
+
+    /// The result without an instruction, if the operands are constants
+    /// or one of them decides it, as in `x + 0` or `x * 0`.
+    pub(crate) fn fold(self, a: VElem, b: VElem) -> Option<VElem> {
+        let (ca, cb) = (a.as_constant(), b.as_constant());
+        if let (Some(ca), Some(cb)) = (ca, cb) {
+            return Some(VElem::Constant(self.apply(ca, cb)));
+        }
+        let (zero, one, max) = (Some(U256::ZERO), Some(U256::ONE), Some(U256::MAX));
+        match self {
+            BinOp::Add | BinOp::Or | BinOp::Xor if ca == zero => Some(b),
+            BinOp::Add | BinOp::Or | BinOp::Xor | BinOp::Sub if cb == zero => Some(a),
+            BinOp::Mul if ca == one => Some(b),
+            BinOp::Mul | BinOp::Div if cb == one => Some(a),
+            BinOp::And if ca == max => Some(b),
+            BinOp::And if cb == max => Some(a),
+            BinOp::Shl | BinOp::Shr | BinOp::Sar if ca == zero => Some(b),
+            BinOp::Mul | BinOp::And | BinOp::Div | BinOp::SDiv | BinOp::Mod | BinOp::SMod
+                if ca == zero || cb == zero =>
+            {
+                Some(VElem::Constant(U256::ZERO))
+            }
+            _ => None,
+        }
+    }
+
+    /// The most bits the result can have, for operands of at most `a` and
+    /// `b` bits, with `a` the value of the first if it is a constant.
+    pub(crate) fn bits(self, a: u32, b: u32, constant: Option<U256>) -> u32 {
+        let shift = constant.map(shift_amount);
+        let bits = match self {
+            BinOp::Add => a.max(b) + 1,
+            BinOp::Mul => a + b,
+            BinOp::Div => a,
+            BinOp::Mod => a.min(b),
+            // Both operands are positive.
+            BinOp::SDiv if a < 256 && b < 256 => a,
+            BinOp::SMod if a < 256 && b < 256 => a.min(b),
+            BinOp::Lt | BinOp::Gt | BinOp::SLt | BinOp::SGt | BinOp::Eq => 1,
+            BinOp::And => a.min(b),
+            BinOp::Or | BinOp::Xor => a.max(b),
+            BinOp::Byte => 8,
+            BinOp::Shl => shift.map_or(256, |shift| if shift < 256 { b + shift } else { 0 }),
+            BinOp::Shr => b.saturating_sub(shift.unwrap_or(0)),
+            BinOp::Sar if b < 256 => b.saturating_sub(shift.unwrap_or(0)),
+            BinOp::Sub | BinOp::SDiv | BinOp::SMod | BinOp::SignExtend | BinOp::Sar => 256,
+        };
+        bits.min(256)
+    }
+
+    /// The result of the instruction on 64-bit operands, for operands and
+    /// a result which fit in 64 bits as `bits` finds. The signed
+    /// instructions are then the same as the unsigned ones.
+    pub fn apply_u64(self, a: u64, b: u64) -> u64 {
+        match self {
+            BinOp::Add => a.wrapping_add(b),
+            BinOp::Mul => a.wrapping_mul(b),
+            BinOp::Div | BinOp::SDiv => a.checked_div(b).unwrap_or(0),
+            BinOp::Mod | BinOp::SMod => a.checked_rem(b).unwrap_or(0),
+            BinOp::Lt | BinOp::SLt => (a < b) as u64,
+            BinOp::Gt | BinOp::SGt => (a > b) as u64,
+            BinOp::Eq => (a == b) as u64,
+            BinOp::And => a & b,
+            BinOp::Or => a | b,
+            BinOp::Xor => a ^ b,
+            // The bytes of `b` are the last 8 of the word.
+            BinOp::Byte if (24..32).contains(&a) => (b >> (8 * (31 - a))) & 0xff,
+            BinOp::Byte => 0,
+            BinOp::Shl if a < 64 => b << a,
+            BinOp::Shr | BinOp::Sar if a < 64 => b >> a,
+            BinOp::Shl | BinOp::Shr | BinOp::Sar => 0,
+            BinOp::Sub | BinOp::SignExtend => unreachable!("{self:?} is not narrowed"),
+        }
+    }
+}
+
+/// An instruction on one stack item.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum UnOp {
+    IsZero,
+    Not,
+}
+
+impl UnOp {
+    pub fn apply(self, a: U256) -> U256 {
+        match self {
+            UnOp::IsZero => U256::from(a.is_zero() as u32),
+            UnOp::Not => !a,
+        }
+    }
+
+    pub(crate) fn bits(self) -> u32 {
+        match self {
+            UnOp::IsZero => 1,
+            UnOp::Not => 256,
+        }
+    }
+}
+
+/// An instruction on three stack items, the first of which is the top.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TernOp {
+    AddMod,
+    MulMod,
+}
+
+impl TernOp {
+    pub fn apply(self, a: U256, b: U256, c: U256) -> U256 {
+        match self {
+            TernOp::AddMod => a.add_mod(b, c),
+            TernOp::MulMod => a.mul_mod(b, c),
+        }
+    }
+}
+
+/// A value of the frame, its transaction or its block, which stays the
+/// same while the frame runs.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Env {
+    Address,
+    Origin,
+    Caller,
+    CallValue,
+    GasPrice,
+    Coinbase,
+    Timestamp,
+    Number,
+    PrevRandao,
+    GasLimit,
+    ChainId,
+    BaseFee,
+    BlobBaseFee,
+}
+
+impl Env {
+    /// The number of values.
+    pub const COUNT: usize = 13;
+
+    /// The most bits the value can have.
+    pub(crate) fn bits(self) -> u32 {
+        match self {
+            Env::Address | Env::Origin | Env::Caller | Env::Coinbase => 160,
+            Env::GasPrice | Env::Number | Env::GasLimit | Env::BaseFee | Env::BlobBaseFee => 128,
+            Env::ChainId => 64,
+            Env::CallValue | Env::Timestamp | Env::PrevRandao => 256,
+        }
+    }
+}
+
+/// How compiled code ends the frame.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Status {
+    /// `STOP`, or running off the end of the code.
+    Stop,
+    /// `RETURN`, with the output.
+    Return,
+    /// `REVERT`, with the output.
+    Revert,
+    /// An undefined instruction, or one the rules do not allow.
+    Invalid,
+}
+
+impl Status {
+    /// What the frame returns, as `interpret` does.
+    pub fn result(self) -> Result<(), VmError> {
+        match self {
+            Status::Stop | Status::Return => Ok(()),
+            Status::Revert => Err(VmError::Revert),
+            Status::Invalid => Err(VmError::InvalidOpcode),
+        }
+    }
+}
+
+/// A buffer of the frame which instructions copy from.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Buffer {
+    CallData,
+    Code,
+    ReturnData,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Ins {
+    /// Start of the segment at `pc`. Charges `gas`, the static gas of the
+    /// segment, if the stack has at least `need` items and room for `grow`
+    /// more, and leaves to the interpreter at `pc` otherwise, which fails
+    /// as the instructions would.
+    Begin { pc: u32, gas: u64, need: u16, grow: u16 },
+
+    /// `dest = op(a, b)`
+    Bin(BinOp, R, VElem, VElem),
+
+    /// `dest = op(a, b)` on the low 64 bits of `a` and `b`, for operands
+    /// and a result known to fit in them.
+    Narrow(BinOp, R, VElem, VElem),
+
+    /// `dest = op(a)`
+    Un(UnOp, R, VElem),
+
+    /// `dest = op(a, b, c)`
+    Tern(TernOp, R, VElem, VElem, VElem),
+
+    /// `dest = a`
+    Mov(R, VElem),
+
+    /// `dest` = the word in memory at `offset`, growing memory, or leave
+    /// through exit n if that costs more gas than is left.
+    MLoad { dest: R, offset: VElem, exit: u32 },
+
+    /// Write `value` to memory at `offset`, as `MLoad`.
+    MStore { offset: VElem, value: VElem, exit: u32 },
+
+    /// Write the low byte of `value` to memory at `offset`, as `MLoad`.
+    MStore8 { offset: VElem, value: VElem, exit: u32 },
+
+    /// `dest` = the size of memory.
+    MSize(R),
+
+    /// `dest` = the value of the environment.
+    Env(R, Env),
+
+    /// `dest` = the balance of the current account.
+    SelfBalance(R),
+
+    /// Copy `len` bytes of memory from `src` to `dest`, charging the copy
+    /// gas, as `MLoad`.
+    MCopy { dest: VElem, src: VElem, len: VElem, exit: u32 },
+
+    /// `dest` = the hash of `len` bytes of memory at `offset`, charging the
+    /// gas of the words hashed, as `MLoad`.
+    Keccak { dest: R, offset: VElem, len: VElem, exit: u32 },
+
+    /// `dest` = the word of call data at `offset`, zero padded.
+    CallDataLoad { dest: R, offset: VElem },
+
+    /// `dest` = the length of the buffer.
+    Size(R, Buffer),
+
+    /// Copy `len` bytes of the buffer from `src` to memory at `dest`,
+    /// zero padded past its end, charging the copy gas, as `MLoad`. Copies
+    /// past the end of the return data leave through the exit as well.
+    Copy { buffer: Buffer, dest: VElem, src: VElem, len: VElem, exit: u32 },
+
+    /// `dest` = the value at `key` in the storage of the current account,
+    /// charging the gas of the access and warming it, or leave through exit
+    /// n if that costs more gas than is left.
+    SLoad { dest: R, key: VElem, exit: u32 },
+
+    /// Write `value` at `key` in the storage of the current account, as
+    /// `SLoad`, counting its refund, and leave as well in a static frame or
+    /// with no more gas left than the call stipend.
+    SStore { key: VElem, value: VElem, exit: u32 },
+
+    /// `dest` = the value at `key` in the transient storage of the current
+    /// account.
+    TLoad { dest: R, key: VElem },
+
+    /// Write `value` at `key` in the transient storage of the current
+    /// account, or leave through exit n in a static frame.
+    TStore { key: VElem, value: VElem, exit: u32 },
+
+    /// Replace the top `pop` items of the stack with `push`, the last of
+    /// which is the new top, and address slots from the new top.
+    Flush { pop: u16, push: Box<[VElem]> },
+
+    /// Continue at the `JUMPDEST` at `dest`, failing as the `JUMP` at `pc`
+    /// if there is none.
+    Jump { dest: VElem, pc: u32 },
+
+    /// `Jump` if `cond` is not zero.
+    JumpI { dest: VElem, cond: VElem, pc: u32 },
+
+    /// Continue at instruction n, the `Begin` of a `JUMPDEST`.
+    Goto(u32),
+
+    /// `Goto` if the value is not zero.
+    GotoIf(u32, VElem),
+
+    /// Write the stack as for `Exit` number n and run the instruction at its
+    /// pc by its implementation, then continue with the next instruction,
+    /// the `Begin` of the segment after it.
+    Host(Ops, u32),
+
+    /// Leave compiled code through `Exit` number n, and run the rest of the
+    /// frame in the interpreter.
+    Interpret(u32),
+
+    /// Write the stack and pc as for `Exit` number n, and end the frame
+    /// with the status.
+    End(Status, u32),
+
+    /// Set the output to `len` bytes of memory at `offset`, growing memory,
+    /// and `End` with the status through exit `done`, or leave through
+    /// `exit` as `MLoad` does.
+    Output { status: Status, offset: VElem, len: VElem, exit: u32, done: u32 },
+}
+
+impl Ins {
+    /// The register the instruction writes.
+    pub(crate) fn dest(&self) -> Option<R> {
+        match self {
+            Ins::Bin(_, dest, ..)
+            | Ins::Narrow(_, dest, ..)
+            | Ins::Un(_, dest, _)
+            | Ins::Tern(_, dest, ..)
+            | Ins::Mov(dest, _)
+            | Ins::MLoad { dest, .. }
+            | Ins::MSize(dest)
+            | Ins::Env(dest, _)
+            | Ins::SelfBalance(dest)
+            | Ins::Keccak { dest, .. }
+            | Ins::CallDataLoad { dest, .. }
+            | Ins::Size(dest, _)
+            | Ins::SLoad { dest, .. }
+            | Ins::TLoad { dest, .. } => Some(*dest),
+            _ => None,
+        }
+    }
+
+    /// Whether the instruction does nothing but write its register, so
+    /// that it can be left out if the value is not read.
+    pub(crate) fn is_pure(&self) -> bool {
+        matches!(
+            self,
+            Ins::Bin(..)
+                | Ins::Narrow(..)
+                | Ins::Un(..)
+                | Ins::Tern(..)
+                | Ins::Mov(..)
+                | Ins::MSize(_)
+                | Ins::Env(..)
+                | Ins::SelfBalance(_)
+                | Ins::CallDataLoad { .. }
+                | Ins::Size(..)
+                | Ins::TLoad { .. }
+        )
+    }
+
+    /// The items the instruction reads, other than through its exit.
+    pub(crate) fn operands(&self) -> Vec<VElem> {
+        match self {
+            Ins::Bin(_, _, a, b) | Ins::Narrow(_, _, a, b) => vec![*a, *b],
+            Ins::Un(_, _, a) | Ins::Mov(_, a) => vec![*a],
+            Ins::Tern(_, _, a, b, c) => vec![*a, *b, *c],
+            Ins::MLoad { offset, .. } | Ins::CallDataLoad { offset, .. } => vec![*offset],
+            Ins::SLoad { key, .. } | Ins::TLoad { key, .. } => vec![*key],
+            Ins::MStore { offset, value, .. } | Ins::MStore8 { offset, value, .. } => vec![*offset, *value],
+            Ins::SStore { key, value, .. } | Ins::TStore { key, value, .. } => vec![*key, *value],
+            Ins::MCopy { dest, src, len, .. } | Ins::Copy { dest, src, len, .. } => vec![*dest, *src, *len],
+            Ins::Keccak { offset, len, .. } | Ins::Output { offset, len, .. } => vec![*offset, *len],
+            Ins::Flush { push, .. } => push.to_vec(),
+            Ins::Jump { dest, .. } => vec![*dest],
+            Ins::JumpI { dest, cond, .. } => vec![*dest, *cond],
+            Ins::GotoIf(_, cond) => vec![*cond],
+            Ins::Begin { .. }
+            | Ins::MSize(_)
+            | Ins::Env(..)
+            | Ins::SelfBalance(_)
+            | Ins::Size(..)
+            | Ins::Goto(_)
+            | Ins::Host(..)
+            | Ins::Interpret(_)
+            | Ins::End(..) => Vec::new(),
+        }
+    }
+
+    /// The exits the instruction may leave through.
+    pub(crate) fn exits(&self) -> Vec<u32> {
+        match self {
+            Ins::Output { exit, done, .. } => vec![*exit, *done],
+            Ins::MLoad { exit, .. }
+            | Ins::MStore { exit, .. }
+            | Ins::MStore8 { exit, .. }
+            | Ins::MCopy { exit, .. }
+            | Ins::Keccak { exit, .. }
+            | Ins::Copy { exit, .. }
+            | Ins::SLoad { exit, .. }
+            | Ins::SStore { exit, .. }
+            | Ins::TStore { exit, .. }
+            | Ins::Host(_, exit)
+            | Ins::Interpret(exit)
+            | Ins::End(_, exit) => vec![*exit],
+            _ => Vec::new(),
+        }
+    }
+}
+
+/// The lower case name of an operation, a variant without fields.
+fn name(op: &dyn fmt::Debug) -> String {
+    format!("{op:?}").to_lowercase()
+}
+
+/// Write `items` as a list.
+fn list(f: &mut fmt::Formatter<'_>, items: &[VElem]) -> fmt::Result {
+    write!(f, "[")?;
+    for (i, e) in items.iter().enumerate() {
+        if i > 0 {
+            write!(f, ", ")?;
+        }
+        write!(f, "{e}")?;
+    }
+    write!(f, "]")
+}
+
+impl fmt::Display for Ins {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Ins::Begin { pc, gas, need, grow } => write!(f, "begin pc {pc} gas {gas} need {need} grow {grow}"),
+            Ins::Bin(op, dest, a, b) => write!(f, "r{dest} = {} {a}, {b}", name(op)),
+            Ins::Narrow(op, dest, a, b) => write!(f, "r{dest} = {}64 {a}, {b}", name(op)),
+            Ins::Un(op, dest, a) => write!(f, "r{dest} = {} {a}", name(op)),
+            Ins::Tern(op, dest, a, b, c) => write!(f, "r{dest} = {} {a}, {b}, {c}", name(op)),
+            Ins::Mov(dest, a) => write!(f, "r{dest} = {a}"),
+            Ins::MLoad { dest, offset, exit } => write!(f, "r{dest} = mload {offset} else exit {exit}"),
+            Ins::MStore { offset, value, exit } => write!(f, "mstore {offset}, {value} else exit {exit}"),
+            Ins::MStore8 { offset, value, exit } => write!(f, "mstore8 {offset}, {value} else exit {exit}"),
+            Ins::MSize(dest) => write!(f, "r{dest} = msize"),
+            Ins::Env(dest, env) => write!(f, "r{dest} = {}", name(env)),
+            Ins::SelfBalance(dest) => write!(f, "r{dest} = selfbalance"),
+            Ins::MCopy { dest, src, len, exit } => write!(f, "mcopy {dest}, {src}, {len} else exit {exit}"),
+            Ins::Keccak { dest, offset, len, exit } => write!(f, "r{dest} = keccak256 {offset}, {len} else exit {exit}"),
+            Ins::CallDataLoad { dest, offset } => write!(f, "r{dest} = calldataload {offset}"),
+            Ins::Size(dest, buffer) => write!(f, "r{dest} = size {}", name(buffer)),
+            Ins::Copy { buffer, dest, src, len, exit } => {
+                write!(f, "copy {} {dest}, {src}, {len} else exit {exit}", name(buffer))
+            }
+            Ins::SLoad { dest, key, exit } => write!(f, "r{dest} = sload {key} else exit {exit}"),
+            Ins::SStore { key, value, exit } => write!(f, "sstore {key}, {value} else exit {exit}"),
+            Ins::TLoad { dest, key } => write!(f, "r{dest} = tload {key}"),
+            Ins::TStore { key, value, exit } => write!(f, "tstore {key}, {value} else exit {exit}"),
+            Ins::Flush { pop, push } => {
+                write!(f, "flush pop {pop} push ")?;
+                list(f, push)
+            }
+            Ins::Jump { dest, pc } => write!(f, "jump {dest} at pc {pc}"),
+            Ins::JumpI { dest, cond, pc } => write!(f, "jump {dest} if {cond} at pc {pc}"),
+            Ins::Goto(target) => write!(f, "goto {target}"),
+            Ins::GotoIf(target, cond) => write!(f, "goto {target} if {cond}"),
+            Ins::Host(op, exit) => write!(f, "host {} exit {exit}", name(op)),
+            Ins::Interpret(exit) => write!(f, "interpret exit {exit}"),
+            Ins::End(status, exit) => write!(f, "{} exit {exit}", name(status)),
+            Ins::Output { status, offset, len, exit, done } => {
+                write!(f, "{} {offset}, {len} exit {done} else exit {exit}", name(status))
+            }
+        }
+    }
+}
+
+/// A way out of compiled code at `pc`, to the interpreter or the end of
+/// the frame.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Exit {
+    pub pc: u32,
+    /// The gas charged by the `Begin` of the segment for the instructions
+    /// from `pc` on, which the interpreter charges again.
+    pub refund: u64,
+    /// The stack as of `pc`, as for `Ins::Flush`.
+    pub pop: u16,
+    pub push: Box<[VElem]>,
+}
+
+impl fmt::Display for Exit {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "pc {} refund {} pop {} push ", self.pc, self.refund, self.pop)?;
+        list(f, &self.push)
+    }
+}
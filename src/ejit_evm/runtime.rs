@@ -0,0 +1,337 @@
+//! Runs the register code of a `Program` on the frame of an `Evm`.
+
+use crate::ethereum::{
+    cancun::{
+        state::{get_account, get_storage, get_transient_storage, set_storage, set_transient_storage},
+        vm::{
+            exceptions::VmError,
+            gas::{
+                calculate_blob_gas_price, calculate_gas_extend_memory, GAS_CALL_STIPEND, GAS_COPY, GAS_KECCAK256_WORD,
+                GAS_RETURN_DATA_COPY,
+            },
+            instructions::{
+                op_implementation,
+                storage::{sload_gas, sstore_gas},
+                words,
+            },
+            interpreter::interpret,
+            memory::{buffer_read, memory_extend, memory_read_bytes, memory_write},
+            stack::STACK_LIMIT,
+            Evm,
+        },
+    },
+    crypto::hash::keccak256,
+    ethereum_types::{
+        bytes::Bytes32,
+        numeric::{Uint, U256},
+    },
+};
+
+use super::{
+    ir::{Buffer, Env, Exit, Ins, Status, VElem, NUM_REGS},
+    Program, NO_LABEL,
+};
+
+/// The registers and stack base of running compiled code.
+#[derive(Debug, Default)]
+pub struct InterpreterState {
+    pub(crate) regs: [U256; NUM_REGS],
+    /// The stack length at the last `Begin` or `Flush`.
+    pub(crate) bp: usize,
+    /// The items being written by a `Flush`.
+    scratch: Vec<U256>,
+    /// The environment values read so far, by `Env`.
+    env: [Option<U256>; Env::COUNT],
+}
+
+impl InterpreterState {
+    #[inline]
+    pub(crate) fn get(&self, stack: &[U256], e: &VElem) -> U256 {
+        match e {
+            VElem::Constant(c) => *c,
+            VElem::Bp(n) => stack[(self.bp as isize + *n as isize) as usize],
+            VElem::Reg(r) => self.regs[*r as usize],
+        }
+    }
+
+    /// Replace the top `pop` items of `stack` with `push`.
+    pub(crate) fn flush(&mut self, stack: &mut Vec<U256>, pop: u16, push: &[VElem]) {
+        let mut scratch = std::mem::take(&mut self.scratch);
+        scratch.clear();
+        scratch.extend(push.iter().map(|e| self.get(stack, e)));
+        stack.truncate(self.bp - pop as usize);
+        stack.extend_from_slice(&scratch);
+        self.bp = stack.len();
+        self.scratch = scratch;
+    }
+
+    /// Write the stack, gas and pc as of `exit`.
+    fn leave(&mut self, evm: &mut Evm, exit: &Exit) {
+        self.flush(&mut evm.stack, exit.pop, &exit.push);
+        evm.gas_left += exit.refund as Uint;
+        evm.pc = exit.pc as Uint;
+    }
+
+    /// Leave through `exit`, ending the frame with `status`.
+    fn end(&mut self, evm: &mut Evm, exit: &Exit, status: Status) -> Result<(), VmError> {
+        self.leave(evm, exit);
+        if let Status::Stop | Status::Return = status {
+            evm.running = false;
+        }
+        status.result()
+    }
+
+    /// Leave through `exit`, running the rest of the frame in the
+    /// interpreter.
+    fn interpret(&mut self, evm: &mut Evm, exit: &Exit) -> Result<(), VmError> {
+        self.leave(evm, exit);
+        interpret(evm)
+    }
+}
+
+/// Charge `gas` and the gas of growing memory to hold `extensions`, and
+/// grow it, or return `false` if that costs more than the gas left, so that
+/// the instruction is left to the interpreter.
+fn expand_memory(evm: &mut Evm, gas: Uint, extensions: &[(U256, U256)]) -> bool {
+    let Ok(extend_memory) = calculate_gas_extend_memory(&evm.memory, extensions) else {
+        return false;
+    };
+    let cost = gas + extend_memory.cost;
+    if evm.gas_left < cost {
+        return false;
+    }
+    evm.gas_left -= cost;
+    memory_extend(&mut evm.memory, extend_memory.expand_by);
+    true
+}
+
+/// The value of `env` for the frame of `evm`, as its instruction pushes it.
+fn environment(evm: &Evm, env: Env) -> U256 {
+    match env {
+        Env::Address => U256::from_be_slice(&evm.message.current_target[..]),
+        Env::Origin => U256::from_be_slice(&evm.env.origin[..]),
+        Env::Caller => U256::from_be_slice(&evm.message.caller[..]),
+        Env::CallValue => evm.message.value,
+        Env::GasPrice => U256::from_uint(evm.env.gas_price),
+        Env::Coinbase => U256::from_be_slice(&evm.env.coinbase[..]),
+        Env::Timestamp => evm.env.time,
+        Env::Number => U256::from_uint(evm.env.number),
+        Env::PrevRandao => U256::from_be_bytes(evm.env.prev_randao.0),
+        Env::GasLimit => U256::from_uint(evm.env.gas_limit),
+        Env::ChainId => U256::from(evm.env.chain_id),
+        Env::BaseFee => U256::from_uint(evm.env.base_fee_per_gas),
+        Env::BlobBaseFee => U256::from_uint(calculate_blob_gas_price(evm.env.excess_blob_gas)),
+    }
+}
+
+/// The contents of `buffer` in the frame of `evm`.
+fn buffer<'a>(evm: &'a Evm, buffer: Buffer) -> &'a [u8] {
+    match buffer {
+        Buffer::CallData => &evm.message.data,
+        Buffer::Code => &evm.message.code,
+        Buffer::ReturnData => &evm.return_data,
+    }
+}
+
+/// Warm `key` in the storage of the current account, from Berlin on.
+fn warm(evm: &mut Evm, key: &Bytes32) {
+    if evm.env.rules.berlin {
+        evm.accessed_storage_keys.insert((evm.message.current_target.clone(), key.clone()));
+    }
+}
+
+/// The instruction to continue at for a jump to `dest`, or the error of
+/// the jump at `pc` if `dest` is not a `JUMPDEST`.
+fn jump(program: &Program, evm: &mut Evm, dest: U256, pc: u32) -> Result<usize, VmError> {
+    let label = dest.to_uint().ok().and_then(|dest| program.labels.get(usize::try_from(dest).ok()?));
+    match label {
+        Some(&label) if label != NO_LABEL => Ok(label as usize),
+        _ => {
+            evm.pc = pc as Uint;
+            Err(VmError::InvalidJumpDestError)
+        }
+    }
+}
+
+/// Run `program` from the start of the frame of `evm` until it stops or
+/// fails, as `interpret` does.
+pub(crate) fn execute(program: &Program, evm: &mut Evm) -> Result<(), VmError> {
+    let mut state = InterpreterState::default();
+    let mut i = 0;
+    loop {
+        match &program.ins[i] {
+            Ins::Begin { pc, gas, need, grow } => {
+                state.bp = evm.stack.len();
+                let gas = *gas as Uint;
+                if state.bp < *need as usize || state.bp + *grow as usize > STACK_LIMIT || evm.gas_left < gas {
+                    evm.pc = *pc as Uint;
+                    return interpret(evm);
+                }
+                evm.gas_left -= gas;
+            }
+            Ins::Bin(op, dest, a, b) => {
+                let value = op.apply(state.get(&evm.stack, a), state.get(&evm.stack, b));
+                state.regs[*dest as usize] = value;
+            }
+            Ins::Narrow(op, dest, a, b) => {
+                let (a, b) = (state.get(&evm.stack, a).low_u64(), state.get(&evm.stack, b).low_u64());
+                state.regs[*dest as usize] = U256::from(op.apply_u64(a, b));
+            }
+            Ins::Un(op, dest, a) => {
+                state.regs[*dest as usize] = op.apply(state.get(&evm.stack, a));
+            }
+            Ins::Tern(op, dest, a, b, c) => {
+                let stack = &evm.stack;
+                let value = op.apply(state.get(stack, a), state.get(stack, b), state.get(stack, c));
+                state.regs[*dest as usize] = value;
+            }
+            Ins::MLoad { dest, offset, exit } => {
+                let offset = state.get(&evm.stack, offset);
+                if !expand_memory(evm, 0, &[(offset, U256::from(32_u32))]) {
+                    return state.interpret(evm, &program.exits[*exit as usize]);
+                }
+                let value = memory_read_bytes(&evm.memory, offset, U256::from(32_u32));
+                state.regs[*dest as usize] = U256::from_be_slice(&value);
+            }
+            Ins::MStore { offset, value, exit } => {
+                let offset = state.get(&evm.stack, offset);
+                if !expand_memory(evm, 0, &[(offset, U256::from(32_u32))]) {
+                    return state.interpret(evm, &program.exits[*exit as usize]);
+                }
+                let value = state.get(&evm.stack, value);
+                memory_write(&mut evm.memory, offset, &value.to_be_bytes());
+            }
+            Ins::MStore8 { offset, value, exit } => {
+                let offset = state.get(&evm.stack, offset);
+                if !expand_memory(evm, 0, &[(offset, U256::ONE)]) {
+                    return state.interpret(evm, &program.exits[*exit as usize]);
+                }
+                let value = state.get(&evm.stack, value);
+                memory_write(&mut evm.memory, offset, &[value.low_u64() as u8]);
+            }
+            Ins::MSize(dest) => state.regs[*dest as usize] = U256::from(evm.memory.len() as u64),
+            Ins::Env(dest, env) => {
+                state.regs[*dest as usize] = *state.env[*env as usize].get_or_insert_with(|| environment(evm, *env));
+            }
+            Ins::SelfBalance(dest) => {
+                state.regs[*dest as usize] = get_account(evm.env.state, &evm.message.current_target).balance;
+            }
+            Ins::MCopy { dest, src, len, exit } => {
+                let (dest, src, len) = (state.get(&evm.stack, dest), state.get(&evm.stack, src), state.get(&evm.stack, len));
+                let copied = words(len).is_ok_and(|words| expand_memory(evm, GAS_COPY * words, &[(src, len), (dest, len)]));
+                if !copied {
+                    return state.interpret(evm, &program.exits[*exit as usize]);
+                }
+                let value = memory_read_bytes(&evm.memory, src, len);
+                memory_write(&mut evm.memory, dest, &value);
+            }
+            Ins::Keccak { dest, offset, len, exit } => {
+                let (offset, len) = (state.get(&evm.stack, offset), state.get(&evm.stack, len));
+                let hashed = words(len).is_ok_and(|words| expand_memory(evm, GAS_KECCAK256_WORD * words, &[(offset, len)]));
+                if !hashed {
+                    return state.interpret(evm, &program.exits[*exit as usize]);
+                }
+                let hash = keccak256(&memory_read_bytes(&evm.memory, offset, len));
+                state.regs[*dest as usize] = U256::from_be_bytes(hash.0);
+            }
+            Ins::CallDataLoad { dest, offset } => {
+                let value = buffer_read(&evm.message.data, state.get(&evm.stack, offset), U256::from(32_u32));
+                state.regs[*dest as usize] = U256::from_be_slice(&value);
+            }
+            Ins::Size(dest, from) => state.regs[*dest as usize] = U256::from(buffer(evm, *from).len() as u64),
+            Ins::Copy { buffer: from, dest, src, len, exit } => {
+                let (dest, src, len) = (state.get(&evm.stack, dest), state.get(&evm.stack, src), state.get(&evm.stack, len));
+                let (word_gas, in_bounds) = match from {
+                    Buffer::ReturnData => {
+                        let (end, overflow) = src.overflowing_add(len);
+                        (GAS_RETURN_DATA_COPY, !overflow && end <= U256::from(evm.return_data.len() as u64))
+                    }
+                    _ => (GAS_COPY, true),
+                };
+                let copied = in_bounds && words(len).is_ok_and(|words| expand_memory(evm, word_gas * words, &[(dest, len)]));
+                if !copied {
+                    return state.interpret(evm, &program.exits[*exit as usize]);
+                }
+                let value = buffer_read(buffer(evm, *from), src, len);
+                memory_write(&mut evm.memory, dest, &value);
+            }
+            Ins::SLoad { dest, key, exit } => {
+                let key = Bytes32(state.get(&evm.stack, key).to_be_bytes());
+                let gas = sload_gas(evm, &key);
+                if evm.gas_left < gas {
+                    return state.interpret(evm, &program.exits[*exit as usize]);
+                }
+                evm.gas_left -= gas;
+                warm(evm, &key);
+                state.regs[*dest as usize] = get_storage(evm.env.state, &evm.message.current_target, &key);
+            }
+            Ins::SStore { key, value, exit } => {
+                let key = Bytes32(state.get(&evm.stack, key).to_be_bytes());
+                let value = state.get(&evm.stack, value);
+                let (gas, refund) = sstore_gas(evm, &key, value);
+                let below_stipend = evm.env.rules.istanbul && evm.gas_left <= GAS_CALL_STIPEND;
+                if below_stipend || evm.gas_left < gas || evm.message.is_static {
+                    return state.interpret(evm, &program.exits[*exit as usize]);
+                }
+                evm.gas_left -= gas;
+                evm.refund_counter += refund;
+                warm(evm, &key);
+                set_storage(evm.env.state, &evm.message.current_target, &key, value);
+            }
+            Ins::TLoad { dest, key } => {
+                let key = Bytes32(state.get(&evm.stack, key).to_be_bytes());
+                let value = get_transient_storage(&evm.env.transient_storage, &evm.message.current_target, &key);
+                state.regs[*dest as usize] = value;
+            }
+            Ins::TStore { key, value, exit } => {
+                if evm.message.is_static {
+                    return state.interpret(evm, &program.exits[*exit as usize]);
+                }
+                let key = Bytes32(state.get(&evm.stack, key).to_be_bytes());
+                let value = state.get(&evm.stack, value);
+                set_transient_storage(&mut evm.env.transient_storage, &evm.message.current_target, &key, value);
+            }
+            Ins::Mov(dest, a) => state.regs[*dest as usize] = state.get(&evm.stack, a),
+            Ins::Flush { pop, push } => state.flush(&mut evm.stack, *pop, push),
+            Ins::Jump { dest, pc } => {
+                i = jump(program, evm, state.get(&evm.stack, dest), *pc)?;
+                continue;
+            }
+            Ins::JumpI { dest, cond, pc } => {
+                if !state.get(&evm.stack, cond).is_zero() {
+                    i = jump(program, evm, state.get(&evm.stack, dest), *pc)?;
+                    continue;
+                }
+            }
+            Ins::Goto(target) => {
+                i = *target as usize;
+                continue;
+            }
+            Ins::GotoIf(target, cond) => {
+                if !state.get(&evm.stack, cond).is_zero() {
+                    i = *target as usize;
+                    continue;
+                }
+            }
+            Ins::Host(op, exit) => {
+                state.leave(evm, &program.exits[*exit as usize]);
+                op_implementation(*op, evm)?;
+                // SELFDESTRUCT stops the frame.
+                if !evm.running {
+                    return Ok(());
+                }
+            }
+            Ins::Interpret(exit) => return state.interpret(evm, &program.exits[*exit as usize]),
+            Ins::End(status, exit) => return state.end(evm, &program.exits[*exit as usize], *status),
+            Ins::Output { status, offset, len, exit, done } => {
+                let (offset, len) = (state.get(&evm.stack, offset), state.get(&evm.stack, len));
+                if !expand_memory(evm, 0, &[(offset, len)]) {
+                    return state.interpret(evm, &program.exits[*exit as usize]);
+                }
+                evm.output = memory_read_bytes(&evm.memory, offset, len);
+                return state.end(evm, &program.exits[*done as usize], *status);
+            }
+        }
+        i += 1;
+    }
+}
@@ -0,0 +1,392 @@
+//! Transactions against a revm `Database`, with hot contracts compiled.
+//!
+//! `EjitEvm` takes the block and transaction as revm's `Evm` does, runs the
+//! transaction through `process_transaction` over a `DatabaseState` with a
+//! `HybridExecutor`, and returns what revm would: the `ExecutionResult` and
+//! the accounts changed. The executor is kept across transactions, so that
+//! the contracts called most are compiled once and then run compiled.
+//!
+//! ```
+//! use ejit_evm::ejit_evm::EjitEvm;
+//! use revm::{
+//!     db::{CacheDB, EmptyDB},
+//!     primitives::{AccountInfo, Address, TxEnv, TxKind, U256},
+//! };
+//!
+//! let caller = Address::repeat_byte(0xca);
+//! let mut db = CacheDB::new(EmptyDB::default());
+//! db.insert_account_info(caller, AccountInfo { balance: U256::from(10_u64.pow(18)), ..Default::default() });
+//! let mut evm = EjitEvm::new(db);
+//! evm.block.number = U256::from(1);
+//! evm.block.gas_limit = U256::from(30_000_000);
+//! let tx = TxEnv {
+//!     caller,
+//!     gas_limit: 21_000,
+//!     gas_price: U256::from(10),
+//!     transact_to: TxKind::Call(Address::repeat_byte(0xbb)),
+//!     value: U256::from(1),
+//!     ..Default::default()
+//! };
+//! let result = evm.transact_commit(&tx)?;
+//! assert!(result.is_success());
+//! # Ok::<(), Box<dyn std::error::Error>>(())
+//! ```
+//!
+//! Transactions which are not valid against the state, as `check_transaction`
+//! finds, fail with `EVMError::Custom` and the message of the `Exception`.
+
+use revm::{
+    primitives::{
+        BlockEnv, EVMError, EVMResult, ExecutionResult, HaltReason, OutOfGasError, Output, ResultAndState,
+        SuccessReason, TxEnv, TxKind, B256,
+    },
+    Database, DatabaseCommit,
+};
+
+use crate::ethereum::{
+    cancun::{
+        blocks::Log,
+        fork::{check_transaction, process_transaction_output},
+        fork_types::{Address, VersionedHash},
+        state::{database::DatabaseState, get_account},
+        transactions::{AccessListTransaction, BlobTransaction, FeeMarketTransaction, LegacyTransaction, Transaction},
+        utils::compute_contract_address,
+        vm::{
+            exceptions::VmError,
+            hybrid::{HybridExecutor, Promotion},
+            Environment,
+        },
+    },
+    crypto::hash::Hash32,
+    ethereum_types::{
+        bytes::{Bytes, Bytes32},
+        numeric::{Uint, U256},
+    },
+    exceptions::Exception,
+    forks::ExecutionRules,
+};
+
+use super::Compiler;
+
+/// Runs transactions against a revm database, compiling hot contracts.
+pub struct EjitEvm<DB: Database> {
+    pub db: DB,
+    /// The block the transactions are in.
+    pub block: BlockEnv,
+    pub chain_id: u64,
+    executor: HybridExecutor,
+    /// The hashes of the blocks before `block`, oldest first, and the
+    /// number of the block they were read for.
+    block_hashes: Option<(Uint, Vec<Hash32>)>,
+}
+
+impl<DB: Database> EjitEvm<DB> {
+    /// A default block on chain 1 over `db`, promoting contracts to
+    /// compiled code as `Promotion::default` does.
+    pub fn new(db: DB) -> Self {
+        Self::with_promotion(db, Promotion::default())
+    }
+
+    pub fn with_promotion(db: DB, promotion: Promotion) -> Self {
+        Self {
+            db,
+            block: BlockEnv::default(),
+            chain_id: 1,
+            executor: HybridExecutor::new(Box::new(Compiler), promotion),
+            block_hashes: None,
+        }
+    }
+
+    pub fn executor(&self) -> &HybridExecutor {
+        &self.executor
+    }
+
+    /// Run `tx` as a Cancun transaction, returning what it did and the
+    /// accounts it changed, without writing them to the database.
+    pub fn transact(&mut self, tx: &TxEnv) -> EVMResult<DB::Error> {
+        let number = self.block.number.saturating_to::<u64>() as Uint;
+        let block_hashes = self.block_hashes(number)?;
+        let custom = |exception: Exception| EVMError::Custom(exception.to_string());
+
+        let block = &self.block;
+        let mut state = DatabaseState::new(&mut self.db);
+        let caller = Address::from_be_bytes(tx.caller.0 .0);
+        let nonce = get_account(&state, &caller).nonce;
+        let transaction = transaction(tx, self.chain_id, nonce)?;
+        let base_fee_per_gas = block.basefee.saturating_to::<u64>() as Uint;
+        let excess_blob_gas = block.blob_excess_gas_and_price.as_ref().map_or(0, |blob| blob.excess_blob_gas);
+        let gas_limit = block.gas_limit.saturating_to::<u64>() as Uint;
+        let (caller, gas_price, blob_versioned_hashes) =
+            check_transaction(&state, &transaction, caller, gas_limit, base_fee_per_gas, excess_blob_gas)
+                .map_err(custom)?;
+        let created = match tx.transact_to {
+            TxKind::Create => Some(compute_contract_address(&caller, nonce).map_err(custom)?),
+            TxKind::Call(_) => None,
+        };
+
+        let mut env = Environment {
+            caller: caller.clone(),
+            origin: caller,
+            block_hashes,
+            coinbase: Address::from_be_bytes(block.coinbase.0 .0),
+            number,
+            gas_limit,
+            base_fee_per_gas,
+            gas_price,
+            time: U256::from_be_bytes(block.timestamp.to_be_bytes()),
+            prev_randao: Bytes32(block.prevrandao.unwrap_or_default().0),
+            chain_id: self.chain_id,
+            excess_blob_gas,
+            blob_versioned_hashes,
+            executor: Some(&self.executor),
+            ..Environment::new(&mut state, ExecutionRules::CANCUN)
+        };
+        let output = process_transaction_output(&mut env, &transaction).map_err(custom)?;
+        drop(env);
+        state.take_error().map_err(EVMError::Database)?;
+
+        let gas_used = output.gas_used as u64;
+        let data = revm::primitives::Bytes::from(output.return_data.0);
+        let result = match output.error {
+            None => ExecutionResult::Success {
+                // A frame which stopped and one which returned nothing look
+                // the same from here.
+                reason: if data.is_empty() { SuccessReason::Stop } else { SuccessReason::Return },
+                gas_used,
+                gas_refunded: output.gas_refund as u64,
+                logs: output.logs.iter().map(revm_log).collect(),
+                output: match created {
+                    Some(address) => Output::Create(data, Some(revm::primitives::Address::from_slice(&address[..]))),
+                    None => Output::Call(data),
+                },
+            },
+            Some(VmError::Revert) => ExecutionResult::Revert { gas_used, output: data },
+            Some(error) => ExecutionResult::Halt { reason: halt_reason(&error), gas_used },
+        };
+        Ok(ResultAndState { result, state: state.changes() })
+    }
+
+    /// The hashes of the up to 256 blocks before block `number`, read from
+    /// the database once for each block.
+    fn block_hashes(&mut self, number: Uint) -> Result<Vec<Hash32>, EVMError<DB::Error>> {
+        if let Some((read_for, hashes)) = &self.block_hashes {
+            if *read_for == number {
+                return Ok(hashes.clone());
+            }
+        }
+        let mut hashes = Vec::new();
+        for n in number.saturating_sub(256)..number {
+            let hash = self.db.block_hash(revm::primitives::U256::from(n)).map_err(EVMError::Database)?;
+            hashes.push(Hash32(hash.0));
+        }
+        self.block_hashes = Some((number, hashes.clone()));
+        Ok(hashes)
+    }
+}
+
+impl<DB: Database + DatabaseCommit> EjitEvm<DB> {
+    /// `transact`, writing the accounts changed, with the code of a
+    /// contract created, to the database.
+    pub fn transact_commit(&mut self, tx: &TxEnv) -> Result<ExecutionResult, EVMError<DB::Error>> {
+        let ResultAndState { result, state } = self.transact(tx)?;
+        self.db.commit(state);
+        Ok(result)
+    }
+}
+
+/// `tx` as a transaction of its type, with `nonce` if it has none.
+fn transaction<E>(tx: &TxEnv, chain_id: u64, nonce: Uint) -> Result<Transaction, EVMError<E>> {
+    let uint = |value: revm::primitives::U256| value.saturating_to::<u64>() as Uint;
+    let nonce = U256::from_uint(tx.nonce.map_or(nonce, |nonce| nonce as Uint));
+    let chain_id = tx.chain_id.unwrap_or(chain_id);
+    let to = match tx.transact_to {
+        TxKind::Create => None,
+        TxKind::Call(to) => Some(Address::from_be_bytes(to.0 .0)),
+    };
+    let value = U256::from_be_bytes(tx.value.to_be_bytes());
+    let data = Bytes(tx.data.to_vec());
+    let gas = tx.gas_limit as Uint;
+    let access_list: Vec<(Address, Vec<Bytes32>)> = tx
+        .access_list
+        .iter()
+        .map(|(address, keys)| {
+            let keys = keys.iter().map(|key| Bytes32(key.to_be_bytes())).collect();
+            (Address::from_be_bytes(address.0 .0), keys)
+        })
+        .collect();
+    Ok(if !tx.blob_hashes.is_empty() {
+        let Some(to) = to else {
+            return Err(EVMError::Custom("blob transactions cannot create contracts".to_string()));
+        };
+        Transaction::BlobTransaction(BlobTransaction {
+            chain_id,
+            nonce,
+            max_priority_fee_per_gas: uint(tx.gas_priority_fee.unwrap_or_default()),
+            max_fee_per_gas: uint(tx.gas_price),
+            gas,
+            to,
+            value,
+            data,
+            access_list,
+            max_fee_per_blob_gas: U256::from_be_bytes(tx.max_fee_per_blob_gas.unwrap_or_default().to_be_bytes()),
+            blob_versioned_hashes: tx.blob_hashes.iter().map(|hash| VersionedHash(hash.0)).collect(),
+            ..Default::default()
+        })
+    } else if let Some(priority_fee) = tx.gas_priority_fee {
+        Transaction::FeeMarketTransaction(FeeMarketTransaction {
+            chain_id,
+            nonce,
+            max_priority_fee_per_gas: uint(priority_fee),
+            max_fee_per_gas: uint(tx.gas_price),
+            gas,
+            to: to.into(),
+            value,
+            data,
+            access_list,
+            ..Default::default()
+        })
+    } else if !access_list.is_empty() {
+        Transaction::AccessListTransaction(AccessListTransaction {
+            chain_id,
+            nonce,
+            gas_price: uint(tx.gas_price),
+            gas,
+            to: to.into(),
+            value,
+            data,
+            access_list,
+            ..Default::default()
+        })
+    } else {
+        Transaction::LegacyTransaction(LegacyTransaction {
+            nonce,
+            gas_price: uint(tx.gas_price),
+            gas,
+            to: to.into(),
+            value,
+            data,
+            ..Default::default()
+        })
+    })
+}
+
+fn revm_log(log: &Log) -> revm::primitives::Log {
+    let topics = log.topics.iter().map(|topic| B256::from(topic.0)).collect();
+    let address = revm::primitives::Address::from_slice(&log.address[..]);
+    revm::primitives::Log::new_unchecked(address, topics, log.data.0.clone().into())
+}
+
+/// The reason revm gives for a frame which failed with `error`.
+///
+/// `VmError::InvalidOpcode` does not say whether the opcode was INVALID,
+/// which revm reports as `InvalidEFOpcode`, so it is `OpcodeNotFound` for
+/// both.
+fn halt_reason(error: &VmError) -> HaltReason {
+    match error {
+        VmError::OutOfGasError => HaltReason::OutOfGas(OutOfGasError::Basic),
+        VmError::StackUnderflowError => HaltReason::StackUnderflow,
+        VmError::StackOverflowError => HaltReason::StackOverflow,
+        VmError::InvalidOpcode | VmError::ExceptionalHalt => HaltReason::OpcodeNotFound,
+        VmError::InvalidJumpDestError => HaltReason::InvalidJump,
+        VmError::StackDepthLimitError => HaltReason::CallTooDeep,
+        VmError::WriteInStaticContext => HaltReason::StateChangeDuringStaticCall,
+        VmError::OutOfBoundsRead => HaltReason::OutOfOffset,
+        VmError::InvalidContractPrefix => HaltReason::CreateContractStartingWithEF,
+        VmError::AddressCollision => HaltReason::CreateCollision,
+        VmError::InvalidParameter | VmError::KZGProofError => HaltReason::PrecompileError,
+        VmError::InvalidEof => HaltReason::InvalidEFOpcode,
+        VmError::Revert => unreachable!("a revert is not a halt"),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use revm::{
+        db::{CacheDB, EmptyDB},
+        primitives::{AccountInfo, ExecutionResult, Output, SpecId, TxEnv, TxKind, U256},
+        Database, Evm,
+    };
+
+    use crate::ethereum::cancun::{fork_types::Address, utils::compute_contract_address};
+
+    use super::EjitEvm;
+
+    const CALLER: [u8; 20] = [0xca; 20];
+
+    /// PUSH1 42 PUSH1 0 MSTORE PUSH1 32 PUSH1 0 RETURN.
+    const RUNTIME: [u8; 10] = [0x60, 0x2a, 0x60, 0x00, 0x52, 0x60, 0x20, 0x60, 0x00, 0xf3];
+
+    fn db() -> CacheDB<EmptyDB> {
+        let mut db = CacheDB::new(EmptyDB::default());
+        let caller = AccountInfo { balance: U256::from(10_u64.pow(18)), ..Default::default() };
+        db.insert_account_info(revm::primitives::Address::from(CALLER), caller);
+        db
+    }
+
+    fn tx(transact_to: TxKind, data: Vec<u8>) -> TxEnv {
+        TxEnv {
+            caller: revm::primitives::Address::from(CALLER),
+            gas_limit: 1_000_000,
+            gas_price: U256::from(10),
+            transact_to,
+            data: data.into(),
+            ..Default::default()
+        }
+    }
+
+    /// Run `txs` in order, committing each, both here and in revm, and
+    /// check that they do the same.
+    fn check(txs: &[TxEnv]) -> Vec<ExecutionResult> {
+        let mut evm = EjitEvm::new(db());
+        evm.block.gas_limit = U256::from(30_000_000);
+        let mut revm = Evm::builder().with_db(db()).with_spec_id(SpecId::CANCUN).build();
+        let mut results = Vec::new();
+        for tx in txs {
+            *revm.tx_mut() = tx.clone();
+            let expected = revm.transact_commit().unwrap();
+            let result = evm.transact_commit(tx).unwrap();
+            assert_eq!(result, expected);
+            results.push(result);
+        }
+        let mut revm_db = revm.into_context().evm.inner.db;
+        for (address, account) in &evm.db.accounts {
+            assert_eq!(account.info, revm_db.basic(*address).unwrap().unwrap_or_default(), "{address}");
+            for (key, value) in &account.storage {
+                assert_eq!(*value, revm_db.storage(*address, *key).unwrap());
+            }
+        }
+        results
+    }
+
+    #[test]
+    fn create_then_call() {
+        // PUSH10 RUNTIME PUSH1 0 MSTORE PUSH1 1 PUSH1 0 SSTORE
+        // PUSH1 10 PUSH1 22 RETURN.
+        let mut init = vec![0x69];
+        init.extend(RUNTIME);
+        init.extend([0x60, 0x00, 0x52, 0x60, 0x01, 0x60, 0x00, 0x55, 0x60, 0x0a, 0x60, 0x16, 0xf3]);
+        let created = compute_contract_address(&Address::from_be_bytes(CALLER), 0).unwrap();
+        let created = revm::primitives::Address::from_slice(&created[..]);
+
+        let results = check(&[tx(TxKind::Create, init), tx(TxKind::Call(created), Vec::new())]);
+        let ExecutionResult::Success { output: Output::Create(code, Some(address)), .. } = &results[0] else {
+            panic!("{:?}", results[0]);
+        };
+        assert_eq!((&code[..], *address), (&RUNTIME[..], created));
+        let ExecutionResult::Success { output: Output::Call(output), .. } = &results[1] else {
+            panic!("{:?}", results[1]);
+        };
+        assert_eq!(U256::from_be_slice(output), U256::from(42));
+    }
+
+    #[test]
+    fn failures() {
+        // PUSH1 0 PUSH1 0 REVERT, then an undefined opcode.
+        check(&[tx(TxKind::Create, vec![0x60, 0x00, 0x60, 0x00, 0xfd]), tx(TxKind::Create, vec![0x0c])]);
+
+        let mut evm = EjitEvm::new(db());
+        let mut poor = tx(TxKind::Create, Vec::new());
+        poor.caller = revm::primitives::Address::from([0x0b; 20]);
+        assert!(matches!(evm.transact(&poor), Err(revm::primitives::EVMError::Custom(_))));
+    }
+}
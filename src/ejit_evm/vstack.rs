@@ -0,0 +1,106 @@
+//! The stack as the compiler knows it while it compiles a segment.
+
+use std::collections::VecDeque;
+
+use super::ir::VElem;
+
+/// Virtual stack, keeps track of items pushed on the stack.
+///
+/// Items the segment did not push are pulled in from below as `Bp` slots
+/// when an instruction needs them.
+#[derive(Debug, Clone, Default)]
+pub struct VStack {
+    /// Bottom first.
+    pub(crate) stack: VecDeque<VElem>,
+    /// The number of items pulled in from below `bp`.
+    pub(crate) old_values: usize,
+}
+
+impl VStack {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Ensure at least n items on the vstack.
+    pub(crate) fn prep(&mut self, n: usize) {
+        while self.stack.len() < n {
+            self.old_values += 1;
+            self.stack.push_front(VElem::Bp(-(self.old_values as i32)));
+        }
+    }
+
+    pub(crate) fn top1(&mut self) -> VElem {
+        self.prep(1);
+        self.stack.pop_back().unwrap()
+    }
+
+    pub(crate) fn top2(&mut self) -> (VElem, VElem) {
+        let a = self.top1();
+        (a, self.top1())
+    }
+
+    pub(crate) fn top3(&mut self) -> (VElem, VElem, VElem) {
+        let (a, b) = self.top2();
+        (a, b, self.top1())
+    }
+
+    pub(crate) fn push(&mut self, e: VElem) {
+        self.stack.push_back(e);
+    }
+
+    /// Push a copy of item `n`, counting from zero at the top.
+    pub(crate) fn dup(&mut self, n: usize) {
+        self.prep(n + 1);
+        let e = self.stack[self.stack.len() - 1 - n];
+        self.stack.push_back(e);
+    }
+
+    /// Swap the top with item `n`.
+    pub(crate) fn swap(&mut self, n: usize) {
+        self.prep(n + 1);
+        let top = self.stack.len() - 1;
+        self.stack.swap(top, top - n);
+    }
+
+    /// The `pop` and `push` of a `Flush` which writes the vstack to the
+    /// stack, leaving out the slots at the bottom which are unchanged, and
+    /// empty the vstack.
+    pub(crate) fn take(&mut self) -> (u16, Box<[VElem]>) {
+        let mut pop = self.old_values;
+        let mut items = std::mem::take(&mut self.stack);
+        while pop > 0 && items.front() == Some(&VElem::Bp(-(pop as i32))) {
+            items.pop_front();
+            pop -= 1;
+        }
+        self.old_values = 0;
+        (pop as u16, items.into_iter().collect())
+    }
+
+    /// The vstack as `take` would leave it, without emptying it.
+    pub(crate) fn snapshot(&self) -> (u16, Box<[VElem]>) {
+        self.clone().take()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::ethereum::ethereum_types::numeric::U256;
+
+    use super::{VElem, VStack};
+
+    #[test]
+    fn take() {
+        let mut vstack = VStack::new();
+        // SWAP2 then PUSH1 1, which writes all three items.
+        vstack.swap(2);
+        vstack.push(VElem::Constant(U256::ONE));
+        assert_eq!(vstack.old_values, 3);
+        let (pop, push) = vstack.take();
+        assert_eq!((pop, &push[..]), (3, &[VElem::Bp(-1), VElem::Bp(-2), VElem::Bp(-3), VElem::Constant(U256::ONE)][..]));
+
+        // DUP2 leaves both items in place.
+        vstack.dup(1);
+        assert_eq!(vstack.take(), (0, vec![VElem::Bp(-2)].into()));
+        assert!(vstack.stack.is_empty());
+    }
+}
@@ -0,0 +1,664 @@
+//! The Engine API, through which a consensus client drives the chain.
+//!
+//! After the merge the consensus layer decides which blocks make the chain.
+//! It hands each new block to the execution layer as an execution payload
+//! with `engine_newPayloadV3`, and tells it which block is the head, and
+//! which are safe and finalized, with `engine_forkchoiceUpdatedV3`. `Engine`
+//! implements both over a `BlockChain`: payloads are translated into
+//! blocks, checked against the hash the consensus layer computed and
+//! imported, and fork choice updates move the head with
+//! `BlockChain::fork_choice_update`. A fork choice update with payload
+//! attributes also builds a block on the new head from the engine's
+//! `transactions`, which `engine_getPayloadV3` returns.
+//!
+//! The blobs of blob transactions travel beside the block, in a
+//! `BlobsBundle`. When the bundle is at hand, `new_payload_v3_with_blobs`
+//! checks its commitments against the versioned hashes of the block and
+//! its KZG proofs against the blobs before importing.
+//!
+//! The types are the JSON objects of the specification, so the methods can
+//! be served by any JSON-RPC transport. Authenticating the consensus client
+//! is left to that transport.
+
+use std::collections::BTreeMap;
+
+use crate::{
+    b11r::{transactions_root, withdrawals_root},
+    builder::{build_payload, BuiltPayload},
+    ethereum::{
+        cancun::{
+            blocks::{Block, Header, Withdrawal},
+            fork::{
+                compute_header_hash, encode_trie_value, validation::ValidationResult, BlockChain, ForkChoiceState,
+                EMPTY_OMMER_HASH,
+            },
+            fork_types::{Address, Bloom, Root},
+            transactions::{decode_raw_transaction, encode_transaction, Transaction},
+        },
+        crypto::{
+            hash::{keccak256, Hash32},
+            kzg::{kzg_commitment_to_versioned_hash, trusted_setup, verify_blob_kzg_proof_batch, KzgSettings},
+        },
+        ethereum_types::{
+            bytes::{Bytes, Bytes32, Bytes48, Bytes8},
+            numeric::{Uint, U256, U64},
+        },
+        exceptions::Exception,
+    },
+    impl_json,
+    json::{to_json, Encoder, JsonEncode},
+};
+
+/// `ExecutionPayloadV3`: a block without the fields which are constant
+/// after the merge, with its transactions in their raw form.
+#[derive(Debug, Clone, Default)]
+pub struct ExecutionPayload {
+    pub parent_hash: Hash32,
+    pub fee_recipient: Address,
+    pub state_root: Root,
+    pub receipts_root: Root,
+    pub logs_bloom: Bloom,
+    pub prev_randao: Bytes32,
+    pub block_number: Uint,
+    pub gas_limit: Uint,
+    pub gas_used: Uint,
+    pub timestamp: U256,
+    pub extra_data: Bytes,
+    pub base_fee_per_gas: Uint,
+    pub block_hash: Hash32,
+    pub transactions: Vec<Bytes>,
+    pub withdrawals: Option<Vec<Withdrawal>>,
+    pub blob_gas_used: Option<U64>,
+    pub excess_blob_gas: Option<U64>,
+}
+
+impl_json!(#[skip_unknown] ExecutionPayload :
+    parent_hash "parentHash",
+    fee_recipient "feeRecipient",
+    state_root "stateRoot",
+    receipts_root "receiptsRoot",
+    logs_bloom "logsBloom",
+    prev_randao "prevRandao",
+    block_number "blockNumber",
+    gas_limit "gasLimit",
+    gas_used "gasUsed",
+    timestamp "timestamp",
+    extra_data "extraData",
+    base_fee_per_gas "baseFeePerGas",
+    block_hash "blockHash",
+    transactions "transactions",
+    withdrawals "withdrawals",
+    blob_gas_used "blobGasUsed",
+    excess_blob_gas "excessBlobGas",
+);
+
+impl ExecutionPayload {
+    /// The payload of `block`.
+    pub fn from_block(block: &Block) -> Result<Self, Exception> {
+        let header = &block.header;
+        let transactions = block
+            .transactions
+            .iter()
+            .map(|tx| encode_trie_value(encode_transaction(tx)?))
+            .collect::<Result<_, _>>()?;
+        Ok(Self {
+            parent_hash: header.parent_hash.clone(),
+            fee_recipient: header.coinbase.clone(),
+            state_root: header.state_root.clone(),
+            receipts_root: header.receipt_root.clone(),
+            logs_bloom: header.bloom.clone(),
+            prev_randao: header.prev_randao.clone(),
+            block_number: header.number,
+            gas_limit: header.gas_limit,
+            gas_used: header.gas_used,
+            timestamp: header.timestamp,
+            extra_data: header.extra_data.clone(),
+            base_fee_per_gas: header.base_fee_per_gas.unwrap_or_default(),
+            block_hash: compute_header_hash(header)?,
+            transactions,
+            withdrawals: block.withdrawals.clone(),
+            blob_gas_used: header.blob_gas_used,
+            excess_blob_gas: header.excess_blob_gas,
+        })
+    }
+
+    /// The block of the payload, whose parent block has the beacon block
+    /// root `parent_beacon_block_root`.
+    pub fn to_block(&self, parent_beacon_block_root: Option<Root>) -> Result<Block, Exception> {
+        let transactions = self
+            .transactions
+            .iter()
+            .map(|tx| decode_raw_transaction(tx))
+            .collect::<Result<Vec<_>, _>>()?;
+        let header = Header {
+            parent_hash: self.parent_hash.clone(),
+            ommers_hash: EMPTY_OMMER_HASH,
+            coinbase: self.fee_recipient.clone(),
+            state_root: self.state_root.clone(),
+            transactions_root: transactions_root(&transactions)?,
+            receipt_root: self.receipts_root.clone(),
+            bloom: self.logs_bloom.clone(),
+            difficulty: 0,
+            number: self.block_number,
+            gas_limit: self.gas_limit,
+            gas_used: self.gas_used,
+            timestamp: self.timestamp,
+            extra_data: self.extra_data.clone(),
+            prev_randao: self.prev_randao.clone(),
+            nonce: Bytes8::default(),
+            base_fee_per_gas: Some(self.base_fee_per_gas),
+            withdrawals_root: self.withdrawals.as_deref().map(withdrawals_root).transpose()?,
+            blob_gas_used: self.blob_gas_used,
+            excess_blob_gas: self.excess_blob_gas,
+            parent_beacon_block_root,
+            requests_hash: None,
+        };
+        Ok(Block { header, transactions, ommers: Vec::new(), withdrawals: self.withdrawals.clone() })
+    }
+}
+
+/// `BlobsBundleV1`: the blobs of the blob transactions of a block, in the
+/// order of their versioned hashes, with their commitments and proofs.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct BlobsBundle {
+    pub commitments: Vec<Bytes48>,
+    pub proofs: Vec<Bytes48>,
+    pub blobs: Vec<Bytes>,
+}
+
+impl_json!(BlobsBundle :
+    commitments "commitments",
+    proofs "proofs",
+    blobs "blobs",
+);
+
+impl BlobsBundle {
+    /// Check that the bundle has a commitment for each of
+    /// `versioned_hashes`, and that its proofs show that the commitments
+    /// are to its blobs.
+    pub fn verify(&self, versioned_hashes: &[Hash32], settings: &KzgSettings) -> Result<(), Exception> {
+        if self.commitments.len() != versioned_hashes.len() {
+            return Err(Exception::InvalidBlock("number of blob commitments does not match"));
+        }
+        let hashes = self.commitments.iter().map(kzg_commitment_to_versioned_hash);
+        if !hashes.eq(versioned_hashes.iter().cloned()) {
+            return Err(Exception::InvalidBlock("blob commitment does not match versioned hash"));
+        }
+        let blobs: Vec<&[u8]> = self.blobs.iter().map(|blob| &blob[..]).collect();
+        match verify_blob_kzg_proof_batch(settings, &blobs, &self.commitments, &self.proofs)? {
+            true => Ok(()),
+            false => Err(Exception::InvalidBlock("invalid blob KZG proof")),
+        }
+    }
+}
+
+/// `PayloadAttributesV3`: what the consensus layer wants in a block built
+/// on the new head.
+#[derive(Debug, Clone, Default)]
+pub struct PayloadAttributes {
+    pub timestamp: U256,
+    pub prev_randao: Bytes32,
+    pub suggested_fee_recipient: Address,
+    pub withdrawals: Vec<Withdrawal>,
+    pub parent_beacon_block_root: Root,
+}
+
+impl_json!(#[skip_unknown] PayloadAttributes :
+    timestamp "timestamp",
+    prev_randao "prevRandao",
+    suggested_fee_recipient "suggestedFeeRecipient",
+    withdrawals "withdrawals",
+    parent_beacon_block_root "parentBeaconBlockRoot",
+);
+
+/// The `status` of a `PayloadStatusV1`.
+pub use crate::ethereum::cancun::fork::validation::ValidationStatus as PayloadStatusKind;
+
+/// `PayloadStatusV1`.
+#[derive(Debug, Clone, PartialEq)]
+pub struct PayloadStatus {
+    pub status: PayloadStatusKind,
+    /// The last valid block on the branch of the payload, `None` if it is
+    /// not known.
+    pub latest_valid_hash: Option<Hash32>,
+    pub validation_error: Option<String>,
+}
+
+impl PayloadStatus {
+    fn new(status: PayloadStatusKind, latest_valid_hash: Option<Hash32>) -> Self {
+        Self { status, latest_valid_hash, validation_error: None }
+    }
+
+    fn invalid(latest_valid_hash: Option<Hash32>, error: impl Into<String>) -> Self {
+        Self { status: PayloadStatusKind::Invalid, latest_valid_hash, validation_error: Some(error.into()) }
+    }
+}
+
+impl From<ValidationResult> for PayloadStatus {
+    fn from(result: ValidationResult) -> Self {
+        let validation_error = result.error.map(|error| error.to_string());
+        Self { status: result.status, latest_valid_hash: result.latest_valid_hash, validation_error }
+    }
+}
+
+impl JsonEncode for PayloadStatus {
+    fn encode_json(&self, encoder: &mut Encoder) {
+        let status = match self.status {
+            PayloadStatusKind::Valid => "VALID",
+            PayloadStatusKind::Invalid => "INVALID",
+            PayloadStatusKind::Syncing => "SYNCING",
+            PayloadStatusKind::Accepted => "ACCEPTED",
+        };
+        let mut o = encoder.object();
+        o.field("status", &status);
+        o.key("latestValidHash");
+        match &self.latest_valid_hash {
+            Some(hash) => hash.encode_json(o.encoder),
+            None => o.encoder.raw("null"),
+        }
+        o.key("validationError");
+        match &self.validation_error {
+            Some(error) => error.encode_json(o.encoder),
+            None => o.encoder.raw("null"),
+        }
+        o.end();
+    }
+}
+
+/// The result of `engine_forkchoiceUpdatedV3`.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ForkChoiceUpdated {
+    pub payload_status: PayloadStatus,
+    /// The build started for the payload attributes, if any.
+    pub payload_id: Option<Bytes8>,
+}
+
+impl JsonEncode for ForkChoiceUpdated {
+    fn encode_json(&self, encoder: &mut Encoder) {
+        let mut o = encoder.object();
+        o.field("payloadStatus", &self.payload_status);
+        o.key("payloadId");
+        match &self.payload_id {
+            Some(id) => id.encode_json(o.encoder),
+            None => o.encoder.raw("null"),
+        }
+        o.end();
+    }
+}
+
+/// The result of `engine_getPayloadV3`.
+#[derive(Debug, Clone)]
+pub struct GetPayloadResponse {
+    pub execution_payload: ExecutionPayload,
+    /// Fees the block pays its fee recipient, in wei.
+    pub block_value: U256,
+    pub blobs_bundle: BlobsBundle,
+}
+
+impl JsonEncode for GetPayloadResponse {
+    fn encode_json(&self, encoder: &mut Encoder) {
+        let mut o = encoder.object();
+        o.field("executionPayload", &self.execution_payload);
+        o.field("blockValue", &self.block_value);
+        o.field("blobsBundle", &self.blobs_bundle);
+        o.field("shouldOverrideBuilder", &false);
+        o.end();
+    }
+}
+
+/// An Engine API error response.
+#[derive(Debug, Clone, PartialEq)]
+pub struct EngineError {
+    pub code: i64,
+    pub message: &'static str,
+}
+
+impl EngineError {
+    pub const INVALID_FORKCHOICE_STATE: Self = Self { code: -38002, message: "Invalid forkchoice state" };
+    pub const UNKNOWN_PAYLOAD: Self = Self { code: -38001, message: "Unknown payload" };
+    pub const INVALID_PAYLOAD_ATTRIBUTES: Self = Self { code: -38003, message: "Invalid payload attributes" };
+}
+
+/// The execution layer of a consensus client.
+pub struct Engine {
+    pub chain: BlockChain,
+    /// Candidates for the blocks the engine builds, in order of inclusion.
+    pub transactions: Vec<Transaction>,
+    /// Blocks built for fork choice updates, by payload id.
+    payloads: BTreeMap<Bytes8, BuiltPayload>,
+}
+
+impl Engine {
+    pub fn new(chain: BlockChain) -> Self {
+        Self { chain, transactions: Vec::new(), payloads: BTreeMap::new() }
+    }
+
+    /// `engine_newPayloadV3`: check `payload` against the blob versioned
+    /// hashes of the consensus layer's block and import it.
+    pub fn new_payload_v3(
+        &mut self,
+        payload: &ExecutionPayload,
+        expected_blob_versioned_hashes: &[Hash32],
+        parent_beacon_block_root: Root,
+    ) -> PayloadStatus {
+        let block = match payload.to_block(Some(parent_beacon_block_root)) {
+            Ok(block) => block,
+            Err(error) => return PayloadStatus::invalid(None, format!("{error:?}")),
+        };
+        match compute_header_hash(&block.header) {
+            Ok(hash) if hash == payload.block_hash => {}
+            _ => return PayloadStatus::invalid(None, "invalid block hash"),
+        }
+
+        if blob_versioned_hashes(&block) != expected_blob_versioned_hashes {
+            return PayloadStatus::invalid(None, "blob versioned hashes do not match");
+        }
+        self.import_payload(block, payload.block_hash.clone())
+    }
+
+    /// `new_payload_v3` for a payload whose blobs are at hand: check
+    /// `blobs_bundle` against the blob versioned hashes of the payload and
+    /// import it.
+    pub fn new_payload_v3_with_blobs(
+        &mut self,
+        payload: &ExecutionPayload,
+        blobs_bundle: &BlobsBundle,
+        parent_beacon_block_root: Root,
+    ) -> PayloadStatus {
+        let block = match payload.to_block(Some(parent_beacon_block_root)) {
+            Ok(block) => block,
+            Err(error) => return PayloadStatus::invalid(None, format!("{error:?}")),
+        };
+        match compute_header_hash(&block.header) {
+            Ok(hash) if hash == payload.block_hash => {}
+            _ => return PayloadStatus::invalid(None, "invalid block hash"),
+        }
+
+        if let Err(error) = blobs_bundle.verify(&blob_versioned_hashes(&block), trusted_setup()) {
+            return PayloadStatus::invalid(None, format!("{error:?}"));
+        }
+        self.import_payload(block, payload.block_hash.clone())
+    }
+
+    /// Import the block of a checked payload with hash `hash`.
+    fn import_payload(&mut self, block: Block, hash: Hash32) -> PayloadStatus {
+        if self.chain.get_block(&hash).is_some() {
+            return PayloadStatus::new(PayloadStatusKind::Valid, Some(hash));
+        }
+        let parent = self.chain.get_block(&block.header.parent_hash);
+        if parent.is_some_and(|parent| parent.header.difficulty != 0)
+            && self.chain.canonical_index(&block.header.parent_hash).is_none()
+        {
+            return PayloadStatus::invalid(None, "parent block is not a proof of stake block");
+        }
+        self.chain.validate_block(block).into()
+    }
+
+    /// `engine_forkchoiceUpdatedV3`: make `fork_choice` the head, safe and
+    /// finalized blocks of the chain, and build a block on the head with
+    /// `payload_attributes` if given.
+    pub fn fork_choice_updated_v3(
+        &mut self,
+        fork_choice: ForkChoiceState,
+        payload_attributes: Option<&PayloadAttributes>,
+    ) -> Result<ForkChoiceUpdated, EngineError> {
+        let head = fork_choice.head_block_hash.clone();
+        if self.chain.get_block(&head).is_none() {
+            let payload_status = PayloadStatus::new(PayloadStatusKind::Syncing, None);
+            return Ok(ForkChoiceUpdated { payload_status, payload_id: None });
+        }
+        self.chain.check_fork_choice(&fork_choice).map_err(|_| EngineError::INVALID_FORKCHOICE_STATE)?;
+        let branch = branch_of(&self.chain, &head);
+        if let Err(error) = self.chain.fork_choice_update(fork_choice) {
+            // The blocks of the branch which failed are gone, and the latest
+            // valid one is the first still known.
+            let latest_valid_hash = branch.into_iter().find(|hash| self.chain.get_block(hash).is_some());
+            let payload_status = PayloadStatus::invalid(latest_valid_hash, format!("{error:?}"));
+            return Ok(ForkChoiceUpdated { payload_status, payload_id: None });
+        }
+
+        let payload_id = match payload_attributes {
+            Some(attributes) => {
+                let payload = build_payload(&self.chain, &self.transactions, attributes)
+                    .map_err(|_| EngineError::INVALID_PAYLOAD_ATTRIBUTES)?;
+                // The same attributes on the same head give the same id.
+                let id = keccak256(&[&head.0[..], to_json(attributes).as_bytes()].concat());
+                let id = Bytes8(id.0[..8].try_into().unwrap());
+                self.payloads.insert(id, payload);
+                Some(id)
+            }
+            None => None,
+        };
+        let payload_status = PayloadStatus::new(PayloadStatusKind::Valid, Some(head));
+        Ok(ForkChoiceUpdated { payload_status, payload_id })
+    }
+
+    /// `engine_getPayloadV3`: the block built for `payload_id`.
+    pub fn get_payload_v3(&self, payload_id: &Bytes8) -> Result<GetPayloadResponse, EngineError> {
+        let payload = self.payloads.get(payload_id).ok_or(EngineError::UNKNOWN_PAYLOAD)?;
+        let execution_payload =
+            ExecutionPayload::from_block(&payload.block).map_err(|_| EngineError::UNKNOWN_PAYLOAD)?;
+        // The engine has no blob sidecars, so it builds no blob
+        // transactions into its blocks.
+        let blobs_bundle = BlobsBundle::default();
+        Ok(GetPayloadResponse { execution_payload, block_value: payload.block_value, blobs_bundle })
+    }
+}
+
+/// The hashes of `hash` and its ancestors, down to the first canonical one.
+fn branch_of(chain: &BlockChain, hash: &Hash32) -> Vec<Hash32> {
+    let mut branch = vec![hash.clone()];
+    while chain.canonical_index(branch.last().unwrap()).is_none() {
+        match chain.get_block(branch.last().unwrap()) {
+            Some(block) => branch.push(block.header.parent_hash.clone()),
+            None => break,
+        }
+    }
+    branch
+}
+
+/// The blob versioned hashes of the transactions of `block`, in order.
+fn blob_versioned_hashes(block: &Block) -> Vec<Hash32> {
+    block
+        .transactions
+        .iter()
+        .filter_map(|tx| match tx {
+            Transaction::BlobTransaction(tx) => Some(&tx.blob_versioned_hashes),
+            _ => None,
+        })
+        .flatten()
+        .map(|hash| Hash32(hash.0))
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{
+        b11r::seal_block,
+        builder::build_payload,
+        ethereum::{
+            cancun::transactions::sign_transaction,
+            crypto::{
+                eliptic_curve::secp256k1_public_key,
+                kzg::{self, kzg_commitment_to_versioned_hash},
+            },
+        },
+        json::{from_json, to_json},
+        prelude::*,
+        t8n::{state_to_alloc, transition, T8nEnv},
+    };
+
+    use super::{BlobsBundle, Bytes8, Engine, EngineError, ExecutionPayload, PayloadAttributes, PayloadStatusKind};
+
+    /// A chain at Cancun from genesis and a valid block on top of it.
+    fn chain_and_block() -> (BlockChain, Block) {
+        let secret_key = U256::from(0x5eed_u32);
+        let sender = Address::from_be_bytes(keccak256(&secp256k1_public_key(secret_key))[12..].try_into().unwrap());
+        let genesis = Genesis::post_merge(&["shanghai", "cancun"])
+            .chain_id(1)
+            .account(sender, Account { balance: U256::from(1_000_000_000_000_000_000_u64), ..Default::default() });
+        let chain = BlockChain::from_genesis(genesis).unwrap();
+        let parent = chain.blocks[0].header.clone();
+
+        let mut tx = Transaction::FeeMarketTransaction(FeeMarketTransaction {
+            chain_id: 1,
+            max_priority_fee_per_gas: 1,
+            max_fee_per_gas: 2_000_000_000,
+            gas: 21000,
+            to: Some(Address::from_be_bytes([0xaa; 20])).into(),
+            value: U256::from(1_u32),
+            ..Default::default()
+        });
+        sign_transaction(&mut tx, secret_key, None).unwrap();
+        let env = T8nEnv {
+            coinbase: Address::from_be_bytes([0xc0; 20]),
+            gas_limit: parent.gas_limit,
+            number: 1,
+            timestamp: U256::from(12_u32),
+            random: Some(Bytes32::default()),
+            parent_base_fee: parent.base_fee_per_gas,
+            parent_gas_used: Some(parent.gas_used),
+            parent_gas_limit: Some(parent.gas_limit),
+            parent_excess_blob_gas: parent.excess_blob_gas,
+            parent_blob_gas_used: parent.blob_gas_used,
+            parent_beacon_block_root: Some(Root::default()),
+            withdrawals: Some(Vec::new()),
+            ..Default::default()
+        };
+        let alloc = state_to_alloc(&chain.state);
+        let fork = fork_rules("cancun").unwrap();
+        let (_, result) = transition(&alloc, &env, &[Ok(tx.clone())], fork, &ChainSpec::MAINNET, None).unwrap();
+        let template = Header {
+            parent_hash: chain.head_hash().clone(),
+            coinbase: env.coinbase.clone(),
+            number: env.number,
+            gas_limit: env.gas_limit,
+            timestamp: env.timestamp,
+            base_fee_per_gas: result.base_fee,
+            excess_blob_gas: result.excess_blob_gas,
+            parent_beacon_block_root: env.parent_beacon_block_root.clone(),
+            ..Default::default()
+        };
+        let block = seal_block(&template, vec![tx], Vec::new(), Some(Vec::new()), &result.body).unwrap();
+        (chain, block)
+    }
+
+    #[test]
+    fn new_payload_and_fork_choice() {
+        let (chain, block) = chain_and_block();
+        let genesis_hash = chain.head_hash().clone();
+        let mut engine = Engine::new(chain);
+
+        let payload = ExecutionPayload::from_block(&block).unwrap();
+        let payload: ExecutionPayload = from_json(to_json(&payload).as_bytes()).unwrap();
+        let root = Root::default();
+
+        // A payload whose hash is wrong, and one whose parent is unknown.
+        let wrong_hash = ExecutionPayload { block_hash: Hash32::default(), ..payload.clone() };
+        let status = engine.new_payload_v3(&wrong_hash, &[], root.clone());
+        assert_eq!(status.status, PayloadStatusKind::Invalid);
+        assert_eq!(status.latest_valid_hash, None);
+        let orphan = Block { header: Header { parent_hash: Hash32([1; 32]), ..block.header.clone() }, ..block.clone() };
+        let orphan = ExecutionPayload::from_block(&orphan).unwrap();
+        assert_eq!(engine.new_payload_v3(&orphan, &[], root.clone()).status, PayloadStatusKind::Syncing);
+
+        let status = engine.new_payload_v3(&payload, &[], root.clone());
+        assert_eq!(status.status, PayloadStatusKind::Valid, "{status:?}");
+        assert_eq!(status.latest_valid_hash.as_ref(), Some(&payload.block_hash));
+        assert_eq!(
+            to_json(&status),
+            format!(r#"{{"status":"VALID","latestValidHash":{},"validationError":null}}"#, to_json(&payload.block_hash)),
+        );
+
+        // Going back to genesis and forward again.
+        let fork_choice = |head: &Hash32, finalized: &Hash32| ForkChoiceState {
+            head_block_hash: head.clone(),
+            safe_block_hash: finalized.clone(),
+            finalized_block_hash: finalized.clone(),
+        };
+        let updated = engine.fork_choice_updated_v3(fork_choice(&genesis_hash, &Hash32::default()), None).unwrap();
+        assert_eq!(updated.payload_status.status, PayloadStatusKind::Valid);
+        assert_eq!(engine.chain.head_hash(), &genesis_hash);
+        let updated = engine.fork_choice_updated_v3(fork_choice(&payload.block_hash, &genesis_hash), None).unwrap();
+        assert_eq!(updated.payload_status.latest_valid_hash.as_ref(), Some(&payload.block_hash));
+        assert_eq!(engine.chain.head_hash(), &payload.block_hash);
+        assert_eq!(engine.chain.fork_choice.finalized_block_hash, genesis_hash);
+
+        let unknown = engine.fork_choice_updated_v3(fork_choice(&Hash32([2; 32]), &genesis_hash), None).unwrap();
+        assert_eq!(unknown.payload_status.status, PayloadStatusKind::Syncing);
+        let not_canonical = engine.fork_choice_updated_v3(fork_choice(&genesis_hash, &payload.block_hash), None);
+        assert_eq!(not_canonical, Err(EngineError::INVALID_FORKCHOICE_STATE));
+        // The head is only switched once the fork choice is consistent.
+        assert_eq!(engine.chain.head_hash(), &payload.block_hash);
+
+        // Building the next block and importing it.
+        let head = fork_choice(&payload.block_hash, &genesis_hash);
+        let attributes = PayloadAttributes { timestamp: U256::from(24_u32), ..Default::default() };
+        let updated = engine.fork_choice_updated_v3(head.clone(), Some(&attributes)).unwrap();
+        let payload_id = updated.payload_id.unwrap();
+        assert_eq!(engine.get_payload_v3(&Bytes8([0; 8])).unwrap_err(), EngineError::UNKNOWN_PAYLOAD);
+        let built = engine.get_payload_v3(&payload_id).unwrap();
+        assert_eq!(built.execution_payload.block_number, 2);
+        assert!(to_json(&built).contains(r#""shouldOverrideBuilder":false"#));
+        let status = engine.new_payload_v3(&built.execution_payload, &[], attributes.parent_beacon_block_root.clone());
+        assert_eq!(status.status, PayloadStatusKind::Valid, "{status:?}");
+        let stale = PayloadAttributes { timestamp: U256::from(12_u32), ..Default::default() };
+        let stale = engine.fork_choice_updated_v3(head, Some(&stale));
+        assert_eq!(stale, Err(EngineError::INVALID_PAYLOAD_ATTRIBUTES));
+    }
+
+    #[test]
+    fn invalid_head_reports_latest_valid_ancestor() {
+        let (chain, block) = chain_and_block();
+        let mut engine = Engine::new(chain.clone());
+        let payload = ExecutionPayload::from_block(&block).unwrap();
+        assert_eq!(engine.new_payload_v3(&payload, &[], Root::default()).status, PayloadStatusKind::Valid);
+        let head = ForkChoiceState { head_block_hash: payload.block_hash.clone(), ..Default::default() };
+        engine.fork_choice_updated_v3(head, None).unwrap();
+
+        // A branch from genesis whose second block has the wrong state root.
+        let mut side = chain;
+        let attributes = PayloadAttributes { timestamp: U256::from(12_u32), ..Default::default() };
+        let a1 = build_payload(&side, &[], &attributes).unwrap().block;
+        let a1_hash = side.import_block(a1.clone()).unwrap();
+        let attributes = PayloadAttributes { timestamp: U256::from(24_u32), ..Default::default() };
+        let mut a2 = build_payload(&side, &[], &attributes).unwrap().block;
+        a2.header.state_root = Root::default();
+        for block in [a1, a2.clone()] {
+            let status = engine.new_payload_v3(&ExecutionPayload::from_block(&block).unwrap(), &[], Root::default());
+            assert_eq!(status.status, PayloadStatusKind::Accepted, "{status:?}");
+        }
+
+        let a2_hash = compute_header_hash(&a2.header).unwrap();
+        let fork_choice = ForkChoiceState { head_block_hash: a2_hash, ..Default::default() };
+        let updated = engine.fork_choice_updated_v3(fork_choice, None).unwrap();
+        assert_eq!(updated.payload_status.status, PayloadStatusKind::Invalid);
+        assert_eq!(updated.payload_status.latest_valid_hash, Some(a1_hash));
+        assert_eq!(engine.chain.head_hash(), &payload.block_hash);
+    }
+
+    #[test]
+    fn blobs_bundles() {
+        let settings = kzg::tests::settings();
+        let blobs = [kzg::tests::blob(1), kzg::tests::blob(2)];
+        let (commitments, proofs): (Vec<_>, Vec<_>) = blobs.iter().map(|blob| kzg::tests::commit_blob(blob)).unzip();
+        let hashes: Vec<_> = commitments.iter().map(kzg_commitment_to_versioned_hash).collect();
+        let bundle = BlobsBundle { commitments, proofs, blobs: blobs.map(Bytes).to_vec() };
+        let bundle: BlobsBundle = from_json(to_json(&bundle).as_bytes()).unwrap();
+        assert!(bundle.verify(&hashes, &settings).is_ok());
+        assert!(bundle.verify(&hashes[..1], &settings).is_err());
+        assert!(bundle.verify(&[hashes[1].clone(), hashes[0].clone()], &settings).is_err());
+        let swapped = BlobsBundle { proofs: vec![bundle.proofs[1], bundle.proofs[0]], ..bundle.clone() };
+        assert!(swapped.verify(&hashes, &settings).is_err());
+
+        // The block has no blob transactions, so only an empty bundle goes
+        // with it.
+        let (chain, block) = chain_and_block();
+        let mut engine = Engine::new(chain);
+        let payload = ExecutionPayload::from_block(&block).unwrap();
+        let status = engine.new_payload_v3_with_blobs(&payload, &bundle, Root::default());
+        assert_eq!(status.status, PayloadStatusKind::Invalid);
+        let status = engine.new_payload_v3_with_blobs(&payload, &BlobsBundle::default(), Root::default());
+        assert_eq!(status.status, PayloadStatusKind::Valid, "{status:?}");
+    }
+}
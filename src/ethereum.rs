@@ -6,9 +6,15 @@ pub mod ethereum_types;
 pub mod ethereum_rlp;
 
 pub mod crypto;
+pub mod pre_merge;
+pub mod paris;
 pub mod cancun;
+pub mod shanghai;
+pub mod prague;
 pub mod exceptions;
+pub mod chain_spec;
 pub mod fork_criteria;
+pub mod forks;
 
 pub mod genesis;
 pub mod utils;
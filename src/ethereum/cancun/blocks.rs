@@ -16,10 +16,11 @@ use crate::{
             bytes::{Bytes, Bytes32, Bytes8},
             numeric::{Uint, U256, U64},
         },
-    }, impl_extended
+        exceptions::Exception,
+    }, impl_extended, impl_json
 };
 
-use super::transactions::{LegacyTransaction, Transaction};
+use super::{fork::compute_header_hash, transactions::{LegacyTransaction, Transaction}};
 
 #[derive(Debug, Clone, Default)]
 /// Withdrawals that have been validated on the consensus layer.
@@ -32,6 +33,9 @@ pub struct Withdrawal {
 
 impl_extended!(Withdrawal: index, validator_index, address, amount);
 
+// The JSON-RPC withdrawal object, with `amount` in Gwei.
+impl_json!(Withdrawal : index "index", validator_index "validatorIndex", address "address", amount "amount");
+
 // impl Extended for Withdrawal {
 //     fn encode<'a, 'b>(&self, buffer: &'a mut Bytes) -> Result<(), RLPException> {
 //         encode_sequence(buffer, &[
@@ -75,9 +79,36 @@ pub struct Header {
     pub blob_gas_used: Option<U64>,
     pub excess_blob_gas: Option<U64>,
     pub parent_beacon_block_root: Option<Root>,
+    pub requests_hash: Option<Hash32>,
 }
 
-impl_extended!(Header: parent_hash, ommers_hash, coinbase, state_root, transactions_root, receipt_root, bloom, difficulty, number, gas_limit, gas_used, timestamp, extra_data, prev_randao, nonce, base_fee_per_gas, withdrawals_root, blob_gas_used, excess_blob_gas, parent_beacon_block_root);
+impl_extended!(Header: parent_hash, ommers_hash, coinbase, state_root, transactions_root, receipt_root, bloom, difficulty, number, gas_limit, gas_used, timestamp, extra_data, prev_randao, nonce, base_fee_per_gas, withdrawals_root, blob_gas_used, excess_blob_gas, parent_beacon_block_root, requests_hash);
+
+// The JSON-RPC block object. Members which are not part of the header,
+// such as `hash`, `size` and `transactions`, are ignored.
+impl_json!(#[skip_unknown] Header :
+    parent_hash "parentHash",
+    ommers_hash "sha3Uncles",
+    coinbase "miner",
+    state_root "stateRoot",
+    transactions_root "transactionsRoot",
+    receipt_root "receiptsRoot",
+    bloom "logsBloom",
+    difficulty "difficulty",
+    number "number",
+    gas_limit "gasLimit",
+    gas_used "gasUsed",
+    timestamp "timestamp",
+    extra_data "extraData",
+    prev_randao "mixHash",
+    nonce "nonce",
+    base_fee_per_gas "baseFeePerGas",
+    withdrawals_root "withdrawalsRoot",
+    blob_gas_used "blobGasUsed",
+    excess_blob_gas "excessBlobGas",
+    parent_beacon_block_root "parentBeaconBlockRoot",
+    requests_hash "requestsHash",
+);
 
 #[derive(Debug, Clone, Default)]
 /// A complete block.
@@ -90,7 +121,22 @@ pub struct Block {
 
 impl_extended!(Block: header,transactions,ommers,withdrawals);
 
-#[derive(Debug, Clone, Default)]
+impl Header {
+    /// The hash of the header, which identifies its block. See
+    /// `compute_header_hash`.
+    pub fn hash(&self) -> Result<Hash32, Exception> {
+        compute_header_hash(self)
+    }
+}
+
+impl Block {
+    /// The hash of the block, which is that of its header.
+    pub fn hash(&self) -> Result<Hash32, Exception> {
+        self.header.hash()
+    }
+}
+
+#[derive(Debug, Clone, Default, PartialEq)]
 /// Data record produced during the execution of a transaction.
 pub struct Log {
     pub address: Address,
@@ -100,6 +146,8 @@ pub struct Log {
 
 impl_extended!(Log: address, topics, data);
 
+impl_json!(#[skip_unknown] Log : address "address", topics "topics", data "data");
+
 #[derive(Debug, Clone, Default)]
 /// Result of a transaction.
 pub struct Receipt {
@@ -111,3 +159,16 @@ pub struct Receipt {
 
 impl_extended!(Receipt: succeeded, cumulative_gas_used, bloom, logs);
 
+
+#[derive(Debug, Clone, Default)]
+/// Result of a transaction before Byzantium, which records the state root
+/// after the transaction where later receipts record whether it succeeded
+/// (EIP-658).
+pub struct PostStateReceipt {
+    pub post_state: Root,
+    pub cumulative_gas_used: Uint,
+    pub bloom: Bloom,
+    pub logs: Vec<Log>,
+}
+
+impl_extended!(PostStateReceipt: post_state, cumulative_gas_used, bloom, logs);
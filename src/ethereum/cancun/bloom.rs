@@ -14,71 +14,81 @@
 //! for efficient searching of logs by address and/or topic, by rapidly
 //! eliminating blocks and receipts from their search.
 
-// from typing import Tuple
-
-// from ethereum_types.numeric import Uint
-
-// from ethereum.crypto.hash import keccak256
-
-// from .blocks import Log
-// from .fork_types import Bloom
-
-
-// def add_to_bloom(bloom: bytearray, bloom_entry: bytes) -> None:
-//     """
-//     Add a bloom entry to the bloom filter (`bloom`).
-
-//     The number of hash functions used is 3. They are calculated by taking the
-//     least significant 11 bits from the first 3 16-bit words of the
-//     `keccak_256()` hash of `bloom_entry`.
-
-//     Parameters
-//     ----------
-//     bloom :
-//         The bloom filter.
-//     bloom_entry :
-//         An entry which is to be added to bloom filter.
-//     """
-//     hash = keccak256(bloom_entry)
-
-//     for idx in (0, 2, 4):
-//         # Obtain the least significant 11 bits from the pair of bytes
-//         # (16 bits), and set this bit in bloom bytearray.
-//         # The obtained bit is 0-indexed in the bloom filter from the least
-//         # significant bit to the most significant bit.
-//         bit_to_set = Uint.from_be_bytes(hash[idx : idx + 2]) & Uint(0x07FF)
-//         # Below is the index of the bit in the bytearray (where 0-indexed
-//         # byte is the most significant byte)
-//         bit_index = 0x07FF - int(bit_to_set)
-
-//         byte_index = bit_index // 8
-//         bit_value = 1 << (7 - (bit_index % 8))
-//         bloom[byte_index] = bloom[byte_index] | bit_value
-
-
-// def logs_bloom(logs: Tuple[Log, ...]) -> Bloom:
-//     """
-//     Obtain the logs bloom from a list of log entries.
-
-//     The address and each topic of a log are added to the bloom filter.
-
-//     Parameters
-//     ----------
-//     logs :
-//         List of logs for which the logs bloom is to be obtained.
-
-//     Returns
-//     -------
-//     logs_bloom : `Bloom`
-//         The logs bloom obtained which is 256 bytes with some bits set as per
-//         the caller address and the log topics.
-//     """
-//     bloom: bytearray = bytearray(b"\x00" * 256)
-
-//     for log in logs:
-//         add_to_bloom(bloom, log.address)
-//         for topic in log.topics:
-//             add_to_bloom(bloom, topic)
-
-//     return Bloom(bloom)
-
+use crate::ethereum::crypto::hash::keccak256;
+
+use super::{blocks::Log, fork_types::Bloom};
+
+/// Add a bloom entry to the bloom filter (`bloom`).
+/// 
+/// The number of hash functions used is 3. They are calculated by taking the
+/// least significant 11 bits from the first 3 16-bit words of the
+/// `keccak_256()` hash of `bloom_entry`.
+/// 
+/// Parameters
+/// ----------
+/// bloom :
+///     The bloom filter.
+/// bloom_entry :
+///     An entry which is to be added to bloom filter.
+pub fn add_to_bloom(bloom: &mut [u8; 256], bloom_entry: &[u8]) {
+    let hash = keccak256(bloom_entry);
+
+    for idx in [0, 2, 4] {
+        // Obtain the least significant 11 bits from the pair of bytes
+        // (16 bits), and set this bit in bloom bytearray.
+        // The obtained bit is 0-indexed in the bloom filter from the least
+        // significant bit to the most significant bit.
+        let bit_to_set = u16::from_be_bytes([hash[idx], hash[idx + 1]]) & 0x07FF;
+        // Below is the index of the bit in the bytearray (where 0-indexed
+        // byte is the most significant byte)
+        let bit_index = (0x07FF - bit_to_set) as usize;
+
+        let byte_index = bit_index / 8;
+        let bit_value = 1 << (7 - (bit_index % 8));
+        bloom[byte_index] |= bit_value;
+    }
+}
+
+/// Check whether `bloom_entry` may have been added to the bloom filter
+/// (`bloom`) with `add_to_bloom`.
+/// 
+/// False positives are possible, false negatives are not.
+/// 
+/// Parameters
+/// ----------
+/// bloom :
+///     The bloom filter.
+/// bloom_entry :
+///     An entry to look for in the bloom filter.
+pub fn bloom_contains(bloom: &[u8; 256], bloom_entry: &[u8]) -> bool {
+    let mut entry_bloom = [0; 256];
+    add_to_bloom(&mut entry_bloom, bloom_entry);
+    bloom.iter().zip(entry_bloom).all(|(bits, entry_bits)| bits & entry_bits == entry_bits)
+}
+
+/// Obtain the logs bloom from a list of log entries.
+/// 
+/// The address and each topic of a log are added to the bloom filter.
+/// 
+/// Parameters
+/// ----------
+/// logs :
+///     List of logs for which the logs bloom is to be obtained.
+/// 
+/// Returns
+/// -------
+/// logs_bloom : `Bloom`
+///     The logs bloom obtained which is 256 bytes with some bits set as per
+///     the caller address and the log topics.
+pub fn logs_bloom(logs: &[Log]) -> Bloom {
+    let mut bloom = Bloom::default();
+
+    for log in logs {
+        add_to_bloom(&mut bloom.0.0, &log.address[..]);
+        for topic in &log.topics {
+            add_to_bloom(&mut bloom.0.0, &topic[..]);
+        }
+    }
+
+    bloom
+}
@@ -10,7 +10,10 @@
 //!
 //! Entry point for the Ethereum specification.
 
-use std::collections::{BTreeSet, HashSet};
+use std::{
+    collections::{BTreeMap, BTreeSet, HashMap, HashSet},
+    sync::Arc,
+};
 
 use crate::ethereum::{
         crypto::hash::{keccak256, Hash32},
@@ -19,57 +22,181 @@ use crate::ethereum::{
             bytes::{Bytes, Bytes20, Bytes32, Bytes8},
             numeric::{Uint, U256, U64},
         },
-        exceptions::Exception, genesis::Genesis,
+        chain_spec::ChainSpec,
+        exceptions::Exception,
+        fork_criteria::ForkSchedule,
+        forks::{fork_rules, ExecutionRules, Fork, HeaderConstraints},
+        genesis::{add_genesis_block, Genesis, MAINNET_GENESIS_HASH},
+        prague::{
+            eoa_delegation::{is_valid_delegation, set_delegation},
+            fork::HISTORY_STORAGE_ADDRESS,
+            requests::{
+                compute_requests_hash, parse_deposit_requests, CONSOLIDATION_REQUEST_PREDEPLOY_ADDRESS,
+                CONSOLIDATION_REQUEST_TYPE, DEPOSIT_REQUEST_TYPE, WITHDRAWAL_REQUEST_PREDEPLOY_ADDRESS,
+                WITHDRAWAL_REQUEST_TYPE,
+            },
+            transactions::SetCodeTransaction,
+        },
     };
+use crate::{
+    log::{self, debug, trace, Level},
+    metrics,
+};
 
 use super::{
-    blocks::{Block, Header, Log, Receipt, Withdrawal},
-    fork_types::{Address, Bloom, Root},
-    state::{get_account, State, TransientStorage},
-    transactions::{AccessListTransaction, BlobTransaction, FeeMarketTransaction, LegacyTransaction, Transaction},
+    blocks::{Block, Header, Log, PostStateReceipt, Receipt, Withdrawal},
+    bloom::logs_bloom,
+    fork_types::{Address, Bloom, Root, VersionedHash},
+    state::{
+        account_exists_and_is_empty, destroy_account, destroy_touched_empty_accounts, get_account,
+        increment_nonce, process_withdrawal, set_account_balance,
+        snapshot::{SnapshotView, Snapshots},
+        state_root, State, StateBackend, StateDiff, TransientStorage,
+    },
+    transactions::{
+        calculate_intrinsic_cost, encode_transaction, validate_transaction,
+        AccessListTransaction, BlobTransaction, Either, FeeMarketTransaction, LegacyTransaction, Transaction,
+    },
     trie::Trie,
-    vm::{self, exceptions::VmError, gas::calculate_excess_blob_gas, interpreter::process_message_call},
+    utils::{compute_contract_address, prepare_message},
+    vm::{
+        self,
+        code_cache::{jump_destinations_cache, CodeCache},
+        exceptions::VmError,
+        gas::{calculate_blob_gas_price, calculate_data_fee, calculate_excess_blob_gas, calculate_total_blob_gas},
+        instructions::Ops,
+        interpreter::{process_message_call, MessageCallOutput},
+        tracing::CallStart,
+    },
 };
 
-const BASE_FEE_MAX_CHANGE_DENOMINATOR: Uint = 8;
-const ELASTICITY_MULTIPLIER: Uint = 2;
-const GAS_LIMIT_ADJUSTMENT_FACTOR: Uint = 1024;
-const GAS_LIMIT_MINIMUM: Uint = 5000;
-const EMPTY_OMMER_HASH: Hash32 = Hash32([0; 32]); //keccak256(rlp.encode([]));
-const SYSTEM_ADDRESS: Address = Address::from_be_bytes([
-    0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff,
-    0xff, 0xff, 0xff, 0xfe,
-]);
-const BEACON_ROOTS_ADDRESS: Address = Address::from_be_bytes([
-    0x00, 0x0F, 0x3d, 0xf6, 0xD7, 0x32, 0x80, 0x7E, 0xf1, 0x31, 0x9f, 0xB7, 0xB8, 0xbB, 0x85, 0x22,
-    0xd0, 0xBe, 0xac, 0x02,
+use prevalidate::BlockPrevalidator;
+use proof_of_work::{has_ommers, pay_rewards, validate_ommers};
+use receipts::TransactionReceipt;
+
+/// Base fee of the first London block (EIP-1559), and of a genesis block
+/// which does not give one if London is active from genesis.
+pub const INITIAL_BASE_FEE: Uint = 1_000_000_000;
+/// `keccak256(rlp.encode([]))`
+pub const EMPTY_OMMER_HASH: Hash32 = Hash32([
+    0x1d, 0xcc, 0x4d, 0xe8, 0xde, 0xc7, 0x5d, 0x7a, 0xab, 0x85, 0xb5, 0x67, 0xb6, 0xcc, 0xd4, 0x1a,
+    0xd3, 0x12, 0x45, 0x1b, 0x94, 0x8a, 0x74, 0x13, 0xf0, 0xa1, 0x42, 0xfd, 0x40, 0xd4, 0x93, 0x47,
 ]);
+/// Diff layers the snapshots of a `BlockChain` keep below its head, as in
+/// geth. Older layers are merged into the disk layer.
+pub const SNAPSHOT_DIFF_LAYERS: usize = 128;
 const SYSTEM_TRANSACTION_GAS: Uint = 30000000;
-const MAX_BLOB_GAS_PER_BLOCK: Uint = 786432;
 const VERSIONED_HASH_VERSION_KZG: &'static [u8] = b"\x01";
 
-#[derive(Debug)]
+/// The Cancun fork.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct Cancun;
+
+impl Fork for Cancun {
+    fn name(&self) -> &'static str {
+        "cancun"
+    }
+
+    fn header_constraints(&self) -> HeaderConstraints {
+        HeaderConstraints::CANCUN
+    }
+
+    fn execution_rules(&self) -> ExecutionRules {
+        ExecutionRules::CANCUN
+    }
+
+    fn state_transition(&self, chain: &mut BlockChain, block: Block) -> Result<(), Exception> {
+        process_block(self, chain, block)
+    }
+}
+
+#[derive(Debug, Clone)]
 /// History and current state of the block chain.
 pub struct BlockChain {
     pub blocks: Vec<Block>,
     pub state: State,
-    pub chain_id: U64,
+    /// Which fork's rules apply to each block.
+    pub fork_schedule: ForkSchedule,
+    /// Imported blocks which are not on the canonical chain, by hash.
+    pub side_blocks: BTreeMap<Hash32, Block>,
+    /// The last fork choice applied with `fork_choice_update`.
+    pub fork_choice: ForkChoiceState,
+    /// The chain id and parameters of the chain, from the `config` section
+    /// of its genesis.
+    pub chain_spec: ChainSpec,
+    /// Hashes of `blocks`.
+    hashes: Vec<Hash32>,
+    /// Position in `blocks` of each of `hashes`.
+    hash_index: HashMap<Hash32, usize>,
+    /// The valid jump destinations of the code run by the blocks and calls
+    /// on the chain, shared by its clones.
+    pub code_cache: Arc<CodeCache<BTreeSet<Uint>>>,
+    /// Receipts of the transactions of `blocks`.
+    receipts: Vec<Vec<TransactionReceipt>>,
+    /// Position in `blocks` and in its block of each canonical transaction.
+    transaction_index: BTreeMap<Hash32, (usize, usize)>,
+    /// State changes made by `blocks[1..]`, used to rewind the chain.
+    diffs: Vec<StateDiff>,
+    /// Hashes of blocks which failed to execute.
+    invalid_blocks: BTreeSet<Hash32>,
+    /// Flat snapshots of the state at the recent blocks, kept if the chain
+    /// was built `with_snapshots`. Only imported blocks reach them, not
+    /// writes made to `state` directly.
+    pub snapshots: Option<Snapshots>,
 }
 
 impl BlockChain {
-    pub fn from_genesis(genesis: Genesis) -> Self {
-        let block = Block {
-            header: genesis.header,
-            transactions: Default::default(),
-            ommers: Default::default(),
-            withdrawals: Default::default(),
-        };
-        let state = State::from_alloc(genesis.alloc);
-        Self {
+    /// Start a chain from the block built by `add_genesis_block`.
+    pub fn from_genesis(genesis: Genesis) -> Result<Self, Exception> {
+        let mut state = State::default();
+        let block = add_genesis_block(&genesis, &mut state)?;
+        let hash = compute_header_hash(&block.header)?;
+        Ok(Self {
             blocks: vec![block],
             state,
-            chain_id: genesis.chain_id,
+            fork_schedule: ForkSchedule::new(&genesis.forks),
+            side_blocks: Default::default(),
+            fork_choice: Default::default(),
+            chain_spec: genesis.chain_spec.clone(),
+            hash_index: HashMap::from([(hash.clone(), 0)]),
+            code_cache: Arc::new(jump_destinations_cache()),
+            hashes: vec![hash],
+            receipts: vec![Vec::new()],
+            transaction_index: BTreeMap::new(),
+            diffs: Vec::new(),
+            invalid_blocks: Default::default(),
+            snapshots: None,
+        })
+    }
+
+    /// Keep flat snapshots of the state, starting at the head, with a layer
+    /// for each block imported. Calls on the chain then read the head state
+    /// from its snapshot rather than copying it.
+    pub fn with_snapshots(mut self) -> Self {
+        let root = self.blocks.last().unwrap().header.state_root.clone();
+        self.snapshots = Some(Snapshots::new(root, &self.state));
+        self
+    }
+
+    /// The snapshot of the head state, if the chain keeps snapshots and the
+    /// head is still in them.
+    pub fn head_snapshot(&self) -> Option<SnapshotView<'_>> {
+        self.snapshots.as_ref()?.get(&self.blocks.last().unwrap().header.state_root)
+    }
+
+    /// The fork whose rules apply to the block with `header`.
+    pub fn fork_for(&self, header: &Header) -> Result<&'static dyn Fork, Exception> {
+        let name = self.fork_schedule.fork_for(header);
+        fork_rules(name).ok_or(Exception::UnsupportedFork(name))
+    }
+
+    /// Start a chain from the mainnet genesis block, checking its hash.
+    pub fn mainnet() -> Result<Self, Exception> {
+        let chain = Self::from_genesis(Genesis::mainnet()?)?;
+        if *chain.head_hash() != MAINNET_GENESIS_HASH {
+            return Err(Exception::InvalidBlock("mainnet genesis hash mismatch"));
         }
+        Ok(chain)
     }
 }
 
@@ -110,17 +237,40 @@ fn apply_fork(chain: BlockChain) -> BlockChain {
 ///     -------
 ///     recent_block_hashes : `List[Hash32]`
 ///         Hashes of the recent 256 blocks in order of increasing block number.
-fn get_last_256_block_hashes(chain: &BlockChain) -> Vec<Hash32> {
-    let start = chain.blocks.len().saturating_sub(256);
-    let recent_blocks = &chain.blocks[start..chain.blocks.len()];
-    let mut recent_block_hashes = recent_blocks
+pub fn get_last_256_block_hashes(chain: &BlockChain) -> Vec<Hash32> {
+    let start = chain.blocks.len().saturating_sub(255);
+    let recent_blocks = &chain.blocks[start..];
+    if recent_blocks.is_empty() {
+        return Vec::new();
+    }
+    let mut recent_block_hashes: Vec<_> = recent_blocks
         .iter()
         .map(|b| b.header.parent_hash.clone())
         .collect();
-    // recent_block_hashes.append(keccak256(rlp::encode(recent_blocks[-1].header));
+    // The hash of the most recent block is not the parent hash of any
+    // block yet. It is kept in `chain.hashes`, rather than hashed again.
+    recent_block_hashes.push(chain.hashes[chain.blocks.len() - 1].clone());
     recent_block_hashes
 }
 
+/// The hashes of the 256 blocks before block `number` in order of
+/// increasing block number, as `get_last_256_block_hashes` gives them, for
+/// tools which are given some of them by number. `hash_of` gives the hash
+/// of a block, and those it does not know are zero.
+pub fn recent_block_hashes(number: Uint, hash_of: impl Fn(Uint) -> Option<Hash32>) -> Vec<Hash32> {
+    (number.saturating_sub(256)..number).map(|number| hash_of(number).unwrap_or_default()).collect()
+}
+
+/// The value of `PREVRANDAO` in a block of `fork`, given the `random` of
+/// its header and its `difficulty`. Before the merge, the instruction was
+/// `DIFFICULTY`.
+pub fn prev_randao(fork: &dyn Fork, random: Option<&Bytes32>, difficulty: Uint) -> Bytes32 {
+    match (random, fork.proof_of_work()) {
+        (Some(random), None) => random.clone(),
+        _ => Bytes32(U256::from_uint(difficulty).to_be_bytes()),
+    }
+}
+
 ///    Attempts to apply a block to an existing block chain.
 ///
 ///    All parts of the block's contents need to be verified before being added
@@ -140,23 +290,73 @@ fn get_last_256_block_hashes(chain: &BlockChain) -> Vec<Hash32> {
 ///        History and current state.
 ///    block :
 ///        Block to apply to `chain`.
-fn state_transition(chain: &mut BlockChain, block: Block) -> Result<(), Exception> {
+///
+///    The block is checked and applied under the rules of the fork which
+///    `chain.fork_schedule` assigns to it. The changes it makes to the state
+///    are kept so that `set_head` can rewind it, and an invalid block leaves
+///    the state as it was.
+pub fn state_transition(chain: &mut BlockChain, block: Block) -> Result<(), Exception> {
+    let fork = chain.fork_for(&block.header)?;
+    let transactions = block.transactions.len() as u64;
+    let _span = log::span(Level::Debug, "block", format_args!("number={} txs={transactions}", block.header.number));
+    let gas_used = block.header.gas_used as u64;
+    let parent_root = chain.blocks.last().unwrap().header.state_root.clone();
+    chain.state.start_diff();
+    let result = metrics::BLOCK_PROCESSING.time(|| fork.state_transition(chain, block));
+    // An invalid block can stop in the middle of a transaction.
+    chain.state.rollback_transactions();
+    let diff = chain.state.take_diff().unwrap_or_default();
+    match &result {
+        Ok(()) => {
+            debug!("imported, {gas_used} gas");
+            metrics::BLOCKS_IMPORTED.inc();
+            metrics::TRANSACTIONS.add(transactions);
+            metrics::GAS_USED.add(gas_used);
+            update_snapshots(chain, parent_root, &diff);
+            chain.diffs.push(diff);
+        }
+        Err(error) => {
+            debug!("rejected: {error}");
+            chain.state.revert_diff(&diff);
+            metrics::BLOCKS_INVALID.inc();
+        }
+    }
+    result
+}
+
+/// Add the layer of the block just imported on top of the state at `parent`
+/// to the snapshots of `chain`. If the parent is no longer in them, because
+/// the chain was rewound below the disk layer, they start again from the
+/// state.
+fn update_snapshots(chain: &mut BlockChain, parent: Root, diff: &StateDiff) {
+    let Some(snapshots) = &mut chain.snapshots else {
+        return;
+    };
+    let root = chain.blocks.last().unwrap().header.state_root.clone();
+    if snapshots.update(root.clone(), parent, diff).is_err() {
+        *snapshots = Snapshots::new(root.clone(), &chain.state);
+    }
+    snapshots.cap(&root, SNAPSHOT_DIFF_LAYERS);
+}
+
+/// `state_transition` under the rules of `fork`, for forks which differ
+/// from Cancun only in what `HeaderConstraints`, `ExecutionRules` and
+/// `ProofOfWork` describe.
+pub(crate) fn process_block(fork: &dyn Fork, chain: &mut BlockChain, block: Block) -> Result<(), Exception> {
     let parent_header = chain
         .blocks
         .get(chain.blocks.len() - 1)
         .map(|b| &b.header)
         .unwrap();
-    let excess_blob_gas = calculate_excess_blob_gas(parent_header);
-    if block.header.excess_blob_gas != excess_blob_gas {
-        return Err(Exception::InvalidBlock(
-            "block.header.excess_blob_gas != excess_blob_gas"
-        ));
-    }
-
-    validate_header(&block.header, parent_header);
-    if !block.ommers.is_empty() {
+    let excess_blob_gas = validate_header_with_parent(&block.header, parent_header, fork, &chain.chain_spec)?;
+    let proof_of_work = fork.proof_of_work();
+    if proof_of_work.is_some() {
+        validate_ommers(&block.ommers, &block.header, chain, fork)?;
+    } else if !block.ommers.is_empty() {
         return Err(Exception::InvalidBlock("!block.ommers.is_empty()"));
     }
+    let rules = fork.execution_rules();
+    let prev_randao = prev_randao(fork, Some(&block.header.prev_randao), block.header.difficulty);
 
     let last_256_block_hashes = get_last_256_block_hashes(chain);
     let apply_body_output = apply_body(
@@ -167,50 +367,73 @@ fn state_transition(chain: &mut BlockChain, block: Block) -> Result<(), Exceptio
         &block.header.base_fee_per_gas,
         &block.header.gas_limit,
         &block.header.timestamp,
-        &block.header.prev_randao,
+        &prev_randao,
         &block.transactions,
-        chain.chain_id,
+        &block.ommers,
+        proof_of_work.map(|pow| pow.block_reward),
+        chain.chain_spec.chain_id,
         block.withdrawals.as_deref(),
         &block.header.parent_beacon_block_root,
         &excess_blob_gas,
+        rules,
+        &chain.chain_spec,
+        Some(chain.code_cache.clone()),
     )?;
-    if apply_body_output.block_gas_used != block.header.gas_used {
-        return Err(Exception::InvalidBlock(
-            "apply_body_output.block_gas_used != block.header.gas_used"
-        ));
+    let header = &block.header;
+    if apply_body_output.block_gas_used != header.gas_used {
+        return Err(Exception::GasUsedMismatch {
+            expected: header.gas_used,
+            actual: apply_body_output.block_gas_used,
+        });
     }
-    if apply_body_output.transactions_root != block.header.transactions_root {
-        return Err(Exception::InvalidBlock(
-            "apply_body_output.transactions_root != block.header.transactions_root"
-        ));
+    if apply_body_output.transactions_root != header.transactions_root {
+        return Err(Exception::TransactionsRootMismatch {
+            expected: header.transactions_root.clone(),
+            actual: apply_body_output.transactions_root,
+        });
     }
-    if apply_body_output.state_root != block.header.state_root {
-        return Err(Exception::InvalidBlock(
-            "apply_body_output.state_root != block.header.state_root"
-        ));
+    if apply_body_output.state_root != header.state_root {
+        return Err(Exception::StateRootMismatch {
+            expected: header.state_root.clone(),
+            actual: apply_body_output.state_root,
+        });
     }
-    if apply_body_output.receipt_root != block.header.receipt_root {
-        return Err(Exception::InvalidBlock(
-            "apply_body_output.receipt_root != block.header.receipt_root"
-        ));
+    if apply_body_output.receipt_root != header.receipt_root {
+        return Err(Exception::ReceiptRootMismatch {
+            expected: header.receipt_root.clone(),
+            actual: apply_body_output.receipt_root,
+        });
     }
-    if apply_body_output.block_logs_bloom != block.header.bloom {
-        return Err(Exception::InvalidBlock(
-            "apply_body_output.block_logs_bloom != block.header.bloom"
-        ));
+    if apply_body_output.block_logs_bloom != header.bloom {
+        return Err(Exception::BloomMismatch {
+            expected: Box::new(header.bloom.clone()),
+            actual: Box::new(apply_body_output.block_logs_bloom),
+        });
     }
-    if apply_body_output.withdrawals_root != block.header.withdrawals_root {
-        return Err(Exception::InvalidBlock(
-            "apply_body_output.withdrawals_root != block.header.withdrawals_root"
-        ));
+    if apply_body_output.withdrawals_root != header.withdrawals_root {
+        return Err(Exception::WithdrawalsRootMismatch {
+            expected: header.withdrawals_root.clone(),
+            actual: apply_body_output.withdrawals_root,
+        });
     }
-    if apply_body_output.blob_gas_used != block.header.blob_gas_used {
-        return Err(Exception::InvalidBlock(
-            "apply_body_output.blob_gas_used != block.header.blob_gas_used"
-        ));
+    if apply_body_output.blob_gas_used != header.blob_gas_used {
+        return Err(Exception::BlobGasUsedMismatch {
+            expected: header.blob_gas_used,
+            actual: apply_body_output.blob_gas_used,
+        });
+    }
+    if apply_body_output.requests_hash != header.requests_hash {
+        return Err(Exception::RequestsHashMismatch {
+            expected: header.requests_hash.clone(),
+            actual: apply_body_output.requests_hash,
+        });
     }
 
+    let hash = compute_header_hash(&block.header)?;
+    chain.hash_index.insert(hash.clone(), chain.blocks.len());
     chain.blocks.push(block);
+    chain.hashes.push(hash);
+    chain.push_receipts(apply_body_output.receipts);
     // if self.blocks.len() > 255 {
     //     // Real clients have to store more blocks to deal with reorgs, but the
     //     // protocol only requires the last 255
@@ -231,19 +454,22 @@ fn state_transition(chain: &mut BlockChain, block: Block) -> Result<(), Exceptio
 ///     Gas used in the parent block.
 /// parent_base_fee_per_gas :
 ///     Base fee per gas of the parent block.
+/// chain_spec :
+///     The fee market and gas limit parameters of the chain.
 ///
 /// Returns
 /// -------
 /// base_fee_per_gas : `Uint`
 ///     Base fee per gas for the block.
-fn calculate_base_fee_per_gas(
+pub fn calculate_base_fee_per_gas(
     block_gas_limit: Uint,
     parent_gas_limit: Uint,
     parent_gas_used: Uint,
     parent_base_fee_per_gas: Uint,
+    chain_spec: &ChainSpec,
 ) -> Result<Uint, Exception> {
-    let parent_gas_target = parent_gas_limit / ELASTICITY_MULTIPLIER;
-    if !check_gas_limit(block_gas_limit, parent_gas_limit) {
+    let parent_gas_target = parent_gas_limit / chain_spec.elasticity_multiplier;
+    if !check_gas_limit(block_gas_limit, parent_gas_limit, chain_spec) {
         return Err(Exception::InvalidBlock(
             "!check_gas_limit(block_gas_limit, parent_gas_limit)"
         ));
@@ -258,7 +484,7 @@ fn calculate_base_fee_per_gas(
         let target_fee_gas_delta = parent_fee_gas_delta / parent_gas_target;
 
         let base_fee_per_gas_delta =
-            (target_fee_gas_delta / BASE_FEE_MAX_CHANGE_DENOMINATOR).max(1);
+            (target_fee_gas_delta / chain_spec.base_fee_max_change_denominator).max(1);
 
         Ok(parent_base_fee_per_gas + base_fee_per_gas_delta)
     } else {
@@ -267,12 +493,36 @@ fn calculate_base_fee_per_gas(
         let parent_fee_gas_delta = parent_base_fee_per_gas * gas_used_delta;
         let target_fee_gas_delta = parent_fee_gas_delta / parent_gas_target;
 
-        let base_fee_per_gas_delta = (target_fee_gas_delta / BASE_FEE_MAX_CHANGE_DENOMINATOR);
+        let base_fee_per_gas_delta = (target_fee_gas_delta / chain_spec.base_fee_max_change_denominator);
 
         Ok(parent_base_fee_per_gas - base_fee_per_gas_delta)
     }
 }
 
+/// Verifies a block header as the header of the next canonical block after
+/// `parent_header`: `validate_header`, and the excess blob gas which only
+/// blocks, not ommers, carry.
+///
+/// Returns the excess blob gas of the block, `None` before Cancun.
+pub(crate) fn validate_header_with_parent(
+    header: &Header,
+    parent_header: &Header,
+    fork: &dyn Fork,
+    chain_spec: &ChainSpec,
+) -> Result<Option<U64>, Exception> {
+    // The fields are zero in the parent of the first block with blob gas.
+    let excess_blob_gas =
+        fork.header_constraints().blob_gas.then(|| calculate_excess_blob_gas(parent_header, chain_spec).unwrap_or(0));
+    if header.excess_blob_gas != excess_blob_gas {
+        return Err(Exception::InvalidBlock(
+            "block.header.excess_blob_gas != excess_blob_gas"
+        ));
+    }
+
+    validate_header(header, parent_header, fork, chain_spec)?;
+    Ok(excess_blob_gas)
+}
+
 /// Verifies a block header.
 ///
 /// In order to consider a block's header valid, the logic for the
@@ -288,20 +538,25 @@ fn calculate_base_fee_per_gas(
 ///     Header to check for correctness.
 /// parent_header :
 ///     Parent Header of the header to check for correctness
-fn validate_header(header: &Header, parent_header: &Header) -> Result<(), Exception> {
+/// fork :
+///     The fork of the header.
+/// chain_spec :
+///     The parameters of the chain.
+fn validate_header(header: &Header, parent_header: &Header, fork: &dyn Fork, chain_spec: &ChainSpec) -> Result<(), Exception> {
     if header.gas_used > header.gas_limit {
         return Err(Exception::InvalidBlock(
             "header.gas_used > header.gas_limit"
         ));
     }
 
-    if let Some(parent_base_fee_per_gas) = parent_header.base_fee_per_gas {
-        if let Some(base_fee_per_gas) = header.base_fee_per_gas {
+    match (parent_header.base_fee_per_gas, header.base_fee_per_gas) {
+        (Some(parent_base_fee_per_gas), Some(base_fee_per_gas)) => {
             let expected_base_fee_per_gas = calculate_base_fee_per_gas(
                 header.gas_limit,
                 parent_header.gas_limit,
                 parent_header.gas_used,
                 parent_base_fee_per_gas,
+                chain_spec,
             )?;
             if expected_base_fee_per_gas != base_fee_per_gas {
                 return Err(Exception::InvalidBlock(
@@ -309,6 +564,27 @@ fn validate_header(header: &Header, parent_header: &Header) -> Result<(), Except
                 ));
             }
         }
+        // The first London block keeps the gas target of its parent, which
+        // is its gas limit before London.
+        (None, Some(base_fee_per_gas)) => {
+            if !check_gas_limit(header.gas_limit, parent_header.gas_limit * chain_spec.elasticity_multiplier, chain_spec) {
+                return Err(Exception::InvalidBlock(
+                    "!check_gas_limit(header.gas_limit, parent_header.gas_limit * ELASTICITY_MULTIPLIER)"
+                ));
+            }
+            if base_fee_per_gas != INITIAL_BASE_FEE {
+                return Err(Exception::InvalidBlock(
+                    "header.base_fee_per_gas != INITIAL_BASE_FEE"
+                ));
+            }
+        }
+        _ => {
+            if !check_gas_limit(header.gas_limit, parent_header.gas_limit, chain_spec) {
+                return Err(Exception::InvalidBlock(
+                    "!check_gas_limit(header.gas_limit, parent_header.gas_limit)"
+                ));
+            }
+        }
     }
 
     if header.timestamp <= parent_header.timestamp {
@@ -321,23 +597,21 @@ fn validate_header(header: &Header, parent_header: &Header) -> Result<(), Except
             "header.number != parent_header.number + Uint(1)"
         ));
     }
-    if header.extra_data.len() > 32 {
-        return Err(Exception::InvalidBlock(
-            "header.extra_data.len() > 32"
-        ));
-    }
-    if header.difficulty != 0 {
-        return Err(Exception::InvalidBlock("header.difficulty != 0"));
-    }
-    if header.nonce != Bytes8(*b"\x00\x00\x00\x00\x00\x00\x00\x00") {
-        return Err(Exception::InvalidBlock(
-            "header.nonce != Bytes8(*b\"\x00\x00\x00\x00\x00\x00\x00\x00\")"
-        ));
-    }
-    if header.ommers_hash != EMPTY_OMMER_HASH {
-        return Err(Exception::InvalidBlock(
-            "header.ommers_hash != EMPTY_OMMER_HASH"
-        ));
+    fork.header_constraints().check(header)?;
+
+    if let Some(proof_of_work) = fork.proof_of_work() {
+        let block_difficulty = proof_of_work.calculate_block_difficulty(
+            header.number,
+            header.timestamp,
+            parent_header.timestamp,
+            parent_header.difficulty,
+            has_ommers(parent_header),
+        );
+        if header.difficulty != block_difficulty {
+            return Err(Exception::InvalidBlock(
+                "header.difficulty != block_difficulty"
+            ));
+        }
     }
 
     let block_parent_hash = keccak256(&rlp::encode(parent_header)?);
@@ -350,138 +624,147 @@ fn validate_header(header: &Header, parent_header: &Header) -> Result<(), Except
     Ok(())
 }
 
-// def check_transaction(
-//     state: State,
-//     tx: Transaction,
-//     gas_available: Uint,
-//     chain_id: U64,
-//     base_fee_per_gas: Uint,
-//     excess_blob_gas: U64,
-// ) -> Tuple[Address, Uint, Tuple[VersionedHash, ...]]:
-//     """
-//     Check if the transaction is includable in the block.
-
-//     Parameters
-//     ----------
-//     state :
-//         Current state.
-//     tx :
-//         The transaction.
-//     gas_available :
-//         The gas remaining in the block.
-//     chain_id :
-//         The ID of the current chain.
-//     base_fee_per_gas :
-//         The block base fee.
-//     excess_blob_gas :
-//         The excess blob gas.
-
-//     Returns
-//     -------
-//     sender_address :
-//         The sender of the transaction.
-//     effective_gas_price :
-//         The price to charge for gas when the transaction is executed.
-//     blob_versioned_hashes :
-//         The blob versioned hashes of the transaction.
-
-//     Raises
-//     ------
-//     InvalidBlock :
-//         If the transaction is not includable.
-//     """
-//     if tx.gas > gas_available:
-//         raise InvalidBlock
-//     sender_address = recover_sender(chain_id, tx)
-//     sender_account = get_account(state, sender_address)
-
-//     if isinstance(tx, (FeeMarketTransaction, BlobTransaction)):
-//         if tx.max_fee_per_gas < tx.max_priority_fee_per_gas:
-//             raise InvalidBlock
-//         if tx.max_fee_per_gas < base_fee_per_gas:
-//             raise InvalidBlock
-
-//         priority_fee_per_gas = min(
-//             tx.max_priority_fee_per_gas,
-//             tx.max_fee_per_gas - base_fee_per_gas,
-//         )
-//         effective_gas_price = priority_fee_per_gas + base_fee_per_gas
-//         max_gas_fee = tx.gas * tx.max_fee_per_gas
-//     else:
-//         if tx.gas_price < base_fee_per_gas:
-//             raise InvalidBlock
-//         effective_gas_price = tx.gas_price
-//         max_gas_fee = tx.gas * tx.gas_price
-
-//     if isinstance(tx, BlobTransaction):
-//         if not isinstance(tx.to, Address):
-//             raise InvalidBlock
-//         if len(tx.blob_versioned_hashes) == 0:
-//             raise InvalidBlock
-//         for blob_versioned_hash in tx.blob_versioned_hashes:
-//             if blob_versioned_hash[0:1] != VERSIONED_HASH_VERSION_KZG:
-//                 raise InvalidBlock
-
-//         blob_gas_price = calculate_blob_gas_price(excess_blob_gas)
-//         if Uint(tx.max_fee_per_blob_gas) < blob_gas_price:
-//             raise InvalidBlock
-
-//         max_gas_fee += calculate_total_blob_gas(tx) * Uint(
-//             tx.max_fee_per_blob_gas
-//         )
-//         blob_versioned_hashes = tx.blob_versioned_hashes
-//     else:
-//         blob_versioned_hashes = ()
-//     if sender_account.nonce != tx.nonce:
-//         raise InvalidBlock
-//     if Uint(sender_account.balance) < max_gas_fee + Uint(tx.value):
-//         raise InvalidBlock
-//     if sender_account.code != bytearray():
-//         raise InvalidSenderError("not EOA")
-
-//     return sender_address, effective_gas_price, blob_versioned_hashes
-
-// def make_receipt(
-//     tx: Transaction,
-//     error: Optional[EthereumException],
-//     cumulative_gas_used: Uint,
-//     logs: Tuple[Log, ...],
-// ) -> Union[Bytes, Receipt]:
-//     """
-//     Make the receipt for a transaction that was executed.
-
-//     Parameters
-//     ----------
-//     tx :
-//         The executed transaction.
-//     error :
-//         Error in the top level frame of the transaction, if any.
-//     cumulative_gas_used :
-//         The total gas used so far in the block after the transaction was
-//         executed.
-//     logs :
-//         The logs produced by the transaction.
-
-//     Returns
-//     -------
-//     receipt :
-//         The receipt for the transaction.
-//     """
-//     receipt = Receipt(
-//         succeeded=error is None,
-//         cumulative_gas_used=cumulative_gas_used,
-//         bloom=logs_bloom(logs),
-//         logs=logs,
-//     )
-
-//     if isinstance(tx, AccessListTransaction):
-//         return b"\x01" + rlp.encode(receipt)
-//     elif isinstance(tx, FeeMarketTransaction):
-//         return b"\x02" + rlp.encode(receipt)
-//     elif isinstance(tx, BlobTransaction):
-//         return b"\x03" + rlp.encode(receipt)
-//     else:
-//         return receipt
+/// Check if the transaction is includable in the block.
+///
+/// The checks which only depend on the transaction have already been made
+/// by `prevalidate::prevalidate_transaction`, which recovered its sender.
+///
+/// Parameters
+/// ----------
+/// state :
+///     Current state.
+/// tx :
+///     The transaction.
+/// sender_address :
+///     The sender recovered from the signature of the transaction.
+/// gas_available :
+///     The gas remaining in the block.
+/// base_fee_per_gas :
+///     The block base fee.
+/// excess_blob_gas :
+///     The excess blob gas.
+///
+/// Returns
+/// -------
+/// sender_address :
+///     The sender of the transaction.
+/// effective_gas_price :
+///     The price to charge for gas when the transaction is executed.
+/// blob_versioned_hashes :
+///     The blob versioned hashes of the transaction.
+///
+/// Raises
+/// ------
+/// InvalidBlock :
+///     If the transaction is not includable.
+pub fn check_transaction<S: StateBackend + ?Sized>(
+    state: &S,
+    tx: &Transaction,
+    sender_address: Address,
+    gas_available: Uint,
+    base_fee_per_gas: Uint,
+    excess_blob_gas: U64,
+) -> Result<(Address, Uint, Vec<VersionedHash>), Exception> {
+    if *tx.gas() > gas_available {
+        return Err(Exception::InvalidBlock("tx.gas > gas_available"));
+    }
+    let sender_account = get_account(state, &sender_address);
+
+    // Fees are compared in 256 bits, where they cannot overflow.
+    let gas = U256::from_uint(*tx.gas());
+    let (effective_gas_price, mut max_gas_fee) = match tx {
+        Transaction::FeeMarketTransaction(FeeMarketTransaction { max_fee_per_gas, max_priority_fee_per_gas, .. }) |
+        Transaction::BlobTransaction(BlobTransaction { max_fee_per_gas, max_priority_fee_per_gas, .. }) |
+        Transaction::SetCodeTransaction(SetCodeTransaction { max_fee_per_gas, max_priority_fee_per_gas, .. }) => {
+            if *max_fee_per_gas < base_fee_per_gas {
+                return Err(Exception::InvalidBlock("tx.max_fee_per_gas < base_fee_per_gas"));
+            }
+
+            let priority_fee_per_gas = (*max_priority_fee_per_gas).min(max_fee_per_gas - base_fee_per_gas);
+            let effective_gas_price = priority_fee_per_gas + base_fee_per_gas;
+            (effective_gas_price, gas * U256::from_uint(*max_fee_per_gas))
+        }
+        _ => {
+            let gas_price = tx.gas_price().unwrap();
+            if gas_price < base_fee_per_gas {
+                return Err(Exception::InvalidBlock("tx.gas_price < base_fee_per_gas"));
+            }
+            (gas_price, gas * U256::from_uint(gas_price))
+        }
+    };
+
+    let blob_versioned_hashes = if let Transaction::BlobTransaction(blob_tx) = tx {
+        let blob_gas_price = U256::from_uint(calculate_blob_gas_price(excess_blob_gas));
+        if blob_tx.max_fee_per_blob_gas < blob_gas_price {
+            return Err(Exception::InvalidBlock("tx.max_fee_per_blob_gas < blob_gas_price"));
+        }
+
+        let blob_fee = U256::from_uint(calculate_total_blob_gas(tx))
+            .widening_mul(blob_tx.max_fee_per_blob_gas);
+        let (high, low) = blob_fee.split_at(4);
+        let (sum, carry) = max_gas_fee.overflowing_add(U256::from_limbs(low.try_into().unwrap()));
+        if high.iter().any(|l| *l != 0) || carry {
+            return Err(Exception::InvalidBlock("max_gas_fee overflow"));
+        }
+        max_gas_fee = sum;
+        blob_tx.blob_versioned_hashes.clone()
+    } else {
+        Vec::new()
+    };
+    if U256::from_uint(sender_account.nonce) != *tx.nonce() {
+        return Err(Exception::InvalidBlock("sender_account.nonce != tx.nonce"));
+    }
+    let (total, overflow) = max_gas_fee.overflowing_add(*tx.value());
+    if overflow || sender_account.balance < total {
+        return Err(Exception::InvalidBlock("sender_account.balance < max_gas_fee + tx.value"));
+    }
+    // Accounts which delegate with EIP-7702 still send transactions.
+    if !sender_account.code.is_empty() && !is_valid_delegation(&sender_account.code) {
+        return Err(Exception::InvalidSenderError("not EOA"));
+    }
+
+    Ok((sender_address, effective_gas_price, blob_versioned_hashes))
+}
+
+/// Make the receipt for a transaction that was executed.
+///
+/// Parameters
+/// ----------
+/// tx :
+///     The executed transaction.
+/// error :
+///     Error in the top level frame of the transaction, if any.
+/// cumulative_gas_used :
+///     The total gas used so far in the block after the transaction was
+///     executed.
+/// logs :
+///     The logs produced by the transaction.
+///
+/// Returns
+/// -------
+/// receipt :
+///     The receipt for the transaction.
+pub fn make_receipt(
+    tx: &Transaction,
+    error: &Option<VmError>,
+    cumulative_gas_used: Uint,
+    logs: &[Log],
+) -> Result<Either<Receipt, Bytes>, Exception> {
+    let receipt = Receipt {
+        succeeded: error.is_none(),
+        cumulative_gas_used,
+        bloom: logs_bloom(logs),
+        logs: logs.to_vec(),
+    };
+
+    Ok(match tx {
+        Transaction::LegacyTransaction(_) => Either::A(receipt),
+        Transaction::AccessListTransaction(_) => Either::B(Bytes([&b"\x01"[..], &rlp::encode(&receipt)?].concat())),
+        Transaction::FeeMarketTransaction(_) => Either::B(Bytes([&b"\x02"[..], &rlp::encode(&receipt)?].concat())),
+        Transaction::BlobTransaction(_) => Either::B(Bytes([&b"\x03"[..], &rlp::encode(&receipt)?].concat())),
+        Transaction::SetCodeTransaction(_) => Either::B(Bytes([&b"\x04"[..], &rlp::encode(&receipt)?].concat())),
+    })
+}
 
 ///     Output from applying the block body to the present state.
 ///
@@ -502,14 +785,21 @@ fn validate_header(header: &Header, parent_header: &Header) -> Result<(), Except
 ///         Trie root of all the withdrawals in the block.
 ///     blob_gas_used : `ethereum.base_types.Uint`
 ///         Total blob gas used in the block.
+///     receipts : `List[TransactionReceipt]`
+///         Receipts of the transactions, with their hashes and senders.
+///     requests_hash : `Bytes`
+///         Hash of all the requests in the block.
+#[derive(Debug, Clone)]
 pub struct ApplyBodyOutput {
-    block_gas_used: Uint,
-    transactions_root: Root,
-    receipt_root: Root,
-    block_logs_bloom: Bloom,
-    state_root: Root,
-    withdrawals_root: Option<Root>,
-    blob_gas_used: Option<U64>,
+    pub block_gas_used: Uint,
+    pub transactions_root: Root,
+    pub receipt_root: Root,
+    pub block_logs_bloom: Bloom,
+    pub state_root: Root,
+    pub withdrawals_root: Option<Root>,
+    pub blob_gas_used: Option<U64>,
+    pub receipts: Vec<TransactionReceipt>,
+    pub requests_hash: Option<Hash32>,
 }
 
 /// Executes a block.
@@ -545,6 +835,8 @@ pub struct ApplyBodyOutput {
 /// ommers :
 ///     Headers of ancestor blocks which are not direct parents (formerly
 ///     uncles.)
+/// block_reward :
+///     Reward of the miner of the block, `None` after the merge.
 /// chain_id :
 ///     ID of the executing chain.
 /// withdrawals :
@@ -553,6 +845,13 @@ pub struct ApplyBodyOutput {
 ///     The root of the beacon block from the parent block.
 /// excess_blob_gas :
 ///     Excess blob gas calculated from the previous block.
+/// rules :
+///     Instructions and system calls of the fork of the block.
+/// chain_spec :
+///     The parameters of the chain.
+/// code_cache :
+///     The valid jump destinations of code to share between transactions,
+///     or `None` to find them on every call.
 ///
 /// Returns
 /// -------
@@ -568,138 +867,426 @@ pub fn apply_body(
     block_time: &U256,
     prev_randao: &Bytes32,
     transactions: &[Transaction],
+    ommers: &[Header],
+    block_reward: Option<Uint>,
     chain_id: U64,
     withdrawals: Option<&[Withdrawal]>,
     parent_beacon_block_root: &Option<Root>,
     excess_blob_gas: &Option<U64>,
+    rules: ExecutionRules,
+    chain_spec: &ChainSpec,
+    code_cache: Option<Arc<CodeCache<BTreeSet<Uint>>>>,
 ) -> Result<ApplyBodyOutput, Exception> {
-    // let blob_gas_used = 0;
-    // let mut gas_available = block_gas_limit;
-    // let transactions_trie: Trie<Bytes, Option<Either<LegacyTransaction, Bytes>>> =
-    //     Trie::new(false, None);
-    // let receipts_trie: Trie<Bytes, Option<Either<Receipt, Bytes>>> = Trie::new(false, None);
-    // let withdrawals_trie: Trie<Bytes, Option<Either<Withdrawal, Bytes>>> = Trie::new(false, None);
-
-    // let mut block_logs = Vec::new();
-
-    // let beacon_block_roots_contract_code = get_account(state, &BEACON_ROOTS_ADDRESS).code;
-
-    // let system_tx_message = vm::Message {
-    //     caller: SYSTEM_ADDRESS,
-    //     target: Either::B(BEACON_ROOTS_ADDRESS),
-    //     gas: SYSTEM_TRANSACTION_GAS,
-    //     value: U256::from(0_u32),
-    //     data: Bytes::from(parent_beacon_block_root.as_ref()),
-    //     code: beacon_block_roots_contract_code,
-    //     depth: Uint::from(0_u32),
-    //     current_target: BEACON_ROOTS_ADDRESS,
-    //     code_address: Some(BEACON_ROOTS_ADDRESS),
-    //     should_transfer_value: false,
-    //     is_static: false,
-    //     accessed_addresses: BTreeSet::new(),
-    //     accessed_storage_keys: BTreeSet::new(),
-    //     parent_evm: None,
-    // };
-
-    // let mut system_tx_env = vm::Environment {
-    //     caller: SYSTEM_ADDRESS,
-    //     origin: SYSTEM_ADDRESS,
-    //     block_hashes: block_hashes.to_vec(),
-    //     coinbase: coinbase,
-    //     number: block_number,
-    //     gas_limit: block_gas_limit,
-    //     base_fee_per_gas: base_fee_per_gas,
-    //     gas_price: base_fee_per_gas,
-    //     time: block_time,
-    //     prev_randao: prev_randao,
-    //     state: state,
-    //     chain_id: chain_id,
-    //     traces: Vec::new(),
-    //     excess_blob_gas: excess_blob_gas,
-    //     blob_versioned_hashes: Vec::new(),
-    //     transient_storage: TransientStorage::default(),
-    // };
-
-    // let system_tx_output = process_message_call(&system_tx_message, &mut system_tx_env)?;
-
-    // destroy_touched_empty_accounts(system_tx_env.state, system_tx_output.touched_accounts);
-
-    // for (i, tx) in transactions.iter().map(decode_transaction).enumerate() {
-    //     trie_set(
-    //         transactions_trie,
-    //         rlp.encode(Uint::from(i)),
-    //         encode_transaction(tx),
-    //     );
-
-    //     let (sender_address, effective_gas_price, blob_versioned_hashes) = check_transaction(
-    //         state,
-    //         tx,
-    //         gas_available,
-    //         chain_id,
-    //         base_fee_per_gas,
-    //         excess_blob_gas,
-    //     );
-
-    //     let env = vm::Environment {
-    //         caller: sender_address,
-    //         origin: sender_address,
-    //         block_hashes: block_hashes,
-    //         coinbase: coinbase,
-    //         number: block_number,
-    //         gas_limit: block_gas_limit,
-    //         base_fee_per_gas: base_fee_per_gas,
-    //         gas_price: effective_gas_price,
-    //         time: block_time,
-    //         prev_randao: prev_randao,
-    //         state: state,
-    //         chain_id: chain_id,
-    //         traces: Vec::new(),
-    //         excess_blob_gas: excess_blob_gas,
-    //         blob_versioned_hashes: blob_versioned_hashes,
-    //         transient_storage: TransientStorage::default(),
-    //     };
-
-    //     let (gas_used, logs, error) = process_transaction(&env, tx)?;
-    //     gas_available -= gas_used;
-
-    //     let receipt = make_receipt(tx, error, (block_gas_limit - gas_available), logs);
-
-    //     trie_set(receipts_trie, rlp::encode(&Uint::from(i)), receipt);
-
-    //     block_logs += logs;
-    //     blob_gas_used += calculate_total_blob_gas(tx);
-    // }
+    let env = BlockEnvironment {
+        block_hashes: block_hashes.to_vec(),
+        coinbase: coinbase.clone(),
+        number: *block_number,
+        base_fee_per_gas: *base_fee_per_gas,
+        gas_limit: *block_gas_limit,
+        time: *block_time,
+        prev_randao: prev_randao.clone(),
+        chain_id,
+        parent_beacon_block_root: parent_beacon_block_root.clone(),
+        excess_blob_gas: *excess_blob_gas,
+        rules,
+        chain_spec: chain_spec.clone(),
+        code_cache,
+    };
+    let mut execution = BlockExecution::begin(state, &env)?;
 
-    // if blob_gas_used > MAX_BLOB_GAS_PER_BLOCK {
-    //     return Err(Exception::InvalidBlock(
-    //         "blob_gas_used > MAX_BLOB_GAS_PER_BLOCK"
-    //     ));
-    // }
-    // let block_gas_used = block_gas_limit - gas_available;
+    // The checks which do not depend on state run on all the transactions
+    // at once, before any of them executes.
+    let prevalidated = BlockPrevalidator::default().prevalidate(transactions, chain_id, rules)?;
+    for (tx, prevalidated) in transactions.iter().zip(prevalidated) {
+        execution.apply_transaction(state, &env, tx, prevalidated.sender)?;
+    }
 
-    // let block_logs_bloom = logs_bloom(block_logs);
+    execution.finish(state, &env, ommers, block_reward, withdrawals)
+}
 
-    // for (i, wd) in withdrawals.iter().enumerate() {
-    //     trie_set(withdrawals_trie, rlp.encode(Uint(i)), rlp.encode(wd));
+/// The fields of a block which its body executes in.
+#[derive(Debug, Clone)]
+pub struct BlockEnvironment {
+    /// Hashes of the previous 256 blocks in the order of increasing block
+    /// number.
+    pub block_hashes: Vec<Hash32>,
+    pub coinbase: Address,
+    pub number: Uint,
+    pub base_fee_per_gas: Option<Uint>,
+    pub gas_limit: Uint,
+    pub time: U256,
+    pub prev_randao: Bytes32,
+    pub chain_id: U64,
+    pub parent_beacon_block_root: Option<Root>,
+    pub excess_blob_gas: Option<U64>,
+    pub rules: ExecutionRules,
+    pub chain_spec: ChainSpec,
+    /// Shares the valid jump destinations of code between the transactions,
+    /// or `None` to find them on every call.
+    pub code_cache: Option<Arc<CodeCache<BTreeSet<Uint>>>>,
+}
 
-    //     process_withdrawal(state, wd);
+/// A block body being applied one transaction at a time.
+///
+/// `apply_body` applies every transaction of a block and fails if one is
+/// invalid. Tools which assemble blocks, such as the transition tool, use
+/// the steps directly to leave out the transactions `apply_transaction`
+/// rejects: a rejected transaction leaves the state and the execution as
+/// they were.
+pub struct BlockExecution {
+    gas_available: Uint,
+    blob_gas_used: Uint,
+    transactions_trie: Trie<Bytes, Bytes>,
+    receipts_trie: Trie<Bytes, Bytes>,
+    block_logs: Vec<Log>,
+    receipts: Vec<TransactionReceipt>,
+}
 
-    //     if account_exists_and_is_empty(state, wd.address) {
-    //         destroy_account(state, wd.address);
-    //     }
-    // }
+impl BlockExecution {
+    /// Start the block, making the system calls which precede its
+    /// transactions.
+    pub fn begin(state: &mut State, env: &BlockEnvironment) -> Result<Self, Exception> {
+        // Blocks before Cancun have no beacon root to store.
+        if let Some(parent_beacon_block_root) = env.parent_beacon_block_root.as_ref().filter(|_| env.rules.beacon_roots) {
+            let mut system_tx_env = system_environment(state, env);
+            process_system_transaction(
+                &mut system_tx_env, env.chain_spec.beacon_roots_address.clone(), Bytes::from(&parent_beacon_block_root[..]),
+            )?;
+        }
+        if let Some(parent_hash) = env.block_hashes.last().filter(|_| env.rules.block_hash_history) {
+            let mut system_tx_env = system_environment(state, env);
+            process_system_transaction(&mut system_tx_env, HISTORY_STORAGE_ADDRESS, Bytes::from(&parent_hash[..]))?;
+        }
+        Ok(Self {
+            gas_available: env.gas_limit,
+            blob_gas_used: 0,
+            transactions_trie: Trie::new(false, Bytes::default()),
+            receipts_trie: Trie::new(false, Bytes::default()),
+            block_logs: Vec::new(),
+            receipts: Vec::new(),
+        })
+    }
+
+    /// Gas left for further transactions.
+    pub fn gas_available(&self) -> Uint {
+        self.gas_available
+    }
+
+    /// Receipts of the transactions applied so far.
+    pub fn receipts(&self) -> &[TransactionReceipt] {
+        &self.receipts
+    }
+
+    /// Execute `tx` from `sender`, which `prevalidate_transaction` recovered,
+    /// and add it to the block.
+    pub fn apply_transaction(
+        &mut self,
+        state: &mut State,
+        env: &BlockEnvironment,
+        tx: &Transaction,
+        sender: Address,
+    ) -> Result<(), Exception> {
+        let _span = log::span(Level::Trace, "transaction", format_args!("index={}", self.receipts.len()));
+        let executed = execute_transaction(state, env, tx, sender, self.gas_available, self.blob_gas_used)?;
+        self.record_transaction(state, env, tx, executed)
+    }
+
+    /// Add `tx`, which `execute_transaction` ran against `state`, to the
+    /// block.
+    pub(crate) fn record_transaction(
+        &mut self,
+        state: &State,
+        env: &BlockEnvironment,
+        tx: &Transaction,
+        executed: ExecutedTransaction,
+    ) -> Result<(), Exception> {
+        let ExecutedTransaction { from, contract_address, effective_gas_price, gas_used, logs, error } = executed;
+        let excess_blob_gas = env.excess_blob_gas.unwrap_or_default();
+        let tx_blob_gas = calculate_total_blob_gas(tx);
+
+        let index = rlp::encode(&(self.receipts.len() as Uint))?;
+        let encoded_tx = encode_trie_value(encode_transaction(tx)?)?;
+        let transaction_hash = keccak256(&encoded_tx);
+        let transaction_type = if encoded_tx.first().is_some_and(|b| *b < 0x80) { encoded_tx[0] } else { 0 };
+        trace!("{transaction_hash} used {gas_used} gas, error {error:?}");
+
+        self.gas_available -= gas_used;
+
+        let cumulative_gas_used = env.gas_limit - self.gas_available;
+        let receipt = if env.rules.byzantium {
+            encode_trie_value(make_receipt(tx, &error, cumulative_gas_used, &logs)?)?
+        } else {
+            let receipt = PostStateReceipt {
+                post_state: state_root(state)?,
+                cumulative_gas_used,
+                bloom: logs_bloom(&logs),
+                logs: logs.clone(),
+            };
+            Bytes(rlp::encode(&receipt)?.0)
+        };
+
+        self.transactions_trie.set(index.clone(), encoded_tx);
+        self.receipts_trie.set(index, receipt);
+
+        self.receipts.push(TransactionReceipt {
+            receipt: Receipt {
+                succeeded: error.is_none(),
+                cumulative_gas_used,
+                bloom: logs_bloom(&logs),
+                logs: logs.clone(),
+            },
+            transaction_hash,
+            transaction_type,
+            from,
+            to: tx.to(),
+            contract_address,
+            gas_used,
+            effective_gas_price,
+            blob_gas: matches!(tx, Transaction::BlobTransaction(_))
+                .then(|| (tx_blob_gas, calculate_blob_gas_price(excess_blob_gas))),
+        });
+
+        self.block_logs.extend(logs);
+        self.blob_gas_used += tx_blob_gas;
+        Ok(())
+    }
 
-    // return Ok(ApplyBodyOutput {
-    //     block_gas_used,
-    //     transactions_root: transactions_trie.root(),
-    //     receipt_root: receipts_trie.root(),
-    //     block_logs_bloom,
-    //     state_root: state.state_root(),
-    //     withdrawals_root: withdrawals_trie.root(),
-    //     blob_gas_used,
-    // });
-
-    todo!()
+    /// End the block: pay the rewards of a proof of work block, process the
+    /// withdrawals and collect the requests.
+    pub fn finish(
+        self,
+        state: &mut State,
+        env: &BlockEnvironment,
+        ommers: &[Header],
+        block_reward: Option<Uint>,
+        withdrawals: Option<&[Withdrawal]>,
+    ) -> Result<ApplyBodyOutput, Exception> {
+        let block_gas_used = env.gas_limit - self.gas_available;
+        let block_logs_bloom = logs_bloom(&self.block_logs);
+
+        if let Some(block_reward) = block_reward {
+            pay_rewards(state, block_reward, env.number, &env.coinbase, ommers);
+        }
+
+        let mut withdrawals_trie: Trie<Bytes, Bytes> = Trie::new(false, Bytes::default());
+        for (i, wd) in withdrawals.unwrap_or_default().iter().enumerate() {
+            withdrawals_trie.set(rlp::encode(&(i as Uint))?, rlp::encode(wd)?);
+
+            process_withdrawal(state, wd);
+
+            if account_exists_and_is_empty(state, &wd.address) {
+                destroy_account(state, &wd.address);
+            }
+        }
+
+        let requests_hash = if env.rules.requests {
+            let mut requests = Vec::new();
+            let deposit_requests = parse_deposit_requests(&self.block_logs)?;
+            if !deposit_requests.is_empty() {
+                requests.push(Bytes([&[DEPOSIT_REQUEST_TYPE][..], &deposit_requests].concat()));
+            }
+            let mut system_tx_env = system_environment(state, env);
+            let request_contracts = [
+                (WITHDRAWAL_REQUEST_TYPE, WITHDRAWAL_REQUEST_PREDEPLOY_ADDRESS),
+                (CONSOLIDATION_REQUEST_TYPE, CONSOLIDATION_REQUEST_PREDEPLOY_ADDRESS),
+            ];
+            for (request_type, address) in request_contracts {
+                let output = process_checked_system_transaction(&mut system_tx_env, address, Bytes::default())?;
+                if !output.return_data.is_empty() {
+                    requests.push(Bytes([&[request_type][..], &output.return_data].concat()));
+                }
+            }
+            Some(compute_requests_hash(&requests))
+        } else {
+            None
+        };
+
+        Ok(ApplyBodyOutput {
+            block_gas_used,
+            transactions_root: self.transactions_trie.root()?,
+            receipt_root: self.receipts_trie.root()?,
+            block_logs_bloom,
+            state_root: state_root(state)?,
+            withdrawals_root: withdrawals.map(|_| withdrawals_trie.root()).transpose()?,
+            blob_gas_used: env.excess_blob_gas.map(|_| self.blob_gas_used as U64),
+            receipts: self.receipts,
+            requests_hash,
+        })
+    }
+}
+
+/// What executing a transaction produced, before it is added to a block.
+pub(crate) struct ExecutedTransaction {
+    pub(crate) from: Address,
+    pub(crate) contract_address: Option<Address>,
+    pub(crate) effective_gas_price: Uint,
+    pub(crate) gas_used: Uint,
+    pub(crate) logs: Vec<Log>,
+    pub(crate) error: Option<VmError>,
+}
+
+/// Check `tx` against `state` and the gas left in the block, then execute
+/// it.
+pub(crate) fn execute_transaction(
+    state: &mut State,
+    env: &BlockEnvironment,
+    tx: &Transaction,
+    sender: Address,
+    gas_available: Uint,
+    blob_gas_used: Uint,
+) -> Result<ExecutedTransaction, Exception> {
+    let base_fee_per_gas = env.base_fee_per_gas.unwrap_or_default();
+    let excess_blob_gas = env.excess_blob_gas.unwrap_or_default();
+    let (sender_address, effective_gas_price, blob_versioned_hashes) =
+        check_transaction(state, tx, sender, gas_available, base_fee_per_gas, excess_blob_gas)?;
+    if blob_gas_used + calculate_total_blob_gas(tx) > env.chain_spec.max_blob_gas_per_block {
+        return Err(Exception::InvalidBlock(
+            "blob_gas_used > MAX_BLOB_GAS_PER_BLOCK"
+        ));
+    }
+
+    let contract_address = match tx.to() {
+        Some(_) => None,
+        None => Some(compute_contract_address(&sender_address, get_account(state, &sender_address).nonce)?),
+    };
+    let from = sender_address.clone();
+
+    let mut tx_env = vm::Environment {
+        caller: sender_address.clone(),
+        origin: sender_address,
+        block_hashes: env.block_hashes.clone(),
+        coinbase: env.coinbase.clone(),
+        number: env.number,
+        gas_limit: env.gas_limit,
+        base_fee_per_gas,
+        gas_price: effective_gas_price,
+        time: env.time,
+        prev_randao: env.prev_randao.clone(),
+        chain_id: env.chain_id,
+        excess_blob_gas,
+        blob_versioned_hashes,
+        code_cache: env.code_cache.as_deref(),
+        ..vm::Environment::new(&mut *state, env.rules)
+    };
+
+    let (gas_used, logs, error) = process_transaction(&mut tx_env, tx)?;
+    Ok(ExecutedTransaction { from, contract_address, effective_gas_price, gas_used, logs, error })
+}
+
+/// The environment of the system calls made by `apply_body`, with the
+/// fields of the block being applied.
+fn system_environment<'s>(state: &'s mut State, env: &'s BlockEnvironment) -> vm::Environment<'s> {
+    let base_fee_per_gas = env.base_fee_per_gas.unwrap_or_default();
+    vm::Environment {
+        caller: env.chain_spec.system_address.clone(),
+        origin: env.chain_spec.system_address.clone(),
+        block_hashes: env.block_hashes.clone(),
+        coinbase: env.coinbase.clone(),
+        number: env.number,
+        gas_limit: env.gas_limit,
+        base_fee_per_gas,
+        gas_price: base_fee_per_gas,
+        time: env.time,
+        prev_randao: env.prev_randao.clone(),
+        chain_id: env.chain_id,
+        excess_blob_gas: env.excess_blob_gas.unwrap_or_default(),
+        code_cache: env.code_cache.as_deref(),
+        ..vm::Environment::new(state, env.rules)
+    }
+}
+
+/// Call the contract at `target` with `data` from the system address, the
+/// caller of `env`, outside of any transaction. Nothing happens if the
+/// contract has no code, as before it is deployed on a new chain.
+///
+/// Parameters
+/// ----------
+/// env :
+///     The environment of the system call.
+/// target :
+///     Address of the contract to call.
+/// data :
+///     Data to pass to the contract.
+///
+/// Returns
+/// -------
+/// system_tx_output : `MessageCallOutput`
+///     Output of processing the system transaction.
+fn process_system_transaction(
+    env: &mut vm::Environment, target: Address, data: Bytes
+) -> Result<MessageCallOutput, Exception> {
+    let code = get_account(env.state, &target).code.clone();
+    if code.is_empty() {
+        return Ok(MessageCallOutput {
+            gas_left: 0,
+            refund_counter: U256::ZERO,
+            logs: Vec::new(),
+            accounts_to_delete: BTreeSet::new(),
+            touched_accounts: BTreeSet::new(),
+            error: None,
+            return_data: Bytes::default(),
+        });
+    }
+    let system_tx_message = vm::Message {
+        caller: env.caller.clone(),
+        target: Some(target.clone()),
+        gas: SYSTEM_TRANSACTION_GAS,
+        value: U256::ZERO,
+        data,
+        code,
+        depth: 0,
+        current_target: target.clone(),
+        code_address: Some(target),
+        should_transfer_value: false,
+        is_static: false,
+        accessed_addresses: BTreeSet::new(),
+        accessed_storage_keys: BTreeSet::new(),
+        accounts_to_delete: BTreeSet::new(),
+        disable_precompiles: false,
+    };
+
+    let system_tx_output = process_message_call(system_tx_message, env)?;
+
+    destroy_touched_empty_accounts(env.state, &system_tx_output.touched_accounts);
+    Ok(system_tx_output)
+}
+
+/// Process a system transaction the block is invalid without: the contract
+/// must have code and the call must succeed.
+///
+/// Parameters
+/// ----------
+/// env :
+///     The environment of the system call.
+/// target :
+///     Address of the contract to call.
+/// data :
+///     Data to pass to the contract.
+///
+/// Returns
+/// -------
+/// system_tx_output : `MessageCallOutput`
+///     Output of processing the system transaction.
+fn process_checked_system_transaction(
+    env: &mut vm::Environment, target: Address, data: Bytes
+) -> Result<MessageCallOutput, Exception> {
+    if get_account(env.state, &target).code.is_empty() {
+        return Err(Exception::InvalidBlock("system contract has no code"));
+    }
+    let system_tx_output = process_system_transaction(env, target, data)?;
+    if system_tx_output.error.is_some() {
+        return Err(Exception::InvalidBlock("system contract call failed"));
+    }
+    Ok(system_tx_output)
+}
+
+/// The value stored in a trie for a transaction or receipt, which is the
+/// RLP encoding of a legacy item or the bytes of a typed one.
+pub(crate) fn encode_trie_value<T : Extended + std::fmt::Debug + Clone>(value: Either<T, Bytes>) -> Result<Bytes, Exception> {
+    match value {
+        Either::A(value) => Ok(Bytes(rlp::encode(&value)?.0)),
+        Either::B(bytes) => Ok(bytes),
+    }
 }
 
 
@@ -724,108 +1311,153 @@ pub fn apply_body(
 /// 
 /// Returns
 /// -------
-/// gas_left : `ethereum.base_types.U256`
-///     Remaining gas after execution.
+/// total_gas_used : `ethereum.base_types.Uint`
+///     Gas used by the transaction, net of refunds.
 /// logs : `Tuple[ethereum.blocks.Log, ...]`
 ///     Logs generated during execution.
 /// """
 pub fn process_transaction(
-    env: &vm::Environment, tx: &Transaction
-) -> (Uint, Vec<Log>, Option<VmError>) {
-    // if !validate_transaction(tx) {
-    //     return Err(Exception::InvalidBlock(
-    //         "!validate_transaction(tx)"
-    //     ));
-    // }
+    env: &mut vm::Environment, tx: &Transaction
+) -> Result<(Uint, Vec<Log>, Option<VmError>), Exception> {
+    let output = process_transaction_output(env, tx)?;
+    Ok((output.gas_used, output.logs, output.error))
+}
 
-    // let sender = env.origin;
-    // let sender_account = get_account(env.state, &sender);
-
-    // let blob_gas_fee = if let Transaction::BlobTransaction(tx) = tx {
-    //     calculate_data_fee(env.excess_blob_gas, tx)
-    // } else {
-    //     Uint::from(0_u32)
-    // };
-
-    // let effective_gas_fee = tx.gas * env.gas_price;
-
-    // let gas = tx.gas - calculate_intrinsic_cost(tx);
-    // increment_nonce(env.state, sender);
-
-    // let sender_balance_after_gas_fee = (
-    //     Uint(sender_account.balance) - effective_gas_fee - blob_gas_fee
-    // );
-    // set_account_balance(env.state, sender, U256(sender_balance_after_gas_fee));
-
-    // let mut preaccessed_addresses = HashSet::new();
-    // let mut preaccessed_storage_keys = HashSet::new();
-    // preaccessed_addresses.insert(env.coinbase);
-
-    // match tx {
-    //     Transaction::AccessListTransaction(AccessListTransaction{access_list, ..}) |
-    //     Transaction::FeeMarketTransaction(FeeMarketTransaction{access_list, ..}) |
-    //     Transaction::BlobTransaction(BlobTransaction{access_list, ..}) => {
-    //         for (address, keys) in access_list {
-    //             preaccessed_addresses.insert(address.clone());
-    //             for key in keys {
-    //                 preaccessed_storage_keys.insert((address, key));
-    //             }
-    //         }
-    //     }
-    //     _ => (),
-    // }
+/// What `process_transaction` reports of a transaction, with the gas
+/// refunded and the output of its message.
+#[derive(Debug)]
+pub(crate) struct TransactionOutput {
+    pub(crate) gas_used: Uint,
+    pub(crate) gas_refund: Uint,
+    pub(crate) logs: Vec<Log>,
+    pub(crate) error: Option<VmError>,
+    pub(crate) return_data: Bytes,
+}
 
-    // let message = prepare_message(
-    //     sender,
-    //     tx.to,
-    //     tx.value,
-    //     tx.data,
-    //     gas,
-    //     env,
-    //     preaccessed_addresses=frozenset(preaccessed_addresses),
-    //     preaccessed_storage_keys=frozenset(preaccessed_storage_keys),
-    // );
-
-    // let output = process_message_call(message, env);
-
-    // let gas_used = tx.gas - output.gas_left;
-    // let gas_refund = min(gas_used / Uint::from(5_u32), Uint::from(output.refund_counter));
-    // let gas_refund_amount = (output.gas_left + gas_refund) * env.gas_price;
-
-    // //  For non-1559 transactions env.gas_price == tx.gas_price
-    // let priority_fee_per_gas = env.gas_price - env.base_fee_per_gas;
-    // transaction_fee = (
-    //     tx.gas - output.gas_left - gas_refund
-    // ) * priority_fee_per_gas;
-
-    // let total_gas_used = gas_used - gas_refund;
-
-    // // refund gas
-    // let sender_balance_after_refund = get_account(
-    //     env.state, &sender
-    // ).balance + U256::from(gas_refund_amount);
-    // set_account_balance(env.state, sender, sender_balance_after_refund);
-
-    // // transfer miner fees
-    // coinbase_balance_after_mining_fee = get_account(
-    //     env.state, &env.coinbase
-    // ).balance + U256::from(transaction_fee);
-    // if coinbase_balance_after_mining_fee != 0 {
-    //     set_account_balance(
-    //         env.state, env.coinbase, coinbase_balance_after_mining_fee
-    //     )
-    // } else if account_exists_and_is_empty(env.state, env.coinbase) {
-    //     destroy_account(env.state, env.coinbase);
-    // }
+/// `process_transaction`, returning all of its `TransactionOutput`.
+pub(crate) fn process_transaction_output(
+    env: &mut vm::Environment, tx: &Transaction
+) -> Result<TransactionOutput, Exception> {
+    let rules = env.rules;
+    if !validate_transaction(tx, rules) {
+        return Err(Exception::InvalidBlock(
+            "!validate_transaction(tx)"
+        ));
+    }
 
-    // for address in output.accounts_to_delete {
-    //     destroy_account(env.state, address);
-    // }
+    let sender = env.origin.clone();
+    let sender_account = get_account(env.state, &sender).into_owned();
+
+    let blob_gas_fee = if let Transaction::BlobTransaction(_) = tx {
+        calculate_data_fee(env.excess_blob_gas, tx)
+    } else {
+        0
+    };
+
+    let effective_gas_fee = U256::from_uint(*tx.gas()) * U256::from_uint(env.gas_price);
+
+    let gas = tx.gas() - calculate_intrinsic_cost(tx, rules);
+    increment_nonce(env.state, &sender);
+
+    let sender_balance_after_gas_fee =
+        sender_account.balance - effective_gas_fee - U256::from_uint(blob_gas_fee);
+    set_account_balance(env.state, &sender, sender_balance_after_gas_fee);
+
+    let mut preaccessed_addresses = BTreeSet::new();
+    let mut preaccessed_storage_keys = BTreeSet::new();
+    if rules.shanghai {
+        preaccessed_addresses.insert(env.coinbase.clone());
+    }
+    if let Some(access_list) = tx.access_list() {
+        for (address, keys) in access_list {
+            preaccessed_addresses.insert(address.clone());
+            for key in keys {
+                preaccessed_storage_keys.insert((address.clone(), key.clone()));
+            }
+        }
+    }
+
+    let mut message = prepare_message(
+        sender.clone(),
+        tx.to(),
+        *tx.value(),
+        Bytes::from(tx.data()),
+        gas,
+        env,
+        None,
+        true,
+        false,
+        preaccessed_addresses,
+        preaccessed_storage_keys,
+    )?;
 
-    // destroy_touched_empty_accounts(env.state, output.touched_accounts);
+    // Delegations are set outside of the message, and are kept if it fails.
+    let authorization_refund = match tx {
+        Transaction::SetCodeTransaction(tx) => set_delegation(env, &mut message, &tx.authorizations)?,
+        _ => U256::ZERO,
+    };
+
+    if let Some(tracer) = &mut env.tracer {
+        tracer.enter(&CallStart {
+            kind: if message.target.is_none() { Ops::CREATE } else { Ops::CALL },
+            from: &sender,
+            to: &message.current_target,
+            value: message.value,
+            gas: *tx.gas(),
+            input: &Bytes::from(tx.data()),
+        });
+    }
+    let output = process_message_call(message, env)?;
+    let refund_counter = output.refund_counter + authorization_refund;
+
+    let gas_used = tx.gas() - output.gas_left;
+    let gas_refund = U256::from_uint(gas_used / rules.max_refund_quotient()).min(refund_counter).to_uint()?;
+    let gas_refund_amount = U256::from_uint(output.gas_left + gas_refund) * U256::from_uint(env.gas_price);
 
-    // (total_gas_used, output.logs, output.error)
-    todo!()
+    // For non-1559 transactions env.gas_price == tx.gas_price
+    let priority_fee_per_gas = env.gas_price - env.base_fee_per_gas;
+    let transaction_fee = U256::from_uint(tx.gas() - output.gas_left - gas_refund)
+        * U256::from_uint(priority_fee_per_gas);
+
+    let total_gas_used = gas_used - gas_refund;
+    if let Some(tracer) = &mut env.tracer {
+        tracer.exit(&output.return_data, total_gas_used, output.error.as_ref());
+    }
+
+    // refund gas
+    let sender_balance_after_refund = get_account(env.state, &sender).balance + gas_refund_amount;
+    set_account_balance(env.state, &sender, sender_balance_after_refund);
+
+    // transfer miner fees
+    // The fee is not recorded as an access, so that transactions which run
+    // in parallel do not all conflict on the coinbase.
+    let coinbase = env.coinbase.clone();
+    env.state.without_access_log(&mut |state| {
+        let coinbase_balance_after_mining_fee =
+            get_account(state, &coinbase).balance + transaction_fee;
+        if !coinbase_balance_after_mining_fee.is_zero() || !rules.spurious_dragon {
+            set_account_balance(state, &coinbase, coinbase_balance_after_mining_fee);
+        } else if account_exists_and_is_empty(state, &coinbase) {
+            destroy_account(state, &coinbase);
+        }
+    });
+
+    for address in &output.accounts_to_delete {
+        destroy_account(env.state, address);
+    }
+
+    // Empty accounts are only removed from Spurious Dragon (EIP-161).
+    if rules.spurious_dragon {
+        destroy_touched_empty_accounts(env.state, &output.touched_accounts);
+    }
+
+    Ok(TransactionOutput {
+        gas_used: total_gas_used,
+        gas_refund,
+        logs: output.logs,
+        error: output.error,
+        return_data: output.return_data,
+    })
 }
 
 /// """
@@ -859,20 +1491,20 @@ pub fn process_transaction(
 /// hash : `ethereum.crypto.hash.Hash32`
 ///     Hash of the header.
 /// """
-fn compute_header_hash(header: &Header) -> Result<Hash32, Exception> {
+pub fn compute_header_hash(header: &Header) -> Result<Hash32, Exception> {
     Ok(keccak256(&rlp::encode(header)?))
 }
 
 /// Validates the gas limit for a block.
 /// 
 /// The bounds of the gas limit, ``max_adjustment_delta``, is set as the
-/// quotient of the parent block's gas limit and the
-/// ``GAS_LIMIT_ADJUSTMENT_FACTOR``. Therefore, if the gas limit that is
+/// quotient of the parent block's gas limit and the chain's
+/// ``gas_limit_adjustment_factor``. Therefore, if the gas limit that is
 /// passed through as a parameter is greater than or equal to the *sum* of
 /// the parent's gas and the adjustment delta then the limit for gas is too
 /// high and fails this function's check. Similarly, if the limit is less
 /// than or equal to the *difference* of the parent's gas and the adjustment
-/// delta *or* the predefined ``GAS_LIMIT_MINIMUM`` then this function's
+/// delta *or* the chain's ``gas_limit_minimum`` then this function's
 /// check fails because the gas limit doesn't allow for a sufficient or
 /// reasonable amount of gas to be used on a block.
 /// 
@@ -884,12 +1516,15 @@ fn compute_header_hash(header: &Header) -> Result<Hash32, Exception> {
 /// parent_gas_limit :
 ///     Gas limit of the parent block.
 /// 
+/// chain_spec :
+///     The gas limit parameters of the chain.
+/// 
 /// Returns
 /// -------
 /// check : `bool`
 ///     True if gas limit constraints are satisfied, False otherwise.
-pub fn check_gas_limit(gas_limit: Uint, parent_gas_limit: Uint) -> bool {
-    let max_adjustment_delta = parent_gas_limit / GAS_LIMIT_ADJUSTMENT_FACTOR;
+pub fn check_gas_limit(gas_limit: Uint, parent_gas_limit: Uint, chain_spec: &ChainSpec) -> bool {
+    let max_adjustment_delta = parent_gas_limit / chain_spec.gas_limit_adjustment_factor;
 
     if gas_limit >= parent_gas_limit + max_adjustment_delta {
         return false;
@@ -899,7 +1534,7 @@ pub fn check_gas_limit(gas_limit: Uint, parent_gas_limit: Uint) -> bool {
         return false;
     }
 
-    if gas_limit < GAS_LIMIT_MINIMUM {
+    if gas_limit < chain_spec.gas_limit_minimum {
         return false;
     }
 
@@ -907,5 +1542,22 @@ pub fn check_gas_limit(gas_limit: Uint, parent_gas_limit: Uint) -> bool {
 }
 
 
+pub mod archive;
+pub mod call;
+pub mod fuzz;
+pub mod headers;
+pub mod logs;
+#[cfg(feature = "parallel")]
+pub mod parallel;
+pub mod prevalidate;
+pub mod proof_of_work;
+pub mod receipts;
+mod reorg;
+pub mod replay;
+pub mod validation;
+pub mod withdrawals;
+
+pub use reorg::ForkChoiceState;
+
 #[cfg(test)]
 mod tests;
@@ -0,0 +1,269 @@
+//! Offline block archives: RLP dumps and era1 files.
+//!
+//! An RLP dump, as written by `geth export`, is the RLP of each block one
+//! after the other. An era1 file is an e2store file: a sequence of entries
+//! which are a two byte type, a four byte little endian length, two
+//! reserved bytes and the data. It holds up to 8192 consecutive blocks,
+//! each as its snappy framed header, body and receipts followed by the total
+//! difficulty of the chain up to it, then an accumulator over the block
+//! hashes and total difficulties, and an index of where each block starts.
+//!
+//! `BlockChain::import_blocks` reads either format and imports the blocks it
+//! does not have yet. The receipts, total difficulties and accumulator of an
+//! era1 file are not checked, since executing the blocks checks them
+//! against their headers.
+
+use std::{
+    io::{Read, Write},
+    ops::RangeBounds,
+};
+
+use crate::{
+    devp2p::{
+        eth::BlockBody,
+        snappy::{compress_framed, decompress_framed},
+    },
+    ethereum::{
+        cancun::blocks::{Block, Header},
+        crypto::hash::{sha256, Hash32},
+        ethereum_rlp::rlp::{self, encode_bytes, encode_joined_encodings, Extended},
+        ethereum_types::{
+            bytes::Bytes,
+            numeric::{Uint, U256},
+        },
+        exceptions::Exception,
+    },
+};
+
+use super::{receipts::TransactionReceipt, BlockChain};
+
+/// Most blocks in one era1 file.
+pub const ERA1_MAX_BLOCKS: usize = 8192;
+
+const TYPE_VERSION: [u8; 2] = [0x65, 0x32];
+const TYPE_COMPRESSED_HEADER: [u8; 2] = [0x03, 0x00];
+const TYPE_COMPRESSED_BODY: [u8; 2] = [0x04, 0x00];
+const TYPE_COMPRESSED_RECEIPTS: [u8; 2] = [0x05, 0x00];
+const TYPE_TOTAL_DIFFICULTY: [u8; 2] = [0x06, 0x00];
+const TYPE_ACCUMULATOR: [u8; 2] = [0x07, 0x00];
+const TYPE_BLOCK_INDEX: [u8; 2] = [0x66, 0x32];
+
+/// Length of the header of an e2store entry.
+const ENTRY_HEADER_LEN: usize = 8;
+
+const WRITE_ERROR: Exception = Exception::EthereumException("cannot write block archive");
+const CORRUPT: Exception = Exception::EthereumException("corrupt era1 file");
+
+impl BlockChain {
+    /// Write the canonical blocks with numbers in `numbers` to `writer` as
+    /// an RLP dump. Returns the number of blocks written.
+    pub fn export_blocks(&self, numbers: impl RangeBounds<Uint>, mut writer: impl Write) -> Result<usize, Exception> {
+        let mut count = 0;
+        for block in self.blocks.iter().filter(|b| numbers.contains(&b.header.number)) {
+            writer.write_all(&rlp::encode(block)?).map_err(|_| WRITE_ERROR)?;
+            count += 1;
+        }
+        Ok(count)
+    }
+
+    /// Write the canonical blocks with numbers in `numbers`, at most
+    /// `ERA1_MAX_BLOCKS` of them, to `writer` as an era1 file. Returns the
+    /// number of blocks written.
+    pub fn export_era1(&self, numbers: impl RangeBounds<Uint>, mut writer: impl Write) -> Result<usize, Exception> {
+        let mut file = Vec::new();
+        write_entry(&mut file, TYPE_VERSION, &[]);
+
+        let mut total_difficulty = U256::ZERO;
+        let mut first_number = None;
+        let mut offsets = Vec::new();
+        let mut records = Vec::new();
+        for (i, block) in self.blocks.iter().enumerate() {
+            total_difficulty = total_difficulty + U256::from_uint(block.header.difficulty);
+            if !numbers.contains(&block.header.number) {
+                continue;
+            }
+            if offsets.len() == ERA1_MAX_BLOCKS {
+                return Err(Exception::EthereumException("too many blocks for one era1 file"));
+            }
+            first_number.get_or_insert(block.header.number);
+            offsets.push(file.len());
+
+            let body = BlockBody {
+                transactions: block.transactions.clone(),
+                ommers: block.ommers.clone(),
+                withdrawals: block.withdrawals.clone(),
+            };
+            write_entry(&mut file, TYPE_COMPRESSED_HEADER, &compress_framed(&rlp::encode(&block.header)?));
+            write_entry(&mut file, TYPE_COMPRESSED_BODY, &compress_framed(&rlp::encode(&body)?));
+            write_entry(&mut file, TYPE_COMPRESSED_RECEIPTS, &compress_framed(&encode_receipts(&self.receipts[i])?));
+            let mut difficulty = total_difficulty.to_be_bytes();
+            difficulty.reverse();
+            write_entry(&mut file, TYPE_TOTAL_DIFFICULTY, &difficulty);
+            records.push(header_record_root(&self.hashes[i], &difficulty));
+        }
+        write_entry(&mut file, TYPE_ACCUMULATOR, &accumulator_root(&records).0);
+
+        // Offsets are relative to the start of the index entry.
+        let index_start = file.len();
+        let mut index = Vec::with_capacity(16 + 8 * offsets.len());
+        index.extend(first_number.unwrap_or_default().to_le_bytes()[..8].iter());
+        for offset in &offsets {
+            index.extend((*offset as i64 - index_start as i64).to_le_bytes());
+        }
+        index.extend((offsets.len() as u64).to_le_bytes());
+        write_entry(&mut file, TYPE_BLOCK_INDEX, &index);
+
+        writer.write_all(&file).map_err(|_| WRITE_ERROR)?;
+        Ok(offsets.len())
+    }
+
+    /// Import the blocks of an RLP dump or an era1 file read from `reader`,
+    /// in order. Blocks which are already in the chain are skipped. Returns
+    /// the number of blocks read.
+    pub fn import_blocks(&mut self, mut reader: impl Read) -> Result<usize, Exception> {
+        let mut data = Vec::new();
+        reader.read_to_end(&mut data).map_err(|_| Exception::EthereumException("cannot read block archive"))?;
+        let blocks = if data.starts_with(&TYPE_VERSION) { read_era1(&data)? } else { read_rlp_dump(&data)? };
+        let count = blocks.len();
+        for block in blocks {
+            self.import_block(block)?;
+        }
+        Ok(count)
+    }
+}
+
+fn write_entry(file: &mut Vec<u8>, kind: [u8; 2], data: &[u8]) {
+    file.extend(kind);
+    file.extend((data.len() as u32).to_le_bytes());
+    file.extend([0, 0]);
+    file.extend(data);
+}
+
+/// The receipts of a block as they are encoded in its receipts trie, in an
+/// RLP list.
+fn encode_receipts(receipts: &[TransactionReceipt]) -> Result<Bytes, Exception> {
+    let mut joined = Bytes::default();
+    for r in receipts {
+        let encoded = rlp::encode(&r.receipt)?;
+        if r.transaction_type == 0 {
+            joined.0.extend(encoded.0);
+        } else {
+            encode_bytes(&mut joined, &[&[r.transaction_type][..], &encoded].concat());
+        }
+    }
+    let mut list = Bytes::default();
+    encode_joined_encodings(&mut list, joined);
+    Ok(list)
+}
+
+/// The SSZ hash tree root of a header record: a block hash and a total
+/// difficulty, the latter as 32 little endian bytes.
+fn header_record_root(block_hash: &Hash32, total_difficulty: &[u8; 32]) -> [u8; 32] {
+    sha256(&[&block_hash.0[..], total_difficulty].concat()).0
+}
+
+/// The SSZ hash tree root of the list of header records whose roots are
+/// `records`, with a limit of `ERA1_MAX_BLOCKS`.
+fn accumulator_root(records: &[[u8; 32]]) -> Hash32 {
+    let mut layer = records.to_vec();
+    let mut zero = [0; 32];
+    let mut width = ERA1_MAX_BLOCKS;
+    while width > 1 {
+        if layer.len() % 2 == 1 {
+            layer.push(zero);
+        }
+        layer = layer.chunks(2).map(|pair| sha256(&[pair[0], pair[1]].concat()).0).collect();
+        zero = sha256(&[zero, zero].concat()).0;
+        width /= 2;
+    }
+    let root = layer.first().copied().unwrap_or(zero);
+    let mut length = [0; 32];
+    length[..8].copy_from_slice(&(records.len() as u64).to_le_bytes());
+    sha256(&[root, length].concat())
+}
+
+fn read_rlp_dump(mut data: &[u8]) -> Result<Vec<Block>, Exception> {
+    let mut blocks = Vec::new();
+    while !data.is_empty() {
+        let mut block = Block::default();
+        block.decode(&mut data)?;
+        blocks.push(block);
+    }
+    Ok(blocks)
+}
+
+fn read_era1(mut data: &[u8]) -> Result<Vec<Block>, Exception> {
+    let mut blocks = Vec::new();
+    let mut header = None;
+    while !data.is_empty() {
+        let entry = data.get(..ENTRY_HEADER_LEN).ok_or(CORRUPT)?;
+        let kind = [entry[0], entry[1]];
+        let length = u32::from_le_bytes(entry[2..6].try_into().unwrap()) as usize;
+        let body = data.get(ENTRY_HEADER_LEN..ENTRY_HEADER_LEN + length).ok_or(CORRUPT)?;
+        data = &data[ENTRY_HEADER_LEN + length..];
+        match kind {
+            TYPE_COMPRESSED_HEADER => {
+                let decompressed = decompress_framed(body).map_err(|_| CORRUPT)?;
+                header = Some(rlp::decode_to_strict::<Header>(&decompressed)?);
+            }
+            TYPE_COMPRESSED_BODY => {
+                let decompressed = decompress_framed(body).map_err(|_| CORRUPT)?;
+                let body = rlp::decode_to_strict::<BlockBody>(&decompressed)?;
+                blocks.push(body.into_block(header.take().ok_or(CORRUPT)?));
+            }
+            _ => {}
+        }
+    }
+    Ok(blocks)
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::ethereum::{
+        cancun::fork::{compute_header_hash, fuzz::tests::valid_block},
+        crypto::hash::{sha256, Hash32},
+    };
+
+    use super::accumulator_root;
+
+    #[test]
+    fn round_trips() {
+        let (mut chain, block) = valid_block();
+        let (fresh, _) = valid_block();
+        let hash = compute_header_hash(&block.header).unwrap();
+        chain.import_block(block).unwrap();
+
+        let mut dump = Vec::new();
+        assert_eq!(chain.export_blocks(.., &mut dump).unwrap(), 2);
+        let mut era1 = Vec::new();
+        assert_eq!(chain.export_era1(1.., &mut era1).unwrap(), 1);
+        assert_eq!(&era1[..8], [0x65, 0x32, 0, 0, 0, 0, 0, 0]);
+        // The index starts at block 1 and counts one block.
+        assert_eq!(era1[era1.len() - 24..era1.len() - 16], [1, 0, 0, 0, 0, 0, 0, 0]);
+        assert_eq!(era1[era1.len() - 8..], [1, 0, 0, 0, 0, 0, 0, 0]);
+
+        for archive in [dump, era1] {
+            let mut imported = fresh.clone();
+            imported.import_blocks(&archive[..]).unwrap();
+            assert_eq!(imported.head_hash(), &hash);
+        }
+
+        let mut truncated = Vec::new();
+        chain.export_era1(1.., &mut truncated).unwrap();
+        truncated.truncate(40);
+        assert!(fresh.clone().import_blocks(&truncated[..]).is_err());
+    }
+
+    #[test]
+    fn accumulator_of_no_records() {
+        // The root of an empty list is that of a tree of zero leaves,
+        // mixed in with a length of zero.
+        let mut zero = [0; 32];
+        for _ in 0..13 {
+            zero = sha256(&[zero, zero].concat()).0;
+        }
+        let expected = sha256(&[zero, [0; 32]].concat());
+        assert_eq!(accumulator_root(&[]), expected);
+        assert_ne!(accumulator_root(&[[1; 32]]), Hash32::default());
+    }
+}
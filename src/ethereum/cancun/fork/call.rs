@@ -0,0 +1,586 @@
+//! Message calls against the head of the chain, as for `eth_call`.
+//!
+//! A call runs in the environment of the head block on a copy of its state,
+//! so nothing it does is kept. There is no transaction: the sender is not
+//! charged, its nonce is not checked and the gas price is the base fee. On a
+//! chain built `with_snapshots`, calls read the head state from its snapshot
+//! and keep their writes aside instead of copying it.
+//!
+//! `BlockChain::create_access_list` runs a call with the state recording
+//! what it accesses, as for `eth_createAccessList`. The accounts and storage
+//! slots it reads or writes, other than those warm at the start of any
+//! transaction, form an EIP-2930 access list. Warming them changes the gas
+//! the call uses, and with it the path it may take, so the call runs again
+//! with the list until the list stops growing.
+//!
+//! `estimate_gas` finds the lowest gas limit with which a transaction
+//! succeeds by bisection, running it each time as the block would. The gas a
+//! successful run uses is not enough in general: a call forwards at most
+//! 63/64 of the gas left to its callee, and gas refunded at the end still has
+//! to be available when it is spent.
+
+use std::collections::{BTreeMap, BTreeSet};
+
+use crate::ethereum::{
+    cancun::{
+        blocks::Header,
+        fork_types::Address,
+        state::{snapshot::SnapshotState, Access, AccessLog, StateBackend, TransientStorage},
+        transactions::{calculate_intrinsic_cost, Transaction},
+        utils::prepare_message,
+        vm::{code_cache::CodeCache, exceptions::VmError, interpreter::process_message_call, Environment},
+    },
+    crypto::hash::Hash32,
+    ethereum_types::{
+        bytes::{Bytes, Bytes32},
+        numeric::{Uint, U256, U64},
+    },
+    exceptions::Exception,
+    forks::ExecutionRules,
+};
+
+use super::BlockChain;
+
+/// Result of `BlockChain::call`.
+#[derive(Debug, Clone)]
+pub struct CallOutput {
+    pub gas_used: Uint,
+    /// Return data, or revert data if `error` is set.
+    pub output: Bytes,
+    /// Why the call failed, `None` if it succeeded.
+    pub error: Option<VmError>,
+}
+
+/// Result of `BlockChain::create_access_list`.
+#[derive(Debug, Clone)]
+pub struct AccessListOutput {
+    pub access_list: Vec<(Address, Vec<Bytes32>)>,
+    /// Gas used by the call with `access_list` warm, excluding the intrinsic
+    /// cost of the transaction.
+    pub gas_used: Uint,
+    pub error: Option<VmError>,
+}
+
+/// Most times a call runs to find its access list.
+const MAX_ACCESS_LIST_ROUNDS: usize = 8;
+
+impl BlockChain {
+    /// Run a message call from `caller` to `target` with `data` and `gas`
+    /// on top of the head state. A `target` of `None` creates a contract
+    /// from `data`.
+    pub fn call(&self, caller: Address, target: Option<Address>, data: Bytes, gas: Uint) -> Result<CallOutput, Exception> {
+        match self.head_snapshot() {
+            Some(view) => Ok(self.run_call(&mut SnapshotState::new(view), caller, target, data, gas, None)?.0),
+            None => Ok(self.call_with_access_list(caller, target, data, gas, None)?.0),
+        }
+    }
+
+    /// Find the access list of a message call like `call`'s, and the gas
+    /// the call uses with it.
+    pub fn create_access_list(
+        &self,
+        caller: Address,
+        target: Option<Address>,
+        data: Bytes,
+        gas: Uint,
+    ) -> Result<AccessListOutput, Exception> {
+        let mut access_list = Vec::new();
+        for _ in 0..MAX_ACCESS_LIST_ROUNDS {
+            let (output, found) = self.call_with_access_list(caller.clone(), target.clone(), data.clone(), gas, Some(&access_list))?;
+            if found == access_list {
+                return Ok(AccessListOutput { access_list, gas_used: output.gas_used, error: output.error });
+            }
+            access_list = found;
+        }
+        Err(Exception::EthereumException("access list did not converge"))
+    }
+
+    /// Run a call on a copy of the head state, returning its output and the
+    /// access list of what it accessed. With an `access_list`, the call
+    /// starts with it and the coinbase warm, as a transaction would.
+    fn call_with_access_list(
+        &self,
+        caller: Address,
+        target: Option<Address>,
+        data: Bytes,
+        gas: Uint,
+        access_list: Option<&[(Address, Vec<Bytes32>)]>,
+    ) -> Result<(CallOutput, Vec<(Address, Vec<Bytes32>)>), Exception> {
+        let mut state = self.state.clone();
+        state.start_access_log();
+        let (output, warm) = self.run_call(&mut state, caller, target, data, gas, access_list)?;
+        let log = state.take_access_log().unwrap_or_default();
+        Ok((output, access_list_of(&log, &warm)))
+    }
+
+    /// Run a call on `state`, returning its output and the addresses warm
+    /// in any transaction, which an access list leaves out.
+    fn run_call(
+        &self,
+        state: &mut dyn StateBackend,
+        caller: Address,
+        target: Option<Address>,
+        data: Bytes,
+        gas: Uint,
+        access_list: Option<&[(Address, Vec<Bytes32>)]>,
+    ) -> Result<(CallOutput, BTreeSet<Address>), Exception> {
+        let header = &self.blocks.last().unwrap().header;
+        let rules = self.fork_for(header)?.execution_rules();
+        let block_hashes = self.head_block_hashes();
+        let mut env = call_environment(state, header, &block_hashes, self.chain_spec.chain_id, &caller, rules);
+        env.code_cache = Some(&self.code_cache);
+        let coinbase = (access_list.is_some() && rules.shanghai).then_some(&header.coinbase);
+        let (preaccessed_addresses, preaccessed_storage_keys) = preaccessed(access_list, coinbase);
+        let message = prepare_message(
+            caller,
+            target,
+            U256::ZERO,
+            data,
+            gas,
+            &env,
+            None,
+            true,
+            false,
+            preaccessed_addresses,
+            preaccessed_storage_keys,
+        )?;
+        // Addresses warm in any transaction, which the list leaves out
+        // unless it names some of their storage.
+        let mut warm = message.accessed_addresses.clone();
+        for (address, _) in access_list.into_iter().flatten() {
+            warm.remove(address);
+        }
+        if rules.shanghai {
+            warm.insert(header.coinbase.clone());
+        }
+
+        let output = process_message_call(message, &mut env)?;
+        let output = CallOutput { gas_used: gas - output.gas_left, output: output.return_data, error: output.error };
+        Ok((output, warm))
+    }
+
+    /// Estimate the gas limit `tx` from `sender` needs on top of the head
+    /// state, with `estimate_gas`.
+    pub fn estimate_gas(&self, tx: &Transaction, sender: &Address) -> Result<CallOutput, Exception> {
+        let header = &self.blocks.last().unwrap().header;
+        let rules = self.fork_for(header)?.execution_rules();
+        let (block_hashes, chain_id) = (self.head_block_hashes(), self.chain_spec.chain_id);
+        let code_cache = Some(&*self.code_cache);
+        match self.head_snapshot() {
+            Some(view) => {
+                estimate_gas(&SnapshotState::new(view), header, &block_hashes, tx, sender, chain_id, rules, code_cache)
+            }
+            None => estimate_gas(&self.state, header, &block_hashes, tx, sender, chain_id, rules, code_cache),
+        }
+    }
+
+    /// The hashes `BLOCKHASH` sees in the head block, in which calls run:
+    /// those of the 256 blocks before it, rather than those
+    /// `get_last_256_block_hashes` gives for the next block.
+    fn head_block_hashes(&self) -> Vec<Hash32> {
+        let start = self.blocks.len().saturating_sub(256);
+        self.blocks[start..].iter().map(|b| b.header.parent_hash.clone()).collect()
+    }
+}
+
+/// The environment of a call by `caller` in the block of `header`, with its
+/// gas priced at the base fee.
+fn call_environment<'s>(
+    state: &'s mut dyn StateBackend,
+    header: &Header,
+    block_hashes: &[Hash32],
+    chain_id: U64,
+    caller: &Address,
+    rules: ExecutionRules,
+) -> Environment<'s> {
+    Environment {
+        caller: caller.clone(),
+        block_hashes: block_hashes.to_vec(),
+        origin: caller.clone(),
+        coinbase: header.coinbase.clone(),
+        number: header.number,
+        base_fee_per_gas: header.base_fee_per_gas.unwrap_or_default(),
+        gas_limit: header.gas_limit,
+        gas_price: header.base_fee_per_gas.unwrap_or_default(),
+        time: header.timestamp,
+        prev_randao: header.prev_randao.clone(),
+        chain_id,
+        excess_blob_gas: header.excess_blob_gas.unwrap_or_default(),
+        ..Environment::new(state, rules)
+    }
+}
+
+/// The addresses and storage keys a call starts with warm: those of
+/// `access_list`, and `coinbase` if given.
+fn preaccessed(
+    access_list: Option<&[(Address, Vec<Bytes32>)]>,
+    coinbase: Option<&Address>,
+) -> (BTreeSet<Address>, BTreeSet<(Address, Bytes32)>) {
+    let mut addresses: BTreeSet<_> = coinbase.into_iter().cloned().collect();
+    let mut storage_keys = BTreeSet::new();
+    for (address, keys) in access_list.into_iter().flatten() {
+        addresses.insert(address.clone());
+        storage_keys.extend(keys.iter().map(|key| (address.clone(), key.clone())));
+    }
+    (addresses, storage_keys)
+}
+
+/// Find the lowest gas limit with which `tx` from `sender` succeeds in the
+/// block of `header`, on top of `state`, which is left as it is.
+///
+/// The transaction runs like a call: its sender pays nothing and its nonce
+/// is not checked, but it has its value, its access list and its intrinsic
+/// cost. The gas limit is searched up to that of `tx`, or of the block if
+/// `tx` has none. If `tx` fails even with the highest limit, the output of
+/// that run is returned with its error, otherwise `gas_used` is the
+/// estimate and `output` what the transaction returns. The runs share
+/// `code_cache`, if given.
+pub fn estimate_gas<S: StateBackend + Clone>(
+    state: &S,
+    header: &Header,
+    block_hashes: &[Hash32],
+    tx: &Transaction,
+    sender: &Address,
+    chain_id: U64,
+    rules: ExecutionRules,
+    code_cache: Option<&CodeCache<BTreeSet<Uint>>>,
+) -> Result<CallOutput, Exception> {
+    let intrinsic_cost = calculate_intrinsic_cost(tx, rules);
+    let run = |gas: Uint| -> Result<CallOutput, Exception> {
+        if gas < intrinsic_cost {
+            return Ok(CallOutput { gas_used: gas, output: Bytes::default(), error: Some(VmError::OutOfGasError) });
+        }
+        let mut state = state.clone();
+        let mut env = call_environment(&mut state, header, block_hashes, chain_id, sender, rules);
+        env.code_cache = code_cache;
+        let coinbase = rules.shanghai.then_some(&header.coinbase);
+        let (preaccessed_addresses, preaccessed_storage_keys) = preaccessed(tx.access_list(), coinbase);
+        let message = prepare_message(
+            sender.clone(),
+            tx.to(),
+            *tx.value(),
+            Bytes::from(tx.data()),
+            gas - intrinsic_cost,
+            &env,
+            None,
+            true,
+            false,
+            preaccessed_addresses,
+            preaccessed_storage_keys,
+        )?;
+        let output = process_message_call(message, &mut env)?;
+        Ok(CallOutput { gas_used: gas - output.gas_left, output: output.return_data, error: output.error })
+    };
+
+    let cap = match *tx.gas() {
+        0 => header.gas_limit,
+        gas => gas,
+    };
+    let highest = run(cap)?;
+    if highest.error.is_some() {
+        return Ok(highest);
+    }
+
+    // A limit below the gas a successful run used fails, and that gas is
+    // often enough.
+    let used = highest.gas_used;
+    let at_used = run(used)?;
+    if at_used.error.is_none() {
+        return Ok(CallOutput { gas_used: used, ..at_used });
+    }
+    let (mut low, mut high, mut best) = (used, cap, highest);
+    while low + 1 < high {
+        let mid = low + (high - low) / 2;
+        let output = run(mid)?;
+        if output.error.is_none() {
+            (high, best) = (mid, output);
+        } else {
+            low = mid;
+        }
+    }
+    Ok(CallOutput { gas_used: high, ..best })
+}
+
+/// The access list of the accounts and slots in `log`, leaving out the
+/// accounts in `warm` of which no slot was accessed.
+fn access_list_of(log: &AccessLog, warm: &BTreeSet<Address>) -> Vec<(Address, Vec<Bytes32>)> {
+    let mut access_list: BTreeMap<Address, Vec<Bytes32>> = BTreeMap::new();
+    for access in log.reads.union(&log.writes) {
+        match access {
+            Access::Account(address) if !warm.contains(address) => {
+                access_list.entry(address.clone()).or_default();
+            }
+            Access::Slot(address, key) => {
+                let keys = access_list.entry(address.clone()).or_default();
+                if !keys.contains(key) {
+                    keys.push(key.clone());
+                }
+            }
+            _ => {}
+        }
+    }
+    for keys in access_list.values_mut() {
+        keys.sort();
+    }
+    access_list.into_iter().collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::prelude::*;
+
+    /// A chain whose genesis has `contracts`, each an address and its code.
+    fn chain(contracts: &[(&Address, Vec<u8>)]) -> BlockChain {
+        let mut genesis = Genesis::post_merge(&["shanghai", "cancun"]).chain_id(1);
+        for (address, code) in contracts {
+            genesis = genesis.contract((*address).clone(), code.clone());
+        }
+        BlockChain::from_genesis(genesis).unwrap()
+    }
+
+    #[test]
+    fn call() {
+        let contract = Address::from_be_bytes([0xc0; 20]);
+        // PUSH1 42 PUSH1 0 MSTORE PUSH1 32 PUSH1 0 RETURN
+        let code = Bytes(vec![0x60, 0x2a, 0x60, 0x00, 0x52, 0x60, 0x20, 0x60, 0x00, 0xf3]);
+        let chain = chain(&[(&contract, code.0)]);
+
+        let caller = Address::from_be_bytes([0xca; 20]);
+        let result = chain.call(caller.clone(), Some(contract.clone()), Bytes::default(), 100_000).unwrap();
+        assert!(result.error.is_none());
+        assert_eq!(U256::from_be_slice(&result.output), U256::from(42_u32));
+        // Four pushes, MSTORE and a word of memory.
+        assert_eq!(result.gas_used, 4 * 3 + 3 + 3);
+        // The head state is left as it was.
+        assert_eq!(*get_account(&chain.state, &caller), Account::default());
+        // Calls share the jump destinations of the code through the chain.
+        chain.call(caller.clone(), Some(contract), Bytes::default(), 100_000).unwrap();
+        assert_eq!(chain.code_cache.hits_and_misses(), (1, 1));
+
+        // INVALID
+        let result = chain.call(caller, None, Bytes(vec![0xfe]), 100_000).unwrap();
+        assert!(matches!(result.error, Some(VmError::InvalidOpcode)));
+        assert_eq!(result.gas_used, 100_000);
+    }
+
+    #[test]
+    fn create_access_list() {
+        let contract = Address::from_be_bytes([0xc0; 20]);
+        let other = Address::from_be_bytes([0x0e; 20]);
+        // PUSH1 1 SLOAD POP, then CALL(GAS, other, 0, 0, 0, 0, 0) POP STOP
+        let mut code = vec![0x60, 0x01, 0x54, 0x50, 0x60, 0x00, 0x60, 0x00, 0x60, 0x00, 0x60, 0x00, 0x60, 0x00, 0x73];
+        code.extend(&other[..]);
+        code.extend([0x5a, 0xf1, 0x50, 0x00]);
+        let chain = chain(&[(&contract, code)]);
+        let caller = Address::from_be_bytes([0xca; 20]);
+
+        let cold = chain.call(caller.clone(), Some(contract.clone()), Bytes::default(), 100_000).unwrap();
+        assert_eq!(cold.gas_used, 3 + 2100 + 2 + 5 * 3 + 3 + 2 + 2600 + 2);
+
+        let output = chain.create_access_list(caller, Some(contract.clone()), Bytes::default(), 100_000).unwrap();
+        assert!(output.error.is_none());
+        let key = Bytes32(U256::ONE.to_be_bytes());
+        // The contract is warm as the target, but its slot is listed.
+        assert_eq!(output.access_list, [(other, vec![]), (contract, vec![key])]);
+        assert_eq!(output.gas_used, 3 + 100 + 2 + 5 * 3 + 3 + 2 + 100 + 2);
+    }
+
+    #[test]
+    fn estimate_gas() {
+        let outer = Address::from_be_bytes([0xc0; 20]);
+        let inner = Address::from_be_bytes([0xc1; 20]);
+        // CALL(GAS, inner, 0, 0, 0, 0, 0), reverting unless it succeeds.
+        let mut code = vec![0x60, 0x00, 0x60, 0x00, 0x60, 0x00, 0x60, 0x00, 0x60, 0x00, 0x73];
+        code.extend(&inner[..]);
+        code.extend([0x5a, 0xf1, 0x60, 0x28, 0x57, 0x60, 0x00, 0x80, 0xfd, 0x5b, 0x00]);
+        // SSTORE(0, 1) STOP
+        let calling = chain(&[(&outer, code), (&inner, vec![0x60, 0x01, 0x60, 0x00, 0x55, 0x00])]);
+        let caller = Address::from_be_bytes([0xca; 20]);
+        let tx = |gas| Transaction::LegacyTransaction(LegacyTransaction { gas, to: Some(outer.clone()).into(), ..Default::default() });
+
+        let used = calling.call(caller.clone(), Some(outer.clone()), Bytes::default(), 1_000_000).unwrap().gas_used;
+        let estimate = calling.estimate_gas(&tx(0), &caller).unwrap();
+        assert!(estimate.error.is_none());
+        // The outer call keeps 1/64 of its gas back, so the gas the
+        // transaction uses is not enough.
+        assert!(estimate.gas_used > 21_000 + used, "{} {used}", estimate.gas_used);
+        assert!(calling.estimate_gas(&tx(estimate.gas_used), &caller).unwrap().error.is_none());
+        let short = calling.estimate_gas(&tx(estimate.gas_used - 1), &caller).unwrap();
+        assert!(matches!(short.error, Some(VmError::Revert)));
+
+        // A transaction which always fails reports its error.
+        let reverting = chain(&[(&outer, vec![0x60, 0x00, 0x80, 0xfd])]);
+        assert!(matches!(reverting.estimate_gas(&tx(0), &caller).unwrap().error, Some(VmError::Revert)));
+    }
+
+    #[test]
+    fn snapshots() {
+        use crate::{builder::build_payload, engine::PayloadAttributes};
+
+        // MSTORE(0, BALANCE(CALLDATALOAD(0))) RETURN(0, 32)
+        let contract = Address::from_be_bytes([0xc0; 20]);
+        let code = vec![0x60, 0x00, 0x35, 0x31, 0x60, 0x00, 0x52, 0x60, 0x20, 0x60, 0x00, 0xf3];
+        let mut chain = chain(&[(&contract, code)]).with_snapshots();
+        // The block after the head with a withdrawal to `0xto..to`.
+        let block = |chain: &BlockChain, timestamp: u32, to: u8| {
+            let withdrawal = Withdrawal { address: Address::from_be_bytes([to; 20]), amount: U256::ONE, ..Default::default() };
+            let attributes = PayloadAttributes { timestamp: U256::from(timestamp), withdrawals: vec![withdrawal], ..Default::default() };
+            build_payload(chain, &[], &attributes).unwrap().block
+        };
+        let balance = |chain: &BlockChain, owner: u8| {
+            let data = [&[0; 12][..], &[owner; 20]].concat();
+            let output = chain.call(Address::default(), Some(contract.clone()), Bytes(data), 100_000).unwrap();
+            U256::from_be_slice(&output.output)
+        };
+        let head_root = |chain: &BlockChain| chain.blocks.last().unwrap().header.state_root.clone();
+        let gwei = U256::from(1_000_000_000_u32);
+
+        let genesis = chain.head_hash().clone();
+        let a1 = chain.import_block(block(&chain, 12, 0xaa)).unwrap();
+        let snapshots = chain.snapshots.as_ref().unwrap();
+        assert_eq!(snapshots.diff_layers(), 1);
+        let head = chain.head_snapshot().unwrap();
+        assert_eq!(head.root(), &head_root(&chain));
+        for (address, account) in chain.state.accounts() {
+            assert_eq!(head.account(address), Some(account));
+        }
+        assert_eq!(balance(&chain, 0xaa), gwei);
+        let mut copying = chain.clone();
+        copying.snapshots = None;
+        assert_eq!(balance(&copying, 0xaa), gwei);
+        let tx = Transaction::LegacyTransaction(LegacyTransaction { to: Some(contract.clone()).into(), ..Default::default() });
+        let estimate = |chain: &BlockChain| chain.estimate_gas(&tx, &Address::default()).unwrap().gas_used;
+        assert_eq!(estimate(&chain), estimate(&copying));
+
+        // A sibling of the head gets its own layer.
+        chain.set_head(&genesis).unwrap();
+        chain.import_block(block(&chain, 12, 0xbb)).unwrap();
+        assert_eq!(chain.snapshots.as_ref().unwrap().diff_layers(), 2);
+        assert_eq!((balance(&chain, 0xaa), balance(&chain, 0xbb)), (U256::ZERO, gwei));
+
+        // Rewound below the disk layer, the snapshots start again.
+        let root = head_root(&chain);
+        chain.snapshots.as_mut().unwrap().cap(&root, 0);
+        chain.set_head(&a1).unwrap();
+        let snapshots = chain.snapshots.as_ref().unwrap();
+        assert_eq!((snapshots.disk_root(), snapshots.diff_layers()), (&head_root(&chain), 0));
+        assert_eq!((balance(&chain, 0xaa), balance(&chain, 0xbb)), (gwei, U256::ZERO));
+    }
+
+    #[test]
+    fn warm_accesses_of_successful_calls() {
+        let outer = Address::from_be_bytes([0xc0; 20]);
+        let inner = Address::from_be_bytes([0xc1; 20]);
+        let reverting_inner = Address::from_be_bytes([0xc2; 20]);
+        // CALL(0, 0xee, 0, 0, 0, 0, 0)
+        let call_ee = [0x60, 0x00, 0x60, 0x00, 0x60, 0x00, 0x60, 0x00, 0x60, 0x00, 0x60, 0xee, 0x60, 0x00, 0xf1];
+        // CALL(GAS, inner, 0, 0, 0, 0, 0) POP, then `call_ee`.
+        let outer_code = |inner: &Address| {
+            let mut code = vec![0x60, 0x00, 0x60, 0x00, 0x60, 0x00, 0x60, 0x00, 0x60, 0x00, 0x73];
+            code.extend(&inner[..]);
+            code.extend([0x5a, 0xf1, 0x50]);
+            code.extend(call_ee);
+            code
+        };
+        let inner_code = call_ee.to_vec();
+        let reverting_code = [&call_ee[..], &[0x60, 0x00, 0x80, 0xfd]].concat();
+        let caller = Address::from_be_bytes([0xca; 20]);
+        let run = |inner: &Address| {
+            let calls = chain(&[(&outer, outer_code(inner)), (&inner, inner_code.clone()), (&reverting_inner, reverting_code.clone())]);
+            calls.call(caller.clone(), Some(outer.clone()), Bytes::default(), 1_000_000).unwrap()
+        };
+        let (succeeding, reverted) = (run(&inner), run(&reverting_inner));
+
+        // After the inner call succeeds 0xee is warm for the outer one. When
+        // it reverts, 0xee is cold again, and the revert costs two pushes.
+        assert!(succeeding.error.is_none() && reverted.error.is_none());
+        assert_eq!(reverted.gas_used - succeeding.gas_used, (2600 - 100) + 2 * 3);
+    }
+
+    #[test]
+    fn block_hash() {
+        use crate::ethereum::cancun::fork::{compute_header_hash, fuzz::tests::valid_block, get_last_256_block_hashes};
+
+        let (mut chain, block) = valid_block();
+        chain.import_block(block).unwrap();
+        let genesis_hash = compute_header_hash(&chain.blocks[0].header).unwrap();
+        // The parent hash of the genesis block, zero, comes first.
+        let hashes = [Hash32::default(), genesis_hash.clone(), chain.head_hash().clone()];
+        assert_eq!(get_last_256_block_hashes(&chain), hashes);
+
+        // BLOCKHASH(CALLDATALOAD(0)), returned.
+        let contract = Address::from_be_bytes([0xc0; 20]);
+        let code = Bytes(vec![0x60, 0x00, 0x35, 0x40, 0x60, 0x00, 0x52, 0x60, 0x20, 0x60, 0x00, 0xf3]);
+        set_account(&mut chain.state, &contract, Some(Account { nonce: 1, code, ..Default::default() }));
+        let block_hash = |number: u8| {
+            let mut data = [0; 32];
+            data[31] = number;
+            let output = chain.call(Address::default(), Some(contract.clone()), Bytes(data.to_vec()), 100_000).unwrap();
+            Hash32(output.output.0.try_into().unwrap())
+        };
+        // Calls run in the head block, which only sees the blocks before it.
+        assert_eq!(block_hash(0), genesis_hash);
+        assert_eq!(block_hash(1), Hash32::default());
+        assert_eq!(block_hash(2), Hash32::default());
+    }
+
+    #[test]
+    fn external_accounts() {
+        let contract = Address::from_be_bytes([0xc0; 20]);
+        let other = Address::from_be_bytes([0x0e; 20]);
+        let other_code = vec![0x60, 0x01, 0x60, 0x02, 0x00];
+        let push_other = || [&[0x73][..], &other[..]].concat();
+        let mut code = Vec::new();
+        // MSTORE(0, BALANCE(other)) MSTORE(32, EXTCODESIZE(other))
+        // MSTORE(64, EXTCODEHASH(other)) MSTORE(96, EXTCODEHASH(0xee))
+        for (op, offset) in [(0x31, 0x00), (0x3b, 0x20), (0x3f, 0x40)] {
+            code.extend(push_other());
+            code.extend([op, 0x60, offset, 0x52]);
+        }
+        code.extend([0x60, 0xee, 0x3f, 0x60, 0x60, 0x52]);
+        // EXTCODECOPY(other, 128, 0, 5) RETURN(0, 160)
+        code.extend([0x60, 0x05, 0x60, 0x00, 0x60, 0x80]);
+        code.extend(push_other());
+        code.extend([0x3c, 0x60, 0xa0, 0x60, 0x00, 0xf3]);
+        let mut chain = chain(&[(&contract, code), (&other, other_code.clone())]);
+        let account = Account { nonce: 1, balance: U256::from(7_u32), code: Bytes(other_code.clone()) };
+        set_account(&mut chain.state, &other, Some(account));
+
+        let output = chain.call(Address::default(), Some(contract), Bytes::default(), 100_000).unwrap();
+        assert!(output.error.is_none());
+        let word = |i: usize| U256::from_be_slice(&output.output[32 * i..32 * (i + 1)]);
+        assert_eq!(word(0), U256::from(7_u32));
+        assert_eq!(word(1), U256::from(5_u32));
+        assert_eq!(word(2), U256::from_be_bytes(keccak256(&other_code).0));
+        assert_eq!(word(3), U256::ZERO);
+        assert_eq!(output.output[128..133], other_code);
+        // `other` is cold only for BALANCE, 0xee is cold too.
+        let access = 2600 + 100 + 100 + 2600 + 100;
+        // Pushes, MSTOREs, a word copied and five words of memory.
+        assert_eq!(output.gas_used, access + 14 * 3 + 4 * 3 + 3 + 5 * 3);
+    }
+
+    #[test]
+    fn pushes() {
+        use crate::ethereum::cancun::vm::instructions::Ops;
+
+        let contract = Address::from_be_bytes([0xc0; 20]);
+        // PUSH0 PUSH32 (32 bytes) ADD PUSH0 MSTORE PUSH1 32 PUSH0 RETURN
+        let mut code = vec![0x5f, 0x7f];
+        code.extend(1..=32);
+        code.extend([0x01, 0x5f, 0x52, 0x60, 0x20, 0x5f, 0xf3]);
+        let pushing = chain(&[(&contract, code)]);
+        let output = pushing.call(Address::default(), Some(contract), Bytes::default(), 100_000).unwrap();
+        assert_eq!(output.output.0, (1..=32).collect::<Vec<u8>>());
+        // Three PUSH0, PUSH32, ADD, PUSH1, MSTORE and a word of memory.
+        assert_eq!(output.gas_used, 3 * 2 + 3 + 3 + 3 + 3 + 3);
+        assert!(!ExecutionRules::LONDON.allows(Ops::PUSH0));
+
+        // A push cut off by the end of the code reads zeros and stops.
+        let truncated = Address::from_be_bytes([0xc1; 20]);
+        let truncating = chain(&[(&truncated, vec![0x5f, 0x62, 0x01])]);
+        let output = truncating.call(Address::default(), Some(truncated), Bytes::default(), 100_000).unwrap();
+        assert!(output.error.is_none());
+        assert_eq!(output.gas_used, 2 + 3);
+    }
+}
@@ -0,0 +1,522 @@
+//! Structured mutation fuzzing of block validation.
+//!
+//! `BlockFuzzer` starts from a chain and a block which is known to extend
+//! it. Each iteration applies one `Mutation` to the block (a corrupted header
+//! field, a truncated RLP encoding, two transactions swapped or a damaged
+//! signature) and checks that `state_transition` rejects the result with
+//! the kind of error the mutation should cause. Accepting a mutated block,
+//! failing with the wrong error and panicking are all reported as a
+//! `FuzzFailure`.
+//!
+//! Mutations are generated from a seed, so a failure can be reproduced by
+//! running the same seed again or by passing the reported mutation to
+//! `BlockFuzzer::check`.
+//!
+//! Only header fields which are committed to by the block contents or
+//! constrained by the parent are mutated. Fields such as `coinbase` or
+//! `prev_randao` can legitimately take other values and are left alone.
+
+use std::panic::{self, AssertUnwindSafe};
+
+use crate::ethereum::{
+    cancun::{blocks::Block, transactions::Transaction},
+    ethereum_rlp::rlp,
+    ethereum_types::numeric::U256,
+    exceptions::Exception,
+};
+
+use super::{state_transition, BlockChain};
+
+/// A header field which cannot change without invalidating the block.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum HeaderField {
+    ParentHash,
+    OmmersHash,
+    StateRoot,
+    TransactionsRoot,
+    ReceiptRoot,
+    Bloom,
+    Difficulty,
+    Number,
+    GasLimit,
+    GasUsed,
+    Timestamp,
+    ExtraData,
+    Nonce,
+    BaseFeePerGas,
+    WithdrawalsRoot,
+    BlobGasUsed,
+    ExcessBlobGas,
+}
+
+impl HeaderField {
+    pub const ALL: [HeaderField; 17] = [
+        HeaderField::ParentHash,
+        HeaderField::OmmersHash,
+        HeaderField::StateRoot,
+        HeaderField::TransactionsRoot,
+        HeaderField::ReceiptRoot,
+        HeaderField::Bloom,
+        HeaderField::Difficulty,
+        HeaderField::Number,
+        HeaderField::GasLimit,
+        HeaderField::GasUsed,
+        HeaderField::Timestamp,
+        HeaderField::ExtraData,
+        HeaderField::Nonce,
+        HeaderField::BaseFeePerGas,
+        HeaderField::WithdrawalsRoot,
+        HeaderField::BlobGasUsed,
+        HeaderField::ExcessBlobGas,
+    ];
+}
+
+/// Part of a transaction signature.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum SignaturePart {
+    R,
+    S,
+    /// `v` of a legacy transaction or `y_parity` of a typed one.
+    V,
+}
+
+/// A change which must make a valid block invalid.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Mutation {
+    /// Flip `bit` of a header field, or for fields with a valid range, use
+    /// `bit` to pick a value outside of it.
+    Header { field: HeaderField, bit: u32 },
+    /// Keep only the first `len` bytes of the block's RLP encoding.
+    TruncateRlp { len: usize },
+    /// Swap two transactions.
+    ReorderTransactions { first: usize, second: usize },
+    /// Flip `bit` of part of the signature of a transaction.
+    CorruptSignature { index: usize, part: SignaturePart, bit: u32 },
+}
+
+impl Mutation {
+    /// True if `error` is what this mutation should cause.
+    pub fn expects(&self, error: &Exception) -> bool {
+        match self {
+            Mutation::Header { .. } | Mutation::ReorderTransactions { .. } => error.is_invalid_block(),
+            Mutation::TruncateRlp { .. } => matches!(error, Exception::RLPException(_)),
+            // Either the signature is rejected, or it recovers a different
+            // sender which cannot pay for the transaction or changes the
+            // state root.
+            Mutation::CorruptSignature { .. } => {
+                matches!(error, Exception::InvalidSignatureError(_)) || error.is_invalid_block()
+            }
+        }
+    }
+}
+
+/// What went wrong with a mutated block.
+#[derive(Debug)]
+pub enum FuzzOutcome {
+    /// The block was accepted.
+    Accepted,
+    /// The block was rejected with an error the mutation should not cause.
+    WrongError(Exception),
+    /// Validation panicked, with the panic message.
+    Panicked(String),
+}
+
+/// A mutation which was not rejected correctly.
+#[derive(Debug)]
+pub struct FuzzFailure {
+    /// Number of mutations checked before this one.
+    pub iteration: usize,
+    pub mutation: Mutation,
+    pub outcome: FuzzOutcome,
+}
+
+/// Mutates a valid block and checks that every mutation is rejected.
+pub struct BlockFuzzer {
+    chain: BlockChain,
+    block: Block,
+    rng: XorShift,
+}
+
+impl BlockFuzzer {
+    /// Fuzz `block`, which must be a valid child of the head of `chain`,
+    /// with mutations generated from `seed`.
+    pub fn new(chain: BlockChain, block: Block, seed: u64) -> Result<Self, Exception> {
+        state_transition(&mut chain.clone(), block.clone())?;
+        Ok(Self { chain, block, rng: XorShift::new(seed) })
+    }
+
+    /// Check `iterations` random mutations, stopping at the first failure.
+    pub fn run(&mut self, iterations: usize) -> Result<(), FuzzFailure> {
+        for iteration in 0..iterations {
+            let mutation = self.next_mutation();
+            if let Err(outcome) = self.check(&mutation) {
+                return Err(FuzzFailure { iteration, mutation, outcome });
+            }
+        }
+        Ok(())
+    }
+
+    /// Generate the next random mutation.
+    pub fn next_mutation(&mut self) -> Mutation {
+        let num_transactions = self.block.transactions.len();
+        let bit = self.rng.next() as u32;
+        loop {
+            match self.rng.below(4) {
+                0 => {
+                    let field = HeaderField::ALL[self.rng.below(HeaderField::ALL.len())];
+                    return Mutation::Header { field, bit };
+                }
+                1 => {
+                    let encoded_len = rlp::encode(&self.block).map_or(0, |b| b.len());
+                    return Mutation::TruncateRlp { len: self.rng.below(encoded_len.max(1)) };
+                }
+                2 if num_transactions >= 2 => {
+                    let first = self.rng.below(num_transactions);
+                    let second = (first + 1 + self.rng.below(num_transactions - 1)) % num_transactions;
+                    return Mutation::ReorderTransactions { first, second };
+                }
+                3 if num_transactions >= 1 => {
+                    let index = self.rng.below(num_transactions);
+                    let part = [SignaturePart::R, SignaturePart::S, SignaturePart::V][self.rng.below(3)];
+                    return Mutation::CorruptSignature { index, part, bit };
+                }
+                _ => continue,
+            }
+        }
+    }
+
+    /// Apply `mutation` to a copy of the block.
+    ///
+    /// Truncation fails here, when the remaining bytes are decoded.
+    pub fn mutate(&self, mutation: &Mutation) -> Result<Block, Exception> {
+        let mut block = self.block.clone();
+        match *mutation {
+            Mutation::Header { field, bit } => self.mutate_header(&mut block, field, bit),
+            Mutation::TruncateRlp { len } => {
+                let encoded = rlp::encode(&block)?;
+                block = rlp::decode_to_strict(&encoded[..len.min(encoded.len())])?;
+            }
+            Mutation::ReorderTransactions { first, second } => {
+                block.transactions.swap(first, second);
+            }
+            Mutation::CorruptSignature { index, part, bit } => {
+                let tx = &mut block.transactions[index];
+                let value = match (tx, part) {
+                    (Transaction::LegacyTransaction(tx), SignaturePart::V) => &mut tx.v,
+                    (Transaction::AccessListTransaction(tx), SignaturePart::V) => &mut tx.y_parity,
+                    (Transaction::FeeMarketTransaction(tx), SignaturePart::V) => &mut tx.y_parity,
+                    (Transaction::BlobTransaction(tx), SignaturePart::V) => &mut tx.y_parity,
+                    (Transaction::SetCodeTransaction(tx), SignaturePart::V) => &mut tx.y_parity,
+                    (Transaction::LegacyTransaction(tx), SignaturePart::R) => &mut tx.r,
+                    (Transaction::AccessListTransaction(tx), SignaturePart::R) => &mut tx.r,
+                    (Transaction::FeeMarketTransaction(tx), SignaturePart::R) => &mut tx.r,
+                    (Transaction::BlobTransaction(tx), SignaturePart::R) => &mut tx.r,
+                    (Transaction::SetCodeTransaction(tx), SignaturePart::R) => &mut tx.r,
+                    (Transaction::LegacyTransaction(tx), SignaturePart::S) => &mut tx.s,
+                    (Transaction::AccessListTransaction(tx), SignaturePart::S) => &mut tx.s,
+                    (Transaction::FeeMarketTransaction(tx), SignaturePart::S) => &mut tx.s,
+                    (Transaction::BlobTransaction(tx), SignaturePart::S) => &mut tx.s,
+                    (Transaction::SetCodeTransaction(tx), SignaturePart::S) => &mut tx.s,
+                };
+                *value = *value ^ U256::ONE.shl(bit % 256);
+            }
+        }
+        Ok(block)
+    }
+
+    /// Check that the block with `mutation` applied is rejected with the
+    /// expected error.
+    pub fn check(&self, mutation: &Mutation) -> Result<(), FuzzOutcome> {
+        let result = panic::catch_unwind(AssertUnwindSafe(|| {
+            let block = self.mutate(mutation)?;
+            state_transition(&mut self.chain.clone(), block)
+        }));
+        match result {
+            Ok(Ok(())) => Err(FuzzOutcome::Accepted),
+            Ok(Err(error)) if mutation.expects(&error) => Ok(()),
+            Ok(Err(error)) => Err(FuzzOutcome::WrongError(error)),
+            Err(payload) => {
+                let message = payload
+                    .downcast_ref::<&str>()
+                    .map(|s| s.to_string())
+                    .or_else(|| payload.downcast_ref::<String>().cloned())
+                    .unwrap_or_default();
+                Err(FuzzOutcome::Panicked(message))
+            }
+        }
+    }
+
+    fn mutate_header(&self, block: &mut Block, field: HeaderField, bit: u32) {
+        let parent = &self.chain.blocks.last().unwrap().header;
+        let header = &mut block.header;
+        let flip = |bytes: &mut [u8]| {
+            let bit = bit as usize % (bytes.len() * 8);
+            bytes[bit / 8] ^= 1 << (bit % 8);
+        };
+        match field {
+            HeaderField::ParentHash => flip(&mut header.parent_hash.0),
+            HeaderField::OmmersHash => flip(&mut header.ommers_hash.0),
+            HeaderField::StateRoot => flip(&mut header.state_root.0),
+            HeaderField::TransactionsRoot => flip(&mut header.transactions_root.0),
+            HeaderField::ReceiptRoot => flip(&mut header.receipt_root.0),
+            HeaderField::Bloom => flip(&mut header.bloom.0.0),
+            HeaderField::Difficulty => header.difficulty ^= 1 << (bit % 64),
+            HeaderField::Number => header.number ^= 1 << (bit % 64),
+            HeaderField::GasLimit => {
+                // Just outside the range allowed by `check_gas_limit`.
+                let spec = &self.chain.chain_spec;
+                let max_adjustment_delta = parent.gas_limit / spec.gas_limit_adjustment_factor;
+                header.gas_limit = match bit % 3 {
+                    0 => parent.gas_limit + max_adjustment_delta,
+                    1 => parent.gas_limit - max_adjustment_delta,
+                    _ => spec.gas_limit_minimum - 1,
+                };
+            }
+            HeaderField::GasUsed => header.gas_used ^= 1 << (bit % 64),
+            HeaderField::Timestamp => {
+                // No later than the parent.
+                header.timestamp = parent.timestamp - U256::from(bit % 2).min(parent.timestamp);
+            }
+            HeaderField::ExtraData => header.extra_data.0.resize(33 + bit as usize % 32, 0xee),
+            HeaderField::Nonce => flip(&mut header.nonce.0),
+            HeaderField::BaseFeePerGas => {
+                header.base_fee_per_gas = Some(header.base_fee_per_gas.unwrap_or_default() ^ 1 << (bit % 64));
+            }
+            HeaderField::WithdrawalsRoot => match &mut header.withdrawals_root {
+                Some(root) => flip(&mut root.0),
+                None => header.withdrawals_root = Some(Default::default()),
+            },
+            HeaderField::BlobGasUsed => {
+                header.blob_gas_used = Some(header.blob_gas_used.map_or(0, |g| g ^ 1 << (bit % 64)));
+            }
+            HeaderField::ExcessBlobGas => {
+                header.excess_blob_gas = Some(header.excess_blob_gas.map_or(0, |g| g ^ 1 << (bit % 64)));
+            }
+        }
+    }
+}
+
+/// Small, seedable pseudo random number generator (xorshift64*).
+struct XorShift(u64);
+
+impl XorShift {
+    fn new(seed: u64) -> Self {
+        // The state must not be zero.
+        Self((seed ^ 0x9E37_79B9_7F4A_7C15).max(1))
+    }
+
+    fn next(&mut self) -> u64 {
+        let mut x = self.0;
+        x ^= x >> 12;
+        x ^= x << 25;
+        x ^= x >> 27;
+        self.0 = x;
+        x.wrapping_mul(0x2545_F491_4F6C_DD1D)
+    }
+
+    /// A number in `0..n`.
+    fn below(&mut self, n: usize) -> usize {
+        (self.next() % n as u64) as usize
+    }
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use std::collections::BTreeMap;
+
+    use crate::ethereum::{
+        cancun::{
+            blocks::{Block, Header, Withdrawal},
+            fork::{apply_body, calculate_base_fee_per_gas, compute_header_hash, get_last_256_block_hashes, BlockChain, EMPTY_OMMER_HASH},
+            fork_types::{Account, Address, Root},
+            transactions::{
+                signing_hash_155, signing_hash_1559, signing_hash_2930, AccessListTransaction,
+                FeeMarketTransaction, LegacyTransaction, Transaction,
+            },
+            trie::EMPTY_TRIE_ROOT,
+        },
+        chain_spec::ChainSpec,
+        crypto::{
+            eliptic_curve::{secp256k1_public_key, secp256k1_sign},
+            hash::keccak256,
+        },
+        ethereum_types::{bytes::{Bytes, Bytes32}, numeric::U256},
+        forks::ExecutionRules,
+        genesis::Genesis,
+    };
+
+    use super::{BlockFuzzer, HeaderField, Mutation, SignaturePart};
+
+    const CHAIN_ID: u64 = 1337;
+
+    fn address_of(secret_key: U256) -> Address {
+        let public_key = secp256k1_public_key(secret_key);
+        Address::from_be_bytes(keccak256(&public_key)[12..].try_into().unwrap())
+    }
+
+    /// A chain with two funded accounts, a contract which stores the block
+    /// number, and a block with three signed transactions and a withdrawal.
+    pub(crate) fn valid_block() -> (BlockChain, Block) {
+        let alice = U256::from(0xa11ce_u32);
+        let bob = U256::from(0xb0b_u32);
+        let contract = Address::from_be_bytes([0xcc; 20]);
+        let funded = Account { balance: U256::from(10_u64.pow(18)), ..Default::default() };
+
+        let mut alloc = BTreeMap::new();
+        alloc.insert(address_of(alice), funded.clone());
+        alloc.insert(address_of(bob), funded);
+        // NUMBER PUSH1 0 SSTORE STOP
+        alloc.insert(contract.clone(), Account { nonce: 1, code: Bytes(vec![0x43, 0x60, 0x00, 0x55, 0x00]), ..Default::default() });
+        // Stores the beacon root under the timestamp:
+        // PUSH1 0 CALLDATALOAD TIMESTAMP SSTORE STOP
+        alloc.insert(ChainSpec::MAINNET.beacon_roots_address, Account { nonce: 1, code: Bytes(vec![0x60, 0x00, 0x35, 0x42, 0x55, 0x00]), ..Default::default() });
+
+        let genesis = Genesis {
+            header: Header {
+                ommers_hash: EMPTY_OMMER_HASH,
+                gas_limit: 30_000_000,
+                base_fee_per_gas: Some(7),
+                withdrawals_root: Some(EMPTY_TRIE_ROOT),
+                blob_gas_used: Some(0),
+                excess_blob_gas: Some(0),
+                parent_beacon_block_root: Some(Root::default()),
+                ..Default::default()
+            },
+            alloc,
+            chain_spec: ChainSpec { chain_id: CHAIN_ID, ..ChainSpec::MAINNET },
+            ..Default::default()
+        };
+        let chain = BlockChain::from_genesis(genesis).unwrap();
+        let parent = chain.blocks[0].header.clone();
+
+        let mut legacy = LegacyTransaction {
+            nonce: U256::ZERO,
+            gas_price: 10,
+            gas: 21_000,
+            to: Some(address_of(bob)).into(),
+            value: U256::from(1000_u32),
+            ..Default::default()
+        };
+        let (r, s, v) = secp256k1_sign(signing_hash_155(&legacy, CHAIN_ID).unwrap(), alice).unwrap();
+        legacy.r = r;
+        legacy.s = s;
+        legacy.v = v + U256::from(35 + 2 * CHAIN_ID);
+
+        let mut fee_market = FeeMarketTransaction {
+            chain_id: CHAIN_ID,
+            nonce: U256::ONE,
+            max_priority_fee_per_gas: 2,
+            max_fee_per_gas: 20,
+            gas: 100_000,
+            to: Some(contract.clone()).into(),
+            ..Default::default()
+        };
+        let (r, s, v) = secp256k1_sign(signing_hash_1559(&fee_market).unwrap(), alice).unwrap();
+        (fee_market.r, fee_market.s, fee_market.y_parity) = (r, s, v);
+
+        let mut access_list = AccessListTransaction {
+            chain_id: CHAIN_ID,
+            nonce: U256::ZERO,
+            gas_price: 9,
+            gas: 100_000,
+            to: Some(contract.clone()).into(),
+            data: Bytes(vec![1, 2, 3]),
+            access_list: vec![(contract, vec![Bytes32::default()])],
+            ..Default::default()
+        };
+        let (r, s, v) = secp256k1_sign(signing_hash_2930(&access_list).unwrap(), bob).unwrap();
+        (access_list.r, access_list.s, access_list.y_parity) = (r, s, v);
+
+        let mut block = Block {
+            header: Header {
+                parent_hash: compute_header_hash(&parent).unwrap(),
+                ommers_hash: EMPTY_OMMER_HASH,
+                coinbase: Address::from_be_bytes([0xcb; 20]),
+                number: 1,
+                gas_limit: parent.gas_limit,
+                timestamp: U256::from(12_u32),
+                base_fee_per_gas: Some(calculate_base_fee_per_gas(parent.gas_limit, parent.gas_limit, parent.gas_used, 7, &chain.chain_spec).unwrap()),
+                excess_blob_gas: Some(0),
+                parent_beacon_block_root: Some(Root([0xbe; 32])),
+                ..Default::default()
+            },
+            transactions: vec![
+                Transaction::LegacyTransaction(legacy),
+                Transaction::FeeMarketTransaction(fee_market),
+                Transaction::AccessListTransaction(access_list),
+            ],
+            ommers: Vec::new(),
+            withdrawals: Some(vec![Withdrawal { index: 0, validator_index: 5, address: Address::from_be_bytes([0xdd; 20]), amount: U256::from(32_u32) }]),
+        };
+
+        // Execute the body to fill in the commitments.
+        let mut state = chain.state.clone();
+        let header = &block.header;
+        let output = apply_body(
+            &mut state,
+            &get_last_256_block_hashes(&chain),
+            &header.coinbase,
+            &header.number,
+            &header.base_fee_per_gas,
+            &header.gas_limit,
+            &header.timestamp,
+            &header.prev_randao,
+            &block.transactions,
+            &block.ommers,
+            None,
+            chain.chain_spec.chain_id,
+            block.withdrawals.as_deref(),
+            &header.parent_beacon_block_root,
+            &header.excess_blob_gas,
+            ExecutionRules::CANCUN,
+            &chain.chain_spec,
+            None,
+        )
+        .unwrap();
+        let header = &mut block.header;
+        header.gas_used = output.block_gas_used;
+        header.transactions_root = output.transactions_root;
+        header.receipt_root = output.receipt_root;
+        header.bloom = output.block_logs_bloom;
+        header.state_root = output.state_root;
+        header.withdrawals_root = output.withdrawals_root;
+        header.blob_gas_used = output.blob_gas_used;
+
+        (chain, block)
+    }
+
+    #[test]
+    fn every_mutation_is_rejected() {
+        // FUZZ_SEED and FUZZ_ITERATIONS allow longer runs with other seeds.
+        let seed = std::env::var("FUZZ_SEED").map_or(0, |s| s.parse().unwrap());
+        let iterations = std::env::var("FUZZ_ITERATIONS").map_or(64, |s| s.parse().unwrap());
+
+        let (chain, block) = valid_block();
+        // A transfer, a cold SSTORE and a warm SSTORE of the same value.
+        assert_eq!(block.header.gas_used, 21_000 + (21_000 + 5 + 22_100) + (21_000 + 48 + 4300 + 5 + 100));
+
+        let mut fuzzer = BlockFuzzer::new(chain, block, seed).unwrap();
+        if let Err(failure) = fuzzer.run(iterations) {
+            panic!("seed {seed}: {failure:?}");
+        }
+
+        // Each kind of mutation at least once.
+        let mutations = HeaderField::ALL
+            .iter()
+            .map(|&field| Mutation::Header { field, bit: 7 })
+            .chain([
+                Mutation::TruncateRlp { len: 0 },
+                Mutation::TruncateRlp { len: 300 },
+                Mutation::ReorderTransactions { first: 0, second: 1 },
+                Mutation::ReorderTransactions { first: 1, second: 2 },
+                Mutation::CorruptSignature { index: 0, part: SignaturePart::V, bit: 0 },
+                Mutation::CorruptSignature { index: 1, part: SignaturePart::S, bit: 255 },
+                Mutation::CorruptSignature { index: 2, part: SignaturePart::R, bit: 3 },
+            ]);
+        for mutation in mutations {
+            if let Err(outcome) = fuzzer.check(&mutation) {
+                panic!("{mutation:?}: {outcome:?}");
+            }
+        }
+    }
+}
@@ -0,0 +1,167 @@
+//! Header-only chain validation, for syncing headers before bodies.
+//!
+//! A `HeaderChain` holds the canonical chain of headers and checks each new
+//! header against its parent with the same checks `state_transition` makes
+//! before executing a block: parent hash, number, timestamp, gas limit,
+//! base fee, blob gas fields, difficulty before the merge and the fixed
+//! fields of each fork. Nothing is executed, so the state root, receipts
+//! root, gas used and blooms of the headers are trusted until their bodies
+//! are imported into a `BlockChain`.
+
+use std::collections::BTreeMap;
+
+use crate::ethereum::{
+    cancun::blocks::Header,
+    chain_spec::ChainSpec,
+    crypto::hash::Hash32,
+    ethereum_types::numeric::Uint,
+    exceptions::Exception,
+    fork_criteria::ForkSchedule,
+    forks::fork_rules,
+};
+
+use super::{compute_header_hash, validate_header_with_parent, BlockChain};
+
+/// The canonical chain of headers from some trusted header, usually the
+/// genesis.
+#[derive(Debug, Clone)]
+pub struct HeaderChain {
+    /// Which fork's rules apply to each header.
+    pub fork_schedule: ForkSchedule,
+    /// Parameters the headers are checked with, those of mainnet unless set
+    /// otherwise.
+    pub chain_spec: ChainSpec,
+    headers: Vec<Header>,
+    /// Hashes of `headers`.
+    hashes: Vec<Hash32>,
+    /// Position of each header in `headers`, by hash.
+    index: BTreeMap<Hash32, usize>,
+}
+
+impl HeaderChain {
+    /// Start a header chain from `header`, which is trusted.
+    pub fn new(header: Header, fork_schedule: ForkSchedule) -> Result<Self, Exception> {
+        let mut chain = Self {
+            fork_schedule,
+            chain_spec: ChainSpec::MAINNET,
+            headers: Vec::new(),
+            hashes: Vec::new(),
+            index: BTreeMap::new(),
+        };
+        chain.push(header)?;
+        Ok(chain)
+    }
+
+    pub fn head(&self) -> &Header {
+        self.headers.last().unwrap()
+    }
+
+    pub fn head_hash(&self) -> &Hash32 {
+        self.hashes.last().unwrap()
+    }
+
+    /// The canonical header with `hash`.
+    pub fn get_header(&self, hash: &Hash32) -> Option<&Header> {
+        self.index.get(hash).map(|i| &self.headers[*i])
+    }
+
+    /// The canonical header at height `number`.
+    pub fn header_by_number(&self, number: Uint) -> Option<&Header> {
+        let first = self.headers[0].number;
+        let i = usize::try_from(number.checked_sub(first)?).ok()?;
+        self.headers.get(i)
+    }
+
+    /// Validate `header` against its parent and add it to the chain.
+    ///
+    /// A header whose parent is the head extends the chain. A header whose
+    /// parent is an earlier canonical header replaces the headers after its
+    /// parent, as the head of a new branch. Returns the hash of the header.
+    pub fn import_header(&mut self, header: Header) -> Result<Hash32, Exception> {
+        let &parent = self.index.get(&header.parent_hash).ok_or(Exception::InvalidBlock("unknown parent block"))?;
+        let name = self.fork_schedule.fork_for(&header);
+        let fork = fork_rules(name).ok_or(Exception::UnsupportedFork(name))?;
+        validate_header_with_parent(&header, &self.headers[parent], fork, &self.chain_spec)?;
+        self.truncate(parent + 1);
+        self.push(header)
+    }
+
+    /// Import `headers`, which are in order, stopping at the first invalid
+    /// one. Returns the number of headers imported.
+    pub fn import_headers(&mut self, headers: impl IntoIterator<Item = Header>) -> Result<usize, Exception> {
+        let mut count = 0;
+        for header in headers {
+            self.import_header(header)?;
+            count += 1;
+        }
+        Ok(count)
+    }
+
+    fn push(&mut self, header: Header) -> Result<Hash32, Exception> {
+        let hash = compute_header_hash(&header)?;
+        self.index.insert(hash.clone(), self.headers.len());
+        self.headers.push(header);
+        self.hashes.push(hash.clone());
+        Ok(hash)
+    }
+
+    fn truncate(&mut self, len: usize) {
+        for hash in self.hashes.drain(len..) {
+            self.index.remove(&hash);
+        }
+        self.headers.truncate(len);
+    }
+}
+
+impl BlockChain {
+    /// A header chain of the canonical headers of this chain, to be extended
+    /// with headers ahead of its head.
+    pub fn header_chain(&self) -> HeaderChain {
+        let mut chain = HeaderChain::new(self.blocks[0].header.clone(), self.fork_schedule.clone())
+            .expect("the genesis header was hashed before");
+        chain.chain_spec = self.chain_spec.clone();
+        for (block, hash) in self.blocks.iter().zip(&self.hashes).skip(1) {
+            chain.index.insert(hash.clone(), chain.headers.len());
+            chain.headers.push(block.header.clone());
+            chain.hashes.push(hash.clone());
+        }
+        chain
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::ethereum::{cancun::fork::fuzz::tests::valid_block, exceptions::Exception};
+
+    #[test]
+    fn import_headers() {
+        let (chain, block) = valid_block();
+        let mut headers = chain.header_chain();
+        let genesis_hash = headers.head_hash().clone();
+
+        let mut bad = block.header.clone();
+        bad.timestamp = headers.head().timestamp;
+        assert!(matches!(headers.import_header(bad), Err(Exception::InvalidBlock("header.timestamp <= parent_header.timestamp"))));
+        let mut bad = block.header.clone();
+        bad.excess_blob_gas = Some(1);
+        assert!(headers.import_header(bad).is_err());
+        let mut orphan = block.header.clone();
+        orphan.parent_hash = Default::default();
+        assert!(matches!(headers.import_header(orphan), Err(Exception::InvalidBlock("unknown parent block"))));
+
+        // Headers are checked without their bodies: a wrong state root goes
+        // unnoticed.
+        let mut unexecuted = block.header.clone();
+        unexecuted.state_root = Default::default();
+        let hash = headers.import_header(unexecuted).unwrap();
+        assert_eq!(headers.head_hash(), &hash);
+        assert_eq!(headers.header_by_number(1).unwrap().state_root, Default::default());
+
+        // A sibling replaces it as the head.
+        let good = headers.import_header(block.header.clone()).unwrap();
+        assert_eq!(headers.head_hash(), &good);
+        assert!(headers.get_header(&hash).is_none());
+        assert_eq!(headers.get_header(&genesis_hash).unwrap().number, 0);
+        assert!(headers.header_by_number(2).is_none());
+    }
+}
@@ -0,0 +1,160 @@
+//! Queries of the logs of the canonical chain, as `eth_getLogs` makes them.
+//!
+//! A `Filter` selects logs by the contract which emitted them, by their
+//! topics and by the range of blocks they are in. Each header and each
+//! receipt carries the bloom of its logs, so a block or a receipt whose
+//! bloom cannot contain what the filter asks for is skipped without looking
+//! at its logs.
+
+use crate::ethereum::{
+    cancun::{blocks::Log, bloom::bloom_contains, fork_types::{Address, Bloom}},
+    crypto::hash::Hash32,
+    ethereum_types::numeric::Uint,
+};
+
+use super::{receipts::RpcLog, BlockChain};
+
+/// Which logs to return.
+#[derive(Debug, Clone, Default)]
+pub struct Filter {
+    /// First block to search, the genesis block if `None`.
+    pub from_block: Option<Uint>,
+    /// Last block to search, the head if `None`.
+    pub to_block: Option<Uint>,
+    /// Logs emitted by any of these addresses, or by any address if empty.
+    pub addresses: Vec<Address>,
+    /// For each position, a topic which must be one of the given ones. An
+    /// empty list matches any topic, and logs must have at least as many
+    /// topics as there are positions.
+    pub topics: Vec<Vec<Hash32>>,
+}
+
+impl Filter {
+    /// Whether `log` is selected, ignoring the block range.
+    pub fn matches(&self, log: &Log) -> bool {
+        if !self.addresses.is_empty() && !self.addresses.contains(&log.address) {
+            return false;
+        }
+        log.topics.len() >= self.topics.len()
+            && self.topics.iter().zip(&log.topics).all(|(wanted, topic)| wanted.is_empty() || wanted.contains(topic))
+    }
+
+    /// Whether the logs summarised by `bloom` may contain a selected log.
+    pub fn matches_bloom(&self, bloom: &Bloom) -> bool {
+        let contains_any = |entries: &mut dyn Iterator<Item = &[u8]>| {
+            let mut entries = entries.peekable();
+            entries.peek().is_none() || entries.any(|entry| bloom_contains(&bloom.0.0, entry))
+        };
+        contains_any(&mut self.addresses.iter().map(|address| &address[..]))
+            && self.topics.iter().all(|wanted| contains_any(&mut wanted.iter().map(|topic| &topic[..])))
+    }
+
+    fn contains_block(&self, number: Uint) -> bool {
+        self.from_block.is_none_or(|from| number >= from) && self.to_block.is_none_or(|to| number <= to)
+    }
+}
+
+impl BlockChain {
+    /// The logs of the canonical chain selected by `filter`, in the order
+    /// they were emitted.
+    pub fn logs(&self, filter: &Filter) -> Vec<RpcLog<'_>> {
+        let mut logs = Vec::new();
+        for (block, b) in self.blocks.iter().enumerate() {
+            if !filter.contains_block(b.header.number) || !filter.matches_bloom(&b.header.bloom) {
+                continue;
+            }
+            for receipt in self.rpc_receipts(block) {
+                if filter.matches_bloom(&receipt.receipt.receipt.bloom) {
+                    logs.extend(receipt.logs().filter(|log| filter.matches(log.log)));
+                }
+            }
+        }
+        logs
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::ethereum::{
+        cancun::{
+            blocks::Log,
+            bloom::logs_bloom,
+            fork::{fuzz::tests::valid_block, BlockChain},
+            fork_types::{Address, Bloom},
+        },
+        crypto::hash::Hash32,
+    };
+
+    use super::Filter;
+
+    fn log(address: u8, topics: &[u8]) -> Log {
+        Log {
+            address: Address::from_be_bytes([address; 20]),
+            topics: topics.iter().map(|t| Hash32([*t; 32])).collect(),
+            ..Default::default()
+        }
+    }
+
+    fn filter(addresses: &[u8], topics: &[&[u8]]) -> Filter {
+        Filter {
+            addresses: addresses.iter().map(|a| Address::from_be_bytes([*a; 20])).collect(),
+            topics: topics.iter().map(|ts| ts.iter().map(|t| Hash32([*t; 32])).collect()).collect(),
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn matching() {
+        let l = log(0xaa, &[1, 2]);
+        let bloom = logs_bloom(std::slice::from_ref(&l));
+        for (f, matches) in [
+            (filter(&[], &[]), true),
+            (filter(&[0xaa, 0xbb], &[]), true),
+            (filter(&[0xbb], &[]), false),
+            (filter(&[], &[&[], &[2, 3]]), true),
+            (filter(&[], &[&[2], &[]]), false),
+            (filter(&[0xaa], &[&[1], &[2], &[]]), false),
+        ] {
+            assert_eq!(f.matches(&l), matches, "{f:?}");
+            // The bloom has no false negatives.
+            assert!(!matches || f.matches_bloom(&bloom), "{f:?}");
+        }
+        assert!(!filter(&[0xbb], &[]).matches_bloom(&bloom));
+        assert!(!filter(&[], &[&[3]]).matches_bloom(&Bloom::default()));
+    }
+
+    /// The chain of `valid_block`, with logs given to the receipts of its
+    /// second block.
+    fn chain_with_logs() -> BlockChain {
+        let (mut chain, block) = valid_block();
+        chain.import_block(block).unwrap();
+        let receipts = &mut chain.receipts[1];
+        receipts[0].receipt.logs = vec![log(0xaa, &[1])];
+        receipts[2].receipt.logs = vec![log(0xbb, &[1, 2]), log(0xaa, &[2])];
+        for r in receipts.iter_mut() {
+            r.receipt.bloom = logs_bloom(&r.receipt.logs);
+        }
+        let all: Vec<_> = receipts.iter().flat_map(|r| r.receipt.logs.clone()).collect();
+        chain.blocks[1].header.bloom = logs_bloom(&all);
+        chain
+    }
+
+    #[test]
+    fn chain_logs() {
+        let chain = chain_with_logs();
+        let positions = |f: &Filter| -> Vec<_> {
+            chain.logs(f).iter().map(|l| (l.block_number, l.transaction_index, l.log_index)).collect()
+        };
+        assert_eq!(positions(&filter(&[], &[])), [(1, 0, 0), (1, 2, 1), (1, 2, 2)]);
+        assert_eq!(positions(&filter(&[0xaa], &[])), [(1, 0, 0), (1, 2, 2)]);
+        assert_eq!(positions(&filter(&[], &[&[1], &[2]])), [(1, 2, 1)]);
+        assert_eq!(positions(&filter(&[0xcc], &[])), []);
+
+        // Only the genesis block is in the range.
+        let genesis = Filter { to_block: Some(0), ..Default::default() };
+        assert_eq!(positions(&genesis), []);
+        let head = Filter { from_block: Some(1), to_block: Some(1), ..Default::default() };
+        assert_eq!(chain.logs(&head).len(), 3);
+        assert_eq!(chain.logs(&head)[1].log.topics.len(), 2);
+    }
+}
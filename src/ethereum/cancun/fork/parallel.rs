@@ -0,0 +1,350 @@
+//! Optimistic parallel execution of the transactions of a block.
+//!
+//! `apply_body_parallel` computes the same result as `apply_body`, but
+//! first executes every transaction speculatively, on its own copy of the
+//! state before the block, across scoped threads. Each speculative run
+//! records the accounts and storage slots it reads and writes. The results
+//! are then committed in block order: a transaction whose reads do not
+//! overlap the writes of the transactions before it saw exactly the state
+//! it would have seen sequentially, so its writes are applied as they are.
+//! Any other transaction is executed again, serially, on the real state.
+//!
+//! Every transaction pays the coinbase, which would make them all conflict,
+//! so the fee is not recorded as an access and is added to the coinbase
+//! balance when the transaction is committed. Transactions which otherwise
+//! touch the coinbase account are always executed again.
+//!
+//! Blocks whose transactions mostly depend on each other run slower than
+//! with `apply_body`, since most of them execute twice.
+
+use std::{borrow::Cow, collections::BTreeSet, num::NonZeroUsize, sync::Arc, thread};
+
+use crate::ethereum::{
+    cancun::{
+        blocks::{Header, Withdrawal},
+        fork_types::{Account, Address, Root},
+        state::{
+            account_exists_and_is_empty, destroy_account, get_account, get_account_optional, get_storage,
+            set_account_balance, Access, AccessLog, State, StateDiff,
+        },
+        transactions::Transaction,
+        vm::code_cache::CodeCache,
+    },
+    chain_spec::ChainSpec,
+    crypto::hash::Hash32,
+    ethereum_types::{
+        bytes::Bytes32,
+        numeric::{Uint, U256, U64},
+    },
+    exceptions::Exception,
+    forks::ExecutionRules,
+};
+
+use super::{
+    apply_body, calculate_total_blob_gas, execute_transaction, ApplyBodyOutput, BlockEnvironment, BlockExecution,
+    BlockPrevalidator, ExecutedTransaction,
+};
+
+/// The result of executing a transaction on the state before the block.
+struct Speculation {
+    executed: Result<ExecutedTransaction, Exception>,
+    log: AccessLog,
+    /// The changes made by the transaction, apart from the fee paid to the
+    /// coinbase.
+    diff: StateDiff,
+    fee: U256,
+}
+
+/// The writes of the transactions committed so far.
+#[derive(Default)]
+struct Writes {
+    accesses: BTreeSet<Access>,
+    /// The accounts with any storage written.
+    storage: BTreeSet<Address>,
+}
+
+impl Writes {
+    fn extend(&mut self, writes: BTreeSet<Access>) {
+        for write in writes {
+            if let Access::Slot(address, _) | Access::Storage(address) = &write {
+                self.storage.insert(address.clone());
+            }
+            self.accesses.insert(write);
+        }
+    }
+
+    /// Whether `read` could have seen a different value had the writes
+    /// happened before it.
+    fn conflicts_with(&self, read: &Access) -> bool {
+        match read {
+            Access::Account(_) => self.accesses.contains(read),
+            Access::Slot(address, _) => {
+                self.accesses.contains(read) || self.accesses.contains(&Access::Storage(address.clone()))
+            }
+            Access::Storage(address) => self.storage.contains(address),
+        }
+    }
+}
+
+/// Executes a block like `apply_body`, running its transactions
+/// optimistically in parallel.
+///
+/// Parameters and result are those of `apply_body`. Blocks before
+/// Byzantium, whose receipts contain the state root after each transaction,
+/// are executed sequentially.
+pub fn apply_body_parallel(
+    state: &mut State,
+    block_hashes: &[Hash32],
+    coinbase: &Address,
+    block_number: &Uint,
+    base_fee_per_gas: &Option<Uint>,
+    block_gas_limit: &Uint,
+    block_time: &U256,
+    prev_randao: &Bytes32,
+    transactions: &[Transaction],
+    ommers: &[Header],
+    block_reward: Option<Uint>,
+    chain_id: U64,
+    withdrawals: Option<&[Withdrawal]>,
+    parent_beacon_block_root: &Option<Root>,
+    excess_blob_gas: &Option<U64>,
+    rules: ExecutionRules,
+    chain_spec: &ChainSpec,
+    code_cache: Option<Arc<CodeCache<BTreeSet<Uint>>>>,
+) -> Result<ApplyBodyOutput, Exception> {
+    if !rules.byzantium {
+        return apply_body(
+            state,
+            block_hashes,
+            coinbase,
+            block_number,
+            base_fee_per_gas,
+            block_gas_limit,
+            block_time,
+            prev_randao,
+            transactions,
+            ommers,
+            block_reward,
+            chain_id,
+            withdrawals,
+            parent_beacon_block_root,
+            excess_blob_gas,
+            rules,
+            chain_spec,
+            code_cache,
+        );
+    }
+
+    let env = BlockEnvironment {
+        block_hashes: block_hashes.to_vec(),
+        coinbase: coinbase.clone(),
+        number: *block_number,
+        base_fee_per_gas: *base_fee_per_gas,
+        gas_limit: *block_gas_limit,
+        time: *block_time,
+        prev_randao: prev_randao.clone(),
+        chain_id,
+        parent_beacon_block_root: parent_beacon_block_root.clone(),
+        excess_blob_gas: *excess_blob_gas,
+        rules,
+        chain_spec: chain_spec.clone(),
+        code_cache,
+    };
+    let mut execution = BlockExecution::begin(state, &env)?;
+
+    let prevalidator = BlockPrevalidator::default();
+    let prevalidated = prevalidator.prevalidate(transactions, chain_id, rules)?;
+    let senders: Vec<_> = prevalidated.into_iter().map(|prevalidated| prevalidated.sender).collect();
+    let speculations = speculate(state, &env, transactions, &senders, prevalidator.threads());
+
+    let mut writes = Writes::default();
+    for ((tx, sender), speculation) in transactions.iter().zip(senders).zip(speculations) {
+        let Speculation { executed, log, diff, fee } = speculation;
+        let fits = *tx.gas() <= execution.gas_available
+            && execution.blob_gas_used + calculate_total_blob_gas(tx) <= chain_spec.max_blob_gas_per_block;
+        let coinbase_account = Access::Account(coinbase.clone());
+        let valid = fits
+            && !log.reads.contains(&coinbase_account)
+            && !log.writes.contains(&coinbase_account)
+            && !log.reads.iter().any(|read| writes.conflicts_with(read));
+
+        match executed {
+            Ok(executed) if valid => {
+                state.apply_diff(&diff);
+                pay_fee(state, coinbase, fee, rules);
+                execution.record_transaction(state, &env, tx, executed)?;
+                writes.extend(log.writes);
+            }
+            _ => {
+                state.start_access_log();
+                let result = execution.apply_transaction(state, &env, tx, sender);
+                let log = state.take_access_log().unwrap_or_default();
+                result?;
+                writes.extend(log.writes);
+            }
+        }
+    }
+
+    execution.finish(state, &env, ommers, block_reward, withdrawals)
+}
+
+/// Execute each of `transactions` on `state`, splitting them into one
+/// contiguous chunk per thread.
+fn speculate(
+    state: &State,
+    env: &BlockEnvironment,
+    transactions: &[Transaction],
+    senders: &[Address],
+    threads: NonZeroUsize,
+) -> Vec<Speculation> {
+    if transactions.is_empty() {
+        return Vec::new();
+    }
+    let chunk_size = transactions.len().div_ceil(threads.get());
+    thread::scope(|scope| {
+        let handles: Vec<_> = transactions
+            .chunks(chunk_size)
+            .zip(senders.chunks(chunk_size))
+            .map(|(transactions, senders)| {
+                scope.spawn(move || {
+                    let mut scratch = state.clone();
+                    transactions
+                        .iter()
+                        .zip(senders)
+                        .map(|(tx, sender)| speculate_transaction(state, &mut scratch, env, tx, sender))
+                        .collect::<Vec<_>>()
+                })
+            })
+            .collect();
+        handles
+            .into_iter()
+            .flat_map(|handle| handle.join().unwrap_or_else(|payload| std::panic::resume_unwind(payload)))
+            .collect()
+    })
+}
+
+/// Execute `tx` on `scratch`, a copy of `base`, and leave `scratch` as it
+/// was.
+fn speculate_transaction(
+    base: &State,
+    scratch: &mut State,
+    env: &BlockEnvironment,
+    tx: &Transaction,
+    sender: &Address,
+) -> Speculation {
+    scratch.start_access_log();
+    let executed = execute_transaction(scratch, env, tx, sender.clone(), env.gas_limit, 0);
+    let log = scratch.take_access_log().unwrap_or_default();
+
+    // The fee is not logged, so the coinbase is added to what the
+    // transaction changed for `scratch` to be restored.
+    let mut diff = StateDiff::default();
+    let coinbase = Access::Account(env.coinbase.clone());
+    for write in log.writes.iter().chain([&coinbase]) {
+        match write {
+            Access::Account(address) => {
+                let old = get_account_optional(base, address);
+                let new = get_account_optional(scratch, address);
+                if old != new {
+                    diff.accounts.insert(address.clone(), (old.map(Cow::into_owned), new.map(Cow::into_owned)));
+                }
+            }
+            Access::Slot(address, key) => insert_slot(&mut diff, base, scratch, address, key),
+            Access::Storage(address) => {
+                let keys: BTreeSet<_> = base.storage(address).chain(scratch.storage(address)).map(|(key, _)| *key).collect();
+                for key in keys {
+                    insert_slot(&mut diff, base, scratch, address, &key);
+                }
+            }
+        }
+    }
+    scratch.revert_diff(&diff);
+
+    let balance = |account: Option<Account>| account.map_or(U256::ZERO, |account| account.balance);
+    let fee = match diff.accounts.remove(&env.coinbase) {
+        Some((old, new)) => balance(new) - balance(old),
+        None => U256::ZERO,
+    };
+    Speculation { executed, log, diff, fee }
+}
+
+fn insert_slot(diff: &mut StateDiff, base: &State, scratch: &State, address: &Address, key: &Bytes32) {
+    let old = get_storage(base, address, key);
+    let new = get_storage(scratch, address, key);
+    if old != new {
+        diff.storage.insert((address.clone(), *key), (old, new));
+    }
+}
+
+/// Pay `fee` to the coinbase as `process_transaction` does.
+fn pay_fee(state: &mut State, coinbase: &Address, fee: U256, rules: ExecutionRules) {
+    let balance = get_account(state, coinbase).balance + fee;
+    if !balance.is_zero() || !rules.spurious_dragon {
+        set_account_balance(state, coinbase, balance);
+    } else if account_exists_and_is_empty(state, coinbase) {
+        destroy_account(state, coinbase);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::ethereum::cancun::fork::{fuzz::tests::valid_block, get_last_256_block_hashes};
+
+    use super::*;
+
+    type ApplyBody = fn(
+        &mut State,
+        &[Hash32],
+        &Address,
+        &Uint,
+        &Option<Uint>,
+        &Uint,
+        &U256,
+        &Bytes32,
+        &[Transaction],
+        &[Header],
+        Option<Uint>,
+        U64,
+        Option<&[Withdrawal]>,
+        &Option<Root>,
+        &Option<U64>,
+        ExecutionRules,
+        &ChainSpec,
+        Option<Arc<CodeCache<BTreeSet<Uint>>>>,
+    ) -> Result<ApplyBodyOutput, Exception>;
+
+    #[test]
+    fn same_result_as_apply_body() {
+        let (chain, block) = valid_block();
+        let hashes = get_last_256_block_hashes(&chain);
+        let header = &block.header;
+        let run = |apply: ApplyBody| {
+            let mut state = chain.state.clone();
+            let output = apply(
+                &mut state,
+                &hashes,
+                &header.coinbase,
+                &header.number,
+                &header.base_fee_per_gas,
+                &header.gas_limit,
+                &header.timestamp,
+                &header.prev_randao,
+                &block.transactions,
+                &block.ommers,
+                None,
+                chain.chain_spec.chain_id,
+                block.withdrawals.as_deref(),
+                &header.parent_beacon_block_root,
+                &header.excess_blob_gas,
+                ExecutionRules::CANCUN,
+                &chain.chain_spec,
+                Some(chain.code_cache.clone()),
+            )
+            .unwrap();
+            (output.state_root, output.receipt_root, output.block_gas_used, output.block_logs_bloom)
+        };
+        let sequential = run(apply_body);
+        assert_eq!(sequential.0, block.header.state_root);
+        assert_eq!(run(apply_body_parallel), sequential);
+    }
+}
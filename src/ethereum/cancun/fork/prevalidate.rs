@@ -0,0 +1,303 @@
+//! Checks of the transactions of a block which do not depend on state.
+//!
+//! Most of what makes a transaction includable can be decided from the
+//! transaction alone: that its type is allowed by the fork, that it pays its
+//! intrinsic gas, that its fee caps and blob versioned hashes are well formed
+//! and that its signature recovers a sender. Recovering senders in
+//! particular is the most expensive part of validating a block before
+//! execution. `BlockPrevalidator` runs these checks for all the transactions
+//! of a block across scoped threads, so that the sequential execution in
+//! `apply_body` only has to check what depends on the state and the block.
+//!
+//! The result does not depend on the number of threads: the checks of a
+//! block fail with the error of its first invalid transaction.
+
+use std::{num::NonZeroUsize, thread};
+
+use crate::ethereum::{
+    cancun::{
+        fork_types::Address,
+        transactions::{
+            decode_transaction, recover_sender, validate_transaction, BlobTransaction, Either, FeeMarketTransaction,
+            LegacyTransaction, Transaction,
+        },
+    },
+    ethereum_rlp::rlp,
+    ethereum_types::{bytes::Bytes, numeric::U64},
+    exceptions::Exception,
+    forks::ExecutionRules,
+    prague::transactions::SetCodeTransaction,
+};
+
+use super::VERSIONED_HASH_VERSION_KZG;
+
+/// Fewer transactions than this are not worth a thread of their own.
+const MIN_TRANSACTIONS_PER_THREAD: usize = 8;
+
+/// What the stateless checks learn about a valid transaction.
+#[derive(Debug, Clone, PartialEq)]
+pub struct PrevalidatedTransaction {
+    pub sender: Address,
+}
+
+/// Runs the stateless checks of the transactions of a block on a number of
+/// threads.
+#[derive(Debug, Clone, Copy)]
+pub struct BlockPrevalidator {
+    threads: NonZeroUsize,
+}
+
+impl Default for BlockPrevalidator {
+    /// A prevalidator using all the parallelism available to the process.
+    fn default() -> Self {
+        Self::new(thread::available_parallelism().unwrap_or(NonZeroUsize::MIN))
+    }
+}
+
+impl BlockPrevalidator {
+    pub fn new(threads: NonZeroUsize) -> Self {
+        Self { threads }
+    }
+
+    pub fn threads(&self) -> NonZeroUsize {
+        self.threads
+    }
+
+    /// Check `transactions` for a chain with `chain_id` under `rules`.
+    ///
+    /// Returns what was learnt about each transaction, in the order of
+    /// `transactions`, or the error of the first invalid one.
+    pub fn prevalidate(
+        &self,
+        transactions: &[Transaction],
+        chain_id: U64,
+        rules: ExecutionRules,
+    ) -> Result<Vec<PrevalidatedTransaction>, Exception> {
+        self.map(transactions, |tx| prevalidate_transaction(tx, chain_id, rules))
+    }
+
+    /// Recover the senders of `transactions` alone, without the other
+    /// checks of `prevalidate`, for tools which already trust them.
+    pub fn recover_senders(
+        &self,
+        transactions: &[Transaction],
+        chain_id: U64,
+        rules: ExecutionRules,
+    ) -> Result<Vec<Address>, Exception> {
+        self.map(transactions, |tx| recover_sender(chain_id, tx, rules))
+    }
+
+    /// Decode transactions as they are encoded in a block body or an engine
+    /// API payload, then check them like `prevalidate`.
+    pub fn decode_and_prevalidate(
+        &self,
+        encoded: &[Bytes],
+        chain_id: U64,
+        rules: ExecutionRules,
+    ) -> Result<Vec<(Transaction, PrevalidatedTransaction)>, Exception> {
+        self.map(encoded, |encoded| {
+            // Legacy transactions are RLP lists, typed ones start with their
+            // type, which is below 0x80.
+            let tx = match encoded.first() {
+                Some(ty) if *ty < 0x80 => Either::B(encoded.clone()),
+                _ => Either::A(rlp::decode_to_strict::<LegacyTransaction>(encoded)?),
+            };
+            let tx = decode_transaction(tx)?;
+            let prevalidated = prevalidate_transaction(&tx, chain_id, rules)?;
+            Ok((tx, prevalidated))
+        })
+    }
+
+    /// Apply `check` to every item, splitting `items` into one contiguous
+    /// chunk per thread.
+    fn map<T: Sync, R: Send>(
+        &self,
+        items: &[T],
+        check: impl Fn(&T) -> Result<R, Exception> + Sync,
+    ) -> Result<Vec<R>, Exception> {
+        let threads = self.threads.get().min(items.len() / MIN_TRANSACTIONS_PER_THREAD);
+        if threads <= 1 {
+            return items.iter().map(check).collect();
+        }
+
+        let chunk_size = items.len().div_ceil(threads);
+        let check = &check;
+        let chunks: Vec<Result<Vec<R>, Exception>> = thread::scope(|scope| {
+            let handles: Vec<_> = items
+                .chunks(chunk_size)
+                .map(|chunk| scope.spawn(move || chunk.iter().map(check).collect()))
+                .collect();
+            handles
+                .into_iter()
+                .map(|handle| handle.join().unwrap_or_else(|payload| std::panic::resume_unwind(payload)))
+                .collect()
+        });
+
+        // Chunks are in order, so the first error is that of the first
+        // invalid item.
+        let mut results = Vec::with_capacity(items.len());
+        for chunk in chunks {
+            results.extend(chunk?);
+        }
+        Ok(results)
+    }
+}
+
+/// Recover the senders of `transactions` using all the parallelism
+/// available to the process.
+///
+/// Parameters
+/// ----------
+/// transactions :
+///     The transactions, in any order.
+/// chain_id :
+///     The ID of the current chain.
+/// rules :
+///     The rules of the fork of the block including the transactions.
+///
+/// Returns
+/// -------
+/// senders : `Vec<Address>`
+///     The sender of each transaction, in the order of `transactions`, or
+///     the error of the first invalid signature.
+pub fn recover_senders_parallel(
+    transactions: &[Transaction],
+    chain_id: U64,
+    rules: ExecutionRules,
+) -> Result<Vec<Address>, Exception> {
+    BlockPrevalidator::default().recover_senders(transactions, chain_id, rules)
+}
+
+/// The checks of `check_transaction` and `process_transaction` which only
+/// depend on the transaction.
+///
+/// Parameters
+/// ----------
+/// tx :
+///     The transaction.
+/// chain_id :
+///     The ID of the current chain.
+/// rules :
+///     The rules of the fork of the block including the transaction.
+///
+/// Returns
+/// -------
+/// prevalidated : `PrevalidatedTransaction`
+///     The sender of the transaction.
+///
+/// Raises
+/// ------
+/// InvalidBlock :
+///     If the transaction is not includable in any block of the fork.
+/// InvalidSignatureError :
+///     If the signature of the transaction is invalid.
+pub fn prevalidate_transaction(
+    tx: &Transaction,
+    chain_id: U64,
+    rules: ExecutionRules,
+) -> Result<PrevalidatedTransaction, Exception> {
+    if !rules.allows_transaction(tx) {
+        return Err(Exception::InvalidBlock("transaction type not allowed by the fork"));
+    }
+    if !validate_transaction(tx, rules) {
+        return Err(Exception::InvalidBlock("!validate_transaction(tx)"));
+    }
+
+    match tx {
+        Transaction::FeeMarketTransaction(FeeMarketTransaction { max_fee_per_gas, max_priority_fee_per_gas, .. }) |
+        Transaction::BlobTransaction(BlobTransaction { max_fee_per_gas, max_priority_fee_per_gas, .. }) |
+        Transaction::SetCodeTransaction(SetCodeTransaction { max_fee_per_gas, max_priority_fee_per_gas, .. })
+            if max_fee_per_gas < max_priority_fee_per_gas =>
+        {
+            return Err(Exception::InvalidBlock("tx.max_fee_per_gas < tx.max_priority_fee_per_gas"));
+        }
+        _ => {}
+    }
+    match tx {
+        Transaction::BlobTransaction(tx) => {
+            if tx.blob_versioned_hashes.is_empty() {
+                return Err(Exception::InvalidBlock("len(tx.blob_versioned_hashes) == 0"));
+            }
+            if tx.blob_versioned_hashes.iter().any(|hash| hash[0..1] != *VERSIONED_HASH_VERSION_KZG) {
+                return Err(Exception::InvalidBlock("blob_versioned_hash[0:1] != VERSIONED_HASH_VERSION_KZG"));
+            }
+        }
+        Transaction::SetCodeTransaction(tx) if tx.authorizations.is_empty() => {
+            return Err(Exception::InvalidBlock("empty authorization list"));
+        }
+        _ => {}
+    }
+
+    let sender = recover_sender(chain_id, tx, rules)?;
+    Ok(PrevalidatedTransaction { sender })
+}
+
+#[cfg(test)]
+mod tests {
+    use std::num::NonZeroUsize;
+
+    use crate::ethereum::{
+        cancun::{
+            fork::fuzz::tests::valid_block,
+            transactions::{encode_transaction, recover_sender, BlobTransaction, Either, Transaction},
+        },
+        ethereum_rlp::rlp,
+        ethereum_types::bytes::Bytes,
+        exceptions::Exception,
+        forks::ExecutionRules,
+    };
+
+    use super::BlockPrevalidator;
+
+    #[test]
+    fn same_result_on_any_number_of_threads() {
+        let (chain, block) = valid_block();
+        let chain_id = chain.chain_spec.chain_id;
+        // Enough transactions for several threads.
+        let transactions: Vec<_> = block.transactions.iter().cycle().take(40).cloned().collect();
+        let senders: Vec<_> = transactions.iter().map(|tx| recover_sender(chain_id, tx, ExecutionRules::CANCUN).unwrap()).collect();
+
+        for threads in [1, 2, 3, 16] {
+            let prevalidator = BlockPrevalidator::new(NonZeroUsize::new(threads).unwrap());
+            let prevalidated = prevalidator.prevalidate(&transactions, chain_id, ExecutionRules::CANCUN).unwrap();
+            assert_eq!(prevalidated.iter().map(|p| p.sender.clone()).collect::<Vec<_>>(), senders);
+            assert_eq!(prevalidator.recover_senders(&transactions, chain_id, ExecutionRules::CANCUN).unwrap(), senders);
+
+            // The first invalid transaction decides the error.
+            let mut invalid = transactions.clone();
+            invalid[37] = Transaction::BlobTransaction(BlobTransaction::default());
+            if let Transaction::LegacyTransaction(tx) = &mut invalid[30] {
+                tx.gas = 0;
+            } else {
+                panic!("not a legacy transaction");
+            }
+            assert!(matches!(
+                prevalidator.prevalidate(&invalid, chain_id, ExecutionRules::CANCUN),
+                Err(Exception::InvalidBlock("!validate_transaction(tx)"))
+            ));
+        }
+    }
+
+    #[test]
+    fn decode() {
+        let (chain, block) = valid_block();
+        let chain_id = chain.chain_spec.chain_id;
+        let encoded_bytes = |tx: &Transaction| match encode_transaction(tx).unwrap() {
+            Either::A(tx) => rlp::encode(&tx).unwrap(),
+            Either::B(tx) => tx,
+        };
+        let encoded: Vec<_> = block.transactions.iter().map(encoded_bytes).collect();
+        let decoded = BlockPrevalidator::default()
+            .decode_and_prevalidate(&encoded, chain_id, ExecutionRules::CANCUN)
+            .unwrap();
+        for ((tx, prevalidated), original) in decoded.iter().zip(&block.transactions) {
+            assert_eq!(encoded_bytes(tx), encoded_bytes(original));
+            assert_eq!(prevalidated.sender, recover_sender(chain_id, original, ExecutionRules::CANCUN).unwrap());
+        }
+
+        assert!(matches!(
+            BlockPrevalidator::default().decode_and_prevalidate(&[Bytes(vec![0x05])], chain_id, ExecutionRules::CANCUN),
+            Err(Exception::TransactionTypeError { transaction_type: 5 })
+        ));
+    }
+}
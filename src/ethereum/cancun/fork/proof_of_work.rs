@@ -0,0 +1,325 @@
+//! Difficulty, ommers and block rewards of the forks before the merge.
+//!
+//! Until Paris, the difficulty of a block follows from its parent by one of
+//! three formulas, each fork pushing the difficulty bomb further back, and
+//! the miner of a block and of each of its ommers are paid a block reward
+//! after its transactions. [`ProofOfWork`] holds what changed from one fork
+//! to the next, as `Fork::proof_of_work` returns it.
+//!
+//! The ethash seal, `nonce` and `mixHash`, is not verified: checking it
+//! needs the ethash dataset of the epoch, and a block whose seal is wrong
+//! could only come from a chain which was never accepted by the network.
+
+use std::collections::BTreeSet;
+
+use crate::ethereum::{
+    cancun::{
+        blocks::Header,
+        fork_types::Address,
+        state::{get_account, set_account_balance, State},
+    },
+    crypto::hash::keccak256,
+    ethereum_rlp::rlp,
+    ethereum_types::numeric::{Uint, U256},
+    exceptions::Exception,
+    forks::Fork,
+};
+
+use super::{compute_header_hash, validate_header, BlockChain, EMPTY_OMMER_HASH};
+
+/// The difficulty never drops below this.
+pub const MINIMUM_DIFFICULTY: Uint = 131072;
+/// Oldest ommer a block can include, counted in blocks back from it.
+pub const MAX_OMMER_DEPTH: Uint = 6;
+/// Most ommers a block can include.
+const MAX_OMMERS: usize = 2;
+/// Blocks between doublings of the difficulty bomb.
+const BOMB_PERIOD: Uint = 100_000;
+const ETHER: Uint = 10_u128.pow(18);
+
+/// How the difficulty of a block follows from its parent.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DifficultyFormula {
+    /// Up by 1/2048 if the block came within 13 seconds of its parent,
+    /// down by as much otherwise.
+    Frontier,
+    /// Down by 1/2048 for every 10 seconds after the first 10 (EIP-2).
+    Homestead,
+    /// Down by 1/2048 for every 9 seconds after the first 9, or the first
+    /// 18 if the parent has ommers (EIP-100).
+    Byzantium,
+}
+
+/// What differs between the proof of work forks.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ProofOfWork {
+    pub difficulty: DifficultyFormula,
+    /// Blocks by which the difficulty bomb is set back.
+    pub bomb_delay: Uint,
+    /// Wei paid to the miner of a block.
+    pub block_reward: Uint,
+}
+
+impl ProofOfWork {
+    pub const FRONTIER: Self = Self { difficulty: DifficultyFormula::Frontier, bomb_delay: 0, block_reward: 5 * ETHER };
+
+    /// Homestead to Spurious Dragon.
+    pub const HOMESTEAD: Self = Self { difficulty: DifficultyFormula::Homestead, ..Self::FRONTIER };
+
+    /// EIP-649.
+    pub const BYZANTIUM: Self =
+        Self { difficulty: DifficultyFormula::Byzantium, bomb_delay: 3_000_000, block_reward: 3 * ETHER };
+
+    /// Constantinople and Istanbul (EIP-1234).
+    pub const CONSTANTINOPLE: Self = Self { bomb_delay: 5_000_000, block_reward: 2 * ETHER, ..Self::BYZANTIUM };
+
+    /// Muir Glacier and Berlin (EIP-2384).
+    pub const MUIR_GLACIER: Self = Self { bomb_delay: 9_000_000, ..Self::CONSTANTINOPLE };
+
+    /// EIP-3554.
+    pub const LONDON: Self = Self { bomb_delay: 9_700_000, ..Self::CONSTANTINOPLE };
+
+    /// EIP-4345.
+    pub const ARROW_GLACIER: Self = Self { bomb_delay: 10_700_000, ..Self::CONSTANTINOPLE };
+
+    /// EIP-5133.
+    pub const GRAY_GLACIER: Self = Self { bomb_delay: 11_400_000, ..Self::CONSTANTINOPLE };
+
+    /// Computes the difficulty a block must have.
+    ///
+    /// Parameters
+    /// ----------
+    /// block_number :
+    ///     Block number of the block.
+    /// block_timestamp :
+    ///     Timestamp of the block.
+    /// parent_timestamp :
+    ///     Timestamp of the parent block.
+    /// parent_difficulty :
+    ///     Difficulty of the parent block.
+    /// parent_has_ommers :
+    ///     Whether the parent block includes ommers.
+    ///
+    /// Returns
+    /// -------
+    /// difficulty : `ethereum.base_types.Uint`
+    ///     Computed difficulty for a block.
+    pub fn calculate_block_difficulty(
+        &self,
+        block_number: Uint,
+        block_timestamp: U256,
+        parent_timestamp: U256,
+        parent_difficulty: Uint,
+        parent_has_ommers: bool,
+    ) -> Uint {
+        // Past 1000 seconds the adjustment is the same.
+        let seconds = if block_timestamp > parent_timestamp {
+            (block_timestamp - parent_timestamp).to_uint().unwrap_or(Uint::MAX).min(1000) as i128
+        } else {
+            0
+        };
+        let parent_difficulty = parent_difficulty.min(i128::MAX as Uint / 2) as i128;
+        let max_adjustment_delta = parent_difficulty / 2048;
+        let offset = match self.difficulty {
+            DifficultyFormula::Frontier if seconds < 13 => max_adjustment_delta,
+            DifficultyFormula::Frontier => -max_adjustment_delta,
+            DifficultyFormula::Homestead => max_adjustment_delta * (1 - seconds / 10).max(-99),
+            DifficultyFormula::Byzantium => {
+                let target = if parent_has_ommers { 2 } else { 1 };
+                max_adjustment_delta * (target - seconds / 9).max(-99)
+            }
+        };
+        let mut difficulty = parent_difficulty + offset;
+
+        // The bomb doubles every 100000 blocks, from the third period after
+        // the delay.
+        let periods = block_number.saturating_sub(self.bomb_delay) / BOMB_PERIOD;
+        if periods >= 2 {
+            difficulty = difficulty.saturating_add(1_i128.checked_shl((periods - 2).min(126) as u32).unwrap_or(0));
+        }
+        difficulty.max(MINIMUM_DIFFICULTY as i128) as Uint
+    }
+}
+
+/// Validates the ommers mentioned in the block.
+///
+/// An ommer block is a block that wasn't canonically added to the
+/// blockchain because it wasn't validated as fast as the canonical block
+/// but was mined at the same time.
+///
+/// To be considered valid, the ommers must adhere to the rules defined in
+/// the Ethereum protocol. The maximum amount of ommers is 2 per block and
+/// there cannot be duplicate ommers in a block. Many of the other ommer
+/// constraints are listed in the in-line comments of this function.
+///
+/// Parameters
+/// ----------
+/// ommers :
+///     List of ommers mentioned in the current block.
+/// block_header:
+///     The header of current block.
+/// chain :
+///     History and current state.
+/// fork :
+///     The fork of the block, whose rules the ommer headers must follow.
+pub fn validate_ommers(ommers: &[Header], block_header: &Header, chain: &BlockChain, fork: &dyn Fork) -> Result<(), Exception> {
+    let block_hash = compute_header_hash(block_header)?;
+    if keccak256(&rlp::encode(&ommers.to_vec())?) != block_header.ommers_hash {
+        return Err(Exception::InvalidBlock("keccak256(rlp.encode(ommers)) != block_header.ommers_hash"));
+    }
+    if ommers.is_empty() {
+        return Ok(());
+    }
+
+    // Check that each ommer satisfies the constraints of a header
+    for ommer in ommers {
+        if ommer.number < 1 || ommer.number >= block_header.number {
+            return Err(Exception::InvalidBlock("ommer.number out of range"));
+        }
+        let ommer_age = block_header.number - ommer.number;
+        if ommer_age > MAX_OMMER_DEPTH {
+            return Err(Exception::InvalidBlock("ommer_age > MAX_OMMER_DEPTH"));
+        }
+        let ommer_parent_header = chain
+            .blocks
+            .len()
+            .checked_sub(ommer_age as usize + 1)
+            .map(|i| &chain.blocks[i].header)
+            .ok_or(Exception::InvalidBlock("ommer parent is not known"))?;
+        validate_header(ommer, ommer_parent_header, fork, &chain.chain_spec)?;
+    }
+
+    // Check that there can be only at most 2 ommers for a block.
+    if ommers.len() > MAX_OMMERS {
+        return Err(Exception::InvalidBlock("len(ommers) > 2"));
+    }
+
+    let ommers_hashes = ommers.iter().map(compute_header_hash).collect::<Result<Vec<_>, _>>()?;
+    // Check that there are no duplicates in the ommers of current block
+    if ommers_hashes.iter().collect::<BTreeSet<_>>().len() != ommers_hashes.len() {
+        return Err(Exception::InvalidBlock("duplicate ommers"));
+    }
+
+    let start = chain.blocks.len().saturating_sub(MAX_OMMER_DEPTH as usize + 1);
+    let recent_canonical_blocks = &chain.blocks[start..];
+    let recent_canonical_block_hashes = recent_canonical_blocks
+        .iter()
+        .map(|block| compute_header_hash(&block.header))
+        .collect::<Result<BTreeSet<_>, _>>()?;
+    let mut recent_ommers_hashes = BTreeSet::new();
+    for block in recent_canonical_blocks {
+        for ommer in &block.ommers {
+            recent_ommers_hashes.insert(compute_header_hash(ommer)?);
+        }
+    }
+
+    for (ommer, ommer_hash) in ommers.iter().zip(&ommers_hashes) {
+        // The current block shouldn't count itself as an ommer
+        if *ommer_hash == block_hash {
+            return Err(Exception::InvalidBlock("ommer_hash == block_hash"));
+        }
+        // An ommer must not be one of the recent canonical blocks
+        if recent_canonical_block_hashes.contains(ommer_hash) {
+            return Err(Exception::InvalidBlock("ommer_hash in recent_canonical_block_hashes"));
+        }
+        // An ommer must not have been included before
+        if recent_ommers_hashes.contains(ommer_hash) {
+            return Err(Exception::InvalidBlock("ommer_hash in recent_ommers_hashes"));
+        }
+        // An ommer must be a sibling of a recent canonical block
+        if !recent_canonical_block_hashes.contains(&ommer.parent_hash) {
+            return Err(Exception::InvalidBlock("ommer.parent_hash not in recent_canonical_block_hashes"));
+        }
+        if ommer.parent_hash == block_header.parent_hash {
+            return Err(Exception::InvalidBlock("ommer.parent_hash == block_header.parent_hash"));
+        }
+    }
+    Ok(())
+}
+
+/// Pay rewards to the block miner as well as the ommers miners.
+///
+/// The miner of the canonical block is rewarded with the predetermined
+/// block reward, ``BLOCK_REWARD``, plus a variable award based off of the
+/// number of ommer blocks that were mined around the same time, and included
+/// in the canonical block's header. An ommer block is a block that wasn't
+/// added to the canonical blockchain because it wasn't validated as fast as
+/// the accepted block but was mined at the same time. Although not all blocks
+/// that are mined are added to the canonical chain, miners are still paid a
+/// reward for their efforts. This reward is called an ommer reward and is
+/// calculated based on the number associated with the ommer block that they
+/// mined.
+///
+/// Parameters
+/// ----------
+/// state :
+///     Current account state.
+/// block_reward :
+///     Reward of the fork for mining a block.
+/// block_number :
+///     Position of the block within the chain.
+/// coinbase :
+///     Address of account which receives block reward and transaction fees.
+/// ommers :
+///     List of ommers mentioned in the current block.
+pub fn pay_rewards(state: &mut State, block_reward: Uint, block_number: Uint, coinbase: &Address, ommers: &[Header]) {
+    let miner_reward = block_reward + ommers.len() as Uint * (block_reward / 32);
+    create_ether(state, coinbase, miner_reward);
+
+    for ommer in ommers {
+        // Ommers have already been validated, their age is at most 6.
+        let ommer_age = block_number - ommer.number;
+        let ommer_miner_reward = (8 - ommer_age) * block_reward / 8;
+        create_ether(state, &ommer.coinbase, ommer_miner_reward);
+    }
+}
+
+/// Add newly created ether to an account.
+fn create_ether(state: &mut State, address: &Address, amount: Uint) {
+    let balance = get_account(state, address).balance + U256::from_uint(amount);
+    set_account_balance(state, address, balance);
+}
+
+/// Whether the block with `header` includes ommers.
+pub(crate) fn has_ommers(header: &Header) -> bool {
+    header.ommers_hash != EMPTY_OMMER_HASH
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::ethereum::ethereum_types::numeric::U256;
+
+    use super::{ProofOfWork, MINIMUM_DIFFICULTY};
+
+    #[test]
+    fn difficulty() {
+        let t = |seconds: u64| (U256::from(1000 + seconds), U256::from(1000_u64));
+        let parent = 2048 * 1_000_000;
+
+        // Mainnet block 1: 13 seconds or more lowers the difficulty.
+        let (time, parent_time) = t(12);
+        assert_eq!(ProofOfWork::FRONTIER.calculate_block_difficulty(1, time, parent_time, parent, false), parent + 1_000_000);
+        let (time, parent_time) = t(13);
+        assert_eq!(ProofOfWork::FRONTIER.calculate_block_difficulty(1, time, parent_time, parent, false), parent - 1_000_000);
+
+        let (time, parent_time) = t(25);
+        assert_eq!(ProofOfWork::HOMESTEAD.calculate_block_difficulty(1, time, parent_time, parent, false), parent - 1_000_000);
+        let (time, parent_time) = t(5000);
+        assert_eq!(ProofOfWork::HOMESTEAD.calculate_block_difficulty(1, time, parent_time, parent, false), parent - 99_000_000);
+
+        // Ommers in the parent raise the target by 9 seconds.
+        let (time, parent_time) = t(9);
+        assert_eq!(ProofOfWork::BYZANTIUM.calculate_block_difficulty(1, time, parent_time, parent, false), parent);
+        assert_eq!(ProofOfWork::BYZANTIUM.calculate_block_difficulty(1, time, parent_time, parent, true), parent + 1_000_000);
+
+        // The bomb, from the third period after the delay.
+        let (time, parent_time) = t(9);
+        let at = |number| ProofOfWork::GRAY_GLACIER.calculate_block_difficulty(number, time, parent_time, parent, false);
+        assert_eq!(at(11_599_999), parent);
+        assert_eq!(at(11_600_000), parent + 1);
+        assert_eq!(at(12_000_000), parent + 16);
+        assert_eq!(ProofOfWork::FRONTIER.calculate_block_difficulty(200_000, t(20).0, t(20).1, parent, false), parent - 1_000_000 + 1);
+
+        assert_eq!(ProofOfWork::HOMESTEAD.calculate_block_difficulty(1, t(5000).0, t(5000).1, MINIMUM_DIFFICULTY, false), MINIMUM_DIFFICULTY);
+    }
+}
@@ -0,0 +1,325 @@
+//! Receipts of executed transactions and their JSON-RPC form.
+//!
+//! The specification only keeps the root of the receipts of each block. To
+//! answer `eth_getTransactionReceipt` and `eth_getBlockReceipts` the chain
+//! also keeps the receipts of every canonical block, together with what the
+//! RPC reports alongside them, and an index from transaction hash to the
+//! position of the transaction in the chain.
+//!
+//! For blocks which were not executed here, such as those of an era1 file,
+//! `transaction_receipts` derives the same from the consensus receipts and
+//! the block, and `rpc_receipts` gives them their RPC form.
+
+use crate::{
+    ethereum::{
+        cancun::{
+            blocks::{Block, Log, Receipt},
+            fork_types::Address,
+            transactions::{encode_raw_transaction, recover_sender, Transaction},
+            utils::compute_contract_address,
+            vm::gas::{calculate_blob_gas_price, calculate_total_blob_gas},
+        },
+        crypto::hash::{keccak256, Hash32},
+        ethereum_types::numeric::{Uint, U64},
+        exceptions::Exception,
+        forks::ExecutionRules,
+    },
+    json::{Encoder, JsonEncode},
+};
+
+use super::BlockChain;
+
+/// A receipt with what is known about its transaction once it has executed.
+#[derive(Debug, Clone, Default)]
+pub struct TransactionReceipt {
+    pub receipt: Receipt,
+    pub transaction_hash: Hash32,
+    /// Zero for legacy transactions.
+    pub transaction_type: u8,
+    pub from: Address,
+    pub to: Option<Address>,
+    /// Address of the contract created by a transaction without `to`.
+    pub contract_address: Option<Address>,
+    /// Gas used by this transaction alone, net of refunds.
+    pub gas_used: Uint,
+    pub effective_gas_price: Uint,
+    /// Blob gas used and its price, for blob transactions.
+    pub blob_gas: Option<(Uint, Uint)>,
+}
+
+/// A log with its position in the chain.
+#[derive(Debug, Clone, Copy)]
+pub struct RpcLog<'a> {
+    pub log: &'a Log,
+    pub block_hash: &'a Hash32,
+    pub block_number: Uint,
+    pub transaction_hash: &'a Hash32,
+    pub transaction_index: usize,
+    /// Position of the log among all the logs of the block.
+    pub log_index: usize,
+    /// The block has been removed from the canonical chain by a reorg.
+    pub removed: bool,
+}
+
+/// A receipt with its position in the chain.
+#[derive(Debug, Clone, Copy)]
+pub struct RpcReceipt<'a> {
+    pub receipt: &'a TransactionReceipt,
+    pub block_hash: &'a Hash32,
+    pub block_number: Uint,
+    pub transaction_index: usize,
+    /// `log_index` of the first log of the transaction.
+    pub first_log_index: usize,
+}
+
+impl<'a> RpcReceipt<'a> {
+    /// The logs of the transaction.
+    pub fn logs(&self) -> impl Iterator<Item = RpcLog<'a>> + '_ {
+        self.receipt.receipt.logs.iter().enumerate().map(|(i, log)| RpcLog {
+            log,
+            block_hash: self.block_hash,
+            block_number: self.block_number,
+            transaction_hash: &self.receipt.transaction_hash,
+            transaction_index: self.transaction_index,
+            log_index: self.first_log_index + i,
+            removed: false,
+        })
+    }
+}
+
+impl JsonEncode for RpcLog<'_> {
+    fn encode_json(&self, encoder: &mut Encoder) {
+        let mut o = encoder.object();
+        o.field("address", &self.log.address);
+        o.field("topics", &self.log.topics);
+        o.field("data", &self.log.data);
+        o.field("blockNumber", &self.block_number);
+        o.field("transactionHash", self.transaction_hash);
+        o.field("transactionIndex", &(self.transaction_index as Uint));
+        o.field("blockHash", self.block_hash);
+        o.field("logIndex", &(self.log_index as Uint));
+        o.field("removed", &self.removed);
+        o.end();
+    }
+}
+
+impl JsonEncode for RpcReceipt<'_> {
+    fn encode_json(&self, encoder: &mut Encoder) {
+        let r = self.receipt;
+        let mut o = encoder.object();
+        o.field("blockHash", self.block_hash);
+        o.field("blockNumber", &self.block_number);
+        o.field("contractAddress", &r.contract_address);
+        o.field("cumulativeGasUsed", &r.receipt.cumulative_gas_used);
+        o.field("effectiveGasPrice", &r.effective_gas_price);
+        o.field("from", &r.from);
+        o.field("gasUsed", &r.gas_used);
+        o.key("logs");
+        let mut logs = o.encoder.array();
+        for log in self.logs() {
+            log.encode_json(logs.next());
+        }
+        logs.end();
+        o.field("logsBloom", &r.receipt.bloom);
+        o.field("status", &(r.receipt.succeeded as U64));
+        o.field("to", &r.to);
+        o.field("transactionHash", &r.transaction_hash);
+        o.field("transactionIndex", &(self.transaction_index as Uint));
+        o.field("type", &(r.transaction_type as U64));
+        if let Some((blob_gas_used, blob_gas_price)) = &r.blob_gas {
+            o.field("blobGasUsed", blob_gas_used);
+            o.field("blobGasPrice", blob_gas_price);
+        }
+        o.end();
+    }
+}
+
+/// The receipts of the transactions of `block`, from their consensus
+/// `receipts`: the sender is recovered from the signature, the gas used is
+/// the difference of the cumulative gas used, and the prices are those of
+/// the block's header.
+pub fn transaction_receipts(
+    block: &Block,
+    receipts: &[Receipt],
+    chain_id: U64,
+    rules: ExecutionRules,
+) -> Result<Vec<TransactionReceipt>, Exception> {
+    if receipts.len() != block.transactions.len() {
+        return Err(Exception::InvalidBlock("one receipt per transaction"));
+    }
+    let header = &block.header;
+    let mut cumulative_gas_used = 0;
+    block
+        .transactions
+        .iter()
+        .zip(receipts)
+        .map(|(tx, receipt)| {
+            let encoded = encode_raw_transaction(tx)?;
+            let from = recover_sender(chain_id, tx, rules)?;
+            let to = tx.to();
+            let contract_address = match to {
+                Some(_) => None,
+                None => Some(compute_contract_address(&from, tx.nonce().to_uint()?)?),
+            };
+            let gas_used = receipt
+                .cumulative_gas_used
+                .checked_sub(cumulative_gas_used)
+                .ok_or(Exception::InvalidBlock("cumulative gas used decreases"))?;
+            cumulative_gas_used = receipt.cumulative_gas_used;
+            Ok(TransactionReceipt {
+                receipt: receipt.clone(),
+                transaction_hash: keccak256(&encoded),
+                transaction_type: if encoded.first().is_some_and(|b| *b < 0x80) { encoded[0] } else { 0 },
+                from,
+                to,
+                contract_address,
+                gas_used,
+                effective_gas_price: tx.effective_gas_price(header.base_fee_per_gas),
+                blob_gas: matches!(tx, Transaction::BlobTransaction(_)).then(|| {
+                    (calculate_total_blob_gas(tx), calculate_blob_gas_price(header.excess_blob_gas.unwrap_or_default()))
+                }),
+            })
+        })
+        .collect()
+}
+
+/// The receipts of the block with `block_hash` and `block_number`, in their
+/// RPC form, with the logs numbered across the block.
+pub fn rpc_receipts<'a>(
+    receipts: &'a [TransactionReceipt],
+    block_hash: &'a Hash32,
+    block_number: Uint,
+) -> impl Iterator<Item = RpcReceipt<'a>> {
+    let mut first_log_index = 0;
+    receipts.iter().enumerate().map(move |(transaction_index, receipt)| {
+        let rpc = RpcReceipt { receipt, block_hash, block_number, transaction_index, first_log_index };
+        first_log_index += receipt.receipt.logs.len();
+        rpc
+    })
+}
+
+impl BlockChain {
+    /// The receipt of the canonical transaction with `transaction_hash`.
+    pub fn receipt(&self, transaction_hash: &Hash32) -> Option<RpcReceipt<'_>> {
+        let &(block, index) = self.transaction_index.get(transaction_hash)?;
+        self.rpc_receipts(block).nth(index)
+    }
+
+    /// The receipts of the canonical block with `block_hash`.
+    pub fn block_receipts(&self, block_hash: &Hash32) -> Option<Vec<RpcReceipt<'_>>> {
+        let block = self.canonical_index(block_hash)?;
+        Some(self.rpc_receipts(block).collect())
+    }
+
+    /// The receipts of the canonical block at `block`.
+    pub(super) fn rpc_receipts(&self, block: usize) -> impl Iterator<Item = RpcReceipt<'_>> {
+        rpc_receipts(&self.receipts[block], &self.hashes[block], self.blocks[block].header.number)
+    }
+
+    /// Record the receipts of the block just added to the head.
+    pub(super) fn push_receipts(&mut self, receipts: Vec<TransactionReceipt>) {
+        let block = self.receipts.len();
+        for (i, r) in receipts.iter().enumerate() {
+            self.transaction_index.insert(r.transaction_hash.clone(), (block, i));
+        }
+        self.receipts.push(receipts);
+    }
+
+    /// Forget the receipts of the block at the head, which is being removed.
+    pub(super) fn pop_receipts(&mut self) {
+        for r in self.receipts.pop().into_iter().flatten() {
+            self.transaction_index.remove(&r.transaction_hash);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{
+        ethereum::{
+            cancun::{
+                blocks::Log,
+                fork::{compute_header_hash, fuzz::tests::valid_block},
+                fork_types::Address,
+            },
+            crypto::hash::Hash32,
+            forks::ExecutionRules,
+        },
+        json::to_json,
+    };
+
+    use super::{rpc_receipts, transaction_receipts, RpcReceipt, TransactionReceipt};
+
+    #[test]
+    fn receipts_of_imported_block() {
+        let (mut chain, block) = valid_block();
+        let hash = compute_header_hash(&block.header).unwrap();
+        assert_eq!(block.hash().unwrap(), hash);
+        let transaction_hashes = block.transactions.iter().map(|tx| tx.hash().unwrap()).collect::<Vec<_>>();
+        chain.import_block(block).unwrap();
+
+        let receipts = chain.block_receipts(&hash).unwrap();
+        assert_eq!(receipts.len(), 3);
+        for (receipt, transaction_hash) in receipts.iter().zip(&transaction_hashes) {
+            assert_eq!(&receipt.receipt.transaction_hash, transaction_hash);
+        }
+        assert_eq!(receipts[0].receipt.gas_used, 21_000);
+        assert_eq!(receipts[0].receipt.transaction_type, 0);
+        assert_eq!(receipts[1].receipt.transaction_type, 2);
+        assert_eq!(receipts[2].receipt.receipt.cumulative_gas_used, chain.blocks[1].header.gas_used);
+
+        let transaction_hash = receipts[1].receipt.transaction_hash.clone();
+        let receipt = chain.receipt(&transaction_hash).unwrap();
+        assert_eq!(receipt.transaction_index, 1);
+        assert_eq!(receipt.block_hash, &hash);
+
+        // Rewinding the block forgets its receipts.
+        let genesis = compute_header_hash(&chain.blocks[0].header).unwrap();
+        chain.set_head(&genesis).unwrap();
+        assert!(chain.receipt(&transaction_hash).is_none());
+        assert!(chain.block_receipts(&hash).is_none());
+        assert_eq!(chain.block_receipts(&genesis).unwrap().len(), 0);
+    }
+
+    #[test]
+    fn receipts_from_consensus_receipts() {
+        let (mut chain, block) = valid_block();
+        let hash = compute_header_hash(&block.header).unwrap();
+        chain.import_block(block.clone()).unwrap();
+        let executed = chain.block_receipts(&hash).unwrap();
+
+        let consensus = executed.iter().map(|r| r.receipt.receipt.clone()).collect::<Vec<_>>();
+        let chain_id = chain.chain_spec.chain_id;
+        let derived = transaction_receipts(&block, &consensus, chain_id, ExecutionRules::CANCUN).unwrap();
+        let rpc = rpc_receipts(&derived, &hash, block.header.number).collect::<Vec<_>>();
+        assert_eq!(rpc.len(), executed.len());
+        for (derived, executed) in rpc.iter().zip(&executed) {
+            assert_eq!(to_json(derived), to_json(executed));
+        }
+        assert!(transaction_receipts(&block, &consensus[1..], chain_id, ExecutionRules::CANCUN).is_err());
+    }
+
+    #[test]
+    fn rpc_json() {
+        let log = Log { address: Address::from_be_bytes([0xaa; 20]), topics: vec![Hash32([1; 32])], ..Default::default() };
+        let mut receipt = TransactionReceipt { gas_used: 30_000, effective_gas_price: 7, ..Default::default() };
+        receipt.receipt.succeeded = true;
+        receipt.receipt.cumulative_gas_used = 50_000;
+        receipt.receipt.logs = vec![log.clone(), log];
+        receipt.contract_address = Some(Address::from_be_bytes([0xcc; 20]));
+        let block_hash = Hash32([2; 32]);
+        let rpc = RpcReceipt { receipt: &receipt, block_hash: &block_hash, block_number: 5, transaction_index: 1, first_log_index: 3 };
+
+        let json = to_json(&rpc);
+        let address = format!("0x{}", "aa".repeat(20));
+        assert!(json.starts_with(&format!("{{\"blockHash\":\"0x{}\",\"blockNumber\":\"0x5\",", "02".repeat(32))), "{json}");
+        assert!(json.contains(&format!("\"contractAddress\":\"0x{}\"", "cc".repeat(20))), "{json}");
+        assert!(json.contains("\"cumulativeGasUsed\":\"0xc350\",\"effectiveGasPrice\":\"0x7\""), "{json}");
+        assert!(json.contains(&format!("\"logs\":[{{\"address\":\"{address}\",")), "{json}");
+        assert!(json.contains("\"transactionIndex\":\"0x1\",\"blockHash\""), "{json}");
+        assert!(json.contains("\"logIndex\":\"0x3\",\"removed\":false}"), "{json}");
+        assert!(json.contains("\"logIndex\":\"0x4\""), "{json}");
+        assert!(json.contains("\"status\":\"0x1\",\"to\":null,"), "{json}");
+        assert!(json.ends_with("\"transactionIndex\":\"0x1\",\"type\":\"0x0\"}"), "{json}");
+    }
+}
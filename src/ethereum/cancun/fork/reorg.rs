@@ -0,0 +1,333 @@
+//! Fork choice and chain rewinding.
+//!
+//! The specification only ever extends the chain at its head. A real client
+//! also has to accept blocks on competing branches and switch between them
+//! when the consensus layer changes its mind. Blocks which are not on the
+//! canonical chain are kept in `side_blocks`, and each canonical block keeps
+//! a [`StateDiff`] so that the state can be rewound to any ancestor.
+
+use crate::ethereum::{
+    cancun::blocks::Block,
+    crypto::hash::Hash32,
+    exceptions::Exception,
+};
+
+use super::{compute_header_hash, state_transition, BlockChain};
+
+/// Head, safe and finalized block hashes as reported by the consensus layer.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct ForkChoiceState {
+    pub head_block_hash: Hash32,
+    pub safe_block_hash: Hash32,
+    pub finalized_block_hash: Hash32,
+}
+
+impl BlockChain {
+    /// Hash of the block at the head of the canonical chain.
+    pub fn head_hash(&self) -> &Hash32 {
+        self.hashes.last().unwrap()
+    }
+
+    /// Position of `hash` in the canonical chain, if it is canonical.
+    pub fn canonical_index(&self, hash: &Hash32) -> Option<usize> {
+        self.hash_index.get(hash).copied()
+    }
+
+    /// Find a block on any branch by its hash.
+    pub fn get_block(&self, hash: &Hash32) -> Option<&Block> {
+        if let Some(i) = self.canonical_index(hash) {
+            Some(&self.blocks[i])
+        } else {
+            self.side_blocks.get(hash)
+        }
+    }
+
+    /// Import a block which may or may not extend the current head.
+    ///
+    /// Blocks extending the head are executed immediately. Blocks on other
+    /// branches are only stored and will be executed if `set_head` selects
+    /// their branch. Blocks which failed to execute, and their descendants,
+    /// are rejected.
+    pub fn import_block(&mut self, block: Block) -> Result<Hash32, Exception> {
+        let hash = compute_header_hash(&block.header)?;
+        if self.invalid_blocks.contains(&hash) || self.invalid_blocks.contains(&block.header.parent_hash) {
+            return Err(Exception::InvalidBlock("invalid block or parent block"));
+        }
+        if self.get_block(&hash).is_some() {
+            return Ok(hash);
+        }
+        if &block.header.parent_hash == self.head_hash() {
+            self.apply_block(hash.clone(), block)?;
+        } else if self.get_block(&block.header.parent_hash).is_some() {
+            self.side_blocks.insert(hash.clone(), block);
+        } else {
+            return Err(Exception::InvalidBlock("unknown parent block"));
+        }
+        Ok(hash)
+    }
+
+    /// Make `hash` the head of the canonical chain.
+    ///
+    /// Canonical blocks after the common ancestor are rewound using their
+    /// state diffs and moved to `side_blocks`. The blocks of the new branch
+    /// are then executed in order. If any of them are invalid, they are
+    /// dropped with their descendants, the previous head is restored and the
+    /// error is returned.
+    pub fn set_head(&mut self, hash: &Hash32) -> Result<(), Exception> {
+        if self.head_hash() == hash {
+            return Ok(());
+        }
+
+        // Walk back from the new head to the canonical chain.
+        let mut branch = Vec::new();
+        let mut cursor = hash.clone();
+        let ancestor = loop {
+            if let Some(i) = self.canonical_index(&cursor) {
+                break i;
+            }
+            let Some(block) = self.side_blocks.get(&cursor) else {
+                return Err(Exception::InvalidBlock("unknown block"));
+            };
+            let parent = block.header.parent_hash.clone();
+            branch.push(cursor);
+            cursor = parent;
+        };
+
+        let old_head = self.head_hash().clone();
+        self.rewind_to(ancestor);
+
+        for h in branch.into_iter().rev() {
+            let block = self.side_blocks.remove(&h).unwrap();
+            if let Err(e) = self.apply_block(h, block) {
+                self.drop_invalid_descendants();
+                self.set_head(&old_head)?;
+                return Err(e);
+            }
+        }
+        Ok(())
+    }
+
+    /// Apply a fork choice update from the consensus layer.
+    ///
+    /// The safe and finalized blocks, if not zero, must be ancestors of the
+    /// new head, which is then switched to with `set_head`. Side chain blocks
+    /// which do not descend from the finalized block can no longer become
+    /// canonical and are discarded.
+    pub fn fork_choice_update(&mut self, fork_choice: ForkChoiceState) -> Result<(), Exception> {
+        self.check_fork_choice(&fork_choice)?;
+        self.set_head(&fork_choice.head_block_hash)?;
+
+        if fork_choice.finalized_block_hash != Hash32::default() {
+            let pruned: Vec<Hash32> = self
+                .side_blocks
+                .keys()
+                .filter(|h| !self.is_ancestor(&fork_choice.finalized_block_hash, h))
+                .cloned()
+                .collect();
+            for h in pruned {
+                self.side_blocks.remove(&h);
+            }
+        }
+        self.fork_choice = fork_choice;
+        Ok(())
+    }
+
+    /// Check that the safe and finalized blocks of `fork_choice`, if not
+    /// zero, are ancestors of its head, without switching to it.
+    pub fn check_fork_choice(&self, fork_choice: &ForkChoiceState) -> Result<(), Exception> {
+        let zero = Hash32::default();
+        for h in [&fork_choice.safe_block_hash, &fork_choice.finalized_block_hash] {
+            if h != &zero && !self.is_ancestor(h, &fork_choice.head_block_hash) {
+                return Err(Exception::InvalidBlock("safe or finalized block is not an ancestor of the head"));
+            }
+        }
+        Ok(())
+    }
+
+    /// Whether the block `ancestor` is `hash` or one of its ancestors, on any
+    /// branch.
+    fn is_ancestor(&self, ancestor: &Hash32, hash: &Hash32) -> bool {
+        let mut cursor = hash;
+        loop {
+            if cursor == ancestor {
+                return true;
+            }
+            if let Some(i) = self.canonical_index(cursor) {
+                return self.canonical_index(ancestor).is_some_and(|j| j <= i);
+            }
+            match self.side_blocks.get(cursor) {
+                Some(block) => cursor = &block.header.parent_hash,
+                None => return false,
+            }
+        }
+    }
+
+    /// Execute the block with hash `hash` on top of the head, remembering it
+    /// as invalid if it fails.
+    fn apply_block(&mut self, hash: Hash32, block: Block) -> Result<(), Exception> {
+        let result = state_transition(self, block);
+        if result.is_err() {
+            self.invalid_blocks.insert(hash);
+        }
+        result
+    }
+
+    /// Move the side blocks descending from invalid blocks to
+    /// `invalid_blocks`.
+    fn drop_invalid_descendants(&mut self) {
+        loop {
+            let invalid: Vec<Hash32> = self
+                .side_blocks
+                .iter()
+                .filter(|(_, b)| self.invalid_blocks.contains(&b.header.parent_hash))
+                .map(|(h, _)| h.clone())
+                .collect();
+            if invalid.is_empty() {
+                return;
+            }
+            for h in invalid {
+                self.side_blocks.remove(&h);
+                self.invalid_blocks.insert(h);
+            }
+        }
+    }
+
+    /// Pop canonical blocks until the block at `index` is the head.
+    fn rewind_to(&mut self, index: usize) {
+        while self.blocks.len() > index + 1 {
+            let block = self.blocks.pop().unwrap();
+            let hash = self.hashes.pop().unwrap();
+            self.hash_index.remove(&hash);
+            let diff = self.diffs.pop().unwrap();
+            self.pop_receipts();
+            self.state.revert_diff(&diff);
+            self.side_blocks.insert(hash, block);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{builder::build_payload, engine::PayloadAttributes, prelude::*};
+
+    fn chain() -> BlockChain {
+        BlockChain::from_genesis(Genesis::post_merge(&["shanghai", "cancun"])).unwrap()
+    }
+
+    /// The block after the head of `chain` at `timestamp`, with a
+    /// withdrawal to `0xto..to`.
+    fn block(chain: &BlockChain, timestamp: u32, to: u8) -> Block {
+        let attributes = PayloadAttributes {
+            timestamp: U256::from(timestamp),
+            withdrawals: vec![Withdrawal { address: Address::from_be_bytes([to; 20]), amount: U256::ONE, ..Default::default() }],
+            ..Default::default()
+        };
+        build_payload(chain, &[], &attributes).unwrap().block
+    }
+
+    fn balance(chain: &BlockChain, owner: u8) -> U256 {
+        get_account(&chain.state, &Address::from_be_bytes([owner; 20])).balance
+    }
+
+    fn assert_state_of(chain: &BlockChain, hash: &Hash32) {
+        assert_eq!(chain.head_hash(), hash);
+        assert_eq!(state_root(&chain.state).unwrap(), chain.get_block(hash).unwrap().header.state_root);
+    }
+
+    #[test]
+    fn reorg_to_side_branch_and_back() {
+        let mut chain = chain();
+        let a1 = block(&chain, 12, 0xaa);
+        let b1 = block(&chain, 12, 0xbb);
+        let a1 = chain.import_block(a1).unwrap();
+
+        let mut other = chain.clone();
+        other.set_head(&compute_header_hash(&chain.blocks[0].header).unwrap()).unwrap();
+        other.import_block(b1.clone()).unwrap();
+        let b2 = block(&other, 24, 0xbb);
+
+        // Blocks off the head are only stored.
+        let b1 = chain.import_block(b1).unwrap();
+        let b2 = chain.import_block(b2).unwrap();
+        assert_state_of(&chain, &a1);
+        assert_eq!(chain.side_blocks.len(), 2);
+        assert_eq!(balance(&chain, 0xbb), U256::ZERO);
+
+        chain.set_head(&b2).unwrap();
+        assert_state_of(&chain, &b2);
+        assert_eq!(chain.canonical_index(&b1), Some(1));
+        assert_eq!(chain.canonical_index(&b2), Some(2));
+        assert_eq!(chain.canonical_index(&a1), None);
+        assert!(chain.side_blocks.contains_key(&a1));
+        assert_eq!(balance(&chain, 0xaa), U256::ZERO);
+        assert_eq!(balance(&chain, 0xbb), U256::from(2_000_000_000_u32));
+
+        chain.set_head(&a1).unwrap();
+        assert_state_of(&chain, &a1);
+        assert_eq!(chain.canonical_index(&a1), Some(1));
+        assert_eq!(chain.canonical_index(&b1), None);
+        assert_eq!(chain.side_blocks.len(), 2);
+        assert_eq!(balance(&chain, 0xaa), U256::from(1_000_000_000_u32));
+        assert_eq!(balance(&chain, 0xbb), U256::ZERO);
+    }
+
+    #[test]
+    fn invalid_branch_restores_head() {
+        let mut chain = chain();
+        let a1 = block(&chain, 12, 0xaa);
+        let mut b1 = block(&chain, 12, 0xbb);
+        // Executes, but does not match the state root.
+        b1.withdrawals.as_mut().unwrap()[0].amount = U256::from(2_u32);
+        let a1 = chain.import_block(a1).unwrap();
+        let b1_hash = chain.import_block(b1.clone()).unwrap();
+        let mut b2 = block(&chain, 24, 0xbb);
+        b2.header.parent_hash = b1_hash.clone();
+        let b2 = chain.import_block(b2).unwrap();
+
+        assert!(matches!(chain.set_head(&b1_hash), Err(Exception::StateRootMismatch { .. })));
+        assert_state_of(&chain, &a1);
+        assert_eq!(balance(&chain, 0xbb), U256::ZERO);
+        // The invalid block and its descendant are gone for good.
+        assert!(chain.side_blocks.is_empty());
+        assert!(chain.set_head(&b2).is_err());
+        assert!(chain.import_block(b1.clone()).is_err());
+
+        // An invalid block on the head leaves the state as it was.
+        let mut a2 = block(&chain, 24, 0xaa);
+        a2.header.gas_used = 1;
+        let a2_hash = compute_header_hash(&a2.header).unwrap();
+        assert!(chain.import_block(a2.clone()).is_err());
+        assert_state_of(&chain, &a1);
+        assert!(matches!(chain.import_block(a2), Err(Exception::InvalidBlock(_))));
+        assert!(chain.get_block(&a2_hash).is_none());
+    }
+
+    #[test]
+    fn fork_choice_update_prunes_side_blocks() {
+        let mut chain = chain();
+        let b1 = block(&chain, 12, 0xbb);
+        let mut other = chain.clone();
+        other.import_block(b1.clone()).unwrap();
+        let b2 = block(&other, 24, 0xbb);
+        let a1 = chain.import_block(block(&chain, 12, 0xaa)).unwrap();
+        let c2 = block(&chain, 24, 0xcc);
+        let a2 = chain.import_block(block(&chain, 24, 0xaa)).unwrap();
+        let c2 = chain.import_block(c2).unwrap();
+        let b1 = chain.import_block(b1).unwrap();
+        let b2 = chain.import_block(b2).unwrap();
+
+        // The safe block is checked against the new head before switching.
+        let not_ancestor = ForkChoiceState { head_block_hash: b2.clone(), safe_block_hash: a1.clone(), ..Default::default() };
+        assert!(chain.fork_choice_update(not_ancestor).is_err());
+        assert_state_of(&chain, &a2);
+        assert!(chain.side_blocks.contains_key(&b1));
+
+        let fork_choice = ForkChoiceState { head_block_hash: a2.clone(), safe_block_hash: a1.clone(), finalized_block_hash: a1 };
+        chain.fork_choice_update(fork_choice.clone()).unwrap();
+        assert_state_of(&chain, &a2);
+        // `b2` is above the finalized block, but not one of its descendants.
+        assert!(!chain.side_blocks.contains_key(&b2));
+        assert_eq!(chain.side_blocks.keys().collect::<Vec<_>>(), [&c2]);
+        assert_eq!(chain.fork_choice, fork_choice);
+    }
+}
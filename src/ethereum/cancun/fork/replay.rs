@@ -0,0 +1,282 @@
+//! Self-contained reproductions of a single block import.
+//!
+//! A [`Replay`] holds everything `state_transition` reads: the chain spec and
+//! fork activations, the headers of the ancestors of the block (for
+//! `BLOCKHASH` and the checks against the parent), the state before the
+//! block and the block itself, along with the result of the import when it
+//! was captured. `Replay::run` imports the block again on a chain built from
+//! these alone, so a `.replay` file attached to a bug report reproduces the
+//! import without the chain it came from.
+//!
+//! The file is JSON. Headers and the block are kept as RLP so that they are
+//! replayed byte for byte. The state is the whole state before the block:
+//! nothing records which accounts a block reads, so a smaller witness
+//! cannot be cut out of it.
+
+use std::{collections::BTreeMap, path::Path, sync::Arc};
+
+use crate::{
+    ethereum::{
+        cancun::{
+            blocks::{Block, Header},
+            fork_types::{Account, Address, Root},
+            state::{set_storage, state_root, State},
+            vm::code_cache::jump_destinations_cache,
+        },
+        chain_spec::ChainSpec,
+        ethereum_rlp::rlp,
+        ethereum_types::{
+            bytes::{Bytes, Bytes32},
+            numeric::{Uint, U256},
+        },
+        exceptions::Exception,
+        fork_criteria::{ForkActivations, ForkSchedule},
+    },
+    impl_json,
+    json::{from_json, to_json_pretty, Decoder, Encoder, JsonDecode, JsonEncode, JsonError, ObjectParser},
+};
+
+use super::{compute_header_hash, state_transition, BlockChain};
+
+/// Number of ancestors kept, enough for `BLOCKHASH`.
+const ANCESTORS: usize = 256;
+
+/// The inputs and result of one `state_transition`.
+#[derive(Debug, Clone)]
+pub struct Replay {
+    pub chain_spec: ChainSpec,
+    pub forks: ForkActivations,
+    /// Headers of the last ancestors of `block`, ending with its parent.
+    pub ancestors: Vec<Header>,
+    /// State after `ancestors`.
+    pub pre: State,
+    pub block: Block,
+    /// `Ok` with the state root after the block, or the error of the
+    /// import, as debug formatted, when the replay was captured.
+    pub result: Result<Root, String>,
+}
+
+/// An account of the `pre` section, with its storage.
+#[derive(Debug, Clone, Default)]
+struct ReplayAccount {
+    nonce: Uint,
+    balance: U256,
+    code: Bytes,
+    storage: BTreeMap<Bytes32, U256>,
+}
+
+impl_json!(ReplayAccount : nonce "nonce", balance "balance", code "code", storage "storage");
+
+/// The chain parameters and the fork activations, as in the `config` of a
+/// genesis file.
+#[derive(Debug, Clone, Default)]
+struct ReplayConfig {
+    chain_spec: ChainSpec,
+    forks: ForkActivations,
+}
+
+impl<'de> JsonDecode<'de> for ReplayConfig {
+    fn decode_json(&mut self, decoder: &mut Decoder<'de>) -> Result<(), JsonError> {
+        let mut p = ObjectParser::new(decoder);
+        while let Some(key) = p.next_key()? {
+            if !self.chain_spec.decode_member(key, p.decoder)? && !self.forks.decode_member(key, p.decoder)? {
+                return Err(JsonError::MissingKey);
+            }
+        }
+        Ok(())
+    }
+}
+
+impl JsonEncode for ReplayConfig {
+    fn encode_json(&self, encoder: &mut Encoder) {
+        let mut o = encoder.object();
+        self.chain_spec.encode_members(&mut o);
+        self.forks.encode_members(&mut o);
+        o.end();
+    }
+}
+
+/// A `Replay` as it is written.
+#[derive(Debug, Clone, Default)]
+struct ReplayFile {
+    config: ReplayConfig,
+    ancestors: Vec<Bytes>,
+    pre: BTreeMap<Address, ReplayAccount>,
+    block: Bytes,
+    state_root: Option<Root>,
+    error: Option<String>,
+}
+
+impl_json!(ReplayFile :
+    config "config",
+    ancestors "ancestors",
+    pre "pre",
+    block "block",
+    state_root "stateRoot",
+    error "error",
+);
+
+impl Replay {
+    /// Capture the import of `block` on top of the head of `chain`.
+    ///
+    /// The block is imported on a copy of `chain`, which is left unchanged.
+    pub fn capture(chain: &BlockChain, block: &Block) -> Self {
+        let start = chain.blocks.len().saturating_sub(ANCESTORS);
+        let mut replay = Replay {
+            chain_spec: chain.chain_spec.clone(),
+            forks: chain.fork_schedule.activations(),
+            ancestors: chain.blocks[start..].iter().map(|b| b.header.clone()).collect(),
+            pre: chain.state.clone(),
+            block: block.clone(),
+            result: Ok(Root::default()),
+        };
+        replay.result = replay.run();
+        replay
+    }
+
+    /// Import the block again on a chain built from the replay.
+    ///
+    /// Returns the state root after the block, or the debug formatted error
+    /// of the import, to be compared with `result`.
+    pub fn run(&self) -> Result<Root, String> {
+        let mut chain = self.chain().map_err(|e| format!("{e:?}"))?;
+        state_transition(&mut chain, self.block.clone()).map_err(|e| format!("{e:?}"))?;
+        state_root(&chain.state).map_err(|e| format!("{:?}", Exception::RLPException(e)))
+    }
+
+    /// A chain whose head is the parent of the block. It has no receipts
+    /// or state diffs for the ancestors, so it can only be extended.
+    fn chain(&self) -> Result<BlockChain, Exception> {
+        if self.ancestors.is_empty() {
+            return Err(Exception::InvalidBlock("replay without a parent block"));
+        }
+        let blocks: Vec<_> = self
+            .ancestors
+            .iter()
+            .map(|header| Block {
+                withdrawals: header.withdrawals_root.as_ref().map(|_| Vec::new()),
+                header: header.clone(),
+                transactions: Vec::new(),
+                ommers: Vec::new(),
+            })
+            .collect();
+        let hashes: Vec<_> = blocks.iter().map(|b| compute_header_hash(&b.header)).collect::<Result<_, _>>()?;
+        Ok(BlockChain {
+            hash_index: hashes.iter().cloned().zip(0..).collect(),
+            code_cache: Arc::new(jump_destinations_cache()),
+            hashes,
+            receipts: vec![Vec::new(); blocks.len()],
+            blocks,
+            state: self.pre.clone(),
+            fork_schedule: ForkSchedule::new(&self.forks),
+            side_blocks: Default::default(),
+            fork_choice: Default::default(),
+            chain_spec: self.chain_spec.clone(),
+            transaction_index: BTreeMap::new(),
+            diffs: Vec::new(),
+            invalid_blocks: Default::default(),
+            snapshots: None,
+        })
+    }
+
+    /// Write the replay as JSON.
+    pub fn to_json(&self) -> Result<String, Exception> {
+        let pre = self
+            .pre
+            .accounts()
+            .map(|(address, account)| {
+                let storage = self.pre.storage(address).map(|(k, v)| (*k, *v)).collect();
+                let account = ReplayAccount {
+                    nonce: account.nonce,
+                    balance: account.balance,
+                    code: account.code.clone(),
+                    storage,
+                };
+                (address.clone(), account)
+            })
+            .collect();
+        let file = ReplayFile {
+            config: ReplayConfig { chain_spec: self.chain_spec.clone(), forks: self.forks.clone() },
+            ancestors: self.ancestors.iter().map(rlp::encode).collect::<Result<_, _>>()?,
+            pre,
+            block: rlp::encode(&self.block)?,
+            state_root: self.result.as_ref().ok().cloned(),
+            error: self.result.as_ref().err().cloned(),
+        };
+        Ok(to_json_pretty(&file))
+    }
+
+    /// Read a replay written by `to_json`.
+    pub fn from_json(json: &str) -> Result<Self, Exception> {
+        let file: ReplayFile = from_json(json.as_bytes()).map_err(Exception::JsonError)?;
+        let mut pre = State::from_alloc(
+            file.pre
+                .iter()
+                .map(|(address, a)| {
+                    (address.clone(), Account { nonce: a.nonce, balance: a.balance, code: a.code.clone() })
+                })
+                .collect(),
+        );
+        for (address, account) in &file.pre {
+            for (key, value) in &account.storage {
+                set_storage(&mut pre, address, key, *value);
+            }
+        }
+        let result = match (file.state_root, file.error) {
+            (Some(state_root), None) => Ok(state_root),
+            (None, Some(error)) => Err(error),
+            _ => return Err(Exception::EthereumException("replay needs one of stateRoot and error")),
+        };
+        Ok(Replay {
+            chain_spec: file.config.chain_spec,
+            forks: file.config.forks,
+            ancestors: file.ancestors.iter().map(|h| rlp::decode_to_strict(h)).collect::<Result<_, _>>()?,
+            pre,
+            block: rlp::decode_to_strict(&file.block)?,
+            result,
+        })
+    }
+
+    /// `to_json` into the file at `path`.
+    pub fn write_file(&self, path: impl AsRef<Path>) -> Result<(), Exception> {
+        std::fs::write(path, self.to_json()?).map_err(|_| Exception::EthereumException("cannot write replay file"))
+    }
+
+    /// `from_json` for the contents of the file at `path`.
+    pub fn from_file(path: impl AsRef<Path>) -> Result<Self, Exception> {
+        let json = std::fs::read_to_string(path)
+            .map_err(|_| Exception::EthereumException("cannot read replay file"))?;
+        Self::from_json(&json)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::ethereum::cancun::{fork::fuzz::tests::valid_block, state::state_root};
+
+    use super::Replay;
+
+    #[test]
+    fn round_trip() {
+        let (chain, block) = valid_block();
+        let replay = Replay::capture(&chain, &block);
+        let mut imported = chain.clone();
+        imported.import_block(block.clone()).unwrap();
+        assert_eq!(replay.result, Ok(state_root(&imported.state).unwrap()));
+
+        let read = Replay::from_json(&replay.to_json().unwrap()).unwrap();
+        assert_eq!(read.result, replay.result);
+        assert_eq!(read.run(), replay.result);
+
+        // A failed import replays with the same error.
+        let mut invalid = block;
+        invalid.header.gas_used += 1;
+        let replay = Replay::capture(&chain, &invalid);
+        assert_eq!(replay.result, Err(format!("GasUsedMismatch {{ expected: {}, actual: {} }}", invalid.header.gas_used, invalid.header.gas_used - 1)));
+        let path = std::env::temp_dir().join(format!("ejit-{}.replay", std::process::id()));
+        replay.write_file(&path).unwrap();
+        let read = Replay::from_file(&path).unwrap();
+        std::fs::remove_file(&path).unwrap();
+        assert_eq!(read.run(), replay.result);
+    }
+}
@@ -1,52 +1,246 @@
 use std::{io::{BufRead, BufReader, Write}, net::TcpStream, path::PathBuf, sync::Arc, time::Duration};
 
-use crate::ethereum::{cancun::blocks::Block, ethereum_rlp::rlp, ethereum_types::bytes::Bytes, genesis::{self, Genesis}};
+use crate::ethereum::{cancun::blocks::Block, chain_spec::ChainSpec, ethereum_rlp::rlp, ethereum_types::{bytes::Bytes, numeric::Uint}, genesis::{self, Genesis}};
 
 use super::BlockChain;
 
+#[cfg(feature = "rpc")]
 #[test]
+#[ignore = "needs a mainnet node at ALCHEMY_URL"]
 fn test_against_alchemy() {
     let url = std::env::var("ALCHEMY_URL").unwrap();
-    let client = reqwest::blocking::Client::new();
-
-    let latest_block = 22445332;
-    let genesis = Genesis::mainnet().unwrap();
-    let chain = BlockChain::from_genesis(genesis);
-
-    // for block in (0..latest_block) /* .step_by(1000000)*/ {
-    //     let res = loop {
-    //         println!("{block}");
-    //         // std::io::stdout().flush();
-    //         let body = format!(
-    //             r#"{{"id": 1,"jsonrpc": "2.0","method": "debug_getRawBlock","params": ["0x{block:x}"]}}"#
-    //         );
-        
-    //         let resp = client
-    //             .post(&url)
-    //             .header("accept", "application/json")
-    //             .header("content-type", "application/json")
-    //             .body(body).send().unwrap();
-        
-    //         if resp.status() == 200 {
-    //             break resp.text().unwrap();
-    //         }
-    //         println!("{}", resp.text().unwrap());
-    //         std::thread::sleep(Duration::from_millis(500));
-    //     };
-
-    //     let (_, rest) = res.split_once(r#"result":"0x"#).unwrap();
-    //     let (hex, _) = rest.split_once('"').unwrap();
-    
-    //     let bytes : Vec<u8> = hex
-    //         .as_bytes()
-    //         .chunks_exact(2)
-    //         .map(|c| u8::from_str_radix(std::str::from_utf8(c).unwrap(), 16).unwrap())
-    //         .collect();
-    
-    //     // std::fs::write("/tmp/1", format!("{bytes:02x?}"));
-    //     let block : Block = rlp::decode_to(&bytes).unwrap();
-    // }
-
-    // println!("block: {block:?}");
+    let client = crate::rpc::Client::new(url);
 
+    // Replays mainnet from block 1, up to `ALCHEMY_LAST_BLOCK` if set.
+    let latest_block = std::env::var("ALCHEMY_LAST_BLOCK").ok().and_then(|b| b.parse().ok()).unwrap_or(22445332_u64);
+    let mut chain = BlockChain::mainnet().unwrap();
+
+    for block in 1..=latest_block {
+        let decoded = client.debug_get_raw_block(block.into()).unwrap();
+        if let Err(e) = super::state_transition(&mut chain, decoded) {
+            panic!("block {block}: {e:?}");
+        }
+    }
+}
+
+#[test]
+fn test_state_diff_rewind() {
+    use std::collections::BTreeMap;
+    use crate::ethereum::{cancun::{fork_types::{Account, Address}, state::State}, ethereum_types::numeric::U256};
+
+    let a = Address::from_be_bytes([1; 20]);
+    let b = Address::from_be_bytes([2; 20]);
+    let account = |balance: u64| Account { balance: U256::from(balance), ..Default::default() };
+
+    let before = State::from_alloc(BTreeMap::from([(a.clone(), account(1)), (b.clone(), account(2))]));
+    let after = State::from_alloc(BTreeMap::from([(a.clone(), account(3))]));
+
+    let diff = State::diff(&before, &after);
+    assert_eq!(diff.accounts.len(), 2);
+    assert_eq!(diff.accounts[&b], (Some(account(2)), None));
+
+    let mut state = before.clone();
+    state.apply_diff(&diff);
+    assert!(State::diff(&state, &after).is_empty());
+    state.revert_diff(&diff);
+    assert!(State::diff(&state, &before).is_empty());
+}
+
+#[test]
+fn test_state_dump() {
+    use std::collections::BTreeMap;
+    use crate::{
+        ethereum::{cancun::{fork_types::{Account, Address}, state::{set_storage, State}}, ethereum_types::{bytes::Bytes32, numeric::U256}},
+        json::to_json,
+    };
+
+    let [a, b, c] = [1, 2, 3].map(|i| Address::from_be_bytes([i; 20]));
+    let account = |balance: u64| Account { balance: U256::from(balance), ..Default::default() };
+
+    let mut before = State::from_alloc(BTreeMap::from([(a.clone(), account(1)), (b.clone(), account(2)), (c.clone(), account(3))]));
+    set_storage(&mut before, &b, &Bytes32([0; 32]), U256::from(5_u64));
+    let mut after = State::from_alloc(BTreeMap::from([(a.clone(), Account { nonce: 1, ..account(1) }), (b.clone(), account(2))]));
+    set_storage(&mut after, &b, &Bytes32([0; 32]), U256::from(6_u64));
+
+    assert_eq!(before.dump(..).len(), 3);
+    let dump = before.dump(b.clone()..c.clone());
+    assert_eq!(dump.len(), 1);
+    assert_eq!(dump[&b].balance, U256::from(2_u64));
+    assert_eq!(dump[&b].storage, BTreeMap::from([(U256::ZERO, U256::from(5_u64))]));
+
+    // Only what changed is in the diff: `a`'s nonce, `b`'s storage and all
+    // of the destroyed `c`.
+    let [a, b, c] = [a, b, c].map(|address| to_json(&address));
+    assert_eq!(
+        to_json(&State::diff(&before, &after)),
+        format!(
+            r#"{{"pre":{{{a}:{{"nonce":"0x0"}},{b}:{{"storage":{{"0x0":"0x5"}}}},{c}:{{"balance":"0x3","code":"0x","nonce":"0x0"}}}},"post":{{{a}:{{"nonce":"0x1"}},{b}:{{"storage":{{"0x0":"0x6"}}}}}}}}"#
+        ),
+    );
+}
+
+#[test]
+fn test_block_with_transactions_round_trip() {
+    use crate::ethereum::{
+        cancun::transactions::{AccessListTransaction, BlobTransaction, FeeMarketTransaction, LegacyTransaction, Transaction},
+        ethereum_types::numeric::U256,
+    };
+
+    let block = Block {
+        transactions: vec![
+            Transaction::LegacyTransaction(LegacyTransaction { nonce: U256::from(1_u32), gas: 21000, ..Default::default() }),
+            Transaction::AccessListTransaction(AccessListTransaction { chain_id: 1, ..Default::default() }),
+            Transaction::FeeMarketTransaction(FeeMarketTransaction { chain_id: 1, max_fee_per_gas: 7, ..Default::default() }),
+            Transaction::BlobTransaction(BlobTransaction { chain_id: 1, ..Default::default() }),
+        ],
+        withdrawals: Some(Vec::new()),
+        ..Default::default()
+    };
+    let encoded = rlp::encode(&block).unwrap();
+    let decoded: Block = rlp::decode_to(&encoded).unwrap();
+    assert_eq!(rlp::encode(&decoded).unwrap(), encoded);
+
+    // Typed transactions are byte strings starting with the type.
+    let encoded = rlp::encode(&block.transactions[2]).unwrap();
+    assert_eq!(encoded[0], 0x80 + encoded.len() as u8 - 1);
+    assert_eq!(encoded[1], 0x02);
+}
+
+#[test]
+fn test_blocks_follow_the_fork_schedule() {
+    use crate::ethereum::{cancun::blocks::Header, exceptions::Exception};
+
+    let mut chain = BlockChain::mainnet().unwrap();
+    let header = |number| Header { number, difficulty: 1, ..Default::default() };
+    let fork = |number| chain.fork_for(&header(number)).unwrap().name();
+    assert_eq!(fork(1), "frontier");
+    assert_eq!(fork(1_920_000), "dao_fork");
+    assert_eq!(fork(12_965_000), "london");
+    assert_eq!(fork(15_050_000), "gray_glacier");
+
+    // Frontier rules apply to block 1, whose parent hash is wrong.
+    let block = Block { header: header(1), ..Default::default() };
+    assert!(matches!(super::state_transition(&mut chain, block), Err(Exception::InvalidBlock(_))));
+    assert_eq!(chain.blocks.len(), 1);
+}
+
+#[test]
+fn test_errors_are_typed() {
+    use std::error::Error;
+
+    use crate::ethereum::{cancun::fork::fuzz::tests::valid_block, exceptions::Exception};
+
+    let (mut chain, mut block) = valid_block();
+    let expected = block.header.state_root.clone();
+    block.header.state_root = Default::default();
+    match chain.import_block(block.clone()) {
+        Err(e @ Exception::StateRootMismatch { .. }) => {
+            assert!(e.is_invalid_block());
+            assert!(e.to_string().starts_with("invalid block: state root is Root(0x"), "{e}");
+            let Exception::StateRootMismatch { expected: header_root, actual } = e else { unreachable!() };
+            assert_eq!(header_root, Default::default());
+            assert_eq!(actual, expected);
+        }
+        r => panic!("{r:?}"),
+    }
+
+    // Decoding errors keep the field they happened in as a source.
+    let encoded = rlp::encode(&block).unwrap();
+    let e = Exception::from(rlp::decode_to::<Block>(&encoded[..encoded.len() - 1]).unwrap_err());
+    let mut messages = vec![e.to_string()];
+    let mut source = e.source();
+    while let Some(s) = source {
+        messages.push(s.to_string());
+        source = s.source();
+    }
+    assert_eq!(messages[0], "invalid RLP");
+    assert!(messages.len() > 1, "{messages:?}");
+}
+
+#[test]
+fn test_chain_spec() {
+    use crate::ethereum::{cancun::fork::fuzz::tests::valid_block, exceptions::Exception};
+
+    // With no change of the gas limit allowed, even keeping the parent's is
+    // too much.
+    let (mut chain, block) = valid_block();
+    let chain_id = chain.chain_spec.chain_id;
+    chain.chain_spec.gas_limit_adjustment_factor = Uint::MAX;
+    assert!(matches!(
+        chain.clone().import_block(block.clone()),
+        Err(Exception::InvalidBlock("!check_gas_limit(block_gas_limit, parent_gas_limit)"))
+    ));
+    assert!(chain.header_chain().import_header(block.header.clone()).is_err());
+
+    chain.chain_spec = ChainSpec { chain_id, gas_limit_minimum: block.header.gas_limit + 1, ..ChainSpec::MAINNET };
+    assert!(chain.clone().import_block(block.clone()).is_err());
+
+    chain.chain_spec = ChainSpec { chain_id, ..Default::default() };
+    chain.import_block(block).unwrap();
+}
+
+#[test]
+fn test_beacon_roots_system_call() {
+    use crate::ethereum::{
+        cancun::{
+            fork::{fuzz::tests::valid_block, BlockEnvironment, BlockExecution},
+            fork_types::Root,
+            state::{get_account_optional, get_storage, set_account},
+        },
+        ethereum_types::{bytes::Bytes32, numeric::U256},
+        forks::ExecutionRules,
+    };
+
+    // The contract of `valid_block` stores the root under the timestamp.
+    let (mut chain, block) = valid_block();
+    let spec = chain.chain_spec.clone();
+    let header = block.header.clone();
+    chain.import_block(block).unwrap();
+    let key = Bytes32(header.timestamp.to_be_bytes());
+    let root = header.parent_beacon_block_root.clone().unwrap();
+    assert_eq!(get_storage(&chain.state, &spec.beacon_roots_address, &key), U256::from_be_bytes(root.0));
+    assert!(get_account_optional(&chain.state, &spec.system_address).is_none());
+
+    // Without the contract the call is skipped and creates no account.
+    let mut state = chain.state.clone();
+    set_account(&mut state, &spec.beacon_roots_address, None);
+    let env = BlockEnvironment {
+        block_hashes: Vec::new(),
+        coinbase: header.coinbase.clone(),
+        number: header.number + 1,
+        base_fee_per_gas: header.base_fee_per_gas,
+        gas_limit: header.gas_limit,
+        time: header.timestamp + U256::ONE,
+        prev_randao: header.prev_randao.clone(),
+        chain_id: chain.chain_spec.chain_id,
+        parent_beacon_block_root: Some(Root([0x0b; 32])),
+        excess_blob_gas: Some(0),
+        rules: ExecutionRules::CANCUN,
+        chain_spec: spec.clone(),
+        code_cache: None,
+    };
+    BlockExecution::begin(&mut state, &env).unwrap();
+    assert!(get_account_optional(&state, &spec.beacon_roots_address).is_none());
+    assert!(get_account_optional(&state, &spec.system_address).is_none());
+}
+
+#[test]
+fn test_block_environment_helpers() {
+    use crate::ethereum::{crypto::hash::Hash32, ethereum_types::bytes::Bytes32, forks::fork_rules};
+
+    use super::{prev_randao, recent_block_hashes};
+
+    // Only the hashes known are set, and the window is 256 blocks long.
+    let hashes = recent_block_hashes(300, |number| (number == 299 || number == 10).then(|| Hash32([7; 32])));
+    assert_eq!(hashes.len(), 256);
+    assert_eq!(hashes[255], Hash32([7; 32]));
+    assert!(hashes[..255].iter().all(|hash| *hash == Hash32::default()));
+    assert_eq!(recent_block_hashes(2, |number| Some(Hash32([number as u8; 32]))), [Hash32([0; 32]), Hash32([1; 32])]);
+
+    // Proof of work blocks give their difficulty instead.
+    let (cancun, london) = (fork_rules("cancun").unwrap(), fork_rules("london").unwrap());
+    let random = Bytes32([9; 32]);
+    assert_eq!(prev_randao(cancun, Some(&random), 5), random);
+    assert_eq!(prev_randao(london, Some(&random), 5).0[31], 5);
+    assert_eq!(prev_randao(cancun, None, 5).0[31], 5);
 }
@@ -0,0 +1,130 @@
+//! The outcome of importing a block, in the terms of the Engine API.
+//!
+//! `import_block` either fails or succeeds, but a consensus client needs to
+//! know more than that: a block whose parent is missing is not invalid, only
+//! not yet checkable, and a block on a branch other than the head's is
+//! stored without being executed. `validate_block` imports a block and sorts
+//! the outcome into the statuses of `PayloadStatusV1`, together with the
+//! hash of the last block known to be valid on the branch of the block.
+//!
+//! The error of an invalid block is kept as it is, so that tests can match
+//! on the check which failed rather than on a message.
+
+use crate::ethereum::{cancun::blocks::Block, crypto::hash::Hash32, exceptions::Exception};
+
+use super::{compute_header_hash, BlockChain};
+
+/// How a block was received.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ValidationStatus {
+    /// The block was executed and is valid.
+    Valid,
+    Invalid,
+    /// The parent of the block is not known.
+    Syncing,
+    /// The block is on a branch other than the head's, and was stored
+    /// without being executed.
+    Accepted,
+}
+
+/// The status of a block, with the last valid block on its branch.
+#[derive(Debug)]
+pub struct ValidationResult {
+    pub status: ValidationStatus,
+    /// The last valid block on the branch of the block, `None` if it is not
+    /// known.
+    pub latest_valid_hash: Option<Hash32>,
+    /// Why the block is invalid.
+    pub error: Option<Exception>,
+}
+
+impl ValidationResult {
+    pub fn new(status: ValidationStatus, latest_valid_hash: Option<Hash32>) -> Self {
+        Self { status, latest_valid_hash, error: None }
+    }
+
+    /// A block failing with `error`.
+    ///
+    /// Errors which do not mean that the block is invalid, such as a fork
+    /// which is not implemented, say nothing of its ancestors, so the
+    /// latest valid hash is dropped for them.
+    pub fn invalid(latest_valid_hash: Option<Hash32>, error: Exception) -> Self {
+        let latest_valid_hash = latest_valid_hash.filter(|_| error.is_invalid_block());
+        Self { status: ValidationStatus::Invalid, latest_valid_hash, error: Some(error) }
+    }
+
+    pub fn is_valid(&self) -> bool {
+        self.status == ValidationStatus::Valid
+    }
+}
+
+impl BlockChain {
+    /// Import `block` as `import_block` does and report its status.
+    ///
+    /// A block extending the head is executed, and is either `Valid` or
+    /// `Invalid` with its parent as the latest valid hash. A block extending
+    /// another known block is `Accepted`, and one whose parent is not known
+    /// is `Syncing`. A block already known is `Valid` if canonical and
+    /// `Accepted` otherwise.
+    pub fn validate_block(&mut self, block: Block) -> ValidationResult {
+        let hash = match compute_header_hash(&block.header) {
+            Ok(hash) => hash,
+            Err(error) => return ValidationResult::invalid(None, error),
+        };
+        if self.canonical_index(&hash).is_some() {
+            return ValidationResult::new(ValidationStatus::Valid, Some(hash));
+        }
+        if self.get_block(&hash).is_some() {
+            return ValidationResult::new(ValidationStatus::Accepted, None);
+        }
+        let parent_hash = block.header.parent_hash.clone();
+        if self.get_block(&parent_hash).is_none() {
+            return ValidationResult::new(ValidationStatus::Syncing, None);
+        }
+        let extends_head = &parent_hash == self.head_hash();
+        match self.import_block(block) {
+            Ok(hash) if extends_head => ValidationResult::new(ValidationStatus::Valid, Some(hash)),
+            Ok(_) => ValidationResult::new(ValidationStatus::Accepted, None),
+            Err(error) => ValidationResult::invalid(Some(parent_hash), error),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::ethereum::{cancun::fork::fuzz::tests::valid_block, crypto::hash::Hash32, exceptions::Exception};
+
+    use super::ValidationStatus;
+
+    #[test]
+    fn validation_statuses() {
+        let (chain, block) = valid_block();
+        let genesis_hash = chain.head_hash().clone();
+
+        let mut invalid = block.clone();
+        invalid.header.state_root = Default::default();
+        let result = chain.clone().validate_block(invalid);
+        assert_eq!(result.status, ValidationStatus::Invalid);
+        assert_eq!(result.latest_valid_hash, Some(genesis_hash.clone()));
+        assert!(matches!(result.error, Some(Exception::StateRootMismatch { .. })), "{:?}", result.error);
+
+        let mut orphan = block.clone();
+        orphan.header.parent_hash = Hash32([0xaa; 32]);
+        let result = chain.clone().validate_block(orphan);
+        assert_eq!((result.status, result.latest_valid_hash), (ValidationStatus::Syncing, None));
+
+        let mut chain = chain;
+        let hash = block.hash().unwrap();
+        let result = chain.validate_block(block.clone());
+        assert_eq!((result.status, result.latest_valid_hash), (ValidationStatus::Valid, Some(hash.clone())));
+        assert!(result.error.is_none());
+        assert!(chain.validate_block(block.clone()).is_valid());
+
+        // A sibling of the head is stored, not executed.
+        let mut sibling = block;
+        sibling.header.extra_data.0.push(1);
+        let result = chain.validate_block(sibling);
+        assert_eq!((result.status, result.latest_valid_hash), (ValidationStatus::Accepted, None));
+        assert_eq!(chain.head_hash(), &hash);
+    }
+}
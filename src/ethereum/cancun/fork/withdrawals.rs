@@ -0,0 +1,155 @@
+//! Consensus layer constraints on withdrawals and per-address totals.
+//!
+//! The execution layer credits whatever withdrawals the consensus layer puts
+//! in a payload. The consensus layer also guarantees a few things about them
+//! that the specification never checks: their indices count up by one
+//! without gaps across blocks, a payload holds at most
+//! `MAX_WITHDRAWALS_PER_PAYLOAD` of them, validator indices fit in the
+//! validator registry and amounts are `uint64` Gwei. These helpers check
+//! those constraints for tools which take blocks from an untrusted source.
+
+use std::{collections::BTreeMap, ops::RangeBounds};
+
+use crate::ethereum::{
+    cancun::{
+        blocks::{Block, Withdrawal},
+        fork_types::Address,
+    },
+    ethereum_types::numeric::{Uint, U256, U64},
+    exceptions::Exception,
+};
+
+use super::BlockChain;
+
+/// Most withdrawals the consensus layer includes in one payload.
+pub const MAX_WITHDRAWALS_PER_PAYLOAD: usize = 16;
+
+/// Size of the validator registry, all validator indices are below this.
+pub const VALIDATOR_REGISTRY_LIMIT: U64 = 1 << 40;
+
+/// Check the withdrawals of one block.
+///
+/// `next_index` is the index the first withdrawal must have, if known,
+/// typically one more than the last withdrawal of the parent block.
+/// Returns the index expected of the next withdrawal after these.
+pub fn validate_withdrawals(
+    withdrawals: &[Withdrawal],
+    next_index: Option<U64>,
+) -> Result<Option<U64>, Exception> {
+    if withdrawals.len() > MAX_WITHDRAWALS_PER_PAYLOAD {
+        return Err(Exception::InvalidBlock("too many withdrawals"));
+    }
+    let mut next_index = next_index;
+    for wd in withdrawals {
+        if next_index.is_some_and(|i| wd.index != i) {
+            return Err(Exception::InvalidBlock("withdrawal index out of sequence"));
+        }
+        if wd.validator_index >= VALIDATOR_REGISTRY_LIMIT {
+            return Err(Exception::InvalidBlock("validator index out of range"));
+        }
+        if wd.amount > U256::from(U64::MAX) {
+            return Err(Exception::InvalidBlock("withdrawal amount exceeds uint64 Gwei"));
+        }
+        next_index = Some(wd.index.checked_add(1).ok_or(Exception::NumericOverflow)?);
+    }
+    Ok(next_index)
+}
+
+/// Check the withdrawals of consecutive blocks, including that the indices
+/// carry on from one block to the next.
+pub fn validate_block_withdrawals<'a>(
+    blocks: impl IntoIterator<Item = &'a Block>,
+) -> Result<(), Exception> {
+    let mut next_index = None;
+    for block in blocks {
+        if let Some(withdrawals) = &block.withdrawals {
+            next_index = validate_withdrawals(withdrawals, next_index)?;
+        }
+    }
+    Ok(())
+}
+
+/// Total amount withdrawn to each address by `blocks`, in Gwei.
+pub fn total_withdrawn<'a>(blocks: impl IntoIterator<Item = &'a Block>) -> BTreeMap<Address, U256> {
+    let mut totals = BTreeMap::new();
+    for wd in blocks.into_iter().flat_map(|b| b.withdrawals.iter().flatten()) {
+        let total = totals.entry(wd.address.clone()).or_insert(U256::ZERO);
+        *total = *total + wd.amount;
+    }
+    totals
+}
+
+impl BlockChain {
+    /// Total amount withdrawn to each address by the canonical blocks with
+    /// numbers in `numbers`, in Gwei.
+    pub fn total_withdrawn(&self, numbers: impl RangeBounds<Uint>) -> BTreeMap<Address, U256> {
+        total_withdrawn(self.blocks.iter().filter(|b| numbers.contains(&b.header.number)))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::ethereum::{
+        cancun::{
+            blocks::{Block, Withdrawal},
+            fork_types::Address,
+        },
+        ethereum_types::numeric::U256,
+        exceptions::Exception,
+    };
+
+    use super::{total_withdrawn, validate_block_withdrawals, validate_withdrawals, MAX_WITHDRAWALS_PER_PAYLOAD};
+
+    fn withdrawal(index: u64, address: u8, amount: u64) -> Withdrawal {
+        Withdrawal {
+            index,
+            validator_index: index * 10,
+            address: Address::from_be_bytes([address; 20]),
+            amount: U256::from(amount),
+        }
+    }
+
+    fn block(withdrawals: Vec<Withdrawal>) -> Block {
+        Block { withdrawals: Some(withdrawals), ..Default::default() }
+    }
+
+    #[test]
+    fn check_constraints() {
+        let wds = [withdrawal(5, 1, 10), withdrawal(6, 2, 20)];
+        assert_eq!(validate_withdrawals(&wds, None).unwrap(), Some(7));
+        assert_eq!(validate_withdrawals(&wds, Some(5)).unwrap(), Some(7));
+        assert_eq!(validate_withdrawals(&[], Some(5)).unwrap(), Some(5));
+
+        let err = |wds: &[Withdrawal], next| match validate_withdrawals(wds, next) {
+            Err(Exception::InvalidBlock(e)) => e,
+            r => panic!("{r:?}"),
+        };
+        assert_eq!(err(&wds, Some(4)), "withdrawal index out of sequence");
+        assert_eq!(err(&[withdrawal(5, 1, 10), withdrawal(7, 2, 20)], None), "withdrawal index out of sequence");
+        let mut big = withdrawal(0, 1, 0);
+        big.amount = U256::from(u64::MAX) + U256::ONE;
+        assert_eq!(err(&[big], None), "withdrawal amount exceeds uint64 Gwei");
+        let mut far = withdrawal(0, 1, 0);
+        far.validator_index = 1 << 40;
+        assert_eq!(err(&[far], None), "validator index out of range");
+        let many: Vec<_> = (0..=MAX_WITHDRAWALS_PER_PAYLOAD as u64).map(|i| withdrawal(i, 1, 1)).collect();
+        assert_eq!(err(&many, None), "too many withdrawals");
+    }
+
+    #[test]
+    fn across_blocks() {
+        let blocks = [
+            block(vec![withdrawal(0, 1, 10), withdrawal(1, 2, 20)]),
+            Block::default(),
+            block(vec![]),
+            block(vec![withdrawal(2, 1, 5)]),
+        ];
+        validate_block_withdrawals(&blocks).unwrap();
+        assert!(validate_block_withdrawals(&[blocks[0].clone(), blocks[0].clone()]).is_err());
+
+        let totals = total_withdrawn(&blocks);
+        assert_eq!(totals.len(), 2);
+        assert_eq!(totals[&Address::from_be_bytes([1; 20])], U256::from(15_u64));
+        assert_eq!(totals[&Address::from_be_bytes([2; 20])], U256::from(20_u64));
+    }
+}
@@ -1,8 +1,8 @@
 use std::ops::Deref;
 
-use crate::{ethereum::{crypto::hash::{keccak256, Hash32}, ethereum_rlp::{exceptions::RLPException, rlp::{self, decode_to_bytes, encode_bytes, Extended}}, ethereum_types::{bytes::{Bytes20, Bytes256, *}, numeric::*}, utils::hexadecimal::hex_to_slice}, impl_json, json::{Decoder, JsonDecode, JsonError, ObjectParser}};
+use crate::{ethereum::{crypto::hash::{keccak256, keccak256_cached, Hash32}, ethereum_rlp::{exceptions::RLPException, rlp::{self, decode_to_fixed_bytes, encode_bytes, Extended}}, ethereum_types::{bytes::{Bytes20, Bytes256, *}, numeric::*}, exceptions::Exception, utils::hexadecimal::{bytes_to_hex, hex_to_array, hex_to_slice}}, impl_json, json::{Decoder, Encoder, JsonDecode, JsonEncode, JsonError}};
 
-#[derive(Clone, PartialEq, PartialOrd, Eq, Ord, Default)]
+#[derive(Clone, PartialEq, PartialOrd, Eq, Ord, Hash, Default)]
 pub struct Address([u8; 20]);
 
 impl Address {
@@ -17,6 +17,41 @@ impl Address {
     pub fn is_zero(&self) -> bool {
         self.0.iter().all(|b| *b == 0)
     }
+
+    /// The address as a "0x" prefixed lower case hex string.
+    pub fn to_hex(&self) -> String {
+        bytes_to_hex(&self.0)
+    }
+
+    /// The address in the mixed case of EIP-55: a letter is upper case if
+    /// the nibble at its position in the keccak256 of the lower case hex is
+    /// at least 8.
+    pub fn to_checksum(&self) -> String {
+        let hex = self.to_hex();
+        let hash = keccak256(&hex.as_bytes()[2..]);
+        let digits = hex[2..].chars().enumerate().map(|(i, c)| {
+            let nibble = hash[i / 2] >> (4 * (1 - i % 2)) & 0x0f;
+            if nibble >= 8 { c.to_ascii_uppercase() } else { c }
+        });
+        "0x".chars().chain(digits).collect()
+    }
+}
+
+impl std::fmt::Display for Address {
+    /// Checksummed, as in EIP-55.
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(&self.to_checksum())
+    }
+}
+
+impl std::str::FromStr for Address {
+    type Err = Exception;
+
+    /// Forty hex digits in any case, with or without "0x". The checksum of
+    /// mixed case is not checked.
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Ok(Self(hex_to_array(s)?))
+    }
 }
 
 impl std::fmt::Debug for Address {
@@ -48,10 +83,23 @@ impl<'de> JsonDecode<'de> for Address {
         let mut bytes = [0; 20];
         hex_to_slice(&mut bytes, s).map_err(|_| JsonError::ExpectedHexString)?;
         *self = Self(bytes);
+        // Only mixed case carries a checksum, and only over all 40 digits.
+        let digits = s.strip_prefix("0x").unwrap_or(s);
+        let mixed = digits.len() == 40
+            && digits.bytes().any(|b| b.is_ascii_uppercase()) && digits.bytes().any(|b| b.is_ascii_lowercase());
+        if mixed && buffer.checks_checksums() && self.to_checksum()[2..] != *digits {
+            return Err(JsonError::BadChecksum);
+        }
         Ok(())
     }
 }
 
+impl JsonEncode for Address {
+    fn encode_json(&self, encoder: &mut Encoder) {
+        encoder.hex(&self.0);
+    }
+}
+
 #[derive(Clone, PartialEq, PartialOrd, Eq, Ord, Default)]
 pub struct Root(pub (crate)[u8; 32]);
 
@@ -61,6 +109,8 @@ impl From<[u8; 32]> for Root {
     }
 }
 
+crate::impl_hex_str!(Root);
+
 impl std::fmt::Debug for Root {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         let mut hex = [0; 32*2+2];
@@ -75,7 +125,7 @@ impl Extended for Root {
     }
 
     fn decode<'a, 'b>(&mut self, buffer: &'a mut &'b [u8]) -> Result<(), RLPException> {
-        decode_to_bytes(buffer, &mut self.0)
+        decode_to_fixed_bytes(buffer, &mut self.0)
     }
 }
 
@@ -90,6 +140,12 @@ impl<'de> JsonDecode<'de> for Root {
     }
 }
 
+impl JsonEncode for Root {
+    fn encode_json(&self, encoder: &mut Encoder) {
+        encoder.hex(&self.0);
+    }
+}
+
 impl Deref for Root {
     type Target = [u8; 32];
 
@@ -123,6 +179,21 @@ impl Extended for Bloom {
     }
 }
 
+impl<'de> JsonDecode<'de> for Bloom {
+    fn decode_json(&mut self, buffer: &mut Decoder<'de>) -> Result<(), crate::json::JsonError> {
+        let mut s = "";
+        s.decode_json(buffer)?;
+        hex_to_slice(&mut self.0.0, s).map_err(|_| JsonError::ExpectedHexString)?;
+        Ok(())
+    }
+}
+
+impl JsonEncode for Bloom {
+    fn encode_json(&self, encoder: &mut Encoder) {
+        encoder.hex(&self.0.0);
+    }
+}
+
 impl Deref for Bloom {
     type Target = [u8; 256];
 
@@ -160,8 +231,30 @@ pub fn encode_account(raw_account_data: &Account, storage_root: &Root) -> Result
             &raw_account_data.nonce,
             &raw_account_data.balance,
             storage_root,
-            &keccak256(&raw_account_data.code),
+            &keccak256_cached(&raw_account_data.code),
         ]
     )?;
     Ok(dest)
 }
+
+#[test]
+fn test_hex_strings() {
+    // EIP-55.
+    for checksummed in [
+        "0x5aAeb6053F3E94C9b9A09f33669435E7Ef1BeAed",
+        "0xfB6916095ca1df60bB79Ce92cE3Ea74c37c5d359",
+        "0xdbF03B407c01E7cD3CBea99509d93f8DDDC8C6FB",
+        "0xD1220A0cf47c7B9Be7A2E6BA89F429762e7b9aDb",
+    ] {
+        let address: Address = checksummed.to_lowercase().parse().unwrap();
+        assert_eq!(address.to_string(), checksummed);
+        assert_eq!(address.to_hex(), checksummed.to_lowercase());
+    }
+    assert!("0x5aAeb6053F3E94C9b9A09f33669435E7Ef1BeA".parse::<Address>().is_err());
+
+    let root: Root = "0x00000000000000000000000000000000000000000000000000000000000000ff".parse().unwrap();
+    assert_eq!(root, Root([0; 31].into_iter().chain([0xff]).collect::<Vec<_>>().try_into().unwrap()));
+    assert_eq!(root.to_string().parse::<Root>().unwrap(), root);
+    let bytes: Bytes = "0x00abc".parse().unwrap();
+    assert_eq!((bytes.to_string(), Bytes8([0, 0, 0, 0, 0, 0, 0, 1]).to_hex()), ("0x000abc".to_string(), "0x0000000000000001".to_string()));
+}
@@ -1,15 +1,19 @@
 //! https://github.com/ethereum/execution-specs/blob/master/src/ethereum/cancun/state.py
 
-use std::collections::{BTreeMap, HashSet};
+use std::{borrow::Cow, collections::{BTreeMap, BTreeSet, HashSet}, ops::RangeBounds, sync::{Arc, Mutex}};
 
 use crate::{
-    ethereum::ethereum_types::{bytes::Bytes32, numeric::U256},
-    ethereum::cancun::fork_types::{Account, Address},
+    ethereum::ethereum_types::{bytes::{Bytes, Bytes32}, numeric::{Uint, U256}},
+    ethereum::cancun::{blocks::Withdrawal, fork_types::{Account, Address}},
+    ethereum::ethereum_rlp::exceptions::RLPException,
+    impl_json,
+    json::{Encoder, JsonEncode},
+    metrics,
 };
 
-use super::{fork_types::EMPTY_ACCOUNT, trie::Trie};
+use super::{fork_types::{encode_account, Root, EMPTY_ACCOUNT}, trie::{Trie, EMPTY_TRIE_ROOT}};
 
-#[derive(Default, Debug)]
+#[derive(Default, Debug, Clone)]
 /// Contains all information that is preserved between transactions.
 pub struct State {
     main_trie: Trie<Address, Option<Account>>,
@@ -19,6 +23,18 @@ pub struct State {
         BTreeMap<Address, Trie<Bytes32, U256>>,
     )>,
     created_accounts: HashSet<Address>,
+    // Shared by clones of the state, which is what makes the reads of
+    // `&State` loggable.
+    access_log: Option<Arc<Mutex<AccessLog>>>,
+    /// Values of the accounts and slots before their first write since
+    /// `start_diff`.
+    originals: Option<Box<Originals>>,
+}
+
+#[derive(Default, Debug, Clone)]
+struct Originals {
+    accounts: BTreeMap<Address, Option<Account>>,
+    storage: BTreeMap<(Address, Bytes32), U256>,
 }
 
 impl State {
@@ -29,6 +45,418 @@ impl State {
         }
         state
     }
+
+    /// The accounts which exist, in address order.
+    pub fn accounts(&self) -> impl Iterator<Item = (&Address, &Account)> {
+        self.main_trie.data().iter().filter_map(|(address, account)| Some((address, account.as_ref()?)))
+    }
+
+    /// The non-zero storage slots of the account at `address`, in key order.
+    pub fn storage(&self, address: &Address) -> impl Iterator<Item = (&Bytes32, &U256)> {
+        self.storage_tries.get(address).into_iter().flat_map(|trie| trie.data())
+    }
+
+    /// The accounts with addresses in `addresses` and their storage, in the
+    /// layout of the `alloc` of a genesis file.
+    pub fn dump(&self, addresses: impl RangeBounds<Address>) -> Alloc {
+        self.main_trie
+            .data()
+            .range(addresses)
+            .filter_map(|(address, account)| Some((address.clone(), self.dump_account(address, account.as_ref()?))))
+            .collect()
+    }
+
+    fn dump_account(&self, address: &Address, account: &Account) -> AllocAccount {
+        AllocAccount {
+            balance: account.balance,
+            code: account.code.clone(),
+            nonce: account.nonce,
+            storage: self.storage(address).map(|(key, value)| (U256::from_be_bytes(key.0), *value)).collect(),
+        }
+    }
+
+    /// Compute the changes needed to turn `before` into `after`.
+    ///
+    /// Both states must be outside of a transaction.
+    pub fn diff(before: &State, after: &State) -> StateDiff {
+        assert!(before.snapshots.is_empty() && after.snapshots.is_empty());
+        let mut diff = StateDiff::default();
+        for (address, old, new) in diff_maps(before.main_trie.data(), after.main_trie.data(), &None) {
+            diff.accounts.insert(address, (old, new));
+        }
+
+        let empty = Trie::new(true, U256::ZERO);
+        let addresses = before.storage_tries.keys().chain(after.storage_tries.keys());
+        for address in addresses {
+            let old = before.storage_tries.get(address).unwrap_or(&empty);
+            let new = after.storage_tries.get(address).unwrap_or(&empty);
+            for (key, old, new) in diff_maps(old.data(), new.data(), &U256::ZERO) {
+                diff.storage.insert((address.clone(), key), (old, new));
+            }
+        }
+        diff
+    }
+
+    /// Apply the "after" side of `diff` to this state.
+    pub fn apply_diff(&mut self, diff: &StateDiff) {
+        for (address, (_, new)) in &diff.accounts {
+            self.set_account_value(address, new.clone());
+        }
+        for ((address, key), (_, new)) in &diff.storage {
+            self.set_storage_value(address, key, *new);
+        }
+    }
+
+    /// Apply the "before" side of `diff`, undoing a previous `apply_diff`.
+    pub fn revert_diff(&mut self, diff: &StateDiff) {
+        for (address, (old, _)) in &diff.accounts {
+            self.set_account_value(address, old.clone());
+        }
+        for ((address, key), (old, _)) in &diff.storage {
+            self.set_storage_value(address, key, *old);
+        }
+    }
+
+    /// Start recording the accounts and storage slots that are read and
+    /// written, replacing any log which was already being recorded.
+    pub fn start_access_log(&mut self) {
+        self.access_log = Some(Arc::default());
+    }
+
+    /// Stop recording accesses and return what was recorded since
+    /// `start_access_log`.
+    pub fn take_access_log(&mut self) -> Option<AccessLog> {
+        let log = self.access_log.take()?;
+        Some(std::mem::take(&mut *log.lock().unwrap()))
+    }
+
+    /// Start recording the changes made to the state, replacing any which
+    /// were already being recorded.
+    ///
+    /// Unlike `State::diff`, which compares every account of two states,
+    /// this only looks at the accounts and slots which are written.
+    pub fn start_diff(&mut self) {
+        self.originals = Some(Box::default());
+    }
+
+    /// Stop recording changes and return those made since `start_diff`.
+    ///
+    /// The state must be outside of a transaction.
+    pub fn take_diff(&mut self) -> Option<StateDiff> {
+        assert!(self.snapshots.is_empty());
+        let originals = self.originals.take()?;
+        let mut diff = StateDiff::default();
+        for (address, old) in originals.accounts {
+            let new = self.main_trie.data().get(&address).cloned().flatten();
+            if old != new {
+                diff.accounts.insert(address, (old, new));
+            }
+        }
+        for ((address, key), old) in originals.storage {
+            let new = self.storage_tries.get(&address).map_or(U256::ZERO, |trie| trie.get(&key));
+            if old != new {
+                diff.storage.insert((address, key), (old, new));
+            }
+        }
+        Some(diff)
+    }
+
+    /// Undo the changes of every open transaction.
+    pub fn rollback_transactions(&mut self) {
+        while !self.snapshots.is_empty() {
+            self.rollback_transaction();
+        }
+    }
+
+    fn log_read(&self, access: Access) {
+        if let Some(log) = &self.access_log {
+            log.lock().unwrap().reads.insert(access);
+        }
+    }
+
+    fn log_write(&self, access: Access) {
+        if let Some(log) = &self.access_log {
+            log.lock().unwrap().writes.insert(access);
+        }
+    }
+
+    fn set_account_value(&mut self, address: &Address, account: Option<Account>) {
+        if let Some(originals) = &mut self.originals {
+            originals.accounts.entry(address.clone()).or_insert_with(|| self.main_trie.data().get(address).cloned().flatten());
+        }
+        self.main_trie.set(address.clone(), account);
+    }
+
+    fn set_storage_value(&mut self, address: &Address, key: &Bytes32, value: U256) {
+        if let Some(originals) = &mut self.originals {
+            let old = self.storage_tries.get(address).map_or(U256::ZERO, |trie| trie.get(key));
+            originals.storage.entry((address.clone(), *key)).or_insert(old);
+        }
+        let trie = self.storage_tries
+            .entry(address.clone())
+            .or_insert_with(|| Trie::new(true, U256::ZERO));
+        trie.set(*key, value);
+        if trie.data().is_empty() {
+            self.storage_tries.remove(address);
+        }
+    }
+}
+
+/// The state as the virtual machine sees it: accounts and storage slots
+/// which can be read and written, inside nested transactions which are
+/// committed or rolled back.
+///
+/// `State` keeps everything in memory. Other backends fetch what is not
+/// written locally from elsewhere, such as a node or a revm database. The
+/// free functions of this module take any backend and are built on these
+/// operations.
+pub trait StateBackend {
+    /// The account at `address`, or `None` if there is none.
+    fn get_account_optional(&self, address: &Address) -> Option<Cow<'_, Account>>;
+
+    /// Set or, with `None`, delete the account at `address`, but not its
+    /// storage.
+    fn set_account(&mut self, address: &Address, account: Option<Account>);
+
+    /// The value of a storage slot, zero if it was never set.
+    fn get_storage(&self, address: &Address, key: &Bytes32) -> U256;
+
+    /// Set a storage slot of an account which exists. Setting zero deletes
+    /// the slot.
+    fn set_storage(&mut self, address: &Address, key: &Bytes32, value: U256);
+
+    /// The value of a storage slot before the current transaction began.
+    fn get_storage_original(&self, address: &Address, key: &Bytes32) -> U256;
+
+    /// Whether the account at `address` has any storage.
+    fn account_has_storage(&self, address: &Address) -> bool;
+
+    /// Delete all the storage of the account at `address`.
+    fn destroy_storage(&mut self, address: &Address);
+
+    /// Mark the account at `address` as created in the current transaction.
+    fn mark_account_created(&mut self, address: &Address);
+
+    /// Whether `mark_account_created` was called for `address` in the
+    /// current transaction.
+    fn is_account_created(&self, address: &Address) -> bool;
+
+    /// Start a nested transaction.
+    fn begin_transaction(&mut self);
+
+    /// Keep the changes of the innermost transaction.
+    fn commit_transaction(&mut self);
+
+    /// Undo the changes of the innermost transaction.
+    fn rollback_transaction(&mut self);
+
+    /// Run `f` on this state without recording the accesses it makes, for
+    /// backends which record them like `State::start_access_log`.
+    fn without_access_log(&mut self, f: &mut dyn FnMut(&mut dyn StateBackend));
+}
+
+impl StateBackend for State {
+    fn get_account_optional(&self, address: &Address) -> Option<Cow<'_, Account>> {
+        self.log_read(Access::Account(address.clone()));
+        self.main_trie.data().get(address).and_then(|account| account.as_ref()).map(Cow::Borrowed)
+    }
+
+    fn set_account(&mut self, address: &Address, account: Option<Account>) {
+        self.log_write(Access::Account(address.clone()));
+        self.set_account_value(address, account);
+    }
+
+    fn get_storage(&self, address: &Address, key: &Bytes32) -> U256 {
+        self.log_read(Access::Slot(address.clone(), *key));
+        match self.storage_tries.get(address) {
+            Some(trie) => trie.get(key),
+            None => U256::ZERO,
+        }
+    }
+
+    fn set_storage(&mut self, address: &Address, key: &Bytes32, value: U256) {
+        self.log_write(Access::Slot(address.clone(), *key));
+        self.set_storage_value(address, key, value);
+    }
+
+    fn get_storage_original(&self, address: &Address, key: &Bytes32) -> U256 {
+        self.log_read(Access::Slot(address.clone(), *key));
+        // In the transaction where an account is created, its preexisting storage
+        // is ignored.
+        if self.created_accounts.contains(address) {
+            return U256::ZERO;
+        }
+
+        let (_, original_trie) = &self.snapshots[0];
+        match original_trie.get(address) {
+            None => U256::ZERO,
+            Some(original_account_trie) => original_account_trie.get(key),
+        }
+    }
+
+    fn account_has_storage(&self, address: &Address) -> bool {
+        self.log_read(Access::Storage(address.clone()));
+        self.storage_tries.contains_key(address)
+    }
+
+    fn destroy_storage(&mut self, address: &Address) {
+        self.log_write(Access::Storage(address.clone()));
+        let trie = self.storage_tries.remove(address);
+        if let (Some(originals), Some(trie)) = (&mut self.originals, trie) {
+            for (key, value) in trie.data() {
+                originals.storage.entry((address.clone(), *key)).or_insert(*value);
+            }
+        }
+    }
+
+    fn mark_account_created(&mut self, address: &Address) {
+        self.created_accounts.insert(address.clone());
+    }
+
+    fn is_account_created(&self, address: &Address) -> bool {
+        self.created_accounts.contains(address)
+    }
+
+    fn begin_transaction(&mut self) {
+        self.snapshots.push((self.main_trie.clone(), self.storage_tries.clone()));
+    }
+
+    fn commit_transaction(&mut self) {
+        self.snapshots.pop();
+        if self.snapshots.is_empty() {
+            self.created_accounts.clear();
+        }
+    }
+
+    fn rollback_transaction(&mut self) {
+        let (main_trie, storage_tries) = self.snapshots.pop().unwrap();
+        self.main_trie = main_trie;
+        self.storage_tries = storage_tries;
+        if self.snapshots.is_empty() {
+            self.created_accounts.clear();
+        }
+    }
+
+    fn without_access_log(&mut self, f: &mut dyn FnMut(&mut dyn StateBackend)) {
+        let log = self.access_log.take();
+        f(self);
+        self.access_log = log;
+    }
+}
+
+/// The accounts and storage slots that differ between two states,
+/// stored as `(before, after)` pairs so that the change can be undone.
+#[derive(Default, Debug, Clone, PartialEq)]
+pub struct StateDiff {
+    pub accounts: BTreeMap<Address, (Option<Account>, Option<Account>)>,
+    pub storage: BTreeMap<(Address, Bytes32), (U256, U256)>,
+}
+
+impl StateDiff {
+    pub fn is_empty(&self) -> bool {
+        self.accounts.is_empty() && self.storage.is_empty()
+    }
+}
+
+/// An account and its storage, as in the `alloc` of a genesis file.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct AllocAccount {
+    pub balance: U256,
+    pub code: Bytes,
+    pub nonce: Uint,
+    pub storage: BTreeMap<U256, U256>,
+}
+
+impl_json!(#[skip_unknown] AllocAccount : balance "balance", code "code", nonce "nonce", storage "storage");
+
+/// The accounts of a state, as in the `alloc` of a genesis file.
+pub type Alloc = BTreeMap<Address, AllocAccount>;
+
+/// The fields of an account which a `StateDiff` changes.
+#[derive(Debug, Clone, Default)]
+struct ChangedAccount {
+    balance: Option<U256>,
+    code: Option<Bytes>,
+    nonce: Option<Uint>,
+    storage: Option<BTreeMap<U256, U256>>,
+}
+
+impl_json!(ChangedAccount : balance "balance", code "code", nonce "nonce", storage "storage");
+
+/// Encodes as `{"pre": alloc, "post": alloc}`, where each side holds only
+/// the fields and storage slots which changed. An account which is created
+/// is missing from `pre` and one which is destroyed is missing from `post`,
+/// unless some of its storage changed.
+impl JsonEncode for StateDiff {
+    fn encode_json(&self, encoder: &mut Encoder) {
+        let mut pre: BTreeMap<Address, ChangedAccount> = BTreeMap::new();
+        let mut post: BTreeMap<Address, ChangedAccount> = BTreeMap::new();
+        for (address, (old, new)) in &self.accounts {
+            let changed = |side: &Option<Account>, other: &Option<Account>| {
+                let account = side.as_ref()?;
+                let other = other.as_ref();
+                Some(ChangedAccount {
+                    balance: Some(account.balance).filter(|b| other.is_none_or(|o| o.balance != *b)),
+                    code: Some(account.code.clone()).filter(|c| other.is_none_or(|o| o.code != *c)),
+                    nonce: Some(account.nonce).filter(|n| other.is_none_or(|o| o.nonce != *n)),
+                    storage: None,
+                })
+            };
+            if let Some(account) = changed(old, new) {
+                pre.insert(address.clone(), account);
+            }
+            if let Some(account) = changed(new, old) {
+                post.insert(address.clone(), account);
+            }
+        }
+        for ((address, key), (old, new)) in &self.storage {
+            let key = U256::from_be_bytes(key.0);
+            for (side, value) in [(&mut pre, old), (&mut post, new)] {
+                let account = side.entry(address.clone()).or_default();
+                account.storage.get_or_insert_default().insert(key, *value);
+            }
+        }
+
+        let mut o = encoder.object();
+        o.field("pre", &pre);
+        o.field("post", &post);
+        o.end();
+    }
+}
+
+/// Entries of `a` and `b` that differ, treating missing keys as `default`.
+/// A part of the state which a transaction can read or write.
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Access {
+    Account(Address),
+    Slot(Address, Bytes32),
+    /// The whole storage of an account, read by `account_has_storage` and
+    /// written by `destroy_storage`.
+    Storage(Address),
+}
+
+/// The accesses recorded between `State::start_access_log` and
+/// `State::take_access_log`.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct AccessLog {
+    pub reads: BTreeSet<Access>,
+    pub writes: BTreeSet<Access>,
+}
+
+fn diff_maps<K : Ord + Clone, V : PartialEq + Clone>(a: &BTreeMap<K, V>, b: &BTreeMap<K, V>, default: &V) -> Vec<(K, V, V)> {
+    let mut res = Vec::new();
+    for (k, old) in a {
+        let new = b.get(k).unwrap_or(default);
+        if old != new {
+            res.push((k.clone(), old.clone(), new.clone()));
+        }
+    }
+    for (k, new) in b {
+        if !a.contains_key(k) && new != default {
+            res.push((k.clone(), default.clone(), new.clone()));
+        }
+    }
+    res
 }
 
 // Contains all information that is preserved between message calls
@@ -49,636 +477,564 @@ pub struct TransientStorage {
 //     del state._snapshots
 //     del state.created_accounts
 
-// def begin_transaction(
-//     state: State, transient_storage: TransientStorage
-// ) -> None:
-//     """
-//     Start a state transaction.
-
-//     Transactions are entirely implicit and can be nested. It is not possible to
-//     calculate the state root during a transaction.
-
-//     Parameters
-//     ----------
-//     state : State
-//         The state.
-//     transient_storage : TransientStorage
-//         The transient storage of the transaction.
-//     """
-//     state._snapshots.append(
-//         (
-//             copy_trie(state._main_trie),
-//             {k: copy_trie(t) for (k, t) in state._storage_tries.items()},
-//         )
-//     )
-//     transient_storage._snapshots.append(
-//         {k: copy_trie(t) for (k, t) in transient_storage._tries.items()}
-//     )
-
-// def commit_transaction(
-//     state: State, transient_storage: TransientStorage
-// ) -> None:
-//     """
-//     Commit a state transaction.
-
-//     Parameters
-//     ----------
-//     state : State
-//         The state.
-//     transient_storage : TransientStorage
-//         The transient storage of the transaction.
-//     """
-//     state._snapshots.pop()
-//     if not state._snapshots:
-//         state.created_accounts.clear()
+/// """
+/// Start a state transaction.
+/// 
+/// Transactions are entirely implicit and can be nested. It is not possible to
+/// calculate the state root during a transaction.
+/// 
+/// Parameters
+/// ----------
+/// state : State
+///     The state.
+/// transient_storage : TransientStorage
+///     The transient storage of the transaction.
+/// """
+pub fn begin_transaction<S: StateBackend + ?Sized>(state: &mut S, transient_storage: &mut TransientStorage) {
+    state.begin_transaction();
+    transient_storage.snapshots.push(transient_storage.tries.clone());
+}
 
-//     transient_storage._snapshots.pop()
+/// """
+/// Commit a state transaction.
+/// 
+/// Parameters
+/// ----------
+/// state : State
+///     The state.
+/// transient_storage : TransientStorage
+///     The transient storage of the transaction.
+/// """
+pub fn commit_transaction<S: StateBackend + ?Sized>(state: &mut S, transient_storage: &mut TransientStorage) {
+    state.commit_transaction();
+    transient_storage.snapshots.pop();
+}
 
-// def rollback_transaction(
-//     state: State, transient_storage: TransientStorage
-// ) -> None:
-//     """
-//     Rollback a state transaction, resetting the state to the point when the
-//     corresponding `start_transaction()` call was made.
-
-//     Parameters
-//     ----------
-//     state : State
-//         The state.
-//     transient_storage : TransientStorage
-//         The transient storage of the transaction.
-//     """
-//     state._main_trie, state._storage_tries = state._snapshots.pop()
-//     if not state._snapshots:
-//         state.created_accounts.clear()
+/// """
+/// Rollback a state transaction, resetting the state to the point when the
+/// corresponding `start_transaction()` call was made.
+/// 
+/// Parameters
+/// ----------
+/// state : State
+///     The state.
+/// transient_storage : TransientStorage
+///     The transient storage of the transaction.
+/// """
+pub fn rollback_transaction<S: StateBackend + ?Sized>(state: &mut S, transient_storage: &mut TransientStorage) {
+    state.rollback_transaction();
+    transient_storage.tries = transient_storage.snapshots.pop().unwrap();
+}
 
-//     transient_storage._tries = transient_storage._snapshots.pop()
+pub fn get_account<'state, S: StateBackend + ?Sized>(state: &'state S, address: &Address) -> Cow<'state, Account> {
+    get_account_optional(state, address).unwrap_or(Cow::Borrowed(&EMPTY_ACCOUNT))
+}
 
 /// """
-/// Get the `Account` object at an address. Returns `EMPTY_ACCOUNT` if there
-/// is no account at the address.
-/// 
-/// Use `get_account_optional()` if you care about the difference between a
-/// non-existent account and `EMPTY_ACCOUNT`.
+/// Get the `Account` object at an address. Returns `None` (rather than
+/// `EMPTY_ACCOUNT`) if there is no account at the address.
 /// 
 /// Parameters
 /// ----------
 /// state: `State`
-///     The state
+///    The state
 /// address : `Address`
-///     Address to lookup.
+///    Address to lookup.
 /// 
 /// Returns
 /// -------
 /// account : `Account`
-///     Account at address.
+///    Account at address.
 /// """
-pub fn get_account<'state, 'address>(state: &'state State, address: &'address Address) -> &'state Account {
-    if let Some(account) = get_account_optional(state, address) {
-        account
-    } else {
-        &EMPTY_ACCOUNT
-    }
+pub fn get_account_optional<'state, S: StateBackend + ?Sized>(state: &'state S, address: &Address) -> Option<Cow<'state, Account>> {
+    state.get_account_optional(address)
 }
 
 /// """
-/// Get the `Account` object at an address. Returns `None` (rather than
-/// `EMPTY_ACCOUNT`) if there is no account at the address.
+/// Set the `Account` object at an address. Setting to `None` deletes
+/// the account (but not its storage, see `destroy_account()`).
 /// 
 /// Parameters
 /// ----------
 /// state: `State`
 ///     The state
 /// address : `Address`
-///     Address to lookup.
-/// 
-/// Returns
-/// -------
+///     Address to set.
 /// account : `Account`
-///     Account at address.
+///     Account to set at address.
 /// """
-pub fn get_account_optional<'state, 'address>(state: &'state State, address: &'address Address) -> Option<&'state Account> {
-    // trie_get(state.main_trie, address)
-    todo!()
+pub fn set_account<S: StateBackend + ?Sized>(state: &mut S, address: &Address, account: Option<Account>) {
+    state.set_account(address, account);
 }
 
-// def set_account(
-//     state: State, address: Address, account: Optional[Account]
-// ) -> None:
-//     """
-//     Set the `Account` object at an address. Setting to `None` deletes
-//     the account (but not its storage, see `destroy_account()`).
-
-//     Parameters
-//     ----------
-//     state: `State`
-//         The state
-//     address : `Address`
-//         Address to set.
-//     account : `Account`
-//         Account to set at address.
-//     """
-//     trie_set(state._main_trie, address, account)
-
-// def destroy_account(state: State, address: Address) -> None:
-//     """
-//     Completely remove the account at `address` and all of its storage.
-
-//     This function is made available exclusively for the `SELFDESTRUCT`
-//     opcode. It is expected that `SELFDESTRUCT` will be disabled in a future
-//     hardfork and this function will be removed.
-
-//     Parameters
-//     ----------
-//     state: `State`
-//         The state
-//     address : `Address`
-//         Address of account to destroy.
-//     """
-//     destroy_storage(state, address)
-//     set_account(state, address, None)
-
-// def destroy_storage(state: State, address: Address) -> None:
-//     """
-//     Completely remove the storage at `address`.
-
-//     Parameters
-//     ----------
-//     state: `State`
-//         The state
-//     address : `Address`
-//         Address of account whose storage is to be deleted.
-//     """
-//     if address in state._storage_tries:
-//         del state._storage_tries[address]
-
-// def mark_account_created(state: State, address: Address) -> None:
-//     """
-//     Mark an account as having been created in the current transaction.
-//     This information is used by `get_storage_original()` to handle an obscure
-//     edgecase.
-
-//     The marker is not removed even if the account creation reverts. Since the
-//     account cannot have had code prior to its creation and can't call
-//     `get_storage_original()`, this is harmless.
-
-//     Parameters
-//     ----------
-//     state: `State`
-//         The state
-//     address : `Address`
-//         Address of the account that has been created.
-//     """
-//     state.created_accounts.add(address)
-
-// def get_storage(state: State, address: Address, key: Bytes32) -> U256:
-//     """
-//     Get a value at a storage key on an account. Returns `U256(0)` if the
-//     storage key has not been set previously.
-
-//     Parameters
-//     ----------
-//     state: `State`
-//         The state
-//     address : `Address`
-//         Address of the account.
-//     key : `Bytes`
-//         Key to lookup.
-
-//     Returns
-//     -------
-//     value : `U256`
-//         Value at the key.
-//     """
-//     trie = state._storage_tries.get(address)
-//     if trie is None:
-//         return U256(0)
-
-//     value = trie_get(trie, key)
-
-//     assert isinstance(value, U256)
-//     return value
-
-// def set_storage(
-//     state: State, address: Address, key: Bytes32, value: U256
-// ) -> None:
-//     """
-//     Set a value at a storage key on an account. Setting to `U256(0)` deletes
-//     the key.
-
-//     Parameters
-//     ----------
-//     state: `State`
-//         The state
-//     address : `Address`
-//         Address of the account.
-//     key : `Bytes`
-//         Key to set.
-//     value : `U256`
-//         Value to set at the key.
-//     """
-//     assert trie_get(state._main_trie, address) is not None
-
-//     trie = state._storage_tries.get(address)
-//     if trie is None:
-//         trie = Trie(secured=True, default=U256(0))
-//         state._storage_tries[address] = trie
-//     trie_set(trie, key, value)
-//     if trie._data == {}:
-//         del state._storage_tries[address]
-
-// def storage_root(state: State, address: Address) -> Root:
-//     """
-//     Calculate the storage root of an account.
-
-//     Parameters
-//     ----------
-//     state:
-//         The state
-//     address :
-//         Address of the account.
-
-//     Returns
-//     -------
-//     root : `Root`
-//         Storage root of the account.
-//     """
-//     assert not state._snapshots
-//     if address in state._storage_tries:
-//         return root(state._storage_tries[address])
-//     else:
-//         return EMPTY_TRIE_ROOT
-
-// def state_root(state: State) -> Root:
-//     """
-//     Calculate the state root.
-
-//     Parameters
-//     ----------
-//     state:
-//         The current state.
-
-//     Returns
-//     -------
-//     root : `Root`
-//         The state root.
-//     """
-//     assert not state._snapshots
-
-//     def get_storage_root(address: Address) -> Root:
-//         return storage_root(state, address)
-
-//     return root(state._main_trie, get_storage_root=get_storage_root)
-
-// def account_exists(state: State, address: Address) -> bool:
-//     """
-//     Checks if an account exists in the state trie
-
-//     Parameters
-//     ----------
-//     state:
-//         The state
-//     address:
-//         Address of the account that needs to be checked.
-
-//     Returns
-//     -------
-//     account_exists : `bool`
-//         True if account exists in the state trie, False otherwise
-//     """
-//     return get_account_optional(state, address) is not None
-
-// def account_has_code_or_nonce(state: State, address: Address) -> bool:
-//     """
-//     Checks if an account has non zero nonce or non empty code
-
-//     Parameters
-//     ----------
-//     state:
-//         The state
-//     address:
-//         Address of the account that needs to be checked.
-
-//     Returns
-//     -------
-//     has_code_or_nonce : `bool`
-//         True if the account has non zero nonce or non empty code,
-//         False otherwise.
-//     """
-//     account = get_account(state, address)
-//     return account.nonce != Uint(0) or account.code != b""
-
-// def account_has_storage(state: State, address: Address) -> bool:
-//     """
-//     Checks if an account has storage.
-
-//     Parameters
-//     ----------
-//     state:
-//         The state
-//     address:
-//         Address of the account that needs to be checked.
-
-//     Returns
-//     -------
-//     has_storage : `bool`
-//         True if the account has storage, False otherwise.
-//     """
-//     return address in state._storage_tries
-
-// def is_account_empty(state: State, address: Address) -> bool:
-//     """
-//     Checks if an account has zero nonce, empty code and zero balance.
-
-//     Parameters
-//     ----------
-//     state:
-//         The state
-//     address:
-//         Address of the account that needs to be checked.
-
-//     Returns
-//     -------
-//     is_empty : `bool`
-//         True if if an account has zero nonce, empty code and zero balance,
-//         False otherwise.
-//     """
-//     account = get_account(state, address)
-//     return (
-//         account.nonce == Uint(0)
-//         and account.code == b""
-//         and account.balance == 0
-//     )
-
-// def account_exists_and_is_empty(state: State, address: Address) -> bool:
-//     """
-//     Checks if an account exists and has zero nonce, empty code and zero
-//     balance.
-
-//     Parameters
-//     ----------
-//     state:
-//         The state
-//     address:
-//         Address of the account that needs to be checked.
-
-//     Returns
-//     -------
-//     exists_and_is_empty : `bool`
-//         True if an account exists and has zero nonce, empty code and zero
-//         balance, False otherwise.
-//     """
-//     account = get_account_optional(state, address)
-//     return (
-//         account is not None
-//         and account.nonce == Uint(0)
-//         and account.code == b""
-//         and account.balance == 0
-//     )
-
-// def is_account_alive(state: State, address: Address) -> bool:
-//     """
-//     Check whether is an account is both in the state and non empty.
-
-//     Parameters
-//     ----------
-//     state:
-//         The state
-//     address:
-//         Address of the account that needs to be checked.
-
-//     Returns
-//     -------
-//     is_alive : `bool`
-//         True if the account is alive.
-//     """
-//     account = get_account_optional(state, address)
-//     if account is None:
-//         return False
-//     else:
-//         return not (
-//             account.nonce == Uint(0)
-//             and account.code == b""
-//             and account.balance == 0
-//         )
-
-// def modify_state(
-//     state: State, address: Address, f: Callable[[Account], None]
-// ) -> None:
-//     """
-//     Modify an `Account` in the `State`.
-//     """
-//     set_account(state, address, modify(get_account(state, address), f))
-
-// def move_ether(
-//     state: State,
-//     sender_address: Address,
-//     recipient_address: Address,
-//     amount: U256,
-// ) -> None:
-//     """
-//     Move funds between accounts.
-//     """
-
-//     def reduce_sender_balance(sender: Account) -> None:
-//         if sender.balance < amount:
-//             raise AssertionError
-//         sender.balance -= amount
-
-//     def increase_recipient_balance(recipient: Account) -> None:
-//         recipient.balance += amount
-
-//     modify_state(state, sender_address, reduce_sender_balance)
-//     modify_state(state, recipient_address, increase_recipient_balance)
-
-// def process_withdrawal(
-//     state: State,
-//     wd: Withdrawal,
-// ) -> None:
-//     """
-//     Increase the balance of the withdrawing account.
-//     """
-
-//     def increase_recipient_balance(recipient: Account) -> None:
-//         recipient.balance += wd.amount * U256(10**9)
-
-//     modify_state(state, wd.address, increase_recipient_balance)
-
-// def set_account_balance(state: State, address: Address, amount: U256) -> None:
-//     """
-//     Sets the balance of an account.
-
-//     Parameters
-//     ----------
-//     state:
-//         The current state.
-
-//     address:
-//         Address of the account whose nonce needs to be incremented.
+/// """
+/// Completely remove the account at `address` and all of its storage.
+/// 
+/// This function is made available exclusively for the `SELFDESTRUCT`
+/// opcode. It is expected that `SELFDESTRUCT` will be disabled in a future
+/// hardfork and this function will be removed.
+/// 
+/// Parameters
+/// ----------
+/// state: `State`
+///     The state
+/// address : `Address`
+///     Address of account to destroy.
+/// """
+pub fn destroy_account<S: StateBackend + ?Sized>(state: &mut S, address: &Address) {
+    destroy_storage(state, address);
+    set_account(state, address, None);
+}
 
-//     amount:
-//         The amount that needs to set in balance.
-//     """
+/// """
+/// Completely remove the storage at `address`.
+/// 
+/// Parameters
+/// ----------
+/// state: `State`
+///     The state
+/// address : `Address`
+///     Address of account whose storage is to be deleted.
+/// """
+pub fn destroy_storage<S: StateBackend + ?Sized>(state: &mut S, address: &Address) {
+    state.destroy_storage(address);
+}
 
-//     def set_balance(account: Account) -> None:
-//         account.balance = amount
+/// """
+/// Mark an account as having been created in the current transaction.
+/// This information is used by `get_storage_original()` to handle an obscure
+/// edgecase.
+/// 
+/// The marker is not removed even if the account creation reverts. Since the
+/// account cannot have had code prior to its creation and can't call
+/// `get_storage_original()`, this is harmless.
+/// 
+/// Parameters
+/// ----------
+/// state: `State`
+///     The state
+/// address : `Address`
+///     Address of the account that has been created.
+/// """
+pub fn mark_account_created<S: StateBackend + ?Sized>(state: &mut S, address: &Address) {
+    state.mark_account_created(address);
+}
 
-//     modify_state(state, address, set_balance)
+/// """
+/// Check whether the account at `address` was created in the current
+/// transaction, as marked by `mark_account_created()`.
+/// 
+/// Parameters
+/// ----------
+/// state: `State`
+///     The state
+/// address : `Address`
+///     Address of the account to check.
+/// """
+pub fn is_account_created<S: StateBackend + ?Sized>(state: &S, address: &Address) -> bool {
+    state.is_account_created(address)
+}
 
-// def touch_account(state: State, address: Address) -> None:
-//     """
-//     Initializes an account to state.
+/// """
+/// Get a value at a storage key on an account. Returns `U256(0)` if the
+/// storage key has not been set previously.
+/// 
+/// Parameters
+/// ----------
+/// state: `State`
+///     The state
+/// address : `Address`
+///     Address of the account.
+/// key : `Bytes`
+///     Key to lookup.
+/// 
+/// Returns
+/// -------
+/// value : `U256`
+///     Value at the key.
+/// """
+pub fn get_storage<S: StateBackend + ?Sized>(state: &S, address: &Address, key: &Bytes32) -> U256 {
+    state.get_storage(address, key)
+}
 
-//     Parameters
-//     ----------
-//     state:
-//         The current state.
+/// """
+/// Set a value at a storage key on an account. Setting to `U256(0)` deletes
+/// the key.
+/// 
+/// Parameters
+/// ----------
+/// state: `State`
+///     The state
+/// address : `Address`
+///     Address of the account.
+/// key : `Bytes`
+///     Key to set.
+/// value : `U256`
+///     Value to set at the key.
+/// """
+pub fn set_storage<S: StateBackend + ?Sized>(state: &mut S, address: &Address, key: &Bytes32, value: U256) {
+    assert!(get_account_optional(state, address).is_some());
 
-//     address:
-//         The address of the account that need to initialised.
-//     """
-//     if not account_exists(state, address):
-//         set_account(state, address, EMPTY_ACCOUNT)
+    state.set_storage(address, key, value);
+}
 
-// def increment_nonce(state: State, address: Address) -> None:
-//     """
-//     Increments the nonce of an account.
+/// """
+/// Calculate the storage root of an account.
+/// 
+/// Parameters
+/// ----------
+/// state:
+///     The state
+/// address :
+///     Address of the account.
+/// 
+/// Returns
+/// -------
+/// root : `Root`
+///     Storage root of the account.
+/// """
+pub fn storage_root(state: &State, address: &Address) -> Result<Root, RLPException> {
+    assert!(state.snapshots.is_empty());
+    match state.storage_tries.get(address) {
+        Some(trie) => trie.root(),
+        None => Ok(EMPTY_TRIE_ROOT),
+    }
+}
 
-//     Parameters
-//     ----------
-//     state:
-//         The current state.
+/// """
+/// Calculate the state root.
+/// 
+/// Parameters
+/// ----------
+/// state:
+///     The current state.
+/// 
+/// Returns
+/// -------
+/// root : `Root`
+///     The state root.
+/// """
+pub fn state_root(state: &State) -> Result<Root, RLPException> {
+    assert!(state.snapshots.is_empty());
+    metrics::TRIE_HASHING.time(|| {
+        let mut trie: Trie<Address, Bytes> = Trie::new(true, Bytes::default());
+        for (address, account) in state.main_trie.data() {
+            if let Some(account) = account {
+                let storage_root = storage_root(state, address)?;
+                trie.set(address.clone(), encode_account(account, &storage_root)?);
+            }
+        }
+        trie.root()
+    })
+}
 
-//     address:
-//         Address of the account whose nonce needs to be incremented.
-//     """
+/// """
+/// Checks if an account exists in the state trie
+/// 
+/// Parameters
+/// ----------
+/// state:
+///     The state
+/// address:
+///     Address of the account that needs to be checked.
+/// 
+/// Returns
+/// -------
+/// account_exists : `bool`
+///     True if account exists in the state trie, False otherwise
+/// """
+pub fn account_exists<S: StateBackend + ?Sized>(state: &S, address: &Address) -> bool {
+    get_account_optional(state, address).is_some()
+}
 
-//     def increase_nonce(sender: Account) -> None:
-//         sender.nonce += Uint(1)
+/// """
+/// Checks if an account has non zero nonce or non empty code
+/// 
+/// Parameters
+/// ----------
+/// state:
+///     The state
+/// address:
+///     Address of the account that needs to be checked.
+/// 
+/// Returns
+/// -------
+/// has_code_or_nonce : `bool`
+///     True if the account has non zero nonce or non empty code,
+///     False otherwise.
+/// """
+pub fn account_has_code_or_nonce<S: StateBackend + ?Sized>(state: &S, address: &Address) -> bool {
+    let account = get_account(state, address);
+    account.nonce != 0 || !account.code.is_empty()
+}
 
-//     modify_state(state, address, increase_nonce)
+/// """
+/// Checks if an account has storage.
+/// 
+/// Parameters
+/// ----------
+/// state:
+///     The state
+/// address:
+///     Address of the account that needs to be checked.
+/// 
+/// Returns
+/// -------
+/// has_storage : `bool`
+///     True if the account has storage, False otherwise.
+/// """
+pub fn account_has_storage<S: StateBackend + ?Sized>(state: &S, address: &Address) -> bool {
+    state.account_has_storage(address)
+}
 
-// def set_code(state: State, address: Address, code: Bytes) -> None:
-//     """
-//     Sets Account code.
+/// """
+/// Checks if an account has zero nonce, empty code and zero balance.
+/// 
+/// Parameters
+/// ----------
+/// state:
+///     The state
+/// address:
+///     Address of the account that needs to be checked.
+/// 
+/// Returns
+/// -------
+/// is_empty : `bool`
+///     True if if an account has zero nonce, empty code and zero balance,
+///     False otherwise.
+/// """
+pub fn is_account_empty<S: StateBackend + ?Sized>(state: &S, address: &Address) -> bool {
+    let account = get_account(state, address);
+    account.nonce == 0 && account.code.is_empty() && account.balance.is_zero()
+}
 
-//     Parameters
-//     ----------
-//     state:
-//         The current state.
+/// """
+/// Checks if an account exists and has zero nonce, empty code and zero
+/// balance.
+/// 
+/// Parameters
+/// ----------
+/// state:
+///     The state
+/// address:
+///     Address of the account that needs to be checked.
+/// 
+/// Returns
+/// -------
+/// exists_and_is_empty : `bool`
+///     True if an account exists and has zero nonce, empty code and zero
+///     balance, False otherwise.
+/// """
+pub fn account_exists_and_is_empty<S: StateBackend + ?Sized>(state: &S, address: &Address) -> bool {
+    matches!(
+        get_account_optional(state, address),
+        Some(account) if account.nonce == 0 && account.code.is_empty() && account.balance.is_zero()
+    )
+}
 
-//     address:
-//         Address of the account whose code needs to be update.
+/// """
+/// Check whether is an account is both in the state and non empty.
+/// 
+/// Parameters
+/// ----------
+/// state:
+///     The state
+/// address:
+///     Address of the account that needs to be checked.
+/// 
+/// Returns
+/// -------
+/// is_alive : `bool`
+///     True if the account is alive.
+/// """
+pub fn is_account_alive<S: StateBackend + ?Sized>(state: &S, address: &Address) -> bool {
+    match get_account_optional(state, address) {
+        None => false,
+        Some(account) => !(account.nonce == 0 && account.code.is_empty() && account.balance.is_zero()),
+    }
+}
 
-//     code:
-//         The bytecode that needs to be set.
-//     """
+/// """
+/// Modify an `Account` in the `State`.
+/// """
+pub fn modify_state<S: StateBackend + ?Sized>(state: &mut S, address: &Address, f: impl FnOnce(&mut Account)) {
+    let mut account = get_account(state, address).into_owned();
+    f(&mut account);
+    set_account(state, address, Some(account));
+}
 
-//     def write_code(sender: Account) -> None:
-//         sender.code = code
+/// """
+/// Move funds between accounts.
+/// """
+pub fn move_ether<S: StateBackend + ?Sized>(state: &mut S, sender_address: &Address, recipient_address: &Address, amount: U256) {
+    modify_state(state, sender_address, |sender| {
+        assert!(sender.balance >= amount);
+        sender.balance = sender.balance - amount;
+    });
+    modify_state(state, recipient_address, |recipient| {
+        recipient.balance = recipient.balance + amount;
+    });
+}
 
-//     modify_state(state, address, write_code)
+/// """
+/// Increase the balance of the withdrawing account.
+/// """
+pub fn process_withdrawal<S: StateBackend + ?Sized>(state: &mut S, wd: &Withdrawal) {
+    modify_state(state, &wd.address, |recipient| {
+        recipient.balance = recipient.balance + wd.amount * U256::from(1_000_000_000_u64);
+    });
+}
 
-// def get_storage_original(state: State, address: Address, key: Bytes32) -> U256:
-//     """
-//     Get the original value in a storage slot i.e. the value before the current
-//     transaction began. This function reads the value from the snapshots taken
-//     before executing the transaction.
-
-//     Parameters
-//     ----------
-//     state:
-//         The current state.
-//     address:
-//         Address of the account to read the value from.
-//     key:
-//         Key of the storage slot.
-//     """
-//     # In the transaction where an account is created, its preexisting storage
-//     # is ignored.
-//     if address in state.created_accounts:
-//         return U256(0)
+/// """
+/// Sets the balance of an account.
+/// 
+/// Parameters
+/// ----------
+/// state:
+///     The current state.
+/// 
+/// address:
+///     Address of the account whose nonce needs to be incremented.
+/// 
+/// amount:
+///     The amount that needs to set in balance.
+/// """
+pub fn set_account_balance<S: StateBackend + ?Sized>(state: &mut S, address: &Address, amount: U256) {
+    modify_state(state, address, |account| account.balance = amount);
+}
 
-//     _, original_trie = state._snapshots[0]
-//     original_account_trie = original_trie.get(address)
+/// """
+/// Initializes an account to state.
+/// 
+/// Parameters
+/// ----------
+/// state:
+///     The current state.
+/// 
+/// address:
+///     The address of the account that need to initialised.
+/// """
+pub fn touch_account<S: StateBackend + ?Sized>(state: &mut S, address: &Address) {
+    if !account_exists(state, address) {
+        set_account(state, address, Some(EMPTY_ACCOUNT.clone()));
+    }
+}
 
-//     if original_account_trie is None:
-//         original_value = U256(0)
-//     else:
-//         original_value = trie_get(original_account_trie, key)
+/// """
+/// Increments the nonce of an account.
+/// 
+/// Parameters
+/// ----------
+/// state:
+///     The current state.
+/// 
+/// address:
+///     Address of the account whose nonce needs to be incremented.
+/// """
+pub fn increment_nonce<S: StateBackend + ?Sized>(state: &mut S, address: &Address) {
+    modify_state(state, address, |sender| sender.nonce += 1);
+}
 
-//     assert isinstance(original_value, U256)
+/// """
+/// Sets Account code.
+/// 
+/// Parameters
+/// ----------
+/// state:
+///     The current state.
+/// 
+/// address:
+///     Address of the account whose code needs to be update.
+/// 
+/// code:
+///     The bytecode that needs to be set.
+/// """
+pub fn set_code<S: StateBackend + ?Sized>(state: &mut S, address: &Address, code: Bytes) {
+    modify_state(state, address, |sender| sender.code = code);
+}
 
-//     return original_value
+/// """
+/// Get the original value in a storage slot i.e. the value before the current
+/// transaction began. This function reads the value from the snapshots taken
+/// before executing the transaction.
+/// 
+/// Parameters
+/// ----------
+/// state:
+///     The current state.
+/// address:
+///     Address of the account to read the value from.
+/// key:
+///     Key of the storage slot.
+/// """
+pub fn get_storage_original<S: StateBackend + ?Sized>(state: &S, address: &Address, key: &Bytes32) -> U256 {
+    state.get_storage_original(address, key)
+}
 
-// def get_transient_storage(
-//     transient_storage: TransientStorage, address: Address, key: Bytes32
-// ) -> U256:
-//     """
-//     Get a value at a storage key on an account from transient storage.
-//     Returns `U256(0)` if the storage key has not been set previously.
-//     Parameters
-//     ----------
-//     transient_storage: `TransientStorage`
-//         The transient storage
-//     address : `Address`
-//         Address of the account.
-//     key : `Bytes`
-//         Key to lookup.
-//     Returns
-//     -------
-//     value : `U256`
-//         Value at the key.
-//     """
-//     trie = transient_storage._tries.get(address)
-//     if trie is None:
-//         return U256(0)
+/// """
+/// Get a value at a storage key on an account from transient storage.
+/// Returns `U256(0)` if the storage key has not been set previously.
+/// Parameters
+/// ----------
+/// transient_storage: `TransientStorage`
+///     The transient storage
+/// address : `Address`
+///     Address of the account.
+/// key : `Bytes`
+///     Key to lookup.
+/// Returns
+/// -------
+/// value : `U256`
+///     Value at the key.
+/// """
+pub fn get_transient_storage(transient_storage: &TransientStorage, address: &Address, key: &Bytes32) -> U256 {
+    match transient_storage.tries.get(address) {
+        Some(trie) => trie.get(key),
+        None => U256::ZERO,
+    }
+}
 
-//     value = trie_get(trie, key)
+/// """
+/// Set a value at a storage key on an account. Setting to `U256(0)` deletes
+/// the key.
+/// Parameters
+/// ----------
+/// transient_storage: `TransientStorage`
+///     The transient storage
+/// address : `Address`
+///     Address of the account.
+/// key : `Bytes`
+///     Key to set.
+/// value : `U256`
+///     Value to set at the key.
+/// """
+pub fn set_transient_storage(transient_storage: &mut TransientStorage, address: &Address, key: &Bytes32, value: U256) {
+    let trie = transient_storage.tries
+        .entry(address.clone())
+        .or_insert_with(|| Trie::new(true, U256::ZERO));
+    trie.set(*key, value);
+    if trie.data().is_empty() {
+        transient_storage.tries.remove(address);
+    }
+}
 
-//     assert isinstance(value, U256)
-//     return value
+/// """
+/// Destroy all touched accounts that are empty.
+/// Parameters
+/// ----------
+/// state: `State`
+///     The current state.
+/// touched_accounts: `Set[Address]`
+///     All the accounts that have been touched in the current transaction.
+/// """
+pub fn destroy_touched_empty_accounts<S: StateBackend + ?Sized>(state: &mut S, touched_accounts: &BTreeSet<Address>) {
+    for address in touched_accounts {
+        if account_exists_and_is_empty(state, address) {
+            destroy_account(state, address);
+        }
+    }
+}
 
-// def set_transient_storage(
-//     transient_storage: TransientStorage,
-//     address: Address,
-//     key: Bytes32,
-//     value: U256,
-// ) -> None:
-//     """
-//     Set a value at a storage key on an account. Setting to `U256(0)` deletes
-//     the key.
-//     Parameters
-//     ----------
-//     transient_storage: `TransientStorage`
-//         The transient storage
-//     address : `Address`
-//         Address of the account.
-//     key : `Bytes`
-//         Key to set.
-//     value : `U256`
-//         Value to set at the key.
-//     """
-//     trie = transient_storage._tries.get(address)
-//     if trie is None:
-//         trie = Trie(secured=True, default=U256(0))
-//         transient_storage._tries[address] = trie
-//     trie_set(trie, key, value)
-//     if trie._data == {}:
-//         del transient_storage._tries[address]
-
-// def destroy_touched_empty_accounts(
-//     state: State, touched_accounts: Set[Address]
-// ) -> None:
-//     """
-//     Destroy all touched accounts that are empty.
-//     Parameters
-//     ----------
-//     state: `State`
-//         The current state.
-//     touched_accounts: `Set[Address]`
-//         All the accounts that have been touched in the current transaction.
-//     """
-//     for address in touched_accounts:
-//         if account_exists_and_is_empty(state, address):
-//             destroy_account(state, address)
+pub mod snapshot;
+#[cfg(feature = "revm")]
+pub mod database;
@@ -0,0 +1,366 @@
+//! Execution against a revm `Database`.
+//!
+//! A `DatabaseState` is a `StateBackend` over any database revm executes
+//! against, such as its `CacheDB` or a database of a node built on revm.
+//! The accounts and storage slots it reads are fetched from the database the
+//! first time and cached, and everything written stays local:
+//!
+//! ```
+//! use std::collections::BTreeSet;
+//!
+//! use ejit_evm::ethereum::{
+//!     cancun::{
+//!         fork_types::Address,
+//!         state::database::DatabaseState,
+//!         utils::prepare_message,
+//!         vm::{interpreter::process_message_call, Environment},
+//!     },
+//!     ethereum_types::{bytes::Bytes, numeric::U256},
+//!     forks::ExecutionRules,
+//! };
+//! use revm::db::{CacheDB, EmptyDB};
+//!
+//! let (caller, contract) = (Address::from_be_bytes([0xca; 20]), Address::from_be_bytes([0xc0; 20]));
+//! let mut state = DatabaseState::new(CacheDB::new(EmptyDB::default()));
+//! let mut env = Environment { gas_limit: 30_000_000, ..Environment::new(&mut state, ExecutionRules::CANCUN) };
+//! let message = prepare_message(
+//!     caller, Some(contract), U256::ZERO, Bytes::default(), 100_000, &env, None, true, false,
+//!     BTreeSet::new(), BTreeSet::new(),
+//! )?;
+//! let output = process_message_call(message, &mut env)?;
+//! assert!(output.error.is_none());
+//! state.take_error()?;
+//! # Ok::<(), Box<dyn std::error::Error>>(())
+//! ```
+//!
+//! As with `ForkedState`, the backend cannot fail, so an error of the
+//! database reads as an empty account or a zero slot and is kept for
+//! `take_error`, and only the slots which were read or written count
+//! towards whether an account has storage.
+//!
+//! `changes` reports the writes as revm reports those of a transaction, for
+//! `DatabaseCommit::commit`.
+
+use std::{
+    borrow::Cow,
+    cell::RefCell,
+    collections::{BTreeMap, BTreeSet, HashSet},
+};
+
+use revm::{
+    primitives::{AccountInfo, Bytecode, EvmState, EvmStorageSlot, KECCAK_EMPTY},
+    Database,
+};
+
+use crate::ethereum::{
+    cancun::fork_types::{Account, Address},
+    ethereum_types::{
+        bytes::{Bytes, Bytes32},
+        numeric::{Uint, U256},
+    },
+};
+
+use super::{
+    snapshot::{LocalStorage, Overlay},
+    StateBackend,
+};
+
+/// The state of a revm database, with local writes on top.
+pub struct DatabaseState<DB: Database> {
+    db: RefCell<DB>,
+    db_accounts: RefCell<BTreeMap<Address, Option<Account>>>,
+    db_storage: RefCell<BTreeMap<(Address, Bytes32), U256>>,
+    overlay: Overlay,
+    snapshots: Vec<Overlay>,
+    created_accounts: HashSet<Address>,
+    error: RefCell<Option<DB::Error>>,
+}
+
+impl<DB: Database> DatabaseState<DB> {
+    pub fn new(db: DB) -> Self {
+        Self {
+            db: RefCell::new(db),
+            db_accounts: RefCell::default(),
+            db_storage: RefCell::default(),
+            overlay: Overlay::default(),
+            snapshots: Vec::new(),
+            created_accounts: HashSet::new(),
+            error: RefCell::default(),
+        }
+    }
+
+    /// The first error of the database since the last call, if any.
+    pub fn take_error(&mut self) -> Result<(), DB::Error> {
+        match self.error.get_mut().take() {
+            Some(error) => Err(error),
+            None => Ok(()),
+        }
+    }
+
+    /// The accounts and slots written, as revm reports the changes of a
+    /// transaction. Accounts deleted are self-destructed, and those whose
+    /// storage was destroyed, as it is when they are created, are created.
+    pub fn changes(&self) -> EvmState {
+        let mut changes = EvmState::default();
+        let addresses: BTreeSet<&Address> = self.overlay.accounts.keys().chain(self.overlay.storage.keys()).collect();
+        for address in addresses {
+            let mut account = match self.get_account_optional(address) {
+                Some(account) => {
+                    let code = Bytecode::new_raw(account.code.0.clone().into());
+                    let balance = revm::primitives::U256::from_be_bytes(account.balance.to_be_bytes());
+                    revm::primitives::Account::from(AccountInfo::new(balance, account.nonce as u64, code.hash_slow(), code))
+                }
+                None => {
+                    let mut account = revm::primitives::Account::new_not_existing();
+                    account.mark_selfdestruct();
+                    account
+                }
+            };
+            account.mark_touch();
+            if let Some(storage) = self.overlay.storage.get(address) {
+                if storage.destroyed {
+                    account.mark_created();
+                }
+                for (key, value) in &storage.slots {
+                    let original = if storage.destroyed { U256::ZERO } else { self.db_storage(address, key) };
+                    let slot = EvmStorageSlot::new_changed(
+                        revm::primitives::U256::from_be_bytes(original.to_be_bytes()),
+                        revm::primitives::U256::from_be_bytes(value.to_be_bytes()),
+                    );
+                    account.storage.insert(revm::primitives::U256::from_be_bytes(key.0), slot);
+                }
+            }
+            changes.insert(revm::primitives::Address::from_slice(&address[..]), account);
+        }
+        changes
+    }
+
+    fn fail<T: Default>(&self, result: Result<T, DB::Error>) -> T {
+        result.unwrap_or_else(|error| {
+            self.error.borrow_mut().get_or_insert(error);
+            T::default()
+        })
+    }
+
+    fn db_account(&self, address: &Address) -> Option<Account> {
+        if let Some(account) = self.db_accounts.borrow().get(address) {
+            return account.clone();
+        }
+        let fetch = || -> Result<Option<Account>, DB::Error> {
+            let mut db = self.db.borrow_mut();
+            let Some(info) = db.basic(revm::primitives::Address::from_slice(&address[..]))? else {
+                return Ok(None);
+            };
+            let code = match info.code {
+                Some(code) => code.original_bytes(),
+                None if info.code_hash == KECCAK_EMPTY => Default::default(),
+                None => db.code_by_hash(info.code_hash)?.original_bytes(),
+            };
+            Ok(Some(Account {
+                nonce: info.nonce as Uint,
+                balance: U256::from_be_bytes(info.balance.to_be_bytes()),
+                code: Bytes(code.to_vec()),
+            }))
+        };
+        let account = self.fail(fetch());
+        self.db_accounts.borrow_mut().insert(address.clone(), account.clone());
+        account
+    }
+
+    fn db_storage(&self, address: &Address, key: &Bytes32) -> U256 {
+        let slot = (address.clone(), *key);
+        if let Some(value) = self.db_storage.borrow().get(&slot) {
+            return *value;
+        }
+        let address = revm::primitives::Address::from_slice(&address[..]);
+        let result = self.db.borrow_mut().storage(address, revm::primitives::U256::from_be_bytes(key.0));
+        let value = U256::from_be_bytes(self.fail(result).to_be_bytes());
+        self.db_storage.borrow_mut().insert(slot, value);
+        value
+    }
+}
+
+impl<DB: Database> StateBackend for DatabaseState<DB> {
+    fn get_account_optional(&self, address: &Address) -> Option<Cow<'_, Account>> {
+        match self.overlay.accounts.get(address) {
+            Some(account) => account.as_ref().map(Cow::Borrowed),
+            None => self.db_account(address).map(Cow::Owned),
+        }
+    }
+
+    fn set_account(&mut self, address: &Address, account: Option<Account>) {
+        self.overlay.accounts.insert(address.clone(), account);
+    }
+
+    fn get_storage(&self, address: &Address, key: &Bytes32) -> U256 {
+        self.overlay.storage(address, key).unwrap_or_else(|| self.db_storage(address, key))
+    }
+
+    fn set_storage(&mut self, address: &Address, key: &Bytes32, value: U256) {
+        self.overlay.storage.entry(address.clone()).or_default().slots.insert(*key, value);
+    }
+
+    fn get_storage_original(&self, address: &Address, key: &Bytes32) -> U256 {
+        // In the transaction where an account is created, its preexisting
+        // storage is ignored.
+        if self.created_accounts.contains(address) {
+            return U256::ZERO;
+        }
+        self.snapshots[0].storage(address, key).unwrap_or_else(|| self.db_storage(address, key))
+    }
+
+    fn account_has_storage(&self, address: &Address) -> bool {
+        let local = self.overlay.storage.get(address);
+        if local.is_some_and(|storage| storage.slots.values().any(|value| !value.is_zero())) {
+            return true;
+        }
+        if local.is_some_and(|storage| storage.destroyed) {
+            return false;
+        }
+        // The slots read from the database which were not written since.
+        let read = self.db_storage.borrow();
+        let mut slots = read.range((address.clone(), Bytes32([0; 32]))..=(address.clone(), Bytes32([0xff; 32])));
+        slots.any(|((_, key), value)| !value.is_zero() && local.is_none_or(|storage| !storage.slots.contains_key(key)))
+    }
+
+    fn destroy_storage(&mut self, address: &Address) {
+        self.overlay.storage.insert(address.clone(), LocalStorage { destroyed: true, slots: BTreeMap::new() });
+    }
+
+    fn mark_account_created(&mut self, address: &Address) {
+        self.created_accounts.insert(address.clone());
+    }
+
+    fn is_account_created(&self, address: &Address) -> bool {
+        self.created_accounts.contains(address)
+    }
+
+    fn begin_transaction(&mut self) {
+        self.snapshots.push(self.overlay.clone());
+    }
+
+    fn commit_transaction(&mut self) {
+        self.snapshots.pop();
+        if self.snapshots.is_empty() {
+            self.created_accounts.clear();
+        }
+    }
+
+    fn rollback_transaction(&mut self) {
+        self.overlay = self.snapshots.pop().unwrap();
+        if self.snapshots.is_empty() {
+            self.created_accounts.clear();
+        }
+    }
+
+    fn without_access_log(&mut self, f: &mut dyn FnMut(&mut dyn StateBackend)) {
+        f(self);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::BTreeSet;
+
+    use revm::{
+        db::{CacheDB, EmptyDB},
+        primitives::{AccountInfo, Bytecode, B256},
+        Database,
+    };
+
+    use crate::{
+        ethereum::cancun::{
+            state::{account_has_storage, begin_transaction, get_account_optional, rollback_transaction, TransientStorage},
+            utils::prepare_message,
+            vm::{interpreter::process_message_call, Environment},
+        },
+        prelude::*,
+    };
+
+    use super::DatabaseState;
+
+    /// A database whose every read fails.
+    struct Unavailable;
+
+    impl Database for Unavailable {
+        type Error = &'static str;
+
+        fn basic(&mut self, _: revm::primitives::Address) -> Result<Option<AccountInfo>, Self::Error> {
+            Err("unavailable")
+        }
+
+        fn code_by_hash(&mut self, _: B256) -> Result<Bytecode, Self::Error> {
+            Err("unavailable")
+        }
+
+        fn storage(&mut self, _: revm::primitives::Address, _: revm::primitives::U256) -> Result<revm::primitives::U256, Self::Error> {
+            Err("unavailable")
+        }
+
+        fn block_hash(&mut self, _: revm::primitives::U256) -> Result<B256, Self::Error> {
+            Err("unavailable")
+        }
+    }
+
+    #[test]
+    fn database_execution() {
+        let contract = Address::from_be_bytes([0xc0; 20]);
+        let slot = Bytes32([0; 32]);
+        // PUSH1 0 SLOAD PUSH1 1 ADD PUSH1 0 SSTORE STOP, with 41 in slot 0.
+        let code = Bytecode::new_raw(vec![0x60, 0x00, 0x54, 0x60, 0x01, 0x01, 0x60, 0x00, 0x55, 0x00].into());
+        let mut db = CacheDB::new(EmptyDB::default());
+        let revm_contract = revm::primitives::Address::from_slice(&contract[..]);
+        let info = AccountInfo { nonce: 1, code_hash: code.hash_slow(), code: Some(code), ..Default::default() };
+        db.insert_account_info(revm_contract, info);
+        db.insert_account_storage(revm_contract, revm::primitives::U256::ZERO, revm::primitives::U256::from(41)).unwrap();
+
+        let mut state = DatabaseState::new(db);
+        assert_eq!(get_account(&state, &contract).nonce, 1);
+        assert_eq!(get_account(&state, &contract).code.0.len(), 10);
+        assert!(!account_has_storage(&state, &contract));
+        assert_eq!(get_storage(&state, &contract, &slot), U256::from(41_u32));
+        assert!(account_has_storage(&state, &contract));
+
+        let caller = Address::from_be_bytes([0xca; 20]);
+        let mut env = Environment {
+            caller: caller.clone(),
+            origin: caller.clone(),
+            number: 1,
+            gas_limit: 1_000_000,
+            ..Environment::new(&mut state, ExecutionRules::CANCUN)
+        };
+        for _ in 0..2 {
+            let message = prepare_message(
+                caller.clone(),
+                Some(contract.clone()),
+                U256::ZERO,
+                Bytes::default(),
+                100_000,
+                &env,
+                None,
+                true,
+                false,
+                BTreeSet::new(),
+                BTreeSet::new(),
+            )
+            .unwrap();
+            assert_eq!(process_message_call(message, &mut env).unwrap().error, None);
+        }
+        assert_eq!(get_storage(&state, &contract, &slot), U256::from(43_u32));
+
+        // Local writes roll back to the local value, not the database's.
+        let mut transient_storage = TransientStorage::default();
+        begin_transaction(&mut state, &mut transient_storage);
+        set_storage(&mut state, &contract, &slot, U256::from(7_u32));
+        rollback_transaction(&mut state, &mut transient_storage);
+        assert_eq!(get_storage(&state, &contract, &slot), U256::from(43_u32));
+        assert!(get_account_optional(&state, &caller).is_none());
+        assert!(state.take_error().is_ok());
+
+        // A database which fails reads as empty and reports it.
+        let mut failing = DatabaseState::new(Unavailable);
+        assert!(get_account_optional(&failing, &contract).is_none());
+        assert_eq!(failing.take_error(), Err("unavailable"));
+        assert!(failing.take_error().is_ok());
+    }
+}
@@ -0,0 +1,521 @@
+//! Flat snapshots of the state at recent blocks.
+//!
+//! As in geth, a snapshot is a disk layer holding every account and storage
+//! slot in flat maps, under a stack of diff layers holding what each later
+//! block changed. Layers are known by the state root after their block, so
+//! several branches can sit on the same disk layer. Reading the state at a
+//! root walks its diff layers, newest first, and then the disk layer:
+//!
+//! ```
+//! # use ejit_evm::{ethereum::cancun::state::{snapshot::Snapshots, StateDiff}, prelude::*};
+//! # let (state, genesis_root, block_root) = (State::default(), Root::from([0; 32]), Root::from([1; 32]));
+//! # let (address, diff) = (Address::from_be_bytes([0xaa; 20]), StateDiff::default());
+//! let mut snapshots = Snapshots::new(genesis_root.clone(), &state);
+//! snapshots.update(block_root.clone(), genesis_root, &diff)?;
+//! let balance = snapshots.get(&block_root).unwrap().account(&address).map(|a| a.balance);
+//! # Ok::<(), Exception>(())
+//! ```
+//!
+//! `cap` folds the diff layers below the newest few into the disk layer.
+//! `flatten` does the merging apart from the snapshots, so that it can run
+//! on another thread while blocks are imported, and `install` swaps the
+//! result in.
+//!
+//! `SnapshotState` executes against a snapshot, keeping its writes aside so
+//! that they can be turned into the diff of the next layer.
+//!
+//! A `BlockChain` built `with_snapshots` adds the diff of each block it
+//! imports as a layer, keeping `SNAPSHOT_DIFF_LAYERS` of them below its
+//! head, and runs calls against the snapshot of the head.
+
+use std::{
+    borrow::Cow,
+    collections::{BTreeMap, HashSet},
+    sync::Arc,
+    thread::JoinHandle,
+};
+
+use crate::ethereum::{
+    cancun::fork_types::{Account, Address, Root},
+    ethereum_types::{bytes::Bytes32, numeric::U256},
+    exceptions::Exception,
+};
+
+use super::{State, StateBackend, StateDiff};
+
+/// Every account and non-zero storage slot of the state at `root`.
+#[derive(Debug, Clone, Default)]
+pub struct DiskLayer {
+    pub root: Root,
+    accounts: BTreeMap<Address, Account>,
+    storage: BTreeMap<(Address, Bytes32), U256>,
+}
+
+/// What one block changed, on top of the state at `parent`.
+#[derive(Debug, Clone)]
+pub struct DiffLayer {
+    pub root: Root,
+    pub parent: Root,
+    /// `None` for accounts which were deleted.
+    accounts: BTreeMap<Address, Option<Account>>,
+    /// Zero for slots which were deleted.
+    storage: BTreeMap<(Address, Bytes32), U256>,
+}
+
+/// The slots of `address` in a map keyed by address and slot.
+fn slots<'a>(
+    storage: &'a BTreeMap<(Address, Bytes32), U256>,
+    address: &Address,
+) -> impl Iterator<Item = (&'a Bytes32, &'a U256)> {
+    storage
+        .range((address.clone(), Bytes32([0; 32]))..=(address.clone(), Bytes32([0xff; 32])))
+        .map(|((_, key), value)| (key, value))
+}
+
+/// A disk layer and the diff layers on top of it.
+///
+/// The layers are shared, so cloning is cheap.
+#[derive(Debug, Clone, Default)]
+pub struct Snapshots {
+    disk: Arc<DiskLayer>,
+    layers: BTreeMap<Root, Arc<DiffLayer>>,
+}
+
+impl Snapshots {
+    /// Snapshot `state`, whose root is `root`. The state must be outside of
+    /// a transaction.
+    pub fn new(root: Root, state: &State) -> Self {
+        let accounts = state.accounts().map(|(address, account)| (address.clone(), account.clone())).collect();
+        let storage = state
+            .storage_tries
+            .iter()
+            .flat_map(|(address, trie)| trie.data().iter().map(|(key, value)| ((address.clone(), *key), *value)))
+            .collect();
+        Self { disk: Arc::new(DiskLayer { root, accounts, storage }), layers: BTreeMap::new() }
+    }
+
+    /// Root of the disk layer.
+    pub fn disk_root(&self) -> &Root {
+        &self.disk.root
+    }
+
+    /// Number of diff layers, on all branches.
+    pub fn diff_layers(&self) -> usize {
+        self.layers.len()
+    }
+
+    /// Add the layer of a block whose state changes are `diff`, from the
+    /// state at `parent` to that at `root`.
+    pub fn update(&mut self, root: Root, parent: Root, diff: &StateDiff) -> Result<(), Exception> {
+        if self.get(&parent).is_none() {
+            return Err(Exception::EthereumException("unknown parent snapshot"));
+        }
+        if root == self.disk.root || self.layers.contains_key(&root) {
+            return Ok(());
+        }
+        let accounts = diff.accounts.iter().map(|(address, (_, new))| (address.clone(), new.clone())).collect();
+        let storage = diff.storage.iter().map(|(slot, (_, new))| (slot.clone(), *new)).collect();
+        self.layers.insert(root.clone(), Arc::new(DiffLayer { root, parent, accounts, storage }));
+        Ok(())
+    }
+
+    /// The state at `root`, if it is the disk layer or one of the diff
+    /// layers.
+    pub fn get(&self, root: &Root) -> Option<SnapshotView<'_>> {
+        Some(SnapshotView { layers: self.branch(root)?, disk: &self.disk })
+    }
+
+    /// The diff layers from `root` down to the disk layer, newest first.
+    fn branch(&self, root: &Root) -> Option<Vec<&DiffLayer>> {
+        let mut branch = Vec::new();
+        let mut root = root;
+        while *root != self.disk.root {
+            let layer = self.layers.get(root)?;
+            branch.push(&**layer);
+            root = &layer.parent;
+        }
+        Some(branch)
+    }
+
+    /// The merging needed to keep at most `layers` diff layers below
+    /// `root`, or `None` if there is nothing to merge or `root` is unknown.
+    pub fn flatten(&self, root: &Root, layers: usize) -> Option<Flatten> {
+        let branch = self.branch(root)?;
+        if branch.len() <= layers {
+            return None;
+        }
+        let merged = branch[layers..].iter().rev().map(|layer| Arc::clone(&self.layers[&layer.root])).collect();
+        Some(Flatten { disk: Arc::clone(&self.disk), layers: merged })
+    }
+
+    /// Run `flatten` on a new thread.
+    pub fn flatten_in_background(&self, root: &Root, layers: usize) -> Option<JoinHandle<DiskLayer>> {
+        let flatten = self.flatten(root, layers)?;
+        Some(std::thread::spawn(move || flatten.run()))
+    }
+
+    /// Make `disk`, the result of a `Flatten`, the disk layer, dropping the
+    /// diff layers it holds and those of branches which do not descend from
+    /// it.
+    ///
+    /// Returns false, and changes nothing, if `disk` is not on top of the
+    /// current disk layer, which happens when another flattening was
+    /// installed first.
+    pub fn install(&mut self, disk: DiskLayer) -> bool {
+        if self.branch(&disk.root).is_none() {
+            return false;
+        }
+        let layers = &self.layers;
+        let descends = |root: &Root| {
+            let mut root = root;
+            while *root != disk.root {
+                match layers.get(root) {
+                    Some(layer) => root = &layer.parent,
+                    None => return false,
+                }
+            }
+            true
+        };
+        let kept = layers.iter().filter(|(root, _)| **root != disk.root && descends(root));
+        self.layers = kept.map(|(root, layer)| (root.clone(), Arc::clone(layer))).collect();
+        self.disk = Arc::new(disk);
+        true
+    }
+
+    /// Keep at most `layers` diff layers below `root`, merging the older ones
+    /// into the disk layer.
+    pub fn cap(&mut self, root: &Root, layers: usize) {
+        if let Some(flatten) = self.flatten(root, layers) {
+            self.install(flatten.run());
+        }
+    }
+}
+
+/// Diff layers to merge into a disk layer, apart from the `Snapshots` they
+/// were taken from.
+#[derive(Debug)]
+pub struct Flatten {
+    disk: Arc<DiskLayer>,
+    /// Oldest first.
+    layers: Vec<Arc<DiffLayer>>,
+}
+
+impl Flatten {
+    /// The disk layer at the root of the newest layer.
+    pub fn run(self) -> DiskLayer {
+        let mut disk = Arc::unwrap_or_clone(self.disk);
+        for layer in &self.layers {
+            for (address, account) in &layer.accounts {
+                match account {
+                    Some(account) => disk.accounts.insert(address.clone(), account.clone()),
+                    None => disk.accounts.remove(address),
+                };
+            }
+            for (slot, value) in &layer.storage {
+                if value.is_zero() {
+                    disk.storage.remove(slot);
+                } else {
+                    disk.storage.insert(slot.clone(), *value);
+                }
+            }
+            disk.root = layer.root.clone();
+        }
+        disk
+    }
+}
+
+/// The state at one root of a `Snapshots`.
+#[derive(Debug, Clone)]
+pub struct SnapshotView<'a> {
+    /// Newest first.
+    layers: Vec<&'a DiffLayer>,
+    disk: &'a DiskLayer,
+}
+
+impl<'a> SnapshotView<'a> {
+    pub fn root(&self) -> &'a Root {
+        self.layers.first().map_or(&self.disk.root, |layer| &layer.root)
+    }
+
+    /// The account at `address`, or `None` if there is none.
+    pub fn account(&self, address: &Address) -> Option<&'a Account> {
+        for layer in &self.layers {
+            if let Some(account) = layer.accounts.get(address) {
+                return account.as_ref();
+            }
+        }
+        self.disk.accounts.get(address)
+    }
+
+    /// The value of a storage slot, zero if it is not set.
+    pub fn storage(&self, address: &Address, key: &Bytes32) -> U256 {
+        let slot = (address.clone(), *key);
+        for layer in &self.layers {
+            if let Some(value) = layer.storage.get(&slot) {
+                return *value;
+            }
+        }
+        self.disk.storage.get(&slot).copied().unwrap_or(U256::ZERO)
+    }
+
+    /// The non-zero storage slots of the account at `address`.
+    pub fn storage_slots(&self, address: &Address) -> BTreeMap<Bytes32, U256> {
+        let mut storage: BTreeMap<Bytes32, U256> =
+            slots(&self.disk.storage, address).map(|(key, value)| (*key, *value)).collect();
+        for layer in self.layers.iter().rev() {
+            storage.extend(slots(&layer.storage, address).map(|(key, value)| (*key, *value)));
+        }
+        storage.retain(|_, value| !value.is_zero());
+        storage
+    }
+}
+
+/// The storage of an account as written on top of a snapshot.
+#[derive(Debug, Clone, Default)]
+pub(super) struct LocalStorage {
+    /// The storage was destroyed, so the slots not written since are zero
+    /// rather than those of the snapshot.
+    pub(super) destroyed: bool,
+    pub(super) slots: BTreeMap<Bytes32, U256>,
+}
+
+/// Writes on top of a snapshot, or of another state read from elsewhere.
+#[derive(Debug, Clone, Default)]
+pub(super) struct Overlay {
+    /// `None` for accounts deleted.
+    pub(super) accounts: BTreeMap<Address, Option<Account>>,
+    pub(super) storage: BTreeMap<Address, LocalStorage>,
+}
+
+impl Overlay {
+    /// The written value of a slot, `None` if it is that of the snapshot.
+    pub(super) fn storage(&self, address: &Address, key: &Bytes32) -> Option<U256> {
+        let storage = self.storage.get(address)?;
+        match storage.slots.get(key) {
+            Some(value) => Some(*value),
+            None => storage.destroyed.then_some(U256::ZERO),
+        }
+    }
+}
+
+/// A `StateBackend` reading from a snapshot, with the writes kept aside.
+#[derive(Debug, Clone)]
+pub struct SnapshotState<'a> {
+    view: SnapshotView<'a>,
+    overlay: Overlay,
+    snapshots: Vec<Overlay>,
+    created_accounts: HashSet<Address>,
+}
+
+impl<'a> SnapshotState<'a> {
+    pub fn new(view: SnapshotView<'a>) -> Self {
+        Self { view, overlay: Overlay::default(), snapshots: Vec::new(), created_accounts: HashSet::new() }
+    }
+
+    /// The changes written since `new`, as the diff for the layer on top of
+    /// the snapshot. Must be called outside of a transaction.
+    pub fn diff(&self) -> StateDiff {
+        assert!(self.snapshots.is_empty());
+        let mut diff = StateDiff::default();
+        for (address, new) in &self.overlay.accounts {
+            let old = self.view.account(address).cloned();
+            if old != *new {
+                diff.accounts.insert(address.clone(), (old, new.clone()));
+            }
+        }
+        for (address, local) in &self.overlay.storage {
+            let mut slots = if local.destroyed { self.view.storage_slots(address) } else { BTreeMap::new() };
+            slots.extend(local.slots.keys().map(|key| (*key, U256::ZERO)));
+            for key in slots.keys() {
+                let old = self.view.storage(address, key);
+                let new = self.overlay.storage(address, key).unwrap_or(old);
+                if old != new {
+                    diff.storage.insert((address.clone(), *key), (old, new));
+                }
+            }
+        }
+        diff
+    }
+}
+
+impl StateBackend for SnapshotState<'_> {
+    fn get_account_optional(&self, address: &Address) -> Option<Cow<'_, Account>> {
+        match self.overlay.accounts.get(address) {
+            Some(account) => account.as_ref().map(Cow::Borrowed),
+            None => self.view.account(address).map(Cow::Borrowed),
+        }
+    }
+
+    fn set_account(&mut self, address: &Address, account: Option<Account>) {
+        self.overlay.accounts.insert(address.clone(), account);
+    }
+
+    fn get_storage(&self, address: &Address, key: &Bytes32) -> U256 {
+        self.overlay.storage(address, key).unwrap_or_else(|| self.view.storage(address, key))
+    }
+
+    fn set_storage(&mut self, address: &Address, key: &Bytes32, value: U256) {
+        self.overlay.storage.entry(address.clone()).or_default().slots.insert(*key, value);
+    }
+
+    fn get_storage_original(&self, address: &Address, key: &Bytes32) -> U256 {
+        // In the transaction where an account is created, its preexisting
+        // storage is ignored.
+        if self.created_accounts.contains(address) {
+            return U256::ZERO;
+        }
+        self.snapshots[0].storage(address, key).unwrap_or_else(|| self.view.storage(address, key))
+    }
+
+    fn account_has_storage(&self, address: &Address) -> bool {
+        let local = self.overlay.storage.get(address);
+        if local.is_some_and(|storage| storage.slots.values().any(|value| !value.is_zero())) {
+            return true;
+        }
+        if local.is_some_and(|storage| storage.destroyed) {
+            return false;
+        }
+        let slots = self.view.storage_slots(address);
+        slots.keys().any(|key| local.is_none_or(|storage| !storage.slots.contains_key(key)))
+    }
+
+    fn destroy_storage(&mut self, address: &Address) {
+        self.overlay.storage.insert(address.clone(), LocalStorage { destroyed: true, slots: BTreeMap::new() });
+    }
+
+    fn mark_account_created(&mut self, address: &Address) {
+        self.created_accounts.insert(address.clone());
+    }
+
+    fn is_account_created(&self, address: &Address) -> bool {
+        self.created_accounts.contains(address)
+    }
+
+    fn begin_transaction(&mut self) {
+        self.snapshots.push(self.overlay.clone());
+    }
+
+    fn commit_transaction(&mut self) {
+        self.snapshots.pop();
+        if self.snapshots.is_empty() {
+            self.created_accounts.clear();
+        }
+    }
+
+    fn rollback_transaction(&mut self) {
+        self.overlay = self.snapshots.pop().unwrap();
+        if self.snapshots.is_empty() {
+            self.created_accounts.clear();
+        }
+    }
+
+    fn without_access_log(&mut self, f: &mut dyn FnMut(&mut dyn StateBackend)) {
+        f(self);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::ethereum::{
+        cancun::{
+            fork::fuzz::tests::valid_block,
+            fork_types::{Account, Address, Root},
+            state::{
+                account_has_storage, begin_transaction, destroy_storage, get_account, get_storage, rollback_transaction,
+                set_account_balance, set_storage, State, TransientStorage,
+            },
+        },
+        ethereum_types::{bytes::Bytes32, numeric::U256},
+    };
+
+    use super::{SnapshotState, SnapshotView, Snapshots};
+
+    /// Check that `view` reads as `state`.
+    fn assert_reads_as(view: &SnapshotView, state: &State) {
+        for (address, account) in state.accounts() {
+            assert_eq!(view.account(address), Some(account));
+            let storage = state.storage(address).map(|(key, value)| (*key, *value)).collect();
+            assert_eq!(view.storage_slots(address), storage);
+            for (key, value) in state.storage(address) {
+                assert_eq!(view.storage(address, key), *value);
+            }
+        }
+    }
+
+    #[test]
+    fn layers_and_flattening() {
+        let (mut chain, block) = valid_block();
+        let genesis_root = chain.blocks[0].header.state_root.clone();
+        let genesis = chain.state.clone();
+        let mut snapshots = Snapshots::new(genesis_root.clone(), &genesis);
+        let root = block.header.state_root.clone();
+        chain.import_block(block).unwrap();
+        let diff = State::diff(&genesis, &chain.state);
+        assert!(!diff.storage.is_empty());
+
+        assert!(snapshots.update(root.clone(), Root([1; 32]), &diff).is_err());
+        snapshots.update(root.clone(), genesis_root.clone(), &diff).unwrap();
+        // A sibling which reverts the block.
+        let sibling = Root([2; 32]);
+        let mut reverse = diff.clone();
+        reverse.accounts.values_mut().for_each(|(old, new)| std::mem::swap(old, new));
+        reverse.storage.values_mut().for_each(|(old, new)| std::mem::swap(old, new));
+        let child = Root([3; 32]);
+        snapshots.update(sibling.clone(), genesis_root.clone(), &Default::default()).unwrap();
+        snapshots.update(child.clone(), root.clone(), &reverse).unwrap();
+        assert_eq!(snapshots.diff_layers(), 3);
+
+        assert_reads_as(&snapshots.get(&genesis_root).unwrap(), &genesis);
+        assert_reads_as(&snapshots.get(&root).unwrap(), &chain.state);
+        assert_reads_as(&snapshots.get(&child).unwrap(), &genesis);
+        assert_eq!(snapshots.get(&child).unwrap().root(), &child);
+
+        let mut capped = snapshots.clone();
+        capped.cap(&child, 1);
+        assert_eq!((capped.disk_root(), capped.diff_layers()), (&root, 1));
+        assert!(capped.get(&genesis_root).is_none() && capped.get(&sibling).is_none());
+        assert_reads_as(&capped.get(&root).unwrap(), &chain.state);
+        assert_reads_as(&capped.get(&child).unwrap(), &genesis);
+
+        // Flattening elsewhere, then installing a stale result.
+        let stale = snapshots.flatten(&root, 0).unwrap().run();
+        let disk = snapshots.flatten_in_background(&child, 0).unwrap().join().unwrap();
+        assert!(snapshots.install(disk));
+        assert_eq!((snapshots.disk_root(), snapshots.diff_layers()), (&child, 0));
+        assert!(!snapshots.install(stale));
+        assert_reads_as(&snapshots.get(&child).unwrap(), &genesis);
+        assert!(snapshots.flatten(&child, 0).is_none());
+    }
+
+    #[test]
+    fn execution_on_a_snapshot() {
+        let a = Address::from_be_bytes([0xaa; 20]);
+        let b = Address::from_be_bytes([0xbb; 20]);
+        let (one, two) = (Bytes32([1; 32]), Bytes32([2; 32]));
+        let mut state = State::default();
+        set_account_balance(&mut state, &a, U256::from(5_u32));
+        set_account_balance(&mut state, &b, U256::from(6_u32));
+        set_storage(&mut state, &a, &one, U256::from(1_u32));
+        set_storage(&mut state, &a, &two, U256::from(2_u32));
+        let snapshots = Snapshots::new(Root::default(), &state);
+
+        let mut snapshot_state = SnapshotState::new(snapshots.get(&Root::default()).unwrap());
+        let mut transient_storage = TransientStorage::default();
+        begin_transaction(&mut snapshot_state, &mut transient_storage);
+        set_storage(&mut snapshot_state, &b, &one, U256::from(7_u32));
+        rollback_transaction(&mut snapshot_state, &mut transient_storage);
+        assert!(!account_has_storage(&snapshot_state, &b));
+
+        let mut changed = state.clone();
+        for state in [&mut changed as &mut dyn super::StateBackend, &mut snapshot_state] {
+            destroy_storage(state, &a);
+            set_storage(state, &a, &two, U256::from(3_u32));
+            set_account_balance(state, &b, U256::from(8_u32));
+        }
+        assert_eq!(get_storage(&snapshot_state, &a, &one), U256::ZERO);
+        assert_eq!(get_account(&snapshot_state, &b).balance, U256::from(8_u32));
+        assert_eq!(snapshot_state.diff(), State::diff(&state, &changed));
+        assert_eq!(get_account(&snapshot_state, &a), get_account(&state, &a));
+        assert_eq!(*get_account(&snapshot_state, &b), Account { balance: U256::from(8_u32), ..Default::default() });
+    }
+}
@@ -2,7 +2,11 @@
 //! submitted to be executed. If Ethereum is viewed as a state machine,
 //! transactions are the events that move between states.
 
-use crate::{ethereum::{cancun::{execptions::TransactionTypeError, fork_types::{Address, VersionedHash}}, crypto::{eliptic_curve::{secp256k1_recover, SECP256K1N}, hash::{keccak256, Hash32}}, ethereum_rlp::{exceptions::RLPException, rlp::{self, decode_to_sequence, encode_sequence, Extended}}, ethereum_types::{bytes::{Bytes, Bytes0, Bytes32}, numeric::{Uint, U256, U64}}, exceptions::Exception}, impl_extended};
+use crate::{ethereum::{cancun::{execptions::TransactionTypeError, fork_types::{Address, VersionedHash}}, crypto::{eliptic_curve::{secp256k1_recover, secp256k1_sign, SECP256K1N}, hash::{keccak256, Hash32}}, ethereum_rlp::{exceptions::RLPException, rlp::{self, decode_to_sequence, encode_sequence, DecodeOptions, Extended, Nullable}}, ethereum_types::{bytes::{Bytes, Bytes0, Bytes32}, numeric::{Uint, U256, U64}}, exceptions::Exception}, impl_extended, impl_json, json::{Decoder, JsonDecode, JsonError}};
+
+use crate::ethereum::prague::transactions::{signing_hash_7702, SetCodeTransaction, PER_EMPTY_ACCOUNT_COST};
+
+use crate::ethereum::forks::ExecutionRules;
 
 use super::vm::{gas::init_code_cost, interpreter::MAX_CODE_SIZE};
 
@@ -15,6 +19,8 @@ pub enum Either<A : std::fmt::Debug+Clone, B : std::fmt::Debug+Clone> {
 
 const TX_BASE_COST : Uint = 21000;
 const TX_DATA_COST_PER_NON_ZERO : Uint = 16;
+/// Before Istanbul (EIP-2028).
+const TX_DATA_COST_PER_NON_ZERO_FRONTIER : Uint = 68;
 const TX_DATA_COST_PER_ZERO : Uint = 4;
 const TX_CREATE_COST : Uint = 32000;
 const TX_ACCESS_LIST_ADDRESS_COST : Uint = 2400;
@@ -26,7 +32,7 @@ pub struct LegacyTransaction {
     pub nonce: U256,
     pub gas_price: Uint,
     pub gas: Uint,
-    pub to: Option<Address>,
+    pub to: Nullable<Address>,
     pub value: U256,
     pub data: Bytes,
     pub v: U256,
@@ -36,6 +42,18 @@ pub struct LegacyTransaction {
 
 impl_extended!(LegacyTransaction : nonce, gas_price, gas, to, value, data, v, r, s);
 
+impl LegacyTransaction {
+    /// The chain id signed over as in EIP-155, where `v` is
+    /// `chain_id * 2 + 35` or `chain_id * 2 + 36`.
+    ///
+    /// `None` for a transaction signed before EIP-155, with `v` of 27 or 28,
+    /// and for a `v` which is neither.
+    pub fn chain_id(&self) -> Option<U64> {
+        let v = self.v.to_uint().ok()?;
+        U64::try_from(v.checked_sub(35)? / 2).ok()
+    }
+}
+
 /// The transaction type added in EIP-2930 to support access lists.
 #[derive(Debug, Clone, Default)]
 pub struct AccessListTransaction {
@@ -43,7 +61,7 @@ pub struct AccessListTransaction {
     pub nonce: U256,
     pub gas_price: Uint,
     pub gas: Uint,
-    pub to: Option<Address>,
+    pub to: Nullable<Address>,
     pub value: U256,
     pub data: Bytes,
     pub access_list: Vec<(Address, Vec<Bytes32>)>,
@@ -64,7 +82,7 @@ pub struct FeeMarketTransaction {
     pub max_priority_fee_per_gas: Uint,
     pub max_fee_per_gas: Uint,
     pub gas: Uint,
-    pub to: Option<Address>,
+    pub to: Nullable<Address>,
     pub value: U256,
     pub data: Bytes,
     pub access_list: Vec<(Address, Vec<Bytes32>)>,
@@ -102,6 +120,8 @@ pub enum Transaction {
     AccessListTransaction(AccessListTransaction),
     FeeMarketTransaction(FeeMarketTransaction),
     BlobTransaction(BlobTransaction),
+    /// Added in Prague, see `prague::transactions`.
+    SetCodeTransaction(SetCodeTransaction),
 }
 
 impl Default for Transaction {
@@ -110,33 +130,205 @@ impl Default for Transaction {
     }
 }
 
-impl Extended for Transaction {
-    fn encode<'a, 'b>(&self, buffer: &'a mut Bytes) -> Result<(), RLPException> {
-        todo!()
+// Typed transactions are wrapped in a byte string, as in
+// `encode_transaction` and `decode_transaction`.
+impl_extended!(enum Transaction: LegacyTransaction, 0x01 => AccessListTransaction, 0x02 => FeeMarketTransaction, 0x03 => BlobTransaction, 0x04 => SetCodeTransaction);
+
+/// An entry of the `accessList` of a transaction object.
+#[derive(Debug, Clone, Default)]
+struct RpcAccessListEntry {
+    address: Address,
+    storage_keys: Vec<Bytes32>,
+}
+
+impl_json!(RpcAccessListEntry : address "address", storage_keys "storageKeys");
+
+/// The members of a transaction object, as returned by `eth_getTransactionByHash`
+/// and in the blocks of `eth_getBlockByNumber`, which make up a transaction of
+/// any type. The members which are not part of the transaction, such as
+/// `hash`, `from` and `blockNumber`, are skipped.
+#[derive(Debug, Clone, Default)]
+struct RpcTransaction {
+    tx_type: Option<U64>,
+    chain_id: Option<U64>,
+    nonce: U256,
+    gas_price: Option<Uint>,
+    max_priority_fee_per_gas: Option<Uint>,
+    max_fee_per_gas: Option<Uint>,
+    gas: Uint,
+    to: Option<Address>,
+    value: U256,
+    input: Bytes,
+    /// The older name of `input`, which it replaces if given.
+    data: Option<Bytes>,
+    access_list: Vec<RpcAccessListEntry>,
+    max_fee_per_blob_gas: Option<U256>,
+    blob_versioned_hashes: Vec<Hash32>,
+    /// For typed transactions, nodes give `v` too, equal to `yParity`.
+    y_parity: Option<U256>,
+    v: U256,
+    r: U256,
+    s: U256,
+}
+
+impl_json!(#[skip_unknown] RpcTransaction :
+    tx_type "type",
+    chain_id "chainId",
+    nonce "nonce",
+    gas_price "gasPrice",
+    max_priority_fee_per_gas "maxPriorityFeePerGas",
+    max_fee_per_gas "maxFeePerGas",
+    gas "gas",
+    to "to",
+    value "value",
+    input "input",
+    data "data",
+    access_list "accessList",
+    max_fee_per_blob_gas "maxFeePerBlobGas",
+    blob_versioned_hashes "blobVersionedHashes",
+    y_parity "yParity",
+    v "v",
+    r "r",
+    s "s",
+);
+
+impl RpcTransaction {
+    fn decode<'de>(decoder: &mut Decoder<'de>, tx_type: U64) -> Result<Self, JsonError> {
+        let mut tx = Self::default();
+        tx.decode_json(decoder)?;
+        if tx.tx_type.unwrap_or(0) != tx_type {
+            return Err(JsonError::InvalidValue("transaction of another type"));
+        }
+        Ok(tx)
     }
 
-    fn decode<'a, 'b>(&mut self, buffer: &'a mut &'b [u8]) -> Result<(), RLPException> {
-        if buffer.len() > 0 && buffer[0] >= 0xc0 {
-            let mut t = LegacyTransaction::default();
-            t.decode(buffer)?;
-            *self = Self::LegacyTransaction(t);
-        } else {
-            let mut bytes = Bytes::default();
-            bytes.decode(buffer)?;
-            if bytes.is_empty() {
-                return Err(RLPException::DecodingError("empty transaction"));
-            }
-            match bytes[0] {
-                0x01 => *self = Transaction::AccessListTransaction(rlp::decode_to::<AccessListTransaction>(&bytes[1..])?),
-                0x02 => *self = Transaction::FeeMarketTransaction(rlp::decode_to::<FeeMarketTransaction>(&bytes[1..])?),
-                0x03 => *self = Transaction::BlobTransaction(rlp::decode_to::<BlobTransaction>(&bytes[1..])?),
-                _ => return Err(RLPException::DecodingError("Bad transaction type")),
-            }
+    fn data(&mut self) -> Bytes {
+        self.data.take().unwrap_or_else(|| std::mem::take(&mut self.input))
+    }
+
+    fn access_list(&mut self) -> Vec<(Address, Vec<Bytes32>)> {
+        self.access_list.drain(..).map(|entry| (entry.address, entry.storage_keys)).collect()
+    }
+
+    fn y_parity(&self) -> U256 {
+        self.y_parity.unwrap_or(self.v)
+    }
+
+    fn into_transaction(self) -> Result<Transaction, JsonError> {
+        Ok(match self.tx_type.unwrap_or(0) {
+            0 => Transaction::LegacyTransaction(self.into_legacy()),
+            1 => Transaction::AccessListTransaction(self.into_access_list()),
+            2 => Transaction::FeeMarketTransaction(self.into_fee_market()),
+            3 => Transaction::BlobTransaction(self.into_blob()?),
+            _ => return Err(JsonError::InvalidValue("unsupported transaction type")),
+        })
+    }
+
+    fn into_legacy(mut self) -> LegacyTransaction {
+        LegacyTransaction {
+            nonce: self.nonce,
+            gas_price: self.gas_price.unwrap_or_default(),
+            gas: self.gas,
+            to: self.to.clone().into(),
+            value: self.value,
+            data: self.data(),
+            v: self.v,
+            r: self.r,
+            s: self.s,
         }
+    }
+
+    fn into_access_list(mut self) -> AccessListTransaction {
+        AccessListTransaction {
+            chain_id: self.chain_id.unwrap_or_default(),
+            nonce: self.nonce,
+            gas_price: self.gas_price.unwrap_or_default(),
+            gas: self.gas,
+            to: self.to.clone().into(),
+            value: self.value,
+            data: self.data(),
+            access_list: self.access_list(),
+            y_parity: self.y_parity(),
+            r: self.r,
+            s: self.s,
+        }
+    }
+
+    fn into_fee_market(mut self) -> FeeMarketTransaction {
+        FeeMarketTransaction {
+            chain_id: self.chain_id.unwrap_or_default(),
+            nonce: self.nonce,
+            max_priority_fee_per_gas: self.max_priority_fee_per_gas.unwrap_or_default(),
+            max_fee_per_gas: self.max_fee_per_gas.unwrap_or_default(),
+            gas: self.gas,
+            to: self.to.clone().into(),
+            value: self.value,
+            data: self.data(),
+            access_list: self.access_list(),
+            y_parity: self.y_parity(),
+            r: self.r,
+            s: self.s,
+        }
+    }
+
+    fn into_blob(mut self) -> Result<BlobTransaction, JsonError> {
+        Ok(BlobTransaction {
+            chain_id: self.chain_id.unwrap_or_default(),
+            nonce: self.nonce,
+            max_priority_fee_per_gas: self.max_priority_fee_per_gas.unwrap_or_default(),
+            max_fee_per_gas: self.max_fee_per_gas.unwrap_or_default(),
+            gas: self.gas,
+            to: self.to.clone().ok_or(JsonError::InvalidValue("blob transaction without to"))?,
+            value: self.value,
+            data: self.data(),
+            access_list: self.access_list(),
+            max_fee_per_blob_gas: self.max_fee_per_blob_gas.unwrap_or_default(),
+            blob_versioned_hashes: self.blob_versioned_hashes.iter().map(|hash| VersionedHash(hash.0)).collect(),
+            y_parity: self.y_parity(),
+            r: self.r,
+            s: self.s,
+        })
+    }
+}
+
+impl<'de> JsonDecode<'de> for LegacyTransaction {
+    fn decode_json(&mut self, decoder: &mut Decoder<'de>) -> Result<(), JsonError> {
+        *self = RpcTransaction::decode(decoder, 0)?.into_legacy();
         Ok(())
     }
 }
 
+impl<'de> JsonDecode<'de> for AccessListTransaction {
+    fn decode_json(&mut self, decoder: &mut Decoder<'de>) -> Result<(), JsonError> {
+        *self = RpcTransaction::decode(decoder, 1)?.into_access_list();
+        Ok(())
+    }
+}
+
+impl<'de> JsonDecode<'de> for FeeMarketTransaction {
+    fn decode_json(&mut self, decoder: &mut Decoder<'de>) -> Result<(), JsonError> {
+        *self = RpcTransaction::decode(decoder, 2)?.into_fee_market();
+        Ok(())
+    }
+}
+
+impl<'de> JsonDecode<'de> for BlobTransaction {
+    fn decode_json(&mut self, decoder: &mut Decoder<'de>) -> Result<(), JsonError> {
+        *self = RpcTransaction::decode(decoder, 3)?.into_blob()?;
+        Ok(())
+    }
+}
+
+/// A transaction object of any type before Prague, by its `type`. A
+/// transaction without one is a legacy transaction.
+impl<'de> JsonDecode<'de> for Transaction {
+    fn decode_json(&mut self, decoder: &mut Decoder<'de>) -> Result<(), JsonError> {
+        let mut tx = RpcTransaction::default();
+        tx.decode_json(decoder)?;
+        *self = tx.into_transaction()?;
+        Ok(())
+    }
+}
 
 
 macro_rules! extract {
@@ -148,12 +340,32 @@ macro_rules! extract {
                 AccessListTransaction(tx) => &tx.$field,
                 FeeMarketTransaction(tx) => &tx.$field,
                 BlobTransaction(tx) => &tx.$field,
+                SetCodeTransaction(tx) => &tx.$field,
             }
         }
     }
 }
 
 impl Transaction {
+    /// The hash which identifies the transaction: that of its encoding as
+    /// sent, which for the typed transactions includes the type.
+    pub fn hash(&self) -> Result<Hash32, Exception> {
+        Ok(keccak256(&encode_raw_transaction(self)?))
+    }
+
+    /// The chain id the transaction was signed for, `None` for a legacy
+    /// transaction signed before EIP-155.
+    pub fn chain_id(&self) -> Option<U64> {
+        use Transaction::*;
+        match self {
+            LegacyTransaction(tx) => tx.chain_id(),
+            AccessListTransaction(tx) => Some(tx.chain_id),
+            FeeMarketTransaction(tx) => Some(tx.chain_id),
+            BlobTransaction(tx) => Some(tx.chain_id),
+            SetCodeTransaction(tx) => Some(tx.chain_id),
+        }
+    }
+
     pub fn nonce(&self) -> &U256 {
         extract!(nonce, &self)
     }
@@ -165,9 +377,26 @@ impl Transaction {
             AccessListTransaction(tx) => Some(tx.gas_price),
             FeeMarketTransaction(tx) => None,
             BlobTransaction(tx) => None,
+            SetCodeTransaction(tx) => None,
         }
     }
 
+    /// The price paid per unit of gas in a block with `base_fee_per_gas`,
+    /// `None` before London: the gas price, or for the fee market types the
+    /// base fee with as much of the priority fee as the max fee allows.
+    pub fn effective_gas_price(&self, base_fee_per_gas: Option<Uint>) -> Uint {
+        use Transaction::*;
+        let (max_fee_per_gas, max_priority_fee_per_gas) = match self {
+            LegacyTransaction(tx) => return tx.gas_price,
+            AccessListTransaction(tx) => return tx.gas_price,
+            FeeMarketTransaction(tx) => (tx.max_fee_per_gas, tx.max_priority_fee_per_gas),
+            BlobTransaction(tx) => (tx.max_fee_per_gas, tx.max_priority_fee_per_gas),
+            SetCodeTransaction(tx) => (tx.max_fee_per_gas, tx.max_priority_fee_per_gas),
+        };
+        let base_fee_per_gas = base_fee_per_gas.unwrap_or_default();
+        base_fee_per_gas + max_priority_fee_per_gas.min(max_fee_per_gas.saturating_sub(base_fee_per_gas))
+    }
+
     pub fn gas(&self) -> &Uint {
         extract!(gas, &self)
     }
@@ -175,10 +404,11 @@ impl Transaction {
     pub fn to(&self) -> Option<Address> {
         use Transaction::*;
         match self {
-            LegacyTransaction(tx) => tx.to.clone(),
-            AccessListTransaction(tx) => tx.to.clone(),
-            FeeMarketTransaction(tx) => tx.to.clone(),
+            LegacyTransaction(tx) => tx.to.0.clone(),
+            AccessListTransaction(tx) => tx.to.0.clone(),
+            FeeMarketTransaction(tx) => tx.to.0.clone(),
             BlobTransaction(tx) => Some(tx.to.clone()),
+            SetCodeTransaction(tx) => Some(tx.to.clone()),
         }
     }
 
@@ -197,6 +427,7 @@ impl Transaction {
             AccessListTransaction(tx) => None,
             FeeMarketTransaction(tx) => None,
             BlobTransaction(tx) => None,
+            SetCodeTransaction(tx) => None,
         }
     }
 
@@ -215,6 +446,7 @@ impl Transaction {
             AccessListTransaction(tx) => Some(&tx.access_list),
             FeeMarketTransaction(tx) => Some(&tx.access_list),
             BlobTransaction(tx) => Some(&tx.access_list),
+            SetCodeTransaction(tx) => Some(&tx.access_list),
         }
     }
 }
@@ -229,6 +461,7 @@ pub fn encode_transaction(tx: &Transaction) -> Result<Either<LegacyTransaction,
         AccessListTransaction(tx) => Ok(Either::B(Bytes([&b"\x01"[..], &rlp::encode(tx)?].concat()))),
         FeeMarketTransaction(tx) => Ok(Either::B(Bytes([&b"\x02"[..], &rlp::encode(tx)?].concat()))),
         BlobTransaction(tx) => Ok(Either::B(Bytes([&b"\x03"[..], &rlp::encode(tx)?].concat()))),
+        SetCodeTransaction(tx) => Ok(Either::B(Bytes([&b"\x04"[..], &rlp::encode(tx)?].concat()))),
     }
 }
 
@@ -238,20 +471,43 @@ pub fn decode_transaction(tx: Either<LegacyTransaction, Bytes>) -> Result<Transa
     match tx {
         Either::A(tx) => Ok(Transaction::LegacyTransaction(tx)),
         Either::B(tx) => {
-            let tx = &*tx;
-            if tx[0] == 1 {
-                Ok(Transaction::AccessListTransaction(rlp::decode_to::<AccessListTransaction>(&tx[1..])?))
-            } else if tx[0] == 2 {
-                Ok(Transaction::FeeMarketTransaction(rlp::decode_to::<FeeMarketTransaction>(&tx[1..])?))
-            } else if tx[0] == 3 {
-                Ok(Transaction::BlobTransaction(rlp::decode_to::<BlobTransaction>(&tx[1..])?))
-            } else {
-                Err(Exception::TransactionTypeError{ transaction_type: tx[0] })
+            let Some((&transaction_type, payload)) = tx.split_first() else {
+                return Err(Exception::InvalidTransaction("empty transaction"));
+            };
+            // Strict, as legacy transactions are, within the limits of the
+            // decode in progress.
+            let options = DecodeOptions { strict: true, ..rlp::decode_options() };
+            match transaction_type {
+                1 => Ok(Transaction::AccessListTransaction(rlp::decode_with(payload, options)?)),
+                2 => Ok(Transaction::FeeMarketTransaction(rlp::decode_with(payload, options)?)),
+                3 => Ok(Transaction::BlobTransaction(rlp::decode_with(payload, options)?)),
+                4 => Ok(Transaction::SetCodeTransaction(rlp::decode_with(payload, options)?)),
+                _ => Err(Exception::TransactionTypeError{ transaction_type }),
             }
         }
     }
 }
 
+/// Encode a transaction in the form it is sent in, as by
+/// `eth_sendRawTransaction`. The inverse of `decode_raw_transaction`.
+pub fn encode_raw_transaction(tx: &Transaction) -> Result<Bytes, Exception> {
+    match encode_transaction(tx)? {
+        Either::A(tx) => Ok(rlp::encode(&tx)?),
+        Either::B(tx) => Ok(tx),
+    }
+}
+
+/// Decode a transaction in the form it is sent in, as by
+/// `eth_sendRawTransaction`: an RLP list for a legacy transaction, its type
+/// followed by its RLP for the others.
+pub fn decode_raw_transaction(raw: &[u8]) -> Result<Transaction, Exception> {
+    match raw.first() {
+        Some(0xc0..) => Ok(Transaction::LegacyTransaction(rlp::decode_to_strict(raw)?)),
+        Some(_) => decode_transaction(Either::B(Bytes(raw.to_vec()))),
+        None => Err(Exception::InvalidTransaction("empty transaction")),
+    }
+}
+
 
 /// """
 /// Verifies a transaction.
@@ -271,14 +527,16 @@ pub fn decode_transaction(tx: Either<LegacyTransaction, Bytes>) -> Result<Transa
 /// ----------
 /// tx :
 ///     Transaction to validate.
+/// rules :
+///     The rules of the fork of the block including the transaction.
 /// 
 /// Returns
 /// -------
 /// verified : `bool`
 ///     True if the transaction can be executed, or false otherwise.
 /// """
-pub fn validate_transaction(tx: &Transaction) -> bool {
-    if calculate_intrinsic_cost(tx) > *tx.gas() {
+pub fn validate_transaction(tx: &Transaction, rules: ExecutionRules) -> bool {
+    if calculate_intrinsic_cost(tx, rules) > *tx.gas() {
         return false;
     }
 
@@ -286,7 +544,7 @@ pub fn validate_transaction(tx: &Transaction) -> bool {
         return false;
     }
 
-    if tx.to().is_none() && tx.data().len() > 2 * MAX_CODE_SIZE {
+    if rules.shanghai && tx.to().is_none() && tx.data().len() > 2 * MAX_CODE_SIZE {
         return false;
     }
 
@@ -310,27 +568,32 @@ pub fn validate_transaction(tx: &Transaction) -> bool {
 /// ----------
 /// tx :
 ///     Transaction to compute the intrinsic cost of.
+/// rules :
+///     The rules of the fork of the block including the transaction.
 /// 
 /// Returns
 /// -------
 /// verified : `ethereum.base_types.Uint`
 ///     The intrinsic cost of the transaction.
 /// """
-pub fn calculate_intrinsic_cost(tx: &Transaction) -> Uint {
+pub fn calculate_intrinsic_cost(tx: &Transaction, rules: ExecutionRules) -> Uint {
     let mut data_cost = 0;
+    let cost_per_non_zero = if rules.istanbul { TX_DATA_COST_PER_NON_ZERO } else { TX_DATA_COST_PER_NON_ZERO_FRONTIER };
 
     for byte in tx.data() {
         if *byte == 0 {
             data_cost += TX_DATA_COST_PER_ZERO;
         } else {
-            data_cost += TX_DATA_COST_PER_NON_ZERO;
+            data_cost += cost_per_non_zero;
         }
     }
 
-    let create_cost = if tx.to().is_none() {
-        TX_CREATE_COST + init_code_cost(tx.data().len() as Uint)
-    } else {
-        0
+    // Creating a contract only costs more than a call from Homestead, and
+    // init code is only paid for from Shanghai.
+    let create_cost = match tx.to() {
+        None if rules.shanghai => TX_CREATE_COST + init_code_cost(tx.data().len() as Uint),
+        None if rules.homestead => TX_CREATE_COST,
+        _ => 0,
     };
 
     let mut access_list_cost = 0;
@@ -341,7 +604,12 @@ pub fn calculate_intrinsic_cost(tx: &Transaction) -> Uint {
         }
     }
 
-    return Uint::from(TX_BASE_COST + data_cost + create_cost + access_list_cost)
+    let auth_cost = match tx {
+        Transaction::SetCodeTransaction(tx) => PER_EMPTY_ACCOUNT_COST * tx.authorizations.len() as Uint,
+        _ => 0,
+    };
+
+    return Uint::from(TX_BASE_COST + data_cost + create_cost + access_list_cost + auth_cost)
 }
 
 
@@ -359,17 +627,22 @@ pub fn calculate_intrinsic_cost(tx: &Transaction) -> Uint {
 ///     Transaction of interest.
 /// chain_id :
 ///     ID of the executing chain.
+/// rules :
+///     The rules of the fork of the block including the transaction, which
+///     decide whether a high ``s`` (EIP-2) and a chain ID in ``v`` (EIP-155)
+///     are allowed.
 /// 
 /// Returns
 /// -------
 /// sender : `ethereum.fork_types.Address`
 ///     The address of the account that signed the transaction.
-pub fn recover_sender(chain_id: U64, tx: &Transaction) -> Result<Address, Exception> {
+pub fn recover_sender(chain_id: U64, tx: &Transaction, rules: ExecutionRules) -> Result<Address, Exception> {
     let (&r, &s) = (tx.r(), tx.s());
     if U256::from(0_u32) >= r || r >= SECP256K1N {
         return Err(Exception::InvalidSignatureError("bad r"));
     }
-    if U256::from(0_u32) >= s || s > SECP256K1N.shr(1) {
+    let max_s = if rules.homestead { SECP256K1N.shr(1) } else { SECP256K1N - U256::ONE };
+    if U256::from(0_u32) >= s || s > max_s {
         return Err(Exception::InvalidSignatureError("bad s"));
     }
 
@@ -377,21 +650,17 @@ pub fn recover_sender(chain_id: U64, tx: &Transaction) -> Result<Address, Except
     let public_key = match tx {
         LegacyTransaction(tx) => {
             let v = tx.v;
-            if v == U256::from(27_u32) || v == U256::from(28_u32) {
-                secp256k1_recover(
+            match tx.chain_id() {
+                None if v == U256::from(27_u32) || v == U256::from(28_u32) => secp256k1_recover(
                     r, s, v - U256::from(27_u32), signing_hash_pre155(tx)?
-                )
-            } else {
-                let chain_id_x2 = U256::from(chain_id * 2);
-                if v != U256::from(35_u32) + chain_id_x2 && v != U256::from(36_u32) + chain_id_x2 {
-                    return Err(Exception::InvalidSignatureError("bad v"));
-                }
-                secp256k1_recover(
+                )?,
+                Some(id) if id == chain_id && rules.spurious_dragon => secp256k1_recover(
                     r,
                     s,
-                    v - U256::from(35) - chain_id_x2,
+                    v - U256::from(35_u32) - U256::from(chain_id * 2),
                     signing_hash_155(tx, chain_id)?,
-                )
+                )?,
+                _ => return Err(Exception::InvalidSignatureError("bad v")),
             }
         }
         AccessListTransaction(tx) => {
@@ -400,7 +669,7 @@ pub fn recover_sender(chain_id: U64, tx: &Transaction) -> Result<Address, Except
             }
             secp256k1_recover(
                 r, s, tx.y_parity, signing_hash_2930(tx)?
-            )
+            )?
         }
         FeeMarketTransaction(tx) => {
             if tx.y_parity != U256::from(0_u32) && tx.y_parity != U256::from(1_u32) {
@@ -408,7 +677,7 @@ pub fn recover_sender(chain_id: U64, tx: &Transaction) -> Result<Address, Except
             }
             secp256k1_recover(
                 r, s, tx.y_parity, signing_hash_1559(tx)?
-            )
+            )?
         }
         BlobTransaction(tx) => {
             if tx.y_parity != U256::from(0_u32) && tx.y_parity != U256::from(1_u32) {
@@ -416,7 +685,15 @@ pub fn recover_sender(chain_id: U64, tx: &Transaction) -> Result<Address, Except
             }
             secp256k1_recover(
                 r, s, tx.y_parity, signing_hash_4844(tx)?
-            )
+            )?
+        }
+        SetCodeTransaction(tx) => {
+            if tx.y_parity != U256::from(0_u32) && tx.y_parity != U256::from(1_u32) {
+                return Err(Exception::InvalidSignatureError("bad y_parity"));
+            }
+            secp256k1_recover(
+                r, s, tx.y_parity, signing_hash_7702(tx)?
+            )?
         }
     };
 
@@ -574,3 +851,156 @@ pub fn signing_hash_4844(tx: &BlobTransaction) -> Result<Hash32, Exception> {
     ]);
     Ok(keccak256(&res))
 }
+
+/// Sign `tx` with `secret_key`, replacing its signature. A legacy
+/// transaction is signed for `chain_id` (EIP-155) if one is given, and
+/// without replay protection otherwise; the other types carry their own
+/// chain id. Fails if `secret_key` is not a valid secp256k1 secret key.
+pub fn sign_transaction(tx: &mut Transaction, secret_key: U256, chain_id: Option<U64>) -> Result<(), Exception> {
+    use Transaction::*;
+    match tx {
+        LegacyTransaction(tx) => {
+            let (r, s, v) = match chain_id {
+                Some(chain_id) => {
+                    let (r, s, v) = secp256k1_sign(signing_hash_155(tx, chain_id)?, secret_key)?;
+                    (r, s, v + U256::from(chain_id * 2 + 35))
+                }
+                None => {
+                    let (r, s, v) = secp256k1_sign(signing_hash_pre155(tx)?, secret_key)?;
+                    (r, s, v + U256::from(27_u32))
+                }
+            };
+            (tx.r, tx.s, tx.v) = (r, s, v);
+        }
+        AccessListTransaction(tx) => (tx.r, tx.s, tx.y_parity) = secp256k1_sign(signing_hash_2930(tx)?, secret_key)?,
+        FeeMarketTransaction(tx) => (tx.r, tx.s, tx.y_parity) = secp256k1_sign(signing_hash_1559(tx)?, secret_key)?,
+        BlobTransaction(tx) => (tx.r, tx.s, tx.y_parity) = secp256k1_sign(signing_hash_4844(tx)?, secret_key)?,
+        SetCodeTransaction(tx) => (tx.r, tx.s, tx.y_parity) = secp256k1_sign(signing_hash_7702(tx)?, secret_key)?,
+    }
+    Ok(())
+}
+
+pub mod builder;
+
+pub use builder::TxBuilder;
+
+#[cfg(test)]
+mod tests {
+    use crate::ethereum::{
+        cancun::fork_types::Address,
+        crypto::{eliptic_curve::secp256k1_public_key, hash::keccak256},
+        ethereum_types::numeric::U256,
+        forks::ExecutionRules,
+    };
+
+    use crate::json::from_json;
+
+    use super::{
+        decode_raw_transaction, encode_raw_transaction, recover_sender, sign_transaction, AccessListTransaction,
+        BlobTransaction, FeeMarketTransaction, LegacyTransaction, Transaction,
+    };
+
+    #[test]
+    fn sign_and_recover() {
+        let secret_key = U256::from(0x5eed_u32);
+        let sender = Address::from_be_bytes(keccak256(&secp256k1_public_key(secret_key))[12..].try_into().unwrap());
+        let transactions = [
+            (Transaction::LegacyTransaction(Default::default()), None),
+            (Transaction::LegacyTransaction(Default::default()), Some(1)),
+            (Transaction::AccessListTransaction(AccessListTransaction { chain_id: 1, ..Default::default() }), None),
+            (Transaction::FeeMarketTransaction(FeeMarketTransaction { chain_id: 1, ..Default::default() }), None),
+            (Transaction::BlobTransaction(BlobTransaction { chain_id: 1, ..Default::default() }), None),
+        ];
+        for (mut tx, chain_id) in transactions {
+            sign_transaction(&mut tx, secret_key, chain_id).unwrap();
+            assert_eq!(recover_sender(1, &tx, ExecutionRules::CANCUN).unwrap(), sender, "{tx:?}");
+        }
+    }
+
+    #[test]
+    fn decode_rpc_transactions() {
+        // As returned by eth_getTransactionByHash, with the members which are
+        // not part of the transaction.
+        let fee_market = br#"{
+            "blockHash": "0x0000000000000000000000000000000000000000000000000000000000000001",
+            "blockNumber": "0x10",
+            "from": "0x00000000000000000000000000000000000000ff",
+            "gas": "0x5208",
+            "gasPrice": "0x9",
+            "maxFeePerGas": "0x10",
+            "maxPriorityFeePerGas": "0x1",
+            "hash": "0x0000000000000000000000000000000000000000000000000000000000000002",
+            "input": "0x1234",
+            "nonce": "0x3",
+            "to": "0x00000000000000000000000000000000000000aa",
+            "transactionIndex": "0x0",
+            "value": "0x1",
+            "type": "0x2",
+            "accessList": [{
+                "address": "0x00000000000000000000000000000000000000bb",
+                "storageKeys": ["0x0000000000000000000000000000000000000000000000000000000000000007"]
+            }],
+            "chainId": "0x1",
+            "v": "0x1",
+            "yParity": "0x1",
+            "r": "0x2",
+            "s": "0x3"
+        }"#;
+        let tx: FeeMarketTransaction = from_json(fee_market).unwrap();
+        assert_eq!((tx.chain_id, tx.nonce, tx.gas, tx.max_fee_per_gas), (1, U256::from(3_u32), 21000, 16));
+        assert_eq!(tx.data.0, [0x12, 0x34]);
+        let mut address = [0; 20];
+        address[19] = 0xbb;
+        assert_eq!(tx.access_list.len(), 1);
+        assert_eq!(tx.access_list[0].0, Address::from_be_bytes(address));
+        assert_eq!(tx.access_list[0].1[0].0[31], 7);
+        assert_eq!(tx.y_parity, U256::from(1_u32));
+        assert!(matches!(from_json::<Transaction>(fee_market).unwrap(), Transaction::FeeMarketTransaction(_)));
+        assert!(from_json::<LegacyTransaction>(fee_market).is_err());
+
+        // No type for a legacy transaction, and a null to to create a contract.
+        let legacy = br#"{"nonce": "0x0", "gasPrice": "0x7", "gas": "0x5208", "to": null, "value": "0x0",
+            "input": "0x", "v": "0x25", "r": "0x1", "s": "0x1"}"#;
+        let Transaction::LegacyTransaction(tx) = from_json::<Transaction>(legacy).unwrap() else { panic!() };
+        assert_eq!((tx.gas_price, tx.to.is_none(), tx.chain_id()), (7, true, Some(1)));
+
+        let blob = br#"{"type": "0x3", "chainId": "0x1", "nonce": "0x0", "maxFeePerGas": "0x10",
+            "maxPriorityFeePerGas": "0x1", "maxFeePerBlobGas": "0x2", "gas": "0x5208",
+            "to": "0x00000000000000000000000000000000000000aa", "value": "0x0", "input": "0x", "accessList": [],
+            "blobVersionedHashes": ["0x0100000000000000000000000000000000000000000000000000000000000000"],
+            "yParity": "0x0", "r": "0x1", "s": "0x1"}"#;
+        let tx: BlobTransaction = from_json(blob).unwrap();
+        assert_eq!((tx.max_fee_per_blob_gas, tx.blob_versioned_hashes.len()), (U256::from(2_u32), 1));
+        assert!(from_json::<Transaction>(&br#"{"type": "0x7"}"#[..]).is_err());
+    }
+
+    #[test]
+    fn legacy_chain_id() {
+        let chain_id = |v: u64| LegacyTransaction { v: U256::from(v), ..Default::default() }.chain_id();
+        assert_eq!(chain_id(27), None);
+        assert_eq!(chain_id(28), None);
+        assert_eq!(chain_id(0), None);
+        assert_eq!(chain_id(35), Some(0));
+        assert_eq!(chain_id(37), Some(1));
+        assert_eq!(chain_id(38), Some(1));
+        assert_eq!(chain_id(2 * 1337 + 36), Some(1337));
+        let huge = LegacyTransaction { v: U256::from(u64::MAX).shl(8), ..Default::default() };
+        assert_eq!(huge.chain_id(), None);
+    }
+
+    #[test]
+    fn raw_transactions() {
+        let tx = Transaction::FeeMarketTransaction(FeeMarketTransaction { chain_id: 1, ..Default::default() });
+        let raw = encode_raw_transaction(&tx).unwrap();
+        let decoded = decode_raw_transaction(&raw).unwrap();
+        assert!(matches!(decoded, Transaction::FeeMarketTransaction(decoded) if decoded.chain_id == 1));
+
+        // Typed transactions are decoded strictly too, so a chain id with a
+        // leading zero byte is rejected.
+        assert_eq!(raw[..3], [0x02, 0xcc, 0x01]);
+        let padded = [&[0x02, 0xce, 0x82, 0x00, 0x01][..], &raw[3..]].concat();
+        assert!(decode_raw_transaction(&padded).is_err());
+        assert!(decode_raw_transaction(&[]).is_err());
+        assert!(decode_raw_transaction(&[0x07, 0xc0]).is_err());
+    }
+}
@@ -0,0 +1,188 @@
+//! Building and signing transactions, for test fixtures and dev chains.
+//!
+//! A `TxBuilder` starts from the type of transaction, takes the fields that
+//! matter and leaves the others zero, then signs with a secret key over the
+//! signing hash of that type:
+//!
+//! ```
+//! # use ejit_evm::prelude::*;
+//! # let (recipient, secret_key) = (Address::from_be_bytes([0xaa; 20]), U256::from(0x5eed_u32));
+//! let tx = TxBuilder::fee_market(1)
+//!     .nonce(0)
+//!     .gas(21000)
+//!     .max_fee_per_gas(10)
+//!     .to(recipient)
+//!     .value(U256::from(1_u32))
+//!     .sign(secret_key)?;
+//! # Ok::<(), Exception>(())
+//! ```
+//!
+//! Fields which the type does not have, such as the gas price of a fee
+//! market transaction, are ignored.
+
+use crate::ethereum::{
+    cancun::fork_types::{Address, VersionedHash},
+    crypto::hash::Hash32,
+    ethereum_types::{
+        bytes::{Bytes, Bytes32},
+        numeric::{Uint, U256, U64},
+    },
+    exceptions::Exception,
+};
+
+use super::{sign_transaction, RpcAccessListEntry, RpcTransaction, Transaction};
+
+/// A transaction of any type before Prague, being built.
+#[derive(Debug, Clone)]
+pub struct TxBuilder {
+    tx: RpcTransaction,
+}
+
+impl TxBuilder {
+    fn new(tx_type: U64, chain_id: Option<U64>) -> Self {
+        Self { tx: RpcTransaction { tx_type: Some(tx_type), chain_id, ..Default::default() } }
+    }
+
+    /// A legacy transaction, signed without replay protection unless given a
+    /// `chain_id`.
+    pub fn legacy() -> Self {
+        Self::new(0, None)
+    }
+
+    /// An EIP-2930 transaction.
+    pub fn access_list(chain_id: U64) -> Self {
+        Self::new(1, Some(chain_id))
+    }
+
+    /// An EIP-1559 transaction.
+    pub fn fee_market(chain_id: U64) -> Self {
+        Self::new(2, Some(chain_id))
+    }
+
+    /// An EIP-4844 transaction, which must be given a `to`.
+    pub fn blob(chain_id: U64) -> Self {
+        Self::new(3, Some(chain_id))
+    }
+
+    /// The chain id, which a legacy transaction is then signed for as in
+    /// EIP-155.
+    pub fn chain_id(mut self, chain_id: U64) -> Self {
+        self.tx.chain_id = Some(chain_id);
+        self
+    }
+
+    pub fn nonce(mut self, nonce: u64) -> Self {
+        self.tx.nonce = U256::from(nonce);
+        self
+    }
+
+    pub fn gas(mut self, gas: Uint) -> Self {
+        self.tx.gas = gas;
+        self
+    }
+
+    pub fn gas_price(mut self, gas_price: Uint) -> Self {
+        self.tx.gas_price = Some(gas_price);
+        self
+    }
+
+    pub fn max_fee_per_gas(mut self, max_fee_per_gas: Uint) -> Self {
+        self.tx.max_fee_per_gas = Some(max_fee_per_gas);
+        self
+    }
+
+    pub fn max_priority_fee_per_gas(mut self, max_priority_fee_per_gas: Uint) -> Self {
+        self.tx.max_priority_fee_per_gas = Some(max_priority_fee_per_gas);
+        self
+    }
+
+    /// Call `to`. Without one, the transaction creates a contract.
+    pub fn to(mut self, to: Address) -> Self {
+        self.tx.to = Some(to);
+        self
+    }
+
+    pub fn value(mut self, value: U256) -> Self {
+        self.tx.value = value;
+        self
+    }
+
+    pub fn data(mut self, data: Bytes) -> Self {
+        self.tx.input = data;
+        self
+    }
+
+    /// Add `address` and `storage_keys` to the access list.
+    pub fn access(mut self, address: Address, storage_keys: Vec<Bytes32>) -> Self {
+        self.tx.access_list.push(RpcAccessListEntry { address, storage_keys });
+        self
+    }
+
+    pub fn max_fee_per_blob_gas(mut self, max_fee_per_blob_gas: U256) -> Self {
+        self.tx.max_fee_per_blob_gas = Some(max_fee_per_blob_gas);
+        self
+    }
+
+    /// Add a blob, by its versioned hash.
+    pub fn blob_versioned_hash(mut self, hash: VersionedHash) -> Self {
+        self.tx.blob_versioned_hashes.push(Hash32(hash.0));
+        self
+    }
+
+    /// The transaction, unsigned.
+    pub fn build(self) -> Result<Transaction, Exception> {
+        self.tx.into_transaction().map_err(|_| Exception::InvalidTransaction("blob transaction without to"))
+    }
+
+    /// The transaction, signed with `secret_key`. Fails if `secret_key` is
+    /// not a valid secp256k1 secret key.
+    pub fn sign(self, secret_key: U256) -> Result<Transaction, Exception> {
+        let chain_id = self.tx.chain_id;
+        let mut tx = self.build()?;
+        sign_transaction(&mut tx, secret_key, chain_id)?;
+        Ok(tx)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::ethereum::{
+        cancun::{
+            fork_types::{Address, VersionedHash},
+            transactions::{recover_sender, LegacyTransaction, Transaction},
+        },
+        crypto::{
+            eliptic_curve::{secp256k1_public_key, SECP256K1N},
+            hash::keccak256,
+        },
+        ethereum_types::{bytes::Bytes32, numeric::U256},
+        forks::ExecutionRules,
+    };
+
+    use super::TxBuilder;
+
+    #[test]
+    fn build_and_sign() {
+        let secret_key = U256::from(0x5eed_u32);
+        let sender = Address::from_be_bytes(keccak256(&secp256k1_public_key(secret_key))[12..].try_into().unwrap());
+        let to = Address::from_be_bytes([0xaa; 20]);
+        let builders = [
+            TxBuilder::legacy().gas_price(7),
+            TxBuilder::legacy().chain_id(1).gas_price(7),
+            TxBuilder::access_list(1).gas_price(7).access(to.clone(), vec![Bytes32([1; 32])]),
+            TxBuilder::fee_market(1).max_fee_per_gas(10).max_priority_fee_per_gas(1),
+            TxBuilder::blob(1).max_fee_per_gas(10).blob_versioned_hash(VersionedHash([1; 32])),
+        ];
+        for builder in builders {
+            let tx = builder.nonce(3).gas(21000).to(to.clone()).value(U256::from(1_u32)).sign(secret_key).unwrap();
+            assert_eq!(recover_sender(1, &tx, ExecutionRules::CANCUN).unwrap(), sender, "{tx:?}");
+        }
+
+        let tx = TxBuilder::legacy().chain_id(1).nonce(1).sign(secret_key).unwrap();
+        let Transaction::LegacyTransaction(LegacyTransaction { nonce, to, v, .. }) = tx else { panic!() };
+        assert_eq!((nonce, to.is_none(), v >= U256::from(37_u32)), (U256::from(1_u32), true, true));
+        assert!(TxBuilder::blob(1).build().is_err());
+        assert!(TxBuilder::legacy().sign(U256::ZERO).is_err());
+        assert!(TxBuilder::fee_market(1).sign(SECP256K1N).is_err());
+    }
+}
@@ -34,11 +34,11 @@
 
 use std::collections::BTreeMap;
 
-use crate::ethereum::{cancun::fork_types::{Account, Root}, crypto::hash::{keccak256, Hash32}, ethereum_rlp::{exceptions::RLPException, rlp::{self, encode, encode_joined_encodings, encode_sequence, Extended}}, ethereum_types::{bytes::{Bytes, Bytes32, Verbatim}, numeric::{Uint, U256}}};
+use crate::ethereum::{cancun::fork_types::{Account, Root}, crypto::hash::{keccak256, keccak256_cached, Hash32}, ethereum_rlp::{exceptions::RLPException, rlp::{self, encode, encode_joined_encodings, encode_sequence, Extended}}, ethereum_types::{bytes::{Bytes, Bytes32, Verbatim}, numeric::{Uint, U256}}};
 
 use super::fork_types::Address;
 
-const EMPTY_TRIE_ROOT : Root = Root([0x56,0xe8,0x1f,0x17,0x1b,0xcc,0x55,0xa6,0xff,0x83,0x45,0xe6,0x92,0xc0,0xf8,0x6e,0x5b,0x48,0xe0,0x1b,0x99,0x6c,0xad,0xc0,0x01,0x62,0x2f,0xb5,0xe3,0x63,0xb4,0x21]);
+pub const EMPTY_TRIE_ROOT : Root = Root([0x56,0xe8,0x1f,0x17,0x1b,0xcc,0x55,0xa6,0xff,0x83,0x45,0xe6,0x92,0xc0,0xf8,0x6e,0x5b,0x48,0xe0,0x1b,0x99,0x6c,0xad,0xc0,0x01,0x62,0x2f,0xb5,0xe3,0x63,0xb4,0x21]);
 
 #[derive(Debug)]
 /// Leaf node in the Merkle Trie
@@ -85,6 +85,18 @@ impl Key for Bytes {
     }
 }
 
+impl Key for Address {
+    fn get_bytes(&self) -> Bytes {
+        Bytes::from(&self[..])
+    }
+}
+
+impl Key for Bytes32 {
+    fn get_bytes(&self) -> Bytes {
+        Bytes::from(&self.0[..])
+    }
+}
+
 pub trait Value {
     fn encode_node(&self) -> Verbatim;
 }
@@ -105,6 +117,14 @@ impl Value for Bytes {
     }
 }
 
+impl Value for U256 {
+    fn encode_node(&self) -> Verbatim {
+        let mut buffer = Bytes::default();
+        self.encode(&mut buffer);
+        Bytes(buffer.0).encode_node()
+    }
+}
+
 impl InternalNode {
     /// Encodes a Merkle Trie node into its RLP form. The RLP will then be
     /// serialized into a `Bytes` and hashed unless it is less that 32 bytes
@@ -113,9 +133,23 @@ impl InternalNode {
     /// This function also accepts `None`, representing the absence of a node,
     /// which is encoded to `b""`.
     fn encode_internal_node(self, rlp_the_hash: bool) -> Verbatim {
+        let encoded = self.encode();
+        if encoded.len() < 32 {
+            Verbatim(encoded.0)
+        } else if rlp_the_hash {
+            let mut rlp = Bytes::default();
+            keccak256(&encoded).encode(&mut rlp);
+            Verbatim(rlp.0)
+        } else {
+            Verbatim(keccak256(&encoded).to_vec())
+        }
+    }
+
+    /// The RLP encoding of the node itself, which `encode_internal_node`
+    /// hashes when it is long.
+    fn encode(self) -> Bytes {
         use InternalNode::*;
         let mut encoded = Bytes::default();
-        if !matches!(&self, None) { println!("unencoded={self:?}"); }
         match self {
             LeafNode(node) => {
                 (
@@ -141,18 +175,7 @@ impl InternalNode {
                 encoded.push(0x80);
             }
         };
-
-        if encoded.len() > 1 { println!("encoded={encoded:?}"); }
-        if encoded.len() < 32 {
-            Verbatim(encoded.0)
-        } else if rlp_the_hash {
-            let mut rlp = Bytes::default();
-            keccak256(&encoded).encode(&mut rlp);
-            Verbatim(rlp.0)
-        } else {
-            Verbatim(keccak256(&encoded).to_vec())
-        }
-
+        encoded
     }
 }
 
@@ -253,6 +276,16 @@ impl<K : Ord, V : PartialEq + Clone> Trie<K, V> {
         self.secured = secured;
         self
     }
+
+    /// The non-default entries of the trie, in key order.
+    pub fn data(&self) -> &BTreeMap<K, V> {
+        &self.data
+    }
+
+    /// The value returned by `get` for missing keys.
+    pub fn default_value(&self) -> &V {
+        &self.default_value
+    }
 }
 
 /// Find the longest common prefix of two sequences.
@@ -270,10 +303,12 @@ fn common_prefix_length(a: &[u8], b:&[u8]) -> usize {
 /// 
 /// Highest nibble::
 /// 
-///     +---+---+----------+--------+
-///     | _ | _ | is_leaf | parity |
-///     +---+---+----------+--------+
-///         3   2      1         0
+/// ```text
+/// +---+---+----------+--------+
+/// | _ | _ | is_leaf | parity |
+/// +---+---+----------+--------+
+///     3   2      1         0
+/// ```
 /// 
 /// 
 /// The lowest bit of the nibble encodes the parity of the length of the
@@ -317,7 +352,7 @@ impl<K : Ord + Key, V : PartialEq + Clone + Value> Trie<K, V> {
             let preimage = key.get_bytes();
             let encoded_value = value.encode_node();
             let key = if self.secured {
-                keccak256(preimage.as_ref()).to_vec()
+                keccak256_cached(preimage.as_ref()).to_vec()
             } else {
                 preimage.as_ref().to_vec()
             };
@@ -331,15 +366,11 @@ impl<K : Ord + Key, V : PartialEq + Clone + Value> Trie<K, V> {
     /// returns MPT root of the underlying key-value pairs.
     pub fn root(&self) -> Result<Root, RLPException> {
         let obj = self.prepare_trie();
-        println!("obj={obj:?}");
         let pat = Self::patricialize(obj, 0);
-        println!("pat={pat:?}");
+        // Either the encoding of a small root node or the hash of a large one.
         let root_node = pat.encode_internal_node(false);
-        // println!("root_node={root_node:?}");
-        let root_node = Bytes(root_node.0);
-        let encoded = rlp::encode(&root_node)?;
-        if encoded.len() < 32 {
-            Ok(Root(keccak256(&encoded).0))
+        if root_node.0.len() < 32 {
+            Ok(Root(keccak256(&root_node.0).0))
         } else {
             Ok(Root(root_node.0.try_into().unwrap()))
         }
@@ -384,7 +415,6 @@ impl<K : Ord + Key, V : PartialEq + Clone + Value> Trie<K, V> {
         if prefix_length > 0 {
             let key_segment = Bytes::from(&arbitrary_key[level..level + prefix_length]);
             let pat = Self::patricialize(obj, level + prefix_length);
-            println!("pat2={pat:?}");
             let subnode = pat.encode_internal_node(true);
 
             return InternalNode::ExtensionNode(ExtensionNode{key_segment, subnode});
@@ -406,8 +436,6 @@ impl<K : Ord + Key, V : PartialEq + Clone + Value> Trie<K, V> {
             }
         }
 
-        println!("branches={branches:?}");
-
         let subnodes : Vec<Verbatim> = branches.into_iter().map(|b| {
             let pat = Self::patricialize(b, level + 1);
             pat.encode_internal_node(true)
@@ -421,6 +449,8 @@ impl<K : Ord + Key, V : PartialEq + Clone + Value> Trie<K, V> {
     }
 }
 
+pub mod proof;
+
 #[cfg(test)]
 mod tests {
     use crate::{ethereum::{cancun::fork_types::Root, ethereum_types::bytes::Bytes, utils::hexadecimal::hex_to_bytes}, json::{Decoder, JsonDecode, JsonError, ObjectParser, Value}};
@@ -428,6 +458,12 @@ mod tests {
     use super::Trie;
 
 
+    #[test]
+    fn empty_trie() {
+        let trie: Trie<Bytes, Bytes> = Trie::default();
+        assert_eq!(trie.root().unwrap(), super::EMPTY_TRIE_ROOT);
+    }
+
     #[test]
     fn trie_any_order() -> Result<(), JsonError> {
         test_trie("trieanyorder.json", false)?;
@@ -478,8 +514,7 @@ mod tests {
                 }
             }
             let r = trie.root().unwrap();
-            println!("{trie:?} {root:?} {r:?}");
-            assert_eq!(root, r, "{name}");
+            assert_eq!(root, r, "{name} {trie:?}");
         })
     }
     
@@ -0,0 +1,457 @@
+//! Merkle proofs over tries whose keys are 32 byte hashes, as the state
+//! and storage tries are, in the form the snap protocol exchanges them.
+//!
+//! `Trie` only computes roots from all of its entries. These functions take
+//! the same entries as a map from hashed key to leaf value and also give
+//! the encodings of single nodes: those on the path to a key, which prove
+//! it, and the node at a path, which a peer healing its copy of a trie asks
+//! for. `verify_range` checks the other side of a proof: that a run of
+//! consecutive entries, with proofs of its first and last keys, holds every
+//! entry of the trie between them. `Healer` brings a map of entries up to
+//! date with a newer root from the nodes of that trie, fetching only the
+//! subtrees which differ.
+
+use std::collections::BTreeMap;
+
+use crate::ethereum::{
+    cancun::fork_types::Root,
+    crypto::hash::{keccak256, keccak256_batch, Hash32},
+    ethereum_rlp::{rlp::Extended, stream::RlpStream},
+    ethereum_types::bytes::{Bytes, Verbatim},
+    exceptions::Exception,
+};
+
+use super::{
+    bytes_to_nibble_list, nibble_list_to_compact, BranchNode, ExtensionNode, InternalNode, LeafNode, Trie, Value,
+    EMPTY_TRIE_ROOT,
+};
+
+/// The entries of a trie: each hashed key with its value as the leaf
+/// stores it, such as an encoded account or storage value.
+pub type Leaves = BTreeMap<Hash32, Bytes>;
+
+/// The root of the trie holding `leaves`.
+pub fn root(leaves: &Leaves) -> Root {
+    root_of(&subtree(leaves, &[]).0)
+}
+
+/// The encodings of the nodes on the path to `key`, from the root, leaving
+/// out those small enough to be embedded in their parent. For a key which
+/// is not in the trie they prove its absence.
+pub fn prove(leaves: &Leaves, key: &Hash32) -> Vec<Bytes> {
+    nodes_on_path(leaves, &bytes_to_nibble_list(&key.0))
+        .into_iter()
+        .filter(|(depth, node)| *depth == 0 || node.len() >= 32)
+        .map(|(_, node)| node)
+        .collect()
+}
+
+/// The encoding of the node which starts at the nibble `path`, if there is
+/// one.
+pub fn node_at(leaves: &Leaves, path: &[u8]) -> Option<Bytes> {
+    nodes_on_path(leaves, path).into_iter().find(|(depth, _)| *depth == path.len()).map(|(_, node)| node)
+}
+
+/// The compact encoding of a nibble path, as `GetTrieNodes` requests it.
+pub fn compact_path(path: &[u8]) -> Bytes {
+    nibble_list_to_compact(path, false)
+}
+
+/// The nibbles of a compact encoded path, and whether it is flagged as the
+/// path of a leaf.
+pub fn decode_compact(compact: &[u8]) -> Result<(Bytes, bool), Exception> {
+    const INVALID: Exception = Exception::EthereumException("invalid compact path");
+    let (&first, rest) = compact.split_first().ok_or(INVALID)?;
+    let flag = first >> 4;
+    if flag > 3 || (flag & 1 == 0 && first & 0x0f != 0) {
+        return Err(INVALID);
+    }
+    let mut nibbles = Vec::with_capacity(2 * compact.len());
+    if flag & 1 == 1 {
+        nibbles.push(first & 0x0f);
+    }
+    nibbles.extend_from_slice(&bytes_to_nibble_list(rest));
+    Ok((Bytes(nibbles), flag & 2 != 0))
+}
+
+/// Check that `leaves` are all the entries of the trie with `root` from
+/// `origin` up to the last of them, or to the end of the trie if there
+/// are none.
+///
+/// `proof` holds the nodes on the paths to `origin` and to the last key.
+/// It is empty when `leaves` are the whole trie.
+pub fn verify_range(root: &Root, origin: &Hash32, leaves: &Leaves, proof: &[Bytes]) -> Result<(), Exception> {
+    if leaves.keys().next().is_some_and(|first| first < origin) {
+        return Err(Exception::EthereumException("range starts before its origin"));
+    }
+    if proof.is_empty() {
+        return match self::root(leaves) == *root {
+            true => Ok(()),
+            false => Err(Exception::EthereumException("range does not match the root")),
+        };
+    }
+    let mut range = Range {
+        leaves,
+        low: bytes_to_nibble_list(&origin.0),
+        high: leaves.keys().next_back().map_or(Bytes(vec![0x0f; 64]), |last| bytes_to_nibble_list(&last.0)),
+        proof: keccak256_batch(proof.iter().map(|node| &node[..])).into_iter().zip(proof.iter().map(|node| &node[..])).collect(),
+        consumed: 0,
+    };
+    let top = range.rebuild(&[], &hash_reference(&Hash32(root.0)))?;
+    if root_of(&top) != *root || range.consumed != leaves.len() {
+        return Err(Exception::EthereumException("range proof does not match the root"));
+    }
+    Ok(())
+}
+
+/// Brings the entries of a trie up to date with the trie of `root`, from
+/// the nodes of that trie where their subtrees differ.
+///
+/// `missing` lists the nodes to fetch next, and each fetched node given to
+/// `insert` updates the entries below it and may lead to more nodes.
+#[derive(Debug, Clone)]
+pub struct Healer {
+    leaves: Leaves,
+    /// Nodes yet to be fetched, by path.
+    pending: BTreeMap<Bytes, Hash32>,
+}
+
+impl Healer {
+    pub fn new(root: &Root, mut leaves: Leaves) -> Self {
+        let mut pending = BTreeMap::new();
+        if *root == EMPTY_TRIE_ROOT {
+            leaves.clear();
+        } else if self::root(&leaves) != *root {
+            pending.insert(Bytes::default(), Hash32(root.0));
+        }
+        Self { leaves, pending }
+    }
+
+    /// Up to `n` nodes to fetch, by path and hash.
+    pub fn missing(&self, n: usize) -> Vec<(Bytes, Hash32)> {
+        self.pending.iter().take(n).map(|(path, hash)| (path.clone(), hash.clone())).collect()
+    }
+
+    pub fn is_done(&self) -> bool {
+        self.pending.is_empty()
+    }
+
+    /// Apply the fetched node at `path`.
+    pub fn insert(&mut self, path: &[u8], node: &[u8]) -> Result<(), Exception> {
+        let path = Bytes(path.to_vec());
+        let Some(hash) = self.pending.get(&path) else {
+            return Err(Exception::EthereumException("trie node was not requested"));
+        };
+        if keccak256(node) != *hash {
+            return Err(Exception::EthereumException("trie node does not match its hash"));
+        }
+        self.pending.remove(&path);
+        self.apply(&path, node)
+    }
+
+    /// The updated entries, once `is_done`.
+    pub fn into_leaves(self) -> Leaves {
+        self.leaves
+    }
+
+    fn apply(&mut self, path: &[u8], node: &[u8]) -> Result<(), Exception> {
+        match decode_node(node)? {
+            Node::Branch(items) => {
+                for (i, child) in items[..16].iter().enumerate() {
+                    self.child(&[path, &[i as u8]].concat(), child)?;
+                }
+            }
+            Node::Extension(segment, child) => {
+                let child_path = [path, &segment].concat();
+                self.remove_under(path, |key| !has_prefix(key, &child_path));
+                self.child(&child_path, child)?;
+            }
+            Node::Leaf(rest, value) => {
+                let key = nibbles_to_key(&[path, &rest].concat())?;
+                self.remove_under(path, |_| true);
+                self.leaves.insert(key, Bytes(RlpStream::new(value).next_bytes()?.to_vec()));
+            }
+        }
+        Ok(())
+    }
+
+    fn child(&mut self, path: &[u8], reference: &[u8]) -> Result<(), Exception> {
+        if reference == [0x80] {
+            self.remove_under(path, |_| true);
+        } else if reference[0] >= 0xc0 {
+            // Small nodes are embedded in their parent.
+            self.apply(path, reference)?;
+        } else if subtree(&self.leaves, path).0.0 != reference {
+            let hash = RlpStream::new(reference).next_bytes()?;
+            let hash = Hash32(hash.try_into().map_err(|_| Exception::EthereumException("invalid node reference"))?);
+            self.pending.insert(Bytes(path.to_vec()), hash);
+        }
+        Ok(())
+    }
+
+    fn remove_under(&mut self, path: &[u8], filter: impl Fn(&Hash32) -> bool) {
+        let (low, high) = bounds(path);
+        let keys: Vec<Hash32> = self.leaves.range(low..=high).map(|(key, _)| key.clone()).filter(|k| filter(k)).collect();
+        for key in keys {
+            self.leaves.remove(&key);
+        }
+    }
+}
+
+/// A node of the trie, with its children and value as their encodings.
+enum Node<'a> {
+    /// The references to the 16 children and the value.
+    Branch(Vec<&'a [u8]>),
+    Extension(Bytes, &'a [u8]),
+    Leaf(Bytes, &'a [u8]),
+}
+
+fn decode_node(encoding: &[u8]) -> Result<Node<'_>, Exception> {
+    let mut stream = RlpStream::list(encoding)?;
+    let mut items = Vec::with_capacity(17);
+    while !stream.is_empty() {
+        items.push(stream.next_raw()?);
+    }
+    match items.len() {
+        17 => Ok(Node::Branch(items)),
+        2 => {
+            let (path, is_leaf) = decode_compact(RlpStream::new(items[0]).next_bytes()?)?;
+            Ok(if is_leaf { Node::Leaf(path, items[1]) } else { Node::Extension(path, items[1]) })
+        }
+        _ => Err(Exception::EthereumException("invalid trie node")),
+    }
+}
+
+/// The reference to the subtree at `path` of the trie holding `leaves`, as
+/// its parent embeds it, and the number of leaves in it.
+fn subtree(leaves: &Leaves, path: &[u8]) -> (Verbatim, usize) {
+    let (low, high) = bounds(path);
+    let nibbles: BTreeMap<Bytes, Verbatim> =
+        leaves.range(low..=high).map(|(key, value)| (bytes_to_nibble_list(&key.0), value.encode_node())).collect();
+    let count = nibbles.len();
+    (Trie::<Bytes, Bytes>::patricialize(nibbles, path.len()).encode_internal_node(true), count)
+}
+
+/// The nodes whose paths are prefixes of `path`, with their depth.
+fn nodes_on_path(leaves: &Leaves, path: &[u8]) -> Vec<(usize, Bytes)> {
+    let mut nodes = Vec::new();
+    let mut depth = 0;
+    loop {
+        let (low, high) = bounds(&path[..depth]);
+        let nibbles: BTreeMap<Bytes, Verbatim> =
+            leaves.range(low..=high).map(|(key, value)| (bytes_to_nibble_list(&key.0), value.encode_node())).collect();
+        let node = Trie::<Bytes, Bytes>::patricialize(nibbles, depth);
+        let next = match &node {
+            InternalNode::None => return nodes,
+            InternalNode::LeafNode(_) => None,
+            InternalNode::ExtensionNode(node) => {
+                path[depth..].starts_with(&node.key_segment).then(|| depth + node.key_segment.len())
+            }
+            InternalNode::BranchNode(_) => Some(depth + 1),
+        };
+        nodes.push((depth, node.encode()));
+        match next {
+            Some(next) if next <= path.len() => depth = next,
+            _ => return nodes,
+        }
+    }
+}
+
+/// The state of `verify_range`.
+struct Range<'a> {
+    leaves: &'a Leaves,
+    low: Bytes,
+    high: Bytes,
+    /// Proof nodes by hash.
+    proof: BTreeMap<Hash32, &'a [u8]>,
+    /// Leaves used so far.
+    consumed: usize,
+}
+
+impl<'a> Range<'a> {
+    /// The reference to the subtree at `path`, which spans an end of the
+    /// range, from the proof node `reference` and the leaves in the range.
+    fn rebuild(&mut self, path: &[u8], reference: &[u8]) -> Result<Verbatim, Exception> {
+        let encoding = if reference == [0x80] {
+            return Ok(self.subtree(path));
+        } else if reference[0] >= 0xc0 {
+            reference
+        } else {
+            let hash = RlpStream::new(reference).next_bytes()?;
+            let hash = Hash32(hash.try_into().map_err(|_| Exception::EthereumException("invalid node reference"))?);
+            *self.proof.get(&hash).ok_or(Exception::EthereumException("range proof is missing a node"))?
+        };
+        let node = match decode_node(encoding)? {
+            Node::Branch(items) => {
+                let mut subnodes = Vec::with_capacity(16);
+                for (i, child) in items[..16].iter().enumerate() {
+                    subnodes.push(self.child(&[path, &[i as u8]].concat(), child)?);
+                }
+                InternalNode::BranchNode(BranchNode { subnodes, value: Verbatim(items[16].to_vec()) })
+            }
+            Node::Extension(key_segment, child) => {
+                let subnode = self.child(&[path, &key_segment].concat(), child)?;
+                InternalNode::ExtensionNode(ExtensionNode { key_segment, subnode })
+            }
+            Node::Leaf(rest_of_key, value) => {
+                let (low, high) = bounds(path);
+                if self.leaves.range(low..=high).next().is_some() {
+                    return Ok(self.subtree(path));
+                }
+                let key = [path, &rest_of_key].concat();
+                if self.low.0 <= key && key <= self.high.0 {
+                    return Err(Exception::EthereumException("range omits a key"));
+                }
+                InternalNode::LeafNode(LeafNode { rest_of_key, value: Verbatim(value.to_vec()) })
+            }
+        };
+        Ok(node.encode_internal_node(true))
+    }
+
+    /// Subtrees outside the range are kept as the proof has them and those
+    /// inside are computed from the leaves.
+    fn child(&mut self, path: &[u8], reference: &[u8]) -> Result<Verbatim, Exception> {
+        let mut first = path.to_vec();
+        first.resize(64, 0);
+        let mut last = path.to_vec();
+        last.resize(64, 0x0f);
+        if last < self.low.0 || first > self.high.0 {
+            Ok(Verbatim(reference.to_vec()))
+        } else if first >= self.low.0 && last <= self.high.0 {
+            Ok(self.subtree(path))
+        } else {
+            self.rebuild(path, reference)
+        }
+    }
+
+    fn subtree(&mut self, path: &[u8]) -> Verbatim {
+        let (reference, count) = subtree(self.leaves, path);
+        self.consumed += count;
+        reference
+    }
+}
+
+/// The first and last keys under the nibble `path`.
+fn bounds(path: &[u8]) -> (Hash32, Hash32) {
+    let key = |fill: u8| {
+        let mut nibbles = path.to_vec();
+        nibbles.resize(64, fill);
+        Hash32(std::array::from_fn(|i| nibbles[2 * i] << 4 | nibbles[2 * i + 1]))
+    };
+    (key(0), key(0x0f))
+}
+
+fn has_prefix(key: &Hash32, path: &[u8]) -> bool {
+    bytes_to_nibble_list(&key.0).starts_with(path)
+}
+
+fn nibbles_to_key(nibbles: &[u8]) -> Result<Hash32, Exception> {
+    if nibbles.len() != 64 {
+        return Err(Exception::EthereumException("leaf key is not 32 bytes"));
+    }
+    Ok(bounds(nibbles).0)
+}
+
+/// The reference to a node with `hash`, as its parent embeds it.
+fn hash_reference(hash: &Hash32) -> Vec<u8> {
+    let mut reference = Bytes::default();
+    hash.encode(&mut reference).unwrap();
+    reference.0
+}
+
+/// The root of a trie whose top node has `reference`.
+fn root_of(reference: &Verbatim) -> Root {
+    if reference.0.len() < 32 { Root(keccak256(&reference.0).0) } else { Root(reference.0[1..].try_into().unwrap()) }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::ethereum::{
+        cancun::trie::{Trie, EMPTY_TRIE_ROOT},
+        crypto::hash::{keccak256, Hash32},
+        ethereum_types::bytes::Bytes,
+    };
+
+    use super::{node_at, prove, root, verify_range, Healer, Leaves};
+
+    fn leaves(n: u32) -> Leaves {
+        (0..n).map(|i| (keccak256(&i.to_be_bytes()), Bytes(vec![(i as u8).wrapping_add(1); 1 + i as usize % 40]))).collect()
+    }
+
+    #[test]
+    fn roots_and_proofs() {
+        let leaves = leaves(200);
+        let mut trie = Trie::new(false, Bytes::default());
+        for (key, value) in &leaves {
+            trie.set(Bytes(key.0.to_vec()), value.clone());
+        }
+        assert_eq!(root(&leaves).0, trie.root().unwrap().0);
+        assert_eq!(root(&Leaves::new()), EMPTY_TRIE_ROOT);
+
+        let key = leaves.keys().nth(17).unwrap();
+        let proof = prove(&leaves, key);
+        assert_eq!(keccak256(&proof[0]).0, root(&leaves).0);
+        assert_eq!(node_at(&leaves, &[]).unwrap(), proof[0]);
+        assert!(node_at(&leaves, &[1, 2, 3, 4, 5]).is_none());
+    }
+
+    #[test]
+    fn ranges() {
+        let all = leaves(300);
+        let root = root(&all);
+        let keys: Vec<&Hash32> = all.keys().collect();
+        let range = |from: usize, to: usize| -> Leaves {
+            all.iter().skip(from).take(to - from).map(|(k, v)| (k.clone(), v.clone())).collect()
+        };
+        let edge_proof = |origin: &Hash32, part: &Leaves| {
+            let mut proof = prove(&all, origin);
+            if let Some(last) = part.keys().next_back() {
+                proof.extend(prove(&all, last));
+            }
+            proof
+        };
+
+        // The whole trie needs no proof.
+        verify_range(&root, &Hash32::default(), &all, &[]).unwrap();
+        // A range from a key, and one from between keys.
+        for (origin, from, to) in [(keys[10].clone(), 10, 50), (Hash32([0x80; 32]), keys.partition_point(|k| k.0 < [0x80; 32]), 290)] {
+            let part = range(from, to);
+            let proof = edge_proof(&origin, &part);
+            verify_range(&root, &origin, &part, &proof).unwrap();
+
+            // Without a key, or with a changed value, it fails.
+            let mut missing = part.clone();
+            missing.remove(keys[from + 3]);
+            assert!(verify_range(&root, &origin, &missing, &proof).is_err());
+            let mut changed = part.clone();
+            changed.insert(keys[from + 1].clone(), Bytes(vec![0xff]));
+            assert!(verify_range(&root, &origin, &changed, &proof).is_err());
+        }
+        // Nothing after the last key.
+        let origin = Hash32([0xff; 32]);
+        verify_range(&root, &origin, &Leaves::new(), &prove(&all, &origin)).unwrap();
+        assert!(verify_range(&root, &keys[299], &Leaves::new(), &prove(&all, keys[299])).is_err());
+    }
+
+    #[test]
+    fn heal() {
+        let target = leaves(150);
+        let root = root(&target);
+        let mut stale = leaves(120);
+        stale.insert(Hash32([1; 32]), Bytes(vec![1]));
+        stale.insert(keccak256(&5_u32.to_be_bytes()), Bytes(vec![9]));
+
+        let mut healer = Healer::new(&root, stale);
+        let mut fetched = 0;
+        while !healer.is_done() {
+            for (path, _) in healer.missing(16) {
+                healer.insert(&path, &node_at(&target, &path).unwrap()).unwrap();
+                fetched += 1;
+            }
+        }
+        assert_eq!(healer.into_leaves(), target);
+        // Subtrees which already match are not fetched.
+        assert!(fetched < 150);
+        assert!(Healer::new(&root, target).is_done());
+    }
+}
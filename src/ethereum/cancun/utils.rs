@@ -0,0 +1,164 @@
+//! https://github.com/ethereum/execution-specs/tree/master/src/ethereum/cancun/utils
+//!
+//! Hardfork Utility Functions For Addresses and Messages.
+
+use std::collections::BTreeSet;
+
+use crate::ethereum::{
+    crypto::hash::keccak256,
+    ethereum_rlp::rlp,
+    ethereum_types::{bytes::{Bytes, Bytes32}, numeric::{Uint, U256}},
+    exceptions::Exception,
+};
+
+use super::{
+    fork_types::Address,
+    state::get_account,
+    vm::{Environment, Message},
+};
+
+/// Convert a Uint or U256 value to a valid address (20 bytes).
+///
+/// Parameters
+/// ----------
+/// data :
+///     The numeric value to be converted to address.
+///
+/// Returns
+/// -------
+/// address : `Address`
+///     The obtained address.
+pub fn to_address(data: U256) -> Address {
+    Address::from_be_bytes(data.to_be_bytes()[12..].try_into().unwrap())
+}
+
+/// Computes address of the new account that needs to be created.
+///
+/// Parameters
+/// ----------
+/// address :
+///     The address of the account that wants to create the new account.
+/// nonce :
+///     The transaction count of the account that wants to create the new
+///     account.
+///
+/// Returns
+/// -------
+/// address: `ethereum.cancun.fork_types.Address`
+///     The computed address of the new account.
+pub fn compute_contract_address(address: &Address, nonce: Uint) -> Result<Address, Exception> {
+    let computed_address = keccak256(&rlp::encode(&(address.clone(), nonce))?);
+    Ok(Address::from_be_bytes(computed_address[12..].try_into().unwrap()))
+}
+
+/// Computes address of the new account that needs to be created, which is
+/// based on the sender address, salt and the call data as well.
+///
+/// Parameters
+/// ----------
+/// address :
+///     The address of the account that wants to create the new account.
+/// salt :
+///     Address generation salt.
+/// call_data :
+///     The code of the new account which is to be created.
+///
+/// Returns
+/// -------
+/// address: `ethereum.cancun.fork_types.Address`
+///     The computed address of the new account.
+pub fn compute_create2_contract_address(address: &Address, salt: &Bytes32, call_data: &[u8]) -> Address {
+    let mut preimage = Vec::with_capacity(1 + 20 + 32 + 32);
+    preimage.push(0xff);
+    preimage.extend_from_slice(&address[..]);
+    preimage.extend_from_slice(&salt.0);
+    preimage.extend_from_slice(&keccak256(call_data)[..]);
+    let computed_address = keccak256(&preimage);
+    Address::from_be_bytes(computed_address[12..].try_into().unwrap())
+}
+
+/// Execute a transaction against the provided environment.
+///
+/// Parameters
+/// ----------
+/// caller :
+///     Address which initiated the transaction
+/// target :
+///     Address whose code will be executed, or `None` to create a contract.
+/// value :
+///     Value to be transferred.
+/// data :
+///     Array of bytes provided to the code in `target`.
+/// gas :
+///     Gas provided for the code in `target`.
+/// env :
+///     Environment for the Ethereum Virtual Machine.
+/// code_address :
+///     This is usually same as the `target` address except when an alternative
+///     accounts code needs to be executed.
+///     eg. `CALLCODE` calling a precompile.
+/// should_transfer_value :
+///     if True ETH should be transferred while executing a message call.
+/// is_static:
+///     if True then it prevents all state-changing operations from being
+///     executed.
+/// preaccessed_addresses:
+///     Addresses that should be marked as accessed prior to the message call
+/// preaccessed_storage_keys:
+///     Storage keys that should be marked as accessed prior to the message
+///     call
+///
+/// Returns
+/// -------
+/// message: `ethereum.cancun.vm.Message`
+///     Items containing contract creation or message call specific data.
+#[allow(clippy::too_many_arguments)]
+pub fn prepare_message(
+    caller: Address,
+    target: Option<Address>,
+    value: U256,
+    data: Bytes,
+    gas: Uint,
+    env: &Environment,
+    code_address: Option<Address>,
+    should_transfer_value: bool,
+    is_static: bool,
+    preaccessed_addresses: BTreeSet<Address>,
+    preaccessed_storage_keys: BTreeSet<(Address, Bytes32)>,
+) -> Result<Message, Exception> {
+    let (current_target, msg_data, code, code_address) = match &target {
+        None => {
+            let nonce = get_account(env.state, &caller).nonce;
+            let current_target = compute_contract_address(&caller, nonce.saturating_sub(1))?;
+            (current_target, Bytes::default(), data, code_address)
+        }
+        Some(target) => {
+            let code = get_account(env.state, target).code.clone();
+            (target.clone(), data, code, code_address.or(Some(target.clone())))
+        }
+    };
+
+    let mut accessed_addresses = BTreeSet::new();
+    accessed_addresses.insert(current_target.clone());
+    accessed_addresses.insert(caller.clone());
+    accessed_addresses.extend(env.rules.pre_compiled_contracts().iter().cloned());
+    accessed_addresses.extend(preaccessed_addresses);
+
+    Ok(Message {
+        caller,
+        target,
+        gas,
+        value,
+        data: msg_data,
+        code,
+        depth: 0,
+        current_target,
+        code_address,
+        should_transfer_value,
+        is_static,
+        accessed_addresses,
+        accessed_storage_keys: preaccessed_storage_keys,
+        accounts_to_delete: BTreeSet::new(),
+        disable_precompiles: false,
+    })
+}
@@ -1,22 +1,31 @@
 //! https://github.com/ethereum/execution-specs/blob/master/src/ethereum/cancun/vm/__init__.py
 //! 
 
-use std::collections::{BTreeMap, BTreeSet};
+use std::{collections::{BTreeMap, BTreeSet}, sync::Arc};
 
 use exceptions::VmError;
 
-use crate::{ethereum::{cancun::fork_types::*, crypto::hash::Hash32, ethereum_types::{bytes::*, numeric::*}}};
+use crate::{ethereum::{cancun::fork_types::*, crypto::hash::Hash32, ethereum_types::{bytes::*, numeric::*}, forks::ExecutionRules}};
 
-use super::{blocks::Log, state::{State, TransientStorage}};
+use code_cache::CodeCache;
+use hybrid::HybridExecutor;
+use precompiled_contracts::RIPEMD160_ADDRESS;
+use tracing::Tracer;
 
+use super::{blocks::Log, state::{account_exists_and_is_empty, StateBackend, TransientStorage}};
+
+pub mod code_cache;
+pub mod eof;
 pub mod exceptions;
 pub mod gas;
+pub mod hybrid;
 pub mod instructions;
 pub mod interpreter;
 pub mod memory;
 pub mod precompiled_contracts;
 pub mod runtime;
 pub mod stack;
+pub mod tracing;
 
 
 /// Items external to the virtual machine itself, provided by the environment.
@@ -31,18 +40,77 @@ pub struct Environment<'a> {
     pub gas_price: Uint,
     pub time: U256,
     pub prev_randao: Bytes32,
-    pub state: &'a mut State,
+    pub state: &'a mut dyn StateBackend,
     pub chain_id: U64,
-    pub traces: Vec<BTreeMap<String, String>>,
+    /// Receives every executed instruction, or `None` if tracing is
+    /// disabled.
+    pub tracer: Option<&'a mut dyn Tracer>,
+    /// One entry per message call or contract creation in the order they
+    /// start, or `None` if call frame tracing is disabled.
+    pub call_frames: Option<Vec<CallFrame>>,
+    /// Shares the valid jump destinations of code between calls, or `None`
+    /// to find them on every call.
+    pub code_cache: Option<&'a CodeCache<BTreeSet<Uint>>>,
+    /// Runs the code of every frame, compiling hot contracts, or `None` to
+    /// interpret all code.
+    pub executor: Option<&'a HybridExecutor>,
     pub excess_blob_gas: U64,
     pub blob_versioned_hashes: Vec<VersionedHash>,
     pub transient_storage: TransientStorage,
+    /// Instructions and precompiles of the fork the block belongs to.
+    pub rules: ExecutionRules,
+}
+
+impl<'a> Environment<'a> {
+    /// An environment on `state` under `rules` with the other fields empty
+    /// or zero, and chain id 1. Callers set the fields they need on top:
+    ///
+    /// ```
+    /// use ejit_evm::ethereum::{
+    ///     cancun::{fork_types::Address, state::State, vm::Environment},
+    ///     forks::ExecutionRules,
+    /// };
+    ///
+    /// let (mut state, caller) = (State::default(), Address::from_be_bytes([0xca; 20]));
+    /// let env = Environment {
+    ///     caller: caller.clone(),
+    ///     origin: caller,
+    ///     ..Environment::new(&mut state, ExecutionRules::CANCUN)
+    /// };
+    /// assert_eq!(env.chain_id, 1);
+    /// ```
+    pub fn new(state: &'a mut dyn StateBackend, rules: ExecutionRules) -> Self {
+        Self {
+            caller: Address::default(),
+            block_hashes: Vec::new(),
+            origin: Address::default(),
+            coinbase: Address::default(),
+            number: 0,
+            base_fee_per_gas: 0,
+            gas_limit: 0,
+            gas_price: 0,
+            time: U256::ZERO,
+            prev_randao: Bytes32::default(),
+            state,
+            chain_id: 1,
+            tracer: None,
+            call_frames: None,
+            code_cache: None,
+            executor: None,
+            excess_blob_gas: 0,
+            blob_versioned_hashes: Vec::new(),
+            transient_storage: TransientStorage::default(),
+            rules,
+        }
+    }
 }
 
 /// Items that are used by contract creation or message call.
-pub struct Message<'a> {
+#[derive(Debug, Clone)]
+pub struct Message {
     pub caller: Address,
-    pub target: Address,
+    /// `None` when the message creates a contract.
+    pub target: Option<Address>,
     pub current_target: Address,
     pub gas: Uint,
     pub value: U256,
@@ -54,28 +122,151 @@ pub struct Message<'a> {
     pub is_static: bool,
     pub accessed_addresses: BTreeSet<Address>,
     pub accessed_storage_keys: BTreeSet<(Address, Bytes32)>,
-    pub parent_evm: Option<&'a Evm<'a>>,
+    /// Accounts which the enclosing frames have self-destructed, so that
+    /// an account is refunded only once before London (EIP-3529).
+    pub accounts_to_delete: BTreeSet<Address>,
+    /// The code is that of an account delegated to with EIP-7702, so a
+    /// precompile address runs as an account without code.
+    pub disable_precompiles: bool,
+}
+
+
+/// A message call or contract creation recorded for analysis.
+#[derive(Debug, Clone)]
+pub struct CallFrame {
+    /// Depth of the frame, zero for the message of a transaction.
+    pub depth: Uint,
+    pub caller: Address,
+    /// Account whose storage and balance the frame operates on.
+    pub current_target: Address,
+    /// Account whose code is executed, which differs from `current_target`
+    /// for `CALLCODE` and `DELEGATECALL`.
+    pub code_address: Address,
+    pub is_create: bool,
+    pub value: U256,
+    pub gas: Uint,
+    /// Gas consumed by the frame and its children, excluding the code deposit
+    /// of a contract creation.
+    pub gas_used: Uint,
+    pub error: Option<VmError>,
 }
 
 
 /// The internal state of the virtual machine.
-pub struct Evm<'a> {
+///
+/// The environment is borrowed mutably for the lifetime `'a` so that child
+/// calls can reborrow it for their own frames.
+pub struct Evm<'a, 'e> {
     pub pc: Uint,
     pub stack: Vec<U256>,
     pub memory: Vec<u8>,
     pub code: Bytes,
     pub gas_left: Uint,
-    pub env: &'a Environment<'a>,
-    pub valid_jump_destinations: Vec<Uint>,
+    pub env: &'a mut Environment<'e>,
+    pub valid_jump_destinations: Arc<BTreeSet<Uint>>,
     pub logs: Vec<Log>,
     pub refund_counter: i64,
     pub running: bool,
-    pub message: Message<'a>,
+    pub message: Message,
     pub output: Bytes,
-    pub accounts_to_delete: Vec<Address>,
-    pub touched_accounts: Vec<Address>,
+    pub accounts_to_delete: BTreeSet<Address>,
+    pub touched_accounts: BTreeSet<Address>,
     pub return_data: Bytes,
     pub error: Option<VmError>,
-    pub accessed_addresses: Vec<Address>,
-    pub accessed_storage_keys: Vec<(Address, Bytes32)>,
+    pub accessed_addresses: BTreeSet<Address>,
+    pub accessed_storage_keys: BTreeSet<(Address, Bytes32)>,
+}
+
+
+/// A finished child frame, detached from the environment it ran in so that
+/// it can be incorporated into its parent.
+pub struct ChildEvm {
+    pub gas_left: Uint,
+    pub logs: Vec<Log>,
+    pub refund_counter: i64,
+    pub message: Message,
+    pub output: Bytes,
+    pub accounts_to_delete: BTreeSet<Address>,
+    pub touched_accounts: BTreeSet<Address>,
+    pub error: Option<VmError>,
+    /// Whether `message.current_target` exists and is empty after the call.
+    pub target_exists_and_is_empty: bool,
+    pub accessed_addresses: BTreeSet<Address>,
+    pub accessed_storage_keys: BTreeSet<(Address, Bytes32)>,
+}
+
+impl Evm<'_, '_> {
+    /// Release the environment, keeping the results of the frame.
+    pub fn detach(self) -> ChildEvm {
+        let target_exists_and_is_empty =
+            account_exists_and_is_empty(self.env.state, &self.message.current_target);
+        ChildEvm {
+            gas_left: self.gas_left,
+            logs: self.logs,
+            refund_counter: self.refund_counter,
+            message: self.message,
+            output: self.output,
+            accounts_to_delete: self.accounts_to_delete,
+            touched_accounts: self.touched_accounts,
+            error: self.error,
+            target_exists_and_is_empty,
+            accessed_addresses: self.accessed_addresses,
+            accessed_storage_keys: self.accessed_storage_keys,
+        }
+    }
+}
+
+
+/// """
+/// Incorporate the state of a successful `child_evm` into the parent `evm`.
+///
+/// Parameters
+/// ----------
+/// evm :
+///     The parent `EVM`.
+/// child_evm :
+///     The child evm to incorporate.
+/// """
+pub fn incorporate_child_on_success(evm: &mut Evm, child_evm: ChildEvm) {
+    evm.gas_left += child_evm.gas_left;
+    evm.logs.extend(child_evm.logs);
+    evm.refund_counter += child_evm.refund_counter;
+    evm.accounts_to_delete.extend(child_evm.accounts_to_delete);
+    evm.touched_accounts.extend(child_evm.touched_accounts);
+    if child_evm.target_exists_and_is_empty {
+        evm.touched_accounts.insert(child_evm.message.current_target);
+    }
+    // The child started from a copy of the parent's sets, so they only
+    // grow (EIP-2929). A failed child's accesses are forgotten, like the
+    // rest of what it did.
+    evm.accessed_addresses.extend(child_evm.accessed_addresses);
+    evm.accessed_storage_keys.extend(child_evm.accessed_storage_keys);
+}
+
+
+/// """
+/// Incorporate the state of an unsuccessful `child_evm` into the parent `evm`.
+///
+/// Parameters
+/// ----------
+/// evm :
+///     The parent `EVM`.
+/// child_evm :
+///     The child evm to incorporate.
+/// """
+pub fn incorporate_child_on_error(evm: &mut Evm, child_evm: ChildEvm) {
+    // In block 2675119, the empty account at 0x3 (the RIPEMD160 precompile) was
+    // cleared despite running out of gas. This is an obscure edge case that can
+    // only happen to a precompile.
+    // According to the general rules governing clearing of empty accounts, the
+    // touch should have been reverted. Due to client bugs, this event went
+    // unnoticed and 0x3 has been exempted from the rule that touches are
+    // reverted in order to preserve this historical behaviour.
+    if child_evm.touched_accounts.contains(&RIPEMD160_ADDRESS) {
+        evm.touched_accounts.insert(RIPEMD160_ADDRESS);
+    }
+    if child_evm.message.current_target == RIPEMD160_ADDRESS && child_evm.target_exists_and_is_empty {
+        evm.touched_accounts.insert(RIPEMD160_ADDRESS);
+    }
+    evm.gas_left += child_evm.gas_left;
 }
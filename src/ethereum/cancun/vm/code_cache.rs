@@ -0,0 +1,295 @@
+//! Analysed code shared between executions, keyed by the hash of the code.
+//!
+//! Before code runs, some work is done on the code alone: the interpreter
+//! finds its valid jump destinations, and a compiling backend produces
+//! machine code. A `CodeCache` keeps the result for each code hash, so that
+//! repeated calls to the same contract reuse it. It holds the analyses of at
+//! most `capacity` bytes of code, evicting the least recently used beyond
+//! that, and is shared between threads by reference:
+//!
+//! ```
+//! use ejit_evm::ethereum::{
+//!     cancun::{
+//!         state::State,
+//!         vm::{code_cache::{CodeCache, DEFAULT_CODE_CACHE_SIZE}, Environment},
+//!     },
+//!     forks::ExecutionRules,
+//! };
+//!
+//! let cache = CodeCache::new(DEFAULT_CODE_CACHE_SIZE);
+//! let mut state = State::default();
+//! let env = Environment { code_cache: Some(&cache), ..Environment::new(&mut state, ExecutionRules::CANCUN) };
+//! ```
+//!
+//! Looking up code hashes all of it, so the cache pays off for analyses
+//! which cost more than a pass over the code.
+
+use std::{
+    collections::{BTreeMap, BTreeSet},
+    sync::{Arc, Mutex, OnceLock},
+};
+
+use crate::{
+    ethereum::{
+        crypto::hash::{keccak256, Hash32},
+        ethereum_types::numeric::Uint,
+    },
+    metrics::{self, Counter},
+};
+
+/// The capacity of a cache for the code of a busy chain, in bytes of code.
+pub const DEFAULT_CODE_CACHE_SIZE: usize = 64 << 20;
+
+/// A cache of the valid jump destinations of code, of the default size,
+/// whose lookups are counted in the metrics.
+pub fn jump_destinations_cache() -> CodeCache<BTreeSet<Uint>> {
+    let mut cache = CodeCache::new(DEFAULT_CODE_CACHE_SIZE);
+    cache.counters = Some((&metrics::CODE_CACHE_HITS, &metrics::CODE_CACHE_MISSES));
+    cache
+}
+
+/// The valid jump destinations cache of the process, for code which runs
+/// without a `BlockChain` to keep one on, such as state tests.
+pub fn shared_code_cache() -> &'static CodeCache<BTreeSet<Uint>> {
+    static CACHE: OnceLock<CodeCache<BTreeSet<Uint>>> = OnceLock::new();
+    CACHE.get_or_init(jump_destinations_cache)
+}
+
+/// Analyses of code by the hash of the code, with least recently used
+/// eviction.
+pub struct CodeCache<T> {
+    capacity: usize,
+    inner: Mutex<Entries<T>>,
+    /// Process wide counters of the hits and misses, besides those of the
+    /// cache.
+    counters: Option<(&'static Counter, &'static Counter)>,
+}
+
+struct Entries<T> {
+    by_hash: BTreeMap<Hash32, Entry<T>>,
+    /// The hashes by the tick they were last used at, oldest first.
+    by_use: BTreeMap<u64, Hash32>,
+    tick: u64,
+    size: usize,
+    hits: u64,
+    misses: u64,
+}
+
+struct Entry<T> {
+    analysis: Arc<T>,
+    size: usize,
+    last_used: u64,
+}
+
+impl<T> CodeCache<T> {
+    /// An empty cache for the analyses of `capacity` bytes of code.
+    pub fn new(capacity: usize) -> Self {
+        Self {
+            capacity,
+            inner: Mutex::new(Entries {
+                by_hash: BTreeMap::new(),
+                by_use: BTreeMap::new(),
+                tick: 0,
+                size: 0,
+                hits: 0,
+                misses: 0,
+            }),
+            counters: None,
+        }
+    }
+
+    /// The analysis of `code`, made with `analyse` unless it is cached.
+    ///
+    /// The lock is not held while `analyse` runs, so threads which miss the
+    /// same code at once each analyse it and the first to finish is kept.
+    /// Code longer than the capacity is analysed every time.
+    pub fn get_or_insert_with(&self, code: &[u8], analyse: impl FnOnce(&[u8]) -> T) -> Arc<T> {
+        let hash = keccak256(code);
+        if let Some(analysis) = self.get(&hash) {
+            return analysis;
+        }
+        let analysis = Arc::new(analyse(code));
+        if code.len() > self.capacity {
+            return analysis;
+        }
+
+        let mut entries = self.inner.lock().unwrap();
+        if let Some(entry) = entries.by_hash.get(&hash) {
+            return entry.analysis.clone();
+        }
+        while entries.size + code.len() > self.capacity {
+            let (_, oldest) = entries.by_use.pop_first().unwrap();
+            let evicted = entries.by_hash.remove(&oldest).unwrap();
+            entries.size -= evicted.size;
+        }
+        let tick = entries.next_tick();
+        entries.by_use.insert(tick, hash.clone());
+        entries.by_hash.insert(hash, Entry { analysis: analysis.clone(), size: code.len(), last_used: tick });
+        entries.size += code.len();
+        analysis
+    }
+
+    /// The cached analysis of the code with hash `hash`, if any.
+    pub fn get(&self, hash: &Hash32) -> Option<Arc<T>> {
+        let mut entries = self.inner.lock().unwrap();
+        let tick = entries.next_tick();
+        let Some(entry) = entries.by_hash.get_mut(hash) else {
+            entries.misses += 1;
+            if let Some((_, misses)) = self.counters {
+                misses.inc();
+            }
+            return None;
+        };
+        let last_used = std::mem::replace(&mut entry.last_used, tick);
+        let analysis = entry.analysis.clone();
+        entries.by_use.remove(&last_used);
+        entries.by_use.insert(tick, hash.clone());
+        entries.hits += 1;
+        if let Some((hits, _)) = self.counters {
+            hits.inc();
+        }
+        Some(analysis)
+    }
+
+    /// The number of cached analyses.
+    pub fn len(&self) -> usize {
+        self.inner.lock().unwrap().by_hash.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// The bytes of code whose analyses are cached.
+    pub fn size(&self) -> usize {
+        self.inner.lock().unwrap().size
+    }
+
+    /// The lookups which found an analysis and those which did not.
+    pub fn hits_and_misses(&self) -> (u64, u64) {
+        let entries = self.inner.lock().unwrap();
+        (entries.hits, entries.misses)
+    }
+}
+
+impl<T> std::fmt::Debug for CodeCache<T> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("CodeCache").field("capacity", &self.capacity).field("size", &self.size()).finish()
+    }
+}
+
+impl<T> Entries<T> {
+    fn next_tick(&mut self) -> u64 {
+        self.tick += 1;
+        self.tick
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{
+        collections::BTreeSet,
+        sync::{Arc, Mutex},
+    };
+
+    use crate::ethereum::{
+        cancun::{
+            fork_types::{Account, Address},
+            state::{set_account, State, TransientStorage},
+            utils::prepare_message,
+            vm::{interpreter::process_message_call, runtime::get_valid_jump_destinations, Environment},
+        },
+        crypto::hash::keccak256,
+        ethereum_types::{
+            bytes::Bytes,
+            numeric::{Uint, U256},
+        },
+        forks::ExecutionRules,
+    };
+
+    use super::CodeCache;
+
+    #[test]
+    fn least_recently_used_eviction() {
+        let cache = CodeCache::new(10);
+        let (a, b, c) = ([1; 4], [2; 4], [3; 4]);
+        assert_eq!(*cache.get_or_insert_with(&a, |code| code[0]), 1);
+        assert_eq!(*cache.get_or_insert_with(&b, |code| code[0]), 2);
+        // A hit does not analyse again, and makes `a` the most recently used.
+        assert_eq!(*cache.get_or_insert_with(&a, |_| unreachable!()), 1);
+        assert_eq!(*cache.get_or_insert_with(&c, |code| code[0]), 3);
+        assert_eq!((cache.len(), cache.size()), (2, 8));
+        assert!(cache.get(&keccak256(&b)).is_none());
+        assert!(cache.get(&keccak256(&a)).is_some());
+
+        // Code over the capacity is analysed but not kept.
+        assert_eq!(*cache.get_or_insert_with(&[4; 11], |code| code[0]), 4);
+        assert_eq!((cache.len(), cache.size()), (2, 8));
+        assert_eq!(cache.hits_and_misses(), (2, 5));
+    }
+
+    #[test]
+    fn shared_between_threads() {
+        let cache = CodeCache::new(1024);
+        let analyses = Mutex::new(0);
+        std::thread::scope(|scope| {
+            for _ in 0..4 {
+                scope.spawn(|| {
+                    for code in [[1; 32], [2; 32]] {
+                        let analysis = cache.get_or_insert_with(&code, |code| {
+                            *analyses.lock().unwrap() += 1;
+                            code.len()
+                        });
+                        assert_eq!(*analysis, 32);
+                    }
+                });
+            }
+        });
+        assert_eq!((cache.len(), cache.size()), (2, 64));
+        let analyses = *analyses.lock().unwrap();
+        assert!((2..=8).contains(&analyses));
+    }
+
+    #[test]
+    fn interpreter_jump_destinations() {
+        let (caller, contract) = (Address::from_be_bytes([0xca; 20]), Address::from_be_bytes([0xc0; 20]));
+        // PUSH1 4 JUMP INVALID JUMPDEST PUSH1 1 PUSH1 0 MSTORE PUSH1 32 PUSH1 0 RETURN
+        let code = vec![0x60, 0x04, 0x56, 0xfe, 0x5b, 0x60, 0x01, 0x60, 0x00, 0x52, 0x60, 0x20, 0x60, 0x00, 0xf3];
+        let cache = CodeCache::new(1024);
+        let mut outputs = Vec::new();
+        for code_cache in [None, Some(&cache), Some(&cache)] {
+            let mut state = State::default();
+            set_account(&mut state, &contract, Some(Account { code: Bytes(code.clone()), ..Default::default() }));
+            let mut env = Environment {
+                caller: caller.clone(),
+                origin: caller.clone(),
+                gas_limit: 100_000,
+                code_cache,
+                ..Environment::new(&mut state, ExecutionRules::CANCUN)
+            };
+            let message = prepare_message(
+                caller.clone(),
+                Some(contract.clone()),
+                U256::ZERO,
+                Bytes::default(),
+                50_000,
+                &env,
+                None,
+                true,
+                false,
+                BTreeSet::new(),
+                BTreeSet::new(),
+            )
+            .unwrap();
+            let output = process_message_call(message, &mut env).unwrap();
+            assert_eq!(output.error, None);
+            outputs.push((output.gas_left, output.return_data));
+        }
+        assert!(outputs.iter().all(|output| *output == outputs[0]));
+        assert_eq!(U256::from_be_bytes(outputs[0].1 .0.clone().try_into().unwrap()), U256::ONE);
+
+        let analysis: Arc<BTreeSet<Uint>> = cache.get(&keccak256(&code)).unwrap();
+        assert_eq!(*analysis, get_valid_jump_destinations(&code));
+        assert_eq!(cache.hits_and_misses(), (2, 1));
+    }
+}
@@ -0,0 +1,286 @@
+//! Ethereum Virtual Machine (EVM) Object Format
+//! ^^^^^^^^^^^^^^^^^^^^^^^^^^^^^^^^^^^^^^^^^^^^
+//!
+//! .. contents:: Table of Contents
+//!     :backlinks: none
+//!     :local:
+//!
+//! Introduction
+//! ------------
+//!
+//! The EOF container (EIP-3540) and its code validation (EIP-3670).
+//!
+//! A container starts with the magic `0xEF00`, which no code deployed since
+//! London can start with (EIP-3541), and a version. A header of sections
+//! follows: exactly one code section and at most one data section, each
+//! with its kind and its size, and a terminator. The bodies of the sections
+//! follow the header and end the container.
+//!
+//! Containers are validated once, when they are deployed, so that code
+//! which is executed needs no checks beyond those of legacy code: every
+//! instruction is defined and no `PUSH` runs past the end of the code.
+//! Only the code section is executed, while `CODESIZE`, `CODECOPY` and the
+//! `EXTCODE` instructions see the whole container. EOF is enabled by
+//! `ExecutionRules::eof`, which no fork sets yet.
+
+use crate::ethereum::forks::ExecutionRules;
+
+use super::{exceptions::VmError, instructions::Ops};
+
+pub const MAGIC: [u8; 2] = [0xEF, 0x00];
+pub const VERSION: u8 = 0x01;
+
+const KIND_TERMINATOR: u8 = 0x00;
+const KIND_CODE: u8 = 0x01;
+const KIND_DATA: u8 = 0x02;
+
+/// The designated invalid instruction, which is valid in EOF code.
+const INVALID: u8 = 0xFE;
+
+/// A container, borrowing the bodies of its sections.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Container<'a> {
+    pub code: &'a [u8],
+    pub data: &'a [u8],
+}
+
+/// """
+/// Whether `code` claims to be an EOF container, by starting with the
+/// magic.
+/// """
+pub fn is_eof(code: &[u8]) -> bool {
+    code.starts_with(&MAGIC)
+}
+
+/// """
+/// Parse the header of an EOF container and split its body into sections.
+///
+/// Parameters
+/// ----------
+/// container :
+///     The code starting with the magic.
+///
+/// Returns
+/// -------
+/// container : `Container`
+///     The sections of the container.
+/// """
+pub fn parse_container(container: &[u8]) -> Result<Container<'_>, VmError> {
+    if !is_eof(container) || container.get(2) != Some(&VERSION) {
+        return Err(VmError::InvalidEof);
+    }
+    let mut pos = 3;
+    let mut code_size = None;
+    let mut data_size = None;
+    loop {
+        let kind = *container.get(pos).ok_or(VmError::InvalidEof)?;
+        pos += 1;
+        if kind == KIND_TERMINATOR {
+            break;
+        }
+        let size = container.get(pos..pos + 2).ok_or(VmError::InvalidEof)?;
+        let size = u16::from_be_bytes([size[0], size[1]]) as usize;
+        pos += 2;
+        // Sections are not empty and come in order: code, then data.
+        match kind {
+            KIND_CODE if code_size.is_none() && size > 0 => code_size = Some(size),
+            KIND_DATA if code_size.is_some() && data_size.is_none() && size > 0 => data_size = Some(size),
+            _ => return Err(VmError::InvalidEof),
+        }
+    }
+    let code_size = code_size.ok_or(VmError::InvalidEof)?;
+    let data_size = data_size.unwrap_or(0);
+    if container.len() != pos + code_size + data_size {
+        return Err(VmError::InvalidEof);
+    }
+    let (code, data) = container[pos..].split_at(code_size);
+    Ok(Container { code, data })
+}
+
+/// """
+/// Check that every instruction of `code` is defined under `rules` and
+/// that the immediate data of the last one is not truncated.
+///
+/// Parameters
+/// ----------
+/// code :
+///     The code section of a container.
+/// rules :
+///     The rules of the fork the code is deployed in.
+/// """
+pub fn validate_code(code: &[u8], rules: ExecutionRules) -> Result<(), VmError> {
+    let mut pc = 0;
+    while pc < code.len() {
+        let op = code[pc];
+        let defined = op == INVALID || Ops::from_u8(op).is_some_and(|op| rules.allows(op));
+        if !defined {
+            return Err(VmError::InvalidEof);
+        }
+        if (Ops::PUSH1 as u8..=Ops::PUSH32 as u8).contains(&op) {
+            pc += (op - Ops::PUSH1 as u8 + 1) as usize;
+        }
+        pc += 1;
+    }
+    if pc > code.len() {
+        return Err(VmError::InvalidEof);
+    }
+    Ok(())
+}
+
+/// """
+/// Parse an EOF container and validate its code.
+///
+/// Parameters
+/// ----------
+/// container :
+///     The code starting with the magic.
+/// rules :
+///     The rules of the fork the code is deployed in.
+///
+/// Returns
+/// -------
+/// container : `Container`
+///     The sections of the valid container.
+/// """
+pub fn validate_container(container: &[u8], rules: ExecutionRules) -> Result<Container<'_>, VmError> {
+    let parsed = parse_container(container)?;
+    validate_code(parsed.code, rules)?;
+    Ok(parsed)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::BTreeSet;
+
+    use crate::ethereum::{
+        cancun::{
+            fork_types::{Account, Address},
+            state::{get_account, get_storage, set_account, State, TransientStorage},
+            utils::{compute_contract_address, prepare_message},
+            vm::{exceptions::VmError, interpreter::{process_message_call, MessageCallOutput}, Environment},
+        },
+        ethereum_types::{bytes::{Bytes, Bytes32}, numeric::U256},
+        forks::ExecutionRules,
+    };
+
+    use super::{parse_container, validate_container, Container};
+
+    /// A container of `code` and `data`.
+    fn container(code: &[u8], data: &[u8]) -> Vec<u8> {
+        let mut container = vec![0xEF, 0x00, 0x01, 0x01];
+        container.extend((code.len() as u16).to_be_bytes());
+        if !data.is_empty() {
+            container.push(0x02);
+            container.extend((data.len() as u16).to_be_bytes());
+        }
+        container.push(0x00);
+        container.extend(code);
+        container.extend(data);
+        container
+    }
+
+    #[test]
+    fn containers() {
+        let rules = ExecutionRules::CANCUN;
+        // PUSH1 1 PUSH1 0 SSTORE STOP
+        let code = [0x60, 0x01, 0x60, 0x00, 0x55, 0x00];
+        let with_data = container(&code, b"data");
+        assert_eq!(validate_container(&with_data, rules), Ok(Container { code: &code, data: b"data" }));
+        assert_eq!(validate_container(&container(&code, &[]), rules).unwrap().data, b"");
+
+        let invalid = [
+            // Legacy code, a wrong version and a missing terminator.
+            code.to_vec(),
+            [&[0xEF, 0x00, 0x02], &with_data[3..]].concat(),
+            with_data[..9].to_vec(),
+            // Data before code, an empty code section and an unknown kind.
+            vec![0xEF, 0x00, 0x01, 0x02, 0x00, 0x01, 0x01, 0x00, 0x01, 0x00, 0xAA, 0x00],
+            vec![0xEF, 0x00, 0x01, 0x01, 0x00, 0x00, 0x00],
+            vec![0xEF, 0x00, 0x01, 0x01, 0x00, 0x01, 0x03, 0x00, 0x01, 0x00, 0x00, 0x00],
+            // Two code sections.
+            vec![0xEF, 0x00, 0x01, 0x01, 0x00, 0x01, 0x01, 0x00, 0x01, 0x00, 0x00, 0x00],
+            // A body which is too short and one which is too long.
+            with_data[..with_data.len() - 1].to_vec(),
+            [&with_data[..], &[0]].concat(),
+        ];
+        for container in invalid {
+            assert_eq!(parse_container(&container), Err(VmError::InvalidEof), "{container:02x?}");
+        }
+    }
+
+    #[test]
+    fn code_validation() {
+        let rules = ExecutionRules::CANCUN;
+        // INVALID is defined, 0x0C is not and MCOPY only from Cancun.
+        assert!(validate_container(&container(&[0x00, 0xFE], &[]), rules).is_ok());
+        assert_eq!(validate_container(&container(&[0x0C], &[]), rules), Err(VmError::InvalidEof));
+        assert!(validate_container(&container(&[0x5E], &[]), rules).is_ok());
+        assert!(validate_container(&container(&[0x5E], &[]), ExecutionRules::SHANGHAI).is_err());
+        // PUSH2 whose data is cut short by the end of the code, even with a
+        // data section after it.
+        assert!(validate_container(&container(&[0x61, 0x01, 0x02], &[]), rules).is_ok());
+        assert!(validate_container(&container(&[0x61, 0x01], &[0x02]), rules).is_err());
+    }
+
+    /// Run a message from an account with nonce 1 to `target`, or create
+    /// a contract with init code `data` if it is `None`.
+    fn run(state: &mut State, rules: ExecutionRules, target: Option<Address>, data: Vec<u8>) -> MessageCallOutput {
+        let caller = Address::from_be_bytes([0xca; 20]);
+        set_account(state, &caller, Some(Account { nonce: 1, ..Default::default() }));
+        let mut env = Environment {
+            caller: caller.clone(),
+            origin: caller.clone(),
+            gas_limit: 1_000_000,
+            ..Environment::new(state, rules)
+        };
+        let message = prepare_message(
+            caller,
+            target,
+            U256::ZERO,
+            Bytes(data),
+            1_000_000,
+            &env,
+            None,
+            true,
+            false,
+            BTreeSet::new(),
+            BTreeSet::new(),
+        )
+        .unwrap();
+        process_message_call(message, &mut env).unwrap()
+    }
+
+    /// Legacy init code which deploys `code`.
+    fn deploy(code: &[u8]) -> Vec<u8> {
+        let len = code.len() as u8;
+        // CODECOPY(0, 12, len) RETURN(0, len)
+        [&[0x60, len, 0x60, 0x0c, 0x60, 0x00, 0x39, 0x60, len, 0x60, 0x00, 0xf3], code].concat()
+    }
+
+    #[test]
+    fn deployment_and_execution() {
+        let eof = ExecutionRules { eof: true, ..ExecutionRules::CANCUN };
+        // SSTORE(0, CODESIZE) STOP
+        let runtime = container(&[0x38, 0x60, 0x00, 0x55, 0x00], b"data");
+        let error = |rules, init_code| run(&mut State::default(), rules, None, init_code).error;
+
+        // Before EOF no code may start with 0xEF, and with EOF only valid
+        // containers may.
+        assert_eq!(error(ExecutionRules::CANCUN, deploy(&runtime)), Some(VmError::InvalidContractPrefix));
+        assert_eq!(error(eof, deploy(&runtime[..runtime.len() - 1])), Some(VmError::InvalidEof));
+        assert_eq!(error(eof, deploy(&[0xEF, 0x01])), Some(VmError::InvalidContractPrefix));
+
+        let mut state = State::default();
+        let address = compute_contract_address(&Address::from_be_bytes([0xca; 20]), 0).unwrap();
+        assert_eq!(run(&mut state, eof, None, deploy(&runtime)).error, None);
+        assert_eq!(get_account(&state, &address).code.0, runtime);
+
+        // Only the code section runs, but CODESIZE is that of the container.
+        assert_eq!(run(&mut state, eof, Some(address.clone()), Vec::new()).error, None);
+        assert_eq!(get_storage(&state, &address, &Bytes32::default()), U256::from(runtime.len() as u64));
+
+        // Init code which is an invalid container fails and uses all gas.
+        let output = run(&mut State::default(), eof, None, container(&[0x0C], &[]));
+        assert_eq!((output.error, output.gas_left), (Some(VmError::InvalidEof), 0));
+    }
+}
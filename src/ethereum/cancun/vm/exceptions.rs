@@ -1,4 +1,4 @@
-//!     """
+//! """
 //! Ethereum Virtual Machine (EVM) Exceptions
 //! ^^^^^^^^^^^^^^^^^^^^^^^^^^^^^^^^^^^^^^^^^
 //!
@@ -12,6 +12,7 @@
 //! Exceptions which cause the EVM to halt exceptionally.
 //! """
 
+#[derive(Debug, Clone, PartialEq)]
 pub enum VmError {
     /// """
     /// Indicates that the EVM has experienced an exceptional halt. This causes
@@ -52,10 +53,12 @@ pub enum VmError {
     /// Occurs when the destination of a jump operation doesn't meet any of the
     /// following criteria:
     ///
-    ///     * The jump destination is less than the length of the code.
-    ///     * The jump destination should have the `JUMPDEST` opcode (0x5B).
-    ///     * The jump destination shouldn't be part of the data corresponding to
-    ///     `PUSH-N` opcodes.
+    /// ```text
+    /// * The jump destination is less than the length of the code.
+    /// * The jump destination should have the `JUMPDEST` opcode (0x5B).
+    /// * The jump destination shouldn't be part of the data corresponding to
+    /// `PUSH-N` opcodes.
+    /// ```
     /// """
     InvalidJumpDestError,
 
@@ -86,6 +89,12 @@ pub enum VmError {
     /// """
     InvalidContractPrefix,
 
+    /// """
+    /// Raised when code starting with the EOF magic is not a valid
+    /// container.
+    /// """
+    InvalidEof,
+
     /// """
     /// Raised when the new contract address has a collision.
     /// """
@@ -96,3 +105,28 @@ pub enum VmError {
     /// """
     KZGProofError,
 }
+
+impl std::fmt::Display for VmError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let message = match self {
+            VmError::ExceptionalHalt => "exceptional halt",
+            VmError::Revert => "execution reverted",
+            VmError::StackUnderflowError => "stack underflow",
+            VmError::StackOverflowError => "stack overflow",
+            VmError::OutOfGasError => "out of gas",
+            VmError::InvalidOpcode => "invalid opcode",
+            VmError::InvalidJumpDestError => "invalid jump destination",
+            VmError::StackDepthLimitError => "call depth limit reached",
+            VmError::WriteInStaticContext => "state modified in a static call",
+            VmError::OutOfBoundsRead => "read out of bounds",
+            VmError::InvalidParameter => "invalid parameter",
+            VmError::InvalidContractPrefix => "contract code starts with 0xEF",
+            VmError::InvalidEof => "invalid EOF container",
+            VmError::AddressCollision => "contract address collision",
+            VmError::KZGProofError => "KZG proof verification failed",
+        };
+        f.write_str(message)
+    }
+}
+
+impl std::error::Error for VmError {}
@@ -10,58 +10,93 @@
 //! 
 //! EVM gas constants and calculators.
 
-use crate::ethereum::{cancun::{blocks::Header, transactions::Transaction}, ethereum_types::numeric::{Uint, U256, U64}, exceptions::Exception, utils::numeric::{ceil32, taylor_exponential}};
+use crate::ethereum::{cancun::{blocks::Header, transactions::Transaction}, chain_spec::ChainSpec, ethereum_types::numeric::{Uint, U256, U64}, exceptions::Exception, utils::numeric::{ceil32, taylor_exponential}};
 
 use super::{exceptions::VmError, Evm};
 
 // https://github.com/ethereum/execution-specs/blob/master/src/ethereum/cancun/vm/gas.py
-const GAS_JUMPDEST : Uint = 1_u128;
-const GAS_BASE : Uint = 2_u128;
-const GAS_VERY_LOW : Uint = 3_u128;
-const GAS_STORAGE_SET : Uint = 20000_u128;
-const GAS_STORAGE_UPDATE : Uint = 5000_u128;
-const GAS_STORAGE_CLEAR_REFUND : Uint = 4800_u128;
-const GAS_LOW : Uint = 5_u128;
-const GAS_MID : Uint = 8_u128;
-const GAS_HIGH : Uint = 10_u128;
-const GAS_EXPONENTIATION : Uint = 10_u128;
-const GAS_EXPONENTIATION_PER_BYTE : Uint = 50_u128;
-const GAS_MEMORY : Uint = 3_u128;
-const GAS_KECCAK256 : Uint = 30_u128;
-const GAS_KECCAK256_WORD : Uint = 6_u128;
-const GAS_COPY : Uint = 3_u128;
-const GAS_BLOCK_HASH : Uint = 20_u128;
-const GAS_LOG : Uint = 375_u128;
-const GAS_LOG_DATA : Uint = 8_u128;
-const GAS_LOG_TOPIC : Uint = 375_u128;
-const GAS_CREATE : Uint = 32000_u128;
-const GAS_CODE_DEPOSIT : Uint = 200_u128;
-const GAS_ZERO : Uint = 0_u128;
-const GAS_NEW_ACCOUNT : Uint = 25000_u128;
-const GAS_CALL_VALUE : Uint = 9000_u128;
-const GAS_CALL_STIPEND : Uint = 2300_u128;
-const GAS_SELF_DESTRUCT : Uint = 5000_u128;
-const GAS_SELF_DESTRUCT_NEW_ACCOUNT : Uint = 25000_u128;
-const GAS_ECRECOVER : Uint = 3000_u128;
-const GAS_SHA256 : Uint = 60_u128;
-const GAS_SHA256_WORD : Uint = 12_u128;
-const GAS_RIPEMD160 : Uint = 600_u128;
-const GAS_RIPEMD160_WORD : Uint = 120_u128;
-const GAS_IDENTITY : Uint = 15_u128;
-const GAS_IDENTITY_WORD : Uint = 3_u128;
-const GAS_RETURN_DATA_COPY : Uint = 3_u128;
-const GAS_FAST_STEP : Uint = 5_u128;
-const GAS_BLAKE2_PER_ROUND : Uint = 1_u128;
-const GAS_COLD_SLOAD : Uint = 2100_u128;
-const GAS_COLD_ACCOUNT_ACCESS : Uint = 2600_u128;
-const GAS_WARM_ACCESS : Uint = 100_u128;
-const GAS_INIT_CODE_WORD_COST : Uint = 2_u128;
-const GAS_BLOBHASH_OPCODE : Uint = 3_u128;
-const GAS_POINT_EVALUATION : Uint = 50000_u128;
-const TARGET_BLOB_GAS_PER_BLOCK : U64 = 393216;
-const GAS_PER_BLOB : Uint = 1_u128<<17;
-const MIN_BLOB_GASPRICE : Uint = 1_u128;
-const BLOB_GASPRICE_UPDATE_FRACTION : Uint = 3338477_u128;
+pub const GAS_JUMPDEST : Uint = 1_u128;
+pub const GAS_BASE : Uint = 2_u128;
+pub const GAS_VERY_LOW : Uint = 3_u128;
+pub const GAS_STORAGE_SET : Uint = 20000_u128;
+pub const GAS_STORAGE_UPDATE : Uint = 5000_u128;
+pub const GAS_STORAGE_CLEAR_REFUND : Uint = 4800_u128;
+/// Before London (EIP-3529).
+pub const GAS_STORAGE_CLEAR_REFUND_FRONTIER : Uint = 15000_u128;
+pub const GAS_SLOAD_FRONTIER : Uint = 50_u128;
+/// EIP-150.
+pub const GAS_SLOAD_TANGERINE_WHISTLE : Uint = 200_u128;
+/// EIP-1884, until the warm and cold costs of EIP-2929.
+pub const GAS_SLOAD_ISTANBUL : Uint = 800_u128;
+pub const GAS_BALANCE_FRONTIER : Uint = 20_u128;
+/// EIP-150.
+pub const GAS_BALANCE_TANGERINE_WHISTLE : Uint = 400_u128;
+/// EIP-1884, until the warm and cold costs of EIP-2929.
+pub const GAS_BALANCE_ISTANBUL : Uint = 700_u128;
+/// `EXTCODESIZE` and `EXTCODECOPY`.
+pub const GAS_EXTERNAL_FRONTIER : Uint = 20_u128;
+/// EIP-150, until the warm and cold costs of EIP-2929.
+pub const GAS_EXTERNAL_TANGERINE_WHISTLE : Uint = 700_u128;
+/// EIP-1052.
+pub const GAS_CODE_HASH_CONSTANTINOPLE : Uint = 400_u128;
+/// EIP-1884, until the warm and cold costs of EIP-2929.
+pub const GAS_CODE_HASH_ISTANBUL : Uint = 700_u128;
+pub const GAS_CALL_FRONTIER : Uint = 40_u128;
+/// EIP-150, until the warm and cold costs of EIP-2929.
+pub const GAS_CALL_TANGERINE_WHISTLE : Uint = 700_u128;
+pub const GAS_LOW : Uint = 5_u128;
+pub const GAS_MID : Uint = 8_u128;
+pub const GAS_HIGH : Uint = 10_u128;
+pub const GAS_EXPONENTIATION : Uint = 10_u128;
+pub const GAS_EXPONENTIATION_PER_BYTE : Uint = 50_u128;
+/// Before Spurious Dragon (EIP-160).
+pub const GAS_EXPONENTIATION_PER_BYTE_FRONTIER : Uint = 10_u128;
+pub const GAS_MEMORY : Uint = 3_u128;
+pub const GAS_KECCAK256 : Uint = 30_u128;
+pub const GAS_KECCAK256_WORD : Uint = 6_u128;
+pub const GAS_COPY : Uint = 3_u128;
+pub const GAS_BLOCK_HASH : Uint = 20_u128;
+pub const GAS_LOG : Uint = 375_u128;
+pub const GAS_LOG_DATA : Uint = 8_u128;
+pub const GAS_LOG_TOPIC : Uint = 375_u128;
+pub const GAS_CREATE : Uint = 32000_u128;
+pub const GAS_CODE_DEPOSIT : Uint = 200_u128;
+pub const GAS_ZERO : Uint = 0_u128;
+pub const GAS_NEW_ACCOUNT : Uint = 25000_u128;
+pub const GAS_CALL_VALUE : Uint = 9000_u128;
+pub const GAS_CALL_STIPEND : Uint = 2300_u128;
+pub const GAS_SELF_DESTRUCT : Uint = 5000_u128;
+pub const GAS_SELF_DESTRUCT_NEW_ACCOUNT : Uint = 25000_u128;
+/// Before London (EIP-3529).
+pub const REFUND_SELF_DESTRUCT : Uint = 24000_u128;
+pub const GAS_ECRECOVER : Uint = 3000_u128;
+pub const GAS_SHA256 : Uint = 60_u128;
+pub const GAS_SHA256_WORD : Uint = 12_u128;
+pub const GAS_RIPEMD160 : Uint = 600_u128;
+pub const GAS_RIPEMD160_WORD : Uint = 120_u128;
+pub const GAS_IDENTITY : Uint = 15_u128;
+pub const GAS_IDENTITY_WORD : Uint = 3_u128;
+pub const GAS_RETURN_DATA_COPY : Uint = 3_u128;
+pub const GAS_FAST_STEP : Uint = 5_u128;
+pub const GAS_BLAKE2_PER_ROUND : Uint = 1_u128;
+/// EIP-196 and EIP-197, until the cheaper costs of EIP-1108 in Istanbul.
+pub const GAS_BN254_ADD_BYZANTIUM : Uint = 500_u128;
+pub const GAS_BN254_MUL_BYZANTIUM : Uint = 40000_u128;
+pub const GAS_BN254_PAIRING_BYZANTIUM : Uint = 100000_u128;
+pub const GAS_BN254_PAIRING_PER_POINT_BYZANTIUM : Uint = 80000_u128;
+pub const GAS_BN254_ADD : Uint = 150_u128;
+pub const GAS_BN254_MUL : Uint = 6000_u128;
+pub const GAS_BN254_PAIRING : Uint = 45000_u128;
+pub const GAS_BN254_PAIRING_PER_POINT : Uint = 34000_u128;
+pub const GAS_COLD_SLOAD : Uint = 2100_u128;
+pub const GAS_COLD_ACCOUNT_ACCESS : Uint = 2600_u128;
+pub const GAS_WARM_ACCESS : Uint = 100_u128;
+pub const GAS_INIT_CODE_WORD_COST : Uint = 2_u128;
+pub const GAS_BLOBHASH_OPCODE : Uint = 3_u128;
+pub const GAS_POINT_EVALUATION : Uint = 50000_u128;
+pub const GAS_PER_BLOB : Uint = 1_u128<<17;
+pub const MIN_BLOB_GASPRICE : Uint = 1_u128;
+pub const BLOB_GASPRICE_UPDATE_FRACTION : Uint = 3338477_u128;
 
 
 
@@ -71,9 +106,9 @@ const BLOB_GASPRICE_UPDATE_FRACTION : Uint = 3338477_u128;
 ///     The gas required to perform the extension
 /// `expand_by`: `ethereum.base_types.Uint`
 ///     The size by which the memory will be extended
-struct ExtendMemory {
-    cost: Uint,
-    expand_by: Uint,
+pub struct ExtendMemory {
+    pub cost: Uint,
+    pub expand_by: Uint,
 }
 
 
@@ -86,9 +121,9 @@ struct ExtendMemory {
 ///    `stipend`: `ethereum.base_types.Uint`
 ///        The portion of gas available to sub-calls that is refundable
 ///        if not consumed
-struct MessageCallGas {
-    cost: Uint,
-    stipend: Uint,
+pub struct MessageCallGas {
+    pub cost: Uint,
+    pub stipend: Uint,
 }
 
 
@@ -104,14 +139,11 @@ struct MessageCallGas {
 ///     The amount of gas the current operation requires.
 /// 
 /// """
-fn charge_gas(evm: &mut Evm, amount: Uint) -> Result<(), VmError> {
+#[inline]
+pub fn charge_gas(evm: &mut Evm, amount: Uint) -> Result<(), VmError> {
     // evm_trace(evm, GasAndRefund(int(amount)));
 
-    if evm.gas_left < amount {
-        return Err(VmError::OutOfGasError);
-    } else {
-        evm.gas_left -= amount;
-    }
+    evm.gas_left = evm.gas_left.checked_sub(amount).ok_or(VmError::OutOfGasError)?;
     Ok(())
 }
 
@@ -160,9 +192,9 @@ pub fn calculate_memory_gas_cost(size_in_bytes: Uint) -> Result<Uint, Exception>
 /// -------
 /// extend_memory: `ExtendMemory`
 /// """
-fn calculate_gas_extend_memory(
+pub fn calculate_gas_extend_memory(
     memory: &[u8], extensions: &[(U256, U256)]
-) -> Result<ExtendMemory, Exception> {
+) -> Result<ExtendMemory, VmError> {
     let mut size_to_extend = Uint::from(0_u32);
     let mut to_be_paid = Uint::from(0_u32);
     let mut current_size = Uint::from(memory.len() as u128);
@@ -171,14 +203,20 @@ fn calculate_gas_extend_memory(
             continue;
         }
         let before_size = ceil32(current_size);
-        let after_size = ceil32(start_position.to_uint()? + size.to_uint()?);
+        // Any extension beyond 2^64 bytes costs more gas than can exist.
+        let end = start_position.to_uint().ok()
+            .zip(size.to_uint().ok())
+            .map(|(start, size)| start + size)
+            .filter(|end| *end <= u64::MAX as Uint)
+            .ok_or(VmError::OutOfGasError)?;
+        let after_size = ceil32(end);
         if after_size <= before_size {
             continue;
         }
 
         size_to_extend += after_size - before_size;
-        let already_paid = calculate_memory_gas_cost(before_size)?;
-        let total_cost = calculate_memory_gas_cost(after_size)?;
+        let already_paid = calculate_memory_gas_cost(before_size).map_err(|_| VmError::OutOfGasError)?;
+        let total_cost = calculate_memory_gas_cost(after_size).map_err(|_| VmError::OutOfGasError)?;
         to_be_paid += total_cost - already_paid;
 
         current_size = after_size;
@@ -276,12 +314,14 @@ pub fn init_code_cost(init_code_length: Uint) -> Uint {
 /// ----------
 /// parent_header :
 ///     The parent block of the current block.
+/// chain_spec :
+///     The blob gas target of the chain.
 /// 
 /// Returns
 /// -------
 /// excess_blob_gas: `ethereum.base_types.U64`
 ///     The excess blob gas for the current block.
-pub fn calculate_excess_blob_gas(parent_header: &Header) -> Option<U64> {
+pub fn calculate_excess_blob_gas(parent_header: &Header, chain_spec: &ChainSpec) -> Option<U64> {
     // At the fork block, these are defined as zero.
     let mut excess_blob_gas = U64::from(0_u64);
     let mut blob_gas_used = U64::from(0_u64);
@@ -294,10 +334,10 @@ pub fn calculate_excess_blob_gas(parent_header: &Header) -> Option<U64> {
         ..
     } = parent_header {
         let parent_blob_gas = excess_blob_gas + blob_gas_used;
-        if parent_blob_gas < TARGET_BLOB_GAS_PER_BLOCK {
+        if parent_blob_gas < chain_spec.target_blob_gas_per_block {
             Some(U64::from(0_u64))
         } else {
-            Some(parent_blob_gas - TARGET_BLOB_GAS_PER_BLOCK)
+            Some(parent_blob_gas - chain_spec.target_blob_gas_per_block)
         }
     } else {
         None
@@ -0,0 +1,379 @@
+//! Hybrid execution: the interpreter by default, compiled code for hot
+//! contracts.
+//!
+//! A `HybridExecutor` in `Environment::executor` runs the code of every
+//! frame. It counts the calls and the gas of each code hash, and once code
+//! reaches the `Promotion` thresholds it hands it to a `Compiler`, such as
+//! the ejit backend, and runs the compiled code from then on:
+//!
+//! ```
+//! use ejit_evm::ethereum::{
+//!     cancun::{
+//!         state::State,
+//!         vm::{hybrid::{Compiled, Compiler, HybridExecutor, Promotion}, Environment},
+//!     },
+//!     forks::ExecutionRules,
+//! };
+//!
+//! /// A backend which declines all code.
+//! struct Interpreted;
+//!
+//! impl Compiler for Interpreted {
+//!     fn compile(&self, _: &[u8], _: ExecutionRules) -> Option<Box<dyn Compiled>> {
+//!         None
+//!     }
+//! }
+//!
+//! let executor = HybridExecutor::new(Box::new(Interpreted), Promotion::default());
+//! let mut state = State::default();
+//! let env = Environment { executor: Some(&executor), ..Environment::new(&mut state, ExecutionRules::CANCUN) };
+//! ```
+//!
+//! A compiler declines code it does not support yet, for instance code
+//! with an instruction it has no translation for, and that code keeps
+//! running in the interpreter.
+//!
+//! The executor keeps the profiles of at most `MAX_PROFILES` code hashes,
+//! and remembers the code hash at each of `MAX_CODE_HASHES` addresses so
+//! that calls to the same contract compare its code instead of hashing it.
+
+use std::{collections::BTreeMap, sync::Mutex};
+
+use crate::{
+    ethereum::{
+        cancun::fork_types::Address,
+        crypto::hash::{keccak256, Hash32},
+        ethereum_types::{bytes::Bytes, numeric::Uint},
+        forks::ExecutionRules,
+    },
+    log::{self, debug, Level},
+    metrics,
+};
+
+use super::{
+    code_cache::{CodeCache, DEFAULT_CODE_CACHE_SIZE},
+    exceptions::VmError,
+    interpreter::interpret,
+    Evm,
+};
+
+/// The most code hashes profiled at once. Beyond it, an arbitrary profile
+/// is dropped for each new one.
+pub const MAX_PROFILES: usize = 16384;
+
+/// The most addresses whose code hash is remembered. Beyond it, an
+/// arbitrary address is forgotten for each new one.
+pub const MAX_CODE_HASHES: usize = 4096;
+
+/// A backend which turns code into something faster to run.
+pub trait Compiler: Send + Sync {
+    /// Compile `code` for `rules`, or `None` if it needs anything the
+    /// backend does not support.
+    fn compile(&self, code: &[u8], rules: ExecutionRules) -> Option<Box<dyn Compiled>>;
+}
+
+/// Code compiled by a `Compiler`.
+pub trait Compiled: Send + Sync {
+    /// Run the code of `evm` until it stops or fails, as `interpret` does.
+    fn run(&self, evm: &mut Evm) -> Result<(), VmError>;
+}
+
+/// When code is hot enough to compile: after `calls` calls to it, or after
+/// its frames used `gas`, including the calls they made.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Promotion {
+    pub calls: u64,
+    pub gas: Uint,
+}
+
+impl Default for Promotion {
+    fn default() -> Self {
+        Self { calls: 100, gas: 10_000_000 }
+    }
+}
+
+/// What became of promoted code.
+enum Tier {
+    Compiled(Box<dyn Compiled>),
+    /// The compiler declined it.
+    Interpreted,
+}
+
+/// The calls and gas of code which is not promoted yet.
+#[derive(Debug, Clone, Copy, Default)]
+struct Profile {
+    calls: u64,
+    gas: Uint,
+}
+
+/// Runs code in the interpreter until it is hot, and compiled after.
+pub struct HybridExecutor {
+    compiler: Box<dyn Compiler>,
+    promotion: Promotion,
+    profiles: Mutex<BTreeMap<Hash32, Profile>>,
+    promoted: CodeCache<Tier>,
+    /// The code last run from each address, with its hash.
+    code_hashes: Mutex<BTreeMap<Address, (Bytes, Hash32)>>,
+}
+
+impl HybridExecutor {
+    pub fn new(compiler: Box<dyn Compiler>, promotion: Promotion) -> Self {
+        Self {
+            compiler,
+            promotion,
+            profiles: Mutex::default(),
+            promoted: CodeCache::new(DEFAULT_CODE_CACHE_SIZE),
+            code_hashes: Mutex::default(),
+        }
+    }
+
+    /// Whether the code with hash `code_hash` was compiled.
+    pub fn is_compiled(&self, code_hash: &Hash32) -> bool {
+        self.promoted.get(code_hash).is_some_and(|tier| matches!(*tier, Tier::Compiled(_)))
+    }
+
+    /// Run the code of `evm` until it stops or fails, compiled if it was
+    /// promoted and the compiler took it.
+    pub fn run(&self, evm: &mut Evm) -> Result<(), VmError> {
+        let code_hash = self.code_hash(evm);
+        match self.promoted.get(&code_hash).as_deref() {
+            Some(Tier::Compiled(compiled)) => {
+                metrics::JIT_COMPILED_FRAMES.inc();
+                return compiled.run(evm);
+            }
+            Some(Tier::Interpreted) => return interpret(evm),
+            None => (),
+        }
+
+        let gas = evm.gas_left;
+        let result = interpret(evm);
+        let hot = {
+            let mut profiles = self.profiles.lock().unwrap();
+            if profiles.len() >= MAX_PROFILES && !profiles.contains_key(&code_hash) {
+                profiles.pop_first();
+            }
+            let profile = profiles.entry(code_hash.clone()).or_default();
+            profile.calls += 1;
+            profile.gas += gas - evm.gas_left;
+            let hot = profile.calls >= self.promotion.calls || profile.gas >= self.promotion.gas;
+            if hot {
+                profiles.remove(&code_hash);
+            }
+            hot
+        };
+        if hot {
+            let rules = evm.env.rules;
+            self.promoted.get_or_insert_with(&evm.code, |code| {
+                let _span = log::span(Level::Debug, "compile", format_args!("code_hash={code_hash} len={}", code.len()));
+                match metrics::JIT_COMPILATION.time(|| self.compiler.compile(code, rules)) {
+                    Some(compiled) => {
+                        debug!("compiled");
+                        metrics::JIT_COMPILED.inc();
+                        Tier::Compiled(compiled)
+                    }
+                    None => {
+                        debug!("declined, interpreting");
+                        metrics::JIT_DECLINED.inc();
+                        Tier::Interpreted
+                    }
+                }
+            });
+        }
+        result
+    }
+
+    /// The hash of the code of `evm`, hashed only if its address ran other
+    /// code last, or none. Code is compared rather than trusted by address,
+    /// since the code at an address changes with contract creation and
+    /// EIP-7702 delegations.
+    fn code_hash(&self, evm: &Evm) -> Hash32 {
+        let Some(address) = &evm.message.code_address else {
+            return keccak256(&evm.code);
+        };
+        if let Some((code, hash)) = self.code_hashes.lock().unwrap().get(address) {
+            if *code == evm.code {
+                return hash.clone();
+            }
+        }
+        let hash = keccak256(&evm.code);
+        let mut code_hashes = self.code_hashes.lock().unwrap();
+        if code_hashes.len() >= MAX_CODE_HASHES && !code_hashes.contains_key(address) {
+            code_hashes.pop_first();
+        }
+        code_hashes.insert(address.clone(), (evm.code.clone(), hash.clone()));
+        hash
+    }
+
+    /// The number of code hashes being profiled.
+    pub fn profiled(&self) -> usize {
+        self.profiles.lock().unwrap().len()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{
+        collections::BTreeSet,
+        sync::{
+            atomic::{AtomicU64, Ordering},
+            Arc,
+        },
+    };
+
+    use crate::ethereum::{
+        cancun::{
+            fork_types::{Account, Address},
+            state::{get_storage, set_account, State, TransientStorage},
+            utils::prepare_message,
+            vm::{exceptions::VmError, interpreter::{interpret, process_message_call}, Environment, Evm},
+        },
+        crypto::hash::keccak256,
+        ethereum_types::{
+            bytes::{Bytes, Bytes32},
+            numeric::{Uint, U256},
+        },
+        forks::ExecutionRules,
+    };
+    use crate::metrics;
+
+    use super::{Compiled, Compiler, HybridExecutor, Promotion};
+
+    /// "Compiles" code without `SSTORE` to the interpreter, counting the
+    /// runs of compiled code and the code it was asked to compile.
+    struct Counting {
+        compiles: Arc<AtomicU64>,
+        runs: Arc<AtomicU64>,
+    }
+
+    struct CountingCode(Arc<AtomicU64>);
+
+    impl Compiler for Counting {
+        fn compile(&self, code: &[u8], _: ExecutionRules) -> Option<Box<dyn Compiled>> {
+            self.compiles.fetch_add(1, Ordering::Relaxed);
+            // Checked byte by byte, which is enough for the code of the tests.
+            (!code.contains(&0x55)).then(|| Box::new(CountingCode(self.runs.clone())) as Box<dyn Compiled>)
+        }
+    }
+
+    impl Compiled for CountingCode {
+        fn run(&self, evm: &mut Evm) -> Result<(), VmError> {
+            self.0.fetch_add(1, Ordering::Relaxed);
+            interpret(evm)
+        }
+    }
+
+    /// Call `contract` in `state` with `executor`, returning the gas left
+    /// and the output.
+    fn call(state: &mut State, executor: &HybridExecutor, contract: &Address) -> (Uint, Bytes) {
+        let caller = Address::from_be_bytes([0xca; 20]);
+        let mut env = Environment {
+            caller: caller.clone(),
+            origin: caller.clone(),
+            gas_limit: 100_000,
+            executor: Some(executor),
+            ..Environment::new(state, ExecutionRules::CANCUN)
+        };
+        let message = prepare_message(
+            caller.clone(),
+            Some(contract.clone()),
+            U256::ZERO,
+            Bytes::default(),
+            50_000,
+            &env,
+            None,
+            true,
+            false,
+            BTreeSet::new(),
+            BTreeSet::new(),
+        )
+        .unwrap();
+        let output = process_message_call(message, &mut env).unwrap();
+        assert_eq!(output.error, None);
+        (output.gas_left, output.return_data)
+    }
+
+    #[test]
+    fn hot_contracts_are_promoted() {
+        let (compiles, runs) = (Arc::new(AtomicU64::new(0)), Arc::new(AtomicU64::new(0)));
+        let compiler = Counting { compiles: compiles.clone(), runs: runs.clone() };
+        let executor = HybridExecutor::new(Box::new(compiler), Promotion { calls: 2, gas: Uint::MAX });
+
+        // CALLVALUE NUMBER ADD MSIZE MSTORE MSIZE CALLVALUE RETURN, which
+        // returns 32 zero bytes.
+        let returns = Address::from_be_bytes([0xc0; 20]);
+        let returns_code = vec![0x34, 0x43, 0x01, 0x59, 0x52, 0x59, 0x34, 0xf3];
+        // ADDRESS SLOAD PUSH1 1 ADD ADDRESS SSTORE, which counts its calls in
+        // the slot of its address.
+        let counts = Address::from_be_bytes([0xc1; 20]);
+        let counts_code = vec![0x30, 0x54, 0x60, 0x01, 0x01, 0x30, 0x55];
+        let mut state = State::default();
+        for (address, code) in [(&returns, &returns_code), (&counts, &counts_code)] {
+            set_account(&mut state, address, Some(Account { code: Bytes(code.clone()), ..Default::default() }));
+        }
+
+        let before = metrics::metrics();
+        let first = call(&mut state, &executor, &returns);
+        assert_eq!(first.1 .0, [0; 32]);
+        for _ in 1..5 {
+            assert_eq!(call(&mut state, &executor, &returns), first);
+        }
+        // The first two calls were interpreted, and the other three compiled.
+        assert!(executor.is_compiled(&keccak256(&returns_code)));
+        assert_eq!((compiles.load(Ordering::Relaxed), runs.load(Ordering::Relaxed)), (1, 3));
+
+        // Code the compiler declines stays in the interpreter.
+        for _ in 0..5 {
+            call(&mut state, &executor, &counts);
+        }
+        assert!(!executor.is_compiled(&keccak256(&counts_code)));
+        assert_eq!((compiles.load(Ordering::Relaxed), runs.load(Ordering::Relaxed)), (2, 3));
+        // Other tests compile at the same time.
+        let after = metrics::metrics();
+        assert!(after.jit_compilation.count >= before.jit_compilation.count + 2);
+        assert!(after.jit_compiled > before.jit_compiled && after.jit_declined > before.jit_declined);
+        assert!(after.jit_compiled_frames >= before.jit_compiled_frames + 3);
+        let slot = Bytes32(U256::from_be_bytes([&[0; 12][..], &[0xc1; 20]].concat().try_into().unwrap()).to_be_bytes());
+        assert_eq!(get_storage(&state, &counts, &slot), U256::from(5_u32));
+    }
+
+    #[test]
+    fn gas_promotes() {
+        let (compiles, runs) = (Arc::new(AtomicU64::new(0)), Arc::new(AtomicU64::new(0)));
+        let compiler = Counting { compiles: compiles.clone(), runs: runs.clone() };
+        let executor = HybridExecutor::new(Box::new(compiler), Promotion { calls: u64::MAX, gas: 25 });
+
+        let contract = Address::from_be_bytes([0xc0; 20]);
+        // CALLVALUE CALLVALUE MSTORE, ten gas with the memory expansion.
+        let code = vec![0x34, 0x34, 0x52];
+        let mut state = State::default();
+        set_account(&mut state, &contract, Some(Account { code: Bytes(code.clone()), ..Default::default() }));
+        for _ in 0..4 {
+            call(&mut state, &executor, &contract);
+        }
+        // Promoted once the third call brought it to 30 gas.
+        assert_eq!((compiles.load(Ordering::Relaxed), runs.load(Ordering::Relaxed)), (1, 1));
+    }
+
+    #[test]
+    fn code_changes_at_an_address() {
+        let (compiles, runs) = (Arc::new(AtomicU64::new(0)), Arc::new(AtomicU64::new(0)));
+        let compiler = Counting { compiles: compiles.clone(), runs: runs.clone() };
+        let executor = HybridExecutor::new(Box::new(compiler), Promotion { calls: 1, gas: Uint::MAX });
+
+        // PUSH1 n PUSH1 0 MSTORE8 PUSH1 1 PUSH1 0 RETURN, which returns n.
+        let returning = |n| vec![0x60, n, 0x60, 0x00, 0x53, 0x60, 0x01, 0x60, 0x00, 0xf3];
+        let contract = Address::from_be_bytes([0xc0; 20]);
+        let mut state = State::default();
+        set_account(&mut state, &contract, Some(Account { code: Bytes(returning(1)), ..Default::default() }));
+        assert_eq!(call(&mut state, &executor, &contract).1 .0, [1]);
+        assert_eq!(call(&mut state, &executor, &contract).1 .0, [1]);
+
+        // The address remembers the hash of the first code, which is not
+        // that of the code now there.
+        set_account(&mut state, &contract, Some(Account { code: Bytes(returning(2)), ..Default::default() }));
+        assert_eq!(call(&mut state, &executor, &contract).1 .0, [2]);
+        assert!(executor.is_compiled(&keccak256(&returning(1))));
+        assert!(executor.is_compiled(&keccak256(&returning(2))));
+        assert_eq!(executor.profiled(), 0);
+    }
+}
@@ -0,0 +1,258 @@
+//! EVM Instruction Encoding (Opcodes)
+//! ^^^^^^^^^^^^^^^^^^^^^^^^^^^^^^^^^^
+//! 
+//! .. contents:: Table of Contents
+//!     :backlinks: none
+//!     :local:
+//! 
+//! Introduction
+//! ------------
+//! 
+//! Machine readable representations of EVM instructions, and a mapping to
+//! their implementations.
+
+use crate::ethereum::{ethereum_types::numeric::{Uint, U256}, utils::numeric::ceil32};
+
+use super::{exceptions::VmError, Evm};
+
+pub mod arithmetic;
+pub mod bitwise;
+pub mod block;
+pub mod comparison;
+pub mod control_flow;
+pub mod environment;
+pub mod keccak;
+pub mod log;
+pub mod memory;
+pub mod stack;
+pub mod storage;
+pub mod system;
+
+/// Number of 32 byte words needed to hold `size` bytes.
+///
+/// Sizes which do not fit in 64 bits can never be paid for.
+pub(crate) fn words(size: U256) -> Result<Uint, VmError> {
+    match size.to_uint() {
+        Ok(size) if size <= u64::MAX as Uint => Ok(ceil32(size) / 32),
+        _ => Err(VmError::OutOfGasError),
+    }
+}
+
+macro_rules! ops {
+    ($($name: ident = $code: literal => $implementation: expr,)*) => {
+        /// """
+        /// Enum for EVM Opcodes
+        /// """
+        #[allow(non_camel_case_types)]
+        #[derive(Debug, Clone, Copy, PartialEq, Eq)]
+        #[repr(u8)]
+        pub enum Ops {
+            $($name = $code,)*
+        }
+
+        impl Ops {
+            /// The opcode for `op`, or `None` if it is not defined.
+            pub fn from_u8(op: u8) -> Option<Ops> {
+                match op {
+                    $($code => Some(Ops::$name),)*
+                    _ => None,
+                }
+            }
+
+            pub fn name(self) -> &'static str {
+                match self {
+                    $(Ops::$name => stringify!($name),)*
+                }
+            }
+        }
+
+        /// Execute the instruction `op`.
+        pub fn op_implementation(op: Ops, evm: &mut Evm) -> Result<(), VmError> {
+            match op {
+                $(Ops::$name => ($implementation)(evm),)*
+            }
+        }
+    };
+}
+
+ops! {
+    // Control Flow Ops
+    STOP = 0x00 => control_flow::stop,
+
+    // Arithmetic Ops
+    ADD = 0x01 => arithmetic::add,
+    MUL = 0x02 => arithmetic::mul,
+    SUB = 0x03 => arithmetic::sub,
+    DIV = 0x04 => arithmetic::div,
+    SDIV = 0x05 => arithmetic::sdiv,
+    MOD = 0x06 => arithmetic::mod_,
+    SMOD = 0x07 => arithmetic::smod,
+    ADDMOD = 0x08 => arithmetic::addmod,
+    MULMOD = 0x09 => arithmetic::mulmod,
+    EXP = 0x0A => arithmetic::exp,
+    SIGNEXTEND = 0x0B => arithmetic::signextend,
+
+    // Comparison Ops
+    LT = 0x10 => comparison::less_than,
+    GT = 0x11 => comparison::greater_than,
+    SLT = 0x12 => comparison::signed_less_than,
+    SGT = 0x13 => comparison::signed_greater_than,
+    EQ = 0x14 => comparison::equal,
+    ISZERO = 0x15 => comparison::is_zero,
+
+    // Bitwise Ops
+    AND = 0x16 => bitwise::bitwise_and,
+    OR = 0x17 => bitwise::bitwise_or,
+    XOR = 0x18 => bitwise::bitwise_xor,
+    NOT = 0x19 => bitwise::bitwise_not,
+    BYTE = 0x1A => bitwise::get_byte,
+    SHL = 0x1B => bitwise::bitwise_shl,
+    SHR = 0x1C => bitwise::bitwise_shr,
+    SAR = 0x1D => bitwise::bitwise_sar,
+
+    // Keccak Op
+    KECCAK = 0x20 => keccak::keccak,
+
+    // Environmental Ops
+    ADDRESS = 0x30 => environment::address,
+    BALANCE = 0x31 => environment::balance,
+    ORIGIN = 0x32 => environment::origin,
+    CALLER = 0x33 => environment::caller,
+    CALLVALUE = 0x34 => environment::callvalue,
+    CALLDATALOAD = 0x35 => environment::calldataload,
+    CALLDATASIZE = 0x36 => environment::calldatasize,
+    CALLDATACOPY = 0x37 => environment::calldatacopy,
+    CODESIZE = 0x38 => environment::codesize,
+    CODECOPY = 0x39 => environment::codecopy,
+    GASPRICE = 0x3A => environment::gasprice,
+    EXTCODESIZE = 0x3B => environment::extcodesize,
+    EXTCODECOPY = 0x3C => environment::extcodecopy,
+    RETURNDATASIZE = 0x3D => environment::returndatasize,
+    RETURNDATACOPY = 0x3E => environment::returndatacopy,
+    EXTCODEHASH = 0x3F => environment::extcodehash,
+
+    // Block Ops
+    BLOCKHASH = 0x40 => block::block_hash,
+    COINBASE = 0x41 => block::coinbase,
+    TIMESTAMP = 0x42 => block::timestamp,
+    NUMBER = 0x43 => block::number,
+    PREVRANDAO = 0x44 => block::prev_randao,
+    GASLIMIT = 0x45 => block::gas_limit,
+    CHAINID = 0x46 => block::chain_id,
+    SELFBALANCE = 0x47 => environment::self_balance,
+    BASEFEE = 0x48 => environment::base_fee,
+    BLOBHASH = 0x49 => environment::blob_hash,
+    BLOBBASEFEE = 0x4A => environment::blob_base_fee,
+
+    // Control Flow Ops
+    JUMP = 0x56 => control_flow::jump,
+    JUMPI = 0x57 => control_flow::jumpi,
+    PC = 0x58 => control_flow::pc,
+    GAS = 0x5A => control_flow::gas_left,
+    JUMPDEST = 0x5B => control_flow::jumpdest,
+
+    // Storage Ops
+    SLOAD = 0x54 => storage::sload,
+    SSTORE = 0x55 => storage::sstore,
+    TLOAD = 0x5C => storage::tload,
+    TSTORE = 0x5D => storage::tstore,
+
+    // Pop Operation
+    POP = 0x50 => stack::pop,
+
+    // Push Operations
+    PUSH0 = 0x5F => |evm| stack::push_n(evm, 0),
+    PUSH1 = 0x60 => |evm| stack::push_n(evm, 1),
+    PUSH2 = 0x61 => |evm| stack::push_n(evm, 2),
+    PUSH3 = 0x62 => |evm| stack::push_n(evm, 3),
+    PUSH4 = 0x63 => |evm| stack::push_n(evm, 4),
+    PUSH5 = 0x64 => |evm| stack::push_n(evm, 5),
+    PUSH6 = 0x65 => |evm| stack::push_n(evm, 6),
+    PUSH7 = 0x66 => |evm| stack::push_n(evm, 7),
+    PUSH8 = 0x67 => |evm| stack::push_n(evm, 8),
+    PUSH9 = 0x68 => |evm| stack::push_n(evm, 9),
+    PUSH10 = 0x69 => |evm| stack::push_n(evm, 10),
+    PUSH11 = 0x6A => |evm| stack::push_n(evm, 11),
+    PUSH12 = 0x6B => |evm| stack::push_n(evm, 12),
+    PUSH13 = 0x6C => |evm| stack::push_n(evm, 13),
+    PUSH14 = 0x6D => |evm| stack::push_n(evm, 14),
+    PUSH15 = 0x6E => |evm| stack::push_n(evm, 15),
+    PUSH16 = 0x6F => |evm| stack::push_n(evm, 16),
+    PUSH17 = 0x70 => |evm| stack::push_n(evm, 17),
+    PUSH18 = 0x71 => |evm| stack::push_n(evm, 18),
+    PUSH19 = 0x72 => |evm| stack::push_n(evm, 19),
+    PUSH20 = 0x73 => |evm| stack::push_n(evm, 20),
+    PUSH21 = 0x74 => |evm| stack::push_n(evm, 21),
+    PUSH22 = 0x75 => |evm| stack::push_n(evm, 22),
+    PUSH23 = 0x76 => |evm| stack::push_n(evm, 23),
+    PUSH24 = 0x77 => |evm| stack::push_n(evm, 24),
+    PUSH25 = 0x78 => |evm| stack::push_n(evm, 25),
+    PUSH26 = 0x79 => |evm| stack::push_n(evm, 26),
+    PUSH27 = 0x7A => |evm| stack::push_n(evm, 27),
+    PUSH28 = 0x7B => |evm| stack::push_n(evm, 28),
+    PUSH29 = 0x7C => |evm| stack::push_n(evm, 29),
+    PUSH30 = 0x7D => |evm| stack::push_n(evm, 30),
+    PUSH31 = 0x7E => |evm| stack::push_n(evm, 31),
+    PUSH32 = 0x7F => |evm| stack::push_n(evm, 32),
+
+    // Dup operations
+    DUP1 = 0x80 => |evm| stack::dup_n(evm, 0),
+    DUP2 = 0x81 => |evm| stack::dup_n(evm, 1),
+    DUP3 = 0x82 => |evm| stack::dup_n(evm, 2),
+    DUP4 = 0x83 => |evm| stack::dup_n(evm, 3),
+    DUP5 = 0x84 => |evm| stack::dup_n(evm, 4),
+    DUP6 = 0x85 => |evm| stack::dup_n(evm, 5),
+    DUP7 = 0x86 => |evm| stack::dup_n(evm, 6),
+    DUP8 = 0x87 => |evm| stack::dup_n(evm, 7),
+    DUP9 = 0x88 => |evm| stack::dup_n(evm, 8),
+    DUP10 = 0x89 => |evm| stack::dup_n(evm, 9),
+    DUP11 = 0x8A => |evm| stack::dup_n(evm, 10),
+    DUP12 = 0x8B => |evm| stack::dup_n(evm, 11),
+    DUP13 = 0x8C => |evm| stack::dup_n(evm, 12),
+    DUP14 = 0x8D => |evm| stack::dup_n(evm, 13),
+    DUP15 = 0x8E => |evm| stack::dup_n(evm, 14),
+    DUP16 = 0x8F => |evm| stack::dup_n(evm, 15),
+
+    // Swap operations
+    SWAP1 = 0x90 => |evm| stack::swap_n(evm, 1),
+    SWAP2 = 0x91 => |evm| stack::swap_n(evm, 2),
+    SWAP3 = 0x92 => |evm| stack::swap_n(evm, 3),
+    SWAP4 = 0x93 => |evm| stack::swap_n(evm, 4),
+    SWAP5 = 0x94 => |evm| stack::swap_n(evm, 5),
+    SWAP6 = 0x95 => |evm| stack::swap_n(evm, 6),
+    SWAP7 = 0x96 => |evm| stack::swap_n(evm, 7),
+    SWAP8 = 0x97 => |evm| stack::swap_n(evm, 8),
+    SWAP9 = 0x98 => |evm| stack::swap_n(evm, 9),
+    SWAP10 = 0x99 => |evm| stack::swap_n(evm, 10),
+    SWAP11 = 0x9A => |evm| stack::swap_n(evm, 11),
+    SWAP12 = 0x9B => |evm| stack::swap_n(evm, 12),
+    SWAP13 = 0x9C => |evm| stack::swap_n(evm, 13),
+    SWAP14 = 0x9D => |evm| stack::swap_n(evm, 14),
+    SWAP15 = 0x9E => |evm| stack::swap_n(evm, 15),
+    SWAP16 = 0x9F => |evm| stack::swap_n(evm, 16),
+
+    // Memory Operations
+    MLOAD = 0x51 => memory::mload,
+    MSTORE = 0x52 => memory::mstore,
+    MSTORE8 = 0x53 => memory::mstore8,
+    MSIZE = 0x59 => memory::msize,
+    MCOPY = 0x5E => memory::mcopy,
+
+    // Log Operations
+    LOG0 = 0xA0 => |evm| log::log_n(evm, 0),
+    LOG1 = 0xA1 => |evm| log::log_n(evm, 1),
+    LOG2 = 0xA2 => |evm| log::log_n(evm, 2),
+    LOG3 = 0xA3 => |evm| log::log_n(evm, 3),
+    LOG4 = 0xA4 => |evm| log::log_n(evm, 4),
+
+    // System Operations
+    CREATE = 0xF0 => system::create,
+    CALL = 0xF1 => system::call,
+    CALLCODE = 0xF2 => system::callcode,
+    RETURN = 0xF3 => system::return_,
+    DELEGATECALL = 0xF4 => system::delegatecall,
+    CREATE2 = 0xF5 => system::create2,
+    STATICCALL = 0xFA => system::staticcall,
+    REVERT = 0xFD => system::revert,
+    SELFDESTRUCT = 0xFF => system::selfdestruct,
+}
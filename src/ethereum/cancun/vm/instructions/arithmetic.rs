@@ -0,0 +1,328 @@
+//! Ethereum Virtual Machine (EVM) Arithmetic Instructions
+//! ^^^^^^^^^^^^^^^^^^^^^^^^^^^^^^^^^^^^^^^^^^^^^^^^^^^^^^
+//! 
+//! .. contents:: Table of Contents
+//!     :backlinks: none
+//!     :local:
+//! 
+//! Introduction
+//! ------------
+//! 
+//! Implementations of the EVM Arithmetic instructions.
+
+use crate::ethereum::{cancun::vm::{
+    exceptions::VmError,
+    gas::{
+        charge_gas, GAS_EXPONENTIATION, GAS_EXPONENTIATION_PER_BYTE, GAS_EXPONENTIATION_PER_BYTE_FRONTIER, GAS_LOW, GAS_MID, GAS_VERY_LOW},
+    stack::{pop, push},
+    Evm,
+}, ethereum_types::numeric::Uint};
+
+/// """
+/// Adds the top two elements of the stack together, and pushes the result back
+/// on the stack.
+/// 
+/// Parameters
+/// ----------
+/// evm :
+///     The current EVM frame.
+/// """
+pub fn add(evm: &mut Evm) -> Result<(), VmError> {
+    // STACK
+    let x = pop(&mut evm.stack)?;
+    let y = pop(&mut evm.stack)?;
+
+    // GAS
+    charge_gas(evm, GAS_VERY_LOW)?;
+
+    // OPERATION
+    let result = x + y;
+
+    push(&mut evm.stack, result)?;
+
+    // PROGRAM COUNTER
+    evm.pc += 1;
+    Ok(())
+}
+
+/// """
+/// Subtracts the top two elements of the stack, and pushes the result back
+/// on the stack.
+/// 
+/// Parameters
+/// ----------
+/// evm :
+///     The current EVM frame.
+/// """
+pub fn sub(evm: &mut Evm) -> Result<(), VmError> {
+    // STACK
+    let x = pop(&mut evm.stack)?;
+    let y = pop(&mut evm.stack)?;
+
+    // GAS
+    charge_gas(evm, GAS_VERY_LOW)?;
+
+    // OPERATION
+    let result = x - y;
+
+    push(&mut evm.stack, result)?;
+
+    // PROGRAM COUNTER
+    evm.pc += 1;
+    Ok(())
+}
+
+/// """
+/// Multiply the top two elements of the stack, and pushes the result back
+/// on the stack.
+/// 
+/// Parameters
+/// ----------
+/// evm :
+///     The current EVM frame.
+/// """
+pub fn mul(evm: &mut Evm) -> Result<(), VmError> {
+    // STACK
+    let x = pop(&mut evm.stack)?;
+    let y = pop(&mut evm.stack)?;
+
+    // GAS
+    charge_gas(evm, GAS_LOW)?;
+
+    // OPERATION
+    let result = x * y;
+
+    push(&mut evm.stack, result)?;
+
+    // PROGRAM COUNTER
+    evm.pc += 1;
+    Ok(())
+}
+
+/// """
+/// Integer division of the top two elements of the stack. Pushes the result
+/// back on the stack.
+/// 
+/// Parameters
+/// ----------
+/// evm :
+///     The current EVM frame.
+/// """
+pub fn div(evm: &mut Evm) -> Result<(), VmError> {
+    // STACK
+    let dividend = pop(&mut evm.stack)?;
+    let divisor = pop(&mut evm.stack)?;
+
+    // GAS
+    charge_gas(evm, GAS_LOW)?;
+
+    // OPERATION
+    let (quotient, _) = dividend.div_rem(divisor);
+
+    push(&mut evm.stack, quotient)?;
+
+    // PROGRAM COUNTER
+    evm.pc += 1;
+    Ok(())
+}
+
+/// """
+/// Signed integer division of the top two elements of the stack. Pushes the
+/// result back on the stack.
+/// 
+/// Parameters
+/// ----------
+/// evm :
+///     The current EVM frame.
+/// """
+pub fn sdiv(evm: &mut Evm) -> Result<(), VmError> {
+    // STACK
+    let dividend = pop(&mut evm.stack)?;
+    let divisor = pop(&mut evm.stack)?;
+
+    // GAS
+    charge_gas(evm, GAS_LOW)?;
+
+    // OPERATION
+    let quotient = dividend.signed_div(divisor);
+
+    push(&mut evm.stack, quotient)?;
+
+    // PROGRAM COUNTER
+    evm.pc += 1;
+    Ok(())
+}
+
+/// """
+/// Modulo remainder of the top two elements of the stack. Pushes the result
+/// back on the stack.
+/// 
+/// Parameters
+/// ----------
+/// evm :
+///     The current EVM frame.
+/// """
+pub fn mod_(evm: &mut Evm) -> Result<(), VmError> {
+    // STACK
+    let x = pop(&mut evm.stack)?;
+    let y = pop(&mut evm.stack)?;
+
+    // GAS
+    charge_gas(evm, GAS_LOW)?;
+
+    // OPERATION
+    let (_, remainder) = x.div_rem(y);
+
+    push(&mut evm.stack, remainder)?;
+
+    // PROGRAM COUNTER
+    evm.pc += 1;
+    Ok(())
+}
+
+/// """
+/// Signed modulo remainder of the top two elements of the stack. Pushes the
+/// result back on the stack.
+/// 
+/// Parameters
+/// ----------
+/// evm :
+///     The current EVM frame.
+/// """
+pub fn smod(evm: &mut Evm) -> Result<(), VmError> {
+    // STACK
+    let x = pop(&mut evm.stack)?;
+    let y = pop(&mut evm.stack)?;
+
+    // GAS
+    charge_gas(evm, GAS_LOW)?;
+
+    // OPERATION
+    let remainder = x.signed_rem(y);
+
+    push(&mut evm.stack, remainder)?;
+
+    // PROGRAM COUNTER
+    evm.pc += 1;
+    Ok(())
+}
+
+/// """
+/// Modulo addition of the top 2 elements with the 3rd element. Pushes the
+/// result back on the stack.
+/// 
+/// Parameters
+/// ----------
+/// evm :
+///     The current EVM frame.
+/// """
+pub fn addmod(evm: &mut Evm) -> Result<(), VmError> {
+    // STACK
+    let x = pop(&mut evm.stack)?;
+    let y = pop(&mut evm.stack)?;
+    let z = pop(&mut evm.stack)?;
+
+    // GAS
+    charge_gas(evm, GAS_MID)?;
+
+    // OPERATION
+    let result = x.add_mod(y, z);
+
+    push(&mut evm.stack, result)?;
+
+    // PROGRAM COUNTER
+    evm.pc += 1;
+    Ok(())
+}
+
+/// """
+/// Modulo multiplication of the top 2 elements with the 3rd element. Pushes
+/// the result back on the stack.
+/// 
+/// Parameters
+/// ----------
+/// evm :
+///     The current EVM frame.
+/// """
+pub fn mulmod(evm: &mut Evm) -> Result<(), VmError> {
+    // STACK
+    let x = pop(&mut evm.stack)?;
+    let y = pop(&mut evm.stack)?;
+    let z = pop(&mut evm.stack)?;
+
+    // GAS
+    charge_gas(evm, GAS_MID)?;
+
+    // OPERATION
+    let result = x.mul_mod(y, z);
+
+    push(&mut evm.stack, result)?;
+
+    // PROGRAM COUNTER
+    evm.pc += 1;
+    Ok(())
+}
+
+/// """
+/// Exponential operation of the top 2 elements. Pushes the result back on
+/// the stack.
+/// 
+/// Parameters
+/// ----------
+/// evm :
+///     The current EVM frame.
+/// """
+pub fn exp(evm: &mut Evm) -> Result<(), VmError> {
+    // STACK
+    let base = pop(&mut evm.stack)?;
+    let exponent = pop(&mut evm.stack)?;
+
+    // GAS
+    // This is equivalent to 1 + floor(log(y, 256)). But in python the log
+    // function is inaccurate leading to wrong results.
+    let exponent_bits = exponent.bits() as Uint;
+    let exponent_bytes = exponent_bits.div_ceil(8);
+    let gas_per_byte = if evm.env.rules.spurious_dragon {
+        GAS_EXPONENTIATION_PER_BYTE
+    } else {
+        GAS_EXPONENTIATION_PER_BYTE_FRONTIER
+    };
+    charge_gas(evm, GAS_EXPONENTIATION + gas_per_byte * exponent_bytes)?;
+
+    // OPERATION
+    let result = base.pow(exponent);
+
+    push(&mut evm.stack, result)?;
+
+    // PROGRAM COUNTER
+    evm.pc += 1;
+    Ok(())
+}
+
+/// """
+/// Sign extend operation. In other words, extend a signed number which
+/// fits in N bytes to 32 bytes.
+/// 
+/// Parameters
+/// ----------
+/// evm :
+///     The current EVM frame.
+/// """
+pub fn signextend(evm: &mut Evm) -> Result<(), VmError> {
+    // STACK
+    let byte_num = pop(&mut evm.stack)?;
+    let value = pop(&mut evm.stack)?;
+
+    // GAS
+    charge_gas(evm, GAS_LOW)?;
+
+    // OPERATION
+    let result = value.sign_extend(byte_num);
+
+    push(&mut evm.stack, result)?;
+
+    // PROGRAM COUNTER
+    evm.pc += 1;
+    Ok(())
+}
+
@@ -0,0 +1,239 @@
+//! Ethereum Virtual Machine (EVM) Bitwise Instructions
+//! ^^^^^^^^^^^^^^^^^^^^^^^^^^^^^^^^^^^^^^^^^^^^^^^^^^^
+//! 
+//! .. contents:: Table of Contents
+//!     :backlinks: none
+//!     :local:
+//! 
+//! Introduction
+//! ------------
+//! 
+//! Implementations of the EVM Bitwise instructions.
+
+use crate::ethereum::{cancun::vm::{
+    exceptions::VmError,
+    gas::{charge_gas, GAS_VERY_LOW},
+    stack::{pop, push},
+    Evm,
+}, ethereum_types::numeric::U256};
+
+/// A shift amount, saturating at 256.
+fn shift_amount(shift: U256) -> u32 {
+    if shift < U256::from(256_u32) { shift.low_u64() as u32 } else { 256 }
+}
+
+/// """
+/// Bitwise AND operation of the top 2 elements of the stack. Pushes the
+/// result back on the stack.
+/// 
+/// Parameters
+/// ----------
+/// evm :
+///     The current EVM frame.
+/// """
+pub fn bitwise_and(evm: &mut Evm) -> Result<(), VmError> {
+    // STACK
+    let x = pop(&mut evm.stack)?;
+    let y = pop(&mut evm.stack)?;
+
+    // GAS
+    charge_gas(evm, GAS_VERY_LOW)?;
+
+    // OPERATION
+    let result = x & y;
+
+    push(&mut evm.stack, result)?;
+
+    // PROGRAM COUNTER
+    evm.pc += 1;
+    Ok(())
+}
+
+/// """
+/// Bitwise OR operation of the top 2 elements of the stack. Pushes the
+/// result back on the stack.
+/// 
+/// Parameters
+/// ----------
+/// evm :
+///     The current EVM frame.
+/// """
+pub fn bitwise_or(evm: &mut Evm) -> Result<(), VmError> {
+    // STACK
+    let x = pop(&mut evm.stack)?;
+    let y = pop(&mut evm.stack)?;
+
+    // GAS
+    charge_gas(evm, GAS_VERY_LOW)?;
+
+    // OPERATION
+    let result = x | y;
+
+    push(&mut evm.stack, result)?;
+
+    // PROGRAM COUNTER
+    evm.pc += 1;
+    Ok(())
+}
+
+/// """
+/// Bitwise XOR operation of the top 2 elements of the stack. Pushes the
+/// result back on the stack.
+/// 
+/// Parameters
+/// ----------
+/// evm :
+///     The current EVM frame.
+/// """
+pub fn bitwise_xor(evm: &mut Evm) -> Result<(), VmError> {
+    // STACK
+    let x = pop(&mut evm.stack)?;
+    let y = pop(&mut evm.stack)?;
+
+    // GAS
+    charge_gas(evm, GAS_VERY_LOW)?;
+
+    // OPERATION
+    let result = x ^ y;
+
+    push(&mut evm.stack, result)?;
+
+    // PROGRAM COUNTER
+    evm.pc += 1;
+    Ok(())
+}
+
+/// """
+/// Bitwise NOT operation of the top element of the stack. Pushes the
+/// result back on the stack.
+/// 
+/// Parameters
+/// ----------
+/// evm :
+///     The current EVM frame.
+/// """
+pub fn bitwise_not(evm: &mut Evm) -> Result<(), VmError> {
+    // STACK
+    let x = pop(&mut evm.stack)?;
+
+    // GAS
+    charge_gas(evm, GAS_VERY_LOW)?;
+
+    // OPERATION
+    let result = !x;
+
+    push(&mut evm.stack, result)?;
+
+    // PROGRAM COUNTER
+    evm.pc += 1;
+    Ok(())
+}
+
+/// """
+/// For a word (defined by next top element of the stack), retrieve the
+/// Nth byte (0-indexed and defined by top element of stack) from the
+/// left (most significant) to right (least significant).
+/// 
+/// Parameters
+/// ----------
+/// evm :
+///     The current EVM frame.
+/// """
+pub fn get_byte(evm: &mut Evm) -> Result<(), VmError> {
+    // STACK
+    let byte_index = pop(&mut evm.stack)?;
+    let word = pop(&mut evm.stack)?;
+
+    // GAS
+    charge_gas(evm, GAS_VERY_LOW)?;
+
+    // OPERATION
+    let result = word.byte(byte_index);
+
+    push(&mut evm.stack, result)?;
+
+    // PROGRAM COUNTER
+    evm.pc += 1;
+    Ok(())
+}
+
+/// """
+/// Logical shift left (SHL) operation of the top 2 elements of the stack.
+/// Pushes the result back on the stack.
+/// 
+/// Parameters
+/// ----------
+/// evm :
+///     The current EVM frame.
+/// """
+pub fn bitwise_shl(evm: &mut Evm) -> Result<(), VmError> {
+    // STACK
+    let shift = pop(&mut evm.stack)?;
+    let value = pop(&mut evm.stack)?;
+
+    // GAS
+    charge_gas(evm, GAS_VERY_LOW)?;
+
+    // OPERATION
+    let result = value.shl(shift_amount(shift));
+
+    push(&mut evm.stack, result)?;
+
+    // PROGRAM COUNTER
+    evm.pc += 1;
+    Ok(())
+}
+
+/// """
+/// Logical shift right (SHR) operation of the top 2 elements of the stack.
+/// Pushes the result back on the stack.
+/// 
+/// Parameters
+/// ----------
+/// evm :
+///     The current EVM frame.
+/// """
+pub fn bitwise_shr(evm: &mut Evm) -> Result<(), VmError> {
+    // STACK
+    let shift = pop(&mut evm.stack)?;
+    let value = pop(&mut evm.stack)?;
+
+    // GAS
+    charge_gas(evm, GAS_VERY_LOW)?;
+
+    // OPERATION
+    let result = value.shr(shift_amount(shift));
+
+    push(&mut evm.stack, result)?;
+
+    // PROGRAM COUNTER
+    evm.pc += 1;
+    Ok(())
+}
+
+/// """
+/// Arithmetic shift right (SAR) operation of the top 2 elements of the stack.
+/// Pushes the result back on the stack.
+/// 
+/// Parameters
+/// ----------
+/// evm :
+///     The current EVM frame.
+/// """
+pub fn bitwise_sar(evm: &mut Evm) -> Result<(), VmError> {
+    // STACK
+    let shift = pop(&mut evm.stack)?;
+    let value = pop(&mut evm.stack)?;
+
+    // GAS
+    charge_gas(evm, GAS_VERY_LOW)?;
+
+    // OPERATION
+    let result = value.sar(shift_amount(shift));
+
+    push(&mut evm.stack, result)?;
+
+    // PROGRAM COUNTER
+    evm.pc += 1;
+    Ok(())
+}
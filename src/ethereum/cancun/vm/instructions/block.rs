@@ -0,0 +1,203 @@
+//! Ethereum Virtual Machine (EVM) Block Instructions
+//! ^^^^^^^^^^^^^^^^^^^^^^^^^^^^^^^^^^^^^^^^^^^^^^^^^
+//! 
+//! .. contents:: Table of Contents
+//!     :backlinks: none
+//!     :local:
+//! 
+//! Introduction
+//! ------------
+//! 
+//! Implementations of the EVM block instructions.
+
+use crate::ethereum::{cancun::vm::{
+    exceptions::VmError,
+    gas::{charge_gas, GAS_BASE, GAS_BLOCK_HASH},
+    stack::{pop, push},
+    Evm,
+}, ethereum_types::numeric::{Uint, U256}};
+
+/// """
+/// Push the hash of one of the 256 most recent complete blocks onto the
+/// stack. The block number to hash is present at the top of the stack.
+/// 
+/// Parameters
+/// ----------
+/// evm :
+///     The current EVM frame.
+/// """
+pub fn block_hash(evm: &mut Evm) -> Result<(), VmError> {
+    // STACK
+    // A number which does not fit in a `Uint` is never a recent block.
+    let block_number = pop(&mut evm.stack)?.to_uint().unwrap_or(Uint::MAX);
+
+    // GAS
+    charge_gas(evm, GAS_BLOCK_HASH)?;
+
+    // OPERATION
+    let max_block_number = block_number.saturating_add(256);
+    let hash = if evm.env.number <= block_number || evm.env.number > max_block_number {
+        U256::ZERO
+    } else {
+        // `block_hashes` may hold fewer hashes than there are blocks, as in
+        // state tests, in which case the older ones are zero.
+        let depth = (evm.env.number - block_number) as usize;
+        let hashes = &evm.env.block_hashes;
+        hashes.len().checked_sub(depth).map_or(U256::ZERO, |i| U256::from_be_bytes(hashes[i].0))
+    };
+
+    push(&mut evm.stack, hash)?;
+
+    // PROGRAM COUNTER
+    evm.pc += 1;
+    Ok(())
+}
+
+/// """
+/// Push the current block's beneficiary address (address of the block miner)
+/// onto the stack.
+/// 
+/// Here the current block refers to the block in which the currently
+/// executing transaction/call resides.
+/// 
+/// Parameters
+/// ----------
+/// evm :
+///     The current EVM frame.
+/// """
+pub fn coinbase(evm: &mut Evm) -> Result<(), VmError> {
+    // STACK
+
+    // GAS
+    charge_gas(evm, GAS_BASE)?;
+
+    // OPERATION
+    push(&mut evm.stack, U256::from_be_slice(&evm.env.coinbase[..]))?;
+
+    // PROGRAM COUNTER
+    evm.pc += 1;
+    Ok(())
+}
+
+/// """
+/// Push the current block's timestamp onto the stack. Here the timestamp
+/// being referred is actually the unix timestamp in seconds.
+/// 
+/// Here the current block refers to the block in which the currently
+/// executing transaction/call resides.
+/// 
+/// Parameters
+/// ----------
+/// evm :
+///     The current EVM frame.
+/// """
+pub fn timestamp(evm: &mut Evm) -> Result<(), VmError> {
+    // STACK
+
+    // GAS
+    charge_gas(evm, GAS_BASE)?;
+
+    // OPERATION
+    push(&mut evm.stack, evm.env.time)?;
+
+    // PROGRAM COUNTER
+    evm.pc += 1;
+    Ok(())
+}
+
+/// """
+/// Push the current block's number onto the stack.
+/// 
+/// Here the current block refers to the block in which the currently
+/// executing transaction/call resides.
+/// 
+/// Parameters
+/// ----------
+/// evm :
+///     The current EVM frame.
+/// """
+pub fn number(evm: &mut Evm) -> Result<(), VmError> {
+    // STACK
+
+    // GAS
+    charge_gas(evm, GAS_BASE)?;
+
+    // OPERATION
+    push(&mut evm.stack, U256::from_uint(evm.env.number))?;
+
+    // PROGRAM COUNTER
+    evm.pc += 1;
+    Ok(())
+}
+
+/// """
+/// Push the `prev_randao` value onto the stack.
+/// 
+/// The `prev_randao` value is the random output of the beacon chain's
+/// randomness oracle for the previous block.
+/// 
+/// Parameters
+/// ----------
+/// evm :
+///     The current EVM frame.
+/// """
+pub fn prev_randao(evm: &mut Evm) -> Result<(), VmError> {
+    // STACK
+
+    // GAS
+    charge_gas(evm, GAS_BASE)?;
+
+    // OPERATION
+    push(&mut evm.stack, U256::from_be_bytes(evm.env.prev_randao.0))?;
+
+    // PROGRAM COUNTER
+    evm.pc += 1;
+    Ok(())
+}
+
+/// """
+/// Push the current block's gas limit onto the stack.
+/// 
+/// Here the current block refers to the block in which the currently
+/// executing transaction/call resides.
+/// 
+/// Parameters
+/// ----------
+/// evm :
+///     The current EVM frame.
+/// """
+pub fn gas_limit(evm: &mut Evm) -> Result<(), VmError> {
+    // STACK
+
+    // GAS
+    charge_gas(evm, GAS_BASE)?;
+
+    // OPERATION
+    push(&mut evm.stack, U256::from_uint(evm.env.gas_limit))?;
+
+    // PROGRAM COUNTER
+    evm.pc += 1;
+    Ok(())
+}
+
+/// """
+/// Push the chain id onto the stack.
+/// 
+/// Parameters
+/// ----------
+/// evm :
+///     The current EVM frame.
+/// """
+pub fn chain_id(evm: &mut Evm) -> Result<(), VmError> {
+    // STACK
+
+    // GAS
+    charge_gas(evm, GAS_BASE)?;
+
+    // OPERATION
+    push(&mut evm.stack, U256::from(evm.env.chain_id))?;
+
+    // PROGRAM COUNTER
+    evm.pc += 1;
+    Ok(())
+}
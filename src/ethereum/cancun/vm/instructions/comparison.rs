@@ -0,0 +1,179 @@
+//! Ethereum Virtual Machine (EVM) Comparison Instructions
+//! ^^^^^^^^^^^^^^^^^^^^^^^^^^^^^^^^^^^^^^^^^^^^^^^^^^^^^^
+//! 
+//! .. contents:: Table of Contents
+//!     :backlinks: none
+//!     :local:
+//! 
+//! Introduction
+//! ------------
+//! 
+//! Implementations of the EVM Comparison instructions.
+
+use std::cmp::Ordering;
+
+use crate::ethereum::{cancun::vm::{
+    exceptions::VmError,
+    gas::{charge_gas, GAS_VERY_LOW},
+    stack::{pop, push},
+    Evm,
+}, ethereum_types::numeric::U256};
+
+/// """
+/// Checks if the top element is less than the next top element. Pushes the
+/// result back on the stack.
+/// 
+/// Parameters
+/// ----------
+/// evm :
+///     The current EVM frame.
+/// """
+pub fn less_than(evm: &mut Evm) -> Result<(), VmError> {
+    // STACK
+    let left = pop(&mut evm.stack)?;
+    let right = pop(&mut evm.stack)?;
+
+    // GAS
+    charge_gas(evm, GAS_VERY_LOW)?;
+
+    // OPERATION
+    let result = U256::from((left < right) as u32);
+
+    push(&mut evm.stack, result)?;
+
+    // PROGRAM COUNTER
+    evm.pc += 1;
+    Ok(())
+}
+
+/// """
+/// Signed less-than comparison.
+/// 
+/// Parameters
+/// ----------
+/// evm :
+///     The current EVM frame.
+/// """
+pub fn signed_less_than(evm: &mut Evm) -> Result<(), VmError> {
+    // STACK
+    let left = pop(&mut evm.stack)?;
+    let right = pop(&mut evm.stack)?;
+
+    // GAS
+    charge_gas(evm, GAS_VERY_LOW)?;
+
+    // OPERATION
+    let result = U256::from((left.signed_cmp(&right) == Ordering::Less) as u32);
+
+    push(&mut evm.stack, result)?;
+
+    // PROGRAM COUNTER
+    evm.pc += 1;
+    Ok(())
+}
+
+/// """
+/// Checks if the top element is greater than the next top element. Pushes
+/// the result back on the stack.
+/// 
+/// Parameters
+/// ----------
+/// evm :
+///     The current EVM frame.
+/// """
+pub fn greater_than(evm: &mut Evm) -> Result<(), VmError> {
+    // STACK
+    let left = pop(&mut evm.stack)?;
+    let right = pop(&mut evm.stack)?;
+
+    // GAS
+    charge_gas(evm, GAS_VERY_LOW)?;
+
+    // OPERATION
+    let result = U256::from((left > right) as u32);
+
+    push(&mut evm.stack, result)?;
+
+    // PROGRAM COUNTER
+    evm.pc += 1;
+    Ok(())
+}
+
+/// """
+/// Signed greater-than comparison.
+/// 
+/// Parameters
+/// ----------
+/// evm :
+///     The current EVM frame.
+/// """
+pub fn signed_greater_than(evm: &mut Evm) -> Result<(), VmError> {
+    // STACK
+    let left = pop(&mut evm.stack)?;
+    let right = pop(&mut evm.stack)?;
+
+    // GAS
+    charge_gas(evm, GAS_VERY_LOW)?;
+
+    // OPERATION
+    let result = U256::from((left.signed_cmp(&right) == Ordering::Greater) as u32);
+
+    push(&mut evm.stack, result)?;
+
+    // PROGRAM COUNTER
+    evm.pc += 1;
+    Ok(())
+}
+
+/// """
+/// Checks if the top element is equal to the next top element. Pushes
+/// the result back on the stack.
+/// 
+/// Parameters
+/// ----------
+/// evm :
+///     The current EVM frame.
+/// """
+pub fn equal(evm: &mut Evm) -> Result<(), VmError> {
+    // STACK
+    let left = pop(&mut evm.stack)?;
+    let right = pop(&mut evm.stack)?;
+
+    // GAS
+    charge_gas(evm, GAS_VERY_LOW)?;
+
+    // OPERATION
+    let result = U256::from((left == right) as u32);
+
+    push(&mut evm.stack, result)?;
+
+    // PROGRAM COUNTER
+    evm.pc += 1;
+    Ok(())
+}
+
+/// """
+/// Checks if the top element is equal to 0. Pushes the result back on the
+/// stack.
+/// 
+/// Parameters
+/// ----------
+/// evm :
+///     The current EVM frame.
+/// """
+pub fn is_zero(evm: &mut Evm) -> Result<(), VmError> {
+    // STACK
+    let x = pop(&mut evm.stack)?;
+
+    // GAS
+    charge_gas(evm, GAS_VERY_LOW)?;
+
+    // OPERATION
+    let result = U256::from(x.is_zero() as u32);
+
+    push(&mut evm.stack, result)?;
+
+    // PROGRAM COUNTER
+    evm.pc += 1;
+    Ok(())
+}
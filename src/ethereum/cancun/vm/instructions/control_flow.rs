@@ -0,0 +1,170 @@
+//! Ethereum Virtual Machine (EVM) Control Flow Instructions
+//! ^^^^^^^^^^^^^^^^^^^^^^^^^^^^^^^^^^^^^^^^^^^^^^^^^^^^^^^^
+//! 
+//! .. contents:: Table of Contents
+//!     :backlinks: none
+//!     :local:
+//! 
+//! Introduction
+//! ------------
+//! 
+//! Implementations of the EVM control flow instructions.
+
+use crate::ethereum::{cancun::vm::{
+    exceptions::VmError,
+    gas::{charge_gas, GAS_BASE, GAS_HIGH, GAS_JUMPDEST, GAS_MID},
+    stack::{pop, push},
+    Evm,
+}, ethereum_types::numeric::{Uint, U256}};
+
+/// """
+/// Stop further execution of EVM code.
+/// 
+/// Parameters
+/// ----------
+/// evm :
+///     The current EVM frame.
+/// """
+pub fn stop(evm: &mut Evm) -> Result<(), VmError> {
+    // STACK
+
+    // GAS
+
+    // OPERATION
+    evm.running = false;
+
+    // PROGRAM COUNTER
+    evm.pc += 1;
+    Ok(())
+}
+
+/// The jump destination `dest`, if it is valid.
+fn jump_destination(evm: &Evm, dest: U256) -> Result<Uint, VmError> {
+    match dest.to_uint() {
+        Ok(dest) if evm.valid_jump_destinations.contains(&dest) => Ok(dest),
+        _ => Err(VmError::InvalidJumpDestError),
+    }
+}
+
+/// """
+/// Alter the program counter to the location specified by the top of the
+/// stack.
+/// 
+/// Parameters
+/// ----------
+/// evm :
+///     The current EVM frame.
+/// """
+pub fn jump(evm: &mut Evm) -> Result<(), VmError> {
+    // STACK
+    let jump_dest = pop(&mut evm.stack)?;
+
+    // GAS
+    charge_gas(evm, GAS_MID)?;
+
+    // OPERATION
+    let jump_dest = jump_destination(evm, jump_dest)?;
+
+    // PROGRAM COUNTER
+    evm.pc = jump_dest;
+    Ok(())
+}
+
+/// """
+/// Alter the program counter to the specified location if and only if a
+/// condition is true. If the condition is not true, then the program counter
+/// would increase only by 1.
+/// 
+/// Parameters
+/// ----------
+/// evm :
+///     The current EVM frame.
+/// """
+pub fn jumpi(evm: &mut Evm) -> Result<(), VmError> {
+    // STACK
+    let jump_dest = pop(&mut evm.stack)?;
+    let conditional_value = pop(&mut evm.stack)?;
+
+    // GAS
+    charge_gas(evm, GAS_HIGH)?;
+
+    // OPERATION
+    let destination = if conditional_value.is_zero() {
+        evm.pc + 1
+    } else {
+        jump_destination(evm, jump_dest)?
+    };
+
+    // PROGRAM COUNTER
+    evm.pc = destination;
+    Ok(())
+}
+
+/// """
+/// Push onto the stack the value of the program counter after reaching the
+/// current instruction and without increasing it for the next instruction.
+/// 
+/// Parameters
+/// ----------
+/// evm :
+///     The current EVM frame.
+/// """
+pub fn pc(evm: &mut Evm) -> Result<(), VmError> {
+    // STACK
+
+    // GAS
+    charge_gas(evm, GAS_BASE)?;
+
+    // OPERATION
+    push(&mut evm.stack, U256::from_uint(evm.pc))?;
+
+    // PROGRAM COUNTER
+    evm.pc += 1;
+    Ok(())
+}
+
+/// """
+/// Push the amount of available gas (including the corresponding reduction
+/// for the cost of this instruction) onto the stack.
+/// 
+/// Parameters
+/// ----------
+/// evm :
+///     The current EVM frame.
+/// """
+pub fn gas_left(evm: &mut Evm) -> Result<(), VmError> {
+    // STACK
+
+    // GAS
+    charge_gas(evm, GAS_BASE)?;
+
+    // OPERATION
+    push(&mut evm.stack, U256::from_uint(evm.gas_left))?;
+
+    // PROGRAM COUNTER
+    evm.pc += 1;
+    Ok(())
+}
+
+/// """
+/// Mark a valid destination for jumps. This is a noop, present only
+/// to be used by `JUMP` and `JUMPI` opcodes to verify that their jump is
+/// valid.
+/// 
+/// Parameters
+/// ----------
+/// evm :
+///     The current EVM frame.
+/// """
+pub fn jumpdest(evm: &mut Evm) -> Result<(), VmError> {
+    // STACK
+
+    // GAS
+    charge_gas(evm, GAS_JUMPDEST)?;
+
+    // OPERATION
+
+    // PROGRAM COUNTER
+    evm.pc += 1;
+    Ok(())
+}
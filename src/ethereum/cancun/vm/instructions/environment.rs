@@ -0,0 +1,573 @@
+//! Ethereum Virtual Machine (EVM) Environmental Instructions
+//! ^^^^^^^^^^^^^^^^^^^^^^^^^^^^^^^^^^^^^^^^^^^^^^^^^^^^^^^^^
+//! 
+//! .. contents:: Table of Contents
+//!     :backlinks: none
+//!     :local:
+//! 
+//! Introduction
+//! ------------
+//! 
+//! Implementations of the EVM environment related instructions.
+
+use crate::ethereum::{cancun::{fork_types::{Address, EMPTY_ACCOUNT}, state::get_account, utils::to_address, vm::{
+    exceptions::VmError,
+    gas::{
+        calculate_blob_gas_price, calculate_gas_extend_memory, charge_gas, GAS_BALANCE_FRONTIER,
+        GAS_BALANCE_ISTANBUL, GAS_BALANCE_TANGERINE_WHISTLE, GAS_BASE, GAS_BLOBHASH_OPCODE,
+        GAS_CODE_HASH_CONSTANTINOPLE, GAS_CODE_HASH_ISTANBUL, GAS_COLD_ACCOUNT_ACCESS, GAS_COPY,
+        GAS_EXTERNAL_FRONTIER, GAS_EXTERNAL_TANGERINE_WHISTLE, GAS_FAST_STEP, GAS_RETURN_DATA_COPY,
+        GAS_VERY_LOW, GAS_WARM_ACCESS,
+    },
+    memory::{buffer_read, memory_extend, memory_write},
+    stack::{pop, push},
+    Evm,
+}}, crypto::hash::keccak256_cached, ethereum_types::numeric::{Uint, U256}};
+
+use super::words;
+
+/// The cost of reading the account at `address`: from Berlin (EIP-2929) the
+/// warm or cold access cost, marking it as warm, and before that
+/// `flat_cost`.
+fn account_access_cost(evm: &mut Evm, address: &Address, flat_cost: Uint) -> Uint {
+    if !evm.env.rules.berlin {
+        flat_cost
+    } else if evm.accessed_addresses.contains(address) {
+        GAS_WARM_ACCESS
+    } else {
+        evm.accessed_addresses.insert(address.clone());
+        GAS_COLD_ACCOUNT_ACCESS
+    }
+}
+
+/// The flat cost of `EXTCODESIZE` and `EXTCODECOPY` before Berlin.
+fn external_cost(evm: &Evm) -> Uint {
+    if evm.env.rules.tangerine_whistle { GAS_EXTERNAL_TANGERINE_WHISTLE } else { GAS_EXTERNAL_FRONTIER }
+}
+
+/// """
+/// Pushes the address of the current executing account to the stack.
+/// 
+/// Parameters
+/// ----------
+/// evm :
+///     The current EVM frame.
+/// """
+pub fn address(evm: &mut Evm) -> Result<(), VmError> {
+    // STACK
+
+    // GAS
+    charge_gas(evm, GAS_BASE)?;
+
+    // OPERATION
+    push(&mut evm.stack, U256::from_be_slice(&evm.message.current_target[..]))?;
+
+    // PROGRAM COUNTER
+    evm.pc += 1;
+    Ok(())
+}
+
+/// """
+/// Pushes the balance of the given account onto the stack.
+/// 
+/// Parameters
+/// ----------
+/// evm :
+///     The current EVM frame.
+/// """
+pub fn balance(evm: &mut Evm) -> Result<(), VmError> {
+    // STACK
+    let address = to_address(pop(&mut evm.stack)?);
+
+    // GAS
+    let rules = evm.env.rules;
+    let flat_cost = if rules.istanbul {
+        GAS_BALANCE_ISTANBUL
+    } else if rules.tangerine_whistle {
+        GAS_BALANCE_TANGERINE_WHISTLE
+    } else {
+        GAS_BALANCE_FRONTIER
+    };
+    let gas_cost = account_access_cost(evm, &address, flat_cost);
+    charge_gas(evm, gas_cost)?;
+
+    // OPERATION
+    // Non-existent accounts default to EMPTY_ACCOUNT, which has balance 0.
+    let balance = get_account(evm.env.state, &address).balance;
+
+    push(&mut evm.stack, balance)?;
+
+    // PROGRAM COUNTER
+    evm.pc += 1;
+    Ok(())
+}
+
+/// """
+/// Pushes the address of the original transaction sender to the stack.
+/// The origin address can only be an EOA.
+/// 
+/// Parameters
+/// ----------
+/// evm :
+///     The current EVM frame.
+/// """
+pub fn origin(evm: &mut Evm) -> Result<(), VmError> {
+    // STACK
+
+    // GAS
+    charge_gas(evm, GAS_BASE)?;
+
+    // OPERATION
+    push(&mut evm.stack, U256::from_be_slice(&evm.env.origin[..]))?;
+
+    // PROGRAM COUNTER
+    evm.pc += 1;
+    Ok(())
+}
+
+/// """
+/// Pushes the address of the caller onto the stack.
+/// 
+/// Parameters
+/// ----------
+/// evm :
+///     The current EVM frame.
+/// """
+pub fn caller(evm: &mut Evm) -> Result<(), VmError> {
+    // STACK
+
+    // GAS
+    charge_gas(evm, GAS_BASE)?;
+
+    // OPERATION
+    push(&mut evm.stack, U256::from_be_slice(&evm.message.caller[..]))?;
+
+    // PROGRAM COUNTER
+    evm.pc += 1;
+    Ok(())
+}
+
+/// """
+/// Push the value (in wei) sent with the call onto the stack.
+/// 
+/// Parameters
+/// ----------
+/// evm :
+///     The current EVM frame.
+/// """
+pub fn callvalue(evm: &mut Evm) -> Result<(), VmError> {
+    // STACK
+
+    // GAS
+    charge_gas(evm, GAS_BASE)?;
+
+    // OPERATION
+    push(&mut evm.stack, evm.message.value)?;
+
+    // PROGRAM COUNTER
+    evm.pc += 1;
+    Ok(())
+}
+
+/// """
+/// Push a word (32 bytes) of the input data belonging to the current
+/// environment onto the stack.
+/// 
+/// Parameters
+/// ----------
+/// evm :
+///     The current EVM frame.
+/// """
+pub fn calldataload(evm: &mut Evm) -> Result<(), VmError> {
+    // STACK
+    let start_index = pop(&mut evm.stack)?;
+
+    // GAS
+    charge_gas(evm, GAS_VERY_LOW)?;
+
+    // OPERATION
+    let value = buffer_read(&evm.message.data, start_index, U256::from(32_u32));
+
+    push(&mut evm.stack, U256::from_be_slice(&value))?;
+
+    // PROGRAM COUNTER
+    evm.pc += 1;
+    Ok(())
+}
+
+/// """
+/// Push the size of input data in current environment onto the stack.
+/// 
+/// Parameters
+/// ----------
+/// evm :
+///     The current EVM frame.
+/// """
+pub fn calldatasize(evm: &mut Evm) -> Result<(), VmError> {
+    // STACK
+
+    // GAS
+    charge_gas(evm, GAS_BASE)?;
+
+    // OPERATION
+    push(&mut evm.stack, U256::from(evm.message.data.len() as u64))?;
+
+    // PROGRAM COUNTER
+    evm.pc += 1;
+    Ok(())
+}
+
+/// """
+/// Copy a portion of the input data in current environment to memory.
+/// 
+/// This will also expand the memory, in case that the memory is insufficient
+/// to store the data.
+/// 
+/// Parameters
+/// ----------
+/// evm :
+///     The current EVM frame.
+/// """
+pub fn calldatacopy(evm: &mut Evm) -> Result<(), VmError> {
+    // STACK
+    let memory_start_index = pop(&mut evm.stack)?;
+    let data_start_index = pop(&mut evm.stack)?;
+    let size = pop(&mut evm.stack)?;
+
+    // GAS
+    let copy_gas_cost = GAS_COPY * words(size)?;
+    let extend_memory = calculate_gas_extend_memory(&evm.memory, &[(memory_start_index, size)])?;
+    charge_gas(evm, GAS_VERY_LOW + copy_gas_cost + extend_memory.cost)?;
+
+    // OPERATION
+    memory_extend(&mut evm.memory, extend_memory.expand_by);
+    let value = buffer_read(&evm.message.data, data_start_index, size);
+    memory_write(&mut evm.memory, memory_start_index, &value);
+
+    // PROGRAM COUNTER
+    evm.pc += 1;
+    Ok(())
+}
+
+/// """
+/// Push the size of code running in current environment onto the stack.
+/// 
+/// Parameters
+/// ----------
+/// evm :
+///     The current EVM frame.
+/// """
+pub fn codesize(evm: &mut Evm) -> Result<(), VmError> {
+    // STACK
+
+    // GAS
+    charge_gas(evm, GAS_BASE)?;
+
+    // OPERATION
+    push(&mut evm.stack, U256::from(evm.message.code.len() as u64))?;
+
+    // PROGRAM COUNTER
+    evm.pc += 1;
+    Ok(())
+}
+
+/// """
+/// Copy a portion of the code in current environment to memory.
+/// 
+/// This will also expand the memory, in case that the memory is insufficient
+/// to store the data.
+/// 
+/// Parameters
+/// ----------
+/// evm :
+///     The current EVM frame.
+/// """
+pub fn codecopy(evm: &mut Evm) -> Result<(), VmError> {
+    // STACK
+    let memory_start_index = pop(&mut evm.stack)?;
+    let code_start_index = pop(&mut evm.stack)?;
+    let size = pop(&mut evm.stack)?;
+
+    // GAS
+    let copy_gas_cost = GAS_COPY * words(size)?;
+    let extend_memory = calculate_gas_extend_memory(&evm.memory, &[(memory_start_index, size)])?;
+    charge_gas(evm, GAS_VERY_LOW + copy_gas_cost + extend_memory.cost)?;
+
+    // OPERATION
+    memory_extend(&mut evm.memory, extend_memory.expand_by);
+    let value = buffer_read(&evm.message.code, code_start_index, size);
+    memory_write(&mut evm.memory, memory_start_index, &value);
+
+    // PROGRAM COUNTER
+    evm.pc += 1;
+    Ok(())
+}
+
+/// """
+/// Push the code size of a given account onto the stack.
+/// 
+/// Parameters
+/// ----------
+/// evm :
+///     The current EVM frame.
+/// """
+pub fn extcodesize(evm: &mut Evm) -> Result<(), VmError> {
+    // STACK
+    let address = to_address(pop(&mut evm.stack)?);
+
+    // GAS
+    let flat_cost = external_cost(evm);
+    let gas_cost = account_access_cost(evm, &address, flat_cost);
+    charge_gas(evm, gas_cost)?;
+
+    // OPERATION
+    let codesize = U256::from(get_account(evm.env.state, &address).code.len() as u64);
+
+    push(&mut evm.stack, codesize)?;
+
+    // PROGRAM COUNTER
+    evm.pc += 1;
+    Ok(())
+}
+
+/// """
+/// Copy a portion of an account's code to memory.
+/// 
+/// Parameters
+/// ----------
+/// evm :
+///     The current EVM frame.
+/// """
+pub fn extcodecopy(evm: &mut Evm) -> Result<(), VmError> {
+    // STACK
+    let address = to_address(pop(&mut evm.stack)?);
+    let memory_start_index = pop(&mut evm.stack)?;
+    let code_start_index = pop(&mut evm.stack)?;
+    let size = pop(&mut evm.stack)?;
+
+    // GAS
+    let copy_gas_cost = GAS_COPY * words(size)?;
+    let extend_memory = calculate_gas_extend_memory(&evm.memory, &[(memory_start_index, size)])?;
+    let flat_cost = external_cost(evm);
+    let access_gas_cost = account_access_cost(evm, &address, flat_cost);
+    charge_gas(evm, access_gas_cost + copy_gas_cost + extend_memory.cost)?;
+
+    // OPERATION
+    memory_extend(&mut evm.memory, extend_memory.expand_by);
+    let code = &get_account(evm.env.state, &address).code;
+    let value = buffer_read(code, code_start_index, size);
+    memory_write(&mut evm.memory, memory_start_index, &value);
+
+    // PROGRAM COUNTER
+    evm.pc += 1;
+    Ok(())
+}
+
+/// """
+/// Returns the keccak256 hash of a contract’s bytecode
+/// 
+/// Parameters
+/// ----------
+/// evm :
+///     The current EVM frame.
+/// """
+pub fn extcodehash(evm: &mut Evm) -> Result<(), VmError> {
+    // STACK
+    let address = to_address(pop(&mut evm.stack)?);
+
+    // GAS
+    let flat_cost = if evm.env.rules.istanbul { GAS_CODE_HASH_ISTANBUL } else { GAS_CODE_HASH_CONSTANTINOPLE };
+    let gas_cost = account_access_cost(evm, &address, flat_cost);
+    charge_gas(evm, gas_cost)?;
+
+    // OPERATION
+    // An account which does not exist, or is empty, has no code hash.
+    let account = get_account(evm.env.state, &address);
+    let codehash = if *account == EMPTY_ACCOUNT {
+        U256::ZERO
+    } else {
+        U256::from_be_bytes(keccak256_cached(&account.code).0)
+    };
+
+    push(&mut evm.stack, codehash)?;
+
+    // PROGRAM COUNTER
+    evm.pc += 1;
+    Ok(())
+}
+
+/// """
+/// Push the gas price used in current environment onto the stack.
+/// 
+/// Parameters
+/// ----------
+/// evm :
+///     The current EVM frame.
+/// """
+pub fn gasprice(evm: &mut Evm) -> Result<(), VmError> {
+    // STACK
+
+    // GAS
+    charge_gas(evm, GAS_BASE)?;
+
+    // OPERATION
+    push(&mut evm.stack, U256::from_uint(evm.env.gas_price))?;
+
+    // PROGRAM COUNTER
+    evm.pc += 1;
+    Ok(())
+}
+
+/// """
+/// Pushes the size of the return data buffer onto the stack.
+/// 
+/// Parameters
+/// ----------
+/// evm :
+///     The current EVM frame.
+/// """
+pub fn returndatasize(evm: &mut Evm) -> Result<(), VmError> {
+    // STACK
+
+    // GAS
+    charge_gas(evm, GAS_BASE)?;
+
+    // OPERATION
+    push(&mut evm.stack, U256::from(evm.return_data.len() as u64))?;
+
+    // PROGRAM COUNTER
+    evm.pc += 1;
+    Ok(())
+}
+
+/// """
+/// Copies data from the return data buffer code to memory
+/// 
+/// Parameters
+/// ----------
+/// evm :
+///     The current EVM frame.
+/// """
+pub fn returndatacopy(evm: &mut Evm) -> Result<(), VmError> {
+    // STACK
+    let memory_start_index = pop(&mut evm.stack)?;
+    let return_data_start_position = pop(&mut evm.stack)?;
+    let size = pop(&mut evm.stack)?;
+
+    // GAS
+    let copy_gas_cost = GAS_RETURN_DATA_COPY * words(size)?;
+    let extend_memory = calculate_gas_extend_memory(&evm.memory, &[(memory_start_index, size)])?;
+    charge_gas(evm, GAS_VERY_LOW + copy_gas_cost + extend_memory.cost)?;
+    let (end, overflow) = return_data_start_position.overflowing_add(size);
+    if overflow || end > U256::from(evm.return_data.len() as u64) {
+        return Err(VmError::OutOfBoundsRead);
+    }
+
+    memory_extend(&mut evm.memory, extend_memory.expand_by);
+    let value = buffer_read(&evm.return_data, return_data_start_position, size);
+    memory_write(&mut evm.memory, memory_start_index, &value);
+
+    // PROGRAM COUNTER
+    evm.pc += 1;
+    Ok(())
+}
+
+/// """
+/// Pushes the balance of the current address to the stack.
+/// 
+/// Parameters
+/// ----------
+/// evm :
+///     The current EVM frame.
+/// """
+pub fn self_balance(evm: &mut Evm) -> Result<(), VmError> {
+    // STACK
+
+    // GAS
+    charge_gas(evm, GAS_FAST_STEP)?;
+
+    // OPERATION
+    // Non-existent accounts default to EMPTY_ACCOUNT, which has balance 0.
+    let balance = get_account(evm.env.state, &evm.message.current_target).balance;
+
+    push(&mut evm.stack, balance)?;
+
+    // PROGRAM COUNTER
+    evm.pc += 1;
+    Ok(())
+}
+
+/// """
+/// Pushes the base fee of the current block on to the stack.
+/// 
+/// Parameters
+/// ----------
+/// evm :
+///     The current EVM frame.
+/// """
+pub fn base_fee(evm: &mut Evm) -> Result<(), VmError> {
+    // STACK
+
+    // GAS
+    charge_gas(evm, GAS_BASE)?;
+
+    // OPERATION
+    push(&mut evm.stack, U256::from_uint(evm.env.base_fee_per_gas))?;
+
+    // PROGRAM COUNTER
+    evm.pc += 1;
+    Ok(())
+}
+
+/// """
+/// Pushes the versioned hash at a particular index on to the stack.
+/// 
+/// Parameters
+/// ----------
+/// evm :
+///     The current EVM frame.
+/// """
+pub fn blob_hash(evm: &mut Evm) -> Result<(), VmError> {
+    // STACK
+    let index = pop(&mut evm.stack)?;
+
+    // GAS
+    charge_gas(evm, GAS_BLOBHASH_OPCODE)?;
+
+    // OPERATION
+    let hashes = &evm.env.blob_versioned_hashes;
+    let blob_hash = match index.to_uint() {
+        Ok(index) if index < hashes.len() as u128 => U256::from_be_bytes(hashes[index as usize].0),
+        _ => U256::ZERO,
+    };
+
+    push(&mut evm.stack, blob_hash)?;
+
+    // PROGRAM COUNTER
+    evm.pc += 1;
+    Ok(())
+}
+
+/// """
+/// Pushes the blob base fee on to the stack.
+/// 
+/// Parameters
+/// ----------
+/// evm :
+///     The current EVM frame.
+/// """
+pub fn blob_base_fee(evm: &mut Evm) -> Result<(), VmError> {
+    // STACK
+
+    // GAS
+    charge_gas(evm, GAS_BASE)?;
+
+    // OPERATION
+    let blob_base_fee = calculate_blob_gas_price(evm.env.excess_blob_gas);
+
+    push(&mut evm.stack, U256::from_uint(blob_base_fee))?;
+
+    // PROGRAM COUNTER
+    evm.pc += 1;
+    Ok(())
+}
@@ -0,0 +1,54 @@
+//! Ethereum Virtual Machine (EVM) Keccak Instructions
+//! ^^^^^^^^^^^^^^^^^^^^^^^^^^^^^^^^^^^^^^^^^^^^^^^^^^
+//! 
+//! .. contents:: Table of Contents
+//!     :backlinks: none
+//!     :local:
+//! 
+//! Introduction
+//! ------------
+//! 
+//! Implementations of the EVM keccak instructions.
+
+use crate::ethereum::{cancun::vm::{
+    exceptions::VmError,
+    gas::{calculate_gas_extend_memory, charge_gas, GAS_KECCAK256, GAS_KECCAK256_WORD},
+    memory::{memory_extend, memory_read_bytes},
+    stack::{pop, push},
+    Evm,
+}, crypto::hash::keccak256, ethereum_types::numeric::U256};
+
+use super::words;
+
+/// """
+/// Pushes to the stack the Keccak-256 hash of a region of memory.
+/// 
+/// This also expands the memory, in case the memory is insufficient to
+/// access the data's memory location.
+/// 
+/// Parameters
+/// ----------
+/// evm :
+///     The current EVM frame.
+/// """
+pub fn keccak(evm: &mut Evm) -> Result<(), VmError> {
+    // STACK
+    let memory_start_index = pop(&mut evm.stack)?;
+    let size = pop(&mut evm.stack)?;
+
+    // GAS
+    let word_gas_cost = GAS_KECCAK256_WORD * words(size)?;
+    let extend_memory = calculate_gas_extend_memory(&evm.memory, &[(memory_start_index, size)])?;
+    charge_gas(evm, GAS_KECCAK256 + word_gas_cost + extend_memory.cost)?;
+
+    // OPERATION
+    memory_extend(&mut evm.memory, extend_memory.expand_by);
+    let data = memory_read_bytes(&evm.memory, memory_start_index, size);
+    let hash = keccak256(&data);
+
+    push(&mut evm.stack, U256::from_be_bytes(hash.0))?;
+
+    // PROGRAM COUNTER
+    evm.pc += 1;
+    Ok(())
+}
@@ -0,0 +1,68 @@
+//! Ethereum Virtual Machine (EVM) Logging Instructions
+//! ^^^^^^^^^^^^^^^^^^^^^^^^^^^^^^^^^^^^^^^^^^^^^^^^^^^
+//! 
+//! .. contents:: Table of Contents
+//!     :backlinks: none
+//!     :local:
+//! 
+//! Introduction
+//! ------------
+//! 
+//! Implementations of the EVM logging instructions.
+
+use crate::ethereum::{cancun::{blocks::Log, vm::{
+    exceptions::VmError,
+    gas::{calculate_gas_extend_memory, charge_gas, GAS_LOG, GAS_LOG_DATA, GAS_LOG_TOPIC},
+    memory::{memory_extend, memory_read_bytes},
+    stack::pop,
+    Evm,
+}}, crypto::hash::Hash32, ethereum_types::numeric::Uint};
+
+/// """
+/// Appends a log entry, having `num_topics` topics, to the evm logs.
+/// 
+/// This will also expand the memory if the data (required by the log entry)
+/// corresponding to the memory is not accessible.
+/// 
+/// Parameters
+/// ----------
+/// evm :
+///     The current EVM frame.
+/// num_topics :
+///     The number of topics to be included in the log entry.
+/// """
+pub fn log_n(evm: &mut Evm, num_topics: usize) -> Result<(), VmError> {
+    // STACK
+    let memory_start_index = pop(&mut evm.stack)?;
+    let size = pop(&mut evm.stack)?;
+
+    let mut topics = Vec::with_capacity(num_topics);
+    for _ in 0..num_topics {
+        topics.push(Hash32(pop(&mut evm.stack)?.to_be_bytes()));
+    }
+
+    // GAS
+    // Data of 2^64 bytes or more can never be paid for.
+    let data_size = size.to_uint().ok().filter(|size| *size <= u64::MAX as Uint).ok_or(VmError::OutOfGasError)?;
+    let extend_memory = calculate_gas_extend_memory(&evm.memory, &[(memory_start_index, size)])?;
+    charge_gas(
+        evm,
+        GAS_LOG + GAS_LOG_DATA * data_size + GAS_LOG_TOPIC * num_topics as Uint + extend_memory.cost,
+    )?;
+
+    // OPERATION
+    memory_extend(&mut evm.memory, extend_memory.expand_by);
+    if evm.message.is_static {
+        return Err(VmError::WriteInStaticContext);
+    }
+    let log_entry = Log {
+        address: evm.message.current_target.clone(),
+        topics,
+        data: memory_read_bytes(&evm.memory, memory_start_index, size),
+    };
+    evm.logs.push(log_entry);
+
+    // PROGRAM COUNTER
+    evm.pc += 1;
+    Ok(())
+}
@@ -0,0 +1,163 @@
+//! Ethereum Virtual Machine (EVM) Memory Instructions
+//! ^^^^^^^^^^^^^^^^^^^^^^^^^^^^^^^^^^^^^^^^^^^^^^^^^^
+//! 
+//! .. contents:: Table of Contents
+//!     :backlinks: none
+//!     :local:
+//! 
+//! Introduction
+//! ------------
+//! 
+//! Implementations of the EVM Memory instructions.
+
+use crate::ethereum::{cancun::vm::{
+    exceptions::VmError,
+    gas::{calculate_gas_extend_memory, charge_gas, GAS_BASE, GAS_COPY, GAS_VERY_LOW},
+    memory::{memory_extend, memory_read_bytes, memory_write},
+    stack::{pop, push},
+    Evm,
+}, ethereum_types::numeric::U256};
+
+use super::words;
+
+/// """
+/// Stores a word to memory.
+/// This also expands the memory, if the memory is
+/// insufficient to store the word.
+/// 
+/// Parameters
+/// ----------
+/// evm :
+///     The current EVM frame.
+/// """
+pub fn mstore(evm: &mut Evm) -> Result<(), VmError> {
+    // STACK
+    let start_position = pop(&mut evm.stack)?;
+    let value = pop(&mut evm.stack)?.to_be_bytes();
+
+    // GAS
+    let extend_memory = calculate_gas_extend_memory(
+        &evm.memory, &[(start_position, U256::from(value.len() as u64))]
+    )?;
+    charge_gas(evm, GAS_VERY_LOW + extend_memory.cost)?;
+
+    // OPERATION
+    memory_extend(&mut evm.memory, extend_memory.expand_by);
+    memory_write(&mut evm.memory, start_position, &value);
+
+    // PROGRAM COUNTER
+    evm.pc += 1;
+    Ok(())
+}
+
+/// """
+/// Stores a byte to memory.
+/// This also expands the memory, if the memory is
+/// insufficient to store the word.
+/// 
+/// Parameters
+/// ----------
+/// evm :
+///     The current EVM frame.
+/// """
+pub fn mstore8(evm: &mut Evm) -> Result<(), VmError> {
+    // STACK
+    let start_position = pop(&mut evm.stack)?;
+    let value = pop(&mut evm.stack)?;
+
+    // GAS
+    let extend_memory = calculate_gas_extend_memory(
+        &evm.memory, &[(start_position, U256::ONE)]
+    )?;
+    charge_gas(evm, GAS_VERY_LOW + extend_memory.cost)?;
+
+    // OPERATION
+    memory_extend(&mut evm.memory, extend_memory.expand_by);
+    let normalized_bytes_value = [value.low_u64() as u8];
+    memory_write(&mut evm.memory, start_position, &normalized_bytes_value);
+
+    // PROGRAM COUNTER
+    evm.pc += 1;
+    Ok(())
+}
+
+/// """
+/// Load word from memory.
+/// 
+/// Parameters
+/// ----------
+/// evm :
+///     The current EVM frame.
+/// """
+pub fn mload(evm: &mut Evm) -> Result<(), VmError> {
+    // STACK
+    let start_position = pop(&mut evm.stack)?;
+
+    // GAS
+    let extend_memory = calculate_gas_extend_memory(
+        &evm.memory, &[(start_position, U256::from(32_u32))]
+    )?;
+    charge_gas(evm, GAS_VERY_LOW + extend_memory.cost)?;
+
+    // OPERATION
+    memory_extend(&mut evm.memory, extend_memory.expand_by);
+    let value = memory_read_bytes(&evm.memory, start_position, U256::from(32_u32));
+    push(&mut evm.stack, U256::from_be_slice(&value))?;
+
+    // PROGRAM COUNTER
+    evm.pc += 1;
+    Ok(())
+}
+
+/// """
+/// Push the size of active memory in bytes onto the stack.
+/// 
+/// Parameters
+/// ----------
+/// evm :
+///     The current EVM frame.
+/// """
+pub fn msize(evm: &mut Evm) -> Result<(), VmError> {
+    // STACK
+
+    // GAS
+    charge_gas(evm, GAS_BASE)?;
+
+    // OPERATION
+    push(&mut evm.stack, U256::from(evm.memory.len() as u64))?;
+
+    // PROGRAM COUNTER
+    evm.pc += 1;
+    Ok(())
+}
+
+/// """
+/// Copy the bytes in memory from one location to another.
+/// 
+/// Parameters
+/// ----------
+/// evm :
+///     The current EVM frame.
+/// """
+pub fn mcopy(evm: &mut Evm) -> Result<(), VmError> {
+    // STACK
+    let destination = pop(&mut evm.stack)?;
+    let source = pop(&mut evm.stack)?;
+    let length = pop(&mut evm.stack)?;
+
+    // GAS
+    let copy_gas_cost = GAS_COPY * words(length)?;
+    let extend_memory = calculate_gas_extend_memory(
+        &evm.memory, &[(source, length), (destination, length)]
+    )?;
+    charge_gas(evm, GAS_VERY_LOW + copy_gas_cost + extend_memory.cost)?;
+
+    // OPERATION
+    memory_extend(&mut evm.memory, extend_memory.expand_by);
+    let value = memory_read_bytes(&evm.memory, source, length);
+    memory_write(&mut evm.memory, destination, &value);
+
+    // PROGRAM COUNTER
+    evm.pc += 1;
+    Ok(())
+}
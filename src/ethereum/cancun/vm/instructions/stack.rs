@@ -0,0 +1,137 @@
+//! Ethereum Virtual Machine (EVM) Stack Instructions
+//! ^^^^^^^^^^^^^^^^^^^^^^^^^^^^^^^^^^^^^^^^^^^^^^^^^
+//! 
+//! .. contents:: Table of Contents
+//!     :backlinks: none
+//!     :local:
+//! 
+//! Introduction
+//! ------------
+//! 
+//! Implementations of the EVM stack related instructions.
+
+use crate::ethereum::{cancun::vm::{
+    exceptions::VmError,
+    gas::{charge_gas, GAS_BASE, GAS_VERY_LOW},
+    memory::buffer_read,
+    stack,
+    Evm,
+}, ethereum_types::numeric::{Uint, U256}};
+
+/// """
+/// Remove item from stack.
+/// 
+/// Parameters
+/// ----------
+/// evm :
+///     The current EVM frame.
+/// """
+pub fn pop(evm: &mut Evm) -> Result<(), VmError> {
+    // STACK
+    stack::pop(&mut evm.stack)?;
+
+    // GAS
+    charge_gas(evm, GAS_BASE)?;
+
+    // OPERATION
+
+    // PROGRAM COUNTER
+    evm.pc += 1;
+    Ok(())
+}
+
+/// """
+/// Pushes a N-byte immediate onto the stack. Push zero if num_bytes is zero.
+/// 
+/// Immediate bytes past the end of the code read as zero, so a push which
+/// is cut off by the end of the code pushes its bytes shifted left.
+/// 
+/// Parameters
+/// ----------
+/// evm :
+///     The current EVM frame.
+/// 
+/// num_bytes :
+///     The number of immediate bytes to be read from the code and pushed to
+///     the stack.
+/// """
+pub fn push_n(evm: &mut Evm, num_bytes: usize) -> Result<(), VmError> {
+    // STACK
+
+    // GAS
+    if num_bytes == 0 {
+        charge_gas(evm, GAS_BASE)?;
+    } else {
+        charge_gas(evm, GAS_VERY_LOW)?;
+    }
+
+    // OPERATION
+    let data_to_push = buffer_read(
+        &evm.code, U256::from_uint(evm.pc + 1), U256::from(num_bytes as u64)
+    );
+    stack::push(&mut evm.stack, U256::from_be_slice(&data_to_push))?;
+
+    // PROGRAM COUNTER
+    evm.pc += 1 + num_bytes as Uint;
+    Ok(())
+}
+
+/// """
+/// Duplicate the Nth stack item (from top of the stack) to the top of stack.
+/// 
+/// Parameters
+/// ----------
+/// evm :
+///     The current EVM frame.
+/// 
+/// item_number :
+///     The stack item number (0-indexed from top of stack) to be duplicated
+///     to the top of stack.
+/// """
+pub fn dup_n(evm: &mut Evm, item_number: usize) -> Result<(), VmError> {
+    // STACK
+
+    // GAS
+    charge_gas(evm, GAS_VERY_LOW)?;
+    if item_number >= evm.stack.len() {
+        return Err(VmError::StackUnderflowError);
+    }
+    let data_to_duplicate = evm.stack[evm.stack.len() - 1 - item_number];
+    stack::push(&mut evm.stack, data_to_duplicate)?;
+
+    // PROGRAM COUNTER
+    evm.pc += 1;
+    Ok(())
+}
+
+/// """
+/// Swap the top and the `item_number` element of the stack, where
+/// the top of the stack is position zero.
+/// 
+/// If `item_number` is zero, this function does nothing (which should not be
+/// possible, since there is no `SWAP0` instruction).
+/// 
+/// Parameters
+/// ----------
+/// evm :
+///     The current EVM frame.
+/// 
+/// item_number :
+///     The stack item number (0-indexed from top of stack) to be swapped
+///     with the top of stack element.
+/// """
+pub fn swap_n(evm: &mut Evm, item_number: usize) -> Result<(), VmError> {
+    // STACK
+
+    // GAS
+    charge_gas(evm, GAS_VERY_LOW)?;
+    if item_number >= evm.stack.len() {
+        return Err(VmError::StackUnderflowError);
+    }
+    let top = evm.stack.len() - 1;
+    evm.stack.swap(top, top - item_number);
+
+    // PROGRAM COUNTER
+    evm.pc += 1;
+    Ok(())
+}
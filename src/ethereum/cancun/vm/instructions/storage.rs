@@ -0,0 +1,233 @@
+//! Ethereum Virtual Machine (EVM) Storage Instructions
+//! ^^^^^^^^^^^^^^^^^^^^^^^^^^^^^^^^^^^^^^^^^^^^^^^^^^^
+//! 
+//! .. contents:: Table of Contents
+//!     :backlinks: none
+//!     :local:
+//! 
+//! Introduction
+//! ------------
+//! 
+//! Implementations of the EVM storage related instructions.
+
+use crate::ethereum::{cancun::{state::{get_storage, get_storage_original, get_transient_storage, set_storage, set_transient_storage}, vm::{
+    exceptions::VmError,
+    gas::{
+        charge_gas, GAS_CALL_STIPEND, GAS_COLD_SLOAD, GAS_SLOAD_FRONTIER, GAS_SLOAD_ISTANBUL,
+        GAS_SLOAD_TANGERINE_WHISTLE, GAS_STORAGE_CLEAR_REFUND, GAS_STORAGE_CLEAR_REFUND_FRONTIER,
+        GAS_STORAGE_SET, GAS_STORAGE_UPDATE, GAS_WARM_ACCESS,
+    },
+    stack::{pop, push},
+    Evm,
+}}, ethereum_types::{bytes::Bytes32, numeric::{Uint, U256}}};
+
+/// The gas of an `SLOAD` of `key` in the storage of the current account,
+/// which is cold until Berlin warms it.
+pub(crate) fn sload_gas(evm: &Evm, key: &Bytes32) -> Uint {
+    let rules = evm.env.rules;
+    if !rules.berlin {
+        if rules.istanbul {
+            GAS_SLOAD_ISTANBUL
+        } else if rules.tangerine_whistle {
+            GAS_SLOAD_TANGERINE_WHISTLE
+        } else {
+            GAS_SLOAD_FRONTIER
+        }
+    } else if evm.accessed_storage_keys.contains(&(evm.message.current_target.clone(), key.clone())) {
+        GAS_WARM_ACCESS
+    } else {
+        GAS_COLD_SLOAD
+    }
+}
+
+/// The gas of an `SSTORE` of `new_value` to `key` in the storage of the
+/// current account, and the change it makes to the refund counter.
+pub(crate) fn sstore_gas(evm: &Evm, key: &Bytes32, new_value: U256) -> (Uint, i64) {
+    let rules = evm.env.rules;
+    let state = &*evm.env.state;
+    let original_value = get_storage_original(state, &evm.message.current_target, key);
+    let current_value = get_storage(state, &evm.message.current_target, key);
+
+    // Before Istanbul (EIP-2200), and with Constantinople's EIP-1283 taken
+    // out again by Petersburg, only the current value counts.
+    if !rules.istanbul {
+        let gas_cost = if current_value.is_zero() && !new_value.is_zero() {
+            GAS_STORAGE_SET
+        } else {
+            GAS_STORAGE_UPDATE
+        };
+        let refund = if !current_value.is_zero() && new_value.is_zero() {
+            GAS_STORAGE_CLEAR_REFUND_FRONTIER as i64
+        } else {
+            0
+        };
+        return (gas_cost, refund);
+    }
+
+    // Until Berlin (EIP-2929) there is no cold access, and a warm one costs
+    // as much as an SLOAD.
+    let (cold_sload, warm_access) = if rules.berlin {
+        (GAS_COLD_SLOAD, GAS_WARM_ACCESS)
+    } else {
+        (0, GAS_SLOAD_ISTANBUL)
+    };
+    let clear_refund = if rules.london { GAS_STORAGE_CLEAR_REFUND } else { GAS_STORAGE_CLEAR_REFUND_FRONTIER };
+
+    let mut gas_cost: Uint = 0;
+    let mut refund: i64 = 0;
+
+    let slot = (evm.message.current_target.clone(), key.clone());
+    if rules.berlin && !evm.accessed_storage_keys.contains(&slot) {
+        gas_cost += cold_sload;
+    }
+
+    if original_value == current_value && current_value != new_value {
+        if original_value.is_zero() {
+            gas_cost += GAS_STORAGE_SET;
+        } else {
+            gas_cost += GAS_STORAGE_UPDATE - cold_sload;
+        }
+    } else {
+        gas_cost += warm_access;
+    }
+
+    // Refund Counter Calculation
+    if current_value != new_value {
+        if !original_value.is_zero() && !current_value.is_zero() && new_value.is_zero() {
+            // Storage is cleared for the first time in the transaction
+            refund += clear_refund as i64;
+        }
+
+        if !original_value.is_zero() && current_value.is_zero() {
+            // Gas refund issued earlier to be reversed
+            refund -= clear_refund as i64;
+        }
+
+        if original_value == new_value {
+            // Storage slot being restored to its original value
+            if original_value.is_zero() {
+                // Slot was originally empty and was SET earlier
+                refund += (GAS_STORAGE_SET - warm_access) as i64;
+            } else {
+                // Slot was originally non-empty and was UPDATED earlier
+                refund += (GAS_STORAGE_UPDATE - cold_sload - warm_access) as i64;
+            }
+        }
+    }
+
+    (gas_cost, refund)
+}
+
+/// """
+/// Loads to the stack, the value corresponding to a certain key from the
+/// storage of the current account.
+/// 
+/// Parameters
+/// ----------
+/// evm :
+///     The current EVM frame.
+/// """
+pub fn sload(evm: &mut Evm) -> Result<(), VmError> {
+    // STACK
+    let key = Bytes32(pop(&mut evm.stack)?.to_be_bytes());
+
+    // GAS
+    let gas_cost = sload_gas(evm, &key);
+    if evm.env.rules.berlin {
+        evm.accessed_storage_keys.insert((evm.message.current_target.clone(), key.clone()));
+    }
+    charge_gas(evm, gas_cost)?;
+
+    // OPERATION
+    let value = get_storage(evm.env.state, &evm.message.current_target, &key);
+
+    push(&mut evm.stack, value)?;
+
+    // PROGRAM COUNTER
+    evm.pc += 1;
+    Ok(())
+}
+
+/// """
+/// Stores a value at a certain key in the current context's storage.
+/// 
+/// Parameters
+/// ----------
+/// evm :
+///     The current EVM frame.
+/// """
+pub fn sstore(evm: &mut Evm) -> Result<(), VmError> {
+    // STACK
+    let key = Bytes32(pop(&mut evm.stack)?.to_be_bytes());
+    let new_value = pop(&mut evm.stack)?;
+    let rules = evm.env.rules;
+    if rules.istanbul && evm.gas_left <= GAS_CALL_STIPEND {
+        return Err(VmError::OutOfGasError);
+    }
+
+    let (gas_cost, refund) = sstore_gas(evm, &key, new_value);
+    if rules.berlin {
+        evm.accessed_storage_keys.insert((evm.message.current_target.clone(), key.clone()));
+    }
+    evm.refund_counter += refund;
+
+    charge_gas(evm, gas_cost)?;
+    if evm.message.is_static {
+        return Err(VmError::WriteInStaticContext);
+    }
+    set_storage(evm.env.state, &evm.message.current_target, &key, new_value);
+
+    // PROGRAM COUNTER
+    evm.pc += 1;
+    Ok(())
+}
+
+/// """
+/// Loads to the stack, the value corresponding to a certain key from the
+/// transient storage of the current account.
+/// 
+/// Parameters
+/// ----------
+/// evm :
+///     The current EVM frame.
+/// """
+pub fn tload(evm: &mut Evm) -> Result<(), VmError> {
+    // STACK
+    let key = Bytes32(pop(&mut evm.stack)?.to_be_bytes());
+
+    // GAS
+    charge_gas(evm, GAS_WARM_ACCESS)?;
+
+    // OPERATION
+    let value = get_transient_storage(&evm.env.transient_storage, &evm.message.current_target, &key);
+    push(&mut evm.stack, value)?;
+
+    // PROGRAM COUNTER
+    evm.pc += 1;
+    Ok(())
+}
+
+/// """
+/// Stores a value at a certain key in the current context's transient storage.
+/// 
+/// Parameters
+/// ----------
+/// evm :
+///     The current EVM frame.
+/// """
+pub fn tstore(evm: &mut Evm) -> Result<(), VmError> {
+    // STACK
+    let key = Bytes32(pop(&mut evm.stack)?.to_be_bytes());
+    let new_value = pop(&mut evm.stack)?;
+
+    // GAS
+    charge_gas(evm, GAS_WARM_ACCESS)?;
+    if evm.message.is_static {
+        return Err(VmError::WriteInStaticContext);
+    }
+    set_transient_storage(&mut evm.env.transient_storage, &evm.message.current_target, &key, new_value);
+
+    // PROGRAM COUNTER
+    evm.pc += 1;
+    Ok(())
+}
@@ -0,0 +1,732 @@
+//! Ethereum Virtual Machine (EVM) System Instructions
+//! ^^^^^^^^^^^^^^^^^^^^^^^^^^^^^^^^^^^^^^^^^^^^^^^^^^
+//! 
+//! .. contents:: Table of Contents
+//!     :backlinks: none
+//!     :local:
+//! 
+//! Introduction
+//! ------------
+//! 
+//! Implementations of the EVM system related instructions.
+
+use crate::ethereum::{cancun::{
+    fork_types::Address,
+    state::{
+        account_exists, account_exists_and_is_empty, account_has_code_or_nonce, account_has_storage, get_account,
+        increment_nonce, is_account_alive, is_account_created, move_ether, set_account_balance,
+    },
+    utils::{compute_contract_address, compute_create2_contract_address, to_address},
+    vm::{
+        exceptions::VmError,
+        gas::{
+            calculate_gas_extend_memory, calculate_message_call_gas, charge_gas, init_code_cost,
+            max_message_call_gas, MessageCallGas, GAS_CALL_FRONTIER, GAS_CALL_STIPEND,
+            GAS_CALL_TANGERINE_WHISTLE, GAS_CALL_VALUE, GAS_COLD_ACCOUNT_ACCESS, GAS_CREATE,
+            GAS_KECCAK256_WORD, GAS_NEW_ACCOUNT, GAS_SELF_DESTRUCT, GAS_SELF_DESTRUCT_NEW_ACCOUNT, GAS_WARM_ACCESS,
+            GAS_ZERO, REFUND_SELF_DESTRUCT,
+        },
+        incorporate_child_on_error, incorporate_child_on_success,
+        interpreter::{process_create_message, process_message, MAX_CODE_SIZE, STACK_DEPTH_LIMIT},
+        memory::{memory_extend, memory_read_bytes, memory_write},
+        stack::{pop, push},
+        tracing::CallStart,
+        ChildEvm, Evm, Message,
+    },
+}, ethereum_types::{bytes::{Bytes, Bytes32}, numeric::{Uint, U256}}, prague::eoa_delegation::access_delegation};
+
+use super::{words, Ops};
+
+/// A gas amount from the stack. Anything above 2^64 is more than can exist.
+fn gas_amount(value: U256) -> Uint {
+    value.to_uint().unwrap_or(Uint::MAX).min(u64::MAX as Uint)
+}
+
+/// Charge for access to `address`, marking it as warm. Before Berlin
+/// (EIP-2929) the cost of the call itself, which is the same for any
+/// address.
+fn access_gas_cost(evm: &mut Evm, address: &Address) -> Uint {
+    let rules = evm.env.rules;
+    if !rules.berlin {
+        return if rules.tangerine_whistle { GAS_CALL_TANGERINE_WHISTLE } else { GAS_CALL_FRONTIER };
+    }
+    if evm.accessed_addresses.contains(address) {
+        GAS_WARM_ACCESS
+    } else {
+        evm.accessed_addresses.insert(address.clone());
+        GAS_COLD_ACCOUNT_ACCESS
+    }
+}
+
+/// `calculate_message_call_gas`, or before Tangerine Whistle (EIP-150), when
+/// all of `gas` had to be available rather than 63/64 of what is left, `gas`
+/// itself.
+fn message_call_gas(evm: &Evm, value: U256, gas: Uint, memory_cost: Uint, extra_gas: Uint) -> MessageCallGas {
+    if evm.env.rules.tangerine_whistle {
+        return calculate_message_call_gas(value, gas, evm.gas_left, memory_cost, extra_gas, GAS_CALL_STIPEND);
+    }
+    let stipend = if value.is_zero() { 0 } else { GAS_CALL_STIPEND };
+    MessageCallGas { cost: gas + extra_gas, stipend: gas + stipend }
+}
+
+/// Report the child `message` of the current instruction to the tracer
+/// before it runs.
+fn trace_enter(evm: &mut Evm, message: &Message) {
+    if let Some(tracer) = &mut evm.env.tracer {
+        tracer.enter(&CallStart {
+            kind: Ops::from_u8(evm.code[evm.pc as usize]).unwrap_or(Ops::CALL),
+            from: &evm.message.current_target,
+            to: message.code_address.as_ref().unwrap_or(&message.current_target),
+            value: message.value,
+            gas: message.gas,
+            input: if message.target.is_none() { &message.code } else { &message.data },
+        });
+    }
+}
+
+/// Report the end of a child frame to the tracer.
+fn trace_exit(evm: &mut Evm, child_evm: &ChildEvm) {
+    if let Some(tracer) = &mut evm.env.tracer {
+        tracer.exit(&child_evm.output, child_evm.message.gas - child_evm.gas_left, child_evm.error.as_ref());
+    }
+}
+
+/// """
+/// Core logic used by the `CREATE*` family of opcodes.
+/// """
+fn generic_create(
+    evm: &mut Evm,
+    endowment: U256,
+    contract_address: Address,
+    memory_start_position: U256,
+    memory_size: U256,
+) -> Result<(), VmError> {
+    let call_data = memory_read_bytes(&evm.memory, memory_start_position, memory_size);
+    if evm.env.rules.shanghai && call_data.len() > 2 * MAX_CODE_SIZE {
+        return Err(VmError::OutOfGasError);
+    }
+
+    evm.accessed_addresses.insert(contract_address.clone());
+
+    let create_message_gas =
+        if evm.env.rules.tangerine_whistle { max_message_call_gas(evm.gas_left) } else { evm.gas_left };
+    evm.gas_left -= create_message_gas;
+    if evm.message.is_static {
+        return Err(VmError::WriteInStaticContext);
+    }
+    evm.return_data = Bytes::default();
+
+    let sender_address = evm.message.current_target.clone();
+    let sender = get_account(evm.env.state, &sender_address);
+
+    if sender.balance < endowment
+        || sender.nonce == u64::MAX as Uint
+        || evm.message.depth + 1 > STACK_DEPTH_LIMIT as Uint
+    {
+        evm.gas_left += create_message_gas;
+        push(&mut evm.stack, U256::ZERO)?;
+        return Ok(());
+    }
+
+    if account_has_code_or_nonce(evm.env.state, &contract_address)
+        || account_has_storage(evm.env.state, &contract_address)
+    {
+        increment_nonce(evm.env.state, &sender_address);
+        push(&mut evm.stack, U256::ZERO)?;
+        return Ok(());
+    }
+
+    increment_nonce(evm.env.state, &sender_address);
+
+    let child_message = Message {
+        caller: sender_address,
+        target: None,
+        gas: create_message_gas,
+        value: endowment,
+        data: Bytes::default(),
+        code: call_data,
+        current_target: contract_address,
+        depth: evm.message.depth + 1,
+        code_address: None,
+        should_transfer_value: true,
+        is_static: false,
+        accessed_addresses: evm.accessed_addresses.clone(),
+        accessed_storage_keys: evm.accessed_storage_keys.clone(),
+        accounts_to_delete: evm.accounts_to_delete.clone(),
+        disable_precompiles: false,
+    };
+    trace_enter(evm, &child_message);
+    let child_evm = process_create_message(child_message, evm.env)?.detach();
+    trace_exit(evm, &child_evm);
+
+    if child_evm.error.is_some() {
+        let output = child_evm.output.clone();
+        incorporate_child_on_error(evm, child_evm);
+        evm.return_data = output;
+        push(&mut evm.stack, U256::ZERO)?;
+    } else {
+        let contract_address = U256::from_be_slice(&child_evm.message.current_target[..]);
+        incorporate_child_on_success(evm, child_evm);
+        evm.return_data = Bytes::default();
+        push(&mut evm.stack, contract_address)?;
+    }
+    Ok(())
+}
+
+/// """
+/// Creates a new account with associated code.
+/// 
+/// Parameters
+/// ----------
+/// evm :
+///     The current EVM frame.
+/// """
+pub fn create(evm: &mut Evm) -> Result<(), VmError> {
+    // STACK
+    let endowment = pop(&mut evm.stack)?;
+    let memory_start_position = pop(&mut evm.stack)?;
+    let memory_size = pop(&mut evm.stack)?;
+
+    // GAS
+    let extend_memory = calculate_gas_extend_memory(
+        &evm.memory, &[(memory_start_position, memory_size)]
+    )?;
+    let init_code_gas = if evm.env.rules.shanghai { init_code_cost(words(memory_size)? * 32) } else { 0 };
+
+    charge_gas(evm, GAS_CREATE + extend_memory.cost + init_code_gas)?;
+
+    // OPERATION
+    memory_extend(&mut evm.memory, extend_memory.expand_by);
+    let nonce = get_account(evm.env.state, &evm.message.current_target).nonce;
+    let contract_address = compute_contract_address(&evm.message.current_target, nonce)
+        .map_err(|_| VmError::ExceptionalHalt)?;
+
+    generic_create(evm, endowment, contract_address, memory_start_position, memory_size)?;
+
+    // PROGRAM COUNTER
+    evm.pc += 1;
+    Ok(())
+}
+
+/// """
+/// Creates a new account with associated code.
+/// 
+/// It's similar to CREATE opcode except that the address of new account
+/// depends on the init_code instead of the nonce of sender.
+/// 
+/// Parameters
+/// ----------
+/// evm :
+///     The current EVM frame.
+/// """
+pub fn create2(evm: &mut Evm) -> Result<(), VmError> {
+    // STACK
+    let endowment = pop(&mut evm.stack)?;
+    let memory_start_position = pop(&mut evm.stack)?;
+    let memory_size = pop(&mut evm.stack)?;
+    let salt = Bytes32(pop(&mut evm.stack)?.to_be_bytes());
+
+    // GAS
+    let extend_memory = calculate_gas_extend_memory(
+        &evm.memory, &[(memory_start_position, memory_size)]
+    )?;
+    let call_data_words = words(memory_size)?;
+    let init_code_gas = if evm.env.rules.shanghai { init_code_cost(call_data_words * 32) } else { 0 };
+    charge_gas(
+        evm,
+        GAS_CREATE + GAS_KECCAK256_WORD * call_data_words + extend_memory.cost + init_code_gas,
+    )?;
+
+    // OPERATION
+    memory_extend(&mut evm.memory, extend_memory.expand_by);
+    let contract_address = compute_create2_contract_address(
+        &evm.message.current_target,
+        &salt,
+        &memory_read_bytes(&evm.memory, memory_start_position, memory_size),
+    );
+
+    generic_create(evm, endowment, contract_address, memory_start_position, memory_size)?;
+
+    // PROGRAM COUNTER
+    evm.pc += 1;
+    Ok(())
+}
+
+/// """
+/// Halts execution returning output data.
+/// 
+/// Parameters
+/// ----------
+/// evm :
+///     The current EVM frame.
+/// """
+pub fn return_(evm: &mut Evm) -> Result<(), VmError> {
+    // STACK
+    let memory_start_position = pop(&mut evm.stack)?;
+    let memory_size = pop(&mut evm.stack)?;
+
+    // GAS
+    let extend_memory = calculate_gas_extend_memory(
+        &evm.memory, &[(memory_start_position, memory_size)]
+    )?;
+
+    charge_gas(evm, GAS_ZERO + extend_memory.cost)?;
+
+    // OPERATION
+    memory_extend(&mut evm.memory, extend_memory.expand_by);
+    evm.output = memory_read_bytes(&evm.memory, memory_start_position, memory_size);
+
+    evm.running = false;
+
+    // PROGRAM COUNTER
+    evm.pc += 1;
+    Ok(())
+}
+
+/// """
+/// Perform the core logic of the `CALL*` family of opcodes.
+/// """
+#[allow(clippy::too_many_arguments)]
+fn generic_call(
+    evm: &mut Evm,
+    gas: Uint,
+    value: U256,
+    caller: Address,
+    to: Address,
+    code_address: Address,
+    should_transfer_value: bool,
+    is_staticcall: bool,
+    memory_input_start_position: U256,
+    memory_input_size: U256,
+    memory_output_start_position: U256,
+    memory_output_size: U256,
+    disable_precompiles: bool,
+) -> Result<(), VmError> {
+    evm.return_data = Bytes::default();
+
+    if evm.message.depth + 1 > STACK_DEPTH_LIMIT as Uint {
+        evm.gas_left += gas;
+        push(&mut evm.stack, U256::ZERO)?;
+        return Ok(());
+    }
+
+    let call_data = memory_read_bytes(&evm.memory, memory_input_start_position, memory_input_size);
+    let code = get_account(evm.env.state, &code_address).code.clone();
+    let child_message = Message {
+        caller,
+        target: Some(to.clone()),
+        gas,
+        value,
+        data: call_data,
+        code,
+        current_target: to,
+        depth: evm.message.depth + 1,
+        code_address: Some(code_address),
+        should_transfer_value,
+        is_static: is_staticcall || evm.message.is_static,
+        accessed_addresses: evm.accessed_addresses.clone(),
+        accessed_storage_keys: evm.accessed_storage_keys.clone(),
+        accounts_to_delete: evm.accounts_to_delete.clone(),
+        disable_precompiles,
+    };
+    trace_enter(evm, &child_message);
+    let child_evm = process_message(child_message, evm.env)?.detach();
+    trace_exit(evm, &child_evm);
+
+    let output = child_evm.output.clone();
+    if child_evm.error.is_some() {
+        incorporate_child_on_error(evm, child_evm);
+        evm.return_data = output.clone();
+        push(&mut evm.stack, U256::ZERO)?;
+    } else {
+        incorporate_child_on_success(evm, child_evm);
+        evm.return_data = output.clone();
+        push(&mut evm.stack, U256::ONE)?;
+    }
+
+    let actual_output_size = memory_output_size.min(U256::from(output.len() as u64));
+    memory_write(
+        &mut evm.memory,
+        memory_output_start_position,
+        &output[..actual_output_size.low_u64() as usize],
+    );
+    Ok(())
+}
+
+/// """
+/// Message-call into an account.
+/// 
+/// Parameters
+/// ----------
+/// evm :
+///     The current EVM frame.
+/// """
+pub fn call(evm: &mut Evm) -> Result<(), VmError> {
+    // STACK
+    let gas = gas_amount(pop(&mut evm.stack)?);
+    let to = to_address(pop(&mut evm.stack)?);
+    let value = pop(&mut evm.stack)?;
+    let memory_input_start_position = pop(&mut evm.stack)?;
+    let memory_input_size = pop(&mut evm.stack)?;
+    let memory_output_start_position = pop(&mut evm.stack)?;
+    let memory_output_size = pop(&mut evm.stack)?;
+
+    // GAS
+    let extend_memory = calculate_gas_extend_memory(
+        &evm.memory,
+        &[
+            (memory_input_start_position, memory_input_size),
+            (memory_output_start_position, memory_output_size),
+        ],
+    )?;
+
+    let access_gas_cost = access_gas_cost(evm, &to);
+    let (disable_precompiles, code_address, delegated_access_gas_cost) = access_delegation(evm, &to);
+    let access_gas_cost = access_gas_cost + delegated_access_gas_cost;
+
+    // Before Spurious Dragon (EIP-161) any call to an account which does not
+    // exist creates it.
+    let creates_account = if evm.env.rules.spurious_dragon {
+        !is_account_alive(evm.env.state, &to) && !value.is_zero()
+    } else {
+        !account_exists(evm.env.state, &to)
+    };
+    let create_gas_cost = if creates_account { GAS_NEW_ACCOUNT } else { 0 };
+    let transfer_gas_cost = if value.is_zero() { 0 } else { GAS_CALL_VALUE };
+    let message_call_gas = message_call_gas(
+        evm,
+        value,
+        gas,
+        extend_memory.cost,
+        access_gas_cost + create_gas_cost + transfer_gas_cost,
+    );
+    charge_gas(evm, message_call_gas.cost + extend_memory.cost)?;
+    if evm.message.is_static && !value.is_zero() {
+        return Err(VmError::WriteInStaticContext);
+    }
+    memory_extend(&mut evm.memory, extend_memory.expand_by);
+    let sender_balance = get_account(evm.env.state, &evm.message.current_target).balance;
+    if sender_balance < value {
+        push(&mut evm.stack, U256::ZERO)?;
+        evm.return_data = Bytes::default();
+        evm.gas_left += message_call_gas.stipend;
+    } else {
+        let caller = evm.message.current_target.clone();
+        generic_call(
+            evm,
+            message_call_gas.stipend,
+            value,
+            caller,
+            to,
+            code_address,
+            true,
+            false,
+            memory_input_start_position,
+            memory_input_size,
+            memory_output_start_position,
+            memory_output_size,
+            disable_precompiles,
+        )?;
+    }
+
+    // PROGRAM COUNTER
+    evm.pc += 1;
+    Ok(())
+}
+
+/// """
+/// Message-call into this account with alternative account’s code.
+/// 
+/// Parameters
+/// ----------
+/// evm :
+///     The current EVM frame.
+/// """
+pub fn callcode(evm: &mut Evm) -> Result<(), VmError> {
+    // STACK
+    let gas = gas_amount(pop(&mut evm.stack)?);
+    let code_address = to_address(pop(&mut evm.stack)?);
+    let value = pop(&mut evm.stack)?;
+    let memory_input_start_position = pop(&mut evm.stack)?;
+    let memory_input_size = pop(&mut evm.stack)?;
+    let memory_output_start_position = pop(&mut evm.stack)?;
+    let memory_output_size = pop(&mut evm.stack)?;
+
+    // GAS
+    let to = evm.message.current_target.clone();
+
+    let extend_memory = calculate_gas_extend_memory(
+        &evm.memory,
+        &[
+            (memory_input_start_position, memory_input_size),
+            (memory_output_start_position, memory_output_size),
+        ],
+    )?;
+
+    let access_gas_cost = access_gas_cost(evm, &code_address);
+    let (disable_precompiles, code_address, delegated_access_gas_cost) = access_delegation(evm, &code_address);
+    let access_gas_cost = access_gas_cost + delegated_access_gas_cost;
+
+    let transfer_gas_cost = if value.is_zero() { 0 } else { GAS_CALL_VALUE };
+    let message_call_gas = message_call_gas(
+        evm,
+        value,
+        gas,
+        extend_memory.cost,
+        access_gas_cost + transfer_gas_cost,
+    );
+    charge_gas(evm, message_call_gas.cost + extend_memory.cost)?;
+
+    // OPERATION
+    memory_extend(&mut evm.memory, extend_memory.expand_by);
+    let sender_balance = get_account(evm.env.state, &evm.message.current_target).balance;
+    if sender_balance < value {
+        push(&mut evm.stack, U256::ZERO)?;
+        evm.return_data = Bytes::default();
+        evm.gas_left += message_call_gas.stipend;
+    } else {
+        let caller = evm.message.current_target.clone();
+        generic_call(
+            evm,
+            message_call_gas.stipend,
+            value,
+            caller,
+            to,
+            code_address,
+            true,
+            false,
+            memory_input_start_position,
+            memory_input_size,
+            memory_output_start_position,
+            memory_output_size,
+            disable_precompiles,
+        )?;
+    }
+
+    // PROGRAM COUNTER
+    evm.pc += 1;
+    Ok(())
+}
+
+/// """
+/// Message-call into this account with an alternative account’s code, but
+/// persisting the current values for sender and value.
+/// 
+/// Parameters
+/// ----------
+/// evm :
+///     The current EVM frame.
+/// """
+pub fn delegatecall(evm: &mut Evm) -> Result<(), VmError> {
+    // STACK
+    let gas = gas_amount(pop(&mut evm.stack)?);
+    let code_address = to_address(pop(&mut evm.stack)?);
+    let memory_input_start_position = pop(&mut evm.stack)?;
+    let memory_input_size = pop(&mut evm.stack)?;
+    let memory_output_start_position = pop(&mut evm.stack)?;
+    let memory_output_size = pop(&mut evm.stack)?;
+
+    // GAS
+    let extend_memory = calculate_gas_extend_memory(
+        &evm.memory,
+        &[
+            (memory_input_start_position, memory_input_size),
+            (memory_output_start_position, memory_output_size),
+        ],
+    )?;
+
+    let access_gas_cost = access_gas_cost(evm, &code_address);
+    let (disable_precompiles, code_address, delegated_access_gas_cost) = access_delegation(evm, &code_address);
+    let access_gas_cost = access_gas_cost + delegated_access_gas_cost;
+
+    let message_call_gas = message_call_gas(
+        evm,
+        U256::ZERO,
+        gas,
+        extend_memory.cost,
+        access_gas_cost,
+    );
+    charge_gas(evm, message_call_gas.cost + extend_memory.cost)?;
+
+    // OPERATION
+    memory_extend(&mut evm.memory, extend_memory.expand_by);
+    let value = evm.message.value;
+    let caller = evm.message.caller.clone();
+    let to = evm.message.current_target.clone();
+    generic_call(
+        evm,
+        message_call_gas.stipend,
+        value,
+        caller,
+        to,
+        code_address,
+        false,
+        false,
+        memory_input_start_position,
+        memory_input_size,
+        memory_output_start_position,
+        memory_output_size,
+        disable_precompiles,
+    )?;
+
+    // PROGRAM COUNTER
+    evm.pc += 1;
+    Ok(())
+}
+
+/// """
+/// Message-call into an account.
+/// 
+/// Parameters
+/// ----------
+/// evm :
+///     The current EVM frame.
+/// """
+pub fn staticcall(evm: &mut Evm) -> Result<(), VmError> {
+    // STACK
+    let gas = gas_amount(pop(&mut evm.stack)?);
+    let to = to_address(pop(&mut evm.stack)?);
+    let memory_input_start_position = pop(&mut evm.stack)?;
+    let memory_input_size = pop(&mut evm.stack)?;
+    let memory_output_start_position = pop(&mut evm.stack)?;
+    let memory_output_size = pop(&mut evm.stack)?;
+
+    // GAS
+    let extend_memory = calculate_gas_extend_memory(
+        &evm.memory,
+        &[
+            (memory_input_start_position, memory_input_size),
+            (memory_output_start_position, memory_output_size),
+        ],
+    )?;
+
+    let access_gas_cost = access_gas_cost(evm, &to);
+    let (disable_precompiles, code_address, delegated_access_gas_cost) = access_delegation(evm, &to);
+    let access_gas_cost = access_gas_cost + delegated_access_gas_cost;
+
+    let message_call_gas = message_call_gas(
+        evm,
+        U256::ZERO,
+        gas,
+        extend_memory.cost,
+        access_gas_cost,
+    );
+    charge_gas(evm, message_call_gas.cost + extend_memory.cost)?;
+
+    // OPERATION
+    memory_extend(&mut evm.memory, extend_memory.expand_by);
+    let caller = evm.message.current_target.clone();
+    generic_call(
+        evm,
+        message_call_gas.stipend,
+        U256::ZERO,
+        caller,
+        to,
+        code_address,
+        true,
+        true,
+        memory_input_start_position,
+        memory_input_size,
+        memory_output_start_position,
+        memory_output_size,
+        disable_precompiles,
+    )?;
+
+    // PROGRAM COUNTER
+    evm.pc += 1;
+    Ok(())
+}
+
+/// """
+/// Halt execution and register account for later deletion.
+/// 
+/// Parameters
+/// ----------
+/// evm :
+///     The current EVM frame.
+/// """
+pub fn selfdestruct(evm: &mut Evm) -> Result<(), VmError> {
+    // STACK
+    let beneficiary = to_address(pop(&mut evm.stack)?);
+
+    // GAS
+    let rules = evm.env.rules;
+    let originator = evm.message.current_target.clone();
+    let mut gas_cost = if rules.tangerine_whistle { GAS_SELF_DESTRUCT } else { GAS_ZERO };
+    if rules.berlin && !evm.accessed_addresses.contains(&beneficiary) {
+        evm.accessed_addresses.insert(beneficiary.clone());
+        gas_cost += GAS_COLD_ACCOUNT_ACCESS;
+    }
+
+    // Before Spurious Dragon (EIP-161) sending nothing to an account which
+    // does not exist also creates it.
+    let creates_account = if rules.spurious_dragon {
+        !is_account_alive(evm.env.state, &beneficiary) && !get_account(evm.env.state, &originator).balance.is_zero()
+    } else {
+        rules.tangerine_whistle && !account_exists(evm.env.state, &beneficiary)
+    };
+    if creates_account {
+        gas_cost += GAS_SELF_DESTRUCT_NEW_ACCOUNT;
+    }
+
+    // Until London (EIP-3529) the first self-destruct of an account is
+    // refunded. A child frame starts from the accounts of its parent, which
+    // include those of its earlier successful siblings.
+    if !rules.london && !evm.accounts_to_delete.contains(&originator) {
+        evm.refund_counter += REFUND_SELF_DESTRUCT as i64;
+    }
+
+    charge_gas(evm, gas_cost)?;
+    if evm.message.is_static {
+        return Err(VmError::WriteInStaticContext);
+    }
+
+    // OPERATION
+    let originator_balance = get_account(evm.env.state, &originator).balance;
+    move_ether(evm.env.state, &originator, &beneficiary, originator_balance);
+
+    // From Cancun (EIP-6780) only an account created in this transaction is
+    // deleted. Until then, or if it is its own beneficiary, its balance is
+    // burnt.
+    if !rules.restricted_selfdestruct || is_account_created(evm.env.state, &originator) {
+        set_account_balance(evm.env.state, &originator, U256::ZERO);
+        evm.accounts_to_delete.insert(originator);
+    }
+
+    // Mark the beneficiary as touched, so that it is removed if it is empty.
+    if rules.spurious_dragon && account_exists_and_is_empty(evm.env.state, &beneficiary) {
+        evm.touched_accounts.insert(beneficiary);
+    }
+
+    // HALT the execution
+    evm.running = false;
+
+    // PROGRAM COUNTER
+    // Not required, as execution has halted.
+    Ok(())
+}
+
+/// """
+/// Stop execution and revert state changes, without consuming all provided gas
+/// and also has the ability to return a reason
+/// 
+/// Parameters
+/// ----------
+/// evm :
+///     The current EVM frame.
+/// """
+pub fn revert(evm: &mut Evm) -> Result<(), VmError> {
+    // STACK
+    let memory_start_index = pop(&mut evm.stack)?;
+    let size = pop(&mut evm.stack)?;
+
+    // GAS
+    let extend_memory = calculate_gas_extend_memory(&evm.memory, &[(memory_start_index, size)])?;
+
+    charge_gas(evm, extend_memory.cost)?;
+
+    // OPERATION
+    memory_extend(&mut evm.memory, extend_memory.expand_by);
+    evm.output = memory_read_bytes(&evm.memory, memory_start_index, size);
+    Err(VmError::Revert)
+}
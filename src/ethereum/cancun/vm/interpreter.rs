@@ -12,27 +12,50 @@
 //! A straightforward interpreter that executes EVM code.
 //! """
 
-use std::collections::BTreeSet;
-
-use crate::ethereum::{cancun::{blocks::Log, fork_types::Address}, ethereum_types::numeric::{Uint, U256}, exceptions::Exception};
-
-use super::{Environment, Evm, Message};
+use std::{collections::{BTreeMap, BTreeSet}, sync::Arc};
+
+use crate::ethereum::{cancun::{
+    blocks::Log,
+    fork_types::Address,
+    state::{
+        account_exists_and_is_empty, account_has_code_or_nonce, account_has_storage,
+        begin_transaction, commit_transaction, destroy_storage, get_account, increment_nonce,
+        mark_account_created, move_ether, rollback_transaction, set_code, touch_account,
+    },
+}, ethereum_types::{bytes::Bytes, numeric::{Uint, U256}}, exceptions::Exception, prague::eoa_delegation::get_delegated_code_address};
+
+use super::{
+    eof::{is_eof, validate_container},
+    exceptions::VmError,
+    gas::{charge_gas, GAS_BASE, GAS_CODE_DEPOSIT, GAS_JUMPDEST, GAS_VERY_LOW},
+    instructions::{op_implementation, Ops},
+    precompiled_contracts::pre_compiled_contract,
+    runtime::get_valid_jump_destinations,
+    stack,
+    tracing::Step,
+    CallFrame, Environment, Evm, Message,
+};
 
 
 pub const STACK_DEPTH_LIMIT : usize = 1024;
 pub const MAX_CODE_SIZE : usize = 0x6000;
+/// The stack items a frame has room for before its stack grows.
+const INITIAL_STACK_CAPACITY : usize = 32;
 
 /// """
 /// Output of a particular message call
 /// 
 /// Contains the following:
 /// 
-///       1. `gas_left`: remaining gas after execution.
-///       2. `refund_counter`: gas to refund after execution.
-///       3. `logs`: list of `Log` generated during execution.
-///       4. `accounts_to_delete`: Contracts which have self-destructed.
-///       5. `touched_accounts`: Accounts that have been touched.
-///       6. `error`: The error from the execution if any.
+/// ```text
+///   1. `gas_left`: remaining gas after execution.
+///   2. `refund_counter`: gas to refund after execution.
+///   3. `logs`: list of `Log` generated during execution.
+///   4. `accounts_to_delete`: Contracts which have self-destructed.
+///   5. `touched_accounts`: Accounts that have been touched.
+///   6. `error`: The error from the execution if any.
+///   7. `return_data`: The output of the execution.
+/// ```
 /// """
 pub struct MessageCallOutput {
     pub gas_left: Uint,
@@ -40,7 +63,8 @@ pub struct MessageCallOutput {
     pub logs: Vec<Log>,
     pub accounts_to_delete: BTreeSet<Address>,
     pub touched_accounts: BTreeSet<Address>,
-    pub error: Option<Exception>,
+    pub error: Option<VmError>,
+    pub return_data: Bytes,
 }
 
 /// """
@@ -61,57 +85,68 @@ pub struct MessageCallOutput {
 ///     Output of the message call
 /// """
 pub fn process_message_call(
-    message: &Message, env: &Environment
+    mut message: Message, env: &mut Environment
 ) -> Result<MessageCallOutput, Exception> {
-    // let evm = if message.target == Bytes0(b"") {
-    //     let is_collision = account_has_code_or_nonce(
-    //         env.state, message.current_target
-    //     ) || account_has_storage(env.state, message.current_target);
-    //     if is_collision {
-    //         return Ok(MessageCallOutput{
-    //             Uint(0), U256(0), tuple(), set(), set(), AddressCollision()
-
-    //         });
-    //     } else {
-    //         process_create_message(message, env)?
-    //     }
-    // } else {
-    //     let evm = process_message(message, env)?;
-    //     if account_exists_and_is_empty(env.state, Address(message.target)):
-    //         evm.touched_accounts.add(Address(message.target))
-    //     evm
-    // };
-
-    // let (logs,
-    // accounts_to_delete,
-    // touched_accounts, 
-    // refund_counter) = if evm.error {
-    //     (Vec::new(),
-    //     BTreeSet::new(),
-    //     BTreeSet::new(),
-    //     U256::from(0_u32))
-    // } else {
-    //     (evm.logs,
-    //     evm.accounts_to_delete,
-    //     evm.touched_accounts,
-    //     U256(evm.refund_counter))
-    // };
-
-    // let tx_end = TransactionEnd {
-    //     int(message.gas) - int(evm.gas_left), evm.output, evm.error
-    // };
-    // evm_trace(evm, tx_end);
-
-    // Ok(MessageCallOutput {
-    //     gas_left: evm.gas_left,
-    //     refund_counter,
-    //     logs,
-    //     accounts_to_delete,
-    //     touched_accounts,
-    //     error: evm.error,
-    // })
-
-    todo!()
+    let target = message.target.clone();
+    let evm = match target {
+        None => {
+            let is_collision = account_has_code_or_nonce(env.state, &message.current_target)
+                || account_has_storage(env.state, &message.current_target);
+            if is_collision {
+                return Ok(MessageCallOutput {
+                    gas_left: 0,
+                    refund_counter: U256::ZERO,
+                    logs: Vec::new(),
+                    accounts_to_delete: BTreeSet::new(),
+                    touched_accounts: BTreeSet::new(),
+                    error: Some(VmError::AddressCollision),
+                    return_data: Bytes::default(),
+                });
+            }
+            process_create_message(message, env)
+        }
+        Some(target) => {
+            // A call to an account delegated with EIP-7702 runs the code
+            // delegated to.
+            let delegated = get_delegated_code_address(&message.code).filter(|_| env.rules.set_code);
+            if let Some(delegated) = delegated {
+                message.disable_precompiles = true;
+                message.accessed_addresses.insert(delegated.clone());
+                message.code = get_account(env.state, &delegated).code.clone();
+                message.code_address = Some(delegated);
+            }
+            let mut evm = process_message(message, env);
+            if let Ok(evm) = &mut evm {
+                if account_exists_and_is_empty(evm.env.state, &target) {
+                    evm.touched_accounts.insert(target);
+                }
+            }
+            evm
+        }
+    };
+    let evm = evm.map_err(|_| Exception::EthereumException("stack depth limit reached"))?;
+
+    if evm.error.is_some() {
+        Ok(MessageCallOutput {
+            gas_left: evm.gas_left,
+            refund_counter: U256::ZERO,
+            logs: Vec::new(),
+            accounts_to_delete: BTreeSet::new(),
+            touched_accounts: BTreeSet::new(),
+            error: evm.error,
+            return_data: evm.output,
+        })
+    } else {
+        Ok(MessageCallOutput {
+            gas_left: evm.gas_left,
+            refund_counter: U256::from_i128(evm.refund_counter as i128),
+            logs: evm.logs,
+            accounts_to_delete: evm.accounts_to_delete,
+            touched_accounts: evm.touched_accounts,
+            error: None,
+            return_data: evm.output,
+        })
+    }
 }
 
 
@@ -130,49 +165,71 @@ pub fn process_message_call(
 /// evm: :py:class:`~ethereum.cancun.vm.Evm`
 ///     Items containing execution specific objects.
 /// """
-pub fn process_create_message<'a>(message: &Message, env: &'a Environment) -> Evm<'a> {
-    // # take snapshot of state before processing the message
-    // begin_transaction(env.state, env.transient_storage)
-
-    // # If the address where the account is being created has storage, it is
-    // # destroyed. This can only happen in the following highly unlikely
-    // # circumstances:
-    // # * The address created by a `CREATE` call collides with a subsequent
-    // #   `CREATE` or `CREATE2` call.
-    // # * The first `CREATE` happened before Spurious Dragon and left empty
-    // #   code.
-    // destroy_storage(env.state, message.current_target)
-
-    // # In the previously mentioned edge case the preexisting storage is ignored
-    // # for gas refund purposes. In order to do this we must track created
-    // # accounts.
-    // mark_account_created(env.state, message.current_target)
-
-    // increment_nonce(env.state, message.current_target)
-    // evm = process_message(message, env)
-    // if not evm.error:
-    //     contract_code = evm.output
-    //     contract_code_gas = Uint(len(contract_code)) * GAS_CODE_DEPOSIT
-    //     try:
-    //         if len(contract_code) > 0:
-    //             if contract_code[0] == 0xEF:
-    //                 raise InvalidContractPrefix
-    //         charge_gas(evm, contract_code_gas)
-    //         if len(contract_code) > MAX_CODE_SIZE:
-    //             raise OutOfGasError
-    //     except ExceptionalHalt as error:
-    //         rollback_transaction(env.state, env.transient_storage)
-    //         evm.gas_left = Uint(0)
-    //         evm.output = b""
-    //         evm.error = error
-    //     else:
-    //         set_code(env.state, message.current_target, contract_code)
-    //         commit_transaction(env.state, env.transient_storage)
-    // else:
-    //     rollback_transaction(env.state, env.transient_storage)
-    // return evm
-
-    todo!()
+pub fn process_create_message<'a, 'e>(
+    message: Message, env: &'a mut Environment<'e>
+) -> Result<Evm<'a, 'e>, VmError> {
+    // take snapshot of state before processing the message
+    begin_transaction(env.state, &mut env.transient_storage);
+
+    // If the address where the account is being created has storage, it is
+    // destroyed. This can only happen in the following highly unlikely
+    // circumstances:
+    // * The address created by a `CREATE` call collides with a subsequent
+    //   `CREATE` or `CREATE2` call.
+    // * The first `CREATE` happened before Spurious Dragon and left empty
+    //   code.
+    destroy_storage(env.state, &message.current_target);
+
+    // In the previously mentioned edge case the preexisting storage is ignored
+    // for gas refund purposes. In order to do this we must track created
+    // accounts.
+    mark_account_created(env.state, &message.current_target);
+
+    // Contracts start with nonce 1 from Spurious Dragon (EIP-161).
+    let rules = env.rules;
+    if rules.spurious_dragon {
+        increment_nonce(env.state, &message.current_target);
+    }
+    let mut evm = process_message(message, env)?;
+    if evm.error.is_none() {
+        let contract_code = evm.output.clone();
+        let contract_code_gas = contract_code.len() as Uint * GAS_CODE_DEPOSIT;
+        let result = if rules.eof && is_eof(&contract_code) {
+            validate_container(&contract_code, rules).map(|_| ())
+        } else if rules.london && contract_code.first() == Some(&0xEF) {
+            Err(VmError::InvalidContractPrefix)
+        } else {
+            Ok(())
+        };
+        let result = result.and_then(|()| charge_gas(&mut evm, contract_code_gas)).and_then(|()| {
+            if rules.spurious_dragon && contract_code.len() > MAX_CODE_SIZE {
+                Err(VmError::OutOfGasError)
+            } else {
+                Ok(())
+            }
+        });
+        match result {
+            // In Frontier, a contract whose code cannot be paid for is
+            // created without code.
+            Err(VmError::OutOfGasError) if !rules.homestead => {
+                evm.output = Bytes::default();
+                commit_transaction(evm.env.state, &mut evm.env.transient_storage);
+            }
+            Err(error) => {
+                rollback_transaction(evm.env.state, &mut evm.env.transient_storage);
+                evm.gas_left = 0;
+                evm.output = Bytes::default();
+                evm.error = Some(error);
+            }
+            Ok(()) => {
+                set_code(evm.env.state, &evm.message.current_target, contract_code);
+                commit_transaction(evm.env.state, &mut evm.env.transient_storage);
+            }
+        }
+    } else {
+        rollback_transaction(evm.env.state, &mut evm.env.transient_storage);
+    }
+    Ok(evm)
 }
 
 
@@ -191,30 +248,50 @@ pub fn process_create_message<'a>(message: &Message, env: &'a Environment) -> Ev
 /// evm: :py:class:`~ethereum.cancun.vm.Evm`
 ///     Items containing execution specific objects
 /// """
-pub fn process_message<'a>(message: &Message, env: &'a Environment) -> Evm<'a> {
-    // if message.depth > STACK_DEPTH_LIMIT:
-    //     raise StackDepthLimitError("Stack depth limit reached")
-
-    // # take snapshot of state before processing the message
-    // begin_transaction(env.state, env.transient_storage)
-
-    // touch_account(env.state, message.current_target)
-
-    // if message.should_transfer_value and message.value != 0:
-    //     move_ether(
-    //         env.state, message.caller, message.current_target, message.value
-    //     )
-
-    // evm = execute_code(message, env)
-    // if evm.error:
-    //     # revert state to the last saved checkpoint
-    //     # since the message call resulted in an error
-    //     rollback_transaction(env.state, env.transient_storage)
-    // else:
-    //     commit_transaction(env.state, env.transient_storage)
-    // return evm
-
-    todo!()
+pub fn process_message<'a, 'e>(
+    message: Message, env: &'a mut Environment<'e>
+) -> Result<Evm<'a, 'e>, VmError> {
+    if message.depth > STACK_DEPTH_LIMIT as Uint {
+        return Err(VmError::StackDepthLimitError);
+    }
+
+    // take snapshot of state before processing the message
+    begin_transaction(env.state, &mut env.transient_storage);
+
+    touch_account(env.state, &message.current_target);
+
+    if message.should_transfer_value && !message.value.is_zero() {
+        move_ether(env.state, &message.caller, &message.current_target, message.value);
+    }
+
+    let frame = env.call_frames.as_mut().map(|frames| {
+        frames.push(CallFrame {
+            depth: message.depth,
+            caller: message.caller.clone(),
+            current_target: message.current_target.clone(),
+            code_address: message.code_address.clone().unwrap_or_else(|| message.current_target.clone()),
+            is_create: message.target.is_none(),
+            value: message.value,
+            gas: message.gas,
+            gas_used: 0,
+            error: None,
+        });
+        frames.len() - 1
+    });
+
+    let evm = execute_code(message, env);
+    if let (Some(index), Some(frames)) = (frame, &mut evm.env.call_frames) {
+        frames[index].gas_used = evm.message.gas - evm.gas_left;
+        frames[index].error = evm.error.clone();
+    }
+    if evm.error.is_some() {
+        // revert state to the last saved checkpoint
+        // since the message call resulted in an error
+        rollback_transaction(evm.env.state, &mut evm.env.transient_storage);
+    } else {
+        commit_transaction(evm.env.state, &mut evm.env.transient_storage);
+    }
+    Ok(evm)
 }
 
 
@@ -233,58 +310,194 @@ pub fn process_message<'a>(message: &Message, env: &'a Environment) -> Evm<'a> {
 /// evm: `ethereum.vm.EVM`
 ///     Items containing execution specific objects
 /// """
-pub fn execute_code<'a>(message: &Message, env: &'a Environment) -> Evm<'a> {
-    // code = message.code
-    // valid_jump_destinations = get_valid_jump_destinations(code)
-
-    // evm = Evm(
-    //     pc=Uint(0),
-    //     stack=[],
-    //     memory=bytearray(),
-    //     code=code,
-    //     gas_left=message.gas,
-    //     env=env,
-    //     valid_jump_destinations=valid_jump_destinations,
-    //     logs=(),
-    //     refund_counter=0,
-    //     running=True,
-    //     message=message,
-    //     output=b"",
-    //     accounts_to_delete=set(),
-    //     touched_accounts=set(),
-    //     return_data=b"",
-    //     error=None,
-    //     accessed_addresses=message.accessed_addresses,
-    //     accessed_storage_keys=message.accessed_storage_keys,
-    // )
-    // try:
-    //     if evm.message.code_address in PRE_COMPILED_CONTRACTS:
-    //         evm_trace(evm, PrecompileStart(evm.message.code_address))
-    //         PRE_COMPILED_CONTRACTS[evm.message.code_address](evm)
-    //         evm_trace(evm, PrecompileEnd())
-    //         return evm
+pub fn execute_code<'a, 'e>(message: Message, env: &'a mut Environment<'e>) -> Evm<'a, 'e> {
+    // Of an EOF container only the code section runs. Init code which is
+    // not a valid container fails like an invalid instruction.
+    let (code, invalid) = if env.rules.eof && is_eof(&message.code) {
+        match validate_container(&message.code, env.rules) {
+            Ok(container) => (Bytes(container.code.to_vec()), None),
+            Err(error) => (Bytes::default(), Some(error)),
+        }
+    } else {
+        (message.code.clone(), None)
+    };
+    let valid_jump_destinations = match env.code_cache {
+        Some(cache) => cache.get_or_insert_with(&code, get_valid_jump_destinations),
+        None => Arc::new(get_valid_jump_destinations(&code)),
+    };
+
+    let mut evm = Evm {
+        pc: 0,
+        // Room for the stack of most code, so that few frames reallocate it.
+        stack: Vec::with_capacity(INITIAL_STACK_CAPACITY),
+        memory: Vec::new(),
+        code,
+        gas_left: message.gas,
+        env,
+        valid_jump_destinations,
+        logs: Vec::new(),
+        refund_counter: 0,
+        running: true,
+        output: Bytes::default(),
+        accounts_to_delete: message.accounts_to_delete.clone(),
+        touched_accounts: BTreeSet::new(),
+        return_data: Bytes::default(),
+        error: None,
+        accessed_addresses: message.accessed_addresses.clone(),
+        accessed_storage_keys: message.accessed_storage_keys.clone(),
+        message,
+    };
+    let result = match invalid {
+        Some(error) => Err(error),
+        None => run(&mut evm),
+    };
+    match result {
+        Ok(()) => (),
+        Err(VmError::Revert) => {
+            evm.error = Some(VmError::Revert);
+        }
+        Err(error) => {
+            evm.gas_left = 0;
+            evm.output = Bytes::default();
+            evm.error = Some(error);
+        }
+    }
+    evm
+}
 
-    //     while evm.running and evm.pc < ulen(evm.code):
-    //         try:
-    //             op = Ops(evm.code[evm.pc])
-    //         except ValueError:
-    //             raise InvalidOpcode(evm.code[evm.pc])
+/// Run a precompile or the code of `evm` until it stops or fails.
+fn run(evm: &mut Evm) -> Result<(), VmError> {
+    let rules = evm.env.rules;
+    let precompile = evm.message.code_address.as_ref()
+        .filter(|address| !evm.message.disable_precompiles && rules.pre_compiled_contracts().contains(address))
+        .and_then(pre_compiled_contract);
+    if let Some(precompile) = precompile {
+        return precompile(evm);
+    }
+    match evm.env.executor {
+        Some(executor) => executor.run(evm),
+        None => interpret(evm),
+    }
+}
 
-    //         evm_trace(evm, OpStart(op))
-    //         op_implementation[op](evm)
-    //         evm_trace(evm, OpEnd())
+/// Run the code of `evm` in the interpreter until it stops or fails.
+pub fn interpret(evm: &mut Evm) -> Result<(), VmError> {
+    if evm.env.tracer.as_ref().is_some_and(|tracer| tracer.traces_steps()) {
+        return interpret_traced(evm);
+    }
+    let rules = evm.env.rules;
+    // The gas left is kept in a local while instructions which only move
+    // stack items run, and written to `evm` for any other instruction and
+    // when the frame stops.
+    let mut gas_left = evm.gas_left;
+    let result = loop {
+        if !evm.running || evm.pc >= evm.code.len() as Uint {
+            break Ok(());
+        }
+        let byte = evm.code[evm.pc as usize];
+        let Some(op) = Ops::from_u8(byte).filter(|op| rules.allows(*op)) else {
+            break Err(VmError::InvalidOpcode);
+        };
+        let step = match stack_op(evm, &mut gas_left, byte) {
+            Some(step) => step,
+            None => {
+                evm.gas_left = gas_left;
+                let step = op_implementation(op, evm);
+                gas_left = evm.gas_left;
+                step
+            }
+        };
+        if let Err(error) = step {
+            break Err(error);
+        }
+    };
+    evm.gas_left = gas_left;
+    result
+}
 
-    //     evm_trace(evm, EvmStop(Ops.STOP))
+/// Run `byte` as `pop`, `push_n`, `dup_n`, `swap_n` or `jumpdest` would,
+/// charging `gas_left` rather than `evm.gas_left`, or return `None` if it
+/// is any other instruction.
+#[inline(always)]
+fn stack_op(evm: &mut Evm, gas_left: &mut Uint, byte: u8) -> Option<Result<(), VmError>> {
+    let mut charge = |amount: Uint| -> Result<(), VmError> {
+        *gas_left = gas_left.checked_sub(amount).ok_or(VmError::OutOfGasError)?;
+        Ok(())
+    };
+    let pc = evm.pc as usize;
+    let step = match byte {
+        // POP
+        0x50 => evm.stack.pop().ok_or(VmError::StackUnderflowError).and_then(|_| charge(GAS_BASE)).map(|()| 1),
+        // JUMPDEST
+        0x5b => charge(GAS_JUMPDEST).map(|()| 1),
+        // PUSH0 to PUSH32
+        0x5f..=0x7f => {
+            let num_bytes = (byte - 0x5f) as usize;
+            charge(if num_bytes == 0 { GAS_BASE } else { GAS_VERY_LOW }).and_then(|()| {
+                // Zero padded past the end of the code, as `buffer_read` is.
+                let mut data = [0; 32];
+                let available = &evm.code[pc + 1..(pc + 1 + num_bytes).min(evm.code.len())];
+                data[32 - num_bytes..][..available.len()].copy_from_slice(available);
+                stack::push(&mut evm.stack, U256::from_be_bytes(data)).map(|()| 1 + num_bytes)
+            })
+        }
+        // DUP1 to DUP16
+        0x80..=0x8f => charge(GAS_VERY_LOW).and_then(|()| {
+            let item_number = (byte - 0x80) as usize;
+            let item = evm.stack.len().checked_sub(1 + item_number).ok_or(VmError::StackUnderflowError)?;
+            let value = evm.stack[item];
+            stack::push(&mut evm.stack, value).map(|()| 1)
+        }),
+        // SWAP1 to SWAP16
+        0x90..=0x9f => charge(GAS_VERY_LOW).and_then(|()| {
+            let item_number = (byte - 0x8f) as usize;
+            if item_number >= evm.stack.len() {
+                return Err(VmError::StackUnderflowError);
+            }
+            let top = evm.stack.len() - 1;
+            evm.stack.swap(top, top - item_number);
+            Ok(1)
+        }),
+        _ => return None,
+    };
+    Some(step.map(|len| evm.pc += len as Uint))
+}
 
-    // except ExceptionalHalt as error:
-    //     evm_trace(evm, OpException(error))
-    //     evm.gas_left = Uint(0)
-    //     evm.output = b""
-    //     evm.error = error
-    // except Revert as error:
-    //     evm_trace(evm, OpException(error))
-    //     evm.error = error
-    // return evm
+/// `interpret`, reporting every instruction to `env.tracer`.
+fn interpret_traced(evm: &mut Evm) -> Result<(), VmError> {
+    let rules = evm.env.rules;
+    while evm.running && evm.pc < evm.code.len() as Uint {
+        let op = Ops::from_u8(evm.code[evm.pc as usize])
+            .filter(|op| rules.allows(*op))
+            .ok_or(VmError::InvalidOpcode)?;
+        let (pc, gas) = (evm.pc, evm.gas_left);
+        if let Some(tracer) = &mut evm.env.tracer {
+            tracer.before_step(&evm.stack, &evm.memory);
+        }
+        let result = op_implementation(op, evm);
+        trace_op(evm, pc, op, gas, &result);
+        result?;
+    }
+    Ok(())
+}
 
-    todo!()
+/// Report the execution of `op` to `env.tracer`.
+fn trace_op(evm: &mut Evm, pc: Uint, op: Ops, gas: Uint, result: &Result<(), VmError>) {
+    let gas_cost = match result {
+        Err(error) if *error != VmError::Revert => gas,
+        _ => gas.saturating_sub(evm.gas_left),
+    };
+    let step = Step {
+        pc,
+        op,
+        gas,
+        gas_cost,
+        depth: evm.message.depth + 1,
+        return_data: &evm.return_data,
+        refund: evm.refund_counter,
+        error: result.as_ref().err(),
+    };
+    if let Some(tracer) = &mut evm.env.tracer {
+        tracer.step(&step);
+    }
 }
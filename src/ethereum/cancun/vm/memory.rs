@@ -0,0 +1,120 @@
+//! Ethereum Virtual Machine (EVM) Memory
+//! ^^^^^^^^^^^^^^^^^^^^^^^^^^^^^^^^^^^^^
+//! 
+//! .. contents:: Table of Contents
+//!     :backlinks: none
+//!     :local:
+//! 
+//! Introduction
+//! ------------
+//! 
+//! EVM memory operations.
+
+use crate::ethereum::ethereum_types::{bytes::Bytes, numeric::{Uint, U256}};
+
+/// Memory is allocated a page at a time, so that growing it a word at a
+/// time does not reallocate and copy it each time.
+pub const PAGE_SIZE: usize = 4096;
+
+/// An offset into a buffer, saturating at `usize::MAX`.
+///
+/// Offsets into memory have already been paid for and always fit, offsets
+/// into other buffers may be arbitrarily large and read as zeros.
+fn index(value: U256) -> usize {
+    value.to_uint().ok().and_then(|v| usize::try_from(v).ok()).unwrap_or(usize::MAX)
+}
+
+/// """
+/// Grow memory by `expand_by` zeroed bytes, allocating whole pages.
+/// 
+/// Parameters
+/// ----------
+/// memory :
+///     Memory contents of the EVM.
+/// expand_by :
+///     The number of bytes to add, already paid for.
+/// """
+pub fn memory_extend(memory: &mut Vec<u8>, expand_by: Uint) {
+    if expand_by == 0 {
+        return;
+    }
+    let size = memory.len() + expand_by as usize;
+    if size > memory.capacity() {
+        memory.reserve_exact(size.next_multiple_of(PAGE_SIZE) - memory.len());
+    }
+    memory.resize(size, 0);
+}
+
+/// """
+/// Writes to memory.
+/// 
+/// Parameters
+/// ----------
+/// memory :
+///     Memory contents of the EVM.
+/// start_position :
+///     Starting pointer to the memory.
+/// value :
+///     Data to write to memory.
+/// """
+pub fn memory_write(memory: &mut [u8], start_position: U256, value: &[u8]) {
+    if value.is_empty() {
+        return;
+    }
+    let start = index(start_position);
+    memory[start..start + value.len()].copy_from_slice(value);
+}
+
+/// """
+/// Read bytes from memory.
+/// 
+/// Parameters
+/// ----------
+/// memory :
+///     Memory contents of the EVM.
+/// start_position :
+///     Starting pointer to the memory.
+/// size :
+///     Size of the data that needs to be read from `start_position`.
+/// 
+/// Returns
+/// -------
+/// data_bytes :
+///     Data read from memory.
+/// """
+pub fn memory_read_bytes(memory: &[u8], start_position: U256, size: U256) -> Bytes {
+    if size.is_zero() {
+        return Bytes::default();
+    }
+    let start = index(start_position);
+    Bytes(memory[start..start + index(size)].to_vec())
+}
+
+/// """
+/// Read bytes from a buffer. Padding with zeros if necessary.
+/// 
+/// Parameters
+/// ----------
+/// buffer :
+///     Memory contents of the EVM.
+/// start_position :
+///     Starting pointer to the memory.
+/// size :
+///     Size of the data that needs to be read from `start_position`.
+/// 
+/// Returns
+/// -------
+/// data_bytes :
+///     Data read from memory.
+/// """
+pub fn buffer_read(buffer: &[u8], start_position: U256, size: U256) -> Bytes {
+    let size = index(size);
+    let mut data = vec![0; size];
+    let start = index(start_position);
+    if start < buffer.len() {
+        let available = &buffer[start..];
+        let len = available.len().min(size);
+        data[..len].copy_from_slice(&available[..len]);
+    }
+    Bytes(data)
+}
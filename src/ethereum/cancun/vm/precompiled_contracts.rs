@@ -0,0 +1,68 @@
+//! https://github.com/ethereum/execution-specs/blob/master/src/ethereum/cancun/vm/precompiled_contracts/__init__.py
+//!
+//! Addresses of precompiled contracts and mappings to their
+//! implementations.
+
+use crate::ethereum::cancun::fork_types::Address;
+
+use super::{exceptions::VmError, Evm};
+
+pub mod alt_bn128;
+pub mod blake2f;
+pub mod ecrecover;
+pub mod identity;
+pub mod modexp;
+pub mod point_evaluation;
+pub mod ripemd160;
+pub mod sha256;
+
+const fn hex_to_address(n: u8) -> Address {
+    let mut bytes = [0; 20];
+    bytes[19] = n;
+    Address::from_be_bytes(bytes)
+}
+
+pub const ECRECOVER_ADDRESS: Address = hex_to_address(0x01);
+pub const SHA256_ADDRESS: Address = hex_to_address(0x02);
+pub const RIPEMD160_ADDRESS: Address = hex_to_address(0x03);
+pub const IDENTITY_ADDRESS: Address = hex_to_address(0x04);
+pub const MODEXP_ADDRESS: Address = hex_to_address(0x05);
+pub const ALT_BN128_ADD_ADDRESS: Address = hex_to_address(0x06);
+pub const ALT_BN128_MUL_ADDRESS: Address = hex_to_address(0x07);
+pub const ALT_BN128_PAIRING_CHECK_ADDRESS: Address = hex_to_address(0x08);
+pub const BLAKE2F_ADDRESS: Address = hex_to_address(0x09);
+pub const POINT_EVALUATION_ADDRESS: Address = hex_to_address(0x0a);
+
+pub const PRE_COMPILED_CONTRACTS: [Address; 10] = [
+    ECRECOVER_ADDRESS,
+    SHA256_ADDRESS,
+    RIPEMD160_ADDRESS,
+    IDENTITY_ADDRESS,
+    MODEXP_ADDRESS,
+    ALT_BN128_ADD_ADDRESS,
+    ALT_BN128_MUL_ADDRESS,
+    ALT_BN128_PAIRING_CHECK_ADDRESS,
+    BLAKE2F_ADDRESS,
+    POINT_EVALUATION_ADDRESS,
+];
+
+/// Implementation of a precompiled contract, running on the message of `evm`.
+pub type PrecompiledContract = fn(&mut Evm) -> Result<(), VmError>;
+
+/// The precompiled contract at `address`, if any.
+pub fn pre_compiled_contract(address: &Address) -> Option<PrecompiledContract> {
+    let contract: PrecompiledContract = match address {
+        a if *a == ECRECOVER_ADDRESS => ecrecover::ecrecover,
+        a if *a == SHA256_ADDRESS => sha256::sha256,
+        a if *a == RIPEMD160_ADDRESS => ripemd160::ripemd160,
+        a if *a == IDENTITY_ADDRESS => identity::identity,
+        a if *a == MODEXP_ADDRESS => modexp::modexp,
+        a if *a == ALT_BN128_ADD_ADDRESS => alt_bn128::alt_bn128_add,
+        a if *a == ALT_BN128_MUL_ADDRESS => alt_bn128::alt_bn128_mul,
+        a if *a == ALT_BN128_PAIRING_CHECK_ADDRESS => alt_bn128::alt_bn128_pairing_check,
+        a if *a == BLAKE2F_ADDRESS => blake2f::blake2f,
+        a if *a == POINT_EVALUATION_ADDRESS => point_evaluation::point_evaluation,
+        _ => return None,
+    };
+    Some(contract)
+}
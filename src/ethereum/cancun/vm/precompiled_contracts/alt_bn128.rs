@@ -0,0 +1,213 @@
+//! Ethereum Virtual Machine (EVM) ALT_BN128 CONTRACTS
+//! ^^^^^^^^^^^^^^^^^^^^^^^^^^^^^^^^^^^^^^^^^^^^^^^^^^
+//! 
+//! .. contents:: Table of Contents
+//!     :backlinks: none
+//!     :local:
+//! 
+//! Introduction
+//! ------------
+//! 
+//! Implementation of the ALT_BN128 precompiled contracts.
+
+use crate::ethereum::{cancun::vm::{
+    exceptions::VmError,
+    gas::{
+        charge_gas, GAS_BN254_ADD, GAS_BN254_ADD_BYZANTIUM, GAS_BN254_MUL, GAS_BN254_MUL_BYZANTIUM, GAS_BN254_PAIRING,
+        GAS_BN254_PAIRING_BYZANTIUM, GAS_BN254_PAIRING_PER_POINT, GAS_BN254_PAIRING_PER_POINT_BYZANTIUM,
+    },
+    memory::buffer_read,
+    Evm,
+}, crypto::alt_bn128::{pairing_check, G1, G2}, ethereum_types::{bytes::Bytes, numeric::U256}};
+
+/// The point of the 64 bytes of input at `start`, padded with zeros. A
+/// coordinate which is not below the modulus, or a point which is not on the
+/// curve, fails the call.
+fn read_g1(data: &[u8], start: u32) -> Result<G1, VmError> {
+    let bytes = buffer_read(data, U256::from(start), U256::from(64_u32));
+    G1::from_be_bytes(bytes.0[..].try_into().unwrap()).ok_or(VmError::OutOfGasError)
+}
+
+/// """
+/// The ALT_BN128 addition precompiled contract.
+/// 
+/// Parameters
+/// ----------
+/// evm :
+///     The current EVM frame.
+/// """
+pub fn alt_bn128_add(evm: &mut Evm) -> Result<(), VmError> {
+    let data = evm.message.data.clone();
+
+    // GAS
+    charge_gas(evm, if evm.env.rules.istanbul { GAS_BN254_ADD } else { GAS_BN254_ADD_BYZANTIUM })?;
+
+    // OPERATION
+    let p0 = read_g1(&data, 0)?;
+    let p1 = read_g1(&data, 64)?;
+    evm.output = Bytes(p0.add(&p1).to_be_bytes().to_vec());
+    Ok(())
+}
+
+/// """
+/// The ALT_BN128 multiplication precompiled contract.
+/// 
+/// Parameters
+/// ----------
+/// evm :
+///     The current EVM frame.
+/// """
+pub fn alt_bn128_mul(evm: &mut Evm) -> Result<(), VmError> {
+    let data = evm.message.data.clone();
+
+    // GAS
+    charge_gas(evm, if evm.env.rules.istanbul { GAS_BN254_MUL } else { GAS_BN254_MUL_BYZANTIUM })?;
+
+    // OPERATION
+    let p0 = read_g1(&data, 0)?;
+    let n = buffer_read(&data, U256::from(64_u32), U256::from(32_u32));
+    let limbs: Vec<u64> = n.0.rchunks(8).map(|limb| u64::from_be_bytes(limb.try_into().unwrap())).collect();
+    evm.output = Bytes(p0.mul_limbs(&limbs).to_be_bytes().to_vec());
+    Ok(())
+}
+
+/// """
+/// The ALT_BN128 pairing check precompiled contract.
+/// 
+/// Parameters
+/// ----------
+/// evm :
+///     The current EVM frame.
+/// """
+pub fn alt_bn128_pairing_check(evm: &mut Evm) -> Result<(), VmError> {
+    let data = evm.message.data.clone();
+
+    // GAS
+    let pairs = (data.len() / 192) as u128;
+    let gas_cost = if evm.env.rules.istanbul {
+        GAS_BN254_PAIRING_PER_POINT * pairs + GAS_BN254_PAIRING
+    } else {
+        GAS_BN254_PAIRING_PER_POINT_BYZANTIUM * pairs + GAS_BN254_PAIRING_BYZANTIUM
+    };
+    charge_gas(evm, gas_cost)?;
+
+    // OPERATION
+    if data.len() % 192 != 0 {
+        return Err(VmError::OutOfGasError);
+    }
+    let mut points = Vec::with_capacity(data.len() / 192);
+    for pair in data.chunks(192) {
+        let p = G1::from_be_bytes(pair[..64].try_into().unwrap()).ok_or(VmError::OutOfGasError)?;
+        let q = G2::from_be_bytes(pair[64..].try_into().unwrap()).ok_or(VmError::OutOfGasError)?;
+        points.push((p, q));
+    }
+    let mut output = vec![0; 32];
+    output[31] = pairing_check(&points) as u8;
+    evm.output = Bytes(output);
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::BTreeSet;
+
+    use crate::ethereum::{
+        cancun::{
+            fork_types::Address,
+            state::{State, TransientStorage},
+            utils::prepare_message,
+            vm::{exceptions::VmError, interpreter::{process_message_call, MessageCallOutput}, Environment},
+        },
+        crypto::alt_bn128::{G1, G2},
+        ethereum_types::{bytes::Bytes, numeric::U256},
+        forks::ExecutionRules,
+        utils::hexadecimal::hex_to_bytes,
+    };
+
+    const GAS: u128 = 1_000_000;
+
+    fn call(rules: ExecutionRules, precompile: u8, data: Vec<u8>) -> MessageCallOutput {
+        let caller = Address::from_be_bytes([0xca; 20]);
+        let mut target = [0; 20];
+        target[19] = precompile;
+        let mut state = State::default();
+        let mut env = Environment {
+            caller: caller.clone(),
+            origin: caller.clone(),
+            gas_limit: GAS,
+            ..Environment::new(&mut state, rules)
+        };
+        let target = Address::from_be_bytes(target);
+        let message = prepare_message(
+            caller,
+            Some(target),
+            U256::ZERO,
+            Bytes(data),
+            GAS,
+            &env,
+            None,
+            true,
+            false,
+            BTreeSet::new(),
+            BTreeSet::new(),
+        )
+        .unwrap();
+        process_message_call(message, &mut env).unwrap()
+    }
+
+    /// `x.c1 || x.c0 || y.c1 || y.c0`.
+    fn g2_bytes(q: &G2) -> Vec<u8> {
+        let (x, y) = q.to_affine().unwrap();
+        [x.c1, x.c0, y.c1, y.c0].iter().flat_map(|c| c.to_be_bytes()).collect()
+    }
+
+    #[test]
+    fn add_and_mul() {
+        let g = G1::generator().to_be_bytes();
+        let two_g = hex_to_bytes("030644e72e131a029b85045b68181585d97816a916871ca8d3c208c16d87cfd315ed738c0e0a7c92e7845f96b2ae9c0a68a6a449e3538fc7ff3ebf7a5a18a2c4").unwrap();
+
+        let output = call(ExecutionRules::CANCUN, 6, [g, g].concat());
+        assert_eq!((output.error, output.return_data), (None, two_g.clone()));
+        assert_eq!(GAS - output.gas_left, 150);
+        let output = call(ExecutionRules::BYZANTIUM, 6, g.to_vec());
+        assert_eq!(output.return_data, Bytes(g.to_vec()));
+        assert_eq!(GAS - output.gas_left, 500);
+
+        let mut two = [0; 32];
+        two[31] = 2;
+        let output = call(ExecutionRules::CANCUN, 7, [&g[..], &two].concat());
+        assert_eq!((output.error, output.return_data), (None, two_g));
+        assert_eq!(GAS - output.gas_left, 6000);
+        // Nothing times a point is the point at infinity.
+        let output = call(ExecutionRules::CANCUN, 7, Vec::new());
+        assert_eq!(output.return_data, Bytes(vec![0; 64]));
+
+        let mut off_curve = g;
+        off_curve[63] = 3;
+        assert_eq!(call(ExecutionRules::CANCUN, 6, off_curve.to_vec()).error, Some(VmError::OutOfGasError));
+        assert_eq!(call(ExecutionRules::CANCUN, 7, off_curve.to_vec()).error, Some(VmError::OutOfGasError));
+    }
+
+    #[test]
+    fn pairing_check() {
+        let (p, q) = (G1::generator(), G2::generator());
+        let one = |output: &MessageCallOutput| output.return_data.0[31];
+
+        let output = call(ExecutionRules::CANCUN, 8, Vec::new());
+        assert_eq!((&output.error, one(&output)), (&None, 1));
+        assert_eq!(GAS - output.gas_left, 45000);
+
+        let pairs = [&p.to_be_bytes()[..], &g2_bytes(&q), &p.neg().to_be_bytes(), &g2_bytes(&q)].concat();
+        let output = call(ExecutionRules::BYZANTIUM, 8, pairs.clone());
+        assert_eq!(one(&output), 1);
+        assert_eq!(GAS - output.gas_left, 2 * 80000 + 100000);
+        let output = call(ExecutionRules::CANCUN, 8, pairs[..192].to_vec());
+        assert_eq!((&output.error, one(&output)), (&None, 0));
+        assert_eq!(GAS - output.gas_left, 34000 + 45000);
+
+        assert_eq!(call(ExecutionRules::CANCUN, 8, pairs[..191].to_vec()).error, Some(VmError::OutOfGasError));
+        let mut off_twist = pairs[..192].to_vec();
+        off_twist[191] ^= 1;
+        assert_eq!(call(ExecutionRules::CANCUN, 8, off_twist).error, Some(VmError::OutOfGasError));
+    }
+}
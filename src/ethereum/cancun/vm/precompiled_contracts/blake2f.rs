@@ -0,0 +1,41 @@
+//! Ethereum Virtual Machine (EVM) Blake2 PRECOMPILED CONTRACT
+//! ^^^^^^^^^^^^^^^^^^^^^^^^^^^^^^^^^^^^^^^^^^^^^^^^^^^^^^^^^^
+//! 
+//! .. contents:: Table of Contents
+//!     :backlinks: none
+//!     :local:
+//! 
+//! Introduction
+//! ------------
+//! 
+//! Implementation of the `Blake2` precompiled contract.
+
+use crate::ethereum::{cancun::vm::{
+    exceptions::VmError,
+    gas::{charge_gas, GAS_BLAKE2_PER_ROUND},
+    Evm,
+}, crypto::blake2::{compress, get_blake2_parameters}, ethereum_types::{bytes::Bytes, numeric::Uint}};
+
+/// """
+/// Writes the Blake2 hash to output.
+/// 
+/// Parameters
+/// ----------
+/// evm :
+///     The current EVM frame.
+/// """
+pub fn blake2f(evm: &mut Evm) -> Result<(), VmError> {
+    let Ok(data) = <&[u8; 213]>::try_from(&evm.message.data[..]) else {
+        return Err(VmError::InvalidParameter);
+    };
+
+    let params = get_blake2_parameters(data);
+
+    charge_gas(evm, GAS_BLAKE2_PER_ROUND * params.rounds as Uint)?;
+    if params.f > 1 {
+        return Err(VmError::InvalidParameter);
+    }
+
+    evm.output = Bytes(compress(&params).to_vec());
+    Ok(())
+}
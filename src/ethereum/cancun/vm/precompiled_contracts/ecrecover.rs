@@ -0,0 +1,61 @@
+//! Ethereum Virtual Machine (EVM) ECRECOVER PRECOMPILED CONTRACT
+//! ^^^^^^^^^^^^^^^^^^^^^^^^^^^^^^^^^^^^^^^^^^^^^^^^^^^^^^^^^^^^^
+//! 
+//! .. contents:: Table of Contents
+//!     :backlinks: none
+//!     :local:
+//! 
+//! Introduction
+//! ------------
+//! 
+//! Implementation of the ECRECOVER precompiled contract.
+
+use crate::ethereum::{cancun::vm::{
+    exceptions::VmError,
+    gas::{charge_gas, GAS_ECRECOVER},
+    memory::buffer_read,
+    Evm,
+}, crypto::{eliptic_curve::{secp256k1_recover, SECP256K1N}, hash::{keccak256, Hash32}}, ethereum_types::{bytes::Bytes, numeric::U256}};
+
+/// """
+/// Decrypts the address using elliptic curve DSA recovery mechanism and writes
+/// the address to output.
+/// 
+/// Parameters
+/// ----------
+/// evm :
+///     The current EVM frame.
+/// """
+pub fn ecrecover(evm: &mut Evm) -> Result<(), VmError> {
+    let data = &evm.message.data;
+    let word = |start: u32| buffer_read(data, U256::from(start), U256::from(32_u32));
+
+    let message_hash = Hash32(word(0).0.try_into().unwrap());
+    let v = U256::from_be_slice(&word(32));
+    let r = U256::from_be_slice(&word(64));
+    let s = U256::from_be_slice(&word(96));
+
+    // GAS
+    charge_gas(evm, GAS_ECRECOVER)?;
+
+    // OPERATION
+    if v != U256::from(27_u32) && v != U256::from(28_u32) {
+        return Ok(());
+    }
+    if r.is_zero() || r >= SECP256K1N {
+        return Ok(());
+    }
+    if s.is_zero() || s >= SECP256K1N {
+        return Ok(());
+    }
+
+    let Ok(public_key) = secp256k1_recover(r, s, v - U256::from(27_u32), message_hash) else {
+        return Ok(());
+    };
+
+    let address = &keccak256(&public_key)[12..32];
+    let mut padded_address = vec![0; 12];
+    padded_address.extend_from_slice(address);
+    evm.output = Bytes(padded_address);
+    Ok(())
+}
@@ -0,0 +1,37 @@
+//! Ethereum Virtual Machine (EVM) IDENTITY PRECOMPILED CONTRACT
+//! ^^^^^^^^^^^^^^^^^^^^^^^^^^^^^^^^^^^^^^^^^^^^^^^^^^^^^^^^^^^^
+//! 
+//! .. contents:: Table of Contents
+//!     :backlinks: none
+//!     :local:
+//! 
+//! Introduction
+//! ------------
+//! 
+//! Implementation of the `IDENTITY` precompiled contract.
+
+use crate::ethereum::{cancun::vm::{
+    exceptions::VmError,
+    gas::{charge_gas, GAS_IDENTITY, GAS_IDENTITY_WORD},
+    Evm,
+}, ethereum_types::numeric::Uint, utils::numeric::ceil32};
+
+/// """
+/// Writes the message data to output.
+/// 
+/// Parameters
+/// ----------
+/// evm :
+///     The current EVM frame.
+/// """
+pub fn identity(evm: &mut Evm) -> Result<(), VmError> {
+    let data = evm.message.data.clone();
+
+    // GAS
+    let word_count = ceil32(data.len() as Uint) / 32;
+    charge_gas(evm, GAS_IDENTITY + GAS_IDENTITY_WORD * word_count)?;
+
+    // OPERATION
+    evm.output = data;
+    Ok(())
+}
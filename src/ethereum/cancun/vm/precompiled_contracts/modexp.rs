@@ -0,0 +1,335 @@
+//! Ethereum Virtual Machine (EVM) MODEXP PRECOMPILED CONTRACT
+//! ^^^^^^^^^^^^^^^^^^^^^^^^^^^^^^^^^^^^^^^^^^^^^^^^^^^^^^^^^^
+//! 
+//! .. contents:: Table of Contents
+//!     :backlinks: none
+//!     :local:
+//! 
+//! Introduction
+//! ------------
+//! 
+//! Implementation of the `MODEXP` precompiled contract.
+
+use std::cmp::Ordering;
+
+use crate::ethereum::{cancun::vm::{
+    exceptions::VmError,
+    gas::charge_gas,
+    memory::buffer_read,
+    Evm,
+}, ethereum_types::{bytes::Bytes, numeric::{Uint, U256}}, forks::ExecutionRules};
+
+const GQUADDIVISOR: Uint = 3;
+/// Before Berlin (EIP-2565).
+const GQUADDIVISOR_BYZANTIUM: Uint = 20;
+
+/// """
+/// Calculates `(base**exp) % modulus` for arbitrary sized `base`, `exp` and.
+/// `modulus`. The return value is the same length as the modulus.
+/// """
+pub fn modexp(evm: &mut Evm) -> Result<(), VmError> {
+    let data = &evm.message.data;
+
+    // GAS
+    let base_length = U256::from_be_slice(&buffer_read(data, U256::ZERO, U256::from(32_u32)));
+    let exp_length = U256::from_be_slice(&buffer_read(data, U256::from(32_u32), U256::from(32_u32)));
+    let modulus_length = U256::from_be_slice(&buffer_read(data, U256::from(64_u32), U256::from(32_u32)));
+
+    let (exp_start, overflow) = U256::from(96_u32).overflowing_add(base_length);
+    let exp_head = if overflow {
+        U256::ZERO
+    } else {
+        U256::from_be_slice(&buffer_read(data, exp_start, exp_length.min(U256::from(32_u32))))
+    };
+
+    let cost = gas_cost(base_length, modulus_length, exp_length, exp_head, evm.env.rules)?;
+    charge_gas(evm, cost)?;
+
+    // Having paid for them, all lengths are small.
+    let (base_length, exp_length, modulus_length) = (
+        base_length.low_u64() as usize,
+        exp_length.low_u64() as usize,
+        modulus_length.low_u64() as usize,
+    );
+    if base_length == 0 && modulus_length == 0 {
+        evm.output = Bytes::default();
+        return Ok(());
+    }
+
+    let data = &evm.message.data;
+    let read = |start: usize, len: usize| {
+        buffer_read(data, U256::from(start as u64), U256::from(len as u64))
+    };
+    let base = read(96, base_length);
+    let exp = read(96 + base_length, exp_length);
+    let modulus = read(96 + base_length + exp_length, modulus_length);
+
+    evm.output = Bytes(mod_pow(&base, &exp, &modulus));
+    Ok(())
+}
+
+/// """
+/// Estimate the complexity of performing a modular exponentiation.
+/// 
+/// Parameters
+/// ----------
+/// 
+/// base_length :
+///     Length of the array representing the base integer.
+/// 
+/// modulus_length :
+///     Length of the array representing the modulus integer.
+/// 
+/// Returns
+/// -------
+/// 
+/// complexity : `Uint`
+///     Complexity of performing the operation.
+/// """
+fn complexity(base_length: Uint, modulus_length: Uint) -> Uint {
+    let max_length = Uint::max(base_length, modulus_length);
+    let words = max_length.div_ceil(8);
+    words * words
+}
+
+/// `complexity` before Berlin (EIP-198), in bytes rather than words.
+fn complexity_byzantium(base_length: Uint, modulus_length: Uint) -> Uint {
+    let x = Uint::max(base_length, modulus_length);
+    if x <= 64 {
+        x * x
+    } else if x <= 1024 {
+        x * x / 4 + 96 * x - 3072
+    } else {
+        x * x / 16 + 480 * x - 199680
+    }
+}
+
+/// """
+/// Calculate the number of iterations required to perform a modular
+/// exponentiation.
+/// 
+/// Parameters
+/// ----------
+/// 
+/// exponent_length :
+///     Length of the array representing the exponent integer.
+/// 
+/// exponent_head :
+///     First 32 bytes of the exponent (with leading zero padding if it is
+///     shorter than 32 bytes), as an unsigned integer.
+/// 
+/// Returns
+/// -------
+/// 
+/// iterations : `Uint`
+///     Number of iterations.
+/// """
+fn iterations(exponent_length: Uint, exponent_head: U256) -> Uint {
+    let bit_length = exponent_head.bits().saturating_sub(1) as Uint;
+    let count = if exponent_length <= 32 {
+        bit_length
+    } else {
+        8 * (exponent_length - 32) + bit_length
+    };
+    Uint::max(count, 1)
+}
+
+/// """
+/// Calculate the gas cost of performing a modular exponentiation.
+/// 
+/// Parameters
+/// ----------
+/// 
+/// base_length :
+///     Length of the array representing the base integer.
+/// 
+/// modulus_length :
+///     Length of the array representing the modulus integer.
+/// 
+/// exponent_length :
+///     Length of the array representing the exponent integer.
+/// 
+/// exponent_head :
+///     First 32 bytes of the exponent (with leading zero padding if it is
+///     shorter than 32 bytes), as an unsigned integer.
+/// 
+/// rules :
+///     Execution rules of the fork, for the pricing before Berlin.
+/// 
+/// Returns
+/// -------
+/// 
+/// gas_cost : `Uint`
+///     Gas required for performing the operation.
+/// """
+fn gas_cost(
+    base_length: U256,
+    modulus_length: U256,
+    exponent_length: U256,
+    exponent_head: U256,
+    rules: ExecutionRules,
+) -> Result<Uint, VmError> {
+    // Lengths beyond 2^64 bytes cost more gas than can exist.
+    let length = |l: U256| match l.to_uint() {
+        Ok(l) if l <= u64::MAX as Uint => Ok(l),
+        _ => Err(VmError::OutOfGasError),
+    };
+    let (base_length, modulus_length) = (length(base_length)?, length(modulus_length)?);
+    let iteration_count = iterations(length(exponent_length)?, exponent_head);
+    if !rules.berlin {
+        let multiplication_complexity = complexity_byzantium(base_length, modulus_length);
+        return Ok(multiplication_complexity.checked_mul(iteration_count).ok_or(VmError::OutOfGasError)?
+            / GQUADDIVISOR_BYZANTIUM);
+    }
+    let multiplication_complexity = complexity(base_length, modulus_length);
+    let cost = multiplication_complexity
+        .checked_mul(iteration_count)
+        .ok_or(VmError::OutOfGasError)?
+        / GQUADDIVISOR;
+    Ok(Uint::max(200, cost))
+}
+
+/// `base ** exp % modulus` on big endian byte strings, returning a result as
+/// long as `modulus`.
+fn mod_pow(base: &[u8], exp: &[u8], modulus: &[u8]) -> Vec<u8> {
+    let m = from_be(modulus);
+    if m.is_empty() {
+        return vec![0; modulus.len()];
+    }
+    let base = rem(&from_be(base), &m);
+    let mut result = rem(&[1], &m);
+    for byte in exp {
+        for i in (0..8).rev() {
+            result = rem(&mul(&result, &result), &m);
+            if byte >> i & 1 != 0 {
+                result = rem(&mul(&result, &base), &m);
+            }
+        }
+    }
+    to_be(&result, modulus.len())
+}
+
+/// Little endian base 2^32 digits of a big endian byte string, without
+/// leading zeros.
+fn from_be(bytes: &[u8]) -> Vec<u32> {
+    let mut digits: Vec<u32> = bytes
+        .rchunks(4)
+        .map(|c| c.iter().fold(0, |acc, b| acc << 8 | *b as u32))
+        .collect();
+    trim(&mut digits);
+    digits
+}
+
+/// Big endian bytes of `digits`, left padded to `len` bytes.
+fn to_be(digits: &[u32], len: usize) -> Vec<u8> {
+    let bytes: Vec<u8> = digits.iter().rev().flat_map(|d| d.to_be_bytes()).collect();
+    let significant = &bytes[bytes.len().min(bytes.iter().position(|b| *b != 0).unwrap_or(bytes.len()))..];
+    let mut res = vec![0; len - significant.len()];
+    res.extend_from_slice(significant);
+    res
+}
+
+fn trim(digits: &mut Vec<u32>) {
+    while digits.last() == Some(&0) {
+        digits.pop();
+    }
+}
+
+fn cmp(a: &[u32], b: &[u32]) -> Ordering {
+    a.len().cmp(&b.len()).then_with(|| a.iter().rev().cmp(b.iter().rev()))
+}
+
+fn mul(a: &[u32], b: &[u32]) -> Vec<u32> {
+    let mut res = vec![0_u32; a.len() + b.len()];
+    for (i, x) in a.iter().enumerate() {
+        let mut carry = 0_u64;
+        for (j, y) in b.iter().enumerate() {
+            let t = *x as u64 * *y as u64 + res[i + j] as u64 + carry;
+            res[i + j] = t as u32;
+            carry = t >> 32;
+        }
+        res[i + b.len()] = carry as u32;
+    }
+    trim(&mut res);
+    res
+}
+
+/// `a % m` for a trimmed non-zero `m`, using Knuth's algorithm D.
+fn rem(a: &[u32], m: &[u32]) -> Vec<u32> {
+    let mut a = a.to_vec();
+    trim(&mut a);
+    if cmp(&a, m) == Ordering::Less {
+        return a;
+    }
+    let n = m.len();
+    if n == 1 {
+        let d = m[0] as u64;
+        let r = a.iter().rev().fold(0, |r, x| ((r << 32) | *x as u64) % d);
+        let mut res = vec![r as u32];
+        trim(&mut res);
+        return res;
+    }
+
+    // Normalize so that the top digit of the divisor has its high bit set.
+    let s = m[n - 1].leading_zeros();
+    let shl = |x: &[u32]| -> Vec<u32> {
+        let mut res = Vec::with_capacity(x.len() + 1);
+        let mut carry = 0;
+        for d in x {
+            res.push((d << s) | carry);
+            carry = if s == 0 { 0 } else { d >> (32 - s) };
+        }
+        res.push(carry);
+        res
+    };
+    let v = &shl(m)[..n];
+    let mut u = shl(&a);
+
+    let b = 1_u64 << 32;
+    for j in (0..u.len() - n).rev() {
+        let num = (u[j + n] as u64) << 32 | u[j + n - 1] as u64;
+        let mut qhat = num / v[n - 1] as u64;
+        let mut rhat = num % v[n - 1] as u64;
+        while qhat >= b || qhat * v[n - 2] as u64 > (rhat << 32 | u[j + n - 2] as u64) {
+            qhat -= 1;
+            rhat += v[n - 1] as u64;
+            if rhat >= b {
+                break;
+            }
+        }
+
+        // Multiply and subtract.
+        let mut borrow = 0_i64;
+        let mut carry = 0_u64;
+        for i in 0..n {
+            let p = qhat * v[i] as u64 + carry;
+            carry = p >> 32;
+            let t = u[i + j] as i64 - borrow - (p & 0xffff_ffff) as i64;
+            u[i + j] = t as u32;
+            borrow = (t < 0) as i64;
+        }
+        let t = u[j + n] as i64 - borrow - carry as i64;
+        u[j + n] = t as u32;
+
+        // Add back if the estimate was one too large.
+        if t < 0 {
+            let mut carry = 0_u64;
+            for i in 0..n {
+                let sum = u[i + j] as u64 + v[i] as u64 + carry;
+                u[i + j] = sum as u32;
+                carry = sum >> 32;
+            }
+            u[j + n] = u[j + n].wrapping_add(carry as u32);
+        }
+    }
+
+    // Unnormalize the remainder.
+    let mut res: Vec<u32> = (0..n)
+        .map(|i| {
+            let high = if s == 0 || i + 1 >= n { 0 } else { u[i + 1] << (32 - s) };
+            (u[i] >> s) | high
+        })
+        .collect();
+    trim(&mut res);
+    res
+}
@@ -0,0 +1,59 @@
+//! Ethereum Virtual Machine (EVM) POINT EVALUATION PRECOMPILED CONTRACT
+//! ^^^^^^^^^^^^^^^^^^^^^^^^^^^^^^^^^^^^^^^^^^^^^^^^^^^^^^^^^^^^^^^^^^^^
+//! 
+//! .. contents:: Table of Contents
+//!     :backlinks: none
+//!     :local:
+//! 
+//! Introduction
+//! ------------
+//! 
+//! Implementation of the POINT EVALUATION precompiled contract.
+
+use crate::ethereum::{cancun::vm::{
+    exceptions::VmError,
+    gas::{charge_gas, GAS_POINT_EVALUATION},
+    Evm,
+}, crypto::{bls12_381::Fr, kzg::{kzg_commitment_to_versioned_hash, trusted_setup, verify_kzg_proof, FIELD_ELEMENTS_PER_BLOB}}, ethereum_types::{bytes::{Bytes, Bytes32, Bytes48}, numeric::U256}};
+
+/// """
+/// A pre-compile that verifies a KZG proof which claims that a blob
+/// (represented by a commitment) evaluates to a given value at a given point.
+/// 
+/// Parameters
+/// ----------
+/// evm :
+///     The current EVM frame.
+/// """
+pub fn point_evaluation(evm: &mut Evm) -> Result<(), VmError> {
+    let data = evm.message.data.clone();
+    if data.len() != 192 {
+        return Err(VmError::KZGProofError);
+    }
+
+    let versioned_hash = &data[..32];
+    let z = Bytes32(data[32..64].try_into().unwrap());
+    let y = Bytes32(data[64..96].try_into().unwrap());
+    let commitment = Bytes48(data[96..144].try_into().unwrap());
+    let proof = Bytes48(data[144..192].try_into().unwrap());
+
+    // GAS
+    charge_gas(evm, GAS_POINT_EVALUATION)?;
+    if kzg_commitment_to_versioned_hash(&commitment).0 != versioned_hash {
+        return Err(VmError::KZGProofError);
+    }
+
+    // Verify KZG proof with z and y in big endian format
+    let Ok(true) = verify_kzg_proof(trusted_setup(), &commitment, &z, &y, &proof) else {
+        return Err(VmError::KZGProofError);
+    };
+
+    // Return FIELD_ELEMENTS_PER_BLOB and BLS_MODULUS as padded 32 byte
+    // big endian values
+    let mut output = U256::from(FIELD_ELEMENTS_PER_BLOB as u64).to_be_bytes().to_vec();
+    let mut modulus = Fr::MODULUS;
+    modulus.reverse();
+    output.extend(modulus.iter().flat_map(|limb| limb.to_be_bytes()));
+    evm.output = Bytes(output);
+    Ok(())
+}
@@ -0,0 +1,40 @@
+//! Ethereum Virtual Machine (EVM) RIPEMD160 PRECOMPILED CONTRACT
+//! ^^^^^^^^^^^^^^^^^^^^^^^^^^^^^^^^^^^^^^^^^^^^^^^^^^^^^^^^^^^^^
+//! 
+//! .. contents:: Table of Contents
+//!     :backlinks: none
+//!     :local:
+//! 
+//! Introduction
+//! ------------
+//! 
+//! Implementation of the `RIPEMD160` precompiled contract.
+
+use crate::ethereum::{cancun::vm::{
+    exceptions::VmError,
+    gas::{charge_gas, GAS_RIPEMD160, GAS_RIPEMD160_WORD},
+    Evm,
+}, crypto::hash, ethereum_types::{bytes::Bytes, numeric::Uint}, utils::numeric::ceil32};
+
+/// """
+/// Writes the ripemd160 hash to output.
+/// 
+/// Parameters
+/// ----------
+/// evm :
+///     The current EVM frame.
+/// """
+pub fn ripemd160(evm: &mut Evm) -> Result<(), VmError> {
+    let data = &evm.message.data;
+
+    // GAS
+    let word_count = ceil32(data.len() as Uint) / 32;
+    charge_gas(evm, GAS_RIPEMD160 + GAS_RIPEMD160_WORD * word_count)?;
+
+    // OPERATION
+    let hash_bytes = hash::ripemd160(&evm.message.data);
+    let mut padded_hash = vec![0; 12];
+    padded_hash.extend_from_slice(&hash_bytes.0);
+    evm.output = Bytes(padded_hash);
+    Ok(())
+}
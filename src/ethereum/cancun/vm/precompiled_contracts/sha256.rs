@@ -0,0 +1,37 @@
+//! Ethereum Virtual Machine (EVM) SHA256 PRECOMPILED CONTRACT
+//! ^^^^^^^^^^^^^^^^^^^^^^^^^^^^^^^^^^^^^^^^^^^^^^^^^^^^^^^^^^
+//! 
+//! .. contents:: Table of Contents
+//!     :backlinks: none
+//!     :local:
+//! 
+//! Introduction
+//! ------------
+//! 
+//! Implementation of the `SHA256` precompiled contract.
+
+use crate::ethereum::{cancun::vm::{
+    exceptions::VmError,
+    gas::{charge_gas, GAS_SHA256, GAS_SHA256_WORD},
+    Evm,
+}, crypto::hash, ethereum_types::{bytes::Bytes, numeric::Uint}, utils::numeric::ceil32};
+
+/// """
+/// Writes the sha256 hash to output.
+/// 
+/// Parameters
+/// ----------
+/// evm :
+///     The current EVM frame.
+/// """
+pub fn sha256(evm: &mut Evm) -> Result<(), VmError> {
+    let data = &evm.message.data;
+
+    // GAS
+    let word_count = ceil32(data.len() as Uint) / 32;
+    charge_gas(evm, GAS_SHA256 + GAS_SHA256_WORD * word_count)?;
+
+    // OPERATION
+    evm.output = Bytes(hash::sha256(&evm.message.data).to_vec());
+    Ok(())
+}
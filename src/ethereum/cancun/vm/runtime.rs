@@ -0,0 +1,69 @@
+//! Ethereum Virtual Machine (EVM) Runtime Operations
+//! ^^^^^^^^^^^^^^^^^^^^^^^^^^^^^^^^^^^^^^^^^^^^^^^^^
+//! 
+//! .. contents:: Table of Contents
+//!     :backlinks: none
+//!     :local:
+//! 
+//! Introduction
+//! ------------
+//! 
+//! Runtime related operations used while executing EVM code.
+
+use std::collections::BTreeSet;
+
+use crate::ethereum::ethereum_types::numeric::Uint;
+
+use super::instructions::Ops;
+
+/// """
+/// Analyze the evm code to obtain the set of valid jump destinations.
+/// 
+/// Valid jump destinations are defined as follows:
+///     * The jump destination is less than the length of the code.
+///     * The jump destination should have the `JUMPDEST` opcode (0x5B).
+///     * The jump destination shouldn't be part of the data corresponding to
+///       `PUSH-N` opcodes.
+/// 
+/// Note - Jump destinations are 0-indexed.
+/// 
+/// Parameters
+/// ----------
+/// code :
+///     The EVM code which is to be executed.
+/// 
+/// Returns
+/// -------
+/// valid_jump_destinations: `Set[Uint]`
+///     The set of valid jump destinations in the code.
+/// """
+pub fn get_valid_jump_destinations(code: &[u8]) -> BTreeSet<Uint> {
+    let mut valid_jump_destinations = BTreeSet::new();
+    let mut pc = 0;
+    while pc < code.len() {
+        let op = code[pc];
+        if op == Ops::JUMPDEST as u8 {
+            valid_jump_destinations.insert(pc as Uint);
+        } else if (Ops::PUSH1 as u8..=Ops::PUSH32 as u8).contains(&op) {
+            // Skip data section of PUSH-N opcodes.
+            pc += (op - Ops::PUSH1 as u8 + 1) as usize;
+        }
+        pc += 1;
+    }
+    valid_jump_destinations
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{get_valid_jump_destinations, Ops};
+
+    #[test]
+    fn jump_destinations() {
+        let (push0, push1, push2, jumpdest) = (Ops::PUSH0 as u8, Ops::PUSH1 as u8, Ops::PUSH2 as u8, Ops::JUMPDEST as u8);
+        // PUSH0 has no immediate; the JUMPDESTs in the data of the PUSH2 and
+        // of the truncated PUSH2 at the end are not destinations.
+        let code = [jumpdest, push0, jumpdest, push2, jumpdest, jumpdest, jumpdest, push1, 0, jumpdest, push2, jumpdest];
+        assert_eq!(get_valid_jump_destinations(&code).into_iter().collect::<Vec<_>>(), [0, 2, 6, 9]);
+        assert!(get_valid_jump_destinations(&[]).is_empty());
+    }
+}
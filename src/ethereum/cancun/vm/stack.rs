@@ -14,6 +14,9 @@ use crate::ethereum::ethereum_types::numeric::U256;
 
 use super::exceptions::VmError;
 
+/// The maximum number of items on the stack.
+pub const STACK_LIMIT: usize = 1024;
+
 /// Pops the top item off of `stack`.
 /// 
 /// Parameters
@@ -26,6 +29,7 @@ use super::exceptions::VmError;
 /// value : `U256`
 ///     The top element on the stack.
 /// 
+#[inline]
 pub fn pop(stack: &mut Vec<U256>) -> Result<U256, VmError> {
     stack.pop().ok_or(VmError::StackUnderflowError)
 }
@@ -41,8 +45,9 @@ pub fn pop(stack: &mut Vec<U256>) -> Result<U256, VmError> {
 /// value :
 ///     Item to be pushed onto `stack`.
 /// 
+#[inline]
 pub fn push(stack: &mut Vec<U256>, value: U256) -> Result<(), VmError> {
-    if stack.len() == 1024 {
+    if stack.len() == STACK_LIMIT {
         Err(VmError::StackOverflowError)
     } else {
         stack.push(value);
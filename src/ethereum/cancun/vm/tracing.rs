@@ -0,0 +1,455 @@
+//! Tracing of the instructions `execute_code` runs and the calls they make.
+//!
+//! A `Tracer` in `Environment::tracer` sees every instruction as a `Step`,
+//! with the machine state before it ran and the gas it cost, and every
+//! message call or contract creation as it starts and ends. Without a tracer,
+//! or with one which does not want steps, the interpreter does not build
+//! steps at all.
+//!
+//! `Eip3155Tracer` writes steps as JSON lines in the format of EIP-3155, as
+//! the tracers of other clients do, so traces can be compared line by line.
+//! `CallTracer` builds the tree of calls of a transaction in the format of
+//! geth's `callTracer`. A `Vec<BTreeMap<String, String>>` keeps a flat
+//! summary of each step, which the Python and WebAssembly bindings return.
+
+use std::{collections::BTreeMap, io::Write};
+
+use crate::{
+    ethereum::{
+        cancun::fork_types::Address,
+        ethereum_types::{bytes::Bytes, numeric::{Uint, U256}},
+    },
+    json::{Encoder, JsonEncode},
+};
+
+use super::{exceptions::VmError, instructions::Ops};
+
+/// An instruction as it was run.
+#[derive(Debug, Clone, Copy)]
+pub struct Step<'s> {
+    pub pc: Uint,
+    pub op: Ops,
+    /// Gas left before the instruction.
+    pub gas: Uint,
+    /// Gas the instruction consumed, including the gas given to a child
+    /// frame, or all of `gas` if it failed.
+    pub gas_cost: Uint,
+    /// Depth of the frame, one for the message of a transaction.
+    pub depth: Uint,
+    /// Return data of the last call the frame made.
+    pub return_data: &'s Bytes,
+    pub refund: i64,
+    pub error: Option<&'s VmError>,
+}
+
+/// A message call or contract creation about to run.
+#[derive(Debug, Clone, Copy)]
+pub struct CallStart<'s> {
+    /// The instruction which made the call, `CALL` or `CREATE` for the
+    /// message of a transaction.
+    pub kind: Ops,
+    /// The account whose code made the call, or the sender of the
+    /// transaction.
+    pub from: &'s Address,
+    /// The account whose code runs, or the created account.
+    pub to: &'s Address,
+    pub value: U256,
+    pub gas: Uint,
+    /// Call data, or the init code of a creation.
+    pub input: &'s Bytes,
+}
+
+/// Receives the steps of `execute_code` and the calls of a transaction.
+///
+/// Every `enter` is followed by an `exit` for the same call once the calls
+/// it made have exited. A call the interpreter refuses before it runs, for
+/// lack of balance or depth, is not entered.
+pub trait Tracer {
+    /// Whether to report steps. Reporting them slows the interpreter down,
+    /// so a tracer which only follows calls should return `false`.
+    fn traces_steps(&self) -> bool {
+        true
+    }
+
+    /// The stack, with the top last, and the memory before the instruction
+    /// of the next `step`. The instruction may change them, so a tracer
+    /// which reports them serializes them here.
+    fn before_step(&mut self, stack: &[U256], memory: &[u8]) {}
+
+    fn step(&mut self, step: &Step) {}
+
+    fn enter(&mut self, call: &CallStart) {}
+
+    /// The innermost call ended after using `gas_used`. For the message of
+    /// a transaction this is the gas used by the transaction.
+    fn exit(&mut self, output: &Bytes, gas_used: Uint, error: Option<&VmError>) {}
+}
+
+impl Tracer for Vec<BTreeMap<String, String>> {
+    fn step(&mut self, step: &Step) {
+        let mut entry = BTreeMap::new();
+        entry.insert("pc".to_string(), step.pc.to_string());
+        entry.insert("op".to_string(), step.op.name().to_string());
+        entry.insert("gas".to_string(), step.gas.to_string());
+        entry.insert("gasCost".to_string(), step.gas_cost.to_string());
+        entry.insert("depth".to_string(), step.depth.to_string());
+        if let Some(error) = step.error {
+            entry.insert("error".to_string(), format!("{error:?}"));
+        }
+        self.push(entry);
+    }
+}
+
+/// Writes each step as a line of JSON in the format of EIP-3155.
+///
+/// Clearing `with_memory` leaves out the memory, which makes up most of a
+/// trace, keeping `memSize`.
+#[derive(Debug)]
+pub struct Eip3155Tracer<W: Write> {
+    out: W,
+    pub with_memory: bool,
+    /// The memory and stack given to `before_step` as JSON, and the size of
+    /// the memory.
+    memory: String,
+    mem_size: usize,
+    stack: String,
+    /// The first error writing to `out`, after which nothing more is written.
+    pub io_error: Option<std::io::Error>,
+}
+
+impl<W: Write> Eip3155Tracer<W> {
+    pub fn new(out: W) -> Self {
+        Self { out, with_memory: true, memory: String::new(), mem_size: 0, stack: String::new(), io_error: None }
+    }
+
+    /// Write the line which ends the trace of a transaction.
+    pub fn summary(&mut self, output: &Bytes, gas_used: Uint, error: Option<&VmError>) {
+        let mut encoder = Encoder::new();
+        let mut o = encoder.object();
+        o.field("output", output);
+        o.key("gasUsed");
+        o.encoder.quantity(&gas_used.to_be_bytes());
+        o.field("pass", &error.is_none());
+        if let Some(error) = error {
+            o.field("error", &format!("{error:?}"));
+        }
+        o.end();
+        self.write_line(encoder.finish());
+    }
+
+    pub fn into_inner(self) -> W {
+        self.out
+    }
+
+    fn write_line(&mut self, mut line: String) {
+        if self.io_error.is_none() {
+            line.push('\n');
+            self.io_error = self.out.write_all(line.as_bytes()).err();
+        }
+    }
+}
+
+impl<W: Write> Tracer for Eip3155Tracer<W> {
+    fn before_step(&mut self, stack: &[U256], memory: &[u8]) {
+        let mut encoder = Encoder::new();
+        if self.with_memory {
+            encoder.hex(memory);
+        }
+        self.memory = encoder.finish();
+        self.mem_size = memory.len();
+        let mut encoder = Encoder::new();
+        stack.encode_json(&mut encoder);
+        self.stack = encoder.finish();
+    }
+
+    fn step(&mut self, step: &Step) {
+        let mut encoder = Encoder::new();
+        let mut o = encoder.object();
+        o.key("pc");
+        o.encoder.raw(&step.pc.to_string());
+        o.key("op");
+        o.encoder.raw(&(step.op as u8).to_string());
+        o.key("gas");
+        o.encoder.quantity(&step.gas.to_be_bytes());
+        o.key("gasCost");
+        o.encoder.quantity(&step.gas_cost.to_be_bytes());
+        if self.with_memory {
+            o.key("memory");
+            o.encoder.raw(&self.memory);
+        }
+        o.key("memSize");
+        o.encoder.raw(&self.mem_size.to_string());
+        o.key("stack");
+        o.encoder.raw(&self.stack);
+        o.field("returnData", step.return_data);
+        o.key("depth");
+        o.encoder.raw(&step.depth.to_string());
+        o.key("refund");
+        o.encoder.raw(&step.refund.to_string());
+        o.field("opName", &step.op.name());
+        if let Some(error) = step.error {
+            o.field("error", &format!("{error:?}"));
+        }
+        o.end();
+        self.write_line(encoder.finish());
+    }
+}
+
+/// A call in the format of geth's `callTracer`.
+#[derive(Debug, Clone, PartialEq)]
+pub struct CallTrace {
+    pub kind: Ops,
+    pub from: Address,
+    pub to: Address,
+    /// `None` for `DELEGATECALL` and `STATICCALL`, which move no value.
+    pub value: Option<U256>,
+    pub gas: Uint,
+    pub gas_used: Uint,
+    pub input: Bytes,
+    pub output: Bytes,
+    /// The error as geth words it.
+    pub error: Option<String>,
+    /// The message of a revert with `Error(string)`.
+    pub revert_reason: Option<String>,
+    pub calls: Vec<CallTrace>,
+}
+
+impl JsonEncode for CallTrace {
+    fn encode_json(&self, encoder: &mut Encoder) {
+        let mut o = encoder.object();
+        o.field("type", &self.kind.name());
+        o.field("from", &self.from);
+        o.field("to", &self.to);
+        if let Some(value) = &self.value {
+            o.field("value", value);
+        }
+        o.key("gas");
+        o.encoder.quantity(&self.gas.to_be_bytes());
+        o.key("gasUsed");
+        o.encoder.quantity(&self.gas_used.to_be_bytes());
+        o.field("input", &self.input);
+        if !self.output.is_empty() {
+            o.field("output", &self.output);
+        }
+        if let Some(error) = &self.error {
+            o.field("error", error);
+        }
+        if let Some(revert_reason) = &self.revert_reason {
+            o.field("revertReason", revert_reason);
+        }
+        if !self.calls.is_empty() {
+            o.field("calls", &self.calls);
+        }
+        o.end();
+    }
+}
+
+/// Builds the tree of calls of a transaction, as geth's `callTracer` does.
+#[derive(Debug, Clone, Default)]
+pub struct CallTracer {
+    /// The calls which have been entered and not exited, outermost first.
+    stack: Vec<CallTrace>,
+    root: Option<CallTrace>,
+}
+
+impl CallTracer {
+    /// The call of the transaction, once it has exited.
+    pub fn into_result(self) -> Option<CallTrace> {
+        self.root
+    }
+}
+
+impl Tracer for CallTracer {
+    fn traces_steps(&self) -> bool {
+        false
+    }
+
+    fn enter(&mut self, call: &CallStart) {
+        self.stack.push(CallTrace {
+            kind: call.kind,
+            from: call.from.clone(),
+            to: call.to.clone(),
+            value: (!matches!(call.kind, Ops::DELEGATECALL | Ops::STATICCALL)).then_some(call.value),
+            gas: call.gas,
+            gas_used: 0,
+            input: call.input.clone(),
+            output: Bytes::default(),
+            error: None,
+            revert_reason: None,
+            calls: Vec::new(),
+        });
+    }
+
+    fn exit(&mut self, output: &Bytes, gas_used: Uint, error: Option<&VmError>) {
+        let Some(mut call) = self.stack.pop() else {
+            return;
+        };
+        call.gas_used = gas_used;
+        match error {
+            None => call.output = output.clone(),
+            Some(VmError::Revert) => {
+                call.output = output.clone();
+                call.revert_reason = revert_reason(output);
+                call.error = Some("execution reverted".to_string());
+            }
+            Some(error) => call.error = Some(geth_error(error).to_string()),
+        }
+        match self.stack.last_mut() {
+            Some(parent) => parent.calls.push(call),
+            None => self.root = Some(call),
+        }
+    }
+}
+
+/// The message of revert data encoded as `Error(string)`.
+fn revert_reason(output: &[u8]) -> Option<String> {
+    let data = output.strip_prefix(&[0x08, 0xc3, 0x79, 0xa0][..])?;
+    let offset = usize::try_from(U256::from_be_slice(data.get(..32)?).to_uint().ok()?).ok()?;
+    let len_end = offset.checked_add(32)?;
+    let len = usize::try_from(U256::from_be_slice(data.get(offset..len_end)?).to_uint().ok()?).ok()?;
+    let message = data.get(len_end..len_end.checked_add(len)?)?;
+    String::from_utf8(message.to_vec()).ok()
+}
+
+/// The wording of geth for the errors which end a call.
+fn geth_error(error: &VmError) -> &'static str {
+    match error {
+        VmError::Revert => "execution reverted",
+        VmError::StackUnderflowError => "stack underflow",
+        VmError::StackOverflowError => "stack limit reached 1024",
+        VmError::OutOfGasError => "out of gas",
+        VmError::InvalidOpcode => "invalid opcode",
+        VmError::InvalidJumpDestError => "invalid jump destination",
+        VmError::StackDepthLimitError => "max call depth exceeded",
+        VmError::WriteInStaticContext => "write protection",
+        VmError::OutOfBoundsRead => "return data out of bounds",
+        VmError::InvalidContractPrefix => "invalid code: must not begin with 0xef",
+        VmError::InvalidEof => "invalid eof",
+        VmError::AddressCollision => "contract address collision",
+        VmError::InvalidParameter | VmError::KZGProofError => "precompile failed",
+        VmError::ExceptionalHalt => "exceptional halt",
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::BTreeSet;
+
+    use crate::{
+        ethereum::{
+            cancun::{
+                fork::process_transaction,
+                fork_types::{Account, Address},
+                state::{set_account, State, TransientStorage},
+                transactions::{LegacyTransaction, Transaction},
+                utils::prepare_message,
+                vm::{instructions::Ops, interpreter::process_message_call, Environment},
+            },
+            ethereum_types::{bytes::Bytes, numeric::U256},
+            forks::ExecutionRules,
+        },
+        json::to_json,
+    };
+
+    use super::{CallTracer, Eip3155Tracer, Tracer};
+
+    fn environment<'a>(caller: &Address, state: &'a mut State, tracer: &'a mut dyn Tracer) -> Environment<'a> {
+        Environment {
+            caller: caller.clone(),
+            origin: caller.clone(),
+            gas_limit: 100_000,
+            tracer: Some(tracer),
+            ..Environment::new(state, ExecutionRules::CANCUN)
+        }
+    }
+
+    #[test]
+    fn eip3155() {
+        let (caller, contract) = (Address::from_be_bytes([0xca; 20]), Address::from_be_bytes([0xc0; 20]));
+        let mut state = State::default();
+        // PUSH1 42 PUSH1 0 MSTORE INVALID
+        let code = Bytes(vec![0x60, 0x2a, 0x60, 0x00, 0x52, 0xfe]);
+        set_account(&mut state, &contract, Some(Account { nonce: 1, code, ..Default::default() }));
+        let mut tracer = Eip3155Tracer::new(Vec::new());
+        let mut env = environment(&caller, &mut state, &mut tracer);
+        let message = prepare_message(
+            caller,
+            Some(contract),
+            U256::ZERO,
+            Bytes::default(),
+            100,
+            &env,
+            None,
+            true,
+            false,
+            BTreeSet::new(),
+            BTreeSet::new(),
+        )
+        .unwrap();
+        let output = process_message_call(message, &mut env).unwrap();
+        tracer.summary(&output.return_data, 100 - output.gas_left, output.error.as_ref());
+
+        let trace = String::from_utf8(tracer.into_inner()).unwrap();
+        let lines = trace.lines().collect::<Vec<_>>();
+        assert_eq!(lines, [
+            r#"{"pc":0,"op":96,"gas":"0x64","gasCost":"0x3","memory":"0x","memSize":0,"stack":[],"returnData":"0x","depth":1,"refund":0,"opName":"PUSH1"}"#,
+            r#"{"pc":2,"op":96,"gas":"0x61","gasCost":"0x3","memory":"0x","memSize":0,"stack":["0x2a"],"returnData":"0x","depth":1,"refund":0,"opName":"PUSH1"}"#,
+            r#"{"pc":4,"op":82,"gas":"0x5e","gasCost":"0x6","memory":"0x","memSize":0,"stack":["0x2a","0x0"],"returnData":"0x","depth":1,"refund":0,"opName":"MSTORE"}"#,
+            r#"{"output":"0x","gasUsed":"0x64","pass":false,"error":"InvalidOpcode"}"#,
+        ]);
+    }
+
+    #[test]
+    fn call_tracer() {
+        let (caller, a, b) = (
+            Address::from_be_bytes([0xca; 20]),
+            Address::from_be_bytes([0xaa; 20]),
+            Address::from_be_bytes([0xbb; 20]),
+        );
+        let mut state = State::default();
+        set_account(&mut state, &caller, Some(Account { nonce: 0, ..Default::default() }));
+        // CALL(0xffff, b, 0, 0, 0, 0, 0) STOP
+        let mut code_a = vec![0x60, 0x00, 0x60, 0x00, 0x60, 0x00, 0x60, 0x00, 0x60, 0x00, 0x73];
+        code_a.extend_from_slice(&b[..]);
+        code_a.extend([0x61, 0xff, 0xff, 0xf1, 0x00]);
+        set_account(&mut state, &a, Some(Account { nonce: 1, code: Bytes(code_a), ..Default::default() }));
+        // CODECOPY(0, 12, 100) REVERT(0, 100) with Error("no") after the code.
+        let mut code_b: Vec<u8> = vec![0x60, 0x64, 0x60, 0x0c, 0x60, 0x00, 0x39, 0x60, 0x64, 0x60, 0x00, 0xfd];
+        code_b.extend([0x08, 0xc3, 0x79, 0xa0]);
+        code_b.extend(U256::from(0x20_u32).to_be_bytes());
+        code_b.extend(U256::from(2_u32).to_be_bytes());
+        code_b.extend([b"no".as_slice(), &[0; 30]].concat());
+        set_account(&mut state, &b, Some(Account { nonce: 1, code: Bytes(code_b), ..Default::default() }));
+
+        let tx = Transaction::LegacyTransaction(LegacyTransaction {
+            gas: 100_000,
+            to: Some(a.clone()).into(),
+            data: Bytes(vec![0x12, 0x34]),
+            ..Default::default()
+        });
+        let mut tracer = CallTracer::default();
+        let mut env = environment(&caller, &mut state, &mut tracer);
+        let (gas_used, _, error) = process_transaction(&mut env, &tx).unwrap();
+        assert!(error.is_none());
+
+        let trace = tracer.into_result().unwrap();
+        assert_eq!((trace.kind, &trace.from, &trace.to), (Ops::CALL, &caller, &a));
+        assert_eq!((trace.gas, trace.gas_used), (100_000, gas_used));
+        let [call] = &trace.calls[..] else { panic!("{trace:?}") };
+        // Three pushes, CODECOPY of four words and two pushes.
+        assert_eq!(
+            to_json(call),
+            format!(
+                concat!(
+                    r#"{{"type":"CALL","from":"0x{}","to":"0x{}","value":"0x0","gas":"0xffff","gasUsed":"0x2a","#,
+                    r#""input":"0x","output":"0x08c379a0{:064x}{:064x}6e6f{}","error":"execution reverted","revertReason":"no"}}"#,
+                ),
+                "aa".repeat(20),
+                "bb".repeat(20),
+                0x20,
+                2,
+                "00".repeat(30),
+            )
+        );
+    }
+}
@@ -0,0 +1,154 @@
+//! Parameters of a chain which the specification fixes for mainnet.
+//!
+//! The gas limit and base fee rules, the blob gas limits and the addresses
+//! of the system calls are constants of the specification. A `ChainSpec`
+//! holds them so that chains which share the rules of mainnet but not its
+//! parameters, such as L2s with another elasticity or blob target, can be
+//! executed. `ChainSpec::MAINNET` is the default.
+//!
+//! The `config` section of a genesis file sets the chain id, the blob gas
+//! limits with the `cancun` entry of `blobSchedule`, and the EIP-1559
+//! parameters with the `optimism` member of OP Stack chains. The other
+//! parameters are those of mainnet.
+
+use crate::{
+    ethereum::{
+        cancun::{fork_types::Address, vm::gas::GAS_PER_BLOB},
+        ethereum_types::numeric::{Uint, U64},
+    },
+    json::{Decoder, JsonDecode, JsonError, ObjectEncoder, ObjectParser},
+};
+
+/// The parameters a chain executes and validates its blocks with.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ChainSpec {
+    /// The chain id signed over by transactions (EIP-155).
+    pub chain_id: U64,
+    /// Bound on the change of the base fee from one block to the next, as
+    /// the inverse of a fraction of the parent's base fee (EIP-1559).
+    pub base_fee_max_change_denominator: Uint,
+    /// Ratio of the gas limit of a block to its gas target (EIP-1559).
+    pub elasticity_multiplier: Uint,
+    /// Bound on the change of the gas limit from one block to the next, as
+    /// the inverse of a fraction of the parent's gas limit.
+    pub gas_limit_adjustment_factor: Uint,
+    /// Lowest gas limit of a block.
+    pub gas_limit_minimum: Uint,
+    /// Most blob gas the transactions of a block may use (EIP-4844).
+    pub max_blob_gas_per_block: Uint,
+    /// Blob gas per block above which the blob gas price rises (EIP-4844).
+    pub target_blob_gas_per_block: U64,
+    /// Sender of the system calls made before and after the transactions of
+    /// a block.
+    pub system_address: Address,
+    /// Contract storing the parent beacon block root of each block
+    /// (EIP-4788).
+    pub beacon_roots_address: Address,
+}
+
+impl ChainSpec {
+    pub const MAINNET: Self = Self {
+        chain_id: 1,
+        base_fee_max_change_denominator: 8,
+        elasticity_multiplier: 2,
+        gas_limit_adjustment_factor: 1024,
+        gas_limit_minimum: 5000,
+        max_blob_gas_per_block: 786432,
+        target_blob_gas_per_block: 393216,
+        system_address: Address::from_be_bytes([
+            0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff,
+            0xff, 0xff, 0xff, 0xfe,
+        ]),
+        beacon_roots_address: Address::from_be_bytes([
+            0x00, 0x0F, 0x3d, 0xf6, 0xD7, 0x32, 0x80, 0x7E, 0xf1, 0x31, 0x9f, 0xB7, 0xB8, 0xbB, 0x85, 0x22,
+            0xd0, 0xBe, 0xac, 0x02,
+        ]),
+    };
+}
+
+impl ChainSpec {
+    /// Read the member `key` of the `config` section of a genesis file, if
+    /// it is one of the parameters. Returns whether it was.
+    pub fn decode_member<'de>(&mut self, key: &str, decoder: &mut Decoder<'de>) -> Result<bool, JsonError> {
+        match key {
+            "chainId" => self.chain_id.decode_json(decoder)?,
+            "blobSchedule" => {
+                let mut p = ObjectParser::new(decoder);
+                while let Some(fork) = p.next_key()? {
+                    // Cancun is the last supported fork with blobs.
+                    if fork != "cancun" {
+                        p.skip_value()?;
+                        continue;
+                    }
+                    let mut q = ObjectParser::new(p.decoder);
+                    while let Some(key) = q.next_key()? {
+                        let mut blobs: U64 = 0;
+                        match key {
+                            "target" => {
+                                blobs.decode_json(q.decoder)?;
+                                self.target_blob_gas_per_block = blobs * GAS_PER_BLOB as U64;
+                            }
+                            "max" => {
+                                blobs.decode_json(q.decoder)?;
+                                self.max_blob_gas_per_block = blobs as Uint * GAS_PER_BLOB;
+                            }
+                            _ => q.skip_value()?,
+                        }
+                    }
+                }
+            }
+            "optimism" => {
+                let mut p = ObjectParser::new(decoder);
+                while let Some(key) = p.next_key()? {
+                    match key {
+                        "eip1559Elasticity" => self.elasticity_multiplier.decode_json(p.decoder)?,
+                        "eip1559Denominator" => self.base_fee_max_change_denominator.decode_json(p.decoder)?,
+                        _ => p.skip_value()?,
+                    }
+                }
+            }
+            _ => return Ok(false),
+        }
+        Ok(true)
+    }
+
+    /// Add the members of the `config` section of a genesis file which
+    /// `decode_member` reads back into these parameters to `o`, leaving out
+    /// those equal to mainnet's.
+    pub fn encode_members(&self, o: &mut ObjectEncoder) {
+        o.key("chainId");
+        o.encoder.raw(&self.chain_id.to_string());
+        let mainnet = Self::MAINNET;
+        if (self.target_blob_gas_per_block, self.max_blob_gas_per_block)
+            != (mainnet.target_blob_gas_per_block, mainnet.max_blob_gas_per_block)
+        {
+            o.key("blobSchedule");
+            let mut schedule = o.encoder.object();
+            schedule.key("cancun");
+            let mut cancun = schedule.encoder.object();
+            cancun.key("target");
+            cancun.encoder.raw(&(self.target_blob_gas_per_block / GAS_PER_BLOB as U64).to_string());
+            cancun.key("max");
+            cancun.encoder.raw(&(self.max_blob_gas_per_block / GAS_PER_BLOB).to_string());
+            cancun.end();
+            schedule.end();
+        }
+        if (self.elasticity_multiplier, self.base_fee_max_change_denominator)
+            != (mainnet.elasticity_multiplier, mainnet.base_fee_max_change_denominator)
+        {
+            o.key("optimism");
+            let mut optimism = o.encoder.object();
+            optimism.key("eip1559Elasticity");
+            optimism.encoder.raw(&self.elasticity_multiplier.to_string());
+            optimism.key("eip1559Denominator");
+            optimism.encoder.raw(&self.base_fee_max_change_denominator.to_string());
+            optimism.end();
+        }
+    }
+}
+
+impl Default for ChainSpec {
+    fn default() -> Self {
+        Self::MAINNET
+    }
+}
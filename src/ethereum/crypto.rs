@@ -1,5 +1,7 @@
+pub mod aes;
 pub mod alt_bn128;
 pub mod blake2;
+pub mod bls12_381;
 pub mod eliptic_curve;
 pub mod finite_field;
 pub mod hash;
@@ -0,0 +1,166 @@
+//! AES block cipher (FIPS 197) with 128 and 256 bit keys, and counter mode.
+//!
+//! Only encryption is implemented: RLPx uses AES in counter mode, where
+//! decryption is the same operation, and encrypts single blocks to update
+//! its MACs.
+
+const SBOX: [u8; 256] = [
+    0x63, 0x7c, 0x77, 0x7b, 0xf2, 0x6b, 0x6f, 0xc5, 0x30, 0x01, 0x67, 0x2b, 0xfe, 0xd7, 0xab, 0x76,
+    0xca, 0x82, 0xc9, 0x7d, 0xfa, 0x59, 0x47, 0xf0, 0xad, 0xd4, 0xa2, 0xaf, 0x9c, 0xa4, 0x72, 0xc0,
+    0xb7, 0xfd, 0x93, 0x26, 0x36, 0x3f, 0xf7, 0xcc, 0x34, 0xa5, 0xe5, 0xf1, 0x71, 0xd8, 0x31, 0x15,
+    0x04, 0xc7, 0x23, 0xc3, 0x18, 0x96, 0x05, 0x9a, 0x07, 0x12, 0x80, 0xe2, 0xeb, 0x27, 0xb2, 0x75,
+    0x09, 0x83, 0x2c, 0x1a, 0x1b, 0x6e, 0x5a, 0xa0, 0x52, 0x3b, 0xd6, 0xb3, 0x29, 0xe3, 0x2f, 0x84,
+    0x53, 0xd1, 0x00, 0xed, 0x20, 0xfc, 0xb1, 0x5b, 0x6a, 0xcb, 0xbe, 0x39, 0x4a, 0x4c, 0x58, 0xcf,
+    0xd0, 0xef, 0xaa, 0xfb, 0x43, 0x4d, 0x33, 0x85, 0x45, 0xf9, 0x02, 0x7f, 0x50, 0x3c, 0x9f, 0xa8,
+    0x51, 0xa3, 0x40, 0x8f, 0x92, 0x9d, 0x38, 0xf5, 0xbc, 0xb6, 0xda, 0x21, 0x10, 0xff, 0xf3, 0xd2,
+    0xcd, 0x0c, 0x13, 0xec, 0x5f, 0x97, 0x44, 0x17, 0xc4, 0xa7, 0x7e, 0x3d, 0x64, 0x5d, 0x19, 0x73,
+    0x60, 0x81, 0x4f, 0xdc, 0x22, 0x2a, 0x90, 0x88, 0x46, 0xee, 0xb8, 0x14, 0xde, 0x5e, 0x0b, 0xdb,
+    0xe0, 0x32, 0x3a, 0x0a, 0x49, 0x06, 0x24, 0x5c, 0xc2, 0xd3, 0xac, 0x62, 0x91, 0x95, 0xe4, 0x79,
+    0xe7, 0xc8, 0x37, 0x6d, 0x8d, 0xd5, 0x4e, 0xa9, 0x6c, 0x56, 0xf4, 0xea, 0x65, 0x7a, 0xae, 0x08,
+    0xba, 0x78, 0x25, 0x2e, 0x1c, 0xa6, 0xb4, 0xc6, 0xe8, 0xdd, 0x74, 0x1f, 0x4b, 0xbd, 0x8b, 0x8a,
+    0x70, 0x3e, 0xb5, 0x66, 0x48, 0x03, 0xf6, 0x0e, 0x61, 0x35, 0x57, 0xb9, 0x86, 0xc1, 0x1d, 0x9e,
+    0xe1, 0xf8, 0x98, 0x11, 0x69, 0xd9, 0x8e, 0x94, 0x9b, 0x1e, 0x87, 0xe9, 0xce, 0x55, 0x28, 0xdf,
+    0x8c, 0xa1, 0x89, 0x0d, 0xbf, 0xe6, 0x42, 0x68, 0x41, 0x99, 0x2d, 0x0f, 0xb0, 0x54, 0xbb, 0x16,
+];
+
+/// Round constants of the key schedule.
+const RCON: [u8; 10] = [0x01, 0x02, 0x04, 0x08, 0x10, 0x20, 0x40, 0x80, 0x1b, 0x36];
+
+/// Multiply by `x` in GF(2**8).
+fn xtime(b: u8) -> u8 {
+    (b << 1) ^ if b & 0x80 != 0 { 0x1b } else { 0 }
+}
+
+/// An AES key with its expanded round keys.
+#[derive(Debug, Clone)]
+pub struct Aes {
+    round_keys: Vec<[u8; 16]>,
+}
+
+impl Aes {
+    /// Expand a 16 or 32 byte key.
+    pub fn new(key: &[u8]) -> Self {
+        assert!(key.len() == 16 || key.len() == 32, "AES key must be 16 or 32 bytes");
+        let nk = key.len() / 4;
+        let rounds = nk + 6;
+        let mut words: Vec<[u8; 4]> = key.chunks(4).map(|w| w.try_into().unwrap()).collect();
+        for i in nk..4 * (rounds + 1) {
+            let mut temp = words[i - 1];
+            if i % nk == 0 {
+                temp = [SBOX[temp[1] as usize], SBOX[temp[2] as usize], SBOX[temp[3] as usize], SBOX[temp[0] as usize]];
+                temp[0] ^= RCON[i / nk - 1];
+            } else if nk > 6 && i % nk == 4 {
+                temp = temp.map(|b| SBOX[b as usize]);
+            }
+            let previous = words[i - nk];
+            words.push(std::array::from_fn(|j| previous[j] ^ temp[j]));
+        }
+        let round_keys = words.chunks(4).map(|w| std::array::from_fn(|i| w[i / 4][i % 4])).collect();
+        Self { round_keys }
+    }
+
+    /// Encrypt one block in place.
+    pub fn encrypt_block(&self, block: &mut [u8; 16]) {
+        let rounds = self.round_keys.len() - 1;
+        add_round_key(block, &self.round_keys[0]);
+        for round in 1..=rounds {
+            for b in block.iter_mut() {
+                *b = SBOX[*b as usize];
+            }
+            shift_rows(block);
+            if round != rounds {
+                mix_columns(block);
+            }
+            add_round_key(block, &self.round_keys[round]);
+        }
+    }
+}
+
+fn add_round_key(block: &mut [u8; 16], key: &[u8; 16]) {
+    for (b, k) in block.iter_mut().zip(key) {
+        *b ^= k;
+    }
+}
+
+/// Rotate row `r` of the column-major state left by `r`.
+fn shift_rows(block: &mut [u8; 16]) {
+    let state = *block;
+    for c in 0..4 {
+        for r in 0..4 {
+            block[4 * c + r] = state[4 * ((c + r) % 4) + r];
+        }
+    }
+}
+
+fn mix_columns(block: &mut [u8; 16]) {
+    for column in block.chunks_mut(4) {
+        let [a0, a1, a2, a3] = [column[0], column[1], column[2], column[3]];
+        let all = a0 ^ a1 ^ a2 ^ a3;
+        column[0] ^= all ^ xtime(a0 ^ a1);
+        column[1] ^= all ^ xtime(a1 ^ a2);
+        column[2] ^= all ^ xtime(a2 ^ a3);
+        column[3] ^= all ^ xtime(a3 ^ a0);
+    }
+}
+
+/// AES in counter mode, with a 128 bit big endian counter starting at the
+/// IV. Encrypting and decrypting are the same operation, and a stream may
+/// be applied to its data in pieces of any size.
+#[derive(Debug, Clone)]
+pub struct AesCtr {
+    aes: Aes,
+    counter: [u8; 16],
+    keystream: [u8; 16],
+    /// Bytes of `keystream` already used.
+    used: usize,
+}
+
+impl AesCtr {
+    pub fn new(key: &[u8], iv: [u8; 16]) -> Self {
+        Self { aes: Aes::new(key), counter: iv, keystream: [0; 16], used: 16 }
+    }
+
+    /// XOR the next bytes of the key stream into `data`.
+    pub fn apply(&mut self, data: &mut [u8]) {
+        for b in data {
+            if self.used == 16 {
+                self.keystream = self.counter;
+                self.aes.encrypt_block(&mut self.keystream);
+                self.counter = (u128::from_be_bytes(self.counter).wrapping_add(1)).to_be_bytes();
+                self.used = 0;
+            }
+            *b ^= self.keystream[self.used];
+            self.used += 1;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::ethereum::utils::hexadecimal::hex_to_bytes;
+
+    use super::{Aes, AesCtr};
+
+    #[test]
+    fn known_answers() {
+        // FIPS 197, appendix C.
+        let plaintext: [u8; 16] = hex_to_bytes("00112233445566778899aabbccddeeff").unwrap().0.try_into().unwrap();
+        let key = hex_to_bytes("000102030405060708090a0b0c0d0e0f101112131415161718191a1b1c1d1e1f").unwrap();
+        let mut block = plaintext;
+        Aes::new(&key[..16]).encrypt_block(&mut block);
+        assert_eq!(block.to_vec(), hex_to_bytes("69c4e0d86a7b0430d8cdb78070b4c55a").unwrap().0);
+        let mut block = plaintext;
+        Aes::new(&key).encrypt_block(&mut block);
+        assert_eq!(block.to_vec(), hex_to_bytes("8ea2b7ca516745bfeafc49904b496089").unwrap().0);
+
+        // NIST SP 800-38A, F.5.1, split across calls.
+        let key = hex_to_bytes("2b7e151628aed2a6abf7158809cf4f3c").unwrap();
+        let iv = hex_to_bytes("f0f1f2f3f4f5f6f7f8f9fafbfcfdfeff").unwrap().0.try_into().unwrap();
+        let mut data = hex_to_bytes("6bc1bee22e409f96e93d7e117393172aae2d8a571e03ac9c9eb76fac45af8e51").unwrap().0;
+        let mut ctr = AesCtr::new(&key, iv);
+        let (first, second) = data.split_at_mut(5);
+        ctr.apply(first);
+        ctr.apply(second);
+        assert_eq!(data, hex_to_bytes("874d6191b620e3261bef6864990db6ce9806f66b7970fdff8617187bb9fffdff").unwrap().0);
+    }
+}
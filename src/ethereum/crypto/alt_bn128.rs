@@ -0,0 +1,472 @@
+//! ALT_BN128
+//! ^^^^^^^^^
+//!
+//! The pairing-friendly curve of the alt_bn128 precompiles (EIP-196,
+//! EIP-197), also known as BN254: its base field `Fp` and the extensions
+//! `Fp2`, `Fp6` and `Fp12`, the groups `G1` and `G2` and the optimal ate
+//! pairing.
+//!
+//! The towers follow those of `bls12_381`, with `9 + u` as the non-residue
+//! of `Fp6`. Field elements are kept in Montgomery form. Nothing here runs
+//! in constant time, as it only handles public data.
+
+use std::ops::{Add, Mul, Neg, Sub};
+
+use super::{eliptic_curve::{CurveField, Point}, finite_field::{bit, montgomery_field}};
+
+montgomery_field!(
+    /// The base field, of integers modulo a 254-bit prime.
+    Fp,
+    4,
+    [0x3c208c16d87cfd47, 0x97816a916871ca8d, 0xb85045b68181585d, 0x30644e72e131a029],
+    0x87d20782e4866389,
+    [0xd35d438dc58f0d9d, 0x0a78eb28f5c70b3d, 0x666ea36f7879462c, 0x0e0a77c19a07df2f],
+    [0xf32cfc5b538afa89, 0xb5e71911d44501fb, 0x47ab1eff0a417ff6, 0x06d89f71cab8351f]
+);
+
+/// The order `r` of `G1` and `G2`.
+pub const GROUP_ORDER: [u64; 4] = [0x43e1f593f0000001, 0x2833e84879b97091, 0xb85045b68181585d, 0x30644e72e131a029];
+
+/// `(p - 1) / 3`.
+const P_MINUS_1_DIV_3: [u64; 4] = [0x69602eb24829a9c2, 0xdd2b2385cd7b4384, 0xe81ac1e7808072c9, 0x10216f7ba065e00d];
+
+/// `(p - 1) / 2`.
+const P_MINUS_1_DIV_2: [u64; 4] = [0x9e10460b6c3e7ea3, 0xcbc0b548b438e546, 0xdc2822db40c0ac2e, 0x183227397098d014];
+
+/// `p^2`.
+const P_SQUARED: [u64; 8] = [
+    0x3b5458a2275d69b1, 0xa602072d09eac101, 0x4a50189c6d96cadc, 0x04689e957a1242c8,
+    0x26edfa5c34c6b38d, 0xb00b855116375606, 0x599a6f7c0348d21c, 0x0925c4b8763cbf9c,
+];
+
+/// `(p^4 - p^2 + 1) / r`, the hard part of the final exponentiation.
+const HARD_EXPONENT: [u64; 12] = [
+    0xe81bb482ccdf42b1, 0x5abf5cc4f49c36d4, 0xf1154e7e1da014fd, 0xdcc7b44c87cdbacf, 0xaaa441e3954bcf8a,
+    0x6b887d56d5095f23, 0x79581e16f3fd90c6, 0x3b1b1355d189227d, 0x4e529a5861876f6b, 0x6c0eb522d5b12278,
+    0x331ec15183177faf, 0x01baaa710b0759ad,
+];
+
+/// `6 u + 2` for the curve parameter `u`, the length of the Miller loop.
+const ATE_LOOP_COUNT: u128 = 0x19d797039be763ba8;
+
+/// `Fp[u] / (u^2 + 1)`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Fp2 {
+    pub c0: Fp,
+    pub c1: Fp,
+}
+
+impl Fp2 {
+    pub const ZERO: Self = Self { c0: Fp::ZERO, c1: Fp::ZERO };
+    pub const ONE: Self = Self { c0: Fp::ONE, c1: Fp::ZERO };
+
+    pub fn new(c0: Fp, c1: Fp) -> Self {
+        Self { c0, c1 }
+    }
+
+    pub fn is_zero(&self) -> bool {
+        self.c0.is_zero() && self.c1.is_zero()
+    }
+
+    pub fn square(self) -> Self {
+        self * self
+    }
+
+    /// `self^p`.
+    pub fn conjugate(self) -> Self {
+        Self { c0: self.c0, c1: -self.c1 }
+    }
+
+    /// `self * (9 + u)`, the non-residue of `Fp6`.
+    fn mul_by_nonresidue(self) -> Self {
+        let nine = |a: Fp| {
+            let a2 = a + a;
+            let a8 = a2 + a2 + a2 + a2;
+            a8 + a
+        };
+        Self { c0: nine(self.c0) - self.c1, c1: self.c0 + nine(self.c1) }
+    }
+
+    pub fn inverse(self) -> Option<Self> {
+        let norm = (self.c0.square() + self.c1.square()).inverse()?;
+        Some(Self { c0: self.c0 * norm, c1: -self.c1 * norm })
+    }
+
+    pub fn pow(self, exponent: &[u64]) -> Self {
+        let mut result = Self::ONE;
+        for i in (0..exponent.len() * 64).rev() {
+            result = result.square();
+            if bit(exponent, i) {
+                result = result * self;
+            }
+        }
+        result
+    }
+}
+
+impl Add for Fp2 {
+    type Output = Self;
+
+    fn add(self, rhs: Self) -> Self {
+        Self { c0: self.c0 + rhs.c0, c1: self.c1 + rhs.c1 }
+    }
+}
+
+impl Sub for Fp2 {
+    type Output = Self;
+
+    fn sub(self, rhs: Self) -> Self {
+        Self { c0: self.c0 - rhs.c0, c1: self.c1 - rhs.c1 }
+    }
+}
+
+impl Neg for Fp2 {
+    type Output = Self;
+
+    fn neg(self) -> Self {
+        Self { c0: -self.c0, c1: -self.c1 }
+    }
+}
+
+impl Mul for Fp2 {
+    type Output = Self;
+
+    fn mul(self, rhs: Self) -> Self {
+        let aa = self.c0 * rhs.c0;
+        let bb = self.c1 * rhs.c1;
+        let c1 = (self.c0 + self.c1) * (rhs.c0 + rhs.c1) - aa - bb;
+        Self { c0: aa - bb, c1 }
+    }
+}
+
+/// `Fp2[v] / (v^3 - (9 + u))`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Fp6 {
+    pub c0: Fp2,
+    pub c1: Fp2,
+    pub c2: Fp2,
+}
+
+impl Fp6 {
+    pub const ZERO: Self = Self { c0: Fp2::ZERO, c1: Fp2::ZERO, c2: Fp2::ZERO };
+    pub const ONE: Self = Self { c0: Fp2::ONE, c1: Fp2::ZERO, c2: Fp2::ZERO };
+
+    /// `self * v`.
+    fn mul_by_v(self) -> Self {
+        Self { c0: self.c2.mul_by_nonresidue(), c1: self.c0, c2: self.c1 }
+    }
+
+    pub fn inverse(self) -> Option<Self> {
+        let c0 = self.c0.square() - (self.c1 * self.c2).mul_by_nonresidue();
+        let c1 = self.c2.square().mul_by_nonresidue() - self.c0 * self.c1;
+        let c2 = self.c1.square() - self.c0 * self.c2;
+        let t = (self.c0 * c0 + (self.c2 * c1 + self.c1 * c2).mul_by_nonresidue()).inverse()?;
+        Some(Self { c0: c0 * t, c1: c1 * t, c2: c2 * t })
+    }
+}
+
+impl Add for Fp6 {
+    type Output = Self;
+
+    fn add(self, rhs: Self) -> Self {
+        Self { c0: self.c0 + rhs.c0, c1: self.c1 + rhs.c1, c2: self.c2 + rhs.c2 }
+    }
+}
+
+impl Sub for Fp6 {
+    type Output = Self;
+
+    fn sub(self, rhs: Self) -> Self {
+        Self { c0: self.c0 - rhs.c0, c1: self.c1 - rhs.c1, c2: self.c2 - rhs.c2 }
+    }
+}
+
+impl Neg for Fp6 {
+    type Output = Self;
+
+    fn neg(self) -> Self {
+        Self { c0: -self.c0, c1: -self.c1, c2: -self.c2 }
+    }
+}
+
+impl Mul for Fp6 {
+    type Output = Self;
+
+    fn mul(self, rhs: Self) -> Self {
+        let t0 = self.c0 * rhs.c0;
+        let t1 = self.c1 * rhs.c1;
+        let t2 = self.c2 * rhs.c2;
+        let c0 = ((self.c1 + self.c2) * (rhs.c1 + rhs.c2) - t1 - t2).mul_by_nonresidue() + t0;
+        let c1 = (self.c0 + self.c1) * (rhs.c0 + rhs.c1) - t0 - t1 + t2.mul_by_nonresidue();
+        let c2 = (self.c0 + self.c2) * (rhs.c0 + rhs.c2) - t0 - t2 + t1;
+        Self { c0, c1, c2 }
+    }
+}
+
+/// `Fp6[w] / (w^2 - v)`, where the pairing takes its values.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Fp12 {
+    pub c0: Fp6,
+    pub c1: Fp6,
+}
+
+impl Fp12 {
+    pub const ONE: Self = Self { c0: Fp6::ONE, c1: Fp6::ZERO };
+
+    pub fn square(self) -> Self {
+        self * self
+    }
+
+    /// `self^(p^6)`.
+    pub fn conjugate(self) -> Self {
+        Self { c0: self.c0, c1: -self.c1 }
+    }
+
+    pub fn inverse(self) -> Option<Self> {
+        let t = (self.c0 * self.c0 - (self.c1 * self.c1).mul_by_v()).inverse()?;
+        Some(Self { c0: self.c0 * t, c1: -(self.c1 * t) })
+    }
+
+    pub fn pow(self, exponent: &[u64]) -> Self {
+        let mut result = Self::ONE;
+        for i in (0..exponent.len() * 64).rev() {
+            result = result.square();
+            if bit(exponent, i) {
+                result = result * self;
+            }
+        }
+        result
+    }
+}
+
+impl Mul for Fp12 {
+    type Output = Self;
+
+    fn mul(self, rhs: Self) -> Self {
+        let aa = self.c0 * rhs.c0;
+        let bb = self.c1 * rhs.c1;
+        let c1 = (self.c0 + self.c1) * (rhs.c0 + rhs.c1) - aa - bb;
+        Self { c0: aa + bb.mul_by_v(), c1 }
+    }
+}
+
+impl CurveField for Fp {
+    const ZERO: Self = Fp::ZERO;
+    const ONE: Self = Fp::ONE;
+    const GROUP_ORDER: &'static [u64] = &GROUP_ORDER;
+
+    fn b() -> Self {
+        Fp::from_u64(3)
+    }
+
+    fn is_zero(&self) -> bool {
+        Fp::is_zero(self)
+    }
+
+    fn inverse(self) -> Option<Self> {
+        Fp::inverse(self)
+    }
+}
+
+impl CurveField for Fp2 {
+    const ZERO: Self = Fp2::ZERO;
+    const ONE: Self = Fp2::ONE;
+    const GROUP_ORDER: &'static [u64] = &GROUP_ORDER;
+
+    /// `3 / (9 + u)`, of the twist.
+    fn b() -> Self {
+        Fp2::new(Fp::from_u64(3), Fp::ZERO) * Fp2::new(Fp::from_u64(9), Fp::ONE).inverse().unwrap()
+    }
+
+    fn is_zero(&self) -> bool {
+        Fp2::is_zero(self)
+    }
+
+    fn inverse(self) -> Option<Self> {
+        Fp2::inverse(self)
+    }
+}
+
+/// The group of points over `Fp`.
+pub type G1 = Point<Fp>;
+
+/// The group of points of the twist over `Fp2`.
+pub type G2 = Point<Fp2>;
+
+impl G1 {
+    pub fn generator() -> Self {
+        Self::from_affine(Fp::from_u64(1), Fp::from_u64(2)).unwrap()
+    }
+
+    /// The point of the 64 byte encoding of the precompiles, `x || y`, if
+    /// it is on the curve. `(0, 0)` is the point at infinity.
+    pub fn from_be_bytes(bytes: &[u8; 64]) -> Option<Self> {
+        let x = Fp::from_be_bytes(bytes[..32].try_into().unwrap())?;
+        let y = Fp::from_be_bytes(bytes[32..].try_into().unwrap())?;
+        if x.is_zero() && y.is_zero() {
+            return Some(Self::infinity());
+        }
+        Self::from_affine(x, y)
+    }
+
+    /// The encoding of `from_be_bytes`.
+    pub fn to_be_bytes(&self) -> [u8; 64] {
+        let mut bytes = [0; 64];
+        if let Some((x, y)) = self.to_affine() {
+            bytes[..32].copy_from_slice(&x.to_be_bytes());
+            bytes[32..].copy_from_slice(&y.to_be_bytes());
+        }
+        bytes
+    }
+}
+
+impl G2 {
+    pub fn generator() -> Self {
+        let fp = |limbs| Fp::from_limbs(limbs).unwrap();
+        let x = Fp2::new(
+            fp([0x46debd5cd992f6ed, 0x674322d4f75edadd, 0x426a00665e5c4479, 0x1800deef121f1e76]),
+            fp([0x97e485b7aef312c2, 0xf1aa493335a9e712, 0x7260bfb731fb5d25, 0x198e9393920d483a]),
+        );
+        let y = Fp2::new(
+            fp([0x4ce6cc0166fa7daa, 0xe3d1e7690c43d37b, 0x4aab71808dcb408f, 0x12c85ea5db8c6deb]),
+            fp([0x55acdadcd122975b, 0xbc4b313370b38ef3, 0xec9e99ad690c3395, 0x090689d0585ff075]),
+        );
+        Self::from_affine(x, y).unwrap()
+    }
+
+    /// The point of the 128 byte encoding of the pairing precompile,
+    /// `x.c1 || x.c0 || y.c1 || y.c0`, if it is on the twist and in the
+    /// group. All zeros is the point at infinity.
+    pub fn from_be_bytes(bytes: &[u8; 128]) -> Option<Self> {
+        let fp = |i: usize| Fp::from_be_bytes(bytes[32 * i..32 * (i + 1)].try_into().unwrap());
+        let x = Fp2::new(fp(1)?, fp(0)?);
+        let y = Fp2::new(fp(3)?, fp(2)?);
+        if x.is_zero() && y.is_zero() {
+            return Some(Self::infinity());
+        }
+        Self::from_affine(x, y).filter(Self::is_torsion_free)
+    }
+}
+
+/// The line through `(x, y)` with slope `slope`, on the twist, evaluated
+/// at `(px, py)` on the curve.
+///
+/// The twist maps to the curve by `(x, y) -> (x w^2, y w^3)`, so the line
+/// is `py - slope px w + (slope x - y) w^3`. The vertical lines left out
+/// are in `Fp6` and are removed by the final exponentiation.
+fn line(slope: Fp2, x: Fp2, y: Fp2, px: Fp, py: Fp) -> Fp12 {
+    let px = Fp2::new(px, Fp::ZERO);
+    let py = Fp2::new(py, Fp::ZERO);
+    Fp12 {
+        c0: Fp6 { c0: py, c1: Fp2::ZERO, c2: Fp2::ZERO },
+        c1: Fp6 { c0: -(slope * px), c1: slope * x - y, c2: Fp2::ZERO },
+    }
+}
+
+/// The Frobenius map of the twist, `(x^p, y^p)` on the curve.
+fn frobenius((x, y): (Fp2, Fp2)) -> (Fp2, Fp2) {
+    let nonresidue = Fp2::new(Fp::from_u64(9), Fp::ONE);
+    (x.conjugate() * nonresidue.pow(&P_MINUS_1_DIV_3), y.conjugate() * nonresidue.pow(&P_MINUS_1_DIV_2))
+}
+
+/// The Miller loop of the optimal ate pairing, over the bits of
+/// `6 u + 2` and then the lines through `p Q` and `-p^2 Q`.
+fn miller_loop(p: (Fp, Fp), q: (Fp2, Fp2)) -> Fp12 {
+    let (px, py) = p;
+    let (mut tx, mut ty) = q;
+    let mut f = Fp12::ONE;
+    let mut add = |f: &mut Fp12, tx: &mut Fp2, ty: &mut Fp2, (qx, qy): (Fp2, Fp2)| {
+        let slope = (qy - *ty) * (qx - *tx).inverse().unwrap();
+        *f = *f * line(slope, *tx, *ty, px, py);
+        let x = slope.square() - *tx - qx;
+        *ty = slope * (*tx - x) - *ty;
+        *tx = x;
+    };
+    for i in (0..64).rev() {
+        let three_tx2 = tx.square() + tx.square() + tx.square();
+        let slope = three_tx2 * (ty + ty).inverse().unwrap();
+        f = f.square() * line(slope, tx, ty, px, py);
+        let x = slope.square() - tx - tx;
+        ty = slope * (tx - x) - ty;
+        tx = x;
+        if ATE_LOOP_COUNT >> i & 1 == 1 {
+            add(&mut f, &mut tx, &mut ty, q);
+        }
+    }
+    let q1 = frobenius(q);
+    let (q2x, q2y) = frobenius(q1);
+    add(&mut f, &mut tx, &mut ty, q1);
+    add(&mut f, &mut tx, &mut ty, (q2x, -q2y));
+    f
+}
+
+/// `f^((p^12 - 1) / r)`, as `f^(p^6 - 1)`, then to the power `p^2 + 1`,
+/// then to the hard part.
+fn final_exponentiation(f: Fp12) -> Fp12 {
+    let Some(inverse) = f.inverse() else { return f };
+    let f = f.conjugate() * inverse;
+    let f = f.pow(&P_SQUARED) * f;
+    f.pow(&HARD_EXPONENT)
+}
+
+/// The pairing of `p` and `q`.
+pub fn pairing(p: &G1, q: &G2) -> Fp12 {
+    match (p.to_affine(), q.to_affine()) {
+        (Some(p), Some(q)) => final_exponentiation(miller_loop(p, q)),
+        _ => Fp12::ONE,
+    }
+}
+
+/// Whether the product of the pairings of `pairs` is one, with a single
+/// final exponentiation.
+pub fn pairing_check(pairs: &[(G1, G2)]) -> bool {
+    let mut f = Fp12::ONE;
+    for (p, q) in pairs {
+        if let (Some(p), Some(q)) = (p.to_affine(), q.to_affine()) {
+            f = f * miller_loop(p, q);
+        }
+    }
+    final_exponentiation(f) == Fp12::ONE
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::ethereum::utils::hexadecimal::hex_to_bytes;
+
+    use super::{pairing, pairing_check, Fp, Fp12, G1, G2, GROUP_ORDER};
+
+    #[test]
+    fn field_arithmetic() {
+        let a = Fp::from_u64(7);
+        assert_eq!(a * a.inverse().unwrap(), Fp::ONE);
+        assert_eq!(-Fp::ONE + Fp::ONE, Fp::ZERO);
+        assert_eq!(Fp::from_be_bytes(&a.to_be_bytes()), Some(a));
+        assert!(Fp::from_be_bytes(&[0xff; 32]).is_none());
+    }
+
+    #[test]
+    fn points() {
+        let g1 = G1::generator();
+        let two_g1 = hex_to_bytes("030644e72e131a029b85045b68181585d97816a916871ca8d3c208c16d87cfd315ed738c0e0a7c92e7845f96b2ae9c0a68a6a449e3538fc7ff3ebf7a5a18a2c4").unwrap();
+        assert_eq!(g1.add(&g1).to_be_bytes()[..], two_g1[..]);
+        assert_eq!(g1.mul_limbs(&[2]), g1.double());
+        assert!(g1.mul_limbs(&GROUP_ORDER).is_infinity());
+        assert_eq!(G1::from_be_bytes(&g1.to_be_bytes()), Some(g1));
+        assert_eq!(G1::from_be_bytes(&[0; 64]), Some(G1::infinity()));
+        let mut off_curve = g1.to_be_bytes();
+        off_curve[63] ^= 1;
+        assert!(G1::from_be_bytes(&off_curve).is_none());
+        assert!(G2::generator().is_torsion_free());
+    }
+
+    #[test]
+    fn bilinearity() {
+        let (p, q) = (G1::generator(), G2::generator());
+        let e = pairing(&p, &q);
+        assert_ne!(e, Fp12::ONE);
+        assert_eq!(e.pow(&GROUP_ORDER), Fp12::ONE);
+        assert_eq!(pairing(&p.mul_limbs(&[5]), &q.mul_limbs(&[7])), e.pow(&[35]));
+        assert!(pairing_check(&[(p.mul_limbs(&[5]), q), (p.neg(), q.mul_limbs(&[5]))]));
+        assert!(!pairing_check(&[(p.mul_limbs(&[5]), q), (p.neg(), q.mul_limbs(&[7]))]));
+    }
+}
@@ -0,0 +1,104 @@
+//! The Blake2 Implementation
+//! ^^^^^^^^^^^^^^^^^^^^^^^^^^
+//!
+//! The compression function F of BLAKE2b, as exposed by the `BLAKE2F`
+//! precompile (EIP-152).
+
+const IV: [u64; 8] = [
+    0x6a09e667f3bcc908, 0xbb67ae8584caa73b, 0x3c6ef372fe94f82b, 0xa54ff53a5f1d36f1,
+    0x510e527fade682d1, 0x9b05688c2b3e6c1f, 0x1f83d9abfb41bd6b, 0x5be0cd19137e2179,
+];
+
+const SIGMA: [[usize; 16]; 10] = [
+    [0, 1, 2, 3, 4, 5, 6, 7, 8, 9, 10, 11, 12, 13, 14, 15],
+    [14, 10, 4, 8, 9, 15, 13, 6, 1, 12, 0, 2, 11, 7, 5, 3],
+    [11, 8, 12, 0, 5, 2, 15, 13, 10, 14, 3, 6, 7, 1, 9, 4],
+    [7, 9, 3, 1, 13, 12, 11, 14, 2, 6, 5, 10, 4, 0, 15, 8],
+    [9, 0, 5, 7, 2, 4, 10, 15, 14, 1, 11, 12, 6, 8, 3, 13],
+    [2, 12, 6, 10, 0, 11, 8, 3, 4, 13, 7, 5, 15, 14, 1, 9],
+    [12, 5, 1, 15, 14, 13, 4, 10, 0, 7, 6, 3, 9, 2, 8, 11],
+    [13, 11, 7, 14, 12, 1, 3, 9, 5, 0, 15, 4, 8, 6, 2, 10],
+    [6, 15, 14, 9, 11, 3, 0, 8, 12, 2, 13, 7, 1, 4, 10, 5],
+    [10, 2, 8, 4, 7, 6, 1, 5, 15, 11, 9, 14, 3, 12, 13, 0],
+];
+
+/// Parameters of the compression function.
+pub struct Blake2Parameters {
+    pub rounds: u32,
+    pub h: [u64; 8],
+    pub m: [u64; 16],
+    pub t_0: u64,
+    pub t_1: u64,
+    pub f: u8,
+}
+
+/// """
+/// Extract the parameters required in the Blake2 compression function
+/// from the provided bytes data.
+/// 
+/// Parameters
+/// ----------
+/// data :
+///     The bytes data that has been passed in the message. Must be 213
+///     bytes long.
+/// """
+pub fn get_blake2_parameters(data: &[u8; 213]) -> Blake2Parameters {
+    let word = |i: usize| u64::from_le_bytes(data[i..i+8].try_into().unwrap());
+    Blake2Parameters {
+        rounds: u32::from_be_bytes(data[0..4].try_into().unwrap()),
+        h: std::array::from_fn(|i| word(4 + i*8)),
+        m: std::array::from_fn(|i| word(68 + i*8)),
+        t_0: word(196),
+        t_1: word(204),
+        f: data[212],
+    }
+}
+
+/// The mixing function G.
+fn g(v: &mut [u64; 16], a: usize, b: usize, c: usize, d: usize, x: u64, y: u64) {
+    v[a] = v[a].wrapping_add(v[b]).wrapping_add(x);
+    v[d] = (v[d] ^ v[a]).rotate_right(32);
+    v[c] = v[c].wrapping_add(v[d]);
+    v[b] = (v[b] ^ v[c]).rotate_right(24);
+    v[a] = v[a].wrapping_add(v[b]).wrapping_add(y);
+    v[d] = (v[d] ^ v[a]).rotate_right(16);
+    v[c] = v[c].wrapping_add(v[d]);
+    v[b] = (v[b] ^ v[c]).rotate_right(63);
+}
+
+/// """
+/// 'F Compression' from section 3.2 of RFC 7693:
+/// https://tools.ietf.org/html/rfc7693#section-3.2
+/// 
+/// Returns the new state vector `h` as 64 little endian bytes.
+/// """
+pub fn compress(params: &Blake2Parameters) -> [u8; 64] {
+    let mut v = [0_u64; 16];
+    v[..8].copy_from_slice(&params.h);
+    v[8..].copy_from_slice(&IV);
+    v[12] ^= params.t_0;
+    v[13] ^= params.t_1;
+    if params.f != 0 {
+        v[14] = !v[14];
+    }
+
+    let m = &params.m;
+    for r in 0..params.rounds as usize {
+        let s = &SIGMA[r % 10];
+        g(&mut v, 0, 4, 8, 12, m[s[0]], m[s[1]]);
+        g(&mut v, 1, 5, 9, 13, m[s[2]], m[s[3]]);
+        g(&mut v, 2, 6, 10, 14, m[s[4]], m[s[5]]);
+        g(&mut v, 3, 7, 11, 15, m[s[6]], m[s[7]]);
+        g(&mut v, 0, 5, 10, 15, m[s[8]], m[s[9]]);
+        g(&mut v, 1, 6, 11, 12, m[s[10]], m[s[11]]);
+        g(&mut v, 2, 7, 8, 13, m[s[12]], m[s[13]]);
+        g(&mut v, 3, 4, 9, 14, m[s[14]], m[s[15]]);
+    }
+
+    let mut output = [0; 64];
+    for i in 0..8 {
+        let h = params.h[i] ^ v[i] ^ v[i + 8];
+        output[i*8..i*8+8].copy_from_slice(&h.to_le_bytes());
+    }
+    output
+}
@@ -0,0 +1,564 @@
+//! BLS12-381
+//! ^^^^^^^^^
+//!
+//! The pairing-friendly curve of KZG commitments: its base field `Fp` and
+//! the extensions `Fp2`, `Fp6` and `Fp12`, the scalar field `Fr`, the
+//! groups `G1` and `G2` with the compressed encoding of their points, and
+//! the optimal ate pairing.
+//!
+//! Field elements are kept in Montgomery form. Nothing here runs in
+//! constant time, as it only handles public data.
+
+use std::ops::{Add, Mul, Neg, Sub};
+
+use super::{eliptic_curve::{CurveField, Point}, finite_field::{bit, montgomery_field}};
+
+montgomery_field!(
+    /// The base field, of integers modulo a 381-bit prime.
+    Fp,
+    6,
+    [0xb9feffffffffaaab, 0x1eabfffeb153ffff, 0x6730d2a0f6b0f624, 0x64774b84f38512bf, 0x4b1ba7b6434bacd7, 0x1a0111ea397fe69a],
+    0x89f3fffcfffcfffd,
+    [0x760900000002fffd, 0xebf4000bc40c0002, 0x5f48985753c758ba, 0x77ce585370525745, 0x5c071a97a256ec6d, 0x15f65ec3fa80e493],
+    [0xf4df1f341c341746, 0x0a76e6a609d104f1, 0x8de5476c4c95b6d5, 0x67eb88a9939d83c0, 0x9a793e85b519952d, 0x11988fe592cae3aa]
+);
+
+montgomery_field!(
+    /// The scalar field, of integers modulo the order `r` of the groups.
+    Fr,
+    4,
+    [0xffffffff00000001, 0x53bda402fffe5bfe, 0x3339d80809a1d805, 0x73eda753299d7d48],
+    0xfffffffeffffffff,
+    [0x00000001fffffffe, 0x5884b7fa00034802, 0x998c4fefecbc4ff5, 0x1824b159acc5056f],
+    [0xc999e990f3f29c6d, 0x2b6cedcb87925c23, 0x05d314967254398f, 0x0748d9d99f59ff11]
+);
+
+/// `(p + 1) / 4`, the exponent of square roots in `Fp`.
+const P_PLUS_1_DIV_4: [u64; 6] =
+    [0xee7fbfffffffeaab, 0x07aaffffac54ffff, 0xd9cc34a83dac3d89, 0xd91dd2e13ce144af, 0x92c6e9ed90d2eb35, 0x0680447a8e5ff9a6];
+
+/// `(p - 3) / 4`.
+const P_MINUS_3_DIV_4: [u64; 6] =
+    [0xee7fbfffffffeaaa, 0x07aaffffac54ffff, 0xd9cc34a83dac3d89, 0xd91dd2e13ce144af, 0x92c6e9ed90d2eb35, 0x0680447a8e5ff9a6];
+
+/// `(p - 1) / 2`.
+const P_MINUS_1_DIV_2: [u64; 6] =
+    [0xdcff7fffffffd555, 0x0f55ffff58a9ffff, 0xb39869507b587b12, 0xb23ba5c279c2895f, 0x258dd3db21a5d66b, 0x0d0088f51cbff34d];
+
+/// `p^2`.
+const P_SQUARED: [u64; 12] = [
+    0x26aa00001c718e39, 0x7ced6b1d76382eab, 0x162c338362113cfd, 0x66bf91ed3e71b743, 0x292e85a87091a049, 0x1d68619c86185c7b,
+    0xf53149330978ef01, 0x50a62cfd16ddca6e, 0x66e59e49349e8bd0, 0xe2dc90e50e7046b4, 0x4bd278eaa22f25e9, 0x02a437a4b8c35fc7,
+];
+
+/// `(p^4 - p^2 + 1) / r`, the hard part of the final exponentiation.
+const HARD_EXPONENT: [u64; 20] = [
+    0xe516c3f438e3ba79, 0xfa9912aae208ccf1, 0x905ce937335d5b68, 0xc71a2629b0dea236, 0x83774940996754c8,
+    0x21d160aeb6a1e799, 0x2ed0b283ed237db4, 0x915c97f36c6f1821, 0x67f17fcbde783765, 0x2378b9039096d1b7,
+    0x7988f8761bdc51dc, 0x2076995003fc77a1, 0x827eca0ba621315b, 0xe5a72bce8d63cb9f, 0xf68f7764c28b6f8a,
+    0x2f230063cf081517, 0x94506632528d6a9a, 0xd3cde88eeb996ca3, 0xc0bd38c3195c899e, 0x000f686b3d807d01,
+];
+
+/// The absolute value of the curve parameter `x`, which is negative.
+const X: u64 = 0xd201000000010000;
+
+impl Fp {
+    pub fn sqrt(self) -> Option<Self> {
+        let root = self.pow(&P_PLUS_1_DIV_4);
+        (root.square() == self).then_some(root)
+    }
+}
+
+/// `Fp[u] / (u^2 + 1)`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Fp2 {
+    pub c0: Fp,
+    pub c1: Fp,
+}
+
+impl Fp2 {
+    pub const ZERO: Self = Self { c0: Fp::ZERO, c1: Fp::ZERO };
+    pub const ONE: Self = Self { c0: Fp::ONE, c1: Fp::ZERO };
+
+    pub fn new(c0: Fp, c1: Fp) -> Self {
+        Self { c0, c1 }
+    }
+
+    pub fn is_zero(&self) -> bool {
+        self.c0.is_zero() && self.c1.is_zero()
+    }
+
+    pub fn square(self) -> Self {
+        self * self
+    }
+
+    pub fn conjugate(self) -> Self {
+        Self { c0: self.c0, c1: -self.c1 }
+    }
+
+    /// `self * (u + 1)`, the non-residue of `Fp6`.
+    fn mul_by_nonresidue(self) -> Self {
+        Self { c0: self.c0 - self.c1, c1: self.c0 + self.c1 }
+    }
+
+    pub fn inverse(self) -> Option<Self> {
+        let norm = (self.c0.square() + self.c1.square()).inverse()?;
+        Some(Self { c0: self.c0 * norm, c1: -self.c1 * norm })
+    }
+
+    pub fn pow(self, exponent: &[u64]) -> Self {
+        let mut result = Self::ONE;
+        for i in (0..exponent.len() * 64).rev() {
+            result = result.square();
+            if bit(exponent, i) {
+                result = result * self;
+            }
+        }
+        result
+    }
+
+    /// Algorithm 9 of "Square root computation over even extension fields"
+    /// for `p = 3 mod 4`.
+    pub fn sqrt(self) -> Option<Self> {
+        let a1 = self.pow(&P_MINUS_3_DIV_4);
+        let x0 = a1 * self;
+        let alpha = a1 * x0;
+        let root = if alpha == -Self::ONE {
+            Self { c0: -x0.c1, c1: x0.c0 }
+        } else {
+            (alpha + Self::ONE).pow(&P_MINUS_1_DIV_2) * x0
+        };
+        (root.square() == self).then_some(root)
+    }
+
+    /// The sign of the compressed encoding of points: that of `c1`, or of
+    /// `c0` if `c1` is zero.
+    pub fn is_lexicographically_largest(self) -> bool {
+        if self.c1.is_zero() { self.c0.is_lexicographically_largest() } else { self.c1.is_lexicographically_largest() }
+    }
+}
+
+impl Add for Fp2 {
+    type Output = Self;
+
+    fn add(self, rhs: Self) -> Self {
+        Self { c0: self.c0 + rhs.c0, c1: self.c1 + rhs.c1 }
+    }
+}
+
+impl Sub for Fp2 {
+    type Output = Self;
+
+    fn sub(self, rhs: Self) -> Self {
+        Self { c0: self.c0 - rhs.c0, c1: self.c1 - rhs.c1 }
+    }
+}
+
+impl Neg for Fp2 {
+    type Output = Self;
+
+    fn neg(self) -> Self {
+        Self { c0: -self.c0, c1: -self.c1 }
+    }
+}
+
+impl Mul for Fp2 {
+    type Output = Self;
+
+    fn mul(self, rhs: Self) -> Self {
+        let aa = self.c0 * rhs.c0;
+        let bb = self.c1 * rhs.c1;
+        let c1 = (self.c0 + self.c1) * (rhs.c0 + rhs.c1) - aa - bb;
+        Self { c0: aa - bb, c1 }
+    }
+}
+
+/// `Fp2[v] / (v^3 - (u + 1))`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Fp6 {
+    pub c0: Fp2,
+    pub c1: Fp2,
+    pub c2: Fp2,
+}
+
+impl Fp6 {
+    pub const ZERO: Self = Self { c0: Fp2::ZERO, c1: Fp2::ZERO, c2: Fp2::ZERO };
+    pub const ONE: Self = Self { c0: Fp2::ONE, c1: Fp2::ZERO, c2: Fp2::ZERO };
+
+    /// `self * v`.
+    fn mul_by_v(self) -> Self {
+        Self { c0: self.c2.mul_by_nonresidue(), c1: self.c0, c2: self.c1 }
+    }
+
+    pub fn inverse(self) -> Option<Self> {
+        let c0 = self.c0.square() - (self.c1 * self.c2).mul_by_nonresidue();
+        let c1 = self.c2.square().mul_by_nonresidue() - self.c0 * self.c1;
+        let c2 = self.c1.square() - self.c0 * self.c2;
+        let t = (self.c0 * c0 + (self.c2 * c1 + self.c1 * c2).mul_by_nonresidue()).inverse()?;
+        Some(Self { c0: c0 * t, c1: c1 * t, c2: c2 * t })
+    }
+}
+
+impl Add for Fp6 {
+    type Output = Self;
+
+    fn add(self, rhs: Self) -> Self {
+        Self { c0: self.c0 + rhs.c0, c1: self.c1 + rhs.c1, c2: self.c2 + rhs.c2 }
+    }
+}
+
+impl Sub for Fp6 {
+    type Output = Self;
+
+    fn sub(self, rhs: Self) -> Self {
+        Self { c0: self.c0 - rhs.c0, c1: self.c1 - rhs.c1, c2: self.c2 - rhs.c2 }
+    }
+}
+
+impl Neg for Fp6 {
+    type Output = Self;
+
+    fn neg(self) -> Self {
+        Self { c0: -self.c0, c1: -self.c1, c2: -self.c2 }
+    }
+}
+
+impl Mul for Fp6 {
+    type Output = Self;
+
+    fn mul(self, rhs: Self) -> Self {
+        let t0 = self.c0 * rhs.c0;
+        let t1 = self.c1 * rhs.c1;
+        let t2 = self.c2 * rhs.c2;
+        let c0 = ((self.c1 + self.c2) * (rhs.c1 + rhs.c2) - t1 - t2).mul_by_nonresidue() + t0;
+        let c1 = (self.c0 + self.c1) * (rhs.c0 + rhs.c1) - t0 - t1 + t2.mul_by_nonresidue();
+        let c2 = (self.c0 + self.c2) * (rhs.c0 + rhs.c2) - t0 - t2 + t1;
+        Self { c0, c1, c2 }
+    }
+}
+
+/// `Fp6[w] / (w^2 - v)`, where the pairing takes its values.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Fp12 {
+    pub c0: Fp6,
+    pub c1: Fp6,
+}
+
+impl Fp12 {
+    pub const ONE: Self = Self { c0: Fp6::ONE, c1: Fp6::ZERO };
+
+    pub fn square(self) -> Self {
+        self * self
+    }
+
+    /// `self^(p^6)`.
+    pub fn conjugate(self) -> Self {
+        Self { c0: self.c0, c1: -self.c1 }
+    }
+
+    pub fn inverse(self) -> Option<Self> {
+        let t = (self.c0 * self.c0 - (self.c1 * self.c1).mul_by_v()).inverse()?;
+        Some(Self { c0: self.c0 * t, c1: -(self.c1 * t) })
+    }
+
+    pub fn pow(self, exponent: &[u64]) -> Self {
+        let mut result = Self::ONE;
+        for i in (0..exponent.len() * 64).rev() {
+            result = result.square();
+            if bit(exponent, i) {
+                result = result * self;
+            }
+        }
+        result
+    }
+}
+
+impl Mul for Fp12 {
+    type Output = Self;
+
+    fn mul(self, rhs: Self) -> Self {
+        let aa = self.c0 * rhs.c0;
+        let bb = self.c1 * rhs.c1;
+        let c1 = (self.c0 + self.c1) * (rhs.c0 + rhs.c1) - aa - bb;
+        Self { c0: aa + bb.mul_by_v(), c1 }
+    }
+}
+
+impl CurveField for Fp {
+    const ZERO: Self = Fp::ZERO;
+    const ONE: Self = Fp::ONE;
+    const GROUP_ORDER: &'static [u64] = &Fr::MODULUS;
+
+    fn b() -> Self {
+        Fp::from_u64(4)
+    }
+
+    fn is_zero(&self) -> bool {
+        Fp::is_zero(self)
+    }
+
+    fn inverse(self) -> Option<Self> {
+        Fp::inverse(self)
+    }
+}
+
+impl CurveField for Fp2 {
+    const ZERO: Self = Fp2::ZERO;
+    const ONE: Self = Fp2::ONE;
+    const GROUP_ORDER: &'static [u64] = &Fr::MODULUS;
+
+    /// `4 (u + 1)`, of the twist.
+    fn b() -> Self {
+        Fp2::new(Fp::from_u64(4), Fp::from_u64(4))
+    }
+
+    fn is_zero(&self) -> bool {
+        Fp2::is_zero(self)
+    }
+
+    fn inverse(self) -> Option<Self> {
+        Fp2::inverse(self)
+    }
+}
+
+/// The group of points over `Fp`.
+pub type G1 = Point<Fp>;
+
+/// The group of points of the twist over `Fp2`.
+pub type G2 = Point<Fp2>;
+
+
+/// Flags in the first byte of a compressed point.
+const COMPRESSED: u8 = 0x80;
+const INFINITY: u8 = 0x40;
+const SIGN: u8 = 0x20;
+
+/// The flags of a compressed point and whether it is the point at infinity,
+/// which must have no other bits set.
+fn compressed_flags(bytes: &[u8]) -> Option<(bool, bool)> {
+    let flags = bytes[0];
+    if flags & COMPRESSED == 0 {
+        return None;
+    }
+    if flags & INFINITY != 0 {
+        let rest_zero = bytes[0] & !(COMPRESSED | INFINITY) == 0 && bytes[1..].iter().all(|b| *b == 0);
+        return rest_zero.then_some((true, false));
+    }
+    Some((false, flags & SIGN != 0))
+}
+
+fn compressed_fp(bytes: &[u8]) -> Option<Fp> {
+    let mut x = [0; 48];
+    x.copy_from_slice(bytes);
+    x[0] &= !(COMPRESSED | INFINITY | SIGN);
+    Fp::from_be_bytes(&x)
+}
+
+impl G1 {
+    pub fn mul(&self, scalar: &Fr) -> Self {
+        self.mul_limbs(&scalar.to_limbs())
+    }
+
+    pub fn generator() -> Self {
+        let x = Fp::from_limbs([0xfb3af00adb22c6bb, 0x6c55e83ff97a1aef, 0xa14e3a3f171bac58, 0xc3688c4f9774b905, 0x2695638c4fa9ac0f, 0x17f1d3a73197d794]);
+        let y = Fp::from_limbs([0x0caa232946c5e7e1, 0xd03cc744a2888ae4, 0x00db18cb2c04b3ed, 0xfcf5e095d5d00af6, 0xa09e30ed741d8ae4, 0x08b3f481e3aaa0f1]);
+        Self::from_affine(x.unwrap(), y.unwrap()).unwrap()
+    }
+
+    /// A point from its compressed encoding, if it is valid and in the
+    /// group.
+    pub fn from_compressed(bytes: &[u8; 48]) -> Option<Self> {
+        let (infinity, sign) = compressed_flags(bytes)?;
+        if infinity {
+            return Some(Self::infinity());
+        }
+        let x = compressed_fp(bytes)?;
+        let mut y = (x.square() * x + Fp::b()).sqrt()?;
+        if y.is_lexicographically_largest() != sign {
+            y = -y;
+        }
+        Self::from_affine(x, y).filter(Self::is_torsion_free)
+    }
+
+    pub fn to_compressed(&self) -> [u8; 48] {
+        let Some((x, y)) = self.to_affine() else {
+            let mut bytes = [0; 48];
+            bytes[0] = COMPRESSED | INFINITY;
+            return bytes;
+        };
+        let mut bytes = x.to_be_bytes();
+        bytes[0] |= COMPRESSED | if y.is_lexicographically_largest() { SIGN } else { 0 };
+        bytes
+    }
+}
+
+impl G2 {
+    pub fn mul(&self, scalar: &Fr) -> Self {
+        self.mul_limbs(&scalar.to_limbs())
+    }
+
+    pub fn generator() -> Self {
+        let fp = |limbs| Fp::from_limbs(limbs).unwrap();
+        let x = Fp2::new(
+            fp([0xd48056c8c121bdb8, 0x0bac0326a805bbef, 0xb4510b647ae3d177, 0xc6e47ad4fa403b02, 0x260805272dc51051, 0x024aa2b2f08f0a91]),
+            fp([0xe5ac7d055d042b7e, 0x334cf11213945d57, 0xb5da61bbdc7f5049, 0x596bd0d09920b61a, 0x7dacd3a088274f65, 0x13e02b6052719f60]),
+        );
+        let y = Fp2::new(
+            fp([0xe193548608b82801, 0x923ac9cc3baca289, 0x6d429a695160d12c, 0xadfd9baa8cbdd3a7, 0x8cc9cdc6da2e351a, 0x0ce5d527727d6e11]),
+            fp([0xaaa9075ff05f79be, 0x3f370d275cec1da1, 0x267492ab572e99ab, 0xcb3e287e85a763af, 0x32acd2b02bc28b99, 0x0606c4a02ea734cc]),
+        );
+        Self::from_affine(x, y).unwrap()
+    }
+
+    /// A point from its compressed encoding, `x.c1 || x.c0` with the flags
+    /// in the first byte, if it is valid and in the group.
+    pub fn from_compressed(bytes: &[u8; 96]) -> Option<Self> {
+        let (infinity, sign) = compressed_flags(bytes)?;
+        if infinity {
+            return Some(Self::infinity());
+        }
+        let x = Fp2::new(Fp::from_be_bytes(bytes[48..].try_into().unwrap())?, compressed_fp(&bytes[..48])?);
+        let mut y = (x.square() * x + Fp2::b()).sqrt()?;
+        if y.is_lexicographically_largest() != sign {
+            y = -y;
+        }
+        Self::from_affine(x, y).filter(Self::is_torsion_free)
+    }
+
+    pub fn to_compressed(&self) -> [u8; 96] {
+        let mut bytes = [0; 96];
+        let Some((x, y)) = self.to_affine() else {
+            bytes[0] = COMPRESSED | INFINITY;
+            return bytes;
+        };
+        bytes[..48].copy_from_slice(&x.c1.to_be_bytes());
+        bytes[48..].copy_from_slice(&x.c0.to_be_bytes());
+        bytes[0] |= COMPRESSED | if y.is_lexicographically_largest() { SIGN } else { 0 };
+        bytes
+    }
+}
+
+/// The line through `(x, y)` with slope `slope`, on the twist, evaluated
+/// at `(px, py)` on the curve and scaled by `w^3`.
+///
+/// The twist maps to the curve by `(x, y) -> (x / w^2, y / w^3)`, so the
+/// line is `py w^3 - slope px w^2 + (slope x - y)`. Factors in proper
+/// subfields of `Fp12`, such as the scale and the vertical lines left out,
+/// are removed by the final exponentiation.
+fn line(slope: Fp2, x: Fp2, y: Fp2, px: Fp, py: Fp) -> Fp12 {
+    let px = Fp2::new(px, Fp::ZERO);
+    let py = Fp2::new(py, Fp::ZERO);
+    Fp12 {
+        c0: Fp6 { c0: slope * x - y, c1: -(slope * px), c2: Fp2::ZERO },
+        c1: Fp6 { c0: Fp2::ZERO, c1: py, c2: Fp2::ZERO },
+    }
+}
+
+/// The Miller loop of the optimal ate pairing, over the bits of `x`.
+fn miller_loop(p: (Fp, Fp), q: (Fp2, Fp2)) -> Fp12 {
+    let (px, py) = p;
+    let (qx, qy) = q;
+    let (mut tx, mut ty) = q;
+    let mut f = Fp12::ONE;
+    for i in (0..63).rev() {
+        let three_tx2 = tx.square() + tx.square() + tx.square();
+        let slope = three_tx2 * (ty + ty).inverse().unwrap();
+        f = f.square() * line(slope, tx, ty, px, py);
+        let x = slope.square() - tx - tx;
+        ty = slope * (tx - x) - ty;
+        tx = x;
+        if X >> i & 1 == 1 {
+            let slope = (qy - ty) * (qx - tx).inverse().unwrap();
+            f = f * line(slope, tx, ty, px, py);
+            let x = slope.square() - tx - qx;
+            ty = slope * (tx - x) - ty;
+            tx = x;
+        }
+    }
+    // `x` is negative.
+    f.conjugate()
+}
+
+/// `f^((p^12 - 1) / r)`, as `f^(p^6 - 1)`, then to the power `p^2 + 1`,
+/// then to the hard part.
+fn final_exponentiation(f: Fp12) -> Fp12 {
+    let Some(inverse) = f.inverse() else { return f };
+    let f = f.conjugate() * inverse;
+    let f = f.pow(&P_SQUARED) * f;
+    f.pow(&HARD_EXPONENT)
+}
+
+/// The pairing of `p` and `q`.
+pub fn pairing(p: &G1, q: &G2) -> Fp12 {
+    match (p.to_affine(), q.to_affine()) {
+        (Some(p), Some(q)) => final_exponentiation(miller_loop(p, q)),
+        _ => Fp12::ONE,
+    }
+}
+
+/// Whether the product of the pairings of `pairs` is one, with a single
+/// final exponentiation.
+pub fn pairing_check(pairs: &[(G1, G2)]) -> bool {
+    let mut f = Fp12::ONE;
+    for (p, q) in pairs {
+        if let (Some(p), Some(q)) = (p.to_affine(), q.to_affine()) {
+            f = f * miller_loop(p, q);
+        }
+    }
+    final_exponentiation(f) == Fp12::ONE
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::ethereum::utils::hexadecimal::hex_to_bytes;
+
+    use super::{pairing, pairing_check, Fp, Fp12, Fr, G1, G2};
+
+    #[test]
+    fn field_arithmetic() {
+        let a = Fp::from_u64(7);
+        assert_eq!(a * a.inverse().unwrap(), Fp::ONE);
+        assert_eq!(Fp::from_u64(9).sqrt().map(|root| root.square()), Some(Fp::from_u64(9)));
+        assert_eq!(-Fp::ONE + Fp::ONE, Fp::ZERO);
+        assert_eq!(Fp::from_be_bytes(&a.to_be_bytes()), Some(a));
+        assert_eq!(Fr::from_u64(3) - Fr::from_u64(5) + Fr::from_u64(2), Fr::ZERO);
+        assert!(Fr::from_be_bytes(&[0xff; 32]).is_none());
+    }
+
+    #[test]
+    fn compressed_points() {
+        let g1 = hex_to_bytes("97f1d3a73197d7942695638c4fa9ac0fc3688c4f9774b905a14e3a3f171bac586c55e83ff97a1aeffb3af00adb22c6bb").unwrap();
+        assert_eq!(G1::generator().to_compressed()[..], g1[..]);
+        assert_eq!(G1::from_compressed(&g1.0.try_into().unwrap()), Some(G1::generator()));
+        let minus_g1 = G1::generator().neg();
+        assert_eq!(G1::from_compressed(&minus_g1.to_compressed()), Some(minus_g1));
+
+        let g2 = hex_to_bytes("93e02b6052719f607dacd3a088274f65596bd0d09920b61ab5da61bbdc7f5049334cf11213945d57e5ac7d055d042b7e024aa2b2f08f0a91260805272dc51051c6e47ad4fa403b02b4510b647ae3d1770bac0326a805bbefd48056c8c121bdb8").unwrap();
+        assert_eq!(G2::generator().to_compressed()[..], g2[..]);
+        let point = G2::generator().mul(&Fr::from_u64(1234));
+        assert_eq!(G2::from_compressed(&point.to_compressed()), Some(point));
+
+        let mut infinity = [0; 48];
+        infinity[0] = 0xc0;
+        assert!(G1::from_compressed(&infinity).unwrap().is_infinity());
+        infinity[47] = 1;
+        assert!(G1::from_compressed(&infinity).is_none());
+        // On the curve but not in the group.
+        let mut outside = [0; 48];
+        outside[0] = 0x80;
+        outside[47] = 4;
+        assert!(G1::from_compressed(&outside).is_none());
+    }
+
+    #[test]
+    fn bilinearity() {
+        let (p, q) = (G1::generator(), G2::generator());
+        let (a, b) = (Fr::from_u64(5), Fr::from_u64(7));
+        let e = pairing(&p, &q);
+        assert_ne!(e, Fp12::ONE);
+        assert_eq!(pairing(&p.mul(&a), &q.mul(&b)), e.pow(&[35]));
+        assert_eq!(pairing(&p.mul(&(a * b)), &q), pairing(&p, &q.mul(&(a * b))));
+        assert!(pairing_check(&[(p.mul(&a), q), (p.neg(), q.mul(&a))]));
+        assert!(!pairing_check(&[(p.mul(&a), q), (p.neg(), q.mul(&b))]));
+    }
+}
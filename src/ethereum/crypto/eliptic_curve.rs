@@ -1,11 +1,11 @@
 //! Elliptic Curves
 //! ^^^^^^^^^^^^^^^
 
-use std::{fmt::Debug, ops::{Add, Div, Mul, Sub}};
+use std::{fmt::Debug, ops::{Add, Div, Mul, Neg, Sub}};
 
-use crate::ethereum::ethereum_types::{bytes::Bytes, numeric::U256};
+use crate::ethereum::{ethereum_types::{bytes::Bytes, numeric::U256}, exceptions::Exception};
 
-use super::hash::Hash32;
+use super::{finite_field::bit, hash::{hmac_sha256, Hash32}};
 
 
 pub const SECP256K1N : U256 = U256::from_limbs([0xFFFFFFFFFFFFFFFF,0xFFFFFFFFFFFFFFFE,0xBAAEDCE6AF48A03B,0xBFD25E8CD0364141]);
@@ -21,16 +21,28 @@ pub trait EllipticCurveAB<F> {
     fn b() -> F;
 }
 
+/// Field prime of secp256k1, `2**256 - 2**32 - 977`.
+const SECP256K1P : U256 = U256::from_limbs([0xFFFFFFFFFFFFFFFF,0xFFFFFFFFFFFFFFFF,0xFFFFFFFFFFFFFFFF,0xFFFFFFFEFFFFFC2F]);
+
+/// `2**256 % SECP256K1P`, used to fold the high half of a product.
+const SECP256K1P_COMPLEMENT : u64 = 0x1000003D1;
+
+/// Generator point of secp256k1.
+const SECP256K1G : (U256, U256) = (
+    U256::from_limbs([0x79BE667EF9DCBBAC,0x55A06295CE870B07,0x029BFCDB2DCE28D9,0x59F2815B16F81798]),
+    U256::from_limbs([0x483ADA7726A3C465,0x5DA4FBFC0E1108A8,0xFD17B448A6855419,0x9C47D08FFB10D4B8]),
+);
+
 /// Recovers the public key from a given signature.
 /// 
 /// Parameters
 /// ----------
 /// r :
-///     TODO
+///     The x coordinate of the random point used in the signature.
 /// s :
-///     TODO
+///     The signature proof.
 /// v :
-///     TODO
+///     The recovery id, the parity of the y coordinate of that point.
 /// msg_hash :
 ///     Hash of the message being recovered.
 /// 
@@ -38,23 +50,453 @@ pub trait EllipticCurveAB<F> {
 /// -------
 /// public_key : `ethereum.base_types.Bytes`
 ///     Recovered public key.
-pub fn secp256k1_recover(r: U256, s: U256, v: U256, msg_hash: Hash32) -> Bytes {
-    let r_bytes = r.to_be_bytes();
-    let s_bytes = s.to_be_bytes();
+pub fn secp256k1_recover(r: U256, s: U256, v: U256, msg_hash: Hash32) -> Result<Bytes, Exception> {
+    if r.is_zero() || r >= SECP256K1N || s.is_zero() || s >= SECP256K1N {
+        return Err(Exception::InvalidSignatureError("signature out of range"));
+    }
+    if v > U256::ONE {
+        return Err(Exception::InvalidSignatureError("bad recovery id"));
+    }
+
+    // The point whose x coordinate is r.
+    let x = r;
+    let y_squared = fadd(fmul(fmul(x, x), x), U256::from(7_u32));
+    let mut y = fpow(y_squared, SECP256K1P.shr(2) + U256::ONE);
+    if fmul(y, y) != y_squared {
+        return Err(Exception::InvalidSignatureError("r is not on the curve"));
+    }
+    if y.bit(0) != v.bit(0) {
+        y = fsub(U256::ZERO, y);
+    }
+
+    // public_key = r**-1 * (s * R - z * G)
+    let r_inv = r.pow_mod(SECP256K1N - U256::from(2_u32), SECP256K1N);
+    let z = U256::from_be_bytes(msg_hash.0).div_rem(SECP256K1N).1;
+    let u1 = (SECP256K1N - z).mul_mod(r_inv, SECP256K1N);
+    let u2 = s.mul_mod(r_inv, SECP256K1N);
+    let point = Jacobian::from_affine(SECP256K1G)
+        .mul(u1)
+        .add(&Jacobian::from_affine((x, y)).mul(u2));
+    let Some((x, y)) = point.to_affine() else {
+        return Err(Exception::InvalidSignatureError("recovered the point at infinity"));
+    };
+
+    let mut public_key = Vec::with_capacity(64);
+    public_key.extend_from_slice(&x.to_be_bytes());
+    public_key.extend_from_slice(&y.to_be_bytes());
+    Ok(Bytes(public_key))
+}
+
+/// Signs a message hash with a secret key.
+/// 
+/// The nonce is derived as in RFC 6979 with HMAC-SHA256, so signatures are
+/// deterministic and the same as those of other libraries before `s` is
+/// normalised: `s` is always in the lower half of the curve order. The
+/// nonce is multiplied by the generator with `ladder`.
+/// 
+/// Parameters
+/// ----------
+/// msg_hash :
+///     Hash of the message being signed.
+/// secret_key :
+///     The secret key, in the range `1..SECP256K1N`.
+/// 
+/// Returns
+/// -------
+/// signature : `Tuple[U256, U256, U256]`
+///     The `r`, `s` and `v` (recovery id) of the signature, or an error if
+///     the secret key is out of range.
+pub fn secp256k1_sign(msg_hash: Hash32, secret_key: U256) -> Result<(U256, U256, U256), Exception> {
+    if secret_key.is_zero() || secret_key >= SECP256K1N {
+        return Err(Exception::InvalidSignatureError("secret key out of range"));
+    }
+    let z = U256::from_be_bytes(msg_hash.0).div_rem(SECP256K1N).1;
+    let mut nonces = Rfc6979::new(secret_key, z);
+    loop {
+        let k = nonces.next();
+        let (x, y) = ladder(SECP256K1G, k).unwrap();
+        let r = x.div_rem(SECP256K1N).1;
+        if r.is_zero() || x >= SECP256K1N {
+            continue;
+        }
+        let k_inv = k.pow_mod(SECP256K1N - U256::from(2_u32), SECP256K1N);
+        let mut s = k_inv.mul_mod(z.add_mod(r.mul_mod(secret_key, SECP256K1N), SECP256K1N), SECP256K1N);
+        if s.is_zero() {
+            continue;
+        }
+        let mut v = y.bit(0);
+        if s > SECP256K1N.shr(1) {
+            s = SECP256K1N - s;
+            v = !v;
+        }
+        return Ok((r, s, U256::from(v as u32)));
+    }
+}
+
+/// The nonces of RFC 6979, section 3.2, with HMAC-SHA256 for a secret key
+/// and a message hash reduced modulo the curve order, in the order a
+/// signer tries them.
+struct Rfc6979 {
+    k: Hash32,
+    v: Hash32,
+    /// Whether a nonce was returned, so the next one must reseed.
+    used: bool,
+}
+
+impl Rfc6979 {
+    fn new(secret_key: U256, z: U256) -> Self {
+        let (x, h) = (secret_key.to_be_bytes(), z.to_be_bytes());
+        let (mut k, mut v) = (Hash32([0; 32]), Hash32([1; 32]));
+        for tag in [0x00, 0x01] {
+            k = hmac_sha256(&k.0, &[&v.0[..], &[tag], &x, &h].concat());
+            v = hmac_sha256(&k.0, &v.0);
+        }
+        Self { k, v, used: false }
+    }
+
+    fn next(&mut self) -> U256 {
+        loop {
+            if self.used {
+                self.k = hmac_sha256(&self.k.0, &[&self.v.0[..], &[0x00]].concat());
+                self.v = hmac_sha256(&self.k.0, &self.v.0);
+            }
+            self.used = true;
+            self.v = hmac_sha256(&self.k.0, &self.v.0);
+            let k = U256::from_be_bytes(self.v.0);
+            if !k.is_zero() && k < SECP256K1N {
+                return k;
+            }
+        }
+    }
+}
+
+/// Computes the uncompressed public key, without the `0x04` prefix, of a
+/// secret key, multiplying the generator with `ladder`.
+pub fn secp256k1_public_key(secret_key: U256) -> Bytes {
+    let (x, y) = ladder(SECP256K1G, secret_key).unwrap();
+    let mut public_key = Vec::with_capacity(64);
+    public_key.extend_from_slice(&x.to_be_bytes());
+    public_key.extend_from_slice(&y.to_be_bytes());
+    Bytes(public_key)
+}
+
+/// Computes the x coordinate of `secret_key` times the point
+/// `public_key`, given without the `0x04` prefix: the secret which the
+/// owners of the two key pairs share. The point is multiplied with
+/// `ladder`.
+pub fn secp256k1_ecdh(secret_key: U256, public_key: &[u8]) -> Result<[u8; 32], Exception> {
+    let point = secp256k1_point(public_key)?;
+    let (x, _) = ladder(point, secret_key).ok_or(Exception::InvalidSignatureError("shared point at infinity"))?;
+    Ok(x.to_be_bytes())
+}
+
+/// Compresses a public key, given without the `0x04` prefix, to its x
+/// coordinate prefixed by `0x02` or `0x03` for the parity of y.
+pub fn secp256k1_compress(public_key: &[u8]) -> Result<[u8; 33], Exception> {
+    let (x, y) = secp256k1_point(public_key)?;
+    let mut compressed = [0; 33];
+    compressed[0] = if y.bit(0) { 0x03 } else { 0x02 };
+    compressed[1..].copy_from_slice(&x.to_be_bytes());
+    Ok(compressed)
+}
+
+/// Decompresses a public key to the uncompressed form, without the `0x04`
+/// prefix.
+pub fn secp256k1_decompress(compressed: &[u8]) -> Result<Bytes, Exception> {
+    let Ok(compressed) = <&[u8; 33]>::try_from(compressed) else {
+        return Err(Exception::InvalidSignatureError("compressed public key is not 33 bytes"));
+    };
+    if compressed[0] != 0x02 && compressed[0] != 0x03 {
+        return Err(Exception::InvalidSignatureError("bad public key prefix"));
+    }
+    let x = U256::from_be_bytes(compressed[1..].try_into().unwrap());
+    let y_squared = fadd(fmul(fmul(x, x), x), U256::from(7_u32));
+    let mut y = fpow(y_squared, SECP256K1P.shr(2) + U256::ONE);
+    if x >= SECP256K1P || fmul(y, y) != y_squared {
+        return Err(Exception::InvalidSignatureError("public key is not on the curve"));
+    }
+    if y.bit(0) != (compressed[0] == 0x03) {
+        y = fsub(U256::ZERO, y);
+    }
+    let mut public_key = Vec::with_capacity(64);
+    public_key.extend_from_slice(&x.to_be_bytes());
+    public_key.extend_from_slice(&y.to_be_bytes());
+    Ok(Bytes(public_key))
+}
+
+/// The point of an uncompressed public key, checked to be on the curve.
+fn secp256k1_point(public_key: &[u8]) -> Result<(U256, U256), Exception> {
+    let Ok(public_key) = <&[u8; 64]>::try_from(public_key) else {
+        return Err(Exception::InvalidSignatureError("public key is not 64 bytes"));
+    };
+    let x = U256::from_be_bytes(public_key[..32].try_into().unwrap());
+    let y = U256::from_be_bytes(public_key[32..].try_into().unwrap());
+    if x >= SECP256K1P || y >= SECP256K1P || fmul(y, y) != fadd(fmul(fmul(x, x), x), U256::from(7_u32)) {
+        return Err(Exception::InvalidSignatureError("public key is not on the curve"));
+    }
+    Ok((x, y))
+}
+
+// The field operations take the same time for all values, since `ladder`
+// runs them on secret points.
+
+fn fadd(a: U256, b: U256) -> U256 {
+    let (sum, carry) = add_carry(a, b);
+    let (diff, borrow) = sub_borrow(sum, SECP256K1P);
+    select(0_u64.wrapping_sub(carry | (borrow ^ 1)), diff, sum)
+}
+
+fn fsub(a: U256, b: U256) -> U256 {
+    let (diff, borrow) = sub_borrow(a, b);
+    add_carry(diff, select(0_u64.wrapping_sub(borrow), SECP256K1P, U256::ZERO)).0
+}
+
+/// `a + b` and the carry out, 0 or 1.
+fn add_carry(a: U256, b: U256) -> (U256, u64) {
+    let (a, b) = (a.to_limbs(), b.to_limbs());
+    let mut res = [0; 4];
+    let mut carry = 0;
+    for i in (0..4).rev() {
+        let t = a[i] as u128 + b[i] as u128 + carry as u128;
+        res[i] = t as u64;
+        carry = (t >> 64) as u64;
+    }
+    (U256::from_limbs(res), carry)
+}
+
+/// `a - b` and the borrow out, 0 or 1.
+fn sub_borrow(a: U256, b: U256) -> (U256, u64) {
+    let (a, b) = (a.to_limbs(), b.to_limbs());
+    let mut res = [0; 4];
+    let mut borrow = 0;
+    for i in (0..4).rev() {
+        let t = (a[i] as u128).wrapping_sub(b[i] as u128).wrapping_sub(borrow as u128);
+        res[i] = t as u64;
+        borrow = (t >> 127) as u64;
+    }
+    (U256::from_limbs(res), borrow)
+}
+
+/// `a` if `mask` is all ones, `b` if it is zero.
+fn select(mask: u64, a: U256, b: U256) -> U256 {
+    let (a, b) = (a.to_limbs(), b.to_limbs());
+    U256::from_limbs([0, 1, 2, 3].map(|i| (a[i] & mask) | (b[i] & !mask)))
+}
+
+fn fmul(a: U256, b: U256) -> U256 {
+    freduce(a.widening_mul(b))
+}
+
+fn fpow(a: U256, exponent: U256) -> U256 {
+    let mut res = U256::ONE;
+    for i in (0..exponent.bits()).rev() {
+        res = fmul(res, res);
+        if exponent.bit(i) {
+            res = fmul(res, a);
+        }
+    }
+    res
+}
+
+/// Reduces a 512 bit product, most significant limb first, modulo the
+/// field prime using `2**256 = SECP256K1P_COMPLEMENT (mod SECP256K1P)`.
+fn freduce(wide: [u64; 8]) -> U256 {
+    // Least significant limb first from here on.
+    let mut lo = [wide[7], wide[6], wide[5], wide[4]];
+    let hi = [wide[3], wide[2], wide[1], wide[0]];
+    let c = SECP256K1P_COMPLEMENT as u128;
+
+    let mut carry = 0_u128;
+    for i in 0..4 {
+        let t = lo[i] as u128 + hi[i] as u128 * c + carry;
+        lo[i] = t as u64;
+        carry = t >> 64;
+    }
+    // At most 34 bits left over, fold them once more.
+    let mut t = carry * c;
+    for limb in &mut lo {
+        t += *limb as u128;
+        *limb = t as u64;
+        t >>= 64;
+    }
+    // If it wrapped past 2**256, the low limbs are small.
+    let mut t = t * c;
+    for limb in &mut lo {
+        t += *limb as u128;
+        *limb = t as u64;
+        t >>= 64;
+    }
+    let res = U256::from_limbs([lo[3], lo[2], lo[1], lo[0]]);
+    let (diff, borrow) = sub_borrow(res, SECP256K1P);
+    select(0_u64.wrapping_sub(borrow ^ 1), diff, res)
+}
 
-    let mut signature = [0; 65];
-    signature[32 - r_bytes.len() .. 32].copy_from_slice(&r_bytes[..]);
-    signature[64 - s_bytes.len() .. 64].copy_from_slice(&s_bytes[..]);
-    signature[64] = v.to_be_bytes()[31];
-    todo!("this is a horrible dependency");
-    // let public_key = coincurve::PublicKey::from_signature_and_message(
-    //     &signature, msg_hash, None
-    // );
-    //public_key = public_key.format(compressed=False)[1:];
-    // return public_key;
-    Bytes::default()
+/// A point on secp256k1 in Jacobian coordinates, `(X / Z**2, Y / Z**3)`.
+/// `Z == 0` is the point at infinity.
+#[derive(Debug, Clone, Copy)]
+struct Jacobian {
+    x: U256,
+    y: U256,
+    z: U256,
 }
 
+impl Jacobian {
+    const INFINITY: Jacobian = Jacobian { x: U256::ONE, y: U256::ONE, z: U256::ZERO };
+
+    fn from_affine((x, y): (U256, U256)) -> Self {
+        Self { x, y, z: U256::ONE }
+    }
+
+    fn to_affine(&self) -> Option<(U256, U256)> {
+        if self.z.is_zero() {
+            return None;
+        }
+        let z_inv = fpow(self.z, SECP256K1P - U256::from(2_u32));
+        let z_inv2 = fmul(z_inv, z_inv);
+        Some((fmul(self.x, z_inv2), fmul(fmul(self.y, z_inv2), z_inv)))
+    }
+
+    fn double(&self) -> Self {
+        if self.z.is_zero() || self.y.is_zero() {
+            return Self::INFINITY;
+        }
+        let a = fmul(self.x, self.x);
+        let b = fmul(self.y, self.y);
+        let c = fmul(b, b);
+        let xb = fadd(self.x, b);
+        let d = fsub(fsub(fmul(xb, xb), a), c);
+        let d = fadd(d, d);
+        let e = fadd(fadd(a, a), a);
+        let f = fmul(e, e);
+        let x = fsub(f, fadd(d, d));
+        let c8 = fadd(c, c);
+        let c8 = fadd(c8, c8);
+        let c8 = fadd(c8, c8);
+        let y = fsub(fmul(e, fsub(d, x)), c8);
+        let yz = fmul(self.y, self.z);
+        Self { x, y, z: fadd(yz, yz) }
+    }
+
+    fn add(&self, other: &Self) -> Self {
+        if self.z.is_zero() {
+            return *other;
+        }
+        if other.z.is_zero() {
+            return *self;
+        }
+        let z1z1 = fmul(self.z, self.z);
+        let z2z2 = fmul(other.z, other.z);
+        let u1 = fmul(self.x, z2z2);
+        let u2 = fmul(other.x, z1z1);
+        let s1 = fmul(fmul(self.y, other.z), z2z2);
+        let s2 = fmul(fmul(other.y, self.z), z1z1);
+        let h = fsub(u2, u1);
+        let r = fsub(s2, s1);
+        if h.is_zero() {
+            return if r.is_zero() { self.double() } else { Self::INFINITY };
+        }
+        let hh = fmul(h, h);
+        let hhh = fmul(h, hh);
+        let v = fmul(u1, hh);
+        let x = fsub(fsub(fmul(r, r), hhh), fadd(v, v));
+        let y = fsub(fmul(r, fsub(v, x)), fmul(s1, hhh));
+        let z = fmul(fmul(self.z, other.z), h);
+        Self { x, y, z }
+    }
+
+    /// Multiply by `n` using the double and add algorithm.
+    fn mul(&self, n: U256) -> Self {
+        let mut res = Self::INFINITY;
+        for i in (0..n.bits()).rev() {
+            res = res.double();
+            if n.bit(i) {
+                res = res.add(self);
+            }
+        }
+        res
+    }
+}
+
+/// `n` times `point`, or `None` for the point at infinity, in the same time
+/// for every `n` below `2**256`: a Montgomery ladder over all 256 bits,
+/// which swaps the two points it keeps by masks rather than branches. It
+/// is for secret scalars; `Jacobian::mul` is faster for public ones.
+fn ladder(point: (U256, U256), n: U256) -> Option<(U256, U256)> {
+    let mut r0 = Projective::INFINITY;
+    let mut r1 = Projective::from_affine(point);
+    let limbs = n.to_limbs();
+    for i in (0..256).rev() {
+        let mask = 0_u64.wrapping_sub((limbs[3 - i / 64] >> (i % 64)) & 1);
+        Projective::swap(&mut r0, &mut r1, mask);
+        r1 = r0.add(&r1);
+        r0 = r0.add(&r0);
+        Projective::swap(&mut r0, &mut r1, mask);
+    }
+    r0.to_affine()
+}
+
+/// A point on secp256k1 in projective coordinates, `(X / Z, Y / Z)`.
+/// `(0, 1, 0)` is the point at infinity.
+#[derive(Debug, Clone, Copy)]
+struct Projective {
+    x: U256,
+    y: U256,
+    z: U256,
+}
+
+impl Projective {
+    const INFINITY: Projective = Projective { x: U256::ZERO, y: U256::ONE, z: U256::ZERO };
+
+    /// `3 * b` of `y**2 = x**3 + b`.
+    const B3: U256 = U256::from_limbs([0, 0, 0, 21]);
+
+    fn from_affine((x, y): (U256, U256)) -> Self {
+        Self { x, y, z: U256::ONE }
+    }
+
+    fn to_affine(&self) -> Option<(U256, U256)> {
+        if self.z.is_zero() {
+            return None;
+        }
+        let z_inv = fpow(self.z, SECP256K1P - U256::from(2_u32));
+        Some((fmul(self.x, z_inv), fmul(self.y, z_inv)))
+    }
+
+    /// Swap `a` and `b` if `mask` is all ones, leave them if it is zero.
+    fn swap(a: &mut Self, b: &mut Self, mask: u64) {
+        let (x, y, z) = (select(mask, b.x, a.x), select(mask, b.y, a.y), select(mask, b.z, a.z));
+        (b.x, b.y, b.z) = (select(mask, a.x, b.x), select(mask, a.y, b.y), select(mask, a.z, b.z));
+        (a.x, a.y, a.z) = (x, y, z);
+    }
+
+    /// The complete addition of Renes, Costello and Batina, "Complete
+    /// addition formulas for prime order elliptic curves", algorithm 7,
+    /// which has no special cases: it doubles, and adds the point at
+    /// infinity, as it adds any two points.
+    fn add(&self, other: &Self) -> Self {
+        let (x1, y1, z1) = (self.x, self.y, self.z);
+        let (x2, y2, z2) = (other.x, other.y, other.z);
+        let mut t0 = fmul(x1, x2);
+        let mut t1 = fmul(y1, y2);
+        let mut t2 = fmul(z1, z2);
+        let mut t3 = fmul(fadd(x1, y1), fadd(x2, y2));
+        let mut t4 = fadd(t0, t1);
+        t3 = fsub(t3, t4);
+        t4 = fmul(fadd(y1, z1), fadd(y2, z2));
+        t4 = fsub(t4, fadd(t1, t2));
+        let mut x3 = fmul(fadd(x1, z1), fadd(x2, z2));
+        let mut y3 = fsub(x3, fadd(t0, t2));
+        t0 = fadd(fadd(t0, t0), t0);
+        t2 = fmul(Self::B3, t2);
+        let mut z3 = fadd(t1, t2);
+        t1 = fsub(t1, t2);
+        y3 = fmul(Self::B3, y3);
+        x3 = fsub(fmul(t3, t1), fmul(t4, y3));
+        y3 = fadd(fmul(t1, z3), fmul(y3, t0));
+        z3 = fadd(fmul(z3, t4), fmul(t0, t3));
+        Self { x: x3, y: y3, z: z3 }
+    }
+}
 
 // /// Superclass for integers modulo a prime. Not intended to be used
 // /// directly, but rather to be subclassed.
@@ -145,3 +587,231 @@ pub fn secp256k1_recover(r: U256, s: U256, v: U256, msg_hash: Hash32) -> Bytes {
 //         return self.__new__(type(self), x, y)
 //     }
 // }
+
+/// A field over which one of the pairing-friendly curves `y^2 = x^3 + b`,
+/// or its twist, is defined.
+pub trait CurveField:
+    Copy + PartialEq + Add<Output = Self> + Sub<Output = Self> + Mul<Output = Self> + Neg<Output = Self>
+{
+    const ZERO: Self;
+    const ONE: Self;
+
+    /// The order of the group of points which the pairing takes, as
+    /// little-endian limbs.
+    const GROUP_ORDER: &'static [u64];
+
+    /// The `b` of `y^2 = x^3 + b`.
+    fn b() -> Self;
+
+    fn is_zero(&self) -> bool;
+
+    fn inverse(self) -> Option<Self>;
+}
+
+/// A point in Jacobian coordinates, `(x / z^2, y / z^3)`, which is the
+/// point at infinity if `z` is zero.
+#[derive(Debug, Clone, Copy)]
+pub struct Point<F> {
+    x: F,
+    y: F,
+    z: F,
+}
+
+impl<F: CurveField> Point<F> {
+    pub fn infinity() -> Self {
+        Self { x: F::ONE, y: F::ONE, z: F::ZERO }
+    }
+
+    /// The point `(x, y)`, if it is on the curve.
+    pub fn from_affine(x: F, y: F) -> Option<Self> {
+        (y * y == x * x * x + F::b()).then_some(Self { x, y, z: F::ONE })
+    }
+
+    pub fn is_infinity(&self) -> bool {
+        self.z.is_zero()
+    }
+
+    /// The affine coordinates, `None` for the point at infinity.
+    pub fn to_affine(&self) -> Option<(F, F)> {
+        let z_inv = self.z.inverse()?;
+        let z_inv2 = z_inv * z_inv;
+        Some((self.x * z_inv2, self.y * z_inv2 * z_inv))
+    }
+
+    pub fn double(&self) -> Self {
+        if self.is_infinity() || self.y.is_zero() {
+            return Self::infinity();
+        }
+        let a = self.x * self.x;
+        let b = self.y * self.y;
+        let c = b * b;
+        let d = (self.x + b) * (self.x + b) - a - c;
+        let d = d + d;
+        let e = a + a + a;
+        let x = e * e - d - d;
+        let c8 = c + c;
+        let c8 = c8 + c8;
+        let c8 = c8 + c8;
+        let y = e * (d - x) - c8;
+        let z = self.y * self.z;
+        Self { x, y, z: z + z }
+    }
+
+    pub fn add(&self, other: &Self) -> Self {
+        if self.is_infinity() {
+            return *other;
+        }
+        if other.is_infinity() {
+            return *self;
+        }
+        let z1z1 = self.z * self.z;
+        let z2z2 = other.z * other.z;
+        let u1 = self.x * z2z2;
+        let u2 = other.x * z1z1;
+        let s1 = self.y * other.z * z2z2;
+        let s2 = other.y * self.z * z1z1;
+        let h = u2 - u1;
+        let r = s2 - s1;
+        if h.is_zero() {
+            return if r.is_zero() { self.double() } else { Self::infinity() };
+        }
+        let r = r + r;
+        let i = (h + h) * (h + h);
+        let j = h * i;
+        let v = u1 * i;
+        let x = r * r - j - v - v;
+        let s1j = s1 * j;
+        let y = r * (v - x) - s1j - s1j;
+        let z = ((self.z + other.z) * (self.z + other.z) - z1z1 - z2z2) * h;
+        Self { x, y, z }
+    }
+
+    pub fn neg(&self) -> Self {
+        Self { x: self.x, y: -self.y, z: self.z }
+    }
+
+    /// The point times the little-endian limbs `scalar`.
+    pub fn mul_limbs(&self, scalar: &[u64]) -> Self {
+        let mut result = Self::infinity();
+        for i in (0..scalar.len() * 64).rev() {
+            result = result.double();
+            if bit(scalar, i) {
+                result = result.add(self);
+            }
+        }
+        result
+    }
+
+    /// Whether the point is in the group of order `F::GROUP_ORDER`.
+    pub fn is_torsion_free(&self) -> bool {
+        self.mul_limbs(F::GROUP_ORDER).is_infinity()
+    }
+}
+
+impl<F: CurveField> PartialEq for Point<F> {
+    fn eq(&self, other: &Self) -> bool {
+        if self.is_infinity() || other.is_infinity() {
+            return self.is_infinity() && other.is_infinity();
+        }
+        let z1z1 = self.z * self.z;
+        let z2z2 = other.z * other.z;
+        self.x * z2z2 == other.x * z1z1 && self.y * z2z2 * other.z == other.y * z1z1 * self.z
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::ethereum::{crypto::hash::{keccak256, sha256, Hash32}, ethereum_types::numeric::U256};
+
+    use super::{
+        ladder, secp256k1_compress, secp256k1_decompress, secp256k1_ecdh, secp256k1_public_key, secp256k1_recover,
+        secp256k1_sign, Jacobian, SECP256K1G, SECP256K1N,
+    };
+
+    fn u256(hex: &str) -> U256 {
+        let mut bytes = [0; 32];
+        for (i, b) in bytes.iter_mut().enumerate() {
+            *b = u8::from_str_radix(&hex[i * 2..i * 2 + 2], 16).unwrap();
+        }
+        U256::from_be_bytes(bytes)
+    }
+
+    #[test]
+    fn recover_known_signature() {
+        // The example input of the ecrecover precompile.
+        let hash = Hash32(u256("456e9aea5e197a1f1af7a3e85a3212fa4049a3ba34c2289b4c860fc0b0c64ef3").to_be_bytes());
+        let r = u256("9242685bf161793cc25603c231bc2f568eb630ea16aa137d2664ac8038825608");
+        let s = u256("4f8ae3bd7535248d0bd448298cc2e2071e56992d0774dc340c368ae950852ada");
+        let public_key = secp256k1_recover(r, s, U256::ONE, hash.clone()).unwrap();
+        assert_eq!(
+            keccak256(&public_key)[12..],
+            u256("0000000000000000000000007156526fbd7a3c72969b54f64e42c10fbb768c8a").to_be_bytes()[12..],
+        );
+        assert!(secp256k1_recover(U256::ZERO, s, U256::ONE, hash.clone()).is_err());
+        assert!(secp256k1_recover(r, s, U256::from(2_u32), hash).is_err());
+    }
+
+    #[test]
+    fn sign_and_recover() {
+        let secret_key = U256::from(0x5ec12e7_u32);
+        let hash = keccak256(b"message");
+        let (r, s, v) = secp256k1_sign(hash.clone(), secret_key).unwrap();
+        assert_eq!(secp256k1_recover(r, s, v, hash.clone()).unwrap(), secp256k1_public_key(secret_key));
+        assert!(secp256k1_sign(hash.clone(), U256::ZERO).is_err());
+        assert!(secp256k1_sign(hash, SECP256K1N).is_err());
+    }
+
+    #[test]
+    fn rfc6979_signatures() {
+        // The secp256k1 vectors of python-ecdsa and Trezor, with key 1.
+        let cases = [
+            (
+                &b"Satoshi Nakamoto"[..],
+                "934b1ea10a4b3c1757e2b0c017d0b6143ce3c9a7e6a4a49860d7a6ab210ee3d8",
+                "2442ce9d2b916064108014783e923ec36b49743e2ffa1c4496f01a512aafd9e5",
+            ),
+            (
+                &b"All those moments will be lost in time, like tears in rain. Time to die..."[..],
+                "8600dbd41e348fe5c9465ab92d23e3db8b98b873beecd930736488696438cb6b",
+                "547fe64427496db33bf66019dacbf0039c04199abb0122918601db38a72cfc21",
+            ),
+        ];
+        for (message, r, s) in cases {
+            let (r2, s2, _) = secp256k1_sign(sha256(message), U256::ONE).unwrap();
+            assert_eq!((r2, s2), (u256(r), u256(s)));
+        }
+    }
+
+    #[test]
+    fn ladder_matches_double_and_add() {
+        let n = u256("7e8c1b5d3a2f40916c0d9e8b7a6f5e4d3c2b1a0918273645546372819a0b1c2d");
+        for k in [U256::ONE, U256::from(2_u32), U256::from(3_u32), n, SECP256K1N - U256::ONE] {
+            assert_eq!(ladder(SECP256K1G, k), Jacobian::from_affine(SECP256K1G).mul(k).to_affine());
+        }
+        assert_eq!(ladder(SECP256K1G, U256::ZERO), None);
+        assert_eq!(ladder(SECP256K1G, SECP256K1N), None);
+    }
+
+    #[test]
+    fn shared_secret() {
+        let a = U256::from(0xa11ce_u32);
+        let b = U256::from(0xb0b_u32);
+        let shared = secp256k1_ecdh(a, &secp256k1_public_key(b)).unwrap();
+        assert_eq!(shared, secp256k1_ecdh(b, &secp256k1_public_key(a)).unwrap());
+        assert!(secp256k1_ecdh(a, &[1; 64]).is_err());
+
+        let point = Jacobian::from_affine(SECP256K1G).mul(b).to_affine().unwrap();
+        let expected = Jacobian::from_affine(point).mul(a).to_affine().unwrap().0;
+        assert_eq!(shared, expected.to_be_bytes());
+    }
+
+    #[test]
+    fn compressed_public_keys() {
+        for secret_key in [1_u32, 2, 0x5ec12e7, 0xb0b] {
+            let public_key = secp256k1_public_key(U256::from(secret_key));
+            let compressed = secp256k1_compress(&public_key).unwrap();
+            assert_eq!(secp256k1_decompress(&compressed).unwrap(), public_key);
+        }
+        assert!(secp256k1_decompress(&[0x04; 33]).is_err());
+    }
+}
@@ -0,0 +1,207 @@
+//! Finite fields
+//! ^^^^^^^^^^^^^
+//!
+//! Arithmetic on little-endian `u64` limbs, and `montgomery_field!` for
+//! prime fields whose elements are kept in Montgomery form, shared by the
+//! pairing-friendly curves.
+
+/// `a < b` for little-endian limbs.
+pub(crate) fn less_than<const N: usize>(a: &[u64; N], b: &[u64; N]) -> bool {
+    for i in (0..N).rev() {
+        if a[i] != b[i] {
+            return a[i] < b[i];
+        }
+    }
+    false
+}
+
+pub(crate) fn add_limbs<const N: usize>(a: &[u64; N], b: &[u64; N]) -> ([u64; N], bool) {
+    let mut out = [0; N];
+    let mut carry = false;
+    for i in 0..N {
+        let (sum, c1) = a[i].overflowing_add(b[i]);
+        let (sum, c2) = sum.overflowing_add(carry as u64);
+        out[i] = sum;
+        carry = c1 || c2;
+    }
+    (out, carry)
+}
+
+pub(crate) fn sub_limbs<const N: usize>(a: &[u64; N], b: &[u64; N]) -> ([u64; N], bool) {
+    let mut out = [0; N];
+    let mut borrow = false;
+    for i in 0..N {
+        let (diff, b1) = a[i].overflowing_sub(b[i]);
+        let (diff, b2) = diff.overflowing_sub(borrow as u64);
+        out[i] = diff;
+        borrow = b1 || b2;
+    }
+    (out, borrow)
+}
+
+/// `a * b / 2^(64 N) mod m`, where `inv` is `-1 / m mod 2^64`.
+pub(crate) fn montgomery_mul<const N: usize>(a: &[u64; N], b: &[u64; N], m: &[u64; N], inv: u64) -> [u64; N] {
+    let mut t = [0; N];
+    let mut t_n = 0;
+    for i in 0..N {
+        let mut carry = 0;
+        for j in 0..N {
+            let s = t[j] as u128 + a[i] as u128 * b[j] as u128 + carry as u128;
+            t[j] = s as u64;
+            carry = (s >> 64) as u64;
+        }
+        let s = t_n as u128 + carry as u128;
+        t_n = s as u64;
+        let t_n1 = (s >> 64) as u64;
+
+        let k = t[0].wrapping_mul(inv);
+        let s = t[0] as u128 + k as u128 * m[0] as u128;
+        let mut carry = (s >> 64) as u64;
+        for j in 1..N {
+            let s = t[j] as u128 + k as u128 * m[j] as u128 + carry as u128;
+            t[j - 1] = s as u64;
+            carry = (s >> 64) as u64;
+        }
+        let s = t_n as u128 + carry as u128;
+        t[N - 1] = s as u64;
+        t_n = t_n1 + (s >> 64) as u64;
+    }
+    if t_n != 0 || !less_than(&t, m) {
+        t = sub_limbs(&t, m).0;
+    }
+    t
+}
+
+/// Whether bit `i` of the little-endian limbs `exponent` is set.
+pub(crate) fn bit(exponent: &[u64], i: usize) -> bool {
+    exponent[i / 64] >> (i % 64) & 1 == 1
+}
+
+/// A prime field of `$n` limbs modulo `$modulus`, where `$inv` is
+/// `-1 / modulus mod 2^64` and `$one` and `$r2` are `2^(64 n)` and its
+/// square modulo the modulus.
+macro_rules! montgomery_field {
+    ($(#[$attr : meta])* $name : ident, $n : literal, $modulus : expr, $inv : expr, $one : expr, $r2 : expr) => {
+        $(#[$attr])*
+        #[derive(Debug, Clone, Copy, PartialEq, Eq)]
+        pub struct $name([u64; $n]);
+
+        impl $name {
+            /// The modulus, in little-endian limbs.
+            pub const MODULUS: [u64; $n] = $modulus;
+            const INV: u64 = $inv;
+            pub const ZERO: Self = Self([0; $n]);
+            pub const ONE: Self = Self($one);
+            const R2: Self = Self($r2);
+
+            /// The element of little-endian limbs, if they are below the
+            /// modulus.
+            pub fn from_limbs(limbs: [u64; $n]) -> Option<Self> {
+                $crate::ethereum::crypto::finite_field::less_than(&limbs, &Self::MODULUS).then(|| Self(limbs) * Self::R2)
+            }
+
+            pub fn from_u64(value: u64) -> Self {
+                let mut limbs = [0; $n];
+                limbs[0] = value;
+                Self::from_limbs(limbs).unwrap()
+            }
+
+            /// The element of a big-endian number, if it is below the
+            /// modulus.
+            pub fn from_be_bytes(bytes: &[u8; $n * 8]) -> Option<Self> {
+                let mut limbs = [0; $n];
+                for (i, chunk) in bytes.rchunks(8).enumerate() {
+                    limbs[i] = u64::from_be_bytes(chunk.try_into().unwrap());
+                }
+                Self::from_limbs(limbs)
+            }
+
+            /// The little-endian limbs of the element.
+            pub fn to_limbs(self) -> [u64; $n] {
+                let mut one = [0; $n];
+                one[0] = 1;
+                $crate::ethereum::crypto::finite_field::montgomery_mul(&self.0, &one, &Self::MODULUS, Self::INV)
+            }
+
+            pub fn to_be_bytes(self) -> [u8; $n * 8] {
+                let mut bytes = [0; $n * 8];
+                for (chunk, limb) in bytes.rchunks_mut(8).zip(self.to_limbs()) {
+                    chunk.copy_from_slice(&limb.to_be_bytes());
+                }
+                bytes
+            }
+
+            pub fn is_zero(&self) -> bool {
+                *self == Self::ZERO
+            }
+
+            pub fn square(self) -> Self {
+                self * self
+            }
+
+            /// `self` to the power of the little-endian limbs `exponent`.
+            pub fn pow(self, exponent: &[u64]) -> Self {
+                let mut result = Self::ONE;
+                for i in (0..exponent.len() * 64).rev() {
+                    result = result.square();
+                    if $crate::ethereum::crypto::finite_field::bit(exponent, i) {
+                        result = result * self;
+                    }
+                }
+                result
+            }
+
+            pub fn inverse(self) -> Option<Self> {
+                let mut exponent = Self::MODULUS;
+                exponent[0] -= 2;
+                (!self.is_zero()).then(|| self.pow(&exponent))
+            }
+
+            /// Whether the element is greater than `-self`, the sign of the
+            /// compressed encoding of points.
+            pub fn is_lexicographically_largest(self) -> bool {
+                $crate::ethereum::crypto::finite_field::less_than(&(-self).to_limbs(), &self.to_limbs())
+            }
+        }
+
+        impl std::ops::Add for $name {
+            type Output = Self;
+
+            fn add(self, rhs: Self) -> Self {
+                let (sum, carry) = $crate::ethereum::crypto::finite_field::add_limbs(&self.0, &rhs.0);
+                if carry || !$crate::ethereum::crypto::finite_field::less_than(&sum, &Self::MODULUS) {
+                    Self($crate::ethereum::crypto::finite_field::sub_limbs(&sum, &Self::MODULUS).0)
+                } else {
+                    Self(sum)
+                }
+            }
+        }
+
+        impl std::ops::Sub for $name {
+            type Output = Self;
+
+            fn sub(self, rhs: Self) -> Self {
+                let (diff, borrow) = $crate::ethereum::crypto::finite_field::sub_limbs(&self.0, &rhs.0);
+                if borrow { Self($crate::ethereum::crypto::finite_field::add_limbs(&diff, &Self::MODULUS).0) } else { Self(diff) }
+            }
+        }
+
+        impl std::ops::Neg for $name {
+            type Output = Self;
+
+            fn neg(self) -> Self {
+                Self::ZERO - self
+            }
+        }
+
+        impl std::ops::Mul for $name {
+            type Output = Self;
+
+            fn mul(self, rhs: Self) -> Self {
+                Self($crate::ethereum::crypto::finite_field::montgomery_mul(&self.0, &rhs.0, &Self::MODULUS, Self::INV))
+            }
+        }
+    };
+}
+
+pub(crate) use montgomery_field;
@@ -1,10 +1,12 @@
 //! https://github.com/ethereum/execution-specs/blob/master/src/ethereum/crypto/hash.py
 
+use std::cell::RefCell;
+
 use tiny_keccak::Hasher;
 
-use crate::{ethereum::{ethereum_rlp::{exceptions::RLPException, rlp::{decode_to_bytes, encode_bytes, Extended}}, ethereum_types::bytes::*, utils::hexadecimal::hex_to_bytes32}, json::{Decoder, JsonDecode, JsonError}};
+use crate::{ethereum::{ethereum_rlp::{exceptions::RLPException, rlp::{decode_to_fixed_bytes, encode_bytes, Extended}}, ethereum_types::bytes::*, utils::hexadecimal::hex_to_bytes32}, json::{Decoder, Encoder, JsonDecode, JsonEncode, JsonError}, metrics};
 
-#[derive(Debug, Clone, Default, PartialEq, PartialOrd, Eq, Ord)]
+#[derive(Debug, Clone, Default, PartialEq, PartialOrd, Eq, Ord, Hash)]
 pub struct Hash32(pub (crate)[u8; 32]);
 
 impl Extended for Hash32 {
@@ -13,7 +15,7 @@ impl Extended for Hash32 {
     }
 
     fn decode<'a, 'b>(&mut self, buffer: &'a mut &'b [u8]) -> Result<(), RLPException> {
-        decode_to_bytes(buffer, &mut self.0)
+        decode_to_fixed_bytes(buffer, &mut self.0)
     }
 }
 
@@ -27,6 +29,14 @@ impl<'de> JsonDecode<'de> for Hash32 {
     }
 }
 
+impl JsonEncode for Hash32 {
+    fn encode_json(&self, encoder: &mut Encoder) {
+        encoder.hex(&self.0);
+    }
+}
+
+crate::impl_hex_str!(Hash32);
+
 #[derive(Debug, Clone, PartialEq, PartialOrd, Eq, Ord)]
 pub struct Hash64([u8; 64]);
 
@@ -65,6 +75,253 @@ pub fn keccak256(buffer: &[u8]) -> Hash32 {
     Hash32(output)
 }
 
+/// Computes the keccak256 hashes of `buffers`, in order.
+///
+/// On x86-64 CPUs with AVX2 the inputs are hashed four at a time, their
+/// states interleaved word by word so that each step of the permutation
+/// runs on all four in one vector register. Inputs are grouped by their
+/// number of blocks, as a group permutes as many times as its longest
+/// input needs. Elsewhere each input is hashed on its own.
+pub fn keccak256_batch<'a>(buffers: impl IntoIterator<Item = &'a [u8]>) -> Vec<Hash32> {
+    #[cfg(target_arch = "x86_64")]
+    if std::arch::is_x86_feature_detected!("avx2") {
+        return keccak256_lanes::batch(buffers.into_iter().collect());
+    }
+    buffers.into_iter().map(keccak256).collect()
+}
+
+#[cfg(target_arch = "x86_64")]
+mod keccak256_lanes {
+    use std::arch::x86_64::*;
+
+    use super::{keccak256, Hash32};
+
+    /// Inputs hashed together.
+    const LANES: usize = 4;
+    /// Bytes absorbed per permutation.
+    const RATE: usize = 136;
+
+    const ROUND_CONSTANTS: [u64; 24] = [
+        0x0000000000000001, 0x0000000000008082, 0x800000000000808a, 0x8000000080008000,
+        0x000000000000808b, 0x0000000080000001, 0x8000000080008081, 0x8000000000008009,
+        0x000000000000008a, 0x0000000000000088, 0x0000000080008009, 0x000000008000000a,
+        0x000000008000808b, 0x800000000000008b, 0x8000000000008089, 0x8000000000008003,
+        0x8000000000008002, 0x8000000000000080, 0x000000000000800a, 0x800000008000000a,
+        0x8000000080008081, 0x8000000000008080, 0x0000000080000001, 0x8000000080008008,
+    ];
+
+    /// Hash `buffers`, on a CPU with AVX2.
+    pub(super) fn batch(buffers: Vec<&[u8]>) -> Vec<Hash32> {
+        let mut order: Vec<usize> = (0..buffers.len()).collect();
+        order.sort_by_key(|&i| buffers[i].len() / RATE);
+        let mut hashes = vec![Hash32::default(); buffers.len()];
+        for group in order.chunks(LANES) {
+            if let [i] = group {
+                hashes[*i] = keccak256(buffers[*i]);
+                continue;
+            }
+            let inputs: Vec<&[u8]> = group.iter().map(|&i| buffers[i]).collect();
+            // SAFETY: `batch` is only called on CPUs with AVX2.
+            let outputs = unsafe { hash(&inputs) };
+            for (output, &i) in outputs.into_iter().zip(group) {
+                hashes[i] = Hash32(output);
+            }
+        }
+        hashes
+    }
+
+    /// The hashes of up to `LANES` inputs, hashed together.
+    #[target_feature(enable = "avx2")]
+    unsafe fn hash(inputs: &[&[u8]]) -> Vec<[u8; 32]> {
+        let last_blocks: Vec<usize> = inputs.iter().map(|input| input.len() / RATE).collect();
+        let mut state = [[0_u64; LANES]; 25];
+        let mut outputs = vec![[0; 32]; inputs.len()];
+        for block in 0..=last_blocks.iter().copied().max().unwrap_or(0) {
+            for (lane, input) in inputs.iter().enumerate() {
+                if block > last_blocks[lane] {
+                    continue;
+                }
+                let mut padded = [0; RATE];
+                let rest = &input[block * RATE..];
+                let bytes = if block < last_blocks[lane] {
+                    &rest[..RATE]
+                } else {
+                    padded[..rest.len()].copy_from_slice(rest);
+                    padded[rest.len()] ^= 0x01;
+                    padded[RATE - 1] ^= 0x80;
+                    &padded[..]
+                };
+                for (word, bytes) in state.iter_mut().zip(bytes.chunks_exact(8)) {
+                    word[lane] ^= u64::from_le_bytes(bytes.try_into().unwrap());
+                }
+            }
+            keccak_f(&mut state);
+            for (lane, output) in outputs.iter_mut().enumerate() {
+                if block == last_blocks[lane] {
+                    for (bytes, word) in output.chunks_exact_mut(8).zip(&state) {
+                        bytes.copy_from_slice(&word[lane].to_le_bytes());
+                    }
+                }
+            }
+        }
+        outputs
+    }
+
+    /// Keccak-f[1600] on `LANES` interleaved states, a vector register per
+    /// word of the state.
+    #[target_feature(enable = "avx2")]
+    unsafe fn keccak_f(state: &mut [[u64; LANES]; 25]) {
+        macro_rules! rotate {
+            ($x:expr, $n:literal) => {
+                _mm256_or_si256(_mm256_slli_epi64::<$n>($x), _mm256_srli_epi64::<{ 64 - $n }>($x))
+            };
+        }
+        // Rho and pi, moving each word to where pi puts it, rotated, in
+        // the order the words follow from the second one.
+        macro_rules! rho_pi {
+            ($a:ident, $($to:literal $rotation:literal),*) => {
+                let mut last = $a[1];
+                $(
+                    let next = $a[$to];
+                    $a[$to] = rotate!(last, $rotation);
+                    last = next;
+                )*
+                let _ = last;
+            };
+        }
+
+        let mut a: [__m256i; 25] = std::mem::transmute(*state);
+        for round_constant in ROUND_CONSTANTS {
+            // Theta
+            let mut columns = [_mm256_setzero_si256(); 5];
+            for (x, column) in columns.iter_mut().enumerate() {
+                let c = _mm256_xor_si256(_mm256_xor_si256(a[x], a[x + 5]), _mm256_xor_si256(a[x + 10], a[x + 15]));
+                *column = _mm256_xor_si256(c, a[x + 20]);
+            }
+            for x in 0..5 {
+                let d = _mm256_xor_si256(columns[(x + 4) % 5], rotate!(columns[(x + 1) % 5], 1));
+                for y in 0..5 {
+                    a[5 * y + x] = _mm256_xor_si256(a[5 * y + x], d);
+                }
+            }
+            rho_pi!(
+                a, 10 1, 7 3, 11 6, 17 10, 18 15, 3 21, 5 28, 16 36, 8 45, 21 55, 24 2, 4 14, 15 27, 23 41, 19 56,
+                13 8, 12 25, 2 43, 20 62, 14 18, 22 39, 9 61, 6 20, 1 44
+            );
+            // Chi
+            for y in 0..5 {
+                let row = [a[5 * y], a[5 * y + 1], a[5 * y + 2], a[5 * y + 3], a[5 * y + 4]];
+                for x in 0..5 {
+                    a[5 * y + x] = _mm256_xor_si256(row[x], _mm256_andnot_si256(row[(x + 1) % 5], row[(x + 2) % 5]));
+                }
+            }
+            // Iota
+            a[0] = _mm256_xor_si256(a[0], _mm256_set1_epi64x(round_constant as i64));
+        }
+        *state = std::mem::transmute::<[__m256i; 25], [[u64; LANES]; 25]>(a);
+    }
+}
+
+/// Entries of the cache of short inputs, such as trie keys.
+const SHORT_CACHE_SIZE: usize = 4096;
+/// Entries of the cache of long inputs, such as contract code.
+const LONG_CACHE_SIZE: usize = 256;
+/// Longest input cached, the size limit of contract code (EIP-170).
+const MAX_CACHED_LEN: usize = 0x6000;
+
+/// A short input and its hash.
+#[derive(Clone, Copy)]
+struct ShortEntry {
+    len: u8,
+    input: [u8; 32],
+    hash: [u8; 32],
+}
+
+/// A long input and its hash.
+#[derive(Default)]
+struct LongEntry {
+    input: Vec<u8>,
+    hash: [u8; 32],
+}
+
+struct KeccakCache {
+    short: Vec<Option<ShortEntry>>,
+    long: Vec<LongEntry>,
+}
+
+thread_local! {
+    static KECCAK_CACHE: RefCell<KeccakCache> = RefCell::new(KeccakCache {
+        short: vec![None; SHORT_CACHE_SIZE],
+        long: (0..LONG_CACHE_SIZE).map(|_| LongEntry::default()).collect(),
+    });
+}
+
+/// Computes the keccak256 hash of `buffer`, remembering the hashes of
+/// recent inputs.
+///
+/// Computing a state root hashes the same inputs again and again: the keys
+/// of the secured tries and the code of every account. Each thread keeps a
+/// direct mapped cache of them, looked up by content: inputs of at most 32
+/// bytes by all of it, longer ones by their length and their first and
+/// last 32 bytes, so that the slot is found without a pass over the code.
+/// An entry is only used if its copy of the input equals `buffer`.
+pub fn keccak256_cached(buffer: &[u8]) -> Hash32 {
+    if buffer.len() > MAX_CACHED_LEN {
+        return keccak256(buffer);
+    }
+    let hit = KECCAK_CACHE.with(|cache| {
+        let mut cache = cache.borrow_mut();
+        if buffer.len() <= 32 {
+            let index = short_index(buffer);
+            if let Some(entry) = &cache.short[index] {
+                if &entry.input[..entry.len as usize] == buffer {
+                    return Ok(Hash32(entry.hash));
+                }
+            }
+            let hash = keccak256(buffer);
+            let mut input = [0; 32];
+            input[..buffer.len()].copy_from_slice(buffer);
+            cache.short[index] = Some(ShortEntry { len: buffer.len() as u8, input, hash: hash.0 });
+            Err(hash)
+        } else {
+            let entry = &mut cache.long[long_index(buffer)];
+            if entry.input == buffer {
+                return Ok(Hash32(entry.hash));
+            }
+            let hash = keccak256(buffer);
+            entry.input.clear();
+            entry.input.extend_from_slice(buffer);
+            entry.hash = hash.0;
+            Err(hash)
+        }
+    });
+    // The caches are per thread, the counts of all of them are process wide.
+    match hit {
+        Ok(_) => metrics::KECCAK_CACHE_HITS.inc(),
+        Err(_) => metrics::KECCAK_CACHE_MISSES.inc(),
+    }
+    hit.unwrap_or_else(|hash| hash)
+}
+
+/// The slot of the short cache for `buffer`, from an FNV-1a hash of it.
+fn short_index(buffer: &[u8]) -> usize {
+    fnv1a(0xcbf2_9ce4_8422_2325, buffer) as usize % SHORT_CACHE_SIZE
+}
+
+/// The slot of the long cache for `buffer`, longer than 32 bytes, from its
+/// length and its ends.
+fn long_index(buffer: &[u8]) -> usize {
+    let h = fnv1a(0xcbf2_9ce4_8422_2325 ^ buffer.len() as u64, &buffer[..32]);
+    fnv1a(h, &buffer[buffer.len() - 32..]) as usize % LONG_CACHE_SIZE
+}
+
+fn fnv1a(mut h: u64, buffer: &[u8]) -> u64 {
+    for &b in buffer {
+        h = (h ^ b as u64).wrapping_mul(0x100_0000_01b3);
+    }
+    h ^ h >> 32
+}
+
 /// Computes the keccak512 hash of the input `buffer`.
 ///
 /// Parameters
@@ -81,3 +338,220 @@ fn keccak512(buffer: Bytes) -> Hash64 {
     // return Hash64(k.update(buffer).digest())
     todo!();
 }
+
+const SHA256_K: [u32; 64] = [
+    0x428a2f98, 0x71374491, 0xb5c0fbcf, 0xe9b5dba5, 0x3956c25b, 0x59f111f1, 0x923f82a4, 0xab1c5ed5,
+    0xd807aa98, 0x12835b01, 0x243185be, 0x550c7dc3, 0x72be5d74, 0x80deb1fe, 0x9bdc06a7, 0xc19bf174,
+    0xe49b69c1, 0xefbe4786, 0x0fc19dc6, 0x240ca1cc, 0x2de92c6f, 0x4a7484aa, 0x5cb0a9dc, 0x76f988da,
+    0x983e5152, 0xa831c66d, 0xb00327c8, 0xbf597fc7, 0xc6e00bf3, 0xd5a79147, 0x06ca6351, 0x14292967,
+    0x27b70a85, 0x2e1b2138, 0x4d2c6dfc, 0x53380d13, 0x650a7354, 0x766a0abb, 0x81c2c92e, 0x92722c85,
+    0xa2bfe8a1, 0xa81a664b, 0xc24b8b70, 0xc76c51a3, 0xd192e819, 0xd6990624, 0xf40e3585, 0x106aa070,
+    0x19a4c116, 0x1e376c08, 0x2748774c, 0x34b0bcb5, 0x391c0cb3, 0x4ed8aa4a, 0x5b9cca4f, 0x682e6ff3,
+    0x748f82ee, 0x78a5636f, 0x84c87814, 0x8cc70208, 0x90befffa, 0xa4506ceb, 0xbef9a3f7, 0xc67178f2,
+];
+
+/// Pad `buffer` to a multiple of 64 bytes in the MD4 style shared by SHA-256
+/// and RIPEMD-160, with the bit length in the given byte order.
+fn md_pad(buffer: &[u8], big_endian: bool) -> Vec<u8> {
+    let bit_len = (buffer.len() as u64).wrapping_mul(8);
+    let mut data = buffer.to_vec();
+    data.push(0x80);
+    while data.len() % 64 != 56 {
+        data.push(0);
+    }
+    data.extend_from_slice(&if big_endian { bit_len.to_be_bytes() } else { bit_len.to_le_bytes() });
+    data
+}
+
+/// Computes the sha256 hash of the input `buffer`.
+pub fn sha256(buffer: &[u8]) -> Hash32 {
+    let mut h: [u32; 8] = [
+        0x6a09e667, 0xbb67ae85, 0x3c6ef372, 0xa54ff53a, 0x510e527f, 0x9b05688c, 0x1f83d9ab, 0x5be0cd19,
+    ];
+    for block in md_pad(buffer, true).chunks(64) {
+        let mut w = [0_u32; 64];
+        for i in 0..16 {
+            w[i] = u32::from_be_bytes(block[i*4..i*4+4].try_into().unwrap());
+        }
+        for i in 16..64 {
+            let s0 = w[i-15].rotate_right(7) ^ w[i-15].rotate_right(18) ^ (w[i-15] >> 3);
+            let s1 = w[i-2].rotate_right(17) ^ w[i-2].rotate_right(19) ^ (w[i-2] >> 10);
+            w[i] = w[i-16].wrapping_add(s0).wrapping_add(w[i-7]).wrapping_add(s1);
+        }
+        let [mut a, mut b, mut c, mut d, mut e, mut f, mut g, mut hh] = h;
+        for i in 0..64 {
+            let s1 = e.rotate_right(6) ^ e.rotate_right(11) ^ e.rotate_right(25);
+            let ch = (e & f) ^ (!e & g);
+            let t1 = hh.wrapping_add(s1).wrapping_add(ch).wrapping_add(SHA256_K[i]).wrapping_add(w[i]);
+            let s0 = a.rotate_right(2) ^ a.rotate_right(13) ^ a.rotate_right(22);
+            let maj = (a & b) ^ (a & c) ^ (b & c);
+            let t2 = s0.wrapping_add(maj);
+            hh = g;
+            g = f;
+            f = e;
+            e = d.wrapping_add(t1);
+            d = c;
+            c = b;
+            b = a;
+            a = t1.wrapping_add(t2);
+        }
+        for (h, v) in h.iter_mut().zip([a, b, c, d, e, f, g, hh]) {
+            *h = h.wrapping_add(v);
+        }
+    }
+    let mut output = [0; 32];
+    for (chunk, h) in output.chunks_mut(4).zip(h) {
+        chunk.copy_from_slice(&h.to_be_bytes());
+    }
+    Hash32(output)
+}
+
+/// Computes the HMAC (RFC 2104) of `message` under `key` with sha256.
+pub fn hmac_sha256(key: &[u8], message: &[u8]) -> Hash32 {
+    let mut block = [0; 64];
+    if key.len() > 64 {
+        block[..32].copy_from_slice(&sha256(key).0);
+    } else {
+        block[..key.len()].copy_from_slice(key);
+    }
+    let inner = [&block.map(|b| b ^ 0x36)[..], message].concat();
+    let outer = [&block.map(|b| b ^ 0x5c)[..], &sha256(&inner).0].concat();
+    sha256(&outer)
+}
+
+/// Computes the ripemd160 hash of the input `buffer`.
+pub fn ripemd160(buffer: &[u8]) -> Bytes20 {
+    const R: [usize; 80] = [
+        0, 1, 2, 3, 4, 5, 6, 7, 8, 9, 10, 11, 12, 13, 14, 15,
+        7, 4, 13, 1, 10, 6, 15, 3, 12, 0, 9, 5, 2, 14, 11, 8,
+        3, 10, 14, 4, 9, 15, 8, 1, 2, 7, 0, 6, 13, 11, 5, 12,
+        1, 9, 11, 10, 0, 8, 12, 4, 13, 3, 7, 15, 14, 5, 6, 2,
+        4, 0, 5, 9, 7, 12, 2, 10, 14, 1, 3, 8, 11, 6, 15, 13,
+    ];
+    const RP: [usize; 80] = [
+        5, 14, 7, 0, 9, 2, 11, 4, 13, 6, 15, 8, 1, 10, 3, 12,
+        6, 11, 3, 7, 0, 13, 5, 10, 14, 15, 8, 12, 4, 9, 1, 2,
+        15, 5, 1, 3, 7, 14, 6, 9, 11, 8, 12, 2, 10, 0, 4, 13,
+        8, 6, 4, 1, 3, 11, 15, 0, 5, 12, 2, 13, 9, 7, 10, 14,
+        12, 15, 10, 4, 1, 5, 8, 7, 6, 2, 13, 14, 0, 3, 9, 11,
+    ];
+    const S: [u32; 80] = [
+        11, 14, 15, 12, 5, 8, 7, 9, 11, 13, 14, 15, 6, 7, 9, 8,
+        7, 6, 8, 13, 11, 9, 7, 15, 7, 12, 15, 9, 11, 7, 13, 12,
+        11, 13, 6, 7, 14, 9, 13, 15, 14, 8, 13, 6, 5, 12, 7, 5,
+        11, 12, 14, 15, 14, 15, 9, 8, 9, 14, 5, 6, 8, 6, 5, 12,
+        9, 15, 5, 11, 6, 8, 13, 12, 5, 12, 13, 14, 11, 8, 5, 6,
+    ];
+    const SP: [u32; 80] = [
+        8, 9, 9, 11, 13, 15, 15, 5, 7, 7, 8, 11, 14, 14, 12, 6,
+        9, 13, 15, 7, 12, 8, 9, 11, 7, 7, 12, 7, 6, 15, 13, 11,
+        9, 7, 15, 11, 8, 6, 6, 14, 12, 13, 5, 14, 13, 13, 7, 5,
+        15, 5, 8, 11, 14, 14, 6, 14, 6, 9, 12, 9, 12, 5, 15, 8,
+        8, 5, 12, 9, 12, 5, 14, 6, 8, 13, 6, 5, 15, 13, 11, 11,
+    ];
+    const K: [u32; 5] = [0x00000000, 0x5a827999, 0x6ed9eba1, 0x8f1bbcdc, 0xa953fd4e];
+    const KP: [u32; 5] = [0x50a28be6, 0x5c4dd124, 0x6d703ef3, 0x7a6d76e9, 0x00000000];
+    fn f(j: usize, x: u32, y: u32, z: u32) -> u32 {
+        match j / 16 {
+            0 => x ^ y ^ z,
+            1 => (x & y) | (!x & z),
+            2 => (x | !y) ^ z,
+            3 => (x & z) | (y & !z),
+            _ => x ^ (y | !z),
+        }
+    }
+
+    let mut h: [u32; 5] = [0x67452301, 0xefcdab89, 0x98badcfe, 0x10325476, 0xc3d2e1f0];
+    for block in md_pad(buffer, false).chunks(64) {
+        let x: Vec<u32> = block.chunks(4).map(|c| u32::from_le_bytes(c.try_into().unwrap())).collect();
+        let [mut a, mut b, mut c, mut d, mut e] = h;
+        let [mut ap, mut bp, mut cp, mut dp, mut ep] = h;
+        for j in 0..80 {
+            let t = a
+                .wrapping_add(f(j, b, c, d))
+                .wrapping_add(x[R[j]])
+                .wrapping_add(K[j / 16])
+                .rotate_left(S[j])
+                .wrapping_add(e);
+            a = e;
+            e = d;
+            d = c.rotate_left(10);
+            c = b;
+            b = t;
+            let t = ap
+                .wrapping_add(f(79 - j, bp, cp, dp))
+                .wrapping_add(x[RP[j]])
+                .wrapping_add(KP[j / 16])
+                .rotate_left(SP[j])
+                .wrapping_add(ep);
+            ap = ep;
+            ep = dp;
+            dp = cp.rotate_left(10);
+            cp = bp;
+            bp = t;
+        }
+        let t = h[1].wrapping_add(c).wrapping_add(dp);
+        h[1] = h[2].wrapping_add(d).wrapping_add(ep);
+        h[2] = h[3].wrapping_add(e).wrapping_add(ap);
+        h[3] = h[4].wrapping_add(a).wrapping_add(bp);
+        h[4] = h[0].wrapping_add(b).wrapping_add(cp);
+        h[0] = t;
+    }
+    let mut output = [0; 20];
+    for (chunk, h) in output.chunks_mut(4).zip(h) {
+        chunk.copy_from_slice(&h.to_le_bytes());
+    }
+    Bytes20(output)
+}
+
+#[test]
+fn test_hmac_sha256() {
+    // RFC 4231, test case 2.
+    let mac = hmac_sha256(b"Jefe", b"what do ya want for nothing?");
+    assert_eq!(
+        mac.0.to_vec(),
+        crate::ethereum::utils::hexadecimal::hex_to_bytes("5bdcc146bf60754e6a042426089575c75a003f089d2739839dec58b964ec3843").unwrap().0,
+    );
+}
+
+#[test]
+fn test_keccak256_cached() {
+    let code = vec![0x60; 100];
+    let key = [7_u8; 32];
+    let inputs: [&[u8]; 4] = [&code, &key, b"", &code[..33]];
+    let before = crate::metrics::metrics();
+    for _ in 0..2 {
+        for input in inputs {
+            assert_eq!(keccak256_cached(input), keccak256(input));
+        }
+    }
+    // Other tests hash at the same time, on other threads.
+    let after = crate::metrics::metrics();
+    assert!(after.keccak_cache_hits >= before.keccak_cache_hits + 4);
+    assert!(after.keccak_cache_misses >= before.keccak_cache_misses + 4);
+    assert_eq!(keccak256_batch(inputs[1..].iter().copied()), inputs[1..].iter().map(|input| keccak256(input)).collect::<Vec<_>>());
+
+    // A copy of the code elsewhere is found, and other code of the same
+    // length and ends is hashed again.
+    let copy = code.clone();
+    let hits = crate::metrics::metrics().keccak_cache_hits;
+    assert_eq!(keccak256_cached(&copy), keccak256(&code));
+    assert!(crate::metrics::metrics().keccak_cache_hits > hits);
+    let mut code = code;
+    code[50] = 0x61;
+    assert_eq!(keccak256_cached(&code), keccak256(&code));
+}
+
+#[test]
+fn test_keccak256_batch() {
+    // Lengths around the 136 byte rate, in groups of different sizes.
+    let inputs: Vec<Vec<u8>> = [0, 1, 32, 135, 136, 137, 271, 272, 300, 1000, 64, 5, 136]
+        .iter()
+        .enumerate()
+        .map(|(i, &len)| (0..len).map(|j| (i * 31 + j * 7) as u8).collect())
+        .collect();
+    for count in [0, 1, 2, 3, 4, 5, inputs.len()] {
+        let hashes = keccak256_batch(inputs[..count].iter().map(|input| &input[..]));
+        assert_eq!(hashes, inputs[..count].iter().map(|input| keccak256(input)).collect::<Vec<_>>());
+    }
+}
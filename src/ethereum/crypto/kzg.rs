@@ -0,0 +1,482 @@
+//! KZG polynomial commitments of EIP-4844 blobs.
+//!
+//! A blob is a polynomial over the scalar field of BLS12-381, given by its
+//! values at the 4096th roots of unity in bit-reversed order. It is
+//! committed to by the point `p(τ) G1` for the secret `τ` of the trusted
+//! setup, and a proof that `p(z) = y` is the point `q(τ) G1` of the
+//! quotient `q(X) = (p(X) - y) / (X - z)`. Verifying needs only `τ G2`,
+//! which `KzgSettings` loads from the trusted setup. The `τ G2` of the
+//! mainnet setup is bundled, and used unless another setup is set.
+//!
+//! Follows the verification half of the polynomial commitments of the
+//! consensus specifications. Blob transactions are checked with
+//! `verify_blob_kzg_proof_batch`, and the point evaluation precompile
+//! with `verify_kzg_proof`.
+
+use std::sync::OnceLock;
+
+use crate::ethereum::{
+    ethereum_types::bytes::{Bytes32, Bytes48},
+    exceptions::Exception,
+    utils::hexadecimal::{hex_to_array, hex_to_bytes},
+};
+
+use super::{
+    bls12_381::{pairing_check, Fr, G1, G2},
+    hash::{sha256, Hash32},
+};
+
+pub const FIELD_ELEMENTS_PER_BLOB: usize = 4096;
+pub const BYTES_PER_FIELD_ELEMENT: usize = 32;
+pub const BYTES_PER_BLOB: usize = FIELD_ELEMENTS_PER_BLOB * BYTES_PER_FIELD_ELEMENT;
+pub const VERSIONED_HASH_VERSION_KZG: u8 = 0x01;
+
+const FIAT_SHAMIR_PROTOCOL_DOMAIN: &[u8] = b"FSBLOBVERIFY_V1_";
+const RANDOM_CHALLENGE_KZG_BATCH_DOMAIN: &[u8] = b"RCKZGBATCH___V1_";
+
+/// The generator of the multiplicative group of the scalar field.
+const PRIMITIVE_ROOT_OF_UNITY: u64 = 7;
+
+/// `τ G2` of the mainnet trusted setup of the KZG ceremony, the second G2
+/// point of its `trusted_setup.txt`.
+const MAINNET_TAU_G2: &str = "b5bfd7dd8cdeb128843bc287230af38926187075cbfbefa81009a2ce615ac53d2914e5870cb452d2afaaab24f3499f72185cbfee53492714734429b7b38608e23926c911cceceac9a36851477ba4c60b087041de621000edc98edada20c1def2";
+
+/// The part of the trusted setup needed to verify proofs.
+#[derive(Debug, Clone)]
+pub struct KzgSettings {
+    /// `τ G2`.
+    tau_g2: G2,
+}
+
+static TRUSTED_SETUP: OnceLock<KzgSettings> = OnceLock::new();
+
+impl KzgSettings {
+    /// The settings of the compressed `τ G2`.
+    pub fn new(tau_g2: &[u8; 96]) -> Result<Self, Exception> {
+        let tau_g2 = G2::from_compressed(tau_g2).ok_or(Exception::EthereumException("invalid trusted setup point"))?;
+        Ok(Self { tau_g2 })
+    }
+
+    /// The settings of the mainnet trusted setup.
+    pub fn mainnet() -> Self {
+        let tau_g2 = hex_to_array(MAINNET_TAU_G2).expect("the mainnet setup is hex");
+        Self::new(&tau_g2).expect("the mainnet setup is a G2 point")
+    }
+
+    /// The settings of a trusted setup in the text format of c-kzg: the
+    /// numbers of G1 and G2 points, then the hex encoded G1 points in
+    /// Lagrange form and the G2 points in monomial form, one per line.
+    pub fn load_trusted_setup(text: &str) -> Result<Self, Exception> {
+        const INVALID: Exception = Exception::EthereumException("invalid trusted setup");
+        let mut words = text.split_whitespace();
+        let mut count = || words.next().and_then(|word| word.parse::<usize>().ok()).ok_or(INVALID);
+        let (n_g1, n_g2) = (count()?, count()?);
+        if n_g1 != FIELD_ELEMENTS_PER_BLOB || n_g2 < 2 {
+            return Err(INVALID);
+        }
+        let tau_g2 = words.nth(n_g1 + 1).ok_or(INVALID)?;
+        let tau_g2 = hex_to_bytes(tau_g2).map_err(|_| INVALID)?;
+        Self::new(&tau_g2.0.try_into().map_err(|_| INVALID)?)
+    }
+}
+
+/// Make `settings` the trusted setup of `trusted_setup`. Fails if one is
+/// already set, or the mainnet one has already been used.
+pub fn set_trusted_setup(settings: KzgSettings) -> Result<(), KzgSettings> {
+    TRUSTED_SETUP.set(settings)
+}
+
+/// The trusted setup used by block validation and the point evaluation
+/// precompile: the one set with `set_trusted_setup`, or else the mainnet
+/// one.
+pub fn trusted_setup() -> &'static KzgSettings {
+    TRUSTED_SETUP.get_or_init(KzgSettings::mainnet)
+}
+
+/// The versioned hash of a commitment, which blob transactions carry.
+pub fn kzg_commitment_to_versioned_hash(commitment: &Bytes48) -> Hash32 {
+    let mut hash = sha256(&commitment.0);
+    hash.0[0] = VERSIONED_HASH_VERSION_KZG;
+    hash
+}
+
+fn hash_to_bls_field(data: &[u8]) -> Fr {
+    let hash = sha256(data);
+    let mut limbs = [0; 4];
+    for (i, chunk) in hash.0.rchunks(8).enumerate() {
+        limbs[i] = u64::from_be_bytes(chunk.try_into().unwrap());
+    }
+    // The hash is below 2^256 < 3r.
+    while Fr::from_limbs(limbs).is_none() {
+        let mut borrow = false;
+        for (limb, m) in limbs.iter_mut().zip(Fr::MODULUS) {
+            let (diff, b1) = limb.overflowing_sub(m);
+            let (diff, b2) = diff.overflowing_sub(borrow as u64);
+            *limb = diff;
+            borrow = b1 || b2;
+        }
+    }
+    Fr::from_limbs(limbs).unwrap()
+}
+
+fn bytes_to_bls_field(bytes: &Bytes32) -> Result<Fr, Exception> {
+    Fr::from_be_bytes(&bytes.0).ok_or(Exception::EthereumException("field element is not below the modulus"))
+}
+
+fn bytes_to_g1(bytes: &Bytes48) -> Result<G1, Exception> {
+    G1::from_compressed(&bytes.0).ok_or(Exception::EthereumException("invalid G1 point"))
+}
+
+fn blob_to_polynomial(blob: &[u8]) -> Result<Vec<Fr>, Exception> {
+    if blob.len() != BYTES_PER_BLOB {
+        return Err(Exception::EthereumException("invalid blob length"));
+    }
+    blob.chunks(BYTES_PER_FIELD_ELEMENT).map(|chunk| bytes_to_bls_field(&Bytes32(chunk.try_into().unwrap()))).collect()
+}
+
+/// The `FIELD_ELEMENTS_PER_BLOB`th roots of unity, in bit-reversed order.
+fn roots_of_unity() -> &'static [Fr] {
+    static ROOTS: OnceLock<Vec<Fr>> = OnceLock::new();
+    ROOTS.get_or_init(|| {
+        // (r - 1) / FIELD_ELEMENTS_PER_BLOB, with r - 1 = 2^32 k.
+        let shift = FIELD_ELEMENTS_PER_BLOB.trailing_zeros();
+        let mut exponent = Fr::MODULUS;
+        exponent[0] -= 1;
+        for i in 0..4 {
+            let high = exponent.get(i + 1).map_or(0, |limb| limb << (64 - shift));
+            exponent[i] = exponent[i] >> shift | high;
+        }
+        let root = Fr::from_u64(PRIMITIVE_ROOT_OF_UNITY).pow(&exponent);
+        let mut powers = Vec::with_capacity(FIELD_ELEMENTS_PER_BLOB);
+        let mut power = Fr::ONE;
+        for _ in 0..FIELD_ELEMENTS_PER_BLOB {
+            powers.push(power);
+            power = power * root;
+        }
+        (0..FIELD_ELEMENTS_PER_BLOB)
+            .map(|i| powers[i.reverse_bits() >> (usize::BITS - shift)])
+            .collect()
+    })
+}
+
+/// The value at `z` of the polynomial with `polynomial` at the roots of
+/// unity, by the barycentric formula.
+pub fn evaluate_polynomial_in_evaluation_form(polynomial: &[Fr], z: Fr) -> Fr {
+    let roots = roots_of_unity();
+    if let Some(i) = roots.iter().position(|root| *root == z) {
+        return polynomial[i];
+    }
+
+    // Invert all of `z - root` with one inversion.
+    let mut products = Vec::with_capacity(roots.len());
+    let mut product = Fr::ONE;
+    for root in roots {
+        products.push(product);
+        product = product * (z - *root);
+    }
+    let mut inverse = product.inverse().unwrap();
+    let mut result = Fr::ZERO;
+    for i in (0..roots.len()).rev() {
+        let denominator_inverse = inverse * products[i];
+        inverse = inverse * (z - roots[i]);
+        result = result + polynomial[i] * roots[i] * denominator_inverse;
+    }
+
+    let width = Fr::from_u64(FIELD_ELEMENTS_PER_BLOB as u64);
+    let z_to_width = z.pow(&[FIELD_ELEMENTS_PER_BLOB as u64]);
+    result * (z_to_width - Fr::ONE) * width.inverse().unwrap()
+}
+
+/// The Fiat-Shamir point at which a blob proof opens the blob.
+pub fn compute_challenge(blob: &[u8], commitment: &Bytes48) -> Fr {
+    let degree = (FIELD_ELEMENTS_PER_BLOB as u128).to_be_bytes();
+    hash_to_bls_field(&[FIAT_SHAMIR_PROTOCOL_DOMAIN, &degree, blob, &commitment.0].concat())
+}
+
+/// Whether `proof` shows that the polynomial of `commitment` is `y` at `z`,
+/// by `e(C - y G1, -G2) e(π, τ G2 - z G2) = 1`.
+fn verify_kzg_proof_impl(settings: &KzgSettings, commitment: &G1, z: Fr, y: Fr, proof: &G1) -> bool {
+    let x_minus_z = settings.tau_g2.add(&G2::generator().mul(&-z));
+    let p_minus_y = commitment.add(&G1::generator().mul(&-y));
+    pairing_check(&[(p_minus_y, G2::generator().neg()), (*proof, x_minus_z)])
+}
+
+/// Whether `proof` shows that the polynomial of `commitment` is `y` at `z`.
+/// Fails on points and field elements which are not validly encoded.
+pub fn verify_kzg_proof(
+    settings: &KzgSettings,
+    commitment: &Bytes48,
+    z: &Bytes32,
+    y: &Bytes32,
+    proof: &Bytes48,
+) -> Result<bool, Exception> {
+    let (z, y) = (bytes_to_bls_field(z)?, bytes_to_bls_field(y)?);
+    Ok(verify_kzg_proof_impl(settings, &bytes_to_g1(commitment)?, z, y, &bytes_to_g1(proof)?))
+}
+
+/// Whether all of the openings `(C, z, y, π)` hold, checked together with
+/// a random linear combination.
+fn verify_kzg_proof_batch(settings: &KzgSettings, openings: &[(G1, Fr, Fr, G1)]) -> bool {
+    let mut data = [RANDOM_CHALLENGE_KZG_BATCH_DOMAIN, &(FIELD_ELEMENTS_PER_BLOB as u64).to_be_bytes()].concat();
+    data.extend_from_slice(&(openings.len() as u64).to_be_bytes());
+    for (commitment, z, y, proof) in openings {
+        data.extend_from_slice(&commitment.to_compressed());
+        data.extend_from_slice(&z.to_be_bytes());
+        data.extend_from_slice(&y.to_be_bytes());
+        data.extend_from_slice(&proof.to_compressed());
+    }
+    let r = hash_to_bls_field(&data);
+
+    let mut proof_lincomb = G1::infinity();
+    let mut other_lincomb = G1::infinity();
+    let mut r_power = Fr::ONE;
+    for (commitment, z, y, proof) in openings {
+        proof_lincomb = proof_lincomb.add(&proof.mul(&r_power));
+        let c_minus_y = commitment.add(&G1::generator().mul(&-*y));
+        other_lincomb = other_lincomb.add(&c_minus_y.mul(&r_power)).add(&proof.mul(&(*z * r_power)));
+        r_power = r_power * r;
+    }
+    pairing_check(&[(proof_lincomb, settings.tau_g2.neg()), (other_lincomb, G2::generator())])
+}
+
+/// Whether `proof` shows that `commitment` commits to `blob`.
+pub fn verify_blob_kzg_proof(
+    settings: &KzgSettings,
+    blob: &[u8],
+    commitment: &Bytes48,
+    proof: &Bytes48,
+) -> Result<bool, Exception> {
+    let polynomial = blob_to_polynomial(blob)?;
+    let z = compute_challenge(blob, commitment);
+    let y = evaluate_polynomial_in_evaluation_form(&polynomial, z);
+    Ok(verify_kzg_proof_impl(settings, &bytes_to_g1(commitment)?, z, y, &bytes_to_g1(proof)?))
+}
+
+/// Whether each of `proofs` shows that the commitment beside it commits to
+/// the blob beside it, checked together.
+pub fn verify_blob_kzg_proof_batch(
+    settings: &KzgSettings,
+    blobs: &[&[u8]],
+    commitments: &[Bytes48],
+    proofs: &[Bytes48],
+) -> Result<bool, Exception> {
+    if blobs.len() != commitments.len() || blobs.len() != proofs.len() {
+        return Err(Exception::EthereumException("numbers of blobs, commitments and proofs differ"));
+    }
+    let mut openings = Vec::with_capacity(blobs.len());
+    for ((blob, commitment), proof) in blobs.iter().zip(commitments).zip(proofs) {
+        let polynomial = blob_to_polynomial(blob)?;
+        let z = compute_challenge(blob, commitment);
+        let y = evaluate_polynomial_in_evaluation_form(&polynomial, z);
+        openings.push((bytes_to_g1(commitment)?, z, y, bytes_to_g1(proof)?));
+    }
+    Ok(verify_kzg_proof_batch(settings, &openings))
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use crate::ethereum::{
+        crypto::bls12_381::{Fr, G1, G2},
+        ethereum_types::bytes::{Bytes32, Bytes48},
+        utils::hexadecimal::hex_to_array,
+    };
+
+    use super::{
+        compute_challenge, evaluate_polynomial_in_evaluation_form, kzg_commitment_to_versioned_hash, roots_of_unity,
+        trusted_setup, verify_blob_kzg_proof, verify_blob_kzg_proof_batch, verify_kzg_proof, KzgSettings,
+        BYTES_PER_BLOB, FIELD_ELEMENTS_PER_BLOB,
+    };
+
+    /// The secret of the insecure setup of the tests.
+    const TAU: u64 = 0x7a0;
+
+    pub(crate) fn settings() -> KzgSettings {
+        KzgSettings::new(&G2::generator().mul(&Fr::from_u64(TAU)).to_compressed()).unwrap()
+    }
+
+    /// A blob with a few non-zero elements, from `seed`.
+    pub(crate) fn blob(seed: u64) -> Vec<u8> {
+        let mut blob = vec![0; BYTES_PER_BLOB];
+        for i in 0..8 {
+            let element = Fr::from_u64(seed * 1000 + i).pow(&[5]).to_be_bytes();
+            let at = (i as usize * 517 % FIELD_ELEMENTS_PER_BLOB) * 32;
+            blob[at..at + 32].copy_from_slice(&element);
+        }
+        blob
+    }
+
+    fn polynomial(blob: &[u8]) -> Vec<Fr> {
+        blob.chunks(32).map(|chunk| Fr::from_be_bytes(chunk.try_into().unwrap()).unwrap()).collect()
+    }
+
+    /// The commitment to `blob` and the proof of its value at `z`, with the
+    /// secret of the setup.
+    fn commit_and_prove(blob: &[u8], z: Fr) -> (Bytes48, Fr, Bytes48) {
+        let polynomial = polynomial(blob);
+        let tau = Fr::from_u64(TAU);
+        let p_tau = evaluate_polynomial_in_evaluation_form(&polynomial, tau);
+        let y = evaluate_polynomial_in_evaluation_form(&polynomial, z);
+        let q_tau = (p_tau - y) * (tau - z).inverse().unwrap();
+        let commitment = Bytes48(G1::generator().mul(&p_tau).to_compressed());
+        (commitment, y, Bytes48(G1::generator().mul(&q_tau).to_compressed()))
+    }
+
+    /// The commitment to `blob` and its blob proof.
+    pub(crate) fn commit_blob(blob: &[u8]) -> (Bytes48, Bytes48) {
+        let tau = Fr::from_u64(TAU);
+        let commitment = Bytes48(G1::generator().mul(&evaluate_polynomial_in_evaluation_form(&polynomial(blob), tau)).to_compressed());
+        let (_, _, proof) = commit_and_prove(blob, compute_challenge(blob, &commitment));
+        (commitment, proof)
+    }
+
+    #[test]
+    fn roots() {
+        let roots = roots_of_unity();
+        assert_eq!(roots[0], Fr::ONE);
+        assert_eq!(roots[1], -Fr::ONE);
+        assert_eq!(roots[2].pow(&[4]), Fr::ONE);
+        assert_ne!(roots[2].pow(&[2]), Fr::ONE);
+        assert_ne!(roots[FIELD_ELEMENTS_PER_BLOB - 1].pow(&[2048]), Fr::ONE);
+        assert_eq!(roots[FIELD_ELEMENTS_PER_BLOB - 1].pow(&[4096]), Fr::ONE);
+
+        let blob = blob(1);
+        let polynomial = polynomial(&blob);
+        assert_eq!(evaluate_polynomial_in_evaluation_form(&polynomial, roots[517]), polynomial[517]);
+    }
+
+    #[test]
+    fn kzg_proofs() {
+        let settings = settings();
+        let blob = blob(1);
+        let z = Fr::from_u64(0x1234);
+        let (commitment, y, proof) = commit_and_prove(&blob, z);
+        let (z, y) = (Bytes32(z.to_be_bytes()), Bytes32(y.to_be_bytes()));
+        assert!(verify_kzg_proof(&settings, &commitment, &z, &y, &proof).unwrap());
+        let wrong_y = Bytes32((Fr::from_be_bytes(&y.0).unwrap() + Fr::ONE).to_be_bytes());
+        assert!(!verify_kzg_proof(&settings, &commitment, &z, &wrong_y, &proof).unwrap());
+        assert!(verify_kzg_proof(&settings, &commitment, &Bytes32([0xff; 32]), &y, &proof).is_err());
+        assert!(verify_kzg_proof(&settings, &Bytes48([0; 48]), &z, &y, &proof).is_err());
+    }
+
+    #[test]
+    fn blob_proofs() {
+        let settings = settings();
+        let blobs = [blob(1), blob(2)];
+        let (commitments, proofs): (Vec<_>, Vec<_>) = blobs.iter().map(|blob| commit_blob(blob)).unzip();
+        assert!(verify_blob_kzg_proof(&settings, &blobs[0], &commitments[0], &proofs[0]).unwrap());
+        assert!(!verify_blob_kzg_proof(&settings, &blobs[1], &commitments[0], &proofs[0]).unwrap());
+        let blobs = blobs.each_ref().map(|blob| &blob[..]);
+        assert!(verify_blob_kzg_proof_batch(&settings, &blobs, &commitments, &proofs).unwrap());
+        assert!(verify_blob_kzg_proof_batch(&settings, &[], &[], &[]).unwrap());
+        assert!(!verify_blob_kzg_proof_batch(&settings, &blobs, &commitments, &[proofs[1], proofs[0]]).unwrap());
+        assert!(verify_blob_kzg_proof_batch(&settings, &blobs, &commitments, &proofs[..1]).is_err());
+        assert!(verify_blob_kzg_proof(&settings, &blobs[0][1..], &commitments[0], &proofs[0]).is_err());
+
+        let hash = kzg_commitment_to_versioned_hash(&commitments[0]);
+        assert_eq!(hash.0[0], 0x01);
+        assert_ne!(hash, kzg_commitment_to_versioned_hash(&commitments[1]));
+    }
+
+    #[test]
+    fn trusted_setup_file() {
+        let g1 = G1::generator().to_compressed().map(|b| format!("{b:02x}")).concat();
+        let g2 = [G2::generator(), G2::generator().mul(&Fr::from_u64(TAU))]
+            .map(|point| point.to_compressed().map(|b| format!("{b:02x}")).concat());
+        let text = format!("4096\n2\n{}{}\n{}\n", format!("{g1}\n").repeat(4096), g2[0], g2[1]);
+        let loaded = KzgSettings::load_trusted_setup(&text).unwrap();
+        assert_eq!(loaded.tau_g2, settings().tau_g2);
+        assert!(KzgSettings::load_trusted_setup("4096\n2\n").is_err());
+        assert!(KzgSettings::load_trusted_setup(&text.replacen("4096", "16", 1)).is_err());
+    }
+
+    fn bytes32(hex: &str) -> Bytes32 {
+        Bytes32(hex_to_array(hex).unwrap())
+    }
+
+    fn bytes48(hex: &str) -> Bytes48 {
+        Bytes48(hex_to_array(hex).unwrap())
+    }
+
+    /// A blob whose `i`th element is `seed * (i + 1)` modulo 2^64.
+    fn mainnet_blob(seed: u64) -> Vec<u8> {
+        let mut blob = vec![0; BYTES_PER_BLOB];
+        for (i, element) in blob.chunks_mut(32).enumerate() {
+            element[24..].copy_from_slice(&seed.wrapping_mul(i as u64 + 1).to_be_bytes());
+        }
+        blob
+    }
+
+    #[test]
+    fn mainnet_kzg_proofs() {
+        // verify_kzg_proof_case_correct_proof_31ebd010e6098750 of the
+        // c-kzg-4844 tests.
+        let settings = trusted_setup();
+        let commitment = bytes48("8f59a8d2a1a625a17f3fea0fe5eb8c896db3764f3185481bc22f91b4aaffcca25f26936857bc3a7c2539ea8ec3a952b7");
+        let z = bytes32("73eda753299d7d483339d80809a1d80553bda402fffe5bfeffffffff00000000");
+        let y = bytes32("1522a4a7f34e1ea350ae07c29c96c7e79655aa926122e95fe69fcbd932ca49e9");
+        let proof = bytes48("a62ad71d14c5719385c0686f1871430475bf3a00f0aa3f7b8dd99a9abc2160744faf0070725e00b60ad9a026a15b1a8c");
+        assert!(verify_kzg_proof(settings, &commitment, &z, &y, &proof).unwrap());
+        assert!(!verify_kzg_proof(settings, &commitment, &z, &Bytes32::default(), &proof).unwrap());
+        assert!(!verify_kzg_proof(&self::settings(), &commitment, &z, &y, &proof).unwrap());
+
+        // The zero polynomial, whose commitment and proofs are the point at
+        // infinity.
+        let infinity = bytes48("c00000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000");
+        assert!(verify_kzg_proof(settings, &infinity, &z, &Bytes32::default(), &infinity).unwrap());
+        assert!(!verify_kzg_proof(settings, &infinity, &z, &y, &infinity).unwrap());
+
+        // Openings at z = 0x1234, computed with c-kzg 1.0.3 and the mainnet
+        // setup.
+        let z = bytes32("0000000000000000000000000000000000000000000000000000000000001234");
+        for (seed, commitment, y, proof) in [
+            (
+                1,
+                "a3c9330a06642467615c00ef352b887068536b670fd7bdae362414d378cf1b3a88fe3eb4264a88612814aecf8fd6acfc",
+                "4e8b7ea264705333e77423daaf8e0748415b74d477ee19222e070a4b93df97e7",
+                "b5656d11951d97c7ba23e5575341c41caef2ef241b4fba9a8cbf82d070bde51317093aea1f8727d311b6c1b605f6f0bb",
+            ),
+            (
+                0x9e3779b97f4a7c15,
+                "9831f06075c97d865f59a56613d06eeb2cf2c9f8d58d66f78da4a81f65bc163739653dd5df7de38ea0928ce9f173d39f",
+                "38ef2653867a091ad7391df73a9127762c0b0fef2e283a18d0482f77de4c3e73",
+                "aaeb09341d1c8bd9e9f09693f11740e5d725dae6e89154c05e8ceaab23ef655333d1f63739b4a7ebc28795a5944e441d",
+            ),
+        ] {
+            let polynomial = polynomial(&mainnet_blob(seed));
+            let y = bytes32(y);
+            assert_eq!(evaluate_polynomial_in_evaluation_form(&polynomial, Fr::from_be_bytes(&z.0).unwrap()).to_be_bytes(), y.0);
+            assert!(verify_kzg_proof(settings, &bytes48(commitment), &z, &y, &bytes48(proof)).unwrap());
+        }
+    }
+
+    #[test]
+    fn mainnet_blob_proofs() {
+        // Computed with c-kzg 1.0.3 and the mainnet setup.
+        let settings = trusted_setup();
+        let blobs = [mainnet_blob(1), mainnet_blob(0x9e3779b97f4a7c15)];
+        let commitments = [
+            bytes48("a3c9330a06642467615c00ef352b887068536b670fd7bdae362414d378cf1b3a88fe3eb4264a88612814aecf8fd6acfc"),
+            bytes48("9831f06075c97d865f59a56613d06eeb2cf2c9f8d58d66f78da4a81f65bc163739653dd5df7de38ea0928ce9f173d39f"),
+        ];
+        let proofs = [
+            bytes48("989736ab512d1b159d388e5187b68554dede1e48257e1de7b6959f58c61ca004390493b0e399c852241928993a2cdd1c"),
+            bytes48("809e1dbd0e3bb4adfc59ebd97a3853f711897618049a1d73f117da11312313c79b33995c4d2d12d75092a847956fcb17"),
+        ];
+        for i in 0..2 {
+            assert!(verify_blob_kzg_proof(settings, &blobs[i], &commitments[i], &proofs[i]).unwrap());
+            assert!(!verify_blob_kzg_proof(settings, &blobs[i], &commitments[i], &proofs[1 - i]).unwrap());
+        }
+        let blobs = blobs.each_ref().map(|blob| &blob[..]);
+        assert!(verify_blob_kzg_proof_batch(settings, &blobs, &commitments, &proofs).unwrap());
+        assert!(!verify_blob_kzg_proof_batch(settings, &blobs, &commitments, &[proofs[1], proofs[0]]).unwrap());
+
+        // verify_blob_kzg_proof_case_correct_proof_point_at_infinity_for_twos_poly
+        // of the c-kzg-4844 tests: a constant polynomial commits to a
+        // multiple of the generator and opens with the point at infinity.
+        let twos = Bytes32(Fr::from_u64(2).to_be_bytes()).0.repeat(FIELD_ELEMENTS_PER_BLOB);
+        let commitment = Bytes48(G1::generator().mul(&Fr::from_u64(2)).to_compressed());
+        let infinity = Bytes48(G1::infinity().to_compressed());
+        assert!(verify_blob_kzg_proof(settings, &twos, &commitment, &infinity).unwrap());
+        assert!(!verify_blob_kzg_proof(settings, &twos, &infinity, &infinity).unwrap());
+    }
+}
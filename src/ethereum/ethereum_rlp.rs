@@ -1,5 +1,7 @@
 pub mod rlp;
 
+pub mod stream;
+
 pub mod py {
 }
 
@@ -9,4 +9,50 @@ pub enum RLPException {
 
     /// Buffer not big enough
     DestTooSmall(usize),
+
+    /// An error decoding the named field or variant of a structure.
+    Field(&'static str, Box<RLPException>),
+
+    /// The input ends before the end of the item.
+    Truncated,
+    /// Input is left over after the item.
+    TrailingBytes,
+    /// A length which is not encoded in the shortest form, or a single byte
+    /// below 0x80 which is not encoded as itself.
+    NonCanonicalLength,
+    /// An integer with leading zero bytes (strict mode).
+    NonCanonicalInteger,
+    /// A fixed size byte string of the wrong length (strict mode).
+    InvalidLength { expected: usize, actual: usize },
+    /// The input, a list or a byte string is longer than a limit in
+    /// `DecodeOptions` allows.
+    LimitExceeded { what: &'static str, limit: usize },
+}
+
+impl std::fmt::Display for RLPException {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            RLPException::DecodingError(e) => write!(f, "cannot decode: {e}"),
+            RLPException::EncodingError(e) => write!(f, "cannot encode: {e}"),
+            RLPException::DestTooSmall(len) => write!(f, "destination too small, {len} bytes needed"),
+            RLPException::Field(name, _) => write!(f, "in {name}"),
+            RLPException::Truncated => write!(f, "input ends inside an item"),
+            RLPException::TrailingBytes => write!(f, "bytes left over after the item"),
+            RLPException::NonCanonicalLength => write!(f, "length not in its shortest form"),
+            RLPException::NonCanonicalInteger => write!(f, "integer with leading zero bytes"),
+            RLPException::InvalidLength { expected, actual } => {
+                write!(f, "{actual} bytes where {expected} are expected")
+            }
+            RLPException::LimitExceeded { what, limit } => write!(f, "{what} longer than the limit of {limit}"),
+        }
+    }
+}
+
+impl std::error::Error for RLPException {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            RLPException::Field(_, e) => Some(e),
+            _ => None,
+        }
+    }
 }
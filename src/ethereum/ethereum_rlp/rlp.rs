@@ -1,8 +1,8 @@
 //! Defines the serialization and deserialization format used throughout Ethereum.
 
-use std::ops::{Deref, DerefMut};
+use std::{cell::Cell, ops::{Deref, DerefMut}};
 
-use crate::ethereum::{cancun::fork_types::{Address, VersionedHash}, ethereum_types::{bytes::{Bytes, Bytes32}, numeric::{Uint, U256, U64}}};
+use crate::ethereum::{cancun::fork_types::{Address, VersionedHash}, ethereum_types::{bytes::{Bytes, Bytes32}, numeric::{Uint, U256, U64, U8}}};
 
 use super::exceptions::RLPException;
 
@@ -10,26 +10,130 @@ use super::exceptions::RLPException;
 pub trait Extended {
     fn encode<'a, 'b>(&self, buffer: &'a mut Bytes) -> Result<(), RLPException>;
     fn decode<'a, 'b>(&mut self, buffer: &'a mut &'b [u8]) -> Result<(), RLPException>;
+
+    /// True for an absent optional field, which encodes to nothing.
+    fn is_absent(&self) -> bool {
+        false
+    }
 }
 
+/// Implement `Extended` for a struct encoded as a list of its fields, or for
+/// an enum of typed envelopes.
+///
+/// Structs list their fields in encoding order:
+///
+/// ```
+/// use ejit_evm::{ethereum::{ethereum_rlp::rlp, ethereum_types::{bytes::Bytes, numeric::U256}}, impl_extended};
+///
+/// #[derive(Debug, Default, PartialEq)]
+/// struct Entry { key: Bytes, value: U256, note: Option<Bytes> }
+/// impl_extended!(Entry: key, value, note);
+///
+/// let entry = Entry { key: Bytes(vec![1]), value: U256::from(2_u32), note: None };
+/// assert_eq!(rlp::decode_to::<Entry>(&rlp::encode(&entry)?)?, entry);
+/// # Ok::<(), ejit_evm::ethereum::ethereum_rlp::exceptions::RLPException>(())
+/// ```
+///
+/// `Option` fields may only appear at the end. They are omitted from the
+/// encoding when `None`, and it is an error to have `Some` after a `None`.
+/// Decoding errors are wrapped in `RLPException::Field` naming the field.
+///
+/// Enums of single-field tuple variants are encoded as a byte string holding
+/// the type byte followed by the encoding of the variant. An optional untyped
+/// variant listed first is encoded as a plain list, as legacy transactions are:
+///
+/// ```
+/// use ejit_evm::{ethereum::{ethereum_rlp::rlp, ethereum_types::{bytes::Bytes, numeric::U256}}, impl_extended};
+///
+/// #[derive(Debug, PartialEq)]
+/// enum Envelope { Plain(Vec<U256>), Tagged(Bytes) }
+/// impl Default for Envelope {
+///     fn default() -> Self { Envelope::Plain(Vec::new()) }
+/// }
+/// impl_extended!(enum Envelope: Plain, 0x01 => Tagged);
+///
+/// let tagged = Envelope::Tagged(Bytes(vec![2]));
+/// assert_eq!(rlp::encode(&tagged)?.0, [0x82, 0x01, 0x02]);
+/// for envelope in [Envelope::Plain(vec![U256::ONE]), tagged] {
+///     assert_eq!(rlp::decode_to::<Envelope>(&rlp::encode(&envelope)?)?, envelope);
+/// }
+/// # Ok::<(), ejit_evm::ethereum::ethereum_rlp::exceptions::RLPException>(())
+/// ```
 #[macro_export]
 macro_rules! impl_extended {
+    (enum $t : ident : $legacy : ident $(, $type_byte : literal => $variant : ident)* $(,)?) => {
+        $crate::impl_extended!(@enum $t, Some($legacy), $($type_byte => $variant),*);
+    };
+    (enum $t : ident : $($type_byte : literal => $variant : ident),+ $(,)?) => {
+        $crate::impl_extended!(@enum $t, None, $($type_byte => $variant),*);
+    };
+    (@enum $t : ident, $(Some($legacy : ident))? $(None)?, $($type_byte : literal => $variant : ident),*) => {
+        impl $crate::ethereum::ethereum_rlp::rlp::Extended for $t {
+            fn encode<'a, 'b>(&self, buffer: &'a mut $crate::ethereum::ethereum_types::bytes::Bytes) -> Result<(), $crate::ethereum::ethereum_rlp::exceptions::RLPException> {
+                let (type_byte, payload) = match self {
+                    $($t::$legacy(inner) => return inner.encode(buffer),)?
+                    $($t::$variant(inner) => ($type_byte as u8, $crate::ethereum::ethereum_rlp::rlp::encode(inner)?),)*
+                };
+                let mut envelope = Vec::with_capacity(1 + payload.len());
+                envelope.push(type_byte);
+                envelope.extend_from_slice(&payload);
+                $crate::ethereum::ethereum_rlp::rlp::encode_bytes(buffer, &envelope);
+                Ok(())
+            }
+
+            fn decode<'a, 'b>(&mut self, buffer: &'a mut &'b [u8]) -> Result<(), $crate::ethereum::ethereum_rlp::exceptions::RLPException> {
+                use $crate::ethereum::ethereum_rlp::{exceptions::RLPException, rlp};
+                $(
+                    if buffer.first().is_some_and(|b| *b >= 0xc0) {
+                        let mut inner = Default::default();
+                        rlp::Extended::decode(&mut inner, buffer)
+                            .map_err(|e| RLPException::Field(stringify!($t::$legacy), Box::new(e)))?;
+                        *self = $t::$legacy(inner);
+                        return Ok(());
+                    }
+                )?
+                let mut envelope = $crate::ethereum::ethereum_types::bytes::Bytes::default();
+                rlp::Extended::decode(&mut envelope, buffer)?;
+                let Some((type_byte, payload)) = envelope.split_first() else {
+                    return Err(RLPException::DecodingError(concat!("empty ", stringify!($t))));
+                };
+                $(
+                    if *type_byte == $type_byte {
+                        let inner = rlp::decode_to(payload)
+                            .map_err(|e| RLPException::Field(stringify!($t::$variant), Box::new(e)))?;
+                        *self = $t::$variant(inner);
+                        return Ok(());
+                    }
+                )*
+                Err(RLPException::DecodingError(concat!("unknown ", stringify!($t), " type")))
+            }
+        }
+    };
     ($t : ty : $($field : ident),*) => {
-        impl Extended for $t {
-            fn encode<'a, 'b>(&self, buffer: &'a mut Bytes) -> Result<(), RLPException> {
-                encode_sequence(buffer, &[
+        impl $crate::ethereum::ethereum_rlp::rlp::Extended for $t {
+            fn encode<'a, 'b>(&self, buffer: &'a mut $crate::ethereum::ethereum_types::bytes::Bytes) -> Result<(), $crate::ethereum::ethereum_rlp::exceptions::RLPException> {
+                let mut absent = false;
+                $(
+                    if $crate::ethereum::ethereum_rlp::rlp::Extended::is_absent(&self.$field) {
+                        absent = true;
+                    } else if absent {
+                        return Err($crate::ethereum::ethereum_rlp::exceptions::RLPException::EncodingError(
+                            concat!(stringify!($t), ".", stringify!($field), " is present after a missing optional field")
+                        ));
+                    }
+                )*
+                $crate::ethereum::ethereum_rlp::rlp::encode_sequence(buffer, &[
                     $(&self.$field),*
                 ])
             }
-        
-            fn decode<'a, 'b>(&mut self, buffer: &'a mut &'b [u8]) -> Result<(), RLPException> {
-                decode_to_sequence(buffer, &mut [
-                    $(&mut self.$field),*
+
+            fn decode<'a, 'b>(&mut self, buffer: &'a mut &'b [u8]) -> Result<(), $crate::ethereum::ethereum_rlp::exceptions::RLPException> {
+                $crate::ethereum::ethereum_rlp::rlp::decode_fields(buffer, stringify!($t), &mut [
+                    $((concat!(stringify!($t), ".", stringify!($field)), &mut self.$field)),*
                 ])
             }
         }
-                
-    }
+    };
 }
 
 //
@@ -69,7 +173,7 @@ impl Extended for bool {
     
     fn decode<'a, 'b>(&mut self, buffer: &'a mut &'b [u8]) -> Result<(), RLPException> {
         let mut bytes = [0; 1];
-        decode_to_bytes(buffer, &mut bytes[..])?;
+        decode_to_uint_bytes(buffer, &mut bytes[..])?;
         if bytes[0] > 1 {
             return Err(RLPException::DecodingError("invalid bool"));
         }
@@ -88,7 +192,7 @@ impl Extended for Uint {
     
     fn decode<'a, 'b>(&mut self, buffer: &'a mut &'b [u8]) -> Result<(), RLPException> {
         let mut bytes = [0; size_of::<Self>()];
-        decode_to_bytes(buffer, &mut bytes[..])?;
+        decode_to_uint_bytes(buffer, &mut bytes[..])?;
         *self = Self::from_be_bytes(bytes);
         Ok(())
     }
@@ -104,7 +208,7 @@ impl Extended for U256 {
     
     fn decode<'a, 'b>(&mut self, buffer: &'a mut &'b [u8]) -> Result<(), RLPException> {
         let mut bytes = [0; size_of::<Self>()];
-        decode_to_bytes(buffer, &mut bytes[..])?;
+        decode_to_uint_bytes(buffer, &mut bytes[..])?;
         *self = Self::from_be_bytes(bytes);
         Ok(())
     }
@@ -112,15 +216,14 @@ impl Extended for U256 {
 
 impl Extended for Bytes32 {
     fn encode<'a, 'b>(&self, buffer: &'a mut Bytes) -> Result<(), RLPException> {
-        let bytes = self.0;
-        let first_nz = bytes.iter().position(|b| *b != 0).unwrap_or(bytes.len());
-        encode_bytes(buffer, &bytes[first_nz..]);
+        // Fixed width, like `Address`.
+        encode_bytes(buffer, &self.0);
         Ok(())
     }
     
     fn decode<'a, 'b>(&mut self, buffer: &'a mut &'b [u8]) -> Result<(), RLPException> {
         let mut bytes = [0; size_of::<Self>()];
-        decode_to_bytes(buffer, &mut bytes[..])?;
+        decode_to_fixed_bytes(buffer, &mut bytes[..])?;
         *self = Self(bytes);
         Ok(())
     }
@@ -128,15 +231,14 @@ impl Extended for Bytes32 {
 
 impl Extended for Address {
     fn encode<'a, 'b>(&self, buffer: &'a mut Bytes) -> Result<(), RLPException> {
-        let bytes = self.to_be_bytes();
-        let first_nz = bytes.iter().position(|b| *b != 0).unwrap_or(bytes.len());
-        encode_bytes(buffer, &bytes[first_nz..]);
+        // Addresses are fixed width byte strings, not integers.
+        encode_bytes(buffer, &self.to_be_bytes());
         Ok(())
     }
     
     fn decode<'a, 'b>(&mut self, buffer: &'a mut &'b [u8]) -> Result<(), RLPException> {
         let mut bytes = [0; 20];
-        decode_to_bytes(buffer, &mut bytes[..])?;
+        decode_to_fixed_bytes(buffer, &mut bytes[..])?;
         *self = Self::from_be_bytes(bytes);
         Ok(())
     }
@@ -155,6 +257,9 @@ impl Extended for Bytes {
                 Ok(())
             }
             Err(RLPException::DestTooSmall(new_len)) => {
+                if let Some(limit) = decode_options().max_bytes_len.filter(|limit| new_len > *limit) {
+                    return Err(RLPException::LimitExceeded { what: "byte string", limit });
+                }
                 self.0.resize(new_len, 0);
                 decode_to_bytes(buffer, self.deref_mut())
             },
@@ -173,7 +278,23 @@ impl Extended for U64 {
     
     fn decode<'a, 'b>(&mut self, buffer: &'a mut &'b [u8]) -> Result<(), RLPException> {
         let mut bytes = [0; size_of::<Self>()];
-        decode_to_bytes(buffer, &mut bytes[..])?;
+        decode_to_uint_bytes(buffer, &mut bytes[..])?;
+        *self = Self::from_be_bytes(bytes);
+        Ok(())
+    }
+}
+
+impl Extended for U8 {
+    fn encode<'a, 'b>(&self, buffer: &'a mut Bytes) -> Result<(), RLPException> {
+        let bytes = self.to_be_bytes();
+        let first_nz = bytes.iter().position(|b| *b != 0).unwrap_or(bytes.len());
+        encode_bytes(buffer, &bytes[first_nz..]);
+        Ok(())
+    }
+    
+    fn decode<'a, 'b>(&mut self, buffer: &'a mut &'b [u8]) -> Result<(), RLPException> {
+        let mut bytes = [0; size_of::<Self>()];
+        decode_to_uint_bytes(buffer, &mut bytes[..])?;
         *self = Self::from_be_bytes(bytes);
         Ok(())
     }
@@ -209,12 +330,15 @@ impl<T : Extended + Default> Extended for Option<T> {
         if let Some(t) = self {
             t.encode(buffer)
         } else {
-            // TODO: disallow None options before Some.
-            // Maybe return a bool indicating this state.
+            // `impl_extended!` rejects a `None` followed by a `Some`.
             Ok(())
         }
     }
 
+    fn is_absent(&self) -> bool {
+        self.is_none()
+    }
+
     fn decode<'a, 'b>(&mut self, buffer: &'a mut &'b [u8]) -> Result<(), RLPException> {
         // Optional items take zero bytes if they are None.
         // But they may only occur at the end of a structure.
@@ -230,6 +354,55 @@ impl<T : Extended + Default> Extended for Option<T> {
     }
 }
 
+/// An optional value which is encoded as the empty byte string when absent,
+/// such as the `to` field of a transaction (`Union[Bytes0, Address]` in the
+/// specification).
+///
+/// Unlike `Option`, it may appear anywhere in a structure.
+#[derive(Debug, Clone, Default, PartialEq, Eq, PartialOrd, Ord)]
+pub struct Nullable<T>(pub Option<T>);
+
+impl<T> Deref for Nullable<T> {
+    type Target = Option<T>;
+
+    fn deref(&self) -> &Self::Target {
+        &self.0
+    }
+}
+
+impl<T> DerefMut for Nullable<T> {
+    fn deref_mut(&mut self) -> &mut Self::Target {
+        &mut self.0
+    }
+}
+
+impl<T> From<Option<T>> for Nullable<T> {
+    fn from(value: Option<T>) -> Self {
+        Self(value)
+    }
+}
+
+impl<T : Extended + Default> Extended for Nullable<T> {
+    fn encode<'a, 'b>(&self, buffer: &'a mut Bytes) -> Result<(), RLPException> {
+        match &self.0 {
+            Some(t) => t.encode(buffer),
+            None => Ok(encode_bytes(buffer, b"")),
+        }
+    }
+
+    fn decode<'a, 'b>(&mut self, buffer: &'a mut &'b [u8]) -> Result<(), RLPException> {
+        if buffer.first() == Some(&0x80) {
+            *buffer = &buffer[1..];
+            self.0 = None;
+        } else {
+            let mut t = T::default();
+            t.decode(buffer)?;
+            self.0 = Some(t);
+        }
+        Ok(())
+    }
+}
+
 impl<T : Extended + Default + Clone> Extended for Vec<T> {
     fn encode<'a, 'b>(&self, buffer: &'a mut Bytes) -> Result<(), RLPException> {
         let refs : Vec<&dyn Extended> = self.iter().map(|e| e as &dyn Extended).collect();
@@ -241,8 +414,12 @@ impl<T : Extended + Default + Clone> Extended for Vec<T> {
         let mut joined_encodings = find_joined_encodings(buffer)?;
     
 
+        let max_list_len = decode_options().max_list_len;
         let mut buffer = &mut joined_encodings;
         while !buffer.is_empty() {
+            if let Some(limit) = max_list_len.filter(|limit| self.len() >= *limit) {
+                return Err(RLPException::LimitExceeded { what: "list", limit });
+            }
             let mut t = T::default();
             t.decode(buffer)?;
             self.push(t);
@@ -257,7 +434,7 @@ impl Extended for VersionedHash {
     }
 
     fn decode<'a, 'b>(&mut self, buffer: &'a mut &'b [u8]) -> Result<(), RLPException> {
-        decode_to_bytes(buffer, &mut self.0)
+        decode_to_fixed_bytes(buffer, &mut self.0)
     }
 }
 
@@ -324,16 +501,84 @@ fn join_encodings(raw_sequence: &[&dyn Extended]) -> Result<Bytes, RLPException>
 //
 
 
+/// Options which apply to everything decoded by one call to `decode_with`.
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct DecodeOptions {
+    /// Reject encodings which other implementations would not produce:
+    /// integers with leading zero bytes and fixed size byte strings
+    /// (addresses, hashes) of the wrong length. Consensus code decodes
+    /// blocks and transactions in strict mode so that every accepted
+    /// encoding hashes to a unique value.
+    pub strict: bool,
+    /// Longest input `decode_with` accepts, in bytes.
+    pub max_size: Option<usize>,
+    /// Most items in any list decoded into a `Vec`, such as the
+    /// transactions, ommers or withdrawals of a block.
+    pub max_list_len: Option<usize>,
+    /// Longest byte string decoded into `Bytes`, such as the data of a
+    /// transaction or the code of a contract.
+    pub max_bytes_len: Option<usize>,
+}
+
+/// Largest RLP encoded block, 10 MiB less a 2 MiB margin for the consensus
+/// layer's wrapping of the payload (EIP-7934).
+pub const MAX_RLP_BLOCK_SIZE: usize = 10 * 1024 * 1024 - 2 * 1024 * 1024;
+
+impl DecodeOptions {
+    /// Strict mode, without limits.
+    pub const STRICT: Self = Self { strict: true, max_size: None, max_list_len: None, max_bytes_len: None };
+
+    /// Strict mode, limited to inputs of at most `MAX_RLP_BLOCK_SIZE` bytes.
+    ///
+    /// Nothing decoded from such an input can be much larger than the input
+    /// itself, so this is enough to bound the memory used decoding a block
+    /// from an untrusted peer. The list and byte string limits can be added
+    /// to reject oversized parts of the block early.
+    pub const BLOCK: Self = Self { max_size: Some(MAX_RLP_BLOCK_SIZE), ..Self::STRICT };
+}
+
+thread_local! {
+    static OPTIONS: Cell<DecodeOptions> = Cell::new(DecodeOptions::default());
+}
+
+/// The options of the decode in progress.
+pub fn decode_options() -> DecodeOptions {
+    OPTIONS.with(|options| options.get())
+}
+
 /// Decodes an integer, byte sequence, or list of RLP encodable objects
 /// from the byte sequence `encoded_data`, using RLP.
-pub fn decode_to<T : Extended  + Default>(mut encoded_data: &[u8]) -> Result<T, RLPException> {
-    if encoded_data.len() <= 0 {
+pub fn decode_to<T : Extended  + Default>(encoded_data: &[u8]) -> Result<T, RLPException> {
+    decode_with(encoded_data, decode_options())
+}
+
+/// `decode_to` in strict mode.
+pub fn decode_to_strict<T : Extended  + Default>(encoded_data: &[u8]) -> Result<T, RLPException> {
+    decode_with(encoded_data, DecodeOptions::STRICT)
+}
+
+/// `decode_to` with `options` in force until it returns.
+pub fn decode_with<T : Extended  + Default>(mut encoded_data: &[u8], options: DecodeOptions) -> Result<T, RLPException> {
+    /// Restores the previous options, also on unwind.
+    struct Restore(DecodeOptions);
+
+    impl Drop for Restore {
+        fn drop(&mut self) {
+            OPTIONS.with(|options| options.set(self.0));
+        }
+    }
+
+    let _restore = Restore(OPTIONS.with(|current| current.replace(options)));
+    if encoded_data.is_empty() {
         return Err(RLPException::DecodingError("Cannot decode empty bytestring"));
     }
+    if let Some(limit) = options.max_size.filter(|limit| encoded_data.len() > *limit) {
+        return Err(RLPException::LimitExceeded { what: "input", limit });
+    }
     let mut res = T::default();
     T::decode(&mut res, &mut encoded_data)?;
     if !encoded_data.is_empty() {
-        return Err(RLPException::DecodingError("too short"));
+        return Err(RLPException::TrailingBytes);
     }
     Ok(res)
 }
@@ -348,44 +593,39 @@ pub fn decode_to_sequence(encoded_sequence: &mut &[u8], dest: &mut [&mut dyn Ext
     Ok(())
 }
 
+/// Decodes a list into the fields of a struct, as `decode_to_sequence`
+/// does, but reports which field failed and rejects extra items.
+///
+/// Each entry of `dest` is the name of a field and the field itself.
+pub fn decode_fields(encoded_sequence: &mut &[u8], name: &'static str, dest: &mut [(&'static str, &mut dyn Extended)]) -> Result<(), RLPException> {
+    let mut joined_encodings = find_joined_encodings(encoded_sequence)
+        .map_err(|e| RLPException::Field(name, Box::new(e)))?;
+    for (field, d) in dest {
+        d.decode(&mut joined_encodings)
+            .map_err(|e| RLPException::Field(field, Box::new(e)))?;
+    }
+    if !joined_encodings.is_empty() {
+        return Err(RLPException::Field(name, Box::new(RLPException::DecodingError("too many items"))));
+    }
+    Ok(())
+}
+
 fn find_joined_encodings<'a>(buffer: &mut &'a [u8]) -> Result<&'a [u8], RLPException> {
     if buffer.is_empty() || buffer[0] <= 0xBF {
         return Err(RLPException::DecodingError("expected sequence"));
     }
-    let encoded_sequence_len = buffer.len();
-    let joined_encodings = if buffer[0] <= 0xF7 {
-        let len_joined_encodings = (buffer[0] - 0xC0) as usize;
-        if len_joined_encodings >= encoded_sequence_len {
-            return Err(RLPException::DecodingError("too long: decode_to_sequence 1"));
-        }
-        let res = &buffer[1..1 + len_joined_encodings];
-        *buffer = &buffer[1 + len_joined_encodings..];
-        res
+    let (start, len) = if buffer[0] <= 0xF7 {
+        (1, (buffer[0] - 0xC0) as usize)
     } else {
-        let joined_encodings_start_idx = (1 + buffer[0] - 0xF7) as usize;
-        if joined_encodings_start_idx - 1 >= encoded_sequence_len {
-            return Err(RLPException::DecodingError("too long: decode_to_sequence 2"));
-        }
-        if buffer[1] == 0 {
-            return Err(RLPException::DecodingError("incorrect length 1"));
-        }
-        let len_joined_encodings = decode_length(&buffer[1..joined_encodings_start_idx]);
-        if len_joined_encodings < 0x38 {
-            return Err(RLPException::DecodingError("incorrect length 2"));
-        }
-        let joined_encodings_end_idx = (
-            joined_encodings_start_idx + len_joined_encodings
-        );
-        if joined_encodings_end_idx - 1 >= encoded_sequence_len {
-            return Err(RLPException::DecodingError("too long: decode_to_sequence 1"));
-        }
-        let res = &buffer[
-            joined_encodings_start_idx..joined_encodings_end_idx
-        ];
-        *buffer = &buffer[joined_encodings_end_idx..];
-        res
+        let start = (buffer[0] - 0xF7) as usize + 1;
+        (start, long_length(buffer, start)?)
     };
-    Ok(joined_encodings)
+    let end = start.checked_add(len)
+        .filter(|end| *end <= buffer.len())
+        .ok_or(RLPException::Truncated)?;
+    let res = &buffer[start..end];
+    *buffer = &buffer[end..];
+    Ok(res)
 }
 
 /// Decodes `joined_encodings`, which is a concatenation of RLP encoded
@@ -409,55 +649,84 @@ fn decode_joined_encodings(mut joined_encodings: &[u8], dest: &mut [&mut dyn Ext
 /// 
 /// It also screens out sequences.
 pub fn decode_to_bytes<'d, 'a, 'b>(buffer: &'a mut &'b [u8], dest: &'d mut [u8]) -> Result<(), RLPException> {
+    decode_bytes(buffer, dest).map(|_| ())
+}
+
+/// `decode_to_bytes` for an unsigned integer, which in strict mode may not
+/// have leading zeros.
+pub fn decode_to_uint_bytes(buffer: &mut &[u8], dest: &mut [u8]) -> Result<(), RLPException> {
+    let len = decode_bytes(buffer, dest)?;
+    if len != 0 && dest[dest.len() - len] == 0 && decode_options().strict {
+        return Err(RLPException::NonCanonicalInteger);
+    }
+    Ok(())
+}
+
+/// `decode_to_bytes` for a fixed size byte string, which in strict mode
+/// must fill `dest`.
+pub fn decode_to_fixed_bytes(buffer: &mut &[u8], dest: &mut [u8]) -> Result<(), RLPException> {
+    let len = decode_bytes(buffer, dest)?;
+    if len != dest.len() && decode_options().strict {
+        return Err(RLPException::InvalidLength { expected: dest.len(), actual: len });
+    }
+    Ok(())
+}
+
+/// `decode_to_bytes`, returning the length of the payload.
+fn decode_bytes(buffer: &mut &[u8], dest: &mut [u8]) -> Result<usize, RLPException> {
     let dest_len = dest.len();
     dest.fill(0);
     if buffer.is_empty() || buffer[0] > 0xBF {
         return Err(RLPException::DecodingError("expected bytes, got a sequence"));
-    } else if buffer[0] <= 0x80 {
+    }
+    if buffer[0] < 0x80 {
         if dest_len < 1 {
             return Err(RLPException::DestTooSmall(1));
         }
         dest[dest_len-1] = buffer[0];
         *buffer = &buffer[1..];
-    } else if buffer[0] <= 0xB7 {
-        let len_raw_data = (buffer[0] - 0x80) as usize;
-        if len_raw_data >= buffer.len() {
-            return Err(RLPException::DecodingError("truncated"));
-        }
-        if len_raw_data > dest_len {
-            return Err(RLPException::DestTooSmall(len_raw_data));
-        }
-        let raw_data = &buffer[1..1 + len_raw_data];
-        if len_raw_data == 1 && raw_data[0] < 0x80 {
-            return Err(RLPException::DecodingError("incorrect length"));
-        }
-        dest[dest_len-len_raw_data..].copy_from_slice(raw_data);
-        *buffer = &buffer[1 + len_raw_data..];
+        return Ok(1);
+    }
+    let (start, len) = if buffer[0] <= 0xB7 {
+        (1, (buffer[0] - 0x80) as usize)
     } else { // 0xb8..0xbf
         // This is the index in the encoded data at which decoded data
         // starts from.
-        let decoded_data_start_idx = (1 + buffer[0] - 0xB7) as usize;
-        if decoded_data_start_idx - 1 >= buffer.len() {
-            return Err(RLPException::DecodingError("truncated"));
-        }
-        if buffer[1] == 0 {
-            return Err(RLPException::DecodingError("incorrect length"));
-        }
-        let len_decoded_data = decode_length(&buffer[1..decoded_data_start_idx]);
-        if len_decoded_data < 0x38 {
-            return Err(RLPException::DecodingError("incorrect length"));
-        }
-        let decoded_data_end_idx = decoded_data_start_idx + len_decoded_data;
-        if decoded_data_end_idx - 1 >= buffer.len() {
-            return Err(RLPException::DecodingError("truncated"));
-        }
-        if len_decoded_data > dest_len {
-            return Err(RLPException::DestTooSmall(len_decoded_data));
-        }
-        dest[dest_len-len_decoded_data..].copy_from_slice(&buffer[decoded_data_start_idx..decoded_data_end_idx]);
-        *buffer = &buffer[decoded_data_end_idx..];
+        let start = (buffer[0] - 0xB7) as usize + 1;
+        (start, long_length(buffer, start)?)
+    };
+    let end = start.checked_add(len)
+        .filter(|end| *end <= buffer.len())
+        .ok_or(RLPException::Truncated)?;
+    let raw_data = &buffer[start..end];
+    if len == 1 && raw_data[0] < 0x80 {
+        return Err(RLPException::NonCanonicalLength);
+    }
+    if len > dest_len {
+        return Err(RLPException::DestTooSmall(len));
+    }
+    dest[dest_len-len..].copy_from_slice(raw_data);
+    *buffer = &buffer[end..];
+    Ok(len)
+}
+
+/// Read the length of a long string or list, which is encoded in the bytes
+/// of `buffer` after the prefix up to `start`.
+fn long_length(buffer: &[u8], start: usize) -> Result<usize, RLPException> {
+    let Some(len_bytes) = buffer.get(1..start) else {
+        return Err(RLPException::Truncated);
+    };
+    if len_bytes[0] == 0 {
+        return Err(RLPException::NonCanonicalLength);
     }
-    Ok(())
+    if len_bytes.len() > size_of::<usize>() {
+        return Err(RLPException::Truncated);
+    }
+    let len = decode_length(len_bytes);
+    if len < 0x38 {
+        return Err(RLPException::NonCanonicalLength);
+    }
+    Ok(len)
 }
 
 /// Decode a variable length slice to a usize.
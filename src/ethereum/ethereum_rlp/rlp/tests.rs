@@ -56,3 +56,431 @@ fn basic_rlp() {
         assert_eq!(&buffer.deref()[0..2], &[0xb8, 0x38]);
     }
 }
+
+#[derive(Debug, Default, Clone, PartialEq)]
+struct Trailing {
+    a: Uint,
+    b: Option<Uint>,
+    c: Option<Uint>,
+}
+
+crate::impl_extended!(Trailing: a, b, c);
+
+#[derive(Debug, Clone, PartialEq)]
+enum Envelope {
+    Plain(Trailing),
+    Typed(Trailing),
+}
+
+impl Default for Envelope {
+    fn default() -> Self {
+        Self::Plain(Default::default())
+    }
+}
+
+crate::impl_extended!(enum Envelope: Plain, 0x05 => Typed);
+
+#[test]
+fn impl_extended_optional_fields() {
+    use super::{decode_to, encode, RLPException};
+
+    let t = Trailing { a: 1, b: Some(2), c: None };
+    let encoded = encode(&t).unwrap();
+    assert_eq!(encoded.deref(), &[0xc2, 0x01, 0x02]);
+    assert_eq!(decode_to::<Trailing>(&encoded).unwrap(), t);
+
+    let t = Trailing { a: 1, b: None, c: Some(3) };
+    assert!(matches!(encode(&t), Err(RLPException::EncodingError(_))));
+
+    assert_eq!(
+        decode_to::<Trailing>(&[0xc4, 0x01, 0x02, 0x03, 0x04]),
+        Err(RLPException::Field("Trailing", Box::new(RLPException::DecodingError("too many items")))),
+    );
+    assert!(matches!(
+        decode_to::<Trailing>(&[0xc2, 0x01, 0xc0]),
+        Err(RLPException::Field("Trailing.b", _)),
+    ));
+}
+
+#[test]
+fn impl_extended_enum() {
+    use super::{decode_to, encode, RLPException};
+
+    let plain = Envelope::Plain(Trailing { a: 1, b: None, c: None });
+    let encoded = encode(&plain).unwrap();
+    assert_eq!(encoded.deref(), &[0xc1, 0x01]);
+    assert_eq!(decode_to::<Envelope>(&encoded).unwrap(), plain);
+
+    let typed = Envelope::Typed(Trailing { a: 1, b: None, c: None });
+    let encoded = encode(&typed).unwrap();
+    assert_eq!(encoded.deref(), &[0x83, 0x05, 0xc1, 0x01]);
+    assert_eq!(decode_to::<Envelope>(&encoded).unwrap(), typed);
+
+    assert_eq!(
+        decode_to::<Envelope>(&[0x83, 0x06, 0xc1, 0x01]),
+        Err(RLPException::DecodingError("unknown Envelope type")),
+    );
+}
+
+#[test]
+fn malformed_corpus() {
+    use crate::ethereum::{
+        cancun::{blocks::{Block, Header}, fork_types::Address, transactions::Transaction},
+        ethereum_types::{bytes::Bytes32, numeric::U256},
+    };
+    use super::{decode_to_strict, decode_with, DecodeOptions, RLPException};
+
+    fn decode_as(ty: &str, data: &[u8], options: DecodeOptions) -> Result<(), RLPException> {
+        match ty {
+            "uint" => decode_with::<Uint>(data, options).map(drop),
+            "u256" => decode_with::<U256>(data, options).map(drop),
+            "bool" => decode_with::<bool>(data, options).map(drop),
+            "address" => decode_with::<Address>(data, options).map(drop),
+            "bytes32" => decode_with::<Bytes32>(data, options).map(drop),
+            "bytes" => decode_with::<Bytes>(data, options).map(drop),
+            "list" => decode_with::<Vec<Uint>>(data, options).map(drop),
+            "transaction" => decode_with::<Transaction>(data, options).map(drop),
+            "header" => decode_with::<Header>(data, options).map(drop),
+            "block" => decode_with::<Block>(data, options).map(drop),
+            _ => panic!("unknown type {ty}"),
+        }
+    }
+
+    const TYPES: [&str; 10] = ["uint", "u256", "bool", "address", "bytes32", "bytes", "list", "transaction", "header", "block"];
+
+    let corpus = include_str!("../../../../assets/rlp/malformed.txt");
+    let mut entries = 0;
+    for line in corpus.lines().filter(|l| !l.is_empty() && !l.starts_with('#')) {
+        let mut parts = line.splitn(3, ' ');
+        let (ty, hex, expected) = (parts.next().unwrap(), parts.next().unwrap(), parts.next().unwrap());
+        let data = (0..hex.len())
+            .step_by(2)
+            .map(|i| u8::from_str_radix(&hex[i..i + 2], 16).unwrap())
+            .collect::<Vec<u8>>();
+
+        let error = decode_as(ty, &data, DecodeOptions::STRICT).unwrap_err();
+        assert!(format!("{error:?}").contains(expected), "{line}: {error:?}");
+        for ty in TYPES {
+            for strict in [false, true] {
+                let _ = decode_as(ty, &data, DecodeOptions { strict, ..Default::default() });
+            }
+        }
+        entries += 1;
+    }
+    assert!(entries > 20);
+
+    // Strict mode only rejects what lenient mode would accept differently.
+    assert_eq!(decode_with::<Uint>(&[0x82, 0x00, 0xff], DecodeOptions::default()), Ok(0xff));
+    assert_eq!(decode_to_strict::<Uint>(&[0x81, 0xff]), Ok(0xff));
+
+    // Fixed size strings round trip in strict mode.
+    let mut hash = [0; 32];
+    hash[31] = 1;
+    let encoded = super::encode(&Bytes32(hash)).unwrap();
+    assert_eq!(encoded.len(), 33);
+    assert_eq!(decode_to_strict::<Bytes32>(&encoded), Ok(Bytes32(hash)));
+}
+
+#[test]
+fn decode_limits() {
+    use crate::ethereum::cancun::blocks::Block;
+    use super::{decode_to, decode_with, encode, DecodeOptions, RLPException, MAX_RLP_BLOCK_SIZE};
+
+    let list = encode(&vec![1 as Uint, 2, 3]).unwrap();
+    let bytes = encode(&Bytes(vec![0xaa; 100])).unwrap();
+    let limited = |max_list_len, max_bytes_len| DecodeOptions { max_list_len, max_bytes_len, ..Default::default() };
+
+    assert_eq!(decode_with::<Vec<Uint>>(&list, limited(Some(3), None)), Ok(vec![1, 2, 3]));
+    assert_eq!(
+        decode_with::<Vec<Uint>>(&list, limited(Some(2), None)),
+        Err(RLPException::LimitExceeded { what: "list", limit: 2 }),
+    );
+    assert_eq!(decode_with::<Bytes>(&bytes, limited(None, Some(100))).map(|b| b.len()), Ok(100));
+    assert_eq!(
+        decode_with::<Bytes>(&bytes, limited(None, Some(99))),
+        Err(RLPException::LimitExceeded { what: "byte string", limit: 99 }),
+    );
+    let options = DecodeOptions { max_size: Some(list.len() - 1), ..Default::default() };
+    assert_eq!(
+        decode_with::<Vec<Uint>>(&list, options),
+        Err(RLPException::LimitExceeded { what: "input", limit: list.len() - 1 }),
+    );
+
+    // Limits apply inside nested items and are lifted when the decode ends.
+    let block = Block { ommers: vec![Default::default(); 3], ..Default::default() };
+    let encoded = encode(&block).unwrap();
+    assert_eq!(decode_with::<Block>(&encoded, DecodeOptions::BLOCK).unwrap().ommers.len(), 3);
+    let error = decode_with::<Block>(&encoded, DecodeOptions { max_list_len: Some(2), ..DecodeOptions::BLOCK });
+    assert!(matches!(error, Err(RLPException::Field("Block.ommers", _))), "{error:?}");
+    assert_eq!(decode_to::<Vec<Uint>>(&list), Ok(vec![1, 2, 3]));
+
+    // A list claiming to hold more than the block size is rejected before
+    // anything is allocated for it.
+    let mut huge = vec![0xfb];
+    huge.extend_from_slice(&(MAX_RLP_BLOCK_SIZE as u32).to_be_bytes());
+    huge.resize(MAX_RLP_BLOCK_SIZE + 5, 0x80);
+    assert!(matches!(
+        decode_with::<Block>(&huge, DecodeOptions::BLOCK),
+        Err(RLPException::LimitExceeded { what: "input", .. }),
+    ));
+}
+
+/// Round trips of arbitrary headers, transactions and blocks, and decoding
+/// of mutated encodings, which must fail cleanly rather than panic.
+mod properties {
+    use proptest::{collection::vec, prelude::*};
+
+    use crate::ethereum::{
+        cancun::{
+            blocks::{Block, Header, Withdrawal},
+            fork_types::{Address, Bloom, Root, VersionedHash},
+            transactions::{
+                decode_raw_transaction, encode_raw_transaction, AccessListTransaction, BlobTransaction,
+                FeeMarketTransaction, LegacyTransaction, Transaction,
+            },
+        },
+        crypto::hash::Hash32,
+        ethereum_rlp::rlp::{decode_to, decode_to_strict, encode, Extended, Nullable},
+        ethereum_types::{
+            bytes::{Bytes, Bytes256, Bytes32, Bytes8},
+            numeric::U256,
+        },
+        prague::transactions::{Authorization, SetCodeTransaction},
+    };
+
+    fn u256() -> impl Strategy<Value = U256> {
+        prop_oneof![any::<u64>().prop_map(U256::from), any::<[u8; 32]>().prop_map(U256::from_be_bytes)]
+    }
+
+    fn address() -> impl Strategy<Value = Address> {
+        any::<[u8; 20]>().prop_map(Address::from_be_bytes)
+    }
+
+    fn bytes(max: usize) -> impl Strategy<Value = Bytes> {
+        vec(any::<u8>(), 0..max).prop_map(Bytes)
+    }
+
+    fn access_list() -> impl Strategy<Value = Vec<(Address, Vec<Bytes32>)>> {
+        vec((address(), vec(any::<[u8; 32]>().prop_map(Bytes32), 0..3)), 0..3)
+    }
+
+    prop_compose! {
+        fn header()(
+            (parent_hash, ommers_hash, coinbase, state_root, transactions_root, receipt_root) in
+                (any::<[u8; 32]>(), any::<[u8; 32]>(), address(), any::<[u8; 32]>(), any::<[u8; 32]>(), any::<[u8; 32]>()),
+            (bloom, difficulty, number, gas_limit, gas_used, timestamp) in
+                (vec(any::<u8>(), 256), any::<u128>(), any::<u128>(), any::<u128>(), any::<u128>(), u256()),
+            (extra_data, prev_randao, nonce) in (bytes(40), any::<[u8; 32]>(), any::<[u8; 8]>()),
+            (optional, base_fee_per_gas, withdrawals_root, blob_gas_used, excess_blob_gas, parent_beacon_block_root, requests_hash) in
+                (0..=6_usize, any::<u128>(), any::<[u8; 32]>(), any::<u64>(), any::<u64>(), any::<[u8; 32]>(), any::<[u8; 32]>()),
+        ) -> Header {
+            // The fields added by forks are trailing, so only a prefix of
+            // them can be present.
+            Header {
+                parent_hash: Hash32(parent_hash),
+                ommers_hash: Hash32(ommers_hash),
+                coinbase,
+                state_root: Root(state_root),
+                transactions_root: Root(transactions_root),
+                receipt_root: Root(receipt_root),
+                bloom: Bloom(Bytes256(bloom.try_into().unwrap())),
+                difficulty,
+                number,
+                gas_limit,
+                gas_used,
+                timestamp,
+                extra_data,
+                prev_randao: Bytes32(prev_randao),
+                nonce: Bytes8(nonce),
+                base_fee_per_gas: (optional > 0).then_some(base_fee_per_gas),
+                withdrawals_root: (optional > 1).then_some(Root(withdrawals_root)),
+                blob_gas_used: (optional > 2).then_some(blob_gas_used),
+                excess_blob_gas: (optional > 3).then_some(excess_blob_gas),
+                parent_beacon_block_root: (optional > 4).then_some(Root(parent_beacon_block_root)),
+                requests_hash: (optional > 5).then_some(Hash32(requests_hash)),
+            }
+        }
+    }
+
+    prop_compose! {
+        fn legacy()(
+            (nonce, gas_price, gas, to, value, data) in
+                (u256(), any::<u128>(), any::<u128>(), proptest::option::of(address()), u256(), bytes(100)),
+            (v, r, s) in (u256(), u256(), u256()),
+        ) -> LegacyTransaction {
+            LegacyTransaction { nonce, gas_price, gas, to: Nullable(to), value, data, v, r, s }
+        }
+    }
+
+    prop_compose! {
+        fn fee_market()(
+            (chain_id, nonce, max_priority_fee_per_gas, max_fee_per_gas, gas, to) in
+                (any::<u64>(), u256(), any::<u128>(), any::<u128>(), any::<u128>(), proptest::option::of(address())),
+            (value, data, access_list, y_parity, r, s) in (u256(), bytes(100), access_list(), u256(), u256(), u256()),
+        ) -> FeeMarketTransaction {
+            FeeMarketTransaction {
+                chain_id, nonce, max_priority_fee_per_gas, max_fee_per_gas, gas, to: Nullable(to), value, data, access_list,
+                y_parity, r, s,
+            }
+        }
+    }
+
+    fn authorization() -> impl Strategy<Value = Authorization> {
+        (u256(), address(), any::<u64>(), any::<u8>(), u256(), u256())
+            .prop_map(|(chain_id, address, nonce, y_parity, r, s)| Authorization { chain_id, address, nonce, y_parity, r, s })
+    }
+
+    /// Transactions of every type. The types after EIP-1559 share its fields,
+    /// so they are built from a fee market transaction.
+    fn transaction() -> impl Strategy<Value = Transaction> {
+        prop_oneof![
+            legacy().prop_map(Transaction::LegacyTransaction),
+            fee_market().prop_map(|tx| Transaction::AccessListTransaction(AccessListTransaction {
+                chain_id: tx.chain_id,
+                nonce: tx.nonce,
+                gas_price: tx.max_fee_per_gas,
+                gas: tx.gas,
+                to: tx.to,
+                value: tx.value,
+                data: tx.data,
+                access_list: tx.access_list,
+                y_parity: tx.y_parity,
+                r: tx.r,
+                s: tx.s,
+            })),
+            fee_market().prop_map(Transaction::FeeMarketTransaction),
+            (fee_market(), address(), u256(), vec(any::<[u8; 32]>(), 0..3)).prop_map(|(tx, to, max_fee_per_blob_gas, hashes)| {
+                Transaction::BlobTransaction(BlobTransaction {
+                    chain_id: tx.chain_id,
+                    nonce: tx.nonce,
+                    max_priority_fee_per_gas: tx.max_priority_fee_per_gas,
+                    max_fee_per_gas: tx.max_fee_per_gas,
+                    gas: tx.gas,
+                    to,
+                    value: tx.value,
+                    data: tx.data,
+                    access_list: tx.access_list,
+                    max_fee_per_blob_gas,
+                    blob_versioned_hashes: hashes.into_iter().map(VersionedHash).collect(),
+                    y_parity: tx.y_parity,
+                    r: tx.r,
+                    s: tx.s,
+                })
+            }),
+            (fee_market(), address(), vec(authorization(), 0..3)).prop_map(|(tx, to, authorizations)| {
+                Transaction::SetCodeTransaction(SetCodeTransaction {
+                    chain_id: tx.chain_id,
+                    nonce: tx.nonce,
+                    max_priority_fee_per_gas: tx.max_priority_fee_per_gas,
+                    max_fee_per_gas: tx.max_fee_per_gas,
+                    gas: tx.gas,
+                    to,
+                    value: tx.value,
+                    data: tx.data,
+                    access_list: tx.access_list,
+                    authorizations,
+                    y_parity: tx.y_parity,
+                    r: tx.r,
+                    s: tx.s,
+                })
+            }),
+        ]
+    }
+
+    fn block() -> impl Strategy<Value = Block> {
+        let withdrawal = (any::<u64>(), any::<u64>(), address(), u256())
+            .prop_map(|(index, validator_index, address, amount)| Withdrawal { index, validator_index, address, amount });
+        (header(), vec(transaction(), 0..4), vec(header(), 0..2), proptest::option::of(vec(withdrawal, 0..3)))
+            .prop_map(|(header, transactions, ommers, withdrawals)| Block { header, transactions, ommers, withdrawals })
+    }
+
+    /// A change to an encoding: a byte replaced, inserted or removed, or the
+    /// encoding cut short.
+    #[derive(Debug, Clone)]
+    enum Mutation {
+        Replace(usize, u8),
+        Insert(usize, u8),
+        Remove(usize),
+        Truncate(usize),
+    }
+
+    fn mutation() -> impl Strategy<Value = Mutation> {
+        prop_oneof![
+            (any::<usize>(), any::<u8>()).prop_map(|(i, b)| Mutation::Replace(i, b)),
+            (any::<usize>(), any::<u8>()).prop_map(|(i, b)| Mutation::Insert(i, b)),
+            any::<usize>().prop_map(Mutation::Remove),
+            any::<usize>().prop_map(Mutation::Truncate),
+        ]
+    }
+
+    fn mutate(mut data: Vec<u8>, mutations: &[Mutation]) -> Vec<u8> {
+        for mutation in mutations {
+            let len = data.len();
+            match *mutation {
+                Mutation::Replace(i, b) if len > 0 => data[i % len] = b,
+                Mutation::Insert(i, b) => data.insert(i % (len + 1), b),
+                Mutation::Remove(i) if len > 0 => drop(data.remove(i % len)),
+                Mutation::Truncate(i) => data.truncate(i % (len + 1)),
+                _ => {}
+            }
+        }
+        data
+    }
+
+    /// Encode `value`, decode it and check that it encodes the same and
+    /// prints the same, which covers every field.
+    fn round_trip<T: Extended + Default + std::fmt::Debug>(value: &T) -> Result<Vec<u8>, TestCaseError> {
+        let encoded = encode(value).unwrap().0;
+        let decoded: T = decode_to_strict(&encoded).map_err(|e| TestCaseError::fail(format!("{e:?}")))?;
+        prop_assert_eq!(&encode(&decoded).unwrap().0, &encoded);
+        prop_assert_eq!(format!("{decoded:?}"), format!("{value:?}"));
+        Ok(encoded)
+    }
+
+    proptest! {
+        #[test]
+        fn headers_round_trip(header in header()) {
+            round_trip(&header)?;
+        }
+
+        #[test]
+        fn transactions_round_trip(tx in transaction()) {
+            round_trip(&tx)?;
+            let raw = encode_raw_transaction(&tx).unwrap();
+            let decoded = decode_raw_transaction(&raw).unwrap();
+            prop_assert_eq!(encode_raw_transaction(&decoded).unwrap().0, raw.0);
+        }
+
+        #[test]
+        fn blocks_round_trip(block in block()) {
+            round_trip(&block)?;
+        }
+
+        #[test]
+        fn mutated_blocks_decode_cleanly(block in block(), mutations in vec(mutation(), 1..4)) {
+            let data = mutate(encode(&block).unwrap().0, &mutations);
+            let _ = decode_to::<Block>(&data);
+            // Strict mode accepts only the canonical encoding.
+            if let Ok(block) = decode_to_strict::<Block>(&data) {
+                prop_assert_eq!(encode(&block).unwrap().0, data.clone());
+            }
+            let _ = decode_to::<Header>(&data);
+            let _ = decode_to::<Vec<Transaction>>(&data);
+        }
+
+        #[test]
+        fn mutated_transactions_decode_cleanly(tx in transaction(), mutations in vec(mutation(), 1..4)) {
+            let data = mutate(encode_raw_transaction(&tx).unwrap().0, &mutations);
+            let _ = decode_raw_transaction(&data);
+            let _ = decode_to::<Transaction>(&data);
+        }
+
+        #[test]
+        fn arbitrary_bytes_decode_cleanly(data in vec(any::<u8>(), 0..300)) {
+            let _ = decode_to::<Block>(&data);
+            let _ = decode_to::<Header>(&data);
+            let _ = decode_to::<Transaction>(&data);
+            let _ = decode_to::<Vec<Vec<Bytes>>>(&data);
+        }
+    }
+}
@@ -0,0 +1,245 @@
+//! Borrowing RLP reader.
+//!
+//! `rlp::decode_to` copies every item into an owned value. `RlpStream` walks
+//! an encoding in place and yields slices of the input instead, which is
+//! useful for large structures such as block bodies where only some of the
+//! items are needed, or where items are to be hashed or forwarded as they
+//! are.
+//!
+//! Every length prefix is checked against the remaining input and must be in
+//! canonical form.
+
+use super::exceptions::RLPException;
+
+/// One item of an RLP encoding.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum RlpItem<'a> {
+    /// The payload of a byte string.
+    Bytes(&'a [u8]),
+    /// The items of a list.
+    List(RlpStream<'a>),
+}
+
+/// A sequence of RLP items, borrowed from the input.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct RlpStream<'a> {
+    data: &'a [u8],
+}
+
+/// Position of an item's payload in the input.
+struct ItemHeader {
+    is_list: bool,
+    payload_start: usize,
+    payload_end: usize,
+}
+
+impl<'a> RlpStream<'a> {
+    /// A stream over the concatenated items in `data`.
+    pub fn new(data: &'a [u8]) -> Self {
+        Self { data }
+    }
+
+    /// A stream over the items of the single list encoded in `data`.
+    pub fn list(data: &'a [u8]) -> Result<Self, RLPException> {
+        let mut outer = Self::new(data);
+        let list = outer.next_list()?;
+        outer.finish()?;
+        Ok(list)
+    }
+
+    /// True if there are no more items.
+    pub fn is_empty(&self) -> bool {
+        self.data.is_empty()
+    }
+
+    /// The encoding of the items not yet read.
+    pub fn remaining(&self) -> &'a [u8] {
+        self.data
+    }
+
+    /// Read the next item, or `None` at the end of the stream.
+    pub fn next_item(&mut self) -> Result<Option<RlpItem<'a>>, RLPException> {
+        if self.data.is_empty() {
+            return Ok(None);
+        }
+        let header = self.header()?;
+        let payload = &self.data[header.payload_start..header.payload_end];
+        self.data = &self.data[header.payload_end..];
+        Ok(Some(if header.is_list {
+            RlpItem::List(RlpStream::new(payload))
+        } else {
+            RlpItem::Bytes(payload)
+        }))
+    }
+
+    /// Read the complete encoding of the next item, prefix included.
+    pub fn next_raw(&mut self) -> Result<&'a [u8], RLPException> {
+        if self.data.is_empty() {
+            return Err(RLPException::DecodingError("unexpected end of list"));
+        }
+        let header = self.header()?;
+        let raw = &self.data[..header.payload_end];
+        self.data = &self.data[header.payload_end..];
+        Ok(raw)
+    }
+
+    /// Read the next item, which must be a byte string.
+    pub fn next_bytes(&mut self) -> Result<&'a [u8], RLPException> {
+        match self.next_item()? {
+            Some(RlpItem::Bytes(bytes)) => Ok(bytes),
+            Some(RlpItem::List(_)) => Err(RLPException::DecodingError("expected bytes, got a sequence")),
+            None => Err(RLPException::DecodingError("unexpected end of list")),
+        }
+    }
+
+    /// Read the next item, which must be a list.
+    pub fn next_list(&mut self) -> Result<RlpStream<'a>, RLPException> {
+        match self.next_item()? {
+            Some(RlpItem::List(list)) => Ok(list),
+            Some(RlpItem::Bytes(_)) => Err(RLPException::DecodingError("expected sequence")),
+            None => Err(RLPException::DecodingError("unexpected end of list")),
+        }
+    }
+
+    /// Read the next item as a big endian integer of at most 8 bytes.
+    pub fn next_u64(&mut self) -> Result<u64, RLPException> {
+        let bytes = self.next_bytes()?;
+        if bytes.len() > 8 {
+            return Err(RLPException::DecodingError("integer too large"));
+        }
+        if bytes.first() == Some(&0) {
+            return Err(RLPException::NonCanonicalInteger);
+        }
+        Ok(bytes.iter().fold(0, |acc, b| acc << 8 | *b as u64))
+    }
+
+    /// Skip the next item.
+    pub fn skip(&mut self) -> Result<(), RLPException> {
+        self.next_raw().map(|_| ())
+    }
+
+    /// Check that all items have been read.
+    pub fn finish(self) -> Result<(), RLPException> {
+        if self.data.is_empty() {
+            Ok(())
+        } else {
+            Err(RLPException::TrailingBytes)
+        }
+    }
+
+    /// Decode and validate the prefix of the next item.
+    fn header(&self) -> Result<ItemHeader, RLPException> {
+        let data = self.data;
+        let prefix = data[0];
+        let (is_list, payload_start, len) = match prefix {
+            0x00..=0x7f => return Ok(ItemHeader { is_list: false, payload_start: 0, payload_end: 1 }),
+            0x80..=0xb7 => (false, 1, (prefix - 0x80) as usize),
+            0xb8..=0xbf => {
+                let len_of_len = (prefix - 0xb7) as usize;
+                (false, 1 + len_of_len, long_length(data, len_of_len)?)
+            }
+            0xc0..=0xf7 => (true, 1, (prefix - 0xc0) as usize),
+            0xf8..=0xff => {
+                let len_of_len = (prefix - 0xf7) as usize;
+                (true, 1 + len_of_len, long_length(data, len_of_len)?)
+            }
+        };
+        let payload_end = payload_start
+            .checked_add(len)
+            .filter(|end| *end <= data.len())
+            .ok_or(RLPException::Truncated)?;
+        if !is_list && len == 1 && data[1] < 0x80 {
+            return Err(RLPException::NonCanonicalLength);
+        }
+        Ok(ItemHeader { is_list, payload_start, payload_end })
+    }
+}
+
+/// Read the `len_of_len` byte length which follows the prefix of a long item.
+fn long_length(data: &[u8], len_of_len: usize) -> Result<usize, RLPException> {
+    let Some(bytes) = data.get(1..1 + len_of_len) else {
+        return Err(RLPException::Truncated);
+    };
+    if bytes[0] == 0 {
+        return Err(RLPException::NonCanonicalLength);
+    }
+    if len_of_len > size_of::<usize>() {
+        return Err(RLPException::Truncated);
+    }
+    let len = bytes.iter().fold(0, |acc, b| acc << 8 | *b as usize);
+    if len < 0x38 {
+        return Err(RLPException::NonCanonicalLength);
+    }
+    Ok(len)
+}
+
+impl<'a> Iterator for RlpStream<'a> {
+    type Item = Result<RlpItem<'a>, RLPException>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        match self.next_item() {
+            Ok(item) => item.map(Ok),
+            Err(e) => {
+                // Stop after an error.
+                self.data = &[];
+                Some(Err(e))
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::ethereum::{
+        cancun::blocks::Block,
+        ethereum_rlp::{exceptions::RLPException, rlp},
+    };
+
+    use super::{RlpItem, RlpStream};
+
+    #[test]
+    fn read_items() {
+        // [ "cat", [ "dog" ], 1024, "" ]
+        let data = [0xcd, 0x83, b'c', b'a', b't', 0xc4, 0x83, b'd', b'o', b'g', 0x82, 0x04, 0x00, 0x80];
+        let mut list = RlpStream::list(&data).unwrap();
+        assert_eq!(list.next_bytes().unwrap(), b"cat");
+        let mut inner = list.next_list().unwrap();
+        assert_eq!(inner.next_bytes().unwrap(), b"dog");
+        inner.finish().unwrap();
+        assert_eq!(list.next_u64().unwrap(), 1024);
+        assert_eq!(list.next_item().unwrap(), Some(RlpItem::Bytes(b"")));
+        assert_eq!(list.next_item().unwrap(), None);
+    }
+
+    #[test]
+    fn reject_bad_prefixes() {
+        let err = |data: &[u8]| RlpStream::new(data).next_item().unwrap_err();
+        // Truncated payload.
+        assert_eq!(err(&[0x83, b'c', b'a']), RLPException::Truncated);
+        // Single byte below 0x80 must be encoded as itself.
+        assert_eq!(err(&[0x81, 0x05]), RLPException::NonCanonicalLength);
+        // Long form for a short payload.
+        assert_eq!(err(&[0xb8, 0x01, 0xff]), RLPException::NonCanonicalLength);
+        // Leading zero in the length.
+        assert_eq!(err(&[0xb9, 0x00, 0x38]), RLPException::NonCanonicalLength);
+        // Length which overflows the input.
+        assert_eq!(err(&[0xbf, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff]), RLPException::Truncated);
+        assert_eq!(
+            RlpStream::list(&[0xc1, 0x01, 0x02]).unwrap_err(),
+            RLPException::TrailingBytes,
+        );
+    }
+
+    #[test]
+    fn block_body_views() {
+        let block = Block { withdrawals: Some(Vec::new()), ..Default::default() };
+        let encoded = rlp::encode(&block).unwrap();
+        let mut fields = RlpStream::list(&encoded).unwrap();
+        let header = fields.next_raw().unwrap();
+        assert_eq!(header, &rlp::encode(&block.header).unwrap()[..]);
+        assert!(fields.next_list().unwrap().is_empty());
+        assert!(fields.next_list().unwrap().is_empty());
+        assert!(fields.next_list().unwrap().is_empty());
+        fields.finish().unwrap();
+    }
+}
@@ -1,9 +1,53 @@
 use std::ops::DerefMut;
 
-use crate::{ethereum::{ethereum_rlp::{exceptions::RLPException, rlp::{decode_to_bytes, encode_bytes, Extended}}, utils::hexadecimal::{hex_to_bytes, hex_to_slice}}, json::{Decoder, JsonDecode, JsonError}};
+use crate::{ethereum::{ethereum_rlp::{exceptions::RLPException, rlp::{decode_to_fixed_bytes, encode_bytes, Extended}, stream::RlpStream}, exceptions::Exception, utils::hexadecimal::{bytes_to_hex, hex_to_bytes, hex_to_slice}}, json::{Decoder, Encoder, JsonDecode, JsonEncode, JsonError}};
 
 use super::numeric::fmt_hex;
 
+/// `to_hex`, `Display` and `FromStr` for a byte string of fixed size held
+/// in field 0, as "0x" and two hex digits for each byte. `FromStr` takes the
+/// prefix as optional but the number of digits as exact.
+///
+/// ```
+/// use ejit_evm::impl_hex_str;
+///
+/// #[derive(Debug, PartialEq)]
+/// struct Selector([u8; 4]);
+/// impl_hex_str!(Selector);
+///
+/// let selector: Selector = "0xa9059cbb".parse()?;
+/// assert_eq!(selector, Selector([0xa9, 0x05, 0x9c, 0xbb]));
+/// assert_eq!(selector.to_string(), "0xa9059cbb");
+/// assert!("a9059c".parse::<Selector>().is_err());
+/// # Ok::<(), ejit_evm::ethereum::exceptions::Exception>(())
+/// ```
+#[macro_export]
+macro_rules! impl_hex_str {
+    ($($name:ident),*) => {$(
+        impl $name {
+            /// The bytes as a "0x" prefixed hex string, leading zeros
+            /// included.
+            pub fn to_hex(&self) -> String {
+                $crate::ethereum::utils::hexadecimal::bytes_to_hex(&self.0)
+            }
+        }
+
+        impl std::fmt::Display for $name {
+            fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+                f.write_str(&self.to_hex())
+            }
+        }
+
+        impl std::str::FromStr for $name {
+            type Err = $crate::ethereum::exceptions::Exception;
+
+            fn from_str(s: &str) -> Result<Self, Self::Err> {
+                Ok(Self($crate::ethereum::utils::hexadecimal::hex_to_array(s)?))
+            }
+        }
+    )*};
+}
+
 #[derive(Debug, Clone, Copy, Default, PartialEq, PartialOrd, Eq, Ord)]
 pub struct Bytes0(pub [u8; 0]);
 
@@ -35,6 +79,22 @@ impl<'de> JsonDecode<'de> for Bytes8 {
     }
 }
 
+impl JsonEncode for Bytes8 {
+    fn encode_json(&self, encoder: &mut Encoder) {
+        encoder.hex(&self.0);
+    }
+}
+
+
+impl Extended for Bytes4 {
+    fn encode<'a, 'b>(&self, buffer: &'a mut Bytes) -> Result<(), RLPException> {
+        Ok(encode_bytes(buffer, &self.0))
+    }
+
+    fn decode<'a, 'b>(&mut self, buffer: &'a mut &'b [u8]) -> Result<(), RLPException> {
+        decode_to_fixed_bytes(buffer, &mut self.0)
+    }
+}
 
 impl Extended for Bytes8 {
     fn encode<'a, 'b>(&self, buffer: &'a mut Bytes) -> Result<(), RLPException> {
@@ -42,7 +102,7 @@ impl Extended for Bytes8 {
     }
 
     fn decode<'a, 'b>(&mut self, buffer: &'a mut &'b [u8]) -> Result<(), RLPException> {
-        decode_to_bytes(buffer, &mut self.0)
+        decode_to_fixed_bytes(buffer, &mut self.0)
     }
 }
 
@@ -70,9 +130,38 @@ impl<'de> JsonDecode<'de> for Bytes32 {
     }
 }
 
+impl JsonEncode for Bytes32 {
+    fn encode_json(&self, encoder: &mut Encoder) {
+        encoder.hex(&self.0);
+    }
+}
+
 #[derive(Debug, Clone, Copy, PartialEq, PartialOrd, Eq, Ord)]
 pub struct Bytes48(pub [u8; 48]);
 
+impl Default for Bytes48 {
+    fn default() -> Self {
+        Self([0; 48])
+    }
+}
+
+impl<'de> JsonDecode<'de> for Bytes48 {
+    fn decode_json(&mut self, buffer: &mut Decoder<'de>) -> Result<(), crate::json::JsonError> {
+        let mut s = "";
+        s.decode_json(buffer)?;
+        let mut bytes = [0; 48];
+        hex_to_slice(&mut bytes, s).map_err(|_| JsonError::ExpectedHexString)?;
+        *self = Self(bytes);
+        Ok(())
+    }
+}
+
+impl JsonEncode for Bytes48 {
+    fn encode_json(&self, encoder: &mut Encoder) {
+        encoder.hex(&self.0);
+    }
+}
+
 #[derive(Debug, Clone, Copy, PartialEq, PartialOrd, Eq, Ord)]
 pub struct Bytes64(pub [u8; 64]);
 
@@ -107,7 +196,7 @@ impl Extended for Bytes256 {
     }
 
     fn decode<'a, 'b>(&mut self, buffer: &'a mut &'b [u8]) -> Result<(), RLPException> {
-        decode_to_bytes(buffer, &mut self.0)
+        decode_to_fixed_bytes(buffer, &mut self.0)
     }
 }
 
@@ -134,6 +223,24 @@ impl std::fmt::Debug for Bytes {
     }
 }
 
+impl_hex_str!(Bytes1, Bytes4, Bytes8, Bytes20, Bytes32, Bytes48, Bytes64, Bytes96, Bytes256);
+
+impl std::fmt::Display for Bytes {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(&self.to_hex())
+    }
+}
+
+impl std::str::FromStr for Bytes {
+    type Err = Exception;
+
+    /// Any number of hex digits, an odd number being read as having a
+    /// leading zero.
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        hex_to_bytes(s)
+    }
+}
+
 impl std::ops::Deref for Bytes {
     type Target = [u8];
 
@@ -166,6 +273,11 @@ impl Bytes {
     pub fn into_verbatim(self) -> Verbatim {
         Verbatim(self.0)
     }
+
+    /// The bytes as a "0x" prefixed hex string.
+    pub fn to_hex(&self) -> String {
+        bytes_to_hex(&self.0)
+    }
 }
 
 impl<'de> JsonDecode<'de> for Bytes {
@@ -177,7 +289,13 @@ impl<'de> JsonDecode<'de> for Bytes {
     }
 }
 
-#[derive(Clone)]
+impl JsonEncode for Bytes {
+    fn encode_json(&self, encoder: &mut Encoder) {
+        encoder.hex(&self.0);
+    }
+}
+
+#[derive(Clone, Default)]
 /// Verbatim RLP encoding.
 pub struct Verbatim(pub Vec<u8>);
 
@@ -187,8 +305,12 @@ impl Extended for Verbatim {
         Ok(())
     }
 
+    /// Take the complete encoding of the next item.
     fn decode<'a, 'b>(&mut self, buffer: &'a mut &'b [u8]) -> Result<(), RLPException> {
-        todo!();
+        let mut stream = RlpStream::new(buffer);
+        self.0 = stream.next_raw()?.to_vec();
+        *buffer = stream.remaining();
+        Ok(())
     }
 }
 
@@ -1,6 +1,6 @@
-use std::{ops::{Add, Div, Mul, Sub}, process::Output};
+use std::{cmp::Ordering, ops::{Add, BitAnd, BitOr, BitXor, Div, Mul, Not, Sub}, process::Output};
 
-use crate::{ethereum::{exceptions::Exception, utils::hexadecimal::{self, hex_to_slice}}, json::{skip_whitespace, Decoder, JsonDecode, JsonError}};
+use crate::{ethereum::{exceptions::Exception, utils::hexadecimal::{self, hex_to_slice}}, json::{parse_number, skip_whitespace, Decoder, Encoder, JsonDecode, JsonEncode, JsonError}};
 
 pub type Int = i128;
 pub type Uint = u128;
@@ -20,6 +20,8 @@ impl std::fmt::Debug for U256 {
 
 impl U256 {
     pub const ZERO : U256 = U256([0; 4]);
+    pub const ONE : U256 = U256([0, 0, 0, 1]);
+    pub const MAX : U256 = U256([u64::MAX; 4]);
 
     pub fn from_be_bytes(value: [u8; 32]) -> Self {
         Self::from_limbs([
@@ -30,6 +32,13 @@ impl U256 {
         ])
     }
 
+    /// Big endian bytes, left padded with zeros. At most 32 bytes.
+    pub fn from_be_slice(value: &[u8]) -> Self {
+        let mut bytes = [0; 32];
+        bytes[32 - value.len()..].copy_from_slice(value);
+        Self::from_be_bytes(bytes)
+    }
+
     pub const fn to_limbs(&self) -> [u64; 4] {
         self.0
     }
@@ -145,24 +154,190 @@ impl U256 {
     }
 
     pub fn overflowing_div(self, rhs: Self) -> (Self, bool) {
+        (self.div_rem(rhs).0, rhs.is_zero())
+    }
+
+    /// Quotient and remainder. Both are zero when `rhs` is zero, as for the
+    /// `DIV` and `MOD` opcodes.
+    pub fn div_rem(self, rhs: Self) -> (Self, Self) {
         // TODO: use the algoritm from the Knuth book
         // and make an exception for power of two divides.
         if rhs.is_zero() {
-            return (Self::ZERO, true)
+            return (Self::ZERO, Self::ZERO);
+        }
+        if let (Ok(a), Ok(b)) = (self.to_uint(), rhs.to_uint()) {
+            return (Self::from_uint(a / b), Self::from_uint(a % b));
         }
 
         let lz = self.leading_zeros();
         let mut q = Self::ZERO;
         let mut r = Self::ZERO;
         for i in (0..256-lz).rev() {
+            let carry = r.bit(255);
             r = r.shl(1);
             if self.bit(i) { r.set_bit(0) }
-            if r >= rhs {
+            if carry || r >= rhs {
                 r = r - rhs;
                 q.set_bit(i);
             }
         }
-        (q, false)
+        (q, r)
+    }
+
+    pub fn overflowing_add(self, rhs: Self) -> (Self, bool) {
+        let (a, b) = (self.to_limbs(), rhs.to_limbs());
+        let mut res = [0; 4];
+        let mut carry = false;
+        for i in (0..4).rev() {
+            let (sum, c1) = a[i].overflowing_add(b[i]);
+            let (sum, c2) = sum.overflowing_add(carry as u64);
+            res[i] = sum;
+            carry = c1 || c2;
+        }
+        (Self::from_limbs(res), carry)
+    }
+
+    /// The full 512 bit product, most significant limb first.
+    pub fn widening_mul(self, rhs: Self) -> [u64; 8] {
+        let mut a = self.to_limbs();
+        let mut b = rhs.to_limbs();
+        a.reverse();
+        b.reverse();
+        let mut res = [0_u64; 8];
+        for i in 0..4 {
+            let mut carry = 0_u128;
+            for j in 0..4 {
+                let t = a[i] as u128 * b[j] as u128 + res[i+j] as u128 + carry;
+                res[i+j] = t as u64;
+                carry = t >> 64;
+            }
+            res[i+4] = carry as u64;
+        }
+        res.reverse();
+        res
+    }
+
+    /// `(self + rhs) % modulus` without intermediate overflow, zero if
+    /// `modulus` is zero.
+    pub fn add_mod(self, rhs: Self, modulus: Self) -> Self {
+        if modulus.is_zero() {
+            return Self::ZERO;
+        }
+        let (sum, carry) = self.overflowing_add(rhs);
+        let [a, b, c, d] = sum.to_limbs();
+        rem_wide(&[carry as u64, a, b, c, d], modulus)
+    }
+
+    /// `(self * rhs) % modulus` without intermediate overflow, zero if
+    /// `modulus` is zero.
+    pub fn mul_mod(self, rhs: Self, modulus: Self) -> Self {
+        if modulus.is_zero() {
+            return Self::ZERO;
+        }
+        rem_wide(&self.widening_mul(rhs), modulus)
+    }
+
+    /// `self` to the power of `exponent`, modulo 2^256.
+    pub fn pow(self, exponent: Self) -> Self {
+        let mut res = Self::ONE;
+        for i in (0..256-exponent.leading_zeros()).rev() {
+            res = res * res;
+            if exponent.bit(i) {
+                res = res * self;
+            }
+        }
+        res
+    }
+
+    /// `self` to the power of `exponent`, modulo `modulus`.
+    pub fn pow_mod(self, exponent: Self, modulus: Self) -> Self {
+        let mut res = Self::ONE.div_rem(modulus).1;
+        for i in (0..256-exponent.leading_zeros()).rev() {
+            res = res.mul_mod(res, modulus);
+            if exponent.bit(i) {
+                res = res.mul_mod(self, modulus);
+            }
+        }
+        res
+    }
+
+    /// Number of significant bits.
+    pub fn bits(&self) -> u32 {
+        256 - self.leading_zeros()
+    }
+
+    /// The least significant 64 bits.
+    pub fn low_u64(&self) -> u64 {
+        self.0[3]
+    }
+
+    /// True if the value is negative in two's complement.
+    pub fn is_negative(&self) -> bool {
+        self.bit(255)
+    }
+
+    pub fn wrapping_neg(self) -> Self {
+        !self + Self::ONE
+    }
+
+    fn abs(self) -> Self {
+        if self.is_negative() { self.wrapping_neg() } else { self }
+    }
+
+    /// Signed division rounding towards zero, zero if `rhs` is zero.
+    ///
+    /// `-2^255 / -1` wraps to `-2^255`.
+    pub fn signed_div(self, rhs: Self) -> Self {
+        let (q, _) = self.abs().div_rem(rhs.abs());
+        if self.is_negative() != rhs.is_negative() { q.wrapping_neg() } else { q }
+    }
+
+    /// Signed remainder with the sign of `self`, zero if `rhs` is zero.
+    pub fn signed_rem(self, rhs: Self) -> Self {
+        let (_, r) = self.abs().div_rem(rhs.abs());
+        if self.is_negative() { r.wrapping_neg() } else { r }
+    }
+
+    /// Compare as two's complement signed integers.
+    pub fn signed_cmp(&self, rhs: &Self) -> Ordering {
+        match (self.is_negative(), rhs.is_negative()) {
+            (true, false) => Ordering::Less,
+            (false, true) => Ordering::Greater,
+            _ => self.cmp(rhs),
+        }
+    }
+
+    /// Extend the sign bit of byte `byte_num` (counting from the least
+    /// significant byte) to the full width.
+    pub fn sign_extend(self, byte_num: Self) -> Self {
+        if byte_num >= Self::from(31_u32) {
+            return self;
+        }
+        let sign_bit = byte_num.low_u64() as u32 * 8 + 7;
+        let mask = Self::MAX.shl(sign_bit + 1);
+        if self.bit(sign_bit) { self | mask } else { self & !mask }
+    }
+
+    /// Byte `index` counting from the most significant byte, zero if out of
+    /// range.
+    pub fn byte(&self, index: Self) -> Self {
+        if index >= Self::from(32_u32) {
+            return Self::ZERO;
+        }
+        Self::from(self.to_be_bytes()[index.low_u64() as usize] as u32)
+    }
+
+    /// Arithmetic shift right.
+    pub fn sar(self, shift: u32) -> Self {
+        if !self.is_negative() {
+            self.shr(shift)
+        } else if shift >= 256 {
+            Self::MAX
+        } else if shift == 0 {
+            self
+        } else {
+            self.shr(shift) | Self::MAX.shl(256 - shift)
+        }
     }
 
     pub fn bit(&self, i: u32) -> bool {
@@ -203,15 +378,80 @@ impl From<u64> for U256 {
     }
 }
 
+/// Largest power of ten in a limb, the base the decimal digits are taken in.
+const TEN_POW_19: u64 = 10_000_000_000_000_000_000;
+
+impl U256 {
+    /// The number as a "0x" prefixed hex string without leading zeros, as
+    /// quantities are written in JSON-RPC.
+    pub fn to_hex(&self) -> String {
+        format!("{self:?}")
+    }
+}
+
+impl std::fmt::Display for U256 {
+    /// Decimal.
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let mut chunks = Vec::new();
+        let mut rest = *self;
+        loop {
+            let (q, r) = rest.div_rem(U256::from(TEN_POW_19));
+            chunks.push(r.low_u64());
+            rest = q;
+            if rest.is_zero() {
+                break;
+            }
+        }
+        let mut chunks = chunks.iter().rev();
+        write!(f, "{}", chunks.next().unwrap())?;
+        for chunk in chunks {
+            write!(f, "{chunk:019}")?;
+        }
+        Ok(())
+    }
+}
+
+impl std::str::FromStr for U256 {
+    type Err = Exception;
+
+    /// A hex number if prefixed with "0x", decimal otherwise.
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        if s.starts_with("0x") {
+            return hexadecimal::hex_to_u256(s);
+        }
+        if s.is_empty() || !s.bytes().all(|b| b.is_ascii_digit()) {
+            return Err(Exception::EthereumException("bad decimal digit"));
+        }
+        let mut value = U256::ZERO;
+        for chunk in s.as_bytes().chunks(19) {
+            let digits = std::str::from_utf8(chunk).unwrap();
+            let product = value.widening_mul(U256::from(10_u64.pow(chunk.len() as u32)));
+            let (sum, carry) = U256::from_limbs(product[4..].try_into().unwrap())
+                .overflowing_add(U256::from(digits.parse::<u64>().unwrap()));
+            if carry || product[..4].iter().any(|limb| *limb != 0) {
+                return Err(Exception::NumericOverflow);
+            }
+            value = sum;
+        }
+        Ok(value)
+    }
+}
+
 impl<'de> JsonDecode<'de> for U256 {
     fn decode_json(&mut self, buffer: &mut Decoder<'de>) -> Result<(), JsonError> {
-        let mut s : &str = "";
-        s.decode_json(buffer)?;
-        *self = hexadecimal::hex_to_u256(s).map_err(|_| JsonError::ExpectedHexString)?;
+        let mut bytes = [0; 32];
+        decode_integer(buffer, &mut bytes)?;
+        *self = U256::from_be_bytes(bytes);
         Ok(())
     }
 }
 
+impl JsonEncode for U256 {
+    fn encode_json(&self, encoder: &mut Encoder) {
+        encoder.quantity(&self.to_be_bytes());
+    }
+}
+
 impl Add<U256> for U256 {
     type Output = U256;
 
@@ -258,40 +498,54 @@ impl Mul<U256> for U256 {
     type Output = U256;
 
     fn mul(self, rhs: U256) -> Self::Output {
-        let ca = self.to_limbs();
-        let cb = rhs.to_limbs();
-        let sum0 =
-            ca[3] as u128 * cb[3] as u128
-        ;
-
-        let sum1 =
-            ca[2] as u128 * cb[3] as u128 +
-            ca[3] as u128 * cb[2] as u128 +
-            sum0 >> 64
-        ;
-
-        let sum2 =
-            ca[1] as u128 * cb[3] as u128 +
-            ca[2] as u128 * cb[2] as u128 +
-            ca[3] as u128 * cb[1] as u128 +
-            sum1 >> 64
-        ;
-
-        let sum3 =
-            ca[0] as u128 * cb[3] as u128 +
-            ca[1] as u128 * cb[2] as u128 +
-            ca[2] as u128 * cb[1] as u128 +
-            ca[3] as u128 * cb[0] as u128 +
-            sum2 >> 64
-        ;
-
-        fn t(x: u128) -> u64 {
-            (x & (u64::MAX as u128)) as u64
-        }
-        Self::from_limbs([t(sum3), t(sum2), t(sum1), t(sum0)])
+        let [_, _, _, _, a, b, c, d] = self.widening_mul(rhs);
+        Self::from_limbs([a, b, c, d])
     }
 }
 
+impl Not for U256 {
+    type Output = U256;
+
+    fn not(self) -> Self::Output {
+        let [a, b, c, d] = self.to_limbs();
+        Self::from_limbs([!a, !b, !c, !d])
+    }
+}
+
+macro_rules! bitwise_op {
+    ($trait: ident, $fn: ident, $op: tt) => {
+        impl $trait<U256> for U256 {
+            type Output = U256;
+
+            fn $fn(self, rhs: U256) -> Self::Output {
+                let (a, b) = (self.to_limbs(), rhs.to_limbs());
+                Self::from_limbs([a[0] $op b[0], a[1] $op b[1], a[2] $op b[2], a[3] $op b[3]])
+            }
+        }
+    };
+}
+
+bitwise_op!(BitAnd, bitand, &);
+bitwise_op!(BitOr, bitor, |);
+bitwise_op!(BitXor, bitxor, ^);
+
+/// Remainder of the big endian number in `limbs` divided by `modulus`.
+fn rem_wide(limbs: &[u64], modulus: U256) -> U256 {
+    let mut r = U256::ZERO;
+    let first = limbs.iter().position(|l| *l != 0).unwrap_or(limbs.len());
+    for limb in &limbs[first..] {
+        for i in (0..64).rev() {
+            let carry = r.bit(255);
+            r = r.shl(1);
+            if limb >> i & 1 != 0 { r.set_bit(0) }
+            if carry || r >= modulus {
+                r = r - modulus;
+            }
+        }
+    }
+    r
+}
+
 pub fn fmt_hex<'a>(buf: &'a mut [u8], bytes: &[u8]) -> &'a str {
     assert!(buf.len() == bytes.len()*2+2);
     let lz = bytes.iter().position(|b| *b != 0).unwrap_or(bytes.len());
@@ -310,38 +564,125 @@ pub fn fmt_hex<'a>(buf: &'a mut [u8], bytes: &[u8]) -> &'a str {
     }
 }
 
+/// Multiply the big endian integer `dest` by `mul` and add `add`, returning
+/// true on overflow.
+fn mul_add(dest: &mut [u8], mul: u8, add: u8) -> bool {
+    let mut carry = add as u16;
+    for b in dest.iter_mut().rev() {
+        let v = *b as u16 * mul as u16 + carry;
+        *b = v as u8;
+        carry = v >> 8;
+    }
+    carry != 0
+}
+
+/// Parse a JSON number into the big endian integer `dest`.
+///
+/// A fraction and an exponent are accepted as long as the value is a whole
+/// number, so `1e18` and `1.5e3` are, but `1.5` and `-1` are not.
+pub fn decimal_to_slice(dest: &mut [u8], s: &[u8]) -> Result<(), JsonError> {
+    dest.fill(0);
+    if s.first() == Some(&b'-') {
+        return Err(JsonError::InvalidValue("negative integer"));
+    }
+    let digits = |s: &[u8]| s.iter().position(|b| !b.is_ascii_digit()).unwrap_or(s.len());
+    let (int, rest) = s.split_at(digits(s));
+    let (frac, rest) = match rest.split_first() {
+        Some((b'.', rest)) => rest.split_at(digits(rest)),
+        _ => (&rest[..0], rest),
+    };
+    if int.is_empty() || (frac.is_empty() && s.contains(&b'.')) {
+        return Err(JsonError::BadNumber);
+    }
+    let exp = match rest.split_first() {
+        Some((b'e' | b'E', rest)) => {
+            let (negative, rest) = match rest.split_first() {
+                Some((b'-', rest)) => (true, rest),
+                Some((b'+', rest)) => (false, rest),
+                _ => (false, rest),
+            };
+            if rest.is_empty() || digits(rest) != rest.len() {
+                return Err(JsonError::BadNumber);
+            }
+            // Exponents this large only leave zero or an overflow.
+            let exp = rest.iter().fold(0_i64, |exp, b| (exp * 10 + (b - b'0') as i64).min(1 << 20));
+            if negative { -exp } else { exp }
+        }
+        None => 0,
+        Some(_) => return Err(JsonError::BadNumber),
+    };
+
+    let mantissa : Vec<u8> = int.iter().chain(frac).copied().skip_while(|b| *b == b'0').collect();
+    let scale = exp - frac.len() as i64;
+    let mantissa = if scale < 0 {
+        let cut = mantissa.len().saturating_sub(scale.unsigned_abs() as usize);
+        if mantissa[cut..].iter().any(|b| *b != b'0') {
+            return Err(JsonError::InvalidValue("number has a fractional part"));
+        }
+        &mantissa[..cut]
+    } else {
+        &mantissa[..]
+    };
+    if mantissa.is_empty() {
+        return Ok(());
+    }
+    // Each byte holds more than two decimal digits.
+    let zeros = scale.max(0) as usize;
+    if mantissa.len() + zeros > dest.len() * 3 {
+        return Err(JsonError::NumericOverflow);
+    }
+    for b in mantissa.iter().copied().chain(std::iter::repeat_n(b'0', zeros)) {
+        if mul_add(dest, 10, b - b'0') {
+            return Err(JsonError::NumericOverflow);
+        }
+    }
+    Ok(())
+}
+
+/// Decode an unsigned integer into the big endian `dest`, from either a JSON
+/// number, a string of decimal digits or a 0x prefixed hex string.
+pub fn decode_integer<'de>(buffer: &mut Decoder<'de>, dest: &mut [u8]) -> Result<(), JsonError> {
+    skip_whitespace(buffer);
+    match buffer.first() {
+        Some(b'"') => {
+            let mut s = "";
+            s.decode_json(buffer)?;
+            match s.strip_prefix("0x").or_else(|| s.strip_prefix("0X")) {
+                Some(hex) => {
+                    let hex = hex.trim_start_matches('0');
+                    if hex.len() > dest.len() * 2 {
+                        return Err(JsonError::NumericOverflow);
+                    }
+                    dest.fill(0);
+                    hex_to_slice(dest, hex).map_err(|_| JsonError::ExpectedHexString)
+                }
+                None => decimal_to_slice(dest, s.as_bytes()),
+            }
+        }
+        Some(c) if c.is_ascii_digit() || *c == b'-' => {
+            let n = parse_number(buffer)?;
+            decimal_to_slice(dest, n)
+        }
+        _ => Err(JsonError::ExpectedDigit),
+    }
+}
+
 macro_rules! decode_int {
     ($t: ty) => {
         impl<'de> JsonDecode<'de> for $t {
             fn decode_json(&mut self, buffer: &mut Decoder<'de>) -> Result<(), JsonError> {
-                skip_whitespace(buffer);
-                if buffer.first() == Some(&b'"') {
-                    let mut s = "";
-                    s.decode_json(buffer)?;
-                    let mut bytes = [0; size_of::<$t>()];
-                    hex_to_slice(&mut bytes, s).map_err(|_| JsonError::ExpectedHexString)?;
-                    *self = Self::from_be_bytes(bytes);
-                } else {
-                    if !matches!(buffer.first(), Some(x) if x.is_ascii_digit()) {
-                        return Err(JsonError::ExpectedDigit);
-                    }
-                    let mut value : $t = (buffer[0] - b'0').into();
-                    buffer.advance(1);
-                    while matches!(buffer.first(), Some(x) if x.is_ascii_digit()) {
-                        let (v, e1) = value.overflowing_mul(10);
-                        let (v, e2) = v.overflowing_add((buffer[0] - b'0').into());
-                        if e1 || e2 {
-                            return Err(JsonError::NumericOverflow);
-                        }
-                        value = v;
-                        buffer.advance(1);
-                    }
-                    *self = value;
-                }
+                let mut bytes = [0; size_of::<$t>()];
+                decode_integer(buffer, &mut bytes)?;
+                *self = Self::from_be_bytes(bytes);
                 Ok(())
             }
         }
-                
+
+        impl JsonEncode for $t {
+            fn encode_json(&self, encoder: &mut Encoder) {
+                encoder.quantity(&self.to_be_bytes());
+            }
+        }
     };
 }
 
@@ -363,12 +704,37 @@ fn test_u256() {
     assert_eq!(U256::from_int(123456) * U256::from_int(7891011), U256::from_i128(123456*7891011));
 
     for i in 0..128 {
-        // println!("{i} {:?}", U256::from_int(1).shl(i));
         assert_eq!(U256::from_int(1).shl(i).shr(i), U256::from_i128(1));
     }
 
     assert_eq!(U256::from_int(123456).overflowing_div(U256::from_int(100)), (U256::from_i128(1234), false));
 
+    let big = U256::MAX.shr(1);
+    assert_eq!(big * U256::from(2), U256::MAX - U256::ONE);
+    assert_eq!(U256::MAX * U256::MAX, U256::ONE);
+    assert_eq!(U256::MAX.div_rem(big), (U256::from(2), U256::ONE));
+    assert_eq!(U256::MAX.add_mod(U256::from(2), U256::from(10)), U256::from(7));
+    assert_eq!(U256::MAX.mul_mod(U256::MAX, U256::from(12)), U256::from(9));
+    assert_eq!(U256::from(3).pow(U256::from(5)), U256::from(243));
+    assert_eq!(U256::from(2).pow(U256::from(256)), U256::ZERO);
+    assert_eq!(U256::from(-7).signed_div(U256::from(2)), U256::from(-3));
+    assert_eq!(U256::from(-7).signed_rem(U256::from(2)), U256::from(-1));
+    assert_eq!(U256::ONE.shl(255).signed_div(U256::from(-1)), U256::ONE.shl(255));
+    assert_eq!(U256::from(0xff).sign_extend(U256::ZERO), U256::from(-1));
+    assert_eq!(U256::from(0x7f).sign_extend(U256::ZERO), U256::from(0x7f));
+    assert_eq!(U256::from(0x1234).byte(U256::from(30)), U256::from(0x12));
+    assert_eq!(U256::from(-16).sar(2), U256::from(-4));
+    assert_eq!(U256::from(-1).sar(300), U256::from(-1));
+
+    for s in ["0", "1", "10000000000000000000", "115792089237316195423570985008687907853269984665640564039457584007913129639935"] {
+        assert_eq!(s.parse::<U256>().unwrap().to_string(), s);
+    }
+    assert_eq!("115792089237316195423570985008687907853269984665640564039457584007913129639935".parse::<U256>().unwrap(), U256::MAX);
+    assert!("115792089237316195423570985008687907853269984665640564039457584007913129639936".parse::<U256>().is_err());
+    assert_eq!("0x1234".parse::<U256>().unwrap(), U256::from(0x1234));
+    assert_eq!(U256::from(0x1234).to_hex(), "0x1234");
+    assert!("".parse::<U256>().is_err() && "12a".parse::<U256>().is_err());
+
     let json = r#""0x123""#;
     let mut value = U256::default();
     value.decode_json(&mut Decoder::new(json.as_bytes())).unwrap();
@@ -389,3 +755,41 @@ fn test_u256() {
     assert!(value.decode_json(&mut Decoder::new(json.as_bytes())).is_err());
 }
 
+
+#[test]
+fn test_json_integers() {
+    use crate::json::from_json;
+
+    assert_eq!(from_json::<U64>(b"1234").unwrap(), 1234);
+    assert_eq!(from_json::<U64>(b"\"1234\"").unwrap(), 1234);
+    assert_eq!(from_json::<U64>(b"\"0x4d2\"").unwrap(), 1234);
+    assert_eq!(from_json::<U64>(b"\"0x00000000000000000000000004d2\"").unwrap(), 1234);
+    assert_eq!(from_json::<U64>(b"1.234e3").unwrap(), 1234);
+    assert_eq!(from_json::<U64>(b"\"12340e-1\"").unwrap(), 1234);
+    assert_eq!(from_json::<U64>(b"0.0").unwrap(), 0);
+    assert_eq!(from_json::<U64>(b"0e999999999999").unwrap(), 0);
+    assert_eq!(from_json::<U64>(b"18446744073709551615").unwrap(), u64::MAX);
+    assert_eq!(from_json::<U8>(b"2.55E+2").unwrap(), 255);
+    assert_eq!(from_json::<Uint>(b"1e18").unwrap(), 1_000_000_000_000_000_000);
+    assert_eq!(from_json::<U256>(b"1e77").unwrap(), U256::from(10).pow(U256::from(77)));
+    assert_eq!(
+        from_json::<U256>(b"\"115792089237316195423570985008687907853269984665640564039457584007913129639935\"").unwrap(),
+        U256::MAX
+    );
+    assert_eq!(from_json::<U256>(b"\"0x\"").unwrap(), U256::ZERO);
+
+    let error = |json: &[u8]| from_json::<U64>(json).unwrap_err().error;
+    assert!(matches!(error(b"18446744073709551616"), JsonError::NumericOverflow));
+    assert!(matches!(error(b"1e20"), JsonError::NumericOverflow));
+    assert!(matches!(error(b"1e999999999999"), JsonError::NumericOverflow));
+    assert!(matches!(error(b"\"0x10000000000000000\""), JsonError::NumericOverflow));
+    assert!(matches!(error(b"1.5"), JsonError::InvalidValue("number has a fractional part")));
+    assert!(matches!(error(b"15e-1"), JsonError::InvalidValue("number has a fractional part")));
+    assert!(matches!(error(b"-1"), JsonError::InvalidValue("negative integer")));
+    assert!(matches!(error(b"\"1.\""), JsonError::BadNumber));
+    assert!(matches!(error(b"\"1e\""), JsonError::BadNumber));
+    assert!(matches!(error(b"\"\""), JsonError::BadNumber));
+    assert!(matches!(error(b"\"0xg\""), JsonError::ExpectedHexString));
+    assert!(matches!(error(b"true"), JsonError::ExpectedDigit));
+    assert!(from_json::<U256>(b"1e78").is_err());
+}
@@ -1,8 +1,19 @@
 //! Error types common across all Ethereum forks.
-//! 
-//! 
+//!
+//! Every error implements `Display` and `std::error::Error`. Errors wrapping
+//! another one, such as `Exception::RLPException`, return it as their
+//! `source`.
 
-use crate::json::JsonError;
+use std::fmt;
+
+use crate::{
+    ethereum::{
+        cancun::fork_types::{Bloom, Root},
+        crypto::hash::Hash32,
+        ethereum_types::numeric::{Uint, U64},
+    },
+    json::JsonErrorAt,
+};
 
 use super::ethereum_rlp::exceptions::RLPException;
 
@@ -20,19 +31,111 @@ pub enum Exception {
     InvalidSenderError(&'static str),
     /// Thrown when a transaction has an invalid signature.
     InvalidSignatureError(&'static str),
+    /// Thrown when a block falls under the rules of a fork which is not
+    /// implemented.
+    UnsupportedFork(&'static str),
+
+    /// The gas used by the transactions of a block is not that of its
+    /// header.
+    GasUsedMismatch { expected: Uint, actual: Uint },
+    /// The blob gas used by the transactions of a block is not that of its
+    /// header.
+    BlobGasUsedMismatch { expected: Option<U64>, actual: Option<U64> },
+    /// The state root after executing a block is not that of its header.
+    StateRootMismatch { expected: Root, actual: Root },
+    /// The root of the transactions of a block is not that of its header.
+    TransactionsRootMismatch { expected: Root, actual: Root },
+    /// The root of the receipts of a block is not that of its header.
+    ReceiptRootMismatch { expected: Root, actual: Root },
+    /// The bloom of the logs of a block is not that of its header.
+    BloomMismatch { expected: Box<Bloom>, actual: Box<Bloom> },
+    /// The root of the withdrawals of a block is not that of its header.
+    WithdrawalsRootMismatch { expected: Option<Root>, actual: Option<Root> },
+    /// The hash of the requests of a block is not that of its header.
+    RequestsHashMismatch { expected: Option<Hash32>, actual: Option<Hash32> },
 
     /// Rlp
     RLPException(RLPException),
 
-    /// Rlp
-    JsonError(JsonError),
+    /// Json, with the position of the error.
+    JsonError(JsonErrorAt),
 
     TransactionTypeError{ transaction_type: u8 },
     NumericOverflow,
 }
 
+impl Exception {
+    /// True if the error means that a block is invalid: either a check of
+    /// the block failed, or executing it gave a result other than its
+    /// header's.
+    pub fn is_invalid_block(&self) -> bool {
+        matches!(
+            self,
+            Exception::InvalidBlock(_)
+                | Exception::GasUsedMismatch { .. }
+                | Exception::BlobGasUsedMismatch { .. }
+                | Exception::StateRootMismatch { .. }
+                | Exception::TransactionsRootMismatch { .. }
+                | Exception::ReceiptRootMismatch { .. }
+                | Exception::BloomMismatch { .. }
+                | Exception::WithdrawalsRootMismatch { .. }
+                | Exception::RequestsHashMismatch { .. }
+        )
+    }
+}
+
+impl fmt::Display for Exception {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let mismatch = |f: &mut fmt::Formatter<'_>, what, expected: &dyn fmt::Debug, actual: &dyn fmt::Debug| {
+            write!(f, "invalid block: {what} is {actual:?}, header has {expected:?}")
+        };
+        match self {
+            Exception::EthereumException(e) => write!(f, "{e}"),
+            Exception::InvalidBlock(e) => write!(f, "invalid block: {e}"),
+            Exception::InvalidTransaction(e) => write!(f, "invalid transaction: {e}"),
+            Exception::InvalidSenderError(e) => write!(f, "invalid sender: {e}"),
+            Exception::InvalidSignatureError(e) => write!(f, "invalid signature: {e}"),
+            Exception::UnsupportedFork(fork) => write!(f, "unsupported fork: {fork}"),
+            Exception::GasUsedMismatch { expected, actual } => mismatch(f, "gas used", expected, actual),
+            Exception::BlobGasUsedMismatch { expected, actual } => mismatch(f, "blob gas used", expected, actual),
+            Exception::StateRootMismatch { expected, actual } => mismatch(f, "state root", expected, actual),
+            Exception::TransactionsRootMismatch { expected, actual } => {
+                mismatch(f, "transactions root", expected, actual)
+            }
+            Exception::ReceiptRootMismatch { expected, actual } => mismatch(f, "receipt root", expected, actual),
+            Exception::BloomMismatch { .. } => write!(f, "invalid block: logs bloom differs from the header's"),
+            Exception::WithdrawalsRootMismatch { expected, actual } => {
+                mismatch(f, "withdrawals root", expected, actual)
+            }
+            Exception::RequestsHashMismatch { expected, actual } => mismatch(f, "requests hash", expected, actual),
+            Exception::RLPException(_) => write!(f, "invalid RLP"),
+            Exception::JsonError(_) => write!(f, "invalid JSON"),
+            Exception::TransactionTypeError { transaction_type } => {
+                write!(f, "unknown transaction type {transaction_type}")
+            }
+            Exception::NumericOverflow => write!(f, "numeric overflow"),
+        }
+    }
+}
+
+impl std::error::Error for Exception {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            Exception::RLPException(e) => Some(e),
+            Exception::JsonError(e) => Some(e),
+            _ => None,
+        }
+    }
+}
+
 impl From<RLPException> for Exception {
     fn from(value: RLPException) -> Self {
         Exception::RLPException(value)
     }
 }
+
+impl From<JsonErrorAt> for Exception {
+    fn from(value: JsonErrorAt) -> Self {
+        Exception::JsonError(value)
+    }
+}
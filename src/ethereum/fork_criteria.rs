@@ -0,0 +1,315 @@
+//! Activation criteria for forks.
+//!
+//! Most generally, a _fork_ is a divergence in a blockchain resulting in
+//! multiple tips. Most forks are short lived, and can be caused by networking
+//! issues or the behavior of block creators. These short-lived forks resolve
+//! themselves according to the rules of the protocol, eventually settling back
+//! to a single tip of the chain.
+//!
+//! A second type of fork, known as a _hard fork_, is a deliberate change to
+//! the rules of the protocol. These changes are activated by a
+//! [`ForkCriteria`], either at a block number or, since the merge, at a
+//! timestamp.
+//!
+//! [`ForkCriteria`]: ref:ethereum.fork_criteria.ForkCriteria
+
+use std::collections::BTreeMap;
+
+use crate::{
+    ethereum::{
+        cancun::blocks::Header,
+        ethereum_types::numeric::{Uint, U256, U64},
+    },
+    json::{Decoder, Encoder, JsonDecode, JsonEncode, JsonError, ObjectEncoder, ObjectParser},
+};
+
+/// """
+/// Conditions specifying when a fork activates.
+///
+/// Criteria are ordered by when they activate: block number criteria come
+/// before timestamp criteria, which come before unscheduled forks.
+/// """
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum ForkCriteria {
+    /// Forks that occur when a specific block number has been reached.
+    ByBlockNumber(Uint),
+    /// Forks that occur when a specific timestamp has been reached.
+    ByTimestamp(U256),
+    /// Forks that have not been scheduled.
+    Unscheduled,
+}
+
+impl ForkCriteria {
+    /// """
+    /// Check whether fork criteria have been met.
+    ///
+    /// Returns `True` when the current block meets or exceeds the criteria.
+    /// """
+    pub fn check(&self, block_number: Uint, timestamp: U256) -> bool {
+        match self {
+            ForkCriteria::ByBlockNumber(n) => block_number >= *n,
+            ForkCriteria::ByTimestamp(t) => timestamp >= *t,
+            ForkCriteria::Unscheduled => false,
+        }
+    }
+
+    /// `check` for the block with `header`.
+    pub fn check_header(&self, header: &Header) -> bool {
+        self.check(header.number, header.timestamp)
+    }
+}
+
+/// Fork activations in the `config` section of a geth style genesis file,
+/// by fork name.
+///
+/// `"londonBlock": 12965000` is `london` activating
+/// `ByBlockNumber(12965000)` and `"shanghaiTime": 1681338455` is `shanghai`
+/// activating `ByTimestamp(1681338455)`. A `null` activation is
+/// `Unscheduled`. Other members, such as `chainId` and consensus engine
+/// settings, are ignored.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct ForkActivations(pub BTreeMap<String, ForkCriteria>);
+
+impl ForkActivations {
+    /// The criteria for the fork `name`, `Unscheduled` if it is not listed.
+    pub fn get(&self, name: &str) -> ForkCriteria {
+        self.0.get(name).copied().unwrap_or(ForkCriteria::Unscheduled)
+    }
+
+    /// Decode the value of the config member `key` if it is a fork
+    /// activation, returning false and leaving the value unread if not.
+    pub fn decode_member<'de>(&mut self, key: &str, decoder: &mut Decoder<'de>) -> Result<bool, JsonError> {
+        let (name, by_time) = if let Some(name) = key.strip_suffix("Block") {
+            (name, false)
+        } else if let Some(name) = key.strip_suffix("Time") {
+            (name, true)
+        } else {
+            return Ok(false);
+        };
+        // Geth writes activations as numbers, but accept quantities too.
+        let mut value: Option<U64> = None;
+        value.decode_json(decoder)?;
+        let criteria = match value {
+            None => ForkCriteria::Unscheduled,
+            Some(t) if by_time => ForkCriteria::ByTimestamp(U256::from(t)),
+            Some(n) => ForkCriteria::ByBlockNumber(n as Uint),
+        };
+        self.0.insert(name.to_string(), criteria);
+        Ok(true)
+    }
+
+    /// Add the members of the JSON for these activations to `o`.
+    pub fn encode_members(&self, o: &mut ObjectEncoder) {
+        for (name, criteria) in &self.0 {
+            match criteria {
+                ForkCriteria::ByBlockNumber(n) => {
+                    o.key(&format!("{name}Block"));
+                    o.encoder.raw(&n.to_string());
+                }
+                ForkCriteria::ByTimestamp(t) => {
+                    o.key(&format!("{name}Time"));
+                    o.encoder.raw(&t.to_uint().unwrap_or(Uint::MAX).to_string());
+                }
+                ForkCriteria::Unscheduled => (),
+            }
+        }
+    }
+}
+
+impl<'de> JsonDecode<'de> for ForkActivations {
+    fn decode_json(&mut self, decoder: &mut Decoder<'de>) -> Result<(), JsonError> {
+        let mut p = ObjectParser::new(decoder);
+        while let Some(key) = p.next_key()? {
+            if !self.decode_member(key, p.decoder)? {
+                p.skip_value()?;
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Written in the same form as read, leaving out unscheduled forks.
+impl JsonEncode for ForkActivations {
+    fn encode_json(&self, encoder: &mut Encoder) {
+        let mut o = encoder.object();
+        self.encode_members(&mut o);
+        o.end();
+    }
+}
+
+/// Forks in activation order, named as their modules are, with the keys
+/// which schedule them in a geth style config, preferred first.
+///
+/// Geth schedules EIP-155 and EIP-158 separately where the specification has
+/// the single Spurious Dragon fork, and has Petersburg reverting part of
+/// Constantinople, where the specification's Constantinople is Petersburg.
+const FORKS: [(&str, &[&str]); 17] = [
+    ("frontier", &[]),
+    ("homestead", &["homestead"]),
+    ("dao_fork", &["daoFork"]),
+    ("tangerine_whistle", &["eip150"]),
+    ("spurious_dragon", &["eip158", "eip155"]),
+    ("byzantium", &["byzantium"]),
+    ("constantinople", &["petersburg", "constantinople"]),
+    ("istanbul", &["istanbul"]),
+    ("muir_glacier", &["muirGlacier"]),
+    ("berlin", &["berlin"]),
+    ("london", &["london"]),
+    ("arrow_glacier", &["arrowGlacier"]),
+    ("gray_glacier", &["grayGlacier"]),
+    ("paris", &["mergeNetsplit"]),
+    ("shanghai", &["shanghai"]),
+    ("cancun", &["cancun"]),
+    ("prague", &["prague"]),
+];
+
+/// Position of the fork `name` in `FORKS`.
+fn fork_index(name: &str) -> usize {
+    FORKS.iter().position(|(n, _)| *n == name).unwrap_or(FORKS.len())
+}
+
+/// Which fork's rules apply to a block, by block number and timestamp.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ForkSchedule(Vec<(ForkCriteria, &'static str)>);
+
+impl ForkSchedule {
+    /// The schedule of the chain with fork `activations`.
+    ///
+    /// A config without any activations, as test genesis files often are,
+    /// follows the latest implemented rules from genesis.
+    pub fn new(activations: &ForkActivations) -> Self {
+        if activations.0.is_empty() {
+            return Self::default();
+        }
+        let mut forks = vec![(ForkCriteria::ByBlockNumber(0), FORKS[0].0)];
+        for (name, keys) in &FORKS[1..] {
+            let criteria = keys.iter().map(|key| activations.get(key)).find(|c| *c != ForkCriteria::Unscheduled);
+            if let Some(criteria) = criteria {
+                forks.push((criteria, *name));
+            }
+        }
+        Self(forks)
+    }
+
+    /// A chain following the rules of `fork` from genesis.
+    pub fn only(fork: &'static str) -> Self {
+        Self(vec![(ForkCriteria::ByBlockNumber(0), fork)])
+    }
+
+    /// Activations which `new` turns back into this schedule, each fork
+    /// under its preferred key.
+    pub fn activations(&self) -> ForkActivations {
+        let activations = self.0.iter().filter_map(|(criteria, name)| {
+            let (_, keys) = FORKS.get(fork_index(name))?;
+            Some((keys.first()?.to_string(), *criteria))
+        });
+        ForkActivations(activations.collect())
+    }
+
+    /// The forks of the schedule with their criteria, in order.
+    pub fn forks(&self) -> &[(ForkCriteria, &'static str)] {
+        &self.0
+    }
+
+    /// The criteria for the fork `name`.
+    pub fn activation(&self, name: &str) -> ForkCriteria {
+        self.0.iter().find(|(_, n)| *n == name).map_or(ForkCriteria::Unscheduled, |(c, _)| *c)
+    }
+
+    /// The latest fork activated at `block_number` and `timestamp`.
+    pub fn fork_at(&self, block_number: Uint, timestamp: U256) -> &'static str {
+        self.0
+            .iter()
+            .rev()
+            .find(|(criteria, _)| criteria.check(block_number, timestamp))
+            .map_or(FORKS[0].0, |(_, name)| name)
+    }
+
+    /// The fork whose rules apply to the block with `header`.
+    ///
+    /// Mainnet switched to proof of stake at a total difficulty rather than
+    /// at a block, so a block with zero difficulty, which proof of work does
+    /// not allow, is taken to be a Paris block if no later fork is active.
+    pub fn fork_for(&self, header: &Header) -> &'static str {
+        let fork = self.fork_at(header.number, header.timestamp);
+        let paris = fork_index("paris");
+        if header.difficulty == 0 && fork_index(fork) < paris {
+            FORKS[paris].0
+        } else {
+            fork
+        }
+    }
+}
+
+/// Cancun from genesis.
+impl Default for ForkSchedule {
+    fn default() -> Self {
+        Self::only("cancun")
+    }
+}
+
+#[test]
+fn test_ordering() {
+    use ForkCriteria::*;
+    let mut forks = vec![Unscheduled, ByTimestamp(U256::from(5_u64)), ByBlockNumber(10), ByTimestamp(U256::ONE), ByBlockNumber(0)];
+    forks.sort();
+    assert_eq!(forks, [ByBlockNumber(0), ByBlockNumber(10), ByTimestamp(U256::ONE), ByTimestamp(U256::from(5_u64)), Unscheduled]);
+
+    let header = Header { number: 10, timestamp: U256::from(4_u64), ..Default::default() };
+    assert!(ByBlockNumber(10).check_header(&header));
+    assert!(!ByBlockNumber(11).check_header(&header));
+    assert!(ByTimestamp(U256::from(4_u64)).check_header(&header));
+    assert!(!ByTimestamp(U256::from(5_u64)).check_header(&header));
+    assert!(!Unscheduled.check_header(&header));
+}
+
+#[test]
+fn test_chain_config() {
+    let json = br#"{
+        "chainId": 1,
+        "homesteadBlock": 1150000,
+        "daoForkBlock": 1920000,
+        "daoForkSupport": true,
+        "londonBlock": "0xc5d488",
+        "terminalTotalDifficulty": 58750000000000000000000,
+        "shanghaiTime": 1681338455,
+        "pragueTime": null,
+        "ethash": {}
+    }"#;
+    let forks: ForkActivations = crate::json::from_json(json).unwrap();
+    assert_eq!(forks.get("homestead"), ForkCriteria::ByBlockNumber(1150000));
+    assert_eq!(forks.get("daoFork"), ForkCriteria::ByBlockNumber(1920000));
+    assert_eq!(forks.get("london"), ForkCriteria::ByBlockNumber(12965000));
+    assert_eq!(forks.get("shanghai"), ForkCriteria::ByTimestamp(U256::from(1681338455_u64)));
+    assert_eq!(forks.get("prague"), ForkCriteria::Unscheduled);
+    assert_eq!(forks.get("osaka"), ForkCriteria::Unscheduled);
+    assert_eq!(
+        crate::json::to_json(&forks),
+        r#"{"daoForkBlock":1920000,"homesteadBlock":1150000,"londonBlock":12965000,"shanghaiTime":1681338455}"#,
+    );
+}
+
+#[test]
+fn test_schedule() {
+    let g = crate::ethereum::genesis::Genesis::mainnet().unwrap();
+    let schedule = ForkSchedule::new(&g.forks);
+    let at = |number: Uint, timestamp: u64| schedule.fork_at(number, U256::from(timestamp));
+    assert_eq!(at(0, 0), "frontier");
+    assert_eq!(at(1_149_999, 0), "frontier");
+    assert_eq!(at(1_150_000, 0), "homestead");
+    assert_eq!(at(2_675_000, 0), "spurious_dragon");
+    assert_eq!(at(7_280_000, 0), "constantinople");
+    assert_eq!(at(12_965_000, 0), "london");
+    assert_eq!(at(17_034_870, 1681338455), "shanghai");
+    assert_eq!(at(19_426_587, 1710338135), "cancun");
+    assert_eq!(schedule.activation("dao_fork"), ForkCriteria::ByBlockNumber(1920000));
+    assert_eq!(schedule.activation("prague"), ForkCriteria::Unscheduled);
+
+    // The merge is recognised by the difficulty of the block.
+    let header = Header { number: 15_537_394, timestamp: U256::from(1663224179_u64), ..Default::default() };
+    assert_eq!(schedule.fork_for(&header), "paris");
+    assert_eq!(schedule.fork_for(&Header { difficulty: 1, ..header }), "gray_glacier");
+
+    assert_eq!(ForkSchedule::new(&ForkActivations::default()), ForkSchedule::only("cancun"));
+    assert_eq!(ForkSchedule::default().fork_at(0, U256::ZERO), "cancun");
+}
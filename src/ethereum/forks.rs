@@ -0,0 +1,515 @@
+//! Rules which differ from one fork to the next.
+//!
+//! Each fork module implements [`Fork`] for a unit type naming the fork,
+//! such as `cancun::fork::Cancun`. Differences which are only a matter of
+//! parameters are expressed as data, so that code shared between forks can
+//! consult the fork instead of hard coding the rules of one of them.
+
+use crate::ethereum::{
+    cancun::{
+        blocks::{Block, Header},
+        fork::{proof_of_work::ProofOfWork, BlockChain, Cancun},
+        fork_types::Address,
+        transactions::Transaction,
+        vm::{instructions::Ops, precompiled_contracts::PRE_COMPILED_CONTRACTS},
+    },
+    crypto::hash::{keccak256, Hash32},
+    ethereum_types::{bytes::Bytes8, numeric::Uint},
+    exceptions::Exception,
+    paris::fork::Paris,
+    pre_merge::fork::{
+        ArrowGlacier, Berlin, Byzantium, Constantinople, DaoFork, Frontier, GrayGlacier, Homestead, Istanbul, London,
+        MuirGlacier, SpuriousDragon, TangerineWhistle,
+    },
+    shanghai::fork::Shanghai,
+};
+
+/// What the `prev_randao` field of a header, called `mixHash` before the
+/// merge, holds.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum MixHash {
+    /// The ethash mix digest, which is checked together with `nonce` by the
+    /// proof of work.
+    ProofOfWork,
+    /// The RANDAO value of the beacon chain, which is not checked by the
+    /// execution layer.
+    Randao,
+}
+
+/// Constraints on the fields of a header which can be checked without its
+/// parent.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct HeaderConstraints {
+    /// Longest `extra_data` in bytes.
+    pub max_extra_data_len: usize,
+    pub mix_hash: MixHash,
+    /// `difficulty` and `nonce` must be zero and `ommers_hash` must be the
+    /// hash of an empty list, as there is no proof of work.
+    pub proof_of_stake: bool,
+    /// Whether `base_fee_per_gas` is present (EIP-1559).
+    pub base_fee: bool,
+    /// Whether `withdrawals_root` is present (EIP-4895).
+    pub withdrawals: bool,
+    /// Whether `blob_gas_used` and `excess_blob_gas` are present (EIP-4844).
+    pub blob_gas: bool,
+    /// Whether `parent_beacon_block_root` is present (EIP-4788).
+    pub parent_beacon_block_root: bool,
+    /// Whether `requests_hash` is present (EIP-7685).
+    pub requests_hash: bool,
+}
+
+impl HeaderConstraints {
+    /// Frontier to Berlin.
+    pub const FRONTIER: Self = Self {
+        max_extra_data_len: 32,
+        mix_hash: MixHash::ProofOfWork,
+        proof_of_stake: false,
+        base_fee: false,
+        withdrawals: false,
+        blob_gas: false,
+        parent_beacon_block_root: false,
+        requests_hash: false,
+    };
+
+    /// London to Gray Glacier.
+    pub const LONDON: Self = Self { base_fee: true, ..Self::FRONTIER };
+
+    /// The merge.
+    pub const PARIS: Self = Self { mix_hash: MixHash::Randao, proof_of_stake: true, ..Self::LONDON };
+
+    pub const SHANGHAI: Self = Self { withdrawals: true, ..Self::PARIS };
+
+    pub const CANCUN: Self = Self { blob_gas: true, parent_beacon_block_root: true, ..Self::SHANGHAI };
+
+    pub const PRAGUE: Self = Self { requests_hash: true, ..Self::CANCUN };
+
+    /// Check the fields of `header`.
+    pub fn check(&self, header: &Header) -> Result<(), Exception> {
+        if header.extra_data.len() > self.max_extra_data_len {
+            return Err(Exception::InvalidBlock("header.extra_data is too long"));
+        }
+        if self.proof_of_stake {
+            if header.difficulty != 0 {
+                return Err(Exception::InvalidBlock("header.difficulty != 0"));
+            }
+            if header.nonce != Bytes8::default() {
+                return Err(Exception::InvalidBlock("header.nonce != 0"));
+            }
+            if header.ommers_hash != empty_ommers_hash() {
+                return Err(Exception::InvalidBlock("header.ommers_hash != EMPTY_OMMER_HASH"));
+            }
+        } else if header.difficulty == 0 {
+            return Err(Exception::InvalidBlock("header.difficulty == 0 before the merge"));
+        }
+        let fields = [
+            (self.base_fee, header.base_fee_per_gas.is_some(), "header.base_fee_per_gas"),
+            (self.withdrawals, header.withdrawals_root.is_some(), "header.withdrawals_root"),
+            (self.blob_gas, header.blob_gas_used.is_some(), "header.blob_gas_used"),
+            (self.blob_gas, header.excess_blob_gas.is_some(), "header.excess_blob_gas"),
+            (
+                self.parent_beacon_block_root,
+                header.parent_beacon_block_root.is_some(),
+                "header.parent_beacon_block_root",
+            ),
+            (self.requests_hash, header.requests_hash.is_some(), "header.requests_hash"),
+        ];
+        for (expected, present, field) in fields {
+            if expected != present {
+                return Err(Exception::InvalidBlock(field));
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Differences in how the transactions of a block are executed.
+///
+/// The forks before Shanghai are described by one flag each, as their
+/// changes were scheduled together and never apart; the flags of later
+/// forks are per feature.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ExecutionRules {
+    /// Creating a contract with a transaction costs 32000 more gas, a
+    /// creation which cannot pay for its code fails and signatures must
+    /// have a low `s` (EIP-2). `DELEGATECALL` (EIP-7).
+    pub homestead: bool,
+    /// Calls and creations pass on at most 63/64 of the gas left, and
+    /// `CALL`, `SLOAD` and the instructions reading other accounts cost
+    /// more (EIP-150).
+    pub tangerine_whistle: bool,
+    /// Replay protected signatures (EIP-155), `EXP` costs 50 per byte of
+    /// exponent (EIP-160), empty accounts are removed when touched and
+    /// contracts start with nonce 1 (EIP-161) and code is at most 24576
+    /// bytes (EIP-170).
+    pub spurious_dragon: bool,
+    /// `REVERT` (EIP-140), the modexp and alt_bn128 precompiles (EIP-196,
+    /// EIP-197, EIP-198), `RETURNDATASIZE` and `RETURNDATACOPY` (EIP-211),
+    /// `STATICCALL` (EIP-214) and receipts with a status instead of a state
+    /// root (EIP-658).
+    pub byzantium: bool,
+    /// Shift instructions (EIP-145), `EXTCODEHASH` (EIP-1052) and `CREATE2`
+    /// (EIP-1014), without the net gas metering which Petersburg took out
+    /// again.
+    pub constantinople: bool,
+    /// The blake2 precompile (EIP-152), `CHAINID` (EIP-1344), `SLOAD`
+    /// costs 800 and `SELFBALANCE` (EIP-1884), cheaper call data (EIP-2028)
+    /// and net gas metering for `SSTORE` (EIP-2200).
+    pub istanbul: bool,
+    /// Modexp repricing (EIP-2565), cold and warm access costs (EIP-2929)
+    /// and access list transactions (EIP-2930).
+    pub berlin: bool,
+    /// Fee market transactions and `BASEFEE` (EIP-1559, EIP-3198), lower
+    /// refunds (EIP-3529) and no new code starting with `0xEF` (EIP-3541).
+    pub london: bool,
+    /// The coinbase starts warm (EIP-3651), `PUSH0` (EIP-3855) and init
+    /// code is limited and paid for (EIP-3860).
+    pub shanghai: bool,
+    /// `TLOAD` and `TSTORE` (EIP-1153).
+    pub transient_storage: bool,
+    /// `MCOPY` (EIP-5656).
+    pub mcopy: bool,
+    /// Blob transactions, `BLOBHASH` and the point evaluation precompile
+    /// (EIP-4844) and `BLOBBASEFEE` (EIP-7516).
+    pub blobs: bool,
+    /// `SELFDESTRUCT` only deletes an account in the transaction which
+    /// created it, and otherwise only moves its balance (EIP-6780).
+    pub restricted_selfdestruct: bool,
+    /// The beacon roots contract is called before the transactions of a
+    /// block (EIP-4788).
+    pub beacon_roots: bool,
+    /// The hash of the parent block is stored in the history contract
+    /// before the transactions of a block (EIP-2935).
+    pub block_hash_history: bool,
+    /// Set code transactions and delegation designators (EIP-7702).
+    pub set_code: bool,
+    /// Deposit, withdrawal and consolidation requests are collected after
+    /// the transactions of a block (EIP-7685).
+    pub requests: bool,
+    /// Code may be an EOF container, which is validated when it is
+    /// deployed and of which only the code section is executed (EIP-3540,
+    /// EIP-3670). Not yet scheduled for any fork.
+    pub eof: bool,
+}
+
+impl ExecutionRules {
+    /// Frontier, and the DAO fork, which only changed the state.
+    pub const FRONTIER: Self = Self {
+        homestead: false,
+        tangerine_whistle: false,
+        spurious_dragon: false,
+        byzantium: false,
+        constantinople: false,
+        istanbul: false,
+        berlin: false,
+        london: false,
+        shanghai: false,
+        transient_storage: false,
+        mcopy: false,
+        blobs: false,
+        restricted_selfdestruct: false,
+        beacon_roots: false,
+        block_hash_history: false,
+        set_code: false,
+        requests: false,
+        eof: false,
+    };
+
+    pub const HOMESTEAD: Self = Self { homestead: true, ..Self::FRONTIER };
+
+    pub const TANGERINE_WHISTLE: Self = Self { tangerine_whistle: true, ..Self::HOMESTEAD };
+
+    pub const SPURIOUS_DRAGON: Self = Self { spurious_dragon: true, ..Self::TANGERINE_WHISTLE };
+
+    pub const BYZANTIUM: Self = Self { byzantium: true, ..Self::SPURIOUS_DRAGON };
+
+    /// Constantinople as activated on mainnet, that is Petersburg.
+    pub const CONSTANTINOPLE: Self = Self { constantinople: true, ..Self::BYZANTIUM };
+
+    /// Istanbul and Muir Glacier.
+    pub const ISTANBUL: Self = Self { istanbul: true, ..Self::CONSTANTINOPLE };
+
+    pub const BERLIN: Self = Self { berlin: true, ..Self::ISTANBUL };
+
+    /// London to Gray Glacier.
+    pub const LONDON: Self = Self { london: true, ..Self::BERLIN };
+
+    /// The merge changed the consensus, not the execution.
+    pub const PARIS: Self = Self::LONDON;
+
+    pub const SHANGHAI: Self = Self { shanghai: true, ..Self::PARIS };
+
+    pub const CANCUN: Self = Self {
+        transient_storage: true,
+        mcopy: true,
+        blobs: true,
+        restricted_selfdestruct: true,
+        beacon_roots: true,
+        ..Self::SHANGHAI
+    };
+
+    pub const PRAGUE: Self = Self { set_code: true, requests: true, block_hash_history: true, ..Self::CANCUN };
+
+    /// Whether `op` is a valid instruction.
+    pub fn allows(&self, op: Ops) -> bool {
+        match op {
+            Ops::DELEGATECALL => self.homestead,
+            Ops::REVERT | Ops::RETURNDATASIZE | Ops::RETURNDATACOPY | Ops::STATICCALL => self.byzantium,
+            Ops::SHL | Ops::SHR | Ops::SAR | Ops::CREATE2 | Ops::EXTCODEHASH => self.constantinople,
+            Ops::CHAINID | Ops::SELFBALANCE => self.istanbul,
+            Ops::BASEFEE => self.london,
+            Ops::PUSH0 => self.shanghai,
+            Ops::TLOAD | Ops::TSTORE => self.transient_storage,
+            Ops::MCOPY => self.mcopy,
+            Ops::BLOBHASH | Ops::BLOBBASEFEE => self.blobs,
+            _ => true,
+        }
+    }
+
+    /// Whether transactions of the type of `tx` are valid.
+    pub fn allows_transaction(&self, tx: &Transaction) -> bool {
+        match tx {
+            Transaction::LegacyTransaction(_) => true,
+            Transaction::AccessListTransaction(_) => self.berlin,
+            Transaction::FeeMarketTransaction(_) => self.london,
+            Transaction::BlobTransaction(_) => self.blobs,
+            Transaction::SetCodeTransaction(_) => self.set_code,
+        }
+    }
+
+    /// Addresses of the precompiled contracts.
+    pub fn pre_compiled_contracts(&self) -> &'static [Address] {
+        let count = if self.blobs {
+            10
+        } else if self.istanbul {
+            // All but point evaluation.
+            9
+        } else if self.byzantium {
+            // Up to the alt_bn128 pairing check.
+            8
+        } else {
+            // ecrecover, sha256, ripemd160 and identity.
+            4
+        };
+        &PRE_COMPILED_CONTRACTS[..count]
+    }
+
+    /// The divisor of the gas used by a transaction which bounds its refund.
+    pub fn max_refund_quotient(&self) -> Uint {
+        if self.london { 5 } else { 2 }
+    }
+}
+
+/// Hash of the RLP encoding of an empty list of ommers.
+fn empty_ommers_hash() -> Hash32 {
+    keccak256(&[0xc0])
+}
+
+/// The rules of one fork.
+pub trait Fork {
+    /// Lower case name, as used for the fork's module.
+    fn name(&self) -> &'static str;
+
+    /// Constraints on header fields.
+    fn header_constraints(&self) -> HeaderConstraints;
+
+    /// How transactions are executed.
+    fn execution_rules(&self) -> ExecutionRules;
+
+    /// Difficulty and rewards of the blocks of the fork, `None` after the
+    /// merge.
+    fn proof_of_work(&self) -> Option<ProofOfWork> {
+        None
+    }
+
+    /// Apply `block` to the head of `chain` under the rules of this fork.
+    fn state_transition(&self, chain: &mut BlockChain, block: Block) -> Result<(), Exception>;
+}
+
+/// The implemented fork named `name`, as in `ForkSchedule`.
+///
+/// Prague is `None` until it has the calldata floor cost (EIP-7623), the
+/// BLS12-381 precompiles (EIP-2537) and its blob schedule; its rules are
+/// only used directly, by their tests.
+///
+/// [`ForkSchedule`]: crate::ethereum::fork_criteria::ForkSchedule
+pub fn fork_rules(name: &str) -> Option<&'static dyn Fork> {
+    match name {
+        "frontier" => Some(&Frontier),
+        "homestead" => Some(&Homestead),
+        "dao_fork" => Some(&DaoFork),
+        "tangerine_whistle" => Some(&TangerineWhistle),
+        "spurious_dragon" => Some(&SpuriousDragon),
+        "byzantium" => Some(&Byzantium),
+        "constantinople" => Some(&Constantinople),
+        "istanbul" => Some(&Istanbul),
+        "muir_glacier" => Some(&MuirGlacier),
+        "berlin" => Some(&Berlin),
+        "london" => Some(&London),
+        "arrow_glacier" => Some(&ArrowGlacier),
+        "gray_glacier" => Some(&GrayGlacier),
+        "paris" => Some(&Paris),
+        "shanghai" => Some(&Shanghai),
+        "cancun" => Some(&Cancun),
+        _ => None,
+    }
+}
+
+/// The implemented fork named `name` as in the fixtures of `ethereum/tests`
+/// and the transition tool, such as `EIP150` or `Cancun`. Forks which are
+/// not implemented, such as Constantinople before Petersburg, the
+/// transition forks and Prague, are `None`.
+pub fn fixture_fork(name: &str) -> Option<&'static dyn Fork> {
+    let name = match name {
+        "Frontier" => "frontier",
+        "Homestead" => "homestead",
+        "EIP150" => "tangerine_whistle",
+        "EIP158" => "spurious_dragon",
+        "Byzantium" => "byzantium",
+        "ConstantinopleFix" | "Petersburg" => "constantinople",
+        "Istanbul" => "istanbul",
+        "MuirGlacier" => "muir_glacier",
+        "Berlin" => "berlin",
+        "London" => "london",
+        "ArrowGlacier" => "arrow_glacier",
+        "GrayGlacier" => "gray_glacier",
+        "Merge" | "Paris" => "paris",
+        "Shanghai" => "shanghai",
+        "Cancun" => "cancun",
+        _ => return None,
+    };
+    fork_rules(name)
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::ethereum::{
+        cancun::{
+            blocks::Header,
+            fork::{proof_of_work::ProofOfWork, Cancun},
+            transactions::Transaction,
+            vm::instructions::Ops,
+        },
+        crypto::hash::keccak256,
+        ethereum_types::bytes::{Bytes, Bytes8},
+        exceptions::Exception,
+        prague::fork::Prague,
+    };
+
+    use super::{fork_rules, ExecutionRules, Fork, HeaderConstraints, MixHash};
+
+    fn header(constraints: &HeaderConstraints) -> Header {
+        let pow = !constraints.proof_of_stake;
+        Header {
+            ommers_hash: keccak256(&[0xc0]),
+            difficulty: if pow { 0x20000 } else { 0 },
+            nonce: if pow { Bytes8([0x42; 8]) } else { Bytes8::default() },
+            extra_data: Bytes(vec![0; 32]),
+            base_fee_per_gas: constraints.base_fee.then_some(7),
+            withdrawals_root: constraints.withdrawals.then(Default::default),
+            blob_gas_used: constraints.blob_gas.then_some(0),
+            excess_blob_gas: constraints.blob_gas.then_some(0),
+            parent_beacon_block_root: constraints.parent_beacon_block_root.then(Default::default),
+            requests_hash: constraints.requests_hash.then(Default::default),
+            ..Default::default()
+        }
+    }
+
+    fn rejects(constraints: &HeaderConstraints, f: impl FnOnce(&mut Header)) -> bool {
+        let mut h = header(constraints);
+        f(&mut h);
+        matches!(constraints.check(&h), Err(Exception::InvalidBlock(_)))
+    }
+
+    #[test]
+    fn every_fork_accepts_its_own_header() {
+        let forks = [
+            HeaderConstraints::FRONTIER,
+            HeaderConstraints::LONDON,
+            HeaderConstraints::PARIS,
+            HeaderConstraints::SHANGHAI,
+            HeaderConstraints::CANCUN,
+            HeaderConstraints::PRAGUE,
+        ];
+        for (i, a) in forks.iter().enumerate() {
+            for (j, b) in forks.iter().enumerate() {
+                assert_eq!(a.check(&header(b)).is_ok(), i == j, "{a:?} {b:?}");
+            }
+        }
+    }
+
+    #[test]
+    fn frontier() {
+        let c = HeaderConstraints::FRONTIER;
+        assert_eq!(c.mix_hash, MixHash::ProofOfWork);
+        assert!(rejects(&c, |h| h.extra_data.0.push(0)));
+        assert!(rejects(&c, |h| h.difficulty = 0));
+        assert!(rejects(&c, |h| h.base_fee_per_gas = Some(1)));
+        // Uncles and any nonce are fine with proof of work.
+        assert!(!rejects(&c, |h| h.ommers_hash = Default::default()));
+        assert!(!rejects(&c, |h| h.nonce = Bytes8::default()));
+    }
+
+    #[test]
+    fn london() {
+        let c = HeaderConstraints::LONDON;
+        assert!(rejects(&c, |h| h.base_fee_per_gas = None));
+        assert!(rejects(&c, |h| h.withdrawals_root = Some(Default::default())));
+    }
+
+    #[test]
+    fn paris() {
+        let c = HeaderConstraints::PARIS;
+        assert_eq!(c.mix_hash, MixHash::Randao);
+        assert!(rejects(&c, |h| h.difficulty = 1));
+        assert!(rejects(&c, |h| h.nonce = Bytes8([0, 0, 0, 0, 0, 0, 0, 1])));
+        assert!(rejects(&c, |h| h.ommers_hash = Default::default()));
+        assert!(rejects(&c, |h| h.extra_data.0.push(0)));
+        assert!(!rejects(&c, |h| h.prev_randao.0 = [0xff; 32]));
+    }
+
+    #[test]
+    fn shanghai() {
+        let c = HeaderConstraints::SHANGHAI;
+        assert!(rejects(&c, |h| h.withdrawals_root = None));
+        assert!(rejects(&c, |h| h.blob_gas_used = Some(0)));
+        assert_eq!(fork_rules("shanghai").map(|f| f.header_constraints()), Some(c));
+
+        let rules = ExecutionRules::SHANGHAI;
+        assert!(!rules.allows(Ops::TLOAD));
+        assert!(!rules.allows(Ops::MCOPY));
+        assert!(rules.allows(Ops::SSTORE));
+        assert_eq!(rules.pre_compiled_contracts().len(), 9);
+        assert_eq!(ExecutionRules::CANCUN.pre_compiled_contracts().len(), 10);
+    }
+
+    #[test]
+    fn cancun() {
+        let c = Cancun.header_constraints();
+        assert_eq!(c, HeaderConstraints::CANCUN);
+        assert_eq!(Cancun.name(), "cancun");
+        assert!(rejects(&c, |h| h.excess_blob_gas = None));
+        assert!(rejects(&c, |h| h.parent_beacon_block_root = None));
+        assert!(rejects(&c, |h| h.nonce = Bytes8([1; 8])));
+        assert_eq!(fork_rules("cancun").map(|f| f.name()), Some("cancun"));
+        assert_eq!(fork_rules("london").and_then(|f| f.proof_of_work()), Some(ProofOfWork::LONDON));
+        assert!(fork_rules("paris").is_some_and(|f| f.proof_of_work().is_none()));
+        // Petersburg is scheduled as "constantinople", with its rules.
+        assert!(fork_rules("petersburg").is_none());
+    }
+
+    #[test]
+    fn prague() {
+        let c = HeaderConstraints::PRAGUE;
+        assert_eq!(Prague.header_constraints(), c);
+        assert!(fork_rules("prague").is_none());
+        assert!(rejects(&c, |h| h.requests_hash = None));
+        assert!(rejects(&HeaderConstraints::CANCUN, |h| h.requests_hash = Some(Default::default())));
+
+        let tx = Transaction::SetCodeTransaction(Default::default());
+        assert!(ExecutionRules::PRAGUE.allows_transaction(&tx));
+        assert!(!ExecutionRules::CANCUN.allows_transaction(&tx));
+        let blob = Transaction::BlobTransaction(Default::default());
+        assert!(ExecutionRules::CANCUN.allows_transaction(&blob));
+        assert!(!ExecutionRules::SHANGHAI.allows_transaction(&blob));
+    }
+}
@@ -11,17 +11,22 @@
 //! [`GenesisConfiguration`]: ref:ethereum.genesis.GenesisConfiguration
 //! [`add_genesis_block`]: ref:ethereum.genesis.add_genesis_block
 
-use std::collections::BTreeMap;
+use std::{collections::BTreeMap, path::Path};
 
-use crate::json::{Decoder, JsonDecode, JsonError, ObjectParser};
+use crate::json::{from_json, Decoder, Encoder, JsonDecode, JsonEncode, JsonError, ObjectParser};
 
-use super::{cancun::{self, blocks::Header, fork::BlockChain, fork_types::{Account, Address, Root}}, crypto::hash::Hash32, ethereum_rlp::rlp::Extended, ethereum_types::{bytes::{Bytes, Bytes32, Bytes8}, numeric::{Uint, U256, U64}}, exceptions::Exception, utils::hexadecimal::{hex_to_bytes, hex_to_bytes8, hex_to_u256, hex_to_uint}};
+use super::{chain_spec::ChainSpec, fork_criteria::{ForkActivations, ForkCriteria}};
+
+use super::{cancun::{self, blocks::{Block, Header}, fork::{BlockChain, EMPTY_OMMER_HASH, INITIAL_BASE_FEE}, fork_types::{Account, Address, Bloom, Root}, state::{state_root, State}, trie::EMPTY_TRIE_ROOT}, crypto::hash::Hash32, ethereum_rlp::rlp::Extended, ethereum_types::{bytes::{Bytes, Bytes32, Bytes8}, numeric::{Uint, U256, U64}}, exceptions::Exception, prague::requests::compute_requests_hash, utils::hexadecimal::{hex_to_bytes, hex_to_bytes8, hex_to_u256, hex_to_uint}};
 
 #[derive(Default, Debug)]
 pub struct Genesis {
     pub header: Header,
     pub alloc: BTreeMap<Address, Account>,
-    pub chain_id: U64,
+    /// The chain id and parameters from the `config` section.
+    pub chain_spec: ChainSpec,
+    /// Fork blocks and timestamps from the `config` section.
+    pub forks: ForkActivations,
 }
 
 impl<'de> JsonDecode<'de> for Genesis {
@@ -34,36 +39,323 @@ impl<'de> JsonDecode<'de> for Genesis {
                 Some(k) if k == "extraData" => self.header.extra_data.decode_json(p.decoder)?,
                 Some(k) if k == "gasLimit" => self.header.gas_limit.decode_json(p.decoder)?,
                 Some(k) if k == "difficulty" => self.header.difficulty.decode_json(p.decoder)?,
-                Some(k) if k == "mixHash" => self.header.prev_randao.decode_json(p.decoder)?,
+                Some(k) if k == "mixHash" || k == "mixhash" => self.header.prev_randao.decode_json(p.decoder)?,
                 Some(k) if k == "coinbase" => self.header.coinbase.decode_json(p.decoder)?,
                 Some(k) if k == "stateRoot" => self.header.state_root.decode_json(p.decoder)?,
                 Some(k) if k == "alloc" => self.alloc.decode_json(p.decoder)?,
                 Some(k) if k == "number" => self.header.number.decode_json(p.decoder)?,
                 Some(k) if k == "gasUsed" => self.header.gas_used.decode_json(p.decoder)?,
                 Some(k) if k == "parentHash" => self.header.parent_hash.decode_json(p.decoder)?,
+                Some(k) if k == "baseFeePerGas" => self.header.base_fee_per_gas.decode_json(p.decoder)?,
+                Some(k) if k == "config" => self.decode_config(p.decoder)?,
+                // Client specific members.
+                Some(_) => p.skip_value()?,
                 None => return Ok(()),
-                _ => return Err(crate::json::JsonError::MissingKey),
             };
         }
     }
 }
 
+impl JsonEncode for Genesis {
+    fn encode_json(&self, encoder: &mut Encoder) {
+        let mut o = encoder.object();
+        o.key("config");
+        let mut config = o.encoder.object();
+        self.chain_spec.encode_members(&mut config);
+        self.forks.encode_members(&mut config);
+        config.end();
+        o.field("nonce", &self.header.nonce);
+        o.field("timestamp", &self.header.timestamp);
+        o.field("extraData", &self.header.extra_data);
+        o.field("gasLimit", &self.header.gas_limit);
+        o.field("difficulty", &self.header.difficulty);
+        o.field("mixHash", &self.header.prev_randao);
+        o.field("coinbase", &self.header.coinbase);
+        o.field("stateRoot", &self.header.state_root);
+        o.field("alloc", &self.alloc);
+        o.field("number", &self.header.number);
+        o.field("gasUsed", &self.header.gas_used);
+        o.field("parentHash", &self.header.parent_hash);
+        if let Some(base_fee_per_gas) = &self.header.base_fee_per_gas {
+            o.field("baseFeePerGas", base_fee_per_gas);
+        }
+        o.end();
+    }
+}
+
 const MAINNET : &'static str = include_str!("../../assets/mainnet.json");
 
+/// Hash of the mainnet genesis block.
+pub const MAINNET_GENESIS_HASH: Hash32 = Hash32([
+    0xd4, 0xe5, 0x67, 0x40, 0xf8, 0x76, 0xae, 0xf8, 0xc0, 0x10, 0xb8, 0x6a, 0x40, 0xd5, 0xf5, 0x67,
+    0x45, 0xa1, 0x18, 0xd0, 0x90, 0x6a, 0x34, 0xe6, 0x9a, 0xec, 0x8c, 0x0d, 0xb1, 0xcb, 0x8f, 0xa3,
+]);
+
+const SEPOLIA : &'static str = include_str!("../../assets/sepolia.json");
+
+/// Hash of the Sepolia genesis block.
+pub const SEPOLIA_GENESIS_HASH: Hash32 = Hash32([
+    0x25, 0xa5, 0xcc, 0x10, 0x6e, 0xea, 0x71, 0x38, 0xac, 0xab, 0x33, 0x23, 0x1d, 0x71, 0x60, 0xd6,
+    0x9c, 0xb7, 0x77, 0xee, 0x0c, 0x2c, 0x55, 0x3f, 0xcd, 0xdf, 0x51, 0x38, 0x99, 0x3e, 0x6d, 0xd9,
+]);
+
 impl Genesis {
     pub fn mainnet() -> Result<Self, Exception> {
-        let mut g = Genesis::default();
-        let mut cursor = MAINNET.as_bytes();
-        g.decode_json(&mut Decoder::new(cursor))
-            .map_err(|e| Exception::JsonError(e))?;
-        Ok(g)
+        Self::from_json(MAINNET)
+    }
+
+    pub fn sepolia() -> Result<Self, Exception> {
+        Self::from_json(SEPOLIA)
+    }
+
+    /// Read a genesis file in geth format.
+    pub fn from_json(json: &str) -> Result<Self, Exception> {
+        from_json(json.as_bytes()).map_err(Exception::JsonError)
+    }
+
+    /// `from_json` for the contents of the file at `path`.
+    pub fn from_file(path: impl AsRef<Path>) -> Result<Self, Exception> {
+        let json = std::fs::read_to_string(path)
+            .map_err(|_| Exception::EthereumException("cannot read genesis file"))?;
+        Self::from_json(&json)
+    }
+
+    /// A genesis with `forks` and nothing else, for tests and development
+    /// chains: no accounts, a gas limit of 30 million and chain id 1337.
+    /// The builder methods below add the rest.
+    pub fn with_forks(forks: &[(&str, ForkCriteria)]) -> Self {
+        Self {
+            header: Header { gas_limit: 30_000_000, ..Default::default() },
+            chain_spec: ChainSpec { chain_id: 1337, ..ChainSpec::MAINNET },
+            forks: ForkActivations(forks.iter().map(|(name, criteria)| (name.to_string(), *criteria)).collect()),
+            ..Default::default()
+        }
     }
+
+    /// `with_forks` for a chain which is past the merge at genesis: London
+    /// and the merge at block zero, and `forks` at timestamp zero.
+    pub fn post_merge(forks: &[&str]) -> Self {
+        let mut genesis = Self::with_forks(&[
+            ("london", ForkCriteria::ByBlockNumber(0)),
+            ("mergeNetsplit", ForkCriteria::ByBlockNumber(0)),
+        ]);
+        for fork in forks {
+            genesis = genesis.fork(fork, ForkCriteria::ByTimestamp(U256::ZERO));
+        }
+        genesis
+    }
+
+    /// Activate the fork `name` at `criteria`.
+    pub fn fork(mut self, name: &str, criteria: ForkCriteria) -> Self {
+        self.forks.0.insert(name.to_string(), criteria);
+        self
+    }
+
+    /// Add `account` at `address` to the initial state.
+    pub fn account(mut self, address: Address, account: Account) -> Self {
+        self.alloc.insert(address, account);
+        self
+    }
+
+    /// Add a contract with `code` and nonce one at `address`.
+    pub fn contract(self, address: Address, code: Vec<u8>) -> Self {
+        self.account(address, Account { nonce: 1, code: Bytes(code), ..Default::default() })
+    }
+
+    pub fn chain_id(mut self, chain_id: U64) -> Self {
+        self.chain_spec.chain_id = chain_id;
+        self
+    }
+
+    /// Run the chain with `chain_spec`, chain id included.
+    pub fn chain_spec(mut self, chain_spec: ChainSpec) -> Self {
+        self.chain_spec = chain_spec;
+        self
+    }
+
+    pub fn gas_limit(mut self, gas_limit: Uint) -> Self {
+        self.header.gas_limit = gas_limit;
+        self
+    }
+
+    /// Read the `config` section: the chain parameters and fork
+    /// activations. Consensus engine settings and other members are
+    /// ignored.
+    fn decode_config<'de>(&mut self, decoder: &mut Decoder<'de>) -> Result<(), JsonError> {
+        let mut p = ObjectParser::new(decoder);
+        while let Some(key) = p.next_key()? {
+            if !self.chain_spec.decode_member(key, p.decoder)? && !self.forks.decode_member(key, p.decoder)? {
+                p.skip_value()?;
+            }
+        }
+        Ok(())
+    }
+}
+
+/// """
+/// Adds the genesis block to an empty blockchain.
+///
+/// The initial accounts of `genesis` are added to `state`, which should be
+/// empty, and the genesis block committing to them is returned. The roots
+/// of the header are computed rather than taken from the genesis file, and
+/// a `stateRoot` in the file which does not match is an error.
+///
+/// The header has the fields of the forks which are active at genesis
+/// according to the `config` section, as well as those set in
+/// `genesis.header`: London adds a base fee of 1 Gwei unless one is given,
+/// Shanghai adds the root of the empty withdrawals, Cancun zero blob gas
+/// and parent beacon block root, and Prague the hash of no requests.
+/// """
+pub fn add_genesis_block(genesis: &Genesis, state: &mut State) -> Result<Block, Exception> {
+    for (address, account) in &genesis.alloc {
+        cancun::state::set_account(state, address, Some(account.clone()));
+    }
+    let mut header = genesis.header.clone();
+    let computed_state_root = state_root(state)?;
+    if header.state_root != Root::default() && header.state_root != computed_state_root {
+        return Err(Exception::InvalidBlock("genesis state root mismatch"));
+    }
+    header.state_root = computed_state_root;
+    header.ommers_hash = EMPTY_OMMER_HASH;
+    header.transactions_root = EMPTY_TRIE_ROOT;
+    header.receipt_root = EMPTY_TRIE_ROOT;
+    header.bloom = Bloom::default();
+    header.gas_used = 0;
+
+    let active = |fork: &str| genesis.forks.get(fork).check(header.number, header.timestamp);
+    if active("london") {
+        header.base_fee_per_gas.get_or_insert(INITIAL_BASE_FEE);
+    }
+    if active("shanghai") || header.withdrawals_root.is_some() {
+        header.withdrawals_root = Some(EMPTY_TRIE_ROOT);
+    }
+    if active("cancun") {
+        header.blob_gas_used.get_or_insert(0);
+        header.excess_blob_gas.get_or_insert(0);
+        header.parent_beacon_block_root.get_or_insert_with(Root::default);
+    }
+    if active("prague") {
+        header.requests_hash.get_or_insert_with(|| compute_requests_hash(&[]));
+    }
+
+    Ok(Block {
+        withdrawals: header.withdrawals_root.as_ref().map(|_| Vec::new()),
+        header,
+        transactions: Vec::new(),
+        ommers: Vec::new(),
+    })
 }
 
 #[test]
 fn test_mainnet() {
     let g = Genesis::mainnet().unwrap();
-    let chain = BlockChain::from_genesis(g);
-    println!("{chain:?}");
+    assert_eq!(g.chain_spec.chain_id, 1);
+    assert_eq!(g.forks.get("london"), ForkCriteria::ByBlockNumber(12965000));
+    assert_eq!(g.forks.get("cancun"), ForkCriteria::ByTimestamp(U256::from(1710338135_u64)));
+    let chain = BlockChain::from_genesis(g).unwrap();
+    assert_eq!(chain.head_hash(), &MAINNET_GENESIS_HASH);
+    let header = &chain.blocks[0].header;
+    assert_eq!(header.base_fee_per_gas, None);
+    assert_eq!(header.withdrawals_root, None);
+    assert!(BlockChain::mainnet().is_ok());
+}
+
+#[test]
+fn test_sepolia() {
+    let g = Genesis::sepolia().unwrap();
+    assert_eq!(g.chain_spec.chain_id, 11155111);
+    assert_eq!(g.forks.get("mergeNetsplit"), ForkCriteria::ByBlockNumber(1735371));
+    assert_eq!(g.forks.get("cancun"), ForkCriteria::ByTimestamp(U256::from(1706655072_u64)));
+    let chain = BlockChain::from_genesis(g).unwrap();
+    assert_eq!(chain.head_hash(), &SEPOLIA_GENESIS_HASH);
+    assert_eq!(chain.blocks[0].header.base_fee_per_gas, Some(1_000_000_000));
+}
+
+#[test]
+fn test_genesis_block() {
+    let json = r#"{
+        "config": {"chainId": 1337, "londonBlock": 0, "shanghaiTime": 0, "cancunTime": 100},
+        "gasLimit": "0x1c9c380",
+        "difficulty": "0x0",
+        "alloc": {"0x00000000000000000000000000000000000000aa": {"balance": "0x1"}}
+    }"#;
+    let g = Genesis::from_json(json).unwrap();
+    let chain = BlockChain::from_genesis(g).unwrap();
+    let block = &chain.blocks[0];
+    assert_eq!(block.header.base_fee_per_gas, Some(INITIAL_BASE_FEE));
+    assert_eq!(block.header.withdrawals_root, Some(EMPTY_TRIE_ROOT));
+    assert!(block.withdrawals.as_ref().is_some_and(Vec::is_empty));
+    assert_eq!(block.header.blob_gas_used, None);
+    assert_ne!(block.header.state_root, EMPTY_TRIE_ROOT);
+    assert_eq!(block.header.state_root, state_root(&chain.state).unwrap());
+
+    let mut g = Genesis::from_json(json).unwrap();
+    g.header.state_root = Root([1; 32]);
+    assert!(matches!(BlockChain::from_genesis(g), Err(Exception::InvalidBlock(_))));
+}
+
+#[test]
+fn test_unknown_keys() {
+    let json = br#"{
+        "config": {"chainId": 1337, "londonBlock": 0, "clique": {"period": 5, "epoch": 30000}, "terminalTotalDifficultyPassed": true},
+        "nonce": "0x0",
+        "gasLimit": "0x1c9c380",
+        "baseFeePerGas": "0x3b9aca00",
+        "alloc": {},
+        "extra": [1, -2.5e3, "x", null, [], {}]
+    }"#;
+    let mut g = Genesis::default();
+    g.decode_json(&mut Decoder::new(json)).unwrap();
+    assert_eq!(g.chain_spec.chain_id, 1337);
+    assert_eq!(g.forks.get("london"), ForkCriteria::ByBlockNumber(0));
+    assert_eq!(g.header.gas_limit, 30_000_000);
+    assert_eq!(g.header.base_fee_per_gas, Some(1_000_000_000));
+
+    let mut g = Genesis::default();
+    assert!(g.decode_json(&mut Decoder::new(br#"{"config": {"chainId": 1,}}"#)).is_err());
+}
+
+
+#[test]
+fn test_chain_spec() {
+    let json = r#"{
+        "config": {
+            "chainId": 10, "londonBlock": 0, "shanghaiTime": 0, "cancunTime": 0,
+            "blobSchedule": {"cancun": {"target": 6, "max": 9}, "prague": {"target": 6, "max": 9}},
+            "optimism": {"eip1559Elasticity": 6, "eip1559Denominator": 50}
+        },
+        "gasLimit": "0x1c9c380",
+        "alloc": {}
+    }"#;
+    let g = Genesis::from_json(json).unwrap();
+    let expected = ChainSpec {
+        chain_id: 10,
+        elasticity_multiplier: 6,
+        base_fee_max_change_denominator: 50,
+        target_blob_gas_per_block: 6 << 17,
+        max_blob_gas_per_block: 9 << 17,
+        ..ChainSpec::MAINNET
+    };
+    assert_eq!(g.chain_spec, expected);
+    let encoded = crate::json::to_json(&g);
+    assert_eq!(Genesis::from_json(&encoded).unwrap().chain_spec, expected);
+    assert_eq!(BlockChain::from_genesis(g).unwrap().chain_spec, expected);
+
+    // Only the parameters a genesis sets differ from mainnet.
+    let g = Genesis::from_json(r#"{"config": {"chainId": 5}, "alloc": {}}"#).unwrap();
+    assert_eq!(g.chain_spec, ChainSpec { chain_id: 5, ..ChainSpec::MAINNET });
+    assert!(!crate::json::to_json(&g).contains("blobSchedule"));
+}
+
+#[test]
+fn test_from_file() {
+    let path = std::env::temp_dir().join(format!("ejit-genesis-{}.json", std::process::id()));
+    std::fs::write(&path, r#"{"config": {"chainId": 11155111, "shanghaiTime": 1677557088}, "mixhash": "0x01", "alloc": {}}"#).unwrap();
+    let g = Genesis::from_file(&path).unwrap();
+    std::fs::remove_file(&path).unwrap();
+    assert_eq!(g.chain_spec.chain_id, 11155111);
+    assert_eq!(g.forks.get("shanghai"), ForkCriteria::ByTimestamp(U256::from(1677557088_u64)));
+    assert_eq!(g.header.prev_randao.0[31], 1);
+    assert!(Genesis::from_file(&path).is_err());
 }
 
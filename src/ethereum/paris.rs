@@ -0,0 +1,8 @@
+//! The Paris fork, the merge.
+//!
+//! Paris replaced proof of work with the beacon chain: headers have zero
+//! difficulty and nonce and no ommers, `mixHash` holds the RANDAO value of
+//! the beacon chain, read with `PREVRANDAO` (EIP-4399), and no block reward
+//! is paid (EIP-3675). Transactions execute as in London.
+
+pub mod fork;
@@ -0,0 +1,32 @@
+//! Block validation under the rules of Paris.
+
+use crate::ethereum::{
+    cancun::{
+        blocks::Block,
+        fork::{process_block, BlockChain},
+    },
+    exceptions::Exception,
+    forks::{ExecutionRules, Fork, HeaderConstraints},
+};
+
+/// The rules of the Paris fork.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct Paris;
+
+impl Fork for Paris {
+    fn name(&self) -> &'static str {
+        "paris"
+    }
+
+    fn header_constraints(&self) -> HeaderConstraints {
+        HeaderConstraints::PARIS
+    }
+
+    fn execution_rules(&self) -> ExecutionRules {
+        ExecutionRules::PARIS
+    }
+
+    fn state_transition(&self, chain: &mut BlockChain, block: Block) -> Result<(), Exception> {
+        process_block(self, chain, block)
+    }
+}
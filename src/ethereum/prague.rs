@@ -0,0 +1,25 @@
+//! The Prague fork, Pectra on the execution layer.
+//!
+//! Prague adds set code transactions (EIP-7702), which let externally owned
+//! accounts delegate to the code of a contract, and the requests of
+//! EIP-7685: deposits (EIP-6110), withdrawals (EIP-7002) and consolidations
+//! (EIP-7251) triggered on the execution layer for the consensus layer. The
+//! hashes of recent blocks are kept in the state by a system contract
+//! (EIP-2935).
+//! Like Shanghai it shares Cancun's blocks, state and interpreter, which
+//! consult the [`ExecutionRules`] of [`Prague`] for what it adds.
+//!
+//! The fork is not complete: it lacks the calldata floor cost (EIP-7623),
+//! the BLS12-381 precompiles (EIP-2537) and the blob schedule of Prague, so
+//! [`fork_rules`] does not return it and chains which schedule it reject
+//! its blocks as [`Exception::UnsupportedFork`].
+//!
+//! [`ExecutionRules`]: crate::ethereum::forks::ExecutionRules
+//! [`Prague`]: fork::Prague
+//! [`fork_rules`]: crate::ethereum::forks::fork_rules
+//! [`Exception::UnsupportedFork`]: crate::ethereum::exceptions::Exception::UnsupportedFork
+
+pub mod eoa_delegation;
+pub mod fork;
+pub mod requests;
+pub mod transactions;
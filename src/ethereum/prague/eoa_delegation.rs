@@ -0,0 +1,200 @@
+//! https://github.com/ethereum/execution-specs/blob/master/src/ethereum/prague/vm/eoa_delegation.py
+//!
+//! Set EOA account code (EIP-7702).
+
+use crate::ethereum::{
+    cancun::{
+        fork_types::Address,
+        state::{account_exists, get_account, increment_nonce, set_code},
+        vm::{
+            gas::{GAS_COLD_ACCOUNT_ACCESS, GAS_WARM_ACCESS},
+            Environment, Evm, Message,
+        },
+    },
+    crypto::{
+        eliptic_curve::{secp256k1_recover, SECP256K1N},
+        hash::keccak256,
+    },
+    ethereum_types::{
+        bytes::Bytes,
+        numeric::{Uint, U256, U64},
+    },
+    exceptions::Exception,
+};
+
+use super::transactions::{signing_hash_authorization, Authorization, PER_AUTH_BASE_COST, PER_EMPTY_ACCOUNT_COST};
+
+pub const EOA_DELEGATION_MARKER: [u8; 3] = [0xef, 0x01, 0x00];
+pub const EOA_DELEGATED_CODE_LENGTH: usize = 23;
+
+/// Whether `code` is a delegation designator, the marker followed by the
+/// address delegated to.
+///
+/// Parameters
+/// ----------
+/// code :
+///     The code to check.
+///
+/// Returns
+/// -------
+/// valid : `bool`
+///     True if the code is a valid delegation designator.
+pub fn is_valid_delegation(code: &[u8]) -> bool {
+    code.len() == EOA_DELEGATED_CODE_LENGTH && code.starts_with(&EOA_DELEGATION_MARKER)
+}
+
+/// Get the address to which the code delegates.
+///
+/// Parameters
+/// ----------
+/// code :
+///     The code of an account.
+///
+/// Returns
+/// -------
+/// address : `Optional[Address]`
+///     The address of the delegated code, if `code` is a delegation.
+pub fn get_delegated_code_address(code: &[u8]) -> Option<Address> {
+    is_valid_delegation(code)
+        .then(|| Address::from_be_bytes(code[EOA_DELEGATION_MARKER.len()..].try_into().unwrap()))
+}
+
+/// Recover the authority address from an authorization.
+///
+/// Parameters
+/// ----------
+/// authorization :
+///     The authorization to recover the authority from.
+///
+/// Returns
+/// -------
+/// authority : `Address`
+///     The recovered authority address.
+pub fn recover_authority(authorization: &Authorization) -> Result<Address, Exception> {
+    let (r, s) = (authorization.r, authorization.s);
+    if authorization.y_parity > 1 {
+        return Err(Exception::InvalidSignatureError("bad y_parity"));
+    }
+    if r.is_zero() || r >= SECP256K1N {
+        return Err(Exception::InvalidSignatureError("bad r"));
+    }
+    if s.is_zero() || s > SECP256K1N.shr(1) {
+        return Err(Exception::InvalidSignatureError("bad s"));
+    }
+    let public_key = secp256k1_recover(
+        r, s, U256::from(authorization.y_parity as u32), signing_hash_authorization(authorization)?
+    )?;
+    Ok(Address::from_be_bytes(keccak256(&public_key)[12..32].try_into().unwrap()))
+}
+
+/// Get the code an instruction executes when calling `address`, and charge
+/// for accessing the account delegated to.
+///
+/// Parameters
+/// ----------
+/// evm :
+///     The current EVM frame.
+/// address :
+///     The address called.
+///
+/// Returns
+/// -------
+/// delegation : `Tuple[bool, Address, Uint]`
+///     Whether `address` is delegated, which disables precompiles, the
+///     address whose code runs and the gas cost of the delegation.
+pub fn access_delegation(evm: &mut Evm, address: &Address) -> (bool, Address, Uint) {
+    let delegated = get_delegated_code_address(&get_account(evm.env.state, address).code)
+        .filter(|_| evm.env.rules.set_code);
+    match delegated {
+        None => (false, address.clone(), 0),
+        Some(delegated) => {
+            let cost = if evm.accessed_addresses.insert(delegated.clone()) {
+                GAS_COLD_ACCOUNT_ACCESS
+            } else {
+                GAS_WARM_ACCESS
+            };
+            (true, delegated, cost)
+        }
+    }
+}
+
+/// Set the delegation code for the authorities in `authorizations`.
+///
+/// Authorizations which are not valid are skipped.
+///
+/// Parameters
+/// ----------
+/// env :
+///     Environment of the transaction.
+/// message :
+///     The message of the transaction, whose code is reloaded afterwards.
+/// authorizations :
+///     The authorizations of the transaction.
+///
+/// Returns
+/// -------
+/// refund_counter: `U256`
+///     Refund from authority which already exists in state.
+pub fn set_delegation(
+    env: &mut Environment, message: &mut Message, authorizations: &[Authorization]
+) -> Result<U256, Exception> {
+    let mut refund_counter = U256::ZERO;
+    for auth in authorizations {
+        if !auth.chain_id.is_zero() && auth.chain_id != U256::from(env.chain_id) {
+            continue;
+        }
+        if auth.nonce == U64::MAX {
+            continue;
+        }
+        let Ok(authority) = recover_authority(auth) else {
+            continue;
+        };
+
+        message.accessed_addresses.insert(authority.clone());
+
+        let authority_account = get_account(env.state, &authority);
+        if !authority_account.code.is_empty() && !is_valid_delegation(&authority_account.code) {
+            continue;
+        }
+        if authority_account.nonce != auth.nonce as Uint {
+            continue;
+        }
+
+        if account_exists(env.state, &authority) {
+            refund_counter = refund_counter + U256::from_uint(PER_EMPTY_ACCOUNT_COST - PER_AUTH_BASE_COST);
+        }
+
+        let code_to_set = if auth.address == Address::default() {
+            Bytes::default()
+        } else {
+            Bytes([&EOA_DELEGATION_MARKER[..], &auth.address[..]].concat())
+        };
+        set_code(env.state, &authority, code_to_set);
+        increment_nonce(env.state, &authority);
+    }
+
+    let code_address = message.code_address.as_ref()
+        .ok_or(Exception::InvalidBlock("invalid type 4 transaction: no target"))?;
+    message.code = get_account(env.state, code_address).code.clone();
+    Ok(refund_counter)
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::ethereum::cancun::fork_types::Address;
+
+    use super::{get_delegated_code_address, is_valid_delegation, EOA_DELEGATION_MARKER};
+
+    #[test]
+    fn delegation_designator() {
+        let target = Address::from_be_bytes([0x42; 20]);
+        let code = [&EOA_DELEGATION_MARKER[..], &target[..]].concat();
+        assert!(is_valid_delegation(&code));
+        assert_eq!(get_delegated_code_address(&code), Some(target));
+
+        assert!(!is_valid_delegation(&code[..22]));
+        assert!(!is_valid_delegation(&[&code[..], &[0]].concat()));
+        assert!(!is_valid_delegation(&[&[0xef, 0x01, 0x01][..], &code[3..]].concat()));
+        assert_eq!(get_delegated_code_address(&[]), None);
+    }
+}
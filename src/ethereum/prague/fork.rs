@@ -0,0 +1,260 @@
+//! Block validation under the rules of Prague.
+//!
+//! Prague blocks go through the same checks and the same execution as
+//! Cancun blocks. Prague's execution rules enable set code transactions,
+//! the call storing the parent hash in the history contract before the
+//! transactions of a block and the collection of requests after them, and
+//! its header constraints require the `requests_hash` they are checked
+//! against.
+
+use crate::ethereum::{
+    cancun::{
+        blocks::Block,
+        fork::{process_block, BlockChain},
+        fork_types::Address,
+    },
+    ethereum_types::numeric::Uint,
+    exceptions::Exception,
+    forks::{ExecutionRules, Fork, HeaderConstraints},
+};
+
+/// The contract which keeps the hashes of recent blocks (EIP-2935).
+pub const HISTORY_STORAGE_ADDRESS: Address = Address::from_be_bytes([
+    0x00, 0x00, 0xF9, 0x08, 0x27, 0xF1, 0xC5, 0x3a, 0x10, 0xcb, 0x7A, 0x02, 0x33, 0x5B, 0x17, 0x53,
+    0x20, 0x00, 0x29, 0x35,
+]);
+/// Number of block hashes the history contract serves.
+pub const HISTORY_SERVE_WINDOW: Uint = 8191;
+
+/// The rules of the Prague fork.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct Prague;
+
+impl Fork for Prague {
+    fn name(&self) -> &'static str {
+        "prague"
+    }
+
+    fn header_constraints(&self) -> HeaderConstraints {
+        HeaderConstraints::PRAGUE
+    }
+
+    fn execution_rules(&self) -> ExecutionRules {
+        ExecutionRules::PRAGUE
+    }
+
+    fn state_transition(&self, chain: &mut BlockChain, block: Block) -> Result<(), Exception> {
+        process_block(self, chain, block)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::ethereum::{
+        cancun::{
+            fork::{compute_header_hash, state_transition, BlockChain},
+            fork_types::{Account, Address},
+            state::{get_account, get_storage, set_account, set_code},
+            transactions::Transaction,
+        },
+        crypto::{
+            eliptic_curve::{secp256k1_public_key, secp256k1_sign, SECP256K1N},
+            hash::keccak256,
+        },
+        ethereum_types::{bytes::{Bytes, Bytes32}, numeric::U256},
+        exceptions::Exception,
+        forks::Fork,
+        genesis::Genesis,
+        prague::{
+            eoa_delegation::EOA_DELEGATION_MARKER,
+            requests::{compute_requests_hash, CONSOLIDATION_REQUEST_PREDEPLOY_ADDRESS, WITHDRAWAL_REQUEST_PREDEPLOY_ADDRESS},
+            transactions::{signing_hash_7702, signing_hash_authorization, Authorization, SetCodeTransaction},
+        },
+        shanghai::fork::tests::next_block,
+    };
+
+    use super::{Prague, HISTORY_STORAGE_ADDRESS};
+
+    const CHAIN_ID: u64 = 1337;
+
+    fn address_of(secret_key: U256) -> Address {
+        let public_key = secp256k1_public_key(secret_key);
+        Address::from_be_bytes(keccak256(&public_key)[12..].try_into().unwrap())
+    }
+
+    /// A chain which is Prague from genesis, with request contracts which
+    /// return no withdrawal request and a consolidation request of `0xaa`,
+    /// a funded account for `alice` and a contract which stores the block
+    /// number.
+    fn chain(alice: U256, contract: &Address) -> BlockChain {
+        let genesis = Genesis::post_merge(&["shanghai", "cancun", "prague"])
+            .chain_id(CHAIN_ID)
+            .account(address_of(alice), Account { balance: U256::from(10_u64.pow(18)), ..Default::default() })
+            // STOP
+            .contract(WITHDRAWAL_REQUEST_PREDEPLOY_ADDRESS, vec![0x00])
+            // PUSH1 0xaa PUSH1 0 MSTORE8 PUSH1 1 PUSH1 0 RETURN
+            .contract(CONSOLIDATION_REQUEST_PREDEPLOY_ADDRESS, vec![0x60, 0xaa, 0x60, 0x00, 0x53, 0x60, 0x01, 0x60, 0x00, 0xf3])
+            // NUMBER PUSH1 0 SSTORE STOP
+            .contract(contract.clone(), vec![0x43, 0x60, 0x00, 0x55, 0x00]);
+        BlockChain::from_genesis(genesis).unwrap()
+    }
+
+    #[test]
+    fn unsupported() {
+        let contract = Address::from_be_bytes([0xcc; 20]);
+        let mut chain = chain(U256::ONE, &contract);
+        let block = next_block(&chain, &Prague, 12, Vec::new());
+        assert!(matches!(state_transition(&mut chain, block), Err(Exception::UnsupportedFork("prague"))));
+    }
+
+    #[test]
+    fn requests() {
+        let contract = Address::from_be_bytes([0xcc; 20]);
+        let mut chain = chain(U256::ONE, &contract);
+        assert_eq!(chain.blocks[0].header.requests_hash, Some(compute_requests_hash(&[])));
+
+        let block = next_block(&chain, &Prague, 12, Vec::new());
+        assert_eq!(block.header.requests_hash, Some(compute_requests_hash(&[Bytes(vec![0x02, 0xaa])])));
+
+        // The request contracts must have code.
+        let mut without_contract = chain.clone();
+        set_code(&mut without_contract.state, &WITHDRAWAL_REQUEST_PREDEPLOY_ADDRESS, Bytes::default());
+        assert!(matches!(
+            Prague.state_transition(&mut without_contract, block.clone()),
+            Err(Exception::InvalidBlock("system contract has no code"))
+        ));
+
+        let mut wrong_hash = block.clone();
+        wrong_hash.header.requests_hash = Some(compute_requests_hash(&[]));
+        assert!(Prague.state_transition(&mut chain.clone(), wrong_hash).is_err());
+
+        Prague.state_transition(&mut chain, block).unwrap();
+    }
+
+    #[test]
+    fn block_hash_history() {
+        let contract = Address::from_be_bytes([0xcc; 20]);
+        let mut chain = chain(U256::ONE, &contract);
+        // Stores the hash it is called with under the number of the parent:
+        // PUSH1 0 CALLDATALOAD PUSH1 1 NUMBER SUB SSTORE STOP
+        let code = Bytes(vec![0x60, 0x00, 0x35, 0x60, 0x01, 0x43, 0x03, 0x55, 0x00]);
+        set_account(&mut chain.state, &HISTORY_STORAGE_ADDRESS, Some(Account { nonce: 1, code, ..Default::default() }));
+
+        let genesis_hash = chain.head_hash().clone();
+        let block = next_block(&chain, &Prague, 12, Vec::new());
+        Prague.state_transition(&mut chain, block).unwrap();
+        let stored = get_storage(&chain.state, &HISTORY_STORAGE_ADDRESS, &Bytes32([0; 32]));
+        assert_eq!(stored.to_be_bytes(), genesis_hash.0);
+
+        // Without the contract, the call does nothing.
+        set_account(&mut chain.state, &HISTORY_STORAGE_ADDRESS, None);
+        let block = next_block(&chain, &Prague, 24, Vec::new());
+        Prague.state_transition(&mut chain, block).unwrap();
+    }
+
+    /// An authorization by `authority` to delegate to `address`.
+    fn authorize(authority: U256, chain_id: u64, address: &Address, nonce: u64) -> Authorization {
+        let mut auth = Authorization { chain_id: U256::from(chain_id), address: address.clone(), nonce, ..Default::default() };
+        let (r, s, v) = secp256k1_sign(signing_hash_authorization(&auth).unwrap(), authority).unwrap();
+        (auth.r, auth.s, auth.y_parity) = (r, s, v.low_u64() as u8);
+        auth
+    }
+
+    /// A set code transaction sent by `sender` with nonce `nonce`.
+    fn set_code_transaction_to(sender: U256, nonce: u64, to: Address, authorizations: Vec<Authorization>) -> Transaction {
+        let mut tx = SetCodeTransaction {
+            chain_id: CHAIN_ID,
+            nonce: U256::from(nonce),
+            max_priority_fee_per_gas: 1,
+            max_fee_per_gas: 2_000_000_000,
+            gas: 200_000,
+            to,
+            authorizations,
+            ..Default::default()
+        };
+        let (r, s, v) = secp256k1_sign(signing_hash_7702(&tx).unwrap(), sender).unwrap();
+        (tx.r, tx.s, tx.y_parity) = (r, s, v);
+        Transaction::SetCodeTransaction(tx)
+    }
+
+    #[test]
+    fn set_code_transaction() {
+        let (alice, bob) = (U256::from(0xa11ce_u32), U256::from(0xb0b_u32));
+        let contract = Address::from_be_bytes([0xcc; 20]);
+        let mut chain = chain(alice, &contract);
+
+        // Bob delegates to the contract and Alice pays for it.
+        let auth = authorize(bob, CHAIN_ID, &contract, 0);
+        let tx = set_code_transaction_to(alice, 0, address_of(bob), vec![auth]);
+
+        let block = next_block(&chain, &Prague, 12, vec![tx]);
+        let hash = compute_header_hash(&block.header).unwrap();
+        Prague.state_transition(&mut chain, block).unwrap();
+
+        let bob = address_of(bob);
+        let account = get_account(&chain.state, &bob);
+        assert_eq!(account.code.0, [&EOA_DELEGATION_MARKER[..], &contract[..]].concat());
+        assert_eq!(account.nonce, 1);
+        // The contract's code ran with Bob's storage.
+        assert_eq!(get_storage(&chain.state, &bob, &Bytes32::default()), U256::ONE);
+        assert_eq!(get_storage(&chain.state, &contract, &Bytes32::default()), U256::ZERO);
+
+        let receipts = chain.block_receipts(&hash).unwrap();
+        assert!(receipts[0].receipt.receipt.succeeded);
+        assert_eq!(receipts[0].receipt.transaction_type, 4);
+    }
+
+    #[test]
+    fn invalid_authorizations_are_skipped() {
+        let (alice, bob) = (U256::from(0xa11ce_u32), U256::from(0xb0b_u32));
+        let contract = Address::from_be_bytes([0xcc; 20]);
+        let mut chain = chain(alice, &contract);
+
+        let mut high_s = authorize(bob, CHAIN_ID, &contract, 0);
+        high_s.s = SECP256K1N - high_s.s;
+        let authorizations = vec![
+            authorize(bob, CHAIN_ID + 1, &contract, 0),
+            authorize(bob, CHAIN_ID, &contract, 1),
+            high_s,
+            // Valid on any chain.
+            authorize(bob, 0, &contract, 0),
+            // Bob now exists, which refunds part of the cost of this one,
+            // and delegating to zero clears the delegation.
+            authorize(bob, CHAIN_ID, &Address::default(), 1),
+            authorize(bob, CHAIN_ID, &contract, 1),
+        ];
+        let tx = set_code_transaction_to(alice, 0, contract.clone(), authorizations);
+        let block = next_block(&chain, &Prague, 12, vec![tx]);
+        let hash = compute_header_hash(&block.header).unwrap();
+        Prague.state_transition(&mut chain, block).unwrap();
+
+        let account = get_account(&chain.state, &address_of(bob));
+        assert!(account.code.is_empty());
+        assert_eq!(account.nonce, 2);
+
+        // 21000 and 25000 per authorization, less 12500 for Bob existing,
+        // plus the contract's NUMBER PUSH1 0 SSTORE.
+        let receipts = chain.block_receipts(&hash).unwrap();
+        assert_eq!(receipts[0].receipt.gas_used, 21_000 + 6 * 25_000 + 2 + 3 + 22_100 - 12_500);
+    }
+
+    #[test]
+    fn call_to_delegated_account() {
+        let (alice, bob) = (U256::from(0xa11ce_u32), U256::from(0xb0b_u32));
+        let contract = Address::from_be_bytes([0xcc; 20]);
+        let mut chain = chain(alice, &contract);
+
+        // PUSH1 0 PUSH1 0 PUSH1 0 PUSH1 0 PUSH1 0 PUSH20 bob GAS CALL STOP
+        let caller = Address::from_be_bytes([0xca; 20]);
+        let code = [&[0x60, 0x00, 0x60, 0x00, 0x60, 0x00, 0x60, 0x00, 0x60, 0x00, 0x73][..], &address_of(bob)[..], &[0x5a, 0xf1, 0x00]].concat();
+        set_account(&mut chain.state, &caller, Some(Account { nonce: 1, code: Bytes(code), ..Default::default() }));
+
+        let tx = set_code_transaction_to(alice, 0, caller, vec![authorize(bob, CHAIN_ID, &contract, 0)]);
+        let block = next_block(&chain, &Prague, 12, vec![tx]);
+        Prague.state_transition(&mut chain, block).unwrap();
+
+        // The delegated code ran in Bob's account.
+        assert_eq!(get_storage(&chain.state, &address_of(bob), &Bytes32::default()), U256::ONE);
+        assert_eq!(get_storage(&chain.state, &contract, &Bytes32::default()), U256::ZERO);
+    }
+}
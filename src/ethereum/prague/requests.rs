@@ -0,0 +1,169 @@
+//! https://github.com/ethereum/execution-specs/blob/master/src/ethereum/prague/requests.py
+//!
+//! Requests were introduced in EIP-7685 as a general purpose framework for
+//! storing contract-triggered requests. It extends the execution header and
+//! body with a single field each to store the request information.
+//! This inherently exposes the requests to the consensus layer, which can
+//! then process each one.
+
+use crate::ethereum::{
+    cancun::{blocks::Log, fork_types::Address},
+    crypto::hash::{sha256, Hash32},
+    ethereum_types::bytes::Bytes,
+    exceptions::Exception,
+};
+
+pub const DEPOSIT_CONTRACT_ADDRESS: Address = Address::from_be_bytes([
+    0x00, 0x00, 0x00, 0x00, 0x21, 0x9a, 0xb5, 0x40, 0x35, 0x6c, 0xbb, 0x83, 0x9c, 0xbe, 0x05, 0x30,
+    0x3d, 0x77, 0x05, 0xfa,
+]);
+pub const DEPOSIT_EVENT_SIGNATURE_HASH: Hash32 = Hash32([
+    0x64, 0x9b, 0xbc, 0x62, 0xd0, 0xe3, 0x13, 0x42, 0xaf, 0xea, 0x4e, 0x5c, 0xd8, 0x2d, 0x40, 0x49,
+    0xe7, 0xe1, 0xee, 0x91, 0x2f, 0xc0, 0x88, 0x9a, 0xa7, 0x90, 0x80, 0x3b, 0xe3, 0x90, 0x38, 0xc5,
+]);
+pub const WITHDRAWAL_REQUEST_PREDEPLOY_ADDRESS: Address = Address::from_be_bytes([
+    0x00, 0x00, 0x09, 0x61, 0xef, 0x48, 0x0e, 0xb5, 0x5e, 0x80, 0xd1, 0x9a, 0xd8, 0x35, 0x79, 0xa6,
+    0x4c, 0x00, 0x70, 0x02,
+]);
+pub const CONSOLIDATION_REQUEST_PREDEPLOY_ADDRESS: Address = Address::from_be_bytes([
+    0x00, 0x00, 0xbb, 0xdd, 0xc7, 0xce, 0x48, 0x86, 0x42, 0xfb, 0x57, 0x9f, 0x8b, 0x00, 0xf3, 0xa5,
+    0x90, 0x00, 0x72, 0x51,
+]);
+
+pub const DEPOSIT_REQUEST_TYPE: u8 = 0x00;
+pub const WITHDRAWAL_REQUEST_TYPE: u8 = 0x01;
+pub const CONSOLIDATION_REQUEST_TYPE: u8 = 0x02;
+
+const DEPOSIT_EVENT_LENGTH: usize = 576;
+
+/// Offset and size of each field of the deposit event, in the order they
+/// are concatenated into a deposit request.
+const DEPOSIT_FIELDS: [(usize, usize); 5] = [
+    // pubkey
+    (160, 48),
+    // withdrawal_credentials
+    (256, 32),
+    // amount
+    (320, 8),
+    // signature
+    (384, 96),
+    // index
+    (512, 8),
+];
+
+/// Extracts deposit data from the log data of a deposit event.
+///
+/// The data is the ABI encoding of five byte strings. Each head must point
+/// at the expected offset and each string must have the expected length.
+pub fn extract_deposit_data(data: &[u8]) -> Result<Bytes, Exception> {
+    if data.len() != DEPOSIT_EVENT_LENGTH {
+        return Err(Exception::InvalidBlock("invalid deposit event data length"));
+    }
+    let word = |at: usize| -> Option<usize> {
+        let word = &data[at..at + 32];
+        word[..24].iter().all(|b| *b == 0).then(|| u64::from_be_bytes(word[24..].try_into().unwrap()) as usize)
+    };
+
+    let mut request = Vec::with_capacity(192);
+    for (i, (offset, size)) in DEPOSIT_FIELDS.into_iter().enumerate() {
+        if word(32 * i) != Some(offset) {
+            return Err(Exception::InvalidBlock("invalid deposit event offset"));
+        }
+        if word(offset) != Some(size) {
+            return Err(Exception::InvalidBlock("invalid deposit event field size"));
+        }
+        request.extend_from_slice(&data[offset + 32..offset + 32 + size]);
+    }
+    Ok(Bytes(request))
+}
+
+/// Parse deposit requests from the logs of a block.
+pub fn parse_deposit_requests(logs: &[Log]) -> Result<Bytes, Exception> {
+    let mut deposit_requests = Vec::new();
+    for log in logs {
+        if log.address == DEPOSIT_CONTRACT_ADDRESS && log.topics.first() == Some(&DEPOSIT_EVENT_SIGNATURE_HASH) {
+            deposit_requests.extend_from_slice(&extract_deposit_data(&log.data)?);
+        }
+    }
+    Ok(Bytes(deposit_requests))
+}
+
+/// Get the hash of the requests using the SHA2-256 algorithm.
+///
+/// Parameters
+/// ----------
+/// requests : `Bytes`
+///     The requests to hash, each its type byte followed by its data.
+///
+/// Returns
+/// -------
+/// requests_hash : `Bytes`
+///     The hash of the requests.
+pub fn compute_requests_hash(requests: &[Bytes]) -> Hash32 {
+    let mut hashes = Vec::with_capacity(32 * requests.len());
+    for request in requests {
+        hashes.extend_from_slice(&sha256(request)[..]);
+    }
+    sha256(&hashes)
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::ethereum::{
+        cancun::{blocks::Log, fork_types::Address},
+        crypto::hash::Hash32,
+        ethereum_types::bytes::Bytes,
+        utils::hexadecimal::hex_to_slice,
+    };
+
+    use super::{
+        compute_requests_hash, parse_deposit_requests, DEPOSIT_CONTRACT_ADDRESS, DEPOSIT_EVENT_SIGNATURE_HASH,
+        DEPOSIT_FIELDS,
+    };
+
+    /// The log data of a deposit whose fields are filled with `1..=5`.
+    fn deposit_event() -> Vec<u8> {
+        let mut data = vec![0; 576];
+        let mut word = |at: usize, value: usize| data[at + 24..at + 32].copy_from_slice(&(value as u64).to_be_bytes());
+        for (i, (offset, size)) in DEPOSIT_FIELDS.into_iter().enumerate() {
+            word(32 * i, offset);
+            word(offset, size);
+        }
+        for (i, (offset, size)) in DEPOSIT_FIELDS.into_iter().enumerate() {
+            data[offset + 32..offset + 32 + size].fill(i as u8 + 1);
+        }
+        data
+    }
+
+    #[test]
+    fn deposits() {
+        let log = Log {
+            address: DEPOSIT_CONTRACT_ADDRESS,
+            topics: vec![DEPOSIT_EVENT_SIGNATURE_HASH],
+            data: Bytes(deposit_event()),
+        };
+        // The same event from another contract is not a deposit.
+        let elsewhere = Log { address: Address::default(), ..log.clone() };
+
+        let requests = parse_deposit_requests(&[log.clone(), elsewhere]).unwrap();
+        assert_eq!(requests.len(), 48 + 32 + 8 + 96 + 8);
+        assert!(requests[..48].iter().all(|b| *b == 1));
+        assert!(requests[184..].iter().all(|b| *b == 5));
+
+        let mut bad_size = log.clone();
+        bad_size.data.0[160 + 31] = 47;
+        assert!(parse_deposit_requests(&[bad_size]).is_err());
+        let mut bad_offset = log;
+        bad_offset.data.0[31] = 0;
+        assert!(parse_deposit_requests(&[bad_offset]).is_err());
+    }
+
+    #[test]
+    fn requests_hash() {
+        // sha256(b"")
+        let mut empty = Hash32::default();
+        hex_to_slice(&mut empty.0, "e3b0c44298fc1c149afbf4c8996fb92427ae41e4649b934ca495991b7852b855").unwrap();
+        assert_eq!(compute_requests_hash(&[]), empty);
+        assert_ne!(compute_requests_hash(&[Bytes(vec![1, 2])]), compute_requests_hash(&[]));
+    }
+}
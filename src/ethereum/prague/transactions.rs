@@ -0,0 +1,103 @@
+//! The set code transaction of EIP-7702, which lets externally owned
+//! accounts delegate to the code of a contract.
+//!
+//! The other transaction types are those of Cancun. Type-4 transactions are
+//! a variant of `cancun::transactions::Transaction`, which the earlier forks
+//! reject through their `ExecutionRules`.
+
+use crate::{
+    ethereum::{
+        cancun::fork_types::Address,
+        crypto::hash::{keccak256, Hash32},
+        ethereum_rlp::rlp,
+        ethereum_types::{
+            bytes::{Bytes, Bytes32},
+            numeric::{Uint, U256, U64, U8},
+        },
+        exceptions::Exception,
+    },
+    impl_extended,
+};
+
+/// Intrinsic gas per authorization, which is what creating the account of
+/// the authority would cost.
+pub const PER_EMPTY_ACCOUNT_COST: Uint = 25000;
+
+/// The part of `PER_EMPTY_ACCOUNT_COST` which is kept when the authority
+/// already exists.
+pub const PER_AUTH_BASE_COST: Uint = 12500;
+
+/// Prefix of the message signed by an authorization.
+pub const SET_CODE_TX_MAGIC: u8 = 0x05;
+
+/// Permission for the account which signed it to be given the code of
+/// `address`.
+#[derive(Debug, Clone, Default)]
+pub struct Authorization {
+    /// Zero for an authorization valid on any chain.
+    pub chain_id: U256,
+    pub address: Address,
+    pub nonce: U64,
+    pub y_parity: U8,
+    pub r: U256,
+    pub s: U256,
+}
+
+impl_extended!(Authorization : chain_id, address, nonce, y_parity, r, s);
+
+/// The transaction type added in EIP-7702.
+#[derive(Debug, Clone, Default)]
+pub struct SetCodeTransaction {
+    pub chain_id: U64,
+    pub nonce: U256,
+    pub max_priority_fee_per_gas: Uint,
+    pub max_fee_per_gas: Uint,
+    pub gas: Uint,
+    pub to: Address,
+    pub value: U256,
+    pub data: Bytes,
+    pub access_list: Vec<(Address, Vec<Bytes32>)>,
+    pub authorizations: Vec<Authorization>,
+    pub y_parity: U256,
+    pub r: U256,
+    pub s: U256,
+}
+
+impl_extended!(SetCodeTransaction : chain_id, nonce, max_priority_fee_per_gas, max_fee_per_gas, gas, to, value, data, access_list, authorizations, y_parity, r, s);
+
+/// Compute the hash of a transaction used in a EIP-7702 signature.
+/// 
+/// Parameters
+/// ----------
+/// tx :
+///     Transaction of interest.
+/// 
+/// Returns
+/// -------
+/// hash : `ethereum.crypto.hash.Hash32`
+///     Hash of the transaction.
+pub fn signing_hash_7702(tx: &SetCodeTransaction) -> Result<Hash32, Exception> {
+    let mut res = Bytes::default();
+    res.push(4);
+    rlp::encode_sequence(&mut res, &[
+        &tx.chain_id,
+        &tx.nonce,
+        &tx.max_priority_fee_per_gas,
+        &tx.max_fee_per_gas,
+        &tx.gas,
+        &tx.to,
+        &tx.value,
+        &tx.data,
+        &tx.access_list,
+        &tx.authorizations,
+    ])?;
+    Ok(keccak256(&res))
+}
+
+/// Compute the hash signed by the authority of an authorization.
+pub fn signing_hash_authorization(auth: &Authorization) -> Result<Hash32, Exception> {
+    let mut res = Bytes::default();
+    res.push(SET_CODE_TX_MAGIC);
+    rlp::encode_sequence(&mut res, &[&auth.chain_id, &auth.address, &auth.nonce])?;
+    Ok(keccak256(&res))
+}
@@ -0,0 +1,14 @@
+//! The proof of work forks, from Frontier to Gray Glacier.
+//!
+//! Blocks, state and the interpreter are shared with Cancun, which consult
+//! the [`ExecutionRules`] of each fork for what it did not have yet. On top
+//! of the checks of later forks, proof of work blocks have their difficulty
+//! checked against their parent's, may include ommers, and pay a block
+//! reward to their miner and to the miners of their ommers, which each fork
+//! describes with its [`ProofOfWork`].
+//!
+//! [`ExecutionRules`]: crate::ethereum::forks::ExecutionRules
+//! [`ProofOfWork`]: crate::ethereum::cancun::fork::proof_of_work::ProofOfWork
+
+pub mod dao;
+pub mod fork;
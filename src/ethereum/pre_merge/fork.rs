@@ -0,0 +1,316 @@
+//! Block validation under the rules of the proof of work forks.
+//!
+//! Each fork is a unit type whose header constraints, execution rules and
+//! proof of work parameters are those it activated, applied by the shared
+//! `process_block`. Forks which only delayed the difficulty bomb, the
+//! glaciers, keep the execution rules of the fork before them.
+
+use crate::ethereum::{
+    cancun::{
+        blocks::Block,
+        fork::{process_block, proof_of_work::ProofOfWork, BlockChain},
+    },
+    exceptions::Exception,
+    fork_criteria::ForkCriteria,
+    forks::{ExecutionRules, Fork, HeaderConstraints},
+};
+
+use super::dao::apply_dao;
+
+macro_rules! proof_of_work_forks {
+    ($($(#[$doc: meta])* $fork: ident $name: literal => $constraints: ident, $rules: ident, $pow: ident;)*) => {$(
+        $(#[$doc])*
+        #[derive(Debug, Clone, Copy, Default)]
+        pub struct $fork;
+
+        impl Fork for $fork {
+            fn name(&self) -> &'static str {
+                $name
+            }
+
+            fn header_constraints(&self) -> HeaderConstraints {
+                HeaderConstraints::$constraints
+            }
+
+            fn execution_rules(&self) -> ExecutionRules {
+                ExecutionRules::$rules
+            }
+
+            fn proof_of_work(&self) -> Option<ProofOfWork> {
+                Some(ProofOfWork::$pow)
+            }
+
+            fn state_transition(&self, chain: &mut BlockChain, block: Block) -> Result<(), Exception> {
+                process_block(self, chain, block)
+            }
+        }
+    )*};
+}
+
+proof_of_work_forks! {
+    /// The rules of Frontier, the launch of mainnet.
+    Frontier "frontier" => FRONTIER, FRONTIER, FRONTIER;
+    /// The rules of the Homestead fork.
+    Homestead "homestead" => FRONTIER, HOMESTEAD, HOMESTEAD;
+    /// The rules of the Tangerine Whistle fork.
+    TangerineWhistle "tangerine_whistle" => FRONTIER, TANGERINE_WHISTLE, HOMESTEAD;
+    /// The rules of the Spurious Dragon fork.
+    SpuriousDragon "spurious_dragon" => FRONTIER, SPURIOUS_DRAGON, HOMESTEAD;
+    /// The rules of the Byzantium fork.
+    Byzantium "byzantium" => FRONTIER, BYZANTIUM, BYZANTIUM;
+    /// The rules of the Constantinople fork, as Petersburg.
+    Constantinople "constantinople" => FRONTIER, CONSTANTINOPLE, CONSTANTINOPLE;
+    /// The rules of the Istanbul fork.
+    Istanbul "istanbul" => FRONTIER, ISTANBUL, CONSTANTINOPLE;
+    /// The rules of the Muir Glacier fork.
+    MuirGlacier "muir_glacier" => FRONTIER, ISTANBUL, MUIR_GLACIER;
+    /// The rules of the Berlin fork.
+    Berlin "berlin" => FRONTIER, BERLIN, MUIR_GLACIER;
+    /// The rules of the London fork.
+    London "london" => LONDON, LONDON, LONDON;
+    /// The rules of the Arrow Glacier fork.
+    ArrowGlacier "arrow_glacier" => LONDON, LONDON, ARROW_GLACIER;
+    /// The rules of the Gray Glacier fork.
+    GrayGlacier "gray_glacier" => LONDON, LONDON, GRAY_GLACIER;
+}
+
+/// The rules of the DAO fork, which are those of Homestead.
+///
+/// The fork block itself moves the balances of the DAO and its child
+/// contracts to a refund contract before its transactions, and it and the
+/// nine blocks after it must have "dao-hard-fork" as their extra data.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct DaoFork;
+
+impl Fork for DaoFork {
+    fn name(&self) -> &'static str {
+        "dao_fork"
+    }
+
+    fn header_constraints(&self) -> HeaderConstraints {
+        HeaderConstraints::FRONTIER
+    }
+
+    fn execution_rules(&self) -> ExecutionRules {
+        ExecutionRules::HOMESTEAD
+    }
+
+    fn proof_of_work(&self) -> Option<ProofOfWork> {
+        Some(ProofOfWork::HOMESTEAD)
+    }
+
+    fn state_transition(&self, chain: &mut BlockChain, block: Block) -> Result<(), Exception> {
+        if let ForkCriteria::ByBlockNumber(fork_block) = chain.fork_schedule.activation(self.name()) {
+            let number = block.header.number;
+            if number >= fork_block && number < fork_block + 10 && block.header.extra_data.0 != b"dao-hard-fork" {
+                return Err(Exception::InvalidBlock("header.extra_data != b\"dao-hard-fork\""));
+            }
+            if number == fork_block {
+                apply_dao(&mut chain.state);
+            }
+        }
+        process_block(self, chain, block)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::ethereum::{
+        cancun::{
+            blocks::{Block, Header},
+            fork::{
+                apply_body, compute_header_hash, get_last_256_block_hashes, state_transition, BlockChain,
+                EMPTY_OMMER_HASH, INITIAL_BASE_FEE,
+            },
+            fork_types::{Account, Address},
+            state::{get_account, set_account},
+            transactions::{signing_hash_pre155, LegacyTransaction, Transaction},
+        },
+        crypto::{eliptic_curve::{secp256k1_public_key, secp256k1_sign}, hash::keccak256},
+        ethereum_types::{
+            bytes::{Bytes, Bytes32},
+            numeric::U256,
+        },
+        exceptions::Exception,
+        fork_criteria::ForkCriteria,
+        forks::Fork,
+        genesis::Genesis,
+        pre_merge::dao::{apply_dao, DAO_ACCOUNTS, DAO_RECOVERY},
+    };
+
+    use super::{DaoFork, Frontier, Homestead, London};
+
+    const ETHER: u64 = 10_u64.pow(18);
+    const ALICE: U256 = U256::ONE;
+
+    fn address_of(secret_key: U256) -> Address {
+        let public_key = secp256k1_public_key(secret_key);
+        Address::from_be_bytes(keccak256(&public_key)[12..].try_into().unwrap())
+    }
+
+    /// A proof of work chain with the forks of `forks` by block number and
+    /// a funded account for `ALICE`.
+    fn chain(forks: &[(&str, u128)]) -> BlockChain {
+        let forks: Vec<_> = forks.iter().map(|(name, number)| (*name, ForkCriteria::ByBlockNumber(*number))).collect();
+        let mut genesis = Genesis::with_forks(&forks)
+            .chain_id(1)
+            .gas_limit(3_000_000)
+            .account(address_of(ALICE), Account { balance: U256::from(ETHER), ..Default::default() });
+        genesis.header.difficulty = 2_048_000;
+        BlockChain::from_genesis(genesis).unwrap()
+    }
+
+    /// A valid block on the head of `chain` at `timestamp` under `fork`,
+    /// mined by `0xcb..cb`.
+    fn next_block(chain: &BlockChain, fork: &dyn Fork, timestamp: u32, transactions: Vec<Transaction>) -> Block {
+        let parent = &chain.blocks.last().unwrap().header;
+        let pow = fork.proof_of_work().unwrap();
+        let timestamp = U256::from(timestamp);
+        // The first London block has twice the gas limit, for the same gas
+        // target.
+        let base_fee = fork.header_constraints().base_fee.then_some(INITIAL_BASE_FEE);
+        let gas_limit = if base_fee.is_some() { 2 * parent.gas_limit } else { parent.gas_limit };
+        let mut block = Block {
+            header: Header {
+                parent_hash: compute_header_hash(parent).unwrap(),
+                ommers_hash: EMPTY_OMMER_HASH,
+                coinbase: Address::from_be_bytes([0xcb; 20]),
+                number: parent.number + 1,
+                gas_limit,
+                timestamp,
+                difficulty: pow.calculate_block_difficulty(parent.number + 1, timestamp, parent.timestamp, parent.difficulty, false),
+                base_fee_per_gas: base_fee,
+                ..Default::default()
+            },
+            transactions,
+            ommers: Vec::new(),
+            withdrawals: None,
+        };
+
+        let mut state = chain.state.clone();
+        let header = &block.header;
+        let output = apply_body(
+            &mut state,
+            &get_last_256_block_hashes(chain),
+            &header.coinbase,
+            &header.number,
+            &header.base_fee_per_gas,
+            &header.gas_limit,
+            &header.timestamp,
+            &Bytes32(U256::from_uint(header.difficulty).to_be_bytes()),
+            &block.transactions,
+            &block.ommers,
+            Some(pow.block_reward),
+            chain.chain_spec.chain_id,
+            None,
+            &None,
+            &None,
+            fork.execution_rules(),
+            &chain.chain_spec,
+            None,
+        )
+        .unwrap();
+        let header = &mut block.header;
+        header.gas_used = output.block_gas_used;
+        header.transactions_root = output.transactions_root;
+        header.receipt_root = output.receipt_root;
+        header.bloom = output.block_logs_bloom;
+        header.state_root = output.state_root;
+        block
+    }
+
+    /// A transfer of `value` to `to` signed by `ALICE` without a chain id.
+    fn transfer(nonce: u64, to: Address, value: u64) -> Transaction {
+        let mut tx = LegacyTransaction {
+            nonce: U256::from(nonce),
+            gas_price: 10,
+            gas: 21000,
+            to: Some(to).into(),
+            value: U256::from(value),
+            ..Default::default()
+        };
+        let (r, s, v) = secp256k1_sign(signing_hash_pre155(&tx).unwrap(), ALICE).unwrap();
+        (tx.r, tx.s, tx.v) = (r, s, v + U256::from(27_u32));
+        Transaction::LegacyTransaction(tx)
+    }
+
+    #[test]
+    fn frontier_and_homestead() {
+        let mut chain = chain(&[("homestead", 2)]);
+        let (bob, miner) = (Address::from_be_bytes([0xb0; 20]), Address::from_be_bytes([0xcb; 20]));
+
+        let block = next_block(&chain, &Frontier, 10, vec![transfer(0, bob.clone(), 1000)]);
+        // Frontier raises the difficulty of a block within 13 seconds.
+        assert_eq!(block.header.difficulty, 2_048_000 + 1000);
+        let mut wrong_difficulty = block.clone();
+        wrong_difficulty.header.difficulty -= 1;
+        assert!(matches!(
+            state_transition(&mut chain.clone(), wrong_difficulty),
+            Err(Exception::InvalidBlock("header.difficulty != block_difficulty"))
+        ));
+        let mut wrong_reward = block.clone();
+        wrong_reward.header.state_root = chain.blocks[0].header.state_root.clone();
+        assert!(state_transition(&mut chain.clone(), wrong_reward).is_err());
+        state_transition(&mut chain, block).unwrap();
+
+        assert_eq!(get_account(&chain.state, &bob).balance, U256::from(1000_u32));
+        // The block reward and the fees, with no base fee burnt.
+        assert_eq!(get_account(&chain.state, &miner).balance, U256::from(5 * ETHER + 21000 * 10));
+
+        let block = next_block(&chain, &Homestead, 40, Vec::new());
+        // Homestead lowers it by 1/2048 for every 10 seconds after the first 10.
+        assert_eq!(block.header.difficulty, 2_049_000 - 2 * 1000);
+        state_transition(&mut chain, block).unwrap();
+        assert_eq!(get_account(&chain.state, &miner).balance, U256::from(10 * ETHER + 21000 * 10));
+    }
+
+    #[test]
+    fn dao_fork() {
+        let mut chain = chain(&[("homestead", 0), ("daoFork", 2)]);
+        for (i, address) in DAO_ACCOUNTS[..3].iter().enumerate() {
+            set_account(&mut chain.state, address, Some(Account { balance: U256::from(i as u64 + 1), ..Default::default() }));
+        }
+        let block = next_block(&chain, &Homestead, 10, Vec::new());
+        state_transition(&mut chain, block).unwrap();
+
+        // The fork block is built on a state where the balances have moved.
+        let mut forked = chain.clone();
+        apply_dao(&mut forked.state);
+        let mut block = next_block(&forked, &DaoFork, 20, Vec::new());
+        assert!(matches!(
+            state_transition(&mut chain.clone(), block.clone()),
+            Err(Exception::InvalidBlock("header.extra_data != b\"dao-hard-fork\""))
+        ));
+        block.header.extra_data = Bytes(b"dao-hard-fork".to_vec());
+        state_transition(&mut chain, block).unwrap();
+        for address in &DAO_ACCOUNTS[..3] {
+            assert_eq!(get_account(&chain.state, address).balance, U256::ZERO);
+        }
+        assert_eq!(get_account(&chain.state, &DAO_RECOVERY).balance, U256::from(6_u32));
+
+        // The blocks after it move nothing, but still need the extra data.
+        let mut block = next_block(&chain, &DaoFork, 30, Vec::new());
+        assert!(state_transition(&mut chain.clone(), block.clone()).is_err());
+        block.header.extra_data = Bytes(b"dao-hard-fork".to_vec());
+        state_transition(&mut chain, block).unwrap();
+        assert_eq!(get_account(&chain.state, &DAO_RECOVERY).balance, U256::from(6_u32));
+    }
+
+    #[test]
+    fn london_transition() {
+        let forks = ["homestead", "eip150", "eip158", "byzantium", "petersburg", "istanbul", "berlin"].map(|key| (key, 0));
+        let mut chain = chain(&[&forks[..], &[("london", 1)]].concat());
+        assert!(chain.blocks[0].header.base_fee_per_gas.is_none());
+
+        let block = next_block(&chain, &London, 10, Vec::new());
+        assert_eq!(block.header.gas_limit, 6_000_000);
+        let mut wrong_base_fee = block.clone();
+        wrong_base_fee.header.base_fee_per_gas = Some(INITIAL_BASE_FEE + 1);
+        assert!(matches!(
+            state_transition(&mut chain.clone(), wrong_base_fee),
+            Err(Exception::InvalidBlock("header.base_fee_per_gas != INITIAL_BASE_FEE"))
+        ));
+        state_transition(&mut chain, block).unwrap();
+        assert_eq!(chain.blocks[1].header.base_fee_per_gas, Some(INITIAL_BASE_FEE));
+    }
+}
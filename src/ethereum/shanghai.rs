@@ -0,0 +1,13 @@
+//! The Shanghai fork.
+//!
+//! Shanghai is Cancun without blob transactions (EIP-4844), the beacon roots
+//! contract (EIP-4788), transient storage (EIP-1153) and `MCOPY`
+//! (EIP-5656). Blocks, state and the interpreter are shared with Cancun:
+//! [`Shanghai`] describes the differences with its header constraints and
+//! execution rules, which only allow the transaction types of
+//! [`transactions::Transaction`].
+//!
+//! [`Shanghai`]: fork::Shanghai
+
+pub mod fork;
+pub mod transactions;
@@ -0,0 +1,153 @@
+//! Block validation under the rules of Shanghai.
+//!
+//! Shanghai blocks go through the same checks and the same execution as
+//! Cancun blocks, with Shanghai's header constraints and execution rules.
+//! The execution rules reject blocks with blob transactions.
+
+use crate::ethereum::{
+    cancun::{
+        blocks::Block,
+        fork::{process_block, BlockChain},
+    },
+    exceptions::Exception,
+    forks::{ExecutionRules, Fork, HeaderConstraints},
+};
+
+/// The rules of the Shanghai fork.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct Shanghai;
+
+impl Fork for Shanghai {
+    fn name(&self) -> &'static str {
+        "shanghai"
+    }
+
+    fn header_constraints(&self) -> HeaderConstraints {
+        HeaderConstraints::SHANGHAI
+    }
+
+    fn execution_rules(&self) -> ExecutionRules {
+        ExecutionRules::SHANGHAI
+    }
+
+    fn state_transition(&self, chain: &mut BlockChain, block: Block) -> Result<(), Exception> {
+        process_block(self, chain, block)
+    }
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use crate::ethereum::{
+        cancun::{
+            blocks::{Block, Header},
+            fork::{apply_body, calculate_base_fee_per_gas, compute_header_hash, get_last_256_block_hashes, state_transition, BlockChain, EMPTY_OMMER_HASH},
+            fork_types::Root,
+            transactions::{BlobTransaction, Transaction},
+        },
+        ethereum_types::numeric::U256,
+        exceptions::Exception,
+        fork_criteria::ForkCriteria,
+        forks::Fork,
+        genesis::Genesis,
+    };
+
+    /// A chain which is Shanghai at genesis and Cancun from timestamp 24.
+    fn chain() -> BlockChain {
+        let genesis = Genesis::post_merge(&["shanghai"]).fork("cancun", ForkCriteria::ByTimestamp(U256::from(24_u32)));
+        BlockChain::from_genesis(genesis).unwrap()
+    }
+
+    /// A valid block on the head of `chain` at `timestamp`, with the header
+    /// fields of `fork`.
+    pub(crate) fn next_block(chain: &BlockChain, fork: &dyn Fork, timestamp: u32, transactions: Vec<Transaction>) -> Block {
+        let parent = &chain.blocks.last().unwrap().header;
+        let constraints = fork.header_constraints();
+        let base_fee = calculate_base_fee_per_gas(
+            parent.gas_limit,
+            parent.gas_limit,
+            parent.gas_used,
+            parent.base_fee_per_gas.unwrap(),
+            &chain.chain_spec,
+        )
+        .unwrap();
+        let mut block = Block {
+            header: Header {
+                parent_hash: compute_header_hash(parent).unwrap(),
+                ommers_hash: EMPTY_OMMER_HASH,
+                number: parent.number + 1,
+                gas_limit: parent.gas_limit,
+                timestamp: U256::from(timestamp),
+                base_fee_per_gas: Some(base_fee),
+                excess_blob_gas: constraints.blob_gas.then_some(0),
+                parent_beacon_block_root: constraints.parent_beacon_block_root.then(|| Root([0xbe; 32])),
+                ..Default::default()
+            },
+            transactions,
+            ommers: Vec::new(),
+            withdrawals: Some(Vec::new()),
+        };
+
+        let mut state = chain.state.clone();
+        let header = &block.header;
+        let output = apply_body(
+            &mut state,
+            &get_last_256_block_hashes(chain),
+            &header.coinbase,
+            &header.number,
+            &header.base_fee_per_gas,
+            &header.gas_limit,
+            &header.timestamp,
+            &header.prev_randao,
+            &block.transactions,
+            &block.ommers,
+            None,
+            chain.chain_spec.chain_id,
+            block.withdrawals.as_deref(),
+            &header.parent_beacon_block_root,
+            &header.excess_blob_gas,
+            fork.execution_rules(),
+            &chain.chain_spec,
+            None,
+        )
+        .unwrap();
+        let header = &mut block.header;
+        header.gas_used = output.block_gas_used;
+        header.transactions_root = output.transactions_root;
+        header.receipt_root = output.receipt_root;
+        header.bloom = output.block_logs_bloom;
+        header.state_root = output.state_root;
+        header.withdrawals_root = output.withdrawals_root;
+        header.blob_gas_used = output.blob_gas_used;
+        header.requests_hash = output.requests_hash;
+        block
+    }
+
+    #[test]
+    fn shanghai_then_cancun() {
+        let mut chain = chain();
+        assert_eq!(chain.fork_for(&chain.blocks[0].header).unwrap().name(), "shanghai");
+        assert!(chain.blocks[0].header.blob_gas_used.is_none());
+
+        let block = next_block(&chain, &super::Shanghai, 12, Vec::new());
+        state_transition(&mut chain, block).unwrap();
+
+        // Cancun rules apply from timestamp 24, a Shanghai header is invalid.
+        let block = next_block(&chain, &super::Shanghai, 24, Vec::new());
+        assert!(matches!(state_transition(&mut chain, block), Err(Exception::InvalidBlock(_))));
+        let block = next_block(&chain, chain.fork_for(&Header { timestamp: U256::from(24_u32), ..Default::default() }).unwrap(), 24, Vec::new());
+        assert!(block.header.excess_blob_gas.is_some());
+        state_transition(&mut chain, block).unwrap();
+        assert_eq!(chain.blocks.len(), 3);
+    }
+
+    #[test]
+    fn no_blob_transactions() {
+        let mut chain = chain();
+        let mut block = next_block(&chain, &super::Shanghai, 12, Vec::new());
+        block.transactions.push(Transaction::BlobTransaction(BlobTransaction::default()));
+        assert!(matches!(
+            state_transition(&mut chain, block),
+            Err(Exception::InvalidBlock("transaction type not allowed by the fork"))
+        ));
+    }
+}
@@ -0,0 +1,50 @@
+//! Transactions of the Shanghai fork.
+//!
+//! The transaction types are those of Cancun, less blob transactions.
+
+use crate::{
+    ethereum::{cancun::transactions as cancun, exceptions::Exception},
+    impl_extended,
+};
+
+pub use cancun::{AccessListTransaction, FeeMarketTransaction, LegacyTransaction};
+
+#[derive(Debug, Clone)]
+pub enum Transaction {
+    LegacyTransaction(LegacyTransaction),
+    AccessListTransaction(AccessListTransaction),
+    FeeMarketTransaction(FeeMarketTransaction),
+}
+
+impl Default for Transaction {
+    fn default() -> Self {
+        Self::LegacyTransaction(Default::default())
+    }
+}
+
+impl_extended!(enum Transaction: LegacyTransaction, 0x01 => AccessListTransaction, 0x02 => FeeMarketTransaction);
+
+/// Fails for blob and set code transactions, which Shanghai does not have.
+impl TryFrom<cancun::Transaction> for Transaction {
+    type Error = Exception;
+
+    fn try_from(tx: cancun::Transaction) -> Result<Self, Exception> {
+        match tx {
+            cancun::Transaction::LegacyTransaction(tx) => Ok(Self::LegacyTransaction(tx)),
+            cancun::Transaction::AccessListTransaction(tx) => Ok(Self::AccessListTransaction(tx)),
+            cancun::Transaction::FeeMarketTransaction(tx) => Ok(Self::FeeMarketTransaction(tx)),
+            cancun::Transaction::BlobTransaction(_) => Err(Exception::TransactionTypeError { transaction_type: 3 }),
+            cancun::Transaction::SetCodeTransaction(_) => Err(Exception::TransactionTypeError { transaction_type: 4 }),
+        }
+    }
+}
+
+impl From<Transaction> for cancun::Transaction {
+    fn from(tx: Transaction) -> Self {
+        match tx {
+            Transaction::LegacyTransaction(tx) => Self::LegacyTransaction(tx),
+            Transaction::AccessListTransaction(tx) => Self::AccessListTransaction(tx),
+            Transaction::FeeMarketTransaction(tx) => Self::FeeMarketTransaction(tx),
+        }
+    }
+}
@@ -2,17 +2,10 @@ use core::num;
 
 use crate::ethereum::{ethereum_types::{bytes::{Bytes, Bytes32, Bytes8}, numeric::{Uint, U256}}, exceptions::Exception};
 
+/// Decode the hex string `s`, with or without a "0x" prefix, into the end of
+/// `d`. An odd number of digits is read as having a leading zero.
 pub fn hex_to_slice(d: &mut [u8], s: &str) -> Result<(), Exception> {
-    let mut s = s.as_bytes();
-    if s == b"0x0" {
-        return Ok(())
-    }
-    if s.len() >= 2 && &s[0..2] != b"0x" {
-        s = &s[2..];
-    }
-    // if s.len() < 2 || &s[0..2] != b"0x" {
-    //     return Err(Exception::EthereumException("expected 0x"));
-    // }
+    let s = strip_0x(s).as_bytes();
 
     fn nib(c: u8) -> Result<u8, Exception> {
         if c.is_ascii_digit() {
@@ -24,31 +17,51 @@ pub fn hex_to_slice(d: &mut [u8], s: &str) -> Result<(), Exception> {
         }
     }
 
-    if s.len() % 2 != 0 {
-        let num_bytes = (s.len()-3)/2 + 1;
-        let dlen = d.len();
-        if num_bytes > dlen {
-            return Err(Exception::EthereumException("hex number too long"))
-        }
-        let d = &mut d[dlen-num_bytes..];
-        d[0] = nib(s[2])?;
-        for (i, c) in s[3..].chunks_exact(2).enumerate() {
-            d[i+1] = nib(c[0])? * 16 + nib(c[1])?
-        }
+    let num_bytes = s.len().div_ceil(2);
+    let dlen = d.len();
+    if num_bytes > dlen {
+        return Err(Exception::EthereumException("hex number too long"))
+    }
+    let d = &mut d[dlen-num_bytes..];
+    let (d, s) = if s.len() % 2 != 0 {
+        d[0] = nib(s[0])?;
+        (&mut d[1..], &s[1..])
     } else {
-        let num_bytes = (s.len()-2)/2;
-        let dlen = d.len();
-        if num_bytes > dlen {
-            return Err(Exception::EthereumException("hex number too long"))
-        }
-        let d = &mut d[dlen-num_bytes..];
-        for (i, c) in s[2..].chunks_exact(2).enumerate() {
-            d[i] = nib(c[0])? * 16 + nib(c[1])?
-        }
+        (d, s)
+    };
+    for (i, c) in s.chunks_exact(2).enumerate() {
+        d[i] = nib(c[0])? * 16 + nib(c[1])?
     }
     Ok(())
 }
 
+/// Decode the hex string `s`, with or without a "0x" prefix, which must have
+/// exactly two digits for each of the `N` bytes.
+pub fn hex_to_array<const N: usize>(s: &str) -> Result<[u8; N], Exception> {
+    if strip_0x(s).len() != N * 2 {
+        return Err(Exception::EthereumException("hex string has the wrong length"));
+    }
+    let mut bytes = [0; N];
+    hex_to_slice(&mut bytes, s)?;
+    Ok(bytes)
+}
+
+/// `bytes` as a "0x" prefixed hex string, leading zeros included.
+pub fn bytes_to_hex(bytes: &[u8]) -> String {
+    let hex = b"0123456789abcdef";
+    let mut s = String::with_capacity(bytes.len() * 2 + 2);
+    s.push_str("0x");
+    for b in bytes {
+        s.push(hex[(b >> 4) as usize] as char);
+        s.push(hex[(b & 0x0f) as usize] as char);
+    }
+    s
+}
+
+fn strip_0x(s: &str) -> &str {
+    s.strip_prefix("0x").unwrap_or(s)
+}
+
 pub fn hex_to_bytes8(s: &str) -> Result<Bytes8, Exception> {
     let mut bytes = [0; 8];
     hex_to_slice(&mut bytes, s)?;
@@ -56,12 +69,7 @@ pub fn hex_to_bytes8(s: &str) -> Result<Bytes8, Exception> {
 }
 
 pub fn hex_to_bytes(s: &str) -> Result<Bytes, Exception> {
-    let num_bytes = if s.len() % 2 != 0 {
-        (s.len()-3)/2 + 1
-    } else {
-        (s.len()-2)/2
-    };
-
+    let num_bytes = strip_0x(s).len().div_ceil(2);
     let mut bytes = vec![0; num_bytes];
     hex_to_slice(&mut bytes, s)?;
     Ok(Bytes(bytes))
@@ -86,3 +94,18 @@ pub fn hex_to_u256(s: &str) -> Result<U256, Exception> {
 }
 
 
+#[test]
+fn test_hex_to_slice() {
+    assert_eq!(hex_to_uint("0x0").unwrap(), 0);
+    assert_eq!(hex_to_uint("0x").unwrap(), 0);
+    assert_eq!(hex_to_uint("0x1ff").unwrap(), 0x1ff);
+    assert_eq!(hex_to_uint("1ff").unwrap(), 0x1ff);
+    assert_eq!(hex_to_uint("a").unwrap(), 10);
+    assert_eq!(hex_to_bytes("000d83").unwrap().0, vec![0x00, 0x0d, 0x83]);
+    assert_eq!(hex_to_bytes("0xabc").unwrap().0, vec![0x0a, 0xbc]);
+    assert!(hex_to_bytes8("0x123456789abcdef01").is_err());
+    assert!(hex_to_uint("0xg").is_err());
+    assert_eq!(hex_to_array::<2>("0x00ff").unwrap(), [0, 0xff]);
+    assert!(hex_to_array::<2>("0xff").is_err());
+    assert_eq!(bytes_to_hex(&[0, 0xab]), "0x00ab");
+}
@@ -0,0 +1,310 @@
+//! C interface to the execution engine.
+//!
+//! Enabled with the `ffi` feature. To build a shared library:
+//!
+//! ```sh
+//! cargo rustc --release --features ffi --crate-type cdylib
+//! ```
+//!
+//! All types are plain C types or opaque pointers, so a header can be
+//! generated with `cbindgen --lang c`. Set `prefix_with_name = true` in the
+//! `[enum]` section of `cbindgen.toml` to get `EjitStatus_Ok` style names.
+//!
+//! A chain is created with `ejit_chain_new_mainnet` or
+//! `ejit_chain_from_genesis_json` and must be released with
+//! `ejit_chain_free`. Every other call returns an `EjitStatus`. On failure
+//! the error message is available from `ejit_chain_error` and on success the
+//! output of the last call from `ejit_chain_output`. Both buffers are owned by
+//! the chain and are valid until the next call on the same chain.
+//!
+//! Panics are caught at the boundary and reported as `EjitStatus::Panic`.
+
+use std::{
+    ffi::{c_char, CString},
+    panic::{catch_unwind, AssertUnwindSafe},
+    ptr, slice,
+};
+
+use crate::{
+    ethereum::{
+        cancun::{
+            blocks::Block,
+            fork::BlockChain,
+            fork_types::Address,
+        },
+        ethereum_rlp::rlp,
+        ethereum_types::{bytes::Bytes, numeric::Uint},
+        exceptions::Exception,
+        genesis::Genesis,
+    },
+    json::from_json,
+};
+
+/// Result of an FFI call.
+#[repr(C)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum EjitStatus {
+    Ok = 0,
+    /// A pointer or length argument was invalid.
+    InvalidArgument = 1,
+    /// The input could not be decoded.
+    DecodingError = 2,
+    /// The block was rejected.
+    InvalidBlock = 3,
+    /// The call ran but halted exceptionally or reverted.
+    ExecutionError = 4,
+    /// The engine panicked.
+    Panic = 5,
+}
+
+/// Opaque handle to a chain and its result buffers.
+pub struct EjitChain {
+    chain: BlockChain,
+    output: Vec<u8>,
+    error: CString,
+}
+
+impl EjitChain {
+    fn new(chain: BlockChain) -> *mut EjitChain {
+        Box::into_raw(Box::new(EjitChain {
+            chain,
+            output: Vec::new(),
+            error: CString::default(),
+        }))
+    }
+
+    fn fail(&mut self, status: EjitStatus, message: String) -> EjitStatus {
+        self.output.clear();
+        self.error = CString::new(message.replace('\0', " ")).unwrap_or_default();
+        status
+    }
+
+    fn succeed(&mut self, output: Vec<u8>) -> EjitStatus {
+        self.output = output;
+        self.error = CString::default();
+        EjitStatus::Ok
+    }
+}
+
+/// Run `f` on the chain behind `handle`, converting panics to `EjitStatus::Panic`.
+unsafe fn with_chain(
+    handle: *mut EjitChain,
+    f: impl FnOnce(&mut EjitChain) -> EjitStatus,
+) -> EjitStatus {
+    let Some(handle) = (unsafe { handle.as_mut() }) else {
+        return EjitStatus::InvalidArgument;
+    };
+    match catch_unwind(AssertUnwindSafe(|| f(&mut *handle))) {
+        Ok(status) => status,
+        Err(payload) => {
+            let message = payload
+                .downcast_ref::<&str>()
+                .map(|s| s.to_string())
+                .or_else(|| payload.downcast_ref::<String>().cloned())
+                .unwrap_or_else(|| "panic".to_string());
+            handle.fail(EjitStatus::Panic, message)
+        }
+    }
+}
+
+/// View `len` bytes at `data`, allowing a null pointer for an empty slice.
+unsafe fn input<'a>(data: *const u8, len: usize) -> Option<&'a [u8]> {
+    if data.is_null() {
+        (len == 0).then_some(&[])
+    } else {
+        Some(unsafe { slice::from_raw_parts(data, len) })
+    }
+}
+
+unsafe fn address(data: *const u8) -> Option<Address> {
+    if data.is_null() {
+        return None;
+    }
+    Some(Address::from_be_bytes(unsafe { ptr::read(data as *const [u8; 20]) }))
+}
+
+/// Create a chain from the mainnet genesis block.
+///
+/// Returns null on failure.
+#[unsafe(no_mangle)]
+pub extern "C" fn ejit_chain_new_mainnet() -> *mut EjitChain {
+    match catch_unwind(|| BlockChain::mainnet()) {
+        Ok(Ok(chain)) => EjitChain::new(chain),
+        _ => ptr::null_mut(),
+    }
+}
+
+/// Create a chain from a genesis file in geth format.
+///
+/// Returns null on failure.
+///
+/// # Safety
+///
+/// `json` must point to `len` readable bytes.
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn ejit_chain_from_genesis_json(json: *const u8, len: usize) -> *mut EjitChain {
+    let Some(json) = (unsafe { input(json, len) }) else {
+        return ptr::null_mut();
+    };
+    let result = catch_unwind(|| from_json::<Genesis>(json).map_err(Exception::JsonError).and_then(BlockChain::from_genesis));
+    match result {
+        Ok(Ok(chain)) => EjitChain::new(chain),
+        _ => ptr::null_mut(),
+    }
+}
+
+/// Release a chain. Null is ignored.
+///
+/// # Safety
+///
+/// `chain` must have been returned by one of the constructors above and not
+/// already freed.
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn ejit_chain_free(chain: *mut EjitChain) {
+    if !chain.is_null() {
+        drop(unsafe { Box::from_raw(chain) });
+    }
+}
+
+/// Number of the block at the head of the chain.
+///
+/// # Safety
+///
+/// `chain` must be a live handle.
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn ejit_chain_head_number(chain: *const EjitChain) -> u64 {
+    match unsafe { chain.as_ref() } {
+        Some(chain) => chain.chain.blocks.last().map_or(0, |b| b.header.number as u64),
+        None => 0,
+    }
+}
+
+/// Import an RLP encoded block.
+///
+/// On success the output buffer holds the 32 byte block hash.
+///
+/// # Safety
+///
+/// `chain` must be a live handle and `block_rlp` must point to `len` readable
+/// bytes.
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn ejit_chain_import_block(
+    chain: *mut EjitChain,
+    block_rlp: *const u8,
+    len: usize,
+) -> EjitStatus {
+    let encoded = unsafe { input(block_rlp, len) };
+    unsafe {
+        with_chain(chain, |handle| {
+            let Some(encoded) = encoded else {
+                return EjitStatus::InvalidArgument;
+            };
+            let block = match rlp::decode_with::<Block>(encoded, rlp::DecodeOptions::BLOCK) {
+                Ok(block) => block,
+                Err(e) => return handle.fail(EjitStatus::DecodingError, format!("{e:?}")),
+            };
+            match handle.chain.import_block(block) {
+                Ok(hash) => handle.succeed(hash.to_vec()),
+                Err(e) => handle.fail(EjitStatus::InvalidBlock, format!("{e:?}")),
+            }
+        })
+    }
+}
+
+/// Execute a message call against the state at the head of the chain.
+///
+/// The state is not modified. A null `to` creates a contract with `data` as
+/// init code. On success the output buffer holds the return data and
+/// `gas_used` (if not null) the gas consumed.
+///
+/// # Safety
+///
+/// `chain` must be a live handle, `from` must point to 20 bytes, `to` must be
+/// null or point to 20 bytes, `data` must point to `data_len` readable bytes
+/// and `gas_used` must be null or writable.
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn ejit_chain_call(
+    chain: *mut EjitChain,
+    from: *const u8,
+    to: *const u8,
+    data: *const u8,
+    data_len: usize,
+    gas: u64,
+    gas_used: *mut u64,
+) -> EjitStatus {
+    let caller = unsafe { address(from) };
+    let target = unsafe { address(to) };
+    let data = unsafe { input(data, data_len) };
+    unsafe {
+        with_chain(chain, |handle| {
+            let (Some(caller), Some(data)) = (caller, data) else {
+                return EjitStatus::InvalidArgument;
+            };
+            let result = match handle.chain.call(caller, target, Bytes(data.to_vec()), gas as Uint) {
+                Ok(result) => result,
+                Err(e) => return handle.fail(EjitStatus::InvalidArgument, format!("{e:?}")),
+            };
+            if let Some(gas_used) = gas_used.as_mut() {
+                *gas_used = result.gas_used as u64;
+            }
+            match result.error {
+                None => handle.succeed(result.output.0),
+                Some(e) => {
+                    let status = handle.fail(EjitStatus::ExecutionError, format!("{e:?}"));
+                    // Revert data is still useful to the caller.
+                    handle.output = result.output.0;
+                    status
+                }
+            }
+        })
+    }
+}
+
+/// Output of the last successful call, or revert data of the last failed
+/// call. Writes the length to `len`.
+///
+/// # Safety
+///
+/// `chain` must be a live handle and `len` must be writable.
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn ejit_chain_output(chain: *const EjitChain, len: *mut usize) -> *const u8 {
+    let Some(chain) = (unsafe { chain.as_ref() }) else {
+        return ptr::null();
+    };
+    if let Some(len) = unsafe { len.as_mut() } {
+        *len = chain.output.len();
+    }
+    chain.output.as_ptr()
+}
+
+/// Nul terminated error message of the last call, empty after a success.
+///
+/// # Safety
+///
+/// `chain` must be a live handle.
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn ejit_chain_error(chain: *const EjitChain) -> *const c_char {
+    match unsafe { chain.as_ref() } {
+        Some(chain) => chain.error.as_ptr(),
+        None => ptr::null(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_import_garbage() {
+        let chain = ejit_chain_new_mainnet();
+        assert!(!chain.is_null());
+        unsafe {
+            assert_eq!(ejit_chain_head_number(chain), 0);
+            let status = ejit_chain_import_block(chain, [0xc1, 0x80, 0x80].as_ptr(), 3);
+            assert_eq!(status, EjitStatus::DecodingError);
+            assert!(*ejit_chain_error(chain) != 0);
+            assert_eq!(ejit_chain_import_block(chain, ptr::null(), 1), EjitStatus::InvalidArgument);
+            ejit_chain_free(chain);
+        }
+    }
+}
@@ -1,11 +1,17 @@
 //! 
-//! Very simple JSON deserialiser
+//! Very simple JSON deserialiser and serialiser
 //!
 //! See https://www.json.org/json-en.html 
+//!
+//! Types which can be read from JSON implement `JsonDecode` and types which
+//! can be written implement `JsonEncode`. Both follow the conventions of the
+//! Ethereum JSON formats: integers, hashes, addresses and byte strings are
+//! `0x` prefixed hex strings.
 
-use std::{collections::BTreeMap, io::Write, ops::Deref};
+use std::{collections::BTreeMap, fmt::Write as _, io::Write, ops::Deref};
 
 use crate::ethereum::{ethereum_types::numeric::{Uint, U64}, utils::hexadecimal::hex_to_slice};
+use crate::log::trace;
 
 #[derive(Debug)]
 pub enum JsonError {
@@ -21,6 +27,10 @@ pub enum JsonError {
     BadString,
     ExpectedHexString,
     BadNumber,
+    /// A well formed value which is not one the type can take.
+    InvalidValue(&'static str),
+    /// An address in mixed case which is not its EIP-55 checksum.
+    BadChecksum,
 }
 
 #[derive(Debug)]
@@ -28,16 +38,80 @@ pub struct Decoder<'de> {
     buffer: &'de [u8],
     start: * const u8,
     len: usize,
+    /// Keys and indices of the objects and arrays being decoded.
+    path: Vec<PathItem<'de>>,
+    /// Check the EIP-55 checksum of addresses in mixed case.
+    check_checksums: bool,
+}
+
+/// One step of the path to a value.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum PathItem<'de> {
+    Key(&'de str),
+    Index(usize),
 }
 
+/// The text around the position of a decoder.
+#[derive(Debug, Clone)]
 pub struct Context {
     text: String,
 }
 
+/// A `JsonError` and where in the input it happened.
+#[derive(Debug)]
+pub struct JsonErrorAt {
+    pub error: JsonError,
+    /// Byte offset from the start of the input.
+    pub offset: usize,
+    /// One based line and column, counting bytes.
+    pub line: usize,
+    pub column: usize,
+    /// Path of the value being decoded, such as `$.alloc.0x12.balance`.
+    pub path: String,
+    pub context: Context,
+}
+
+impl std::fmt::Display for JsonErrorAt {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "{} at line {} column {} (byte {}) in {} near {:?}",
+            self.error, self.line, self.column, self.offset, self.path, self.context.text,
+        )
+    }
+}
+
+impl std::error::Error for JsonErrorAt {}
+
+impl std::fmt::Display for JsonError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            JsonError::UnexpectedEof => write!(f, "unexpected end of input"),
+            JsonError::UnexpectedChar => write!(f, "unexpected character"),
+            JsonError::UnterminatedString => write!(f, "unterminated string"),
+            JsonError::Expected(c) => write!(f, "expected {c:?}"),
+            JsonError::MissingKey => write!(f, "unknown key"),
+            JsonError::ExpectedDigit => write!(f, "expected a digit"),
+            JsonError::ExpectedIdentifier => write!(f, "expected an identifier"),
+            JsonError::ExpectedBool => write!(f, "expected true or false"),
+            JsonError::NumericOverflow => write!(f, "number too large"),
+            JsonError::BadString => write!(f, "invalid string"),
+            JsonError::ExpectedHexString => write!(f, "expected a 0x prefixed hex string"),
+            JsonError::BadNumber => write!(f, "invalid number"),
+            JsonError::InvalidValue(e) => write!(f, "invalid value: {e}"),
+            JsonError::BadChecksum => write!(f, "address does not match its EIP-55 checksum"),
+        }
+    }
+}
+
+impl std::error::Error for JsonError {}
+
+#[derive(Debug, Clone, Default, PartialEq)]
 pub enum Value {
     String(Box<str>),
     Numeric(Box<str>),
     Bool(bool),
+    #[default]
     Null,
     Array(Box<[Value]>),
     Map(Box<[(Box<str>, Value)]>),
@@ -59,7 +133,65 @@ impl<'de> From<&Decoder<'de>> for Context {
 
 impl<'de> Decoder<'de> {
     pub fn new(buffer: &'de [u8]) -> Self {
-        Self { buffer, start: buffer.as_ptr(), len: buffer.len() }
+        Self { buffer, start: buffer.as_ptr(), len: buffer.len(), path: Vec::new(), check_checksums: true }
+    }
+
+    /// Accept addresses in mixed case whatever their EIP-55 checksum, as
+    /// some fixtures older than EIP-55 have them.
+    pub fn skip_checksums(mut self) -> Self {
+        self.check_checksums = false;
+        self
+    }
+
+    /// True unless `skip_checksums` was called.
+    pub fn checks_checksums(&self) -> bool {
+        self.check_checksums
+    }
+
+    /// Number of bytes read so far.
+    pub fn offset(&self) -> usize {
+        self.len - self.buffer.len()
+    }
+
+    /// The keys and indices leading to the current value. After an error,
+    /// this is the path of the value which failed to decode.
+    pub fn path(&self) -> &[PathItem<'de>] {
+        &self.path
+    }
+
+    /// Add `error` to the current position and path.
+    pub fn error_at(&self, error: JsonError) -> JsonErrorAt {
+        let offset = self.offset();
+        // Safety: `start` and `len` are the whole input, of which `buffer`
+        // is the unread tail.
+        let read = unsafe { std::slice::from_raw_parts(self.start, offset) };
+        let line = read.iter().filter(|b| **b == b'\n').count() + 1;
+        let column = offset - read.iter().rposition(|b| *b == b'\n').map_or(0, |i| i + 1) + 1;
+        let mut path = String::from("$");
+        for item in &self.path {
+            match item {
+                PathItem::Key(key) => write!(path, ".{key}").unwrap(),
+                PathItem::Index(i) => write!(path, "[{i}]").unwrap(),
+            }
+        }
+        JsonErrorAt { error, offset, line, column, path, context: self.into() }
+    }
+
+    /// Start an object or array.
+    fn enter(&mut self, item: PathItem<'de>) {
+        self.path.push(item);
+    }
+
+    /// Move to the next member or element of an object or array.
+    fn step(&mut self, item: PathItem<'de>) {
+        if let Some(last) = self.path.last_mut() {
+            *last = item;
+        }
+    }
+
+    /// Finish an object or array.
+    fn leave(&mut self) {
+        self.path.pop();
     }
     
     pub fn advance(&mut self, n: usize) -> &'de [u8] {
@@ -85,45 +217,296 @@ pub trait JsonDecode<'de> : where Self : 'de {
     fn decode_json(&mut self, decoder: &mut Decoder<'de>) -> Result<(), JsonError>;
 }
 
-#[macro_export]
-macro_rules! impl_json {
-    ($t : ty : $f1 : ident $n1 : expr) => {
-        impl<'de> JsonDecode<'de> for $t {
-            fn decode_json(&mut self, decoder: &mut Decoder<'de>) -> Result<(), JsonError> {
-                let mut p = ObjectParser::new(decoder);
-                p.decode_one(&mut self.$f1, $n1)
+pub trait JsonEncode {
+    fn encode_json(&self, encoder: &mut Encoder);
+
+    /// True if the value should be left out of an object made by
+    /// `impl_json!`, as for `None`.
+    fn is_absent(&self) -> bool {
+        false
+    }
+}
+
+/// Writes JSON to a string, either compact or indented.
+#[derive(Debug, Default)]
+pub struct Encoder {
+    buffer: String,
+    /// Spaces per level of nesting, or `None` for compact output.
+    indent: Option<usize>,
+    depth: usize,
+}
+
+impl Encoder {
+    /// An encoder for compact output.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// An encoder which puts every member and element on its own line,
+    /// indented by `indent` spaces per level.
+    pub fn pretty(indent: usize) -> Self {
+        Self { indent: Some(indent), ..Self::default() }
+    }
+
+    pub fn finish(self) -> String {
+        self.buffer
+    }
+
+    /// Append `text`, which must already be valid JSON.
+    pub fn raw(&mut self, text: &str) {
+        self.buffer.push_str(text);
+    }
+
+    /// Append a string literal, escaping as needed.
+    pub fn string(&mut self, s: &str) {
+        self.buffer.push('"');
+        for c in s.chars() {
+            match c {
+                '"' => self.buffer.push_str("\\\""),
+                '\\' => self.buffer.push_str("\\\\"),
+                '\n' => self.buffer.push_str("\\n"),
+                '\r' => self.buffer.push_str("\\r"),
+                '\t' => self.buffer.push_str("\\t"),
+                c if c.is_control() => write!(self.buffer, "\\u{:04x}", c as u32).unwrap(),
+                c => self.buffer.push(c),
             }
         }
-    };
-    ($t : ty : $f1 : ident $n1 : expr, $f2 : ident $n2 : expr) => {
-        impl<'de> JsonDecode<'de> for $t {
-            fn decode_json(&mut self, decoder: &mut Decoder<'de>) -> Result<(), JsonError> {
-                let mut p = ObjectParser::new(decoder);
-                p.decode_two(&mut self.$f1, $n1, &mut self.$f2, $n2)
+        self.buffer.push('"');
+    }
+
+    /// Append `bytes` as a `0x` prefixed hex string of their full length.
+    pub fn hex(&mut self, bytes: &[u8]) {
+        self.buffer.push_str("\"0x");
+        for b in bytes {
+            write!(self.buffer, "{b:02x}").unwrap();
+        }
+        self.buffer.push('"');
+    }
+
+    /// Append the big endian integer `bytes` as a `0x` prefixed hex string
+    /// without leading zeros.
+    pub fn quantity(&mut self, bytes: &[u8]) {
+        let lz = bytes.iter().position(|b| *b != 0).unwrap_or(bytes.len());
+        let bytes = &bytes[lz..];
+        match bytes.split_first() {
+            None => self.buffer.push_str("\"0x0\""),
+            Some((first, rest)) => {
+                write!(self.buffer, "\"0x{first:x}").unwrap();
+                for b in rest {
+                    write!(self.buffer, "{b:02x}").unwrap();
+                }
+                self.buffer.push('"');
             }
         }
-    };
-    ($t : ty : $f1 : ident $n1 : expr, $f2 : ident $n2 : expr, $f3 : ident $n3 : expr) => {
-        impl<'de> JsonDecode<'de> for $t {
-            fn decode_json(&mut self, decoder: &mut Decoder<'de>) -> Result<(), JsonError> {
-                let mut p = ObjectParser::new(decoder);
-                p.decode_three(&mut self.$f1, $n1, &mut self.$f2, $n2, &mut self.$f3, $n3)
+    }
+
+    /// Start an object. Members are added with `ObjectEncoder::field`.
+    pub fn object(&mut self) -> ObjectEncoder<'_> {
+        self.buffer.push('{');
+        self.depth += 1;
+        ObjectEncoder { encoder: self, empty: true }
+    }
+
+    /// Start an array. Elements are added with `ArrayEncoder::element`.
+    pub fn array(&mut self) -> ArrayEncoder<'_> {
+        self.buffer.push('[');
+        self.depth += 1;
+        ArrayEncoder { encoder: self, empty: true }
+    }
+
+    /// Separate an item from the previous one and indent it.
+    fn next_item(&mut self, first: bool) {
+        if !first {
+            self.buffer.push(',');
+        }
+        self.newline();
+    }
+
+    /// Close an object or array.
+    fn close(&mut self, empty: bool, c: char) {
+        self.depth -= 1;
+        if !empty {
+            self.newline();
+        }
+        self.buffer.push(c);
+    }
+
+    fn newline(&mut self) {
+        if let Some(indent) = self.indent {
+            self.buffer.push('\n');
+            for _ in 0..indent * self.depth {
+                self.buffer.push(' ');
             }
         }
+    }
+}
+
+pub struct ObjectEncoder<'b> {
+    pub encoder: &'b mut Encoder,
+    empty: bool,
+}
+
+impl<'b> ObjectEncoder<'b> {
+    /// Add a member.
+    pub fn field(&mut self, key: &str, value: &dyn JsonEncode) {
+        self.key(key);
+        value.encode_json(self.encoder);
+    }
+
+    /// Start a member, leaving the encoder ready for its value.
+    pub fn key(&mut self, key: &str) {
+        self.encoder.next_item(self.empty);
+        self.empty = false;
+        self.encoder.string(key);
+        self.colon();
+    }
+
+    /// `key` for a key which is already a JSON string literal.
+    fn quoted_key(&mut self, key: &str) {
+        self.encoder.next_item(self.empty);
+        self.empty = false;
+        self.encoder.raw(key);
+        self.colon();
+    }
+
+    fn colon(&mut self) {
+        self.encoder.buffer.push(':');
+        if self.encoder.indent.is_some() {
+            self.encoder.buffer.push(' ');
+        }
+    }
+
+    pub fn end(self) {
+        self.encoder.close(self.empty, '}');
+    }
+}
+
+pub struct ArrayEncoder<'b> {
+    pub encoder: &'b mut Encoder,
+    empty: bool,
+}
+
+impl<'b> ArrayEncoder<'b> {
+    pub fn element(&mut self, value: &dyn JsonEncode) {
+        value.encode_json(self.next());
+    }
+
+    /// Start an element, returning the encoder to write it with.
+    pub fn next(&mut self) -> &mut Encoder {
+        self.encoder.next_item(self.empty);
+        self.empty = false;
+        self.encoder
+    }
+
+    pub fn end(self) {
+        self.encoder.close(self.empty, ']');
+    }
+}
+
+/// Decode a complete JSON document, reporting where any error happened.
+///
+/// Only whitespace may follow the value.
+pub fn from_json<'de, T : JsonDecode<'de> + Default>(json: &'de [u8]) -> Result<T, JsonErrorAt> {
+    from_json_with(Decoder::new(json))
+}
+
+/// `from_json` with a decoder set up by the caller, such as one which
+/// `skip_checksums`.
+pub fn from_json_with<'de, T : JsonDecode<'de> + Default>(mut decoder: Decoder<'de>) -> Result<T, JsonErrorAt> {
+    let mut t = T::default();
+    t.decode_json(&mut decoder).map_err(|e| decoder.error_at(e))?;
+    skip_whitespace(&mut decoder);
+    if !decoder.is_empty() {
+        return Err(decoder.error_at(JsonError::UnexpectedChar));
+    }
+    Ok(t)
+}
+
+/// Compact JSON for `t`.
+pub fn to_json<T : JsonEncode + ?Sized>(t: &T) -> String {
+    let mut encoder = Encoder::new();
+    t.encode_json(&mut encoder);
+    encoder.finish()
+}
+
+/// JSON for `t`, indented by two spaces per level.
+pub fn to_json_pretty<T : JsonEncode + ?Sized>(t: &T) -> String {
+    let mut encoder = Encoder::pretty(2);
+    t.encode_json(&mut encoder);
+    encoder.finish()
+}
+
+/// Implement `JsonDecode` and `JsonEncode` for a struct as an object with the
+/// given member names:
+///
+/// ```
+/// use ejit_evm::{ethereum::ethereum_types::numeric::U256, impl_json, json};
+///
+/// #[derive(Debug, Default, PartialEq)]
+/// struct Account { nonce: U256, balance: U256, code_hash: Option<U256> }
+/// impl_json!(Account : nonce "nonce", balance "balance", code_hash "codeHash");
+///
+/// let account: Account = json::from_json(br#"{"nonce": "0x1", "balance": "0x10"}"#)?;
+/// assert_eq!(account, Account { nonce: U256::ONE, balance: U256::from(16_u32), code_hash: None });
+/// assert_eq!(json::to_json(&account), r#"{"nonce":"0x1","balance":"0x10"}"#);
+/// # Ok::<(), json::JsonErrorAt>(())
+/// ```
+///
+/// Any number of members may be given and the names need not match the
+/// field names, so camelCase formats such as JSON-RPC map directly onto
+/// snake_case fields. Members which are missing keep their default value.
+/// `Option` fields are optional: `None` is left out when encoding and may be
+/// missing or `null` when decoding.
+///
+/// Unknown members are an error when decoding, unless the type is marked
+/// `#[skip_unknown]`, in which case they are read and discarded:
+///
+/// ```
+/// use ejit_evm::{ethereum::ethereum_types::numeric::U256, impl_json, json};
+///
+/// #[derive(Debug, Default)]
+/// struct Head { number: U256 }
+/// impl_json!(#[skip_unknown] Head : number "number");
+///
+/// let head: Head = json::from_json(br#"{"number": "0x2a", "miner": {"name": "x"}}"#)?;
+/// assert_eq!(head.number, U256::from(42_u32));
+/// # Ok::<(), json::JsonErrorAt>(())
+/// ```
+#[macro_export]
+macro_rules! impl_json {
+    (#[skip_unknown] $t : ty : $($field : ident $name : expr),* $(,)?) => {
+        $crate::impl_json!(@impl $t, true : $($field $name),*);
     };
-    ($t : ty : $($field : ident $name : expr),*) => {
-        impl<'de> JsonDecode<'de> for $t {
-            fn decode_json(&mut self, decoder: &mut Decoder<'de>) -> Result<(), JsonError> {
-                let mut p = ObjectParser::new(decoder);
+    ($t : ty : $($field : ident $name : expr),* $(,)?) => {
+        $crate::impl_json!(@impl $t, false : $($field $name),*);
+    };
+    (@impl $t : ty, $skip_unknown : literal : $($field : ident $name : expr),*) => {
+        impl<'de> $crate::json::JsonDecode<'de> for $t {
+            fn decode_json(&mut self, decoder: &mut $crate::json::Decoder<'de>) -> Result<(), $crate::json::JsonError> {
+                let mut p = $crate::json::ObjectParser::new(decoder);
+                // Note that the JSON may repeat a key, the last value wins.
                 loop {
                     match p.next_key()? {
-                        $(Some(k) if k == $name => self.$field.decode_json(p.decoder)?,)*
+                        $(Some(k) if k == $name => $crate::json::JsonDecode::decode_json(&mut self.$field, p.decoder)?,)*
+                        Some(_) if $skip_unknown => p.skip_value()?,
+                        Some(_) => return Err($crate::json::JsonError::MissingKey),
                         None => return Ok(()),
-                        _ => return Err(crate::json::JsonError::MissingKey),
-                    };
+                    }
                 }
             }
         }
+
+        impl $crate::json::JsonEncode for $t {
+            fn encode_json(&self, encoder: &mut $crate::json::Encoder) {
+                let mut o = encoder.object();
+                $(
+                    if !$crate::json::JsonEncode::is_absent(&self.$field) {
+                        o.field($name, &self.$field);
+                    }
+                )*
+                o.end();
+            }
+        }
     };
 }
 
@@ -142,7 +525,9 @@ impl<'de, T : JsonDecode<'de> + Default> JsonDecode<'de> for Vec<T> {
         expect(decoder, b'[')?;
         skip_whitespace(decoder);
         if decoder.first() != Some(&b']') {
+            decoder.enter(PathItem::Index(0));
             loop {
+                decoder.step(PathItem::Index(self.len()));
                 let mut t : T = Default::default();
                 t.decode_json(decoder)?;
                 self.push(t);
@@ -152,6 +537,7 @@ impl<'de, T : JsonDecode<'de> + Default> JsonDecode<'de> for Vec<T> {
                 };
                 expect(decoder, b',')?;
             }
+            decoder.leave();
         }
         decoder.advance(1);
         Ok(())
@@ -171,6 +557,22 @@ impl<'de, K : JsonDecode<'de> + Default + Ord, V : JsonDecode<'de> + Default> Js
     }
 }
 
+/// `null` is `None`.
+impl<'de, T : JsonDecode<'de> + Default> JsonDecode<'de> for Option<T> {
+    fn decode_json(&mut self, decoder: &mut Decoder<'de>) -> Result<(), JsonError> {
+        skip_whitespace(decoder);
+        if decoder.starts_with(b"null") {
+            decoder.advance(4);
+            *self = None;
+        } else {
+            let mut t = T::default();
+            t.decode_json(decoder)?;
+            *self = Some(t);
+        }
+        Ok(())
+    }
+}
+
 impl<'de> JsonDecode<'de> for bool {
     fn decode_json(&mut self, decoder: &mut Decoder<'de>) -> Result<(), JsonError> {
         skip_whitespace(decoder);
@@ -185,49 +587,78 @@ impl<'de> JsonDecode<'de> for bool {
 
 impl<'de> JsonDecode<'de> for String {
     fn decode_json(&mut self, decoder: &mut Decoder<'de>) -> Result<(), JsonError> {
-        let mut s = parse_string(decoder)?;
-        if s.iter().any(|b| b.is_ascii_control()) {
-            return Err(JsonError::BadString);
+        *self = unescape(parse_string(decoder)?)?;
+        Ok(())
+    }
+}
+
+/// The contents of a string as returned by `parse_string`, with its escapes
+/// replaced by the characters they stand for.
+///
+/// A `\u` escape of a high surrogate must be followed by one of a low
+/// surrogate, the two making one character outside the basic multilingual
+/// plane. A surrogate alone is not a character and is rejected.
+pub fn unescape(s: &[u8]) -> Result<String, JsonError> {
+    if s.iter().any(|b| b.is_ascii_control()) {
+        return Err(JsonError::BadString);
+    }
+    if !s.contains(&b'\\') {
+        return String::from_utf8(s.to_vec()).map_err(|_| JsonError::BadString);
+    }
+
+    /// The code unit of the `\u` escape at the start of `s`.
+    fn code_unit(s: &[u8]) -> Result<u32, JsonError> {
+        match s {
+            [b'\\', b'u', digits @ ..] if digits.len() >= 4 => {
+                let digits = std::str::from_utf8(&digits[..4]).map_err(|_| JsonError::BadString)?;
+                u32::from_str_radix(digits, 16).map_err(|_| JsonError::BadString)
+            }
+            _ => Err(JsonError::BadString),
         }
-        if !s.contains(&b'\\') {
-            *self = String::from_utf8(s.to_vec())
-                .map_err(|_| JsonError::BadString)?;
-        } else {
-            let mut i = 0;
-            *self = String::with_capacity(s.len());
-            while i != s.len() {
-                if s[i] != b'\\' {
-                    self.push(s[i].into());
-                    i += 1;
-                } else {
-                    match s[i+1] {
-                        b'"' => self.push('"'),
-                        b'\\' => self.push('\\'),
-                        b'b' => self.push('\x08'),
-                        b'f' => self.push('\x0c'),
-                        b'n' => self.push('\n'),
-                        b'r' => self.push('\r'),
-                        b'u' => {
-                            if i + 6 > s.len() {
-                                return Err(JsonError::BadString);
-                            }
-                            let x = std::str::from_utf8(&s[i+2..i+6])
-                                .map_err(|_| JsonError::BadString)?;
-                            let c = u32::from_str_radix(x, 16)
-                                .map_err(|_| JsonError::BadString)?
-                                .try_into()
-                                .map_err(|_| JsonError::BadString)?;
-                            self.push(c);
-                            i += 4;
+    }
+
+    let mut res = String::with_capacity(s.len());
+    let mut i = 0;
+    while i != s.len() {
+        if s[i] != b'\\' {
+            // The bytes up to the next escape, which may be any UTF-8.
+            let end = s[i..].iter().position(|b| *b == b'\\').map_or(s.len(), |n| i + n);
+            res.push_str(std::str::from_utf8(&s[i..end]).map_err(|_| JsonError::BadString)?);
+            i = end;
+            continue;
+        }
+        let c = match s.get(i + 1) {
+            Some(b'"') => '"',
+            Some(b'\\') => '\\',
+            Some(b'/') => '/',
+            Some(b'b') => '\x08',
+            Some(b'f') => '\x0c',
+            Some(b'n') => '\n',
+            Some(b'r') => '\r',
+            Some(b't') => '\t',
+            Some(b'u') => {
+                let unit = code_unit(&s[i..])?;
+                let c = match unit {
+                    0xd800..=0xdbff => {
+                        let low = code_unit(&s[i + 6..])?;
+                        if !(0xdc00..=0xdfff).contains(&low) {
+                            return Err(JsonError::BadString);
                         }
-                        _ => return Err(JsonError::BadString),
+                        i += 6;
+                        0x10000 + ((unit - 0xd800) << 10) + (low - 0xdc00)
                     }
-                    i += 2;
-                }
+                    0xdc00..=0xdfff => return Err(JsonError::BadString),
+                    unit => unit,
+                };
+                i += 4;
+                char::from_u32(c).ok_or(JsonError::BadString)?
             }
-        }
-        Ok(())
+            _ => return Err(JsonError::BadString),
+        };
+        res.push(c);
+        i += 2;
     }
+    Ok(res)
 }
 
 impl<'de> JsonDecode<'de> for Value {
@@ -241,9 +672,9 @@ impl<'de> JsonDecode<'de> for Value {
                 Ok(())
             }
             Some(c) if c.is_ascii_digit() || *c == b'-' => {
-                let mut s = String::new();
-                s.decode_json(decoder)?;
-                *self = Value::String(s.into());
+                let n = parse_number(decoder)?;
+                let n = std::str::from_utf8(n).map_err(|_| JsonError::BadNumber)?;
+                *self = Value::Numeric(n.into());
                 Ok(())
             }
             Some(x) if x.is_ascii_alphabetic() => {
@@ -260,7 +691,9 @@ impl<'de> JsonDecode<'de> for Value {
                 skip_whitespace(decoder);
                 let mut array = Vec::new();
                 if decoder.first() != Some(&b']') {
+                    decoder.enter(PathItem::Index(0));
                     loop {
+                        decoder.step(PathItem::Index(array.len()));
                         let mut t = Value::Null;
                         t.decode_json(decoder)?;
                         array.push(t);
@@ -270,6 +703,7 @@ impl<'de> JsonDecode<'de> for Value {
                         };
                         expect(decoder, b',')?;
                     }
+                    decoder.leave();
                 }
                 decoder.advance(1);
                 *self = Value::Array(array.into());
@@ -283,7 +717,7 @@ impl<'de> JsonDecode<'de> for Value {
                         Some(k) => {
                             let mut v = Value::Null;
                             v.decode_json(p.decoder)?;
-                            map.push((k.into(), v));
+                            map.push((unescape(k.as_bytes())?.into(), v));
                         }
                         None => break,
                     }
@@ -298,6 +732,95 @@ impl<'de> JsonDecode<'de> for Value {
 }
 
 
+impl JsonEncode for str {
+    fn encode_json(&self, encoder: &mut Encoder) {
+        encoder.string(self);
+    }
+}
+
+impl JsonEncode for &str {
+    fn encode_json(&self, encoder: &mut Encoder) {
+        encoder.string(self);
+    }
+}
+
+impl JsonEncode for String {
+    fn encode_json(&self, encoder: &mut Encoder) {
+        encoder.string(self);
+    }
+}
+
+impl JsonEncode for bool {
+    fn encode_json(&self, encoder: &mut Encoder) {
+        encoder.raw(if *self { "true" } else { "false" });
+    }
+}
+
+impl<T : JsonEncode> JsonEncode for Option<T> {
+    fn encode_json(&self, encoder: &mut Encoder) {
+        match self {
+            Some(t) => t.encode_json(encoder),
+            None => encoder.raw("null"),
+        }
+    }
+
+    fn is_absent(&self) -> bool {
+        self.is_none()
+    }
+}
+
+impl<T : JsonEncode> JsonEncode for [T] {
+    fn encode_json(&self, encoder: &mut Encoder) {
+        let mut a = encoder.array();
+        for t in self {
+            a.element(t);
+        }
+        a.end();
+    }
+}
+
+impl<T : JsonEncode> JsonEncode for Vec<T> {
+    fn encode_json(&self, encoder: &mut Encoder) {
+        self.as_slice().encode_json(encoder);
+    }
+}
+
+/// Keys which do not encode to a string, such as numbers, are quoted.
+impl<K : JsonEncode, V : JsonEncode> JsonEncode for BTreeMap<K, V> {
+    fn encode_json(&self, encoder: &mut Encoder) {
+        let mut o = encoder.object();
+        for (k, v) in self {
+            let key = to_json(k);
+            if key.starts_with('"') {
+                o.quoted_key(&key);
+            } else {
+                o.key(&key);
+            }
+            v.encode_json(o.encoder);
+        }
+        o.end();
+    }
+}
+
+impl JsonEncode for Value {
+    fn encode_json(&self, encoder: &mut Encoder) {
+        match self {
+            Value::String(s) => encoder.string(s),
+            Value::Numeric(n) => encoder.raw(n),
+            Value::Bool(b) => b.encode_json(encoder),
+            Value::Null => encoder.raw("null"),
+            Value::Array(array) => array.encode_json(encoder),
+            Value::Map(map) => {
+                let mut o = encoder.object();
+                for (k, v) in map {
+                    o.field(k, v);
+                }
+                o.end();
+            }
+        }
+    }
+}
+
 pub fn skip_whitespace<'de>(decoder: &mut Decoder<'de>) {
     while decoder.first().map(u8::is_ascii_whitespace) == Some(true) {
         decoder.advance(1);
@@ -311,58 +834,111 @@ pub fn expect<'de>(decoder: &mut Decoder<'de>, chr: u8) -> Result<(), JsonError>
     Ok(())
 }
 
+/// The contents of the string at the cursor, between its quotes and with
+/// its escapes as written.
+///
+/// The string ends at the first quote which is not escaped. A backslash
+/// escapes the byte after it, so `"a\\"` is a string of two characters,
+/// of which the second is the escaped backslash.
 pub fn parse_string<'de>(decoder: &mut Decoder<'de>) -> Result<&'de [u8], JsonError> {
     skip_whitespace(decoder);
     match decoder.first() {
         Some(b'"') => {
-            if let Some(nbytes) = decoder.windows(2).position(|w| w[1] == b'"' && w[0] != b'\\') {
-                let res = &decoder.cur()[1..nbytes+1];
-                decoder.advance(nbytes+2);
-                Ok(res)
-            } else {
-                Err(JsonError::UnterminatedString)
+            let s = decoder.cur();
+            let mut i = 1;
+            let mut escaped = false;
+            while let Some(&b) = s.get(i) {
+                match b {
+                    _ if escaped => escaped = false,
+                    b'\\' => escaped = true,
+                    b'"' => {
+                        decoder.advance(i + 1);
+                        return Ok(&s[1..i]);
+                    }
+                    _ => {}
+                }
+                i += 1;
             }
+            Err(JsonError::UnterminatedString)
         }
         Some(_) => Err(JsonError::UnexpectedChar),
         None => Err(JsonError::UnexpectedEof),
     }
 }
 
+/// Advance past a run of digits, returning false if there are none.
+fn skip_digits(decoder: &mut Decoder) -> bool {
+    let len = decoder.cur().iter().take_while(|b| b.is_ascii_digit()).count();
+    decoder.advance(len);
+    len != 0
+}
+
+/// Read a number: an optional minus sign, digits, then an optional fraction
+/// and exponent.
 pub fn parse_number<'de>(decoder: &mut Decoder<'de>) -> Result<&'de [u8], JsonError> {
     skip_whitespace(decoder);
     let res = decoder.cur();
     if decoder.first() == Some(&b'-') {
         decoder.advance(1);
     }
-    let mut ok = false;
-    while matches!(decoder.first(), Some(b) if b.is_ascii_digit()) {
-        decoder.advance(1);
-        ok = true;
+    if !skip_digits(decoder) {
+        return Err(JsonError::BadNumber);
     }
-    if !ok { return Err(JsonError::BadNumber); }
-
     if decoder.first() == Some(&b'.') {
-        let mut ok = false;
-        while matches!(decoder.first(), Some(b) if b.is_ascii_digit()) {
-            decoder.advance(1);
+        decoder.advance(1);
+        if !skip_digits(decoder) {
+            return Err(JsonError::BadNumber);
         }
-        ok = true;
-        if !ok { return Err(JsonError::BadNumber); }
     }
-
-    if decoder.first() == Some(&b'e') || decoder.first() == Some(&b'E') {
+    if matches!(decoder.first(), Some(b'e' | b'E')) {
         decoder.advance(1);
-        if decoder.first() == Some(&b'+') || decoder.first() == Some(&b'-') {
+        if matches!(decoder.first(), Some(b'+' | b'-')) {
             decoder.advance(1);
         }
-        let mut ok = false;
-        while matches!(decoder.first(), Some(b) if b.is_ascii_digit()) {
+        if !skip_digits(decoder) {
+            return Err(JsonError::BadNumber);
+        }
+    }
+    Ok(&res[..res.len() - decoder.len()])
+}
+
+/// Read and discard one value of any type, checking that it is well formed.
+pub fn skip_value<'de>(decoder: &mut Decoder<'de>) -> Result<(), JsonError> {
+    skip_whitespace(decoder);
+    match decoder.first() {
+        Some(b'"') => parse_string(decoder).map(|_| ()),
+        Some(c) if c.is_ascii_digit() || *c == b'-' => parse_number(decoder).map(|_| ()),
+        Some(c) if c.is_ascii_alphabetic() => match parse_indent(decoder)? {
+            b"true" | b"false" | b"null" => Ok(()),
+            _ => Err(JsonError::UnexpectedChar),
+        },
+        Some(b'[') => {
             decoder.advance(1);
+            if expect(decoder, b']').is_ok() {
+                return Ok(());
+            }
+            decoder.enter(PathItem::Index(0));
+            for i in 0.. {
+                decoder.step(PathItem::Index(i));
+                skip_value(decoder)?;
+                if expect(decoder, b']').is_ok() {
+                    break;
+                }
+                expect(decoder, b',')?;
+            }
+            decoder.leave();
+            Ok(())
         }
-        ok = true;
-        if !ok { return Err(JsonError::BadNumber); }
+        Some(b'{') => {
+            let mut p = ObjectParser::new(decoder);
+            while p.next_key()?.is_some() {
+                p.skip_value()?;
+            }
+            Ok(())
+        }
+        Some(_) => Err(JsonError::UnexpectedChar),
+        None => Err(JsonError::UnexpectedEof),
     }
-    Ok(res)
 }
 
 pub fn parse_indent<'de>(decoder: &mut Decoder<'de>) -> Result<&'de [u8], JsonError> {
@@ -389,7 +965,7 @@ pub fn decode_object<'de>(dest: &mut [(&mut dyn JsonDecode<'de>, &str)], decoder
     loop {
         let key = parse_string(decoder)?;
 
-        println!("{key:02x?} {decoder:02x?}");
+        trace!("key {}", String::from_utf8_lossy(key));
         expect(decoder, b':')?;
 
         let Some((obj, _)) = dest.iter_mut().find(|(_, k)| k.as_bytes() == key) else {
@@ -423,44 +999,57 @@ impl<'b, 'de> ObjectParser<'b, 'de> {
     }
 
     pub fn next_key(&mut self) -> Result<Option<&'de str>, JsonError> {
-        if !self.started {
-            expect(self.decoder, b'{')?;
-            if expect(self.decoder, b'}').is_ok() {
-                return Ok(None)
-            }
-            self.started = true;
-        } else {
-            if expect(self.decoder, b'}').is_ok() {
-                return Ok(None);
-            } else {
-                expect(self.decoder, b',')?;
-            }
+        if !self.start()? {
+            return Ok(None);
         }
         let mut key = "";
         key.decode_json(self.decoder)?;
+        self.decoder.step(PathItem::Key(key));
         expect(self.decoder, b':')?;
         return Ok(Some(key));
     }
 
-    /// When decoding maps, we do accept non-strings as keys.
-    /// 
-    /// Also many types have string encodings.
-    pub fn next_map_key<T : JsonDecode<'de> + Default>(&mut self) -> Result<Option<T>, JsonError> {
+    /// Read up to the next key, returning false at the end of the object.
+    fn start(&mut self) -> Result<bool, JsonError> {
         if !self.started {
             expect(self.decoder, b'{')?;
             if expect(self.decoder, b'}').is_ok() {
-                return Ok(None)
+                return Ok(false)
             }
             self.started = true;
+            self.decoder.enter(PathItem::Key(""));
         } else {
             if expect(self.decoder, b'}').is_ok() {
-                return Ok(None);
+                self.decoder.leave();
+                return Ok(false);
             } else {
                 expect(self.decoder, b',')?;
             }
         }
+        Ok(true)
+    }
+
+    /// Skip the value of the current key, for keys which are not used.
+    pub fn skip_value(&mut self) -> Result<(), JsonError> {
+        skip_value(self.decoder)
+    }
+
+    /// When decoding maps, we do accept non-strings as keys.
+    /// 
+    /// Also many types have string encodings.
+    pub fn next_map_key<T : JsonDecode<'de> + Default>(&mut self) -> Result<Option<T>, JsonError> {
+        if !self.start()? {
+            return Ok(None);
+        }
+        skip_whitespace(self.decoder);
+        let raw = self.decoder.cur();
         let mut key = T::default();
-        key.decode_json(self.decoder)?;
+        let result = key.decode_json(self.decoder);
+        // The path shows the key as written.
+        let raw = &raw[..raw.len() - self.decoder.len()];
+        let raw = raw.strip_prefix(b"\"").and_then(|r| r.strip_suffix(b"\"")).unwrap_or(raw);
+        self.decoder.step(PathItem::Key(std::str::from_utf8(raw).unwrap_or("?")));
+        result?;
         expect(self.decoder, b':')?;
         return Ok(Some(key));
     }
@@ -504,7 +1093,19 @@ impl<'b, 'de> ObjectParser<'b, 'de> {
 
 #[cfg(test)]
 mod tests {
-    use crate::json::{decode_object, expect, skip_whitespace, Decoder, ObjectParser};
+    use std::collections::BTreeMap;
+
+    use crate::{
+        ethereum::{
+            cancun::{blocks::Header, fork_types::{Account, Address}},
+            crypto::hash::keccak256,
+            ethereum_rlp::rlp,
+            ethereum_types::{bytes::Bytes, numeric::U256},
+            genesis::Genesis,
+        },
+        impl_json,
+        json::{decode_object, expect, from_json, from_json_with, skip_whitespace, to_json, to_json_pretty, Decoder, ObjectParser, Value},
+    };
 
     use super::{JsonDecode, JsonError};
 
@@ -546,36 +1147,91 @@ mod tests {
         assert!(b.decode_json(&mut cursor).is_err());
     }
 
-    // #[test]
-    // fn test_struct() {
-    //     #[derive(Debug, PartialEq, Default)]
-    //     struct ABC {
-    //         a: u128,
-    //         b: bool,
-    //         c: u128,
-    //     }
+    #[test]
+    fn test_struct() {
+        #[derive(Debug, PartialEq, Default)]
+        struct ABC {
+            a: u128,
+            b: bool,
+            c: Option<u128>,
+            d: String,
+        }
 
-    //     // impl_json!(ABC : a "a", b "b", c "c");
-    //     impl_json!(ABC : a "a");
+        impl_json!(ABC : a "a", b "b", c "cValue", d "d");
 
-    //     // impl<'de> JsonDecode<'de> for ABC {
-    //     //     fn decode_json(&mut self, decoder: &mut Decoder<'de>) -> Result<(), super::JsonError> {
-    //     //         let mut p = ObjectParser::new(decoder);
-    //     //         p.decode_three(&mut self.a, "a", &mut self.b, "b", &mut self.c, "c")
-    //     //     }
-    //     // }
+        let mut b : ABC = Default::default();
+        b.decode_json(&mut Decoder::new(b"{}")).unwrap();
+        assert_eq!(b, ABC{..Default::default()});
 
-    //     let mut cursor = b"{}".as_slice();
-    //     let mut b : ABC = Default::default();
-    //     b.decode_json(&mut Decoder::new(cursor)).unwrap();
-    //     assert_eq!(b, ABC{..Default::default()});
+        let mut b : ABC = Default::default();
+        b.decode_json(&mut Decoder::new(br#"{"a":1,"b":true,"cValue":"0x2","d":"x"}"#)).unwrap();
+        assert_eq!(b, ABC{a:1, b:true, c:Some(2), d:"x".into()});
+        assert_eq!(to_json(&b), r#"{"a":"0x1","b":true,"cValue":"0x2","d":"x"}"#);
 
-    //     let mut cursor = br#"{"a":1,"b":true,"c":2}"#.as_slice();
-    //     let mut b : ABC = Default::default();
-    //     b.decode_json(&mut Decoder::new(cursor)).unwrap();
-    //     assert_eq!(b, ABC{a:1, b:true, c:2});
+        let mut b : ABC = Default::default();
+        b.decode_json(&mut Decoder::new(br#"{"cValue":null}"#)).unwrap();
+        assert_eq!(b.c, None);
+        assert_eq!(to_json(&b), r#"{"a":"0x0","b":false,"d":""}"#);
 
-    // }
+        let mut b : ABC = Default::default();
+        assert!(matches!(
+            b.decode_json(&mut Decoder::new(br#"{"a":1,"e":2}"#)),
+            Err(JsonError::MissingKey),
+        ));
+
+        #[derive(Debug, PartialEq, Default)]
+        struct Lenient {
+            a: u128,
+        }
+
+        impl_json!(#[skip_unknown] Lenient : a "a");
+
+        let mut b : Lenient = Default::default();
+        b.decode_json(&mut Decoder::new(br#"{"x":[1.5,{"y":null}],"a":3,"z":-2e3}"#)).unwrap();
+        assert_eq!(b, Lenient{a:3});
+    }
+
+    #[test]
+    fn test_rpc_header() {
+        // Mainnet block 1 as returned by eth_getBlockByNumber.
+        let json = br#"{
+            "difficulty": "0x3ff800000",
+            "extraData": "0x476574682f76312e302e302f6c696e75782f676f312e342e32",
+            "gasLimit": "0x1388",
+            "gasUsed": "0x0",
+            "hash": "0x88e96d4537bea4d9c05d12549907b32561d3bf31f45aae734cdc119f13406cb6",
+            "logsBloom": "0x00000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000",
+            "miner": "0x05a56e2d52c817161883f50c441c3228cfe54d9f",
+            "mixHash": "0x969b900de27b6ac6a67742365dd65f55a0526c41fd18e1b16f1a1215c2e66f59",
+            "nonce": "0x539bd4979fef1ec4",
+            "number": "0x1",
+            "parentHash": "0xd4e56740f876aef8c010b86a40d5f56745a118d0906a34e69aec8c0db1cb8fa3",
+            "receiptsRoot": "0x56e81f171bcc55a6ff8345e692c0f86e5b48e01b996cadc001622fb5e363b421",
+            "sha3Uncles": "0x1dcc4de8dec75d7aab85b567b6ccd41ad312451b948a7413f0a142fd40d49347",
+            "size": "0x219",
+            "stateRoot": "0xd67e4d450343046425ae4271474353857ab860dbc0a1dde64b41b5cd3a532bf3",
+            "timestamp": "0x55ba4224",
+            "totalDifficulty": "0x7ff800000",
+            "transactions": [],
+            "transactionsRoot": "0x56e81f171bcc55a6ff8345e692c0f86e5b48e01b996cadc001622fb5e363b421",
+            "uncles": []
+        }"#;
+        let mut header = Header::default();
+        header.decode_json(&mut Decoder::new(json)).unwrap();
+        assert_eq!(header.number, 1);
+        assert_eq!(header.base_fee_per_gas, None);
+        assert_eq!(
+            to_json(&keccak256(&rlp::encode(&header).unwrap())),
+            r#""0x88e96d4537bea4d9c05d12549907b32561d3bf31f45aae734cdc119f13406cb6""#,
+        );
+
+        let encoded = to_json(&header);
+        assert!(encoded.starts_with(r#"{"parentHash":"0xd4e5"#), "{encoded}");
+        assert!(!encoded.contains("baseFeePerGas"), "{encoded}");
+        let mut decoded = Header::default();
+        decoded.decode_json(&mut Decoder::new(encoded.as_bytes())).unwrap();
+        assert_eq!(to_json(&decoded), encoded);
+    }
 
     #[test]
     fn test_string() {
@@ -587,5 +1243,183 @@ mod tests {
         let mut b : String = Default::default();
         b.decode_json(&mut Decoder::new(cursor)).unwrap();
         assert_eq!(b, "abc\\\"\u{8}\u{c}\n\r你def");
+
+        // An escaped backslash does not escape the quote after it.
+        assert_eq!(from_json::<String>(br#""a\\""#).unwrap(), "a\\");
+        assert_eq!(from_json::<Vec<String>>(br#"["\\", "\\\"\\"]"#).unwrap(), ["\\", "\\\"\\"]);
+        assert!(matches!(from_json::<String>(br#""a\""#).unwrap_err().error, JsonError::UnterminatedString));
+
+        // Characters outside the basic multilingual plane are escaped as
+        // surrogate pairs.
+        assert_eq!(from_json::<String>(br#""\ud83d\ude00\/\t""#).unwrap(), "\u{1f600}/\t");
+        for lone in [&br#""\ud83d""#[..], br#""\ude00""#, br#""\ud83dx""#, br#""\ud83d\u0041""#, br#""\u12""#] {
+            assert!(matches!(from_json::<String>(lone).unwrap_err().error, JsonError::BadString), "{lone:?}");
+        }
+        let value: Value = from_json(br#"{"a\"b": "\\"}"#).unwrap();
+        assert_eq!(value, Value::Map([("a\"b".into(), Value::String("\\".into()))].into()));
+    }
+
+    #[test]
+    fn test_encode() {
+        assert_eq!(to_json("a\"b\\c\n\u{1}"), r#""a\"b\\c\n\u0001""#);
+        assert_eq!(to_json(&U256::ZERO), r#""0x0""#);
+        assert_eq!(to_json(&U256::from(0x1234_u64)), r#""0x1234""#);
+        assert_eq!(to_json(&0x0f_u128), r#""0xf""#);
+        assert_eq!(to_json(&Bytes(vec![0, 1])), r#""0x0001""#);
+        assert_eq!(to_json(&Vec::<bool>::new()), "[]");
+        assert_eq!(to_json(&vec![Some(true), None]), "[true,null]");
+
+        let mut alloc = BTreeMap::new();
+        alloc.insert(Address::from_be_bytes([1; 20]), Account { nonce: 1, balance: U256::from(2_u64), code: Bytes::default() });
+        let json = to_json(&alloc);
+        assert_eq!(
+            json,
+            r#"{"0x0101010101010101010101010101010101010101":{"nonce":"0x1","balance":"0x2","code":"0x"}}"#,
+        );
+        let mut decoded = BTreeMap::<Address, Account>::new();
+        decoded.decode_json(&mut Decoder::new(json.as_bytes())).unwrap();
+        assert_eq!(decoded, alloc);
+
+        let mut numbers = BTreeMap::new();
+        numbers.insert(1_u128, "one");
+        assert_eq!(to_json_pretty(&numbers), "{\n  \"0x1\": \"one\"\n}");
+        assert_eq!(to_json_pretty(&BTreeMap::<u128, u128>::new()), "{}");
+
+        let mut value = Value::Null;
+        value.decode_json(&mut Decoder::new(br#"{"a": [true, null, "x"], "b": {}}"#)).unwrap();
+        assert_eq!(to_json(&value), r#"{"a":[true,null,"x"],"b":{}}"#);
+        assert_eq!(to_json_pretty(&value), "{\n  \"a\": [\n    true,\n    null,\n    \"x\"\n  ],\n  \"b\": {}\n}");
+    }
+
+    #[test]
+    fn test_address_checksums() {
+        let checksummed = br#"{"0x5aAeb6053F3E94C9b9A09f33669435E7Ef1BeAed": {}}"#;
+        let alloc: BTreeMap<Address, Account> = from_json(checksummed).unwrap();
+        let address = alloc.keys().next().unwrap();
+        assert_eq!(address.to_string(), "0x5aAeb6053F3E94C9b9A09f33669435E7Ef1BeAed");
+        for plain in [&br#""0x5aaeb6053f3e94c9b9a09f33669435e7ef1beaed""#[..], br#""0x5AAEB6053F3E94C9B9A09F33669435E7EF1BEAED""#] {
+            assert_eq!(&from_json::<Address>(plain).unwrap(), address);
+        }
+
+        let bad = br#"{"0x5aAeb6053F3E94C9b9A09f33669435E7Ef1BeAeD": {}}"#;
+        let e = from_json::<BTreeMap<Address, Account>>(bad).unwrap_err();
+        assert!(matches!(e.error, JsonError::BadChecksum));
+        assert_eq!(e.path, "$.0x5aAeb6053F3E94C9b9A09f33669435E7Ef1BeAeD");
+        let alloc: BTreeMap<Address, Account> = from_json_with(Decoder::new(bad).skip_checksums()).unwrap();
+        assert_eq!(alloc.keys().next().unwrap(), address);
+    }
+
+    #[test]
+    fn test_error_location() {
+        let json = b"{\n  \"nonce\": \"0x0\",\n  \"alloc\": {\n    \"0x0101010101010101010101010101010101010101\": {\"balance\": \"0xzz\"}\n  }\n}";
+        let e = from_json::<Genesis>(json).unwrap_err();
+        assert!(matches!(e.error, JsonError::ExpectedHexString));
+        assert_eq!(e.path, "$.alloc.0x0101010101010101010101010101010101010101.balance");
+        // Just after the value, which is where decoding stopped.
+        assert_eq!((e.line, e.column, e.offset), (4, 69, 101));
+
+        let e = from_json::<Vec<Vec<bool>>>(b"[[], [true,\n false, no]]").unwrap_err();
+        assert!(matches!(e.error, JsonError::ExpectedBool));
+        assert_eq!(e.path, "$[1][2]");
+        assert_eq!((e.line, e.column), (2, 11));
+
+        let e = from_json::<Vec<bool>>(b"[true] x").unwrap_err();
+        assert!(matches!(e.error, JsonError::UnexpectedChar));
+        assert_eq!((e.path.as_str(), e.offset), ("$", 7));
+        assert_eq!(from_json::<Vec<bool>>(b" [true] ").unwrap(), [true]);
+    }
+
+    #[test]
+    fn test_genesis_round_trip() {
+        let genesis = Genesis::mainnet().unwrap();
+        let json = to_json_pretty(&genesis);
+        let mut decoded = Genesis::default();
+        decoded.decode_json(&mut Decoder::new(json.as_bytes())).unwrap();
+        assert_eq!(decoded.alloc, genesis.alloc);
+        assert_eq!(to_json(&decoded), to_json(&genesis));
+    }
+
+    /// Round trips of arbitrary values, and decoding of mutated and
+    /// arbitrary input, which must fail cleanly rather than panic.
+    mod properties {
+        use proptest::{collection::vec, prelude::*};
+
+        use crate::{
+            ethereum::{cancun::blocks::Header, ethereum_types::numeric::U256, genesis::Genesis},
+            json::{from_json, to_json, to_json_pretty, Value},
+        };
+
+        fn number() -> impl Strategy<Value = String> {
+            (any::<i64>(), proptest::option::of(any::<u32>()), proptest::option::of(any::<i8>())).prop_map(
+                |(int, fraction, exponent)| {
+                    let mut n = int.to_string();
+                    if let Some(fraction) = fraction {
+                        n += &format!(".{fraction}");
+                    }
+                    if let Some(exponent) = exponent {
+                        n += &format!("e{exponent}");
+                    }
+                    n
+                },
+            )
+        }
+
+        fn value() -> impl Strategy<Value = Value> {
+            let leaf = prop_oneof![
+                Just(Value::Null),
+                any::<bool>().prop_map(Value::Bool),
+                number().prop_map(|n| Value::Numeric(n.into())),
+                ".{0,20}".prop_map(|s| Value::String(s.into())),
+            ];
+            leaf.prop_recursive(4, 64, 8, |inner| {
+                prop_oneof![
+                    vec(inner.clone(), 0..8).prop_map(|a| Value::Array(a.into())),
+                    vec((".{0,8}", inner), 0..8)
+                        .prop_map(|m| Value::Map(m.into_iter().map(|(k, v)| (k.into(), v)).collect())),
+                ]
+            })
+        }
+
+        /// A document with objects, arrays, strings, hex and numbers.
+        fn document() -> String {
+            let header = Header { number: 1, gas_limit: 30_000_000, timestamp: U256::from(12_u32), base_fee_per_gas: Some(7), ..Default::default() };
+            format!(r#"{{"header": {}, "list": [1, -2.5e3, true, null, "a\"b\u00e9"]}}"#, to_json_pretty(&header))
+        }
+
+        proptest! {
+            #[test]
+            fn values_round_trip(value in value()) {
+                prop_assert_eq!(from_json::<Value>(to_json(&value).as_bytes()).unwrap(), value.clone());
+                prop_assert_eq!(from_json::<Value>(to_json_pretty(&value).as_bytes()).unwrap(), value);
+            }
+
+            #[test]
+            fn mutated_documents_decode_cleanly(edits in vec((any::<usize>(), any::<u8>(), 0..3_u8), 1..4)) {
+                let mut json = document().into_bytes();
+                for (i, b, op) in edits {
+                    let len = json.len();
+                    match op {
+                        0 => json[i % len] = b,
+                        1 => json.insert(i % (len + 1), b),
+                        _ => json.truncate(i % (len + 1)),
+                    }
+                    if json.is_empty() {
+                        break;
+                    }
+                }
+                let _ = from_json::<Value>(&json);
+                if let Ok(value) = from_json::<Value>(&json) {
+                    let _ = from_json::<Header>(to_json(&value).as_bytes());
+                }
+                let _ = from_json::<Genesis>(&json);
+            }
+
+            #[test]
+            fn arbitrary_bytes_decode_cleanly(json in vec(any::<u8>(), 0..200)) {
+                let _ = from_json::<Value>(&json);
+                let _ = from_json::<Header>(&json);
+                let _ = from_json::<Vec<U256>>(&json);
+            }
+        }
     }
 }
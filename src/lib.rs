@@ -5,8 +5,43 @@ use std::collections::BTreeMap;
 
 pub mod ethereum;
 
+pub mod prelude;
+
 pub mod json;
 
+pub mod call_graph;
+
+pub mod spec_tests;
+
+pub mod t8n;
+
+pub mod b11r;
+
+pub mod builder;
+
+pub mod engine;
+
+pub mod devp2p;
+
+pub mod log;
+
+pub mod metrics;
+
+#[cfg(feature = "ffi")]
+pub mod ffi;
+
+#[cfg(feature = "python")]
+pub mod python;
+
+#[cfg(feature = "wasm")]
+pub mod wasm;
+
+#[cfg(feature = "rpc")]
+pub mod rpc;
+
+#[cfg(feature = "revm")]
+pub mod differential;
 
-// mod ejit_evm;
+#[cfg(feature = "jit")]
+pub mod ejit_evm;
 
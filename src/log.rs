@@ -0,0 +1,243 @@
+//! Diagnostic logging, off unless a logger is installed.
+//!
+//! The crate reports what it does through a `Logger`, at five levels of
+//! detail. Nothing is formatted for levels above `set_max_level`, which is
+//! off by default, so logging costs one atomic load when it is not wanted.
+//! `init` logs to standard error, and `init_from_env` does so at the level
+//! named by `EJIT_LOG`:
+//!
+//! ```sh
+//! EJIT_LOG=debug ejit-evm replay block.replay
+//! ```
+//!
+//! A `SpanGuard` from `span` marks a unit of work, such as importing a
+//! block. The records emitted while it lives carry its name and fields, and
+//! closing it emits a record with how long it took:
+//!
+//! ```text
+//! DEBUG block{number=1 txs=2}: ejit_evm::ethereum::cancun::fork: imported
+//! DEBUG block{number=1 txs=2}: span: closed after 1.2ms
+//! ```
+
+use std::{
+    cell::RefCell,
+    fmt,
+    str::FromStr,
+    sync::{
+        atomic::{AtomicU8, Ordering},
+        Arc, RwLock,
+    },
+    time::{Duration, Instant},
+};
+
+/// How much detail a record is.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum Level {
+    Error = 1,
+    Warn,
+    Info,
+    Debug,
+    Trace,
+}
+
+impl fmt::Display for Level {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.pad(match self {
+            Level::Error => "ERROR",
+            Level::Warn => "WARN",
+            Level::Info => "INFO",
+            Level::Debug => "DEBUG",
+            Level::Trace => "TRACE",
+        })
+    }
+}
+
+impl FromStr for Level {
+    type Err = ();
+
+    fn from_str(s: &str) -> Result<Self, ()> {
+        match s.to_ascii_lowercase().as_str() {
+            "error" => Ok(Level::Error),
+            "warn" => Ok(Level::Warn),
+            "info" => Ok(Level::Info),
+            "debug" => Ok(Level::Debug),
+            "trace" => Ok(Level::Trace),
+            _ => Err(()),
+        }
+    }
+}
+
+/// An open span, as seen by a `Logger`.
+#[derive(Debug, Clone)]
+pub struct SpanInfo {
+    pub name: &'static str,
+    pub fields: String,
+}
+
+impl fmt::Display for SpanInfo {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}{{{}}}", self.name, self.fields)
+    }
+}
+
+/// One thing to log.
+pub struct Record<'a> {
+    pub level: Level,
+    /// The module the record comes from.
+    pub target: &'static str,
+    pub message: fmt::Arguments<'a>,
+    /// The spans open on the thread, outermost first.
+    pub spans: &'a [SpanInfo],
+}
+
+/// Where records go.
+pub trait Logger: Send + Sync {
+    fn log(&self, record: &Record);
+}
+
+/// Writes records to standard error, one per line.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct StderrLogger;
+
+impl Logger for StderrLogger {
+    fn log(&self, record: &Record) {
+        let spans: Vec<String> = record.spans.iter().map(ToString::to_string).collect();
+        let spans = if spans.is_empty() { String::new() } else { spans.join(":") + ": " };
+        eprintln!("{:5} {spans}{}: {}", record.level, record.target, record.message);
+    }
+}
+
+/// The most detailed level logged, 0 for none.
+static MAX_LEVEL: AtomicU8 = AtomicU8::new(0);
+static LOGGER: RwLock<Option<Arc<dyn Logger>>> = RwLock::new(None);
+
+thread_local! {
+    static SPANS: RefCell<Vec<SpanInfo>> = const { RefCell::new(Vec::new()) };
+}
+
+/// Send records to `logger`, replacing the one installed before.
+pub fn set_logger(logger: Arc<dyn Logger>) {
+    *LOGGER.write().unwrap_or_else(|poisoned| poisoned.into_inner()) = Some(logger);
+}
+
+/// Log records at `level` and less detailed ones, or nothing with `None`.
+pub fn set_max_level(level: Option<Level>) {
+    MAX_LEVEL.store(level.map_or(0, |level| level as u8), Ordering::Relaxed);
+}
+
+/// Log to standard error at `level` and less detailed levels.
+pub fn init(level: Level) {
+    set_logger(Arc::new(StderrLogger));
+    set_max_level(Some(level));
+}
+
+/// `init` at the level named by the `EJIT_LOG` environment variable, if it
+/// names one.
+pub fn init_from_env() {
+    if let Some(level) = std::env::var("EJIT_LOG").ok().and_then(|level| level.parse().ok()) {
+        init(level);
+    }
+}
+
+/// Whether records at `level` are logged.
+pub fn enabled(level: Level) -> bool {
+    level as u8 <= MAX_LEVEL.load(Ordering::Relaxed)
+}
+
+/// Send a record to the logger. Use the macros, which check `enabled`
+/// before formatting anything.
+pub fn emit(level: Level, target: &'static str, message: fmt::Arguments) {
+    let logger = LOGGER.read().unwrap_or_else(|poisoned| poisoned.into_inner()).clone();
+    if let Some(logger) = logger {
+        SPANS.with(|spans| logger.log(&Record { level, target, message, spans: &spans.borrow() }));
+    }
+}
+
+/// Open a span named `name` on this thread, if `level` is logged. It closes
+/// when the guard is dropped.
+pub fn span(level: Level, name: &'static str, fields: fmt::Arguments) -> SpanGuard {
+    if !enabled(level) {
+        return SpanGuard { open: None };
+    }
+    SPANS.with(|spans| spans.borrow_mut().push(SpanInfo { name, fields: fields.to_string() }));
+    SpanGuard { open: Some((level, Instant::now())) }
+}
+
+/// An open span, closed on drop.
+#[must_use = "the span closes when the guard is dropped"]
+pub struct SpanGuard {
+    open: Option<(Level, Instant)>,
+}
+
+impl Drop for SpanGuard {
+    fn drop(&mut self) {
+        if let Some((level, start)) = self.open {
+            let elapsed: Duration = start.elapsed();
+            emit(level, "span", format_args!("closed after {elapsed:.1?}"));
+            SPANS.with(|spans| spans.borrow_mut().pop());
+        }
+    }
+}
+
+macro_rules! log {
+    ($level: expr, $($arg: tt)+) => {
+        if $crate::log::enabled($level) {
+            $crate::log::emit($level, module_path!(), format_args!($($arg)+));
+        }
+    };
+}
+
+macro_rules! debug {
+    ($($arg: tt)+) => { $crate::log::log!($crate::log::Level::Debug, $($arg)+) };
+}
+
+macro_rules! trace {
+    ($($arg: tt)+) => { $crate::log::log!($crate::log::Level::Trace, $($arg)+) };
+}
+
+pub(crate) use {debug, log, trace};
+
+#[cfg(test)]
+mod tests {
+    use std::sync::{Arc, Mutex};
+
+    use super::{enabled, set_logger, set_max_level, span, Level, Logger, Record};
+
+    #[derive(Default)]
+    struct Capture(Mutex<Vec<String>>);
+
+    impl Logger for Capture {
+        fn log(&self, record: &Record) {
+            // Other tests log at the same time, on other threads.
+            if record.spans.first().is_some_and(|span| span.name == "log_test") {
+                let spans: Vec<String> = record.spans.iter().map(ToString::to_string).collect();
+                self.0.lock().unwrap().push(format!("{} {}: {}", record.level, spans.join(":"), record.message));
+            }
+        }
+    }
+
+    #[test]
+    fn spans_and_levels() {
+        assert_eq!("Debug".parse(), Ok(Level::Debug));
+        assert!("loud".parse::<Level>().is_err());
+
+        let capture = Arc::new(Capture::default());
+        set_logger(capture.clone());
+        set_max_level(Some(Level::Debug));
+        assert!(enabled(Level::Warn) && enabled(Level::Debug) && !enabled(Level::Trace));
+        {
+            let _outer = span(Level::Debug, "log_test", format_args!("n={}", 1));
+            super::debug!("shown {}", 2);
+            super::trace!("hidden");
+            let _inner = span(Level::Trace, "hidden", format_args!(""));
+            let _inner = span(Level::Warn, "inner", format_args!("x"));
+            super::log!(Level::Warn, "nested");
+        }
+        let lines = capture.0.lock().unwrap().clone();
+        assert_eq!(lines.len(), 4, "{lines:?}");
+        assert_eq!(lines[0], "DEBUG log_test{n=1}: shown 2");
+        assert_eq!(lines[1], "WARN log_test{n=1}:inner{x}: nested");
+        assert!(lines[2].starts_with("WARN log_test{n=1}:inner{x}: closed after "), "{lines:?}");
+        assert!(lines[3].starts_with("DEBUG log_test{n=1}: closed after "), "{lines:?}");
+    }
+}
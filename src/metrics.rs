@@ -0,0 +1,311 @@
+//! Counters and timings of block import and code execution, for dashboards
+//! and benchmarks.
+//!
+//! The metrics are process wide and cheap to record: counters are atomics
+//! and histograms count observations into fixed buckets. `metrics` takes a
+//! consistent enough copy of all of them, which is the pull API:
+//!
+//! ```
+//! use ejit_evm::{
+//!     builder::build_payload,
+//!     engine::PayloadAttributes,
+//!     ethereum::{cancun::fork::BlockChain, ethereum_types::numeric::U256, genesis::Genesis},
+//!     metrics,
+//! };
+//!
+//! let mut chain = BlockChain::from_genesis(Genesis::post_merge(&["shanghai", "cancun"]))?;
+//! let attributes = PayloadAttributes { timestamp: U256::from(12_u32), ..Default::default() };
+//! let block = build_payload(&chain, &[], &attributes)?.block;
+//! chain.import_block(block)?;
+//! assert!(metrics::metrics().blocks_imported >= 1);
+//! println!("{:.0} gas/s", metrics::metrics().gas_per_second());
+//! # Ok::<(), ejit_evm::ethereum::exceptions::Exception>(())
+//! ```
+//!
+//! `Metrics::to_prometheus` renders them in the Prometheus text format,
+//! which the RPC server serves on `GET /metrics` when built with
+//! `Server::with_metrics`.
+
+use std::{
+    fmt::Write,
+    sync::atomic::{AtomicU64, Ordering},
+    time::{Duration, Instant},
+};
+
+/// A count which only goes up.
+#[derive(Debug, Default)]
+pub struct Counter(AtomicU64);
+
+impl Counter {
+    pub const fn new() -> Self {
+        Self(AtomicU64::new(0))
+    }
+
+    pub fn add(&self, n: u64) {
+        self.0.fetch_add(n, Ordering::Relaxed);
+    }
+
+    pub fn inc(&self) {
+        self.add(1);
+    }
+
+    pub fn get(&self) -> u64 {
+        self.0.load(Ordering::Relaxed)
+    }
+}
+
+/// Upper bounds of the buckets of a `Histogram`, in seconds: from ten
+/// microseconds, for hashing a small trie, to ten seconds, for a slow block.
+pub const BUCKETS: [f64; 13] = [1e-5, 5e-5, 1e-4, 5e-4, 1e-3, 5e-3, 0.01, 0.05, 0.1, 0.5, 1.0, 5.0, 10.0];
+
+/// A distribution of durations.
+#[derive(Debug)]
+pub struct Histogram {
+    /// Observations at most each bound of `BUCKETS`, not cumulative.
+    buckets: [AtomicU64; BUCKETS.len()],
+    count: AtomicU64,
+    sum_nanos: AtomicU64,
+}
+
+impl Histogram {
+    pub const fn new() -> Self {
+        Self {
+            buckets: [const { AtomicU64::new(0) }; BUCKETS.len()],
+            count: AtomicU64::new(0),
+            sum_nanos: AtomicU64::new(0),
+        }
+    }
+
+    pub fn observe(&self, duration: Duration) {
+        let seconds = duration.as_secs_f64();
+        if let Some(bucket) = BUCKETS.iter().position(|bound| seconds <= *bound) {
+            self.buckets[bucket].fetch_add(1, Ordering::Relaxed);
+        }
+        self.count.fetch_add(1, Ordering::Relaxed);
+        self.sum_nanos.fetch_add(duration.as_nanos() as u64, Ordering::Relaxed);
+    }
+
+    /// Run `f` and observe how long it took.
+    pub fn time<T>(&self, f: impl FnOnce() -> T) -> T {
+        let start = Instant::now();
+        let result = f();
+        self.observe(start.elapsed());
+        result
+    }
+
+    pub fn get(&self) -> HistogramSnapshot {
+        let mut cumulative = 0;
+        let buckets = BUCKETS.iter().zip(&self.buckets).map(|(bound, count)| {
+            cumulative += count.load(Ordering::Relaxed);
+            (*bound, cumulative)
+        });
+        HistogramSnapshot {
+            buckets: buckets.collect(),
+            count: self.count.load(Ordering::Relaxed),
+            sum: Duration::from_nanos(self.sum_nanos.load(Ordering::Relaxed)),
+        }
+    }
+}
+
+/// The state of a `Histogram` at one time.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct HistogramSnapshot {
+    /// Upper bound in seconds and number of observations at most it, as in
+    /// Prometheus.
+    pub buckets: Vec<(f64, u64)>,
+    pub count: u64,
+    pub sum: Duration,
+}
+
+impl HistogramSnapshot {
+    /// The mean duration, zero if nothing was observed.
+    pub fn mean(&self) -> Duration {
+        match self.count {
+            0 => Duration::ZERO,
+            count => Duration::from_nanos((self.sum.as_nanos() / count as u128) as u64),
+        }
+    }
+}
+
+/// Blocks executed and added to the chain.
+pub static BLOCKS_IMPORTED: Counter = Counter::new();
+/// Blocks which failed validation or execution.
+pub static BLOCKS_INVALID: Counter = Counter::new();
+/// Transactions of the blocks imported.
+pub static TRANSACTIONS: Counter = Counter::new();
+/// Gas used by the blocks imported.
+pub static GAS_USED: Counter = Counter::new();
+/// Time to execute and validate a block, valid or not.
+pub static BLOCK_PROCESSING: Histogram = Histogram::new();
+/// Time to compute a state root.
+pub static TRIE_HASHING: Histogram = Histogram::new();
+/// Hashes of trie keys and code found in the caches of `keccak256_cached`,
+/// on every thread.
+pub static KECCAK_CACHE_HITS: Counter = Counter::new();
+pub static KECCAK_CACHE_MISSES: Counter = Counter::new();
+/// Lookups of the valid jump destinations of code in the code caches of
+/// chains, found or not.
+pub static CODE_CACHE_HITS: Counter = Counter::new();
+pub static CODE_CACHE_MISSES: Counter = Counter::new();
+/// Time for the compiler of a `HybridExecutor` to compile promoted code,
+/// whether it took the code or declined it.
+pub static JIT_COMPILATION: Histogram = Histogram::new();
+/// Code promoted by a `HybridExecutor`, which the compiler took and which
+/// it declined.
+pub static JIT_COMPILED: Counter = Counter::new();
+pub static JIT_DECLINED: Counter = Counter::new();
+/// Frames which ran compiled code.
+pub static JIT_COMPILED_FRAMES: Counter = Counter::new();
+
+/// All the metrics at one time.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Metrics {
+    pub blocks_imported: u64,
+    pub blocks_invalid: u64,
+    pub transactions: u64,
+    pub gas_used: u64,
+    pub block_processing: HistogramSnapshot,
+    pub trie_hashing: HistogramSnapshot,
+    pub keccak_cache_hits: u64,
+    pub keccak_cache_misses: u64,
+    pub code_cache_hits: u64,
+    pub code_cache_misses: u64,
+    pub jit_compilation: HistogramSnapshot,
+    pub jit_compiled: u64,
+    pub jit_declined: u64,
+    pub jit_compiled_frames: u64,
+}
+
+/// Read every metric.
+pub fn metrics() -> Metrics {
+    Metrics {
+        blocks_imported: BLOCKS_IMPORTED.get(),
+        blocks_invalid: BLOCKS_INVALID.get(),
+        transactions: TRANSACTIONS.get(),
+        gas_used: GAS_USED.get(),
+        block_processing: BLOCK_PROCESSING.get(),
+        trie_hashing: TRIE_HASHING.get(),
+        keccak_cache_hits: KECCAK_CACHE_HITS.get(),
+        keccak_cache_misses: KECCAK_CACHE_MISSES.get(),
+        code_cache_hits: CODE_CACHE_HITS.get(),
+        code_cache_misses: CODE_CACHE_MISSES.get(),
+        jit_compilation: JIT_COMPILATION.get(),
+        jit_compiled: JIT_COMPILED.get(),
+        jit_declined: JIT_DECLINED.get(),
+        jit_compiled_frames: JIT_COMPILED_FRAMES.get(),
+    }
+}
+
+impl Metrics {
+    /// Transactions per second of block processing.
+    pub fn transactions_per_second(&self) -> f64 {
+        per_second(self.transactions, self.block_processing.sum)
+    }
+
+    /// Gas per second of block processing.
+    pub fn gas_per_second(&self) -> f64 {
+        per_second(self.gas_used, self.block_processing.sum)
+    }
+
+    /// Fraction of `keccak256_cached` calls answered from the cache.
+    pub fn keccak_cache_hit_rate(&self) -> f64 {
+        let total = self.keccak_cache_hits + self.keccak_cache_misses;
+        if total == 0 { 0.0 } else { self.keccak_cache_hits as f64 / total as f64 }
+    }
+
+    /// The metrics in the Prometheus text exposition format.
+    pub fn to_prometheus(&self) -> String {
+        let mut out = String::new();
+        let counters = [
+            ("ejit_blocks_imported_total", "Blocks executed and added to the chain.", self.blocks_imported),
+            ("ejit_blocks_invalid_total", "Blocks which failed validation or execution.", self.blocks_invalid),
+            ("ejit_transactions_total", "Transactions of the blocks imported.", self.transactions),
+            ("ejit_gas_used_total", "Gas used by the blocks imported.", self.gas_used),
+            ("ejit_keccak_cache_hits_total", "Hashes found in the keccak cache.", self.keccak_cache_hits),
+            ("ejit_keccak_cache_misses_total", "Hashes not found in the keccak cache.", self.keccak_cache_misses),
+            ("ejit_code_cache_hits_total", "Jump destinations found in a code cache.", self.code_cache_hits),
+            ("ejit_code_cache_misses_total", "Jump destinations not found in a code cache.", self.code_cache_misses),
+            ("ejit_jit_compiled_total", "Promoted code the compiler took.", self.jit_compiled),
+            ("ejit_jit_declined_total", "Promoted code the compiler declined.", self.jit_declined),
+            ("ejit_jit_compiled_frames_total", "Frames which ran compiled code.", self.jit_compiled_frames),
+        ];
+        for (name, help, value) in counters {
+            let _ = writeln!(out, "# HELP {name} {help}\n# TYPE {name} counter\n{name} {value}");
+        }
+        let histograms = [
+            ("ejit_block_processing_seconds", "Time to execute and validate a block.", &self.block_processing),
+            ("ejit_trie_hashing_seconds", "Time to compute a state root.", &self.trie_hashing),
+            ("ejit_jit_compilation_seconds", "Time to compile promoted code.", &self.jit_compilation),
+        ];
+        for (name, help, histogram) in histograms {
+            let _ = writeln!(out, "# HELP {name} {help}\n# TYPE {name} histogram");
+            for (bound, count) in &histogram.buckets {
+                let _ = writeln!(out, "{name}_bucket{{le=\"{bound}\"}} {count}");
+            }
+            let _ = writeln!(out, "{name}_bucket{{le=\"+Inf\"}} {}", histogram.count);
+            let _ = writeln!(out, "{name}_sum {}", histogram.sum.as_secs_f64());
+            let _ = writeln!(out, "{name}_count {}", histogram.count);
+        }
+        out
+    }
+}
+
+fn per_second(n: u64, time: Duration) -> f64 {
+    if time.is_zero() { 0.0 } else { n as f64 / time.as_secs_f64() }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use super::{metrics, Histogram, HistogramSnapshot, BUCKETS};
+    use crate::ethereum::cancun::fork::fuzz::tests::valid_block;
+
+    #[test]
+    fn histogram() {
+        let histogram = Histogram::new();
+        histogram.observe(Duration::from_micros(3));
+        histogram.observe(Duration::from_millis(2));
+        histogram.observe(Duration::from_secs(60));
+        let snapshot = histogram.get();
+        assert_eq!((snapshot.count, snapshot.sum), (3, Duration::from_nanos(60_002_003_000)));
+        assert_eq!(snapshot.buckets.len(), BUCKETS.len());
+        assert_eq!(snapshot.buckets[0], (1e-5, 1));
+        assert_eq!(snapshot.buckets[4], (1e-3, 1));
+        assert_eq!(snapshot.buckets[5], (5e-3, 2));
+        assert_eq!(snapshot.buckets.last(), Some(&(10.0, 2)));
+        assert_eq!(histogram.time(|| 7), 7);
+        assert_eq!(histogram.get().count, 4);
+    }
+
+    #[test]
+    fn mean() {
+        assert_eq!(HistogramSnapshot::default().mean(), Duration::ZERO);
+        // More observations than a `u32` counts.
+        let snapshot = HistogramSnapshot { count: 1 << 33, sum: Duration::from_secs(1 << 33), ..Default::default() };
+        assert_eq!(snapshot.mean(), Duration::from_secs(1));
+    }
+
+    #[test]
+    fn block_import() {
+        let (mut chain, block) = valid_block();
+        let transactions = block.transactions.len() as u64;
+        let before = metrics();
+        chain.import_block(block).unwrap();
+        let after = metrics();
+        // Other tests import blocks at the same time.
+        assert!(after.blocks_imported > before.blocks_imported);
+        assert!(after.transactions >= before.transactions + transactions);
+        assert!(after.block_processing.count > before.block_processing.count);
+        assert!(after.trie_hashing.count > before.trie_hashing.count);
+        assert!(after.gas_per_second() > 0.0);
+
+        let text = after.to_prometheus();
+        assert!(text.contains("# TYPE ejit_blocks_imported_total counter\n"), "{text}");
+        assert!(text.contains("ejit_block_processing_seconds_bucket{le=\"+Inf\"} "), "{text}");
+        assert!(text.contains("# TYPE ejit_jit_compilation_seconds histogram\n"), "{text}");
+        // The chain finds the code of its genesis contracts again.
+        assert!(after.code_cache_hits + after.code_cache_misses > before.code_cache_hits + before.code_cache_misses);
+        assert!(text.ends_with("\n"));
+    }
+}
@@ -0,0 +1,61 @@
+//! The types and entry points most users of the crate need, in one import.
+//!
+//! The modules under `ethereum` follow the layout of the execution
+//! specification, so a type's path says which fork first defined it rather
+//! than what it is for, and it may move when a fork is added. These names
+//! are the ones to depend on:
+//!
+//! ```no_run
+//! use ejit_evm::prelude::*;
+//!
+//! # let (block_rlp, from, to, calldata) = (Vec::new(), Address::default(), Address::default(), Vec::new());
+//! let mut chain = BlockChain::mainnet()?;
+//! let block: Block = rlp::decode_to_strict(&block_rlp)?;
+//! chain.import_block(block)?;
+//! let result = chain.call(from, Some(to), Bytes(calldata), 1_000_000)?;
+//! # Ok::<(), Exception>(())
+//! ```
+//!
+//! A `Tracer` in `Environment::tracer` sees the steps of
+//! `process_transaction`, `Environment::call_frames` records its calls and
+//! a `CallGraph` summarises the call frames.
+
+pub use crate::{
+    call_graph::CallGraph,
+    ethereum::{
+        cancun::{
+            blocks::{Block, Header, Log, Receipt, Withdrawal},
+            fork::{
+                call::{AccessListOutput, CallOutput},
+                compute_header_hash, process_transaction,
+                receipts::{RpcReceipt, TransactionReceipt},
+                replay::Replay,
+                state_transition, BlockChain, ForkChoiceState,
+            },
+            fork_types::{Account, Address, Bloom, Root},
+            state::{get_account, get_storage, set_account, set_storage, state_root, State, StateBackend},
+            transactions::{
+                AccessListTransaction, BlobTransaction, FeeMarketTransaction, LegacyTransaction, Transaction,
+                TxBuilder,
+            },
+            vm::{
+                exceptions::VmError,
+                tracing::{CallStart, CallTrace, CallTracer, Eip3155Tracer, Step, Tracer},
+                CallFrame, Environment,
+            },
+        },
+        chain_spec::ChainSpec,
+        crypto::hash::{keccak256, Hash32},
+        ethereum_rlp::rlp,
+        ethereum_types::{
+            bytes::{Bytes, Bytes32},
+            numeric::{Uint, U256, U64},
+        },
+        exceptions::Exception,
+        fork_criteria::{ForkActivations, ForkCriteria},
+        forks::{fork_rules, ExecutionRules, Fork},
+        genesis::Genesis,
+        prague::transactions::SetCodeTransaction,
+    },
+    json::{from_json, to_json, JsonDecode, JsonEncode},
+};
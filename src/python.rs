@@ -0,0 +1,158 @@
+//! Python bindings.
+//!
+//! Enabled with the `python` feature. The module is built with maturin,
+//! which picks up the features from `pyproject.toml`:
+//!
+//! ```sh
+//! maturin develop
+//! ```
+//!
+//! and exposes just enough to drive the execution-specs test fixtures
+//! through this implementation and compare the results with the Python
+//! specification:
+//!
+//! ```python
+//! import ejit_evm
+//! chain = ejit_evm.BlockChain.from_genesis_json(open("genesis.json").read())
+//! chain.state_transition(block_rlp)
+//! result = chain.process_transaction(header_rlp, tx_rlp, origin, gas_price)
+//! result["gas_used"], result["logs"], result["error"], result["traces"]
+//! ```
+//!
+//! Blocks, headers, transactions and logs cross the boundary RLP encoded.
+//! Errors are raised as `ejit_evm.EthereumException`.
+
+use pyo3::{
+    create_exception,
+    exceptions::PyException,
+    prelude::*,
+    types::{PyBytes, PyDict},
+};
+
+use crate::{
+    ethereum::{
+        cancun::{
+            blocks::{Block, Header},
+            fork::{get_last_256_block_hashes, process_transaction, state_transition, BlockChain},
+            fork_types::Address,
+            state::TransientStorage,
+            transactions::Transaction,
+            vm::Environment,
+        },
+        ethereum_rlp::rlp,
+        genesis::Genesis,
+    },
+    json::from_json,
+};
+
+create_exception!(ejit_evm, EthereumException, PyException);
+
+fn err(e: impl std::fmt::Debug) -> PyErr {
+    EthereumException::new_err(format!("{e:?}"))
+}
+
+/// History and current state of the block chain.
+#[pyclass(name = "BlockChain")]
+pub struct PyBlockChain {
+    chain: BlockChain,
+}
+
+#[pymethods]
+impl PyBlockChain {
+    /// Start a chain from the mainnet genesis block.
+    #[staticmethod]
+    fn mainnet() -> PyResult<Self> {
+        Ok(Self { chain: BlockChain::mainnet().map_err(err)? })
+    }
+
+    /// Start a chain from a genesis file in geth format.
+    #[staticmethod]
+    fn from_genesis_json(json: &str) -> PyResult<Self> {
+        let genesis: Genesis = from_json(json.as_bytes())
+            .map_err(|e| EthereumException::new_err(e.to_string()))?;
+        Ok(Self { chain: BlockChain::from_genesis(genesis).map_err(err)? })
+    }
+
+    /// Number of the block at the head of the chain.
+    #[getter]
+    fn head_number(&self) -> u128 {
+        self.chain.blocks.last().map_or(0, |b| b.header.number)
+    }
+
+    /// Hash of the block at the head of the chain.
+    #[getter]
+    fn head_hash<'py>(&self, py: Python<'py>) -> Bound<'py, PyBytes> {
+        PyBytes::new(py, &self.chain.head_hash()[..])
+    }
+
+    /// Apply an RLP encoded block to the head of the chain.
+    fn state_transition(&mut self, block_rlp: &[u8]) -> PyResult<()> {
+        let block: Block = rlp::decode_with(block_rlp, rlp::DecodeOptions::BLOCK).map_err(err)?;
+        state_transition(&mut self.chain, block).map_err(err)
+    }
+
+    /// Execute an RLP encoded transaction against the current state, in the
+    /// environment of the RLP encoded block header `header_rlp`.
+    ///
+    /// Returns a dict of `gas_used`, `logs` (RLP encoded), `error` (a string
+    /// or `None`) and `traces`.
+    fn process_transaction<'py>(
+        &mut self,
+        py: Python<'py>,
+        header_rlp: &[u8],
+        tx_rlp: &[u8],
+        origin: &[u8],
+        gas_price: u128,
+    ) -> PyResult<Bound<'py, PyDict>> {
+        let header: Header = rlp::decode_to_strict(header_rlp).map_err(err)?;
+        let tx: Transaction = rlp::decode_to_strict(tx_rlp).map_err(err)?;
+        let origin = Address::from_be_bytes(
+            origin.try_into().map_err(|_| EthereumException::new_err("origin must be 20 bytes"))?,
+        );
+        let blob_versioned_hashes = match &tx {
+            Transaction::BlobTransaction(tx) => tx.blob_versioned_hashes.clone(),
+            _ => Vec::new(),
+        };
+        let block_hashes = get_last_256_block_hashes(&self.chain);
+        let rules = self.chain.fork_for(&header).map_err(err)?.execution_rules();
+        let mut traces = Vec::new();
+        let mut env = Environment {
+            caller: origin.clone(),
+            block_hashes,
+            origin,
+            coinbase: header.coinbase,
+            number: header.number,
+            base_fee_per_gas: header.base_fee_per_gas.unwrap_or_default(),
+            gas_limit: header.gas_limit,
+            gas_price,
+            time: header.timestamp,
+            prev_randao: header.prev_randao,
+            chain_id: self.chain.chain_spec.chain_id,
+            tracer: Some(&mut traces),
+            excess_blob_gas: header.excess_blob_gas.unwrap_or_default(),
+            blob_versioned_hashes,
+            code_cache: Some(&self.chain.code_cache),
+            ..Environment::new(&mut self.chain.state, rules)
+        };
+        let (gas_used, logs, error) = process_transaction(&mut env, &tx).map_err(err)?;
+
+        let result = PyDict::new(py);
+        result.set_item("gas_used", gas_used)?;
+        let logs = logs
+            .iter()
+            .map(|log| rlp::encode(log).map(|b| PyBytes::new(py, &b)))
+            .collect::<Result<Vec<_>, _>>()
+            .map_err(err)?;
+        result.set_item("logs", logs)?;
+        result.set_item("error", error.map(|e| format!("{e:?}")))?;
+        result.set_item("traces", traces)?;
+        Ok(result)
+    }
+}
+
+#[pymodule]
+fn ejit_evm(m: &Bound<'_, PyModule>) -> PyResult<()> {
+    m.add_class::<PyBlockChain>()?;
+    m.add("EthereumException", m.py().get_type::<EthereumException>())?;
+    Ok(())
+}
@@ -0,0 +1,405 @@
+//! A JSON-RPC client for fetching blocks and receipts from a node.
+//!
+//! `Client` posts requests over HTTP and decodes their results with the
+//! crate's JSON decoder into the crate's own types. Requests which fail for
+//! reasons that may pass, such as a dropped connection or a rate limit
+//! (HTTP 429 or 5xx), are retried with exponential backoff; errors returned
+//! by the node are not.
+//!
+//! ```no_run
+//! use ejit_evm::{ethereum::cancun::fork::BlockChain, rpc::Client};
+//!
+//! let mut chain = BlockChain::mainnet()?;
+//! let client = Client::new(std::env::var("RPC_URL")?);
+//! let block = client.debug_get_raw_block(1)?;
+//! chain.import_block(block)?;
+//! # Ok::<(), Box<dyn std::error::Error>>(())
+//! ```
+//!
+//! `server` answers the same requests from a `BlockChain`, and
+//! `forked::ForkedState` executes against the state of a node. This module
+//! needs the `rpc` feature.
+
+use std::{cell::Cell, thread, time::Duration};
+
+use crate::{
+    ethereum::{
+        cancun::{
+            blocks::{Block, Header, Log},
+            fork_types::{Address, Bloom},
+        },
+        crypto::hash::Hash32,
+        ethereum_rlp::{exceptions::RLPException, rlp},
+        ethereum_types::{
+            bytes::{Bytes, Bytes32},
+            numeric::{Uint, U256, U64},
+        },
+    },
+    impl_json,
+    json::{from_json, Decoder, Encoder, JsonDecode, JsonEncode, JsonError, JsonErrorAt, ObjectParser, Value},
+};
+
+pub mod forked;
+pub mod server;
+
+/// Why a request failed.
+#[derive(Debug)]
+pub enum RpcError {
+    /// The request could not be sent or its response read.
+    Http(reqwest::Error),
+    /// The node answered with an HTTP status other than 200.
+    Status(u16),
+    /// The response is not a JSON-RPC response with a result of the
+    /// expected type.
+    Json(JsonErrorAt),
+    /// The node returned an error object.
+    Rpc { code: i64, message: String },
+    /// A raw result is not valid RLP.
+    Rlp(RLPException),
+}
+
+impl From<reqwest::Error> for RpcError {
+    fn from(value: reqwest::Error) -> Self {
+        RpcError::Http(value)
+    }
+}
+
+impl From<RLPException> for RpcError {
+    fn from(value: RLPException) -> Self {
+        RpcError::Rlp(value)
+    }
+}
+
+impl std::fmt::Display for RpcError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            RpcError::Http(e) => write!(f, "request failed: {e}"),
+            RpcError::Status(status) => write!(f, "HTTP status {status}"),
+            RpcError::Json(e) => write!(f, "bad response: {e}"),
+            RpcError::Rpc { code, message } => write!(f, "node error {code}: {message}"),
+            RpcError::Rlp(e) => write!(f, "bad raw result: {e}"),
+        }
+    }
+}
+
+impl std::error::Error for RpcError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            RpcError::Http(e) => Some(e),
+            RpcError::Json(e) => Some(e),
+            RpcError::Rlp(e) => Some(e),
+            _ => None,
+        }
+    }
+}
+
+impl RpcError {
+    /// Whether the same request may succeed if sent again.
+    fn is_transient(&self) -> bool {
+        match self {
+            RpcError::Http(_) => true,
+            RpcError::Status(status) => *status == 429 || *status >= 500,
+            _ => false,
+        }
+    }
+}
+
+/// A receipt as returned by `eth_getTransactionReceipt`.
+#[derive(Debug, Clone, Default)]
+pub struct ReceiptResponse {
+    pub transaction_hash: Hash32,
+    pub transaction_index: U64,
+    pub block_hash: Hash32,
+    pub block_number: Uint,
+    pub from: Address,
+    pub to: Option<Address>,
+    pub contract_address: Option<Address>,
+    pub cumulative_gas_used: Uint,
+    pub gas_used: Uint,
+    pub effective_gas_price: Uint,
+    /// One for success, `None` before Byzantium.
+    pub status: Option<U64>,
+    /// The post-state root, before Byzantium.
+    pub root: Option<Hash32>,
+    pub logs_bloom: Bloom,
+    pub logs: Vec<Log>,
+    /// Zero for legacy transactions.
+    pub transaction_type: U64,
+}
+
+impl_json!(#[skip_unknown] ReceiptResponse :
+    transaction_hash "transactionHash",
+    transaction_index "transactionIndex",
+    block_hash "blockHash",
+    block_number "blockNumber",
+    from "from",
+    to "to",
+    contract_address "contractAddress",
+    cumulative_gas_used "cumulativeGasUsed",
+    gas_used "gasUsed",
+    effective_gas_price "effectiveGasPrice",
+    status "status",
+    root "root",
+    logs_bloom "logsBloom",
+    logs "logs",
+    transaction_type "type",
+);
+
+/// The error object of a response.
+#[derive(Debug, Clone, Default)]
+struct ErrorObject {
+    code: i64,
+    message: String,
+}
+
+impl<'de> JsonDecode<'de> for ErrorObject {
+    fn decode_json(&mut self, decoder: &mut Decoder<'de>) -> Result<(), JsonError> {
+        let mut p = ObjectParser::new(decoder);
+        loop {
+            match p.next_key()? {
+                Some("code") => {
+                    // Error codes are negative, which the integer decoders
+                    // do not take.
+                    let mut code = Value::Null;
+                    code.decode_json(p.decoder)?;
+                    let Value::Numeric(code) = code else {
+                        return Err(JsonError::BadNumber);
+                    };
+                    self.code = code.parse().map_err(|_| JsonError::BadNumber)?;
+                }
+                Some("message") => self.message.decode_json(p.decoder)?,
+                Some(_) => p.skip_value()?,
+                None => return Ok(()),
+            }
+        }
+    }
+}
+
+/// A response with a result of type `T`.
+#[derive(Debug, Default)]
+struct Response<T> {
+    result: Option<T>,
+    error: Option<ErrorObject>,
+}
+
+impl<'de, T: JsonDecode<'de> + Default> JsonDecode<'de> for Response<T> {
+    fn decode_json(&mut self, decoder: &mut Decoder<'de>) -> Result<(), JsonError> {
+        let mut p = ObjectParser::new(decoder);
+        loop {
+            match p.next_key()? {
+                Some("result") => {
+                    let mut result = T::default();
+                    result.decode_json(p.decoder)?;
+                    self.result = Some(result);
+                }
+                Some("error") => self.error.decode_json(p.decoder)?,
+                Some(_) => p.skip_value()?,
+                None => return Ok(()),
+            }
+        }
+    }
+}
+
+/// A JSON-RPC client of one node.
+pub struct Client {
+    url: String,
+    http: reqwest::blocking::Client,
+    /// Times a request is sent again after a transient failure.
+    pub retries: u32,
+    /// Wait before the first retry, doubled for each one after it.
+    pub backoff: Duration,
+    next_id: Cell<u64>,
+}
+
+impl Client {
+    pub fn new(url: impl Into<String>) -> Self {
+        Self {
+            url: url.into(),
+            http: reqwest::blocking::Client::new(),
+            retries: 5,
+            backoff: Duration::from_millis(500),
+            next_id: Cell::new(1),
+        }
+    }
+
+    /// Call `method` with `params`, decoding its result as a `T`.
+    pub fn request<T: for<'de> JsonDecode<'de> + Default>(
+        &self,
+        method: &str,
+        params: &[&dyn JsonEncode],
+    ) -> Result<T, RpcError> {
+        let id = self.next_id.replace(self.next_id.get() + 1);
+        let mut encoder = Encoder::new();
+        let mut o = encoder.object();
+        o.field("jsonrpc", &"2.0");
+        o.key("id");
+        o.encoder.raw(&id.to_string());
+        o.field("method", &method);
+        o.key("params");
+        let mut array = o.encoder.array();
+        for param in params {
+            param.encode_json(array.next());
+        }
+        array.end();
+        o.end();
+        let body = encoder.finish();
+
+        let mut backoff = self.backoff;
+        let mut attempt = 0;
+        let text = loop {
+            match self.post(&body) {
+                Err(error) if error.is_transient() && attempt < self.retries => {
+                    thread::sleep(backoff);
+                    backoff *= 2;
+                    attempt += 1;
+                }
+                result => break result?,
+            }
+        };
+
+        let response: Response<T> = from_json(text.as_bytes()).map_err(RpcError::Json)?;
+        match response {
+            Response { error: Some(error), .. } => Err(RpcError::Rpc { code: error.code, message: error.message }),
+            Response { result: Some(result), .. } => Ok(result),
+            Response { result: None, error: None } => Err(RpcError::Json(missing_result(&text))),
+        }
+    }
+
+    fn post(&self, body: &str) -> Result<String, RpcError> {
+        let response = self
+            .http
+            .post(&self.url)
+            .header("accept", "application/json")
+            .header("content-type", "application/json")
+            .body(body.to_string())
+            .send()?;
+        if response.status() != 200 {
+            return Err(RpcError::Status(response.status().as_u16()));
+        }
+        Ok(response.text()?)
+    }
+
+    /// The block `number` of the canonical chain, from its RLP.
+    pub fn debug_get_raw_block(&self, number: Uint) -> Result<Block, RpcError> {
+        let encoded: Bytes = self.request("debug_getRawBlock", &[&number])?;
+        Ok(rlp::decode_to_strict(&encoded)?)
+    }
+
+    /// The header of block `number` of the canonical chain, `None` if the
+    /// node does not have it.
+    pub fn eth_get_block_by_number(&self, number: Uint) -> Result<Option<Header>, RpcError> {
+        self.request("eth_getBlockByNumber", &[&number, &false])
+    }
+
+    /// The receipt of the transaction `hash`, `None` if it is unknown or
+    /// pending.
+    pub fn eth_get_transaction_receipt(&self, hash: &Hash32) -> Result<Option<ReceiptResponse>, RpcError> {
+        self.request("eth_getTransactionReceipt", &[hash])
+    }
+
+    /// The balance of `address` after block `number`.
+    pub fn eth_get_balance(&self, address: &Address, number: Uint) -> Result<U256, RpcError> {
+        self.request("eth_getBalance", &[address, &number])
+    }
+
+    /// The nonce of `address` after block `number`.
+    pub fn eth_get_transaction_count(&self, address: &Address, number: Uint) -> Result<Uint, RpcError> {
+        self.request("eth_getTransactionCount", &[address, &number])
+    }
+
+    /// The code of `address` after block `number`.
+    pub fn eth_get_code(&self, address: &Address, number: Uint) -> Result<Bytes, RpcError> {
+        self.request("eth_getCode", &[address, &number])
+    }
+
+    /// The storage slot `key` of `address` after block `number`.
+    pub fn eth_get_storage_at(&self, address: &Address, key: &Bytes32, number: Uint) -> Result<U256, RpcError> {
+        self.request("eth_getStorageAt", &[address, key, &number])
+    }
+}
+
+/// The error of a response with neither a result nor an error.
+fn missing_result(text: &str) -> JsonErrorAt {
+    let mut decoder = Decoder::new(text.as_bytes());
+    decoder.error_at(JsonError::MissingKey)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{
+        io::{BufRead, BufReader, Read, Write},
+        net::TcpListener,
+        thread,
+        time::Duration,
+    };
+
+    use crate::{
+        ethereum::{cancun::blocks::Block, ethereum_rlp::rlp, ethereum_types::bytes::Bytes},
+        json::to_json,
+    };
+
+    use super::{Client, RpcError};
+
+    /// Serve one connection per response, answering each request with the
+    /// next of `responses` as `(status, body)`, and return the URL and the
+    /// bodies of the requests.
+    fn serve(responses: Vec<(u16, String)>) -> (String, thread::JoinHandle<Vec<String>>) {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let url = format!("http://{}", listener.local_addr().unwrap());
+        let handle = thread::spawn(move || {
+            let mut requests = Vec::new();
+            for (status, body) in responses {
+                let (stream, _) = listener.accept().unwrap();
+                let mut reader = BufReader::new(stream);
+                let mut length = 0;
+                loop {
+                    let mut line = String::new();
+                    reader.read_line(&mut line).unwrap();
+                    if let Some((name, value)) = line.split_once(':') {
+                        if name.eq_ignore_ascii_case("content-length") {
+                            length = value.trim().parse().unwrap();
+                        }
+                    }
+                    if line == "\r\n" {
+                        break;
+                    }
+                }
+                let mut request = vec![0; length];
+                reader.read_exact(&mut request).unwrap();
+                requests.push(String::from_utf8(request).unwrap());
+                let response = format!(
+                    "HTTP/1.1 {status} X\r\ncontent-type: application/json\r\ncontent-length: {}\r\nconnection: close\r\n\r\n{body}",
+                    body.len()
+                );
+                reader.into_inner().write_all(response.as_bytes()).unwrap();
+            }
+            requests
+        });
+        (url, handle)
+    }
+
+    #[test]
+    fn requests() {
+        let block = Block::default();
+        let raw = to_json(&Bytes(rlp::encode(&block).unwrap().0));
+        let (url, server) = serve(vec![
+            (503, String::new()),
+            (200, format!(r#"{{"jsonrpc":"2.0","id":1,"result":{raw}}}"#)),
+            (200, r#"{"jsonrpc":"2.0","id":2,"result":null}"#.to_string()),
+            (200, r#"{"jsonrpc":"2.0","id":3,"error":{"code":-32601,"message":"no such method"}}"#.to_string()),
+        ]);
+        let mut client = Client::new(url);
+        client.backoff = Duration::from_millis(1);
+
+        // The 503 is retried.
+        let decoded = client.debug_get_raw_block(0x10).unwrap();
+        assert_eq!(rlp::encode(&decoded).unwrap(), rlp::encode(&block).unwrap());
+        assert!(client.eth_get_block_by_number(1).unwrap().is_none());
+        let error = client.eth_get_transaction_receipt(&Default::default()).unwrap_err();
+        assert!(matches!(error, RpcError::Rpc { code: -32601, ref message } if message == "no such method"));
+
+        let requests = server.join().unwrap();
+        assert_eq!(requests[1], r#"{"jsonrpc":"2.0","id":1,"method":"debug_getRawBlock","params":["0x10"]}"#);
+        assert_eq!(requests[2], r#"{"jsonrpc":"2.0","id":2,"method":"eth_getBlockByNumber","params":["0x1",false]}"#);
+    }
+}
@@ -0,0 +1,329 @@
+//! Execution against the state of a live network, as hardhat and anvil do
+//! when forking mainnet.
+//!
+//! A `ForkedState` is a `StateBackend` pinned to one block of a node. The
+//! accounts and storage slots it reads are fetched from the node the first
+//! time and cached, and everything written stays local, so transactions can
+//! be run and rolled back without touching the node:
+//!
+//! ```no_run
+//! use std::collections::BTreeSet;
+//!
+//! use ejit_evm::{
+//!     ethereum::{
+//!         cancun::{
+//!             fork_types::Address,
+//!             utils::prepare_message,
+//!             vm::{interpreter::process_message_call, Environment},
+//!         },
+//!         ethereum_types::{bytes::Bytes, numeric::U256},
+//!         forks::ExecutionRules,
+//!     },
+//!     rpc::{forked::ForkedState, Client},
+//! };
+//!
+//! # let (url, caller, contract) = (String::new(), Address::default(), Address::default());
+//! let mut state = ForkedState::new(Client::new(url), 20_000_000);
+//! let mut env = Environment { gas_limit: 30_000_000, ..Environment::new(&mut state, ExecutionRules::CANCUN) };
+//! let message = prepare_message(
+//!     caller, Some(contract), U256::ZERO, Bytes::default(), 100_000, &env, None, true, false,
+//!     BTreeSet::new(), BTreeSet::new(),
+//! )?;
+//! let output = process_message_call(message, &mut env)?;
+//! state.take_error()?;
+//! # Ok::<(), Box<dyn std::error::Error>>(())
+//! ```
+//!
+//! The backend cannot fail, so a failed request reads as an empty account
+//! or a zero slot and is kept for `take_error`. Whether a remote account has
+//! any storage cannot be asked for without a proof, so only slots which were
+//! read or written are counted.
+
+use std::{
+    borrow::Cow,
+    cell::RefCell,
+    collections::{BTreeMap, HashSet},
+};
+
+use crate::ethereum::{
+    cancun::{
+        fork_types::{Account, Address},
+        state::StateBackend,
+    },
+    ethereum_types::{bytes::Bytes32, numeric::{Uint, U256}},
+};
+
+use super::{Client, RpcError};
+
+/// The storage of an account as written locally.
+#[derive(Debug, Clone, Default)]
+struct LocalStorage {
+    /// The storage was destroyed, so the slots not written since are zero
+    /// rather than remote.
+    destroyed: bool,
+    slots: BTreeMap<Bytes32, U256>,
+}
+
+/// Local writes over the remote state.
+#[derive(Debug, Clone, Default)]
+struct Overlay {
+    /// `None` for accounts deleted locally.
+    accounts: BTreeMap<Address, Option<Account>>,
+    storage: BTreeMap<Address, LocalStorage>,
+}
+
+impl Overlay {
+    /// The local value of a slot, `None` if it is that of the node.
+    fn storage(&self, address: &Address, key: &Bytes32) -> Option<U256> {
+        let storage = self.storage.get(address)?;
+        match storage.slots.get(key) {
+            Some(value) => Some(*value),
+            None => storage.destroyed.then_some(U256::ZERO),
+        }
+    }
+}
+
+/// The state of a node at a block, with local writes on top.
+pub struct ForkedState {
+    client: Client,
+    block: Uint,
+    remote_accounts: RefCell<BTreeMap<Address, Option<Account>>>,
+    remote_storage: RefCell<BTreeMap<(Address, Bytes32), U256>>,
+    overlay: Overlay,
+    snapshots: Vec<Overlay>,
+    created_accounts: HashSet<Address>,
+    error: RefCell<Option<RpcError>>,
+}
+
+impl ForkedState {
+    /// Fork the state after block `block` of the node `client` talks to.
+    pub fn new(client: Client, block: Uint) -> Self {
+        Self {
+            client,
+            block,
+            remote_accounts: RefCell::default(),
+            remote_storage: RefCell::default(),
+            overlay: Overlay::default(),
+            snapshots: Vec::new(),
+            created_accounts: HashSet::new(),
+            error: RefCell::default(),
+        }
+    }
+
+    /// The block the state was forked at.
+    pub fn block(&self) -> Uint {
+        self.block
+    }
+
+    /// The first request which failed since the last call, if any.
+    pub fn take_error(&mut self) -> Result<(), RpcError> {
+        match self.error.get_mut().take() {
+            Some(error) => Err(error),
+            None => Ok(()),
+        }
+    }
+
+    fn fail<T: Default>(&self, result: Result<T, RpcError>) -> T {
+        result.unwrap_or_else(|error| {
+            self.error.borrow_mut().get_or_insert(error);
+            T::default()
+        })
+    }
+
+    fn remote_account(&self, address: &Address) -> Option<Account> {
+        if let Some(account) = self.remote_accounts.borrow().get(address) {
+            return account.clone();
+        }
+        let fetch = || -> Result<Account, RpcError> {
+            Ok(Account {
+                nonce: self.client.eth_get_transaction_count(address, self.block)?,
+                balance: self.client.eth_get_balance(address, self.block)?,
+                code: self.client.eth_get_code(address, self.block)?,
+            })
+        };
+        // Nodes answer for accounts which do not exist as for empty ones.
+        let account = Some(self.fail(fetch())).filter(|account| *account != Account::default());
+        self.remote_accounts.borrow_mut().insert(address.clone(), account.clone());
+        account
+    }
+
+    fn remote_storage(&self, address: &Address, key: &Bytes32) -> U256 {
+        let slot = (address.clone(), *key);
+        if let Some(value) = self.remote_storage.borrow().get(&slot) {
+            return *value;
+        }
+        let value = self.fail(self.client.eth_get_storage_at(address, key, self.block));
+        self.remote_storage.borrow_mut().insert(slot, value);
+        value
+    }
+}
+
+impl StateBackend for ForkedState {
+    fn get_account_optional(&self, address: &Address) -> Option<Cow<'_, Account>> {
+        match self.overlay.accounts.get(address) {
+            Some(account) => account.as_ref().map(Cow::Borrowed),
+            None => self.remote_account(address).map(Cow::Owned),
+        }
+    }
+
+    fn set_account(&mut self, address: &Address, account: Option<Account>) {
+        self.overlay.accounts.insert(address.clone(), account);
+    }
+
+    fn get_storage(&self, address: &Address, key: &Bytes32) -> U256 {
+        self.overlay.storage(address, key).unwrap_or_else(|| self.remote_storage(address, key))
+    }
+
+    fn set_storage(&mut self, address: &Address, key: &Bytes32, value: U256) {
+        self.overlay.storage.entry(address.clone()).or_default().slots.insert(*key, value);
+    }
+
+    fn get_storage_original(&self, address: &Address, key: &Bytes32) -> U256 {
+        // In the transaction where an account is created, its preexisting
+        // storage is ignored.
+        if self.created_accounts.contains(address) {
+            return U256::ZERO;
+        }
+        self.snapshots[0].storage(address, key).unwrap_or_else(|| self.remote_storage(address, key))
+    }
+
+    fn account_has_storage(&self, address: &Address) -> bool {
+        let local = self.overlay.storage.get(address);
+        if local.is_some_and(|storage| storage.slots.values().any(|value| !value.is_zero())) {
+            return true;
+        }
+        if local.is_some_and(|storage| storage.destroyed) {
+            return false;
+        }
+        // The slots fetched from the node which were not written since.
+        let remote = self.remote_storage.borrow();
+        let mut slots = remote.range((address.clone(), Bytes32([0; 32]))..=(address.clone(), Bytes32([0xff; 32])));
+        slots.any(|((_, key), value)| !value.is_zero() && local.is_none_or(|storage| !storage.slots.contains_key(key)))
+    }
+
+    fn destroy_storage(&mut self, address: &Address) {
+        self.overlay.storage.insert(address.clone(), LocalStorage { destroyed: true, slots: BTreeMap::new() });
+    }
+
+    fn mark_account_created(&mut self, address: &Address) {
+        self.created_accounts.insert(address.clone());
+    }
+
+    fn is_account_created(&self, address: &Address) -> bool {
+        self.created_accounts.contains(address)
+    }
+
+    fn begin_transaction(&mut self) {
+        self.snapshots.push(self.overlay.clone());
+    }
+
+    fn commit_transaction(&mut self) {
+        self.snapshots.pop();
+        if self.snapshots.is_empty() {
+            self.created_accounts.clear();
+        }
+    }
+
+    fn rollback_transaction(&mut self) {
+        self.overlay = self.snapshots.pop().unwrap();
+        if self.snapshots.is_empty() {
+            self.created_accounts.clear();
+        }
+    }
+
+    fn without_access_log(&mut self, f: &mut dyn FnMut(&mut dyn StateBackend)) {
+        f(self);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{
+        collections::{BTreeMap, BTreeSet},
+        net::TcpListener,
+        sync::{Arc, RwLock},
+    };
+
+    use crate::{
+        ethereum::cancun::{
+            state::{account_has_storage, begin_transaction, get_account_optional, rollback_transaction, TransientStorage},
+            utils::prepare_message,
+            vm::{interpreter::process_message_call, Environment},
+        },
+        prelude::*,
+        rpc::{server::Server, Client},
+    };
+
+    use super::ForkedState;
+
+    /// The URL of a node serving a chain with a contract which adds one to
+    /// its slot 0, which holds 41.
+    fn node(contract: &Address) -> String {
+        // PUSH1 0 SLOAD PUSH1 1 ADD PUSH1 0 SSTORE STOP
+        let code = vec![0x60, 0x00, 0x54, 0x60, 0x01, 0x01, 0x60, 0x00, 0x55, 0x00];
+        let genesis = Genesis::with_forks(&[("london", ForkCriteria::ByBlockNumber(0))]).contract(contract.clone(), code);
+        let mut chain = BlockChain::from_genesis(genesis).unwrap();
+        set_storage(&mut chain.state, contract, &Bytes32([0; 32]), U256::from(41_u32));
+
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let url = format!("http://{}", listener.local_addr().unwrap());
+        let server = Server::new(Arc::new(RwLock::new(chain)));
+        std::thread::spawn(move || server.serve(listener));
+        url
+    }
+
+    #[test]
+    fn forked_execution() {
+        let contract = Address::from_be_bytes([0xc0; 20]);
+        let slot = Bytes32([0; 32]);
+        let mut state = ForkedState::new(Client::new(node(&contract)), 0);
+        assert_eq!(get_account(&state, &contract).nonce, 1);
+        assert!(!account_has_storage(&state, &contract));
+        assert_eq!(get_storage(&state, &contract, &slot), U256::from(41_u32));
+        assert!(account_has_storage(&state, &contract));
+
+        let caller = Address::from_be_bytes([0xca; 20]);
+        let mut env = Environment {
+            caller: caller.clone(),
+            origin: caller.clone(),
+            number: 1,
+            gas_limit: 1_000_000,
+            chain_id: 1337,
+            ..Environment::new(&mut state, ExecutionRules::CANCUN)
+        };
+        for _ in 0..2 {
+            let message = prepare_message(
+                caller.clone(),
+                Some(contract.clone()),
+                U256::ZERO,
+                Bytes::default(),
+                100_000,
+                &env,
+                None,
+                true,
+                false,
+                BTreeSet::new(),
+                BTreeSet::new(),
+            )
+            .unwrap();
+            assert_eq!(process_message_call(message, &mut env).unwrap().error, None);
+        }
+        assert_eq!(get_storage(&state, &contract, &slot), U256::from(43_u32));
+
+        // Local writes roll back to the local value, not the node's.
+        let mut transient_storage = TransientStorage::default();
+        begin_transaction(&mut state, &mut transient_storage);
+        set_storage(&mut state, &contract, &slot, U256::from(7_u32));
+        rollback_transaction(&mut state, &mut transient_storage);
+        assert_eq!(get_storage(&state, &contract, &slot), U256::from(43_u32));
+        assert!(state.take_error().is_ok());
+
+        // A node which cannot be reached reads as empty and reports it.
+        let mut client = Client::new("http://127.0.0.1:1");
+        client.retries = 0;
+        let mut gone = ForkedState::new(client, 0);
+        assert!(get_account_optional(&gone, &contract).is_none());
+        assert!(gone.take_error().is_err());
+        assert!(gone.take_error().is_ok());
+    }
+}
@@ -0,0 +1,546 @@
+//! A minimal JSON-RPC server over a `BlockChain`, for using the crate as an
+//! embedded development chain.
+//!
+//! The server only reads: it answers from the head block and its state, and
+//! requests for other blocks fail, as the chain keeps no historical state.
+//! The `safe` and `finalized` tags name the blocks of the last fork choice
+//! update, so they only work while those are the head.
+//! `eth_call` runs the message against a copy of the head state with
+//! `BlockChain::call`, with its gas capped by the gas cap of the server. `eth_estimateGas` searches for the lowest gas limit
+//! with which the message succeeds with `BlockChain::estimate_gas`.
+//! `eth_createAccessList` finds the list with
+//! `BlockChain::create_access_list` and reports the gas of a transaction
+//! carrying it.
+//!
+//! HTTP is handled by hand over `std::net`: each connection carries one
+//! `POST` whose body is a request or a batch of requests, of at most
+//! `MAX_BODY_SIZE` bytes. A server built
+//! `with_metrics` also answers `GET /metrics` for Prometheus.
+
+use std::{
+    io::{self, BufRead, BufReader, Read, Write},
+    net::{TcpListener, TcpStream},
+    sync::{Arc, RwLock},
+    thread,
+};
+
+use crate::{
+    ethereum::{
+        cancun::{
+            fork::{call::AccessListOutput, BlockChain},
+            fork_types::Address,
+            state::{get_account, get_storage},
+            transactions::{calculate_intrinsic_cost, AccessListTransaction, LegacyTransaction, Transaction},
+            vm::exceptions::VmError,
+        },
+        crypto::hash::Hash32,
+        ethereum_types::{
+            bytes::{Bytes, Bytes32},
+            numeric::{Uint, U256},
+        },
+    },
+    impl_json,
+    json::{from_json, to_json, Encoder, JsonDecode, JsonEncode, Value},
+    metrics,
+};
+
+// Error codes of JSON-RPC 2.0 and of the Ethereum nodes.
+const PARSE_ERROR: i64 = -32700;
+const INVALID_REQUEST: i64 = -32600;
+const METHOD_NOT_FOUND: i64 = -32601;
+const INVALID_PARAMS: i64 = -32602;
+const SERVER_ERROR: i64 = -32000;
+const EXECUTION_REVERTED: i64 = 3;
+
+/// The largest body of a request, as in geth.
+pub const MAX_BODY_SIZE: usize = 5 * 1024 * 1024;
+
+#[derive(Debug, Clone, Default)]
+struct Request {
+    id: Value,
+    method: String,
+    params: Vec<Value>,
+}
+
+impl_json!(#[skip_unknown] Request : id "id", method "method", params "params");
+
+/// The call object of `eth_call` and `eth_estimateGas`.
+#[derive(Debug, Clone, Default)]
+struct CallRequest {
+    from: Option<Address>,
+    to: Option<Address>,
+    gas: Option<Uint>,
+    value: Option<U256>,
+    input: Option<Bytes>,
+    data: Option<Bytes>,
+}
+
+impl_json!(#[skip_unknown] CallRequest :
+    from "from",
+    to "to",
+    gas "gas",
+    value "value",
+    input "input",
+    data "data",
+);
+
+/// An error response.
+#[derive(Debug, Clone, PartialEq)]
+struct Error {
+    code: i64,
+    message: String,
+    /// Revert data of a reverted call.
+    data: Option<Bytes>,
+}
+
+impl Error {
+    fn new(code: i64, message: impl Into<String>) -> Self {
+        Self { code, message: message.into(), data: None }
+    }
+}
+
+impl JsonEncode for Error {
+    fn encode_json(&self, encoder: &mut Encoder) {
+        let mut o = encoder.object();
+        o.key("code");
+        o.encoder.raw(&self.code.to_string());
+        o.field("message", &self.message);
+        if let Some(data) = &self.data {
+            o.field("data", data);
+        }
+        o.end();
+    }
+}
+
+/// The result of `eth_createAccessList`.
+struct AccessListResult {
+    output: AccessListOutput,
+    /// Gas of a transaction with the access list, intrinsic cost included.
+    gas_used: Uint,
+}
+
+impl JsonEncode for AccessListResult {
+    fn encode_json(&self, encoder: &mut Encoder) {
+        let mut o = encoder.object();
+        o.key("accessList");
+        let mut entries = o.encoder.array();
+        for (address, keys) in &self.output.access_list {
+            let mut entry = entries.next().object();
+            entry.field("address", address);
+            entry.field("storageKeys", keys);
+            entry.end();
+        }
+        entries.end();
+        o.field("gasUsed", &self.gas_used);
+        if let Some(error) = &self.output.error {
+            o.field("error", &format!("{error:?}"));
+        }
+        o.end();
+    }
+}
+
+/// Serves JSON-RPC requests from a chain shared with its owner, which may
+/// keep importing blocks.
+#[derive(Clone)]
+pub struct Server {
+    chain: Arc<RwLock<BlockChain>>,
+    /// Answer `GET /metrics` with the metrics of the process.
+    metrics: bool,
+    /// The most gas a call may use, or the gas limit of the head block if
+    /// `None`.
+    gas_cap: Option<Uint>,
+}
+
+impl Server {
+    pub fn new(chain: Arc<RwLock<BlockChain>>) -> Self {
+        Self { chain, metrics: false, gas_cap: None }
+    }
+
+    /// Cap the gas of calls at `gas_cap` instead of the gas limit of the
+    /// head block.
+    pub fn with_gas_cap(mut self, gas_cap: Uint) -> Self {
+        self.gas_cap = Some(gas_cap);
+        self
+    }
+
+    /// Also serve `crate::metrics` in the Prometheus text format on
+    /// `GET /metrics`.
+    pub fn with_metrics(mut self) -> Self {
+        self.metrics = true;
+        self
+    }
+
+    /// Accept connections on `listener` until it fails, serving each on a
+    /// thread of its own.
+    pub fn serve(&self, listener: TcpListener) -> io::Result<()> {
+        for stream in listener.incoming() {
+            let stream = stream?;
+            let server = self.clone();
+            thread::spawn(move || {
+                // A client which goes away is not the server's problem.
+                let _ = server.serve_connection(stream);
+            });
+        }
+        Ok(())
+    }
+
+    fn serve_connection(&self, stream: TcpStream) -> io::Result<()> {
+        let mut reader = BufReader::new(stream);
+        let mut request_line = String::new();
+        reader.read_line(&mut request_line)?;
+        let mut length = 0;
+        loop {
+            let mut line = String::new();
+            if reader.read_line(&mut line)? == 0 || line == "\r\n" || line == "\n" {
+                break;
+            }
+            if let Some((name, value)) = line.split_once(':') {
+                if name.eq_ignore_ascii_case("content-length") {
+                    length = value.trim().parse().unwrap_or(0);
+                }
+            }
+        }
+        let json = "application/json";
+        let (status, content_type, body) = if request_line.starts_with("POST ") && length > MAX_BODY_SIZE {
+            ("413 Payload Too Large", json, String::new())
+        } else if request_line.starts_with("POST ") {
+            let mut body = vec![0; length];
+            reader.read_exact(&mut body)?;
+            ("200 OK", json, self.handle(&String::from_utf8_lossy(&body)))
+        } else if self.metrics && request_line.split(' ').take(2).eq(["GET", "/metrics"]) {
+            ("200 OK", "text/plain; version=0.0.4", metrics::metrics().to_prometheus())
+        } else {
+            ("405 Method Not Allowed", json, String::new())
+        };
+        let response = format!(
+            "HTTP/1.1 {status}\r\ncontent-type: {content_type}\r\ncontent-length: {}\r\nconnection: close\r\n\r\n{body}",
+            body.len()
+        );
+        reader.into_inner().write_all(response.as_bytes())
+    }
+
+    /// The response to the body of a `POST`, a request or a batch of them.
+    pub fn handle(&self, body: &str) -> String {
+        if body.trim_start().starts_with('[') {
+            match from_json::<Vec<Request>>(body.as_bytes()) {
+                Ok(requests) => {
+                    let responses = requests.iter().map(|request| self.respond(request)).collect::<Vec<_>>();
+                    format!("[{}]", responses.join(","))
+                }
+                Err(error) => response(&Value::Null, Err(Error::new(PARSE_ERROR, error.to_string()))),
+            }
+        } else {
+            match from_json::<Request>(body.as_bytes()) {
+                Ok(request) => self.respond(&request),
+                Err(error) => response(&Value::Null, Err(Error::new(PARSE_ERROR, error.to_string()))),
+            }
+        }
+    }
+
+    fn respond(&self, request: &Request) -> String {
+        // A poisoned lock still holds a consistent chain: blocks are
+        // imported whole or not at all.
+        let chain = self.chain.read().unwrap_or_else(|poisoned| poisoned.into_inner());
+        let params = &request.params;
+        let result = match request.method.as_str() {
+            "" => Err(Error::new(INVALID_REQUEST, "missing method")),
+            "eth_chainId" => Ok(to_json(&chain.chain_spec.chain_id)),
+            "eth_blockNumber" => Ok(to_json(&head_number(&chain))),
+            "eth_getBalance" => account_query(&chain, params, |chain, address| {
+                to_json(&get_account(&chain.state, &address).balance)
+            }),
+            "eth_getTransactionCount" => account_query(&chain, params, |chain, address| {
+                to_json(&get_account(&chain.state, &address).nonce)
+            }),
+            "eth_getCode" => account_query(&chain, params, |chain, address| {
+                to_json(&get_account(&chain.state, &address).code)
+            }),
+            "eth_getStorageAt" => storage_at(&chain, params),
+            "eth_call" => call(&chain, params, self.gas_cap).map(|output| to_json(&output)),
+            "eth_estimateGas" => estimate_gas(&chain, params, self.gas_cap).map(|gas| to_json(&gas)),
+            "eth_createAccessList" => {
+                create_access_list(&chain, params, self.gas_cap).map(|result| to_json(&result))
+            }
+            method => Err(Error::new(METHOD_NOT_FOUND, format!("the method {method} does not exist/is not available"))),
+        };
+        response(&request.id, result)
+    }
+}
+
+/// A response with `result`, JSON already.
+fn response(id: &Value, result: Result<String, Error>) -> String {
+    let mut encoder = Encoder::new();
+    let mut o = encoder.object();
+    o.field("jsonrpc", &"2.0");
+    o.field("id", id);
+    match result {
+        Ok(result) => {
+            o.key("result");
+            o.encoder.raw(&result);
+        }
+        Err(error) => o.field("error", &error),
+    }
+    o.end();
+    encoder.finish()
+}
+
+/// Parameter `i`, decoded as a `T`.
+fn param<T: for<'de> JsonDecode<'de> + Default>(params: &[Value], i: usize) -> Result<T, Error> {
+    let value = params.get(i).ok_or_else(|| Error::new(INVALID_PARAMS, format!("missing value for required argument {i}")))?;
+    from_json(to_json(value).as_bytes()).map_err(|error| Error::new(INVALID_PARAMS, format!("invalid argument {i}: {error}")))
+}
+
+fn head_number(chain: &BlockChain) -> Uint {
+    chain.blocks.last().unwrap().header.number
+}
+
+/// Fail unless `block`, a block number or tag, names the head block.
+fn check_block(chain: &BlockChain, block: Option<&Value>) -> Result<(), Error> {
+    match block {
+        None | Some(Value::Null) => Ok(()),
+        Some(Value::String(tag)) if ["latest", "pending"].contains(&&**tag) => Ok(()),
+        Some(Value::String(tag)) if ["safe", "finalized"].contains(&&**tag) => {
+            let fork_choice = &chain.fork_choice;
+            let hash = match &**tag {
+                "safe" => &fork_choice.safe_block_hash,
+                _ => &fork_choice.finalized_block_hash,
+            };
+            if *hash == Hash32::default() {
+                Err(Error::new(SERVER_ERROR, format!("{tag} block not found")))
+            } else if hash == chain.head_hash() {
+                Ok(())
+            } else {
+                Err(Error::new(SERVER_ERROR, "historical state is not available"))
+            }
+        }
+        Some(value) => {
+            let number: Uint = from_json(to_json(value).as_bytes())
+                .map_err(|_| Error::new(INVALID_PARAMS, "invalid block number or tag"))?;
+            if number == head_number(chain) {
+                Ok(())
+            } else {
+                Err(Error::new(SERVER_ERROR, "historical state is not available"))
+            }
+        }
+    }
+}
+
+/// A query of the head state of the account in the first parameter.
+fn account_query(
+    chain: &BlockChain,
+    params: &[Value],
+    f: impl FnOnce(&BlockChain, Address) -> String,
+) -> Result<String, Error> {
+    let address = param::<Address>(params, 0)?;
+    check_block(chain, params.get(1))?;
+    Ok(f(chain, address))
+}
+
+fn storage_at(chain: &BlockChain, params: &[Value]) -> Result<String, Error> {
+    let address = param::<Address>(params, 0)?;
+    let key = param::<U256>(params, 1)?;
+    check_block(chain, params.get(2))?;
+    let value = get_storage(&chain.state, &address, &Bytes32(key.to_be_bytes()));
+    // A slot is a word, not a quantity.
+    Ok(to_json(&Bytes32(value.to_be_bytes())))
+}
+
+/// The message of a call object, as `(from, to, data, gas)`, with the gas
+/// capped at `gas_cap` or else the gas limit of the head block.
+fn call_message(
+    chain: &BlockChain,
+    params: &[Value],
+    gas_cap: Option<Uint>,
+) -> Result<(Address, Option<Address>, Bytes, Uint), Error> {
+    let request = param::<CallRequest>(params, 0)?;
+    check_block(chain, params.get(1))?;
+    if request.value.is_some_and(|value| value != U256::ZERO) {
+        return Err(Error::new(INVALID_PARAMS, "calls with value are not supported"));
+    }
+    let gas_cap = gas_cap.unwrap_or(chain.blocks.last().unwrap().header.gas_limit);
+    let gas = request.gas.map_or(gas_cap, |gas| gas.min(gas_cap));
+    let data = request.input.or(request.data).unwrap_or_default();
+    Ok((request.from.unwrap_or_default(), request.to, data, gas))
+}
+
+fn call(chain: &BlockChain, params: &[Value], gas_cap: Option<Uint>) -> Result<Bytes, Error> {
+    let (from, to, data, gas) = call_message(chain, params, gas_cap)?;
+    let output = chain.call(from, to, data, gas).map_err(|error| Error::new(SERVER_ERROR, format!("{error:?}")))?;
+    match output.error {
+        None => Ok(output.output),
+        Some(error) => Err(execution_error(&error, output.output)),
+    }
+}
+
+fn estimate_gas(chain: &BlockChain, params: &[Value], gas_cap: Option<Uint>) -> Result<Uint, Error> {
+    let (from, to, data, gas) = call_message(chain, params, gas_cap)?;
+    let tx = Transaction::LegacyTransaction(LegacyTransaction { to: to.into(), data, gas, ..Default::default() });
+    let output = chain.estimate_gas(&tx, &from).map_err(|error| Error::new(SERVER_ERROR, format!("{error:?}")))?;
+    match output.error {
+        None => Ok(output.gas_used),
+        Some(error) => Err(execution_error(&error, output.output)),
+    }
+}
+
+fn create_access_list(chain: &BlockChain, params: &[Value], gas_cap: Option<Uint>) -> Result<AccessListResult, Error> {
+    let (from, to, data, gas) = call_message(chain, params, gas_cap)?;
+    let rules = chain
+        .fork_for(&chain.blocks.last().unwrap().header)
+        .map_err(|error| Error::new(SERVER_ERROR, format!("{error:?}")))?
+        .execution_rules();
+    let intrinsic_cost = |access_list| {
+        let tx = AccessListTransaction { to: to.clone().into(), data: data.clone(), access_list, ..Default::default() };
+        calculate_intrinsic_cost(&Transaction::AccessListTransaction(tx), rules)
+    };
+    let output = chain
+        .create_access_list(from, to.clone(), data.clone(), gas.saturating_sub(intrinsic_cost(Vec::new())))
+        .map_err(|error| Error::new(SERVER_ERROR, format!("{error:?}")))?;
+    let gas_used = intrinsic_cost(output.access_list.clone()) + output.gas_used;
+    Ok(AccessListResult { output, gas_used })
+}
+
+/// The error of a failed call, with the revert data of a reverted one.
+fn execution_error(error: &VmError, output: Bytes) -> Error {
+    match error {
+        VmError::Revert => Error { code: EXECUTION_REVERTED, message: "execution reverted".to_string(), data: Some(output) },
+        error => Error::new(SERVER_ERROR, format!("{error:?}")),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{
+        collections::BTreeMap,
+        io::{Read, Write},
+        net::{TcpListener, TcpStream},
+        sync::{Arc, RwLock},
+    };
+
+    use crate::prelude::*;
+
+    use super::Server;
+
+    fn chain() -> BlockChain {
+        let contract = Address::from_be_bytes([0xc0; 20]);
+        // PUSH1 42 PUSH1 0 MSTORE PUSH1 32 PUSH1 0 CALLDATASIZE JUMPI RETURN JUMPDEST REVERT
+        let code = vec![0x60, 0x2a, 0x60, 0x00, 0x52, 0x60, 0x20, 0x60, 0x00, 0x36, 0x60, 0x0e, 0x57, 0xf3, 0x5b, 0xfd];
+        let genesis = Genesis::with_forks(&[("london", ForkCriteria::ByBlockNumber(0))])
+            .contract(contract, code)
+            .account(Address::from_be_bytes([0xaa; 20]), Account { balance: U256::from(1000_u32), ..Default::default() });
+        BlockChain::from_genesis(genesis).unwrap()
+    }
+
+    #[test]
+    fn handle() {
+        let server = Server::new(Arc::new(RwLock::new(chain())));
+        let call = |method: &str, params: &str| {
+            server.handle(&format!(r#"{{"jsonrpc":"2.0","id":1,"method":"{method}","params":{params}}}"#))
+        };
+        let result = |json: &str| format!(r#"{{"jsonrpc":"2.0","id":1,"result":{json}}}"#);
+
+        assert_eq!(call("eth_chainId", "[]"), result(r#""0x539""#));
+        assert_eq!(call("eth_blockNumber", "[]"), result(r#""0x0""#));
+        let balance = call("eth_getBalance", r#"["0xaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaa","latest"]"#);
+        assert_eq!(balance, result(r#""0x3e8""#));
+        let old = call("eth_getBalance", r#"["0xaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaa","0x1"]"#);
+        assert!(old.contains(r#""error":{"code":-32000,"message":"historical state is not available"}"#), "{old}");
+        let safe = call("eth_getBalance", r#"["0xaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaa","safe"]"#);
+        assert!(safe.contains(r#""message":"safe block not found""#), "{safe}");
+
+        let to = r#""to":"0xc0c0c0c0c0c0c0c0c0c0c0c0c0c0c0c0c0c0c0c0""#;
+        let output = call("eth_call", &format!("[{{{to}}},\"latest\"]"));
+        assert_eq!(output, result(r#""0x000000000000000000000000000000000000000000000000000000000000002a""#));
+        let reverted = call("eth_call", &format!(r#"[{{{to},"input":"0x01"}}]"#));
+        assert!(reverted.contains(r#""error":{"code":3,"message":"execution reverted","data":"#), "{reverted}");
+
+        // 21000, five pushes, MSTORE with a word of memory, CALLDATASIZE
+        // and JUMPI.
+        let gas = call("eth_estimateGas", &format!("[{{{to}}}]"));
+        assert_eq!(gas, result(&format!(r#""0x{:x}""#, 21000 + 5 * 3 + 3 + 3 + 2 + 10)));
+
+        let access_list = call("eth_createAccessList", &format!("[{{{to}}}]"));
+        assert_eq!(access_list, result(&format!(r#"{{"accessList":[],"gasUsed":"0x{:x}"}}"#, 21000 + 5 * 3 + 3 + 3 + 2 + 10)));
+
+        let unknown = call("eth_sendTransaction", "[]");
+        assert!(unknown.contains(r#""code":-32601"#), "{unknown}");
+        let batch = server.handle(r#"[{"id":1,"method":"eth_chainId"},{"id":"two","method":"eth_blockNumber"}]"#);
+        assert_eq!(batch, r#"[{"jsonrpc":"2.0","id":1,"result":"0x539"},{"jsonrpc":"2.0","id":"two","result":"0x0"}]"#);
+        assert!(server.handle("{").contains(r#""code":-32700"#));
+    }
+
+    #[test]
+    fn safe_and_finalized_blocks() {
+        let mut chain = chain();
+        let head = chain.head_hash().clone();
+        chain.fork_choice = ForkChoiceState { head_block_hash: head.clone(), safe_block_hash: head, ..Default::default() };
+        let server = Server::new(Arc::new(RwLock::new(chain)));
+        let balance = |block: &str| {
+            server.handle(&format!(
+                r#"{{"id":1,"method":"eth_getBalance","params":["0xaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaa","{block}"]}}"#
+            ))
+        };
+        assert!(balance("safe").contains(r#""result":"0x3e8""#));
+        assert!(balance("finalized").contains(r#""message":"finalized block not found""#));
+
+        // A safe block below the head has no state.
+        let mut chain = server.chain.write().unwrap();
+        chain.fork_choice.safe_block_hash = Hash32([1; 32]);
+        drop(chain);
+        assert!(balance("safe").contains(r#""message":"historical state is not available""#));
+    }
+
+    #[test]
+    fn gas_cap() {
+        let to = r#""to":"0xc0c0c0c0c0c0c0c0c0c0c0c0c0c0c0c0c0c0c0c0""#;
+        let estimate = |server: &Server, gas: &str| {
+            server.handle(&format!(r#"{{"id":1,"method":"eth_estimateGas","params":[{{{to},"gas":"{gas}"}}]}}"#))
+        };
+        let server = Server::new(Arc::new(RwLock::new(chain())));
+        assert!(estimate(&server, "0xffffffffffffffff").contains(r#""result":"0x"#));
+        // The call needs more than 21000 gas.
+        let server = server.with_gas_cap(21000);
+        let capped = estimate(&server, "0xffffffffffffffff");
+        assert!(capped.contains(r#""error""#), "{capped}");
+    }
+
+    #[test]
+    fn serve() {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let address = listener.local_addr().unwrap();
+        let server = Server::new(Arc::new(RwLock::new(chain())));
+        std::thread::spawn(move || server.serve(listener));
+
+        let body = r#"{"jsonrpc":"2.0","id":7,"method":"eth_chainId","params":[]}"#;
+        let mut stream = TcpStream::connect(address).unwrap();
+        write!(stream, "POST / HTTP/1.1\r\ncontent-type: application/json\r\ncontent-length: {}\r\n\r\n{body}", body.len())
+            .unwrap();
+        let mut response = String::new();
+        stream.read_to_string(&mut response).unwrap();
+        assert!(response.starts_with("HTTP/1.1 200 OK\r\n"), "{response}");
+        assert!(response.ends_with(r#"{"jsonrpc":"2.0","id":7,"result":"0x539"}"#), "{response}");
+
+        // A body over the limit is refused before it is read.
+        let mut stream = TcpStream::connect(address).unwrap();
+        write!(stream, "POST / HTTP/1.1\r\ncontent-length: {}\r\n\r\n", u64::MAX).unwrap();
+        let mut response = String::new();
+        stream.read_to_string(&mut response).unwrap();
+        assert!(response.starts_with("HTTP/1.1 413 Payload Too Large\r\n"), "{response}");
+
+        let get = |address, path| {
+            let mut stream = TcpStream::connect(address).unwrap();
+            write!(stream, "GET {path} HTTP/1.1\r\n\r\n").unwrap();
+            let mut response = String::new();
+            stream.read_to_string(&mut response).unwrap();
+            response
+        };
+        assert!(get(address, "/metrics").starts_with("HTTP/1.1 405 "));
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let address = listener.local_addr().unwrap();
+        let server = Server::new(Arc::new(RwLock::new(chain()))).with_metrics();
+        std::thread::spawn(move || server.serve(listener));
+        let response = get(address, "/metrics");
+        assert!(response.starts_with("HTTP/1.1 200 OK\r\ncontent-type: text/plain"), "{response}");
+        assert!(response.contains("\r\n\r\n# HELP ejit_blocks_imported_total "), "{response}");
+        assert!(get(address, "/other").starts_with("HTTP/1.1 405 "));
+    }
+}
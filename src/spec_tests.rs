@@ -0,0 +1,466 @@
+//! Runner for the `GeneralStateTests` of `ethereum/tests`.
+//!
+//! A state test gives a pre-state, a block environment and a transaction
+//! whose `data`, `gasLimit` and `value` are lists. Each entry of `post`
+//! names a fork and picks one of each by index, with the state root and the
+//! hash of the logs expected after the transaction. The runner executes
+//! every entry the way a block would execute the transaction, without the
+//! block: no system calls, no block reward and no withdrawals.
+//!
+//! A transaction the fork must reject, marked by `expectException`, leaves
+//! the pre-state as it was, so its root is checked all the same.
+//!
+//! Fixtures are read with the crate's JSON decoder. Entries whose
+//! transaction is given as `txbytes` are decoded from it, others are built
+//! from the fields of `transaction` and signed with `secretKey`. The full
+//! suite is run by pointing `ETHEREUM_TESTS` at a checkout of
+//! `ethereum/tests`; see the tests at the end of this file.
+
+use std::{collections::BTreeMap, path::Path};
+
+use crate::{
+    ethereum::{
+        cancun::{
+            blocks::Log,
+            fork::{
+                check_transaction, prev_randao, prevalidate::prevalidate_transaction, process_transaction,
+                recent_block_hashes,
+            },
+            fork_types::{Account, Address, Root, VersionedHash},
+            state::{set_account, set_storage, state_root, State, TransientStorage},
+            transactions::{
+                decode_raw_transaction, sign_transaction, AccessListTransaction, BlobTransaction,
+                FeeMarketTransaction, LegacyTransaction, Transaction,
+            },
+            vm::{code_cache::shared_code_cache, Environment},
+        },
+        crypto::hash::{keccak256, Hash32},
+        ethereum_rlp::rlp,
+        ethereum_types::{
+            bytes::{Bytes, Bytes32},
+            numeric::{Uint, U256, U64},
+        },
+        exceptions::Exception,
+        forks::{fixture_fork, Fork},
+        utils::hexadecimal::hex_to_bytes,
+    },
+    impl_json,
+    json::{from_json_with, Decoder, JsonErrorAt},
+};
+
+/// Chain id of every state test.
+const CHAIN_ID: U64 = 1;
+
+/// The block a state test's transaction is executed in.
+#[derive(Debug, Clone, Default)]
+struct StateTestEnv {
+    coinbase: Address,
+    difficulty: Uint,
+    gas_limit: Uint,
+    number: Uint,
+    timestamp: U256,
+    base_fee: Option<Uint>,
+    random: Option<Bytes32>,
+    excess_blob_gas: Option<U64>,
+    previous_hash: Option<Hash32>,
+}
+
+impl_json!(#[skip_unknown] StateTestEnv :
+    coinbase "currentCoinbase",
+    difficulty "currentDifficulty",
+    gas_limit "currentGasLimit",
+    number "currentNumber",
+    timestamp "currentTimestamp",
+    base_fee "currentBaseFee",
+    random "currentRandom",
+    excess_blob_gas "currentExcessBlobGas",
+    previous_hash "previousHash",
+);
+
+#[derive(Debug, Clone, Default)]
+struct StateTestAccount {
+    balance: U256,
+    code: Bytes,
+    nonce: Uint,
+    storage: BTreeMap<U256, U256>,
+}
+
+impl_json!(StateTestAccount : balance "balance", code "code", nonce "nonce", storage "storage");
+
+#[derive(Debug, Clone, Default)]
+struct AccessListEntry {
+    address: Address,
+    storage_keys: Vec<Bytes32>,
+}
+
+impl_json!(AccessListEntry : address "address", storage_keys "storageKeys");
+
+/// The transaction of a state test, with lists of `data`, `gasLimit` and
+/// `value` to pick from.
+#[derive(Debug, Clone, Default)]
+struct StateTestTransaction {
+    data: Vec<Bytes>,
+    gas_limit: Vec<Uint>,
+    value: Vec<U256>,
+    /// One per entry of `data`, `null` for none.
+    access_lists: Option<Vec<Option<Vec<AccessListEntry>>>>,
+    gas_price: Option<Uint>,
+    max_fee_per_gas: Option<Uint>,
+    max_priority_fee_per_gas: Option<Uint>,
+    max_fee_per_blob_gas: Option<U256>,
+    blob_versioned_hashes: Option<Vec<Hash32>>,
+    nonce: U256,
+    secret_key: Bytes32,
+    /// Empty to create a contract.
+    to: String,
+}
+
+impl_json!(#[skip_unknown] StateTestTransaction :
+    data "data",
+    gas_limit "gasLimit",
+    value "value",
+    access_lists "accessLists",
+    gas_price "gasPrice",
+    max_fee_per_gas "maxFeePerGas",
+    max_priority_fee_per_gas "maxPriorityFeePerGas",
+    max_fee_per_blob_gas "maxFeePerBlobGas",
+    blob_versioned_hashes "blobVersionedHashes",
+    nonce "nonce",
+    secret_key "secretKey",
+    to "to",
+);
+
+#[derive(Debug, Clone, Default)]
+struct PostIndexes {
+    data: U64,
+    gas: U64,
+    value: U64,
+}
+
+impl_json!(PostIndexes : data "data", gas "gas", value "value");
+
+/// One expected outcome of a state test.
+#[derive(Debug, Clone, Default)]
+struct PostEntry {
+    hash: Root,
+    logs: Hash32,
+    indexes: PostIndexes,
+    txbytes: Option<Bytes>,
+    expect_exception: Option<String>,
+}
+
+impl_json!(#[skip_unknown] PostEntry :
+    hash "hash",
+    logs "logs",
+    indexes "indexes",
+    txbytes "txbytes",
+    expect_exception "expectException",
+);
+
+#[derive(Debug, Clone, Default)]
+struct StateTest {
+    env: StateTestEnv,
+    pre: BTreeMap<Address, StateTestAccount>,
+    transaction: StateTestTransaction,
+    post: BTreeMap<String, Vec<PostEntry>>,
+}
+
+impl_json!(#[skip_unknown] StateTest : env "env", pre "pre", transaction "transaction", post "post");
+
+/// Which entries of a fixture to run.
+#[derive(Debug, Clone, Default)]
+pub struct StateTestFilter {
+    /// Only the entries of this fork, named as in the fixtures, such as
+    /// `Cancun`.
+    pub fork: Option<String>,
+    /// Only tests whose name contains this.
+    pub name: Option<String>,
+}
+
+impl StateTestFilter {
+    fn matches(&self, name: &str, fork: &str) -> bool {
+        self.fork.as_ref().is_none_or(|f| f == fork) && self.name.as_ref().is_none_or(|n| name.contains(n.as_str()))
+    }
+}
+
+/// The outcome of one entry of `post`.
+#[derive(Debug, Clone, PartialEq)]
+pub struct StateTestResult {
+    pub name: String,
+    /// The fork as named in the fixture.
+    pub fork: String,
+    /// Position of the entry in the fork's list.
+    pub index: usize,
+    /// Why the entry failed, `None` if it passed.
+    pub failure: Option<String>,
+}
+
+impl StateTestResult {
+    pub fn passed(&self) -> bool {
+        self.failure.is_none()
+    }
+}
+
+/// Run the state tests of a fixture file's contents, returning one result
+/// per entry which `filter` selects. Entries of forks which are not
+/// implemented are skipped.
+pub fn run_state_tests(json: &str, filter: &StateTestFilter) -> Result<Vec<StateTestResult>, JsonErrorAt> {
+    // The fixtures are older than EIP-55 in places, so the case of their
+    // addresses means nothing.
+    let tests: BTreeMap<String, StateTest> = from_json_with(Decoder::new(json.as_bytes()).skip_checksums())?;
+    let mut results = Vec::new();
+    for (name, test) in &tests {
+        for (fork_name, entries) in &test.post {
+            let Some(fork) = fixture_fork(fork_name).filter(|_| filter.matches(name, fork_name)) else {
+                continue;
+            };
+            for (index, entry) in entries.iter().enumerate() {
+                results.push(StateTestResult {
+                    name: name.clone(),
+                    fork: fork_name.clone(),
+                    index,
+                    failure: run_entry(test, fork, entry).err(),
+                });
+            }
+        }
+    }
+    Ok(results)
+}
+
+/// `run_state_tests` for the fixture file at `path`.
+pub fn run_state_test_file(path: impl AsRef<Path>, filter: &StateTestFilter) -> Result<Vec<StateTestResult>, Exception> {
+    let json = std::fs::read_to_string(path).map_err(|_| Exception::EthereumException("cannot read state test"))?;
+    run_state_tests(&json, filter).map_err(Exception::JsonError)
+}
+
+/// Run one entry, returning why it failed.
+fn run_entry(test: &StateTest, fork: &dyn Fork, entry: &PostEntry) -> Result<(), String> {
+    let mut state = State::default();
+    for (address, account) in &test.pre {
+        set_account(
+            &mut state,
+            address,
+            Some(Account { nonce: account.nonce, balance: account.balance, code: account.code.clone() }),
+        );
+        for (key, value) in &account.storage {
+            set_storage(&mut state, address, &Bytes32(key.to_be_bytes()), *value);
+        }
+    }
+
+    let tx = match &entry.txbytes {
+        Some(txbytes) => decode_raw_transaction(txbytes).map_err(|e| format!("txbytes: {e:?}"))?,
+        None => build_transaction(&test.transaction, &entry.indexes, fork).map_err(|e| format!("transaction: {e:?}"))?,
+    };
+    let logs = match execute(&mut state, &test.env, fork, &tx) {
+        Ok(logs) if entry.expect_exception.is_some() => {
+            return Err(format!("expected {}, the transaction was valid", entry.expect_exception.as_ref().unwrap()));
+        }
+        Ok(logs) => logs,
+        Err(_) if entry.expect_exception.is_some() => Vec::new(),
+        Err(e) => return Err(format!("unexpected {e:?}")),
+    };
+
+    let root = state_root(&state).map_err(|e| format!("{e:?}"))?;
+    if root != entry.hash {
+        return Err(format!("state root {root:?}, expected {:?}", entry.hash));
+    }
+    let logs_hash = keccak256(&rlp::encode(&logs).map_err(|e| format!("{e:?}"))?);
+    if logs_hash != entry.logs {
+        return Err(format!("logs hash {logs_hash:?}, expected {:?}", entry.logs));
+    }
+    Ok(())
+}
+
+/// Check and execute `tx` in the block `env` under `fork`, returning its
+/// logs.
+fn execute(
+    state: &mut State,
+    env: &StateTestEnv,
+    fork: &dyn Fork,
+    tx: &Transaction,
+) -> Result<Vec<Log>, Exception> {
+    let rules = fork.execution_rules();
+    let sender = prevalidate_transaction(tx, CHAIN_ID, rules)?.sender;
+    // Fixtures give a base fee for every fork.
+    let base_fee_per_gas = if rules.london { env.base_fee.unwrap_or_default() } else { 0 };
+    let excess_blob_gas = env.excess_blob_gas.unwrap_or_default();
+    let (sender, gas_price, blob_versioned_hashes) =
+        check_transaction(state, tx, sender, env.gas_limit, base_fee_per_gas, excess_blob_gas)?;
+
+    // State tests give the hash of the parent block only.
+    let block_hashes = recent_block_hashes(env.number, |number| {
+        env.previous_hash.clone().filter(|_| number + 1 == env.number)
+    });
+    let mut env = Environment {
+        caller: sender.clone(),
+        origin: sender,
+        block_hashes,
+        coinbase: env.coinbase.clone(),
+        number: env.number,
+        gas_limit: env.gas_limit,
+        base_fee_per_gas,
+        gas_price,
+        time: env.timestamp,
+        prev_randao: prev_randao(fork, env.random.as_ref(), env.difficulty),
+        chain_id: CHAIN_ID,
+        excess_blob_gas,
+        blob_versioned_hashes,
+        code_cache: Some(shared_code_cache()),
+        ..Environment::new(state, rules)
+    };
+    let (_, logs, _) = process_transaction(&mut env, tx)?;
+    Ok(logs)
+}
+
+/// The transaction an entry picks from the fields of `transaction`, signed
+/// with its `secretKey`. The type follows from the fields present.
+fn build_transaction(tx: &StateTestTransaction, indexes: &PostIndexes, fork: &dyn Fork) -> Result<Transaction, Exception> {
+    let (Some(data), Some(&gas), Some(&value)) = (
+        tx.data.get(indexes.data as usize).cloned(),
+        tx.gas_limit.get(indexes.gas as usize),
+        tx.value.get(indexes.value as usize),
+    ) else {
+        return Err(Exception::EthereumException("index out of range"));
+    };
+    let to = hex_to_bytes(&tx.to)?;
+    let to = match to.len() {
+        0 => None,
+        20 => Some(Address::from_be_bytes(to[..].try_into().unwrap())),
+        _ => return Err(Exception::EthereumException("bad to")),
+    };
+    let access_list = tx.access_lists.as_ref().map(|lists| {
+        lists
+            .get(indexes.data as usize)
+            .cloned()
+            .flatten()
+            .unwrap_or_default()
+            .into_iter()
+            .map(|entry| (entry.address, entry.storage_keys))
+            .collect::<Vec<_>>()
+    });
+    let secret_key = U256::from_be_bytes(tx.secret_key.0);
+
+    let mut tx = match (&tx.blob_versioned_hashes, tx.max_fee_per_gas, access_list) {
+        (Some(blob_versioned_hashes), Some(max_fee_per_gas), access_list) => {
+            let tx = BlobTransaction {
+                chain_id: CHAIN_ID,
+                nonce: tx.nonce,
+                max_priority_fee_per_gas: tx.max_priority_fee_per_gas.unwrap_or_default(),
+                max_fee_per_gas,
+                gas,
+                to: to.ok_or(Exception::EthereumException("blob transaction without to"))?,
+                value,
+                data,
+                access_list: access_list.unwrap_or_default(),
+                max_fee_per_blob_gas: tx.max_fee_per_blob_gas.unwrap_or_default(),
+                blob_versioned_hashes: blob_versioned_hashes.iter().map(|hash| VersionedHash(hash.0)).collect(),
+                ..Default::default()
+            };
+            Transaction::BlobTransaction(tx)
+        }
+        (None, Some(max_fee_per_gas), access_list) => {
+            let tx = FeeMarketTransaction {
+                chain_id: CHAIN_ID,
+                nonce: tx.nonce,
+                max_priority_fee_per_gas: tx.max_priority_fee_per_gas.unwrap_or_default(),
+                max_fee_per_gas,
+                gas,
+                to: to.into(),
+                value,
+                data,
+                access_list: access_list.unwrap_or_default(),
+                ..Default::default()
+            };
+            Transaction::FeeMarketTransaction(tx)
+        }
+        (_, None, Some(access_list)) => {
+            let tx = AccessListTransaction {
+                chain_id: CHAIN_ID,
+                nonce: tx.nonce,
+                gas_price: tx.gas_price.unwrap_or_default(),
+                gas,
+                to: to.into(),
+                value,
+                data,
+                access_list,
+                ..Default::default()
+            };
+            Transaction::AccessListTransaction(tx)
+        }
+        (_, None, None) => {
+            let tx = LegacyTransaction {
+                nonce: tx.nonce,
+                gas_price: tx.gas_price.unwrap_or_default(),
+                gas,
+                to: to.into(),
+                value,
+                data,
+                ..Default::default()
+            };
+            Transaction::LegacyTransaction(tx)
+        }
+    };
+    // EIP-155 signatures from Spurious Dragon.
+    let chain_id = fork.execution_rules().spurious_dragon.then_some(CHAIN_ID);
+    sign_transaction(&mut tx, secret_key, chain_id)?;
+    Ok(tx)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{run_state_test_file, run_state_tests, StateTestFilter};
+
+    #[test]
+    fn filter() {
+        let json = std::fs::read_to_string(format!(
+            "{}/assets/GeneralStateTests/stExample/add11.json",
+            env!("CARGO_MANIFEST_DIR")
+        ))
+        .unwrap();
+        let filter = StateTestFilter { fork: Some("Cancun".to_string()), name: None };
+        let results = run_state_tests(&json, &filter).unwrap();
+        assert_eq!(results.iter().map(|r| (r.name.as_str(), r.index)).collect::<Vec<_>>(), [("add11", 0), ("add11", 1)]);
+        assert!(results.iter().all(|r| r.passed()));
+
+        // Constantinople before Petersburg is not implemented.
+        let filter = StateTestFilter { fork: Some("Constantinople".to_string()), name: None };
+        assert!(run_state_tests(&json, &filter).unwrap().is_empty());
+        let filter = StateTestFilter { fork: None, name: Some("accessList".to_string()) };
+        assert_eq!(run_state_tests(&json, &filter).unwrap().len(), 4);
+    }
+
+    /// The bundled fixtures, or with `ETHEREUM_TESTS` set the
+    /// `GeneralStateTests` of that checkout of `ethereum/tests`, filtered by
+    /// `STATE_TEST_FORK` and `STATE_TEST_NAME`.
+    #[test]
+    fn general_state_tests() {
+        let dir = match std::env::var("ETHEREUM_TESTS") {
+            Ok(dir) => format!("{dir}/GeneralStateTests"),
+            Err(_) => format!("{}/assets/GeneralStateTests", env!("CARGO_MANIFEST_DIR")),
+        };
+        let filter = StateTestFilter {
+            fork: std::env::var("STATE_TEST_FORK").ok(),
+            name: std::env::var("STATE_TEST_NAME").ok(),
+        };
+
+        let mut files = vec![std::path::PathBuf::from(dir)];
+        let (mut passed, mut failed) = (0, Vec::new());
+        while let Some(path) = files.pop() {
+            if path.is_dir() {
+                files.extend(std::fs::read_dir(&path).unwrap().map(|entry| entry.unwrap().path()));
+                continue;
+            }
+            if path.extension().is_none_or(|e| e != "json") {
+                continue;
+            }
+            for result in run_state_test_file(&path, &filter).unwrap() {
+                match result.failure {
+                    None => passed += 1,
+                    Some(failure) => failed.push(format!("{} {} {}: {failure}", result.name, result.fork, result.index)),
+                }
+            }
+        }
+        assert!(failed.is_empty(), "{passed} passed, {} failed:\n{}", failed.len(), failed.join("\n"));
+        assert!(passed > 0);
+    }
+}
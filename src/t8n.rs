@@ -0,0 +1,748 @@
+//! A state transition in the form of the `t8n` tools of the execution
+//! clients.
+//!
+//! `transition` takes the accounts before a block (`alloc`), the fields of
+//! the block (`env`) and its transactions, and returns the accounts after
+//! it with a summary of the block: its roots, receipts and the transactions
+//! which could not be included. Unlike `apply_body`, an invalid transaction
+//! does not fail the block. It is reported in `rejected` and left out, and
+//! the following transactions execute as if it had not been there.
+//!
+//! The inputs and outputs have the JSON layout of the files read and
+//! written by `evm t8n`, so that test fillers written for it can run
+//! against this crate through `ejit-evm t8n`. Fields of the block which
+//! follow from its parent, such as the base fee and the difficulty, are
+//! computed from the `parent*` fields when they are not given.
+
+use std::{collections::BTreeMap, sync::Arc};
+
+use crate::{
+    ethereum::{
+        cancun::{
+            blocks::{Header, Withdrawal},
+            fork::{
+                calculate_base_fee_per_gas, prevalidate::prevalidate_transaction, receipts::RpcReceipt,
+                prev_randao, recent_block_hashes, ApplyBodyOutput, BlockEnvironment, BlockExecution, EMPTY_OMMER_HASH,
+            },
+            fork_types::{Account, Address, Root, VersionedHash},
+            state::{set_storage, State},
+            transactions::{
+                sign_transaction, AccessListTransaction, BlobTransaction, FeeMarketTransaction, LegacyTransaction,
+                Transaction,
+            },
+            vm::{
+                code_cache::jump_destinations_cache,
+                gas::calculate_excess_blob_gas,
+            },
+        },
+        chain_spec::ChainSpec,
+        crypto::hash::{keccak256, Hash32},
+        ethereum_rlp::rlp,
+        ethereum_types::{
+            bytes::{Bytes, Bytes32},
+            numeric::{Uint, U256, U64},
+        },
+        exceptions::Exception,
+        forks::Fork,
+    },
+    impl_json,
+    json::{Encoder, JsonEncode},
+};
+
+pub use crate::ethereum::cancun::state::{Alloc, AllocAccount};
+
+/// Load `alloc` into a new state.
+pub fn alloc_to_state(alloc: &Alloc) -> State {
+    let accounts = alloc
+        .iter()
+        .map(|(address, account)| {
+            let account = Account { nonce: account.nonce, balance: account.balance, code: account.code.clone() };
+            (address.clone(), account)
+        })
+        .collect();
+    let mut state = State::from_alloc(accounts);
+    for (address, account) in alloc {
+        for (key, value) in &account.storage {
+            set_storage(&mut state, address, &Bytes32(key.to_be_bytes()), *value);
+        }
+    }
+    state
+}
+
+/// The accounts of `state`.
+pub fn state_to_alloc(state: &State) -> Alloc {
+    state.dump(..)
+}
+
+/// An ommer of the block, `delta` blocks before it.
+#[derive(Debug, Clone, Default)]
+pub struct T8nOmmer {
+    pub delta: Uint,
+    pub address: Address,
+}
+
+impl_json!(T8nOmmer : delta "delta", address "address");
+
+/// The block, as in `env.json`.
+#[derive(Debug, Clone, Default)]
+pub struct T8nEnv {
+    pub coinbase: Address,
+    pub gas_limit: Uint,
+    pub number: Uint,
+    pub timestamp: U256,
+    /// Computed from the parent before the merge if not given.
+    pub difficulty: Option<Uint>,
+    /// `PREVRANDAO` after the merge.
+    pub random: Option<Bytes32>,
+    /// Computed from the parent from London if not given.
+    pub base_fee: Option<Uint>,
+    pub parent_difficulty: Option<Uint>,
+    pub parent_timestamp: Option<U256>,
+    pub parent_base_fee: Option<Uint>,
+    pub parent_gas_used: Option<Uint>,
+    pub parent_gas_limit: Option<Uint>,
+    pub parent_uncle_hash: Option<Hash32>,
+    /// Hashes of previous blocks by decimal block number, for `BLOCKHASH`.
+    pub block_hashes: BTreeMap<String, Hash32>,
+    pub ommers: Vec<T8nOmmer>,
+    pub withdrawals: Option<Vec<Withdrawal>>,
+    /// Computed from the parent from Cancun if not given.
+    pub excess_blob_gas: Option<U64>,
+    pub parent_excess_blob_gas: Option<U64>,
+    pub parent_blob_gas_used: Option<U64>,
+    pub parent_beacon_block_root: Option<Root>,
+}
+
+impl_json!(#[skip_unknown] T8nEnv :
+    coinbase "currentCoinbase",
+    gas_limit "currentGasLimit",
+    number "currentNumber",
+    timestamp "currentTimestamp",
+    difficulty "currentDifficulty",
+    random "currentRandom",
+    base_fee "currentBaseFee",
+    parent_difficulty "parentDifficulty",
+    parent_timestamp "parentTimestamp",
+    parent_base_fee "parentBaseFee",
+    parent_gas_used "parentGasUsed",
+    parent_gas_limit "parentGasLimit",
+    parent_uncle_hash "parentUncleHash",
+    block_hashes "blockHashes",
+    ommers "ommers",
+    withdrawals "withdrawals",
+    excess_blob_gas "currentExcessBlobGas",
+    parent_excess_blob_gas "parentExcessBlobGas",
+    parent_blob_gas_used "parentBlobGasUsed",
+    parent_beacon_block_root "parentBeaconBlockRoot",
+);
+
+#[derive(Debug, Clone, Default)]
+pub struct T8nAccessListEntry {
+    pub address: Address,
+    pub storage_keys: Vec<Bytes32>,
+}
+
+impl_json!(T8nAccessListEntry : address "address", storage_keys "storageKeys");
+
+/// A transaction of `txs.json`. It is signed with `secret_key` if one is
+/// given, and carries its signature in `v`, `r` and `s` otherwise.
+#[derive(Debug, Clone, Default)]
+pub struct T8nTransaction {
+    /// Zero, the default, for a legacy transaction.
+    pub tx_type: U64,
+    pub chain_id: Option<U64>,
+    pub nonce: U256,
+    pub gas_price: Option<Uint>,
+    pub max_priority_fee_per_gas: Option<Uint>,
+    pub max_fee_per_gas: Option<Uint>,
+    pub gas: Uint,
+    /// `None` to create a contract.
+    pub to: Option<Address>,
+    pub value: U256,
+    pub input: Bytes,
+    /// The older name of `input`, which it replaces if given.
+    pub data: Option<Bytes>,
+    pub access_list: Vec<T8nAccessListEntry>,
+    pub max_fee_per_blob_gas: Option<U256>,
+    pub blob_versioned_hashes: Vec<Hash32>,
+    pub v: U256,
+    pub r: U256,
+    pub s: U256,
+    pub secret_key: Option<Bytes32>,
+    /// Whether a legacy transaction signed with `secret_key` is replay
+    /// protected (EIP-155).
+    pub protected: Option<bool>,
+}
+
+impl_json!(#[skip_unknown] T8nTransaction :
+    tx_type "type",
+    chain_id "chainId",
+    nonce "nonce",
+    gas_price "gasPrice",
+    max_priority_fee_per_gas "maxPriorityFeePerGas",
+    max_fee_per_gas "maxFeePerGas",
+    gas "gas",
+    to "to",
+    value "value",
+    input "input",
+    data "data",
+    access_list "accessList",
+    max_fee_per_blob_gas "maxFeePerBlobGas",
+    blob_versioned_hashes "blobVersionedHashes",
+    v "v",
+    r "r",
+    s "s",
+    secret_key "secretKey",
+    protected "protected",
+);
+
+impl T8nTransaction {
+    /// The transaction this describes, signed if it has a `secret_key`.
+    pub fn to_transaction(&self, chain_id: U64) -> Result<Transaction, Exception> {
+        let tx_chain_id = self.chain_id.unwrap_or(chain_id);
+        let access_list = self
+            .access_list
+            .iter()
+            .map(|entry| (entry.address.clone(), entry.storage_keys.clone()))
+            .collect::<Vec<_>>();
+        let y_parity = self.v;
+        let data = self.data.as_ref().unwrap_or(&self.input);
+        let mut tx = match self.tx_type {
+            0 => Transaction::LegacyTransaction(LegacyTransaction {
+                nonce: self.nonce,
+                gas_price: self.gas_price.unwrap_or_default(),
+                gas: self.gas,
+                to: self.to.clone().into(),
+                value: self.value,
+                data: data.clone(),
+                v: self.v,
+                r: self.r,
+                s: self.s,
+            }),
+            1 => Transaction::AccessListTransaction(AccessListTransaction {
+                chain_id: tx_chain_id,
+                nonce: self.nonce,
+                gas_price: self.gas_price.unwrap_or_default(),
+                gas: self.gas,
+                to: self.to.clone().into(),
+                value: self.value,
+                data: data.clone(),
+                access_list,
+                y_parity,
+                r: self.r,
+                s: self.s,
+            }),
+            2 => Transaction::FeeMarketTransaction(FeeMarketTransaction {
+                chain_id: tx_chain_id,
+                nonce: self.nonce,
+                max_priority_fee_per_gas: self.max_priority_fee_per_gas.unwrap_or_default(),
+                max_fee_per_gas: self.max_fee_per_gas.unwrap_or_default(),
+                gas: self.gas,
+                to: self.to.clone().into(),
+                value: self.value,
+                data: data.clone(),
+                access_list,
+                y_parity,
+                r: self.r,
+                s: self.s,
+            }),
+            3 => Transaction::BlobTransaction(BlobTransaction {
+                chain_id: tx_chain_id,
+                nonce: self.nonce,
+                max_priority_fee_per_gas: self.max_priority_fee_per_gas.unwrap_or_default(),
+                max_fee_per_gas: self.max_fee_per_gas.unwrap_or_default(),
+                gas: self.gas,
+                to: self.to.clone().ok_or(Exception::InvalidTransaction("blob transaction without to"))?,
+                value: self.value,
+                data: data.clone(),
+                access_list,
+                max_fee_per_blob_gas: self.max_fee_per_blob_gas.unwrap_or_default(),
+                blob_versioned_hashes: self.blob_versioned_hashes.iter().map(|hash| VersionedHash(hash.0)).collect(),
+                y_parity,
+                r: self.r,
+                s: self.s,
+            }),
+            _ => return Err(Exception::InvalidTransaction("unsupported transaction type")),
+        };
+        if let Some(secret_key) = &self.secret_key {
+            let chain_id = self.protected.unwrap_or(true).then_some(tx_chain_id);
+            sign_transaction(&mut tx, U256::from_be_bytes(secret_key.0), chain_id)?;
+        }
+        Ok(tx)
+    }
+}
+
+/// A transaction left out of the block.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Rejected {
+    /// Position of the transaction in the input.
+    pub index: usize,
+    pub error: String,
+}
+
+impl JsonEncode for Rejected {
+    fn encode_json(&self, encoder: &mut Encoder) {
+        let mut o = encoder.object();
+        o.key("index");
+        o.encoder.raw(&self.index.to_string());
+        o.field("error", &self.error);
+        o.end();
+    }
+}
+
+/// The summary of the block, as in `result.json`.
+#[derive(Debug, Clone)]
+pub struct T8nResult {
+    pub body: ApplyBodyOutput,
+    /// Hash of the RLP of all the logs of the block.
+    pub logs_hash: Hash32,
+    pub rejected: Vec<Rejected>,
+    /// The block fields `transition` computed or was given.
+    pub difficulty: Uint,
+    pub base_fee: Option<Uint>,
+    pub excess_blob_gas: Option<U64>,
+}
+
+impl JsonEncode for T8nResult {
+    fn encode_json(&self, encoder: &mut Encoder) {
+        let body = &self.body;
+        let mut o = encoder.object();
+        o.field("stateRoot", &body.state_root);
+        o.field("txRoot", &body.transactions_root);
+        o.field("receiptsRoot", &body.receipt_root);
+        o.field("logsHash", &self.logs_hash);
+        o.field("logsBloom", &body.block_logs_bloom);
+        // The block has no hash yet.
+        let block_hash = Hash32::default();
+        o.key("receipts");
+        let mut receipts = o.encoder.array();
+        let mut first_log_index = 0;
+        for (transaction_index, receipt) in body.receipts.iter().enumerate() {
+            let rpc = RpcReceipt { receipt, block_hash: &block_hash, block_number: 0, transaction_index, first_log_index };
+            rpc.encode_json(receipts.next());
+            first_log_index += receipt.receipt.logs.len();
+        }
+        receipts.end();
+        if !self.rejected.is_empty() {
+            o.field("rejected", &self.rejected);
+        }
+        o.field("currentDifficulty", &self.difficulty);
+        o.field("gasUsed", &body.block_gas_used);
+        if let Some(base_fee) = &self.base_fee {
+            o.field("currentBaseFee", base_fee);
+        }
+        if let Some(withdrawals_root) = &body.withdrawals_root {
+            o.field("withdrawalsRoot", withdrawals_root);
+        }
+        if let Some(excess_blob_gas) = &self.excess_blob_gas {
+            o.field("currentExcessBlobGas", excess_blob_gas);
+        }
+        if let Some(blob_gas_used) = &body.blob_gas_used {
+            o.field("blobGasUsed", blob_gas_used);
+        }
+        if let Some(requests_hash) = &body.requests_hash {
+            o.field("requestsHash", requests_hash);
+        }
+        o.end();
+    }
+}
+
+/// Apply `txs` in the block `env` to the accounts of `alloc` under the
+/// rules of `fork` and the parameters of `chain_spec`, returning the
+/// accounts after the block and its summary.
+///
+/// `reward` is the reward of the miner of a proof of work block, usually
+/// that of `fork`; `None` pays nothing. Transactions which cannot be
+/// decoded, signed or included are reported in `rejected`.
+pub fn transition(
+    alloc: &Alloc,
+    env: &T8nEnv,
+    txs: &[Result<Transaction, Exception>],
+    fork: &dyn Fork,
+    chain_spec: &ChainSpec,
+    reward: Option<Uint>,
+) -> Result<(Alloc, T8nResult), Exception> {
+    let rules = fork.execution_rules();
+    let mut state = alloc_to_state(alloc);
+
+    let difficulty = match (env.difficulty, fork.proof_of_work()) {
+        (Some(difficulty), _) => difficulty,
+        (None, Some(proof_of_work)) => {
+            let (Some(parent_difficulty), Some(parent_timestamp)) = (env.parent_difficulty, env.parent_timestamp) else {
+                return Err(Exception::EthereumException("currentDifficulty or parentDifficulty required"));
+            };
+            let parent_has_ommers = env.parent_uncle_hash.as_ref().is_some_and(|hash| *hash != EMPTY_OMMER_HASH);
+            proof_of_work.calculate_block_difficulty(
+                env.number,
+                env.timestamp,
+                parent_timestamp,
+                parent_difficulty,
+                parent_has_ommers,
+            )
+        }
+        (None, None) => 0,
+    };
+    let prev_randao = prev_randao(fork, env.random.as_ref(), difficulty);
+
+    let base_fee = match (env.base_fee, rules.london) {
+        (_, false) => None,
+        (Some(base_fee), true) => Some(base_fee),
+        (None, true) => {
+            let (Some(parent_gas_limit), Some(parent_gas_used), Some(parent_base_fee)) =
+                (env.parent_gas_limit, env.parent_gas_used, env.parent_base_fee)
+            else {
+                return Err(Exception::EthereumException("currentBaseFee or parentBaseFee required"));
+            };
+            Some(calculate_base_fee_per_gas(
+                env.gas_limit,
+                parent_gas_limit,
+                parent_gas_used,
+                parent_base_fee,
+                chain_spec,
+            )?)
+        }
+    };
+
+    let excess_blob_gas = match (env.excess_blob_gas, rules.blobs) {
+        (_, false) => None,
+        (Some(excess_blob_gas), true) => Some(excess_blob_gas),
+        (None, true) => {
+            let parent = Header {
+                excess_blob_gas: env.parent_excess_blob_gas,
+                blob_gas_used: env.parent_blob_gas_used,
+                ..Default::default()
+            };
+            // At the fork block the parent has neither.
+            Some(calculate_excess_blob_gas(&parent, chain_spec).unwrap_or_default())
+        }
+    };
+
+    let block_hashes = recent_block_hashes(env.number, |number| env.block_hashes.get(&number.to_string()).cloned());
+
+    let block_env = BlockEnvironment {
+        block_hashes,
+        coinbase: env.coinbase.clone(),
+        number: env.number,
+        base_fee_per_gas: base_fee,
+        gas_limit: env.gas_limit,
+        time: env.timestamp,
+        prev_randao,
+        chain_id: chain_spec.chain_id,
+        parent_beacon_block_root: env.parent_beacon_block_root.clone(),
+        excess_blob_gas,
+        rules,
+        chain_spec: chain_spec.clone(),
+        code_cache: Some(Arc::new(jump_destinations_cache())),
+    };
+
+    let mut execution = BlockExecution::begin(&mut state, &block_env)?;
+    let mut rejected = Vec::new();
+    for (index, tx) in txs.iter().enumerate() {
+        let result = tx.as_ref().map_err(|error| format!("{error:?}")).and_then(|tx| {
+            prevalidate_transaction(tx, chain_spec.chain_id, rules)
+                .and_then(|prevalidated| execution.apply_transaction(&mut state, &block_env, tx, prevalidated.sender))
+                .map_err(|error| format!("{error:?}"))
+        });
+        if let Err(error) = result {
+            rejected.push(Rejected { index, error });
+        }
+    }
+
+    let ommers = env
+        .ommers
+        .iter()
+        .map(|ommer| Header {
+            number: env.number.saturating_sub(ommer.delta),
+            coinbase: ommer.address.clone(),
+            ..Default::default()
+        })
+        .collect::<Vec<_>>();
+    let body = execution.finish(&mut state, &block_env, &ommers, reward, env.withdrawals.as_deref())?;
+
+    let logs = body.receipts.iter().flat_map(|receipt| receipt.receipt.logs.iter().cloned()).collect::<Vec<_>>();
+    let logs_hash = keccak256(&rlp::encode(&logs)?);
+    let result = T8nResult { body, logs_hash, rejected, difficulty, base_fee, excess_blob_gas };
+    Ok((state_to_alloc(&state), result))
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::BTreeMap;
+
+    use crate::{
+        ethereum::{
+            cancun::{fork_types::Address, transactions::Transaction},
+            chain_spec::ChainSpec,
+            crypto::{eliptic_curve::secp256k1_public_key, hash::{keccak256, Hash32}},
+            ethereum_types::{bytes::{Bytes, Bytes32}, numeric::U256},
+            forks::fixture_fork,
+        },
+        json::{from_json, to_json},
+    };
+
+    use super::{transition, Alloc, AllocAccount, T8nEnv, T8nTransaction};
+
+    fn sender(secret_key: &Bytes32) -> Address {
+        Address::from_be_bytes(
+            keccak256(&secp256k1_public_key(U256::from_be_bytes(secret_key.0)))[12..].try_into().unwrap(),
+        )
+    }
+
+    fn env() -> T8nEnv {
+    from_json(
+        br#"{
+            "currentCoinbase": "0xc000000000000000000000000000000000000000",
+            "currentGasLimit": "0x1c9c380",
+            "currentNumber": "0x1",
+            "currentTimestamp": "0xc",
+            "currentRandom": "0x0000000000000000000000000000000000000000000000000000000000000000",
+            "parentBaseFee": "0x7",
+            "parentGasUsed": "0x0",
+            "parentGasLimit": "0x1c9c380",
+            "parentExcessBlobGas": "0x0",
+            "parentBlobGasUsed": "0x0",
+            "parentBeaconBlockRoot": "0x0000000000000000000000000000000000000000000000000000000000000000",
+            "withdrawals": []
+        }"#,
+    )
+    .unwrap()
+    }
+
+    #[test]
+    fn transfer_and_reject() {
+        let secret_key = Bytes32([0x45; 32]);
+        let sender = sender(&secret_key);
+        let alloc: Alloc = BTreeMap::from([(
+            sender.clone(),
+            AllocAccount { balance: U256::from(1_000_000_000_000_000_000_u64), ..Default::default() },
+        )]);
+        let env = env();
+        let key = to_json(&secret_key);
+        let txs: Vec<T8nTransaction> = from_json(
+            format!(
+                r#"[
+                    {{"type": "0x2", "nonce": "0x0", "maxFeePerGas": "0x10", "maxPriorityFeePerGas": "0x1",
+                      "gas": "0x5208", "to": "0x00000000000000000000000000000000000000aa", "value": "0x1",
+                      "input": "0x", "chainId": "0x1", "secretKey": {key}}},
+                    {{"type": "0x2", "nonce": "0x5", "maxFeePerGas": "0x10", "maxPriorityFeePerGas": "0x1",
+                      "gas": "0x5208", "to": "0x00000000000000000000000000000000000000aa", "value": "0x1",
+                      "input": "0x", "chainId": "0x1", "secretKey": {key}}}
+                ]"#
+            )
+            .as_bytes(),
+        )
+        .unwrap();
+        let txs = txs.iter().map(|tx| tx.to_transaction(1)).collect::<Vec<_>>();
+        assert!(matches!(txs[0], Ok(Transaction::FeeMarketTransaction(_))));
+
+        let fork = fixture_fork("Cancun").unwrap();
+        let (post, result) = transition(&alloc, &env, &txs, fork, &ChainSpec::MAINNET, None).unwrap();
+
+        // The second transaction has the wrong nonce and is left out.
+        assert_eq!(result.rejected.len(), 1);
+        assert_eq!(result.rejected[0].index, 1);
+        assert_eq!(result.body.receipts.len(), 1);
+        assert_eq!(result.body.block_gas_used, 21000);
+        // An empty parent lowers the base fee by an eighth, rounded down.
+        assert_eq!(result.base_fee, Some(7));
+        assert_eq!(result.excess_blob_gas, Some(0));
+
+        let recipient = Address::from_be_bytes([0; 19].into_iter().chain([0xaa]).collect::<Vec<_>>().try_into().unwrap());
+        assert_eq!(post[&recipient].balance, U256::from(1_u32));
+        assert_eq!(post[&sender].nonce, 1);
+        let coinbase = post.get(&env.coinbase).map(|account| account.balance);
+        assert_eq!(coinbase, Some(U256::from(21000_u32)));
+
+        let json = to_json(&result);
+        assert!(json.contains(r#""rejected":[{"index":1,"error":"#), "{json}");
+        assert!(json.contains(r#""gasUsed":"0x5208""#), "{json}");
+    }
+
+    #[test]
+    fn selfdestruct() {
+        let secret_key = Bytes32([0x45; 32]);
+        let sender = sender(&secret_key);
+        let contract = Address::from_be_bytes([0xcc; 20]);
+        let beneficiary = Address::from_be_bytes([0; 19].into_iter().chain([0xbb]).collect::<Vec<_>>().try_into().unwrap());
+        // PUSH1 0xbb SELFDESTRUCT
+        let code = Bytes(vec![0x60, 0xbb, 0xff]);
+        let alloc: Alloc = BTreeMap::from([
+            (sender.clone(), AllocAccount { balance: U256::from(1_000_000_000_000_000_000_u64), ..Default::default() }),
+            (contract.clone(), AllocAccount { balance: U256::from(5_u32), code: code.clone(), nonce: 1, ..Default::default() }),
+        ]);
+        let key = to_json(&secret_key);
+        // A call to `contract`, then a creation whose init code is the code
+        // of `contract`.
+        let txs: Vec<T8nTransaction> = from_json(
+            format!(
+                r#"[
+                    {{"type": "0x2", "nonce": "0x0", "maxFeePerGas": "0x10", "maxPriorityFeePerGas": "0x1",
+                      "gas": "0x186a0", "to": {contract}, "value": "0x0",
+                      "input": "0x", "chainId": "0x1", "secretKey": {key}}},
+                    {{"type": "0x2", "nonce": "0x1", "maxFeePerGas": "0x10", "maxPriorityFeePerGas": "0x1",
+                      "gas": "0x186a0", "to": null, "value": "0x7",
+                      "input": {code}, "chainId": "0x1", "secretKey": {key}}}
+                ]"#,
+                contract = to_json(&contract),
+                code = to_json(&code),
+            )
+            .as_bytes(),
+        )
+        .unwrap();
+        let txs = txs.iter().map(|tx| tx.to_transaction(1)).collect::<Vec<_>>();
+
+        // Since Cancun (EIP-6780) an account which was not created in the
+        // same transaction only gives away its balance.
+        let fork = fixture_fork("Cancun").unwrap();
+        let (post, result) = transition(&alloc, &env(), &txs, fork, &ChainSpec::MAINNET, None).unwrap();
+        assert!(result.rejected.is_empty());
+        // The call, PUSH1, SELFDESTRUCT, a cold beneficiary and its creation.
+        assert_eq!(result.body.receipts[0].receipt.cumulative_gas_used, 21000 + 3 + 5000 + 2600 + 25000);
+        assert_eq!(post[&contract].code, code);
+        assert_eq!(post[&contract].balance, U256::ZERO);
+        assert_eq!(post[&beneficiary].balance, U256::from(12_u32));
+        // The created contract is gone.
+        assert_eq!(post.len(), 4);
+
+        // Before, the account is deleted.
+        let fork = fixture_fork("Shanghai").unwrap();
+        let (post, _) = transition(&alloc, &env(), &txs, fork, &ChainSpec::MAINNET, None).unwrap();
+        assert!(!post.contains_key(&contract));
+        assert_eq!(post[&beneficiary].balance, U256::from(12_u32));
+    }
+
+    #[test]
+    fn selfdestruct_refund() {
+        let secret_key = Bytes32([0x45; 32]);
+        let sender = sender(&secret_key);
+        let victim = Address::from_be_bytes([0xcc; 20]);
+        let caller = Address::from_be_bytes([0xcd; 20]);
+        // PUSH1 0xbb SELFDESTRUCT
+        let victim_code = vec![0x60, 0xbb, 0xff];
+        // SSTORE(0, 1), then CALL(GAS, victim, 0, 0, 0, 0, 0) twice.
+        let mut caller_code = vec![0x60, 0x01, 0x60, 0x00, 0x55];
+        for _ in 0..2 {
+            caller_code.extend([0x60, 0x00, 0x60, 0x00, 0x60, 0x00, 0x60, 0x00, 0x60, 0x00, 0x73]);
+            caller_code.extend(&victim[..]);
+            caller_code.extend([0x5a, 0xf1, 0x50]);
+        }
+        let alloc: Alloc = BTreeMap::from([
+            (sender.clone(), AllocAccount { balance: U256::from(1_000_000_000_000_000_000_u64), ..Default::default() }),
+            (victim.clone(), AllocAccount { code: Bytes(victim_code), nonce: 1, ..Default::default() }),
+            (caller.clone(), AllocAccount { code: Bytes(caller_code), nonce: 1, ..Default::default() }),
+        ]);
+        let txs: Vec<T8nTransaction> = from_json(
+            format!(
+                r#"[
+                    {{"type": "0x1", "nonce": "0x0", "gasPrice": "0x10", "gas": "0x186a0", "to": {caller},
+                      "value": "0x0", "input": "0x", "chainId": "0x1", "secretKey": {key}}}
+                ]"#,
+                caller = to_json(&caller),
+                key = to_json(&secret_key),
+            )
+            .as_bytes(),
+        )
+        .unwrap();
+        let txs = txs.iter().map(|tx| tx.to_transaction(1)).collect::<Vec<_>>();
+
+        // Before London the sibling calls self-destruct the same account,
+        // which is refunded once. The SSTORE keeps the refund under the cap
+        // of half the gas used.
+        let fork = fixture_fork("Berlin").unwrap();
+        let env = T8nEnv { difficulty: Some(0x20000), ..env() };
+        let (post, result) = transition(&alloc, &env, &txs, fork, &ChainSpec::MAINNET, None).unwrap();
+        assert!(result.rejected.is_empty(), "{:?}", result.rejected);
+        assert!(!post.contains_key(&victim));
+        let sstore = 3 + 3 + 2100 + 20000;
+        let calls = 2 * (5 * 3 + 3 + 2 + 2) + 2600 + 100;
+        let selfdestructs = 2 * (3 + 5000) + 2600;
+        assert_eq!(result.body.receipts[0].receipt.cumulative_gas_used, 21000 + sstore + calls + selfdestructs - 24000);
+    }
+
+    #[test]
+    fn blob_opcodes() {
+        let secret_key = Bytes32([0x45; 32]);
+        let sender = sender(&secret_key);
+        let contract = Address::from_be_bytes([0xcc; 20]);
+        // SSTORE(0, BLOBHASH(0)) SSTORE(1, BLOBHASH(1)) SSTORE(2, BLOBBASEFEE)
+        let code = Bytes(vec![
+            0x60, 0x00, 0x49, 0x60, 0x00, 0x55, 0x60, 0x01, 0x49, 0x60, 0x01, 0x55, 0x4a, 0x60, 0x02, 0x55, 0x00,
+        ]);
+        let alloc: Alloc = BTreeMap::from([
+            (sender.clone(), AllocAccount { balance: U256::from(1_000_000_000_000_000_000_u64), ..Default::default() }),
+            (contract.clone(), AllocAccount { code, nonce: 1, ..Default::default() }),
+        ]);
+        let versioned_hash = format!("0x01{}", "ab".repeat(31));
+        let txs: Vec<T8nTransaction> = from_json(
+            format!(
+                r#"[
+                    {{"type": "0x3", "nonce": "0x0", "maxFeePerGas": "0x10", "maxPriorityFeePerGas": "0x1",
+                      "gas": "0x186a0", "to": {contract}, "value": "0x0", "input": "0x", "chainId": "0x1",
+                      "maxFeePerBlobGas": "0x1", "blobVersionedHashes": ["{versioned_hash}"], "secretKey": {key}}}
+                ]"#,
+                contract = to_json(&contract),
+                key = to_json(&secret_key),
+            )
+            .as_bytes(),
+        )
+        .unwrap();
+        let txs = txs.iter().map(|tx| tx.to_transaction(1)).collect::<Vec<_>>();
+
+        let fork = fixture_fork("Cancun").unwrap();
+        let (post, result) = transition(&alloc, &env(), &txs, fork, &ChainSpec::MAINNET, None).unwrap();
+        assert!(result.rejected.is_empty(), "{:?}", result.rejected);
+        let storage = &post[&contract].storage;
+        let mut hash = [0xab; 32];
+        hash[0] = 0x01;
+        assert_eq!(storage[&U256::ZERO], U256::from_be_bytes(hash));
+        // Past the last blob the hash is zero, and zero is not stored.
+        assert!(!storage.contains_key(&U256::ONE));
+        // The minimum blob gas price, as the parent had no excess blob gas.
+        assert_eq!(storage[&U256::from(2_u32)], U256::ONE);
+    }
+
+    #[test]
+    fn logs() {
+        let secret_key = Bytes32([0x45; 32]);
+        let sender = sender(&secret_key);
+        let logger = Address::from_be_bytes([0xcc; 20]);
+        let static_caller = Address::from_be_bytes([0xcd; 20]);
+        // MSTORE8(0, 0xaa) LOG2(0, 1, 1, 2)
+        let logger_code = vec![0x60, 0xaa, 0x60, 0x00, 0x53, 0x60, 0x02, 0x60, 0x01, 0x60, 0x01, 0x60, 0x00, 0xa2, 0x00];
+        // SSTORE(0, ISZERO(STATICCALL(10000, logger, 0, 0, 0, 0)))
+        let mut static_code = vec![0x60, 0x00, 0x60, 0x00, 0x60, 0x00, 0x60, 0x00, 0x73];
+        static_code.extend(&logger[..]);
+        static_code.extend([0x61, 0x27, 0x10, 0xfa, 0x15, 0x60, 0x00, 0x55, 0x00]);
+        let alloc: Alloc = BTreeMap::from([
+            (sender.clone(), AllocAccount { balance: U256::from(1_000_000_000_000_000_000_u64), ..Default::default() }),
+            (logger.clone(), AllocAccount { code: Bytes(logger_code), nonce: 1, ..Default::default() }),
+            (static_caller.clone(), AllocAccount { code: Bytes(static_code), nonce: 1, ..Default::default() }),
+        ]);
+        let key = to_json(&secret_key);
+        let tx = |nonce: u8, to: &Address| {
+            format!(
+                r#"{{"type": "0x2", "nonce": "{nonce:#x}", "maxFeePerGas": "0x10", "maxPriorityFeePerGas": "0x1",
+                    "gas": "0x186a0", "to": {to}, "value": "0x0", "input": "0x", "chainId": "0x1", "secretKey": {key}}}"#,
+                to = to_json(to),
+            )
+        };
+        let txs: Vec<T8nTransaction> =
+            from_json(format!("[{}, {}]", tx(0, &logger), tx(1, &static_caller)).as_bytes()).unwrap();
+        let txs = txs.iter().map(|tx| tx.to_transaction(1)).collect::<Vec<_>>();
+
+        let fork = fixture_fork("Cancun").unwrap();
+        let (post, result) = transition(&alloc, &env(), &txs, fork, &ChainSpec::MAINNET, None).unwrap();
+        assert!(result.rejected.is_empty(), "{:?}", result.rejected);
+        let receipts = &result.body.receipts;
+        let logs = &receipts[0].receipt.logs;
+        assert_eq!(logs.len(), 1);
+        assert_eq!(logs[0].address, logger);
+        assert_eq!(logs[0].topics, [1, 2].map(|t| U256::from(t as u32).to_be_bytes()).map(Hash32));
+        assert_eq!(logs[0].data, Bytes(vec![0xaa]));
+        assert_ne!(receipts[0].receipt.bloom, Default::default());
+
+        // Logging is a write, so it fails in a static call.
+        assert!(receipts[1].receipt.logs.is_empty());
+        assert_eq!(post[&static_caller].storage[&U256::ZERO], U256::ONE);
+    }
+}
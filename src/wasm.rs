@@ -0,0 +1,151 @@
+//! WebAssembly interface for in-browser playgrounds.
+//!
+//! Enabled with the `wasm` feature. To build a module for the browser:
+//!
+//! ```sh
+//! cargo rustc --release --target wasm32-unknown-unknown --features wasm --crate-type cdylib
+//! wasm-bindgen --target web --out-dir examples/wasm/pkg \
+//!     target/wasm32-unknown-unknown/release/ejit_evm.wasm
+//! ```
+//!
+//! and serve `examples/wasm/` to try it out.
+//!
+//! `run_bytecode` executes code as the body of a contract in an otherwise
+//! empty state and returns the result and a trace of the executed opcodes as
+//! JSON:
+//!
+//! ```json
+//! {
+//!   "success": true,
+//!   "gasUsed": 24,
+//!   "output": "0x...",
+//!   "error": null,
+//!   "trace": [{"pc": 0, "op": "PUSH1", "gas": 100000, "gasCost": 3, "depth": 1}, ...]
+//! }
+//! ```
+//!
+//! `gasUsed` does not include the intrinsic cost of a transaction.
+//!
+//! No KZG trusted setup is loaded, so the point evaluation precompile at
+//! 0x0a cannot be called.
+
+use std::collections::BTreeSet;
+
+use wasm_bindgen::prelude::wasm_bindgen;
+
+use crate::ethereum::{
+    cancun::{
+        fork_types::{Account, Address},
+        state::{set_account, State, TransientStorage},
+        utils::prepare_message,
+        vm::{code_cache::shared_code_cache, interpreter::process_message_call, Environment},
+    },
+    ethereum_types::{
+        bytes::Bytes,
+        numeric::{Uint, U256},
+    },
+    forks::ExecutionRules,
+};
+
+use crate::json::Encoder;
+
+/// Sender of the message.
+const CALLER: [u8; 20] = [0xca; 20];
+
+/// Account holding the code.
+const CONTRACT: [u8; 20] = [0xc0; 20];
+
+/// Run `code` with `calldata` and `gas`, returning the result and trace as JSON.
+#[wasm_bindgen]
+pub fn run_bytecode(code: &[u8], calldata: &[u8], gas: u64) -> String {
+    let caller = Address::from_be_bytes(CALLER);
+    let contract = Address::from_be_bytes(CONTRACT);
+    let mut state = State::default();
+    set_account(&mut state, &caller, Some(Account::default()));
+    set_account(
+        &mut state,
+        &contract,
+        Some(Account { nonce: 1, balance: U256::ZERO, code: Bytes(code.to_vec()) }),
+    );
+    let mut traces = Vec::new();
+    let mut env = Environment {
+        caller: caller.clone(),
+        origin: caller.clone(),
+        gas_limit: gas as Uint,
+        tracer: Some(&mut traces),
+        code_cache: Some(shared_code_cache()),
+        ..Environment::new(&mut state, ExecutionRules::CANCUN)
+    };
+    let message = prepare_message(
+        caller,
+        Some(contract),
+        U256::ZERO,
+        Bytes(calldata.to_vec()),
+        gas as Uint,
+        &env,
+        None,
+        true,
+        false,
+        BTreeSet::new(),
+        BTreeSet::new(),
+    );
+    let mut encoder = Encoder::new();
+    let mut o = encoder.object();
+    match message.and_then(|message| process_message_call(message, &mut env)) {
+        Ok(output) => {
+            o.field("success", &output.error.is_none());
+            o.key("gasUsed");
+            o.encoder.raw(&(gas as Uint - output.gas_left).to_string());
+            o.field("output", &output.return_data);
+            o.field("error", &output.error.map(|e| format!("{e:?}")));
+        }
+        Err(e) => {
+            o.field("success", &false);
+            o.key("gasUsed");
+            o.encoder.raw("0");
+            o.field("output", &Bytes::default());
+            o.field("error", &Some(format!("{e:?}")));
+        }
+    }
+    o.key("trace");
+    let mut trace = o.encoder.array();
+    for entry in &traces {
+        let mut step = trace.next().object();
+        for (key, value) in entry {
+            step.key(key);
+            // Numbers are emitted as numbers, names and errors as strings.
+            if !value.is_empty() && value.bytes().all(|b| b.is_ascii_digit()) {
+                step.encoder.raw(value);
+            } else {
+                step.encoder.string(value);
+            }
+        }
+        step.end();
+    }
+    trace.end();
+    o.end();
+    encoder.finish()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::run_bytecode;
+
+    #[test]
+    fn add_and_return() {
+        // PUSH1 2 PUSH1 3 ADD PUSH1 0 MSTORE PUSH1 32 PUSH1 0 RETURN
+        let code = [0x60, 0x02, 0x60, 0x03, 0x01, 0x60, 0x00, 0x52, 0x60, 0x20, 0x60, 0x00, 0xf3];
+        let json = run_bytecode(&code, &[], 100_000);
+        let expected_output = format!("\"output\":\"0x{}05\"", "00".repeat(31));
+        assert!(json.starts_with(r#"{"success":true,"gasUsed":24,"#), "{json}");
+        assert!(json.contains(&expected_output), "{json}");
+        assert!(json.contains(r#"{"depth":1,"gas":100000,"gasCost":3,"op":"PUSH1","pc":0}"#), "{json}");
+        assert!(json.contains(r#""op":"RETURN","pc":12}]}"#), "{json}");
+    }
+
+    #[test]
+    fn invalid_opcode() {
+        let json = run_bytecode(&[0xfe], &[], 1000);
+        assert!(json.starts_with(r#"{"success":false,"gasUsed":1000,"output":"0x","error":"#), "{json}");
+    }
+}